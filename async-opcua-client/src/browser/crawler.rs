@@ -0,0 +1,348 @@
+use futures::TryStreamExt;
+use hashbrown::{HashMap, HashSet};
+use opcua_nodes::{DefaultTypeTree, NodeType, ReferenceDirection, ReferenceRef, References};
+use opcua_types::{
+    BrowseDirection, BrowseResultMaskFlags, Error, ErrorContext, NamespaceMap, NodeClass,
+    NodeClassMask, NodeId, ObjectId, QualifiedName, ReferenceTypeId,
+};
+
+use crate::Session;
+
+use super::{BrowseFilter, BrowserConfig, NoneBrowserPolicy};
+
+/// Utility for crawling the address space of a server, reading the attributes and
+/// references of each node into a [`CrawledAddressSpace`]. Create one with
+/// [`Session::crawler`].
+///
+/// The crawler browses forward hierarchical references from the `Objects` folder by
+/// default. Nodes excluded by the namespace or node class filters are still browsed,
+/// so that matching nodes below them are found.
+pub struct Crawler<'a> {
+    session: &'a Session,
+    roots: Vec<NodeId>,
+    reference_type_id: NodeId,
+    max_depth: usize,
+    namespaces: Option<HashSet<u16>>,
+    node_class_mask: NodeClassMask,
+    config: BrowserConfig,
+}
+
+impl<'a> Crawler<'a> {
+    /// Create a new crawler with default settings.
+    pub fn new(session: &'a Session) -> Self {
+        Self {
+            session,
+            roots: vec![ObjectId::ObjectsFolder.into()],
+            reference_type_id: ReferenceTypeId::HierarchicalReferences.into(),
+            max_depth: 0,
+            namespaces: None,
+            node_class_mask: NodeClassMask::all(),
+            config: BrowserConfig::default(),
+        }
+    }
+
+    /// Set the nodes to start crawling from. Defaults to the `Objects` folder.
+    pub fn roots(mut self, roots: Vec<NodeId>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    /// Set the type of the references that are followed, including subtypes.
+    /// Defaults to `HierarchicalReferences`.
+    pub fn reference_type_id(mut self, reference_type_id: impl Into<NodeId>) -> Self {
+        self.reference_type_id = reference_type_id.into();
+        self
+    }
+
+    /// Set the maximum depth of the crawl. If this is 1 only the roots and their
+    /// direct children are crawled, if it is 0, there is no upper limit.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Only include nodes in the given namespaces. Defaults to all namespaces.
+    pub fn namespaces(mut self, namespaces: impl IntoIterator<Item = u16>) -> Self {
+        self.namespaces = Some(namespaces.into_iter().collect());
+        self
+    }
+
+    /// Only include nodes with a node class in `mask`. Defaults to `all`.
+    pub fn node_class_mask(mut self, mask: NodeClassMask) -> Self {
+        self.node_class_mask = mask;
+        self
+    }
+
+    /// Set the configuration of the browser used to crawl. The maximum number of
+    /// nodes per request also limits the number of nodes read per request.
+    pub fn config(mut self, config: BrowserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Crawl the address space, returning the crawled nodes and their references.
+    pub async fn crawl(self) -> Result<CrawledAddressSpace, Error> {
+        let discovered = self
+            .discover()
+            .await
+            .context("discovering nodes below the crawl roots")?;
+        let included: Vec<_> = discovered
+            .into_iter()
+            .filter(|(id, class)| self.is_included(id, *class))
+            .collect();
+
+        let mut nodes = HashMap::with_capacity(included.len());
+        let chunk_size = self.config.max_nodes_per_request.max(1);
+        for chunk in included.chunks(chunk_size) {
+            let chunk_nodes = self
+                .session
+                .read_nodes_of_class(chunk)
+                .await
+                .map_err(|e| Error::new(e, "Failed to read node attributes"))?;
+            for node in chunk_nodes {
+                nodes.insert(node.as_node().node_id().clone(), node);
+            }
+        }
+        let references = self
+            .browse_references(&included)
+            .await
+            .context("browsing references of crawled nodes")?;
+
+        Ok(CrawledAddressSpace {
+            namespaces: self.session.encoding_context().read().namespaces().clone(),
+            nodes,
+            references,
+        })
+    }
+
+    fn is_included(&self, node_id: &NodeId, node_class: NodeClass) -> bool {
+        self.namespaces
+            .as_ref()
+            .is_none_or(|ns| ns.contains(&node_id.namespace))
+            && self
+                .node_class_mask
+                .contains(NodeClassMask::from_bits_truncate(node_class as u32))
+    }
+
+    /// Find all nodes below the roots, and their node classes, in the order they
+    /// were discovered.
+    async fn discover(&self) -> Result<Vec<(NodeId, NodeClass)>, Error> {
+        let mut seen = HashSet::new();
+        let mut discovered = Vec::new();
+
+        let classes = self
+            .session
+            .read_node_classes(&self.roots)
+            .await
+            .map_err(|e| Error::new(e, "Failed to read node class of roots"))?;
+        for (root, class) in self.roots.iter().zip(classes) {
+            let node_class = class.map_err(|e| {
+                Error::new(e, "Failed to read root node class").with_node_id(root.clone())
+            })?;
+            if seen.insert(root.clone()) {
+                discovered.push((root.clone(), node_class));
+            }
+        }
+
+        let filter = BrowseFilter::new(
+            BrowseDirection::Forward,
+            self.reference_type_id.clone(),
+            true,
+        )
+        .result_mask(BrowseResultMaskFlags::NodeClass)
+        .max_depth(self.max_depth);
+        let initial = self
+            .roots
+            .iter()
+            .map(|id| filter.new_description_from_node(id.clone()))
+            .collect();
+        let stream = self
+            .session
+            .browser()
+            .handler(filter)
+            .config(self.config.clone())
+            .run(initial);
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.try_next().await? {
+            let (_, references) = item.into_results();
+            for r in references {
+                if r.node_id.server_index == 0 && seen.insert(r.node_id.node_id.clone()) {
+                    discovered.push((r.node_id.node_id, r.node_class));
+                }
+            }
+        }
+        Ok(discovered)
+    }
+
+    async fn browse_references(&self, nodes: &[(NodeId, NodeClass)]) -> Result<References, Error> {
+        let filter = BrowseFilter::new(BrowseDirection::Both, ReferenceTypeId::References, true)
+            .result_mask(BrowseResultMaskFlags::ReferenceTypeId | BrowseResultMaskFlags::IsForward);
+        let initial = nodes
+            .iter()
+            .map(|(id, _)| filter.new_description_from_node(id.clone()))
+            .collect();
+
+        let mut references = References::new();
+        let stream = self
+            .session
+            .browser()
+            .handler(NoneBrowserPolicy)
+            .config(self.config.clone())
+            .run(initial);
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.try_next().await? {
+            let (source, refs) = item.into_results();
+            for r in refs {
+                let target = r.node_id.node_id;
+                if r.node_id.server_index != 0 || target == source {
+                    continue;
+                }
+                if r.is_forward {
+                    references.insert_reference(&source, &target, r.reference_type_id);
+                } else {
+                    references.insert_reference(&target, &source, r.reference_type_id);
+                }
+            }
+        }
+        Ok(references)
+    }
+}
+
+/// Offline model of (part of) the address space of a server, produced by a [`Crawler`].
+#[derive(Debug, Default)]
+pub struct CrawledAddressSpace {
+    /// The namespace array of the server, when the address space was crawled.
+    pub namespaces: NamespaceMap,
+    /// The crawled nodes, with all their attributes.
+    pub nodes: HashMap<NodeId, NodeType>,
+    /// All references from and to the crawled nodes, including references to nodes
+    /// that were not crawled.
+    pub references: References,
+}
+
+impl CrawledAddressSpace {
+    /// Get a crawled node.
+    pub fn get(&self, node_id: &NodeId) -> Option<&NodeType> {
+        self.nodes.get(node_id)
+    }
+
+    /// Get an iterator over the crawled nodes, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &NodeType> {
+        self.nodes.values()
+    }
+
+    /// Get the number of crawled nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Get whether no nodes were crawled.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Get the crawled nodes with the given browse name.
+    pub fn find_by_browse_name<'b>(
+        &'b self,
+        browse_name: &'b QualifiedName,
+    ) -> impl Iterator<Item = &'b NodeType> + 'b {
+        self.iter()
+            .filter(move |n| n.as_node().browse_name() == browse_name)
+    }
+
+    /// Get the references of `node_id` in `direction` with exactly the type
+    /// `reference_type_id`, or of any type if it is `None`.
+    pub fn find_references(
+        &self,
+        node_id: &NodeId,
+        reference_type_id: Option<&NodeId>,
+        direction: BrowseDirection,
+    ) -> Vec<ReferenceRef<'_>> {
+        let type_tree = DefaultTypeTree::new();
+        self.references
+            .find_references(
+                node_id,
+                reference_type_id.map(|t| (t.clone(), false)),
+                &type_tree,
+                direction,
+            )
+            .collect()
+    }
+
+    /// Get the crawled nodes that `node_id` references with the given reference type,
+    /// not including subtypes. For example, pass `HasComponent` to get the components
+    /// of a node.
+    pub fn targets(&self, node_id: &NodeId, reference_type_id: &NodeId) -> Vec<&NodeType> {
+        self.find_references(node_id, Some(reference_type_id), BrowseDirection::Forward)
+            .into_iter()
+            .filter_map(|r| self.nodes.get(r.target_node))
+            .collect()
+    }
+
+    /// Get the type definition of `node_id`, if it has one.
+    pub fn type_definition(&self, node_id: &NodeId) -> Option<&NodeId> {
+        self.find_references(
+            node_id,
+            Some(&ReferenceTypeId::HasTypeDefinition.into()),
+            BrowseDirection::Forward,
+        )
+        .into_iter()
+        .next()
+        .map(|r| r.target_node)
+    }
+
+    /// Add the nodes and references of another crawl of the same server to this one.
+    pub fn extend(&mut self, other: CrawledAddressSpace) {
+        for node_id in other.nodes.keys() {
+            for r in other.find_references(node_id, None, BrowseDirection::Both) {
+                match r.direction {
+                    ReferenceDirection::Forward => self.references.insert_reference(
+                        node_id,
+                        r.target_node,
+                        r.reference_type.clone(),
+                    ),
+                    ReferenceDirection::Inverse => self.references.insert_reference(
+                        r.target_node,
+                        node_id,
+                        r.reference_type.clone(),
+                    ),
+                }
+            }
+        }
+        self.nodes.extend(other.nodes);
+    }
+
+    /// Write the crawled nodes and their references as a NodeSet2 XML document.
+    /// Namespace indices are renumbered to start at 1 in the namespace table of
+    /// the document, see [`opcua_nodes::write_nodeset2`].
+    #[cfg(feature = "xml")]
+    pub fn write_nodeset2(&self, writer: &mut dyn std::io::Write) -> Result<(), Error> {
+        let ctx =
+            opcua_types::ContextOwned::new_default(self.namespaces.clone(), Default::default());
+        opcua_nodes::write_nodeset2(
+            writer,
+            self.nodes.values(),
+            &self.references,
+            &ctx.context(),
+        )
+    }
+
+    /// Write the crawled nodes and their references as a NodeSet2 XML document defining
+    /// `model`, see [`opcua_nodes::write_model_nodeset2`].
+    #[cfg(feature = "xml")]
+    pub fn write_model_nodeset2(
+        &self,
+        writer: &mut dyn std::io::Write,
+        model: &opcua_nodes::NodeSetModel,
+    ) -> Result<(), Error> {
+        let ctx =
+            opcua_types::ContextOwned::new_default(self.namespaces.clone(), Default::default());
+        opcua_nodes::write_model_nodeset2(
+            writer,
+            model,
+            self.nodes.values(),
+            &self.references,
+            &ctx.context(),
+        )
+    }
+}
@@ -0,0 +1,166 @@
+use futures::TryStreamExt;
+use hashbrown::HashSet;
+use opcua_types::{
+    BrowseDescription, BrowseDirection, BrowseResultMaskFlags, NamespaceMap, NodeClassMask, NodeId,
+    ReferenceDescription, ReferenceTypeId, StatusCode,
+};
+
+use crate::{session::session_error, Session};
+
+use super::BrowseFilter;
+
+impl Session {
+    /// Browse the variables that are hierarchical children of `parent`, such as its
+    /// properties and components, following continuation points until all are received.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - The node to browse.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ReferenceDescription>)` - The references to the variables, with all fields
+    ///   of the references filled in.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn browse_variables(
+        &self,
+        parent: &NodeId,
+    ) -> Result<Vec<ReferenceDescription>, StatusCode> {
+        self.browse_children(parent, NodeClassMask::VARIABLE).await
+    }
+
+    /// Browse the objects and variables that are hierarchical children of `parent`, and
+    /// have `type_definition` or one of its subtypes as type definition.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent` - The node to browse.
+    /// * `type_definition` - The object type or variable type of the children to return.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ReferenceDescription>)` - The references to the matching children, with all
+    ///   fields of the references filled in.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn browse_children_of_type(
+        &self,
+        parent: &NodeId,
+        type_definition: &NodeId,
+    ) -> Result<Vec<ReferenceDescription>, StatusCode> {
+        let types = self.type_and_subtypes(type_definition).await?;
+        let namespaces = self.encoding_context().read().namespaces().clone();
+        let children = self
+            .browse_children(parent, NodeClassMask::OBJECT | NodeClassMask::VARIABLE)
+            .await?;
+        Ok(children
+            .into_iter()
+            .filter(|r| is_of_type(r, &types, &namespaces))
+            .collect())
+    }
+
+    /// Recursively search the objects below `root` for objects with `type_definition` or one
+    /// of its subtypes as type definition, following hierarchical references. Only objects
+    /// are browsed, so objects below variables are not found.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The node to start searching from, for example the `Objects` folder.
+    /// * `type_definition` - The object type of the objects to find.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ReferenceDescription>)` - A reference to each matching object, with all
+    ///   fields of the references filled in. Objects reachable in several ways are only
+    ///   returned once.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn find_objects_of_type(
+        &self,
+        root: &NodeId,
+        type_definition: &NodeId,
+    ) -> Result<Vec<ReferenceDescription>, StatusCode> {
+        let types = self.type_and_subtypes(type_definition).await?;
+        let namespaces = self.encoding_context().read().namespaces().clone();
+        let filter = BrowseFilter::new_hierarchical().node_class_mask(NodeClassMask::OBJECT);
+        let initial = vec![filter.new_description_from_node(root.clone())];
+
+        let mut found = HashSet::new();
+        let mut objects = Vec::new();
+        let stream = self.browser().handler(filter).run(initial);
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.try_next().await.map_err(|e| e.status())? {
+            let (_, references) = item.into_results();
+            for r in references {
+                if r.node_id.server_index == 0
+                    && is_of_type(&r, &types, &namespaces)
+                    && found.insert(r.node_id.node_id.clone())
+                {
+                    objects.push(r);
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn browse_children(
+        &self,
+        parent: &NodeId,
+        node_class_mask: NodeClassMask,
+    ) -> Result<Vec<ReferenceDescription>, StatusCode> {
+        let description = BrowseDescription {
+            node_id: parent.clone(),
+            browse_direction: BrowseDirection::Forward,
+            reference_type_id: ReferenceTypeId::HierarchicalReferences.into(),
+            include_subtypes: true,
+            node_class_mask: node_class_mask.bits(),
+            result_mask: BrowseResultMaskFlags::all().bits(),
+        };
+        let result = self
+            .browse_all(&[description], 0, None)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(StatusCode::BadUnexpectedError)?;
+        if result.status_code.is_bad() {
+            session_error!(self, "Failed to browse {}: {}", parent, result.status_code);
+            return Err(result.status_code);
+        }
+        Ok(result.references.unwrap_or_default())
+    }
+
+    /// Get `type_id` and all its subtypes.
+    async fn type_and_subtypes(&self, type_id: &NodeId) -> Result<HashSet<NodeId>, StatusCode> {
+        let filter = BrowseFilter::new(BrowseDirection::Forward, ReferenceTypeId::HasSubtype, true)
+            .node_class_mask(NodeClassMask::OBJECT_TYPE | NodeClassMask::VARIABLE_TYPE)
+            .result_mask(BrowseResultMaskFlags::empty());
+        let initial = vec![filter.new_description_from_node(type_id.clone())];
+
+        let mut types = HashSet::new();
+        types.insert(type_id.clone());
+        let stream = self.browser().handler(filter).run(initial);
+        futures::pin_mut!(stream);
+        while let Some(item) = stream.try_next().await.map_err(|e| e.status())? {
+            let (_, references) = item.into_results();
+            types.extend(
+                references
+                    .into_iter()
+                    .filter(|r| r.node_id.server_index == 0)
+                    .map(|r| r.node_id.node_id),
+            );
+        }
+        Ok(types)
+    }
+}
+
+fn is_of_type(
+    reference: &ReferenceDescription,
+    types: &HashSet<NodeId>,
+    namespaces: &NamespaceMap,
+) -> bool {
+    reference
+        .type_definition
+        .try_resolve(namespaces)
+        .is_some_and(|t| types.contains(t.as_ref()))
+}
@@ -47,8 +47,11 @@ use opcua_types::{
 use tokio_util::sync::CancellationToken;
 
 mod browse;
+mod crawler;
+mod helpers;
 mod result;
 
+pub use crawler::{CrawledAddressSpace, Crawler};
 pub use result::{BrowserResult, NodeDescription};
 
 use crate::{RequestRetryPolicy, Session};
@@ -1,9 +1,28 @@
 use std::{path::PathBuf, time::Duration};
 
-use opcua_core::config::{Config, ConfigError};
+use opcua_core::{
+    comms::{
+        interceptor::MessageInterceptor,
+        metrics::{TransportMetrics, TransportMetricsHandle},
+        socket::SocketOptions,
+    },
+    config::{Config, ConfigError, TokenRenewalHooks, TokenRenewalObserver, TokenRenewalPolicy},
+};
 use tracing::error;
 
-use super::{Client, ClientConfig, ClientEndpoint, ClientUserToken, ANONYMOUS_USER_TOKEN_ID};
+#[cfg(feature = "request-metrics")]
+use crate::metrics::{RequestMetrics, SharedRequestMetrics};
+#[cfg(feature = "request-metrics")]
+use std::sync::Arc;
+
+use crate::endpoint_selector::{EndpointSelector, EndpointSelectorHandle};
+
+use super::{
+    Client, ClientConfig, ClientEndpoint, ClientUserToken, ConnectionPathOptions,
+    CustomTypeDiscovery, HttpsOptions, PublishOptions, ReadCacheOptions, RequestLimitOptions,
+    SampledFallbackOptions, ServiceRetryOptions, SubscriptionTransferPolicy,
+    ANONYMOUS_USER_TOKEN_ID,
+};
 
 #[derive(Default)]
 /// Client builder.
@@ -24,6 +43,14 @@ impl ClientBuilder {
         })
     }
 
+    /// Creates a `ClientBuilder` using the default configuration overridden by environment
+    /// variables starting with `prefix` as the initial state. See [`Config::from_env_prefix`].
+    pub fn from_env_prefix(prefix: &str) -> Result<ClientBuilder, ConfigError> {
+        Ok(ClientBuilder {
+            config: ClientConfig::from_env_prefix(prefix)?,
+        })
+    }
+
     /// Yields a [`Client`] from the values set by the builder. If the builder is not in a valid state
     /// it will return a list of errors.
     ///
@@ -159,10 +186,70 @@ impl ClientBuilder {
     }
 
     /// Requested secure channel token lifetime, in milliseconds.
-    /// The channel will be renewed once 3/4 of the
-    /// lifetime has elapsed. Setting this too low is likely to cause issues.
+    /// The channel will be renewed once the renewal threshold, by default 3/4 of the
+    /// lifetime, has elapsed. Setting this too low is likely to cause issues.
     pub fn channel_lifetime(mut self, channel_lifetime: u32) -> Self {
-        self.config.channel_lifetime = channel_lifetime;
+        self.config.token_renewal.requested_lifetime_ms = channel_lifetime;
+        self
+    }
+
+    /// Set the percentage of the secure channel token lifetime that must elapse
+    /// before the token is renewed. Must be between 1 and 100, default is 75.
+    pub fn token_renewal_threshold(mut self, percent: u8) -> Self {
+        self.config.token_renewal.renewal_threshold_percent = percent;
+        self
+    }
+
+    /// Set the number of times a failed secure channel token renewal is retried,
+    /// and the delay between each attempt. By default renewal is not retried.
+    pub fn token_renewal_retry(mut self, max_retries: u32, interval: Duration) -> Self {
+        self.config.token_renewal.max_retries = max_retries;
+        self.config.token_renewal.retry_interval_ms = interval.as_millis() as u64;
+        self
+    }
+
+    /// Set an observer that is notified when the secure channel token is
+    /// renewed, or renewal fails.
+    pub fn token_renewal_observer(mut self, observer: impl TokenRenewalObserver + 'static) -> Self {
+        self.config.token_renewal.hooks = TokenRenewalHooks::new(observer);
+        self
+    }
+
+    /// Set hooks for collecting metrics about traffic to and from the server,
+    /// such as bytes and messages sent and received.
+    pub fn transport_metrics(mut self, metrics: impl TransportMetrics + 'static) -> Self {
+        self.config.transport_metrics = TransportMetricsHandle::new(metrics);
+        self
+    }
+
+    /// Set the collector of per-service request metrics. Pass the same collector to
+    /// several clients to aggregate their metrics. By default each client has its own.
+    #[cfg(feature = "request-metrics")]
+    pub fn request_metrics(mut self, metrics: Arc<RequestMetrics>) -> Self {
+        self.config.request_metrics = SharedRequestMetrics(metrics);
+        self
+    }
+
+    /// Add an interceptor called with every request sent to and response received
+    /// from the server. Interceptors can observe or reject messages, and are called
+    /// in the order they were added.
+    pub fn interceptor(mut self, interceptor: impl MessageInterceptor + 'static) -> Self {
+        self.config.interceptors.push(interceptor);
+        self
+    }
+
+    /// Set the selector picking the endpoint to connect to among the endpoints
+    /// returned by the server, for [`Client::connect_to_selected_endpoint`] and
+    /// [`Client::get_best_endpoint`]. By default the endpoint with the highest
+    /// security level is picked.
+    pub fn endpoint_selector(mut self, selector: impl EndpointSelector + 'static) -> Self {
+        self.config.endpoint_selector = EndpointSelectorHandle::new(selector);
+        self
+    }
+
+    /// Set the full secure channel token renewal policy.
+    pub fn token_renewal_policy(mut self, policy: TokenRenewalPolicy) -> Self {
+        self.config.token_renewal = policy;
         self
     }
 
@@ -210,30 +297,48 @@ impl ClientBuilder {
         self
     }
 
-    /// Sets the maximum number of chunks in an outgoing message. 0 means no limit.
+    /// Sets the maximum number of chunks in a message. 0 means no limit.
+    /// This applies to outgoing messages as well, unless `max_send_chunk_count` is set.
     pub fn max_chunk_count(mut self, max_chunk_count: usize) -> Self {
         self.config.decoding_options.max_chunk_count = max_chunk_count;
         self
     }
 
-    /// Maximum size of each individual outgoing message chunk.
+    /// Maximum size of each individual outgoing message chunk. This is sent to the server
+    /// as the send buffer size in the `HELLO` message, and must be at least 8192.
     pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
         self.config.decoding_options.max_chunk_size = max_chunk_size;
         self
     }
 
-    /// Maximum size of each incoming chunk.
+    /// Maximum size of each incoming chunk. This is sent to the server as the receive
+    /// buffer size in the `HELLO` message, and must be at least 8192.
     pub fn max_incoming_chunk_size(mut self, max_incoming_chunk_size: usize) -> Self {
         self.config.decoding_options.max_incoming_chunk_size = max_incoming_chunk_size;
         self
     }
 
-    /// Sets the maximum outgoing message size in bytes. 0 means no limit.
+    /// Sets the maximum message size in bytes. 0 means no limit.
+    /// This applies to outgoing messages as well, unless `max_send_message_size` is set.
     pub fn max_message_size(mut self, max_message_size: usize) -> Self {
         self.config.decoding_options.max_message_size = max_message_size;
         self
     }
 
+    /// Sets the maximum outgoing message size in bytes, if it should differ from
+    /// `max_message_size`. The server may lower this further. 0 means no limit.
+    pub fn max_send_message_size(mut self, max_send_message_size: usize) -> Self {
+        self.config.decoding_options.max_send_message_size = Some(max_send_message_size);
+        self
+    }
+
+    /// Sets the maximum number of chunks in an outgoing message, if it should differ
+    /// from `max_chunk_count`. The server may lower this further. 0 means no limit.
+    pub fn max_send_chunk_count(mut self, max_send_chunk_count: usize) -> Self {
+        self.config.decoding_options.max_send_chunk_count = Some(max_send_chunk_count);
+        self
+    }
+
     /// Maximum length in bytes of a string. 0 actually means 0, i.e. no string permitted.
     pub fn max_string_length(mut self, max_string_length: usize) -> Self {
         self.config.decoding_options.max_string_length = max_string_length;
@@ -262,6 +367,20 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the timeout for the server to answer the `HELLO` message sent when
+    /// connecting. The default is 30 seconds.
+    pub fn hello_timeout(mut self, hello_timeout: Duration) -> Self {
+        self.config.hello_timeout = hello_timeout;
+        self
+    }
+
+    /// Set the timeout for the server to answer `OpenSecureChannel` requests, when
+    /// connecting and when renewing the secure channel token. The default is 30 seconds.
+    pub fn open_secure_channel_timeout(mut self, open_secure_channel_timeout: Duration) -> Self {
+        self.config.open_secure_channel_timeout = open_secure_channel_timeout;
+        self
+    }
+
     /// Set the lowest allowed publishing interval by the client.
     /// The server may also enforce its own minimum.
     pub fn min_publish_interval(mut self, min_publish_interval: Duration) -> Self {
@@ -269,6 +388,14 @@ impl ClientBuilder {
         self
     }
 
+    /// Set options for the publish requests each session sends to receive notifications,
+    /// such as the number of outstanding requests. This can be overridden per session with
+    /// [`crate::SessionBuilder::publish_options`].
+    pub fn publish_options(mut self, publish: PublishOptions) -> Self {
+        self.config.publish = publish;
+        self
+    }
+
     /// Sets whether the client should ignore clock skew so the client can make a successful
     /// connection to the server, even when the client and server clocks are out of sync.
     pub fn ignore_clock_skew(mut self, ignore_clock_skew: bool) -> Self {
@@ -284,6 +411,27 @@ impl ClientBuilder {
         self
     }
 
+    /// Minimum number of chunks in a message before the chunks are signed and encrypted,
+    /// or verified and decrypted, on multiple threads. This speeds up large
+    /// messages on secure channels, such as big history reads with `SignAndEncrypt`.
+    ///
+    /// Defaults to 0, meaning that chunks are always processed sequentially. Chunks are
+    /// only processed on multiple threads with the `parallel-crypto` feature.
+    pub fn parallel_crypto_chunk_threshold(mut self, threshold: usize) -> Self {
+        self.config.performance.parallel_crypto_chunk_threshold = threshold;
+        self
+    }
+
+    /// Read the operation limits of the server after connecting, such as `MaxNodesPerRead`,
+    /// and split calls to `read`, `write`, `browse`, `call`, and `history_read` on the session
+    /// that exceed them into several requests. The results are merged in order.
+    ///
+    /// Defaults to `true`. The request builders in [`crate::services`] are never split.
+    pub fn split_by_operation_limits(mut self, split_by_operation_limits: bool) -> Self {
+        self.config.performance.split_by_operation_limits = split_by_operation_limits;
+        self
+    }
+
     /// Automatically recreate subscriptions on reconnect, by first calling
     /// [`crate::Session::transfer_subscriptions`], then attempting to recreate
     /// subscriptions if that fails.
@@ -295,6 +443,41 @@ impl ClientBuilder {
         self
     }
 
+    /// How subscriptions are moved to a new session when the session is recreated
+    /// on reconnect. By default they are transferred with `TransferSubscriptions`,
+    /// falling back to creating them again. This can be overridden per session with
+    /// [`crate::SessionBuilder::subscription_transfer_policy`].
+    ///
+    /// Only used if [`ClientBuilder::recreate_subscriptions`] is enabled.
+    pub fn subscription_transfer_policy(mut self, policy: SubscriptionTransferPolicy) -> Self {
+        self.config.subscription_transfer_policy = policy;
+        self
+    }
+
+    /// Read the namespace array of the server after connecting, caching it for
+    /// [`crate::Session::resolve`] and for decoding custom types.
+    ///
+    /// If namespaces moved to other indices since the session last connected, for example
+    /// because the server restarted with a different namespace array, the node IDs of
+    /// monitored items are changed to the new indices before subscriptions are recreated,
+    /// and [`crate::ConnectionEvent::NamespacesChanged`] is emitted.
+    ///
+    /// Defaults to `true`.
+    pub fn read_namespaces_on_connect(mut self, read_namespaces_on_connect: bool) -> Self {
+        self.config.read_namespaces_on_connect = read_namespaces_on_connect;
+        self
+    }
+
+    /// When to read the data type definitions of the server, to decode values of structures
+    /// defined by the server into [`opcua_types::custom::DynamicStructure`], without generating
+    /// code for them. See [`CustomTypeDiscovery`].
+    ///
+    /// Defaults to [`CustomTypeDiscovery::Disabled`].
+    pub fn custom_type_discovery(mut self, custom_type_discovery: CustomTypeDiscovery) -> Self {
+        self.config.custom_type_discovery = custom_type_discovery;
+        self
+    }
+
     /// Session name - the default name to use for a new session
     pub fn session_name(mut self, session_name: impl Into<String>) -> Self {
         self.config.session_name = session_name.into();
@@ -307,6 +490,55 @@ impl ClientBuilder {
         self
     }
 
+    /// Set socket options for TCP connections, such as `TCP_NODELAY`, keep-alive,
+    /// OS buffer sizes, local address, and connect timeout.
+    pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.config.socket_options = socket_options;
+        self
+    }
+
+    /// Set options for connections to `opc.https` endpoints, such as an HTTP proxy.
+    /// These are only used if the `https` feature is enabled.
+    pub fn https_options(mut self, https: HttpsOptions) -> Self {
+        self.config.https = https;
+        self
+    }
+
+    /// Set options for endpoints with alternate network paths to the server, such as
+    /// when a path that keeps failing is demoted.
+    pub fn connection_path_options(mut self, connection_paths: ConnectionPathOptions) -> Self {
+        self.config.connection_paths = connection_paths;
+        self
+    }
+
+    /// Limit the number of service calls each session has in flight at once, and set
+    /// what happens to calls made while the limit is reached.
+    pub fn request_limit_options(mut self, request_limits: RequestLimitOptions) -> Self {
+        self.config.request_limits = request_limits;
+        self
+    }
+
+    /// Automatically retry failed calls to idempotent services, after re-activating
+    /// the session if the server no longer accepts it.
+    pub fn service_retry_options(mut self, service_retry: ServiceRetryOptions) -> Self {
+        self.config.service_retry = service_retry;
+        self
+    }
+
+    /// Cache values read by sessions, answering reads that accept values of a certain
+    /// age without sending them to the server. Disabled by default.
+    pub fn read_cache_options(mut self, read_cache: ReadCacheOptions) -> Self {
+        self.config.read_cache = read_cache;
+        self
+    }
+
+    /// Emulate subscriptions with periodic reads when the server rejects them, or
+    /// delivers their notifications unreliably. Disabled by default.
+    pub fn sampled_fallback_options(mut self, sampled_fallback: SampledFallbackOptions) -> Self {
+        self.config.sampled_fallback = sampled_fallback;
+        self
+    }
+
     /// Set the length of the nonce generated for CreateSession requests.
     pub fn session_nonce_length(mut self, session_nonce_length: usize) -> Self {
         self.config.session_nonce_length = session_nonce_length;
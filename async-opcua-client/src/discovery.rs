@@ -0,0 +1,419 @@
+//! Network-wide discovery of OPC UA servers.
+//!
+//! Servers announce themselves with DNS-SD records of the `_opcua-tcp._tcp` service type,
+//! as described in OPC UA Part 12 - Discovery. These can be found either by asking a local
+//! discovery server with multicast extension (LDS-ME), using
+//! [`Client::find_all_servers_on_network`](crate::Client::find_all_servers_on_network),
+//! or by browsing for them directly with multicast DNS using [`browse_mdns`].
+//!
+//! Results from several sources can be combined with [`dedup_servers_on_network`].
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use opcua_types::{ServerOnNetwork, StatusCode, UAString};
+use tokio::net::UdpSocket;
+use tracing::{debug, error};
+
+/// The DNS-SD service type announced by OPC UA servers supporting `opc.tcp`.
+pub const OPCUA_TCP_SERVICE: &str = "_opcua-tcp._tcp.local";
+
+const MDNS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// Browse the local network for OPC UA servers using multicast DNS, collecting
+/// responses until `timeout` has passed.
+///
+/// The query is sent from an ephemeral port, so responders answer with unicast
+/// directly to this socket. Each server is returned with a `record_id` of 0, its
+/// DNS-SD instance name as `server_name`, and a discovery URL built from the host
+/// name and port of its SRV record, and the `path` key of its TXT record.
+///
+/// # Arguments
+///
+/// * `timeout` - How long to wait for responses.
+/// * `server_capability_filter` - Only servers announcing all of these capabilities,
+///   in the `caps` key of their TXT record, are returned.
+///
+/// # Returns
+///
+/// * `Ok(Vec<ServerOnNetwork>)` - The servers found on the network, deduplicated.
+/// * `Err(StatusCode)` - The query could not be sent.
+pub async fn browse_mdns(
+    timeout: Duration,
+    server_capability_filter: &[&str],
+) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|e| {
+            error!("Failed to bind mDNS socket: {e}");
+            StatusCode::BadCommunicationError
+        })?;
+    socket
+        .send_to(&mdns_query(OPCUA_TCP_SERVICE), MDNS_ADDR)
+        .await
+        .map_err(|e| {
+            error!("Failed to send mDNS query: {e}");
+            StatusCode::BadCommunicationError
+        })?;
+
+    let mut records = MdnsRecords::default();
+    let mut buf = vec![0u8; 9000];
+    let deadline = tokio::time::Instant::now() + timeout;
+    while let Ok(res) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        match res {
+            Ok((len, addr)) => {
+                if let Err(e) = records.add_response(&buf[..len]) {
+                    debug!("Ignoring invalid mDNS response from {addr}: {e}");
+                }
+            }
+            Err(e) => {
+                error!("Failed to receive mDNS response: {e}");
+                break;
+            }
+        }
+    }
+
+    Ok(dedup_servers_on_network(
+        records
+            .servers(OPCUA_TCP_SERVICE)
+            .into_iter()
+            .filter(|s| has_capabilities(s, server_capability_filter)),
+    ))
+}
+
+/// Combine server records found through several sources, such as different
+/// discovery servers and mDNS, into one record per discovery URL.
+///
+/// Discovery URLs are compared ignoring case. The first record for each URL is kept,
+/// with the server capabilities of all records for the URL merged.
+pub fn dedup_servers_on_network(
+    servers: impl IntoIterator<Item = ServerOnNetwork>,
+) -> Vec<ServerOnNetwork> {
+    let mut result: Vec<ServerOnNetwork> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for server in servers {
+        let key = server.discovery_url.as_ref().to_lowercase();
+        if let Some(idx) = index.get(&key) {
+            let caps = result[*idx]
+                .server_capabilities
+                .get_or_insert_with(Vec::new);
+            for cap in server.server_capabilities.unwrap_or_default() {
+                if !caps.contains(&cap) {
+                    caps.push(cap);
+                }
+            }
+        } else {
+            index.insert(key, result.len());
+            result.push(server);
+        }
+    }
+    result
+}
+
+/// Whether `server` announces every capability in `filter`.
+pub(crate) fn has_capabilities(server: &ServerOnNetwork, filter: &[&str]) -> bool {
+    let caps = server.server_capabilities.as_deref().unwrap_or_default();
+    filter
+        .iter()
+        .all(|f| caps.iter().any(|c| c.as_ref().eq_ignore_ascii_case(f)))
+}
+
+/// Build a DNS query for the PTR records of `service`.
+fn mdns_query(service: &str) -> Vec<u8> {
+    // ID 0, standard query, one question.
+    let mut buf = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in service.split('.').filter(|l| !l.is_empty()) {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+    buf
+}
+
+/// Records collected from mDNS responses.
+#[derive(Default)]
+struct MdnsRecords {
+    pointers: Vec<(String, String)>,
+    services: HashMap<String, (String, u16)>,
+    texts: HashMap<String, Vec<String>>,
+    addresses: HashMap<String, IpAddr>,
+}
+
+impl MdnsRecords {
+    /// Parse a DNS response message and collect the records relevant to service discovery.
+    fn add_response(&mut self, msg: &[u8]) -> Result<(), &'static str> {
+        let mut reader = DnsReader { msg, pos: 12 };
+        let header = reader.bytes(0, 12)?;
+        // Ignore queries from other hosts.
+        if header[2] & 0x80 == 0 {
+            return Ok(());
+        }
+        let count = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+        let (questions, records) = (count(4), count(6) + count(8) + count(10));
+
+        for _ in 0..questions {
+            reader.name()?;
+            reader.skip(4)?;
+        }
+        for _ in 0..records {
+            let owner = reader.name()?.to_lowercase();
+            let rtype = reader.u16()?;
+            reader.skip(6)?;
+            let len = reader.u16()? as usize;
+            let end = reader.pos + len;
+            if end > msg.len() {
+                return Err("record data out of bounds");
+            }
+            match rtype {
+                TYPE_PTR => {
+                    let target = reader.name()?;
+                    if !self.pointers.contains(&(owner.clone(), target.clone())) {
+                        self.pointers.push((owner, target));
+                    }
+                }
+                TYPE_SRV => {
+                    reader.skip(4)?;
+                    let port = reader.u16()?;
+                    let target = reader.name()?;
+                    self.services.insert(owner, (target, port));
+                }
+                TYPE_TXT => {
+                    let mut entries = Vec::new();
+                    while reader.pos < end {
+                        let len = reader.bytes(reader.pos, 1)?[0] as usize;
+                        let entry = reader.bytes(reader.pos + 1, len)?;
+                        entries.push(String::from_utf8_lossy(entry).into_owned());
+                        reader.pos += len + 1;
+                    }
+                    self.texts.insert(owner, entries);
+                }
+                TYPE_A if len == 4 => {
+                    let b = reader.bytes(reader.pos, 4)?;
+                    self.addresses
+                        .insert(owner, Ipv4Addr::new(b[0], b[1], b[2], b[3]).into());
+                }
+                TYPE_AAAA if len == 16 => {
+                    let b: [u8; 16] = reader.bytes(reader.pos, 16)?.try_into().unwrap();
+                    self.addresses.insert(owner, Ipv6Addr::from(b).into());
+                }
+                _ => (),
+            }
+            reader.pos = end;
+        }
+        Ok(())
+    }
+
+    /// Build server records for every instance of `service` with a known SRV record.
+    fn servers(&self, service: &str) -> Vec<ServerOnNetwork> {
+        let service = service.to_lowercase();
+        self.pointers
+            .iter()
+            .filter(|(owner, _)| *owner == service)
+            .filter_map(|(_, instance)| {
+                let key = instance.to_lowercase();
+                let (host, port) = self.services.get(&key)?;
+                let mut path = String::new();
+                let mut caps = Vec::new();
+                for entry in self.texts.get(&key).into_iter().flatten() {
+                    match entry.split_once('=') {
+                        Some(("path", p)) => path = p.to_owned(),
+                        Some(("caps", c)) => caps.extend(
+                            c.split(',')
+                                .filter(|c| !c.is_empty())
+                                .map(|c| UAString::from(c.trim())),
+                        ),
+                        _ => (),
+                    }
+                }
+                if !path.is_empty() && !path.starts_with('/') {
+                    path.insert(0, '/');
+                }
+                // Use the address if the host name only resolves through mDNS,
+                // since the system resolver may not support that.
+                let host = match self.addresses.get(&host.to_lowercase()) {
+                    Some(IpAddr::V4(addr)) if host.ends_with(".local") => addr.to_string(),
+                    Some(IpAddr::V6(addr)) if host.ends_with(".local") => format!("[{addr}]"),
+                    _ => host.clone(),
+                };
+                let name = instance
+                    .strip_suffix(&format!(".{service}"))
+                    .unwrap_or(instance);
+                Some(ServerOnNetwork {
+                    record_id: 0,
+                    server_name: name.into(),
+                    discovery_url: format!("opc.tcp://{host}:{port}{path}").into(),
+                    server_capabilities: Some(caps),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reader for names and integers in a DNS message.
+struct DnsReader<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> DnsReader<'a> {
+    fn bytes(&self, start: usize, len: usize) -> Result<&'a [u8], &'static str> {
+        self.msg
+            .get(start..start + len)
+            .ok_or("message is truncated")
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), &'static str> {
+        self.bytes(self.pos, len)?;
+        self.pos += len;
+        Ok(())
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let b = self.bytes(self.pos, 2)?;
+        self.pos += 2;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// Read a possibly compressed name, returning it without the trailing dot.
+    fn name(&mut self) -> Result<String, &'static str> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        // Each pointer must point backwards, which guarantees termination.
+        let mut limit = pos;
+        loop {
+            let len = self.bytes(pos, 1)?[0] as usize;
+            if len & 0xC0 == 0xC0 {
+                let target = ((len & 0x3F) << 8) | self.bytes(pos + 1, 1)?[0] as usize;
+                if target >= limit {
+                    return Err("invalid name pointer");
+                }
+                end.get_or_insert(pos + 2);
+                limit = target;
+                pos = target;
+            } else if len == 0 {
+                end.get_or_insert(pos + 1);
+                break;
+            } else {
+                let label = self.bytes(pos + 1, len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += len + 1;
+            }
+        }
+        self.pos = end.unwrap_or(pos);
+        Ok(labels.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{ServerOnNetwork, UAString};
+
+    use super::{
+        dedup_servers_on_network, has_capabilities, mdns_query, MdnsRecords, CLASS_IN,
+        OPCUA_TCP_SERVICE, TYPE_A, TYPE_PTR, TYPE_SRV, TYPE_TXT,
+    };
+
+    fn record(buf: &mut Vec<u8>, owner: &[u8], rtype: u16, data: &[u8]) {
+        buf.extend_from_slice(owner);
+        buf.extend_from_slice(&rtype.to_be_bytes());
+        buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buf.extend_from_slice(&120u32.to_be_bytes());
+        buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        buf.extend_from_slice(data);
+    }
+
+    fn response() -> Vec<u8> {
+        // Response with the question echoed, then PTR, SRV, TXT and A records.
+        let mut buf = vec![0, 0, 0x84, 0, 0, 1, 0, 1, 0, 0, 0, 3];
+        let query = mdns_query(OPCUA_TCP_SERVICE);
+        buf.extend_from_slice(&query[12..]);
+        // Pointer to the service name in the question at offset 12.
+        let service = [0xC0, 12];
+        let instance_offset = buf.len() + 12;
+        let mut ptr = vec![6];
+        ptr.extend_from_slice(b"Server");
+        ptr.extend_from_slice(&service);
+        record(&mut buf, &service, TYPE_PTR, &ptr);
+        let instance = [0xC0, instance_offset as u8];
+
+        let mut srv = vec![0, 0, 0, 0, 0x12, 0x7A];
+        srv.extend_from_slice(b"\x04host\x05local\x00");
+        let host_offset = buf.len() + 2 + 10 + 6;
+        record(&mut buf, &instance, TYPE_SRV, &srv);
+
+        let mut txt = Vec::new();
+        for entry in ["path=ua/server", "caps=LDS,DA"] {
+            txt.push(entry.len() as u8);
+            txt.extend_from_slice(entry.as_bytes());
+        }
+        record(&mut buf, &instance, TYPE_TXT, &txt);
+        record(&mut buf, &[0xC0, host_offset as u8], TYPE_A, &[10, 0, 0, 5]);
+        buf
+    }
+
+    #[test]
+    fn parse_mdns_response() {
+        let mut records = MdnsRecords::default();
+        records.add_response(&response()).unwrap();
+        let servers = records.servers(OPCUA_TCP_SERVICE);
+        assert_eq!(servers.len(), 1);
+        let server = &servers[0];
+        assert_eq!(server.server_name.as_ref(), "Server");
+        assert_eq!(
+            server.discovery_url.as_ref(),
+            "opc.tcp://10.0.0.5:4730/ua/server"
+        );
+        assert_eq!(
+            server.server_capabilities,
+            Some(vec!["LDS".into(), "DA".into()])
+        );
+        assert!(has_capabilities(server, &["da"]));
+        assert!(!has_capabilities(server, &["DA", "HD"]));
+    }
+
+    #[test]
+    fn parse_invalid_mdns_response() {
+        let mut records = MdnsRecords::default();
+        let msg = response();
+        assert!(records.add_response(&msg[..msg.len() - 3]).is_err());
+        // A pointer to itself must not loop forever.
+        let mut msg = vec![0, 0, 0x84, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        msg.extend_from_slice(&[0xC0, 12]);
+        assert!(records.add_response(&msg).is_err());
+    }
+
+    #[test]
+    fn dedup_servers() {
+        let server = |id: u32, url: &str, caps: &[&str]| ServerOnNetwork {
+            record_id: id,
+            server_name: "Server".into(),
+            discovery_url: url.into(),
+            server_capabilities: Some(caps.iter().map(|c| UAString::from(*c)).collect()),
+        };
+        let servers = dedup_servers_on_network([
+            server(1, "opc.tcp://host:4840/", &["DA"]),
+            server(2, "opc.tcp://other:4840/", &["LDS"]),
+            server(0, "opc.tcp://HOST:4840/", &["HD", "DA"]),
+        ]);
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].record_id, 1);
+        assert_eq!(
+            servers[0].server_capabilities,
+            Some(vec!["DA".into(), "HD".into()])
+        );
+        assert_eq!(servers[1].record_id, 2);
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+};
+
+use opcua_core::comms::url::hostname_from_url;
+use opcua_crypto::SecurityPolicy;
+use opcua_types::{EndpointDescription, MessageSecurityMode};
+
+/// Trait for picking the endpoint to connect to among the endpoints returned by
+/// `GetEndpoints`, see [`ClientBuilder::endpoint_selector`](crate::ClientBuilder::endpoint_selector).
+///
+/// The selector is only given endpoints with a security policy supported by the client.
+/// When connecting a session, endpoints that do not accept the identity token of the
+/// session are left out as well.
+pub trait EndpointSelector: Send + Sync {
+    /// Pick the endpoint to connect to, or return `None` to fail the connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL the client was asked to connect to.
+    /// * `endpoints` - The candidate endpoints, in the order returned by the server.
+    fn select<'a>(
+        &self,
+        url: &str,
+        endpoints: &'a [EndpointDescription],
+    ) -> Option<&'a EndpointDescription>;
+}
+
+/// Select the endpoint with the highest `securityLevel`. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighestSecurityLevel;
+
+impl EndpointSelector for HighestSecurityLevel {
+    fn select<'a>(
+        &self,
+        _url: &str,
+        endpoints: &'a [EndpointDescription],
+    ) -> Option<&'a EndpointDescription> {
+        // Pick the first endpoint among those with the highest security level.
+        endpoints.iter().rev().max_by_key(|e| e.security_level)
+    }
+}
+
+/// Select an endpoint with the given security policy and mode, and fail if the
+/// server has none.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireSecurity {
+    security_policy: SecurityPolicy,
+    security_mode: MessageSecurityMode,
+}
+
+impl RequireSecurity {
+    /// Create a selector requiring `security_policy` and `security_mode`.
+    pub fn new(security_policy: SecurityPolicy, security_mode: MessageSecurityMode) -> Self {
+        Self {
+            security_policy,
+            security_mode,
+        }
+    }
+}
+
+impl EndpointSelector for RequireSecurity {
+    fn select<'a>(
+        &self,
+        _url: &str,
+        endpoints: &'a [EndpointDescription],
+    ) -> Option<&'a EndpointDescription> {
+        endpoints
+            .iter()
+            .rev()
+            .filter(|e| {
+                e.security_mode == self.security_mode
+                    && SecurityPolicy::from_uri(e.security_policy_uri.as_ref())
+                        == self.security_policy
+            })
+            .max_by_key(|e| e.security_level)
+    }
+}
+
+/// Prefer endpoints whose advertised hostname matches the hostname of the URL the
+/// client connects to, and pick among them with another selector. If no endpoint
+/// matches, the other selector picks among all endpoints.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferMatchingHostname<S = HighestSecurityLevel> {
+    inner: S,
+}
+
+impl<S: EndpointSelector> PreferMatchingHostname<S> {
+    /// Create a selector preferring matching hostnames, and picking among endpoints
+    /// with `inner`.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: EndpointSelector> EndpointSelector for PreferMatchingHostname<S> {
+    fn select<'a>(
+        &self,
+        url: &str,
+        endpoints: &'a [EndpointDescription],
+    ) -> Option<&'a EndpointDescription> {
+        let Ok(hostname) = hostname_from_url(url) else {
+            return self.inner.select(url, endpoints);
+        };
+        let matching = endpoints
+            .iter()
+            .filter(|e| {
+                hostname_from_url(e.endpoint_url.as_ref())
+                    .is_ok_and(|h| h.eq_ignore_ascii_case(&hostname))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return self.inner.select(url, endpoints);
+        }
+        let selected = self.inner.select(url, &matching)?;
+        endpoints.iter().find(|e| *e == selected)
+    }
+}
+
+/// Select the endpoint with the highest score computed by a closure. Endpoints the
+/// closure returns `None` for are never selected.
+#[derive(Clone, Copy)]
+pub struct ScoreEndpoints<F> {
+    score: F,
+}
+
+impl<F> ScoreEndpoints<F>
+where
+    F: Fn(&EndpointDescription) -> Option<i64> + Send + Sync,
+{
+    /// Create a selector scoring endpoints with `score`.
+    pub fn new(score: F) -> Self {
+        Self { score }
+    }
+}
+
+impl<F> EndpointSelector for ScoreEndpoints<F>
+where
+    F: Fn(&EndpointDescription) -> Option<i64> + Send + Sync,
+{
+    fn select<'a>(
+        &self,
+        _url: &str,
+        endpoints: &'a [EndpointDescription],
+    ) -> Option<&'a EndpointDescription> {
+        endpoints
+            .iter()
+            .rev()
+            .filter_map(|e| (self.score)(e).map(|s| (s, e)))
+            .max_by_key(|(s, _)| *s)
+            .map(|(_, e)| e)
+    }
+}
+
+impl<F> fmt::Debug for ScoreEndpoints<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ScoreEndpoints")
+    }
+}
+
+/// Cheaply cloneable handle to the endpoint selector of a client.
+/// Defaults to [`HighestSecurityLevel`].
+#[derive(Clone)]
+pub(crate) struct EndpointSelectorHandle(Arc<dyn EndpointSelector>);
+
+impl EndpointSelectorHandle {
+    pub(crate) fn new(selector: impl EndpointSelector + 'static) -> Self {
+        Self(Arc::new(selector))
+    }
+
+    /// Select an endpoint among `endpoints`, leaving out endpoints with a security
+    /// policy the client does not support.
+    pub(crate) fn select(
+        &self,
+        url: &str,
+        endpoints: impl IntoIterator<Item = EndpointDescription>,
+    ) -> Option<EndpointDescription> {
+        let candidates = endpoints
+            .into_iter()
+            .filter(|e| {
+                SecurityPolicy::from_uri(e.security_policy_uri.as_ref()) != SecurityPolicy::Unknown
+            })
+            .collect::<Vec<_>>();
+        self.0.select(url, &candidates).cloned()
+    }
+}
+
+impl Default for EndpointSelectorHandle {
+    fn default() -> Self {
+        // Share a single default instance, so that default handles compare equal.
+        static DEFAULT: OnceLock<Arc<dyn EndpointSelector>> = OnceLock::new();
+        Self(
+            DEFAULT
+                .get_or_init(|| Arc::new(HighestSecurityLevel))
+                .clone(),
+        )
+    }
+}
+
+impl fmt::Debug for EndpointSelectorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EndpointSelectorHandle")
+    }
+}
+
+impl PartialEq for EndpointSelectorHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_crypto::SecurityPolicy;
+    use opcua_types::{EndpointDescription, MessageSecurityMode};
+
+    use super::{
+        EndpointSelector, HighestSecurityLevel, PreferMatchingHostname, RequireSecurity,
+        ScoreEndpoints,
+    };
+
+    fn endpoint(
+        url: &str,
+        policy: SecurityPolicy,
+        mode: MessageSecurityMode,
+        level: u8,
+    ) -> EndpointDescription {
+        EndpointDescription {
+            endpoint_url: url.into(),
+            security_policy_uri: policy.to_uri().into(),
+            security_mode: mode,
+            security_level: level,
+            ..Default::default()
+        }
+    }
+
+    fn endpoints() -> Vec<EndpointDescription> {
+        vec![
+            endpoint(
+                "opc.tcp://server:4855",
+                SecurityPolicy::None,
+                MessageSecurityMode::None,
+                0,
+            ),
+            endpoint(
+                "opc.tcp://server:4855",
+                SecurityPolicy::Basic256Sha256,
+                MessageSecurityMode::Sign,
+                3,
+            ),
+            endpoint(
+                "opc.tcp://other:4855",
+                SecurityPolicy::Aes256Sha256RsaPss,
+                MessageSecurityMode::SignAndEncrypt,
+                10,
+            ),
+            endpoint(
+                "opc.tcp://server:4855",
+                SecurityPolicy::Basic256Sha256,
+                MessageSecurityMode::SignAndEncrypt,
+                5,
+            ),
+        ]
+    }
+
+    #[test]
+    fn highest_security_level() {
+        let endpoints = endpoints();
+        let selected = HighestSecurityLevel
+            .select("opc.tcp://server:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_level, 10);
+        assert!(HighestSecurityLevel
+            .select("opc.tcp://server:4855", &[])
+            .is_none());
+    }
+
+    #[test]
+    fn require_security() {
+        let endpoints = endpoints();
+        let selector = RequireSecurity::new(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+        );
+        let selected = selector
+            .select("opc.tcp://server:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_level, 5);
+
+        let selector = RequireSecurity::new(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::Sign,
+        );
+        assert!(selector
+            .select("opc.tcp://server:4855", &endpoints)
+            .is_none());
+    }
+
+    #[test]
+    fn prefer_matching_hostname() {
+        let endpoints = endpoints();
+        let selector = PreferMatchingHostname::new(HighestSecurityLevel);
+        let selected = selector
+            .select("opc.tcp://SERVER:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_level, 5);
+        let selected = selector.select("opc.tcp://other:4855", &endpoints).unwrap();
+        assert_eq!(selected.security_level, 10);
+        // Falls back to all endpoints if no hostname matches.
+        let selected = selector
+            .select("opc.tcp://localhost:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_level, 10);
+    }
+
+    #[test]
+    fn score_endpoints() {
+        let endpoints = endpoints();
+        // Prefer signing without encryption, never connect without security.
+        let selector = ScoreEndpoints::new(|e: &EndpointDescription| match e.security_mode {
+            MessageSecurityMode::Sign => Some(20),
+            MessageSecurityMode::SignAndEncrypt => Some(i64::from(e.security_level)),
+            _ => None,
+        });
+        let selected = selector
+            .select("opc.tcp://server:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_mode, MessageSecurityMode::Sign);
+
+        let selector = ScoreEndpoints::new(|e: &EndpointDescription| {
+            (e.security_mode == MessageSecurityMode::None).then_some(0)
+        });
+        let selected = selector
+            .select("opc.tcp://server:4855", &endpoints)
+            .unwrap();
+        assert_eq!(selected.security_mode, MessageSecurityMode::None);
+    }
+}
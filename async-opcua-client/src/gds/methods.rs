@@ -0,0 +1,62 @@
+use opcua_types::{NodeId, StatusCode, TryFromVariant, Variant};
+
+use crate::{session::session_error, Session};
+
+/// Call a method, returning the output arguments, or the status of the method if it failed.
+pub(super) async fn call(
+    session: &Session,
+    object_id: &NodeId,
+    method_id: NodeId,
+    input: Vec<Variant>,
+) -> Result<Vec<Variant>, StatusCode> {
+    let result = session
+        .call_one((object_id.clone(), method_id, Some(input)))
+        .await?;
+    if result.status_code.is_bad() {
+        return Err(result.status_code);
+    }
+    Ok(result.output_arguments.unwrap_or_default())
+}
+
+/// Resolve the method with browse name `method` on `object_id`, and call it.
+pub(super) async fn call_by_name(
+    session: &Session,
+    object_id: &NodeId,
+    method: &str,
+    input: Vec<Variant>,
+) -> Result<Vec<Variant>, StatusCode> {
+    let method_id = session
+        .translate_path(object_id.clone(), &format!(".{method}"))
+        .await?;
+    call(session, object_id, method_id, input).await
+}
+
+/// Check that a method returned exactly `N` output arguments.
+pub(super) fn outputs<const N: usize>(
+    session: &Session,
+    outputs: Vec<Variant>,
+    method: &str,
+) -> Result<[Variant; N], StatusCode> {
+    <[Variant; N]>::try_from(outputs).map_err(|outputs| {
+        session_error!(
+            session,
+            "Expected {} output arguments from {}, got {}",
+            N,
+            method,
+            outputs.len()
+        );
+        StatusCode::BadTypeMismatch
+    })
+}
+
+/// Convert an output argument of a method to the expected type.
+pub(super) fn output<T: TryFromVariant>(
+    session: &Session,
+    value: Variant,
+    method: &str,
+) -> Result<T, StatusCode> {
+    T::try_from_variant(value).map_err(|e| {
+        session_error!(session, "Invalid output argument from {}: {}", method, e);
+        StatusCode::BadTypeMismatch
+    })
+}
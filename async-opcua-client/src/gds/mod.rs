@@ -0,0 +1,481 @@
+//! Certificate management with a Global Discovery Server (GDS), using the pull model
+//! described in OPC UA Part 12 - Discovery and Global Services, 7.
+//!
+//! In the pull model, the application connects to the GDS itself, asks it to issue
+//! a certificate with [`GdsClient::start_signing_request`] or
+//! [`GdsClient::start_new_key_pair_request`], and polls for the result with
+//! [`GdsClient::finish_request`] until an administrator or the certificate manager has
+//! approved the request. Trust lists are downloaded with [`GdsClient::get_trust_list`].
+//!
+//! [`GdsClient::request_certificate`] and [`GdsClient::update_trust_list`] combine these
+//! steps, and store the results in the certificate store of the client, so that they are
+//! used for connections made afterwards.
+//!
+//! In the push model, a certificate manager administers the certificates and trust lists
+//! of a remote server through its `ServerConfiguration` object instead, with
+//! [`ServerConfigurationClient`].
+
+mod methods;
+mod push;
+mod trust_list;
+
+use std::{sync::Arc, time::Duration};
+
+use opcua_core::{sync::RwLock, trace_read_lock};
+use opcua_crypto::{CertificateStore, PrivateKey, X509Data, X509};
+use opcua_types::{
+    ByteString, NodeId, ObjectId, StatusCode, TrustListDataType, TrustListMasks, TryFromVariant,
+    UAString, Variant,
+};
+
+use crate::{
+    session::{session_debug, session_error, session_warn},
+    Session,
+};
+
+use methods::{call_by_name, output, outputs};
+use trust_list::read_trust_list;
+
+pub use push::ServerConfigurationClient;
+
+/// The namespace URI of the GDS information model.
+pub const GDS_NAMESPACE_URI: &str = "http://opcfoundation.org/UA/GDS/";
+
+/// Private key format requested with `StartNewKeyPairRequest`.
+const PRIVATE_KEY_FORMAT_PEM: &str = "PEM";
+
+/// A certificate issued by a GDS, returned by `FinishRequest`.
+#[derive(Debug, Clone)]
+pub struct IssuedCertificate {
+    /// The DER encoded certificate.
+    pub certificate: ByteString,
+    /// The private key of the certificate, for requests started with
+    /// [`GdsClient::start_new_key_pair_request`]. Empty for signing requests.
+    pub private_key: ByteString,
+    /// The DER encoded certificates of the issuer chain.
+    pub issuer_certificates: Vec<ByteString>,
+}
+
+/// Client for the `Directory` object of a Global Discovery Server, for requesting
+/// certificates and trust lists in the pull model.
+///
+/// Applications are identified by the `application_id` the GDS assigned to them when
+/// they were registered. Certificate groups and certificate types can be null to use
+/// the defaults of the GDS.
+pub struct GdsClient {
+    session: Arc<Session>,
+    directory_id: NodeId,
+    namespace: u16,
+}
+
+impl GdsClient {
+    /// Create a client for the GDS the session is connected to, looking up the
+    /// `Directory` object in the `Objects` folder.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(GdsClient)` - The client for the GDS.
+    /// * `Err(StatusCode)` - The server does not have the GDS namespace, or no `Directory` object.
+    ///
+    pub async fn connect(session: Arc<Session>) -> Result<Self, StatusCode> {
+        let namespace = session
+            .get_namespace_index(GDS_NAMESPACE_URI)
+            .await
+            .map_err(|e| {
+                session_error!(session, "Server is not a global discovery server: {}", e);
+                e.status()
+            })?;
+        let directory_id = session
+            .translate_path(ObjectId::ObjectsFolder, &format!("/{namespace}:Directory"))
+            .await?;
+        Ok(Self::new(session, directory_id, namespace))
+    }
+
+    /// Create a client for a GDS `Directory` object with a known node ID, whose methods
+    /// have browse names in the namespace with index `namespace`.
+    pub fn new(session: Arc<Session>, directory_id: NodeId, namespace: u16) -> Self {
+        Self {
+            session,
+            directory_id,
+            namespace,
+        }
+    }
+
+    /// The session connected to the GDS.
+    pub fn session(&self) -> &Arc<Session> {
+        &self.session
+    }
+
+    /// The node ID of the `Directory` object.
+    pub fn directory_id(&self) -> &NodeId {
+        &self.directory_id
+    }
+
+    /// Ask the GDS to sign a certificate for an existing key, calling `StartSigningRequest`.
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The ID of the application assigned by the GDS.
+    /// * `certificate_group_id` - The certificate group to issue the certificate in.
+    /// * `certificate_type_id` - The type of certificate to issue.
+    /// * `certificate_request` - A DER encoded PKCS #10 certificate signing request,
+    ///   see [`X509::create_signing_request`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The ID of the request, for [`GdsClient::finish_request`].
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn start_signing_request(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        certificate_request: ByteString,
+    ) -> Result<NodeId, StatusCode> {
+        let outputs = self
+            .call_directory(
+                "StartSigningRequest",
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                    certificate_request.into(),
+                ],
+            )
+            .await?;
+        let [request_id] = self.outputs(outputs, "StartSigningRequest")?;
+        self.output(request_id, "StartSigningRequest")
+    }
+
+    /// Ask the GDS to create a new key pair and certificate, calling `StartNewKeyPairRequest`.
+    /// The private key is requested in PEM format, without a password.
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The ID of the application assigned by the GDS.
+    /// * `certificate_group_id` - The certificate group to issue the certificate in.
+    /// * `certificate_type_id` - The type of certificate to issue.
+    /// * `subject_name` - The subject name of the certificate, such as `CN=MyClient,O=MyOrg`.
+    /// * `domain_names` - The domain names or IP addresses of the application.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The ID of the request, for [`GdsClient::finish_request`].
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn start_new_key_pair_request(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        subject_name: &str,
+        domain_names: &[String],
+    ) -> Result<NodeId, StatusCode> {
+        let domain_names: Vec<UAString> = domain_names.iter().map(UAString::from).collect();
+        let outputs = self
+            .call_directory(
+                "StartNewKeyPairRequest",
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                    subject_name.into(),
+                    domain_names.into(),
+                    PRIVATE_KEY_FORMAT_PEM.into(),
+                    UAString::null().into(),
+                ],
+            )
+            .await?;
+        let [request_id] = self.outputs(outputs, "StartNewKeyPairRequest")?;
+        self.output(request_id, "StartNewKeyPairRequest")
+    }
+
+    /// Check whether a certificate request has completed, calling `FinishRequest`.
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The ID of the application assigned by the GDS.
+    /// * `request_id` - The ID of the request, returned when it was started.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(IssuedCertificate))` - The issued certificate.
+    /// * `Ok(None)` - The request has not been approved yet.
+    /// * `Err(StatusCode)` - The request was rejected, or failed.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn finish_request(
+        &self,
+        application_id: &NodeId,
+        request_id: &NodeId,
+    ) -> Result<Option<IssuedCertificate>, StatusCode> {
+        let outputs = match self
+            .call_directory(
+                "FinishRequest",
+                vec![application_id.clone().into(), request_id.clone().into()],
+            )
+            .await
+        {
+            Ok(outputs) => outputs,
+            Err(StatusCode::BadNothingToDo) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let [certificate, private_key, issuer_certificates] =
+            self.outputs(outputs, "FinishRequest")?;
+        Ok(Some(IssuedCertificate {
+            certificate: self.output(certificate, "FinishRequest")?,
+            private_key: self.output(private_key, "FinishRequest")?,
+            issuer_certificates: self
+                .output::<Option<Vec<ByteString>>>(issuer_certificates, "FinishRequest")?
+                .unwrap_or_default(),
+        }))
+    }
+
+    /// Poll [`GdsClient::finish_request`] every `poll_interval` until the certificate has
+    /// been issued. Approval may need an administrator, so wrap this in a timeout if the
+    /// application cannot wait indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(IssuedCertificate)` - The issued certificate.
+    /// * `Err(StatusCode)` - The request was rejected, or failed.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn wait_for_certificate(
+        &self,
+        application_id: &NodeId,
+        request_id: &NodeId,
+        poll_interval: Duration,
+    ) -> Result<IssuedCertificate, StatusCode> {
+        loop {
+            if let Some(issued) = self.finish_request(application_id, request_id).await? {
+                return Ok(issued);
+            }
+            session_debug!(
+                self.session,
+                "Certificate request {} not finished yet",
+                request_id
+            );
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Download the trust list of a certificate group, calling `GetTrustList` and reading
+    /// the returned trust list file.
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The ID of the application assigned by the GDS.
+    /// * `certificate_group_id` - The certificate group to get the trust list of.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TrustListDataType)` - The trust list.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn get_trust_list(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+    ) -> Result<TrustListDataType, StatusCode> {
+        let outputs = self
+            .call_directory(
+                "GetTrustList",
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                ],
+            )
+            .await?;
+        let [trust_list_id] = self.outputs(outputs, "GetTrustList")?;
+        let trust_list_id: NodeId = self.output(trust_list_id, "GetTrustList")?;
+        read_trust_list(&self.session, &trust_list_id, TrustListMasks::All as u32).await
+    }
+
+    /// Request a certificate for the existing private key of the client, wait for it to be
+    /// issued, and store it as the certificate of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate_store` - The certificate store of the client, see [`crate::Client::certificate_store`].
+    /// * `application_id` - The ID of the application assigned by the GDS.
+    /// * `certificate_group_id` - The certificate group to issue the certificate in.
+    /// * `certificate_type_id` - The type of certificate to issue.
+    /// * `x509_data` - The subject and alternate host names to request.
+    /// * `poll_interval` - How often to check whether the certificate has been issued.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(X509)` - The issued certificate, which has been stored.
+    /// * `Err(StatusCode)` - The request failed, or the certificate could not be stored.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn request_certificate(
+        &self,
+        certificate_store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        x509_data: &X509Data,
+        poll_interval: Duration,
+    ) -> Result<X509, StatusCode> {
+        let pkey = trace_read_lock!(certificate_store)
+            .read_own_pkey()
+            .map_err(|e| {
+                session_error!(self.session, "Failed to read private key: {}", e);
+                StatusCode::BadConfigurationError
+            })?;
+        let request = X509::create_signing_request(&pkey, x509_data).map_err(|e| {
+            session_error!(self.session, "Failed to create signing request: {}", e);
+            StatusCode::BadInternalError
+        })?;
+        let request_id = self
+            .start_signing_request(
+                application_id,
+                certificate_group_id,
+                certificate_type_id,
+                ByteString::from(request),
+            )
+            .await?;
+        let issued = self
+            .wait_for_certificate(application_id, &request_id, poll_interval)
+            .await?;
+        let cert = X509::from_byte_string(&issued.certificate).map_err(|e| {
+            session_error!(self.session, "GDS issued an invalid certificate: {}", e);
+            e.status()
+        })?;
+        self.store_own_cert(certificate_store, &cert, &pkey)?;
+        Ok(cert)
+    }
+
+    /// Like [`GdsClient::request_certificate`], but with a new key pair created by the GDS,
+    /// replacing the private key of the client as well.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(X509)` - The issued certificate, which has been stored with its private key.
+    /// * `Err(StatusCode)` - The request failed, or the certificate could not be stored.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub async fn request_new_key_pair(
+        &self,
+        certificate_store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        subject_name: &str,
+        domain_names: &[String],
+        poll_interval: Duration,
+    ) -> Result<X509, StatusCode> {
+        let request_id = self
+            .start_new_key_pair_request(
+                application_id,
+                certificate_group_id,
+                certificate_type_id,
+                subject_name,
+                domain_names,
+            )
+            .await?;
+        let issued = self
+            .wait_for_certificate(application_id, &request_id, poll_interval)
+            .await?;
+        let cert = X509::from_byte_string(&issued.certificate).map_err(|e| {
+            session_error!(self.session, "GDS issued an invalid certificate: {}", e);
+            e.status()
+        })?;
+        let pkey = PrivateKey::from_pem(issued.private_key.as_ref()).map_err(|_| {
+            session_error!(self.session, "GDS returned an invalid private key");
+            StatusCode::BadCertificateInvalid
+        })?;
+        self.store_own_cert(certificate_store, &cert, &pkey)?;
+        Ok(cert)
+    }
+
+    /// Download the trust list of a certificate group, and replace the trusted certificates
+    /// of the client with the trusted certificates in it.
+    ///
+    /// The certificate store does not support issuer certificates or revocation lists,
+    /// so these are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The trusted certificates were replaced.
+    /// * `Err(StatusCode)` - The request failed, or the certificates could not be stored.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn update_trust_list(
+        &self,
+        certificate_store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+    ) -> Result<(), StatusCode> {
+        let trust_list = self
+            .get_trust_list(application_id, certificate_group_id)
+            .await?;
+        if trust_list.specified_lists & TrustListMasks::TrustedCertificates as u32 == 0 {
+            session_warn!(
+                self.session,
+                "Trust list from the GDS has no trusted certificates, not updating"
+            );
+            return Ok(());
+        }
+        let certs = trust_list
+            .trusted_certificates
+            .iter()
+            .flatten()
+            .map(X509::from_byte_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                session_error!(self.session, "Trust list has an invalid certificate: {}", e);
+                e.status()
+            })?;
+        trace_read_lock!(certificate_store)
+            .replace_trusted_certs(&certs)
+            .map_err(|e| {
+                session_error!(self.session, "Failed to store trusted certificates: {}", e);
+                StatusCode::BadConfigurationError
+            })
+    }
+
+    fn store_own_cert(
+        &self,
+        certificate_store: &RwLock<CertificateStore>,
+        cert: &X509,
+        pkey: &PrivateKey,
+    ) -> Result<(), StatusCode> {
+        trace_read_lock!(certificate_store)
+            .store_own_cert_and_pkey(cert, Some(pkey))
+            .map_err(|e| {
+                session_error!(self.session, "Failed to store certificate: {}", e);
+                StatusCode::BadConfigurationError
+            })
+    }
+
+    async fn call_directory(
+        &self,
+        method: &str,
+        input: Vec<Variant>,
+    ) -> Result<Vec<Variant>, StatusCode> {
+        call_by_name(
+            &self.session,
+            &self.directory_id,
+            &format!("{}:{}", self.namespace, method),
+            input,
+        )
+        .await
+    }
+
+    fn outputs<const N: usize>(
+        &self,
+        res: Vec<Variant>,
+        method: &str,
+    ) -> Result<[Variant; N], StatusCode> {
+        outputs(&self.session, res, method)
+    }
+
+    fn output<T: TryFromVariant>(&self, value: Variant, method: &str) -> Result<T, StatusCode> {
+        output(&self.session, value, method)
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use opcua_types::{
+    ByteString, NodeId, ObjectId, StatusCode, TrustListDataType, TryFromVariant, UAString, Variant,
+};
+
+use crate::Session;
+
+use super::{
+    methods::{call_by_name, output, outputs},
+    trust_list::{read_trust_list, write_trust_list},
+};
+
+/// Client for the `ServerConfiguration` object of a remote server, for administering its
+/// certificates and trust lists in the push model, described in OPC UA Part 12 - Discovery
+/// and Global Services, 7.10.
+///
+/// The session must be authenticated as a user that may change the configuration of the server,
+/// typically with the `SecurityAdmin` role, over an encrypted connection.
+///
+/// Changes made with [`ServerConfigurationClient::update_certificate`] and
+/// [`ServerConfigurationClient::write_trust_list`] may only take effect once
+/// [`ServerConfigurationClient::apply_changes`] is called, if the server says so.
+pub struct ServerConfigurationClient {
+    session: Arc<Session>,
+    object_id: NodeId,
+}
+
+impl ServerConfigurationClient {
+    /// Create a client for the standard `ServerConfiguration` object of the server.
+    pub fn new(session: Arc<Session>) -> Self {
+        Self::with_object_id(session, ObjectId::ServerConfiguration.into())
+    }
+
+    /// Create a client for a server configuration object with a non-standard node ID.
+    pub fn with_object_id(session: Arc<Session>, object_id: NodeId) -> Self {
+        Self { session, object_id }
+    }
+
+    /// The session connected to the server.
+    pub fn session(&self) -> &Arc<Session> {
+        &self.session
+    }
+
+    /// The node ID of the server configuration object.
+    pub fn object_id(&self) -> &NodeId {
+        &self.object_id
+    }
+
+    /// Ask the server to create a certificate signing request, calling `CreateSigningRequest`.
+    /// The request is then signed by a certificate authority, and the resulting certificate
+    /// given to the server with [`ServerConfigurationClient::update_certificate`].
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate_group_id` - The certificate group of the certificate, null for the default group.
+    /// * `certificate_type_id` - The type of the certificate, null for the default type.
+    /// * `subject_name` - The subject name to request, `None` to use the subject of the current certificate.
+    /// * `regenerate_private_key` - Create a new private key for the request.
+    /// * `nonce` - Additional entropy for creating the new private key, at least 32 bytes if
+    ///   `regenerate_private_key` is set.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ByteString)` - The DER encoded PKCS #10 certificate signing request.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn create_signing_request(
+        &self,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        subject_name: Option<&str>,
+        regenerate_private_key: bool,
+        nonce: ByteString,
+    ) -> Result<ByteString, StatusCode> {
+        let res = self
+            .call(
+                "CreateSigningRequest",
+                vec![
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                    subject_name
+                        .map(UAString::from)
+                        .unwrap_or_else(UAString::null)
+                        .into(),
+                    regenerate_private_key.into(),
+                    nonce.into(),
+                ],
+            )
+            .await?;
+        self.single_output(res, "CreateSigningRequest")
+    }
+
+    /// Give the server a new certificate, calling `UpdateCertificate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate_group_id` - The certificate group of the certificate, null for the default group.
+    /// * `certificate_type_id` - The type of the certificate, null for the default type.
+    /// * `certificate` - The DER encoded certificate.
+    /// * `issuer_certificates` - The DER encoded certificates of the issuer chain.
+    /// * `private_key` - The format, `PEM` or `PFX`, and content of a new private key for the
+    ///   certificate, or `None` if the certificate was issued for the existing private key or
+    ///   a private key created by [`ServerConfigurationClient::create_signing_request`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(bool)` - Whether [`ServerConfigurationClient::apply_changes`] must be called for the
+    ///   new certificate to be used.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn update_certificate(
+        &self,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        certificate: ByteString,
+        issuer_certificates: Vec<ByteString>,
+        private_key: Option<(&str, ByteString)>,
+    ) -> Result<bool, StatusCode> {
+        let (private_key_format, private_key) = match private_key {
+            Some((format, key)) => (UAString::from(format), key),
+            None => (UAString::null(), ByteString::null()),
+        };
+        let res = self
+            .call(
+                "UpdateCertificate",
+                vec![
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                    certificate.into(),
+                    issuer_certificates.into(),
+                    private_key_format.into(),
+                    private_key.into(),
+                ],
+            )
+            .await?;
+        self.single_output(res, "UpdateCertificate")
+    }
+
+    /// Get the certificates the server has rejected, calling `GetRejectedList`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ByteString>)` - The DER encoded rejected certificates.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn get_rejected_list(&self) -> Result<Vec<ByteString>, StatusCode> {
+        let res = self.call("GetRejectedList", Vec::new()).await?;
+        Ok(self
+            .single_output::<Option<Vec<ByteString>>>(res, "GetRejectedList")?
+            .unwrap_or_default())
+    }
+
+    /// Tell the server to start using changed certificates and trust lists, calling `ApplyChanges`.
+    /// This may close all sessions of the server, including this one.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The changes were applied.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn apply_changes(&self) -> Result<(), StatusCode> {
+        self.call("ApplyChanges", Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Get the trust list of a certificate group, from the `TrustList` of the group in the
+    /// `CertificateGroups` folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate_group` - The browse name of the certificate group,
+    ///   such as `DefaultApplicationGroup`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The node ID of the trust list, for [`ServerConfigurationClient::read_trust_list`]
+    ///   and [`ServerConfigurationClient::write_trust_list`].
+    /// * `Err(StatusCode)` - The server has no such certificate group, or the request failed.
+    ///
+    pub async fn trust_list_id(&self, certificate_group: &str) -> Result<NodeId, StatusCode> {
+        self.session
+            .translate_path(
+                self.object_id.clone(),
+                &format!("/CertificateGroups/{certificate_group}/TrustList"),
+            )
+            .await
+    }
+
+    /// Read a trust list of the server, opening, reading and closing the trust list file.
+    ///
+    /// # Arguments
+    ///
+    /// * `trust_list_id` - The node ID of the trust list, see [`ServerConfigurationClient::trust_list_id`].
+    /// * `masks` - The lists to read, as a combination of [`opcua_types::TrustListMasks`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TrustListDataType)` - The trust list.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read_trust_list(
+        &self,
+        trust_list_id: &NodeId,
+        masks: u32,
+    ) -> Result<TrustListDataType, StatusCode> {
+        read_trust_list(&self.session, trust_list_id, masks).await
+    }
+
+    /// Replace a trust list of the server, opening the trust list file for writing, writing
+    /// `trust_list` to it, and closing it with `CloseAndUpdate`. Only the lists selected by
+    /// `specified_lists` of `trust_list` are replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `trust_list_id` - The node ID of the trust list, see [`ServerConfigurationClient::trust_list_id`].
+    /// * `trust_list` - The new content of the trust list.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(bool)` - Whether [`ServerConfigurationClient::apply_changes`] must be called for the
+    ///   new trust list to be used.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn write_trust_list(
+        &self,
+        trust_list_id: &NodeId,
+        trust_list: &TrustListDataType,
+    ) -> Result<bool, StatusCode> {
+        write_trust_list(&self.session, trust_list_id, trust_list).await
+    }
+
+    async fn call(&self, method: &str, input: Vec<Variant>) -> Result<Vec<Variant>, StatusCode> {
+        call_by_name(&self.session, &self.object_id, method, input).await
+    }
+
+    fn single_output<T: TryFromVariant>(
+        &self,
+        res: Vec<Variant>,
+        method: &str,
+    ) -> Result<T, StatusCode> {
+        let [value] = outputs(&self.session, res, method)?;
+        output(&self.session, value, method)
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::Cursor;
+
+use opcua_types::{
+    BinaryDecodable, BinaryEncodable, ByteString, NodeId, OpenFileMode, StatusCode,
+    TrustListDataType, TrustListMasks,
+};
+
+use crate::{
+    session::{session_error, session_warn},
+    Session,
+};
+
+use super::methods::{call_by_name, output, outputs};
+
+/// Number of bytes read or written by each call to `Read` or `Write` when transferring
+/// a trust list.
+const TRUST_LIST_CHUNK_SIZE: usize = 65536;
+
+/// Read a trust list file. If `masks` only selects some of the lists, the file is opened
+/// with `OpenWithMasks`, otherwise with `Open`.
+pub(super) async fn read_trust_list(
+    session: &Session,
+    trust_list_id: &NodeId,
+    masks: u32,
+) -> Result<TrustListDataType, StatusCode> {
+    let res = if masks == TrustListMasks::All as u32 {
+        call_by_name(
+            session,
+            trust_list_id,
+            "Open",
+            vec![(OpenFileMode::Read as u8).into()],
+        )
+        .await?
+    } else {
+        call_by_name(session, trust_list_id, "OpenWithMasks", vec![masks.into()]).await?
+    };
+    let [handle] = outputs(session, res, "Open")?;
+    let handle: u32 = output(session, handle, "Open")?;
+
+    let mut data = Vec::new();
+    let res = loop {
+        let chunk = match call_by_name(
+            session,
+            trust_list_id,
+            "Read",
+            vec![handle.into(), (TRUST_LIST_CHUNK_SIZE as i32).into()],
+        )
+        .await
+        .and_then(|res| {
+            let [chunk] = outputs(session, res, "Read")?;
+            output::<ByteString>(session, chunk, "Read")
+        }) {
+            Ok(chunk) => chunk,
+            Err(e) => break Err(e),
+        };
+        match chunk.value {
+            Some(chunk) if !chunk.is_empty() => data.extend_from_slice(&chunk),
+            _ => break Ok(()),
+        }
+    };
+    close(session, trust_list_id, handle).await;
+    res?;
+
+    let ctx = session.encoding_context().read();
+    TrustListDataType::decode(&mut Cursor::new(data), &ctx.context()).map_err(|e| {
+        session_error!(session, "Invalid trust list {}: {}", trust_list_id, e);
+        StatusCode::BadDecodingError
+    })
+}
+
+/// Replace the content of a trust list file, writing it in chunks and applying it with
+/// `CloseAndUpdate`. Returns whether the server needs `ApplyChanges` to be called.
+pub(super) async fn write_trust_list(
+    session: &Session,
+    trust_list_id: &NodeId,
+    trust_list: &TrustListDataType,
+) -> Result<bool, StatusCode> {
+    let data = {
+        let ctx = session.encoding_context().read();
+        trust_list.encode_to_vec(&ctx.context())
+    };
+    let mode = OpenFileMode::Write as u8 | OpenFileMode::EraseExisting as u8;
+    let res = call_by_name(session, trust_list_id, "Open", vec![mode.into()]).await?;
+    let [handle] = outputs(session, res, "Open")?;
+    let handle: u32 = output(session, handle, "Open")?;
+
+    for chunk in data.chunks(TRUST_LIST_CHUNK_SIZE) {
+        if let Err(e) = call_by_name(
+            session,
+            trust_list_id,
+            "Write",
+            vec![handle.into(), ByteString::from(chunk).into()],
+        )
+        .await
+        {
+            close(session, trust_list_id, handle).await;
+            return Err(e);
+        }
+    }
+
+    let res = call_by_name(
+        session,
+        trust_list_id,
+        "CloseAndUpdate",
+        vec![handle.into()],
+    )
+    .await?;
+    let [apply_changes_required] = outputs(session, res, "CloseAndUpdate")?;
+    output(session, apply_changes_required, "CloseAndUpdate")
+}
+
+async fn close(session: &Session, trust_list_id: &NodeId, handle: u32) {
+    if let Err(e) = call_by_name(session, trust_list_id, "Close", vec![handle.into()]).await {
+        session_warn!(session, "Failed to close file {}: {}", trust_list_id, e);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::{future::Future, path::Path, sync::Arc};
 
 use async_trait::async_trait;
 use opcua_crypto::{CertificateStore, PrivateKey, X509};
@@ -11,6 +11,11 @@ pub trait IssuedTokenSource: Send + Sync {
     /// Get a valid issued token. This may be a cached token,
     /// or a new one if the cache is empty or expired.
     async fn get_issued_token(&self) -> Result<ByteString, Error>;
+
+    /// Called when the server rejects a token returned from [`IssuedTokenSource::get_issued_token`],
+    /// for example because it has expired. Sources that cache tokens should discard the cached
+    /// token, so that the next call returns a new one. The default implementation does nothing.
+    fn invalidate_issued_token(&self) {}
 }
 
 #[async_trait]
@@ -20,6 +25,21 @@ impl IssuedTokenSource for ByteString {
     }
 }
 
+/// Issued token source calling a function each time a token is needed,
+/// created with [`IdentityToken::new_issued_token_fn`].
+struct IssuedTokenFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> IssuedTokenSource for IssuedTokenFn<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<ByteString, Error>> + Send,
+{
+    async fn get_issued_token(&self) -> Result<ByteString, Error> {
+        (self.0)().await
+    }
+}
+
 /// Wrapper for an issued token source.
 #[derive(Clone)]
 pub struct IssuedTokenWrapper(pub(crate) Arc<dyn IssuedTokenSource>);
@@ -114,6 +134,20 @@ impl IdentityToken {
         IdentityToken::IssuedToken(IssuedTokenWrapper::new_source(token_source))
     }
 
+    /// Create a new issued token based identity token calling `token_fn` each time
+    /// the session is activated, including when it is reactivated after a reconnect.
+    ///
+    /// This lets tokens be obtained or refreshed outside the client, for example through
+    /// an OAuth2 or OpenID Connect flow. The token is encrypted as required by the
+    /// user token policy of the endpoint.
+    pub fn new_issued_token_fn<F, Fut>(token_fn: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ByteString, Error>> + Send + 'static,
+    {
+        Self::new_issued_token(IssuedTokenFn(token_fn))
+    }
+
     /// Create a new issued token based identity token from a shared reference.
     pub fn new_issued_token_arc(token_source: Arc<dyn IssuedTokenSource>) -> Self {
         IdentityToken::IssuedToken(IssuedTokenWrapper::new(token_source))
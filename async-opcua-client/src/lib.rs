@@ -115,21 +115,52 @@ pub mod browser;
 mod builder;
 mod config;
 pub mod custom_types;
+pub mod discovery;
+mod endpoint_selector;
+pub mod gds;
 mod identity_token;
+#[cfg(feature = "request-metrics")]
+pub mod metrics;
+#[cfg(feature = "recording")]
+pub mod recording;
 mod retry;
 mod session;
 pub mod transport;
 
 pub use builder::ClientBuilder;
-pub use config::{ClientConfig, ClientEndpoint, ClientUserToken, ANONYMOUS_USER_TOKEN_ID};
-pub use retry::{ExponentialBackoff, SessionRetryPolicy};
+pub use config::{
+    ClientConfig, ClientEndpoint, ClientUserToken, ConnectionPathOptions, CustomTypeDiscovery,
+    HttpsOptions, PublishOptions, ReadCacheOptions, RequestLimitOptions, RequestOverflow,
+    SampledFallbackOptions, SequenceGapRecovery, ServiceRetryOptions, SubscriptionTransferPolicy,
+    ANONYMOUS_USER_TOKEN_ID,
+};
+pub use endpoint_selector::{
+    EndpointSelector, HighestSecurityLevel, PreferMatchingHostname, RequireSecurity, ScoreEndpoints,
+};
+pub use retry::{
+    ExponentialBackoff, ExponentialReconnect, ReconnectDecision, ReconnectEvent, ReconnectStrategy,
+    SessionRetryPolicy,
+};
 pub use session::{
-    Client, ConnectionSource, DataChangeCallback, DefaultRetryPolicy, DirectConnectionSource,
-    EventCallback, HistoryReadAction, HistoryUpdateAction, MonitoredItem,
-    OnSubscriptionNotification, OnSubscriptionNotificationCore, RequestRetryPolicy, Session,
-    SessionActivity, SessionBuilder, SessionConnectMode, SessionEventLoop, SessionPollResult,
-    Subscription, SubscriptionActivity, SubscriptionCallbacks, UARequest,
+    AggregateSeries, AggregateValue, BrowseResultStream, Client, ConditionState,
+    ConditionSubscription, ConnectionEvent, ConnectionSource, DataChangeCallback,
+    DefaultRetryPolicy, DirectConnectionSource, EventCallback, EventSubscription, FailoverMode,
+    FileSubscriptionStore, FilteredNotifications, HealthReport, HealthStatus, HistoryEvents,
+    HistoryReadAction, HistoryReadRawOptions, HistoryUpdateAction, HistoryUpdateOutcome,
+    HistoryValueStream, MonitoredItem, MonitoredItemChange, MonitoredItemHandle,
+    MonitoredItemResync, NotificationFilter, NotificationSender, NotificationStream,
+    OnSubscriptionNotification, OnSubscriptionNotificationCore, OperationLimits,
+    PersistedMonitoredItem, PersistedSubscription, PersistedSubscriptions, PooledSession,
+    RedundancyEvent, RedundancyOptions, RedundantSession, RegisteredNodes, RemoteFile,
+    RequestRetryPolicy, SequenceGap, ServerRedundancyInfo, ServerStatusEvent, ServerStatusInfo,
+    ServerStatusMonitor, Session, SessionActivity, SessionBuilder, SessionConnectMode,
+    SessionEventLoop, SessionPollResult, SessionPool, SessionlessChannel, Subscription,
+    SubscriptionActivity, SubscriptionCallbacks, SubscriptionChange, SubscriptionHealth,
+    SubscriptionNotification, SubscriptionStore, TagBinding, TagSubscription, UARequest,
+    WriteNodeResult, WriteReport, WriteVerification,
 };
+
+pub use opcua_macros::TagBinding;
 pub use transport::AsyncSecureChannel;
 
 pub mod services {
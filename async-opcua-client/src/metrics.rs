@@ -0,0 +1,361 @@
+//! Metrics of the requests sent by the client, per service.
+//!
+//! Only available with the `request-metrics` feature. Every client records the number
+//! of requests, the number of failed requests by status code, and a latency histogram
+//! for each service, in a shared [`RequestMetrics`]. Get it with
+//! [`Client::request_metrics`](crate::Client::request_metrics) or
+//! [`Session::request_metrics`](crate::Session::request_metrics), or share one between
+//! several clients with [`ClientBuilder::request_metrics`](crate::ClientBuilder::request_metrics).
+//!
+//! Note that publish requests are held by the server until it has notifications to send,
+//! so their latency is mostly the publishing interval of the subscriptions.
+
+use std::{fmt::Write, ops::Deref, sync::Arc, time::Duration};
+
+use hashbrown::HashMap;
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::StatusCode;
+
+/// Default upper bounds of the latency histogram buckets, from 1 millisecond to 10 seconds.
+pub const DEFAULT_LATENCY_BUCKETS: &[Duration] = &[
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+#[derive(Default)]
+struct ServiceStats {
+    requests: u64,
+    errors: HashMap<StatusCode, u64>,
+    buckets: Vec<u64>,
+    latency_sum: Duration,
+}
+
+/// Collector of metrics of the requests sent by one or more clients.
+pub struct RequestMetrics {
+    bounds: Vec<Duration>,
+    services: Mutex<HashMap<&'static str, ServiceStats>>,
+}
+
+impl Default for RequestMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for RequestMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestMetrics")
+            .field("bounds", &self.bounds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestMetrics {
+    /// Create a new collector with [`DEFAULT_LATENCY_BUCKETS`].
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_LATENCY_BUCKETS.to_vec())
+    }
+
+    /// Create a new collector with the given upper bounds of the latency histogram
+    /// buckets. Bounds are sorted, and duplicates are removed.
+    pub fn with_buckets(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort();
+        bounds.dedup();
+        Self {
+            bounds,
+            services: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a request for `service` that completed with `status` after `elapsed`.
+    /// Requests with a bad status are counted as errors.
+    pub fn record(&self, service: &'static str, status: StatusCode, elapsed: Duration) {
+        let bucket = self.bounds.partition_point(|b| *b < elapsed);
+        let mut services = trace_lock!(self.services);
+        let stats = services.entry(service).or_default();
+        if stats.buckets.is_empty() {
+            stats.buckets = vec![0; self.bounds.len() + 1];
+        }
+        stats.requests += 1;
+        stats.buckets[bucket] += 1;
+        stats.latency_sum += elapsed;
+        if status.is_bad() {
+            *stats.errors.entry(status).or_default() += 1;
+        }
+    }
+
+    /// Get a snapshot of the metrics recorded so far.
+    pub fn snapshot(&self) -> RequestMetricsSnapshot {
+        let services = trace_lock!(self.services);
+        let mut snapshot: Vec<_> = services
+            .iter()
+            .map(|(service, stats)| {
+                let mut errors: Vec<_> = stats.errors.iter().map(|(s, c)| (*s, *c)).collect();
+                errors.sort_by_key(|(s, _)| s.bits());
+                ServiceMetrics {
+                    service,
+                    requests: stats.requests,
+                    errors,
+                    latency: LatencyHistogram {
+                        bounds: self.bounds.clone(),
+                        counts: stats.buckets.clone(),
+                        sum: stats.latency_sum,
+                        count: stats.requests,
+                    },
+                }
+            })
+            .collect();
+        snapshot.sort_by_key(|s| s.service);
+        RequestMetricsSnapshot { services: snapshot }
+    }
+
+    /// Clear all recorded metrics.
+    pub fn reset(&self) {
+        trace_lock!(self.services).clear();
+    }
+}
+
+/// Shared handle to the [`RequestMetrics`] of a client. Recorded metrics are not part of
+/// the configuration, so all handles compare equal.
+#[derive(Clone, Default, Debug)]
+pub(crate) struct SharedRequestMetrics(pub(crate) Arc<RequestMetrics>);
+
+impl Deref for SharedRequestMetrics {
+    type Target = Arc<RequestMetrics>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PartialEq for SharedRequestMetrics {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Histogram of the latency of requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    /// Upper bounds of the buckets, in ascending order.
+    pub bounds: Vec<Duration>,
+    /// Number of requests in each bucket. There is one more count than there are
+    /// bounds, the last is the number of requests slower than the largest bound.
+    /// Counts are not cumulative.
+    pub counts: Vec<u64>,
+    /// Total latency of all requests.
+    pub sum: Duration,
+    /// Total number of requests.
+    pub count: u64,
+}
+
+impl LatencyHistogram {
+    /// Get the mean latency, or `None` if there were no requests.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|c| *c > 0)?;
+        Some(self.sum / count)
+    }
+
+    /// Estimate the latency below which a fraction `q` of the requests completed,
+    /// as the upper bound of the bucket containing that quantile. Returns `None` if
+    /// there were no requests, or the quantile is above the largest bound.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            seen += count;
+            if seen >= rank {
+                return Some(*bound);
+            }
+        }
+        None
+    }
+}
+
+/// Metrics of the requests for a single service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceMetrics {
+    /// Name of the service, for example `Read`.
+    pub service: &'static str,
+    /// Number of requests sent, including failed requests.
+    pub requests: u64,
+    /// Number of failed requests by status code, ordered by status code. Requests fail
+    /// if the server returns a bad service result, or if the client fails to send the
+    /// request or receive the response, for example with `BadTimeout`.
+    pub errors: Vec<(StatusCode, u64)>,
+    /// Latency of the requests, from sending the request to receiving the response.
+    pub latency: LatencyHistogram,
+}
+
+impl ServiceMetrics {
+    /// Get the total number of failed requests.
+    pub fn error_count(&self) -> u64 {
+        self.errors.iter().map(|(_, c)| c).sum()
+    }
+}
+
+/// Snapshot of [`RequestMetrics`] at a point in time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RequestMetricsSnapshot {
+    /// Metrics of each service requests were sent for, ordered by service name.
+    pub services: Vec<ServiceMetrics>,
+}
+
+impl RequestMetricsSnapshot {
+    /// Get the metrics of `service`, if any requests were sent for it.
+    pub fn service(&self, service: &str) -> Option<&ServiceMetrics> {
+        self.services.iter().find(|s| s.service == service)
+    }
+
+    /// Encode the snapshot in the Prometheus text exposition format, as the metrics
+    /// `opcua_client_requests_total`, `opcua_client_request_errors_total` and
+    /// `opcua_client_request_duration_seconds`, labeled by `service`.
+    pub fn encode_prometheus(&self, out: &mut impl Write) -> std::fmt::Result {
+        writeln!(
+            out,
+            "# HELP opcua_client_requests_total Number of requests sent by the client."
+        )?;
+        writeln!(out, "# TYPE opcua_client_requests_total counter")?;
+        for s in &self.services {
+            writeln!(
+                out,
+                "opcua_client_requests_total{{service=\"{}\"}} {}",
+                s.service, s.requests
+            )?;
+        }
+
+        writeln!(
+            out,
+            "# HELP opcua_client_request_errors_total Number of failed requests by status code."
+        )?;
+        writeln!(out, "# TYPE opcua_client_request_errors_total counter")?;
+        for s in &self.services {
+            for (status, count) in &s.errors {
+                writeln!(
+                    out,
+                    "opcua_client_request_errors_total{{service=\"{}\",status=\"{}\"}} {}",
+                    s.service, status, count
+                )?;
+            }
+        }
+
+        writeln!(
+            out,
+            "# HELP opcua_client_request_duration_seconds Latency of requests sent by the client."
+        )?;
+        writeln!(
+            out,
+            "# TYPE opcua_client_request_duration_seconds histogram"
+        )?;
+        for s in &self.services {
+            let h = &s.latency;
+            let mut cumulative = 0;
+            for (bound, count) in h.bounds.iter().zip(&h.counts) {
+                cumulative += count;
+                writeln!(
+                    out,
+                    "opcua_client_request_duration_seconds_bucket{{service=\"{}\",le=\"{}\"}} {}",
+                    s.service,
+                    bound.as_secs_f64(),
+                    cumulative
+                )?;
+            }
+            writeln!(
+                out,
+                "opcua_client_request_duration_seconds_bucket{{service=\"{}\",le=\"+Inf\"}} {}",
+                s.service, h.count
+            )?;
+            writeln!(
+                out,
+                "opcua_client_request_duration_seconds_sum{{service=\"{}\"}} {}",
+                s.service,
+                h.sum.as_secs_f64()
+            )?;
+            writeln!(
+                out,
+                "opcua_client_request_duration_seconds_count{{service=\"{}\"}} {}",
+                s.service, h.count
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Encode the snapshot in the Prometheus text exposition format, see
+    /// [`RequestMetricsSnapshot::encode_prometheus`].
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        // Writing to a string cannot fail.
+        let _ = self.encode_prometheus(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_types::StatusCode;
+
+    use super::RequestMetrics;
+
+    #[test]
+    fn record_and_encode() {
+        let metrics = RequestMetrics::with_buckets(vec![
+            Duration::from_millis(100),
+            Duration::from_millis(10),
+        ]);
+        metrics.record("Read", StatusCode::Good, Duration::from_millis(5));
+        metrics.record("Read", StatusCode::BadTimeout, Duration::from_millis(50));
+        metrics.record("Read", StatusCode::BadTimeout, Duration::from_secs(1));
+        metrics.record("Browse", StatusCode::Good, Duration::from_millis(10));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.services.len(), 2);
+        assert_eq!(snapshot.services[0].service, "Browse");
+        let read = snapshot.service("Read").unwrap();
+        assert_eq!(read.requests, 3);
+        assert_eq!(read.errors, vec![(StatusCode::BadTimeout, 2)]);
+        assert_eq!(read.error_count(), 2);
+        assert_eq!(read.latency.counts, vec![1, 1, 1]);
+        assert_eq!(read.latency.sum, Duration::from_millis(1055));
+        assert_eq!(read.latency.quantile(0.5), Some(Duration::from_millis(100)));
+        assert_eq!(read.latency.quantile(1.0), None);
+        // The upper bound of a bucket is inclusive.
+        assert_eq!(
+            snapshot.service("Browse").unwrap().latency.counts,
+            vec![1, 0, 0]
+        );
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("opcua_client_requests_total{service=\"Read\"} 3\n"));
+        assert!(text.contains(
+            "opcua_client_request_errors_total{service=\"Read\",status=\"BadTimeout\"} 2\n"
+        ));
+        assert!(text.contains(
+            "opcua_client_request_duration_seconds_bucket{service=\"Read\",le=\"0.1\"} 2\n"
+        ));
+        assert!(text.contains(
+            "opcua_client_request_duration_seconds_bucket{service=\"Read\",le=\"+Inf\"} 3\n"
+        ));
+        assert!(
+            text.contains("opcua_client_request_duration_seconds_count{service=\"Browse\"} 1\n")
+        );
+
+        metrics.reset();
+        assert!(metrics.snapshot().services.is_empty());
+    }
+}
@@ -0,0 +1,465 @@
+//! Recording of the requests sent and responses received by the client, for audit
+//! logs and for replaying the requests against another server.
+//!
+//! Add a [`MessageRecorder`] to a client with
+//! [`ClientBuilder::interceptor`](crate::ClientBuilder::interceptor) to write every
+//! message on its secure channels to a file, with a timestamp and the security policy
+//! and mode of the channel. Messages are written in OPC UA binary, either in a compact
+//! binary file, or as one JSON object per line with the message base64 encoded. Message
+//! payloads can be redacted, see [`Redaction`].
+//!
+//! Read a recording with [`RecordingReader`], and send the recorded requests again on
+//! another session with a [`Replayer`].
+
+mod recorder;
+mod replay;
+
+pub use recorder::MessageRecorder;
+pub use replay::{ReplayedRequest, Replayer};
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use opcua_core::{comms::interceptor::MessageDirection, Message, RequestMessage, ResponseMessage};
+use opcua_crypto::SecurityPolicy;
+use opcua_types::{
+    read_u32, read_u8, write_u32, write_u8, BinaryDecodable, BinaryEncodable, ByteString, Context,
+    DateTime, EncodingResult, Error, MessageSecurityMode, NodeId, ObjectId, UAString,
+};
+use serde::{Deserialize, Serialize};
+
+/// Magic bytes at the start of a binary recording.
+const BINARY_MAGIC: &[u8; 4] = b"UARC";
+const FILE_FORMAT_VERSION: u32 = 1;
+
+/// Format of a recording file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordFormat {
+    /// Compact binary file, with the messages in OPC UA binary.
+    #[default]
+    Binary,
+    /// One JSON object per line, with the messages base64 encoded OPC UA binary.
+    JsonLines,
+}
+
+/// How much of each message is written to a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Redaction {
+    /// Record the full messages.
+    None,
+    /// Remove the authentication token of the session from requests, and user identity
+    /// tokens, signatures, certificates and nonces from the messages opening secure
+    /// channels and creating and activating sessions.
+    #[default]
+    Credentials,
+    /// Only record the metadata of each message, without the message itself.
+    /// Such recordings cannot be replayed.
+    Payloads,
+}
+
+/// A message read from a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    /// Time the message was sent or received.
+    pub timestamp: DateTime,
+    /// Whether the message was sent or received by the client.
+    pub direction: MessageDirection,
+    /// Name of the service the message belongs to, such as `Read` or `ServiceFault`.
+    pub service: String,
+    /// Request handle of the message, from its request or response header.
+    pub request_handle: u32,
+    /// ID of the request on the secure channel.
+    pub request_id: u32,
+    /// ID of the secure channel the message was sent or received on.
+    pub secure_channel_id: u32,
+    /// Security policy of the secure channel.
+    pub security_policy: SecurityPolicy,
+    /// Security mode of the secure channel.
+    pub security_mode: MessageSecurityMode,
+    /// Binary encoding ID of the message.
+    pub type_id: NodeId,
+    /// The message encoded in OPC UA binary, without its type ID, or `None` if it
+    /// was redacted.
+    pub payload: Option<Vec<u8>>,
+}
+
+impl RecordedMessage {
+    fn object_id(&self) -> EncodingResult<ObjectId> {
+        self.type_id
+            .as_object_id()
+            .map_err(|_| Error::decoding(format!("Unknown message type ID {}", self.type_id)))
+    }
+
+    /// Decode the recorded request.
+    ///
+    /// Returns `Ok(None)` if the message is a response, or its payload was redacted.
+    pub fn request(&self, ctx: &Context<'_>) -> EncodingResult<Option<RequestMessage>> {
+        let Some(payload) = self.payload.as_ref() else {
+            return Ok(None);
+        };
+        if self.direction != MessageDirection::Outgoing {
+            return Ok(None);
+        }
+        RequestMessage::decode_by_object_id(&mut Cursor::new(payload), self.object_id()?, ctx)
+            .map(Some)
+    }
+
+    /// Decode the recorded response.
+    ///
+    /// Returns `Ok(None)` if the message is a request, or its payload was redacted.
+    pub fn response(&self, ctx: &Context<'_>) -> EncodingResult<Option<ResponseMessage>> {
+        let Some(payload) = self.payload.as_ref() else {
+            return Ok(None);
+        };
+        if self.direction != MessageDirection::Incoming {
+            return Ok(None);
+        }
+        ResponseMessage::decode_by_object_id(&mut Cursor::new(payload), self.object_id()?, ctx)
+            .map(Some)
+    }
+
+    fn encode_binary<S: Write + ?Sized>(
+        &self,
+        stream: &mut S,
+        ctx: &Context<'_>,
+    ) -> EncodingResult<()> {
+        self.timestamp.encode(stream, ctx)?;
+        write_u8(stream, direction_to_u8(self.direction))?;
+        UAString::from(self.service.as_str()).encode(stream, ctx)?;
+        write_u32(stream, self.request_handle)?;
+        write_u32(stream, self.request_id)?;
+        write_u32(stream, self.secure_channel_id)?;
+        UAString::from(self.security_policy.to_uri()).encode(stream, ctx)?;
+        self.security_mode.encode(stream, ctx)?;
+        self.type_id.encode(stream, ctx)?;
+        match &self.payload {
+            Some(payload) => ByteString::from(payload.as_slice()).encode(stream, ctx),
+            None => ByteString::null().encode(stream, ctx),
+        }
+    }
+
+    fn decode_binary<S: Read + ?Sized>(stream: &mut S, ctx: &Context<'_>) -> EncodingResult<Self> {
+        Ok(Self {
+            timestamp: DateTime::decode(stream, ctx)?,
+            direction: direction_from_u8(read_u8(stream)?)?,
+            service: UAString::decode(stream, ctx)?.to_string(),
+            request_handle: read_u32(stream)?,
+            request_id: read_u32(stream)?,
+            secure_channel_id: read_u32(stream)?,
+            security_policy: SecurityPolicy::from_uri(UAString::decode(stream, ctx)?.as_ref()),
+            security_mode: MessageSecurityMode::decode(stream, ctx)?,
+            type_id: NodeId::decode(stream, ctx)?,
+            payload: ByteString::decode(stream, ctx)?.value,
+        })
+    }
+
+    fn to_json(&self) -> JsonRecord {
+        JsonRecord {
+            timestamp: self.timestamp.to_rfc3339(),
+            direction: match self.direction {
+                MessageDirection::Outgoing => "Outgoing",
+                MessageDirection::Incoming => "Incoming",
+            }
+            .to_owned(),
+            service: self.service.clone(),
+            request_handle: self.request_handle,
+            request_id: self.request_id,
+            secure_channel_id: self.secure_channel_id,
+            security_policy: self.security_policy.to_uri().to_owned(),
+            security_mode: match self.security_mode {
+                MessageSecurityMode::None => "None",
+                MessageSecurityMode::Sign => "Sign",
+                MessageSecurityMode::SignAndEncrypt => "SignAndEncrypt",
+                MessageSecurityMode::Invalid => "Invalid",
+            }
+            .to_owned(),
+            type_id: self.type_id.to_string(),
+            payload: self
+                .payload
+                .as_ref()
+                .map(|p| ByteString::from(p.as_slice()).as_base64()),
+        }
+    }
+
+    fn from_json(record: JsonRecord) -> EncodingResult<Self> {
+        Ok(Self {
+            timestamp: DateTime::from_str(&record.timestamp).map_err(Error::decoding)?,
+            direction: match record.direction.as_str() {
+                "Outgoing" => MessageDirection::Outgoing,
+                "Incoming" => MessageDirection::Incoming,
+                d => return Err(Error::decoding(format!("Invalid message direction {d}"))),
+            },
+            service: record.service,
+            request_handle: record.request_handle,
+            request_id: record.request_id,
+            secure_channel_id: record.secure_channel_id,
+            security_policy: SecurityPolicy::from_uri(&record.security_policy),
+            security_mode: match record.security_mode.as_str() {
+                "None" => MessageSecurityMode::None,
+                "Sign" => MessageSecurityMode::Sign,
+                "SignAndEncrypt" => MessageSecurityMode::SignAndEncrypt,
+                _ => MessageSecurityMode::Invalid,
+            },
+            type_id: NodeId::from_str(&record.type_id)
+                .map_err(|_| Error::decoding(format!("Invalid type ID {}", record.type_id)))?,
+            payload: record
+                .payload
+                .map(|p| {
+                    ByteString::from_base64(&p)
+                        .and_then(|b| b.value)
+                        .ok_or_else(|| Error::decoding("Invalid base64 message payload"))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+fn direction_to_u8(direction: MessageDirection) -> u8 {
+    match direction {
+        MessageDirection::Outgoing => 0,
+        MessageDirection::Incoming => 1,
+    }
+}
+
+fn direction_from_u8(value: u8) -> EncodingResult<MessageDirection> {
+    match value {
+        0 => Ok(MessageDirection::Outgoing),
+        1 => Ok(MessageDirection::Incoming),
+        v => Err(Error::decoding(format!("Invalid message direction {v}"))),
+    }
+}
+
+/// A line of a JSON lines recording.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonRecord {
+    timestamp: String,
+    direction: String,
+    service: String,
+    request_handle: u32,
+    request_id: u32,
+    secure_channel_id: u32,
+    security_policy: String,
+    security_mode: String,
+    type_id: String,
+    payload: Option<String>,
+}
+
+/// Writes messages to a recording in either format.
+pub(crate) struct RecordWriter<W: Write> {
+    writer: W,
+    format: RecordFormat,
+    started: bool,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub(crate) fn new(writer: W, format: RecordFormat) -> Self {
+        Self {
+            writer,
+            format,
+            started: false,
+        }
+    }
+
+    pub(crate) fn write(
+        &mut self,
+        message: &RecordedMessage,
+        ctx: &Context<'_>,
+    ) -> EncodingResult<()> {
+        match self.format {
+            RecordFormat::Binary => {
+                if !self.started {
+                    self.writer.write_all(BINARY_MAGIC)?;
+                    write_u32(&mut self.writer, FILE_FORMAT_VERSION)?;
+                    self.started = true;
+                }
+                message.encode_binary(&mut self.writer, ctx)
+            }
+            RecordFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &message.to_json())
+                    .map_err(Error::encoding)?;
+                self.writer.write_all(b"\n")?;
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reader of the messages in a recording written by a [`MessageRecorder`], in either
+/// format. The format is detected from the start of the recording.
+pub struct RecordingReader<R> {
+    reader: R,
+    format: Option<RecordFormat>,
+    line: String,
+}
+
+impl RecordingReader<BufReader<File>> {
+    /// Open a recording file.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> RecordingReader<R> {
+    /// Create a reader of the recording in `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            format: None,
+            line: String::new(),
+        }
+    }
+
+    /// Read the next message, or `None` at the end of the recording.
+    pub fn read_message(&mut self, ctx: &Context<'_>) -> EncodingResult<Option<RecordedMessage>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                let format = if self.reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+                    self.reader.consume(BINARY_MAGIC.len());
+                    let version = read_u32(&mut self.reader)?;
+                    if version != FILE_FORMAT_VERSION {
+                        return Err(Error::decoding(format!(
+                            "Unsupported recording version {version}"
+                        )));
+                    }
+                    RecordFormat::Binary
+                } else {
+                    RecordFormat::JsonLines
+                };
+                self.format = Some(format);
+                // An empty binary recording.
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                format
+            }
+        };
+        match format {
+            RecordFormat::Binary => RecordedMessage::decode_binary(&mut self.reader, ctx).map(Some),
+            RecordFormat::JsonLines => loop {
+                self.line.clear();
+                if self.reader.read_line(&mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                if self.line.trim().is_empty() {
+                    continue;
+                }
+                let record: JsonRecord =
+                    serde_json::from_str(&self.line).map_err(Error::decoding)?;
+                return RecordedMessage::from_json(record).map(Some);
+            },
+        }
+    }
+
+    /// Read all remaining messages.
+    pub fn read_all(&mut self, ctx: &Context<'_>) -> EncodingResult<Vec<RecordedMessage>> {
+        let mut messages = Vec::new();
+        while let Some(message) = self.read_message(ctx)? {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_core::comms::interceptor::MessageDirection;
+    use opcua_crypto::SecurityPolicy;
+    use opcua_types::{
+        ContextOwned, DateTime, MessageInfo, MessageSecurityMode, NodeId, ReadRequest, ReadValueId,
+        RequestHeader, TimestampsToReturn,
+    };
+
+    use super::{RecordFormat, RecordWriter, RecordedMessage, RecordingReader};
+    use opcua_core::RequestMessage;
+    use opcua_types::BinaryEncodable;
+
+    fn read_request() -> RequestMessage {
+        ReadRequest {
+            request_header: RequestHeader {
+                request_handle: 5,
+                ..Default::default()
+            },
+            max_age: 0.0,
+            timestamps_to_return: TimestampsToReturn::Both,
+            nodes_to_read: Some(vec![ReadValueId::new_value(NodeId::new(2, "Var"))]),
+        }
+        .into()
+    }
+
+    fn recorded(request: &RequestMessage, payload: bool) -> RecordedMessage {
+        let ctx_owned = ContextOwned::default();
+        let RequestMessage::Read(read) = request else {
+            unreachable!()
+        };
+        RecordedMessage {
+            timestamp: DateTime::now(),
+            direction: MessageDirection::Outgoing,
+            service: "Read".to_owned(),
+            request_handle: 5,
+            request_id: 3,
+            secure_channel_id: 1,
+            security_policy: SecurityPolicy::Basic256Sha256,
+            security_mode: MessageSecurityMode::SignAndEncrypt,
+            type_id: read.type_id().into(),
+            payload: payload.then(|| request.encode_to_vec(&ctx_owned.context())),
+        }
+    }
+
+    #[test]
+    fn round_trip_formats() {
+        let ctx_owned = ContextOwned::default();
+        let ctx = ctx_owned.context();
+        let request = read_request();
+        let messages = vec![recorded(&request, true), recorded(&request, false)];
+
+        for format in [RecordFormat::Binary, RecordFormat::JsonLines] {
+            let mut buf = Vec::new();
+            let mut writer = RecordWriter::new(&mut buf, format);
+            for message in &messages {
+                writer.write(message, &ctx).unwrap();
+            }
+            writer.flush().unwrap();
+
+            let read = RecordingReader::new(buf.as_slice()).read_all(&ctx).unwrap();
+            assert_eq!(read.len(), 2);
+            // Timestamps are written with millisecond precision in JSON.
+            assert_eq!(
+                read[0].timestamp.to_rfc3339(),
+                messages[0].timestamp.to_rfc3339()
+            );
+            for (read, message) in read.iter().zip(&messages) {
+                assert_eq!(read.service, message.service);
+                assert_eq!(read.security_policy, message.security_policy);
+                assert_eq!(read.security_mode, message.security_mode);
+                assert_eq!(read.type_id, message.type_id);
+                assert_eq!(read.payload, message.payload);
+            }
+            assert_eq!(read[0].request(&ctx).unwrap(), Some(request.clone()));
+            assert_eq!(read[1].request(&ctx).unwrap(), None);
+            assert_eq!(read[0].response(&ctx).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn empty_recording() {
+        let ctx_owned = ContextOwned::default();
+        let ctx = ctx_owned.context();
+        assert!(RecordingReader::new(&[][..])
+            .read_all(&ctx)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+
+use opcua_core::{
+    comms::interceptor::{InterceptedMessage, MessageInterceptor},
+    RequestMessage, ResponseMessage,
+};
+use opcua_types::{
+    BinaryEncodable, ByteString, ContextOwned, DateTime, ExtensionObject, NodeId, SignatureData,
+    StatusCode,
+};
+use tracing::error;
+
+use super::{RecordFormat, RecordWriter, RecordedMessage, Redaction};
+
+enum Command {
+    Record(Box<RecordedMessage>),
+    Flush(mpsc::Sender<std::io::Result<()>>),
+}
+
+/// A [`MessageInterceptor`] writing every request and response passing through the
+/// client to a recording, see the [module documentation](super).
+///
+/// Messages are encoded when they are intercepted, and written to the recording by a
+/// background thread, which flushes it when the recorder is dropped. Add the recorder
+/// wrapped in an `Arc` to keep a reference to it, for example to call
+/// [`MessageRecorder::flush`].
+pub struct MessageRecorder {
+    sender: mpsc::Sender<Command>,
+    redaction: Redaction,
+    context: ContextOwned,
+}
+
+impl MessageRecorder {
+    /// Create a recorder writing to a new file at `path`, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>, format: RecordFormat) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+
+    /// Create a recorder writing to `writer`.
+    pub fn new(writer: impl Write + Send + 'static, format: RecordFormat) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // Only used for the metadata, the messages are already encoded.
+            let context = ContextOwned::default();
+            let mut writer = RecordWriter::new(writer, format);
+            let mut failed = false;
+            for command in receiver {
+                match command {
+                    Command::Record(message) if !failed => {
+                        if let Err(e) = writer.write(&message, &context.context()) {
+                            error!("Failed to write message to recording: {e}");
+                            failed = true;
+                        }
+                    }
+                    Command::Record(_) => (),
+                    Command::Flush(reply) => {
+                        let res = if failed {
+                            Err(std::io::Error::other("Writing the recording failed"))
+                        } else {
+                            writer.flush()
+                        };
+                        let _ = reply.send(res);
+                    }
+                }
+            }
+            if let Err(e) = writer.flush() {
+                error!("Failed to flush recording: {e}");
+            }
+        });
+        Self {
+            sender,
+            redaction: Redaction::default(),
+            context: ContextOwned::default(),
+        }
+    }
+
+    /// Set how much of each message is recorded. The default is
+    /// [`Redaction::Credentials`].
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Use a custom encoding context, for example one with type loaders for custom
+    /// types in requests.
+    pub fn with_context(mut self, context: ContextOwned) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Wait until all messages recorded so far are written and flushed.
+    pub fn flush(&self) -> std::io::Result<()> {
+        let (reply, result) = mpsc::channel();
+        self.sender
+            .send(Command::Flush(reply))
+            .map_err(|_| std::io::Error::other("Recording thread stopped"))?;
+        result
+            .recv()
+            .map_err(|_| std::io::Error::other("Recording thread stopped"))?
+    }
+
+    fn record(&self, info: &InterceptedMessage, payload: Option<Vec<u8>>) {
+        let message = RecordedMessage {
+            timestamp: DateTime::now(),
+            direction: info.direction,
+            service: info.service.to_owned(),
+            request_handle: info.request_handle,
+            request_id: info.request_id,
+            secure_channel_id: info.secure_channel_id,
+            security_policy: info.security_policy,
+            security_mode: info.security_mode,
+            type_id: info.type_id.clone(),
+            payload,
+        };
+        let _ = self.sender.send(Command::Record(Box::new(message)));
+    }
+
+    fn encode(&self, message: &impl BinaryEncodable) -> Option<Vec<u8>> {
+        Some(message.encode_to_vec(&self.context.context()))
+    }
+}
+
+impl MessageInterceptor for MessageRecorder {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        let payload = match self.redaction {
+            Redaction::None => self.encode(request),
+            Redaction::Credentials => self.encode(&redact_request(request)),
+            Redaction::Payloads => None,
+        };
+        self.record(info, payload);
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        info: &InterceptedMessage,
+        response: &ResponseMessage,
+    ) -> Result<(), StatusCode> {
+        let payload = match self.redaction {
+            Redaction::None => self.encode(response),
+            Redaction::Credentials => match redact_response(response) {
+                Some(redacted) => self.encode(&redacted),
+                None => self.encode(response),
+            },
+            Redaction::Payloads => None,
+        };
+        self.record(info, payload);
+        Ok(())
+    }
+}
+
+/// A copy of `request` without credentials or the authentication token of the session.
+fn redact_request(request: &RequestMessage) -> RequestMessage {
+    let mut request = request.clone();
+    request.request_header_mut().authentication_token = NodeId::null();
+    match &mut request {
+        RequestMessage::OpenSecureChannel(r) => {
+            r.client_nonce = ByteString::null();
+        }
+        RequestMessage::CreateSession(r) => {
+            r.client_nonce = ByteString::null();
+            r.client_certificate = ByteString::null();
+        }
+        RequestMessage::ActivateSession(r) => {
+            r.client_signature = SignatureData::default();
+            r.user_identity_token = ExtensionObject::null();
+            r.user_token_signature = SignatureData::default();
+        }
+        _ => (),
+    }
+    request
+}
+
+/// A copy of `response` without credentials, or `None` if it has none.
+fn redact_response(response: &ResponseMessage) -> Option<ResponseMessage> {
+    if !matches!(
+        response,
+        ResponseMessage::OpenSecureChannel(_)
+            | ResponseMessage::CreateSession(_)
+            | ResponseMessage::ActivateSession(_)
+    ) {
+        return None;
+    }
+    let mut response = response.clone();
+    match &mut response {
+        ResponseMessage::OpenSecureChannel(r) => {
+            r.server_nonce = ByteString::null();
+        }
+        ResponseMessage::CreateSession(r) => {
+            r.server_nonce = ByteString::null();
+            r.server_certificate = ByteString::null();
+            r.server_signature = SignatureData::default();
+            r.authentication_token = NodeId::null();
+        }
+        ResponseMessage::ActivateSession(r) => {
+            r.server_nonce = ByteString::null();
+        }
+        _ => (),
+    }
+    Some(response)
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use opcua_core::{comms::interceptor::MessageDirection, RequestMessage, ResponseMessage};
+use opcua_types::{ContextOwned, DateTime, StatusCode};
+use tracing::{debug, warn};
+
+use crate::Session;
+
+use super::RecordedMessage;
+
+/// A request sent again by a [`Replayer`].
+#[derive(Debug, Clone)]
+pub struct ReplayedRequest {
+    /// The request as it was recorded.
+    pub recorded: RecordedMessage,
+    /// The request as it was sent, with the request header of the replaying session.
+    pub request: RequestMessage,
+    /// The recorded response to the request, if the recording has one.
+    pub recorded_response: Option<ResponseMessage>,
+    /// The response from the server, or the status the request failed with.
+    pub response: Result<ResponseMessage, StatusCode>,
+}
+
+/// Sends the requests of a recording again on a session, for example to reproduce
+/// an issue against a test server.
+///
+/// Requests opening and closing secure channels and sessions are skipped, since the
+/// replaying session manages its own, as are publish requests, which are sent by the
+/// session event loop, and requests with redacted payloads. Every other request gets
+/// the request header of the replaying session, and is otherwise sent as recorded, so
+/// IDs assigned by the original server, such as subscription IDs, are not translated.
+#[derive(Clone, Default)]
+pub struct Replayer {
+    context: ContextOwned,
+    preserve_timing: bool,
+}
+
+impl Replayer {
+    /// Create a replayer sending requests one after another, without delay.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a custom encoding context, for example one with type loaders for custom
+    /// types in the recorded requests.
+    pub fn with_context(mut self, context: ContextOwned) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Wait between requests for as long as passed between them in the recording.
+    pub fn preserve_timing(mut self, preserve_timing: bool) -> Self {
+        self.preserve_timing = preserve_timing;
+        self
+    }
+
+    /// Send the recorded requests in `messages` on `session`, in order. Each request is
+    /// sent when the response to the previous one is received.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to send the requests on.
+    /// * `messages` - Messages read from a recording with a [`RecordingReader`](super::RecordingReader).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ReplayedRequest>)` - The requests that were sent, with their responses.
+    /// * `Err(StatusCode)` - A recorded message could not be decoded.
+    ///
+    pub async fn replay(
+        &self,
+        session: &Session,
+        messages: &[RecordedMessage],
+    ) -> Result<Vec<ReplayedRequest>, StatusCode> {
+        let ctx = self.context.context();
+        let responses = messages
+            .iter()
+            .filter(|m| m.direction == MessageDirection::Incoming)
+            .map(|m| ((m.secure_channel_id, m.request_id), m))
+            .collect::<HashMap<_, _>>();
+
+        let mut replayed = Vec::new();
+        let mut last_timestamp: Option<DateTime> = None;
+        for recorded in messages {
+            if recorded.direction != MessageDirection::Outgoing {
+                continue;
+            }
+            let request = recorded.request(&ctx).map_err(|e| {
+                warn!(
+                    "Failed to decode recorded {} request: {e}",
+                    recorded.service
+                );
+                e.status()
+            })?;
+            let Some(mut request) = request.filter(is_replayable) else {
+                debug!("Skipping recorded {} request", recorded.service);
+                continue;
+            };
+            let recorded_response = responses
+                .get(&(recorded.secure_channel_id, recorded.request_id))
+                .map(|m| m.response(&ctx))
+                .transpose()
+                .map_err(|e| {
+                    warn!(
+                        "Failed to decode recorded {} response: {e}",
+                        recorded.service
+                    );
+                    e.status()
+                })?
+                .flatten();
+
+            if self.preserve_timing {
+                if let Some(delay) =
+                    last_timestamp.and_then(|last| (recorded.timestamp - last).to_std().ok())
+                {
+                    tokio::time::sleep(delay).await;
+                }
+                last_timestamp = Some(recorded.timestamp);
+            }
+
+            let header = request.request_header_mut();
+            let mut new_header = session
+                .channel()
+                .make_request_header(session.request_timeout());
+            new_header.return_diagnostics = header.return_diagnostics;
+            new_header.audit_entry_id = header.audit_entry_id.clone();
+            *header = new_header;
+
+            let response = session
+                .channel()
+                .send(request.clone(), session.request_timeout())
+                .await;
+            replayed.push(ReplayedRequest {
+                recorded: recorded.clone(),
+                request,
+                recorded_response,
+                response,
+            });
+        }
+        Ok(replayed)
+    }
+}
+
+fn is_replayable(request: &RequestMessage) -> bool {
+    !matches!(
+        request,
+        RequestMessage::OpenSecureChannel(_)
+            | RequestMessage::CloseSecureChannel(_)
+            | RequestMessage::CreateSession(_)
+            | RequestMessage::ActivateSession(_)
+            | RequestMessage::CloseSession(_)
+            | RequestMessage::Cancel(_)
+            | RequestMessage::Publish(_)
+            | RequestMessage::Republish(_)
+    )
+}
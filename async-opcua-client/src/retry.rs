@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::{fmt, sync::Arc, time::Duration};
+
+use opcua_types::{StatusCode, UAString};
 
 #[derive(Debug, Clone)]
 /// A type implementing [`Iterator<Item = Option<Duration>`] with simple exponential backoff.
@@ -101,11 +103,187 @@ impl SessionRetryPolicy {
     }
 }
 
+impl ReconnectStrategy for SessionRetryPolicy {
+    fn next_attempt(&self, attempt: u32, _error: StatusCode) -> ReconnectDecision {
+        if self.reconnect_retry_limit.is_some_and(|max| attempt > max) {
+            return ReconnectDecision::GiveUp;
+        }
+        ReconnectDecision::Retry(exponential_delay(
+            self.reconnect_initial_sleep,
+            self.reconnect_max_sleep,
+            attempt,
+        ))
+    }
+}
+
+/// The delay before retry number `attempt`, starting at 1, doubling from `initial` up to `max`.
+fn exponential_delay(initial: Duration, max: Duration, attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    initial.saturating_mul(factor).min(max)
+}
+
+/// What the session should do after a failed attempt to connect to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectDecision {
+    /// Try to connect again after the given delay.
+    Retry(Duration),
+    /// Call `GetEndpoints` on the server to refresh the endpoint, for example to pick up
+    /// a new server certificate, then try to connect again after the given delay.
+    RediscoverAndRetry(Duration),
+    /// Stop trying to connect. The session event loop ends with the last error.
+    GiveUp,
+}
+
+/// Events reported to [`ReconnectStrategy::on_event`] while the session is connecting.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ReconnectEvent {
+    /// An attempt to connect failed, and the strategy decided what to do next.
+    AttemptFailed {
+        /// The number of failed attempts since the session started connecting.
+        attempt: u32,
+        /// The reason the attempt failed.
+        error: StatusCode,
+        /// What the session will do next.
+        decision: ReconnectDecision,
+    },
+    /// The endpoint was refreshed by calling `GetEndpoints` on the server.
+    Rediscovered {
+        /// The URL of the new endpoint.
+        endpoint_url: UAString,
+    },
+    /// Refreshing the endpoint failed, the session keeps using the old endpoint.
+    RediscoveryFailed(StatusCode),
+    /// The session connected to the server after `attempts` failed attempts.
+    Connected {
+        /// The number of failed attempts before the session connected.
+        attempts: u32,
+    },
+}
+
+/// Strategy controlling how a session reconnects to the server after losing the connection,
+/// and how it makes its first connection.
+///
+/// The default strategy is the [`SessionRetryPolicy`] from the client config. Set a
+/// different strategy, for example [`ExponentialReconnect`], with
+/// [`SessionBuilder::reconnect_strategy`](crate::SessionBuilder::reconnect_strategy).
+pub trait ReconnectStrategy: Send + Sync {
+    /// Decide what to do after a failed attempt to connect. `attempt` is the number of
+    /// failed attempts since the session started connecting, starting at 1.
+    fn next_attempt(&self, attempt: u32, error: StatusCode) -> ReconnectDecision;
+
+    /// Called with events while the session is connecting. The default implementation
+    /// does nothing.
+    fn on_event(&self, event: &ReconnectEvent) {
+        let _ = event;
+    }
+}
+
+type ReconnectEventCallback = dyn Fn(&ReconnectEvent) + Send + Sync;
+
+/// Reconnect strategy with exponential backoff and random jitter.
+///
+/// The delay starts at the initial delay, doubles after each failed attempt up to the maximum
+/// delay, and is then shortened by a random fraction given by the jitter, so that many clients
+/// losing the connection at once do not all reconnect at the same time.
+#[derive(Clone)]
+pub struct ExponentialReconnect {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+    jitter: f64,
+    rediscover_after: Option<u32>,
+    on_event: Option<Arc<ReconnectEventCallback>>,
+}
+
+impl ExponentialReconnect {
+    /// Create a new strategy retrying forever, with delays starting at `initial_delay`
+    /// and doubling up to `max_delay`, and a jitter of `0.2`.
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            max_attempts: None,
+            jitter: 0.2,
+            rediscover_after: None,
+            on_event: None,
+        }
+    }
+
+    /// Give up after `max_attempts` failed attempts. `None` retries forever.
+    pub fn max_attempts(mut self, max_attempts: Option<u32>) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the fraction of each delay that is random, between `0.0` and `1.0`.
+    /// With a jitter of `0.2`, each delay is between 80% and 100% of the backoff delay.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Refresh the endpoint with `GetEndpoints` after every `attempts` failed attempts.
+    /// `None`, the default, never refreshes the endpoint.
+    pub fn rediscover_after(mut self, attempts: Option<u32>) -> Self {
+        self.rediscover_after = attempts.filter(|a| *a > 0);
+        self
+    }
+
+    /// Set a callback called with every [`ReconnectEvent`].
+    pub fn on_event(mut self, callback: impl Fn(&ReconnectEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl fmt::Debug for ExponentialReconnect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExponentialReconnect")
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("max_attempts", &self.max_attempts)
+            .field("jitter", &self.jitter)
+            .field("rediscover_after", &self.rediscover_after)
+            .finish()
+    }
+}
+
+impl ReconnectStrategy for ExponentialReconnect {
+    fn next_attempt(&self, attempt: u32, _error: StatusCode) -> ReconnectDecision {
+        if self.max_attempts.is_some_and(|max| attempt >= max) {
+            return ReconnectDecision::GiveUp;
+        }
+        let delay = exponential_delay(self.initial_delay, self.max_delay, attempt);
+        let mut random = [0u8; 4];
+        opcua_crypto::random::bytes(&mut random);
+        let fraction = u32::from_le_bytes(random) as f64 / u32::MAX as f64;
+        let delay = delay.mul_f64(1.0 - self.jitter * fraction);
+
+        if self
+            .rediscover_after
+            .is_some_and(|n| attempt.is_multiple_of(n))
+        {
+            ReconnectDecision::RediscoverAndRetry(delay)
+        } else {
+            ReconnectDecision::Retry(delay)
+        }
+    }
+
+    fn on_event(&self, event: &ReconnectEvent) {
+        if let Some(cb) = &self.on_event {
+            cb(event);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
-    use super::SessionRetryPolicy;
+    use opcua_types::StatusCode;
+
+    use super::{ExponentialReconnect, ReconnectDecision, ReconnectStrategy, SessionRetryPolicy};
 
     #[test]
     fn session_retry() {
@@ -147,4 +325,63 @@ mod tests {
         let mut backoff = policy.new_backoff();
         assert!(backoff.next().is_none());
     }
+
+    #[test]
+    fn session_retry_strategy() {
+        let policy = SessionRetryPolicy::new(
+            Duration::from_millis(3000),
+            Some(3),
+            Duration::from_millis(500),
+        );
+        let e = StatusCode::BadCommunicationError;
+        assert_eq!(
+            policy.next_attempt(1, e),
+            ReconnectDecision::Retry(Duration::from_millis(500))
+        );
+        assert_eq!(
+            policy.next_attempt(2, e),
+            ReconnectDecision::Retry(Duration::from_millis(1000))
+        );
+        assert_eq!(
+            policy.next_attempt(3, e),
+            ReconnectDecision::Retry(Duration::from_millis(2000))
+        );
+        assert_eq!(policy.next_attempt(4, e), ReconnectDecision::GiveUp);
+        assert_eq!(
+            SessionRetryPolicy::never().next_attempt(1, e),
+            ReconnectDecision::GiveUp
+        );
+    }
+
+    #[test]
+    fn exponential_reconnect() {
+        let strategy = ExponentialReconnect::new(Duration::from_secs(1), Duration::from_secs(8))
+            .max_attempts(Some(10))
+            .rediscover_after(Some(3));
+        let e = StatusCode::BadCommunicationError;
+        for attempt in 1..10 {
+            let expected = Duration::from_secs(1 << (attempt - 1).min(3));
+            let delay = match strategy.next_attempt(attempt, e) {
+                ReconnectDecision::Retry(d) => {
+                    assert_ne!(attempt % 3, 0);
+                    d
+                }
+                ReconnectDecision::RediscoverAndRetry(d) => {
+                    assert_eq!(attempt % 3, 0);
+                    d
+                }
+                ReconnectDecision::GiveUp => panic!("Gave up after {attempt} attempts"),
+            };
+            assert!(delay <= expected);
+            assert!(delay >= expected.mul_f64(0.8));
+        }
+        assert_eq!(strategy.next_attempt(10, e), ReconnectDecision::GiveUp);
+
+        let strategy =
+            ExponentialReconnect::new(Duration::from_secs(1), Duration::from_secs(8)).jitter(0.0);
+        assert_eq!(
+            strategy.next_attempt(40, e),
+            ReconnectDecision::Retry(Duration::from_secs(8))
+        );
+    }
 }
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use opcua_types::{NodeId, ReadValueId, StatusCode, TimestampsToReturn, Variant, WriteValue};
+
+use super::{session_debug, RequestRetryPolicy, Session};
+
+/// The result of verifying a written value by reading it back, see
+/// [`Session::write_all_verified`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteVerification {
+    /// The value was not read back, because verification was disabled or the write failed.
+    NotVerified,
+    /// The value read back is equal to the written value.
+    Verified,
+    /// The value read back differs from the written value.
+    Mismatch(Option<Variant>),
+    /// Reading the value back failed with the given status.
+    ReadFailed(StatusCode),
+}
+
+/// The result of writing a single node with [`Session::write_all_verified`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteNodeResult {
+    /// The node that was written.
+    pub node_id: NodeId,
+    /// The attribute that was written.
+    pub attribute_id: u32,
+    /// The status of the last write attempt.
+    pub status: StatusCode,
+    /// The number of times the value was written.
+    pub attempts: u32,
+    /// The result of reading the value back.
+    pub verification: WriteVerification,
+}
+
+impl WriteNodeResult {
+    /// Whether the write succeeded, and the value read back matches if it was verified.
+    pub fn is_success(&self) -> bool {
+        self.status.is_good()
+            && matches!(
+                self.verification,
+                WriteVerification::NotVerified | WriteVerification::Verified
+            )
+    }
+}
+
+/// Report of a [`Session::write_all_verified`] call, with one result per written node
+/// in the order of the written values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WriteReport {
+    /// The result for each written node.
+    pub results: Vec<WriteNodeResult>,
+}
+
+impl WriteReport {
+    /// Whether all writes succeeded, see [`WriteNodeResult::is_success`].
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.is_success())
+    }
+
+    /// Get the results of the nodes that were not written successfully.
+    pub fn failed(&self) -> impl Iterator<Item = &WriteNodeResult> {
+        self.results.iter().filter(|r| !r.is_success())
+    }
+}
+
+impl Session {
+    /// Write values to nodes, retrying the nodes that fail with a transient status, and
+    /// optionally reading the values back to verify that they were written.
+    ///
+    /// Like [`Session::write`], the values are split into several requests if the server
+    /// has a limit on the number of nodes per write. Each node is retried according to
+    /// its own copy of `policy`, which is also asked about failures of an entire request.
+    /// Each round of retries waits for the longest delay of the retried nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes_to_write` - A list of [`WriteValue`] to be sent to the server.
+    /// * `policy` - The retry policy, called with the status of each failed write.
+    /// * `verify` - Read back the values that were written, and compare them to the
+    ///   written values. Servers may convert values to the data type of the node, so only
+    ///   enable this if the values have the exact data type of the nodes.
+    ///
+    /// # Returns
+    ///
+    /// * `WriteReport` - The result of each write, in the same order as `nodes_to_write`.
+    ///
+    pub async fn write_all_verified(
+        &self,
+        nodes_to_write: &[WriteValue],
+        policy: impl RequestRetryPolicy + Clone,
+        verify: bool,
+    ) -> WriteReport {
+        let mut results: Vec<_> = nodes_to_write
+            .iter()
+            .map(|v| WriteNodeResult {
+                node_id: v.node_id.clone(),
+                attribute_id: v.attribute_id,
+                status: StatusCode::BadUnexpectedError,
+                attempts: 0,
+                verification: WriteVerification::NotVerified,
+            })
+            .collect();
+        let mut pending: Vec<_> = (0..nodes_to_write.len())
+            .map(|idx| (idx, policy.clone()))
+            .collect();
+
+        while !pending.is_empty() {
+            let values: Vec<_> = pending
+                .iter()
+                .map(|(idx, _)| nodes_to_write[*idx].clone())
+                .collect();
+            let statuses = match self.write(&values).await {
+                Ok(statuses) if statuses.len() == values.len() => statuses,
+                Ok(_) => vec![StatusCode::BadUnexpectedError; values.len()],
+                Err(e) => vec![e; values.len()],
+            };
+
+            let mut retry = Vec::new();
+            let mut delay = Duration::ZERO;
+            for ((idx, mut policy), status) in pending.into_iter().zip(statuses) {
+                let result = &mut results[idx];
+                result.status = status;
+                result.attempts += 1;
+                if status.is_good() {
+                    continue;
+                }
+                if let Some(next_delay) = policy.get_next_delay(status) {
+                    delay = delay.max(next_delay);
+                    retry.push((idx, policy));
+                }
+            }
+
+            if !retry.is_empty() {
+                session_debug!(
+                    self,
+                    "Retrying write of {} nodes after {:?}",
+                    retry.len(),
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            pending = retry;
+        }
+
+        if verify {
+            self.verify_written(nodes_to_write, &mut results).await;
+        }
+
+        WriteReport { results }
+    }
+
+    async fn verify_written(&self, nodes_to_write: &[WriteValue], results: &mut [WriteNodeResult]) {
+        let written: Vec<_> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.status.is_good())
+            .map(|(idx, _)| idx)
+            .collect();
+        if written.is_empty() {
+            return;
+        }
+        let to_read: Vec<_> = written
+            .iter()
+            .map(|idx| {
+                let v = &nodes_to_write[*idx];
+                ReadValueId {
+                    node_id: v.node_id.clone(),
+                    attribute_id: v.attribute_id,
+                    index_range: v.index_range.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let values = match self.read(&to_read, TimestampsToReturn::Neither, 0.0).await {
+            Ok(values) if values.len() == to_read.len() => values,
+            r => {
+                let status = r.err().unwrap_or(StatusCode::BadUnexpectedError);
+                for idx in written {
+                    results[idx].verification = WriteVerification::ReadFailed(status);
+                }
+                return;
+            }
+        };
+
+        for (idx, value) in written.into_iter().zip(values) {
+            let status = value.status();
+            results[idx].verification = if status.is_bad() {
+                WriteVerification::ReadFailed(status)
+            } else if value.value == nodes_to_write[idx].value.value {
+                WriteVerification::Verified
+            } else {
+                WriteVerification::Mismatch(value.value)
+            };
+        }
+    }
+}
@@ -2,16 +2,20 @@ use std::{str::FromStr, sync::Arc};
 
 use chrono::Duration;
 use tokio::{pin, select};
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::{
-    transport::{tcp::TransportConfiguration, Connector, ConnectorBuilder, TransportPollResult},
+    discovery::dedup_servers_on_network,
+    transport::{
+        tcp::TransportConfiguration, Connector, ConnectorBuilder, RequestLimiter,
+        TransportPollResult,
+    },
     AsyncSecureChannel, ClientConfig, ClientEndpoint, IdentityToken,
 };
 use opcua_core::{
     comms::url::{
-        hostname_from_url, server_url_from_endpoint_url, url_matches_except_host,
-        url_with_replaced_hostname,
+        hostname_from_url, is_opc_ua_https_url, server_url_from_endpoint_url,
+        url_matches_except_host, url_with_replaced_hostname,
     },
     config::Config,
     sync::RwLock,
@@ -20,9 +24,9 @@ use opcua_core::{
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{
     ApplicationDescription, ContextOwned, DecodingOptions, EndpointDescription, Error,
-    FindServersOnNetworkRequest, FindServersOnNetworkResponse, FindServersRequest,
+    ErrorContext, FindServersOnNetworkRequest, FindServersOnNetworkResponse, FindServersRequest,
     GetEndpointsRequest, MessageSecurityMode, NamespaceMap, RegisterServerRequest,
-    RegisteredServer, StatusCode, UAString,
+    RegisteredServer, ServerOnNetwork, StatusCode, UAString,
 };
 
 use super::{
@@ -141,6 +145,38 @@ impl Client {
             .build(self.certificate_store.clone())
     }
 
+    /// Connects to the server at `url`, using the endpoint picked among the endpoints of
+    /// the server by the endpoint selector of the client, see
+    /// [`ClientBuilder::endpoint_selector`](crate::ClientBuilder::endpoint_selector).
+    /// By default this is the endpoint with the highest security level that supports
+    /// `user_identity_token`.
+    ///
+    /// This function returns both a reference to the session, and a `SessionEventLoop`. You must run and
+    /// poll the event loop in order to actually establish a connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - Discovery URL of the server, the client will first connect to this in order to get a
+    ///   list of the available endpoints on the server.
+    /// * `user_identity_token` - Identity token to use for authentication.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((Arc<Session>, SessionEventLoop))` - Session and event loop.
+    /// * `Err(Error)` - Getting the endpoints failed, or the selector rejected all of them.
+    ///
+    pub async fn connect_to_selected_endpoint(
+        &mut self,
+        url: &str,
+        user_identity_token: IdentityToken,
+    ) -> Result<(Arc<Session>, SessionEventLoop), Error> {
+        self.session_builder()
+            .with_endpoints(self.get_server_endpoints_from_url(url).await?)
+            .user_identity_token(user_identity_token)
+            .connect_to_selected_endpoint(url)?
+            .build(self.certificate_store.clone())
+    }
+
     /// Connects to a server directly using provided [`EndpointDescription`].
     ///
     /// This function returns both a reference to the session, and a `SessionEventLoop`. You must run and
@@ -170,6 +206,61 @@ impl Client {
             .build(self.certificate_store.clone())
     }
 
+    /// Connects to the server at `url` without calling `GetEndpoints`, for servers whose
+    /// discovery endpoint is broken or unreachable. The endpoint is constructed locally,
+    /// and completed with the user token policies the server returns when the session
+    /// is created.
+    ///
+    /// The security mode is `None` for [`SecurityPolicy::None`], and `SignAndEncrypt`
+    /// otherwise. Securing an `opc.tcp` channel needs the server certificate, which is
+    /// only known from `GetEndpoints`, so other security policies are only supported
+    /// for `opc.https` URLs. Use [`Client::connect_to_endpoint_directly`] with an endpoint
+    /// including the server certificate otherwise.
+    ///
+    /// This function returns both a reference to the session, and a `SessionEventLoop`. You must run and
+    /// poll the event loop in order to actually establish a connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the endpoint to connect to.
+    /// * `security_policy` - Security policy of the endpoint.
+    /// * `identity_token` - Identity token for authentication.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((Arc<Session>, SessionEventLoop))` - Session and event loop.
+    /// * `Err(Error)` - The URL is invalid, or the security policy is not supported.
+    ///
+    pub fn connect_simple(
+        &mut self,
+        url: &str,
+        security_policy: SecurityPolicy,
+        identity_token: IdentityToken,
+    ) -> Result<(Arc<Session>, SessionEventLoop), Error> {
+        let security_mode = match security_policy {
+            SecurityPolicy::Unknown => {
+                return Err(Error::new(
+                    StatusCode::BadSecurityPolicyRejected,
+                    "Cannot connect with an unknown security policy",
+                ))
+            }
+            SecurityPolicy::None => MessageSecurityMode::None,
+            _ if !is_opc_ua_https_url(url) => {
+                return Err(Error::new(
+                    StatusCode::BadSecurityPolicyRejected,
+                    format!(
+                        "Security policy {security_policy} needs the server certificate to connect to {url}"
+                    ),
+                ))
+            }
+            _ => MessageSecurityMode::SignAndEncrypt,
+        };
+        self.connect_to_endpoint_directly(
+            (url, security_policy.to_uri(), security_mode),
+            identity_token,
+        )
+    }
+
     /// Creates a new [`Session`] using the default endpoint specified in the config. If
     /// there is no default, or the endpoint does not exist, this function will return an error
     ///
@@ -193,7 +284,8 @@ impl Client {
     ) -> Result<(Arc<Session>, SessionEventLoop), Error> {
         self.session_builder()
             .with_endpoints(self.get_server_endpoints().await?)
-            .connect_to_default_endpoint()?
+            .connect_to_default_endpoint()
+            .context("selecting default endpoint")?
             .build(self.certificate_store.clone())
     }
 
@@ -201,10 +293,9 @@ impl Client {
     ///
     /// This is used when creating temporary connections to the server, when creating a session,
     /// [`Session`] manages its own channel.
-    fn channel_from_endpoint_info(
+    pub(super) fn channel_from_endpoint_info(
         &self,
         endpoint_info: EndpointInfo,
-        channel_lifetime: u32,
         connector: Box<dyn Connector + Send + Sync>,
     ) -> AsyncSecureChannel {
         AsyncSecureChannel::new(
@@ -218,9 +309,25 @@ impl Client {
                 recv_buffer_size: self.config.decoding_options.max_incoming_chunk_size,
                 max_message_size: self.config.decoding_options.max_message_size,
                 max_chunk_count: self.config.decoding_options.max_chunk_count,
+                max_send_message_size: self.config.decoding_options.max_send_message_size(),
+                max_send_chunk_count: self.config.decoding_options.max_send_chunk_count(),
+                socket_options: self.config.socket_options.clone(),
+                hello_timeout: self.config.hello_timeout,
+                open_secure_channel_timeout: self.config.open_secure_channel_timeout,
+                parallel_crypto_threshold: self.config.performance.parallel_crypto_chunk_threshold,
+                metrics: self.config.transport_metrics.clone(),
+                interceptors: self.config.interceptors.clone(),
+                https: self.config.https.clone(),
+                #[cfg(feature = "https")]
+                certificate_store: self.certificate_store.clone(),
+                aborts: Default::default(),
+                request_limiter: Arc::new(RequestLimiter::new(&self.config.request_limits)),
+                service_retry: self.config.service_retry.clone(),
+                #[cfg(feature = "request-metrics")]
+                request_metrics: self.config.request_metrics.clone(),
             },
             connector,
-            channel_lifetime,
+            self.config.token_renewal.clone(),
             // We should only ever need the default decoding context for temporary connections.
             Arc::new(RwLock::new(ContextOwned::new_default(
                 NamespaceMap::new(),
@@ -258,19 +365,34 @@ impl Client {
     pub async fn get_server_endpoints(&self) -> Result<Vec<EndpointDescription>, Error> {
         let default_endpoint = self
             .default_endpoint()
-            .map_err(|e| Error::new(StatusCode::BadConfigurationError, e))?;
-        if let Ok(server_url) = server_url_from_endpoint_url(&default_endpoint.url) {
+            .map_err(|e| Error::new(StatusCode::BadConfigurationError, e))
+            .context("getting server endpoints")?;
+        let mut result = self
+            .get_server_endpoints_from_endpoint_url(&default_endpoint.url)
+            .await;
+        // Try the alternate network paths to the server, if there are any.
+        for url in &default_endpoint.alternate_urls {
+            let Err(e) = &result else {
+                break;
+            };
+            warn!("Failed to get server endpoints ({e}), trying alternate url {url}");
+            result = self.get_server_endpoints_from_endpoint_url(url).await;
+        }
+        result
+    }
+
+    async fn get_server_endpoints_from_endpoint_url(
+        &self,
+        endpoint_url: &str,
+    ) -> Result<Vec<EndpointDescription>, Error> {
+        if let Ok(server_url) = server_url_from_endpoint_url(endpoint_url) {
             self.get_server_endpoints_from_url(server_url).await
         } else {
-            error!(
-                "Cannot create a server url from the specified endpoint url {}",
-                default_endpoint.url
-            );
+            error!("Cannot create a server url from the specified endpoint url {endpoint_url}");
             Err(Error::new(
                 StatusCode::BadUnexpectedError,
                 format!(
-                    "Cannot create a server url from the specified endpoint url {}",
-                    default_endpoint.url
+                    "Cannot create a server url from the specified endpoint url {endpoint_url}"
                 ),
             ))
         }
@@ -361,13 +483,13 @@ impl Client {
             user_identity_token: IdentityToken::Anonymous,
             preferred_locales,
         };
-        let channel =
-            self.channel_from_endpoint_info(endpoint_info, self.config.channel_lifetime, server);
+        let channel = self.channel_from_endpoint_info(endpoint_info, server);
 
         let mut evt_loop = channel
             .connect()
             .await
-            .map_err(|e| Error::new(e, "Failed to connect to server"))?;
+            .map_err(|e| Error::new(e, "Failed to connect to server"))
+            .context_with(|| format!("getting endpoints from {}", endpoint.endpoint_url))?;
 
         let send_fut = self.get_server_endpoints_inner(
             &endpoint,
@@ -389,10 +511,13 @@ impl Client {
             select! {
                 r = evt_loop.poll() => {
                     if let TransportPollResult::Closed(e) = r {
-                        return Err(Error::new(e, "Transport closed unexpectedly"));
+                        return Err(Error::new(e, "Transport closed unexpectedly")
+                            .with_operation(format!("getting endpoints from {}", endpoint.endpoint_url)));
                     }
                 },
-                res = &mut send_fut => break res.map_err(|e| Error::new(e, "Failed to get endpoints")),
+                res = &mut send_fut => break res
+                    .map_err(|e| Error::new(e, "Failed to get endpoints"))
+                    .context_with(|| format!("getting endpoints from {}", endpoint.endpoint_url)),
             }
         };
 
@@ -456,11 +581,7 @@ impl Client {
             user_identity_token: IdentityToken::Anonymous,
             preferred_locales: Vec::new(),
         };
-        let channel = self.channel_from_endpoint_info(
-            session_info,
-            self.config.channel_lifetime,
-            discovery_endpoint,
-        );
+        let channel = self.channel_from_endpoint_info(session_info, discovery_endpoint);
 
         let mut evt_loop = channel.connect().await?;
 
@@ -549,11 +670,7 @@ impl Client {
             user_identity_token: IdentityToken::Anonymous,
             preferred_locales: Vec::new(),
         };
-        let channel = self.channel_from_endpoint_info(
-            session_info,
-            self.config.channel_lifetime,
-            discovery_endpoint,
-        );
+        let channel = self.channel_from_endpoint_info(session_info, discovery_endpoint);
 
         let mut evt_loop = channel.connect().await?;
 
@@ -587,6 +704,118 @@ impl Client {
         res
     }
 
+    async fn find_all_servers_on_network_inner(
+        &self,
+        server_capability_filter: Option<Vec<UAString>>,
+        channel: &AsyncSecureChannel,
+    ) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+        let mut servers = Vec::new();
+        let mut starting_record_id = 0;
+        let mut counter_reset_time = None;
+        loop {
+            let response = self
+                .find_servers_on_network_inner(
+                    starting_record_id,
+                    0,
+                    server_capability_filter.clone(),
+                    channel,
+                )
+                .await?;
+            // If the record IDs were reset while paging, start over.
+            if counter_reset_time.is_some_and(|t| t != response.last_counter_reset_time) {
+                servers.clear();
+                starting_record_id = 0;
+                counter_reset_time = None;
+                continue;
+            }
+            counter_reset_time = Some(response.last_counter_reset_time);
+
+            let page = response.servers.unwrap_or_default();
+            let Some(last_record_id) = page.iter().map(|s| s.record_id).max() else {
+                break;
+            };
+            // Stop if the server ignores the starting record ID.
+            if !servers.is_empty() && last_record_id <= starting_record_id {
+                break;
+            }
+            starting_record_id = last_record_id;
+            servers.extend(page);
+        }
+        Ok(dedup_servers_on_network(servers))
+    }
+
+    /// Connects to a discovery server and gets every server it has found on the network,
+    /// calling `FindServersOnNetwork` repeatedly until all records have been returned.
+    ///
+    /// Records are deduplicated by discovery URL, see [`dedup_servers_on_network`]. Use this
+    /// function to combine the result with servers from other discovery servers, or from
+    /// [`browse_mdns`](crate::discovery::browse_mdns).
+    ///
+    /// See OPC UA Part 4 - Services 5.5.3 for a complete description of the service.
+    ///
+    /// # Arguments
+    ///
+    /// * `discovery_endpoint` - Endpoint of the discovery server, typically an LDS-ME.
+    /// * `server_capability_filter` - List of server capability filters. Only records with
+    ///   all the specified server capabilities are returned.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<ServerOnNetwork>)` - The servers found on the network.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    pub async fn find_all_servers_on_network(
+        &self,
+        discovery_endpoint: impl ConnectorBuilder,
+        server_capability_filter: &[&str],
+    ) -> Result<Vec<ServerOnNetwork>, StatusCode> {
+        let discovery_endpoint = discovery_endpoint.build()?;
+        let endpoint = discovery_endpoint.default_endpoint();
+        let session_info = EndpointInfo {
+            endpoint: endpoint.clone(),
+            user_identity_token: IdentityToken::Anonymous,
+            preferred_locales: Vec::new(),
+        };
+        let channel = self.channel_from_endpoint_info(session_info, discovery_endpoint);
+
+        let mut evt_loop = channel.connect().await?;
+
+        let send_fut = self.find_all_servers_on_network_inner(
+            if server_capability_filter.is_empty() {
+                None
+            } else {
+                Some(
+                    server_capability_filter
+                        .iter()
+                        .map(|c| (*c).into())
+                        .collect(),
+                )
+            },
+            &channel,
+        );
+        pin!(send_fut);
+
+        let res = loop {
+            select! {
+                r = evt_loop.poll() => {
+                    if let TransportPollResult::Closed(e) = r {
+                        return Err(e);
+                    }
+                },
+                res = &mut send_fut => break res
+            }
+        };
+
+        channel.close_channel().await;
+
+        loop {
+            if matches!(evt_loop.poll().await, TransportPollResult::Closed(_)) {
+                break;
+            }
+        }
+
+        res
+    }
+
     /// Find an endpoint supplied from the list of endpoints that matches the input criteria.
     ///
     /// # Arguments
@@ -666,7 +895,9 @@ impl Client {
         }
     }
 
-    /// Get the best endpoint for the server, "best" here is the endpoint with the highest security level.
+    /// Get the best endpoint for the server, picked by the endpoint selector of the client,
+    /// see [`ClientBuilder::endpoint_selector`](crate::ClientBuilder::endpoint_selector).
+    /// By default this is the endpoint with the highest security level.
     ///
     /// # Arguments
     ///
@@ -676,11 +907,11 @@ impl Client {
         discovery_endpoint: impl ConnectorBuilder,
     ) -> Result<EndpointDescription, Error> {
         let discovery_endpoint = discovery_endpoint.build()?;
+        let url = discovery_endpoint.default_endpoint().endpoint_url;
         let endpoints = self
-            .get_server_endpoints_from_url(
-                discovery_endpoint.default_endpoint().endpoint_url.as_ref(),
-            )
-            .await?;
+            .get_server_endpoints_from_url(url.as_ref())
+            .await
+            .context("finding best endpoint")?;
         if endpoints.is_empty() {
             return Err(Error::new(
                 StatusCode::BadUnexpectedError,
@@ -688,15 +919,15 @@ impl Client {
             ));
         }
 
-        let Some(endpoint) = endpoints
-            .into_iter()
-            .filter(|e| self.is_supported_endpoint(e))
-            .max_by(|a, b| a.security_level.cmp(&b.security_level))
+        let Some(endpoint) = self
+            .config
+            .endpoint_selector
+            .select(url.as_ref(), endpoints)
         else {
             error!("Cannot find an endpoint that we can use");
             return Err(Error::new(
                 StatusCode::BadUnexpectedError,
-                "No supported endpoints returned from server",
+                "No supported endpoint was accepted by the endpoint selector",
             ));
         };
 
@@ -735,8 +966,7 @@ impl Client {
             preferred_locales: Vec::new(),
         };
         let connector = connector.build()?;
-        let channel =
-            self.channel_from_endpoint_info(endpoint_info, self.config.channel_lifetime, connector);
+        let channel = self.channel_from_endpoint_info(endpoint_info, connector);
 
         let mut evt_loop = channel.connect().await?;
 
@@ -769,4 +999,11 @@ impl Client {
     pub fn certificate_store(&self) -> &Arc<RwLock<CertificateStore>> {
         &self.certificate_store
     }
+
+    /// Get the collector of per-service metrics of the requests sent by sessions
+    /// and channels created by this client.
+    #[cfg(feature = "request-metrics")]
+    pub fn request_metrics(&self) -> &Arc<crate::metrics::RequestMetrics> {
+        &self.config.request_metrics
+    }
 }
@@ -6,7 +6,7 @@ use tracing::info;
 use crate::transport::{SecureChannelEventLoop, TransportPollResult};
 use opcua_types::{NodeId, StatusCode};
 
-use super::Session;
+use super::{ConnectionEvent, Session};
 
 /// This struct manages the task of connecting to the server.
 /// It will only make a single attempt, so whatever is calling it is responsible for retries.
@@ -88,6 +88,8 @@ impl SessionConnector {
                 self.inner.reset();
                 let id = self.inner.create_session().await?;
                 self.inner.activate_session().await?;
+                self.inner
+                    .emit_connection_event(ConnectionEvent::SessionRecreated);
                 SessionConnectMode::NewSession(id)
             }
             Err(e) => return Err(e),
@@ -101,10 +103,26 @@ impl SessionConnector {
             }
         };
 
-        if self.inner.recreate_subscriptions {
-            self.inner.transfer_subscriptions_from_old_session().await;
+        if self.inner.split_by_operation_limits {
+            self.inner.read_operation_limits().await;
         }
 
+        if self.inner.read_namespaces_on_connect {
+            self.inner.update_namespaces().await;
+        }
+
+        if matches!(reconnect, SessionConnectMode::NewSession(_)) {
+            self.inner.on_new_server_session().await;
+        }
+
+        if self.inner.recreate_subscriptions
+            && self.inner.transfer_subscriptions_from_old_session().await
+        {
+            self.inner
+                .emit_connection_event(ConnectionEvent::SubscriptionsRestored);
+        }
+        self.inner.persist_subscriptions();
+
         Ok(reconnect)
     }
 }
@@ -1,6 +1,10 @@
 use std::{str::FromStr, sync::Arc};
 
-use opcua_core::{comms::url::is_opc_ua_binary_url, config::Config, sync::RwLock};
+use opcua_core::{
+    comms::url::{hostname_from_url, is_valid_opc_ua_url, url_with_replaced_hostname},
+    config::Config,
+    sync::RwLock,
+};
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{
     ContextOwned, EndpointDescription, Error, MessageSecurityMode, NamespaceMap, NodeId,
@@ -8,8 +12,9 @@ use opcua_types::{
 };
 
 use crate::{
-    transport::{tcp::TransportConfiguration, Connector, ConnectorBuilder},
-    AsyncSecureChannel, ClientConfig, IdentityToken,
+    transport::{tcp::TransportConfiguration, Connector, ConnectorBuilder, RequestLimiter},
+    AsyncSecureChannel, ClientConfig, IdentityToken, PublishOptions, ReconnectStrategy,
+    SubscriptionStore, SubscriptionTransferPolicy,
 };
 
 use super::{Client, EndpointInfo, Session, SessionEventLoop};
@@ -18,6 +23,12 @@ struct SessionBuilderInner {
     session_id: Option<NodeId>,
     user_identity_token: IdentityToken,
     type_loaders: Vec<Arc<dyn TypeLoader>>,
+    subscription_transfer_policy: SubscriptionTransferPolicy,
+    subscription_store: Option<Arc<dyn SubscriptionStore>>,
+    reconnect_strategy: Option<Arc<dyn ReconnectStrategy>>,
+    publish_options: PublishOptions,
+    preferred_locales: Vec<String>,
+    alternate_urls: Vec<String>,
 }
 
 /// Trait for getting a connection builder for a given endpoint.
@@ -78,6 +89,12 @@ impl<'a> SessionBuilder<'a, (), (), DirectConnectionSource> {
                 session_id: None,
                 user_identity_token: IdentityToken::Anonymous,
                 type_loaders: Vec::new(),
+                subscription_transfer_policy: config.subscription_transfer_policy,
+                subscription_store: None,
+                reconnect_strategy: None,
+                publish_options: config.publish.clone(),
+                preferred_locales: config.preferred_locales.clone(),
+                alternate_urls: Vec::new(),
             },
             connection_source: DirectConnectionSource,
         }
@@ -109,6 +126,30 @@ impl<'a, T, R, C> SessionBuilder<'a, T, R, C> {
         self
     }
 
+    /// Set the locales the server should use for human readable strings, such as display
+    /// names, descriptions and other localized text values, in order of preference.
+    /// Defaults to the preferred locales in the client config.
+    ///
+    /// The locales can be changed later with [`Session::set_preferred_locales`].
+    pub fn preferred_locales(mut self, preferred_locales: Vec<String>) -> Self {
+        self.inner.preferred_locales = preferred_locales;
+        self
+    }
+
+    /// Set URLs of alternate network paths to the server, for example over a backup
+    /// network. When connecting and reconnecting, these are tried in order if the
+    /// server cannot be reached at the URL of the endpoint, see
+    /// [`ConnectionPathOptions`](crate::ConnectionPathOptions). The endpoint is otherwise
+    /// unchanged, so the alternate URLs must lead to the same server.
+    ///
+    /// When connecting to an endpoint from the client config, with
+    /// [`SessionBuilder::connect_to_endpoint_id`] or [`SessionBuilder::connect_to_default_endpoint`],
+    /// the alternate URLs of that endpoint are used. Call this afterwards to override them.
+    pub fn alternate_urls(mut self, alternate_urls: Vec<String>) -> Self {
+        self.inner.alternate_urls = alternate_urls;
+        self
+    }
+
     /// Set an initial session ID. The session will try to reactivate this session
     /// before creating a new session. This can be useful to persist session IDs
     /// between program executions, to avoid having to recreate subscriptions.
@@ -125,6 +166,37 @@ impl<'a, T, R, C> SessionBuilder<'a, T, R, C> {
         self
     }
 
+    /// Set how subscriptions are moved to a new session if the session is recreated
+    /// after losing the connection. Defaults to the policy in the client config.
+    pub fn subscription_transfer_policy(mut self, policy: SubscriptionTransferPolicy) -> Self {
+        self.inner.subscription_transfer_policy = policy;
+        self
+    }
+
+    /// Set a store for the subscriptions in the session. The session saves its
+    /// subscriptions, monitored items, and the last received sequence numbers to the store
+    /// as they change. Call [`Session::restore_subscriptions`] before starting the event loop to
+    /// pick up the stored subscriptions, for example after a restart.
+    pub fn subscription_store(mut self, store: Arc<dyn SubscriptionStore>) -> Self {
+        self.inner.subscription_store = Some(store);
+        self
+    }
+
+    /// Set the strategy for reconnecting to the server when the connection is lost.
+    /// Defaults to the session retry policy in the client config.
+    pub fn reconnect_strategy(mut self, strategy: impl ReconnectStrategy + 'static) -> Self {
+        self.inner.reconnect_strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Set options for the publish requests this session sends to receive notifications,
+    /// such as the number of outstanding requests. Defaults to the publish options in
+    /// the client config.
+    pub fn publish_options(mut self, options: PublishOptions) -> Self {
+        self.inner.publish_options = options;
+        self
+    }
+
     fn endpoint_supports_token(&self, endpoint: &EndpointDescription) -> bool {
         match &self.inner.user_identity_token {
             IdentityToken::Anonymous => {
@@ -226,6 +298,7 @@ impl<'a, C> SessionBuilder<'a, (), Vec<EndpointDescription>, C> {
             ));
         };
         let user_identity_token = self.config.client_identity_token(&endpoint.user_token_id)?;
+        self.inner.alternate_urls = endpoint.alternate_urls.clone();
         let endpoint = self
             .config
             .endpoint_description_for_client_endpoint(&endpoint, &self.endpoints)?;
@@ -253,6 +326,7 @@ impl<'a, C> SessionBuilder<'a, (), Vec<EndpointDescription>, C> {
             )
         })?;
         let user_identity_token = self.config.client_identity_token(&endpoint.user_token_id)?;
+        self.inner.alternate_urls = endpoint.alternate_urls.clone();
 
         let endpoint = self
             .config
@@ -267,6 +341,43 @@ impl<'a, C> SessionBuilder<'a, (), Vec<EndpointDescription>, C> {
         })
     }
 
+    /// Connect to the endpoint picked by the endpoint selector of the client, see
+    /// [`ClientBuilder::endpoint_selector`](crate::ClientBuilder::endpoint_selector).
+    /// The selector is given the endpoints that support the configured identity token.
+    ///
+    /// The hostname of the selected endpoint is replaced with the hostname of `url`,
+    /// since the server may advertise a hostname that is inaccessible to the client.
+    pub fn connect_to_selected_endpoint(
+        self,
+        url: &str,
+    ) -> Result<SessionBuilder<'a, EndpointDescription, Vec<EndpointDescription>, C>, Error> {
+        let candidates = self
+            .endpoints
+            .iter()
+            .filter(|e| self.endpoint_supports_token(e))
+            .cloned();
+        let Some(mut endpoint) = self.config.endpoint_selector.select(url, candidates) else {
+            return Err(Error::new(
+                StatusCode::BadSecurityPolicyRejected,
+                "No endpoint was accepted by the endpoint selector",
+            ));
+        };
+        if let Ok(hostname) = hostname_from_url(url) {
+            if let Ok(endpoint_url) =
+                url_with_replaced_hostname(endpoint.endpoint_url.as_ref(), &hostname)
+            {
+                endpoint.endpoint_url = endpoint_url.into();
+            }
+        }
+        Ok(SessionBuilder {
+            inner: self.inner,
+            endpoint,
+            config: self.config,
+            endpoints: self.endpoints,
+            connection_source: self.connection_source,
+        })
+    }
+
     /// Attempt to pick the "best" endpoint. If `secure` is `false` this means
     /// any unencrypted endpoint that supports the configured identity token.
     /// If `secure` is `true`, the endpoint that supports the configured identity token with the highest
@@ -309,7 +420,7 @@ impl<'a, R, C> SessionBuilder<'a, (), R, C> {
         endpoint: impl Into<EndpointDescription>,
     ) -> Result<SessionBuilder<'a, EndpointDescription, R, C>, Error> {
         let endpoint = endpoint.into();
-        if !is_opc_ua_binary_url(endpoint.endpoint_url.as_ref()) {
+        if !is_valid_opc_ua_url(endpoint.endpoint_url.as_ref()) {
             return Err(Error::new(
                 StatusCode::BadTcpEndpointUrlInvalid,
                 format!(
@@ -342,22 +453,30 @@ where
             .connection_source
             .get_connector(&self.endpoint)?
             .build()?;
+        let alternates = self.alternate_connectors()?;
         let ctx = self.make_encoding_context();
         Ok(Session::new(
             Self::build_channel_inner(
                 certificate_store,
                 self.inner.user_identity_token,
+                self.inner.preferred_locales,
                 self.endpoint,
                 self.config,
                 connector,
+                alternates,
                 ctx,
             ),
             self.config.session_name.clone().into(),
             self.config.application_description(),
-            self.config.session_retry_policy(),
+            self.inner
+                .reconnect_strategy
+                .unwrap_or_else(|| Arc::new(self.config.session_retry_policy())),
             self.config.decoding_options.as_comms_decoding_options(),
             self.config,
             self.inner.session_id,
+            self.inner.subscription_transfer_policy,
+            self.inner.subscription_store,
+            self.inner.publish_options,
         ))
     }
 
@@ -374,20 +493,45 @@ where
         encoding_context
     }
 
+    /// Get connectors for the alternate network paths to the server, by getting a
+    /// connector for the endpoint with each alternate URL.
+    fn alternate_connectors(&self) -> Result<Vec<Box<dyn Connector + Send + Sync>>, Error> {
+        self.inner
+            .alternate_urls
+            .iter()
+            .map(|url| {
+                if !is_valid_opc_ua_url(url) {
+                    return Err(Error::new(
+                        StatusCode::BadTcpEndpointUrlInvalid,
+                        format!("Alternate url {url} is not a valid / supported url"),
+                    ));
+                }
+                let endpoint = EndpointDescription {
+                    endpoint_url: url.as_str().into(),
+                    ..self.endpoint.clone()
+                };
+                self.connection_source.get_connector(&endpoint)?.build()
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn build_channel_inner(
         certificate_store: Arc<RwLock<CertificateStore>>,
         identity_token: IdentityToken,
+        preferred_locales: Vec<String>,
         endpoint: EndpointDescription,
         config: &ClientConfig,
         connector: Box<dyn Connector + Send + Sync + 'static>,
+        alternates: Vec<Box<dyn Connector + Send + Sync + 'static>>,
         ctx: ContextOwned,
     ) -> AsyncSecureChannel {
-        AsyncSecureChannel::new(
-            certificate_store,
+        let mut channel = AsyncSecureChannel::new(
+            certificate_store.clone(),
             EndpointInfo {
                 endpoint,
                 user_identity_token: identity_token,
-                preferred_locales: config.preferred_locales.clone(),
+                preferred_locales,
             },
             config.session_retry_policy(),
             config.performance.ignore_clock_skew,
@@ -397,11 +541,31 @@ where
                 recv_buffer_size: config.decoding_options.max_incoming_chunk_size,
                 max_message_size: config.decoding_options.max_message_size,
                 max_chunk_count: config.decoding_options.max_chunk_count,
+                max_send_message_size: config.decoding_options.max_send_message_size(),
+                max_send_chunk_count: config.decoding_options.max_send_chunk_count(),
+                socket_options: config.socket_options.clone(),
+                hello_timeout: config.hello_timeout,
+                open_secure_channel_timeout: config.open_secure_channel_timeout,
+                parallel_crypto_threshold: config.performance.parallel_crypto_chunk_threshold,
+                metrics: config.transport_metrics.clone(),
+                interceptors: config.interceptors.clone(),
+                https: config.https.clone(),
+                #[cfg(feature = "https")]
+                certificate_store,
+                aborts: Default::default(),
+                request_limiter: Arc::new(RequestLimiter::new(&config.request_limits)),
+                service_retry: config.service_retry.clone(),
+                #[cfg(feature = "request-metrics")]
+                request_metrics: config.request_metrics.clone(),
             },
             connector,
-            config.channel_lifetime,
+            config.token_renewal.clone(),
             Arc::new(RwLock::new(ctx)),
-        )
+        );
+        if !alternates.is_empty() {
+            channel.add_alternate_paths(alternates, config.connection_paths.clone());
+        }
+        channel
     }
 
     /// Build a channel only, not creating a session.
@@ -415,12 +579,15 @@ where
             .connection_source
             .get_connector(&self.endpoint)?
             .build()?;
+        let alternates = self.alternate_connectors()?;
         Ok(Self::build_channel_inner(
             certificate_store,
             self.inner.user_identity_token,
+            self.inner.preferred_locales,
             self.endpoint,
             self.config,
             connector,
+            alternates,
             ctx,
         ))
     }
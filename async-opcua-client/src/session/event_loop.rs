@@ -1,14 +1,16 @@
 use std::{
+    collections::HashMap,
     sync::{atomic::Ordering, Arc},
     time::{Duration, Instant},
 };
 
 use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream, StreamExt, TryStreamExt};
+use opcua_core::trace_lock;
 use tracing::warn;
 
 use crate::{
-    retry::{ExponentialBackoff, SessionRetryPolicy},
-    session::{session_error, session_warn},
+    retry::{ReconnectDecision, ReconnectEvent, ReconnectStrategy},
+    session::{session_debug, session_error, session_warn},
     transport::{SecureChannelEventLoop, TransportPollResult},
 };
 use opcua_types::{
@@ -21,6 +23,53 @@ use super::{
     Session, SessionState,
 };
 
+/// Changes to the connection of a session, for driving health indicators or alarms.
+/// Subscribe to these with [`Session::connection_events`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// The session begins connecting to the server.
+    Connecting,
+    /// The session is connected to the server and ready for use.
+    Connected,
+    /// Connecting failed, and the session will try again.
+    WaitingForRetry {
+        /// The number of failed attempts since the session started connecting.
+        attempt: u32,
+        /// The time until the next attempt.
+        next_in: Duration,
+    },
+    /// The session was disconnected from the server, or gave up connecting.
+    Disconnected {
+        /// The reason the session was disconnected.
+        reason: StatusCode,
+    },
+    /// The old session could not be reactivated, so a new session was created
+    /// on the server.
+    SessionRecreated,
+    /// Subscriptions from the old session were transferred to, or recreated on,
+    /// the new session.
+    SubscriptionsRestored,
+    /// Namespaces of the server moved to other indices since the session last
+    /// connected, for example because the server restarted. Monitored items were
+    /// changed to use the new indices, node IDs kept elsewhere must be changed
+    /// by the application.
+    NamespacesChanged {
+        /// The new index of each namespace that moved, by its old index.
+        remapped: HashMap<u16, u16>,
+    },
+    /// The session connected over a different network path than the last time,
+    /// either an alternate path because the server could not be reached over
+    /// paths with higher priority, or the primary path again. See
+    /// [`SessionBuilder::alternate_urls`](crate::SessionBuilder::alternate_urls).
+    PathChanged {
+        /// The priority of the path, 0 is the primary path, 1 the first alternate.
+        priority: usize,
+        /// The URL the session connected to.
+        url: String,
+    },
+}
+
 /// A list of possible events that happens while polling the session.
 /// The client can use this list to monitor events such as disconnects,
 /// publish failures, etc.
@@ -59,7 +108,9 @@ struct ConnectedState {
 #[allow(clippy::large_enum_variant)]
 enum SessionEventLoopState {
     Connected(ConnectedState),
-    Connecting(SessionConnector, ExponentialBackoff, Instant),
+    /// Connecting, with the number of failed attempts so far, the time of the next attempt,
+    /// and whether to refresh the endpoint before it.
+    Connecting(SessionConnector, u32, Instant, bool),
     Disconnected,
 }
 
@@ -68,7 +119,7 @@ enum SessionEventLoopState {
 pub struct SessionEventLoop {
     inner: Arc<Session>,
     trigger_publish_recv: tokio::sync::watch::Receiver<Instant>,
-    retry: SessionRetryPolicy,
+    retry: Arc<dyn ReconnectStrategy>,
     keep_alive_interval: Duration,
     max_failed_keep_alive_count: u64,
 }
@@ -76,7 +127,7 @@ pub struct SessionEventLoop {
 impl SessionEventLoop {
     pub(crate) fn new(
         inner: Arc<Session>,
-        retry: SessionRetryPolicy,
+        retry: Arc<dyn ReconnectStrategy>,
         trigger_publish_recv: tokio::sync::watch::Receiver<Instant>,
         keep_alive_interval: Duration,
         max_failed_keep_alive_count: u64,
@@ -139,6 +190,7 @@ impl SessionEventLoop {
                                 if let TransportPollResult::Closed(code) = r {
                                     session_warn!(slf.inner, "Transport disconnected: {code}");
                                     let _ = slf.inner.state_watch_tx.send(SessionState::Disconnected);
+                                    slf.inner.emit_connection_event(ConnectionEvent::Disconnected { reason: code });
 
                                     let should_reconnect = slf.inner.should_reconnect.load(Ordering::Relaxed);
                                     if !should_reconnect {
@@ -163,6 +215,9 @@ impl SessionEventLoop {
                                     return Err(StatusCode::BadUnexpectedError);
                                 };
 
+                                slf.inner
+                                    .health
+                                    .on_keep_alive(matches!(r, SessionActivity::KeepAliveSucceeded));
                                 match r {
                                     SessionActivity::KeepAliveSucceeded => state.current_failed_keep_alive_count = 0,
                                     SessionActivity::KeepAliveFailed(status_code) => {
@@ -224,22 +279,63 @@ impl SessionEventLoop {
                         let connector = SessionConnector::new(slf.inner.clone());
 
                         let _ = slf.inner.state_watch_tx.send(SessionState::Connecting);
+                        slf.inner.emit_connection_event(ConnectionEvent::Connecting);
 
                         Ok((
                             SessionPollResult::BeginConnect,
-                            SessionEventLoopState::Connecting(
-                                connector,
-                                slf.retry.new_backoff(),
-                                Instant::now(),
-                            ),
+                            SessionEventLoopState::Connecting(connector, 0, Instant::now(), false),
                         ))
                     }
-                    SessionEventLoopState::Connecting(connector, mut backoff, next_try) => {
+                    SessionEventLoopState::Connecting(connector, attempt, next_try, rediscover) => {
                         tokio::time::sleep_until(next_try.into()).await;
 
+                        if rediscover {
+                            match slf
+                                .inner
+                                .channel
+                                .rediscover_endpoint(slf.inner.request_timeout)
+                                .await
+                            {
+                                Ok(info) => slf.retry.on_event(&ReconnectEvent::Rediscovered {
+                                    endpoint_url: info.endpoint.endpoint_url.clone(),
+                                }),
+                                Err(e) => {
+                                    session_warn!(slf.inner, "Failed to refresh endpoint: {e}");
+                                    slf.retry.on_event(&ReconnectEvent::RediscoveryFailed(e));
+                                }
+                            }
+                        }
+
                         match connector.try_connect().await {
                             Ok((channel, result)) => {
+                                slf.inner.health.on_connected();
+                                trace_lock!(slf.inner.subscription_state)
+                                    .reset_last_publish_received();
                                 let _ = slf.inner.state_watch_tx.send(SessionState::Connected);
+                                slf.inner.emit_connection_event(ConnectionEvent::Connected);
+                                if let Some(priority) = slf.inner.channel.take_path_change() {
+                                    if priority > 0 {
+                                        session_warn!(
+                                            slf.inner,
+                                            "Connected over alternate network path at {}",
+                                            channel.connected_url()
+                                        );
+                                    } else {
+                                        session_debug!(
+                                            slf.inner,
+                                            "Connected over primary network path again"
+                                        );
+                                    }
+                                    slf.inner
+                                        .emit_connection_event(ConnectionEvent::PathChanged {
+                                            priority,
+                                            url: channel.connected_url().to_owned(),
+                                        });
+                                }
+                                if attempt > 0 {
+                                    slf.retry
+                                        .on_event(&ReconnectEvent::Connected { attempts: attempt });
+                                }
                                 Ok((
                                     SessionPollResult::Reconnected(result),
                                     SessionEventLoopState::Connected(ConnectedState {
@@ -264,16 +360,41 @@ impl SessionEventLoop {
                             }
                             Err(e) => {
                                 warn!("Failed to connect to server, status code: {e}");
-                                match backoff.next() {
-                                    Some(x) => Ok((
-                                        SessionPollResult::ReconnectFailed(e),
-                                        SessionEventLoopState::Connecting(
-                                            connector,
-                                            backoff,
-                                            Instant::now() + x,
-                                        ),
-                                    )),
-                                    None => Err(e),
+                                let attempt = attempt + 1;
+                                let decision = slf.retry.next_attempt(attempt, e);
+                                slf.retry.on_event(&ReconnectEvent::AttemptFailed {
+                                    attempt,
+                                    error: e,
+                                    decision,
+                                });
+                                match decision {
+                                    ReconnectDecision::Retry(delay)
+                                    | ReconnectDecision::RediscoverAndRetry(delay) => {
+                                        slf.inner.emit_connection_event(
+                                            ConnectionEvent::WaitingForRetry {
+                                                attempt,
+                                                next_in: delay,
+                                            },
+                                        );
+                                        Ok((
+                                            SessionPollResult::ReconnectFailed(e),
+                                            SessionEventLoopState::Connecting(
+                                                connector,
+                                                attempt,
+                                                Instant::now() + delay,
+                                                matches!(
+                                                    decision,
+                                                    ReconnectDecision::RediscoverAndRetry(_)
+                                                ),
+                                            ),
+                                        ))
+                                    }
+                                    ReconnectDecision::GiveUp => {
+                                        slf.inner.emit_connection_event(
+                                            ConnectionEvent::Disconnected { reason: e },
+                                        );
+                                        Err(e)
+                                    }
                                 }
                             }
                         }
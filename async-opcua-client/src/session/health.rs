@@ -0,0 +1,194 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use opcua_core::{sync::Mutex, trace_lock};
+
+use super::Session;
+
+/// Overall health of a session, see [`Session::health`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthStatus {
+    /// The session is connected, and keep-alives and publish responses arrive on time.
+    Healthy,
+    /// The session is connected, but keep-alives failed recently, or a subscription has
+    /// not received a publish response in time.
+    Degraded,
+    /// The session is not connected, or the server has not responded to keep-alives
+    /// for longer than the session tolerates.
+    Unhealthy,
+}
+
+/// Health of a single subscription, see [`HealthReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionHealth {
+    /// The ID of the subscription on the server.
+    pub subscription_id: u32,
+    /// Time since the last publish response for the subscription, which the server
+    /// must send at least every keep-alive period.
+    pub last_publish_age: Duration,
+    /// Whether the time since the last publish response exceeds the keep-alive period
+    /// of the subscription.
+    pub overdue: bool,
+    /// Number of publish responses that arrived later than the keep-alive period of
+    /// the subscription.
+    pub late_publish_count: u64,
+}
+
+/// Report of the health of a session, returned by [`Session::health`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// The overall health, derived from the rest of the report.
+    pub status: HealthStatus,
+    /// Whether the session is connected to the server.
+    pub connected: bool,
+    /// Time since the last successful keep-alive, or since the session connected if
+    /// there has been none. `None` if the session has never connected.
+    pub last_keep_alive_age: Option<Duration>,
+    /// Number of keep-alives that failed since the last successful one.
+    pub failed_keep_alives: u64,
+    /// Number of publish requests waiting for a response. The server holds on to publish
+    /// requests until it has notifications to send, so this is normally close to
+    /// `max_publish_requests`, a persistently lower value means publishing is stalled.
+    pub publish_backlog: usize,
+    /// Maximum number of publish requests the session keeps waiting for a response.
+    pub max_publish_requests: usize,
+    /// Health of each subscription.
+    pub subscriptions: Vec<SubscriptionHealth>,
+}
+
+impl HealthReport {
+    /// Whether the session is alive, meaning it is healthy or degraded. Suitable as
+    /// a liveness probe, since an unhealthy session may still recover on its own.
+    pub fn is_alive(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+}
+
+#[derive(Default)]
+struct KeepAliveState {
+    last_success: Option<Instant>,
+    failed: u64,
+}
+
+/// Liveness information recorded by the session while it runs.
+pub(super) struct HealthTracker {
+    keep_alive: Mutex<KeepAliveState>,
+    publish_backlog: AtomicUsize,
+    keep_alive_interval: Duration,
+    max_failed_keep_alive_count: u64,
+}
+
+/// Counts a publish request as waiting for a response until dropped.
+pub(crate) struct PublishGuard<'a>(&'a HealthTracker);
+
+impl Drop for PublishGuard<'_> {
+    fn drop(&mut self) {
+        self.0.publish_backlog.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl HealthTracker {
+    pub(super) fn new(keep_alive_interval: Duration, max_failed_keep_alive_count: u64) -> Self {
+        Self {
+            keep_alive: Mutex::new(KeepAliveState::default()),
+            publish_backlog: AtomicUsize::new(0),
+            keep_alive_interval,
+            max_failed_keep_alive_count,
+        }
+    }
+
+    /// Called when the session connects, which counts as a successful keep-alive.
+    pub(super) fn on_connected(&self) {
+        *trace_lock!(self.keep_alive) = KeepAliveState {
+            last_success: Some(Instant::now()),
+            failed: 0,
+        };
+    }
+
+    pub(super) fn on_keep_alive(&self, success: bool) {
+        let mut keep_alive = trace_lock!(self.keep_alive);
+        if success {
+            keep_alive.last_success = Some(Instant::now());
+            keep_alive.failed = 0;
+        } else {
+            keep_alive.failed += 1;
+        }
+    }
+
+    pub(crate) fn start_publish(&self) -> PublishGuard<'_> {
+        self.publish_backlog.fetch_add(1, Ordering::Relaxed);
+        PublishGuard(self)
+    }
+
+    /// The time without a successful keep-alive after which the session is unhealthy.
+    /// The session is closed after the same number of failed keep-alives, if configured.
+    fn max_keep_alive_age(&self) -> Duration {
+        let count = u32::try_from(self.max_failed_keep_alive_count.max(1)).unwrap_or(u32::MAX);
+        self.keep_alive_interval
+            .saturating_mul(count.saturating_add(1))
+    }
+}
+
+impl Session {
+    /// Get a report on the health of the session, combining the state of the connection,
+    /// keep-alives, outstanding publish requests and subscriptions into a single
+    /// [`HealthStatus`]. This does not send any requests, so it is cheap enough to call
+    /// from a liveness probe.
+    ///
+    /// The session is unhealthy if it is disconnected, or if no keep-alive succeeded for
+    /// `keep_alive_interval` times `max_failed_keep_alive_count` plus one, from the
+    /// client configuration. It is degraded if the last keep-alive failed, or if a
+    /// subscription is overdue.
+    pub fn health(&self) -> HealthReport {
+        let now = Instant::now();
+        let connected = self.is_connected();
+        let (last_keep_alive_age, failed_keep_alives) = {
+            let keep_alive = trace_lock!(self.health.keep_alive);
+            (
+                keep_alive.last_success.map(|t| now.duration_since(t)),
+                keep_alive.failed,
+            )
+        };
+        let publish_backlog = self.health.publish_backlog.load(Ordering::Relaxed);
+        let max_publish_requests = self.publish_limits_watch_rx.borrow().max_publish_requests();
+
+        let mut subscriptions: Vec<_> = {
+            let state = trace_lock!(self.subscription_state);
+            state
+                .subscriptions()
+                .map(|s| {
+                    let last_publish_age = now.duration_since(s.last_publish_received());
+                    SubscriptionHealth {
+                        subscription_id: s.subscription_id(),
+                        last_publish_age,
+                        overdue: last_publish_age > s.late_publish_threshold(),
+                        late_publish_count: s.late_publish_count(),
+                    }
+                })
+                .collect()
+        };
+        subscriptions.sort_by_key(|s| s.subscription_id);
+
+        let status = if !connected
+            || last_keep_alive_age.is_none_or(|age| age > self.health.max_keep_alive_age())
+        {
+            HealthStatus::Unhealthy
+        } else if failed_keep_alives > 0 || subscriptions.iter().any(|s| s.overdue) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        HealthReport {
+            status,
+            connected,
+            last_keep_alive_age,
+            failed_keep_alives,
+            publish_backlog,
+            max_publish_requests,
+            subscriptions,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use opcua_types::StatusCode;
+
+use super::{session_debug, session_warn, Session};
+use crate::IdentityToken;
+
+impl Session {
+    /// Change the user identity of the session, for example when an operator logs in or
+    /// out of an HMI.
+    ///
+    /// If the session is connected, it is activated again with the new identity token.
+    /// The session, and any subscriptions and monitored items on it, are kept as they are,
+    /// only the user the server associates with the session changes. The token is signed
+    /// using the nonce returned by the server when the session was last activated.
+    ///
+    /// If the session is not connected, the new identity is used the next time the session
+    /// is activated. If activating the session fails, for example because the server rejects
+    /// the token, the previous identity is restored and the session keeps running as the
+    /// previous user.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity_token` - The new user identity.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The identity was changed.
+    /// * `Err(StatusCode)` - Activating the session failed, [Status code](StatusCode) is
+    ///   the reason for failure.
+    ///
+    pub async fn change_identity(&self, identity_token: IdentityToken) -> Result<(), StatusCode> {
+        let previous = self.current_endpoint_info().user_identity_token.clone();
+        self.channel.set_user_identity_token(identity_token);
+        if !self.is_connected() {
+            return Ok(());
+        }
+
+        session_debug!(self, "Activating session with a new user identity");
+        if let Err(e) = self.activate_session().await {
+            session_warn!(self, "Failed to change user identity: {}", e);
+            self.channel.set_user_identity_token(previous);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use opcua_types::{
+    DataValue, NodeId, ReadValueId, StatusCode, TimestampsToReturn, VariableId, Variant,
+};
+
+use super::{session_debug, session_warn, Session};
+
+impl Session {
+    /// Get the locales requested from the server for human readable strings, in order
+    /// of preference.
+    pub fn preferred_locales(&self) -> Vec<String> {
+        self.current_endpoint_info().preferred_locales.clone()
+    }
+
+    /// Change the locales the server should use for human readable strings, such as
+    /// display names, descriptions and other localized text values, in order of preference.
+    ///
+    /// If the session is connected, it is activated again to pass the new locales to the
+    /// server, otherwise they are used the next time the session is activated. If activating
+    /// the session fails, the previous locales are restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `preferred_locales` - Locale IDs, for example `en-US` or `de`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The locales were changed.
+    /// * `Err(StatusCode)` - Activating the session failed, [Status code](StatusCode) is
+    ///   the reason for failure.
+    ///
+    pub async fn set_preferred_locales(
+        &self,
+        preferred_locales: Vec<String>,
+    ) -> Result<(), StatusCode> {
+        let previous = self.preferred_locales();
+        self.channel.set_preferred_locales(preferred_locales);
+        if !self.is_connected() {
+            return Ok(());
+        }
+
+        session_debug!(
+            self,
+            "Activating session with preferred locales {:?}",
+            self.preferred_locales()
+        );
+        if let Err(e) = self.activate_session().await {
+            session_warn!(self, "Failed to change preferred locales: {}", e);
+            self.channel.set_preferred_locales(previous);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Read the locales supported by the server from
+    /// `Server/ServerCapabilities/LocaleIdArray`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<String>)` - The locale IDs supported by the server.
+    /// * `Err(StatusCode)` - Request failed, or the value could not be read,
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn server_locales(&self) -> Result<Vec<String>, StatusCode> {
+        let node_id: NodeId = VariableId::Server_ServerCapabilities_LocaleIdArray.into();
+        let value = self
+            .read(
+                &[ReadValueId::from(node_id)],
+                TimestampsToReturn::Neither,
+                0.0,
+            )
+            .await?
+            .pop()
+            .ok_or(StatusCode::BadUnexpectedError)?;
+        locales_from_value(value)
+    }
+}
+
+fn locales_from_value(value: DataValue) -> Result<Vec<String>, StatusCode> {
+    if let Some(status) = value.status.filter(|s| s.is_bad()) {
+        return Err(status);
+    }
+    match value.value {
+        Some(Variant::Array(array)) => Ok(array
+            .values
+            .into_iter()
+            .filter_map(|v| match v {
+                Variant::String(s) => s.value().clone(),
+                _ => None,
+            })
+            .collect()),
+        Some(Variant::Empty) | None => Ok(Vec::new()),
+        _ => Err(StatusCode::BadTypeMismatch),
+    }
+}
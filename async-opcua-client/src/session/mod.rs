@@ -1,10 +1,23 @@
+mod bulk_write;
 mod client;
 mod connect;
 mod connection;
 mod event_loop;
+mod health;
+mod identity;
+mod locales;
+mod namespaces;
+mod operation_limits;
+mod pool;
+mod read_cache;
+mod read_node;
+mod redundancy;
 mod request_builder;
 mod retry;
+mod server_status;
 mod services;
+mod sessionless;
+mod type_discovery;
 
 /// Information about the server endpoint, security policy, security mode and user identity that the session will
 /// will use to establish a connection.
@@ -38,38 +51,61 @@ impl From<(EndpointDescription, IdentityToken)> for EndpointInfo {
     }
 }
 
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use arc_swap::ArcSwap;
+pub use bulk_write::{WriteNodeResult, WriteReport, WriteVerification};
 pub use client::Client;
 pub use connect::SessionConnectMode;
 pub use connection::{ConnectionSource, DirectConnectionSource, SessionBuilder};
-pub use event_loop::{SessionActivity, SessionEventLoop, SessionPollResult};
+pub use event_loop::{ConnectionEvent, SessionActivity, SessionEventLoop, SessionPollResult};
+use futures::{stream::BoxStream, Future, StreamExt};
+pub use health::{HealthReport, HealthStatus, SubscriptionHealth};
+use opcua_core::comms::tcp_types::ConnectionLimits;
 use opcua_core::handle::AtomicHandle;
 use opcua_core::sync::{Mutex, RwLock};
+pub use operation_limits::OperationLimits;
+pub use pool::{PooledSession, SessionPool};
+pub use redundancy::{
+    FailoverMode, RedundancyEvent, RedundancyOptions, RedundantSession, ServerRedundancyInfo,
+};
 pub use request_builder::UARequest;
 pub use retry::{DefaultRetryPolicy, RequestRetryPolicy};
+pub use server_status::{ServerStatusEvent, ServerStatusInfo, ServerStatusMonitor};
 pub use services::attributes::{
-    HistoryRead, HistoryReadAction, HistoryUpdate, HistoryUpdateAction, Read, Write,
+    AggregateSeries, AggregateValue, HistoryEvents, HistoryRead, HistoryReadAction,
+    HistoryReadRawOptions, HistoryUpdate, HistoryUpdateAction, HistoryUpdateOutcome, Read, Write,
 };
+pub use services::file::RemoteFile;
 pub use services::method::Call;
 pub use services::node_management::{AddNodes, AddReferences, DeleteNodes, DeleteReferences};
+pub use services::paging::{BrowseResultStream, HistoryValueStream};
+pub use services::registered_nodes::RegisteredNodes;
 pub use services::session::{ActivateSession, Cancel, CloseSession, CreateSession};
 use services::subscriptions::state::SubscriptionState;
 use services::subscriptions::PublishLimits;
+use services::subscriptions::SubscriptionPersister;
 pub use services::subscriptions::{
-    CreateMonitoredItems, CreateSubscription, DataChangeCallback, DeleteMonitoredItems,
-    DeleteSubscriptions, EventCallback, ModifyMonitoredItems, ModifySubscription, MonitoredItem,
-    OnSubscriptionNotification, OnSubscriptionNotificationCore, Publish, Republish,
+    ConditionState, ConditionSubscription, CreateMonitoredItems, CreateSubscription,
+    DataChangeCallback, DeleteMonitoredItems, DeleteSubscriptions, EventCallback,
+    EventSubscription, FileSubscriptionStore, FilteredNotifications, ModifyMonitoredItems,
+    ModifySubscription, MonitoredItem, MonitoredItemChange, MonitoredItemHandle,
+    MonitoredItemResync, NotificationFilter, NotificationSender, NotificationStream,
+    OnSubscriptionNotification, OnSubscriptionNotificationCore, PersistedMonitoredItem,
+    PersistedSubscription, PersistedSubscriptions, Publish, Republish, SequenceGap,
     SetMonitoringMode, SetPublishingMode, SetTriggering, Subscription, SubscriptionActivity,
-    SubscriptionCallbacks, TransferSubscriptions,
+    SubscriptionCallbacks, SubscriptionChange, SubscriptionNotification, SubscriptionStore,
+    TagBinding, TagSubscription, TransferSubscriptions,
 };
 pub use services::view::{
     Browse, BrowseNext, RegisterNodes, TranslateBrowsePaths, UnregisterNodes,
 };
-use tracing::{error, info};
+pub use sessionless::SessionlessChannel;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
 
 #[allow(unused)]
 macro_rules! session_warn {
@@ -109,13 +145,16 @@ pub(crate) use session_trace;
 
 use opcua_core::ResponseMessage;
 use opcua_types::{
-    ApplicationDescription, ContextOwned, DecodingOptions, EndpointDescription, Error, IntegerId,
-    NamespaceMap, NodeId, ReadValueId, RequestHeader, ResponseHeader, StatusCode,
-    TimestampsToReturn, TypeLoader, UAString, VariableId, Variant,
+    ApplicationDescription, Argument, ContextOwned, DecodingOptions, EndpointDescription, Error,
+    ErrorContext, IntegerId, NamespaceMap, NodeId, ReadValueId, RequestHeader, ResponseHeader,
+    StatusCode, TimestampsToReturn, TypeLoader, UAString, VariableId, Variant,
 };
 
-use crate::browser::Browser;
-use crate::{AsyncSecureChannel, ClientConfig, ExponentialBackoff, SessionRetryPolicy};
+use crate::browser::{Browser, Crawler};
+use crate::{
+    AsyncSecureChannel, ClientConfig, ExponentialBackoff, PublishOptions, ReconnectStrategy,
+    SampledFallbackOptions, SubscriptionTransferPolicy,
+};
 
 use super::IdentityToken;
 
@@ -158,6 +197,18 @@ pub(super) enum SessionState {
 
 static NEXT_SESSION_ID: AtomicU32 = AtomicU32::new(1);
 
+/// Number of connection events buffered for each subscriber to
+/// [`Session::connection_events`] before the oldest are dropped.
+const CONNECTION_EVENTS_CAPACITY: usize = 32;
+
+/// First client handle assigned to monitored items.
+const FIRST_MONITORED_ITEM_HANDLE: u32 = 1000;
+
+tokio::task_local! {
+    /// Request timeout set by [`Session::with_timeout`].
+    static REQUEST_TIMEOUT_OVERRIDE: Duration;
+}
+
 /// An OPC-UA session. This session provides methods for all supported services that require an open session.
 ///
 /// Note that not all servers may support all service requests and calling an unsupported API
@@ -168,6 +219,7 @@ pub struct Session {
     pub(super) channel: AsyncSecureChannel,
     pub(super) state_watch_rx: tokio::sync::watch::Receiver<SessionState>,
     pub(super) state_watch_tx: tokio::sync::watch::Sender<SessionState>,
+    pub(super) connection_events_tx: tokio::sync::broadcast::Sender<ConnectionEvent>,
     pub(super) session_id: Arc<ArcSwap<NodeId>>,
     pub(super) internal_session_id: AtomicU32,
     pub(super) session_name: UAString,
@@ -175,13 +227,25 @@ pub struct Session {
     pub(super) request_timeout: Duration,
     pub(super) publish_timeout: Duration,
     pub(super) recreate_monitored_items_chunk: usize,
+    pub(super) split_by_operation_limits: bool,
+    pub(super) operation_limits: RwLock<OperationLimits>,
+    pub(super) method_input_arguments: RwLock<HashMap<NodeId, Arc<Vec<Argument>>>>,
     pub(super) recreate_subscriptions: bool,
+    pub(super) subscription_transfer_policy: SubscriptionTransferPolicy,
+    pub(super) read_namespaces_on_connect: bool,
+    type_discovery: type_discovery::TypeDiscovery,
+    health: health::HealthTracker,
+    read_cache: read_cache::ReadCache,
+    pub(super) subscription_store: Option<Arc<dyn SubscriptionStore>>,
+    subscription_persister: Option<SubscriptionPersister>,
     pub(super) should_reconnect: AtomicBool,
     pub(super) session_timeout: f64,
     /// Reference to the subscription cache for the client.
     pub subscription_state: Mutex<SubscriptionState>,
     pub(super) publish_limits_watch_rx: tokio::sync::watch::Receiver<PublishLimits>,
     pub(super) publish_limits_watch_tx: tokio::sync::watch::Sender<PublishLimits>,
+    pub(super) publish_options: PublishOptions,
+    pub(super) sampled_fallback: SampledFallbackOptions,
     pub(super) monitored_item_handle: AtomicHandle,
     pub(super) trigger_publish_tx: tokio::sync::watch::Sender<Instant>,
     pub(super) session_nonce_length: usize,
@@ -191,51 +255,88 @@ pub struct Session {
 impl Session {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        channel: AsyncSecureChannel,
+        mut channel: AsyncSecureChannel,
         session_name: UAString,
         application_description: ApplicationDescription,
-        session_retry_policy: SessionRetryPolicy,
+        reconnect_strategy: Arc<dyn ReconnectStrategy>,
         decoding_options: DecodingOptions,
         config: &ClientConfig,
         session_id: Option<NodeId>,
+        subscription_transfer_policy: SubscriptionTransferPolicy,
+        subscription_store: Option<Arc<dyn SubscriptionStore>>,
+        publish_options: PublishOptions,
     ) -> (Arc<Self>, SessionEventLoop) {
         let (publish_limits_watch_tx, publish_limits_watch_rx) =
-            tokio::sync::watch::channel(PublishLimits::new());
+            tokio::sync::watch::channel(PublishLimits::new(&publish_options));
         let (state_watch_tx, state_watch_rx) =
             tokio::sync::watch::channel(SessionState::Disconnected);
         let (trigger_publish_tx, trigger_publish_rx) = tokio::sync::watch::channel(Instant::now());
-
-        let session = Arc::new(Session {
-            channel,
-            internal_session_id: AtomicU32::new(NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed)),
-            state_watch_rx,
-            state_watch_tx,
-            session_id: Arc::new(ArcSwap::new(Arc::new(session_id.unwrap_or_default()))),
-            session_name,
-            application_description,
-            request_timeout: config.request_timeout,
-            session_timeout: config.session_timeout as f64,
-            publish_timeout: config.publish_timeout,
-            recreate_monitored_items_chunk: config.performance.recreate_monitored_items_chunk,
-            recreate_subscriptions: config.recreate_subscriptions,
-            should_reconnect: AtomicBool::new(true),
-            subscription_state: Mutex::new(SubscriptionState::new(
-                config.min_publish_interval,
-                publish_limits_watch_tx.clone(),
-            )),
-            monitored_item_handle: AtomicHandle::new(1000),
-            publish_limits_watch_rx,
-            publish_limits_watch_tx,
-            trigger_publish_tx,
-            session_nonce_length: config.session_nonce_length,
-            decoding_options,
+        let (connection_events_tx, _) = tokio::sync::broadcast::channel(CONNECTION_EVENTS_CAPACITY);
+
+        let session_id = Arc::new(ArcSwap::new(Arc::new(session_id.unwrap_or_default())));
+        channel.set_session_id(session_id.clone());
+
+        let session = Arc::new_cyclic(|session| {
+            channel.set_session_recovery(Box::new(session.clone()));
+            let type_discovery =
+                type_discovery::TypeDiscovery::new(config.custom_type_discovery, session.clone());
+            channel
+                .encoding_context()
+                .write()
+                .loaders_mut()
+                .add(type_discovery.loader());
+            Session {
+                channel,
+                internal_session_id: AtomicU32::new(
+                    NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+                ),
+                state_watch_rx,
+                state_watch_tx,
+                connection_events_tx,
+                session_id,
+                session_name,
+                application_description,
+                request_timeout: config.request_timeout,
+                session_timeout: config.session_timeout as f64,
+                publish_timeout: config.publish_timeout,
+                recreate_monitored_items_chunk: config.performance.recreate_monitored_items_chunk,
+                split_by_operation_limits: config.performance.split_by_operation_limits,
+                operation_limits: RwLock::new(OperationLimits::default()),
+                method_input_arguments: RwLock::new(HashMap::new()),
+                recreate_subscriptions: config.recreate_subscriptions,
+                subscription_transfer_policy,
+                read_namespaces_on_connect: config.read_namespaces_on_connect,
+                type_discovery,
+                health: health::HealthTracker::new(
+                    config.keep_alive_interval,
+                    config.max_failed_keep_alive_count,
+                ),
+                read_cache: read_cache::ReadCache::new(&config.read_cache),
+                subscription_persister: subscription_store
+                    .clone()
+                    .map(|store| SubscriptionPersister::new(session.clone(), store)),
+                subscription_store,
+                should_reconnect: AtomicBool::new(true),
+                subscription_state: Mutex::new(SubscriptionState::new(
+                    config.min_publish_interval,
+                    publish_limits_watch_tx.clone(),
+                )),
+                monitored_item_handle: AtomicHandle::new(FIRST_MONITORED_ITEM_HANDLE),
+                publish_limits_watch_rx,
+                publish_limits_watch_tx,
+                publish_options,
+                sampled_fallback: config.sampled_fallback.clone(),
+                trigger_publish_tx,
+                session_nonce_length: config.session_nonce_length,
+                decoding_options,
+            }
         });
 
         (
             session.clone(),
             SessionEventLoop::new(
                 session,
-                session_retry_policy,
+                reconnect_strategy,
                 trigger_publish_rx,
                 config.keep_alive_interval,
                 config.max_failed_keep_alive_count,
@@ -245,7 +346,37 @@ impl Session {
 
     /// Create a request header with the default timeout.
     pub(super) fn make_request_header(&self) -> RequestHeader {
-        self.channel.make_request_header(self.request_timeout)
+        self.channel.make_request_header(self.request_timeout())
+    }
+
+    /// The timeout of requests sent by this session, which is the configured request
+    /// timeout unless overridden with [`Session::with_timeout`].
+    pub(crate) fn request_timeout(&self) -> Duration {
+        REQUEST_TIMEOUT_OVERRIDE
+            .try_with(|timeout| *timeout)
+            .unwrap_or(self.request_timeout)
+    }
+
+    /// Run `fut` with a different request timeout, for example for a single long
+    /// `HistoryRead` or `Call`, without changing the timeout of the session.
+    ///
+    /// The timeout applies to every service call made by `fut` on any session, in
+    /// the same task. Request builders can also set their own timeout with `timeout`.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # async fn f(session: &opcua_client::Session) {
+    /// # let nodes_to_read = Vec::new();
+    /// let values = session
+    ///     .with_timeout(
+    ///         Duration::from_secs(120),
+    ///         session.read(&nodes_to_read, opcua_types::TimestampsToReturn::Both, 0.0),
+    ///     )
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn with_timeout<F: Future>(&self, timeout: Duration, fut: F) -> F::Output {
+        REQUEST_TIMEOUT_OVERRIDE.scope(timeout, fut).await
     }
 
     /// Reset the session after a hard disconnect, clearing the session ID and incrementing the internal
@@ -274,6 +405,11 @@ impl Session {
         res
     }
 
+    /// Whether the session is currently connected to the server.
+    pub(crate) fn is_connected(&self) -> bool {
+        matches!(*self.state_watch_rx.borrow(), SessionState::Connected)
+    }
+
     /// The internal ID of the session, used to keep track of multiple sessions in the same program.
     pub fn session_id(&self) -> u32 {
         self.internal_session_id.load(Ordering::Relaxed)
@@ -292,6 +428,31 @@ impl Session {
         self.wait_for_state(true).await
     }
 
+    /// Get a stream of changes to the connection of this session, see [`ConnectionEvent`].
+    ///
+    /// The stream only yields events that happen after this is called. If the stream
+    /// is not polled fast enough, the oldest events are skipped. It ends when the
+    /// session is dropped.
+    pub fn connection_events(&self) -> BoxStream<'static, ConnectionEvent> {
+        futures::stream::unfold(self.connection_events_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Connection event stream lagged, skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    pub(super) fn emit_connection_event(&self, event: ConnectionEvent) {
+        // Fails only if there are no subscribers.
+        let _ = self.connection_events_tx.send(event);
+    }
+
     /// Disable automatic reconnects.
     /// This will make the event loop quit the next time
     /// it disconnects for whatever reason.
@@ -324,6 +485,10 @@ impl Session {
                 "Failed to close session, channel will be closed anyway: {e}"
             );
         }
+        // Save any changes to the subscriptions that are still waiting to be saved.
+        if let Some(persister) = &self.subscription_persister {
+            persister.save().await;
+        }
         self.channel.close_channel().await;
 
         self.wait_for_state(false).await;
@@ -354,6 +519,19 @@ impl Session {
         &self.channel
     }
 
+    /// Get the message limits negotiated with the server for the current connection.
+    /// Use these to split large writes before they exceed what the server accepts.
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        self.channel.connection_limits()
+    }
+
+    /// Get the collector of per-service metrics of the requests sent by the session,
+    /// shared with the client that created it.
+    #[cfg(feature = "request-metrics")]
+    pub fn request_metrics(&self) -> &Arc<crate::metrics::RequestMetrics> {
+        self.channel.request_metrics()
+    }
+
     /// Get the next request handle.
     pub fn request_handle(&self) -> IntegerId {
         self.channel.request_handle()
@@ -364,11 +542,26 @@ impl Session {
         self.channel.encoding_context()
     }
 
+    /// Get the priority of the network path the session last connected over, 0 is
+    /// the primary path, 1 the first alternate URL. See
+    /// [`SessionBuilder::alternate_urls`](crate::SessionBuilder::alternate_urls).
+    pub fn connection_path(&self) -> usize {
+        self.channel.connection_path()
+    }
+
     /// Get the target endpoint for the session.
     pub fn endpoint_info(&self) -> &EndpointInfo {
         self.channel.endpoint_info()
     }
 
+    /// Get the endpoint the session currently connects to. This differs from
+    /// [`Session::endpoint_info`] if the endpoint was refreshed while reconnecting, see
+    /// [`ReconnectDecision::RediscoverAndRetry`](crate::ReconnectDecision::RediscoverAndRetry),
+    /// or if the identity token or preferred locales were changed.
+    pub fn current_endpoint_info(&self) -> Arc<EndpointInfo> {
+        self.channel.current_endpoint_info()
+    }
+
     /// Set the namespace array on the session.
     /// Make sure that this namespace array contains the base namespace,
     /// or the session may behave unexpectedly.
@@ -408,22 +601,27 @@ impl Session {
         )
     }
 
+    /// Create a crawler, used to read the attributes and references of all nodes
+    /// in (part of) the address space into an offline model.
+    pub fn crawler(&self) -> Crawler<'_> {
+        Crawler::new(self)
+    }
+
     /// Return namespace array from server and store in namespace cache
     pub async fn read_namespace_array(&self) -> Result<NamespaceMap, Error> {
         let nodeid: NodeId = VariableId::Server_NamespaceArray.into();
         let result = self
             .read(
-                &[ReadValueId::from(nodeid)],
+                &[ReadValueId::from(nodeid.clone())],
                 TimestampsToReturn::Neither,
                 0.0,
             )
             .await
-            .map_err(|status_code| {
-                Error::new(status_code, "Reading Server namespace array failed")
-            })?;
+            .node_context(nodeid.clone())
+            .context("reading server namespace array")?;
         if let Some(Variant::Array(array)) = &result[0].value {
             let map = NamespaceMap::new_from_variant_array(&array.values)
-                .map_err(|e| Error::new(StatusCode::Bad, e))?;
+                .map_err(|e| Error::new(StatusCode::Bad, e).with_node_id(nodeid))?;
             let map_clone = map.clone();
             self.set_namespaces(map);
             Ok(map_clone)
@@ -431,7 +629,8 @@ impl Session {
             Err(Error::new(
                 StatusCode::BadNoValue,
                 format!("Server namespace array is None. The server has an issue {result:?}"),
-            ))
+            )
+            .with_node_id(nodeid))
         }
     }
 
@@ -446,7 +645,10 @@ impl Session {
         if let Some(idx) = self.get_namespace_index_from_cache(url) {
             return Ok(idx);
         };
-        let map = self.read_namespace_array().await?;
+        let map = self
+            .read_namespace_array()
+            .await
+            .context_with(|| format!("looking up namespace index of {url}"))?;
         let idx = map.get_index(url).ok_or_else(|| {
             Error::new(
                 StatusCode::BadNoMatch,
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, str::FromStr};
+
+use opcua_core::trace_lock;
+use opcua_types::{ExpandedNodeId, NodeId, StatusCode};
+
+use super::{session_debug, session_error, session_warn, ConnectionEvent, Session};
+
+impl Session {
+    /// Get the node ID with identifier `identifier` in the namespace with URI `namespace_uri`,
+    /// looking up the index of the namespace in the namespace array of the server.
+    ///
+    /// Namespace indices may change when the server restarts, so configure node IDs by
+    /// namespace URI and resolve them with this, instead of storing namespace indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace_uri` - The URI of the namespace, such as `http://mynamespace`.
+    /// * `identifier` - The identifier of the node ID without namespace,
+    ///   such as `s=Tag1` or `i=1001`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The node ID with the current index of the namespace.
+    /// * `Err(StatusCode)` - The identifier is invalid, or the server does not have the namespace.
+    ///
+    pub async fn resolve(
+        &self,
+        namespace_uri: &str,
+        identifier: &str,
+    ) -> Result<NodeId, StatusCode> {
+        let mut node_id = match NodeId::from_str(identifier) {
+            Ok(node_id) if node_id.namespace == 0 => node_id,
+            _ => {
+                session_error!(self, "Invalid node ID identifier {}", identifier);
+                return Err(StatusCode::BadNodeIdInvalid);
+            }
+        };
+        node_id.namespace = self.namespace_index(namespace_uri).await?;
+        Ok(node_id)
+    }
+
+    /// Get the node ID of an expanded node ID on this server, looking up the index of its
+    /// namespace URI in the namespace array of the server, if it has one.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The node ID with the current index of the namespace.
+    /// * `Err(StatusCode)` - The node ID refers to another server, or the server does not have
+    ///   the namespace.
+    ///
+    pub async fn resolve_node_id(&self, node_id: &ExpandedNodeId) -> Result<NodeId, StatusCode> {
+        if node_id.server_index != 0 {
+            session_error!(self, "Node ID {} refers to another server", node_id);
+            return Err(StatusCode::BadNodeIdUnknown);
+        }
+        let Some(uri) = node_id.namespace_uri.value() else {
+            return Ok(node_id.node_id.clone());
+        };
+        Ok(NodeId {
+            namespace: self.namespace_index(uri).await?,
+            identifier: node_id.node_id.identifier.clone(),
+        })
+    }
+
+    /// Get the URI of the namespace with the given index from the cached namespace array,
+    /// see [`Session::read_namespace_array`].
+    pub fn namespace_uri(&self, index: u16) -> Option<String> {
+        self.encoding_context()
+            .read()
+            .namespaces()
+            .known_namespaces()
+            .iter()
+            .find(|(_, idx)| **idx == index)
+            .map(|(uri, _)| uri.clone())
+    }
+
+    async fn namespace_index(&self, uri: &str) -> Result<u16, StatusCode> {
+        self.get_namespace_index(uri).await.map_err(|e| {
+            session_error!(self, "Failed to find namespace {}: {}", uri, e);
+            e.status()
+        })
+    }
+
+    /// Read the namespace array of the server after connecting. If namespaces moved to
+    /// other indices since the namespace array was last read, the node IDs of monitored
+    /// items are changed to the new indices before subscriptions are recreated.
+    pub(super) async fn update_namespaces(&self) {
+        let old = self.encoding_context().read().namespaces().clone();
+        let new = match self.read_namespace_array().await {
+            Ok(new) => new,
+            Err(e) => {
+                session_warn!(self, "Failed to read namespace array: {}", e);
+                return;
+            }
+        };
+
+        let mut remapped = HashMap::new();
+        for (uri, old_index) in old.known_namespaces() {
+            match new.get_index(uri) {
+                Some(new_index) if new_index != *old_index => {
+                    remapped.insert(*old_index, new_index);
+                }
+                Some(_) => {}
+                None => {
+                    session_warn!(self, "Namespace {} was removed from the server", uri);
+                }
+            }
+        }
+        if remapped.is_empty() {
+            return;
+        }
+
+        session_debug!(self, "Namespace indices changed, remapping {:?}", remapped);
+        trace_lock!(self.subscription_state).remap_namespaces(&remapped);
+        // Cached by method node ID.
+        self.method_input_arguments.write().clear();
+        self.read_cache.clear();
+        self.emit_connection_event(ConnectionEvent::NamespacesChanged { remapped });
+    }
+}
@@ -0,0 +1,115 @@
+use std::future::Future;
+
+use opcua_types::{NodeId, ReadValueId, StatusCode, TimestampsToReturn, VariableId, Variant};
+
+use super::{session_debug, session_error, session_warn, Session};
+
+/// Operation limits of the server, read from `Server/ServerCapabilities/OperationLimits`
+/// after connecting. A limit of `0` means that the server did not report one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationLimits {
+    /// Maximum number of nodes in a Read request.
+    pub max_nodes_per_read: u32,
+    /// Maximum number of nodes in a Write request.
+    pub max_nodes_per_write: u32,
+    /// Maximum number of nodes in a Browse request.
+    pub max_nodes_per_browse: u32,
+    /// Maximum number of methods in a Call request.
+    pub max_nodes_per_method_call: u32,
+    /// Maximum number of nodes in a HistoryRead request for data.
+    pub max_nodes_per_history_read_data: u32,
+    /// Maximum number of nodes in a HistoryRead request for events.
+    pub max_nodes_per_history_read_events: u32,
+}
+
+impl OperationLimits {
+    const NODES: [VariableId; 6] = [
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerRead,
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerWrite,
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerBrowse,
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerMethodCall,
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerHistoryReadData,
+        VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerHistoryReadEvents,
+    ];
+}
+
+impl Session {
+    /// Get the operation limits of the server. These are read after connecting
+    /// if `split_by_operation_limits` is enabled in the client config, otherwise
+    /// they are all `0`.
+    pub fn operation_limits(&self) -> OperationLimits {
+        *self.operation_limits.read()
+    }
+
+    /// Read the operation limits from the server. Limits that cannot be read are set to `0`.
+    pub(crate) async fn read_operation_limits(&self) {
+        let nodes_to_read = OperationLimits::NODES
+            .iter()
+            .map(|id| ReadValueId::from(NodeId::from(*id)))
+            .collect::<Vec<_>>();
+        let values = match self
+            .read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)
+            .await
+        {
+            Ok(values) => values,
+            Err(e) => {
+                session_warn!(self, "Failed to read operation limits: {}", e);
+                return;
+            }
+        };
+        let mut values = values.into_iter().map(|v| match v.value {
+            Some(Variant::UInt32(v)) => v,
+            _ => 0,
+        });
+        let mut next = || values.next().unwrap_or(0);
+        let limits = OperationLimits {
+            max_nodes_per_read: next(),
+            max_nodes_per_write: next(),
+            max_nodes_per_browse: next(),
+            max_nodes_per_method_call: next(),
+            max_nodes_per_history_read_data: next(),
+            max_nodes_per_history_read_events: next(),
+        };
+        session_debug!(self, "Server operation limits: {:?}", limits);
+        *self.operation_limits.write() = limits;
+    }
+
+    /// Send `items` in requests of at most `limit` items each, merging the results
+    /// in order. A `limit` of `0` sends everything in a single request.
+    pub(super) async fn send_in_chunks<T, R, Fut>(
+        &self,
+        items: Vec<T>,
+        limit: u32,
+        send: impl Fn(Vec<T>) -> Fut,
+    ) -> Result<Vec<R>, StatusCode>
+    where
+        Fut: Future<Output = Result<Vec<R>, StatusCode>>,
+    {
+        let limit = limit as usize;
+        if limit == 0 || items.len() <= limit {
+            return send(items).await;
+        }
+
+        let mut results = Vec::with_capacity(items.len());
+        let mut iter = items.into_iter();
+        loop {
+            let chunk = (&mut iter).take(limit).collect::<Vec<_>>();
+            if chunk.is_empty() {
+                break;
+            }
+            let len = chunk.len();
+            let chunk_results = send(chunk).await?;
+            if chunk_results.len() != len {
+                session_error!(
+                    self,
+                    "Expected {} results but got {}",
+                    len,
+                    chunk_results.len()
+                );
+                return Err(StatusCode::BadUnexpectedError);
+            }
+            results.extend(chunk_results);
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,249 @@
+use std::{ops::Deref, sync::Arc};
+
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{EndpointDescription, Error, StatusCode};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+use tracing::warn;
+
+use crate::IdentityToken;
+
+use super::{Client, Session, SessionEventLoop};
+
+type SessionFactory = dyn Fn() -> Result<(Arc<Session>, SessionEventLoop), Error> + Send + Sync;
+
+struct PoolSlot {
+    session: Arc<Session>,
+    event_loop: JoinHandle<StatusCode>,
+    checked_out: usize,
+}
+
+impl PoolSlot {
+    fn new(factory: &SessionFactory) -> Result<Self, Error> {
+        let (session, event_loop) = factory()?;
+        Ok(Self {
+            session,
+            event_loop: event_loop.spawn(),
+            checked_out: 0,
+        })
+    }
+
+    /// A session has failed once its event loop has stopped, which happens
+    /// when it cannot reconnect to the server.
+    fn is_failed(&self) -> bool {
+        self.event_loop.is_finished()
+    }
+}
+
+struct SessionPoolInner {
+    factory: Box<SessionFactory>,
+    slots: Mutex<Vec<PoolSlot>>,
+    permits: Arc<Semaphore>,
+}
+
+/// A pool of sessions to the same server.
+///
+/// Sessions are handed out with [`SessionPool::checkout`], which waits until a session
+/// is available and returns a guard that gives the session back to the pool when dropped.
+/// Each session may be checked out a limited number of times at once, and checkouts go to
+/// the session with the fewest active checkouts, spreading load over the pool. This is useful
+/// when sending many concurrent requests, which could otherwise hit limits the server
+/// places on each session.
+///
+/// Each session runs its own event loop. If a session stops because it could not reconnect
+/// to the server, it is replaced with a new session the next time a session is checked out.
+///
+/// The pool can be cloned cheaply, clones share the same sessions.
+#[derive(Clone)]
+pub struct SessionPool {
+    inner: Arc<SessionPoolInner>,
+}
+
+impl SessionPool {
+    /// Create a pool of `size` sessions, each created by calling `factory`. The event loop
+    /// of each session is spawned on the tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of sessions in the pool, must be at least 1.
+    /// * `max_checkouts_per_session` - The maximum number of times each session
+    ///   can be checked out at once, must be at least 1.
+    /// * `factory` - Function creating a new session and its event loop, for example by calling
+    ///   [`Client::connect_to_endpoint_directly`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SessionPool)` - The created pool.
+    /// * `Err(Error)` - `factory` failed, or `size` or `max_checkouts_per_session` is zero.
+    ///
+    pub fn new(
+        size: usize,
+        max_checkouts_per_session: usize,
+        factory: impl Fn() -> Result<(Arc<Session>, SessionEventLoop), Error> + Send + Sync + 'static,
+    ) -> Result<Self, Error> {
+        if size == 0 || max_checkouts_per_session == 0 {
+            return Err(Error::new(
+                StatusCode::BadInvalidArgument,
+                "Session pool size and checkouts per session must be at least 1",
+            ));
+        }
+        let factory: Box<SessionFactory> = Box::new(factory);
+        let slots = (0..size)
+            .map(|_| PoolSlot::new(&*factory))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            inner: Arc::new(SessionPoolInner {
+                factory,
+                slots: Mutex::new(slots),
+                permits: Arc::new(Semaphore::new(size * max_checkouts_per_session)),
+            }),
+        })
+    }
+
+    /// Create a pool of `size` sessions to an endpoint matching `endpoint`. `GetEndpoints` is
+    /// called once on the server to find the endpoint, then the sessions are created as with
+    /// [`Client::connect_to_matching_endpoint`].
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to create sessions.
+    /// * `endpoint` - Discovery endpoint, used to find the server endpoint to connect to.
+    /// * `user_identity_token` - Identity token to use for authentication on every session.
+    /// * `size` - The number of sessions in the pool, must be at least 1.
+    /// * `max_checkouts_per_session` - The maximum number of times each session
+    ///   can be checked out at once, must be at least 1.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SessionPool)` - The created pool.
+    /// * `Err(Error)` - No matching endpoint was found, or a session could not be created.
+    ///
+    pub async fn connect_to_matching_endpoint(
+        client: Client,
+        endpoint: impl Into<EndpointDescription>,
+        user_identity_token: IdentityToken,
+        size: usize,
+        max_checkouts_per_session: usize,
+    ) -> Result<Self, Error> {
+        let endpoint = endpoint.into();
+        let endpoints = client
+            .get_server_endpoints_from_url(endpoint.endpoint_url.as_ref())
+            .await?;
+
+        Self::new(size, max_checkouts_per_session, move || {
+            client
+                .session_builder()
+                .with_endpoints(endpoints.clone())
+                .connect_to_matching_endpoint(endpoint.clone())?
+                .user_identity_token(user_identity_token.clone())
+                .build(client.certificate_store().clone())
+        })
+    }
+
+    /// Check out a session from the pool, waiting until one is available.
+    ///
+    /// The session with the fewest active checkouts is chosen. Sessions that have
+    /// failed are replaced before a session is chosen. The session is returned to the
+    /// pool when the returned guard is dropped.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(PooledSession)` - A guard dereferencing to the checked out session.
+    /// * `Err(StatusCode)` - The pool is closed, or a failed session could not be replaced.
+    ///
+    pub async fn checkout(&self) -> Result<PooledSession, StatusCode> {
+        let permit = self
+            .inner
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| StatusCode::BadShutdown)?;
+
+        let mut slots = trace_lock!(self.inner.slots);
+        for slot in slots.iter_mut() {
+            if slot.is_failed() {
+                warn!("Session in pool has stopped, replacing it with a new session");
+                let mut new_slot = PoolSlot::new(&*self.inner.factory).map_err(|e| {
+                    warn!("Failed to replace session in pool: {e}");
+                    e.status()
+                })?;
+                // Checkouts of the old session are still counted until they are dropped.
+                new_slot.checked_out = slot.checked_out;
+                *slot = new_slot;
+            }
+        }
+
+        // The permits limit the number of checkouts, so there is always a
+        // session with fewer than the maximum number of checkouts.
+        let (index, slot) = slots
+            .iter_mut()
+            .enumerate()
+            .min_by_key(|(_, s)| s.checked_out)
+            .ok_or(StatusCode::BadShutdown)?;
+        slot.checked_out += 1;
+
+        Ok(PooledSession {
+            session: slot.session.clone(),
+            index,
+            pool: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Get every session currently in the pool, whether or not it is checked out.
+    pub fn sessions(&self) -> Vec<Arc<Session>> {
+        trace_lock!(self.inner.slots)
+            .iter()
+            .map(|s| s.session.clone())
+            .collect()
+    }
+
+    /// Close the pool, disconnecting every session. Sessions that are checked out
+    /// are disconnected as well, and later calls to [`SessionPool::checkout`] fail.
+    pub async fn close(&self) {
+        self.inner.permits.close();
+        for session in self.sessions() {
+            if let Err(e) = session.disconnect().await {
+                warn!("Failed to disconnect pooled session: {e}");
+            }
+        }
+    }
+}
+
+/// A session checked out from a [`SessionPool`]. Dereferences to [`Session`],
+/// and gives the session back to the pool when dropped.
+pub struct PooledSession {
+    session: Arc<Session>,
+    index: usize,
+    pool: Arc<SessionPoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledSession {
+    /// Get a shared reference to the session. This may outlive the checkout,
+    /// but is then no longer counted when balancing load over the pool.
+    pub fn session(&self) -> &Arc<Session> {
+        &self.session
+    }
+}
+
+impl Deref for PooledSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Self::Target {
+        &self.session
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        // If the session was replaced, the count was carried over to the new session.
+        if let Some(slot) = trace_lock!(self.pool.slots).get_mut(self.index) {
+            slot.checked_out = slot.checked_out.saturating_sub(1);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, time::Instant};
+
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{DataValue, NodeId, QualifiedName, ReadValueId, TimestampsToReturn};
+
+use crate::ReadCacheOptions;
+
+use super::Session;
+
+/// A value of `max_age` at or above this lets the server return any cached value,
+/// see OPC UA Part 4 - Services 5.10.2.
+const MAX_AGE_ANY: f64 = i32::MAX as f64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    node_id: NodeId,
+    attribute_id: u32,
+    index_range: String,
+    data_encoding: QualifiedName,
+    timestamps_to_return: i32,
+}
+
+impl CacheKey {
+    fn new(read: &ReadValueId, timestamps_to_return: TimestampsToReturn) -> Self {
+        Self {
+            node_id: read.node_id.clone(),
+            attribute_id: read.attribute_id,
+            index_range: read.index_range.to_string(),
+            data_encoding: read.data_encoding.clone(),
+            timestamps_to_return: timestamps_to_return as i32,
+        }
+    }
+}
+
+struct CacheEntry {
+    value: DataValue,
+    read_at: Instant,
+}
+
+/// Cache of read results, keyed by the node, attribute, index range and encoding read.
+/// Reads are answered from the cache when the cached value is no older than the
+/// `max_age` of the read, measured from when the client received it.
+pub(super) struct ReadCache {
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    max_entries: usize,
+}
+
+impl ReadCache {
+    pub(super) fn new(options: &ReadCacheOptions) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_entries: options.max_entries,
+        }
+    }
+
+    /// Whether reads with the given `max_age` may use the cache.
+    pub(super) fn is_usable(&self, max_age: f64) -> bool {
+        self.max_entries > 0 && max_age > 0.0
+    }
+
+    /// Get a cached value of `read` no older than `max_age` milliseconds.
+    pub(super) fn get(
+        &self,
+        read: &ReadValueId,
+        timestamps_to_return: TimestampsToReturn,
+        max_age: f64,
+    ) -> Option<DataValue> {
+        let entries = trace_lock!(self.entries);
+        let entry = entries.get(&CacheKey::new(read, timestamps_to_return))?;
+        let fresh =
+            max_age >= MAX_AGE_ANY || entry.read_at.elapsed().as_secs_f64() * 1000.0 <= max_age;
+        fresh.then(|| entry.value.clone())
+    }
+
+    /// Store the result of `read`. Only good results are cached, evicting the oldest
+    /// entry if the cache is full.
+    pub(super) fn insert(
+        &self,
+        read: &ReadValueId,
+        timestamps_to_return: TimestampsToReturn,
+        value: &DataValue,
+    ) {
+        if self.max_entries == 0 || value.status.is_some_and(|s| !s.is_good()) {
+            return;
+        }
+        let key = CacheKey::new(read, timestamps_to_return);
+        let mut entries = trace_lock!(self.entries);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.read_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                value: value.clone(),
+                read_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Remove cached values of an attribute of a node, for any index range.
+    pub(super) fn invalidate(&self, node_id: &NodeId, attribute_id: u32) {
+        if self.max_entries == 0 {
+            return;
+        }
+        trace_lock!(self.entries)
+            .retain(|k, _| k.node_id != *node_id || k.attribute_id != attribute_id);
+    }
+
+    pub(super) fn clear(&self) {
+        trace_lock!(self.entries).clear();
+    }
+}
+
+impl Session {
+    /// Remove all values from the read cache, so the next read of each value is sent
+    /// to the server. See [`ClientBuilder::read_cache_options`](crate::ClientBuilder::read_cache_options).
+    pub fn clear_read_cache(&self) {
+        self.read_cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_types::{
+        AttributeId, DataValue, NumericRange, ReadValueId, StatusCode, TimestampsToReturn,
+        VariableId,
+    };
+
+    use super::{ReadCache, MAX_AGE_ANY};
+    use crate::ReadCacheOptions;
+
+    fn cache(max_entries: usize) -> ReadCache {
+        ReadCache::new(&ReadCacheOptions { max_entries })
+    }
+
+    #[test]
+    fn max_age() {
+        let cache = cache(10);
+        let read = ReadValueId::new_value(VariableId::Server_ServiceLevel.into());
+        assert!(!cache.is_usable(0.0));
+        assert!(cache.is_usable(100.0));
+
+        cache.insert(&read, TimestampsToReturn::Both, &DataValue::new_now(5u8));
+        assert!(cache
+            .get(&read, TimestampsToReturn::Both, 10_000.0)
+            .is_some());
+        assert!(cache
+            .get(&read, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_some());
+        // Different timestamps are a different result.
+        assert!(cache
+            .get(&read, TimestampsToReturn::Neither, 10_000.0)
+            .is_none());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get(&read, TimestampsToReturn::Both, 10.0).is_none());
+        assert!(cache
+            .get(&read, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_some());
+    }
+
+    #[test]
+    fn insert_and_invalidate() {
+        let cache = cache(2);
+        let value = ReadValueId::new_value(VariableId::Server_ServiceLevel.into());
+        let range = ReadValueId {
+            index_range: NumericRange::Index(1),
+            ..value.clone()
+        };
+        let name = ReadValueId {
+            attribute_id: AttributeId::DisplayName as u32,
+            ..value.clone()
+        };
+
+        // Bad results are not cached.
+        cache.insert(
+            &value,
+            TimestampsToReturn::Both,
+            &DataValue::new_now_status(5u8, StatusCode::BadNotReadable),
+        );
+        assert!(cache
+            .get(&value, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_none());
+
+        cache.insert(&value, TimestampsToReturn::Both, &DataValue::new_now(5u8));
+        cache.insert(&range, TimestampsToReturn::Both, &DataValue::new_now(6u8));
+        // The cache is full, so the oldest entry is evicted.
+        cache.insert(&name, TimestampsToReturn::Both, &DataValue::new_now("Name"));
+        assert!(cache
+            .get(&value, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_none());
+        assert!(cache
+            .get(&range, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_some());
+
+        // Invalidating the value removes every index range, but not other attributes.
+        cache.invalidate(&value.node_id, AttributeId::Value as u32);
+        assert!(cache
+            .get(&range, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_none());
+        assert!(cache
+            .get(&name, TimestampsToReturn::Both, MAX_AGE_ANY)
+            .is_some());
+    }
+}
@@ -0,0 +1,213 @@
+use opcua_nodes::{new_node_from_attributes, NodeType};
+use opcua_types::{
+    AddNodeAttributes, AttributeId, GenericAttributes, NodeClass, NodeId, QualifiedName,
+    ReadValueId, StatusCode, TimestampsToReturn, Variant,
+};
+
+use super::{session_debug, Session};
+
+impl Session {
+    /// Read all attributes of a node. The node class is read first, then all attributes
+    /// defined for that node class are read in a single request, and returned as the
+    /// node type for the class.
+    ///
+    /// Optional attributes the server does not return, for example the value of a variable
+    /// the user is not allowed to read, are left at their default values.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to read.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeType)` - The node, with all attributes returned by the server.
+    /// * `Err(StatusCode)` - Request failed, or the node class could not be read,
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read_node(&self, node_id: &NodeId) -> Result<NodeType, StatusCode> {
+        let node_class = self
+            .read_node_classes(std::slice::from_ref(node_id))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(StatusCode::BadUnexpectedError)??;
+        self.read_nodes_of_class(&[(node_id.clone(), node_class)])
+            .await?
+            .pop()
+            .ok_or(StatusCode::BadUnexpectedError)
+    }
+
+    /// Read the node class of each node, with the status of the read if it failed.
+    pub(crate) async fn read_node_classes(
+        &self,
+        node_ids: &[NodeId],
+    ) -> Result<Vec<Result<NodeClass, StatusCode>>, StatusCode> {
+        let to_read: Vec<_> = node_ids
+            .iter()
+            .map(|id| ReadValueId {
+                node_id: id.clone(),
+                attribute_id: AttributeId::NodeClass as u32,
+                ..Default::default()
+            })
+            .collect();
+        let values = self
+            .read(&to_read, TimestampsToReturn::Neither, 0.0)
+            .await?;
+        if values.len() != node_ids.len() {
+            return Err(StatusCode::BadUnexpectedError);
+        }
+        Ok(values
+            .into_iter()
+            .map(|v| match v.value {
+                _ if v.status().is_bad() => Err(v.status()),
+                Some(Variant::Int32(c)) => {
+                    NodeClass::try_from(c).map_err(|_| StatusCode::BadNodeClassInvalid)
+                }
+                _ => Err(StatusCode::BadTypeMismatch),
+            })
+            .collect())
+    }
+
+    /// Read all attributes of nodes with known node classes.
+    pub(crate) async fn read_nodes_of_class(
+        &self,
+        nodes: &[(NodeId, NodeClass)],
+    ) -> Result<Vec<NodeType>, StatusCode> {
+        let to_read: Vec<_> = nodes
+            .iter()
+            .flat_map(|(id, class)| {
+                attributes_for_class(*class).iter().map(|a| ReadValueId {
+                    node_id: id.clone(),
+                    attribute_id: *a as u32,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let values = self
+            .read(&to_read, TimestampsToReturn::Neither, 0.0)
+            .await?;
+        if values.len() != to_read.len() {
+            return Err(StatusCode::BadUnexpectedError);
+        }
+        let mut values = values.into_iter();
+
+        let mut result = Vec::with_capacity(nodes.len());
+        for (id, class) in nodes {
+            let mut attributes = Vec::new();
+            let mut browse_name = QualifiedName::null();
+            for (attribute, value) in attributes_for_class(*class).iter().zip(values.by_ref()) {
+                if value.status().is_bad() {
+                    continue;
+                }
+                match (attribute, value.value) {
+                    (AttributeId::BrowseName, Some(Variant::QualifiedName(name))) => {
+                        browse_name = *name
+                    }
+                    (_, Some(value)) => attributes.push((*attribute, value)),
+                    (_, None) => (),
+                }
+            }
+
+            let mut node = new_node_from_attributes(
+                id.clone(),
+                browse_name,
+                *class,
+                AddNodeAttributes::Generic(GenericAttributes::default()),
+            )?;
+            for (attribute, value) in attributes {
+                if let Err(e) = node.as_mut_node().set_attribute(attribute, value) {
+                    session_debug!(self, "Ignoring attribute {attribute:?} of node {id}: {e}");
+                }
+            }
+            result.push(node);
+        }
+        Ok(result)
+    }
+}
+
+/// Get the attributes defined for a node class, except `NodeId` and `NodeClass`.
+fn attributes_for_class(node_class: NodeClass) -> &'static [AttributeId] {
+    match node_class {
+        NodeClass::Object => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::EventNotifier,
+        ],
+        NodeClass::Variable => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::Value,
+            AttributeId::DataType,
+            AttributeId::ValueRank,
+            AttributeId::ArrayDimensions,
+            AttributeId::AccessLevel,
+            AttributeId::UserAccessLevel,
+            AttributeId::MinimumSamplingInterval,
+            AttributeId::Historizing,
+        ],
+        NodeClass::Method => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::Executable,
+            AttributeId::UserExecutable,
+        ],
+        NodeClass::ObjectType => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::IsAbstract,
+        ],
+        NodeClass::VariableType => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::Value,
+            AttributeId::DataType,
+            AttributeId::ValueRank,
+            AttributeId::ArrayDimensions,
+            AttributeId::IsAbstract,
+        ],
+        NodeClass::ReferenceType => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::IsAbstract,
+            AttributeId::Symmetric,
+            AttributeId::InverseName,
+        ],
+        NodeClass::DataType => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::IsAbstract,
+            AttributeId::DataTypeDefinition,
+        ],
+        NodeClass::View => &[
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::Description,
+            AttributeId::WriteMask,
+            AttributeId::UserWriteMask,
+            AttributeId::ContainsNoLoops,
+            AttributeId::EventNotifier,
+        ],
+        NodeClass::Unspecified => &[],
+    }
+}
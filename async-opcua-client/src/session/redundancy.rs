@@ -0,0 +1,1020 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use arc_swap::ArcSwap;
+use futures::{stream::BoxStream, StreamExt};
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{
+    AttributeId, EndpointDescription, Error, MonitoredItemCreateRequest, ReadValueId,
+    RedundancySupport, StatusCode, TimestampsToReturn, VariableId, Variant,
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tracing::warn;
+
+use crate::IdentityToken;
+
+use super::{
+    services::subscriptions::Detached, session_debug, session_warn, Client, Session,
+    SessionEventLoop, Subscription,
+};
+
+/// Number of redundancy events buffered for each subscriber to
+/// [`RedundantSession::events`] before the oldest are dropped.
+const REDUNDANCY_EVENTS_CAPACITY: usize = 32;
+
+/// How a [`RedundantSession`] uses the backup servers of a non-transparent redundant
+/// server set, see OPC UA Part 4, 6.6.2.4.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverMode {
+    /// Only connect to a backup server once the active server fails.
+    /// Subscriptions are created on the backup server on failover.
+    Cold,
+    /// Keep sessions to the backup servers open, and monitor their service level,
+    /// so that failover only needs to create the subscriptions.
+    Warm,
+    /// Like [`FailoverMode::Warm`], but also keep copies of the subscriptions on the
+    /// backup servers, with publishing disabled. On failover, publishing is enabled on
+    /// the copies, so values sampled before failover are not lost.
+    Hot,
+}
+
+impl FailoverMode {
+    /// The failover mode for the redundancy support reported by a server,
+    /// or `None` if the server is not part of a non-transparent redundant server set.
+    pub fn from_redundancy_support(support: RedundancySupport) -> Option<Self> {
+        match support {
+            RedundancySupport::Cold => Some(Self::Cold),
+            RedundancySupport::Warm => Some(Self::Warm),
+            RedundancySupport::Hot | RedundancySupport::HotAndMirrored => Some(Self::Hot),
+            RedundancySupport::None | RedundancySupport::Transparent => None,
+        }
+    }
+}
+
+/// Options for a [`RedundantSession`].
+#[derive(Debug, Clone)]
+pub struct RedundancyOptions {
+    /// How the backup servers are used.
+    pub mode: FailoverMode,
+    /// How often the service level of the servers is read.
+    pub service_level_interval: Duration,
+    /// The lowest service level of a healthy server. The session fails over when the
+    /// service level of the active server drops below this, and only fails over to
+    /// servers with at least this service level. Servers report 200 and above when
+    /// healthy, and lower values when degraded or in maintenance.
+    pub min_service_level: u8,
+    /// How long the active server may be disconnected before failing over, and how
+    /// long to wait for a connection to a backup server in cold mode.
+    pub connect_timeout: Duration,
+}
+
+impl RedundancyOptions {
+    /// Default options for the given failover mode.
+    pub fn new(mode: FailoverMode) -> Self {
+        Self {
+            mode,
+            service_level_interval: Duration::from_secs(1),
+            min_service_level: 200,
+            connect_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Changes to the servers of a [`RedundantSession`]. Servers are identified by their
+/// index in the list of endpoints the session was created with.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum RedundancyEvent {
+    /// The service level of a server changed, `None` if the server is not connected,
+    /// or its service level could not be read.
+    ServiceLevelChanged {
+        /// The index of the server.
+        server: usize,
+        /// The new service level.
+        service_level: Option<u8>,
+    },
+    /// The session is failing over from the active server to a backup server.
+    FailoverStarted {
+        /// The index of the previously active server.
+        from: usize,
+        /// The index of the backup server.
+        to: usize,
+    },
+    /// The backup server is now the active server, and the subscriptions were moved to it.
+    FailoverCompleted {
+        /// The index of the previously active server.
+        from: usize,
+        /// The index of the new active server.
+        to: usize,
+    },
+    /// The backup server is now the active server, but some subscriptions could not be
+    /// created on it. They keep their monitored items and callbacks, and creating them
+    /// on the active server is retried every [`RedundancyOptions::service_level_interval`].
+    FailoverPartial {
+        /// The index of the previously active server.
+        from: usize,
+        /// The index of the new active server.
+        to: usize,
+        /// The IDs the subscriptions that could not be created had before the failover,
+        /// and the reason creating them failed.
+        failed: Vec<(u32, StatusCode)>,
+    },
+    /// Failing over to a backup server failed, the active server was not changed.
+    FailoverFailed {
+        /// The index of the backup server.
+        to: usize,
+        /// The reason for the failure.
+        reason: StatusCode,
+    },
+    /// The active server is unhealthy, and there is no healthy backup server to fail over to.
+    NoHealthyServer,
+    /// A server announced that it is shutting down. If it is the active server, the
+    /// session fails over to a backup server before the server goes away.
+    ShutdownAnnounced {
+        /// The index of the server.
+        server: usize,
+        /// The number of seconds until the server shuts down, if known.
+        seconds_till_shutdown: Option<u32>,
+    },
+}
+
+/// Redundancy information read from a server with [`Session::read_server_redundancy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerRedundancyInfo {
+    /// The kind of redundancy the server supports, [`RedundancySupport::None`] if the
+    /// server does not report it.
+    pub redundancy_support: RedundancySupport,
+    /// The URIs of the servers in the redundant server set, from `ServerUriArray`.
+    /// Empty if the server does not provide it.
+    pub server_uris: Vec<String>,
+    /// The current service level of the server.
+    pub service_level: u8,
+}
+
+impl Session {
+    /// Read the service level of the server, from 0 to 255. Servers report 200 and above
+    /// when healthy, and lower values when degraded or in maintenance.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u8)` - The service level of the server.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read_service_level(&self) -> Result<u8, StatusCode> {
+        let [service_level] = self
+            .read_server_values([VariableId::Server_ServiceLevel])
+            .await?;
+        match service_level? {
+            Variant::Byte(v) => Ok(v),
+            _ => Err(StatusCode::BadTypeMismatch),
+        }
+    }
+
+    /// Read the redundancy support, the URIs of the redundant servers, and the service level
+    /// of the server. Use this to find the failover mode and backup servers for a
+    /// [`RedundantSession`]. The endpoints of the backup servers can be found with
+    /// [`Client::find_servers`] and [`Client::get_server_endpoints_from_url`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ServerRedundancyInfo)` - The redundancy information of the server.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read_server_redundancy(&self) -> Result<ServerRedundancyInfo, StatusCode> {
+        let [redundancy_support, server_uris, service_level] = self
+            .read_server_values([
+                VariableId::Server_ServerRedundancy_RedundancySupport,
+                VariableId::Server_ServerRedundancy_ServerUriArray,
+                VariableId::Server_ServiceLevel,
+            ])
+            .await?;
+        // Servers that are not part of a redundant server set may not report this.
+        let redundancy_support = match redundancy_support {
+            Ok(Variant::Int32(v)) => {
+                RedundancySupport::try_from(v).map_err(|_| StatusCode::BadTypeMismatch)?
+            }
+            Ok(_) => return Err(StatusCode::BadTypeMismatch),
+            Err(_) => RedundancySupport::None,
+        };
+        // Only servers in a non-transparent redundant server set have a ServerUriArray.
+        let server_uris = match server_uris {
+            Ok(Variant::Array(arr)) => arr
+                .values
+                .into_iter()
+                .filter_map(|v| match v {
+                    Variant::String(s) if !s.is_null() => Some(s.as_ref().to_owned()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let service_level = match service_level? {
+            Variant::Byte(v) => v,
+            _ => return Err(StatusCode::BadTypeMismatch),
+        };
+        Ok(ServerRedundancyInfo {
+            redundancy_support,
+            server_uris,
+            service_level,
+        })
+    }
+
+    pub(super) async fn read_server_values<const N: usize>(
+        &self,
+        ids: [VariableId; N],
+    ) -> Result<[Result<Variant, StatusCode>; N], StatusCode> {
+        let nodes_to_read: Vec<_> = ids
+            .iter()
+            .map(|id| ReadValueId::new((*id).into(), AttributeId::Value))
+            .collect();
+        let values = self
+            .read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)
+            .await?;
+        let values: Vec<_> = values
+            .into_iter()
+            .map(|v| match v.status {
+                Some(status) if status.is_bad() => Err(status),
+                _ => v.value.ok_or(StatusCode::BadNoValue),
+            })
+            .collect();
+        values
+            .try_into()
+            .map_err(|_| StatusCode::BadUnexpectedError)
+    }
+}
+
+type SessionFactory =
+    dyn Fn(&EndpointDescription) -> Result<(Arc<Session>, SessionEventLoop), Error> + Send + Sync;
+
+/// A copy of a subscription on the active server, on a backup server in hot mode.
+struct Mirror {
+    subscription_id: u32,
+    client_handles: BTreeSet<u32>,
+}
+
+/// The parameters of a subscription on the active server, for creating its copies.
+struct SubscriptionParameters {
+    publishing_interval: Duration,
+    lifetime_count: u32,
+    max_keep_alive_count: u32,
+    max_notifications_per_publish: u32,
+    priority: u8,
+}
+
+struct ServerSlot {
+    endpoint: EndpointDescription,
+    session: Option<(Arc<Session>, JoinHandle<StatusCode>)>,
+    service_level: Option<u8>,
+    /// Whether the server announced that it is shutting down.
+    shutting_down: bool,
+    /// Copies of the subscriptions on the active server, by the ID of the subscription on
+    /// the active server. Only used in hot mode.
+    mirrors: HashMap<u32, Mirror>,
+}
+
+struct ActiveServer {
+    index: usize,
+    session: Arc<Session>,
+}
+
+/// State of the monitor task between checks.
+#[derive(Default)]
+struct MonitorState {
+    disconnected_since: Option<Instant>,
+    outage_reported: bool,
+}
+
+struct RedundantSessionInner {
+    factory: Box<SessionFactory>,
+    options: RedundancyOptions,
+    servers: Mutex<Vec<ServerSlot>>,
+    active: ArcSwap<ActiveServer>,
+    events_tx: broadcast::Sender<RedundancyEvent>,
+    /// Held while failing over or updating subscription copies, so they never overlap.
+    failover_lock: tokio::sync::Mutex<()>,
+    /// Subscriptions that could not be created on the active server on failover.
+    failed_subscriptions: Mutex<Vec<Subscription>>,
+    monitor: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// A session to a set of non-transparent redundant servers, which fails over to a backup
+/// server when the active server fails, see OPC UA Part 4, 6.6.2.
+///
+/// The first server in the list is active initially. The service level of the servers is
+/// monitored, and the session fails over to the backup server with the highest service
+/// level when the active server stays disconnected for longer than
+/// [`RedundancyOptions::connect_timeout`], its service level drops below
+/// [`RedundancyOptions::min_service_level`], or it announces that it is shutting down.
+/// How the backup servers are used before that depends on the [`FailoverMode`].
+///
+/// On failover, the subscriptions on the active session are moved to the session of the
+/// backup server, keeping their callbacks and the client handles of their monitored items.
+/// Subscription IDs and monitored item IDs change, so look up items by client handle.
+///
+/// Always use [`RedundantSession::session`] to get the active session for requests, and
+/// subscribe to [`RedundantSession::events`] to be told about failovers. Each session
+/// runs its own event loop, and should use a reconnect strategy that does not give up.
+///
+/// The redundant session can be cloned cheaply, clones share the same sessions.
+#[derive(Clone)]
+pub struct RedundantSession {
+    inner: Arc<RedundantSessionInner>,
+}
+
+impl RedundantSession {
+    /// Create a redundant session to the servers with the given endpoints, creating each
+    /// session by calling `factory`. The event loop of each session, and a task monitoring
+    /// the servers, are spawned on the tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoints` - The endpoints of the servers, starting with the server to use first.
+    /// * `options` - How to use the backup servers, and when to fail over.
+    /// * `factory` - Function creating a new session to an endpoint and its event loop,
+    ///   for example by calling [`Client::connect_to_endpoint_directly`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RedundantSession)` - The redundant session.
+    /// * `Err(Error)` - `factory` failed, or `endpoints` is empty.
+    ///
+    pub fn new(
+        endpoints: Vec<EndpointDescription>,
+        options: RedundancyOptions,
+        factory: impl Fn(&EndpointDescription) -> Result<(Arc<Session>, SessionEventLoop), Error>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<Self, Error> {
+        if endpoints.is_empty() {
+            return Err(Error::new(
+                StatusCode::BadInvalidArgument,
+                "A redundant session needs at least one server",
+            ));
+        }
+        let factory: Box<SessionFactory> = Box::new(factory);
+        let mut servers = Vec::with_capacity(endpoints.len());
+        for (index, endpoint) in endpoints.into_iter().enumerate() {
+            let session = if index == 0 || options.mode != FailoverMode::Cold {
+                let (session, event_loop) = factory(&endpoint)?;
+                Some((session, event_loop.spawn()))
+            } else {
+                None
+            };
+            servers.push(ServerSlot {
+                endpoint,
+                session,
+                service_level: None,
+                shutting_down: false,
+                mirrors: HashMap::new(),
+            });
+        }
+        let active = ActiveServer {
+            index: 0,
+            session: servers[0].session.as_ref().unwrap().0.clone(),
+        };
+        let (events_tx, _) = broadcast::channel(REDUNDANCY_EVENTS_CAPACITY);
+
+        let inner = Arc::new(RedundantSessionInner {
+            factory,
+            options,
+            servers: Mutex::new(servers),
+            active: ArcSwap::new(Arc::new(active)),
+            events_tx,
+            failover_lock: tokio::sync::Mutex::new(()),
+            failed_subscriptions: Mutex::new(Vec::new()),
+            monitor: Mutex::new(None),
+        });
+        let monitor = tokio::task::spawn(Self::monitor(Arc::downgrade(&inner)));
+        *trace_lock!(inner.monitor) = Some(monitor);
+        Ok(Self { inner })
+    }
+
+    /// Create a redundant session to the servers with the given endpoints, creating each
+    /// session as with [`Client::connect_to_endpoint_directly`].
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to create sessions.
+    /// * `endpoints` - The endpoints of the servers, starting with the server to use first.
+    /// * `user_identity_token` - Identity token to use for authentication on every server.
+    /// * `options` - How to use the backup servers, and when to fail over.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RedundantSession)` - The redundant session.
+    /// * `Err(Error)` - An endpoint is invalid, or `endpoints` is empty.
+    ///
+    pub fn connect(
+        client: Client,
+        endpoints: Vec<EndpointDescription>,
+        user_identity_token: IdentityToken,
+        options: RedundancyOptions,
+    ) -> Result<Self, Error> {
+        Self::new(endpoints, options, move |endpoint| {
+            client
+                .session_builder()
+                .connect_to_endpoint_directly(endpoint.clone())?
+                .user_identity_token(user_identity_token.clone())
+                .build(client.certificate_store().clone())
+        })
+    }
+
+    /// Get the session to the active server. This changes on failover, so get the
+    /// session again for each use instead of keeping it.
+    pub fn session(&self) -> Arc<Session> {
+        self.inner.active.load().session.clone()
+    }
+
+    /// Get the index of the active server in the list of endpoints.
+    pub fn active_server(&self) -> usize {
+        self.inner.active.load().index
+    }
+
+    /// Get the endpoints of the servers.
+    pub fn endpoints(&self) -> Vec<EndpointDescription> {
+        trace_lock!(self.inner.servers)
+            .iter()
+            .map(|s| s.endpoint.clone())
+            .collect()
+    }
+
+    /// Get the last read service level of each server, `None` for servers that are not
+    /// connected, or whose service level could not be read.
+    pub fn service_levels(&self) -> Vec<Option<u8>> {
+        trace_lock!(self.inner.servers)
+            .iter()
+            .map(|s| s.service_level)
+            .collect()
+    }
+
+    /// Get a stream of changes to the servers, see [`RedundancyEvent`].
+    ///
+    /// The stream only yields events that happen after this is called. If the stream
+    /// is not polled fast enough, the oldest events are skipped.
+    pub fn events(&self) -> BoxStream<'static, RedundancyEvent> {
+        futures::stream::unfold(self.inner.events_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Redundancy event stream lagged, skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Fail over to the server with the given index, regardless of its service level,
+    /// for example to take the active server down for maintenance.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The server is now the active server. Subscriptions that could not be
+    ///   moved to it are reported with [`RedundancyEvent::FailoverPartial`].
+    /// * `Err(StatusCode)` - The index is invalid, or the server could not be connected to.
+    ///
+    pub async fn failover(&self, server: usize) -> Result<(), StatusCode> {
+        if server >= trace_lock!(self.inner.servers).len() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let _guard = self.inner.failover_lock.lock().await;
+        self.inner.failover_to(server).await
+    }
+
+    /// Stop monitoring the servers, and disconnect every session.
+    pub async fn close(&self) {
+        if let Some(monitor) = trace_lock!(self.inner.monitor).take() {
+            monitor.abort();
+        }
+        let _guard = self.inner.failover_lock.lock().await;
+        let sessions: Vec<_> = trace_lock!(self.inner.servers)
+            .iter()
+            .filter_map(|s| s.session.as_ref().map(|(s, _)| s.clone()))
+            .collect();
+        for session in sessions {
+            if let Err(e) = session.disconnect().await {
+                warn!("Failed to disconnect redundant session: {e}");
+            }
+        }
+    }
+
+    async fn monitor(inner: Weak<RedundantSessionInner>) {
+        let Some(interval) = inner.upgrade().map(|i| i.options.service_level_interval) else {
+            return;
+        };
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut state = MonitorState::default();
+        loop {
+            ticker.tick().await;
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            inner.check(&mut state).await;
+        }
+    }
+}
+
+impl RedundantSessionInner {
+    fn emit(&self, event: RedundancyEvent) {
+        // Fails only if there are no subscribers.
+        let _ = self.events_tx.send(event);
+    }
+
+    /// Update the service levels, fail over if the active server is unhealthy,
+    /// and update the copies of the subscriptions in hot mode.
+    async fn check(&self, state: &mut MonitorState) {
+        let _guard = self.failover_lock.lock().await;
+        self.update_service_levels().await;
+
+        let active = self.active.load_full();
+        let (active_level, shutting_down, event_loop_finished) = {
+            let servers = trace_lock!(self.servers);
+            let slot = &servers[active.index];
+            (
+                slot.service_level,
+                slot.shutting_down,
+                slot.session
+                    .as_ref()
+                    .is_none_or(|(_, event_loop)| event_loop.is_finished()),
+            )
+        };
+        if active.session.is_connected() {
+            state.disconnected_since = None;
+        } else {
+            state.disconnected_since.get_or_insert_with(Instant::now);
+        }
+        let unhealthy = event_loop_finished
+            || shutting_down
+            || state
+                .disconnected_since
+                .is_some_and(|t| t.elapsed() >= self.options.connect_timeout)
+            || active_level.is_some_and(|l| l < self.options.min_service_level);
+
+        if unhealthy {
+            match self.find_backup(active.index).await {
+                Some(to) => {
+                    let res = self.failover_to(to).await;
+                    if res.is_ok() {
+                        state.disconnected_since = None;
+                        state.outage_reported = false;
+                    }
+                }
+                None if !state.outage_reported => {
+                    warn!(
+                        "Active server {} is unhealthy, and there is no healthy backup server",
+                        active.index
+                    );
+                    state.outage_reported = true;
+                    self.emit(RedundancyEvent::NoHealthyServer);
+                }
+                None => {}
+            }
+        } else {
+            state.outage_reported = false;
+        }
+
+        self.recreate_failed_subscriptions().await;
+
+        if self.options.mode == FailoverMode::Hot {
+            self.sync_mirrors().await;
+        }
+    }
+
+    /// Read the service level of every connected server. In warm and hot mode, sessions
+    /// to backup servers that stopped are replaced.
+    async fn update_service_levels(&self) {
+        let active = self.active.load().index;
+        let sessions: Vec<_> = {
+            let mut servers = trace_lock!(self.servers);
+            for (index, slot) in servers.iter_mut().enumerate() {
+                let failed = slot
+                    .session
+                    .as_ref()
+                    .is_some_and(|(_, event_loop)| event_loop.is_finished());
+                if index == active || !failed {
+                    continue;
+                }
+                slot.session = None;
+                slot.mirrors.clear();
+                if self.options.mode != FailoverMode::Cold {
+                    warn!("Session to backup server {index} has stopped, replacing it");
+                    match (self.factory)(&slot.endpoint) {
+                        Ok((session, event_loop)) => {
+                            slot.session = Some((session, event_loop.spawn()))
+                        }
+                        Err(e) => warn!("Failed to replace session to backup server: {e}"),
+                    }
+                }
+            }
+            servers
+                .iter()
+                .map(|s| s.session.as_ref().map(|(s, _)| s.clone()))
+                .collect()
+        };
+
+        for (index, session) in sessions.into_iter().enumerate() {
+            let status = match session {
+                Some(session) if session.is_connected() => session.read_server_status().await.ok(),
+                _ => None,
+            };
+            let service_level = status.as_ref().map(|s| s.service_level);
+            let shutting_down = status.as_ref().is_some_and(|s| s.shutdown_announced());
+            let (changed, announced) = {
+                let mut servers = trace_lock!(self.servers);
+                let slot = &mut servers[index];
+                let changed = slot.service_level != service_level;
+                slot.service_level = service_level;
+                // A server that stopped responding may have shut down, keep avoiding it
+                // until its status can be read again.
+                let announced = shutting_down && !slot.shutting_down;
+                if status.is_some() {
+                    slot.shutting_down = shutting_down;
+                }
+                (changed, announced)
+            };
+            if announced {
+                warn!("Server {index} announced that it is shutting down");
+                self.emit(RedundancyEvent::ShutdownAnnounced {
+                    server: index,
+                    seconds_till_shutdown: status.and_then(|s| s.seconds_till_shutdown),
+                });
+            }
+            if changed {
+                self.emit(RedundancyEvent::ServiceLevelChanged {
+                    server: index,
+                    service_level,
+                });
+            }
+        }
+    }
+
+    /// Find a healthy backup server to fail over to. In warm and hot mode this is the
+    /// backup server with the highest service level. In cold mode, the backup servers
+    /// are connected to in order until a healthy one is found.
+    async fn find_backup(&self, active: usize) -> Option<usize> {
+        let min_service_level = self.options.min_service_level;
+        if self.options.mode != FailoverMode::Cold {
+            return trace_lock!(self.servers)
+                .iter()
+                .enumerate()
+                .filter(|(index, s)| *index != active && !s.shutting_down)
+                .filter_map(|(index, s)| s.service_level.map(|l| (index, l)))
+                .filter(|(_, l)| *l >= min_service_level)
+                .max_by_key(|(_, l)| *l)
+                .map(|(index, _)| index);
+        }
+
+        let count = trace_lock!(self.servers).len();
+        for index in (1..count).map(|offset| (active + offset) % count) {
+            let session = match self.connect_server(index).await {
+                Ok(session) => session,
+                Err(e) => {
+                    warn!("Failed to connect to backup server {index}: {e}");
+                    continue;
+                }
+            };
+            let status = session.read_server_status().await.ok();
+            let service_level = status.as_ref().map(|s| s.service_level);
+            let shutting_down = status.as_ref().is_some_and(|s| s.shutdown_announced());
+            {
+                let mut servers = trace_lock!(self.servers);
+                servers[index].service_level = service_level;
+                servers[index].shutting_down = shutting_down;
+            }
+            if !shutting_down && service_level.is_some_and(|l| l >= min_service_level) {
+                return Some(index);
+            }
+            self.disconnect_server(index).await;
+        }
+        None
+    }
+
+    /// Get the session to a server, creating it if necessary, and wait until it is connected.
+    async fn connect_server(&self, index: usize) -> Result<Arc<Session>, StatusCode> {
+        let session = {
+            let mut servers = trace_lock!(self.servers);
+            let slot = &mut servers[index];
+            match &slot.session {
+                Some((session, _)) => session.clone(),
+                None => {
+                    let (session, event_loop) =
+                        (self.factory)(&slot.endpoint).map_err(|e| e.status())?;
+                    slot.session = Some((session.clone(), event_loop.spawn()));
+                    session
+                }
+            }
+        };
+        tokio::time::timeout(self.options.connect_timeout, session.wait_for_connection())
+            .await
+            .map_err(|_| StatusCode::BadTimeout)?;
+        Ok(session)
+    }
+
+    /// Disconnect the session to a server, and stop its event loop.
+    async fn disconnect_server(&self, index: usize) {
+        let Some((session, event_loop)) = trace_lock!(self.servers)[index].session.take() else {
+            return;
+        };
+        if tokio::time::timeout(self.options.connect_timeout, session.disconnect())
+            .await
+            .is_err()
+        {
+            session_debug!(session, "Timed out disconnecting from server {}", index);
+        }
+        event_loop.abort();
+    }
+
+    /// Make the server with the given index the active server, moving the subscriptions to it.
+    /// The failover lock must be held.
+    async fn failover_to(&self, to: usize) -> Result<(), StatusCode> {
+        let from = self.active.load_full();
+        if from.index == to {
+            return Ok(());
+        }
+        warn!("Failing over from server {} to server {}", from.index, to);
+        self.emit(RedundancyEvent::FailoverStarted {
+            from: from.index,
+            to,
+        });
+
+        let target = match self.connect_server(to).await {
+            Ok(target) => target,
+            Err(reason) => {
+                warn!("Failover to server {to} failed: {reason}");
+                self.emit(RedundancyEvent::FailoverFailed { to, reason });
+                return Err(reason);
+            }
+        };
+
+        let moved_ids = trace_lock!(from.session.subscription_state)
+            .subscription_ids()
+            .unwrap_or_default();
+        let mut mirrors = std::mem::take(&mut trace_lock!(self.servers)[to].mirrors);
+
+        let mut to_enable = Vec::new();
+        // Subscriptions that failed to move on an earlier failover are tried again.
+        let mut to_recreate = std::mem::take(&mut *trace_lock!(self.failed_subscriptions));
+        let mut stale = Vec::new();
+        for &subscription_id in &moved_ids {
+            // Each subscription is only taken from the old session once it is moved.
+            let Some(mut subscription) =
+                trace_lock!(from.session.subscription_state).delete_subscription(subscription_id)
+            else {
+                continue;
+            };
+            let mirror = match mirrors.remove(&subscription.subscription_id()) {
+                Some(m) if m.client_handles == client_handles(&subscription) => m,
+                Some(m) => {
+                    stale.push(m.subscription_id);
+                    to_recreate.push(subscription);
+                    continue;
+                }
+                None => {
+                    to_recreate.push(subscription);
+                    continue;
+                }
+            };
+            let mut state = trace_lock!(target.subscription_state);
+            match state.get_mut(mirror.subscription_id) {
+                Some(copy) => {
+                    copy.replace_callback(subscription.replace_callback(Box::new(Detached)));
+                    if subscription.publishing_enabled() {
+                        to_enable.push(mirror.subscription_id);
+                    }
+                }
+                None => to_recreate.push(subscription),
+            }
+        }
+
+        // Copies of subscriptions that no longer exist on the active server.
+        stale.extend(mirrors.into_values().map(|m| m.subscription_id));
+        if !stale.is_empty() {
+            let _ = target.delete_subscriptions(&stale).await;
+        }
+        if !to_enable.is_empty() {
+            if let Err(e) = target.set_publishing_mode(&to_enable, true).await {
+                session_warn!(target, "Failed to enable publishing after failover: {}", e);
+            }
+        }
+        let mut failed = Vec::new();
+        for subscription in to_recreate {
+            let subscription_id = subscription.subscription_id();
+            if let Err((e, subscription)) = target.recreate_subscription(subscription).await {
+                failed.push((subscription_id, e));
+                trace_lock!(self.failed_subscriptions).push(*subscription);
+            }
+        }
+
+        self.active.store(Arc::new(ActiveServer {
+            index: to,
+            session: target,
+        }));
+
+        if self.options.mode == FailoverMode::Cold {
+            self.disconnect_server(from.index).await;
+        } else {
+            // The old server stays a backup server, the moved subscriptions are deleted on it
+            // in the background, in case it does not respond.
+            let old = from.session.clone();
+            tokio::task::spawn(async move {
+                if !moved_ids.is_empty() {
+                    let _ = old.delete_subscriptions(&moved_ids).await;
+                }
+            });
+        }
+
+        // Copies on other backup servers are of the subscriptions on the old active server.
+        if self.options.mode == FailoverMode::Hot {
+            let stale: Vec<_> = {
+                let mut servers = trace_lock!(self.servers);
+                servers
+                    .iter_mut()
+                    .filter_map(|s| {
+                        let mirrors = std::mem::take(&mut s.mirrors);
+                        let (session, _) = s.session.as_ref()?;
+                        Some((
+                            session.clone(),
+                            mirrors
+                                .into_values()
+                                .map(|m| m.subscription_id)
+                                .collect::<Vec<_>>(),
+                        ))
+                    })
+                    .filter(|(_, ids)| !ids.is_empty())
+                    .collect()
+            };
+            for (session, ids) in stale {
+                tokio::task::spawn(async move {
+                    let _ = session.delete_subscriptions(&ids).await;
+                });
+            }
+        }
+
+        if failed.is_empty() {
+            warn!("Failed over from server {} to server {}", from.index, to);
+            self.emit(RedundancyEvent::FailoverCompleted {
+                from: from.index,
+                to,
+            });
+        } else {
+            warn!(
+                "Failed over from server {} to server {}, {} subscriptions could not be moved",
+                from.index,
+                to,
+                failed.len()
+            );
+            self.emit(RedundancyEvent::FailoverPartial {
+                from: from.index,
+                to,
+                failed,
+            });
+        }
+        Ok(())
+    }
+
+    /// Try again to create the subscriptions that could not be created on the active
+    /// server on failover.
+    async fn recreate_failed_subscriptions(&self) {
+        let active = self.active.load_full();
+        if !active.session.is_connected() {
+            return;
+        }
+        let failed = std::mem::take(&mut *trace_lock!(self.failed_subscriptions));
+        for subscription in failed {
+            if let Err((_, subscription)) = active.session.recreate_subscription(subscription).await
+            {
+                trace_lock!(self.failed_subscriptions).push(*subscription);
+            }
+        }
+    }
+
+    /// In hot mode, create copies of the subscriptions on the active server on every
+    /// connected backup server, with publishing disabled.
+    async fn sync_mirrors(&self) {
+        let active = self.active.load_full();
+        let sources: Vec<_> = {
+            let state = trace_lock!(active.session.subscription_state);
+            state
+                .subscription_ids()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| state.get(id))
+                .map(|s| {
+                    let items: Vec<MonitoredItemCreateRequest> = s
+                        .monitored_items()
+                        .values()
+                        .map(|i| i.create_request())
+                        .collect();
+                    let parameters = SubscriptionParameters {
+                        publishing_interval: s.publishing_interval(),
+                        lifetime_count: s.lifetime_count(),
+                        max_keep_alive_count: s.max_keep_alive_count(),
+                        max_notifications_per_publish: s.max_notifications_per_publish(),
+                        priority: s.priority(),
+                    };
+                    (s.subscription_id(), client_handles(s), parameters, items)
+                })
+                .collect()
+        };
+
+        let backups: Vec<_> = trace_lock!(self.servers)
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != active.index)
+            .filter_map(|(index, s)| {
+                let (session, _) = s.session.as_ref()?;
+                session.is_connected().then(|| (index, session.clone()))
+            })
+            .collect();
+
+        for (index, backup) in backups {
+            let mut mirrors = std::mem::take(&mut trace_lock!(self.servers)[index].mirrors);
+            // The backup session only holds copies. Drop copies that were lost, for example
+            // because the backup session was recreated, and delete unknown subscriptions.
+            let existing = trace_lock!(backup.subscription_state)
+                .subscription_ids()
+                .unwrap_or_default();
+            mirrors.retain(|_, m| existing.contains(&m.subscription_id));
+            let mut to_delete: Vec<_> = existing
+                .into_iter()
+                .filter(|id| !mirrors.values().any(|m| m.subscription_id == *id))
+                .collect();
+            mirrors.retain(|source_id, m| {
+                let keep = sources
+                    .iter()
+                    .any(|(id, handles, _, _)| id == source_id && *handles == m.client_handles);
+                if !keep {
+                    to_delete.push(m.subscription_id);
+                }
+                keep
+            });
+            if !to_delete.is_empty() {
+                let _ = backup.delete_subscriptions(&to_delete).await;
+            }
+
+            for (source_id, handles, parameters, items) in &sources {
+                if mirrors.contains_key(source_id) {
+                    continue;
+                }
+                let subscription_id = match backup
+                    .create_subscription(
+                        parameters.publishing_interval,
+                        parameters.lifetime_count,
+                        parameters.max_keep_alive_count,
+                        parameters.max_notifications_per_publish,
+                        parameters.priority,
+                        false,
+                        Detached,
+                    )
+                    .await
+                {
+                    Ok(id) => id,
+                    Err(e) => {
+                        session_warn!(backup, "Failed to copy subscription to backup: {}", e);
+                        continue;
+                    }
+                };
+                if !items.is_empty() {
+                    if let Err(e) = backup
+                        .create_monitored_items(
+                            subscription_id,
+                            TimestampsToReturn::Both,
+                            items.clone(),
+                        )
+                        .await
+                    {
+                        session_warn!(backup, "Failed to copy monitored items to backup: {}", e);
+                        let _ = backup.delete_subscriptions(&[subscription_id]).await;
+                        continue;
+                    }
+                }
+                mirrors.insert(
+                    *source_id,
+                    Mirror {
+                        subscription_id,
+                        client_handles: handles.clone(),
+                    },
+                );
+            }
+            trace_lock!(self.servers)[index].mirrors = mirrors;
+        }
+    }
+}
+
+fn client_handles(subscription: &Subscription) -> BTreeSet<u32> {
+    subscription
+        .monitored_items()
+        .values()
+        .map(|i| i.client_handle())
+        .collect()
+}
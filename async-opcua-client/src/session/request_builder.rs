@@ -33,7 +33,7 @@ impl RequestHeaderBuilder {
     pub(super) fn new_from_session(session: &Session) -> Self {
         Self {
             header: session.make_request_header(),
-            timeout: session.request_timeout,
+            timeout: session.request_timeout(),
             session_id: session.session_id(),
         }
     }
@@ -1,11 +1,19 @@
-use std::time::Duration;
+use std::{
+    sync::{atomic::Ordering, Weak},
+    time::Duration,
+};
 
+use async_trait::async_trait;
 use futures::FutureExt;
-use opcua_types::StatusCode;
+use opcua_types::{NodeId, StatusCode};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::retry::ExponentialBackoff;
+use crate::{
+    retry::ExponentialBackoff,
+    transport::{is_session_error, SessionRecovery},
+};
 
-use super::{session_debug, Session, UARequest};
+use super::{session_debug, session_warn, ConnectionEvent, Session, UARequest};
 
 /// Trait for generic retry policies, used with [`Session::send_with_retry`].
 /// For simple use cases you can use [`DefaultRetryPolicy`].
@@ -157,3 +165,70 @@ impl Session {
         }
     }
 }
+
+#[async_trait]
+impl SessionRecovery for Weak<Session> {
+    async fn recover(
+        &self,
+        status: StatusCode,
+        auth_token: &NodeId,
+        timeout: Duration,
+    ) -> Result<(), StatusCode> {
+        let Some(session) = self.upgrade() else {
+            return Err(StatusCode::BadSessionClosed);
+        };
+        tokio::time::timeout(timeout, session.recover_for_retry(status, auth_token))
+            .await
+            .map_err(|_| StatusCode::BadTimeout)?
+    }
+}
+
+impl Session {
+    /// Restore the session after a request sent with `auth_token` failed with `status`,
+    /// so that the request can be retried.
+    ///
+    /// If the server rejected the session, it is re-activated. If that fails too, the
+    /// connection is closed, and the session event loop creates a new session when it
+    /// reconnects. Otherwise, this waits until the session is connected.
+    async fn recover_for_retry(
+        &self,
+        status: StatusCode,
+        auth_token: &NodeId,
+    ) -> Result<(), StatusCode> {
+        // The session will not reconnect after it was disconnected on purpose.
+        if !self.should_reconnect.load(Ordering::Relaxed) {
+            return Err(status);
+        }
+        if !is_session_error(status) || !self.is_connected() {
+            self.wait_for_connection().await;
+            return Ok(());
+        }
+        // Another request may have restored the session already.
+        if self.channel.auth_token().as_ref() != auth_token {
+            return Ok(());
+        }
+
+        session_debug!(self, "Re-activating session after {status}");
+        let Err(e) = self.activate_session().await else {
+            return Ok(());
+        };
+        session_warn!(
+            self,
+            "Could not re-activate session ({e}), reconnecting to create a new session"
+        );
+        let mut events = self.connection_events_tx.subscribe();
+        self.channel.close_channel().await;
+        loop {
+            match events.recv().await {
+                Ok(ConnectionEvent::Connected) => return Ok(()),
+                Ok(ConnectionEvent::Disconnected { reason })
+                    if !self.should_reconnect.load(Ordering::Relaxed) =>
+                {
+                    return Err(reason)
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => return Err(status),
+            }
+        }
+    }
+}
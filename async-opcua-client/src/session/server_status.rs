@@ -0,0 +1,368 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use futures::{stream::BoxStream, StreamExt};
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{LocalizedText, ServerState, StatusCode, VariableId, Variant};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tracing::warn;
+
+use super::{session_warn, Session};
+
+/// Number of server status events buffered for each subscriber to
+/// [`ServerStatusMonitor::events`] before the oldest are dropped.
+const SERVER_STATUS_EVENTS_CAPACITY: usize = 32;
+
+/// Status of a server, read with [`Session::read_server_status`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatusInfo {
+    /// The state of the server.
+    pub state: ServerState,
+    /// The service level of the server, from 0 to 255. Servers report 200 and above
+    /// when healthy, and lower values when degraded or in maintenance.
+    pub service_level: u8,
+    /// The number of seconds until the server shuts down, `None` if no shutdown
+    /// is scheduled.
+    pub seconds_till_shutdown: Option<u32>,
+    /// The reason the server is shutting down, if it gives one.
+    pub shutdown_reason: Option<LocalizedText>,
+}
+
+impl ServerStatusInfo {
+    /// Whether the server is shutting down, or has announced that it will.
+    pub fn shutdown_announced(&self) -> bool {
+        self.state == ServerState::Shutdown || self.seconds_till_shutdown.is_some()
+    }
+}
+
+/// Changes to the status of a server, reported by a [`ServerStatusMonitor`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ServerStatusEvent {
+    /// The state of the server changed. `previous` is `None` the first time the
+    /// status is read.
+    StateChanged {
+        /// The previous state.
+        previous: Option<ServerState>,
+        /// The new state.
+        state: ServerState,
+    },
+    /// The service level of the server changed. `previous` is `None` the first time
+    /// the status is read.
+    ServiceLevelChanged {
+        /// The previous service level.
+        previous: Option<u8>,
+        /// The new service level.
+        service_level: u8,
+    },
+    /// The server announced that it is shutting down. Move work to another server
+    /// before it goes away.
+    ShutdownAnnounced {
+        /// The number of seconds until the server shuts down, if known.
+        seconds_till_shutdown: Option<u32>,
+        /// The reason the server is shutting down, if it gives one.
+        reason: Option<LocalizedText>,
+    },
+    /// The status of the server could not be read, because the session is not
+    /// connected or the read failed. Reported once, until the status can be read again.
+    Unavailable(StatusCode),
+}
+
+type ServerStatusCallback = dyn Fn(&ServerStatusEvent) + Send + Sync;
+
+struct MonitorShared {
+    status: Mutex<Option<ServerStatusInfo>>,
+    events_tx: broadcast::Sender<ServerStatusEvent>,
+    callbacks: Mutex<Vec<Arc<ServerStatusCallback>>>,
+}
+
+impl MonitorShared {
+    fn emit(&self, event: ServerStatusEvent) {
+        let callbacks = trace_lock!(self.callbacks).clone();
+        for callback in callbacks {
+            callback(&event);
+        }
+        // Fails only if there are no subscribers.
+        let _ = self.events_tx.send(event);
+    }
+
+    /// Compare a newly read status with the last one, and report what changed.
+    fn update(&self, status: Result<ServerStatusInfo, StatusCode>, available: &mut bool) {
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                if std::mem::replace(available, false) {
+                    self.emit(ServerStatusEvent::Unavailable(e));
+                }
+                return;
+            }
+        };
+        *available = true;
+
+        let previous = trace_lock!(self.status).replace(status.clone());
+        let previous = previous.as_ref();
+        if previous.map(|p| p.state) != Some(status.state) {
+            self.emit(ServerStatusEvent::StateChanged {
+                previous: previous.map(|p| p.state),
+                state: status.state,
+            });
+        }
+        if previous.map(|p| p.service_level) != Some(status.service_level) {
+            self.emit(ServerStatusEvent::ServiceLevelChanged {
+                previous: previous.map(|p| p.service_level),
+                service_level: status.service_level,
+            });
+        }
+        if status.shutdown_announced() && !previous.is_some_and(|p| p.shutdown_announced()) {
+            self.emit(ServerStatusEvent::ShutdownAnnounced {
+                seconds_till_shutdown: status.seconds_till_shutdown,
+                reason: status.shutdown_reason,
+            });
+        }
+    }
+}
+
+/// Monitors the status of the server of a session, reading `ServerStatus.State`,
+/// `ServiceLevel`, `ServerStatus.SecondsTillShutdown` and `ServerStatus.ShutdownReason`
+/// periodically, and reporting changes as [`ServerStatusEvent`]s.
+///
+/// Changes are passed to the callbacks added with [`ServerStatusMonitor::on_event`],
+/// and yielded by the streams returned from [`ServerStatusMonitor::events`]. Use
+/// [`ServerStatusEvent::ShutdownAnnounced`] to move work to another server before
+/// the server shuts down, [`RedundantSession`](super::RedundantSession) does this
+/// automatically.
+///
+/// Monitoring stops when the monitor is dropped, or the session is dropped.
+pub struct ServerStatusMonitor {
+    shared: Arc<MonitorShared>,
+    task: JoinHandle<()>,
+}
+
+impl ServerStatusMonitor {
+    /// Start monitoring the status of the server of `session`, on a task spawned on the
+    /// tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to the server to monitor.
+    /// * `interval` - How often the status of the server is read.
+    ///
+    pub fn new(session: &Arc<Session>, interval: Duration) -> Self {
+        let (events_tx, _) = broadcast::channel(SERVER_STATUS_EVENTS_CAPACITY);
+        let shared = Arc::new(MonitorShared {
+            status: Mutex::new(None),
+            events_tx,
+            callbacks: Mutex::new(Vec::new()),
+        });
+        let task = tokio::task::spawn(Self::run(Arc::downgrade(session), shared.clone(), interval));
+        Self { shared, task }
+    }
+
+    /// Get the last status read from the server, `None` if it has not been read yet.
+    pub fn status(&self) -> Option<ServerStatusInfo> {
+        trace_lock!(self.shared.status).clone()
+    }
+
+    /// Call `callback` with every change to the status of the server. Callbacks are
+    /// called on the monitor task, and should not block.
+    pub fn on_event(&self, callback: impl Fn(&ServerStatusEvent) + Send + Sync + 'static) {
+        trace_lock!(self.shared.callbacks).push(Arc::new(callback));
+    }
+
+    /// Get a stream of changes to the status of the server, see [`ServerStatusEvent`].
+    ///
+    /// The stream only yields events that happen after this is called. If the stream
+    /// is not polled fast enough, the oldest events are skipped. It ends when the
+    /// monitor is dropped.
+    pub fn events(&self) -> BoxStream<'static, ServerStatusEvent> {
+        futures::stream::unfold(self.shared.events_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Server status event stream lagged, skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    async fn run(session: Weak<Session>, shared: Arc<MonitorShared>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Report the first failure to read the status, even before a successful read.
+        let mut available = true;
+        loop {
+            ticker.tick().await;
+            let Some(session) = session.upgrade() else {
+                return;
+            };
+            let status = if session.is_connected() {
+                session.read_server_status().await
+            } else {
+                Err(StatusCode::BadNotConnected)
+            };
+            if let Err(e) = &status {
+                if available {
+                    session_warn!(session, "Failed to read server status: {e}");
+                }
+            }
+            shared.update(status, &mut available);
+        }
+    }
+}
+
+impl Drop for ServerStatusMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Session {
+    /// Read the state, service level and scheduled shutdown of the server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ServerStatusInfo)` - The status of the server.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read_server_status(&self) -> Result<ServerStatusInfo, StatusCode> {
+        let [state, service_level, seconds_till_shutdown, shutdown_reason] = self
+            .read_server_values([
+                VariableId::Server_ServerStatus_State,
+                VariableId::Server_ServiceLevel,
+                VariableId::Server_ServerStatus_SecondsTillShutdown,
+                VariableId::Server_ServerStatus_ShutdownReason,
+            ])
+            .await?;
+        let state = match state? {
+            Variant::Int32(v) => {
+                ServerState::try_from(v).map_err(|_| StatusCode::BadTypeMismatch)?
+            }
+            _ => return Err(StatusCode::BadTypeMismatch),
+        };
+        let service_level = match service_level? {
+            Variant::Byte(v) => v,
+            _ => return Err(StatusCode::BadTypeMismatch),
+        };
+        // Servers leave these empty, or report 0 seconds, when no shutdown is scheduled.
+        let seconds_till_shutdown = match seconds_till_shutdown {
+            Ok(Variant::UInt32(v)) if v > 0 => Some(v),
+            _ => None,
+        };
+        let shutdown_reason = match shutdown_reason {
+            Ok(Variant::LocalizedText(t)) if !t.text.is_null() => Some(*t),
+            _ => None,
+        };
+        Ok(ServerStatusInfo {
+            state,
+            service_level,
+            seconds_till_shutdown,
+            shutdown_reason,
+        })
+    }
+
+    /// Start monitoring the status of the server, see [`ServerStatusMonitor`].
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - How often the status of the server is read.
+    ///
+    pub fn monitor_server_status(self: &Arc<Self>, interval: Duration) -> ServerStatusMonitor {
+        ServerStatusMonitor::new(self, interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use opcua_core::sync::Mutex;
+    use opcua_types::{LocalizedText, ServerState, StatusCode};
+    use tokio::sync::broadcast;
+
+    use super::{MonitorShared, ServerStatusEvent, ServerStatusInfo};
+
+    fn status(state: ServerState, seconds_till_shutdown: Option<u32>) -> ServerStatusInfo {
+        ServerStatusInfo {
+            state,
+            service_level: 255,
+            seconds_till_shutdown,
+            shutdown_reason: seconds_till_shutdown.map(|_| LocalizedText::from("Maintenance")),
+        }
+    }
+
+    #[test]
+    fn report_changes() {
+        let (events_tx, _) = broadcast::channel(16);
+        let shared = MonitorShared {
+            status: Mutex::new(None),
+            events_tx,
+            callbacks: Mutex::new(Vec::new()),
+        };
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_ref = events.clone();
+        shared
+            .callbacks
+            .lock()
+            .push(Arc::new(move |e: &ServerStatusEvent| {
+                events_ref.lock().push(e.clone())
+            }));
+        let mut available = true;
+        let take = || std::mem::take(&mut *events.lock());
+
+        shared.update(Ok(status(ServerState::Running, None)), &mut available);
+        assert_eq!(
+            take(),
+            vec![
+                ServerStatusEvent::StateChanged {
+                    previous: None,
+                    state: ServerState::Running
+                },
+                ServerStatusEvent::ServiceLevelChanged {
+                    previous: None,
+                    service_level: 255
+                },
+            ]
+        );
+
+        // Nothing changed.
+        shared.update(Ok(status(ServerState::Running, None)), &mut available);
+        assert!(take().is_empty());
+
+        // The shutdown is announced once, even as the countdown continues.
+        shared.update(Ok(status(ServerState::Running, Some(10))), &mut available);
+        assert_eq!(
+            take(),
+            vec![ServerStatusEvent::ShutdownAnnounced {
+                seconds_till_shutdown: Some(10),
+                reason: Some(LocalizedText::from("Maintenance")),
+            }]
+        );
+        shared.update(Ok(status(ServerState::Running, Some(9))), &mut available);
+        assert!(take().is_empty());
+
+        shared.update(Err(StatusCode::BadNotConnected), &mut available);
+        shared.update(Err(StatusCode::BadNotConnected), &mut available);
+        assert_eq!(
+            take(),
+            vec![ServerStatusEvent::Unavailable(StatusCode::BadNotConnected)]
+        );
+
+        shared.update(Ok(status(ServerState::Shutdown, None)), &mut available);
+        assert_eq!(
+            take(),
+            vec![ServerStatusEvent::StateChanged {
+                previous: Some(ServerState::Running),
+                state: ServerState::Shutdown
+            }]
+        );
+    }
+}
@@ -1,21 +1,25 @@
-use std::time::Duration;
+use std::{ops::Range, time::Duration};
 
 use crate::{
     session::{
         process_service_result, process_unexpected_response,
         request_builder::{builder_base, builder_debug, builder_error, RequestHeaderBuilder},
-        UARequest,
+        session_error, session_warn, UARequest,
     },
     AsyncSecureChannel, Session,
 };
+use futures::{Stream, TryStreamExt};
 use opcua_core::ResponseMessage;
 use opcua_types::{
-    DataValue, DeleteAtTimeDetails, DeleteEventDetails, DeleteRawModifiedDetails, ExtensionObject,
-    HistoryReadRequest, HistoryReadResponse, HistoryReadResult, HistoryReadValueId,
+    event_field::{event_from_fields, SetEventField},
+    AggregateConfiguration, ContinuationPoint, DataValue, DateTime, DeleteAtTimeDetails,
+    DeleteEventDetails, DeleteRawModifiedDetails, EventFilter, ExtensionObject, HistoryData,
+    HistoryEvent, HistoryReadRequest, HistoryReadResponse, HistoryReadResult, HistoryReadValueId,
     HistoryUpdateRequest, HistoryUpdateResponse, HistoryUpdateResult, IntegerId, NodeId,
-    ReadAtTimeDetails, ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, ReadRequest,
-    ReadResponse, ReadValueId, StatusCode, TimestampsToReturn, UpdateDataDetails,
-    UpdateEventDetails, UpdateStructureDataDetails, WriteRequest, WriteResponse, WriteValue,
+    PerformUpdateType, ReadAtTimeDetails, ReadEventDetails, ReadProcessedDetails,
+    ReadRawModifiedDetails, ReadRequest, ReadResponse, ReadValueId, StatusCode,
+    StatusCodeValueType, TimestampsToReturn, UpdateDataDetails, UpdateEventDetails,
+    UpdateStructureDataDetails, Variant, WriteRequest, WriteResponse, WriteValue,
 };
 
 /// Enumeration used with Session::history_read()
@@ -31,6 +35,115 @@ pub enum HistoryReadAction {
     ReadAtTimeDetails(ReadAtTimeDetails),
 }
 
+/// Options for [`Session::history_read_raw`].
+#[derive(Debug, Clone)]
+pub struct HistoryReadRawOptions {
+    /// Maximum number of values returned for each node in a single request,
+    /// `0` lets the server decide.
+    pub num_values_per_node: u32,
+    /// Whether to return the bounding values of the time range.
+    pub return_bounds: bool,
+    /// Timestamps to return with each value.
+    pub timestamps_to_return: TimestampsToReturn,
+}
+
+impl Default for HistoryReadRawOptions {
+    fn default() -> Self {
+        Self {
+            num_values_per_node: 0,
+            return_bounds: false,
+            timestamps_to_return: TimestampsToReturn::Both,
+        }
+    }
+}
+
+/// Events for a single node, returned from [`Session::history_read_events`].
+#[derive(Debug, Clone)]
+pub struct HistoryEvents<T> {
+    /// The node that was read.
+    pub node_id: NodeId,
+    /// Status of the read operation for this node. If this is bad, `events` may be
+    /// incomplete or empty.
+    pub status: StatusCode,
+    /// Events read for the node, converted to `T`.
+    pub events: Vec<T>,
+}
+
+/// Aggregated values for a single node, returned from [`Session::history_read_processed`].
+#[derive(Debug, Clone)]
+pub struct AggregateSeries {
+    /// The node that was read.
+    pub node_id: NodeId,
+    /// The aggregate that was calculated.
+    pub aggregate_type: NodeId,
+    /// Status of the read operation for this node. If this is bad, `values` may be
+    /// incomplete or empty.
+    pub status: StatusCode,
+    /// Aggregated values, one for each processing interval.
+    pub values: Vec<AggregateValue>,
+}
+
+/// A single aggregated value, calculated for one processing interval.
+#[derive(Debug, Clone)]
+pub struct AggregateValue {
+    /// The start of the processing interval.
+    pub timestamp: Option<DateTime>,
+    /// The aggregated value, if one could be calculated.
+    pub value: Option<Variant>,
+    /// Status of the value, including the historian info bits. Compare
+    /// [`StatusCode::sub_code`] to check the status while ignoring these bits.
+    pub status: StatusCode,
+}
+
+impl AggregateValue {
+    /// Whether the value was calculated from data covering only part of the
+    /// processing interval, for example because the interval extends past
+    /// the end of the time range, or past the end of the stored data.
+    pub fn is_partial(&self) -> bool {
+        self.status.partial()
+    }
+
+    /// Whether the value is raw, calculated or interpolated.
+    pub fn value_type(&self) -> StatusCodeValueType {
+        self.status.value_type()
+    }
+
+    /// Whether the value is usable, meaning it is present and its status is not bad.
+    /// Partial and uncertain values are usable, but may be less accurate.
+    pub fn is_usable(&self) -> bool {
+        self.value.is_some() && !self.status.is_bad()
+    }
+}
+
+impl From<DataValue> for AggregateValue {
+    fn from(value: DataValue) -> Self {
+        Self {
+            timestamp: value.source_timestamp.or(value.server_timestamp),
+            value: value.value,
+            status: value.status.unwrap_or(StatusCode::Good),
+        }
+    }
+}
+
+/// Result of a single history update operation, returned from the typed history update
+/// methods on [`Session`], such as [`Session::history_update_data`].
+#[derive(Debug, Clone)]
+pub struct HistoryUpdateOutcome {
+    /// Status of the operation as a whole. If this is bad, none of the values
+    /// were updated.
+    pub status: StatusCode,
+    /// Status of each value or timestamp in the operation, in the order they were given.
+    /// Operations without individual values, such as deleting a time range, have no results.
+    pub operation_results: Vec<StatusCode>,
+}
+
+impl HistoryUpdateOutcome {
+    /// Whether the operation and every individual value succeeded.
+    pub fn is_good(&self) -> bool {
+        !self.status.is_bad() && self.operation_results.iter().all(|s| !s.is_bad())
+    }
+}
+
 impl From<HistoryReadAction> for ExtensionObject {
     fn from(action: HistoryReadAction) -> Self {
         match action {
@@ -469,7 +582,7 @@ impl UARequest for HistoryUpdate {
         };
         let response = channel.send(request, self.header.timeout).await?;
         if let ResponseMessage::HistoryUpdate(response) = response {
-            builder_error!(self, "history_update(), success");
+            builder_debug!(self, "history_update(), success");
             process_service_result(&response.response_header)?;
             Ok(*response)
         } else {
@@ -492,6 +605,8 @@ impl Session {
     ///   for details. Basically it will attempt to read a value within the age range or
     ///   attempt to read a new value. If 0 the server will attempt to read a new value from the datasource.
     ///   If set to `i32::MAX` or greater, the server shall attempt to get a cached value.
+    ///   If the read cache is enabled, values in the cache are returned by the same rules,
+    ///   see [`ClientBuilder::read_cache_options`](crate::ClientBuilder::read_cache_options).
     ///
     /// # Returns
     ///
@@ -504,14 +619,61 @@ impl Session {
         timestamps_to_return: TimestampsToReturn,
         max_age: f64,
     ) -> Result<Vec<DataValue>, StatusCode> {
-        Ok(Read::new(self)
-            .nodes_to_read(nodes_to_read.to_vec())
-            .timestamps_to_return(timestamps_to_return)
-            .max_age(max_age)
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default())
+        if !self.read_cache.is_usable(max_age) {
+            let results = self
+                .read_from_server(nodes_to_read.to_vec(), timestamps_to_return, max_age)
+                .await?;
+            for (node, value) in nodes_to_read.iter().zip(&results) {
+                self.read_cache.insert(node, timestamps_to_return, value);
+            }
+            return Ok(results);
+        }
+
+        let mut results: Vec<_> = nodes_to_read
+            .iter()
+            .map(|node| self.read_cache.get(node, timestamps_to_return, max_age))
+            .collect();
+        let missing: Vec<_> = nodes_to_read
+            .iter()
+            .zip(&results)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(node, _)| node.clone())
+            .collect();
+        if !missing.is_empty() {
+            let read = self
+                .read_from_server(missing.clone(), timestamps_to_return, max_age)
+                .await?;
+            for (node, value) in missing.iter().zip(&read) {
+                self.read_cache.insert(node, timestamps_to_return, value);
+            }
+            let mut read = read.into_iter();
+            for slot in results.iter_mut().filter(|r| r.is_none()) {
+                *slot = read.next();
+            }
+        }
+        // If the server returned too few results the list is cut short at the first
+        // missing value, like the response would have been without the cache.
+        Ok(results.into_iter().map_while(|r| r).collect())
+    }
+
+    async fn read_from_server(
+        &self,
+        nodes_to_read: Vec<ReadValueId>,
+        timestamps_to_return: TimestampsToReturn,
+        max_age: f64,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        let limit = self.operation_limits().max_nodes_per_read;
+        self.send_in_chunks(nodes_to_read, limit, |chunk| async move {
+            Ok(Read::new(self)
+                .nodes_to_read(chunk)
+                .timestamps_to_return(timestamps_to_return)
+                .max_age(max_age)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default())
+        })
+        .await
     }
 
     /// Reads historical values or events of one or more nodes. The caller is expected to provide
@@ -543,14 +705,358 @@ impl Session {
         release_continuation_points: bool,
         nodes_to_read: &[HistoryReadValueId],
     ) -> Result<Vec<HistoryReadResult>, StatusCode> {
-        Ok(HistoryRead::new(history_read_details, self)
-            .timestamps_to_return(timestamps_to_return)
-            .release_continuation_points(release_continuation_points)
-            .nodes_to_read(nodes_to_read.to_vec())
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default())
+        let limits = self.operation_limits();
+        let limit = match history_read_details {
+            HistoryReadAction::ReadEventDetails(_) => limits.max_nodes_per_history_read_events,
+            _ => limits.max_nodes_per_history_read_data,
+        };
+        let history_read_details = &history_read_details;
+        self.send_in_chunks(nodes_to_read.to_vec(), limit, |chunk| async move {
+            Ok(HistoryRead::new(history_read_details.clone(), self)
+                .timestamps_to_return(timestamps_to_return)
+                .release_continuation_points(release_continuation_points)
+                .nodes_to_read(chunk)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Read raw historical values for a list of nodes, following continuation points until
+    /// every value in the time range has been received, returning a stream of pages.
+    ///
+    /// Each page contains the values returned by a single request, paired with the node they
+    /// belong to. Values for each node are returned in order. If the server reports an error for
+    /// a node, the page contains a single [`DataValue`] for that node with only the status set.
+    ///
+    /// If a request fails, any outstanding continuation points are released, and the stream ends
+    /// after returning the error. If the stream is dropped before it ends, the server keeps the
+    /// continuation points until the session is closed, or until it needs them for other requests.
+    /// Use [`HistoryValueStream`](crate::HistoryValueStream) to release them when the stream
+    /// is dropped.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.4.3 for a description of how the time range
+    /// and options are interpreted.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The nodes to read history for.
+    /// * `time_range` - The time range to read. If `end` is before `start`, values are
+    ///   returned in reverse order.
+    /// * `options` - Additional options for the request.
+    ///
+    pub fn history_read_raw<'a>(
+        &'a self,
+        nodes: &'a [NodeId],
+        time_range: Range<DateTime>,
+        options: HistoryReadRawOptions,
+    ) -> impl Stream<Item = Result<Vec<(NodeId, DataValue)>, StatusCode>> + 'a {
+        let details = ReadRawModifiedDetails {
+            is_read_modified: false,
+            start_time: time_range.start,
+            end_time: time_range.end,
+            num_values_per_node: options.num_values_per_node,
+            return_bounds: options.return_bounds,
+        };
+        self.history_read_batches(nodes, options.timestamps_to_return, move |_| {
+            HistoryReadAction::ReadRawModifiedDetails(details.clone())
+        })
+        .map_ok(move |batch| {
+            batch
+                .into_iter()
+                .flat_map(|(idx, result)| {
+                    history_data_values(result)
+                        .into_iter()
+                        .map(move |v| (nodes[idx].clone(), v))
+                })
+                .collect()
+        })
+    }
+
+    /// Read aggregated historical values for a list of nodes, following continuation points
+    /// until every processing interval in the time range has been received.
+    ///
+    /// Aggregated values carry historian info bits in their status code, see [`AggregateValue`]
+    /// for how to inspect them. In particular, values for intervals that are only partially
+    /// covered by data are typically good or uncertain, with the `partial` bit set.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.4.4 and OPC UA Part 13 - Aggregates for
+    /// a complete description of how aggregates are calculated.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - A list of pairs of node to read and the aggregate to calculate for it,
+    ///   for example [`ObjectId::AggregateFunction_Average`](opcua_types::ObjectId::AggregateFunction_Average).
+    /// * `time_range` - The time range to read. If `end` is before `start`, values are
+    ///   returned in reverse order.
+    /// * `processing_interval` - The length of each interval to aggregate over. If this is zero,
+    ///   the server returns a single value for the entire time range.
+    /// * `aggregate_configuration` - Configuration of the aggregate calculation. If this is `None`,
+    ///   the server uses its own defaults.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<AggregateSeries>)` - A list of [`AggregateSeries`] corresponding to each node to read.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_read_processed(
+        &self,
+        nodes: &[(NodeId, NodeId)],
+        time_range: Range<DateTime>,
+        processing_interval: Duration,
+        aggregate_configuration: Option<AggregateConfiguration>,
+    ) -> Result<Vec<AggregateSeries>, StatusCode> {
+        let node_ids = nodes.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        let aggregate_configuration =
+            aggregate_configuration.unwrap_or_else(|| AggregateConfiguration {
+                use_server_capabilities_defaults: true,
+                ..Default::default()
+            });
+        let details = |indices: &[usize]| {
+            HistoryReadAction::ReadProcessedDetails(ReadProcessedDetails {
+                start_time: time_range.start,
+                end_time: time_range.end,
+                processing_interval: processing_interval.as_secs_f64() * 1000.0,
+                aggregate_type: Some(indices.iter().map(|i| nodes[*i].1.clone()).collect()),
+                aggregate_configuration: aggregate_configuration.clone(),
+            })
+        };
+
+        let series = nodes
+            .iter()
+            .map(|(node_id, aggregate_type)| AggregateSeries {
+                node_id: node_id.clone(),
+                aggregate_type: aggregate_type.clone(),
+                status: StatusCode::Good,
+                values: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        self.history_read_batches(&node_ids, TimestampsToReturn::Source, details)
+            .try_fold(series, |mut series, batch| async move {
+                for (idx, result) in batch {
+                    let series = &mut series[idx];
+                    series.status = result.status_code;
+                    if !result.status_code.is_bad() {
+                        series.values.extend(
+                            history_data_values(result)
+                                .into_iter()
+                                .map(AggregateValue::from),
+                        );
+                    }
+                }
+                Ok(series)
+            })
+            .await
+    }
+
+    /// Read historical events for a list of nodes, following continuation points until
+    /// every event in the time range has been received, and convert each event to `T`.
+    ///
+    /// `T` is typically a type deriving `Event` or `EventField`, and `Default`. The fields
+    /// selected in `filter` are set on a default instance of `T`. Build the filter with
+    /// [`EventFilterBuilder`](opcua_types::EventFilterBuilder), selecting the fields of `T`
+    /// that should be read.
+    ///
+    /// If an event cannot be converted to `T`, the event is skipped and the status of the
+    /// node is set to `BadTypeMismatch`.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.4.2 for a complete description of the
+    /// service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The nodes to read events for, typically objects with the `HistoryRead`
+    ///   bit set in their `EventNotifier` attribute.
+    /// * `time_range` - The time range to read. If `end` is before `start`, events are
+    ///   returned in reverse order.
+    /// * `filter` - The event filter, selecting which fields to read and which events to return.
+    /// * `num_values_per_node` - Maximum number of events returned for each node in a
+    ///   single request, `0` lets the server decide.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<HistoryEvents<T>>)` - A list of [`HistoryEvents`] corresponding to each node to read.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_read_events<T>(
+        &self,
+        nodes: &[NodeId],
+        time_range: Range<DateTime>,
+        filter: &EventFilter,
+        num_values_per_node: u32,
+    ) -> Result<Vec<HistoryEvents<T>>, StatusCode>
+    where
+        T: SetEventField + Default,
+    {
+        let details = ReadEventDetails {
+            num_values_per_node,
+            start_time: time_range.start,
+            end_time: time_range.end,
+            filter: filter.clone(),
+        };
+        let select_clauses = filter.select_clauses.as_deref().unwrap_or_default();
+
+        let events = nodes
+            .iter()
+            .map(|node_id| HistoryEvents {
+                node_id: node_id.clone(),
+                status: StatusCode::Good,
+                events: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        self.history_read_batches(nodes, TimestampsToReturn::Neither, |_| {
+            HistoryReadAction::ReadEventDetails(details.clone())
+        })
+        .try_fold(events, |mut events, batch| async move {
+            for (idx, result) in batch {
+                let events = &mut events[idx];
+                if result.status_code.is_bad() {
+                    events.status = result.status_code;
+                    continue;
+                }
+                if events.status.is_good() {
+                    events.status = result.status_code;
+                }
+                let Some(data) = result.history_data.into_inner_as::<HistoryEvent>() else {
+                    continue;
+                };
+                for fields in data.events.into_iter().flatten() {
+                    match event_from_fields(select_clauses, fields.event_fields.unwrap_or_default())
+                    {
+                        Ok(event) => events.events.push(event),
+                        Err(e) => {
+                            session_error!(self, "Failed to convert event: {}", e);
+                            events.status = StatusCode::BadTypeMismatch;
+                        }
+                    }
+                }
+            }
+            Ok(events)
+        })
+        .await
+    }
+
+    /// Stream of history read results, calling `HistoryRead` with the continuation points
+    /// from each batch until there are none left. `details` is called with the indices of
+    /// the nodes in each request, to create the details for that request.
+    ///
+    /// Each batch contains the results for the nodes that had more data, paired with the
+    /// index of the node in `nodes`. Continuation points are removed from the results.
+    fn history_read_batches<'a>(
+        &'a self,
+        nodes: &'a [NodeId],
+        timestamps_to_return: TimestampsToReturn,
+        details: impl Fn(&[usize]) -> HistoryReadAction + 'a,
+    ) -> impl Stream<Item = Result<Vec<(usize, HistoryReadResult)>, StatusCode>> + 'a {
+        // Each pending read is the index of the node, and its continuation point, which
+        // is null for the first request.
+        let pending = (0..nodes.len())
+            .map(|idx| (idx, ContinuationPoint::null()))
+            .collect::<Vec<_>>();
+
+        futures::stream::unfold(
+            (Some(pending), details),
+            move |(pending, details)| async move {
+                let pending = pending.filter(|p| !p.is_empty())?;
+                let mut batch = match self
+                    .history_read_page(nodes, timestamps_to_return, &details, pending)
+                    .await
+                {
+                    Ok(batch) => batch,
+                    Err(e) => return Some((Err(e), (None, details))),
+                };
+                let next = batch
+                    .iter_mut()
+                    .filter(|(_, r)| !r.continuation_point.is_null())
+                    .map(|(idx, r)| (*idx, std::mem::take(&mut r.continuation_point)))
+                    .collect();
+                Some((Ok(batch), (Some(next), details)))
+            },
+        )
+    }
+
+    /// Call `HistoryRead` for a list of nodes, each given by its index in `nodes` and
+    /// paired with its continuation point, which is null for the first request. Releases
+    /// the continuation points on failure.
+    pub(super) async fn history_read_page(
+        &self,
+        nodes: &[NodeId],
+        timestamps_to_return: TimestampsToReturn,
+        details: impl Fn(&[usize]) -> HistoryReadAction,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> Result<Vec<(usize, HistoryReadResult)>, StatusCode> {
+        let indices = pending.iter().map(|(idx, _)| *idx).collect::<Vec<_>>();
+        let to_read = pending
+            .iter()
+            .map(|(idx, cp)| history_read_value_id(&nodes[*idx], cp.clone()))
+            .collect::<Vec<_>>();
+
+        match self
+            .history_read(details(&indices), timestamps_to_return, false, &to_read)
+            .await
+        {
+            Ok(results) if results.len() == to_read.len() => {
+                Ok(indices.into_iter().zip(results).collect())
+            }
+            Ok(results) => {
+                session_error!(
+                    self,
+                    "Expected {} history read results but got {}",
+                    to_read.len(),
+                    results.len()
+                );
+                let to_release = indices
+                    .into_iter()
+                    .zip(results)
+                    .map(|(idx, r)| (idx, r.continuation_point))
+                    .collect();
+                self.release_history_continuation_points(nodes, &details, to_release)
+                    .await;
+                Err(StatusCode::BadUnexpectedError)
+            }
+            Err(e) => {
+                self.release_history_continuation_points(nodes, &details, pending)
+                    .await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Try to release a list of history read continuation points, each paired with the
+    /// index of the node it belongs to, ignoring any errors.
+    pub(super) async fn release_history_continuation_points(
+        &self,
+        nodes: &[NodeId],
+        details: impl Fn(&[usize]) -> HistoryReadAction,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) {
+        let pending = pending
+            .into_iter()
+            .filter(|(_, cp)| !cp.is_null())
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            return;
+        }
+        let indices = pending.iter().map(|(idx, _)| *idx).collect::<Vec<_>>();
+        let to_read = pending
+            .into_iter()
+            .map(|(idx, cp)| history_read_value_id(&nodes[idx], cp))
+            .collect::<Vec<_>>();
+        if let Err(e) = self
+            .history_read(
+                details(&indices),
+                TimestampsToReturn::Neither,
+                true,
+                &to_read,
+            )
+            .await
+        {
+            session_warn!(self, "Failed to release continuation points: {}", e);
+        }
     }
 
     /// Writes values to nodes by sending a [`WriteRequest`] to the server. Note that some servers may reject DataValues
@@ -571,12 +1077,22 @@ impl Session {
         &self,
         nodes_to_write: &[WriteValue],
     ) -> Result<Vec<StatusCode>, StatusCode> {
-        Ok(Write::new(self)
-            .nodes_to_write(nodes_to_write.to_vec())
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default())
+        let limit = self.operation_limits().max_nodes_per_write;
+        let results = self
+            .send_in_chunks(nodes_to_write.to_vec(), limit, |chunk| async move {
+                Ok(Write::new(self)
+                    .nodes_to_write(chunk)
+                    .send(&self.channel)
+                    .await?
+                    .results
+                    .unwrap_or_default())
+            })
+            .await;
+        // Even a failed write may have changed the value on the server.
+        for node in nodes_to_write {
+            self.read_cache.invalidate(&node.node_id, node.attribute_id);
+        }
+        results
     }
 
     /// Updates historical values. The caller is expected to provide one or more history update operations
@@ -611,4 +1127,202 @@ impl Session {
             .results
             .unwrap_or_default())
     }
+
+    /// Insert, replace or remove raw historical values of a node.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.9.2 for a complete description of the
+    /// operation and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to update history for.
+    /// * `perform_insert_replace` - Whether to insert new values, replace existing values, or both.
+    /// * `values` - The values to write. Values are matched with stored values by their source timestamp.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HistoryUpdateOutcome)` - The result of the operation, with one result for each value.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_update_data(
+        &self,
+        node_id: &NodeId,
+        perform_insert_replace: PerformUpdateType,
+        values: Vec<DataValue>,
+    ) -> Result<HistoryUpdateOutcome, StatusCode> {
+        let num_operations = values.len();
+        self.history_update_single(
+            UpdateDataDetails {
+                node_id: node_id.clone(),
+                perform_insert_replace,
+                update_values: Some(values),
+            },
+            num_operations,
+        )
+        .await
+    }
+
+    /// Insert, replace or remove historical structure data, such as annotations, of a node.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.9.3 for a complete description of the
+    /// operation and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to update history for.
+    /// * `perform_insert_replace` - Whether to insert, replace or remove structures.
+    /// * `values` - The structures to write, each containing an [`ExtensionObject`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HistoryUpdateOutcome)` - The result of the operation, with one result for each value.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_update_structure_data(
+        &self,
+        node_id: &NodeId,
+        perform_insert_replace: PerformUpdateType,
+        values: Vec<DataValue>,
+    ) -> Result<HistoryUpdateOutcome, StatusCode> {
+        let num_operations = values.len();
+        self.history_update_single(
+            UpdateStructureDataDetails {
+                node_id: node_id.clone(),
+                perform_insert_replace,
+                update_values: Some(values),
+            },
+            num_operations,
+        )
+        .await
+    }
+
+    /// Delete raw or modified historical values of a node in a time range.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.9.5 for a complete description of the
+    /// operation and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to delete history for.
+    /// * `time_range` - The time range to delete values in. `start` is inclusive and `end` is exclusive.
+    /// * `is_delete_modified` - Delete modified values instead of raw values.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HistoryUpdateOutcome)` - The result of the operation, without individual results.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_delete_raw_modified(
+        &self,
+        node_id: &NodeId,
+        time_range: Range<DateTime>,
+        is_delete_modified: bool,
+    ) -> Result<HistoryUpdateOutcome, StatusCode> {
+        self.history_update_single(
+            DeleteRawModifiedDetails {
+                node_id: node_id.clone(),
+                is_delete_modified,
+                start_time: time_range.start,
+                end_time: time_range.end,
+            },
+            0,
+        )
+        .await
+    }
+
+    /// Delete historical values of a node at specific timestamps.
+    ///
+    /// See OPC UA Part 11 - Historical Access 6.9.6 for a complete description of the
+    /// operation and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to delete history for.
+    /// * `times` - The source timestamps of the values to delete.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HistoryUpdateOutcome)` - The result of the operation, with one result for each timestamp.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn history_delete_at_time(
+        &self,
+        node_id: &NodeId,
+        times: Vec<DateTime>,
+    ) -> Result<HistoryUpdateOutcome, StatusCode> {
+        let num_operations = times.len();
+        self.history_update_single(
+            DeleteAtTimeDetails {
+                node_id: node_id.clone(),
+                req_times: Some(times),
+            },
+            num_operations,
+        )
+        .await
+    }
+
+    /// Send a history update request with a single operation, expecting `num_operations`
+    /// individual results.
+    async fn history_update_single(
+        &self,
+        action: impl Into<HistoryUpdateAction>,
+        num_operations: usize,
+    ) -> Result<HistoryUpdateOutcome, StatusCode> {
+        let Some(result) = self
+            .history_update(&[action.into()])
+            .await?
+            .into_iter()
+            .next()
+        else {
+            session_error!(self, "Expected a history update result but got none");
+            return Err(StatusCode::BadUnexpectedError);
+        };
+
+        let mut operation_results = result.operation_results.unwrap_or_default();
+        if operation_results.is_empty() {
+            // Servers may leave out the individual results if the operation failed as a whole.
+            operation_results = vec![result.status_code; num_operations];
+        } else if operation_results.len() != num_operations {
+            session_error!(
+                self,
+                "Expected {} history update operation results but got {}",
+                num_operations,
+                operation_results.len()
+            );
+            return Err(StatusCode::BadUnexpectedError);
+        }
+
+        Ok(HistoryUpdateOutcome {
+            status: result.status_code,
+            operation_results,
+        })
+    }
+}
+
+/// Get the data values from a history read result. If the result has a bad status,
+/// this returns a single value with only the status set.
+pub(super) fn history_data_values(result: HistoryReadResult) -> Vec<DataValue> {
+    if result.status_code.is_bad() {
+        return vec![DataValue {
+            status: Some(result.status_code),
+            ..Default::default()
+        }];
+    }
+    result
+        .history_data
+        .into_inner_as::<HistoryData>()
+        .and_then(|d| d.data_values)
+        .unwrap_or_default()
+}
+
+fn history_read_value_id(
+    node_id: &NodeId,
+    continuation_point: ContinuationPoint,
+) -> HistoryReadValueId {
+    HistoryReadValueId {
+        node_id: node_id.clone(),
+        index_range: Default::default(),
+        data_encoding: Default::default(),
+        continuation_point,
+    }
 }
@@ -0,0 +1,446 @@
+use opcua_core::comms::tcp_types::MessageLimits;
+use opcua_types::{
+    ByteString, CallMethodRequest, MethodId, NodeId, OpenFileMode, QualifiedName, ReadValueId,
+    ReferenceTypeId, RelativePath, StatusCode, TimestampsToReturn, TryFromVariant, VariableId,
+    Variant,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    session::{session_debug, session_error},
+    Session,
+};
+
+/// Room left in each message for the headers and arguments around the file data.
+const MESSAGE_OVERHEAD: usize = 1024;
+/// Room left in each message chunk for the chunk headers, padding and signature.
+const CHUNK_OVERHEAD: usize = 256;
+/// Largest amount of data read or written in a single call, even if the
+/// message limits allow more.
+const MAX_FILE_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The methods of a `FileType` object, see OPC UA Part 5 - Information Model C.2.
+struct FileMethods {
+    open: NodeId,
+    close: NodeId,
+    read: NodeId,
+    write: NodeId,
+    get_position: NodeId,
+    set_position: NodeId,
+}
+
+impl FileMethods {
+    const METHODS: [(&'static str, MethodId); 6] = [
+        ("Open", MethodId::FileType_Open),
+        ("Close", MethodId::FileType_Close),
+        ("Read", MethodId::FileType_Read),
+        ("Write", MethodId::FileType_Write),
+        ("GetPosition", MethodId::FileType_GetPosition),
+        ("SetPosition", MethodId::FileType_SetPosition),
+    ];
+
+    /// Find the methods of `object_id`. Methods the object does not have as components
+    /// are called with the node ID of the method on `FileType`.
+    async fn resolve(session: &Session, object_id: &NodeId) -> Result<Self, StatusCode> {
+        let paths = Self::METHODS
+            .iter()
+            .map(|(name, _)| {
+                let path = RelativePath::builder()
+                    .reference(
+                        ReferenceTypeId::HasComponent,
+                        false,
+                        false,
+                        QualifiedName::new(0, *name),
+                    )
+                    .build();
+                (object_id.clone(), path)
+            })
+            .collect::<Vec<_>>();
+        let mut ids = session
+            .translate_paths(&paths)
+            .await?
+            .into_iter()
+            .zip(Self::METHODS)
+            .map(|(r, (_, type_method))| match r {
+                Ok(id) => Ok(id),
+                Err(StatusCode::BadNoMatch) => Ok(type_method.into()),
+                Err(e) => Err(e),
+            });
+        let mut next = || ids.next().unwrap_or(Err(StatusCode::BadUnexpectedError));
+        Ok(Self {
+            open: next()?,
+            close: next()?,
+            read: next()?,
+            write: next()?,
+            get_position: next()?,
+            set_position: next()?,
+        })
+    }
+}
+
+/// A file opened on the server with the `Open` method of a `FileType` object,
+/// created with [`RemoteFile::open`].
+///
+/// The file stays open until [`RemoteFile::close`] is called, or until the session
+/// is closed. Servers may refuse to open a file that is already open for writing.
+///
+/// See OPC UA Part 5 - Information Model C.2 for a description of `FileType`.
+pub struct RemoteFile<'a> {
+    session: &'a Session,
+    object_id: NodeId,
+    methods: FileMethods,
+    handle: u32,
+}
+
+impl<'a> RemoteFile<'a> {
+    /// Open a file on the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to open the file on.
+    /// * `object_id` - The `FileType` object of the file.
+    /// * `mode` - A combination of [`OpenFileMode`] flags, for example
+    ///   `OpenFileMode::Write as u8 | OpenFileMode::EraseExisting as u8`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RemoteFile)` - The open file.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn open(
+        session: &'a Session,
+        object_id: impl Into<NodeId>,
+        mode: u8,
+    ) -> Result<Self, StatusCode> {
+        let object_id = object_id.into();
+        let methods = FileMethods::resolve(session, &object_id).await?;
+        let mut outputs = call_file_method(
+            session,
+            &object_id,
+            &methods.open,
+            vec![Variant::from(mode)],
+        )
+        .await?;
+        let handle = u32::try_from_variant(take_output(session, &mut outputs, "Open")?)
+            .map_err(|_| StatusCode::BadTypeMismatch)?;
+        session_debug!(session, "Opened file {} with handle {}", object_id, handle);
+        Ok(Self {
+            session,
+            object_id,
+            methods,
+            handle,
+        })
+    }
+
+    /// The `FileType` object of the file.
+    pub fn object_id(&self) -> &NodeId {
+        &self.object_id
+    }
+
+    /// The file handle returned by the server.
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    /// The largest number of bytes that fits in the response to a single `Read` call,
+    /// derived from the message limits negotiated with the server and the decoding
+    /// limits of the session.
+    pub fn read_chunk_size(&self) -> usize {
+        let limits = self.session.connection_limits();
+        chunk_size(&limits.receive)
+            .min(
+                self.session
+                    .decoding_options()
+                    .max_byte_string_length
+                    .max(1),
+            )
+            .min(i32::MAX as usize)
+    }
+
+    /// The largest number of bytes that fits in a single `Write` call, derived from
+    /// the message limits negotiated with the server.
+    pub fn write_chunk_size(&self) -> usize {
+        chunk_size(&self.session.connection_limits().send)
+    }
+
+    /// Read up to `length` bytes from the current position. An empty result means that
+    /// the end of the file has been reached. Servers may return fewer bytes than requested
+    /// before the end of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` - The bytes that were read.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read(&self, length: usize) -> Result<Vec<u8>, StatusCode> {
+        let length = i32::try_from(length).unwrap_or(i32::MAX);
+        let mut outputs = self
+            .call(
+                &self.methods.read,
+                vec![Variant::from(self.handle), Variant::from(length)],
+            )
+            .await?;
+        let data = ByteString::try_from_variant(take_output(self.session, &mut outputs, "Read")?)
+            .map_err(|_| StatusCode::BadTypeMismatch)?;
+        Ok(data.value.unwrap_or_default())
+    }
+
+    /// Write `data` at the current position. The data must fit in a single call,
+    /// see [`RemoteFile::write_chunk_size`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The data was written.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn write(&self, data: &[u8]) -> Result<(), StatusCode> {
+        self.call(
+            &self.methods.write,
+            vec![
+                Variant::from(self.handle),
+                Variant::from(ByteString::from(data)),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Get the current position in the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The position, in bytes from the start of the file.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn position(&self) -> Result<u64, StatusCode> {
+        let mut outputs = self
+            .call(&self.methods.get_position, vec![Variant::from(self.handle)])
+            .await?;
+        u64::try_from_variant(take_output(self.session, &mut outputs, "GetPosition")?)
+            .map_err(|_| StatusCode::BadTypeMismatch)
+    }
+
+    /// Set the position in the file, in bytes from the start of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The position was set.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn set_position(&self, position: u64) -> Result<(), StatusCode> {
+        self.call(
+            &self.methods.set_position,
+            vec![Variant::from(self.handle), Variant::from(position)],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Close the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The file was closed.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn close(self) -> Result<(), StatusCode> {
+        self.call(&self.methods.close, vec![Variant::from(self.handle)])
+            .await?;
+        Ok(())
+    }
+
+    async fn call(
+        &self,
+        method_id: &NodeId,
+        args: Vec<Variant>,
+    ) -> Result<Vec<Variant>, StatusCode> {
+        call_file_method(self.session, &self.object_id, method_id, args).await
+    }
+}
+
+/// Call a method of a file object, returning its output arguments.
+async fn call_file_method(
+    session: &Session,
+    object_id: &NodeId,
+    method_id: &NodeId,
+    args: Vec<Variant>,
+) -> Result<Vec<Variant>, StatusCode> {
+    let result = session
+        .call_one(CallMethodRequest {
+            object_id: object_id.clone(),
+            method_id: method_id.clone(),
+            input_arguments: Some(args),
+        })
+        .await?;
+    if result.status_code.is_bad() {
+        return Err(result.status_code);
+    }
+    Ok(result.output_arguments.unwrap_or_default())
+}
+
+fn take_output(
+    session: &Session,
+    outputs: &mut Vec<Variant>,
+    method: &str,
+) -> Result<Variant, StatusCode> {
+    if outputs.len() != 1 {
+        session_error!(
+            session,
+            "Expected 1 output argument from {} but got {}",
+            method,
+            outputs.len()
+        );
+        return Err(StatusCode::BadUnexpectedError);
+    }
+    Ok(outputs.remove(0))
+}
+
+/// The largest amount of file data that fits in a single message with `limits`.
+fn chunk_size(limits: &MessageLimits) -> usize {
+    let mut size = MAX_FILE_CHUNK_SIZE + MESSAGE_OVERHEAD;
+    if limits.max_message_size > 0 {
+        size = size.min(limits.max_message_size);
+    }
+    if limits.max_chunk_count > 0 && limits.max_chunk_size > 0 {
+        size =
+            size.min(limits.max_chunk_count * limits.max_chunk_size.saturating_sub(CHUNK_OVERHEAD));
+    }
+    size.saturating_sub(MESSAGE_OVERHEAD).max(1)
+}
+
+impl Session {
+    /// Download the contents of a file on the server, reading it in chunks sized to
+    /// fit the message limits negotiated with the server.
+    ///
+    /// The file is opened for reading, and closed when the download completes or fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The `FileType` object of the file.
+    /// * `writer` - Where to write the contents of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of bytes downloaded.
+    /// * `Err(StatusCode)` - A request failed, or writing to `writer` failed.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn download_file(
+        &self,
+        object_id: impl Into<NodeId>,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<u64, StatusCode> {
+        let file = RemoteFile::open(self, object_id, OpenFileMode::Read as u8).await?;
+        let chunk_size = file.read_chunk_size();
+        let mut total = 0u64;
+        let result = async {
+            loop {
+                let data = file.read(chunk_size).await?;
+                if data.is_empty() {
+                    break;
+                }
+                writer.write_all(&data).await.map_err(|e| {
+                    session_error!(self, "Failed to write downloaded file: {}", e);
+                    StatusCode::BadUnexpectedError
+                })?;
+                total += data.len() as u64;
+            }
+            writer.flush().await.map_err(|e| {
+                session_error!(self, "Failed to write downloaded file: {}", e);
+                StatusCode::BadUnexpectedError
+            })
+        }
+        .await;
+        close_after(file, result).await?;
+        Ok(total)
+    }
+
+    /// Upload the contents of a file to the server, replacing its current contents and
+    /// writing it in chunks sized to fit the message limits negotiated with the server
+    /// and the `MaxByteStringLength` of the server.
+    ///
+    /// The file is opened for writing, and closed when the upload completes or fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The `FileType` object of the file.
+    /// * `reader` - Where to read the new contents of the file from.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of bytes uploaded.
+    /// * `Err(StatusCode)` - A request failed, or reading from `reader` failed.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn upload_file(
+        &self,
+        object_id: impl Into<NodeId>,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> Result<u64, StatusCode> {
+        let max_byte_string_length = self.server_max_byte_string_length().await;
+        let file = RemoteFile::open(
+            self,
+            object_id,
+            OpenFileMode::Write as u8 | OpenFileMode::EraseExisting as u8,
+        )
+        .await?;
+        let mut chunk_size = file.write_chunk_size();
+        if max_byte_string_length > 0 {
+            chunk_size = chunk_size.min(max_byte_string_length);
+        }
+        let mut total = 0u64;
+        let result = async {
+            let mut buf = Vec::with_capacity(chunk_size);
+            loop {
+                buf.clear();
+                (&mut reader)
+                    .take(chunk_size as u64)
+                    .read_to_end(&mut buf)
+                    .await
+                    .map_err(|e| {
+                        session_error!(self, "Failed to read file to upload: {}", e);
+                        StatusCode::BadUnexpectedError
+                    })?;
+                if buf.is_empty() {
+                    break Ok(());
+                }
+                file.write(&buf).await?;
+                total += buf.len() as u64;
+            }
+        }
+        .await;
+        close_after(file, result).await?;
+        Ok(total)
+    }
+
+    /// Read the `MaxByteStringLength` server capability, `0` if the server does not
+    /// report one.
+    async fn server_max_byte_string_length(&self) -> usize {
+        let value = self
+            .read(
+                &[ReadValueId::new_value(
+                    VariableId::Server_ServerCapabilities_MaxByteStringLength.into(),
+                )],
+                TimestampsToReturn::Neither,
+                0.0,
+            )
+            .await
+            .ok()
+            .and_then(|mut v| v.pop())
+            .and_then(|v| v.value);
+        match value {
+            Some(Variant::UInt32(v)) => v as usize,
+            _ => 0,
+        }
+    }
+}
+
+/// Close `file`, returning the error of `result` if it failed, or else the result
+/// of closing the file.
+async fn close_after(
+    file: RemoteFile<'_>,
+    result: Result<(), StatusCode>,
+) -> Result<(), StatusCode> {
+    let closed = file.close().await;
+    result.and(closed)
+}
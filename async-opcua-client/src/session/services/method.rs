@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use crate::{
     session::{
@@ -10,8 +10,9 @@ use crate::{
 };
 use opcua_core::ResponseMessage;
 use opcua_types::{
-    CallMethodRequest, CallMethodResult, CallRequest, CallResponse, IntegerId, MethodId, NodeId,
-    ObjectId, StatusCode, TryFromVariant, Variant,
+    validate_arguments, Argument, CallMethodRequest, CallMethodResult, CallRequest, CallResponse,
+    IntegerId, MethodArgs, MethodId, NodeId, ObjectId, QualifiedName, ReadValueId, ReferenceTypeId,
+    RelativePath, StatusCode, TimestampsToReturn, TryFromVariant, Variant,
 };
 
 #[derive(Debug, Clone)]
@@ -123,12 +124,16 @@ impl Session {
         &self,
         methods: Vec<CallMethodRequest>,
     ) -> Result<Vec<CallMethodResult>, StatusCode> {
-        Ok(Call::new(self)
-            .methods_to_call(methods)
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default())
+        let limit = self.operation_limits().max_nodes_per_method_call;
+        self.send_in_chunks(methods, limit, |chunk| async move {
+            Ok(Call::new(self)
+                .methods_to_call(chunk)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default())
+        })
+        .await
     }
 
     /// Calls a single method on an object on the server by sending a [`CallRequest`] to the server.
@@ -158,6 +163,119 @@ impl Session {
             .unwrap())
     }
 
+    /// Calls a single method with typed input and output arguments, typically structs deriving
+    /// `MethodArgs`. Use `()` for methods without input or output arguments.
+    ///
+    /// The first time a method is called, its `InputArguments` property is read from the server
+    /// and kept for the lifetime of the session. The input arguments are checked against it before
+    /// calling the method, see [`validate_arguments`].
+    ///
+    /// See OPC UA Part 4 - Services 5.11.2 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The object or object type the method is called on.
+    /// * `method_id` - The method to call.
+    /// * `input` - The input arguments to the method.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Out)` - The output arguments of the method.
+    /// * `Err(StatusCode)` - The arguments are invalid, the request failed, or the method returned
+    ///   a bad status. [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn call_typed<In: MethodArgs, Out: MethodArgs>(
+        &self,
+        object_id: impl Into<NodeId>,
+        method_id: impl Into<NodeId>,
+        input: In,
+    ) -> Result<Out, StatusCode> {
+        let method_id = method_id.into();
+        let input_arguments = input.into_arguments();
+        let declared = self.method_input_arguments(&method_id).await?;
+        if let Err(e) = validate_arguments(&declared, &input_arguments) {
+            session_error!(
+                self,
+                "Invalid input arguments for method {}: {}",
+                method_id,
+                e
+            );
+            return Err(e.status());
+        }
+
+        let result = self
+            .call_one((object_id.into(), method_id.clone(), Some(input_arguments)))
+            .await?;
+        if result.status_code.is_bad() {
+            return Err(result.status_code);
+        }
+        Out::from_arguments(result.output_arguments.unwrap_or_default()).map_err(|e| {
+            session_error!(
+                self,
+                "Invalid output arguments from method {}: {}",
+                method_id,
+                e
+            );
+            e.status()
+        })
+    }
+
+    /// Get the input arguments of a method, reading the `InputArguments` property
+    /// of the method if it is not already cached. Methods without the property
+    /// have no input arguments.
+    async fn method_input_arguments(
+        &self,
+        method_id: &NodeId,
+    ) -> Result<Arc<Vec<Argument>>, StatusCode> {
+        if let Some(arguments) = self.method_input_arguments.read().get(method_id) {
+            return Ok(arguments.clone());
+        }
+
+        let path = RelativePath::builder()
+            .reference(
+                ReferenceTypeId::HasProperty,
+                false,
+                false,
+                QualifiedName::new(0, "InputArguments"),
+            )
+            .build();
+        let arguments = match self
+            .translate_paths(&[(method_id.clone(), path)])
+            .await?
+            .pop()
+            .unwrap_or(Err(StatusCode::BadUnexpectedError))
+        {
+            Ok(property_id) => {
+                let value = self
+                    .read(
+                        &[ReadValueId::new_value(property_id)],
+                        TimestampsToReturn::Neither,
+                        0.0,
+                    )
+                    .await?
+                    .pop()
+                    .and_then(|v| v.value)
+                    .unwrap_or_default();
+                parse_arguments(value).ok_or_else(|| {
+                    session_error!(
+                        self,
+                        "Invalid InputArguments property on method {}",
+                        method_id
+                    );
+                    StatusCode::BadTypeMismatch
+                })?
+            }
+            Err(StatusCode::BadNoMatch) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let arguments = Arc::new(arguments);
+        self.method_input_arguments
+            .write()
+            .insert(method_id.clone(), arguments.clone());
+        Ok(arguments)
+    }
+
     /// Calls GetMonitoredItems via call_method(), putting a sane interface on the input / output.
     ///
     /// # Arguments
@@ -198,4 +316,73 @@ impl Session {
             Err(StatusCode::BadUnexpectedError)
         }
     }
+
+    /// Calls SetSubscriptionDurable via call_method(), putting a sane interface on the input / output.
+    ///
+    /// A durable subscription keeps its monitored item queues on the server for the given lifetime,
+    /// even if the client is disconnected. The call must be made before any monitored items are
+    /// created in the subscription. Use [`SessionBuilder::subscription_store`](crate::SessionBuilder::subscription_store)
+    /// to keep track of the subscription between program executions.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - Server allocated identifier for the subscription to make durable.
+    /// * `lifetime_in_hours` - Requested lifetime of the subscription, in hours.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u32)` - The lifetime in hours revised by the server.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn call_set_subscription_durable(
+        &self,
+        subscription_id: u32,
+        lifetime_in_hours: u32,
+    ) -> Result<u32, StatusCode> {
+        let args = Some(vec![
+            Variant::from(subscription_id),
+            Variant::from(lifetime_in_hours),
+        ]);
+        let object_id: NodeId = ObjectId::Server.into();
+        let method_id: NodeId = MethodId::Server_SetSubscriptionDurable.into();
+        let request: CallMethodRequest = (object_id, method_id, args).into();
+        let response = self.call_one(request).await?;
+        if response.status_code.is_bad() {
+            return Err(response.status_code);
+        }
+        match response.output_arguments {
+            Some(mut result) if result.len() == 1 => {
+                u32::try_from_variant(result.remove(0)).map_err(|_| StatusCode::BadUnexpectedError)
+            }
+            Some(result) => {
+                session_error!(
+                    self,
+                    "Expected a result with 1 arg but got {}",
+                    result.len()
+                );
+                Err(StatusCode::BadUnexpectedError)
+            }
+            None => {
+                session_error!(self, "Expected output arguments but got null");
+                Err(StatusCode::BadUnexpectedError)
+            }
+        }
+    }
+}
+
+/// Get a list of arguments from the value of an `InputArguments` or `OutputArguments`
+/// property, which is an array of extension objects.
+fn parse_arguments(value: Variant) -> Option<Vec<Argument>> {
+    let values = match value {
+        Variant::Empty => return Some(Vec::new()),
+        Variant::Array(array) => array.values,
+        _ => return None,
+    };
+    values
+        .into_iter()
+        .map(|v| match v {
+            Variant::ExtensionObject(obj) => obj.into_inner_as::<Argument>().map(|a| *a),
+            _ => None,
+        })
+        .collect()
 }
@@ -1,6 +1,9 @@
 pub(super) mod attributes;
+pub(super) mod file;
 pub(super) mod method;
 pub(super) mod node_management;
+pub(super) mod paging;
+pub(super) mod registered_nodes;
 pub(super) mod session;
 pub(super) mod subscriptions;
 pub(super) mod view;
@@ -0,0 +1,443 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt, Stream, StreamExt};
+use opcua_types::{
+    BrowseDescription, BrowseResult, ContinuationPoint, DataValue, DateTime, NodeId,
+    ReadRawModifiedDetails, ReferenceDescription, StatusCode, TimestampsToReturn, ViewDescription,
+};
+
+use crate::{
+    session::{session_warn, HistoryReadAction, HistoryReadRawOptions},
+    Session,
+};
+
+use super::attributes::history_data_values;
+
+/// The result of a single request for one node.
+struct Page<T> {
+    /// Index of the node.
+    index: usize,
+    items: Vec<Result<T, StatusCode>>,
+    /// Continuation point for the next page, null if this was the last page.
+    continuation_point: ContinuationPoint,
+}
+
+type PageFuture<T> = BoxFuture<'static, Result<Vec<Page<T>>, StatusCode>>;
+
+/// A service returning results for a list of nodes in pages, which are requested
+/// with continuation points.
+trait PagedRead: Send + Sync + 'static {
+    type Item: Send + 'static;
+
+    /// Request the first page for every node.
+    fn read_first(self: Arc<Self>, session: Arc<Session>) -> PageFuture<Self::Item>;
+
+    /// Request the next page for each node, given by its index paired with its
+    /// continuation point. Releases the continuation points on failure.
+    fn read_next(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> PageFuture<Self::Item>;
+
+    /// Try to release a list of continuation points, ignoring any errors.
+    fn release(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> BoxFuture<'static, ()>;
+}
+
+/// Stream of the items of a paged read, requesting the next pages once every
+/// item received so far has been consumed.
+struct Pager<R: PagedRead> {
+    session: Arc<Session>,
+    read: Arc<R>,
+    buffered: VecDeque<Result<R::Item, StatusCode>>,
+    /// Continuation points of the nodes with more pages.
+    pending: Vec<(usize, ContinuationPoint)>,
+    request: Option<PageFuture<R::Item>>,
+    started: bool,
+    done: bool,
+}
+
+impl<R: PagedRead> Pager<R> {
+    fn new(session: Arc<Session>, read: R) -> Self {
+        Self {
+            session,
+            read: Arc::new(read),
+            buffered: VecDeque::new(),
+            pending: Vec::new(),
+            request: None,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Take the continuation points held by the server, including any returned by the
+    /// request in flight, and release them. The stream ends after this is called.
+    fn release_outstanding(&mut self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let read = self.read.clone();
+        let session = self.session.clone();
+        let mut pending = std::mem::take(&mut self.pending);
+        let request = self.request.take();
+        self.buffered.clear();
+        self.started = true;
+        self.done = true;
+        async move {
+            if let Some(request) = request {
+                if let Ok(pages) = request.await {
+                    pending.extend(
+                        pages
+                            .into_iter()
+                            .filter(|p| !p.continuation_point.is_null())
+                            .map(|p| (p.index, p.continuation_point)),
+                    );
+                }
+            }
+            if !pending.is_empty() {
+                read.release(session, pending).await;
+            }
+        }
+    }
+}
+
+impl<R: PagedRead> Unpin for Pager<R> {}
+
+impl<R: PagedRead> Stream for Pager<R> {
+    type Item = Result<R::Item, StatusCode>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.buffered.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            if let Some(request) = &mut this.request {
+                let result = ready!(request.poll_unpin(cx));
+                this.request = None;
+                match result {
+                    Ok(pages) => {
+                        for page in pages {
+                            if !page.continuation_point.is_null() {
+                                this.pending.push((page.index, page.continuation_point));
+                            }
+                            this.buffered.extend(page.items);
+                        }
+                    }
+                    Err(e) => {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                continue;
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if !this.started {
+                this.started = true;
+                this.request = Some(this.read.clone().read_first(this.session.clone()));
+            } else if this.pending.is_empty() {
+                this.done = true;
+            } else {
+                let pending = std::mem::take(&mut this.pending);
+                this.request = Some(this.read.clone().read_next(this.session.clone(), pending));
+            }
+        }
+    }
+}
+
+impl<R: PagedRead> Drop for Pager<R> {
+    fn drop(&mut self) {
+        if self.pending.is_empty() && self.request.is_none() {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            session_warn!(
+                self.session,
+                "Paged result stream dropped outside a tokio runtime, its continuation points are not released"
+            );
+            return;
+        };
+        handle.spawn(self.release_outstanding());
+    }
+}
+
+struct HistoryRawRead {
+    nodes: Vec<NodeId>,
+    details: ReadRawModifiedDetails,
+    timestamps_to_return: TimestampsToReturn,
+}
+
+impl HistoryRawRead {
+    fn action(&self) -> impl Fn(&[usize]) -> HistoryReadAction + Send + Sync + '_ {
+        |_| HistoryReadAction::ReadRawModifiedDetails(self.details.clone())
+    }
+}
+
+impl PagedRead for HistoryRawRead {
+    type Item = (NodeId, DataValue);
+
+    fn read_first(self: Arc<Self>, session: Arc<Session>) -> PageFuture<Self::Item> {
+        if self.nodes.is_empty() {
+            return futures::future::ready(Ok(Vec::new())).boxed();
+        }
+        let pending = (0..self.nodes.len())
+            .map(|idx| (idx, ContinuationPoint::null()))
+            .collect();
+        self.read_next(session, pending)
+    }
+
+    fn read_next(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> PageFuture<Self::Item> {
+        async move {
+            let results = session
+                .history_read_page(
+                    &self.nodes,
+                    self.timestamps_to_return,
+                    self.action(),
+                    pending,
+                )
+                .await?;
+            Ok(results
+                .into_iter()
+                .map(|(index, mut result)| {
+                    let continuation_point = std::mem::take(&mut result.continuation_point);
+                    let node_id = &self.nodes[index];
+                    Page {
+                        index,
+                        items: history_data_values(result)
+                            .into_iter()
+                            .map(|v| Ok((node_id.clone(), v)))
+                            .collect(),
+                        continuation_point,
+                    }
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn release(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> BoxFuture<'static, ()> {
+        async move {
+            session
+                .release_history_continuation_points(&self.nodes, self.action(), pending)
+                .await
+        }
+        .boxed()
+    }
+}
+
+/// Stream of the raw historical values of a list of nodes, following continuation
+/// points until every value in the time range has been received.
+///
+/// Values are paired with the node they belong to, and values for each node are
+/// returned in order. If the server reports an error for a node, the stream returns a
+/// single [`DataValue`] for that node with only the status set. If a request fails,
+/// the stream ends after returning the error.
+///
+/// The next page is requested once every value received so far has been consumed.
+/// Any continuation points held by the server are released when this is dropped, in
+/// a task spawned on the current tokio runtime. Use [`HistoryValueStream::release`]
+/// to wait for that instead.
+///
+/// See also [`Session::history_read_raw`], which borrows the session.
+pub struct HistoryValueStream(Pager<HistoryRawRead>);
+
+impl HistoryValueStream {
+    /// Create a stream reading raw historical values. Nothing is requested until the
+    /// stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to read history on.
+    /// * `nodes` - The nodes to read history for.
+    /// * `time_range` - The time range to read. If `end` is before `start`, values are
+    ///   returned in reverse order.
+    /// * `options` - Additional options for the request.
+    ///
+    pub fn new(
+        session: Arc<Session>,
+        nodes: Vec<NodeId>,
+        time_range: Range<DateTime>,
+        options: HistoryReadRawOptions,
+    ) -> Self {
+        let read = HistoryRawRead {
+            nodes,
+            details: ReadRawModifiedDetails {
+                is_read_modified: false,
+                start_time: time_range.start,
+                end_time: time_range.end,
+                num_values_per_node: options.num_values_per_node,
+                return_bounds: options.return_bounds,
+            },
+            timestamps_to_return: options.timestamps_to_return,
+        };
+        Self(Pager::new(session, read))
+    }
+
+    /// Stop reading, and release any continuation points held by the server.
+    pub async fn release(mut self) {
+        self.0.release_outstanding().await
+    }
+}
+
+impl Stream for HistoryValueStream {
+    type Item = Result<(NodeId, DataValue), StatusCode>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+struct BrowseRead {
+    nodes_to_browse: Vec<BrowseDescription>,
+    max_references_per_node: u32,
+    view: Option<ViewDescription>,
+}
+
+impl BrowseRead {
+    fn pages(
+        &self,
+        results: Vec<(usize, BrowseResult)>,
+    ) -> Vec<Page<(NodeId, ReferenceDescription)>> {
+        results
+            .into_iter()
+            .map(|(index, result)| {
+                let node_id = &self.nodes_to_browse[index].node_id;
+                let items = if result.status_code.is_bad() {
+                    vec![Err(result.status_code)]
+                } else {
+                    result
+                        .references
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|r| Ok((node_id.clone(), r)))
+                        .collect()
+                };
+                Page {
+                    index,
+                    items,
+                    continuation_point: result.continuation_point,
+                }
+            })
+            .collect()
+    }
+}
+
+impl PagedRead for BrowseRead {
+    type Item = (NodeId, ReferenceDescription);
+
+    fn read_first(self: Arc<Self>, session: Arc<Session>) -> PageFuture<Self::Item> {
+        async move {
+            if self.nodes_to_browse.is_empty() {
+                return Ok(Vec::new());
+            }
+            let results = session
+                .browse_first_page(
+                    &self.nodes_to_browse,
+                    self.max_references_per_node,
+                    self.view.clone(),
+                )
+                .await?;
+            Ok(self.pages(results))
+        }
+        .boxed()
+    }
+
+    fn read_next(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> PageFuture<Self::Item> {
+        async move {
+            let results = session.browse_next_pending(pending).await?;
+            Ok(self.pages(results))
+        }
+        .boxed()
+    }
+
+    fn release(
+        self: Arc<Self>,
+        session: Arc<Session>,
+        pending: Vec<(usize, ContinuationPoint)>,
+    ) -> BoxFuture<'static, ()> {
+        async move {
+            let continuation_points = pending.into_iter().map(|(_, cp)| cp).collect::<Vec<_>>();
+            session
+                .release_continuation_points(&continuation_points)
+                .await
+        }
+        .boxed()
+    }
+}
+
+/// Stream of the references of a list of nodes, following continuation points until
+/// every reference has been received.
+///
+/// References are paired with the node they were browsed from, and references for
+/// each node are returned in order. If the server reports an error for a node, the
+/// stream returns the status for that node and continues with the other nodes. If a
+/// request fails, the stream ends after returning the error.
+///
+/// The next page is requested once every reference received so far has been consumed.
+/// Any continuation points held by the server are released when this is dropped, in
+/// a task spawned on the current tokio runtime. Use [`BrowseResultStream::release`]
+/// to wait for that instead.
+///
+/// See also [`Session::browse_all_stream`], which borrows the session.
+pub struct BrowseResultStream(Pager<BrowseRead>);
+
+impl BrowseResultStream {
+    /// Create a stream browsing a list of nodes. Nothing is requested until the
+    /// stream is polled.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to browse on.
+    /// * `nodes_to_browse` - A list of [`BrowseDescription`] describing nodes to browse.
+    /// * `max_references_per_node` - Maximum number of references returned for each node
+    ///   in a single request, `0` lets the server decide.
+    /// * `view` - Optional view to browse.
+    ///
+    pub fn new(
+        session: Arc<Session>,
+        nodes_to_browse: Vec<BrowseDescription>,
+        max_references_per_node: u32,
+        view: Option<ViewDescription>,
+    ) -> Self {
+        let read = BrowseRead {
+            nodes_to_browse,
+            max_references_per_node,
+            view,
+        };
+        Self(Pager::new(session, read))
+    }
+
+    /// Stop browsing, and release any continuation points held by the server.
+    pub async fn release(mut self) {
+        self.0.release_outstanding().await
+    }
+}
+
+impl Stream for BrowseResultStream {
+    type Item = Result<(NodeId, ReferenceDescription), StatusCode>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
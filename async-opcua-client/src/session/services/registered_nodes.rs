@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use opcua_types::{DataValue, NodeId, ReadValueId, StatusCode, TimestampsToReturn};
+
+use crate::{
+    session::{session_debug, session_warn},
+    Session,
+};
+
+/// A set of nodes registered with [`Session::register_nodes`] for fast cyclic reading,
+/// created with [`RegisteredNodes::register`].
+///
+/// Reads use the node IDs returned by the server, which some servers, typically PLCs,
+/// can resolve much faster than the original node IDs. Registered node IDs are only valid
+/// within the session that registered them, so the nodes are registered again on the
+/// next read if the session has been recreated since.
+///
+/// The nodes are unregistered when this is dropped, in a task spawned on the current
+/// tokio runtime. Use [`RegisteredNodes::unregister`] to wait for that instead.
+pub struct RegisteredNodes {
+    session: Arc<Session>,
+    node_ids: Vec<NodeId>,
+    registered: Vec<NodeId>,
+    server_session_id: NodeId,
+    unregistered: bool,
+}
+
+impl RegisteredNodes {
+    /// Register `node_ids` on the server, for reading them with [`RegisteredNodes::read`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RegisteredNodes)` - The registered nodes.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn register(
+        session: Arc<Session>,
+        node_ids: Vec<NodeId>,
+    ) -> Result<Self, StatusCode> {
+        let mut nodes = Self {
+            session,
+            node_ids,
+            registered: Vec::new(),
+            server_session_id: NodeId::null(),
+            unregistered: false,
+        };
+        nodes.register_inner().await?;
+        Ok(nodes)
+    }
+
+    async fn register_inner(&mut self) -> Result<(), StatusCode> {
+        let server_session_id = self.session.server_session_id();
+        let registered = self.session.register_nodes(&self.node_ids).await?;
+        if registered.len() != self.node_ids.len() {
+            session_warn!(
+                self.session,
+                "RegisterNodes returned {} node IDs for {} nodes",
+                registered.len(),
+                self.node_ids.len()
+            );
+            return Err(StatusCode::BadUnexpectedError);
+        }
+        self.registered = registered;
+        self.server_session_id = server_session_id;
+        Ok(())
+    }
+
+    /// The node IDs that were registered.
+    pub fn node_ids(&self) -> &[NodeId] {
+        &self.node_ids
+    }
+
+    /// The node IDs returned by the server, in the same order as [`RegisteredNodes::node_ids`].
+    pub fn registered_node_ids(&self) -> &[NodeId] {
+        &self.registered
+    }
+
+    /// Read the value of every registered node.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamps_to_return` - The [`TimestampsToReturn`] for each node.
+    /// * `max_age` - The maximum age of value to read in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<DataValue>)` - A [`DataValue`] for each node, in the same order as
+    ///   [`RegisteredNodes::node_ids`].
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read(
+        &mut self,
+        timestamps_to_return: TimestampsToReturn,
+        max_age: f64,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        if self.session.server_session_id() != self.server_session_id {
+            session_debug!(
+                self.session,
+                "Session was recreated, registering {} nodes again",
+                self.node_ids.len()
+            );
+            self.register_inner().await?;
+        }
+        let nodes_to_read: Vec<_> = self
+            .registered
+            .iter()
+            .map(|id| ReadValueId::from(id.clone()))
+            .collect();
+        self.session
+            .read(&nodes_to_read, timestamps_to_return, max_age)
+            .await
+    }
+
+    /// Unregister the nodes on the server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Request succeeded, or the session has been recreated since the nodes
+    ///   were registered, so there is nothing to unregister.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn unregister(mut self) -> Result<(), StatusCode> {
+        self.unregistered = true;
+        if self.session.server_session_id() != self.server_session_id {
+            return Ok(());
+        }
+        self.session.unregister_nodes(&self.registered).await
+    }
+}
+
+impl Drop for RegisteredNodes {
+    fn drop(&mut self) {
+        if self.unregistered || self.session.server_session_id() != self.server_session_id {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            session_warn!(
+                self.session,
+                "Registered nodes dropped outside a tokio runtime, they are not unregistered"
+            );
+            return;
+        };
+        let session = self.session.clone();
+        let registered = std::mem::take(&mut self.registered);
+        handle.spawn(async move {
+            if let Err(e) = session.unregister_nodes(&registered).await {
+                session_warn!(session, "Failed to unregister nodes: {}", e);
+            }
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use std::{sync::Arc, time::Duration};
+use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use opcua_core::{
     comms::url::hostname_from_url, sync::RwLock, trace_read_lock, trace_write_lock, ResponseMessage,
@@ -11,9 +11,9 @@ use opcua_types::{
     ActivateSessionRequest, ActivateSessionResponse, AnonymousIdentityToken,
     ApplicationDescription, ByteString, CancelRequest, CancelResponse, CloseSessionRequest,
     CloseSessionResponse, CreateSessionRequest, CreateSessionResponse, EndpointDescription, Error,
-    ExtensionObject, IntegerId, IssuedIdentityToken, MessageSecurityMode, NodeId, SignatureData,
-    SignedSoftwareCertificate, StatusCode, UAString, UserNameIdentityToken, UserTokenType,
-    X509IdentityToken,
+    ErrorContext, ExtensionObject, IntegerId, IssuedIdentityToken, MessageSecurityMode, NodeId,
+    SignatureData, SignedSoftwareCertificate, StatusCode, UAString, UserNameIdentityToken,
+    UserTokenType, X509IdentityToken,
 };
 use rsa::RsaPrivateKey;
 use tracing::error;
@@ -22,6 +22,7 @@ use crate::{
     session::{
         process_service_result, process_unexpected_response,
         request_builder::{builder_base, builder_error, RequestHeaderBuilder},
+        session_warn,
     },
     AsyncSecureChannel, IdentityToken, Session, UARequest,
 };
@@ -47,7 +48,7 @@ pub struct CreateSession<'a> {
     session_timeout: f64,
     max_response_message_size: u32,
     certificate_store: &'a RwLock<CertificateStore>,
-    endpoint: &'a EndpointDescription,
+    endpoint: Cow<'a, EndpointDescription>,
     nonce_length: usize,
 
     header: RequestHeaderBuilder,
@@ -60,8 +61,9 @@ impl<'a> CreateSession<'a> {
     ///
     /// Crate private since there is no way to safely use this.
     pub(crate) fn new(session: &'a Session) -> Self {
+        let endpoint_info = session.current_endpoint_info();
         Self {
-            endpoint_url: session.endpoint_info().endpoint.endpoint_url.clone(),
+            endpoint_url: endpoint_info.endpoint.endpoint_url.clone(),
             server_uri: UAString::null(),
             client_description: session.application_description.clone(),
             session_name: session.session_name.clone(),
@@ -70,7 +72,7 @@ impl<'a> CreateSession<'a> {
                 .read_own_certificate()
                 .map(|r| r.as_byte_string())
                 .unwrap_or_default(),
-            endpoint: &session.endpoint_info().endpoint,
+            endpoint: Cow::Owned(endpoint_info.endpoint.clone()),
             certificate_store: session.channel.certificate_store(),
             session_timeout: session.session_timeout,
             max_response_message_size: 0,
@@ -97,7 +99,7 @@ impl<'a> CreateSession<'a> {
             session_timeout: 0.0,
             max_response_message_size: 0,
             certificate_store,
-            endpoint,
+            endpoint: Cow::Borrowed(endpoint),
             nonce_length: 32,
             header: RequestHeaderBuilder::new(session_id, timeout, auth_token, request_handle),
         }
@@ -198,14 +200,17 @@ impl UARequest for CreateSession<'_> {
                     // Validate server certificate against hostname and application_uri
                     let hostname = hostname_from_url(self.endpoint.endpoint_url.as_ref())
                         .map_err(|_| StatusCode::BadUnexpectedError)?;
+                    // Endpoints constructed without calling `GetEndpoints` do not know
+                    // the application URI of the server.
                     let application_uri = self.endpoint.server.application_uri.as_ref();
+                    let application_uri = (!application_uri.is_empty()).then_some(application_uri);
 
                     let certificate_store = trace_write_lock!(self.certificate_store);
                     certificate_store.validate_or_reject_application_instance_cert(
                         &server_certificate,
                         security_policy,
                         Some(&hostname),
-                        Some(application_uri),
+                        application_uri,
                     )?;
                 } else {
                     return Err(StatusCode::BadCertificateInvalid);
@@ -258,16 +263,16 @@ impl ActivateSession {
     /// Crate private since there is no way to safely use this.
     pub(crate) fn new(session: &Session) -> Self {
         Self {
-            identity_token: session.endpoint_info().user_identity_token.clone(),
+            identity_token: session.current_endpoint_info().user_identity_token.clone(),
             private_key: session.channel.read_own_private_key(),
             locale_ids: session
-                .endpoint_info()
+                .current_endpoint_info()
                 .preferred_locales
                 .iter()
                 .map(UAString::from)
                 .collect(),
             client_software_certificates: Vec::new(),
-            endpoint: session.endpoint_info().endpoint.clone(),
+            endpoint: session.current_endpoint_info().endpoint.clone(),
             header: RequestHeaderBuilder::new_from_session(session),
         }
     }
@@ -356,8 +361,9 @@ impl ActivateSession {
             ));
         };
         let security_policy = if policy.security_policy_uri.is_empty() {
-            // Assume None
-            SecurityPolicy::None
+            // Per the standard, the security policy of the channel is used if the
+            // user token policy does not specify one.
+            channel_security_policy
         } else {
             SecurityPolicy::from_uri(policy.security_policy_uri.as_ref())
         };
@@ -407,6 +413,10 @@ impl ActivateSession {
                     error!("Cannot create an X509IdentityToken because the remote server has no cert with which to create a signature");
                     return Err(Error::new(StatusCode::BadCertificateInvalid, "Cannot create an X509IdentityToken because the remote server has no cert with which to create a signature"));
                 };
+                if security_policy == SecurityPolicy::None {
+                    error!("Cannot create an X509IdentityToken because the user token policy has no security policy to sign with");
+                    return Err(Error::new(StatusCode::BadSecurityPolicyRejected, "Cannot create an X509IdentityToken because the user token policy has no security policy to sign with"));
+                }
 
                 let user_token_signature = opcua_crypto::create_signature_data(
                     private_key,
@@ -428,7 +438,11 @@ impl ActivateSession {
                 ))
             }
             IdentityToken::IssuedToken(source) => {
-                let token = source.0.get_issued_token().await?;
+                let token = source
+                    .0
+                    .get_issued_token()
+                    .await
+                    .context("getting issued identity token")?;
                 let nonce = remote_nonce.as_ref();
                 let cert = remote_cert;
                 let secret = legacy_encrypt_secret(
@@ -472,7 +486,8 @@ impl ActivateSession {
                 message_security_mode,
                 security_policy,
             )
-            .await?;
+            .await
+            .context("creating user identity token")?;
         let client_signature = match security_policy {
             SecurityPolicy::None => SignatureData::null(),
             _ => {
@@ -537,6 +552,10 @@ impl UARequest for ActivateSession {
             tracing::debug!("activate_session success");
             // trace!("ActivateSessionResponse = {:#?}", response);
             process_service_result(&response.response_header)?;
+            // The next activation must sign the nonce returned by this one.
+            if !response.server_nonce.is_null_or_empty() {
+                channel.update_from_activated_session(&response.server_nonce)?;
+            }
             Ok(*response)
         } else {
             tracing::error!("activate_session failed");
@@ -613,6 +632,8 @@ impl UARequest for CloseSession {
 
 #[derive(Debug, Clone)]
 /// Cancels an outstanding service request by sending a [`CancelRequest`] to the server.
+/// The request is also aborted locally, so it fails immediately with
+/// `BadRequestCancelledByClient`, and any chunks not yet sent are discarded.
 ///
 /// See OPC UA Part 4 - Services 5.6.5 for complete description of the service and error responses.
 pub struct Cancel {
@@ -653,6 +674,9 @@ impl UARequest for Cancel {
     where
         Self: 'a,
     {
+        // Stop sending the request if it is still in flight, the server may
+        // not even have received it yet.
+        channel.abort_request(self.request_handle);
         let request = CancelRequest {
             request_header: self.header.header,
             request_handle: self.request_handle,
@@ -682,6 +706,8 @@ impl Session {
     ///
     pub(crate) async fn create_session(&self) -> Result<NodeId, StatusCode> {
         let response = CreateSession::new(self).send(&self.channel).await?;
+        self.channel
+            .complete_endpoint(response.server_endpoints.as_deref().unwrap_or_default());
 
         let session_id = {
             self.session_id.store(Arc::new(response.session_id.clone()));
@@ -693,6 +719,9 @@ impl Session {
 
     /// Sends an [`ActivateSessionRequest`] to the server to activate this session
     ///
+    /// When using an issued token, a token rejected by the server is invalidated
+    /// and activation is retried once with a new token from the token source.
+    ///
     /// See OPC UA Part 4 - Services 5.6.3 for complete description of the service and error responses.
     ///
     /// # Returns
@@ -701,6 +730,26 @@ impl Session {
     /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
     ///
     pub(crate) async fn activate_session(&self) -> Result<(), StatusCode> {
+        let status = match ActivateSession::new(self).send(&self.channel).await {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        let IdentityToken::IssuedToken(source) = &self.current_endpoint_info().user_identity_token
+        else {
+            return Err(status);
+        };
+        if !matches!(
+            status,
+            StatusCode::BadIdentityTokenRejected | StatusCode::BadIdentityTokenInvalid
+        ) {
+            return Err(status);
+        }
+        session_warn!(
+            self,
+            "Issued token was rejected: {}, retrying with a new token",
+            status
+        );
+        source.0.invalidate_issued_token();
         ActivateSession::new(self).send(&self.channel).await?;
         Ok(())
     }
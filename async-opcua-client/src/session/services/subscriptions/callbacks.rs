@@ -3,7 +3,7 @@ use opcua_types::{
     NotificationMessage, StatusChangeNotification, Variant,
 };
 
-use crate::{session::services::subscriptions::MonitoredItemMap, MonitoredItem};
+use crate::{session::services::subscriptions::MonitoredItemMap, MonitoredItem, SequenceGap};
 
 /// A trait for handling subscription notifications.
 /// Typically, you will want to use OnSubscriptionNotification instead,
@@ -15,6 +15,24 @@ pub trait OnSubscriptionNotificationCore: Send + Sync {
         notification: NotificationMessage,
         monitored_items: MonitoredItemMap<'_>,
     );
+
+    /// Called when notifications of the subscription were found to be missing, after
+    /// the session tried to recover them.
+    #[allow(unused)]
+    fn on_sequence_gap(&mut self, gap: &SequenceGap) {}
+}
+
+/// Callback that ignores all notifications, for subscriptions whose notifications
+/// are not delivered to the user, or not yet.
+pub(crate) struct Detached;
+
+impl OnSubscriptionNotificationCore for Detached {
+    fn on_subscription_notification(
+        &mut self,
+        _notification: NotificationMessage,
+        _monitored_items: MonitoredItemMap<'_>,
+    ) {
+    }
 }
 
 impl<T> OnSubscriptionNotificationCore for T
@@ -58,6 +76,10 @@ where
             )
         }
     }
+
+    fn on_sequence_gap(&mut self, gap: &SequenceGap) {
+        OnSubscriptionNotification::on_sequence_gap(self, gap);
+    }
 }
 
 /// A set of callbacks for notifications on a subscription.
@@ -75,6 +97,15 @@ pub trait OnSubscriptionNotification: Send + Sync {
     /// Called for each received event.
     #[allow(unused)]
     fn on_event(&mut self, event_fields: Option<Vec<Variant>>, item: &MonitoredItem) {}
+
+    /// Called when notifications of the subscription were found to be missing, after
+    /// the session tried to recover them. Data changes and events in the interval of
+    /// the gap may be missing.
+    ///
+    /// This is only called on the callback of the subscription, not on callbacks of
+    /// individual monitored items.
+    #[allow(unused)]
+    fn on_sequence_gap(&mut self, gap: &SequenceGap) {}
 }
 
 type StatusChangeCallbackFun = dyn FnMut(StatusChangeNotification) + Send + Sync;
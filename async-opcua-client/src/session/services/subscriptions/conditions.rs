@@ -0,0 +1,482 @@
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{
+    event_field::select_clause, AttributeId, ByteString, ContentFilter, DateTime, EventFilter,
+    LocalizedText, MethodId, NodeId, NumericRange, ObjectTypeId, QualifiedName,
+    SimpleAttributeOperand, StatusCode, TryFromVariant, UAString, Variant,
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::{session::session_error, EventCallback, Session};
+
+/// Number of condition events buffered for the stream of a [`ConditionSubscription`]
+/// before new events are dropped from the stream.
+const CONDITION_EVENTS_CAPACITY: usize = 256;
+
+/// Paths of the event fields read into a [`ConditionState`], after the `ConditionId`.
+const CONDITION_FIELDS: &[&str] = &[
+    "EventId",
+    "EventType",
+    "SourceNode",
+    "SourceName",
+    "Time",
+    "Message",
+    "Severity",
+    "ConditionName",
+    "BranchId",
+    "Retain",
+    "EnabledState/Id",
+    "AckedState/Id",
+    "ConfirmedState/Id",
+    "ActiveState/Id",
+    "Comment",
+];
+
+/// The state of a condition, from the last event received for it.
+///
+/// The state variables of subtypes of `ConditionType` are `None` if the condition
+/// does not have them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConditionState {
+    /// The ID of the condition object, which methods on the condition are called on.
+    pub condition_id: NodeId,
+    /// The ID of the condition branch, null for the main branch.
+    pub branch_id: NodeId,
+    /// The ID of the last event for the condition. Methods such as `Acknowledge`
+    /// refer to the condition state by this ID.
+    pub event_id: ByteString,
+    /// The event type of the condition.
+    pub event_type: NodeId,
+    /// The node the condition belongs to.
+    pub source_node: NodeId,
+    /// The name of the node the condition belongs to.
+    pub source_name: UAString,
+    /// The time of the last event for the condition.
+    pub time: DateTime,
+    /// The message of the last event for the condition.
+    pub message: LocalizedText,
+    /// The severity of the condition, between 1 and 1000.
+    pub severity: u16,
+    /// The name of the condition.
+    pub condition_name: UAString,
+    /// Whether the condition is of interest to the client. Conditions are removed
+    /// from the cache when they are no longer retained.
+    pub retain: bool,
+    /// Whether the condition is enabled.
+    pub enabled: Option<bool>,
+    /// Whether the condition is acknowledged, for acknowledgeable conditions.
+    pub acked: Option<bool>,
+    /// Whether the condition is confirmed, for acknowledgeable conditions.
+    pub confirmed: Option<bool>,
+    /// Whether the alarm is active, for alarm conditions.
+    pub active: Option<bool>,
+    /// The last comment added to the condition.
+    pub comment: LocalizedText,
+}
+
+impl ConditionState {
+    /// Select clauses for the fields of a condition state, in the order
+    /// expected by `from_fields`.
+    fn select_clauses() -> Vec<SimpleAttributeOperand> {
+        let condition_id = SimpleAttributeOperand {
+            type_definition_id: ObjectTypeId::ConditionType.into(),
+            browse_path: Some(Vec::new()),
+            attribute_id: AttributeId::NodeId as u32,
+            index_range: NumericRange::None,
+        };
+        std::iter::once(condition_id)
+            .chain(CONDITION_FIELDS.iter().map(|path| {
+                let path = path
+                    .split('/')
+                    .map(|p| QualifiedName::new(0, p))
+                    .collect::<Vec<_>>();
+                select_clause(&path, AttributeId::Value)
+            }))
+            .collect()
+    }
+
+    fn from_fields(fields: Vec<Variant>) -> Self {
+        fn value<T: TryFromVariant + Default>(field: Option<Variant>) -> T {
+            field
+                .and_then(|v| T::try_from_variant(v).ok())
+                .unwrap_or_default()
+        }
+        fn state(field: Option<Variant>) -> Option<bool> {
+            field.and_then(|v| bool::try_from_variant(v).ok())
+        }
+
+        let mut fields = fields.into_iter();
+        Self {
+            condition_id: value(fields.next()),
+            event_id: value(fields.next()),
+            event_type: value(fields.next()),
+            source_node: value(fields.next()),
+            source_name: value(fields.next()),
+            time: value(fields.next()),
+            message: value(fields.next()),
+            severity: value(fields.next()),
+            condition_name: value(fields.next()),
+            branch_id: value(fields.next()),
+            retain: value(fields.next()),
+            enabled: state(fields.next()),
+            acked: state(fields.next()),
+            confirmed: state(fields.next()),
+            active: state(fields.next()),
+            comment: value(fields.next()),
+        }
+    }
+
+    fn key(&self) -> (NodeId, NodeId) {
+        (self.condition_id.clone(), self.branch_id.clone())
+    }
+}
+
+#[derive(Default)]
+struct ConditionCache {
+    /// Retained conditions by condition ID and branch ID.
+    conditions: HashMap<(NodeId, NodeId), ConditionState>,
+    /// Conditions received since the start of a refresh, if one is in progress.
+    refreshed: Option<HashSet<(NodeId, NodeId)>>,
+}
+
+impl ConditionCache {
+    fn update(&mut self, state: ConditionState) {
+        let key = state.key();
+        if let Some(refreshed) = &mut self.refreshed {
+            refreshed.insert(key.clone());
+        }
+        if state.retain {
+            self.conditions.insert(key, state);
+        } else {
+            self.conditions.remove(&key);
+        }
+    }
+
+    fn begin_refresh(&mut self) {
+        self.refreshed = Some(HashSet::new());
+    }
+
+    fn end_refresh(&mut self) {
+        // Conditions the server did not send during the refresh are no longer retained.
+        if let Some(refreshed) = self.refreshed.take() {
+            self.conditions.retain(|key, _| refreshed.contains(key));
+        }
+    }
+}
+
+/// A subscription to condition events on a single node, created with
+/// [`Session::subscribe_conditions`]. Keeps a cache of the retained conditions, and
+/// delivers every received condition event as a [`Stream`] of [`ConditionState`].
+///
+/// Call [`ConditionSubscription::refresh`] to have the server send the current state of
+/// every retained condition, for example after creating the subscription, or after a
+/// reconnect. Conditions not sent during a refresh are removed from the cache.
+///
+/// If the stream is not polled, at most 256 events are buffered, and further events are
+/// dropped from the stream. The cache of retained conditions is still updated.
+///
+/// The subscription is not deleted when this is dropped, use
+/// [`Session::delete_subscription`] with [`ConditionSubscription::subscription_id`].
+/// The stream ends when the subscription is deleted.
+pub struct ConditionSubscription {
+    subscription_id: u32,
+    monitored_item_id: u32,
+    cache: Arc<Mutex<ConditionCache>>,
+    events: mpsc::Receiver<ConditionState>,
+}
+
+impl ConditionSubscription {
+    /// The ID of the subscription on the server.
+    pub fn subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+
+    /// The ID of the monitored item on the server.
+    pub fn monitored_item_id(&self) -> u32 {
+        self.monitored_item_id
+    }
+
+    /// Get the state of every retained condition and condition branch.
+    pub fn conditions(&self) -> Vec<ConditionState> {
+        trace_lock!(self.cache)
+            .conditions
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Get the state of the main branch of the condition with ID `condition_id`.
+    pub fn get(&self, condition_id: &NodeId) -> Option<ConditionState> {
+        trace_lock!(self.cache)
+            .conditions
+            .get(&(condition_id.clone(), NodeId::null()))
+            .cloned()
+    }
+
+    /// Get the retained condition whose last event has ID `event_id`.
+    pub fn find_event(&self, event_id: &ByteString) -> Option<ConditionState> {
+        trace_lock!(self.cache)
+            .conditions
+            .values()
+            .find(|c| &c.event_id == event_id)
+            .cloned()
+    }
+
+    /// Ask the server to send the current state of every retained condition by
+    /// calling `ConditionRefresh` on this subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session the subscription was created on.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The refresh was started, the conditions are received as events.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn refresh(&self, session: &Session) -> Result<(), StatusCode> {
+        session.condition_refresh(self.subscription_id).await
+    }
+}
+
+impl Stream for ConditionSubscription {
+    type Item = ConditionState;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Session {
+    /// Create a subscription to condition events on `node_id`, keeping track of the
+    /// state of each retained condition.
+    ///
+    /// Events that are not condition events are skipped, except for the
+    /// `RefreshStartEventType` and `RefreshEndEventType` events sent by the server
+    /// during a refresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to receive condition events from, typically the `Server`
+    ///   object or an object with the `SubscribeToEvents` bit set in its `EventNotifier`
+    ///   attribute.
+    /// * `publishing_interval` - The requested publishing interval.
+    /// * `where_clause` - Filter for which events to receive, for example on the event type.
+    ///   Use an empty [`ContentFilter`] to receive all conditions.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ConditionSubscription)` - The cache and stream of conditions on the subscription.
+    /// * `Err(StatusCode)` - The subscription or monitored item could not be created.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn subscribe_conditions(
+        &self,
+        node_id: &NodeId,
+        publishing_interval: Duration,
+        where_clause: ContentFilter,
+    ) -> Result<ConditionSubscription, StatusCode> {
+        let filter = EventFilter {
+            select_clauses: Some(ConditionState::select_clauses()),
+            where_clause,
+        };
+
+        let cache = Arc::new(Mutex::new(ConditionCache::default()));
+        let (tx, rx) = mpsc::channel(CONDITION_EVENTS_CAPACITY);
+        let callback = {
+            let cache = cache.clone();
+            EventCallback::new(move |fields, _| {
+                let Some(fields) = fields else {
+                    return;
+                };
+                let state = ConditionState::from_fields(fields);
+                let mut cache = trace_lock!(cache);
+                if state.event_type == ObjectTypeId::RefreshStartEventType {
+                    cache.begin_refresh();
+                } else if state.event_type == ObjectTypeId::RefreshEndEventType {
+                    cache.end_refresh();
+                } else if !state.condition_id.is_null() {
+                    cache.update(state.clone());
+                    if let Err(mpsc::error::TrySendError::Full(state)) = tx.try_send(state) {
+                        warn!(
+                            "Condition event stream is full, dropping event for condition {}",
+                            state.condition_id
+                        );
+                    }
+                }
+            })
+        };
+
+        let (subscription_id, monitored_item_id) = self
+            .create_event_subscription(node_id, publishing_interval, filter, callback)
+            .await?;
+        Ok(ConditionSubscription {
+            subscription_id,
+            monitored_item_id,
+            cache,
+            events: rx,
+        })
+    }
+
+    /// Ask the server to send the current state of every retained condition to a
+    /// subscription, by calling `ConditionRefresh`.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The subscription to send the conditions to.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The refresh was started, the conditions are received as events.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn condition_refresh(&self, subscription_id: u32) -> Result<(), StatusCode> {
+        self.call_condition_method(
+            ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh,
+            vec![subscription_id.into()],
+        )
+        .await
+    }
+
+    /// Ask the server to send the current state of every retained condition to a single
+    /// monitored item, by calling `ConditionRefresh2`.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The subscription of the monitored item.
+    /// * `monitored_item_id` - The monitored item to send the conditions to.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The refresh was started, the conditions are received as events.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn condition_refresh2(
+        &self,
+        subscription_id: u32,
+        monitored_item_id: u32,
+    ) -> Result<(), StatusCode> {
+        self.call_condition_method(
+            ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh2,
+            vec![subscription_id.into(), monitored_item_id.into()],
+        )
+        .await
+    }
+
+    /// Acknowledge a condition, by calling `Acknowledge` on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition_id` - The condition to acknowledge.
+    /// * `event_id` - The ID of the event being acknowledged, typically
+    ///   [`ConditionState::event_id`] of the last event for the condition.
+    /// * `comment` - A comment to add to the condition.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The condition was acknowledged.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn acknowledge_condition(
+        &self,
+        condition_id: &NodeId,
+        event_id: &ByteString,
+        comment: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        self.call_condition_method(
+            condition_id.clone(),
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            vec![event_id.clone().into(), comment.into().into()],
+        )
+        .await
+    }
+
+    /// Confirm a condition, by calling `Confirm` on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition_id` - The condition to confirm.
+    /// * `event_id` - The ID of the event being confirmed, typically
+    ///   [`ConditionState::event_id`] of the last event for the condition.
+    /// * `comment` - A comment to add to the condition.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The condition was confirmed.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn confirm_condition(
+        &self,
+        condition_id: &NodeId,
+        event_id: &ByteString,
+        comment: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        self.call_condition_method(
+            condition_id.clone(),
+            MethodId::AcknowledgeableConditionType_Confirm,
+            vec![event_id.clone().into(), comment.into().into()],
+        )
+        .await
+    }
+
+    /// Add a comment to a condition, by calling `AddComment` on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition_id` - The condition to comment.
+    /// * `event_id` - The ID of the event being commented, typically
+    ///   [`ConditionState::event_id`] of the last event for the condition.
+    /// * `comment` - The comment to add.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The comment was added.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn add_condition_comment(
+        &self,
+        condition_id: &NodeId,
+        event_id: &ByteString,
+        comment: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        self.call_condition_method(
+            condition_id.clone(),
+            MethodId::ConditionType_AddComment,
+            vec![event_id.clone().into(), comment.into().into()],
+        )
+        .await
+    }
+
+    async fn call_condition_method(
+        &self,
+        object_id: NodeId,
+        method_id: MethodId,
+        args: Vec<Variant>,
+    ) -> Result<(), StatusCode> {
+        let result = self
+            .call_one((object_id.clone(), method_id.into(), Some(args)))
+            .await?;
+        if result.status_code.is_bad() {
+            session_error!(
+                self,
+                "Failed to call {:?} on {}: {}",
+                method_id,
+                object_id,
+                result.status_code
+            );
+            return Err(result.status_code);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use opcua_types::{
+    AttributeId, DataChangeFilter, ExtensionObject, MonitoredItemCreateRequest, NodeId, Range,
+    ReadValueId, StatusCode, TimestampsToReturn, Variant,
+};
+
+use crate::{
+    session::{session_debug, session_warn},
+    Session,
+};
+
+use super::service::CreatedMonitoredItem;
+
+/// Status codes returned by servers that do not accept a percent deadband for an item.
+fn is_percent_deadband_rejected(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BadDeadbandFilterInvalid
+            | StatusCode::BadMonitoredItemFilterUnsupported
+            | StatusCode::BadFilterNotAllowed
+    )
+}
+
+impl Session {
+    /// Read the `EURange` property of an analog item, which is the range of values
+    /// the item is expected to have, and the range percent deadband is relative to.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The analog item to read the range of.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Range)` - The engineering unit range of the item.
+    /// * `Err(StatusCode)` - The item has no `EURange` property, or the request failed.
+    ///
+    pub async fn read_eu_range(&self, node_id: &NodeId) -> Result<Range, StatusCode> {
+        let range_id = self.translate_path(node_id.clone(), ".EURange").await?;
+        let value = self
+            .read(
+                &[ReadValueId::new(range_id, AttributeId::Value)],
+                TimestampsToReturn::Neither,
+                0.0,
+            )
+            .await?
+            .pop()
+            .ok_or(StatusCode::BadUnexpectedError)?;
+        if let Some(status) = value.status.filter(|s| s.is_bad()) {
+            return Err(status);
+        }
+        match value.value {
+            Some(Variant::ExtensionObject(obj)) => obj
+                .into_inner_as::<Range>()
+                .map(|r| *r)
+                .ok_or(StatusCode::BadTypeMismatch),
+            _ => Err(StatusCode::BadTypeMismatch),
+        }
+    }
+
+    /// Creates monitored items like [`Session::create_monitored_items`], falling back to an
+    /// absolute deadband for items with a percent deadband [`DataChangeFilter`] that the
+    /// server rejects.
+    ///
+    /// Servers are not required to support percent deadband. For each rejected item, the
+    /// `EURange` of the node is read with [`Session::read_eu_range`], and the item is created
+    /// again with an absolute deadband of the same percentage of that range. Items whose range
+    /// cannot be read keep the error from the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The Server-assigned identifier for the Subscription that will report Notifications for this MonitoredItem
+    /// * `timestamps_to_return` - An enumeration that specifies the timestamp Attributes to be transmitted for each MonitoredItem.
+    /// * `items_to_create` - A list of [`MonitoredItemCreateRequest`] to be created and assigned to the specified Subscription.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<CreatedMonitoredItem>)` - A list of [`CreatedMonitoredItem`] corresponding to the items to create.
+    ///   The size and order of the list matches the size and order of the `items_to_create` request parameter.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn create_monitored_items_with_deadband_fallback(
+        &self,
+        subscription_id: u32,
+        timestamps_to_return: TimestampsToReturn,
+        items_to_create: Vec<MonitoredItemCreateRequest>,
+    ) -> Result<Vec<CreatedMonitoredItem>, StatusCode> {
+        let mut results = self
+            .create_monitored_items(subscription_id, timestamps_to_return, items_to_create)
+            .await?;
+
+        let mut retry_indices = Vec::new();
+        let mut retry_items = Vec::new();
+        for (index, item) in results.iter().enumerate() {
+            if !is_percent_deadband_rejected(item.result.status_code) {
+                continue;
+            }
+            let Some(percent) = item
+                .requested_parameters
+                .filter
+                .inner_as::<DataChangeFilter>()
+                .and_then(|f| f.percent().map(|p| (f.trigger, p)))
+            else {
+                continue;
+            };
+            let node_id = &item.item_to_monitor.node_id;
+            let range = match self.read_eu_range(node_id).await {
+                Ok(range) => range,
+                Err(e) => {
+                    session_warn!(
+                        self,
+                        "Server rejected percent deadband for {}, and its EURange could not be read: {}",
+                        node_id,
+                        e
+                    );
+                    continue;
+                }
+            };
+            session_debug!(
+                self,
+                "Server rejected percent deadband for {}, using absolute deadband from EURange {}..{}",
+                node_id,
+                range.low,
+                range.high
+            );
+            let (trigger, percent) = percent;
+            let mut requested_parameters = item.requested_parameters.clone();
+            requested_parameters.filter = ExtensionObject::from_message(
+                DataChangeFilter::absolute_deadband_from_percent(trigger, percent, &range),
+            );
+            retry_indices.push(index);
+            retry_items.push(MonitoredItemCreateRequest {
+                item_to_monitor: item.item_to_monitor.clone(),
+                monitoring_mode: item.monitoring_mode,
+                requested_parameters,
+            });
+        }
+
+        if retry_items.is_empty() {
+            return Ok(results);
+        }
+
+        let retried = self
+            .create_monitored_items(subscription_id, timestamps_to_return, retry_items)
+            .await?;
+        for (index, item) in retry_indices.into_iter().zip(retried) {
+            results[index] = item;
+        }
+        Ok(results)
+    }
+}
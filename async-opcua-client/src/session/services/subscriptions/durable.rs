@@ -0,0 +1,462 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+use opcua_core::trace_lock;
+use opcua_types::{
+    read_u32, read_u8, write_u32, write_u8, BinaryDecodable, BinaryEncodable, Context,
+    ContextOwned, EncodingResult, Error, MonitoredItemCreateRequest, MonitoringParameters, NodeId,
+    StatusCode,
+};
+
+use super::{MonitoredItem, OnSubscriptionNotificationCore, Subscription};
+use crate::{session::session_warn, Session};
+
+/// Version of the format written by [`FileSubscriptionStore`].
+const FILE_FORMAT_VERSION: u32 = 1;
+
+/// Delay between a change to the subscriptions and saving them to the store. Further
+/// changes in the meantime, such as more notifications, are saved together.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Storage for the subscriptions of a session, so that they can be picked up again
+/// after the client has been disconnected for a long time, or restarted.
+///
+/// The session calls [`SubscriptionStore::save`] shortly after subscriptions or monitored items
+/// change, or a notification is received, and when it disconnects. Saving runs on the blocking
+/// thread pool, so it may do blocking IO. Implement this to keep the state in a database, for
+/// example. [`FileSubscriptionStore`] keeps it in a single file.
+pub trait SubscriptionStore: Send + Sync {
+    /// Persist the current state of the subscriptions in the session.
+    fn save(&self, subscriptions: &PersistedSubscriptions) -> Result<(), Error>;
+
+    /// Load the last persisted state, or `None` if nothing has been stored.
+    fn load(&self) -> Result<Option<PersistedSubscriptions>, Error>;
+}
+
+/// Saves the subscriptions of a session to its [`SubscriptionStore`] in the background,
+/// so that writing to the store never holds up the publish loop.
+#[derive(Clone)]
+pub(crate) struct SubscriptionPersister {
+    session: Weak<Session>,
+    store: Arc<dyn SubscriptionStore>,
+    scheduled: Arc<AtomicBool>,
+    save_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SubscriptionPersister {
+    pub(crate) fn new(session: Weak<Session>, store: Arc<dyn SubscriptionStore>) -> Self {
+        Self {
+            session,
+            store,
+            scheduled: Arc::new(AtomicBool::new(false)),
+            save_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Save the subscriptions after [`SAVE_DELAY`], unless a save is already scheduled.
+    pub(crate) fn schedule(&self) {
+        if self.scheduled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let persister = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            persister.save().await;
+        });
+    }
+
+    /// Save the current state of the subscriptions now.
+    pub(crate) async fn save(&self) {
+        // Only one save runs at a time, so that an older snapshot never overwrites a newer one.
+        let _guard = self.save_lock.lock().await;
+        // Changes made from here on schedule another save.
+        self.scheduled.store(false, Ordering::Release);
+        let Some(session) = self.session.upgrade() else {
+            return;
+        };
+        let subscriptions = {
+            let subscription_state = trace_lock!(session.subscription_state);
+            subscription_state.persisted(session.server_session_id())
+        };
+        let store = self.store.clone();
+        match tokio::task::spawn_blocking(move || store.save(&subscriptions)).await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                session_warn!(session, "Failed to save subscriptions: {e}");
+            }
+            Err(e) => {
+                session_warn!(session, "Failed to save subscriptions: {e}");
+            }
+        }
+    }
+}
+
+/// Snapshot of the subscriptions in a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedSubscriptions {
+    /// ID of the session on the server the subscriptions belong to.
+    pub session_id: NodeId,
+    /// The subscriptions in the session.
+    pub subscriptions: Vec<PersistedSubscription>,
+}
+
+/// Snapshot of a single subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedSubscription {
+    /// Subscription ID, supplied by the server.
+    pub subscription_id: u32,
+    /// Revised publishing interval.
+    pub publishing_interval: Duration,
+    /// Revised lifetime count.
+    pub lifetime_count: u32,
+    /// Revised max keep alive count.
+    pub max_keep_alive_count: u32,
+    /// Max notifications per publish.
+    pub max_notifications_per_publish: u32,
+    /// Subscription priority.
+    pub priority: u8,
+    /// Whether publishing is enabled.
+    pub publishing_enabled: bool,
+    /// Sequence number of the last notification received with data, if any.
+    pub last_sequence_number: Option<u32>,
+    /// Monitored items in the subscription.
+    pub monitored_items: Vec<PersistedMonitoredItem>,
+}
+
+/// Snapshot of a single monitored item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedMonitoredItem {
+    /// Monitored item ID, supplied by the server.
+    pub id: u32,
+    /// The parameters the monitored item was created with, revised by the server.
+    pub request: MonitoredItemCreateRequest,
+    /// IDs of the monitored items triggered by this one.
+    pub triggered_items: Vec<u32>,
+}
+
+impl PersistedSubscription {
+    pub(crate) fn new(subscription: &Subscription) -> Self {
+        let mut monitored_items = subscription
+            .monitored_items
+            .values()
+            .map(PersistedMonitoredItem::new)
+            .collect::<Vec<_>>();
+        monitored_items.sort_by_key(|i| i.id);
+        Self {
+            subscription_id: subscription.subscription_id,
+            publishing_interval: subscription.publishing_interval,
+            lifetime_count: subscription.lifetime_count,
+            max_keep_alive_count: subscription.max_keep_alive_count,
+            max_notifications_per_publish: subscription.max_notifications_per_publish,
+            priority: subscription.priority,
+            publishing_enabled: subscription.publishing_enabled,
+            last_sequence_number: subscription.last_sequence_number,
+            monitored_items,
+        }
+    }
+
+    pub(crate) fn into_subscription(
+        self,
+        callback: Box<dyn OnSubscriptionNotificationCore>,
+    ) -> Subscription {
+        let mut subscription = Subscription::new(
+            self.subscription_id,
+            self.publishing_interval,
+            self.lifetime_count,
+            self.max_keep_alive_count,
+            self.max_notifications_per_publish,
+            self.priority,
+            self.publishing_enabled,
+            callback,
+        );
+        subscription.last_sequence_number = self.last_sequence_number;
+        for item in self.monitored_items {
+            subscription.insert_existing_monitored_item(item.into_monitored_item());
+        }
+        subscription
+    }
+
+    fn encode<S: Write + ?Sized>(&self, stream: &mut S, ctx: &Context<'_>) -> EncodingResult<()> {
+        write_u32(stream, self.subscription_id)?;
+        (self.publishing_interval.as_secs_f64() * 1000.0).encode(stream, ctx)?;
+        write_u32(stream, self.lifetime_count)?;
+        write_u32(stream, self.max_keep_alive_count)?;
+        write_u32(stream, self.max_notifications_per_publish)?;
+        write_u8(stream, self.priority)?;
+        self.publishing_enabled.encode(stream, ctx)?;
+        // Sequence numbers start at 1, so 0 means that nothing has been received.
+        write_u32(stream, self.last_sequence_number.unwrap_or(0))?;
+        write_u32(stream, self.monitored_items.len() as u32)?;
+        for item in &self.monitored_items {
+            write_u32(stream, item.id)?;
+            item.request.encode(stream, ctx)?;
+            Some(item.triggered_items.clone()).encode(stream, ctx)?;
+        }
+        Ok(())
+    }
+
+    fn decode<S: Read + ?Sized>(stream: &mut S, ctx: &Context<'_>) -> EncodingResult<Self> {
+        let subscription_id = read_u32(stream)?;
+        let publishing_interval = f64::decode(stream, ctx)?;
+        let lifetime_count = read_u32(stream)?;
+        let max_keep_alive_count = read_u32(stream)?;
+        let max_notifications_per_publish = read_u32(stream)?;
+        let priority = read_u8(stream)?;
+        let publishing_enabled = bool::decode(stream, ctx)?;
+        let last_sequence_number = Some(read_u32(stream)?).filter(|s| *s != 0);
+        let len = read_u32(stream)? as usize;
+        if len > ctx.options().max_array_length {
+            return Err(Error::decoding(format!(
+                "Monitored item count {} exceeds decoding limit {}",
+                len,
+                ctx.options().max_array_length
+            )));
+        }
+        let mut monitored_items = Vec::with_capacity(len);
+        for _ in 0..len {
+            monitored_items.push(PersistedMonitoredItem {
+                id: read_u32(stream)?,
+                request: MonitoredItemCreateRequest::decode(stream, ctx)?,
+                triggered_items: <Option<Vec<u32>>>::decode(stream, ctx)?.unwrap_or_default(),
+            });
+        }
+        Ok(Self {
+            subscription_id,
+            publishing_interval: Duration::from_millis(publishing_interval.max(0.0).floor() as u64),
+            lifetime_count,
+            max_keep_alive_count,
+            max_notifications_per_publish,
+            priority,
+            publishing_enabled,
+            last_sequence_number,
+            monitored_items,
+        })
+    }
+}
+
+impl PersistedMonitoredItem {
+    fn new(item: &MonitoredItem) -> Self {
+        Self {
+            id: item.id,
+            request: MonitoredItemCreateRequest {
+                item_to_monitor: item.item_to_monitor.clone(),
+                monitoring_mode: item.monitoring_mode,
+                requested_parameters: MonitoringParameters {
+                    client_handle: item.client_handle,
+                    sampling_interval: item.sampling_interval,
+                    filter: item.filter.clone(),
+                    queue_size: item.queue_size as u32,
+                    discard_oldest: item.discard_oldest,
+                },
+            },
+            triggered_items: item.triggered_items.iter().copied().collect(),
+        }
+    }
+
+    fn into_monitored_item(self) -> MonitoredItem {
+        let parameters = self.request.requested_parameters;
+        MonitoredItem {
+            id: self.id,
+            client_handle: parameters.client_handle,
+            item_to_monitor: self.request.item_to_monitor,
+            queue_size: parameters.queue_size as usize,
+            monitoring_mode: self.request.monitoring_mode,
+            sampling_interval: parameters.sampling_interval,
+            triggered_items: self.triggered_items.into_iter().collect::<BTreeSet<_>>(),
+            discard_oldest: parameters.discard_oldest,
+            filter: parameters.filter,
+        }
+    }
+}
+
+impl PersistedSubscriptions {
+    pub(crate) fn new(session_id: NodeId, subscriptions: &HashMap<u32, Subscription>) -> Self {
+        let mut subscriptions = subscriptions
+            .values()
+            .map(PersistedSubscription::new)
+            .collect::<Vec<_>>();
+        subscriptions.sort_by_key(|s| s.subscription_id);
+        Self {
+            session_id,
+            subscriptions,
+        }
+    }
+
+    /// Encode the subscriptions in OPC UA binary.
+    pub fn encode<S: Write + ?Sized>(
+        &self,
+        stream: &mut S,
+        ctx: &Context<'_>,
+    ) -> EncodingResult<()> {
+        write_u32(stream, FILE_FORMAT_VERSION)?;
+        self.session_id.encode(stream, ctx)?;
+        write_u32(stream, self.subscriptions.len() as u32)?;
+        for subscription in &self.subscriptions {
+            subscription.encode(stream, ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Decode subscriptions written by [`PersistedSubscriptions::encode`].
+    pub fn decode<S: Read + ?Sized>(stream: &mut S, ctx: &Context<'_>) -> EncodingResult<Self> {
+        let version = read_u32(stream)?;
+        if version != FILE_FORMAT_VERSION {
+            return Err(Error::decoding(format!(
+                "Unsupported subscription store version {version}"
+            )));
+        }
+        let session_id = NodeId::decode(stream, ctx)?;
+        let len = read_u32(stream)? as usize;
+        if len > ctx.options().max_array_length {
+            return Err(Error::decoding(format!(
+                "Subscription count {} exceeds decoding limit {}",
+                len,
+                ctx.options().max_array_length
+            )));
+        }
+        let mut subscriptions = Vec::with_capacity(len);
+        for _ in 0..len {
+            subscriptions.push(PersistedSubscription::decode(stream, ctx)?);
+        }
+        Ok(Self {
+            session_id,
+            subscriptions,
+        })
+    }
+}
+
+/// A [`SubscriptionStore`] keeping the subscriptions in a single file, encoded
+/// in OPC UA binary. The file is replaced atomically on each save.
+pub struct FileSubscriptionStore {
+    path: PathBuf,
+    context: ContextOwned,
+}
+
+impl FileSubscriptionStore {
+    /// Create a new file subscription store writing to `path`. The file
+    /// is created on the first save.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            context: ContextOwned::default(),
+        }
+    }
+
+    /// Use a custom encoding context, for example one with type loaders for
+    /// custom monitored item filters.
+    pub fn with_context(mut self, context: ContextOwned) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Path of the file the subscriptions are stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SubscriptionStore for FileSubscriptionStore {
+    fn save(&self, subscriptions: &PersistedSubscriptions) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        subscriptions.encode(&mut buf, &self.context.context())?;
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, buf)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                Error::new(
+                    StatusCode::BadResourceUnavailable,
+                    format!(
+                        "Failed to write subscriptions to {}: {e}",
+                        self.path.display()
+                    ),
+                )
+            })
+    }
+
+    fn load(&self) -> Result<Option<PersistedSubscriptions>, Error> {
+        let buf = match std::fs::read(&self.path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(Error::new(
+                    StatusCode::BadResourceUnavailable,
+                    format!(
+                        "Failed to read subscriptions from {}: {e}",
+                        self.path.display()
+                    ),
+                ))
+            }
+        };
+        PersistedSubscriptions::decode(&mut Cursor::new(buf), &self.context.context()).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_types::{
+        DataChangeFilter, DataChangeTrigger, DeadbandType, ExtensionObject,
+        MonitoredItemCreateRequest, MonitoringMode, MonitoringParameters, NodeId, ReadValueId,
+    };
+
+    use super::{
+        FileSubscriptionStore, PersistedMonitoredItem, PersistedSubscription,
+        PersistedSubscriptions, SubscriptionStore,
+    };
+
+    #[test]
+    fn file_store_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "opcua-subscription-store-{}.bin",
+            std::process::id()
+        ));
+        let store = FileSubscriptionStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let subscriptions = PersistedSubscriptions {
+            session_id: NodeId::new(1, "session"),
+            subscriptions: vec![PersistedSubscription {
+                subscription_id: 5,
+                publishing_interval: Duration::from_millis(250),
+                lifetime_count: 60,
+                max_keep_alive_count: 20,
+                max_notifications_per_publish: 100,
+                priority: 3,
+                publishing_enabled: true,
+                last_sequence_number: Some(17),
+                monitored_items: vec![PersistedMonitoredItem {
+                    id: 9,
+                    request: MonitoredItemCreateRequest {
+                        item_to_monitor: ReadValueId::from(NodeId::new(2, 15)),
+                        monitoring_mode: MonitoringMode::Reporting,
+                        requested_parameters: MonitoringParameters {
+                            client_handle: 1001,
+                            sampling_interval: 100.0,
+                            filter: ExtensionObject::from_message(DataChangeFilter {
+                                trigger: DataChangeTrigger::StatusValue,
+                                deadband_type: DeadbandType::Absolute as u32,
+                                deadband_value: 0.5,
+                            }),
+                            queue_size: 10,
+                            discard_oldest: false,
+                        },
+                    },
+                    triggered_items: vec![10, 11],
+                }],
+            }],
+        };
+        store.save(&subscriptions).unwrap();
+        let loaded = store.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Some(subscriptions));
+    }
+}
@@ -27,7 +27,7 @@ pub enum SubscriptionActivity {
 /// An event loop for running periodic subscription tasks.
 ///
 /// This handles publshing on a fixed interval, republishing failed requests,
-/// and subscription keep-alive.
+/// subscription keep-alive, and reading the monitored items of sampled subscriptions.
 pub(crate) struct SubscriptionEventLoop {
     session: Arc<Session>,
     trigger_publish_recv: tokio::sync::watch::Receiver<Instant>,
@@ -67,8 +67,8 @@ impl SubscriptionEventLoop {
     /// [SubscriptionActivity] enums, reporting activity to the session event loop.
     pub(crate) fn run(self) -> impl Stream<Item = SubscriptionActivity> {
         futures::stream::unfold(
-            (self, FuturesUnordered::new()),
-            |(mut slf, mut futures)| async move {
+            (self, FuturesUnordered::new(), FuturesUnordered::new()),
+            |(mut slf, mut futures, mut samples)| async move {
                 // Store the next publish time, or None if there are no active subscriptions.
                 let mut next = slf.session.next_publish_time(false);
                 let mut recv: tokio::sync::watch::Receiver<Instant> =
@@ -96,7 +96,25 @@ impl SubscriptionEventLoop {
                         Either::Right(futures.next())
                     };
 
+                    // Sampled subscriptions are read one batch at a time, the next read
+                    // is not scheduled until the current one is done.
+                    let next_sample_fut = match slf.session.next_sample_time() {
+                        Some(next_sample) if samples.is_empty() => {
+                            Either::Left(tokio::time::sleep_until(next_sample.into()))
+                        }
+                        _ => Either::Right(futures::future::pending::<()>()),
+                    };
+                    let sample_done_fut = if samples.is_empty() {
+                        Either::Left(futures::future::pending())
+                    } else {
+                        Either::Right(samples.next())
+                    };
+
                     tokio::select! {
+                        _ = next_sample_fut => {
+                            samples.push(slf.static_sample());
+                        }
+                        _ = sample_done_fut => {}
                         // Both internal ticks and external triggers result in publish requests.
                         v = recv.wait_for(|i| i > &slf.last_external_trigger) => {
                             if let Ok(v) = v {
@@ -194,7 +212,7 @@ impl SubscriptionEventLoop {
                     }
                 };
 
-                Some((res, (slf, futures)))
+                Some((res, (slf, futures, samples)))
             },
         )
     }
@@ -203,4 +221,9 @@ impl SubscriptionEventLoop {
         let inner_session = self.session.clone();
         async move { inner_session.publish().await }
     }
+
+    fn static_sample(&self) -> impl Future<Output = ()> + 'static {
+        let inner_session = self.session.clone();
+        async move { inner_session.sample_subscriptions().await }
+    }
 }
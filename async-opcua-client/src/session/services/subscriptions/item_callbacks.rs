@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{
+    DataChangeNotification, EventNotificationList, ExtensionObject, MonitoredItemCreateRequest,
+    NotificationMessage, StatusCode, TimestampsToReturn,
+};
+
+use crate::{
+    session::{session_error, session_warn},
+    MonitoredItem, OnSubscriptionNotification, Session,
+};
+
+use super::Subscription;
+
+/// Callback shared between the monitored items it was registered for.
+pub(crate) type ItemCallback = Arc<Mutex<dyn OnSubscriptionNotification>>;
+
+/// Handle to a monitored item, wrapping its client handle.
+///
+/// Unlike the server assigned subscription and monitored item IDs, the handle stays
+/// the same when the session recreates the subscription on a new session, or restores
+/// it from a [`SubscriptionStore`](crate::SubscriptionStore), so it can be kept to
+/// refer to the item across reconnects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MonitoredItemHandle(u32);
+
+impl MonitoredItemHandle {
+    /// Create a handle from the client handle of a monitored item.
+    pub fn new(client_handle: u32) -> Self {
+        Self(client_handle)
+    }
+
+    /// Get the client handle of the monitored item.
+    pub fn client_handle(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Subscription {
+    pub(super) fn set_item_callback(&mut self, client_handle: u32, callback: ItemCallback) {
+        self.item_callbacks.insert(client_handle, callback);
+    }
+
+    /// Take the callbacks of individual monitored items, to move them to a recreated
+    /// subscription.
+    pub(crate) fn take_item_callbacks(&mut self) -> Vec<(u32, ItemCallback)> {
+        self.item_callbacks.drain().collect()
+    }
+
+    pub(crate) fn extend_item_callbacks(&mut self, callbacks: Vec<(u32, ItemCallback)>) {
+        self.item_callbacks.extend(callbacks);
+    }
+
+    fn item_callback(&self, client_handle: u32) -> Option<(&ItemCallback, &MonitoredItem)> {
+        let callback = self.item_callbacks.get(&client_handle)?;
+        let item = self
+            .client_handles
+            .get(&client_handle)
+            .and_then(|id| self.monitored_items.get(id))?;
+        Some((callback, item))
+    }
+
+    /// Deliver data changes and events to the callbacks of their monitored items.
+    /// Returns the rest of the notification for the subscription callback, or `None`
+    /// if all of it was delivered.
+    pub(super) fn dispatch_to_items(
+        &self,
+        mut notification: NotificationMessage,
+    ) -> Option<NotificationMessage> {
+        if self.item_callbacks.is_empty() {
+            return Some(notification);
+        }
+        let data = match notification.notification_data.take() {
+            Some(data) if !data.is_empty() => data,
+            // Keep-alives go to the subscription callback.
+            data => {
+                notification.notification_data = data;
+                return Some(notification);
+            }
+        };
+
+        let mut remaining = Vec::with_capacity(data.len());
+        for obj in data {
+            if obj.inner_is::<DataChangeNotification>() {
+                let Some(change) = obj.into_inner_as::<DataChangeNotification>() else {
+                    continue;
+                };
+                let mut rest = Vec::new();
+                for notif in change.monitored_items.into_iter().flatten() {
+                    match self.item_callback(notif.client_handle) {
+                        Some((callback, item)) => {
+                            trace_lock!(callback).on_data_value(notif.value, item)
+                        }
+                        None => rest.push(notif),
+                    }
+                }
+                if !rest.is_empty() {
+                    remaining.push(ExtensionObject::from_message(DataChangeNotification {
+                        monitored_items: Some(rest),
+                        diagnostic_infos: None,
+                    }));
+                }
+            } else if obj.inner_is::<EventNotificationList>() {
+                let Some(list) = obj.into_inner_as::<EventNotificationList>() else {
+                    continue;
+                };
+                let mut rest = Vec::new();
+                for notif in list.events.into_iter().flatten() {
+                    match self.item_callback(notif.client_handle) {
+                        Some((callback, item)) => {
+                            trace_lock!(callback).on_event(notif.event_fields, item)
+                        }
+                        None => rest.push(notif),
+                    }
+                }
+                if !rest.is_empty() {
+                    remaining.push(ExtensionObject::from_message(EventNotificationList {
+                        events: Some(rest),
+                    }));
+                }
+            } else {
+                remaining.push(obj);
+            }
+        }
+
+        if remaining.is_empty() {
+            return None;
+        }
+        notification.notification_data = Some(remaining);
+        Some(notification)
+    }
+}
+
+impl Session {
+    /// Create monitored items on a subscription, with a callback receiving the data changes
+    /// and events of just these items, instead of the callback of the subscription. The
+    /// callback is shared by the items, so creating a group of items with one callback is
+    /// the same as registering it with [`Session::set_monitored_item_callback`].
+    ///
+    /// Status changes of the subscription are only delivered to the subscription callback.
+    /// The callback is kept when the subscription is recreated on reconnect.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The Server-assigned identifier for the Subscription that will report Notifications for the items.
+    /// * `timestamps_to_return` - An enumeration that specifies the timestamp Attributes to be transmitted for each MonitoredItem.
+    /// * `items_to_create` - A list of [`MonitoredItemCreateRequest`] to be created and assigned to the specified Subscription.
+    /// * `callback` - The callback receiving notifications for the items.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<Result<MonitoredItemHandle, StatusCode>>)` - The handle of each item, or the status
+    ///   it could not be created with, in the order of `items_to_create`.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn create_monitored_items_with_callback(
+        &self,
+        subscription_id: u32,
+        timestamps_to_return: TimestampsToReturn,
+        items_to_create: Vec<MonitoredItemCreateRequest>,
+        callback: impl OnSubscriptionNotification + 'static,
+    ) -> Result<Vec<Result<MonitoredItemHandle, StatusCode>>, StatusCode> {
+        let callback: ItemCallback = Arc::new(Mutex::new(callback));
+        let results = self
+            .create_monitored_items_inner(
+                subscription_id,
+                timestamps_to_return,
+                items_to_create,
+                Some(callback),
+            )
+            .await?;
+        Ok(results
+            .into_iter()
+            .map(|r| {
+                if r.result.status_code.is_bad() {
+                    Err(r.result.status_code)
+                } else {
+                    Ok(MonitoredItemHandle::new(
+                        r.requested_parameters.client_handle,
+                    ))
+                }
+            })
+            .collect())
+    }
+
+    /// Create a single monitored item on a subscription, with a callback receiving the
+    /// data changes and events of just this item. See
+    /// [`Session::create_monitored_items_with_callback`].
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The Server-assigned identifier for the Subscription that will report Notifications for the item.
+    /// * `timestamps_to_return` - An enumeration that specifies the timestamp Attributes to be transmitted for the MonitoredItem.
+    /// * `item_to_create` - The [`MonitoredItemCreateRequest`] for the item.
+    /// * `callback` - The callback receiving notifications for the item.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MonitoredItemHandle)` - The handle of the created item.
+    /// * `Err(StatusCode)` - Request failed, or the item could not be created,
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn create_monitored_item_with_callback(
+        &self,
+        subscription_id: u32,
+        timestamps_to_return: TimestampsToReturn,
+        item_to_create: MonitoredItemCreateRequest,
+        callback: impl OnSubscriptionNotification + 'static,
+    ) -> Result<MonitoredItemHandle, StatusCode> {
+        self.create_monitored_items_with_callback(
+            subscription_id,
+            timestamps_to_return,
+            vec![item_to_create],
+            callback,
+        )
+        .await?
+        .pop()
+        .ok_or(StatusCode::BadUnexpectedError)?
+        .inspect_err(|e| {
+            session_error!(self, "Failed to create monitored item: {}", e);
+        })
+    }
+
+    /// Register a callback receiving the data changes and events of existing monitored
+    /// items, replacing any callback they had. The callback is shared by the items.
+    ///
+    /// Use this to attach callbacks to items created without one, or to items of
+    /// subscriptions restored with [`Session::restore_subscriptions`], since callbacks
+    /// are not persisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Handles of the monitored items.
+    /// * `callback` - The callback receiving notifications for the items.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The callback was registered for all items.
+    /// * `Err(StatusCode)` - An item does not exist, and the callback was not registered.
+    ///
+    pub fn set_monitored_item_callback(
+        &self,
+        items: &[MonitoredItemHandle],
+        callback: impl OnSubscriptionNotification + 'static,
+    ) -> Result<(), StatusCode> {
+        let callback: ItemCallback = Arc::new(Mutex::new(callback));
+        let mut state = trace_lock!(self.subscription_state);
+        let mut subscription_ids = Vec::with_capacity(items.len());
+        for item in items {
+            let Some(subscription_id) = state.subscription_for_item(*item) else {
+                session_warn!(
+                    self,
+                    "Monitored item with client handle {} does not exist",
+                    item.client_handle()
+                );
+                return Err(StatusCode::BadMonitoredItemIdInvalid);
+            };
+            subscription_ids.push(subscription_id);
+        }
+        for (item, subscription_id) in items.iter().zip(subscription_ids) {
+            if let Some(subscription) = state.get_mut(subscription_id) {
+                subscription.set_item_callback(item.client_handle(), callback.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the callback of a monitored item, so that its notifications are delivered
+    /// to the callback of its subscription again.
+    ///
+    /// Returns `true` if the item had a callback.
+    pub fn remove_monitored_item_callback(&self, item: MonitoredItemHandle) -> bool {
+        let mut state = trace_lock!(self.subscription_state);
+        let Some(subscription_id) = state.subscription_for_item(item) else {
+            return false;
+        };
+        state
+            .get_mut(subscription_id)
+            .is_some_and(|s| s.item_callbacks.remove(&item.client_handle()).is_some())
+    }
+
+    /// Find a monitored item by its handle.
+    ///
+    /// Returns the ID of the subscription the item currently belongs to, which changes
+    /// if the subscription is recreated, and the item itself.
+    pub fn find_monitored_item(&self, item: MonitoredItemHandle) -> Option<(u32, MonitoredItem)> {
+        let state = trace_lock!(self.subscription_state);
+        let subscription_id = state.subscription_for_item(item)?;
+        let subscription = state.get(subscription_id)?;
+        let id = subscription.client_handles.get(&item.client_handle())?;
+        subscription
+            .monitored_items
+            .get(id)
+            .map(|i| (subscription_id, i.clone()))
+    }
+}
@@ -2,20 +2,53 @@ pub(crate) mod event_loop;
 pub use event_loop::SubscriptionActivity;
 
 mod callbacks;
+mod conditions;
+mod deadband;
+mod durable;
+mod item_callbacks;
+mod notification_filter;
+mod notification_stream;
+mod resync;
+mod sampled;
+mod sequence_gaps;
 mod service;
 pub(crate) mod state;
+mod tag_binding;
+mod tuning;
+mod typed_events;
 
+pub(crate) use callbacks::Detached;
 pub use callbacks::{
     DataChangeCallback, EventCallback, OnSubscriptionNotification, OnSubscriptionNotificationCore,
     SubscriptionCallbacks,
 };
+pub use conditions::{ConditionState, ConditionSubscription};
+pub(crate) use durable::SubscriptionPersister;
+pub use durable::{
+    FileSubscriptionStore, PersistedMonitoredItem, PersistedSubscription, PersistedSubscriptions,
+    SubscriptionStore,
+};
+pub use item_callbacks::MonitoredItemHandle;
+pub use notification_filter::{FilteredNotifications, NotificationFilter};
+pub use notification_stream::{NotificationSender, NotificationStream, SubscriptionNotification};
+pub use resync::MonitoredItemResync;
+pub use sequence_gaps::SequenceGap;
+pub use tag_binding::{TagBinding, TagSubscription};
+pub use tuning::{MonitoredItemChange, SubscriptionChange};
+pub use typed_events::EventSubscription;
 
 use std::{
     collections::{BTreeSet, HashMap},
-    time::Duration,
+    time::{Duration, Instant},
+};
+
+use item_callbacks::ItemCallback;
+use opcua_types::{
+    DateTime, ExtensionObject, MonitoredItemCreateRequest, MonitoringMode, MonitoringParameters,
+    NotificationMessage, ReadValueId,
 };
 
-use opcua_types::{ExtensionObject, MonitoringMode, NotificationMessage, ReadValueId};
+use crate::PublishOptions;
 
 pub use service::{
     CreateMonitoredItems, CreateSubscription, DeleteMonitoredItems, DeleteSubscriptions,
@@ -38,6 +71,8 @@ pub(crate) struct ModifyMonitoredItem {
     pub id: u32,
     pub sampling_interval: f64,
     pub queue_size: u32,
+    pub discard_oldest: bool,
+    pub filter: ExtensionObject,
 }
 
 #[derive(Debug, Clone)]
@@ -109,6 +144,32 @@ impl MonitoredItem {
         self.discard_oldest
     }
 
+    /// Filter applied to the monitored item on the server.
+    pub fn filter(&self) -> &ExtensionObject {
+        &self.filter
+    }
+
+    /// Handle of the monitored item, which stays the same when the subscription
+    /// is recreated.
+    pub fn handle(&self) -> MonitoredItemHandle {
+        MonitoredItemHandle::new(self.client_handle)
+    }
+
+    /// A request to create this monitored item again, with the same client handle.
+    pub(crate) fn create_request(&self) -> MonitoredItemCreateRequest {
+        MonitoredItemCreateRequest {
+            item_to_monitor: self.item_to_monitor.clone(),
+            monitoring_mode: self.monitoring_mode,
+            requested_parameters: MonitoringParameters {
+                client_handle: self.client_handle,
+                sampling_interval: self.sampling_interval,
+                filter: self.filter.clone(),
+                queue_size: self.queue_size as u32,
+                discard_oldest: self.discard_oldest,
+            },
+        }
+    }
+
     pub(crate) fn set_sampling_interval(&mut self, value: f64) {
         self.sampling_interval = value;
     }
@@ -151,11 +212,30 @@ pub struct Subscription {
     publishing_enabled: bool,
     /// Subscription priority
     priority: u8,
+    /// Sequence number of the last notification received with data
+    last_sequence_number: Option<u32>,
+    /// Sequence number expected for the next notification with data, from the last
+    /// notification or keep-alive received
+    next_sequence_number: Option<u32>,
+    /// Publish time of the last notification or keep-alive received in sequence
+    last_publish_time: Option<DateTime>,
+    /// Sequence numbers of notifications received out of sequence, not yet
+    /// accounted for by gap recovery
+    late_sequence_numbers: Vec<u32>,
+    /// Time of the last publish response, or of creation if none was received
+    last_publish_received: Instant,
+    /// Number of publish responses received later than the keep-alive period
+    late_publish_count: u64,
+    /// State of the emulation of the subscription with periodic reads, if it is
+    /// not a subscription on the server
+    sampling: Option<sampled::SampledState>,
 
     /// A map of monitored items associated with the subscription (key = monitored_item_id)
     monitored_items: HashMap<u32, MonitoredItem>,
     /// A map of client handle to monitored item id
     client_handles: HashMap<u32, u32>,
+    /// Callbacks for individual monitored items (key = client handle)
+    item_callbacks: HashMap<u32, ItemCallback>,
 
     callback: Box<dyn OnSubscriptionNotificationCore>,
 }
@@ -181,8 +261,16 @@ impl Subscription {
             max_notifications_per_publish,
             publishing_enabled,
             priority,
+            last_sequence_number: None,
+            next_sequence_number: None,
+            last_publish_time: None,
+            late_sequence_numbers: Vec::new(),
+            last_publish_received: Instant::now(),
+            late_publish_count: 0,
+            sampling: None,
             monitored_items: HashMap::new(),
             client_handles: HashMap::new(),
+            item_callbacks: HashMap::new(),
             callback: status_change_callback,
         }
     }
@@ -227,6 +315,36 @@ impl Subscription {
         self.publishing_enabled
     }
 
+    /// Get the sequence number of the last notification message received with data.
+    pub fn last_sequence_number(&self) -> Option<u32> {
+        self.last_sequence_number
+    }
+
+    /// Get the time the last publish response for this subscription was received,
+    /// or the time it was created if none was received yet.
+    pub fn last_publish_received(&self) -> Instant {
+        self.last_publish_received
+    }
+
+    /// Get the number of publish responses for this subscription that were received
+    /// later than its keep-alive period.
+    pub fn late_publish_count(&self) -> u64 {
+        self.late_publish_count
+    }
+
+    /// Get whether the subscription is emulated by the client with periodic reads,
+    /// instead of existing on the server. See [`crate::SampledFallbackOptions`].
+    pub fn is_sampled(&self) -> bool {
+        self.sampling.is_some()
+    }
+
+    /// The time after which a publish response is late. The server sends a keep-alive
+    /// after `max_keep_alive_count` publishing intervals without notifications, allow
+    /// one more interval for the response to arrive.
+    pub(crate) fn late_publish_threshold(&self) -> Duration {
+        self.publishing_interval * (self.max_keep_alive_count.saturating_add(1))
+    }
+
     /// Insert a monitored item that has been created on the server.
     ///
     /// If you call this yourself you are responsible for knowing that the
@@ -290,6 +408,8 @@ impl Subscription {
             if let Some(ref mut monitored_item) = self.monitored_items.get_mut(&i.id) {
                 monitored_item.set_sampling_interval(i.sampling_interval);
                 monitored_item.set_queue_size(i.queue_size as usize);
+                monitored_item.discard_oldest = i.discard_oldest;
+                monitored_item.filter = i.filter.clone();
             }
         });
     }
@@ -299,6 +419,8 @@ impl Subscription {
             // Remove the monitored item and the client handle / id entry
             if let Some(monitored_item) = self.monitored_items.remove(id) {
                 let _ = self.client_handles.remove(&monitored_item.client_handle());
+                let _ = self.item_callbacks.remove(&monitored_item.client_handle());
+                self.remove_sampled_value(*id);
             }
         })
     }
@@ -314,7 +436,34 @@ impl Subscription {
         }
     }
 
-    pub(crate) fn on_notification(&mut self, notification: NotificationMessage) {
+    /// Replace the callback of the subscription, returning the old callback.
+    pub(crate) fn replace_callback(
+        &mut self,
+        callback: Box<dyn OnSubscriptionNotificationCore>,
+    ) -> Box<dyn OnSubscriptionNotificationCore> {
+        std::mem::replace(&mut self.callback, callback)
+    }
+
+    /// Handle a notification message received for the subscription, returning the gap
+    /// in sequence numbers it revealed, if any.
+    pub(crate) fn on_notification(
+        &mut self,
+        notification: NotificationMessage,
+    ) -> Option<SequenceGap> {
+        let now = Instant::now();
+        if now.duration_since(self.last_publish_received) > self.late_publish_threshold() {
+            self.late_publish_count += 1;
+        }
+        self.last_publish_received = now;
+        let gap = self.track_sequence_number(&notification);
+        self.deliver(notification);
+        gap
+    }
+
+    fn deliver(&mut self, notification: NotificationMessage) {
+        let Some(notification) = self.dispatch_to_items(notification) else {
+            return;
+        };
         self.callback.on_subscription_notification(
             notification,
             MonitoredItemMap::new(&self.monitored_items, &self.client_handles),
@@ -355,22 +504,32 @@ pub(crate) struct PublishLimits {
     subscriptions: usize,
     min_publish_requests: usize,
     max_publish_requests: usize,
+    requests_per_subscription: usize,
+    min_outstanding_requests: usize,
+    max_outstanding_requests: usize,
 }
 
 impl PublishLimits {
     const MIN_MESSAGE_ROUNDTRIP: Duration = Duration::from_millis(10);
-    const REQUESTS_PER_SUBSCRIPTION: usize = 2;
 
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(options: &PublishOptions) -> Self {
         Self {
             message_roundtrip: Self::MIN_MESSAGE_ROUNDTRIP,
             publish_interval: Duration::ZERO,
             subscriptions: 0,
             min_publish_requests: 0,
             max_publish_requests: 0,
+            requests_per_subscription: options.requests_per_subscription.max(1),
+            min_outstanding_requests: options.min_outstanding_requests,
+            max_outstanding_requests: options.max_outstanding_requests,
         }
     }
 
+    /// The maximum number of publish requests to keep waiting for a response.
+    pub(crate) fn max_publish_requests(&self) -> usize {
+        self.max_publish_requests
+    }
+
     pub(crate) fn update_message_roundtrip(&mut self, message_roundtrip: Duration) {
         self.message_roundtrip = message_roundtrip.max(Self::MIN_MESSAGE_ROUNDTRIP);
         self.calculate_publish_limits();
@@ -387,10 +546,69 @@ impl PublishLimits {
     }
 
     fn calculate_publish_limits(&mut self) {
-        self.min_publish_requests = self.subscriptions * Self::REQUESTS_PER_SUBSCRIPTION;
-        self.max_publish_requests = (self.message_roundtrip.as_millis() as f32
+        if self.subscriptions == 0 {
+            self.min_publish_requests = 0;
+            self.max_publish_requests = 0;
+            return;
+        }
+        let min = (self.subscriptions * self.requests_per_subscription)
+            .max(self.min_outstanding_requests);
+        // Keep enough requests outstanding to cover the round trip to the server.
+        let max = ((self.message_roundtrip.as_millis() as f32
             / self.publish_interval.as_millis() as f32)
-            .ceil() as usize
-            * (self.min_publish_requests);
+            .ceil() as usize)
+            .saturating_mul(min)
+            .max(min);
+        let limit = match self.max_outstanding_requests {
+            0 => usize::MAX,
+            n => n,
+        };
+        self.min_publish_requests = min.min(limit);
+        self.max_publish_requests = max.min(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::PublishOptions;
+
+    use super::PublishLimits;
+
+    #[test]
+    fn publish_limits() {
+        let mut limits = PublishLimits::new(&PublishOptions::default());
+        limits.update_subscriptions(3, Duration::from_millis(100));
+        assert_eq!(limits.min_publish_requests, 6);
+        assert_eq!(limits.max_publish_requests, 6);
+
+        // A round trip longer than the publishing interval needs more requests in flight.
+        limits.update_message_roundtrip(Duration::from_millis(250));
+        assert_eq!(limits.max_publish_requests, 18);
+
+        limits.update_subscriptions(0, Duration::ZERO);
+        assert_eq!(limits.min_publish_requests, 0);
+        assert_eq!(limits.max_publish_requests, 0);
+    }
+
+    #[test]
+    fn publish_limits_options() {
+        let mut limits = PublishLimits::new(&PublishOptions {
+            requests_per_subscription: 1,
+            min_outstanding_requests: 8,
+            max_outstanding_requests: 12,
+            ..Default::default()
+        });
+        limits.update_subscriptions(2, Duration::from_millis(100));
+        assert_eq!(limits.min_publish_requests, 8);
+        assert_eq!(limits.max_publish_requests, 8);
+
+        limits.update_message_roundtrip(Duration::from_millis(1000));
+        assert_eq!(limits.max_publish_requests, 12);
+
+        limits.update_subscriptions(20, Duration::from_millis(100));
+        assert_eq!(limits.min_publish_requests, 12);
+        assert_eq!(limits.max_publish_requests, 12);
     }
 }
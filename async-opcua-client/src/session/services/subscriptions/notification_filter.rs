@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use opcua_types::{
+    match_extension_object_owned, DataChangeNotification, DataValue, EventNotificationList, NodeId,
+    NotificationMessage, StatusChangeNotification, Variant,
+};
+
+use crate::{
+    session::services::subscriptions::MonitoredItemMap, MonitoredItem, OnSubscriptionNotification,
+    OnSubscriptionNotificationCore, SequenceGap,
+};
+
+/// Client-side filter for data change notifications on a monitored item, applied by
+/// [`FilteredNotifications`] before the notifications reach the user callbacks.
+///
+/// This is useful against servers that ignore the requested sampling interval or
+/// data change filter, or report the same value over and over. The first value of each
+/// monitored item is always delivered, later values are compared to the last delivered value.
+#[derive(Debug, Clone, Default)]
+pub struct NotificationFilter {
+    /// Minimum time between two values delivered for the same monitored item. A value
+    /// received sooner is held back, and delivered once the interval has passed if no
+    /// newer value replaced it. Held back values are delivered when the next publish
+    /// response for the subscription arrives, including keep-alives.
+    pub min_interval: Option<Duration>,
+    /// Absolute deadband for numeric values. Values that differ from the last delivered
+    /// value by this much or less are dropped. Non-numeric values are only dropped if they
+    /// are equal to the last delivered value. Setting a deadband also drops duplicates.
+    pub deadband: Option<f64>,
+    /// Drop values that are equal to the last delivered value, with the same status,
+    /// typically only differing in their timestamps.
+    pub suppress_duplicates: bool,
+    /// Drop values where only the status changed, and the value itself did not.
+    pub suppress_status_only_changes: bool,
+}
+
+impl NotificationFilter {
+    /// Check whether `value` is different enough from `last` to be delivered.
+    fn is_change(&self, last: &DataValue, value: &DataValue) -> bool {
+        let value_changed = match self.deadband {
+            Some(deadband) => exceeds_deadband(&last.value, &value.value, deadband),
+            None => last.value != value.value,
+        };
+        if value_changed {
+            return true;
+        }
+        if last.status() != value.status() {
+            return !self.suppress_status_only_changes;
+        }
+        !self.suppress_duplicates && self.deadband.is_none()
+    }
+}
+
+pub(super) fn exceeds_deadband(
+    last: &Option<Variant>,
+    value: &Option<Variant>,
+    deadband: f64,
+) -> bool {
+    match (last, value) {
+        (Some(l), Some(v)) if l.is_numeric() && v.is_numeric() => match (l.as_f64(), v.as_f64()) {
+            (Some(l), Some(v)) => (v - l).abs() > deadband,
+            _ => l != v,
+        },
+        _ => last != value,
+    }
+}
+
+#[derive(Default)]
+struct FilterState {
+    last: Option<(DataValue, Instant)>,
+    pending: Option<DataValue>,
+}
+
+/// A wrapper around an [`OnSubscriptionNotification`] that filters data change notifications
+/// on the client with a [`NotificationFilter`] before passing them on. Events and status
+/// changes are passed on unchanged.
+///
+/// Use this as the callback of a subscription in place of the wrapped callback:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use opcua_client::{DataChangeCallback, FilteredNotifications, NotificationFilter};
+/// let callback = FilteredNotifications::new(
+///     DataChangeCallback::new(|value, item| println!("{:?}: {:?}", item.item_to_monitor(), value)),
+///     NotificationFilter {
+///         min_interval: Some(Duration::from_millis(500)),
+///         suppress_duplicates: true,
+///         ..Default::default()
+///     },
+/// );
+/// ```
+pub struct FilteredNotifications<T> {
+    inner: T,
+    default_filter: NotificationFilter,
+    item_filters: HashMap<NodeId, NotificationFilter>,
+    state: HashMap<u32, FilterState>,
+}
+
+impl<T: OnSubscriptionNotification> FilteredNotifications<T> {
+    /// Create a new filtering wrapper around `inner`, applying `default_filter` to every
+    /// monitored item without a filter of its own.
+    pub fn new(inner: T, default_filter: NotificationFilter) -> Self {
+        Self {
+            inner,
+            default_filter,
+            item_filters: HashMap::new(),
+            state: HashMap::new(),
+        }
+    }
+
+    /// Use `filter` for monitored items on `node_id` instead of the default filter.
+    pub fn with_item_filter(
+        mut self,
+        node_id: impl Into<NodeId>,
+        filter: NotificationFilter,
+    ) -> Self {
+        self.item_filters.insert(node_id.into(), filter);
+        self
+    }
+
+    /// Get a reference to the wrapped callback.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped callback.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    fn filter_for(&self, item: &MonitoredItem) -> &NotificationFilter {
+        self.item_filters
+            .get(&item.item_to_monitor().node_id)
+            .unwrap_or(&self.default_filter)
+    }
+
+    fn on_data_value(&mut self, value: DataValue, item: &MonitoredItem, now: Instant) {
+        let filter = self
+            .item_filters
+            .get(&item.item_to_monitor().node_id)
+            .unwrap_or(&self.default_filter);
+        let state = self.state.entry(item.client_handle()).or_default();
+        let Some((last, delivered_at)) = &state.last else {
+            state.last = Some((value.clone(), now));
+            self.inner.on_data_value(value, item);
+            return;
+        };
+        if !filter.is_change(last, &value) {
+            // Any held back value is outdated, the item is back at the delivered value.
+            state.pending = None;
+            return;
+        }
+        if filter
+            .min_interval
+            .is_some_and(|interval| now.duration_since(*delivered_at) < interval)
+        {
+            state.pending = Some(value);
+            return;
+        }
+        state.pending = None;
+        state.last = Some((value.clone(), now));
+        self.inner.on_data_value(value, item);
+    }
+
+    fn flush_pending(&mut self, monitored_items: &MonitoredItemMap<'_>, now: Instant) {
+        let ready: Vec<_> = self
+            .state
+            .iter()
+            .filter_map(|(handle, state)| {
+                let (_, delivered_at) = state.last.as_ref()?;
+                state.pending.as_ref()?;
+                let item = monitored_items.get(*handle)?;
+                let interval = self.filter_for(item).min_interval.unwrap_or_default();
+                (now.duration_since(*delivered_at) >= interval).then_some(*handle)
+            })
+            .collect();
+        for handle in ready {
+            let Some(item) = monitored_items.get(handle) else {
+                continue;
+            };
+            let state = self.state.get_mut(&handle).unwrap();
+            let Some(value) = state.pending.take() else {
+                continue;
+            };
+            state.last = Some((value.clone(), now));
+            self.inner.on_data_value(value, item);
+        }
+    }
+}
+
+impl<T: OnSubscriptionNotification> OnSubscriptionNotificationCore for FilteredNotifications<T> {
+    fn on_subscription_notification(
+        &mut self,
+        notification: NotificationMessage,
+        monitored_items: MonitoredItemMap<'_>,
+    ) {
+        let now = Instant::now();
+        for obj in notification.notification_data.into_iter().flatten() {
+            match_extension_object_owned!(obj,
+                v: DataChangeNotification => {
+                    for notif in v.monitored_items.into_iter().flatten() {
+                        if let Some(item) = monitored_items.get(notif.client_handle) {
+                            self.on_data_value(notif.value, item, now);
+                        } else {
+                            tracing::warn!("Received notification for unknown monitored item {}", notif.client_handle);
+                        }
+                    }
+                },
+                v: EventNotificationList => {
+                    for notif in v.events.into_iter().flatten() {
+                        if let Some(item) = monitored_items.get(notif.client_handle) {
+                            self.inner.on_event(notif.event_fields, item);
+                        }
+                    }
+                },
+                v: StatusChangeNotification => {
+                    self.inner.on_subscription_status_change(v);
+                }
+            )
+        }
+        self.flush_pending(&monitored_items, now);
+        // Forget monitored items that have been deleted.
+        self.state
+            .retain(|handle, _| monitored_items.get(*handle).is_some());
+    }
+
+    fn on_sequence_gap(&mut self, gap: &SequenceGap) {
+        self.inner.on_sequence_gap(gap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    use opcua_types::{DataValue, StatusCode};
+
+    use crate::{
+        session::services::subscriptions::MonitoredItemMap, DataChangeCallback, MonitoredItem,
+    };
+
+    use super::{FilteredNotifications, NotificationFilter};
+
+    fn filtered(
+        filter: NotificationFilter,
+    ) -> (
+        FilteredNotifications<DataChangeCallback>,
+        std::sync::Arc<std::sync::Mutex<Vec<DataValue>>>,
+    ) {
+        let values = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let values_ref = values.clone();
+        let callback = DataChangeCallback::new(move |v, _| values_ref.lock().unwrap().push(v));
+        (FilteredNotifications::new(callback, filter), values)
+    }
+
+    #[test]
+    fn deadband_and_status_changes() {
+        let (mut filtered, values) = filtered(NotificationFilter {
+            deadband: Some(2.0),
+            suppress_status_only_changes: true,
+            ..Default::default()
+        });
+        let item = MonitoredItem::new(1);
+        let now = Instant::now();
+        for v in [1.0, 2.0, 3.0, 3.5, 0.5] {
+            filtered.on_data_value(DataValue::new_now(v), &item, now);
+        }
+        let mut uncertain = DataValue::new_now(0.5);
+        uncertain.status = Some(StatusCode::Uncertain);
+        filtered.on_data_value(uncertain, &item, now);
+
+        let values: Vec<_> = values
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|v| v.value.clone().unwrap())
+            .collect();
+        assert_eq!(values, vec![1.0.into(), 3.5.into(), 0.5.into()]);
+    }
+
+    #[test]
+    fn duplicates() {
+        let (mut filtered, values) = filtered(NotificationFilter {
+            suppress_duplicates: true,
+            ..Default::default()
+        });
+        let item = MonitoredItem::new(1);
+        let now = Instant::now();
+        for v in [1, 1, 2, 2, 1] {
+            filtered.on_data_value(DataValue::new_now(v), &item, now);
+        }
+        let mut bad = DataValue::new_now(1);
+        bad.status = Some(StatusCode::BadCommunicationError);
+        filtered.on_data_value(bad, &item, now);
+
+        let values = values.lock().unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[3].status(), StatusCode::BadCommunicationError);
+    }
+
+    #[test]
+    fn min_interval() {
+        let (mut filtered, values) = filtered(NotificationFilter {
+            min_interval: Some(Duration::from_secs(1)),
+            suppress_duplicates: true,
+            ..Default::default()
+        });
+        let mut item = MonitoredItem::new(1);
+        item.id = 5;
+        let monitored_items = HashMap::from([(5, item.clone())]);
+        let client_handles = HashMap::from([(1, 5)]);
+        let map = MonitoredItemMap::new(&monitored_items, &client_handles);
+
+        let start = Instant::now();
+        filtered.on_data_value(DataValue::new_now(1), &item, start);
+        filtered.on_data_value(DataValue::new_now(2), &item, start);
+        filtered.on_data_value(DataValue::new_now(3), &item, start);
+        filtered.flush_pending(&map, start + Duration::from_millis(500));
+        assert_eq!(values.lock().unwrap().len(), 1);
+
+        // The latest held back value is delivered once the interval has passed.
+        filtered.flush_pending(&map, start + Duration::from_secs(1));
+        {
+            let values = values.lock().unwrap();
+            assert_eq!(values.len(), 2);
+            assert_eq!(values[1].value, Some(3.into()));
+        }
+
+        // A held back value is dropped if the item returns to the delivered value.
+        filtered.on_data_value(DataValue::new_now(4), &item, start + Duration::from_secs(1));
+        filtered.on_data_value(DataValue::new_now(3), &item, start + Duration::from_secs(1));
+        filtered.flush_pending(&map, start + Duration::from_secs(3));
+        assert_eq!(values.lock().unwrap().len(), 2);
+    }
+}
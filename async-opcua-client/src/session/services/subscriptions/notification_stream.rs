@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures::Stream;
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{DataValue, NodeId, StatusChangeNotification, StatusCode, Variant};
+
+use crate::{MonitoredItem, OnSubscriptionNotification, SequenceGap, Session};
+
+/// A notification received on a subscription, delivered by a [`NotificationStream`].
+#[derive(Debug, Clone)]
+pub enum SubscriptionNotification {
+    /// The value of a monitored item changed.
+    DataChange {
+        /// The client handle of the monitored item.
+        client_handle: u32,
+        /// The node ID of the monitored item.
+        node_id: NodeId,
+        /// The new value.
+        value: DataValue,
+    },
+    /// An event was received on a monitored item.
+    Event {
+        /// The client handle of the monitored item.
+        client_handle: u32,
+        /// The node ID of the monitored item.
+        node_id: NodeId,
+        /// The fields of the event, in the order of the select clauses of the event filter.
+        fields: Option<Vec<Variant>>,
+    },
+    /// The subscription changed state on the server.
+    StatusChange(StatusChangeNotification),
+    /// Notifications of the subscription were found to be missing.
+    SequenceGap(SequenceGap),
+    /// The stream was not read fast enough, and this many of the oldest buffered
+    /// notifications were dropped to make room for new ones.
+    Lagged(u64),
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: VecDeque<SubscriptionNotification>,
+    lagged: u64,
+    waker: Option<Waker>,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+/// The sending half of a [`NotificationStream`], passed as callback when creating a
+/// subscription. Dropping it, typically by deleting the subscription, ends the stream.
+pub struct NotificationSender {
+    shared: Arc<Mutex<Shared>>,
+    capacity: usize,
+}
+
+impl NotificationSender {
+    fn send(&self, notification: SubscriptionNotification) {
+        let mut shared = trace_lock!(self.shared);
+        if shared.receiver_closed {
+            return;
+        }
+        if shared.queue.len() >= self.capacity {
+            shared.queue.pop_front();
+            shared.lagged += 1;
+        }
+        shared.queue.push_back(notification);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for NotificationSender {
+    fn drop(&mut self) {
+        let mut shared = trace_lock!(self.shared);
+        shared.sender_closed = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl OnSubscriptionNotification for NotificationSender {
+    fn on_subscription_status_change(&mut self, notification: StatusChangeNotification) {
+        self.send(SubscriptionNotification::StatusChange(notification));
+    }
+
+    fn on_data_value(&mut self, notification: DataValue, item: &MonitoredItem) {
+        self.send(SubscriptionNotification::DataChange {
+            client_handle: item.client_handle(),
+            node_id: item.item_to_monitor().node_id.clone(),
+            value: notification,
+        });
+    }
+
+    fn on_event(&mut self, event_fields: Option<Vec<Variant>>, item: &MonitoredItem) {
+        self.send(SubscriptionNotification::Event {
+            client_handle: item.client_handle(),
+            node_id: item.item_to_monitor().node_id.clone(),
+            fields: event_fields,
+        });
+    }
+
+    fn on_sequence_gap(&mut self, gap: &SequenceGap) {
+        self.send(SubscriptionNotification::SequenceGap(gap.clone()));
+    }
+}
+
+/// A [`Stream`] of the notifications received on a subscription, as an alternative to
+/// handling them in callbacks.
+///
+/// Notifications are buffered until they are read from the stream. If the buffer is full,
+/// the oldest notification is dropped, and a [`SubscriptionNotification::Lagged`] with the
+/// number of dropped notifications is returned before the next buffered notification.
+/// The stream ends when the subscription is deleted.
+pub struct NotificationStream {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl NotificationStream {
+    /// Create a new notification stream, and the sender to pass as callback to
+    /// [`Session::create_subscription`].
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer_size` - The maximum number of notifications buffered until they are read,
+    ///   at least 1.
+    pub fn channel(buffer_size: usize) -> (NotificationSender, NotificationStream) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        (
+            NotificationSender {
+                shared: shared.clone(),
+                capacity: buffer_size.max(1),
+            },
+            NotificationStream { shared },
+        )
+    }
+}
+
+impl Stream for NotificationStream {
+    type Item = SubscriptionNotification;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = trace_lock!(self.shared);
+        if shared.lagged > 0 {
+            let lagged = std::mem::take(&mut shared.lagged);
+            return Poll::Ready(Some(SubscriptionNotification::Lagged(lagged)));
+        }
+        if let Some(notification) = shared.queue.pop_front() {
+            return Poll::Ready(Some(notification));
+        }
+        if shared.sender_closed {
+            return Poll::Ready(None);
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for NotificationStream {
+    fn drop(&mut self) {
+        let mut shared = trace_lock!(self.shared);
+        shared.receiver_closed = true;
+        shared.queue.clear();
+    }
+}
+
+impl Session {
+    /// Create a subscription delivering its notifications as a [`NotificationStream`],
+    /// instead of to a callback. See [`Session::create_subscription`] for a description of
+    /// the subscription parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer_size` - The maximum number of notifications buffered until they are read
+    ///   from the stream, see [`NotificationStream`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((u32, NotificationStream))` - identifier for new subscription, and the stream of
+    ///   its notifications.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_subscription_stream(
+        &self,
+        publishing_interval: Duration,
+        lifetime_count: u32,
+        max_keep_alive_count: u32,
+        max_notifications_per_publish: u32,
+        priority: u8,
+        publishing_enabled: bool,
+        buffer_size: usize,
+    ) -> Result<(u32, NotificationStream), StatusCode> {
+        let (sender, stream) = NotificationStream::channel(buffer_size);
+        let subscription_id = self
+            .create_subscription(
+                publishing_interval,
+                lifetime_count,
+                max_keep_alive_count,
+                max_notifications_per_publish,
+                priority,
+                publishing_enabled,
+                sender,
+            )
+            .await?;
+        Ok((subscription_id, stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, StreamExt};
+    use opcua_types::{DataValue, Variant};
+
+    use crate::{MonitoredItem, OnSubscriptionNotification};
+
+    use super::{NotificationStream, SubscriptionNotification};
+
+    fn value(notification: Option<SubscriptionNotification>) -> Variant {
+        match notification {
+            Some(SubscriptionNotification::DataChange { value, .. }) => value.value.unwrap(),
+            r => panic!("Expected data change, got {r:?}"),
+        }
+    }
+
+    #[test]
+    fn lagged() {
+        let (mut sender, mut stream) = NotificationStream::channel(2);
+        let item = MonitoredItem::new(1);
+        for v in 0..5 {
+            sender.on_data_value(DataValue::new_now(v), &item);
+        }
+
+        let next = stream.next().now_or_never().unwrap();
+        assert!(matches!(next, Some(SubscriptionNotification::Lagged(3))));
+        assert_eq!(value(stream.next().now_or_never().unwrap()), 3.into());
+        assert_eq!(value(stream.next().now_or_never().unwrap()), 4.into());
+        assert!(stream.next().now_or_never().is_none());
+
+        sender.on_data_value(DataValue::new_now(5), &item);
+        drop(sender);
+        assert_eq!(value(stream.next().now_or_never().unwrap()), 5.into());
+        assert!(stream.next().now_or_never().unwrap().is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use opcua_core::trace_lock;
+use opcua_types::{StatusCode, TimestampsToReturn};
+
+use crate::{
+    session::{session_debug, session_warn},
+    Session, UARequest,
+};
+
+use super::{DeleteMonitoredItems, MonitoredItemHandle};
+
+/// Differences between the monitored items of a subscription in the client and on the
+/// server, found and fixed by [`Session::resynchronize_monitored_items`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitoredItemResync {
+    /// The subscription that was resynchronized.
+    pub subscription_id: u32,
+    /// IDs of monitored items that existed on the server but not in the client.
+    /// They were deleted on the server.
+    pub orphaned: Vec<u32>,
+    /// Handles of monitored items that existed in the client but not on the server.
+    /// They were created again, and have new IDs.
+    pub recreated: Vec<MonitoredItemHandle>,
+    /// Handles of monitored items that existed in the client but not on the server,
+    /// and could not be created again, with the reason. They were removed from the client.
+    pub failed: Vec<(MonitoredItemHandle, StatusCode)>,
+}
+
+impl MonitoredItemResync {
+    /// Whether the client and the server had the same monitored items.
+    pub fn was_in_sync(&self) -> bool {
+        self.orphaned.is_empty() && self.recreated.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl Session {
+    /// Compare the monitored items of a subscription in the client with those on the
+    /// server, using the `GetMonitoredItems` method of the server, and fix any differences.
+    /// This is useful after a reconnect, or if notifications for some items are missing.
+    ///
+    /// Items on the server that the client does not know, or knows with a different client
+    /// handle, are deleted on the server. Items the server does not have are created again
+    /// with the same parameters and client handle, keeping their callbacks. Triggering links
+    /// of recreated items are not restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The subscription to resynchronize.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MonitoredItemResync)` - The differences that were found.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn resynchronize_monitored_items(
+        &self,
+        subscription_id: u32,
+    ) -> Result<MonitoredItemResync, StatusCode> {
+        let client_items = {
+            let state = trace_lock!(self.subscription_state);
+            let subscription = state
+                .get(subscription_id)
+                .ok_or(StatusCode::BadSubscriptionIdInvalid)?;
+            subscription
+                .monitored_items
+                .values()
+                .map(|i| (i.id(), i.client_handle()))
+                .collect::<HashSet<_>>()
+        };
+        let (server_ids, server_handles) = self.call_get_monitored_items(subscription_id).await?;
+        let server_items = server_ids
+            .into_iter()
+            .zip(server_handles)
+            .collect::<HashSet<_>>();
+
+        let mut report = MonitoredItemResync {
+            subscription_id,
+            ..Default::default()
+        };
+        report.orphaned = server_items
+            .difference(&client_items)
+            .map(|(id, _)| *id)
+            .collect();
+        report.orphaned.sort_unstable();
+        let mut missing = client_items
+            .difference(&server_items)
+            .copied()
+            .collect::<Vec<_>>();
+        missing.sort_unstable_by_key(|(_, handle)| *handle);
+
+        if !report.orphaned.is_empty() {
+            session_debug!(
+                self,
+                "Deleting {} orphaned monitored items on subscription {}",
+                report.orphaned.len(),
+                subscription_id
+            );
+            // Delete them on the server only, the client does not know these items.
+            DeleteMonitoredItems::new(subscription_id, self)
+                .items_to_delete(report.orphaned.clone())
+                .send(&self.channel)
+                .await?;
+        }
+        if missing.is_empty() {
+            return Ok(report);
+        }
+
+        session_debug!(
+            self,
+            "Recreating {} missing monitored items on subscription {}",
+            missing.len(),
+            subscription_id
+        );
+        let (old_ids, items_to_create): (Vec<_>, Vec<_>) = {
+            let state = trace_lock!(self.subscription_state);
+            let subscription = state
+                .get(subscription_id)
+                .ok_or(StatusCode::BadSubscriptionIdInvalid)?;
+            missing
+                .iter()
+                .filter_map(|(id, _)| subscription.monitored_items.get(id))
+                .map(|item| (item.id(), item.create_request()))
+                .unzip()
+        };
+        let created = self
+            .create_monitored_items(subscription_id, TimestampsToReturn::Both, items_to_create)
+            .await?;
+
+        let mut state = trace_lock!(self.subscription_state);
+        let Some(subscription) = state.get_mut(subscription_id) else {
+            return Ok(report);
+        };
+        for (old_id, item) in old_ids.into_iter().zip(created) {
+            let client_handle = item.requested_parameters.client_handle;
+            let handle = MonitoredItemHandle::new(client_handle);
+            let status = item.result.status_code;
+            // The recreated item replaced the client handle entry, drop the item with the old ID
+            // unless the server happened to reuse it.
+            if item.result.monitored_item_id != old_id || status.is_bad() {
+                subscription.monitored_items.remove(&old_id);
+            }
+            if !status.is_bad() {
+                report.recreated.push(handle);
+                continue;
+            }
+            session_warn!(
+                self,
+                "Failed to recreate monitored item with client handle {}: {}",
+                client_handle,
+                status
+            );
+            report.failed.push((handle, status));
+            if let Some(id) = subscription.client_handles.remove(&client_handle) {
+                if subscription
+                    .monitored_items
+                    .get(&id)
+                    .is_some_and(|i| i.client_handle() == client_handle)
+                {
+                    subscription.monitored_items.remove(&id);
+                }
+            }
+            subscription.item_callbacks.remove(&client_handle);
+        }
+        drop(state);
+        self.persist_subscriptions();
+        Ok(report)
+    }
+
+    /// Resynchronize the monitored items of every subscription of the session, see
+    /// [`Session::resynchronize_monitored_items`].
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(u32, Result<MonitoredItemResync, StatusCode>)>` - The ID of each subscription,
+    ///   with the differences found, or the status it could not be resynchronized with.
+    ///
+    pub async fn resynchronize_subscriptions(
+        &self,
+    ) -> Vec<(u32, Result<MonitoredItemResync, StatusCode>)> {
+        let mut subscription_ids = trace_lock!(self.subscription_state)
+            .subscription_ids()
+            .unwrap_or_default();
+        subscription_ids.sort_unstable();
+        let mut results = Vec::with_capacity(subscription_ids.len());
+        for subscription_id in subscription_ids {
+            let result = self
+                .resynchronize_monitored_items(subscription_id)
+                .await
+                .inspect_err(|e| {
+                    session_warn!(
+                        self,
+                        "Failed to resynchronize subscription {}: {}",
+                        subscription_id,
+                        e
+                    );
+                });
+            results.push((subscription_id, result));
+        }
+        results
+    }
+}
@@ -0,0 +1,580 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use opcua_core::trace_lock;
+use opcua_types::{
+    DataChangeFilter, DataChangeNotification, DataChangeTrigger, DataValue, DateTime, DeadbandType,
+    EventFilter, ExtensionObject, MonitoredItemCreateRequest, MonitoredItemCreateResult,
+    MonitoredItemModifyRequest, MonitoredItemModifyResult, MonitoredItemNotification,
+    MonitoringMode, NotificationMessage, ReadValueId, StatusCode, TimestampsToReturn,
+};
+
+use crate::{
+    session::{session_debug, session_error, session_warn},
+    Session, UARequest,
+};
+
+use super::{
+    item_callbacks::ItemCallback, notification_filter::exceeds_deadband,
+    service::CreatedMonitoredItem, CreateMonitoredItem, DeleteSubscriptions, ModifyMonitoredItem,
+    OnSubscriptionNotificationCore, Subscription,
+};
+
+/// Lower limit on the interval sampled subscriptions are read at, so that a publishing
+/// interval of zero does not read as fast as possible.
+pub(super) const MIN_SAMPLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// State of a subscription that is emulated by the client with periodic reads.
+pub(crate) struct SampledState {
+    /// Time the monitored items are read next
+    next_sample: Instant,
+    /// Sequence number of the last notification delivered
+    sequence_number: u32,
+    /// ID assigned to the next monitored item created
+    next_item_id: u32,
+    /// Last value delivered for each monitored item (key = monitored item id)
+    last_values: HashMap<u32, DataValue>,
+}
+
+/// Check whether the server failing a request to create a subscription or monitored items
+/// with `status` means it will not create them, rather than that the request failed.
+fn is_rejection(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BadServiceUnsupported
+            | StatusCode::BadNotSupported
+            | StatusCode::BadNotImplemented
+            | StatusCode::BadTooManySubscriptions
+            | StatusCode::BadTooManyMonitoredItems
+            | StatusCode::BadResourceUnavailable
+    )
+}
+
+/// Check whether `value` is a data change from `last`, according to the trigger and
+/// absolute deadband of the data change filter of the monitored item, if any.
+fn is_data_change(filter: &ExtensionObject, last: &DataValue, value: &DataValue) -> bool {
+    let filter = filter.inner_as::<DataChangeFilter>();
+    if last.status() != value.status() {
+        return true;
+    }
+    let trigger = filter.map_or(DataChangeTrigger::StatusValue, |f| f.trigger);
+    if trigger == DataChangeTrigger::Status {
+        return false;
+    }
+    let value_changed = match filter.filter(|f| f.deadband_type == DeadbandType::Absolute as u32) {
+        Some(f) => exceeds_deadband(&last.value, &value.value, f.deadband_value),
+        None => last.value != value.value,
+    };
+    value_changed
+        || trigger == DataChangeTrigger::StatusValueTimestamp
+            && last.source_timestamp != value.source_timestamp
+}
+
+/// Get the results of a request for `subscription_ids`, where the subscriptions in `sampled`
+/// were handled by the client, and the rest by the server with `server_results`.
+pub(super) fn merge_sampled_results(
+    subscription_ids: &[u32],
+    sampled: &[u32],
+    server_results: Vec<StatusCode>,
+) -> Vec<StatusCode> {
+    if sampled.is_empty() {
+        return server_results;
+    }
+    let mut server_results = server_results.into_iter();
+    subscription_ids
+        .iter()
+        .map(|id| {
+            if sampled.contains(id) {
+                StatusCode::Good
+            } else {
+                server_results
+                    .next()
+                    .unwrap_or(StatusCode::BadSubscriptionIdInvalid)
+            }
+        })
+        .collect()
+}
+
+impl Subscription {
+    /// Emulate the subscription with periodic reads from now on. Monitored items keep
+    /// their IDs, and their current values are delivered with the first read.
+    pub(crate) fn start_sampling(&mut self) {
+        let next_item_id = self
+            .monitored_items
+            .keys()
+            .max()
+            .map_or(1, |id| id.saturating_add(1));
+        self.sampling = Some(SampledState {
+            next_sample: Instant::now(),
+            sequence_number: 0,
+            next_item_id,
+            last_values: HashMap::new(),
+        });
+        self.last_publish_received = Instant::now();
+    }
+
+    /// Get the time the monitored items of a sampled subscription are read next,
+    /// if it is sampled and publishing is enabled.
+    pub(super) fn next_sample_time(&self) -> Option<Instant> {
+        self.sampling
+            .as_ref()
+            .filter(|_| self.publishing_enabled)
+            .map(|s| s.next_sample)
+    }
+
+    /// Get the monitored items to read if the subscription is sampled and due at `now`,
+    /// scheduling the next read.
+    pub(super) fn take_due_sample(
+        &mut self,
+        now: Instant,
+        min_interval: Duration,
+    ) -> Option<Vec<(u32, ReadValueId)>> {
+        let interval = self.publishing_interval.max(min_interval);
+        let sampling = self.sampling.as_mut()?;
+        if !self.publishing_enabled || sampling.next_sample > now {
+            return None;
+        }
+        // Skip reads that were missed, rather than catching up on them.
+        sampling.next_sample += interval;
+        if sampling.next_sample <= now {
+            sampling.next_sample = now + interval;
+        }
+        Some(
+            self.monitored_items
+                .values()
+                .filter(|i| i.monitoring_mode == MonitoringMode::Reporting)
+                .map(|i| (i.id, i.item_to_monitor.clone()))
+                .collect(),
+        )
+    }
+
+    /// Deliver the values of a read of the monitored items (key = monitored item id)
+    /// that changed since the last read, as a data change notification.
+    pub(super) fn on_samples(&mut self, values: Vec<(u32, DataValue)>) {
+        let Some(sampling) = self.sampling.as_mut() else {
+            return;
+        };
+        // A read counts as a publish response, even if nothing changed.
+        self.last_publish_received = Instant::now();
+
+        let mut notifications = Vec::new();
+        for (id, value) in values {
+            let Some(item) = self.monitored_items.get(&id) else {
+                continue;
+            };
+            let changed = sampling
+                .last_values
+                .get(&id)
+                .is_none_or(|last| is_data_change(&item.filter, last, &value));
+            if changed {
+                notifications.push(MonitoredItemNotification {
+                    client_handle: item.client_handle,
+                    value: value.clone(),
+                });
+                sampling.last_values.insert(id, value);
+            }
+        }
+        if notifications.is_empty() {
+            return;
+        }
+
+        sampling.sequence_number = sampling.sequence_number.wrapping_add(1).max(1);
+        let sequence_number = sampling.sequence_number;
+        self.deliver(NotificationMessage {
+            sequence_number,
+            publish_time: DateTime::now(),
+            notification_data: Some(vec![ExtensionObject::from_message(
+                DataChangeNotification {
+                    monitored_items: Some(notifications),
+                    diagnostic_infos: None,
+                },
+            )]),
+        });
+    }
+
+    /// Create monitored items on a sampled subscription. Items are read at the sampling
+    /// interval of the subscription, and event monitored items are not supported.
+    fn create_sampled_items(
+        &mut self,
+        items_to_create: Vec<MonitoredItemCreateRequest>,
+        interval: Duration,
+    ) -> Vec<CreatedMonitoredItem> {
+        let Some(sampling) = self.sampling.as_mut() else {
+            return Vec::new();
+        };
+        let sampling_interval = interval.as_secs_f64() * 1000.0;
+        let mut to_insert = Vec::new();
+        let mut results = Vec::with_capacity(items_to_create.len());
+        for item in items_to_create {
+            let parameters = &item.requested_parameters;
+            let mut result = MonitoredItemCreateResult {
+                status_code: StatusCode::Good,
+                monitored_item_id: 0,
+                revised_sampling_interval: sampling_interval,
+                revised_queue_size: 1,
+                filter_result: ExtensionObject::null(),
+            };
+            if parameters.filter.inner_is::<EventFilter>() {
+                result.status_code = StatusCode::BadMonitoredItemFilterUnsupported;
+            } else {
+                result.monitored_item_id = sampling.next_item_id;
+                sampling.next_item_id = sampling.next_item_id.wrapping_add(1).max(1);
+                to_insert.push(CreateMonitoredItem {
+                    id: result.monitored_item_id,
+                    client_handle: parameters.client_handle,
+                    item_to_monitor: item.item_to_monitor.clone(),
+                    monitoring_mode: item.monitoring_mode,
+                    queue_size: 1,
+                    discard_oldest: parameters.discard_oldest,
+                    sampling_interval,
+                    filter: parameters.filter.clone(),
+                });
+            }
+            results.push(CreatedMonitoredItem {
+                result,
+                requested_parameters: item.requested_parameters,
+                monitoring_mode: item.monitoring_mode,
+                item_to_monitor: item.item_to_monitor,
+            });
+        }
+        // Read the new items right away, like the server sends their initial values.
+        sampling.next_sample = Instant::now();
+        self.insert_monitored_items(to_insert);
+        results
+    }
+
+    /// Set the monitoring mode of monitored items on a sampled subscription.
+    fn set_sampled_monitoring_mode(
+        &mut self,
+        monitored_item_ids: &[u32],
+        monitoring_mode: MonitoringMode,
+    ) -> Vec<StatusCode> {
+        monitored_item_ids
+            .iter()
+            .map(|id| {
+                let Some(item) = self.monitored_items.get_mut(id) else {
+                    return StatusCode::BadMonitoredItemIdInvalid;
+                };
+                item.set_monitoring_mode(monitoring_mode);
+                // Items that start reporting again deliver their current value.
+                if let Some(sampling) = self.sampling.as_mut() {
+                    sampling.last_values.remove(id);
+                }
+                StatusCode::Good
+            })
+            .collect()
+    }
+
+    /// Forget the last value delivered for a monitored item that was deleted.
+    pub(super) fn remove_sampled_value(&mut self, monitored_item_id: u32) {
+        if let Some(sampling) = self.sampling.as_mut() {
+            sampling.last_values.remove(&monitored_item_id);
+        }
+    }
+}
+
+impl Session {
+    /// Check whether the subscription with the given ID is sampled.
+    pub(super) fn is_sampled(&self, subscription_id: u32) -> bool {
+        trace_lock!(self.subscription_state)
+            .get(subscription_id)
+            .is_some_and(|s| s.is_sampled())
+    }
+
+    /// Check whether a failure to create a subscription or monitored items with `status`
+    /// should make the session fall back to sampling.
+    pub(super) fn should_fall_back_to_sampling(&self, status: StatusCode) -> bool {
+        self.sampled_fallback.enabled && is_rejection(status)
+    }
+
+    /// Create a subscription that only exists on the client, and is emulated with
+    /// periodic reads. Returns the client assigned ID of the subscription.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn create_sampled_subscription(
+        &self,
+        publishing_interval: Duration,
+        lifetime_count: u32,
+        max_keep_alive_count: u32,
+        max_notifications_per_publish: u32,
+        publishing_enabled: bool,
+        priority: u8,
+        callback: Box<dyn OnSubscriptionNotificationCore>,
+    ) -> u32 {
+        let subscription_id = {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            let subscription_id = subscription_state.next_sampled_subscription_id();
+            let mut subscription = Subscription::new(
+                subscription_id,
+                subscription_state.sampling_interval(publishing_interval),
+                lifetime_count,
+                max_keep_alive_count,
+                max_notifications_per_publish,
+                priority,
+                publishing_enabled,
+                callback,
+            );
+            subscription.start_sampling();
+            subscription_state.add_subscription(subscription);
+            subscription_id
+        };
+        self.persist_subscriptions();
+        // Wake up the subscription event loop, which runs the reads.
+        self.trigger_publish_now();
+        subscription_id
+    }
+
+    /// Switch a subscription to sampled mode, emulating it with periodic reads of its
+    /// monitored items instead of receiving notifications from the server. The subscription
+    /// is deleted on the server, its ID, monitored items and callbacks stay the same.
+    ///
+    /// This happens automatically when the subscription falls behind, if
+    /// [`SampledFallbackOptions::max_late_publishes`](crate::SampledFallbackOptions::max_late_publishes)
+    /// is set. Use this to switch subscriptions that are unreliable for other reasons.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_id` - The ID of the subscription to sample.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The subscription is sampled.
+    /// * `Err(StatusCode)` - The subscription does not exist.
+    ///
+    pub async fn fall_back_to_sampling(&self, subscription_id: u32) -> Result<(), StatusCode> {
+        if self.is_sampled(subscription_id) {
+            return Ok(());
+        }
+        if !trace_lock!(self.subscription_state).subscription_exists(subscription_id) {
+            session_error!(
+                self,
+                "fall_back_to_sampling, subscription id {} does not exist",
+                subscription_id
+            );
+            return Err(StatusCode::BadSubscriptionIdInvalid);
+        }
+        // This is best effort, the server may have lost the subscription already.
+        if let Err(e) = DeleteSubscriptions::new(self)
+            .subscription(subscription_id)
+            .send(&self.channel)
+            .await
+        {
+            session_debug!(
+                self,
+                "Failed to delete subscription {} on the server: {}",
+                subscription_id,
+                e
+            );
+        }
+        if trace_lock!(self.subscription_state).start_sampling(subscription_id) {
+            session_warn!(
+                self,
+                "Subscription {} is now emulated with periodic reads",
+                subscription_id
+            );
+        }
+        self.persist_subscriptions();
+        self.trigger_publish_now();
+        Ok(())
+    }
+
+    /// Switch a subscription to sampled mode if it received too many publish responses late.
+    pub(super) async fn fall_back_if_unreliable(&self, subscription_id: u32) {
+        let max_late_publishes = self.sampled_fallback.max_late_publishes;
+        if max_late_publishes == 0 {
+            return;
+        }
+        let unreliable = trace_lock!(self.subscription_state)
+            .get(subscription_id)
+            .is_some_and(|s| !s.is_sampled() && s.late_publish_count() >= max_late_publishes);
+        if unreliable {
+            session_warn!(
+                self,
+                "Subscription {} received {} publish responses late",
+                subscription_id,
+                max_late_publishes
+            );
+            let _ = self.fall_back_to_sampling(subscription_id).await;
+        }
+    }
+
+    /// Create monitored items on a sampled subscription.
+    pub(super) fn create_sampled_monitored_items(
+        &self,
+        subscription_id: u32,
+        items_to_create: Vec<MonitoredItemCreateRequest>,
+        callback: Option<ItemCallback>,
+    ) -> Result<Vec<CreatedMonitoredItem>, StatusCode> {
+        let results = {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            let Some(subscription) = subscription_state.get(subscription_id) else {
+                return Err(StatusCode::BadSubscriptionIdInvalid);
+            };
+            let interval = subscription_state.sampling_interval(subscription.publishing_interval);
+            let Some(subscription) = subscription_state.get_mut(subscription_id) else {
+                return Err(StatusCode::BadSubscriptionIdInvalid);
+            };
+            let results = subscription.create_sampled_items(items_to_create, interval);
+            if let Some(callback) = callback {
+                for item in results.iter().filter(|r| !r.result.status_code.is_bad()) {
+                    subscription.set_item_callback(
+                        item.requested_parameters.client_handle,
+                        callback.clone(),
+                    );
+                }
+            }
+            results
+        };
+        self.persist_subscriptions();
+        self.trigger_publish_now();
+        Ok(results)
+    }
+
+    /// Modify monitored items on a sampled subscription.
+    pub(super) fn modify_sampled_monitored_items(
+        &self,
+        subscription_id: u32,
+        items_to_modify: &[MonitoredItemModifyRequest],
+    ) -> Vec<MonitoredItemModifyResult> {
+        let results = {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            let Some(subscription) = subscription_state.get(subscription_id) else {
+                return Vec::new();
+            };
+            let sampling_interval = subscription_state
+                .sampling_interval(subscription.publishing_interval)
+                .as_secs_f64()
+                * 1000.0;
+            let mut results = Vec::with_capacity(items_to_modify.len());
+            let mut modified = Vec::new();
+            for item in items_to_modify {
+                let mut result = MonitoredItemModifyResult {
+                    status_code: StatusCode::Good,
+                    revised_sampling_interval: sampling_interval,
+                    revised_queue_size: 1,
+                    filter_result: ExtensionObject::null(),
+                };
+                let parameters = &item.requested_parameters;
+                if !subscription
+                    .monitored_items
+                    .contains_key(&item.monitored_item_id)
+                {
+                    result.status_code = StatusCode::BadMonitoredItemIdInvalid;
+                } else if parameters.filter.inner_is::<EventFilter>() {
+                    result.status_code = StatusCode::BadMonitoredItemFilterUnsupported;
+                } else {
+                    modified.push(ModifyMonitoredItem {
+                        id: item.monitored_item_id,
+                        sampling_interval,
+                        queue_size: 1,
+                        discard_oldest: parameters.discard_oldest,
+                        filter: parameters.filter.clone(),
+                    });
+                }
+                results.push(result);
+            }
+            subscription_state.modify_monitored_items(subscription_id, &modified);
+            results
+        };
+        self.persist_subscriptions();
+        results
+    }
+
+    /// Set the monitoring mode of monitored items on a sampled subscription.
+    pub(super) fn set_sampled_monitoring_mode(
+        &self,
+        subscription_id: u32,
+        monitoring_mode: MonitoringMode,
+        monitored_item_ids: &[u32],
+    ) -> Vec<StatusCode> {
+        let results = trace_lock!(self.subscription_state)
+            .get_mut(subscription_id)
+            .map(|s| s.set_sampled_monitoring_mode(monitored_item_ids, monitoring_mode))
+            .unwrap_or_default();
+        self.persist_subscriptions();
+        results
+    }
+
+    /// Delete monitored items from a sampled subscription.
+    pub(super) fn delete_sampled_monitored_items(
+        &self,
+        subscription_id: u32,
+        items_to_delete: &[u32],
+    ) -> Vec<StatusCode> {
+        let results = {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            let results = subscription_state
+                .get(subscription_id)
+                .map(|s| {
+                    items_to_delete
+                        .iter()
+                        .map(|id| {
+                            if s.monitored_items.contains_key(id) {
+                                StatusCode::Good
+                            } else {
+                                StatusCode::BadMonitoredItemIdInvalid
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            subscription_state.delete_monitored_items(subscription_id, items_to_delete);
+            results
+        };
+        self.persist_subscriptions();
+        results
+    }
+
+    /// Read the monitored items of the sampled subscriptions that are due, in a single
+    /// batch, and deliver the values that changed to the subscription callbacks.
+    pub(crate) async fn sample_subscriptions(&self) {
+        let due = trace_lock!(self.subscription_state).take_due_samples(Instant::now());
+        if due.is_empty() {
+            return;
+        }
+        let nodes_to_read: Vec<ReadValueId> = due
+            .iter()
+            .flat_map(|(_, items)| items.iter().map(|(_, node)| node.clone()))
+            .collect();
+        let mut values = if nodes_to_read.is_empty() {
+            Vec::new()
+        } else {
+            match self
+                .read(&nodes_to_read, TimestampsToReturn::Both, 0.0)
+                .await
+            {
+                Ok(values) => values,
+                Err(e) => {
+                    session_debug!(self, "Failed to read sampled monitored items: {}", e);
+                    return;
+                }
+            }
+        }
+        .into_iter();
+
+        let mut subscription_state = trace_lock!(self.subscription_state);
+        for (subscription_id, items) in due {
+            let samples = items
+                .into_iter()
+                .map(|(id, _)| id)
+                .zip(&mut values)
+                .collect();
+            subscription_state.deliver_samples(subscription_id, samples);
+        }
+    }
+
+    /// Get the time the next sampled subscription is due to be read, if any.
+    pub(crate) fn next_sample_time(&self) -> Option<Instant> {
+        trace_lock!(self.subscription_state).next_sample_time()
+    }
+
+    /// Split `subscription_ids` into the IDs of sampled subscriptions and the rest.
+    pub(super) fn partition_sampled(&self, subscription_ids: &[u32]) -> (Vec<u32>, Vec<u32>) {
+        let subscription_state = trace_lock!(self.subscription_state);
+        subscription_ids
+            .iter()
+            .copied()
+            .partition(|id| subscription_state.get(*id).is_some_and(|s| s.is_sampled()))
+    }
+}
@@ -0,0 +1,429 @@
+use opcua_core::trace_lock;
+use opcua_types::{
+    AttributeId, DataChangeNotification, DataValue, DateTime, ExtensionObject,
+    MonitoredItemNotification, MonitoringMode, NotificationMessage, ReadValueId,
+    TimestampsToReturn,
+};
+
+use crate::{
+    session::{session_debug, session_warn},
+    SequenceGapRecovery, Session, UARequest,
+};
+
+use super::{Republish, Subscription};
+
+/// A gap in the sequence numbers of the notification messages received for a subscription,
+/// meaning that notifications sent by the server were lost, for example because a publish
+/// response was lost on the connection.
+///
+/// The gap is reported to the callback of the subscription after the session tried to
+/// recover the missing notifications, as configured with
+/// [`PublishOptions::sequence_gap_recovery`](crate::PublishOptions::sequence_gap_recovery).
+/// Data changes and events between `start_time` and `end_time` may be missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceGap {
+    /// ID of the subscription.
+    pub subscription_id: u32,
+    /// Sequence number of the first missing notification message.
+    pub first_sequence_number: u32,
+    /// Sequence number of the last missing notification message.
+    pub last_sequence_number: u32,
+    /// Publish time of the last notification message or keep-alive received before
+    /// the gap, if any.
+    pub start_time: Option<DateTime>,
+    /// Publish time of the notification message or keep-alive that revealed the gap.
+    pub end_time: DateTime,
+    /// How the session tried to recover the missing notifications.
+    pub recovery: SequenceGapRecovery,
+    /// Number of missing notification messages that were received after all, either
+    /// republished or delivered late.
+    pub recovered: u32,
+}
+
+impl SequenceGap {
+    /// Number of notification messages missing in the gap.
+    pub fn count(&self) -> u32 {
+        sequence_distance(self.first_sequence_number, self.last_sequence_number) + 1
+    }
+
+    /// Number of missing notification messages that were not recovered.
+    pub fn lost(&self) -> u32 {
+        self.count().saturating_sub(self.recovered)
+    }
+
+    fn contains(&self, sequence_number: u32) -> bool {
+        !is_after(self.first_sequence_number, sequence_number)
+            && !is_after(sequence_number, self.last_sequence_number)
+    }
+}
+
+// Sequence numbers run from 1 to `u32::MAX`, and then wrap around to 1.
+
+fn next_sequence_number(sequence_number: u32) -> u32 {
+    if sequence_number == u32::MAX {
+        1
+    } else {
+        sequence_number + 1
+    }
+}
+
+fn previous_sequence_number(sequence_number: u32) -> u32 {
+    if sequence_number <= 1 {
+        u32::MAX
+    } else {
+        sequence_number - 1
+    }
+}
+
+/// Number of steps from `from` forward to `to`.
+fn sequence_distance(from: u32, to: u32) -> u32 {
+    let distance = to.wrapping_sub(from);
+    if to < from {
+        distance - 1
+    } else {
+        distance
+    }
+}
+
+/// Whether `a` comes after `b` in the sequence.
+fn is_after(a: u32, b: u32) -> bool {
+    a != b && sequence_distance(b, a) < u32::MAX / 2
+}
+
+impl Subscription {
+    /// Update the expected sequence number from a received notification message, and
+    /// return the gap of missing sequence numbers before it, if any.
+    pub(super) fn track_sequence_number(
+        &mut self,
+        notification: &NotificationMessage,
+    ) -> Option<SequenceGap> {
+        let sequence_number = notification.sequence_number;
+        if sequence_number == 0 {
+            return None;
+        }
+        let has_data = notification
+            .notification_data
+            .as_ref()
+            .is_some_and(|d| !d.is_empty());
+        let expected = self
+            .next_sequence_number
+            .or(self.last_sequence_number.map(next_sequence_number));
+
+        if expected.is_some_and(|e| is_after(e, sequence_number)) {
+            // Republished, or the response to an earlier publish request handled late.
+            if has_data {
+                self.late_sequence_numbers.push(sequence_number);
+            }
+            return None;
+        }
+
+        // Keep-alives carry the sequence number of the next notification message.
+        let gap = expected
+            .filter(|e| is_after(sequence_number, *e))
+            .map(|expected| SequenceGap {
+                subscription_id: self.subscription_id,
+                first_sequence_number: expected,
+                last_sequence_number: previous_sequence_number(sequence_number),
+                start_time: self.last_publish_time,
+                end_time: notification.publish_time,
+                recovery: SequenceGapRecovery::default(),
+                recovered: 0,
+            });
+        if has_data {
+            self.last_sequence_number = Some(sequence_number);
+            self.next_sequence_number = Some(next_sequence_number(sequence_number));
+        } else {
+            self.next_sequence_number = Some(sequence_number);
+        }
+        self.last_publish_time = Some(notification.publish_time);
+        gap
+    }
+
+    /// Remove the notifications received out of sequence that belong to `gap`,
+    /// returning how many there were.
+    fn take_late_sequence_numbers(&mut self, gap: &SequenceGap) -> u32 {
+        let mut taken = Vec::new();
+        self.late_sequence_numbers.retain(|s| {
+            if gap.contains(*s) {
+                taken.push(*s);
+                false
+            } else {
+                true
+            }
+        });
+        taken.sort_unstable();
+        taken.dedup();
+        taken.len() as u32
+    }
+
+    /// Deliver values read to resynchronize the monitored items to the callbacks,
+    /// as if they were a data change notification.
+    fn deliver_values(&mut self, values: Vec<(u32, DataValue)>) {
+        let monitored_items = values
+            .into_iter()
+            .map(|(client_handle, value)| MonitoredItemNotification {
+                client_handle,
+                value,
+            })
+            .collect();
+        self.deliver(NotificationMessage {
+            sequence_number: 0,
+            publish_time: DateTime::now(),
+            notification_data: Some(vec![ExtensionObject::from_message(
+                DataChangeNotification {
+                    monitored_items: Some(monitored_items),
+                    diagnostic_infos: None,
+                },
+            )]),
+        });
+    }
+}
+
+impl Session {
+    /// Try to recover the notifications missing in `gap` as configured, then report
+    /// the gap to the callback of the subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `gap` - The gap found in the sequence numbers of the subscription.
+    /// * `available_sequence_numbers` - Sequence numbers of the notifications the server
+    ///   still holds for the subscription.
+    pub(crate) async fn recover_sequence_gap(
+        &self,
+        mut gap: SequenceGap,
+        available_sequence_numbers: Vec<u32>,
+    ) {
+        gap.recovery = self.publish_options.sequence_gap_recovery;
+        // Responses to concurrent publish requests may be handled out of order, so give
+        // the missing notifications a chance to arrive before treating them as lost.
+        tokio::task::yield_now().await;
+        {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            let Some(subscription) = subscription_state.get_mut(gap.subscription_id) else {
+                return;
+            };
+            gap.recovered = subscription.take_late_sequence_numbers(&gap);
+        }
+        if gap.lost() == 0 {
+            return;
+        }
+
+        session_warn!(
+            self,
+            "Notifications {} to {} of subscription {} are missing",
+            gap.first_sequence_number,
+            gap.last_sequence_number,
+            gap.subscription_id
+        );
+        match gap.recovery {
+            SequenceGapRecovery::Republish => {
+                let missing = available_sequence_numbers
+                    .into_iter()
+                    .filter(|s| gap.contains(*s))
+                    .collect();
+                self.republish_notifications(
+                    gap.subscription_id,
+                    previous_sequence_number(gap.first_sequence_number),
+                    missing,
+                )
+                .await;
+            }
+            SequenceGapRecovery::Resync => self.resync_values(gap.subscription_id).await,
+            SequenceGapRecovery::Report => (),
+        }
+
+        let mut subscription_state = trace_lock!(self.subscription_state);
+        if let Some(subscription) = subscription_state.get_mut(gap.subscription_id) {
+            gap.recovered += subscription.take_late_sequence_numbers(&gap);
+            subscription.callback.on_sequence_gap(&gap);
+        }
+    }
+
+    /// Republish the notifications in `available_sequence_numbers` that come after
+    /// `last_sequence_number` in order, and hand them to the subscription. Only the most
+    /// recent ones are republished if there are more than
+    /// [`PublishOptions::republish_window`](crate::PublishOptions::republish_window).
+    ///
+    /// Returns the gaps revealed by the republished notifications.
+    pub(crate) async fn republish_notifications(
+        &self,
+        subscription_id: u32,
+        last_sequence_number: u32,
+        available_sequence_numbers: Vec<u32>,
+    ) -> Vec<SequenceGap> {
+        let mut sequence_numbers = available_sequence_numbers
+            .into_iter()
+            .filter(|s| is_after(*s, last_sequence_number))
+            .collect::<Vec<_>>();
+        sequence_numbers.sort_by_key(|s| sequence_distance(last_sequence_number, *s));
+        let window = self.publish_options.republish_window;
+        if window > 0 && sequence_numbers.len() > window {
+            sequence_numbers.drain(..sequence_numbers.len() - window);
+        }
+
+        let mut gaps = Vec::new();
+        for sequence_number in sequence_numbers {
+            match Republish::new(subscription_id, sequence_number, self)
+                .send(&self.channel)
+                .await
+            {
+                Ok(r) => {
+                    let mut subscription_state = trace_lock!(self.subscription_state);
+                    gaps.extend(
+                        subscription_state
+                            .handle_notification(subscription_id, r.notification_message),
+                    );
+                }
+                Err(e) => {
+                    session_warn!(
+                        self,
+                        "Failed to republish notification {} on subscription {}: {}",
+                        sequence_number,
+                        subscription_id,
+                        e
+                    );
+                }
+            }
+        }
+        gaps
+    }
+
+    /// Read the current values of the data change monitored items of a subscription,
+    /// and deliver them to its callbacks.
+    async fn resync_values(&self, subscription_id: u32) {
+        let (client_handles, nodes_to_read): (Vec<u32>, Vec<ReadValueId>) = {
+            let subscription_state = trace_lock!(self.subscription_state);
+            let Some(subscription) = subscription_state.get(subscription_id) else {
+                return;
+            };
+            subscription
+                .monitored_items
+                .values()
+                .filter(|i| {
+                    i.item_to_monitor.attribute_id == AttributeId::Value as u32
+                        && i.monitoring_mode != MonitoringMode::Disabled
+                })
+                .map(|i| (i.client_handle, i.item_to_monitor.clone()))
+                .unzip()
+        };
+        if nodes_to_read.is_empty() {
+            return;
+        }
+
+        session_debug!(
+            self,
+            "Reading {} values to resynchronize subscription {}",
+            nodes_to_read.len(),
+            subscription_id
+        );
+        match self
+            .read(&nodes_to_read, TimestampsToReturn::Both, 0.0)
+            .await
+        {
+            Ok(values) => {
+                let mut subscription_state = trace_lock!(self.subscription_state);
+                if let Some(subscription) = subscription_state.get_mut(subscription_id) {
+                    subscription.deliver_values(client_handles.into_iter().zip(values).collect());
+                }
+            }
+            Err(e) => {
+                session_warn!(
+                    self,
+                    "Failed to read values to resynchronize subscription {}: {}",
+                    subscription_id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{DateTime, ExtensionObject, NotificationMessage, StatusChangeNotification};
+
+    use super::{is_after, sequence_distance, SequenceGap};
+    use crate::{session::services::subscriptions::Subscription, DataChangeCallback};
+
+    fn message(sequence_number: u32, has_data: bool) -> NotificationMessage {
+        if has_data {
+            NotificationMessage {
+                sequence_number,
+                publish_time: DateTime::now(),
+                notification_data: Some(vec![ExtensionObject::from_message(
+                    StatusChangeNotification::default(),
+                )]),
+            }
+        } else {
+            NotificationMessage::keep_alive(sequence_number, DateTime::now())
+        }
+    }
+
+    fn gap_range(gap: Option<SequenceGap>) -> Option<(u32, u32, u32)> {
+        gap.map(|g| (g.first_sequence_number, g.last_sequence_number, g.count()))
+    }
+
+    #[test]
+    fn sequence_number_order() {
+        assert_eq!(sequence_distance(1, 3), 2);
+        assert_eq!(sequence_distance(u32::MAX, 1), 1);
+        assert_eq!(sequence_distance(u32::MAX - 1, 2), 3);
+        assert!(is_after(2, 1));
+        assert!(is_after(1, u32::MAX));
+        assert!(!is_after(u32::MAX, 1));
+        assert!(!is_after(5, 5));
+    }
+
+    #[test]
+    fn detect_sequence_gaps() {
+        let mut sub = Subscription::new(
+            1,
+            std::time::Duration::from_millis(100),
+            100,
+            20,
+            0,
+            0,
+            true,
+            Box::new(DataChangeCallback::new(|_, _| {})),
+        );
+        // Nothing is known about the first message.
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(4, true))),
+            None
+        );
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(5, true))),
+            None
+        );
+        // A keep-alive with the next sequence number, and one revealing a gap.
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(6, false))),
+            None
+        );
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(8, false))),
+            Some((6, 7, 2))
+        );
+        assert_eq!(sub.last_sequence_number, Some(5));
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(10, true))),
+            Some((8, 9, 2))
+        );
+        assert_eq!(sub.last_sequence_number, Some(10));
+
+        // Older messages do not move the sequence back, and are recorded as late.
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(9, true))),
+            None
+        );
+        assert_eq!(sub.last_sequence_number, Some(10));
+        assert_eq!(sub.late_sequence_numbers, vec![9]);
+
+        // Wrapping around skips 0.
+        sub.next_sequence_number = Some(u32::MAX);
+        assert_eq!(
+            gap_range(sub.track_sequence_number(&message(2, true))),
+            Some((u32::MAX, 1, 2))
+        );
+    }
+}
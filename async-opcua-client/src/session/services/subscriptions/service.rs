@@ -1,5 +1,6 @@
 use std::{
-    collections::HashSet,
+    collections::HashMap,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -8,30 +9,33 @@ use crate::{
         process_service_result, process_unexpected_response,
         request_builder::{builder_base, builder_debug, builder_error, RequestHeaderBuilder},
         services::subscriptions::{
-            callbacks::OnSubscriptionNotificationCore, CreateMonitoredItem, ModifyMonitoredItem,
+            callbacks::{Detached, OnSubscriptionNotificationCore},
+            item_callbacks::ItemCallback,
+            CreateMonitoredItem, ModifyMonitoredItem, MonitoredItem, PersistedSubscription,
             Subscription,
         },
-        session_debug, session_error, session_warn,
+        session_debug, session_error, session_warn, FIRST_MONITORED_ITEM_HANDLE,
     },
-    Session, UARequest,
+    Session, SubscriptionTransferPolicy, UARequest,
 };
 use opcua_core::{handle::AtomicHandle, sync::Mutex, trace_lock, ResponseMessage};
 use opcua_types::{
     AttributeId, CreateMonitoredItemsRequest, CreateSubscriptionRequest,
-    CreateSubscriptionResponse, DeleteMonitoredItemsRequest, DeleteMonitoredItemsResponse,
-    DeleteSubscriptionsRequest, DeleteSubscriptionsResponse, DiagnosticInfo, IntegerId,
-    ModifyMonitoredItemsRequest, ModifyMonitoredItemsResponse, ModifySubscriptionRequest,
-    ModifySubscriptionResponse, MonitoredItemCreateRequest, MonitoredItemCreateResult,
-    MonitoredItemModifyRequest, MonitoredItemModifyResult, MonitoringMode, MonitoringParameters,
-    NodeId, NotificationMessage, PublishRequest, PublishResponse, ReadValueId, RepublishRequest,
-    RepublishResponse, ResponseHeader, SetMonitoringModeRequest, SetMonitoringModeResponse,
-    SetPublishingModeRequest, SetPublishingModeResponse, SetTriggeringRequest,
-    SetTriggeringResponse, StatusCode, SubscriptionAcknowledgement, TimestampsToReturn,
+    CreateSubscriptionResponse, DateTime, DeleteMonitoredItemsRequest,
+    DeleteMonitoredItemsResponse, DeleteSubscriptionsRequest, DeleteSubscriptionsResponse,
+    DiagnosticInfo, Error, ExtensionObject, IntegerId, ModifyMonitoredItemsRequest,
+    ModifyMonitoredItemsResponse, ModifySubscriptionRequest, ModifySubscriptionResponse,
+    MonitoredItemCreateRequest, MonitoredItemCreateResult, MonitoredItemModifyRequest,
+    MonitoredItemModifyResult, MonitoringMode, MonitoringParameters, NodeId, NotificationMessage,
+    PublishRequest, PublishResponse, ReadValueId, RepublishRequest, RepublishResponse,
+    ResponseHeader, SetMonitoringModeRequest, SetMonitoringModeResponse, SetPublishingModeRequest,
+    SetPublishingModeResponse, SetTriggeringRequest, SetTriggeringResponse,
+    StatusChangeNotification, StatusCode, SubscriptionAcknowledgement, TimestampsToReturn,
     TransferResult, TransferSubscriptionsRequest, TransferSubscriptionsResponse,
 };
 use tracing::enabled;
 
-use super::state::SubscriptionState;
+use super::{sampled::merge_sampled_results, state::SubscriptionState};
 
 /// Create a subscription by sending a [`CreateSubscriptionRequest`] to the server.
 ///
@@ -1427,7 +1431,7 @@ impl Session {
         priority: u8,
         callback: Box<dyn OnSubscriptionNotificationCore>,
     ) -> Result<u32, StatusCode> {
-        let response = CreateSubscription::new(self)
+        let response = match CreateSubscription::new(self)
             .publishing_interval(publishing_interval)
             .max_lifetime_count(lifetime_count)
             .max_keep_alive_count(max_keep_alive_count)
@@ -1435,7 +1439,27 @@ impl Session {
             .publishing_enabled(publishing_enabled)
             .priority(priority)
             .send(&self.channel)
-            .await?;
+            .await
+        {
+            Ok(response) => response,
+            Err(e) if self.should_fall_back_to_sampling(e) => {
+                session_warn!(
+                    self,
+                    "Server rejected subscription with {}, emulating it with periodic reads",
+                    e
+                );
+                return Ok(self.create_sampled_subscription(
+                    publishing_interval,
+                    lifetime_count,
+                    max_keep_alive_count,
+                    max_notifications_per_publish,
+                    publishing_enabled,
+                    priority,
+                    callback,
+                ));
+            }
+            Err(e) => return Err(e),
+        };
 
         let subscription = Subscription::new(
             response.subscription_id,
@@ -1451,6 +1475,7 @@ impl Session {
             let mut subscription_state = trace_lock!(self.subscription_state);
             subscription_state.add_subscription(subscription);
         }
+        self.persist_subscriptions();
 
         self.trigger_publish_now();
 
@@ -1577,6 +1602,22 @@ impl Session {
             session_error!(self, "modify_subscription, subscription id does not exist");
             return Err(StatusCode::BadInvalidArgument);
         }
+        if self.is_sampled(subscription_id) {
+            {
+                let mut subscription_state = trace_lock!(self.subscription_state);
+                let publishing_interval = subscription_state.sampling_interval(publishing_interval);
+                subscription_state.modify_subscription(
+                    subscription_id,
+                    publishing_interval,
+                    lifetime_count,
+                    max_keep_alive_count,
+                    max_notifications_per_publish,
+                    priority,
+                );
+            }
+            self.persist_subscriptions();
+            return Ok(());
+        }
 
         let response = ModifySubscription::new(subscription_id, self)
             .publishing_interval(publishing_interval)
@@ -1598,6 +1639,7 @@ impl Session {
                 priority,
             );
         }
+        self.persist_subscriptions();
 
         Ok(())
     }
@@ -1622,12 +1664,19 @@ impl Session {
         subscription_ids: &[u32],
         publishing_enabled: bool,
     ) -> Result<Vec<StatusCode>, StatusCode> {
-        let results = SetPublishingMode::new(publishing_enabled, self)
-            .subscription_ids(subscription_ids.to_vec())
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default();
+        // Sampled subscriptions only exist on the client.
+        let (sampled, server_ids) = self.partition_sampled(subscription_ids);
+        let results = if server_ids.is_empty() {
+            Vec::new()
+        } else {
+            SetPublishingMode::new(publishing_enabled, self)
+                .subscription_ids(server_ids)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default()
+        };
+        let results = merge_sampled_results(subscription_ids, &sampled, results);
 
         {
             // Update all subscriptions where the returned status is good.
@@ -1640,6 +1689,7 @@ impl Session {
                 .collect::<Vec<_>>();
             subscription_state.set_publishing_mode(&ids, publishing_enabled);
         }
+        self.persist_subscriptions();
 
         if publishing_enabled {
             self.trigger_publish_now();
@@ -1739,12 +1789,19 @@ impl Session {
         &self,
         subscription_ids: &[u32],
     ) -> Result<Vec<StatusCode>, StatusCode> {
-        let result = DeleteSubscriptions::new(self)
-            .subscription_ids(subscription_ids.to_vec())
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default();
+        // Sampled subscriptions only exist on the client.
+        let (sampled, server_ids) = self.partition_sampled(subscription_ids);
+        let result = if server_ids.is_empty() {
+            Vec::new()
+        } else {
+            DeleteSubscriptions::new(self)
+                .subscription_ids(server_ids)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default()
+        };
+        let result = merge_sampled_results(subscription_ids, &sampled, result);
         {
             // Clear out deleted subscriptions, assuming the delete worked
             let mut subscription_state = trace_lock!(self.subscription_state);
@@ -1752,6 +1809,7 @@ impl Session {
                 subscription_state.delete_subscription(*id);
             }
         }
+        self.persist_subscriptions();
 
         Ok(result)
     }
@@ -1777,6 +1835,24 @@ impl Session {
         subscription_id: u32,
         timestamps_to_return: TimestampsToReturn,
         items_to_create: Vec<MonitoredItemCreateRequest>,
+    ) -> Result<Vec<CreatedMonitoredItem>, StatusCode> {
+        self.create_monitored_items_inner(
+            subscription_id,
+            timestamps_to_return,
+            items_to_create,
+            None,
+        )
+        .await
+    }
+
+    /// Create monitored items, registering `callback` for the created items while
+    /// inserting them, so that no notifications for them are missed.
+    pub(crate) async fn create_monitored_items_inner(
+        &self,
+        subscription_id: u32,
+        timestamps_to_return: TimestampsToReturn,
+        items_to_create: Vec<MonitoredItemCreateRequest>,
+        callback: Option<ItemCallback>,
     ) -> Result<Vec<CreatedMonitoredItem>, StatusCode> {
         {
             let state = trace_lock!(self.subscription_state);
@@ -1789,11 +1865,32 @@ impl Session {
                 return Err(StatusCode::BadSubscriptionIdInvalid);
             }
         }
-        let result = CreateMonitoredItems::new(subscription_id, self)
-            .items_to_create(items_to_create)
+        if self.is_sampled(subscription_id) {
+            return self.create_sampled_monitored_items(subscription_id, items_to_create, callback);
+        }
+        let result = match CreateMonitoredItems::new(subscription_id, self)
+            .items_to_create(items_to_create.clone())
             .timestamps_to_return(timestamps_to_return)
             .send(&self.channel)
-            .await?;
+            .await
+        {
+            Ok(result) => result,
+            Err(e) if self.should_fall_back_to_sampling(e) => {
+                session_warn!(
+                    self,
+                    "Server rejected monitored items with {}, emulating subscription {} with periodic reads",
+                    e,
+                    subscription_id
+                );
+                self.fall_back_to_sampling(subscription_id).await?;
+                return self.create_sampled_monitored_items(
+                    subscription_id,
+                    items_to_create,
+                    callback,
+                );
+            }
+            Err(e) => return Err(e),
+        };
         // Set the items in our internal state
         let items_to_create = result
             .results
@@ -1812,7 +1909,22 @@ impl Session {
         {
             let mut subscription_state = trace_lock!(self.subscription_state);
             subscription_state.insert_monitored_items(subscription_id, items_to_create);
+            if let (Some(callback), Some(subscription)) =
+                (callback, subscription_state.get_mut(subscription_id))
+            {
+                for item in result
+                    .results
+                    .iter()
+                    .filter(|r| !r.result.status_code.is_bad())
+                {
+                    subscription.set_item_callback(
+                        item.requested_parameters.client_handle,
+                        callback.clone(),
+                    );
+                }
+            }
         }
+        self.persist_subscriptions();
 
         Ok(result.results)
     }
@@ -1850,10 +1962,9 @@ impl Session {
                 return Err(StatusCode::BadSubscriptionIdInvalid);
             }
         }
-        let ids = items_to_modify
-            .iter()
-            .map(|i| i.monitored_item_id)
-            .collect::<Vec<_>>();
+        if self.is_sampled(subscription_id) {
+            return Ok(self.modify_sampled_monitored_items(subscription_id, items_to_modify));
+        }
         let results = ModifyMonitoredItems::new(subscription_id, self)
             .timestamps_to_return(timestamps_to_return)
             .items_to_modify(items_to_modify.to_vec())
@@ -1862,19 +1973,24 @@ impl Session {
             .results
             .unwrap_or_default();
 
-        let items_to_modify = ids
+        // Items the server failed to modify keep their previous parameters.
+        let items_to_modify = items_to_modify
             .iter()
             .zip(results.iter())
-            .map(|(id, r)| ModifyMonitoredItem {
-                id: *id,
+            .filter(|(_, r)| r.status_code.is_good())
+            .map(|(i, r)| ModifyMonitoredItem {
+                id: i.monitored_item_id,
                 queue_size: r.revised_queue_size,
                 sampling_interval: r.revised_sampling_interval,
+                discard_oldest: i.requested_parameters.discard_oldest,
+                filter: i.requested_parameters.filter.clone(),
             })
             .collect::<Vec<ModifyMonitoredItem>>();
         {
             let mut subscription_state = trace_lock!(self.subscription_state);
             subscription_state.modify_monitored_items(subscription_id, &items_to_modify);
         }
+        self.persist_subscriptions();
 
         Ok(results)
     }
@@ -1912,6 +2028,13 @@ impl Session {
                 return Err(StatusCode::BadSubscriptionIdInvalid);
             }
         }
+        if self.is_sampled(subscription_id) {
+            return Ok(self.set_sampled_monitoring_mode(
+                subscription_id,
+                monitoring_mode,
+                monitored_item_ids,
+            ));
+        }
         let results = SetMonitoringMode::new(subscription_id, monitoring_mode, self)
             .monitored_item_ids(monitored_item_ids.to_vec())
             .send(&self.channel)
@@ -1929,6 +2052,7 @@ impl Session {
             let mut subscription_state = trace_lock!(self.subscription_state);
             subscription_state.set_monitoring_mode(subscription_id, &ok_ids, monitoring_mode);
         }
+        self.persist_subscriptions();
 
         Ok(results)
     }
@@ -1969,6 +2093,14 @@ impl Session {
                 return Err(StatusCode::BadSubscriptionIdInvalid);
             }
         }
+        if self.is_sampled(subscription_id) {
+            session_error!(
+                self,
+                "set_triggering, triggering is not supported on sampled subscription {}",
+                subscription_id
+            );
+            return Err(StatusCode::BadServiceUnsupported);
+        }
         let response = SetTriggering::new(subscription_id, triggering_item_id, self)
             .links_to_add(links_to_add.to_vec())
             .links_to_remove(links_to_remove.to_vec())
@@ -1993,14 +2125,17 @@ impl Session {
             .copied()
             .collect::<Vec<_>>();
 
-        // Update client side state
-        let mut subscription_state = trace_lock!(self.subscription_state);
-        subscription_state.set_triggering(
-            subscription_id,
-            triggering_item_id,
-            &ok_adds,
-            &ok_removes,
-        );
+        {
+            // Update client side state
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            subscription_state.set_triggering(
+                subscription_id,
+                triggering_item_id,
+                &ok_adds,
+                &ok_removes,
+            );
+        }
+        self.persist_subscriptions();
         Ok((response.add_results, response.remove_results))
     }
 
@@ -2035,14 +2170,20 @@ impl Session {
                 return Err(StatusCode::BadSubscriptionIdInvalid);
             }
         }
+        if self.is_sampled(subscription_id) {
+            return Ok(self.delete_sampled_monitored_items(subscription_id, items_to_delete));
+        }
         let response = DeleteMonitoredItems::new(subscription_id, self)
             .items_to_delete(items_to_delete.to_vec())
             .send(&self.channel)
             .await?
             .results
             .unwrap_or_default();
-        let mut subscription_state = trace_lock!(self.subscription_state);
-        subscription_state.delete_monitored_items(subscription_id, items_to_delete);
+        {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            subscription_state.delete_monitored_items(subscription_id, items_to_delete);
+        }
+        self.persist_subscriptions();
         Ok(response)
     }
 
@@ -2056,10 +2197,12 @@ impl Session {
 
     /// Send a publish request, returning `true` if the session should send a new request
     /// immediately.
-    pub(crate) async fn publish(&self) -> Result<bool, StatusCode> {
+    pub(crate) async fn publish(self: &Arc<Self>) -> Result<bool, StatusCode> {
+        let _publish = self.health.start_publish();
         let acks = {
             let mut subscription_state = trace_lock!(self.subscription_state);
-            let acks = subscription_state.take_acknowledgements();
+            let acks = subscription_state
+                .take_acknowledgements(self.publish_options.max_acknowledgements_per_publish);
             if !acks.is_empty() {
                 Some(acks)
             } else {
@@ -2073,8 +2216,33 @@ impl Session {
             .await
         {
             Ok(r) => {
-                let mut subscription_state = trace_lock!(self.subscription_state);
-                subscription_state.handle_notification(r.subscription_id, r.notification_message);
+                let has_data = r
+                    .notification_message
+                    .notification_data
+                    .as_ref()
+                    .is_some_and(|d| !d.is_empty());
+                let gap = {
+                    let mut subscription_state = trace_lock!(self.subscription_state);
+                    subscription_state
+                        .handle_notification(r.subscription_id, r.notification_message)
+                };
+                // Keep alives do not change the last received sequence number.
+                if has_data {
+                    self.persist_subscriptions();
+                }
+                // Recovering the gap may republish notifications, which must not hold up
+                // handling of further publish responses.
+                if let Some(gap) = gap {
+                    let session = self.clone();
+                    let available_sequence_numbers =
+                        r.available_sequence_numbers.unwrap_or_default();
+                    tokio::task::spawn(async move {
+                        session
+                            .recover_sequence_gap(gap, available_sequence_numbers)
+                            .await;
+                    });
+                }
+                self.fall_back_if_unreliable(r.subscription_id).await;
                 Ok(r.more_notifications)
             }
             Err(e) => {
@@ -2118,41 +2286,201 @@ impl Session {
         Ok(res.notification_message)
     }
 
+    /// Schedule saving the current state of the subscriptions to the subscription store,
+    /// if one is configured. The save happens in the background shortly after.
+    pub(crate) fn persist_subscriptions(&self) {
+        if let Some(persister) = &self.subscription_persister {
+            persister.schedule();
+        }
+    }
+
+    /// Load the subscriptions saved in the subscription store set with
+    /// [`SessionBuilder::subscription_store`](crate::SessionBuilder::subscription_store),
+    /// and add them to the subscription state.
+    ///
+    /// Call this before starting the event loop. When the session connects, it will try to
+    /// reactivate the stored session, or transfer the subscriptions to a new session, according
+    /// to the [`SubscriptionTransferPolicy`]. Notifications the server still holds for a transferred
+    /// subscription that were not received before are republished.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - Called for each stored subscription to get the callback for its notifications.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of subscriptions restored.
+    /// * `Err(Error)` - No store is configured, or loading from it failed.
+    ///
+    pub fn restore_subscriptions(
+        &self,
+        mut callback: impl FnMut(&PersistedSubscription) -> Box<dyn OnSubscriptionNotificationCore>,
+    ) -> Result<usize, Error> {
+        let Some(store) = &self.subscription_store else {
+            return Err(Error::new(
+                StatusCode::BadInvalidState,
+                "No subscription store is configured",
+            ));
+        };
+        let Some(persisted) = store.load()? else {
+            return Ok(0);
+        };
+
+        if self.session_id.load().is_null() {
+            self.session_id
+                .store(std::sync::Arc::new(persisted.session_id));
+        }
+
+        let count = persisted.subscriptions.len();
+        let mut max_client_handle = 0;
+        {
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            for subscription in persisted.subscriptions {
+                for item in &subscription.monitored_items {
+                    max_client_handle =
+                        max_client_handle.max(item.request.requested_parameters.client_handle);
+                }
+                let callback = callback(&subscription);
+                subscription_state.add_subscription(subscription.into_subscription(callback));
+            }
+        }
+        // Make sure new monitored items do not reuse the client handles of the restored ones.
+        if (FIRST_MONITORED_ITEM_HANDLE..u32::MAX).contains(&max_client_handle) {
+            self.monitored_item_handle.set_next(max_client_handle + 1);
+        }
+
+        Ok(count)
+    }
+
+    /// Republish the notifications in `available_sequence_numbers` that come after
+    /// the last notification received on the subscription, if any.
+    async fn republish_missed_notifications(
+        &self,
+        subscription_id: u32,
+        available_sequence_numbers: Vec<u32>,
+    ) {
+        let last_sequence_number = {
+            let subscription_state = trace_lock!(self.subscription_state);
+            subscription_state
+                .get(subscription_id)
+                .and_then(|s| s.last_sequence_number())
+        };
+        let Some(last_sequence_number) = last_sequence_number else {
+            return;
+        };
+
+        let gaps = self
+            .republish_notifications(
+                subscription_id,
+                last_sequence_number,
+                available_sequence_numbers,
+            )
+            .await;
+        // Notifications the server no longer holds can only be recovered by other means.
+        for gap in gaps {
+            self.recover_sequence_gap(gap, Vec::new()).await;
+        }
+    }
+
     /// This code attempts to take the existing subscriptions created by a previous session and
-    /// either transfer them to this session, or construct them from scratch.
-    pub(crate) async fn transfer_subscriptions_from_old_session(&self) {
+    /// either transfer them to this session, or construct them from scratch, depending on the
+    /// configured [`SubscriptionTransferPolicy`].
+    ///
+    /// Returns `true` if there were subscriptions, and they were transferred or recreated.
+    pub(crate) async fn transfer_subscriptions_from_old_session(&self) -> bool {
+        // Sampled subscriptions only exist on the client, so they need not be transferred.
         let subscription_ids = {
             let subscription_state = trace_lock!(self.subscription_state);
-            subscription_state.subscription_ids()
+            subscription_state.server_subscription_ids()
         };
 
-        let Some(subscription_ids) = subscription_ids else {
-            return;
-        };
+        if subscription_ids.is_empty() {
+            return false;
+        }
 
         // Start by getting the subscription ids
         // Try to use TransferSubscriptions to move subscriptions_ids over. If this
         // works then there is nothing else to do.
-        let mut subscription_ids_to_recreate =
-            subscription_ids.iter().copied().collect::<HashSet<u32>>();
-        if let Ok(transfer_results) = self.transfer_subscriptions(&subscription_ids, true).await {
-            session_debug!(self, "transfer_results = {:?}", transfer_results);
-            transfer_results.iter().enumerate().for_each(|(i, r)| {
-                if r.status_code.is_good() {
-                    // Subscription was transferred so it does not need to be recreated
-                    subscription_ids_to_recreate.remove(&subscription_ids[i]);
+        let mut subscription_ids_to_recreate = subscription_ids
+            .iter()
+            .map(|id| (*id, StatusCode::BadSubscriptionIdInvalid))
+            .collect::<HashMap<u32, StatusCode>>();
+        if self.subscription_transfer_policy != SubscriptionTransferPolicy::Recreate {
+            match self.transfer_subscriptions(&subscription_ids, true).await {
+                Ok(transfer_results) => {
+                    session_debug!(self, "transfer_results = {:?}", transfer_results);
+                    for (id, r) in subscription_ids.iter().zip(transfer_results) {
+                        if r.status_code.is_good() {
+                            // Subscription was transferred so it does not need to be recreated
+                            subscription_ids_to_recreate.remove(id);
+                            if self.publish_options.republish_missed_notifications {
+                                self.republish_missed_notifications(
+                                    *id,
+                                    r.available_sequence_numbers.unwrap_or_default(),
+                                )
+                                .await;
+                            }
+                        } else {
+                            subscription_ids_to_recreate.insert(*id, r.status_code);
+                        }
+                    }
+                }
+                Err(e) => {
+                    subscription_ids_to_recreate
+                        .values_mut()
+                        .for_each(|status| *status = e);
                 }
-            });
+            }
+        }
+
+        if subscription_ids_to_recreate.is_empty() {
+            return true;
+        }
+
+        if self.subscription_transfer_policy == SubscriptionTransferPolicy::Fail {
+            session_warn!(
+                self,
+                "Some or all of the existing subscriptions could not be transferred and will be removed"
+            );
+            let mut subscription_state = trace_lock!(self.subscription_state);
+            for (subscription_id, status) in subscription_ids_to_recreate {
+                if let Some(mut subscription) =
+                    subscription_state.delete_subscription(subscription_id)
+                {
+                    subscription.on_notification(NotificationMessage {
+                        sequence_number: 0,
+                        publish_time: DateTime::now(),
+                        notification_data: Some(vec![ExtensionObject::from_message(
+                            StatusChangeNotification {
+                                status,
+                                diagnostic_info: DiagnosticInfo::null(),
+                            },
+                        )]),
+                    });
+                }
+            }
+            return false;
         }
 
         // But if it didn't work, then some or all subscriptions have to be remade.
-        if !subscription_ids_to_recreate.is_empty() {
+        if self.subscription_transfer_policy == SubscriptionTransferPolicy::Transfer {
             session_warn!(self, "Some or all of the existing subscriptions could not be transferred and must be created manually");
         }
 
-        for subscription_id in subscription_ids_to_recreate {
-            session_debug!(self, "Recreating subscription {}", subscription_id);
+        self.recreate_subscriptions(subscription_ids_to_recreate.into_keys())
+            .await;
+
+        true
+    }
 
+    /// Create the subscriptions with the given IDs again on the server, with the same
+    /// parameters, monitored items and callbacks, replacing them in the subscription state.
+    /// This is used when the subscriptions no longer exist on the server.
+    pub(crate) async fn recreate_subscriptions(
+        &self,
+        subscription_ids: impl IntoIterator<Item = u32>,
+    ) {
+        for subscription_id in subscription_ids {
             let deleted_subscription = {
                 let mut subscription_state = trace_lock!(self.subscription_state);
                 subscription_state.delete_subscription(subscription_id)
@@ -2166,67 +2494,87 @@ impl Session {
                 continue;
             };
 
-            let Ok(subscription_id) = self
-                .create_subscription_inner(
-                    subscription.publishing_interval,
-                    subscription.lifetime_count,
-                    subscription.max_keep_alive_count,
-                    subscription.max_notifications_per_publish,
-                    subscription.publishing_enabled,
-                    subscription.priority,
-                    subscription.callback,
-                )
-                .await
-            else {
+            let _ = self.recreate_subscription(subscription).await;
+        }
+    }
+
+    /// Create a subscription on the server from an existing subscription, which may come from
+    /// another session, with the same parameters, monitored items and callback.
+    /// Monitored items keep their client handles.
+    ///
+    /// Returns the ID of the new subscription, or the existing subscription, unchanged,
+    /// if the subscription could not be created.
+    pub(crate) async fn recreate_subscription(
+        &self,
+        mut subscription: Subscription,
+    ) -> Result<u32, (StatusCode, Box<Subscription>)> {
+        let old_subscription_id = subscription.subscription_id;
+        session_debug!(self, "Recreating subscription {}", old_subscription_id);
+
+        // The callback is moved over once the subscription exists, so that it is kept if
+        // creating the subscription fails.
+        let subscription_id = match self
+            .create_subscription_inner(
+                subscription.publishing_interval,
+                subscription.lifetime_count,
+                subscription.max_keep_alive_count,
+                subscription.max_notifications_per_publish,
+                subscription.publishing_enabled,
+                subscription.priority,
+                Box::new(Detached),
+            )
+            .await
+        {
+            Ok(subscription_id) => subscription_id,
+            Err(e) => {
                 session_warn!(
                     self,
-                    "Could not create a subscription from the existing subscription {}",
-                    subscription_id
+                    "Could not create a subscription from the existing subscription {}: {}",
+                    old_subscription_id,
+                    e
                 );
-                continue;
-            };
+                return Err((e, Box::new(subscription)));
+            }
+        };
+        if let Some(new_subscription) =
+            trace_lock!(self.subscription_state).get_mut(subscription_id)
+        {
+            new_subscription.replace_callback(subscription.replace_callback(Box::new(Detached)));
+            new_subscription.extend_item_callbacks(subscription.take_item_callbacks());
+        }
 
-            let items_to_create = subscription
-                .monitored_items
-                .values()
-                .map(|item| MonitoredItemCreateRequest {
-                    item_to_monitor: item.item_to_monitor().clone(),
-                    monitoring_mode: item.monitoring_mode,
-                    requested_parameters: MonitoringParameters {
-                        client_handle: item.client_handle(),
-                        sampling_interval: item.sampling_interval(),
-                        filter: item.filter.clone(),
-                        queue_size: item.queue_size() as u32,
-                        discard_oldest: item.discard_oldest(),
-                    },
-                })
-                .collect::<Vec<MonitoredItemCreateRequest>>();
+        let items_to_create = subscription
+            .monitored_items
+            .values()
+            .map(MonitoredItem::create_request)
+            .collect::<Vec<MonitoredItemCreateRequest>>();
 
-            let mut iter = items_to_create.into_iter();
+        let mut iter = items_to_create.into_iter();
 
-            loop {
-                let chunk = (&mut iter)
-                    .take(self.recreate_monitored_items_chunk)
-                    .collect::<Vec<_>>();
+        loop {
+            let chunk = (&mut iter)
+                .take(self.recreate_monitored_items_chunk)
+                .collect::<Vec<_>>();
 
-                if chunk.is_empty() {
-                    break;
-                }
+            if chunk.is_empty() {
+                break;
+            }
+
+            let _ = self
+                .create_monitored_items(subscription_id, TimestampsToReturn::Both, chunk)
+                .await;
+        }
 
+        for item in subscription.monitored_items.values() {
+            let triggered_items = item.triggered_items();
+            if !triggered_items.is_empty() {
+                let links_to_add = triggered_items.iter().copied().collect::<Vec<u32>>();
                 let _ = self
-                    .create_monitored_items(subscription_id, TimestampsToReturn::Both, chunk)
+                    .set_triggering(subscription_id, item.id(), links_to_add.as_slice(), &[])
                     .await;
             }
-
-            for item in subscription.monitored_items.values() {
-                let triggered_items = item.triggered_items();
-                if !triggered_items.is_empty() {
-                    let links_to_add = triggered_items.iter().copied().collect::<Vec<u32>>();
-                    let _ = self
-                        .set_triggering(subscription_id, item.id(), links_to_add.as_slice(), &[])
-                        .await;
-                }
-            }
         }
+
+        Ok(subscription_id)
     }
 }
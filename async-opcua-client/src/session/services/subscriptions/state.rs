@@ -3,9 +3,15 @@ use std::{
     time::{Duration, Instant},
 };
 
-use opcua_types::{MonitoringMode, NotificationMessage, SubscriptionAcknowledgement};
+use opcua_types::{
+    DataValue, MonitoringMode, NodeId, NotificationMessage, ReadValueId,
+    SubscriptionAcknowledgement,
+};
 
-use super::{CreateMonitoredItem, ModifyMonitoredItem, PublishLimits, Subscription};
+use super::{
+    sampled::MIN_SAMPLING_INTERVAL, CreateMonitoredItem, ModifyMonitoredItem, MonitoredItemHandle,
+    PersistedSubscriptions, PublishLimits, SequenceGap, Subscription,
+};
 
 /// State containing all known subscriptions in the session.
 pub struct SubscriptionState {
@@ -39,14 +45,15 @@ impl SubscriptionState {
     }
 
     pub(crate) fn next_publish_time(&self) -> Option<Instant> {
-        if self.subscriptions.is_empty() {
+        // Sampled subscriptions do not exist on the server, so they need no publish requests.
+        if self.subscriptions.values().all(|s| s.is_sampled()) {
             return None;
         }
 
         let next = self
             .subscriptions
             .values()
-            .filter(|s| s.publishing_enabled())
+            .filter(|s| s.publishing_enabled() && !s.is_sampled())
             .map(|s| s.publishing_interval().max(self.min_publish_interval))
             .min()
             .or(self.keep_alive_timeout)
@@ -59,8 +66,13 @@ impl SubscriptionState {
         self.last_publish = Instant::now();
     }
 
-    pub(crate) fn take_acknowledgements(&mut self) -> Vec<SubscriptionAcknowledgement> {
-        std::mem::take(&mut self.acknowledgements)
+    /// Take up to `max` acknowledgements to send, or all of them if `max` is 0.
+    pub(crate) fn take_acknowledgements(&mut self, max: usize) -> Vec<SubscriptionAcknowledgement> {
+        if max == 0 || self.acknowledgements.len() <= max {
+            std::mem::take(&mut self.acknowledgements)
+        } else {
+            self.acknowledgements.drain(..max).collect()
+        }
     }
 
     pub(crate) fn add_acknowledgement(&mut self, subscription_id: u32, sequence_number: u32) {
@@ -83,6 +95,16 @@ impl SubscriptionState {
         }
     }
 
+    /// List of IDs of the subscriptions that exist on the server, which are all
+    /// subscriptions that are not sampled.
+    pub(crate) fn server_subscription_ids(&self) -> Vec<u32> {
+        self.subscriptions
+            .values()
+            .filter(|s| !s.is_sampled())
+            .map(|s| s.subscription_id())
+            .collect()
+    }
+
     /// Check if the subscription ID is known.
     pub fn subscription_exists(&self, subscription_id: u32) -> bool {
         self.subscriptions.contains_key(&subscription_id)
@@ -93,6 +115,28 @@ impl SubscriptionState {
         self.subscriptions.get(&subscription_id)
     }
 
+    /// Treat all subscriptions as having received a publish response now, so that
+    /// the time spent reconnecting does not count as a late publish response.
+    pub(crate) fn reset_last_publish_received(&mut self) {
+        let now = Instant::now();
+        for subscription in self.subscriptions.values_mut() {
+            subscription.last_publish_received = now;
+        }
+    }
+
+    /// Get the ID of the subscription containing the monitored item with the given handle.
+    pub(crate) fn subscription_for_item(&self, item: MonitoredItemHandle) -> Option<u32> {
+        self.subscriptions
+            .values()
+            .find(|s| s.client_handles.contains_key(&item.client_handle()))
+            .map(|s| s.subscription_id())
+    }
+
+    /// Iterate over all subscriptions.
+    pub(crate) fn subscriptions(&self) -> impl Iterator<Item = &Subscription> {
+        self.subscriptions.values()
+    }
+
     /// Get the number of subscriptions.
     pub fn len(&self) -> usize {
         self.subscriptions.len()
@@ -117,6 +161,23 @@ impl SubscriptionState {
         self.update_publish_limits();
     }
 
+    pub(crate) fn get_mut(&mut self, subscription_id: u32) -> Option<&mut Subscription> {
+        self.subscriptions.get_mut(&subscription_id)
+    }
+
+    /// Change the namespace index of the node IDs of monitored items from each key
+    /// of `remapped` to its value.
+    pub(crate) fn remap_namespaces(&mut self, remapped: &HashMap<u16, u16>) {
+        for subscription in self.subscriptions.values_mut() {
+            for item in subscription.monitored_items.values_mut() {
+                let node_id = &mut item.item_to_monitor.node_id;
+                if let Some(namespace) = remapped.get(&node_id.namespace) {
+                    node_id.namespace = *namespace;
+                }
+            }
+        }
+    }
+
     pub(crate) fn modify_subscription(
         &mut self,
         subscription_id: u32,
@@ -208,26 +269,102 @@ impl SubscriptionState {
         }
     }
 
+    /// Handle a notification message received for a subscription, returning the gap in
+    /// sequence numbers it revealed, if any.
     pub(crate) fn handle_notification(
         &mut self,
         subscription_id: u32,
         notification: NotificationMessage,
-    ) {
+    ) -> Option<SequenceGap> {
+        // A subscription switched to sampled mode may still get responses from the server
+        // that were in flight, the values are read by the client instead.
+        if self
+            .subscriptions
+            .get(&subscription_id)
+            .is_some_and(|s| s.is_sampled())
+        {
+            return None;
+        }
         self.add_acknowledgement(subscription_id, notification.sequence_number);
         if let Some(sub) = self.subscriptions.get_mut(&subscription_id) {
-            sub.on_notification(notification);
+            sub.on_notification(notification)
         } else {
             tracing::warn!(
                 "Received notification for unknown subscription {}",
                 subscription_id
             );
+            None
         }
     }
 
+    /// Switch a subscription to sampled mode, returning `false` if it does not exist
+    /// or is sampled already.
+    pub(crate) fn start_sampling(&mut self, subscription_id: u32) -> bool {
+        let Some(subscription) = self.subscriptions.get_mut(&subscription_id) else {
+            return false;
+        };
+        if subscription.is_sampled() {
+            return false;
+        }
+        subscription.start_sampling();
+        self.set_keep_alive_timeout();
+        self.update_publish_limits();
+        true
+    }
+
+    /// Get an unused ID for a sampled subscription. IDs are assigned counting down from
+    /// the largest ID, as servers typically count up.
+    pub(crate) fn next_sampled_subscription_id(&self) -> u32 {
+        (1..=u32::MAX)
+            .rev()
+            .find(|id| !self.subscriptions.contains_key(id))
+            .unwrap_or_default()
+    }
+
+    /// The interval sampled subscriptions with the given publishing interval are read at.
+    pub(crate) fn sampling_interval(&self, publishing_interval: Duration) -> Duration {
+        publishing_interval
+            .max(self.min_publish_interval)
+            .max(MIN_SAMPLING_INTERVAL)
+    }
+
+    /// Get the time the next sampled subscription is due to be read, if any.
+    pub(crate) fn next_sample_time(&self) -> Option<Instant> {
+        self.subscriptions
+            .values()
+            .filter_map(|s| s.next_sample_time())
+            .min()
+    }
+
+    /// Take the monitored items to read of each sampled subscription that is due at `now`,
+    /// scheduling the next read of those subscriptions.
+    pub(crate) fn take_due_samples(&mut self, now: Instant) -> Vec<(u32, Vec<(u32, ReadValueId)>)> {
+        let min_interval = self.min_publish_interval.max(MIN_SAMPLING_INTERVAL);
+        self.subscriptions
+            .values_mut()
+            .filter_map(|s| {
+                s.take_due_sample(now, min_interval)
+                    .map(|items| (s.subscription_id(), items))
+            })
+            .collect()
+    }
+
+    /// Deliver the values read for the monitored items of a sampled subscription, by ID.
+    pub(crate) fn deliver_samples(&mut self, subscription_id: u32, values: Vec<(u32, DataValue)>) {
+        if let Some(subscription) = self.subscriptions.get_mut(&subscription_id) {
+            subscription.on_samples(values);
+        }
+    }
+
+    pub(crate) fn persisted(&self, session_id: NodeId) -> PersistedSubscriptions {
+        PersistedSubscriptions::new(session_id, &self.subscriptions)
+    }
+
     fn set_keep_alive_timeout(&mut self) {
         self.keep_alive_timeout = self
             .subscriptions
             .values()
+            .filter(|s| !s.is_sampled())
             .map(|v| v.publishing_interval() * v.lifetime_count())
             .min()
     }
@@ -236,13 +373,18 @@ impl SubscriptionState {
         let publish_interval = self
             .subscriptions
             .values()
-            .filter(|s| s.publishing_enabled())
+            .filter(|s| s.publishing_enabled() && !s.is_sampled())
             .map(|s| s.publishing_interval().max(self.min_publish_interval))
             .min()
             .unwrap_or(Duration::ZERO);
+        let server_subscriptions = self
+            .subscriptions
+            .values()
+            .filter(|s| !s.is_sampled())
+            .count();
 
         self.publish_limits_watch_tx.send_modify(|limits| {
-            limits.update_subscriptions(self.subscriptions.len(), publish_interval);
+            limits.update_subscriptions(server_subscriptions, publish_interval);
         });
     }
 }
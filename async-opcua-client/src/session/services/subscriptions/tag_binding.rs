@@ -0,0 +1,188 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use opcua_types::{
+    DataValue, Error, MonitoredItemCreateRequest, MonitoringMode, MonitoringParameters, NodeId,
+    ReadValueId, StatusCode, TimestampsToReturn,
+};
+use tokio::sync::watch;
+
+use crate::{
+    session::{session_error, session_warn},
+    DataChangeCallback, Session,
+};
+
+/// Trait for structs with fields bound to the values of nodes on the server, kept up to date
+/// by a subscription created with [`Session::subscribe_tags`].
+///
+/// This is typically derived with `#[derive(TagBinding)]`, setting the node ID of each field
+/// with `#[opcua(node_id = "...")]`.
+pub trait TagBinding: Default + Send + Sync + 'static {
+    /// The node IDs bound to the struct, as strings such as `ns=2;s=Tag1`,
+    /// or `nsu=urn:my:namespace;s=Tag1` to look up the namespace index on the server.
+    fn node_ids() -> Vec<&'static str>;
+
+    /// Set the field bound to the node at `index` in [`TagBinding::node_ids`]
+    /// from a value received from the server.
+    fn set_value(&mut self, index: usize, value: DataValue) -> Result<(), Error>;
+}
+
+/// A subscription keeping an instance of `T` updated with the values of the nodes
+/// bound to its fields, created with [`Session::subscribe_tags`].
+///
+/// The subscription is not deleted when this is dropped, use
+/// [`Session::delete_subscription`] with [`TagSubscription::subscription_id`].
+pub struct TagSubscription<T> {
+    subscription_id: u32,
+    values: watch::Receiver<T>,
+}
+
+impl<T: TagBinding> TagSubscription<T> {
+    /// The ID of the subscription on the server.
+    pub fn subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+
+    /// Get a reference to the current values. Holding this reference blocks
+    /// updates from the subscription, so it should be dropped quickly.
+    pub fn current(&self) -> watch::Ref<'_, T> {
+        self.values.borrow()
+    }
+
+    /// Get a receiver for the values, which can be used to wait for changes
+    /// from a different task.
+    pub fn receiver(&self) -> watch::Receiver<T> {
+        self.values.clone()
+    }
+
+    /// Wait until the values change, then return a snapshot of the current values.
+    /// Changes made while no one is waiting are not lost, the next call returns immediately.
+    ///
+    /// Returns an error if the subscription is deleted, or the session is dropped.
+    pub async fn changed(&mut self) -> Result<T, StatusCode>
+    where
+        T: Clone,
+    {
+        self.values
+            .changed()
+            .await
+            .map_err(|_| StatusCode::BadSessionClosed)?;
+        Ok(self.values.borrow_and_update().clone())
+    }
+}
+
+impl Session {
+    /// Create a subscription monitoring the nodes bound to the fields of `T`, keeping
+    /// an instance of `T` updated with their values. See [`TagBinding`].
+    ///
+    /// If a monitored item could not be created, for example because a node does not
+    /// exist, the subscription is deleted again and the status of the item is returned.
+    /// Values that cannot be converted to the type of their field are logged and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `publishing_interval` - The requested publishing interval, also used as the sampling interval.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TagSubscription<T>)` - A handle to the subscription and the current values.
+    /// * `Err(StatusCode)` - A node ID is invalid, or the subscription or monitored items could
+    ///   not be created. [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn subscribe_tags<T: TagBinding>(
+        &self,
+        publishing_interval: Duration,
+    ) -> Result<TagSubscription<T>, StatusCode> {
+        let mut node_ids = Vec::new();
+        for node_id in T::node_ids() {
+            node_ids.push(self.resolve_tag_node_id(node_id).await?);
+        }
+        let mut fields: HashMap<NodeId, Vec<usize>> = HashMap::new();
+        for (idx, node_id) in node_ids.iter().enumerate() {
+            fields.entry(node_id.clone()).or_default().push(idx);
+        }
+
+        let (tx, rx) = watch::channel(T::default());
+        let callback = DataChangeCallback::new(move |value, item| {
+            let Some(indices) = fields.get(&item.item_to_monitor().node_id) else {
+                return;
+            };
+            tx.send_modify(|values| {
+                for idx in indices {
+                    if let Err(e) = values.set_value(*idx, value.clone()) {
+                        tracing::warn!(
+                            "Failed to set value of {}: {}",
+                            item.item_to_monitor().node_id,
+                            e
+                        );
+                    }
+                }
+            });
+        });
+
+        let subscription_id = self
+            .create_subscription(publishing_interval, 30, 10, 0, 0, true, callback)
+            .await?;
+
+        let items = node_ids
+            .iter()
+            .map(|node_id| {
+                MonitoredItemCreateRequest::new(
+                    ReadValueId::new_value(node_id.clone()),
+                    MonitoringMode::Reporting,
+                    MonitoringParameters {
+                        sampling_interval: publishing_interval.as_secs_f64() * 1000.0,
+                        queue_size: 1,
+                        discard_oldest: true,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let status = match self
+            .create_monitored_items(subscription_id, TimestampsToReturn::Both, items)
+            .await
+        {
+            Ok(results) => results
+                .iter()
+                .zip(&node_ids)
+                .find(|(r, _)| r.result.status_code.is_bad())
+                .map(|(r, node_id)| {
+                    session_error!(
+                        self,
+                        "Failed to monitor tag {}: {}",
+                        node_id,
+                        r.result.status_code
+                    );
+                    r.result.status_code
+                }),
+            Err(e) => Some(e),
+        };
+        if let Some(status) = status {
+            if let Err(e) = self.delete_subscription(subscription_id).await {
+                session_warn!(self, "Failed to delete subscription: {}", e);
+            }
+            return Err(status);
+        }
+
+        Ok(TagSubscription {
+            subscription_id,
+            values: rx,
+        })
+    }
+
+    /// Parse a node ID bound to a tag, looking up the namespace index
+    /// on the server if it is given as a namespace URI.
+    async fn resolve_tag_node_id(&self, node_id: &str) -> Result<NodeId, StatusCode> {
+        let Some(rest) = node_id.strip_prefix("nsu=") else {
+            return NodeId::from_str(node_id).map_err(|_| {
+                session_error!(self, "Invalid tag node ID {}", node_id);
+                StatusCode::BadNodeIdInvalid
+            });
+        };
+        let Some((uri, identifier)) = rest.split_once(';') else {
+            session_error!(self, "Invalid tag node ID {}", node_id);
+            return Err(StatusCode::BadNodeIdInvalid);
+        };
+        self.resolve(uri, identifier).await
+    }
+}
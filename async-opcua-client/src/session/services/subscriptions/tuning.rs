@@ -0,0 +1,311 @@
+use std::{collections::HashMap, time::Duration};
+
+use opcua_core::trace_lock;
+use opcua_types::{
+    ExtensionObject, MonitoredItemModifyRequest, MonitoringParameters, StatusCode,
+    TimestampsToReturn,
+};
+
+use crate::{
+    session::{session_debug, session_warn},
+    MonitoredItem, Session,
+};
+
+use super::MonitoredItemHandle;
+
+/// Changes to the parameters of a monitored item, for
+/// [`Session::modify_monitored_items_by_handle`]. Parameters that are not set keep
+/// their current value.
+#[derive(Debug, Clone, Default)]
+pub struct MonitoredItemChange {
+    sampling_interval: Option<f64>,
+    queue_size: Option<u32>,
+    discard_oldest: Option<bool>,
+    filter: Option<ExtensionObject>,
+}
+
+impl MonitoredItemChange {
+    /// Create a change that keeps all parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the requested sampling interval in milliseconds. The server may revise it.
+    pub fn sampling_interval(mut self, sampling_interval: f64) -> Self {
+        self.sampling_interval = Some(sampling_interval);
+        self
+    }
+
+    /// Set the requested queue size. The server may revise it.
+    pub fn queue_size(mut self, queue_size: u32) -> Self {
+        self.queue_size = Some(queue_size);
+        self
+    }
+
+    /// Set whether the oldest values are discarded on queue overflow on the server.
+    pub fn discard_oldest(mut self, discard_oldest: bool) -> Self {
+        self.discard_oldest = Some(discard_oldest);
+        self
+    }
+
+    /// Set the filter, for example a `DataChangeFilter` or an `EventFilter`. Use
+    /// [`ExtensionObject::null`] to remove the filter of the item.
+    pub fn filter(mut self, filter: ExtensionObject) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    fn modify_request(&self, item: &MonitoredItem) -> MonitoredItemModifyRequest {
+        MonitoredItemModifyRequest {
+            monitored_item_id: item.id(),
+            requested_parameters: MonitoringParameters {
+                client_handle: item.client_handle(),
+                sampling_interval: self
+                    .sampling_interval
+                    .unwrap_or_else(|| item.sampling_interval()),
+                filter: self.filter.clone().unwrap_or_else(|| item.filter().clone()),
+                queue_size: self.queue_size.unwrap_or(item.queue_size() as u32),
+                discard_oldest: self.discard_oldest.unwrap_or(item.discard_oldest()),
+            },
+        }
+    }
+}
+
+/// Changes to the parameters of a subscription, for [`Session::tune_subscriptions`].
+/// Parameters that are not set keep their current value.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionChange {
+    publishing_interval: Option<Duration>,
+    lifetime_count: Option<u32>,
+    max_keep_alive_count: Option<u32>,
+    max_notifications_per_publish: Option<u32>,
+    priority: Option<u8>,
+}
+
+impl SubscriptionChange {
+    /// Create a change that keeps all parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the requested publishing interval. The server may revise it.
+    pub fn publishing_interval(mut self, publishing_interval: Duration) -> Self {
+        self.publishing_interval = Some(publishing_interval);
+        self
+    }
+
+    /// Set the requested lifetime count. The server may revise it.
+    pub fn lifetime_count(mut self, lifetime_count: u32) -> Self {
+        self.lifetime_count = Some(lifetime_count);
+        self
+    }
+
+    /// Set the requested maximum keep-alive count. The server may revise it.
+    ///
+    /// If the lifetime count is not set, and the current lifetime count is less than
+    /// three times the new keep-alive count, the lifetime count is raised to that.
+    pub fn max_keep_alive_count(mut self, max_keep_alive_count: u32) -> Self {
+        self.max_keep_alive_count = Some(max_keep_alive_count);
+        self
+    }
+
+    /// Set the maximum number of notifications per publish response, 0 means no limit.
+    pub fn max_notifications_per_publish(mut self, max_notifications_per_publish: u32) -> Self {
+        self.max_notifications_per_publish = Some(max_notifications_per_publish);
+        self
+    }
+
+    /// Set the relative priority of the subscription.
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+}
+
+impl Session {
+    /// Modify the sampling interval, queue size, discard policy or filter of many
+    /// monitored items at once, identified by their handles. The items may belong to
+    /// different subscriptions, a [`ModifyMonitoredItemsRequest`](opcua_types::ModifyMonitoredItemsRequest)
+    /// is sent for each subscription.
+    ///
+    /// The client keeps the values revised by the server for each item that was modified,
+    /// items the server failed to modify keep their previous parameters. If the request
+    /// for a subscription fails, each of its items gets the status of the failed request.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamps_to_return` - An enumeration that specifies the timestamp Attributes to be transmitted for each MonitoredItem.
+    /// * `items_to_modify` - Handles of the monitored items, with the changes to apply to each.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Result<MonitoredItem, StatusCode>>` - The item with its revised parameters, or the
+    ///   status it could not be modified with, in the order of `items_to_modify`.
+    ///
+    pub async fn modify_monitored_items_by_handle(
+        &self,
+        timestamps_to_return: TimestampsToReturn,
+        items_to_modify: &[(MonitoredItemHandle, MonitoredItemChange)],
+    ) -> Vec<Result<MonitoredItem, StatusCode>> {
+        let mut results = vec![Err(StatusCode::BadMonitoredItemIdInvalid); items_to_modify.len()];
+        // Requests per subscription, with the index of each item in `items_to_modify`.
+        let mut requests: HashMap<u32, (Vec<usize>, Vec<MonitoredItemModifyRequest>)> =
+            HashMap::new();
+        {
+            let state = trace_lock!(self.subscription_state);
+            for (idx, (handle, change)) in items_to_modify.iter().enumerate() {
+                let item = state.subscription_for_item(*handle).and_then(|id| {
+                    let subscription = state.get(id)?;
+                    let item_id = subscription.client_handles.get(&handle.client_handle())?;
+                    Some((id, subscription.monitored_items.get(item_id)?))
+                });
+                let Some((subscription_id, item)) = item else {
+                    session_warn!(
+                        self,
+                        "Monitored item with client handle {} does not exist",
+                        handle.client_handle()
+                    );
+                    continue;
+                };
+                let entry = requests.entry(subscription_id).or_default();
+                entry.0.push(idx);
+                entry.1.push(change.modify_request(item));
+            }
+        }
+
+        for (subscription_id, (indices, requests)) in requests {
+            session_debug!(
+                self,
+                "Modifying {} monitored items on subscription {}",
+                requests.len(),
+                subscription_id
+            );
+            let modified = match self
+                .modify_monitored_items(subscription_id, timestamps_to_return, &requests)
+                .await
+            {
+                Ok(modified) => modified,
+                Err(e) => {
+                    session_warn!(
+                        self,
+                        "Failed to modify monitored items on subscription {}: {}",
+                        subscription_id,
+                        e
+                    );
+                    for idx in indices {
+                        results[idx] = Err(e);
+                    }
+                    continue;
+                }
+            };
+            for (idx, r) in indices.into_iter().zip(modified) {
+                results[idx] = if r.status_code.is_bad() {
+                    Err(r.status_code)
+                } else {
+                    self.find_monitored_item(items_to_modify[idx].0)
+                        .map(|(_, item)| item)
+                        .ok_or(StatusCode::BadMonitoredItemIdInvalid)
+                };
+            }
+        }
+        results
+    }
+
+    /// Apply the same change to many monitored items at once. See
+    /// [`Session::modify_monitored_items_by_handle`].
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamps_to_return` - An enumeration that specifies the timestamp Attributes to be transmitted for each MonitoredItem.
+    /// * `items` - Handles of the monitored items.
+    /// * `change` - The change to apply to every item.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Result<MonitoredItem, StatusCode>>` - The item with its revised parameters, or the
+    ///   status it could not be modified with, in the order of `items`.
+    ///
+    pub async fn modify_all_monitored_items(
+        &self,
+        timestamps_to_return: TimestampsToReturn,
+        items: &[MonitoredItemHandle],
+        change: MonitoredItemChange,
+    ) -> Vec<Result<MonitoredItem, StatusCode>> {
+        let items_to_modify = items
+            .iter()
+            .map(|h| (*h, change.clone()))
+            .collect::<Vec<_>>();
+        self.modify_monitored_items_by_handle(timestamps_to_return, &items_to_modify)
+            .await
+    }
+
+    /// Change the publishing interval, lifetime, keep-alive count, notification limit or
+    /// priority of live subscriptions, with a [`ModifySubscriptionRequest`](opcua_types::ModifySubscriptionRequest)
+    /// for each subscription. The client keeps the values revised by the server, and
+    /// adjusts the keep-alive timeout of the session to them.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscription_ids` - The subscriptions to change.
+    /// * `change` - The change to apply to every subscription.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<StatusCode>` - The result for each subscription, in the order of `subscription_ids`,
+    ///   `Good` or the status it could not be modified with.
+    ///
+    pub async fn tune_subscriptions(
+        &self,
+        subscription_ids: &[u32],
+        change: &SubscriptionChange,
+    ) -> Vec<StatusCode> {
+        let mut results = Vec::with_capacity(subscription_ids.len());
+        for subscription_id in subscription_ids {
+            let current = {
+                let state = trace_lock!(self.subscription_state);
+                state.get(*subscription_id).map(|s| {
+                    (
+                        s.publishing_interval(),
+                        s.lifetime_count(),
+                        s.max_keep_alive_count(),
+                        s.max_notifications_per_publish(),
+                        s.priority(),
+                    )
+                })
+            };
+            let Some((interval, lifetime, keep_alive, max_notifications, priority)) = current
+            else {
+                results.push(StatusCode::BadSubscriptionIdInvalid);
+                continue;
+            };
+            let keep_alive = change.max_keep_alive_count.unwrap_or(keep_alive);
+            let lifetime = change
+                .lifetime_count
+                .unwrap_or_else(|| lifetime.max(keep_alive.saturating_mul(3)));
+            let result = self
+                .modify_subscription(
+                    *subscription_id,
+                    change.publishing_interval.unwrap_or(interval),
+                    lifetime,
+                    keep_alive,
+                    change
+                        .max_notifications_per_publish
+                        .unwrap_or(max_notifications),
+                    change.priority.unwrap_or(priority),
+                )
+                .await;
+            if let Err(e) = result {
+                session_warn!(
+                    self,
+                    "Failed to modify subscription {}: {}",
+                    subscription_id,
+                    e
+                );
+                results.push(e);
+            } else {
+                results.push(StatusCode::Good);
+            }
+        }
+        results
+    }
+}
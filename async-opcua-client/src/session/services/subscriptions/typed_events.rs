@@ -0,0 +1,177 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
+use opcua_types::{
+    event_field::{event_from_fields, event_select_clauses, SelectEventField, SetEventField},
+    AttributeId, ContentFilter, EventFilter, ExtensionObject, MonitoredItemCreateRequest,
+    MonitoringMode, MonitoringParameters, NodeId, ReadValueId, StatusCode, TimestampsToReturn,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    session::{session_error, session_warn},
+    EventCallback, Session,
+};
+
+/// A subscription to events of type `T` on a single node, created with
+/// [`Session::subscribe_events`]. Received events are delivered as a [`Stream`].
+///
+/// The subscription is not deleted when this is dropped, use
+/// [`Session::delete_subscription`] with [`EventSubscription::subscription_id`].
+/// The stream ends when the subscription is deleted.
+pub struct EventSubscription<T> {
+    subscription_id: u32,
+    monitored_item_id: u32,
+    events: mpsc::UnboundedReceiver<T>,
+}
+
+impl<T> EventSubscription<T> {
+    /// The ID of the subscription on the server.
+    pub fn subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+
+    /// The ID of the monitored item on the server.
+    pub fn monitored_item_id(&self) -> u32 {
+        self.monitored_item_id
+    }
+}
+
+impl<T> Stream for EventSubscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Session {
+    /// Create a subscription to events of type `T` on `node_id`, decoding each received
+    /// event into an instance of `T`.
+    ///
+    /// `T` is typically a type deriving `Event` or `EventField`, and `Default`. The select
+    /// clauses of the event filter are created from the fields of `T`, see
+    /// [`SelectEventField`]. Events that cannot be converted to `T` are logged and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_id` - The node to receive events from, typically the `Server` object or an
+    ///   object with the `SubscribeToEvents` bit set in its `EventNotifier` attribute.
+    /// * `publishing_interval` - The requested publishing interval.
+    /// * `where_clause` - Filter for which events to receive, for example on the event type.
+    ///   Use an empty [`ContentFilter`] to receive all events.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(EventSubscription<T>)` - A stream of events received on the subscription.
+    /// * `Err(StatusCode)` - The subscription or monitored item could not be created.
+    ///   [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn subscribe_events<T>(
+        &self,
+        node_id: &NodeId,
+        publishing_interval: Duration,
+        where_clause: ContentFilter,
+    ) -> Result<EventSubscription<T>, StatusCode>
+    where
+        T: SelectEventField + SetEventField + Default + Send + 'static,
+    {
+        let select_clauses = event_select_clauses::<T>(self.encoding_context().read().namespaces());
+        let filter = EventFilter {
+            select_clauses: Some(select_clauses.clone()),
+            where_clause,
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let callback = EventCallback::new(move |fields, item| {
+            let Some(fields) = fields else {
+                return;
+            };
+            match event_from_fields::<T>(&select_clauses, fields) {
+                Ok(event) => {
+                    let _ = tx.send(event);
+                }
+                Err(e) => tracing::warn!(
+                    "Failed to convert event from {}: {}",
+                    item.item_to_monitor().node_id,
+                    e
+                ),
+            }
+        });
+
+        let (subscription_id, monitored_item_id) = self
+            .create_event_subscription(node_id, publishing_interval, filter, callback)
+            .await?;
+        Ok(EventSubscription {
+            subscription_id,
+            monitored_item_id,
+            events: rx,
+        })
+    }
+
+    /// Create a subscription with a single monitored item for events on `node_id`,
+    /// deleting the subscription again if the monitored item could not be created.
+    /// Returns the subscription ID and the monitored item ID.
+    pub(super) async fn create_event_subscription(
+        &self,
+        node_id: &NodeId,
+        publishing_interval: Duration,
+        filter: EventFilter,
+        callback: EventCallback,
+    ) -> Result<(u32, u32), StatusCode> {
+        let subscription_id = self
+            .create_subscription(publishing_interval, 30, 10, 0, 0, true, callback)
+            .await?;
+
+        let item = MonitoredItemCreateRequest::new(
+            ReadValueId {
+                node_id: node_id.clone(),
+                attribute_id: AttributeId::EventNotifier as u32,
+                ..Default::default()
+            },
+            MonitoringMode::Reporting,
+            MonitoringParameters {
+                sampling_interval: 0.0,
+                queue_size: 100,
+                discard_oldest: true,
+                filter: ExtensionObject::from_message(filter),
+                ..Default::default()
+            },
+        );
+        let result = match self
+            .create_monitored_items(subscription_id, TimestampsToReturn::Neither, vec![item])
+            .await
+        {
+            Ok(mut results) if !results.is_empty() => {
+                let result = results.remove(0).result;
+                if result.status_code.is_bad() {
+                    session_error!(
+                        self,
+                        "Failed to monitor events on {}: {}",
+                        node_id,
+                        result.status_code
+                    );
+                    Err(result.status_code)
+                } else {
+                    Ok(result.monitored_item_id)
+                }
+            }
+            Ok(_) => Err(StatusCode::BadUnexpectedError),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(monitored_item_id) => Ok((subscription_id, monitored_item_id)),
+            Err(status) => {
+                if let Err(e) = self.delete_subscription(subscription_id).await {
+                    session_warn!(self, "Failed to delete subscription: {}", e);
+                }
+                Err(status)
+            }
+        }
+    }
+}
@@ -1,19 +1,21 @@
-use std::time::Duration;
+use std::{future::Future, time::Duration};
 
 use crate::{
     session::{
         process_service_result, process_unexpected_response,
         request_builder::{builder_base, builder_debug, builder_error, RequestHeaderBuilder},
+        session_error, session_warn,
     },
     Session, UARequest,
 };
+use futures::{Stream, TryStreamExt};
 use opcua_core::ResponseMessage;
 use opcua_types::{
     BrowseDescription, BrowseNextRequest, BrowseNextResponse, BrowsePath, BrowsePathResult,
     BrowseRequest, BrowseResponse, BrowseResult, ByteString, IntegerId, NodeId,
-    RegisterNodesRequest, RegisterNodesResponse, StatusCode, TranslateBrowsePathsToNodeIdsRequest,
-    TranslateBrowsePathsToNodeIdsResponse, UnregisterNodesRequest, UnregisterNodesResponse,
-    ViewDescription,
+    RegisterNodesRequest, RegisterNodesResponse, RelativePath, StatusCode,
+    TranslateBrowsePathsToNodeIdsRequest, TranslateBrowsePathsToNodeIdsResponse,
+    UnregisterNodesRequest, UnregisterNodesResponse, ViewDescription,
 };
 
 #[derive(Debug, Clone)]
@@ -462,14 +464,19 @@ impl Session {
         max_references_per_node: u32,
         view: Option<ViewDescription>,
     ) -> Result<Vec<BrowseResult>, StatusCode> {
-        Ok(Browse::new(self)
-            .nodes_to_browse(nodes_to_browse.to_vec())
-            .view(view.unwrap_or_default())
-            .max_references_per_node(max_references_per_node)
-            .send(&self.channel)
-            .await?
-            .results
-            .unwrap_or_default())
+        let limit = self.operation_limits().max_nodes_per_browse;
+        let view = &view.unwrap_or_default();
+        self.send_in_chunks(nodes_to_browse.to_vec(), limit, |chunk| async move {
+            Ok(Browse::new(self)
+                .nodes_to_browse(chunk)
+                .view(view.clone())
+                .max_references_per_node(max_references_per_node)
+                .send(&self.channel)
+                .await?
+                .results
+                .unwrap_or_default())
+        })
+        .await
     }
 
     /// Continue to discover references to nodes by sending continuation points in a [`BrowseNextRequest`]
@@ -502,6 +509,207 @@ impl Session {
             .unwrap_or_default())
     }
 
+    /// Browse the specified nodes, following continuation points until every reference
+    /// has been received.
+    ///
+    /// If a request fails, any outstanding continuation points are released before the
+    /// error is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes_to_browse` - A list of [`BrowseDescription`] describing nodes to browse.
+    /// * `max_references_per_node` - Maximum number of references returned in each request,
+    ///   `0` lets the server decide.
+    /// * `view` - Optional view to browse.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<BrowseResult>)` - A list [`BrowseResult`] corresponding to each node to browse, containing
+    ///   all references of the node. None of the results contain a continuation point.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn browse_all(
+        &self,
+        nodes_to_browse: &[BrowseDescription],
+        max_references_per_node: u32,
+        view: Option<ViewDescription>,
+    ) -> Result<Vec<BrowseResult>, StatusCode> {
+        collect_browse_batches(
+            nodes_to_browse.len(),
+            self.browse_all_stream(nodes_to_browse, max_references_per_node, view),
+        )
+        .await
+    }
+
+    /// Follow a list of continuation points returned from `browse()` or `browse_next()`
+    /// until every reference has been received.
+    ///
+    /// If a request fails, any outstanding continuation points are released before the
+    /// error is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `continuation_points` - A list of continuation points.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<BrowseResult>)` - A list [`BrowseResult`] corresponding to each continuation point, containing
+    ///   the remaining references. None of the results contain a continuation point.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn browse_next_all(
+        &self,
+        continuation_points: &[ByteString],
+    ) -> Result<Vec<BrowseResult>, StatusCode> {
+        let pending = continuation_points.iter().cloned().enumerate().collect();
+        collect_browse_batches(
+            continuation_points.len(),
+            self.browse_batches(self.browse_next_pending(pending)),
+        )
+        .await
+    }
+
+    /// Browse the specified nodes, following continuation points until every reference
+    /// has been received, returning a stream of the result of each request.
+    ///
+    /// Each batch contains the results for the nodes that had more references,
+    /// paired with the index of the node in `nodes_to_browse`. Continuation points
+    /// are removed from the results, they are followed by the stream.
+    ///
+    /// If a request fails, any outstanding continuation points are released, and the
+    /// stream ends after returning the error. If the stream is dropped before it ends,
+    /// the server keeps the continuation points until the session is closed, or until it
+    /// needs them for other requests. Use [`BrowseResultStream`](crate::BrowseResultStream)
+    /// to release them when the stream is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes_to_browse` - A list of [`BrowseDescription`] describing nodes to browse.
+    /// * `max_references_per_node` - Maximum number of references returned in each request,
+    ///   `0` lets the server decide.
+    /// * `view` - Optional view to browse.
+    ///
+    pub fn browse_all_stream<'a>(
+        &'a self,
+        nodes_to_browse: &'a [BrowseDescription],
+        max_references_per_node: u32,
+        view: Option<ViewDescription>,
+    ) -> impl Stream<Item = Result<Vec<(usize, BrowseResult)>, StatusCode>> + 'a {
+        self.browse_batches(self.browse_first_page(nodes_to_browse, max_references_per_node, view))
+    }
+
+    /// Browse the specified nodes, pairing each result with the index of its node in
+    /// `nodes_to_browse`. Releases any continuation points if the server returns the
+    /// wrong number of results.
+    pub(super) async fn browse_first_page(
+        &self,
+        nodes_to_browse: &[BrowseDescription],
+        max_references_per_node: u32,
+        view: Option<ViewDescription>,
+    ) -> Result<Vec<(usize, BrowseResult)>, StatusCode> {
+        let results = self
+            .browse(nodes_to_browse, max_references_per_node, view)
+            .await?;
+        if results.len() != nodes_to_browse.len() {
+            session_error!(
+                self,
+                "Expected {} browse results but got {}",
+                nodes_to_browse.len(),
+                results.len()
+            );
+            let continuation_points = results
+                .into_iter()
+                .map(|r| r.continuation_point)
+                .filter(|c| !c.is_null())
+                .collect::<Vec<_>>();
+            self.release_continuation_points(&continuation_points).await;
+            return Err(StatusCode::BadUnexpectedError);
+        }
+        Ok(results.into_iter().enumerate().collect())
+    }
+
+    /// Stream of browse results, starting with the results of `first`, then calling
+    /// `BrowseNext` for the continuation points in each batch until there are none left.
+    fn browse_batches<'a>(
+        &'a self,
+        first: impl Future<Output = Result<Vec<(usize, BrowseResult)>, StatusCode>> + 'a,
+    ) -> impl Stream<Item = Result<Vec<(usize, BrowseResult)>, StatusCode>> + 'a {
+        enum State<F> {
+            First(F),
+            Next(Vec<(usize, ByteString)>),
+            Done,
+        }
+
+        futures::stream::unfold(State::First(first), move |state| async move {
+            let results = match state {
+                State::First(first) => first.await,
+                State::Next(pending) => self.browse_next_pending(pending).await,
+                State::Done => return None,
+            };
+            let mut results = match results {
+                Ok(results) => results,
+                Err(e) => return Some((Err(e), State::Done)),
+            };
+
+            let pending = results
+                .iter_mut()
+                .filter(|(_, r)| !r.continuation_point.is_null())
+                .map(|(idx, r)| (*idx, std::mem::take(&mut r.continuation_point)))
+                .collect::<Vec<_>>();
+            let next = if pending.is_empty() {
+                State::Done
+            } else {
+                State::Next(pending)
+            };
+            Some((Ok(results), next))
+        })
+    }
+
+    /// Call `BrowseNext` with a list of continuation points, each paired with the index
+    /// of the node it belongs to. Releases the continuation points on failure.
+    pub(super) async fn browse_next_pending(
+        &self,
+        pending: Vec<(usize, ByteString)>,
+    ) -> Result<Vec<(usize, BrowseResult)>, StatusCode> {
+        let continuation_points = pending.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>();
+        match self.browse_next(false, &continuation_points).await {
+            Ok(results) if results.len() == pending.len() => Ok(pending
+                .into_iter()
+                .map(|(idx, _)| idx)
+                .zip(results)
+                .collect()),
+            Ok(results) => {
+                session_error!(
+                    self,
+                    "Expected {} browse next results but got {}",
+                    pending.len(),
+                    results.len()
+                );
+                let continuation_points = results
+                    .into_iter()
+                    .map(|r| r.continuation_point)
+                    .filter(|c| !c.is_null())
+                    .collect::<Vec<_>>();
+                self.release_continuation_points(&continuation_points).await;
+                Err(StatusCode::BadUnexpectedError)
+            }
+            Err(e) => {
+                self.release_continuation_points(&continuation_points).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Try to release a list of continuation points, ignoring any errors.
+    pub(super) async fn release_continuation_points(&self, continuation_points: &[ByteString]) {
+        if continuation_points.is_empty() {
+            return;
+        }
+        if let Err(e) = self.browse_next(true, continuation_points).await {
+            session_warn!(self, "Failed to release continuation points: {}", e);
+        }
+    }
+
     /// Translate browse paths to NodeIds by sending a [`TranslateBrowsePathsToNodeIdsRequest`] request to the Server
     /// Each [`BrowsePath`] is constructed of a starting node and a `RelativePath`. The specified starting node
     /// identifies the node from which the RelativePath is based. The RelativePath contains a sequence of
@@ -532,6 +740,97 @@ impl Session {
             .unwrap_or_default())
     }
 
+    /// Resolve a path from a starting node to a node ID, using TranslateBrowsePathsToNodeIds.
+    /// The path is given in the string format from OPC UA Part 4, Annex A, for example
+    /// `/2:Block.2:Output`. Use [`Session::translate_paths`] to resolve many paths at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_node` - The node to start from.
+    /// * `path` - Relative path from the starting node.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NodeId)` - The node at the end of the path. If there are several, the first one is returned.
+    /// * `Err(StatusCode)` - The path is invalid, the request failed, or the path could not be resolved.
+    ///
+    pub async fn translate_path(
+        &self,
+        start_node: impl Into<NodeId>,
+        path: &str,
+    ) -> Result<NodeId, StatusCode> {
+        let relative_path = RelativePath::try_from(path).map_err(|e| {
+            session_error!(self, "Invalid relative path {}: {}", path, e);
+            StatusCode::BadBrowseNameInvalid
+        })?;
+        self.translate_paths(&[(start_node.into(), relative_path)])
+            .await?
+            .pop()
+            .unwrap_or(Err(StatusCode::BadUnexpectedError))
+    }
+
+    /// Resolve a list of paths from starting nodes to node IDs, using TranslateBrowsePathsToNodeIds.
+    /// Paths can be parsed from strings with `RelativePath::try_from`, or built with
+    /// [`RelativePath::builder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - A list of starting nodes and relative paths from them.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<Result<NodeId, StatusCode>>)` - The result for each path, in the same order as `paths`.
+    ///   If a path resolves to several nodes, the first one is returned. Paths that the server could not
+    ///   resolve, or that end in a node on a different server, have an error.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn translate_paths(
+        &self,
+        paths: &[(NodeId, RelativePath)],
+    ) -> Result<Vec<Result<NodeId, StatusCode>>, StatusCode> {
+        let browse_paths = paths
+            .iter()
+            .map(|(starting_node, relative_path)| BrowsePath {
+                starting_node: starting_node.clone(),
+                relative_path: relative_path.clone(),
+            })
+            .collect::<Vec<_>>();
+        let results = self
+            .translate_browse_paths_to_node_ids(&browse_paths)
+            .await?;
+        if results.len() != paths.len() {
+            session_error!(
+                self,
+                "Expected {} browse path results but got {}",
+                paths.len(),
+                results.len()
+            );
+            return Err(StatusCode::BadUnexpectedError);
+        }
+
+        let ctx = self.encoding_context().read();
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                if result.status_code.is_bad() {
+                    return Err(result.status_code);
+                }
+                result
+                    .targets
+                    .into_iter()
+                    .flatten()
+                    // Targets with a remaining path index are on another server.
+                    .find(|t| t.remaining_path_index == u32::MAX)
+                    .and_then(|t| {
+                        t.target_id
+                            .try_resolve(ctx.namespaces())
+                            .map(|id| id.into_owned())
+                    })
+                    .ok_or(StatusCode::BadNoMatch)
+            })
+            .collect())
+    }
+
     /// Register nodes on the server by sending a [`RegisterNodesRequest`]. The purpose of this
     /// call is server-dependent but allows a client to ask a server to create nodes which are
     /// otherwise expensive to set up or maintain, e.g. nodes attached to hardware.
@@ -583,3 +882,25 @@ impl Session {
         Ok(())
     }
 }
+
+/// Merge a stream of browse batches into one result per node.
+async fn collect_browse_batches(
+    len: usize,
+    batches: impl Stream<Item = Result<Vec<(usize, BrowseResult)>, StatusCode>>,
+) -> Result<Vec<BrowseResult>, StatusCode> {
+    let mut results = vec![BrowseResult::default(); len];
+    futures::pin_mut!(batches);
+    while let Some(batch) = batches.try_next().await? {
+        for (idx, result) in batch {
+            let target = &mut results[idx];
+            target.status_code = result.status_code;
+            if let Some(references) = result.references {
+                target
+                    .references
+                    .get_or_insert_with(Vec::new)
+                    .extend(references);
+            }
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use opcua_types::{
+    CallMethodRequest, CallMethodResult, DataValue, EndpointDescription, Error, NodeId,
+    ReadValueId, StatusCode, TimestampsToReturn, UAString, WriteValue,
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    services::{Call, Read, Write},
+    transport::{ConnectorBuilder, TransportPollResult},
+    AsyncSecureChannel, IdentityToken, UARequest,
+};
+
+use super::{Client, EndpointInfo};
+
+/// A secure channel used to call services without creating a session, as described in
+/// OPC UA Part 4 - Services 5.4 for session-less service invocation. Created with
+/// [`Client::connect_sessionless`].
+///
+/// Each request carries an access token in the `authenticationToken` of its request header
+/// instead of a session authentication token, which avoids the cost of creating and activating
+/// a session for short-lived, stateless access. Only servers that support session-less
+/// invocation accept these requests, other servers reject them with `BadSessionIdInvalid`.
+///
+/// The channel is not reconnected if the connection is lost, create a new channel instead.
+pub struct SessionlessChannel {
+    channel: AsyncSecureChannel,
+    event_loop: Option<JoinHandle<StatusCode>>,
+    auth_token: NodeId,
+    request_timeout: Duration,
+}
+
+impl SessionlessChannel {
+    /// The authentication token sent in the header of every request on this channel.
+    pub fn auth_token(&self) -> &NodeId {
+        &self.auth_token
+    }
+
+    /// Get the underlying secure channel, for sending other requests built with `new_manual`
+    /// and [`SessionlessChannel::auth_token`].
+    pub fn channel(&self) -> &AsyncSecureChannel {
+        &self.channel
+    }
+
+    /// Reads the value of nodes by sending a [`ReadRequest`](opcua_types::ReadRequest)
+    /// to the server, outside of a session.
+    ///
+    /// See OPC UA Part 4 - Services 5.10.2 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes_to_read` - A list of [`ReadValueId`] to be read by the server.
+    /// * `timestamps_to_return` - The [`TimestampsToReturn`] for each node, Both, Server, Source or None
+    /// * `max_age` - The maximum age of value to read in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<DataValue>)` - A list of [`DataValue`] corresponding to each read operation.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn read(
+        &self,
+        nodes_to_read: &[ReadValueId],
+        timestamps_to_return: TimestampsToReturn,
+        max_age: f64,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        Ok(Read::new_manual(
+            0,
+            self.request_timeout,
+            self.auth_token.clone(),
+            self.channel.request_handle(),
+        )
+        .nodes_to_read(nodes_to_read.to_vec())
+        .timestamps_to_return(timestamps_to_return)
+        .max_age(max_age)
+        .send(&self.channel)
+        .await?
+        .results
+        .unwrap_or_default())
+    }
+
+    /// Writes values to nodes by sending a [`WriteRequest`](opcua_types::WriteRequest)
+    /// to the server, outside of a session.
+    ///
+    /// See OPC UA Part 4 - Services 5.10.4 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes_to_write` - A list of [`WriteValue`] to be sent to the server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<StatusCode>)` - A list of [`StatusCode`] results corresponding to each write operation.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn write(
+        &self,
+        nodes_to_write: &[WriteValue],
+    ) -> Result<Vec<StatusCode>, StatusCode> {
+        Ok(Write::new_manual(
+            0,
+            self.request_timeout,
+            self.auth_token.clone(),
+            self.channel.request_handle(),
+        )
+        .nodes_to_write(nodes_to_write.to_vec())
+        .send(&self.channel)
+        .await?
+        .results
+        .unwrap_or_default())
+    }
+
+    /// Calls methods on the server by sending a [`CallRequest`](opcua_types::CallRequest)
+    /// to the server, outside of a session.
+    ///
+    /// See OPC UA Part 4 - Services 5.11.2 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `methods` - The methods to call.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<CallMethodResult>)` - A [`CallMethodResult`] for each method call.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn call(
+        &self,
+        methods: Vec<CallMethodRequest>,
+    ) -> Result<Vec<CallMethodResult>, StatusCode> {
+        Ok(Call::new_manual(
+            0,
+            self.request_timeout,
+            self.auth_token.clone(),
+            self.channel.request_handle(),
+        )
+        .methods_to_call(methods)
+        .send(&self.channel)
+        .await?
+        .results
+        .unwrap_or_default())
+    }
+
+    /// Close the secure channel, waiting for the connection to shut down.
+    pub async fn close(mut self) {
+        self.channel.close_channel().await;
+        if let Some(event_loop) = self.event_loop.take() {
+            let _ = event_loop.await;
+        }
+    }
+}
+
+impl Drop for SessionlessChannel {
+    fn drop(&mut self) {
+        if let Some(event_loop) = self.event_loop.take() {
+            event_loop.abort();
+        }
+    }
+}
+
+impl Client {
+    /// Open a secure channel for calling services without a session, see [`SessionlessChannel`].
+    ///
+    /// # Arguments
+    ///
+    /// * `connector` - Connector to the server. This is implemented for `String` and `&str`.
+    /// * `server_endpoint` - The endpoint to connect to, which determines the security of the
+    ///   channel. Get this from `get_server_endpoints_from_url` or `get_best_endpoint`.
+    /// * `access_token` - Access token sent with every request, typically issued by an
+    ///   authorization service. An empty token makes anonymous requests.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SessionlessChannel)` - The connected channel.
+    /// * `Err(Error)` - The channel could not be opened.
+    ///
+    pub async fn connect_sessionless(
+        &self,
+        connector: impl ConnectorBuilder,
+        server_endpoint: &EndpointDescription,
+        access_token: impl Into<UAString>,
+    ) -> Result<SessionlessChannel, Error> {
+        let endpoint_info = EndpointInfo {
+            endpoint: server_endpoint.clone(),
+            user_identity_token: IdentityToken::Anonymous,
+            preferred_locales: Vec::new(),
+        };
+        let connector = connector.build()?;
+        let channel = self.channel_from_endpoint_info(endpoint_info, connector);
+        let mut evt_loop = channel
+            .connect()
+            .await
+            .map_err(|e| Error::new(e, "Failed to connect to server"))?;
+        let event_loop = tokio::spawn(async move {
+            loop {
+                if let TransportPollResult::Closed(e) = evt_loop.poll().await {
+                    break e;
+                }
+            }
+        });
+
+        Ok(SessionlessChannel {
+            channel,
+            event_loop: Some(event_loop),
+            auth_token: sessionless_auth_token(access_token.into()),
+            request_timeout: self.config.request_timeout,
+        })
+    }
+}
+
+/// Session-less requests carry the access token as a string node ID in namespace 0,
+/// and anonymous requests a null node ID.
+fn sessionless_auth_token(access_token: UAString) -> NodeId {
+    if access_token.is_empty() {
+        NodeId::null()
+    } else {
+        NodeId::new(0, access_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{Identifier, NodeId, UAString};
+
+    use super::sessionless_auth_token;
+
+    #[test]
+    fn auth_token() {
+        assert!(sessionless_auth_token(UAString::null()).is_null());
+        assert!(sessionless_auth_token(UAString::from("")).is_null());
+        let token = sessionless_auth_token(UAString::from("abc.def.ghi"));
+        assert_eq!(token.namespace, 0);
+        assert_eq!(token.identifier, Identifier::String("abc.def.ghi".into()));
+        assert_ne!(token, NodeId::null());
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Weak},
+};
+
+use opcua_core::{sync::Mutex, trace_lock};
+use opcua_types::{custom::DiscoveredTypeLoader, Error, NodeId};
+
+use crate::{custom_types::DataTypeTreeBuilder, CustomTypeDiscovery};
+
+use super::{session_debug, session_warn, Session};
+
+/// Custom types of the server discovered by a session, see [`CustomTypeDiscovery`].
+pub(super) struct TypeDiscovery {
+    mode: CustomTypeDiscovery,
+    loader: Arc<DiscoveredTypeLoader>,
+    /// Encoding IDs of unknown types that discovery was started for, so that types the
+    /// server has no data type definition for are only looked for once.
+    unknown: Arc<Mutex<HashSet<NodeId>>>,
+    /// Held while discovering types, so that only one discovery runs at a time.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl TypeDiscovery {
+    pub(super) fn new(mode: CustomTypeDiscovery, session: Weak<Session>) -> Self {
+        let unknown = Arc::new(Mutex::new(HashSet::new()));
+        let mut loader = DiscoveredTypeLoader::new();
+        if mode == CustomTypeDiscovery::Lazy {
+            let unknown = unknown.clone();
+            loader = loader.on_unknown_type(move |id| {
+                if !trace_lock!(unknown).insert(id.clone()) {
+                    return;
+                }
+                let (Some(session), Ok(runtime)) =
+                    (session.upgrade(), tokio::runtime::Handle::try_current())
+                else {
+                    return;
+                };
+                let id = id.clone();
+                runtime.spawn(async move { session.discover_unknown_type(&id).await });
+            });
+        }
+        Self {
+            mode,
+            loader: Arc::new(loader),
+            unknown,
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub(super) fn loader(&self) -> Arc<DiscoveredTypeLoader> {
+        self.loader.clone()
+    }
+}
+
+impl Session {
+    /// Read the data type definitions of the server, and decode values of structures defined
+    /// by the server into [`DynamicStructure`](opcua_types::custom::DynamicStructure) from now on,
+    /// replacing any types discovered before. Values of other types are still decoded by the
+    /// type loaders added with [`Session::add_type_loader`], which take priority.
+    ///
+    /// This is done automatically if enabled with
+    /// [`ClientBuilder::custom_type_discovery`](crate::ClientBuilder::custom_type_discovery).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The data type definitions were read.
+    /// * `Err(Error)` - Reading the data type definitions failed, the types discovered before are kept.
+    ///
+    pub async fn discover_custom_types(&self) -> Result<(), Error> {
+        let _guard = self.type_discovery.lock.lock().await;
+        self.discover_custom_types_inner().await
+    }
+
+    async fn discover_custom_types_inner(&self) -> Result<(), Error> {
+        // The type tree must include the types the custom types depend on,
+        // including those in the base namespace.
+        let type_tree = DataTypeTreeBuilder::new(|_| true).build(self).await?;
+        session_debug!(
+            self,
+            "Discovered {} data types on the server",
+            type_tree.encoding_to_data_type().len()
+        );
+        self.type_discovery
+            .loader
+            .set_type_tree(Arc::new(type_tree));
+        Ok(())
+    }
+
+    async fn discover_unknown_type(&self, encoding_id: &NodeId) {
+        let _guard = self.type_discovery.lock.lock().await;
+        // The type may have been found by a discovery started for another type.
+        if let Some(type_tree) = self.type_discovery.loader.type_tree() {
+            if type_tree.encoding_to_data_type().contains_key(encoding_id)
+                || type_tree.get_struct_type(encoding_id).is_some()
+            {
+                return;
+            }
+        }
+        if let Err(e) = self.discover_custom_types_inner().await {
+            session_warn!(self, "Failed to discover custom types: {}", e);
+        }
+    }
+
+    /// Called when a new session is created on the server, which may have
+    /// restarted with other types.
+    pub(super) async fn on_new_server_session(&self) {
+        match self.type_discovery.mode {
+            CustomTypeDiscovery::Disabled => {}
+            CustomTypeDiscovery::OnConnect => {
+                if let Err(e) = self.discover_custom_types().await {
+                    session_warn!(self, "Failed to discover custom types: {}", e);
+                }
+            }
+            CustomTypeDiscovery::Lazy => {
+                let _guard = self.type_discovery.lock.lock().await;
+                self.type_discovery.loader.clear();
+                trace_lock!(self.type_discovery.unknown).clear();
+            }
+        }
+    }
+}
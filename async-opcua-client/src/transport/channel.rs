@@ -1,28 +1,42 @@
-use std::{str::FromStr, sync::Arc, time::Duration};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{session::EndpointInfo, transport::core::TransportPollResult};
 use arc_swap::{ArcSwap, ArcSwapOption};
 use opcua_core::{
-    comms::secure_channel::{Role, SecureChannel},
+    comms::{
+        secure_channel::{Role, SecureChannel},
+        tcp_types::ConnectionLimits,
+    },
+    config::TokenRenewalPolicy,
     sync::RwLock,
-    trace_read_lock, trace_write_lock, RequestMessage, ResponseMessage,
+    trace_read_lock, trace_write_lock, RequestMessage, ResponseMessage, ServiceSpan,
 };
 use opcua_crypto::{CertificateStore, PrivateKey, SecurityPolicy, X509};
 use opcua_types::{
-    ByteString, CloseSecureChannelRequest, ContextOwned, IntegerId, NodeId, RequestHeader,
+    ByteString, CancelRequest, CloseSecureChannelRequest, ContextOwned, EndpointDescription,
+    GetEndpointsRequest, IntegerId, MessageSecurityMode, NodeId, RequestHeader,
     SecurityTokenRequestType, StatusCode,
 };
-use tracing::{debug, error};
+use tracing::{debug, error, warn, Instrument};
 
 use super::{
-    connect::{Connector, Transport},
+    connect::{ConnectedTransport, Connector, Transport},
+    paths::ConnectionPaths,
+    retry::{
+        is_retryable_request, is_retryable_status, is_session_error, retry_backoff, SessionRecovery,
+    },
     state::{Request, RequestSend, SecureChannelState},
-    tcp::TcpTransport,
 };
 
 use crate::{
     retry::SessionRetryPolicy,
+    session::{process_service_result, process_unexpected_response},
     transport::{tcp::TransportConfiguration, OutgoingMessage},
+    Client, ConnectionPathOptions, IdentityToken,
 };
 
 // This is an arbitrary limit which should never be reached in practice,
@@ -30,25 +44,60 @@ use crate::{
 // memory if it gets into an unexpected (bad) state.
 const MAX_INFLIGHT_MESSAGES: usize = 1_000_000;
 
+/// Sends a `Cancel` for a request if its future is dropped or times out before
+/// the response arrives.
+struct CancelOnDrop<'a> {
+    channel: &'a AsyncSecureChannel,
+    request_handle: IntegerId,
+    timeout: Duration,
+    armed: bool,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.channel
+                .cancel_abandoned(self.request_handle, self.timeout);
+        }
+    }
+}
+
+/// Only requests in a session can be cancelled. Publish requests are managed by the
+/// session event loop, and are abandoned when the session is closed.
+fn is_cancellable(request: &RequestMessage) -> bool {
+    !request.request_header().authentication_token.is_null()
+        && !matches!(
+            request,
+            RequestMessage::Cancel(_)
+                | RequestMessage::Publish(_)
+                | RequestMessage::CloseSession(_)
+                | RequestMessage::ActivateSession(_)
+        )
+}
+
 /// Wrapper around an open secure channel
 pub struct AsyncSecureChannel {
     endpoint_info: EndpointInfo,
+    current_endpoint: ArcSwap<EndpointInfo>,
     session_retry_policy: SessionRetryPolicy,
     pub(crate) secure_channel: Arc<RwLock<SecureChannel>>,
     certificate_store: Arc<RwLock<CertificateStore>>,
     transport_config: TransportConfiguration,
     state: SecureChannelState,
     issue_channel_lock: tokio::sync::Mutex<()>,
-    connector: Box<dyn Connector>,
-    channel_lifetime: u32,
+    session_recovery: Option<Box<dyn SessionRecovery>>,
+    recovery_lock: tokio::sync::Mutex<()>,
+    paths: ConnectionPaths,
+    renewal_policy: TokenRenewalPolicy,
 
     request_send: ArcSwapOption<RequestSend>,
     encoding_context: Arc<RwLock<ContextOwned>>,
+    session_id: Arc<ArcSwap<NodeId>>,
 }
 
 /// Event loop for a secure channel. This must be polled to make progress.
 pub struct SecureChannelEventLoop {
-    transport: TcpTransport,
+    transport: ConnectedTransport,
 }
 
 impl SecureChannelEventLoop {
@@ -89,21 +138,116 @@ impl AsyncSecureChannel {
         Ok(())
     }
 
+    pub(crate) fn update_from_activated_session(
+        &self,
+        nonce: &ByteString,
+    ) -> Result<(), StatusCode> {
+        let mut secure_channel = trace_write_lock!(self.secure_channel);
+        secure_channel.set_remote_nonce_from_byte_string(nonce)
+    }
+
     pub(crate) fn security_policy(&self) -> SecurityPolicy {
         let secure_channel = trace_read_lock!(self.secure_channel);
         secure_channel.security_policy()
     }
 
-    /// Get the target endpoint of the secure channel.
+    /// Add alternate network paths to the server, tried in order when connecting
+    /// over the paths already added fails.
+    pub(crate) fn add_alternate_paths(
+        &mut self,
+        connectors: Vec<Box<dyn Connector + Send + Sync>>,
+        options: ConnectionPathOptions,
+    ) {
+        self.paths.add_alternates(connectors, options);
+    }
+
+    /// Get the priority of the network path the channel last connected over,
+    /// 0 is the primary path.
+    pub fn connection_path(&self) -> usize {
+        self.paths.active()
+    }
+
+    /// Get the priority of the network path the channel last connected over, if it
+    /// changed since this was last called.
+    pub(crate) fn take_path_change(&self) -> Option<usize> {
+        self.paths.take_path_change()
+    }
+
+    /// Get the target endpoint of the secure channel, as it was when the channel was created.
     pub fn endpoint_info(&self) -> &EndpointInfo {
         &self.endpoint_info
     }
 
+    /// Get the endpoint the secure channel currently connects to. This differs from
+    /// [`AsyncSecureChannel::endpoint_info`] if the endpoint was refreshed from the server,
+    /// or the user identity or preferred locales were changed.
+    pub fn current_endpoint_info(&self) -> Arc<EndpointInfo> {
+        self.current_endpoint.load_full()
+    }
+
+    /// Set the locales requested when activating a session on this channel.
+    pub(crate) fn set_preferred_locales(&self, preferred_locales: Vec<String>) {
+        self.current_endpoint.rcu(|current| EndpointInfo {
+            endpoint: current.endpoint.clone(),
+            user_identity_token: current.user_identity_token.clone(),
+            preferred_locales: preferred_locales.clone(),
+        });
+    }
+
+    /// Set the user identity used when activating a session on this channel.
+    pub(crate) fn set_user_identity_token(&self, user_identity_token: IdentityToken) {
+        self.current_endpoint.rcu(|current| EndpointInfo {
+            endpoint: current.endpoint.clone(),
+            user_identity_token: user_identity_token.clone(),
+            preferred_locales: current.preferred_locales.clone(),
+        });
+    }
+
+    /// Abort the in-flight request with the given request handle. The request fails
+    /// with `BadRequestCancelledByClient`, and if it is still being sent, its remaining
+    /// chunks are discarded.
+    ///
+    /// This only affects the local transport, use the `Cancel` service to also ask the
+    /// server to stop processing the request.
+    pub fn abort_request(&self, request_handle: IntegerId) {
+        self.transport_config
+            .aborts
+            .push(request_handle, StatusCode::BadRequestCancelledByClient);
+    }
+
+    /// Get the message limits negotiated with the server when the channel was last
+    /// connected. All limits are zero if the channel has never been connected.
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        trace_read_lock!(self.secure_channel).connection_limits()
+    }
+
+    /// Get the collector of per-service metrics of requests sent on this channel.
+    #[cfg(feature = "request-metrics")]
+    pub fn request_metrics(&self) -> &Arc<crate::metrics::RequestMetrics> {
+        &self.transport_config.request_metrics
+    }
+
     /// Get the current global encoding context in use by this channel.
     pub fn encoding_context(&self) -> &RwLock<ContextOwned> {
         &self.encoding_context
     }
 
+    /// Share the server session ID with this channel, so that it can be attached to
+    /// service call spans.
+    pub(crate) fn set_session_id(&mut self, session_id: Arc<ArcSwap<NodeId>>) {
+        self.session_id = session_id;
+    }
+
+    /// Set the hook restoring the session before a failed request is retried.
+    pub(crate) fn set_session_recovery(&mut self, recovery: Box<dyn SessionRecovery>) {
+        self.session_recovery = Some(recovery);
+    }
+
+    /// Get the active authentication token for this channel.
+    pub(crate) fn auth_token(&self) -> Arc<NodeId> {
+        self.state.auth_token()
+    }
+
     /// Set the active authentication token for this channel.
     pub fn set_auth_token(&self, token: NodeId) {
         self.state.set_auth_token(token);
@@ -135,35 +279,178 @@ impl AsyncSecureChannel {
         auth_token: Arc<ArcSwap<NodeId>>,
         transport_config: TransportConfiguration,
         connector: Box<dyn Connector>,
-        channel_lifetime: u32,
+        renewal_policy: TokenRenewalPolicy,
         encoding_context: Arc<RwLock<ContextOwned>>,
     ) -> Self {
-        let secure_channel = Arc::new(RwLock::new(SecureChannel::new(
+        let mut secure_channel = SecureChannel::new(
             certificate_store.clone(),
             Role::Client,
             encoding_context.clone(),
-        )));
+        );
+        secure_channel.set_renewal_threshold_percent(renewal_policy.renewal_threshold_percent);
+        secure_channel.set_metrics(transport_config.metrics.clone());
+        secure_channel.set_interceptors(transport_config.interceptors.clone());
+        let secure_channel = Arc::new(RwLock::new(secure_channel));
 
         Self {
             transport_config,
             issue_channel_lock: tokio::sync::Mutex::new(()),
+            session_recovery: None,
+            recovery_lock: tokio::sync::Mutex::new(()),
             state: SecureChannelState::new(ignore_clock_skew, secure_channel.clone(), auth_token),
+            current_endpoint: ArcSwap::new(Arc::new(endpoint_info.clone())),
             endpoint_info,
             secure_channel,
             certificate_store,
             session_retry_policy,
             request_send: Default::default(),
-            connector,
-            channel_lifetime,
+            paths: ConnectionPaths::new(connector),
+            renewal_policy,
             encoding_context,
+            session_id: Arc::default(),
         }
     }
 
     /// Send a message on the secure channel, and wait for a response.
+    ///
+    /// Failed requests are retried according to the configured
+    /// [`ServiceRetryOptions`](crate::ServiceRetryOptions).
     pub async fn send(
         &self,
         request: impl Into<RequestMessage>,
         timeout: Duration,
+    ) -> Result<ResponseMessage, StatusCode> {
+        let mut request = request.into();
+        let options = &self.transport_config.service_retry;
+        if !is_retryable_request(&request, options) {
+            return self.send_once(request, timeout).await;
+        }
+
+        let mut backoff = retry_backoff(options);
+        loop {
+            let auth_token = request.request_header().authentication_token.clone();
+            let res = self.send_once(request.clone(), timeout).await;
+            let status = match &res {
+                Ok(r) => r.response_header().service_result,
+                Err(e) => *e,
+            };
+            let Some(delay) = is_retryable_status(status)
+                .then(|| backoff.next())
+                .flatten()
+            else {
+                return res;
+            };
+            debug!(
+                "{} request failed with {status}, retrying after {delay:?}",
+                request.type_name()
+            );
+            tokio::time::sleep(delay).await;
+
+            if let Some(recovery) = &self.session_recovery {
+                // Requests failing together wait for the same recovery, the first
+                // one to get here re-activates the session.
+                let _guard = self.recovery_lock.lock().await;
+                if let Err(e) = recovery.recover(status, &auth_token, timeout).await {
+                    if is_session_error(status) {
+                        warn!("Could not restore the session to retry the request: {e}");
+                    }
+                    return res;
+                }
+            }
+
+            // The session may have been recreated, so the request needs a new
+            // authentication token.
+            let header = request.request_header_mut();
+            let mut new_header = self.make_request_header(timeout);
+            new_header.return_diagnostics = header.return_diagnostics;
+            new_header.audit_entry_id = header.audit_entry_id.clone();
+            *header = new_header;
+        }
+    }
+
+    /// Send a message on the secure channel once, and wait for a response.
+    async fn send_once(
+        &self,
+        request: RequestMessage,
+        timeout: Duration,
+    ) -> Result<ResponseMessage, StatusCode> {
+        let span = ServiceSpan::client(&request);
+        #[cfg(feature = "request-metrics")]
+        let (service, start) = (request.type_name(), Instant::now());
+        let session_id = self.session_id.load();
+        if !session_id.is_null() {
+            span.record_session_id(&**session_id);
+        }
+
+        let res = match self
+            .transport_config
+            .request_limiter
+            .acquire(&request, timeout)
+            .await
+        {
+            Ok(permit) => {
+                self.send_cancellable(request, permit.timeout)
+                    .instrument(span.span().clone())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let status = match &res {
+            Ok(r) => r.response_header().service_result,
+            Err(e) => *e,
+        };
+        span.finish(status);
+        #[cfg(feature = "request-metrics")]
+        self.transport_config
+            .request_metrics
+            .record(service, status, start.elapsed());
+        res
+    }
+
+    /// Send a request, cancelling it on the server if it times out or the future is dropped.
+    async fn send_cancellable(
+        &self,
+        request: RequestMessage,
+        timeout: Duration,
+    ) -> Result<ResponseMessage, StatusCode> {
+        let mut cancel_guard = CancelOnDrop {
+            channel: self,
+            request_handle: request.request_header().request_handle,
+            timeout,
+            armed: is_cancellable(&request),
+        };
+        let res = self.send_inner(request, timeout).await;
+        // The server may still be working on requests we stopped waiting for.
+        cancel_guard.armed &= matches!(res, Err(StatusCode::BadTimeout));
+        drop(cancel_guard);
+        res
+    }
+
+    /// Ask the server to stop processing a request nobody is waiting for any more,
+    /// without waiting for the response.
+    fn cancel_abandoned(&self, request_handle: IntegerId, timeout: Duration) {
+        let Some(send) = self.request_send.load_full() else {
+            return;
+        };
+        debug!("Cancelling abandoned request {}", request_handle);
+        let request = CancelRequest {
+            request_header: self.make_request_header(timeout),
+            request_handle,
+        };
+        let message = OutgoingMessage {
+            request: request.into(),
+            callback: None,
+            deadline: Instant::now() + timeout,
+        };
+        if send.try_send(message).is_err() {
+            debug!("Failed to cancel abandoned request {}", request_handle);
+        }
+    }
+
+    async fn send_inner(
+        &self,
+        request: RequestMessage,
+        timeout: Duration,
     ) -> Result<ResponseMessage, StatusCode> {
         let sender = self.request_send.load().as_deref().cloned();
         let Some(send) = sender else {
@@ -187,16 +474,7 @@ impl AsyncSecureChannel {
             };
 
             if should_renew_security_token {
-                let request = self.state.begin_issue_or_renew_secure_channel(
-                    SecurityTokenRequestType::Renew,
-                    self.channel_lifetime,
-                    Duration::from_secs(30),
-                    send.clone(),
-                );
-
-                let resp = request.send().await?;
-
-                self.state.end_issue_or_renew_secure_channel(resp)?;
+                self.renew_security_token(&send).await?;
             }
 
             drop(guard);
@@ -205,6 +483,43 @@ impl AsyncSecureChannel {
         Request::new(request, send, timeout).send().await
     }
 
+    /// Renew the security token, retrying according to the renewal policy.
+    async fn renew_security_token(&self, send: &RequestSend) -> Result<(), StatusCode> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let request = self.state.begin_issue_or_renew_secure_channel(
+                SecurityTokenRequestType::Renew,
+                self.renewal_policy.requested_lifetime_ms,
+                self.transport_config.open_secure_channel_timeout,
+                send.clone(),
+            );
+
+            let res = match request.send().await {
+                Ok(resp) => self.state.end_issue_or_renew_secure_channel(resp),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(token) => {
+                    self.transport_config.metrics.secure_channel_renewed();
+                    self.renewal_policy.hooks.token_renewed(&token);
+                    break Ok(());
+                }
+                Err(e) => {
+                    let will_retry = attempt <= self.renewal_policy.max_retries;
+                    warn!("Failed to renew secure channel token on attempt {attempt}: {e}");
+                    self.renewal_policy
+                        .hooks
+                        .renewal_failed(e, attempt, will_retry);
+                    if !will_retry {
+                        break Err(e);
+                    }
+                    tokio::time::sleep(self.renewal_policy.retry_interval()).await;
+                }
+            }
+        }
+    }
+
     /// Attempt to establish a connection using this channel, returning an event loop
     /// for polling the connection.
     pub async fn connect(&self) -> Result<SecureChannelEventLoop, StatusCode> {
@@ -226,19 +541,170 @@ impl AsyncSecureChannel {
         }
     }
 
+    /// Refresh the endpoint of this channel by calling `GetEndpoints` on the server over an
+    /// unsecured channel, and picking the endpoint with the same URL, security policy and
+    /// security mode. This picks up changes such as a new server certificate.
+    ///
+    /// The channel must not be connected when this is called. The current endpoint is
+    /// only replaced once a matching endpoint is found, otherwise it is left unchanged.
+    pub(crate) async fn rediscover_endpoint(
+        &self,
+        timeout: Duration,
+    ) -> Result<Arc<EndpointInfo>, StatusCode> {
+        let current = self.current_endpoint.load_full();
+        let security_policy =
+            SecurityPolicy::from_str(current.endpoint.security_policy_uri.as_ref()).unwrap();
+        if security_policy == SecurityPolicy::Unknown {
+            return Err(StatusCode::BadSecurityPolicyRejected);
+        }
+
+        let discovery_endpoint = EndpointInfo {
+            endpoint: EndpointDescription {
+                endpoint_url: current.endpoint.endpoint_url.clone(),
+                security_policy_uri: SecurityPolicy::None.to_uri().into(),
+                security_mode: MessageSecurityMode::None,
+                ..Default::default()
+            },
+            user_identity_token: IdentityToken::Anonymous,
+            preferred_locales: Vec::new(),
+        };
+        let endpoints = self.get_endpoints(&discovery_endpoint, timeout).await?;
+        let Some(endpoint) = Client::find_matching_endpoint(
+            &endpoints,
+            current.endpoint.endpoint_url.as_ref(),
+            security_policy,
+            current.endpoint.security_mode,
+        ) else {
+            warn!(
+                "Server at {} no longer has a matching endpoint",
+                current.endpoint.endpoint_url
+            );
+            return Err(StatusCode::BadTcpEndpointUrlInvalid);
+        };
+        // Keep any changes to the identity or locales made while discovering.
+        self.current_endpoint.rcu(|current| EndpointInfo {
+            endpoint: endpoint.clone(),
+            user_identity_token: current.user_identity_token.clone(),
+            preferred_locales: current.preferred_locales.clone(),
+        });
+        Ok(self.current_endpoint.load_full())
+    }
+
+    /// Complete an endpoint that was constructed without calling `GetEndpoints`, and so
+    /// has no user token policies, from the endpoints the server returned when creating
+    /// the session. The endpoint URL is kept, since the server may advertise a hostname
+    /// that is not reachable from the client. Other endpoints are left unchanged.
+    pub(crate) fn complete_endpoint(&self, server_endpoints: &[EndpointDescription]) {
+        let current = self.current_endpoint.load_full();
+        if current
+            .endpoint
+            .user_identity_tokens
+            .as_ref()
+            .is_some_and(|t| !t.is_empty())
+        {
+            return;
+        }
+        let security_policy =
+            SecurityPolicy::from_uri(current.endpoint.security_policy_uri.as_ref());
+        if security_policy == SecurityPolicy::Unknown {
+            return;
+        }
+        let security_mode = current.endpoint.security_mode;
+        let url = current.endpoint.endpoint_url.as_ref();
+        let Some(endpoint) =
+            Client::find_matching_endpoint(server_endpoints, url, security_policy, security_mode)
+                .or_else(|| {
+                    // The URL may not match if the server is reached through a forwarded port.
+                    server_endpoints
+                        .iter()
+                        .find(|e| {
+                            e.security_mode == security_mode
+                                && SecurityPolicy::from_uri(e.security_policy_uri.as_ref())
+                                    == security_policy
+                        })
+                        .cloned()
+                })
+        else {
+            warn!(
+                "Server returned no endpoint matching {}, user token policies are unknown",
+                url
+            );
+            return;
+        };
+        self.current_endpoint.store(Arc::new(EndpointInfo {
+            endpoint: EndpointDescription {
+                endpoint_url: current.endpoint.endpoint_url.clone(),
+                ..endpoint
+            },
+            user_identity_token: current.user_identity_token.clone(),
+            preferred_locales: current.preferred_locales.clone(),
+        }));
+    }
+
+    /// Connect to `endpoint_info`, call `GetEndpoints`, and close the channel again.
+    async fn get_endpoints(
+        &self,
+        endpoint_info: &EndpointInfo,
+        timeout: Duration,
+    ) -> Result<Vec<EndpointDescription>, StatusCode> {
+        let mut event_loop = self.connect_to_endpoint(endpoint_info).await?;
+        let request = GetEndpointsRequest {
+            request_header: self.make_request_header(timeout),
+            endpoint_url: endpoint_info.endpoint.endpoint_url.clone(),
+            locale_ids: None,
+            profile_uris: None,
+        };
+        let send_fut = self.send(request, timeout);
+        tokio::pin!(send_fut);
+        let res = loop {
+            tokio::select! {
+                r = event_loop.poll() => {
+                    if let TransportPollResult::Closed(e) = r {
+                        return Err(e);
+                    }
+                }
+                r = &mut send_fut => break r,
+            }
+        };
+
+        self.close_channel().await;
+        loop {
+            if matches!(event_loop.poll().await, TransportPollResult::Closed(_)) {
+                break;
+            }
+        }
+
+        match res? {
+            ResponseMessage::GetEndpoints(response) => {
+                process_service_result(&response.response_header)?;
+                Ok(response.endpoints.unwrap_or_default())
+            }
+            r => Err(process_unexpected_response(r)),
+        }
+    }
+
     /// Connect to the server without attempting to retry if it fails.
     pub async fn connect_no_retry(&self) -> Result<SecureChannelEventLoop, StatusCode> {
+        self.connect_to_endpoint(&self.current_endpoint.load_full())
+            .await
+    }
+
+    /// Connect to the server at `endpoint_info`, without attempting to retry if it fails.
+    async fn connect_to_endpoint(
+        &self,
+        endpoint_info: &EndpointInfo,
+    ) -> Result<SecureChannelEventLoop, StatusCode> {
         {
             let mut secure_channel = trace_write_lock!(self.secure_channel);
             secure_channel.clear_security_token();
         }
 
-        let (mut transport, send) = self.create_transport().await?;
+        let (mut transport, send) = self.create_transport(endpoint_info).await?;
 
         let request = self.state.begin_issue_or_renew_secure_channel(
             SecurityTokenRequestType::Issue,
-            self.channel_lifetime,
-            Duration::from_secs(30),
+            self.renewal_policy.requested_lifetime_ms,
+            self.transport_config.open_secure_channel_timeout,
             send.clone(),
         );
 
@@ -265,16 +731,22 @@ impl AsyncSecureChannel {
 
     async fn create_transport(
         &self,
-    ) -> Result<(TcpTransport, tokio::sync::mpsc::Sender<OutgoingMessage>), StatusCode> {
+        endpoint_info: &EndpointInfo,
+    ) -> Result<
+        (
+            ConnectedTransport,
+            tokio::sync::mpsc::Sender<OutgoingMessage>,
+        ),
+        StatusCode,
+    > {
         debug!("Connect");
         let security_policy =
-            SecurityPolicy::from_str(self.endpoint_info.endpoint.security_policy_uri.as_ref())
-                .unwrap();
+            SecurityPolicy::from_str(endpoint_info.endpoint.security_policy_uri.as_ref()).unwrap();
 
         if security_policy == SecurityPolicy::Unknown {
             error!(
                 "connect, security policy \"{}\" is unknown",
-                self.endpoint_info.endpoint.security_policy_uri.as_ref()
+                endpoint_info.endpoint.security_policy_uri.as_ref()
             );
             Err(StatusCode::BadSecurityPolicyRejected)
         } else {
@@ -291,28 +763,40 @@ impl AsyncSecureChannel {
                 secure_channel.set_private_key(key);
                 secure_channel.set_cert(cert);
                 secure_channel.set_security_policy(security_policy);
-                secure_channel.set_security_mode(self.endpoint_info.endpoint.security_mode);
-                let _ = secure_channel.set_remote_cert_from_byte_string(
-                    &self.endpoint_info.endpoint.server_certificate,
-                );
+                secure_channel.set_security_mode(endpoint_info.endpoint.security_mode);
+                let _ = secure_channel
+                    .set_remote_cert_from_byte_string(&endpoint_info.endpoint.server_certificate);
                 debug!("Security policy = {:?}", security_policy);
-                debug!(
-                    "Security mode = {:?}",
-                    self.endpoint_info.endpoint.security_mode
-                );
+                debug!("Security mode = {:?}", endpoint_info.endpoint.security_mode);
             }
 
-            let (send, recv) = tokio::sync::mpsc::channel(MAX_INFLIGHT_MESSAGES);
-            let transport = self
-                .connector
-                .connect(
-                    self.secure_channel.clone(),
-                    recv,
-                    self.transport_config.clone(),
-                )
-                .await?;
-
-            Ok((transport, send))
+            let mut last_error = StatusCode::BadNotConnected;
+            for priority in self.paths.order() {
+                let (send, recv) = tokio::sync::mpsc::channel(MAX_INFLIGHT_MESSAGES);
+                match self
+                    .paths
+                    .connector(priority)
+                    .connect(
+                        self.secure_channel.clone(),
+                        recv,
+                        self.transport_config.clone(),
+                    )
+                    .await
+                {
+                    Ok(transport) => {
+                        self.paths.on_connected(priority);
+                        return Ok((transport, send));
+                    }
+                    Err(e) => {
+                        if self.paths.len() > 1 {
+                            warn!("Failed to connect over path with priority {priority}: {e}");
+                        }
+                        self.paths.on_failed(priority);
+                        last_error = e;
+                    }
+                }
+            }
+            Err(last_error)
         }
     }
 
@@ -1,12 +1,20 @@
 use std::{future::Future, sync::Arc};
 
 use async_trait::async_trait;
-use opcua_core::{comms::secure_channel::SecureChannel, sync::RwLock};
+use opcua_core::{
+    comms::{
+        secure_channel::SecureChannel,
+        url::{is_opc_ua_https_url, is_opc_ua_uds_url},
+    },
+    sync::RwLock,
+};
 use opcua_types::{EndpointDescription, Error, StatusCode};
 
+#[cfg(feature = "https")]
+use super::{https::HttpsTransport, HttpsConnector};
 use super::{
     tcp::{TcpTransport, TransportConfiguration},
-    OutgoingMessage, TcpConnector, TransportPollResult,
+    OutgoingMessage, TcpConnector, TransportPollResult, UdsConnector,
 };
 
 #[async_trait]
@@ -19,14 +27,14 @@ use super::{
 ///  - This should not do any retries, that's handled on a higher level.
 pub trait Connector: Send + Sync {
     /// Attempt to establish a connection to the OPC UA endpoint given by `endpoint_url`.
-    /// Note that on success, this returns a `TcpTransport`. The caller is responsible for
-    /// calling `run` on the returned transport in order to actually send and receive messages.
+    /// Note that on success, this returns a `ConnectedTransport`. The caller is responsible for
+    /// calling `poll` on the returned transport in order to actually send and receive messages.
     async fn connect(
         &self,
         channel: Arc<RwLock<SecureChannel>>,
         outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
         config: TransportConfiguration,
-    ) -> Result<TcpTransport, StatusCode>;
+    ) -> Result<ConnectedTransport, StatusCode>;
 
     /// Get the default endpoint for this connector.
     fn default_endpoint(&self) -> EndpointDescription;
@@ -47,7 +55,23 @@ impl ConnectorBuilder for String {
 
 impl ConnectorBuilder for &str {
     fn build(self) -> Result<Box<dyn Connector + Send + Sync>, Error> {
-        Ok(Box::new(TcpConnector::new(self)?))
+        if is_opc_ua_uds_url(self) {
+            Ok(Box::new(UdsConnector::new(self)?))
+        } else if is_opc_ua_https_url(self) {
+            #[cfg(feature = "https")]
+            {
+                Ok(Box::new(HttpsConnector::new(self)?))
+            }
+            #[cfg(not(feature = "https"))]
+            {
+                Err(Error::new(
+                    StatusCode::BadInvalidArgument,
+                    format!("Connecting to {self} requires the `https` feature"),
+                ))
+            }
+        } else {
+            Ok(Box::new(TcpConnector::new(self)?))
+        }
     }
 }
 
@@ -97,3 +121,46 @@ pub trait Transport: Send + Sync + 'static {
     /// Note that this method _must_ be cancellation safe.
     fn poll(&mut self) -> impl Future<Output = TransportPollResult> + Send + Sync;
 }
+
+/// A connected transport, one variant for each kind of transport supported by the client.
+pub enum ConnectedTransport {
+    /// OPC-UA binary framing over TCP, or another stream such as a Unix domain socket.
+    Tcp(Box<TcpTransport>),
+    /// Binary encoded messages over HTTPS.
+    #[cfg(feature = "https")]
+    Https(HttpsTransport),
+}
+
+impl ConnectedTransport {
+    /// Get the URL of the connected server.
+    pub fn connected_url(&self) -> &str {
+        match self {
+            Self::Tcp(t) => t.connected_url(),
+            #[cfg(feature = "https")]
+            Self::Https(t) => t.connected_url(),
+        }
+    }
+}
+
+impl From<TcpTransport> for ConnectedTransport {
+    fn from(value: TcpTransport) -> Self {
+        Self::Tcp(Box::new(value))
+    }
+}
+
+#[cfg(feature = "https")]
+impl From<HttpsTransport> for ConnectedTransport {
+    fn from(value: HttpsTransport) -> Self {
+        Self::Https(value)
+    }
+}
+
+impl Transport for ConnectedTransport {
+    async fn poll(&mut self) -> TransportPollResult {
+        match self {
+            Self::Tcp(t) => t.poll().await,
+            #[cfg(feature = "https")]
+            Self::Https(t) => t.poll().await,
+        }
+    }
+}
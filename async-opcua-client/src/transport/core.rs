@@ -5,16 +5,18 @@ use std::time::Instant;
 use futures::future::Either;
 use opcua_core::comms::sequence_number::SequenceNumberHandle;
 use opcua_core::{trace_read_lock, trace_write_lock, RequestMessage, ResponseMessage};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::Notify;
 use tracing::{debug, error, trace, warn};
 
 use opcua_core::comms::buffer::SendBuffer;
+use opcua_core::comms::interceptor::MessageDirection;
 use opcua_core::comms::message_chunk::MessageIsFinalType;
 use opcua_core::comms::{
     chunker::Chunker, message_chunk::MessageChunk, message_chunk_info::ChunkInfo,
     secure_channel::SecureChannel, tcp_codec::Message,
 };
-use opcua_types::{Error, StatusCode};
+use opcua_types::{Error, ErrorContext, StatusCode};
 
 #[derive(Debug)]
 struct MessageChunkWithChunkInfo {
@@ -26,6 +28,31 @@ pub(crate) struct MessageState {
     callback: tokio::sync::oneshot::Sender<Result<ResponseMessage, StatusCode>>,
     chunks: Vec<MessageChunkWithChunkInfo>,
     deadline: Instant,
+    request_handle: u32,
+}
+
+/// Requests to abort in-flight messages, shared between a channel and its transport.
+#[derive(Debug, Default)]
+pub(crate) struct AbortQueue {
+    pending: Mutex<Vec<(u32, StatusCode)>>,
+    notify: Notify,
+}
+
+impl AbortQueue {
+    /// Abort the request with the given request handle, failing it with `status`.
+    pub(crate) fn push(&self, request_handle: u32, status: StatusCode) {
+        self.pending.lock().push((request_handle, status));
+        self.notify.notify_one();
+    }
+
+    pub(super) fn take(&self) -> Vec<(u32, StatusCode)> {
+        std::mem::take(&mut *self.pending.lock())
+    }
+
+    /// Wait until an abort is requested. This is cancel safe.
+    pub(super) async fn wait(&self) {
+        self.notify.notified().await
+    }
 }
 
 pub(super) struct TransportState {
@@ -42,6 +69,16 @@ pub(super) struct TransportState {
     /// Max size of incoming chunks
     #[allow(unused)]
     receive_buffer_size: usize,
+    /// Minimum number of chunks before they are decrypted in parallel, 0 to disable.
+    parallel_crypto_threshold: usize,
+    /// Received chunks that have not been verified yet, only used if
+    /// `parallel_crypto_threshold` is non-zero.
+    pending_chunks: Vec<MessageChunk>,
+    /// Requests to abort in-flight messages.
+    pub(super) aborts: Arc<AbortQueue>,
+    /// Aborted request handles that did not match any in-flight message yet,
+    /// since they may still be waiting in the outgoing queue.
+    unmatched_aborts: HashMap<u32, StatusCode>,
 }
 
 #[derive(Debug)]
@@ -54,9 +91,11 @@ pub enum TransportPollResult {
     OutgoingMessageSent,
     /// An incoming message was received from the server.
     IncomingMessage,
-    /// An error occured that is recoverable, so the transport can continue and
+    /// An error occurred that is recoverable, so the transport can continue and
     /// simply fail the request.
     RecoverableError(StatusCode),
+    /// One or more in-flight requests were aborted.
+    RequestAborted,
     /// The transport was closed with the given status code.
     Closed(StatusCode),
 }
@@ -73,6 +112,8 @@ impl TransportState {
         outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
         max_chunk_count: usize,
         receive_buffer_size: usize,
+        parallel_crypto_threshold: usize,
+        aborts: Arc<AbortQueue>,
     ) -> Self {
         let legacy_sequence_numbers = secure_channel
             .read()
@@ -85,6 +126,12 @@ impl TransportState {
             sequence_numbers: SequenceNumberHandle::new(legacy_sequence_numbers),
             max_chunk_count,
             receive_buffer_size,
+            parallel_crypto_threshold,
+            pending_chunks: Vec::new(),
+            // Aborts requested before this connection was established refer to requests
+            // that have already failed.
+            unmatched_aborts: aborts.take().into_iter().collect(),
+            aborts,
         }
     }
 
@@ -107,15 +154,27 @@ impl TransportState {
                     }
                     outgoing = self.outgoing_recv.recv() => {
                         let outgoing = outgoing?;
-                        let request_id = send_buffer.next_request_id();
+                        let request_handle = outgoing.request.request_header().request_handle;
                         if let Some(callback) = outgoing.callback {
+                            // Don't bother sending requests nobody is waiting for.
+                            if callback.is_closed() {
+                                debug!("Dropping request {} since its caller is gone", request_handle);
+                                continue;
+                            }
+                            if let Some(status) = self.unmatched_aborts.remove(&request_handle) {
+                                let _ = callback.send(Err(status));
+                                continue;
+                            }
+                            let request_id = send_buffer.next_request_id();
                             self.message_states.insert(request_id, MessageState {
                                 callback,
                                 chunks: Vec::new(),
                                 deadline: outgoing.deadline,
+                                request_handle,
                             });
+                            break Some((outgoing.request, request_id));
                         }
-                        break Some((outgoing.request, request_id));
+                        break Some((outgoing.request, send_buffer.next_request_id()));
                     }
             }
         }
@@ -149,6 +208,77 @@ impl TransportState {
         }
     }
 
+    /// Abort requests queued in the abort queue. Any chunks of the requests that have
+    /// not been sent yet are discarded.
+    pub(super) fn process_aborts(
+        &mut self,
+        send_buffer: &mut SendBuffer,
+    ) -> Result<(), StatusCode> {
+        for (request_handle, status) in self.aborts.take() {
+            let request_id = self
+                .message_states
+                .iter()
+                .find(|(_, s)| s.request_handle == request_handle)
+                .map(|(id, _)| *id);
+            match request_id {
+                Some(request_id) => self.abort_message(request_id, status, send_buffer)?,
+                None => {
+                    self.unmatched_aborts.insert(request_handle, status);
+                }
+            }
+        }
+        if self.message_states.is_empty() && self.outgoing_recv.is_empty() {
+            // Nothing left that the remaining aborts could refer to.
+            self.unmatched_aborts.clear();
+        }
+        Ok(())
+    }
+
+    /// Abort the message that is about to be sent next if its caller has gone away,
+    /// so that we don't keep sending chunks nobody is waiting for.
+    pub(super) fn abort_abandoned(
+        &mut self,
+        send_buffer: &mut SendBuffer,
+    ) -> Result<(), StatusCode> {
+        let Some(request_id) = send_buffer.next_queued_request_id() else {
+            return Ok(());
+        };
+        if self
+            .message_states
+            .get(&request_id)
+            .is_some_and(|s| s.callback.is_closed())
+        {
+            debug!("Aborting request {} since its caller is gone", request_id);
+            self.abort_message(
+                request_id,
+                StatusCode::BadRequestCancelledByClient,
+                send_buffer,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn abort_message(
+        &mut self,
+        request_id: u32,
+        status: StatusCode,
+        send_buffer: &mut SendBuffer,
+    ) -> Result<(), StatusCode> {
+        if let Some(state) = self.message_states.remove(&request_id) {
+            let _ = state.callback.send(Err(status));
+        }
+        let secure_channel = trace_read_lock!(self.secure_channel);
+        if send_buffer.abort(
+            request_id,
+            status,
+            "Request aborted by client",
+            &secure_channel,
+        )? {
+            debug!("Aborted sending request {}", request_id);
+        }
+        Ok(())
+    }
+
     pub(super) fn message_send_failed(&mut self, request_id: u32, err: StatusCode) {
         if let Some(message_state) = self.message_states.remove(&request_id) {
             let _ = message_state.callback.send(Err(err));
@@ -180,17 +310,46 @@ impl TransportState {
     }
 
     fn process_chunk(&mut self, chunk: MessageChunk) -> Result<(), StatusCode> {
-        let mut secure_channel = trace_write_lock!(self.secure_channel);
-        let chunk = secure_channel.verify_and_remove_security(&chunk.data)?;
+        if self.parallel_crypto_threshold == 0 {
+            let chunk = trace_write_lock!(self.secure_channel).remove_security(chunk)?;
+            return self.process_verified_chunk(chunk);
+        }
 
-        let chunk_info = chunk.chunk_info(&secure_channel)?;
+        // Hold on to intermediate chunks until the final chunk arrives, then
+        // verify and decrypt them all at once, which can be done in parallel.
+        // The sequence header is encrypted, so we cannot tell which message
+        // the chunks belong to before this.
+        let mut secure_channel = trace_write_lock!(self.secure_channel);
+        let header = chunk.message_header(&secure_channel.decoding_options())?;
+        self.pending_chunks.push(chunk);
+        if header.is_final == MessageIsFinalType::Intermediate
+            && (self.max_chunk_count == 0 || self.pending_chunks.len() < self.max_chunk_count)
+        {
+            return Ok(());
+        }
+        let chunks = secure_channel.remove_security_batch(
+            std::mem::take(&mut self.pending_chunks),
+            self.parallel_crypto_threshold,
+        )?;
         drop(secure_channel);
+        for chunk in chunks {
+            self.process_verified_chunk(chunk)?;
+        }
+        Ok(())
+    }
+
+    fn process_verified_chunk(&mut self, mut chunk: MessageChunk) -> Result<(), StatusCode> {
+        let chunk_info = {
+            let secure_channel = trace_read_lock!(self.secure_channel);
+            chunk.chunk_info(&secure_channel)?
+        };
         let req_id = chunk_info.sequence_header.request_id;
 
         // We do not care at all about incoming messages without a
-        // corresponding request.
+        // corresponding request, for example responses to aborted or timed out
+        // requests, but we still need to keep track of their sequence numbers.
         let Some(message_state) = self.message_states.get_mut(&req_id) else {
-            return Ok(());
+            return self.skip_sequence_number(chunk_info.sequence_header.sequence_number);
         };
 
         match chunk_info.message_header.is_final {
@@ -203,7 +362,7 @@ impl TransportState {
                 );
                 message_state.chunks.push(MessageChunkWithChunkInfo {
                     header: chunk_info,
-                    data_with_header: chunk.data,
+                    data_with_header: std::mem::take(&mut chunk.data),
                 });
                 if self.max_chunk_count > 0 && message_state.chunks.len() > self.max_chunk_count {
                     error!(
@@ -219,6 +378,7 @@ impl TransportState {
             }
             MessageIsFinalType::FinalError => {
                 warn!("Discarding chunk marked in as final error");
+                self.skip_sequence_number(chunk_info.sequence_header.sequence_number)?;
                 let message_state = self.message_states.remove(&req_id).unwrap();
                 let _ = message_state
                     .callback
@@ -233,31 +393,57 @@ impl TransportState {
                 );
                 message_state.chunks.push(MessageChunkWithChunkInfo {
                     header: chunk_info,
-                    data_with_header: chunk.data,
+                    data_with_header: std::mem::take(&mut chunk.data),
                 });
                 let message_state = self.message_states.remove(&req_id).unwrap();
                 let in_chunks = Self::merge_chunks(message_state.chunks)?;
-                let message = self.turn_received_chunks_into_message(&in_chunks)?;
+                let size = in_chunks.iter().map(|c| c.data.len()).sum();
+                let message = self.turn_received_chunks_into_message(in_chunks)?;
 
-                let _ = message_state.callback.send(Ok(message));
+                let intercepted = trace_read_lock!(self.secure_channel).intercept(
+                    MessageDirection::Incoming,
+                    &message,
+                    req_id,
+                    size,
+                );
+                if let Err(status) = intercepted {
+                    debug!(
+                        "Response to request {} rejected by interceptor: {}",
+                        req_id, status
+                    );
+                    let _ = message_state.callback.send(Err(status));
+                } else {
+                    let _ = message_state.callback.send(Ok(message));
+                }
             }
         }
         Ok(())
     }
 
+    /// Account for a chunk that is discarded without being validated, so that
+    /// the next message is expected to continue after it.
+    fn skip_sequence_number(&mut self, sequence_number: u32) -> Result<(), StatusCode> {
+        let secure_channel = trace_read_lock!(self.secure_channel);
+        Chunker::skip_sequence_number(
+            &mut self.sequence_numbers,
+            &secure_channel,
+            sequence_number,
+        )?;
+        Ok(())
+    }
+
     fn turn_received_chunks_into_message(
         &mut self,
-        chunks: &[MessageChunk],
+        chunks: Vec<MessageChunk>,
     ) -> Result<ResponseMessage, Error> {
         // Validate that all chunks have incrementing sequence numbers and valid chunk types
         let secure_channel = trace_read_lock!(self.secure_channel);
-        self.sequence_numbers.set(Chunker::validate_chunks(
-            self.sequence_numbers.clone(),
-            &secure_channel,
-            chunks,
-        )?);
+        self.sequence_numbers.set(
+            Chunker::validate_chunks(self.sequence_numbers.clone(), &secure_channel, &chunks)
+                .context("validating response chunks")?,
+        );
         // Now decode
-        Chunker::decode(chunks, &secure_channel, None)
+        Chunker::decode_owned(chunks, &secure_channel, None).context("decoding response")
     }
 
     fn merge_chunks(
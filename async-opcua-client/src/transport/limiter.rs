@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+use opcua_core::RequestMessage;
+use opcua_types::StatusCode;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::debug;
+
+use crate::{RequestLimitOptions, RequestOverflow};
+
+/// Limits the number of requests in flight on a channel, see [`RequestLimitOptions`].
+#[derive(Debug, Default)]
+pub(crate) struct RequestLimiter {
+    // Tokio semaphores are fair, so queued requests are sent in order.
+    permits: Option<Semaphore>,
+    overflow: RequestOverflow,
+}
+
+/// Permission to send a request, held until the response is received.
+pub(crate) struct RequestPermit<'a> {
+    _permit: Option<SemaphorePermit<'a>>,
+    /// What remains of the request timeout after waiting for the permit.
+    pub(crate) timeout: Duration,
+}
+
+/// Requests managing the session, and publish requests, which are limited separately,
+/// are never held back.
+fn is_limited(request: &RequestMessage) -> bool {
+    !matches!(
+        request,
+        RequestMessage::OpenSecureChannel(_)
+            | RequestMessage::CloseSecureChannel(_)
+            | RequestMessage::CreateSession(_)
+            | RequestMessage::ActivateSession(_)
+            | RequestMessage::CloseSession(_)
+            | RequestMessage::Cancel(_)
+            | RequestMessage::Publish(_)
+    )
+}
+
+impl RequestLimiter {
+    pub(crate) fn new(options: &RequestLimitOptions) -> Self {
+        Self {
+            permits: (options.max_inflight_requests > 0)
+                .then(|| Semaphore::new(options.max_inflight_requests)),
+            overflow: options.overflow,
+        }
+    }
+
+    /// Wait until `request` may be sent, according to the overflow behavior.
+    pub(crate) async fn acquire(
+        &self,
+        request: &RequestMessage,
+        timeout: Duration,
+    ) -> Result<RequestPermit<'_>, StatusCode> {
+        let Some(permits) = self.permits.as_ref().filter(|_| is_limited(request)) else {
+            return Ok(RequestPermit {
+                _permit: None,
+                timeout,
+            });
+        };
+        let handle = request.request_header().request_handle;
+        let permit = match self.overflow {
+            RequestOverflow::Reject => permits.try_acquire().map_err(|_| {
+                debug!("Rejecting request {handle}, too many requests in flight");
+                StatusCode::BadTooManyOperations
+            })?,
+            RequestOverflow::Queue => {
+                let start = Instant::now();
+                let permit = tokio::time::timeout(timeout, permits.acquire())
+                    .await
+                    .map_err(|_| {
+                        debug!("Request {handle} timed out waiting for requests in flight");
+                        StatusCode::BadTimeout
+                    })?
+                    // The semaphore is never closed.
+                    .map_err(|_| StatusCode::BadInternalError)?;
+                return Ok(RequestPermit {
+                    _permit: Some(permit),
+                    timeout: timeout.saturating_sub(start.elapsed()),
+                });
+            }
+        };
+        Ok(RequestPermit {
+            _permit: Some(permit),
+            timeout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_core::RequestMessage;
+    use opcua_types::{PublishRequest, ReadRequest, StatusCode};
+
+    use super::RequestLimiter;
+    use crate::{RequestLimitOptions, RequestOverflow};
+
+    fn limiter(overflow: RequestOverflow) -> RequestLimiter {
+        RequestLimiter::new(&RequestLimitOptions {
+            max_inflight_requests: 1,
+            overflow,
+        })
+    }
+
+    fn read() -> RequestMessage {
+        ReadRequest::default().into()
+    }
+
+    #[tokio::test]
+    async fn reject_when_full() {
+        let limiter = limiter(RequestOverflow::Reject);
+        let timeout = Duration::from_secs(1);
+        let permit = limiter.acquire(&read(), timeout).await.unwrap();
+        assert_eq!(
+            limiter.acquire(&read(), timeout).await.err(),
+            Some(StatusCode::BadTooManyOperations)
+        );
+        // Publish requests are not limited.
+        let publish: RequestMessage = PublishRequest::default().into();
+        limiter.acquire(&publish, timeout).await.unwrap();
+
+        drop(permit);
+        limiter.acquire(&read(), timeout).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn queue_when_full() {
+        let limiter = limiter(RequestOverflow::Queue);
+        let permit = limiter
+            .acquire(&read(), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            limiter
+                .acquire(&read(), Duration::from_millis(50))
+                .await
+                .err(),
+            Some(StatusCode::BadTimeout)
+        );
+
+        // A queued request is sent once the request in flight completes, with what
+        // remains of its timeout.
+        let timeout = Duration::from_secs(5);
+        let request = read();
+        let (queued, _) = tokio::join!(limiter.acquire(&request, timeout), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(permit);
+        });
+        let queued = queued.unwrap();
+        assert!(queued.timeout < timeout);
+        assert!(queued.timeout > Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn no_limit_by_default() {
+        let limiter = RequestLimiter::default();
+        let timeout = Duration::from_secs(1);
+        let _permits = [
+            limiter.acquire(&read(), timeout).await.unwrap(),
+            limiter.acquire(&read(), timeout).await.unwrap(),
+        ];
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use opcua_core::comms::secure_channel::SecureChannel;
+use opcua_types::{EndpointDescription, Error, StatusCode};
+use parking_lot::RwLock;
+use tokio::{io::DuplexStream, sync::mpsc};
+use tracing::{debug, error};
+
+use super::connect::{ConnectedTransport, Connector};
+use super::core::OutgoingMessage;
+use super::tcp::{TcpTransport, TransportConfiguration};
+use crate::ConnectionSource;
+
+/// Default size of the in-memory buffer in each direction of a connection.
+const DEFAULT_BUFFER_SIZE: usize = 65536;
+
+/// Connector for an in-memory transport, OPC-UA binary framing over a tokio
+/// [`DuplexStream`] instead of a socket.
+///
+/// Each connection creates a new duplex stream, and hands the server end to the
+/// [`MemoryListener`] created together with the connector. This lets tests connect
+/// a client to a server, or a [`ScriptedResponder`](super::ScriptedResponder), running
+/// in the same process, without binding any sockets.
+///
+/// The connector is also a [`ConnectionSource`], so it can be passed directly to
+/// [`SessionBuilder::with_connector`](crate::SessionBuilder::with_connector).
+#[derive(Clone)]
+pub struct MemoryConnector {
+    endpoint_url: String,
+    buffer_size: usize,
+    streams: mpsc::UnboundedSender<DuplexStream>,
+}
+
+/// Server end of an in-memory transport, receiving a stream for each connection made
+/// by the matching [`MemoryConnector`].
+pub struct MemoryListener {
+    streams: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+impl MemoryConnector {
+    /// Create a new in-memory connector and the listener receiving its connections.
+    ///
+    /// `endpoint_url` is sent to the server in the HELLO message, and used as the
+    /// URL of the default endpoint, but is not otherwise used to connect.
+    pub fn new(endpoint_url: &str) -> (Self, MemoryListener) {
+        let (send, recv) = mpsc::unbounded_channel();
+        (
+            Self {
+                endpoint_url: endpoint_url.to_owned(),
+                buffer_size: DEFAULT_BUFFER_SIZE,
+                streams: send,
+            },
+            MemoryListener { streams: recv },
+        )
+    }
+
+    /// Set the number of bytes that can be written in each direction before the
+    /// writer has to wait for the other end to read. Defaults to 64 KiB.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+}
+
+impl MemoryListener {
+    /// Wait for the next connection, returning the server end of its stream.
+    ///
+    /// Returns `None` once every connector has been dropped.
+    pub async fn accept(&mut self) -> Option<DuplexStream> {
+        self.streams.recv().await
+    }
+}
+
+#[async_trait]
+impl Connector for MemoryConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+    ) -> Result<ConnectedTransport, StatusCode> {
+        debug!("Connecting in memory with url {}", self.endpoint_url);
+        let (client, server) = tokio::io::duplex(self.buffer_size);
+        if self.streams.send(server).is_err() {
+            error!(
+                "Could not connect to {}, the listener is closed",
+                self.endpoint_url
+            );
+            return Err(StatusCode::BadCommunicationError);
+        }
+        TcpTransport::connect_stream(
+            client.into(),
+            channel,
+            outgoing_recv,
+            config,
+            &self.endpoint_url,
+        )
+        .await
+        .map(Into::into)
+    }
+
+    fn default_endpoint(&self) -> EndpointDescription {
+        EndpointDescription::from(self.endpoint_url.as_str())
+    }
+}
+
+impl ConnectionSource for MemoryConnector {
+    type Builder = MemoryConnector;
+
+    fn get_connector(&self, _endpoint: &EndpointDescription) -> Result<Self::Builder, Error> {
+        Ok(self.clone())
+    }
+}
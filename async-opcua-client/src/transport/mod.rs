@@ -3,11 +3,28 @@
 mod channel;
 mod connect;
 mod core;
+#[cfg(feature = "https")]
+mod https;
+mod limiter;
+mod memory;
+mod paths;
+mod responder;
+mod retry;
+mod reverse;
 mod state;
 pub(super) mod tcp;
+mod uds;
 
 pub use channel::{AsyncSecureChannel, SecureChannelEventLoop};
 pub use connect::{Connector, ConnectorBuilder, Transport};
 pub(crate) use core::OutgoingMessage;
 pub use core::TransportPollResult;
+#[cfg(feature = "https")]
+pub use https::HttpsConnector;
+pub(crate) use limiter::RequestLimiter;
+pub use memory::{MemoryConnector, MemoryListener};
+pub use responder::ScriptedResponder;
+pub(crate) use retry::{is_session_error, SessionRecovery};
+pub use reverse::{ReverseConnectListener, ReverseConnector};
 pub use tcp::TcpConnector;
+pub use uds::UdsConnector;
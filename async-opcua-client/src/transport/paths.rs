@@ -0,0 +1,216 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use opcua_core::{sync::Mutex, trace_lock};
+
+use crate::ConnectionPathOptions;
+
+use super::connect::Connector;
+
+#[derive(Default)]
+struct PathHealth {
+    consecutive_failures: u32,
+    demoted_until: Option<Instant>,
+}
+
+struct ConnectionPath {
+    connector: Box<dyn Connector>,
+    health: Mutex<PathHealth>,
+}
+
+/// The network paths a secure channel connects to the server over, in order of
+/// priority. The first path is the primary path, the others are alternate paths
+/// to the same server.
+///
+/// Paths are tried in order of priority, except that a path that failed
+/// `demote_after_failures` times in a row is tried after all other paths until its
+/// demotion period has passed.
+pub(crate) struct ConnectionPaths {
+    paths: Vec<ConnectionPath>,
+    options: ConnectionPathOptions,
+    /// Priority of the path of the last successful connection.
+    active: AtomicUsize,
+    /// Priority of the path last returned from `take_path_change`.
+    reported: AtomicUsize,
+}
+
+impl ConnectionPaths {
+    pub(crate) fn new(primary: Box<dyn Connector>) -> Self {
+        Self {
+            paths: vec![ConnectionPath {
+                connector: primary,
+                health: Mutex::default(),
+            }],
+            options: ConnectionPathOptions::default(),
+            active: AtomicUsize::new(0),
+            reported: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add alternate paths, tried after the paths already added.
+    pub(crate) fn add_alternates(
+        &mut self,
+        alternates: Vec<Box<dyn Connector + Send + Sync>>,
+        options: ConnectionPathOptions,
+    ) {
+        self.paths
+            .extend(alternates.into_iter().map(|connector| ConnectionPath {
+                connector,
+                health: Mutex::default(),
+            }));
+        self.options = options;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub(crate) fn connector(&self, priority: usize) -> &dyn Connector {
+        &*self.paths[priority].connector
+    }
+
+    /// Get the priorities of the paths in the order they should be tried.
+    pub(crate) fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (healthy, demoted): (Vec<_>, Vec<_>) = (0..self.paths.len()).partition(|idx| {
+            trace_lock!(self.paths[*idx].health)
+                .demoted_until
+                .is_none_or(|until| until <= now)
+        });
+        healthy.into_iter().chain(demoted).collect()
+    }
+
+    pub(crate) fn on_connected(&self, priority: usize) {
+        let mut health = trace_lock!(self.paths[priority].health);
+        health.consecutive_failures = 0;
+        health.demoted_until = None;
+        self.active.store(priority, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_failed(&self, priority: usize) {
+        let mut health = trace_lock!(self.paths[priority].health);
+        health.consecutive_failures += 1;
+        // The count is kept, so a demoted path that fails again once its demotion
+        // period has passed is demoted again right away.
+        if self.options.demote_after_failures > 0
+            && health.consecutive_failures >= self.options.demote_after_failures
+        {
+            health.demoted_until = Some(Instant::now() + self.options.demotion_period);
+        }
+    }
+
+    /// Get the priority of the path of the last successful connection.
+    pub(crate) fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Get the priority of the path of the last successful connection, if it
+    /// changed since this was last called. Before the first call the primary
+    /// path is assumed.
+    pub(crate) fn take_path_change(&self) -> Option<usize> {
+        let active = self.active();
+        let reported = self.reported.swap(active, Ordering::Relaxed);
+        (reported != active).then_some(active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use opcua_core::{comms::secure_channel::SecureChannel, sync::RwLock};
+    use opcua_types::{EndpointDescription, StatusCode};
+
+    use crate::{
+        transport::{
+            connect::ConnectedTransport, tcp::TransportConfiguration, Connector, OutgoingMessage,
+        },
+        ConnectionPathOptions,
+    };
+
+    use super::ConnectionPaths;
+
+    struct Unreachable;
+
+    #[async_trait]
+    impl Connector for Unreachable {
+        async fn connect(
+            &self,
+            _channel: Arc<RwLock<SecureChannel>>,
+            _outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+            _config: TransportConfiguration,
+        ) -> Result<ConnectedTransport, StatusCode> {
+            Err(StatusCode::BadNotConnected)
+        }
+
+        fn default_endpoint(&self) -> EndpointDescription {
+            EndpointDescription::default()
+        }
+    }
+
+    fn paths(options: ConnectionPathOptions) -> ConnectionPaths {
+        let mut paths = ConnectionPaths::new(Box::new(Unreachable));
+        paths.add_alternates(vec![Box::new(Unreachable), Box::new(Unreachable)], options);
+        paths
+    }
+
+    #[test]
+    fn demote_failing_paths() {
+        let paths = paths(ConnectionPathOptions {
+            demote_after_failures: 2,
+            demotion_period: Duration::from_secs(60),
+        });
+        assert_eq!(paths.order(), vec![0, 1, 2]);
+
+        paths.on_failed(0);
+        assert_eq!(paths.order(), vec![0, 1, 2]);
+        paths.on_failed(0);
+        assert_eq!(paths.order(), vec![1, 2, 0]);
+        paths.on_failed(1);
+        paths.on_failed(1);
+        assert_eq!(paths.order(), vec![2, 0, 1]);
+
+        // A successful connection restores the path.
+        paths.on_connected(0);
+        assert_eq!(paths.order(), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn demotion_expires() {
+        let paths = paths(ConnectionPathOptions {
+            demote_after_failures: 1,
+            demotion_period: Duration::ZERO,
+        });
+        paths.on_failed(0);
+        assert_eq!(paths.order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn never_demote() {
+        let paths = paths(ConnectionPathOptions {
+            demote_after_failures: 0,
+            ..Default::default()
+        });
+        for _ in 0..10 {
+            paths.on_failed(0);
+        }
+        assert_eq!(paths.order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn path_changes() {
+        let paths = paths(ConnectionPathOptions::default());
+        assert_eq!(paths.take_path_change(), None);
+        paths.on_connected(0);
+        assert_eq!(paths.take_path_change(), None);
+        paths.on_connected(2);
+        assert_eq!(paths.active(), 2);
+        assert_eq!(paths.take_path_change(), Some(2));
+        assert_eq!(paths.take_path_change(), None);
+        paths.on_connected(0);
+        assert_eq!(paths.take_path_change(), Some(0));
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use futures::StreamExt;
+use opcua_core::{
+    comms::{
+        buffer::SendBuffer,
+        chunker::Chunker,
+        message_chunk::{MessageChunk, MessageIsFinalType},
+        secure_channel::SecureChannel,
+        stream::TransportStream,
+        tcp_codec::{Message, TcpCodec},
+        tcp_types::AcknowledgeMessage,
+    },
+    RequestMessage, ResponseMessage,
+};
+use opcua_crypto::SecurityPolicy;
+use opcua_types::{
+    ActivateSessionResponse, ChannelSecurityToken, CloseSessionResponse, CreateSessionResponse,
+    DateTime, EndpointDescription, MessageSecurityMode, NodeId, OpenSecureChannelResponse,
+    ResponseHeader, ServiceFault, StatusCode, UserTokenPolicy,
+};
+use parking_lot::Mutex;
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio_util::codec::FramedRead;
+use tracing::{debug, warn};
+
+use super::MemoryListener;
+
+type Handler = Box<dyn FnMut(&RequestMessage) -> Option<ResponseMessage> + Send>;
+
+#[derive(Default)]
+struct ResponderState {
+    handlers: Vec<Handler>,
+    once: VecDeque<Handler>,
+    requests: Vec<RequestMessage>,
+}
+
+/// A lightweight OPC-UA server for unit tests, answering requests from a script
+/// of handlers instead of an address space.
+///
+/// The responder takes care of the HELLO handshake, secure channels and sessions,
+/// so a client can connect to it as to a normal server. Every other request is
+/// passed to the registered handlers, and the response of the first handler that
+/// returns one is sent back. Requests that no handler answers get a service fault
+/// with `BadServiceUnsupported`. Sessions only accept anonymous users by default,
+/// handlers can also answer the requests handled by the responder, for example to
+/// offer other user token policies in `CreateSession`, or to make `ActivateSession` fail.
+///
+/// Only `SecurityPolicy::None` is supported. Together with [`MemoryConnector`](super::MemoryConnector)
+/// this makes it possible to test client logic without binding any sockets.
+///
+/// The responder is cheap to clone, clones share handlers and received requests.
+#[derive(Clone, Default)]
+pub struct ScriptedResponder {
+    state: Arc<Mutex<ResponderState>>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl ScriptedResponder {
+    /// Create a new responder without any handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a handler that is called for every request not answered by an earlier
+    /// handler. The handler returns `None` for requests it does not answer.
+    pub fn on(
+        self,
+        handler: impl FnMut(&RequestMessage) -> Option<ResponseMessage> + Send + 'static,
+    ) -> Self {
+        self.state.lock().handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Add a handler that answers a single request. Handlers added with this method
+    /// are tried before those added with [`ScriptedResponder::on`], and are removed
+    /// once they return a response, so they can be used to script a sequence of
+    /// responses to the same service.
+    pub fn once(
+        self,
+        handler: impl FnMut(&RequestMessage) -> Option<ResponseMessage> + Send + 'static,
+    ) -> Self {
+        self.state.lock().once.push_back(Box::new(handler));
+        self
+    }
+
+    /// Get the requests received so far, in order, including requests handled by
+    /// the responder itself.
+    pub fn requests(&self) -> Vec<RequestMessage> {
+        self.state.lock().requests.clone()
+    }
+
+    /// Serve every connection accepted by `listener`, until all its connectors are
+    /// dropped. Each connection is served on a separate task.
+    pub async fn serve(&self, mut listener: MemoryListener) {
+        while let Some(stream) = listener.accept().await {
+            let responder = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = responder.serve_stream(stream).await {
+                    debug!("Scripted responder connection closed: {e}");
+                }
+            });
+        }
+    }
+
+    /// Serve a single connection until the client closes it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The server end of the connection, for example from a [`MemoryListener`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The client closed the secure channel.
+    /// * `Err(StatusCode)` - The connection failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn serve_stream(&self, stream: impl Into<TransportStream>) -> Result<(), StatusCode> {
+        let mut channel = SecureChannel::new_no_certificate_store();
+        let (read, mut write) = tokio::io::split(stream.into());
+        let mut read = FramedRead::new(read, TcpCodec::new(channel.decoding_options()));
+
+        let Some(Ok(Message::Hello(hello))) = read.next().await else {
+            warn!("Scripted responder expected a HELLO message");
+            return Err(StatusCode::BadCommunicationError);
+        };
+        let ack =
+            AcknowledgeMessage::new(0, hello.send_buffer_size, hello.receive_buffer_size, 0, 0);
+        let mut buffer = SendBuffer::new(
+            ack.send_buffer_size as usize,
+            hello.max_message_size as usize,
+            hello.max_chunk_count as usize,
+            true,
+        );
+        buffer.write_ack(ack);
+        flush(&mut buffer, &channel, &mut write).await?;
+
+        let mut chunks = Vec::new();
+        loop {
+            let Some((request_id, request)) =
+                read_request(&mut read, &mut channel, &mut chunks).await?
+            else {
+                continue;
+            };
+            if matches!(request, RequestMessage::CloseSecureChannel(_)) {
+                self.state.lock().requests.push(request);
+                return Ok(());
+            }
+            let response = self.respond(&request);
+            if let ResponseMessage::OpenSecureChannel(response) = &response {
+                channel.set_security_token(response.security_token.clone());
+            }
+            buffer
+                .write(request_id, response, &channel)
+                .map_err(|e| e.status())?;
+            flush(&mut buffer, &channel, &mut write).await?;
+        }
+    }
+
+    fn respond(&self, request: &RequestMessage) -> ResponseMessage {
+        let mut state = self.state.lock();
+        state.requests.push(request.clone());
+
+        let answered = state
+            .once
+            .iter_mut()
+            .enumerate()
+            .find_map(|(idx, handler)| handler(request).map(|r| (idx, r)));
+        if let Some((idx, response)) = answered {
+            state.once.remove(idx);
+            return response;
+        }
+        for handler in state.handlers.iter_mut() {
+            if let Some(response) = handler(request) {
+                return response;
+            }
+        }
+        drop(state);
+        self.default_response(request)
+    }
+
+    fn default_response(&self, request: &RequestMessage) -> ResponseMessage {
+        let header = ResponseHeader::new_good(request.request_header());
+        match request {
+            RequestMessage::OpenSecureChannel(r) => OpenSecureChannelResponse {
+                response_header: header,
+                server_protocol_version: 0,
+                security_token: ChannelSecurityToken {
+                    channel_id: 1,
+                    token_id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+                    created_at: DateTime::now(),
+                    revised_lifetime: r.requested_lifetime,
+                },
+                server_nonce: Default::default(),
+            }
+            .into(),
+            RequestMessage::CreateSession(r) => {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+                CreateSessionResponse {
+                    response_header: header,
+                    session_id: NodeId::new(1, id),
+                    authentication_token: NodeId::new(1, format!("token-{id}")),
+                    revised_session_timeout: r.requested_session_timeout,
+                    server_endpoints: Some(vec![EndpointDescription::from((
+                        r.endpoint_url.as_ref(),
+                        SecurityPolicy::None.to_uri(),
+                        MessageSecurityMode::None,
+                        UserTokenPolicy::anonymous(),
+                    ))]),
+                    ..Default::default()
+                }
+                .into()
+            }
+            RequestMessage::ActivateSession(_) => ActivateSessionResponse {
+                response_header: header,
+                ..Default::default()
+            }
+            .into(),
+            RequestMessage::CloseSession(_) => CloseSessionResponse {
+                response_header: header,
+            }
+            .into(),
+            _ => ServiceFault::new(request.request_header(), StatusCode::BadServiceUnsupported)
+                .into(),
+        }
+    }
+}
+
+/// Read chunks until a full request is received. Returns `None` if the client
+/// aborted the message.
+async fn read_request(
+    read: &mut FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+    channel: &mut SecureChannel,
+    chunks: &mut Vec<MessageChunk>,
+) -> Result<Option<(u32, RequestMessage)>, StatusCode> {
+    loop {
+        let chunk = match read.next().await {
+            Some(Ok(Message::Chunk(chunk))) => chunk,
+            Some(Ok(other)) => {
+                warn!("Scripted responder received unexpected message {other:?}");
+                return Err(StatusCode::BadUnexpectedError);
+            }
+            Some(Err(_)) | None => return Err(StatusCode::BadConnectionClosed),
+        };
+        let header = chunk
+            .message_header(&channel.decoding_options())
+            .map_err(|e| e.status())?;
+        let chunk = channel.remove_security(chunk).map_err(|e| e.status())?;
+        match header.is_final {
+            MessageIsFinalType::Intermediate => chunks.push(chunk),
+            MessageIsFinalType::FinalError => {
+                chunks.clear();
+                return Ok(None);
+            }
+            MessageIsFinalType::Final => {
+                chunks.push(chunk);
+                let request_id = chunks[0]
+                    .chunk_info(channel)
+                    .map_err(|e| e.status())?
+                    .sequence_header
+                    .request_id;
+                let request = Chunker::decode_owned(std::mem::take(chunks), channel, None)
+                    .map_err(|e| e.status());
+                return Ok(Some((request_id, request?)));
+            }
+        }
+    }
+}
+
+/// Write everything queued in `buffer` to the stream.
+async fn flush(
+    buffer: &mut SendBuffer,
+    channel: &SecureChannel,
+    write: &mut WriteHalf<TransportStream>,
+) -> Result<(), StatusCode> {
+    loop {
+        if buffer.should_encode_chunks() {
+            buffer.wait_for_stream().await;
+            buffer.encode_next_chunk(channel)?;
+        }
+        if !buffer.can_read() {
+            return Ok(());
+        }
+        buffer
+            .read_into_async(write)
+            .await
+            .map_err(|_| StatusCode::BadConnectionClosed)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_core::{RequestMessage, ResponseMessage};
+    use opcua_types::{
+        DataValue, NodeId, ReadResponse, ReadValueId, ResponseHeader, StatusCode,
+        TimestampsToReturn, VariableId,
+    };
+
+    use super::ScriptedResponder;
+    use crate::{transport::MemoryConnector, ClientBuilder, Session};
+
+    fn read_service_level(request: &RequestMessage, value: DataValue) -> Option<ResponseMessage> {
+        let RequestMessage::Read(request) = request else {
+            return None;
+        };
+        let node_id: NodeId = VariableId::Server_ServiceLevel.into();
+        if request.nodes_to_read.as_deref()?.first()?.node_id != node_id {
+            return None;
+        }
+        Some(
+            ReadResponse {
+                response_header: ResponseHeader::new_good(&request.request_header),
+                results: Some(vec![value]),
+                diagnostic_infos: None,
+            }
+            .into(),
+        )
+    }
+
+    async fn read_service_level_value(session: &Session) -> DataValue {
+        session
+            .read(
+                &[ReadValueId::new_value(
+                    VariableId::Server_ServiceLevel.into(),
+                )],
+                TimestampsToReturn::Neither,
+                0.0,
+            )
+            .await
+            .unwrap()
+            .remove(0)
+    }
+
+    #[tokio::test]
+    async fn read_from_scripted_responder() {
+        let url = "opc.tcp://memory:4840/";
+        let (connector, listener) = MemoryConnector::new(url);
+        let responder = ScriptedResponder::new()
+            .once(|r| {
+                read_service_level(
+                    r,
+                    DataValue {
+                        status: Some(StatusCode::BadNodeIdUnknown),
+                        ..Default::default()
+                    },
+                )
+            })
+            .on(|r| read_service_level(r, DataValue::new_now(200u8)));
+        tokio::spawn({
+            let responder = responder.clone();
+            async move { responder.serve(listener).await }
+        });
+
+        let client = ClientBuilder::new()
+            .application_name("Memory client")
+            .application_uri("urn:MemoryClient")
+            .pki_dir(std::env::temp_dir().join("opcua-memory-transport-pki"))
+            .client()
+            .unwrap();
+        let (session, event_loop) = client
+            .session_builder()
+            .connect_to_endpoint_directly(url)
+            .unwrap()
+            .with_connector(connector)
+            .build(client.certificate_store().clone())
+            .unwrap();
+        let handle = event_loop.spawn();
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), session.wait_for_connection())
+                .await
+                .unwrap()
+        );
+
+        // The first read gets the scripted error, later reads the value.
+        assert_eq!(
+            read_service_level_value(&session).await.status,
+            Some(StatusCode::BadNodeIdUnknown)
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                read_service_level_value(&session).await.value,
+                Some(200u8.into())
+            );
+        }
+
+        // Services without a handler get a service fault.
+        let err = session
+            .browse(&[Default::default()], 0, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, StatusCode::BadServiceUnsupported);
+
+        session.disconnect().await.unwrap();
+        handle.await.unwrap();
+
+        let requests = responder.requests();
+        assert!(matches!(requests[0], RequestMessage::OpenSecureChannel(_)));
+        assert!(matches!(requests[1], RequestMessage::CreateSession(_)));
+        assert!(matches!(requests[2], RequestMessage::ActivateSession(_)));
+        // A keep-alive read may still arrive after the session is closed.
+        assert!(requests
+            .iter()
+            .any(|r| matches!(r, RequestMessage::CloseSession(_))));
+        assert!(matches!(
+            requests.last().unwrap(),
+            RequestMessage::CloseSecureChannel(_)
+        ));
+    }
+}
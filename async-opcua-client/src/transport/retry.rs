@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use opcua_core::RequestMessage;
+use opcua_types::{NodeId, StatusCode};
+
+use crate::{retry::ExponentialBackoff, ServiceRetryOptions};
+
+/// Restores the session a channel belongs to, so that a failed request can be retried.
+#[async_trait]
+pub(crate) trait SessionRecovery: Send + Sync {
+    /// Called after a request sent with `auth_token` failed with `status`. Returns once
+    /// the request may be sent again, or with an error if the session could not be
+    /// restored within `timeout`.
+    async fn recover(
+        &self,
+        status: StatusCode,
+        auth_token: &NodeId,
+        timeout: Duration,
+    ) -> Result<(), StatusCode>;
+}
+
+/// Whether `status` means the server no longer accepts the session of the request.
+pub(crate) fn is_session_error(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BadSessionIdInvalid
+            | StatusCode::BadSessionClosed
+            | StatusCode::BadSessionNotActivated
+    )
+}
+
+/// Whether a request failing with `status` may succeed if sent again, once the session
+/// is restored or the client has reconnected.
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    is_session_error(status)
+        || matches!(
+            status,
+            StatusCode::BadNotConnected
+                | StatusCode::BadConnectionClosed
+                | StatusCode::BadDisconnect
+                | StatusCode::BadEndOfStream
+                | StatusCode::BadCommunicationError
+                | StatusCode::BadServerNotConnected
+                | StatusCode::BadSecureChannelClosed
+                | StatusCode::BadSecureChannelIdInvalid
+        )
+}
+
+/// Whether `request` may be retried according to `options`. Requests managing the
+/// channel or session, and publish requests, which are managed by the session event
+/// loop, are never retried.
+pub(crate) fn is_retryable_request(
+    request: &RequestMessage,
+    options: &ServiceRetryOptions,
+) -> bool {
+    !matches!(
+        request,
+        RequestMessage::OpenSecureChannel(_)
+            | RequestMessage::CloseSecureChannel(_)
+            | RequestMessage::CreateSession(_)
+            | RequestMessage::ActivateSession(_)
+            | RequestMessage::CloseSession(_)
+            | RequestMessage::Cancel(_)
+            | RequestMessage::Publish(_)
+    ) && options.should_retry(request.type_name())
+}
+
+/// Delays between retries of a single request.
+pub(crate) fn retry_backoff(options: &ServiceRetryOptions) -> ExponentialBackoff {
+    ExponentialBackoff::new(
+        options.max_delay,
+        Some(options.max_retries),
+        options.initial_delay,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_core::RequestMessage;
+    use opcua_types::{
+        ActivateSessionRequest, CallRequest, HistoryUpdateRequest, ReadRequest, StatusCode,
+    };
+
+    use super::{is_retryable_request, is_retryable_status};
+    use crate::ServiceRetryOptions;
+
+    #[test]
+    fn retryable_requests() {
+        let read: RequestMessage = ReadRequest::default().into();
+        let call: RequestMessage = CallRequest::default().into();
+        let history_update: RequestMessage = HistoryUpdateRequest::default().into();
+
+        // Retries are disabled by default.
+        let mut options = ServiceRetryOptions::default();
+        assert!(!is_retryable_request(&read, &options));
+
+        options.max_retries = 3;
+        assert!(is_retryable_request(&read, &options));
+        assert!(!is_retryable_request(&call, &options));
+
+        options.services.push("Call".to_owned());
+        assert!(is_retryable_request(&call, &options));
+
+        // Services that are never retried win over services to retry.
+        options.services.push("HistoryUpdate".to_owned());
+        assert!(!is_retryable_request(&history_update, &options));
+
+        options.services.push("ActivateSession".to_owned());
+        let activate: RequestMessage = ActivateSessionRequest::default().into();
+        assert!(!is_retryable_request(&activate, &options));
+    }
+
+    #[test]
+    fn retryable_status() {
+        assert!(is_retryable_status(StatusCode::BadSessionIdInvalid));
+        assert!(is_retryable_status(StatusCode::BadNotConnected));
+        assert!(!is_retryable_status(StatusCode::BadNodeIdUnknown));
+        assert!(!is_retryable_status(StatusCode::BadTimeout));
+        assert!(!is_retryable_status(StatusCode::Good));
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use opcua_core::comms::{
+    secure_channel::SecureChannel,
+    socket::SocketOptions,
+    tcp_types::{
+        ErrorMessage, MessageHeader, MessageType, ReverseHelloMessage, MESSAGE_HEADER_LEN,
+    },
+};
+use opcua_types::{
+    DecodingOptions, EndpointDescription, Error, SimpleBinaryDecodable, SimpleBinaryEncodable,
+    StatusCode,
+};
+use parking_lot::{Mutex, RwLock};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+    task::JoinHandle,
+};
+use tracing::{debug, error, warn};
+
+use super::connect::{ConnectedTransport, Connector};
+use super::core::OutgoingMessage;
+use super::tcp::{TcpTransport, TransportConfiguration};
+use crate::ConnectionSource;
+
+/// Maximum size of a REVERSE HELLO message, a header and two strings of at most 4096 bytes.
+const MAX_REVERSE_HELLO_SIZE: usize = MESSAGE_HEADER_LEN + 2 * (4 + 4096);
+
+/// Listener for reverse connections, where the server opens the TCP connection to
+/// the client and sends a REVERSE HELLO message, identifying itself by its application
+/// URI and endpoint URL. The client then uses the connection for the normal
+/// HELLO/ACKNOWLEDGE exchange, secure channel, and session establishment.
+///
+/// This is useful when the server is behind a firewall that does not allow
+/// incoming connections.
+///
+/// Connections are only accepted from servers that a session is created for. The listener
+/// is a [`ConnectionSource`], pass it to
+/// [`SessionBuilder::with_connector`](crate::SessionBuilder::with_connector) and each
+/// session will wait for a connection from the server matching the application URI of
+/// the server and the URL of its endpoint. When the connection is lost, the session
+/// waits for the server to dial the client again.
+///
+/// The listener is cheap to clone, and stops listening once it and every connector
+/// created from it has been dropped.
+#[derive(Clone)]
+pub struct ReverseConnectListener {
+    inner: Arc<ListenerInner>,
+}
+
+struct ListenerInner {
+    local_addr: SocketAddr,
+    servers: Mutex<Vec<Weak<ServerSlot>>>,
+    socket_options: SocketOptions,
+    timeouts: Mutex<Timeouts>,
+    accept: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Clone, Copy)]
+struct Timeouts {
+    reverse_hello: Duration,
+    wait: Duration,
+}
+
+/// A server that the client accepts reverse connections from, and the latest
+/// connection received from it that has not been used yet.
+struct ServerSlot {
+    server_uri: Option<String>,
+    endpoint_url: Option<String>,
+    pending: Mutex<Option<PendingConnection>>,
+    notify: Notify,
+    waiting: AtomicUsize,
+}
+
+struct PendingConnection {
+    stream: TcpStream,
+    endpoint_url: String,
+}
+
+impl ServerSlot {
+    fn matches(&self, hello: &ReverseHelloMessage) -> bool {
+        self.server_uri
+            .as_ref()
+            .is_none_or(|uri| uri == hello.server_uri.as_ref())
+            && self
+                .endpoint_url
+                .as_ref()
+                .is_none_or(|url| urls_match(url, hello.endpoint_url.as_ref()))
+    }
+}
+
+fn urls_match(url1: &str, url2: &str) -> bool {
+    url1.trim_end_matches('/')
+        .eq_ignore_ascii_case(url2.trim_end_matches('/'))
+}
+
+impl ReverseConnectListener {
+    /// Start listening for reverse connections on `addr`. Must be called from within
+    /// a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Local address to listen on.
+    /// * `socket_options` - Options applied to the listening socket and accepted connections.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReverseConnectListener)` - The listener, accepting connections in the background.
+    /// * `Err(Error)` - The address could not be bound.
+    ///
+    pub fn bind(addr: SocketAddr, socket_options: SocketOptions) -> Result<Self, Error> {
+        let listener = socket_options.listen(addr).map_err(|e| {
+            Error::new(
+                StatusCode::BadCommunicationError,
+                format!("Failed to listen for reverse connections on {addr}: {e}"),
+            )
+        })?;
+        let local_addr = listener.local_addr().map_err(|e| {
+            Error::new(
+                StatusCode::BadCommunicationError,
+                format!("Failed to get the local address of the listener: {e}"),
+            )
+        })?;
+        let inner = Arc::new(ListenerInner {
+            local_addr,
+            servers: Mutex::new(Vec::new()),
+            socket_options,
+            timeouts: Mutex::new(Timeouts {
+                reverse_hello: Duration::from_secs(10),
+                wait: Duration::from_secs(60),
+            }),
+            accept: Mutex::new(None),
+        });
+        let task = tokio::spawn(Self::accept_loop(listener, Arc::downgrade(&inner)));
+        *inner.accept.lock() = Some(task);
+        Ok(Self { inner })
+    }
+
+    /// Set the time a server has to send the REVERSE HELLO message after connecting,
+    /// before the connection is closed. Defaults to 10 seconds.
+    pub fn reverse_hello_timeout(self, timeout: Duration) -> Self {
+        self.inner.timeouts.lock().reverse_hello = timeout;
+        self
+    }
+
+    /// Set the time a connector waits for the server to connect before failing with
+    /// `BadTimeout`, after which the session retry policy decides whether to keep waiting.
+    /// Defaults to 60 seconds.
+    pub fn wait_timeout(self, timeout: Duration) -> Self {
+        self.inner.timeouts.lock().wait = timeout;
+        self
+    }
+
+    /// Get the local address the listener is bound to, which is the address
+    /// servers must be configured to connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.inner.local_addr
+    }
+
+    /// Create a connector that waits for a reverse connection from the server of `endpoint`.
+    ///
+    /// Connections are matched by the application URI of the server and the URL of the
+    /// endpoint, either is ignored if it is empty.
+    pub fn connector(&self, endpoint: &EndpointDescription) -> ReverseConnector {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_owned());
+        let slot = Arc::new(ServerSlot {
+            server_uri: non_empty(endpoint.server.application_uri.as_ref()),
+            endpoint_url: non_empty(endpoint.endpoint_url.as_ref()),
+            pending: Mutex::new(None),
+            notify: Notify::new(),
+            waiting: AtomicUsize::new(0),
+        });
+        let mut servers = self.inner.servers.lock();
+        servers.retain(|s| s.strong_count() > 0);
+        servers.push(Arc::downgrade(&slot));
+        ReverseConnector {
+            listener: self.clone(),
+            slot,
+            endpoint_url: endpoint.endpoint_url.as_ref().to_owned(),
+        }
+    }
+
+    async fn accept_loop(listener: TcpListener, inner: Weak<ListenerInner>) {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(r) => r,
+                Err(e) => {
+                    error!("Failed to accept reverse connection: {e}");
+                    // Errors like running out of file descriptors are likely to persist
+                    // for a while, so avoid spinning.
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            inner.socket_options.apply_or_warn(&stream);
+            tokio::spawn(async move {
+                inner.handle_connection(stream, addr).await;
+            });
+        }
+    }
+}
+
+impl ListenerInner {
+    async fn handle_connection(&self, mut stream: TcpStream, addr: SocketAddr) {
+        let timeout = self.timeouts.lock().reverse_hello;
+        let hello = match tokio::time::timeout(timeout, read_reverse_hello(&mut stream)).await {
+            Ok(Ok(hello)) => hello,
+            Ok(Err(e)) => {
+                warn!("Invalid reverse hello from {addr}: {e}");
+                send_error(&mut stream, e).await;
+                return;
+            }
+            Err(_) => {
+                warn!("Timed out after {timeout:?} waiting for reverse hello from {addr}");
+                send_error(&mut stream, StatusCode::BadTimeout).await;
+                return;
+            }
+        };
+        debug!(
+            "Received reverse hello from {addr}, server {}, endpoint {}",
+            hello.server_uri, hello.endpoint_url
+        );
+
+        let Some(slot) = self.find_server(&hello) else {
+            warn!(
+                "Rejected reverse connection from unknown server {} with endpoint {}",
+                hello.server_uri, hello.endpoint_url
+            );
+            send_error(&mut stream, StatusCode::BadTcpEndpointUrlInvalid).await;
+            return;
+        };
+        // Replace any connection that has not been used yet, the server will have
+        // given up on it.
+        *slot.pending.lock() = Some(PendingConnection {
+            stream,
+            endpoint_url: hello.endpoint_url.as_ref().to_owned(),
+        });
+        slot.notify.notify_one();
+    }
+
+    /// Find the server a reverse hello is from, preferring a connector that
+    /// is waiting for a connection.
+    fn find_server(&self, hello: &ReverseHelloMessage) -> Option<Arc<ServerSlot>> {
+        let mut servers = self.servers.lock();
+        servers.retain(|s| s.strong_count() > 0);
+        let matching: Vec<_> = servers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|s| s.matches(hello))
+            .collect();
+        matching
+            .iter()
+            .find(|s| s.waiting.load(Ordering::Relaxed) > 0)
+            .or_else(|| matching.first())
+            .cloned()
+    }
+}
+
+impl Drop for ListenerInner {
+    fn drop(&mut self) {
+        if let Some(task) = self.accept.get_mut().take() {
+            task.abort();
+        }
+    }
+}
+
+async fn read_reverse_hello(stream: &mut TcpStream) -> Result<ReverseHelloMessage, StatusCode> {
+    let mut buf = vec![0u8; MESSAGE_HEADER_LEN];
+    stream
+        .read_exact(&mut buf)
+        .await
+        .map_err(|_| StatusCode::BadConnectionClosed)?;
+    let header = MessageHeader::decode(&mut buf.as_slice(), &DecodingOptions::default())?;
+    if header.message_type != MessageType::ReverseHello {
+        return Err(StatusCode::BadTcpMessageTypeInvalid);
+    }
+    let size = header.message_size as usize;
+    if !(MESSAGE_HEADER_LEN..=MAX_REVERSE_HELLO_SIZE).contains(&size) {
+        return Err(StatusCode::BadTcpMessageTooLarge);
+    }
+    buf.resize(size, 0);
+    stream
+        .read_exact(&mut buf[MESSAGE_HEADER_LEN..])
+        .await
+        .map_err(|_| StatusCode::BadConnectionClosed)?;
+    let hello = ReverseHelloMessage::decode(&mut buf.as_slice(), &DecodingOptions::default())?;
+    if !hello.is_valid() {
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    }
+    Ok(hello)
+}
+
+async fn send_error(stream: &mut TcpStream, status: StatusCode) {
+    let error = ErrorMessage::from_status_code(status);
+    let _ = stream.write_all(&error.encode_to_vec()).await;
+}
+
+/// Connector waiting for a reverse connection from a server, created by
+/// [`ReverseConnectListener::connector`].
+///
+/// Each call to `connect` waits for the server to connect to the listener, and then
+/// sends the HELLO message with the endpoint URL from the REVERSE HELLO message.
+pub struct ReverseConnector {
+    listener: ReverseConnectListener,
+    slot: Arc<ServerSlot>,
+    endpoint_url: String,
+}
+
+impl ReverseConnector {
+    async fn wait_for_connection(&self) -> PendingConnection {
+        loop {
+            if let Some(pending) = self.slot.pending.lock().take() {
+                return pending;
+            }
+            // A notification sent while the lock was released is stored as a permit,
+            // so this cannot miss a connection.
+            self.slot.notify.notified().await;
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for ReverseConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+    ) -> Result<ConnectedTransport, StatusCode> {
+        debug!(
+            "Waiting for reverse connection on {} from {}",
+            self.listener.local_addr(),
+            self.endpoint_url
+        );
+        let timeout = self.listener.inner.timeouts.lock().wait;
+        self.slot.waiting.fetch_add(1, Ordering::Relaxed);
+        let pending = tokio::time::timeout(timeout, self.wait_for_connection()).await;
+        self.slot.waiting.fetch_sub(1, Ordering::Relaxed);
+        let Ok(pending) = pending else {
+            error!(
+                "Timed out after {timeout:?} waiting for reverse connection from {}",
+                self.endpoint_url
+            );
+            return Err(StatusCode::BadTimeout);
+        };
+        TcpTransport::connect_stream(
+            pending.stream.into(),
+            channel,
+            outgoing_recv,
+            config,
+            &pending.endpoint_url,
+        )
+        .await
+        .map(Into::into)
+    }
+
+    fn default_endpoint(&self) -> EndpointDescription {
+        EndpointDescription::from(self.endpoint_url.as_str())
+    }
+}
+
+impl ConnectionSource for ReverseConnectListener {
+    type Builder = ReverseConnector;
+
+    fn get_connector(&self, endpoint: &EndpointDescription) -> Result<Self::Builder, Error> {
+        Ok(self.connector(endpoint))
+    }
+}
@@ -14,8 +14,8 @@ use opcua_core::{
 };
 use opcua_crypto::SecurityPolicy;
 use opcua_types::{
-    DateTime, DiagnosticBits, IntegerId, MessageSecurityMode, NodeId, OpenSecureChannelRequest,
-    RequestHeader, SecurityTokenRequestType, StatusCode,
+    ChannelSecurityToken, DateTime, DiagnosticBits, IntegerId, MessageSecurityMode, NodeId,
+    OpenSecureChannelRequest, RequestHeader, SecurityTokenRequestType, StatusCode,
 };
 
 pub(crate) type RequestSend = tokio::sync::mpsc::Sender<OutgoingMessage>;
@@ -153,7 +153,7 @@ impl SecureChannelState {
     pub(super) fn end_issue_or_renew_secure_channel(
         &self,
         response: ResponseMessage,
-    ) -> Result<(), StatusCode> {
+    ) -> Result<ChannelSecurityToken, StatusCode> {
         if let ResponseMessage::OpenSecureChannel(response) = response {
             // Extract the security token from the response.
             let mut security_token = response.security_token.clone();
@@ -176,18 +176,18 @@ impl SecureChannelState {
             {
                 let mut secure_channel = trace_write_lock!(self.secure_channel);
                 secure_channel.set_client_offset(**self.client_offset.load());
-                secure_channel.set_security_token(security_token);
+                secure_channel.set_security_token(security_token.clone());
 
                 if secure_channel.security_policy() != SecurityPolicy::None
                     && (secure_channel.security_mode() == MessageSecurityMode::Sign
                         || secure_channel.security_mode() == MessageSecurityMode::SignAndEncrypt)
                 {
-                    secure_channel.validate_secure_channel_nonce_length(&response.server_nonce)?;
+                    secure_channel.validate_secure_channel_nonce(&response.server_nonce)?;
                     secure_channel.set_remote_nonce_from_byte_string(&response.server_nonce)?;
                     secure_channel.derive_keys();
                 }
             }
-            Ok(())
+            Ok(security_token)
         } else {
             Err(process_unexpected_response(response))
         }
@@ -210,6 +210,10 @@ impl SecureChannelState {
         self.request_handle.next()
     }
 
+    pub(super) fn auth_token(&self) -> Arc<NodeId> {
+        self.authentication_token.load_full()
+    }
+
     pub(super) fn set_auth_token(&self, token: NodeId) {
         self.authentication_token.store(Arc::new(token));
     }
@@ -1,23 +1,29 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::connect::{Connector, Transport};
-use super::core::{OutgoingMessage, TransportPollResult, TransportState};
+use super::connect::{ConnectedTransport, Connector, Transport};
+use super::core::{AbortQueue, OutgoingMessage, TransportPollResult, TransportState};
+use super::limiter::RequestLimiter;
+use crate::{HttpsOptions, ServiceRetryOptions};
 use async_trait::async_trait;
 use futures::StreamExt;
-use opcua_core::comms::tcp_types::AcknowledgeMessage;
+use opcua_core::comms::tcp_types::{AcknowledgeMessage, ConnectionLimits, MessageLimits};
 use opcua_core::comms::url::is_opc_ua_binary_url;
 use opcua_core::RequestMessage;
 use opcua_core::{
     comms::{
         buffer::SendBuffer,
+        interceptor::MessageInterceptors,
+        metrics::TransportMetricsHandle,
         secure_channel::SecureChannel,
+        socket::SocketOptions,
+        stream::TransportStream,
         tcp_codec::{Message, TcpCodec},
         tcp_types::HelloMessage,
         url::hostname_port_from_url,
     },
-    trace_read_lock,
+    trace_read_lock, trace_write_lock,
 };
-use opcua_crypto::SecurityPolicy;
 use opcua_types::{Error, StatusCode};
 use parking_lot::RwLock;
 use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
@@ -34,8 +40,8 @@ enum TransportCloseState {
 
 pub struct TcpTransport {
     state: TransportState,
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+    write: WriteHalf<TransportStream>,
     send_buffer: SendBuffer,
     should_close: bool,
     closed: TransportCloseState,
@@ -48,6 +54,22 @@ pub struct TransportConfiguration {
     pub recv_buffer_size: usize,
     pub max_message_size: usize,
     pub max_chunk_count: usize,
+    pub max_send_message_size: usize,
+    pub max_send_chunk_count: usize,
+    pub socket_options: SocketOptions,
+    pub hello_timeout: Duration,
+    pub open_secure_channel_timeout: Duration,
+    pub parallel_crypto_threshold: usize,
+    pub metrics: TransportMetricsHandle,
+    pub interceptors: MessageInterceptors,
+    pub https: HttpsOptions,
+    #[cfg(feature = "https")]
+    pub(crate) certificate_store: Arc<RwLock<opcua_crypto::CertificateStore>>,
+    pub(crate) aborts: Arc<AbortQueue>,
+    pub(crate) request_limiter: Arc<RequestLimiter>,
+    pub(crate) service_retry: ServiceRetryOptions,
+    #[cfg(feature = "request-metrics")]
+    pub(crate) request_metrics: crate::metrics::SharedRequestMetrics,
 }
 
 /// Connector for `opc.tcp` transport.
@@ -70,9 +92,88 @@ impl TcpConnector {
         }
     }
 
+    async fn connect_inner(
+        endpoint_url: &str,
+        socket_options: &SocketOptions,
+    ) -> Result<TcpStream, StatusCode> {
+        let (host, port) = hostname_port_from_url(
+            endpoint_url,
+            opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
+        )?;
+        Self::connect_host(&host, port, endpoint_url, socket_options).await
+    }
+
+    /// Resolve `host` and open a TCP connection to it. `endpoint_url` is only
+    /// used for logging.
+    pub(super) async fn connect_host(
+        host: &str,
+        port: u16,
+        endpoint_url: &str,
+        socket_options: &SocketOptions,
+    ) -> Result<TcpStream, StatusCode> {
+        let addr = {
+            let addr = format!("{host}:{port}");
+            match tokio::net::lookup_host(addr).await {
+                Ok(mut addrs) => {
+                    if let Some(addr) = addrs.next() {
+                        addr
+                    } else {
+                        error!(
+                            "Invalid address {}, does not resolve to any socket",
+                            endpoint_url
+                        );
+                        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+                    }
+                }
+                Err(e) => {
+                    error!("Invalid address {}, cannot be parsed {:?}", endpoint_url, e);
+                    return Err(StatusCode::BadTcpEndpointUrlInvalid);
+                }
+            }
+        };
+
+        debug!("Connecting to {} with url {}", addr, endpoint_url);
+
+        socket_options.connect(addr).await.map_err(|err| {
+            error!("Could not connect to host {}, {:?}", addr, err);
+            if err.kind() == std::io::ErrorKind::TimedOut {
+                StatusCode::BadTimeout
+            } else {
+                StatusCode::BadCommunicationError
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Connector for TcpConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+    ) -> Result<ConnectedTransport, StatusCode> {
+        let socket = Self::connect_inner(&self.endpoint_url, &config.socket_options).await?;
+        TcpTransport::connect_stream(
+            socket.into(),
+            channel,
+            outgoing_recv,
+            config,
+            &self.endpoint_url,
+        )
+        .await
+        .map(Into::into)
+    }
+
+    fn default_endpoint(&self) -> opcua_types::EndpointDescription {
+        opcua_types::EndpointDescription::from(self.endpoint_url.as_str())
+    }
+}
+
+impl TcpTransport {
     async fn hello_exchange(
-        reader: &mut FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-        writer: &mut WriteHalf<TcpStream>,
+        reader: &mut FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+        writer: &mut WriteHalf<TransportStream>,
         endpoint_url: &str,
         config: &TransportConfiguration,
     ) -> Result<AcknowledgeMessage, StatusCode> {
@@ -92,15 +193,22 @@ impl TcpConnector {
                 error!("Cannot send hello to server, err = {}", err);
                 StatusCode::BadCommunicationError
             })?;
-        match reader.next().await {
+        let next = tokio::time::timeout(config.hello_timeout, reader.next())
+            .await
+            .map_err(|_| {
+                error!(
+                    "Timed out after {:?} waiting for server ACK",
+                    config.hello_timeout
+                );
+                StatusCode::BadTimeout
+            })?;
+        match next {
             Some(Ok(Message::Acknowledge(ack))) => {
-                if ack.send_buffer_size > hello.receive_buffer_size {
-                    tracing::warn!("Acknowledged send buffer size is greater than receive buffer size in hello message!")
-                }
-                if ack.receive_buffer_size > hello.send_buffer_size {
-                    tracing::warn!("Acknowledged receive buffer size is greater than send buffer size in hello message!")
-                }
                 tracing::trace!("Received acknowledgement: {:?}", ack);
+                if let Err(e) = ack.validate(&hello) {
+                    error!("Server sent an invalid ACK: {e}");
+                    return Err(e.status());
+                }
                 Ok(ack)
             }
             other => {
@@ -113,85 +221,37 @@ impl TcpConnector {
         }
     }
 
-    async fn connect_inner(
-        secure_channel: &RwLock<SecureChannel>,
-        config: &TransportConfiguration,
+    /// Perform the HELLO/ACKNOWLEDGE exchange over an already connected stream,
+    /// and create a transport from it. This is shared by all connectors that
+    /// use OPC-UA binary framing.
+    pub(super) async fn connect_stream(
+        stream: TransportStream,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
         endpoint_url: &str,
-    ) -> Result<
-        (
-            FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-            WriteHalf<TcpStream>,
-            AcknowledgeMessage,
-            SecurityPolicy,
-        ),
-        StatusCode,
-    > {
-        let (host, port) = hostname_port_from_url(
-            endpoint_url,
-            opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
-        )?;
-
-        let addr = {
-            let addr = format!("{host}:{port}");
-            match tokio::net::lookup_host(addr).await {
-                Ok(mut addrs) => {
-                    if let Some(addr) = addrs.next() {
-                        addr
-                    } else {
-                        error!(
-                            "Invalid address {}, does not resolve to any socket",
-                            endpoint_url
-                        );
-                        return Err(StatusCode::BadTcpEndpointUrlInvalid);
-                    }
-                }
-                Err(e) => {
-                    error!("Invalid address {}, cannot be parsed {:?}", endpoint_url, e);
-                    return Err(StatusCode::BadTcpEndpointUrlInvalid);
-                }
-            }
-        };
-
-        debug!("Connecting to {} with url {}", addr, endpoint_url);
-
-        let socket = TcpStream::connect(&addr).await.map_err(|err| {
-            error!("Could not connect to host {}, {:?}", addr, err);
-            StatusCode::BadCommunicationError
-        })?;
-
-        let (reader, mut writer) = tokio::io::split(socket);
+    ) -> Result<TcpTransport, StatusCode> {
+        let (reader, mut writer) = tokio::io::split(stream);
 
         let (mut framed_read, policy) = {
-            let secure_channel = trace_read_lock!(secure_channel);
+            let secure_channel = trace_read_lock!(channel);
             (
-                FramedRead::new(reader, TcpCodec::new(secure_channel.decoding_options())),
+                FramedRead::new(
+                    reader,
+                    TcpCodec::new(secure_channel.decoding_options())
+                        .with_metrics(config.metrics.clone()),
+                ),
                 secure_channel.security_policy(),
             )
         };
 
-        let ack = Self::hello_exchange(&mut framed_read, &mut writer, endpoint_url, config).await?;
-
-        Ok((framed_read, writer, ack, policy))
-    }
-}
+        let ack =
+            Self::hello_exchange(&mut framed_read, &mut writer, endpoint_url, &config).await?;
 
-#[async_trait]
-impl Connector for TcpConnector {
-    async fn connect(
-        &self,
-        channel: Arc<RwLock<SecureChannel>>,
-        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
-        config: TransportConfiguration,
-    ) -> Result<TcpTransport, StatusCode> {
-        let (framed_read, writer, ack, policy) =
-            match Self::connect_inner(&channel, &config, &self.endpoint_url).await {
-                Ok(k) => k,
-                Err(status) => return Err(status),
-            };
         let mut buffer = SendBuffer::new(
             config.send_buffer_size,
-            config.max_message_size,
-            config.max_chunk_count,
+            config.max_send_message_size,
+            config.max_send_chunk_count,
             policy.legacy_sequence_numbers(),
         );
         buffer.revise(
@@ -199,29 +259,43 @@ impl Connector for TcpConnector {
             ack.max_message_size as usize,
             ack.max_chunk_count as usize,
         );
+        buffer.parallel_crypto_threshold = config.parallel_crypto_threshold;
+        buffer.metrics = config.metrics.clone();
+
+        let receive_buffer_size = ack.send_buffer_size.min(config.recv_buffer_size as u32) as usize;
+        let limits = ConnectionLimits {
+            send: MessageLimits {
+                max_message_size: buffer.max_message_size,
+                max_chunk_size: buffer.send_buffer_size,
+                max_chunk_count: buffer.max_chunk_count,
+            },
+            receive: MessageLimits {
+                max_message_size: config.max_message_size,
+                max_chunk_size: receive_buffer_size,
+                max_chunk_count: config.max_chunk_count,
+            },
+        };
+        debug!("Negotiated connection limits: {limits:?}");
+        trace_write_lock!(channel).set_connection_limits(limits);
 
         Ok(TcpTransport {
             state: TransportState::new(
                 channel,
                 outgoing_recv,
                 config.max_chunk_count,
-                ack.send_buffer_size.min(config.recv_buffer_size as u32) as usize,
+                receive_buffer_size,
+                config.parallel_crypto_threshold,
+                config.aborts.clone(),
             ),
             read: framed_read,
             write: writer,
             send_buffer: buffer,
             should_close: false,
             closed: TransportCloseState::Open,
-            connected_url: self.endpoint_url.to_string(),
+            connected_url: endpoint_url.to_string(),
         })
     }
 
-    fn default_endpoint(&self) -> opcua_types::EndpointDescription {
-        opcua_types::EndpointDescription::from(self.endpoint_url.as_str())
-    }
-}
-
-impl TcpTransport {
     fn handle_incoming_message(
         &mut self,
         incoming: Option<Result<Message, std::io::Error>>,
@@ -253,6 +327,12 @@ impl TcpTransport {
         // If there's nothing in the send buffer, but there are chunks available,
         // write them to the send buffer before proceeding.
         if self.send_buffer.should_encode_chunks() {
+            if let Err(e) = self.state.abort_abandoned(&mut self.send_buffer) {
+                return TransportPollResult::Closed(e);
+            }
+        }
+        if self.send_buffer.should_encode_chunks() {
+            self.send_buffer.wait_for_stream().await;
             let secure_channel = trace_read_lock!(self.state.secure_channel);
             if let Err(e) = self.send_buffer.encode_next_chunk(&secure_channel) {
                 return TransportPollResult::Closed(e);
@@ -261,7 +341,8 @@ impl TcpTransport {
 
         // If there is something in the send buffer, write to the stream.
         // If not, wait for outgoing messages.
-        // Either way, listen to incoming messages while we do this.
+        // Either way, listen to incoming messages and aborted requests while we do this.
+        let aborts = self.state.aborts.clone();
         if self.send_buffer.can_read() {
             tokio::select! {
                 r = self.send_buffer.read_into_async(&mut self.write) => {
@@ -274,6 +355,9 @@ impl TcpTransport {
                 incoming = self.read.next() => {
                     self.handle_incoming_message(incoming)
                 }
+                _ = aborts.wait() => {
+                    self.process_aborts()
+                }
             }
         } else {
             if self.should_close {
@@ -308,10 +392,20 @@ impl TcpTransport {
                 incoming = self.read.next() => {
                     self.handle_incoming_message(incoming)
                 }
+                _ = aborts.wait() => {
+                    self.process_aborts()
+                }
             }
         }
     }
 
+    fn process_aborts(&mut self) -> TransportPollResult {
+        match self.state.process_aborts(&mut self.send_buffer) {
+            Ok(()) => TransportPollResult::RequestAborted,
+            Err(e) => TransportPollResult::Closed(e),
+        }
+    }
+
     pub fn connected_url(&self) -> &str {
         &self.connected_url
     }
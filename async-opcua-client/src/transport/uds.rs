@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use opcua_core::comms::{
+    secure_channel::SecureChannel,
+    stream::TransportStream,
+    url::{is_opc_ua_uds_url, uds_path_from_url},
+};
+use opcua_types::{Error, StatusCode};
+use parking_lot::RwLock;
+use tracing::{debug, error};
+
+use super::connect::{ConnectedTransport, Connector};
+use super::core::OutgoingMessage;
+use super::tcp::{TcpTransport, TransportConfiguration};
+
+/// Connector for `opc.uds` transport, i.e. OPC-UA binary framing over a
+/// Unix domain socket, or a named pipe on Windows.
+///
+/// This is useful for processes running on the same machine as the server,
+/// avoiding the TCP stack altogether.
+pub struct UdsConnector {
+    endpoint_url: String,
+    path: PathBuf,
+}
+
+impl UdsConnector {
+    /// Create a new `UdsConnector` with the given endpoint URL, on the form
+    /// `opc.uds:///path/to/socket`.
+    pub fn new(endpoint_url: &str) -> Result<Self, Error> {
+        if !is_opc_ua_uds_url(endpoint_url) {
+            return Err(Error::new(
+                StatusCode::BadInvalidArgument,
+                format!("Invalid OPC-UA UDS URL: {}", endpoint_url),
+            ));
+        }
+        let path = uds_path_from_url(endpoint_url).map_err(|e| {
+            Error::new(
+                e,
+                format!("Invalid socket path in OPC-UA UDS URL: {}", endpoint_url),
+            )
+        })?;
+        Ok(Self {
+            endpoint_url: endpoint_url.to_owned(),
+            path,
+        })
+    }
+
+    #[cfg(unix)]
+    async fn connect_inner(&self) -> Result<TransportStream, StatusCode> {
+        let stream = tokio::net::UnixStream::connect(&self.path)
+            .await
+            .map_err(|err| {
+                error!(
+                    "Could not connect to unix socket {}, {:?}",
+                    self.path.display(),
+                    err
+                );
+                StatusCode::BadCommunicationError
+            })?;
+        Ok(stream.into())
+    }
+
+    #[cfg(windows)]
+    async fn connect_inner(&self) -> Result<TransportStream, StatusCode> {
+        use tokio::net::windows::named_pipe::ClientOptions;
+        // ERROR_PIPE_BUSY, all pipe instances are in use, so wait a little and try again.
+        const ERROR_PIPE_BUSY: i32 = 231;
+        loop {
+            match ClientOptions::new().open(&self.path) {
+                Ok(client) => break Ok(client.into()),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                Err(err) => {
+                    error!(
+                        "Could not connect to named pipe {}, {:?}",
+                        self.path.display(),
+                        err
+                    );
+                    break Err(StatusCode::BadCommunicationError);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    async fn connect_inner(&self) -> Result<TransportStream, StatusCode> {
+        error!("opc.uds transport is not supported on this platform");
+        Err(StatusCode::BadNotSupported)
+    }
+}
+
+#[async_trait]
+impl Connector for UdsConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+    ) -> Result<ConnectedTransport, StatusCode> {
+        debug!(
+            "Connecting to socket {} with url {}",
+            self.path.display(),
+            self.endpoint_url
+        );
+        let stream = self.connect_inner().await?;
+        TcpTransport::connect_stream(stream, channel, outgoing_recv, config, &self.endpoint_url)
+            .await
+            .map(Into::into)
+    }
+
+    fn default_endpoint(&self) -> opcua_types::EndpointDescription {
+        opcua_types::EndpointDescription::from(self.endpoint_url.as_str())
+    }
+}
@@ -8,7 +8,7 @@ mod utils;
 
 use std::{collections::HashSet, io::Write, path::Path};
 
-use config::{load_schemas, CodeGenSource};
+use config::load_schemas;
 pub use error::CodeGenError;
 use ids::generate_node_ids;
 use nodeset::{generate_events, generate_target, make_root_module};
@@ -19,6 +19,7 @@ use types::base_native_type_mappings;
 use types::{generate_types, generate_types_nodeset, type_loader_impl, EncodingIds};
 use utils::{create_module_file, GeneratedOutput};
 
+pub use crate::config::{CodeGenSource, ExplicitCodeGenSource};
 pub use crate::ids::NodeIdCodeGenTarget;
 pub use crate::nodeset::{DependentNodeset, EventsTarget, NodeSetCodeGenTarget, NodeSetTypes};
 pub use crate::types::{ExternalIds, ExternalType, TypeCodeGenTarget};
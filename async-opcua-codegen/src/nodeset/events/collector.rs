@@ -16,6 +16,8 @@ pub struct CollectedField<'a> {
     pub type_id: FieldKind<'a>,
     pub data_type_id: Option<&'a str>,
     pub placeholder: bool,
+    /// The field is a variable with one or more array dimensions.
+    pub is_array: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -208,6 +210,7 @@ impl<'a> TypeCollector<'a> {
                     let mut is_placeholder = false;
                     let mut type_def: Option<&'a str> = None;
                     let mut data_type_id: Option<&'a str> = None;
+                    let mut is_array = false;
                     let target = node.lookup_node_id(rf.target);
                     for crf in self
                         .references
@@ -254,6 +257,7 @@ impl<'a> TypeCollector<'a> {
                                 .with_context(format!("collecting type {type_id}")));
                             };
                             data_type_id = Some(target_node.lookup_node_id(v.data_type.0.as_str()));
+                            is_array = v.value_rank.0 >= 1;
                             FieldKind::Variable(type_def)
                         }
                         UANode::Method(_) => FieldKind::Method,
@@ -275,6 +279,7 @@ impl<'a> TypeCollector<'a> {
                             placeholder: is_placeholder,
                             type_id: kind,
                             data_type_id,
+                            is_array,
                         },
                     );
                 }
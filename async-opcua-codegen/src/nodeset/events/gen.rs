@@ -172,7 +172,12 @@ impl<'a> EventGenerator<'a> {
                             CodeGenError::other(format!("Missing valid data type for variable {v}"))
                         })?;
 
-                        self.get_data_type(data_type_id)?
+                        let data_type = self.get_data_type(data_type_id)?;
+                        if field.is_array {
+                            quote! { Vec<#data_type> }
+                        } else {
+                            data_type
+                        }
                     } else {
                         let typ_ident = safe_ident(typ.name).0;
                         syn::parse_str(&format!("{}{}", typ.import_path, typ_ident))?
@@ -78,8 +78,8 @@ pub struct AudioVariableType {
 #[opcua(identifier = "i=2075")]
 pub struct AuditActivateSessionEventType {
     pub base: AuditSessionEventType,
-    pub client_software_certificates: types::SignedSoftwareCertificate,
-    pub current_role_ids: types::NodeId,
+    pub client_software_certificates: Vec<types::SignedSoftwareCertificate>,
+    pub current_role_ids: Vec<types::NodeId>,
     pub secure_channel_id: opcua::types::UAString,
     pub user_identity_token: opcua::types::ExtensionObject,
 }
@@ -87,13 +87,13 @@ pub struct AuditActivateSessionEventType {
 #[opcua(identifier = "i=2091")]
 pub struct AuditAddNodesEventType {
     pub base: AuditNodeManagementEventType,
-    pub nodes_to_add: types::AddNodesItem,
+    pub nodes_to_add: Vec<types::AddNodesItem>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=2095")]
 pub struct AuditAddReferencesEventType {
     pub base: AuditNodeManagementEventType,
-    pub references_to_add: types::AddReferencesItem,
+    pub references_to_add: Vec<types::AddReferencesItem>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=2078")]
@@ -155,10 +155,10 @@ pub struct AuditClientEventType {
 #[opcua(identifier = "i=23926")]
 pub struct AuditClientUpdateMethodResultEventType {
     pub base: AuditClientEventType,
-    pub input_arguments: opcua::types::ExtensionObject,
+    pub input_arguments: Vec<opcua::types::ExtensionObject>,
     pub method_id: types::ExpandedNodeId,
     pub object_id: types::ExpandedNodeId,
-    pub output_arguments: opcua::types::ExtensionObject,
+    pub output_arguments: Vec<opcua::types::ExtensionObject>,
     pub status_code_id: types::StatusCode,
 }
 #[derive(Debug, opcua::Event)]
@@ -237,13 +237,13 @@ pub struct AuditCreateSessionEventType {
 #[opcua(identifier = "i=2093")]
 pub struct AuditDeleteNodesEventType {
     pub base: AuditNodeManagementEventType,
-    pub nodes_to_delete: types::DeleteNodesItem,
+    pub nodes_to_delete: Vec<types::DeleteNodesItem>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=2097")]
 pub struct AuditDeleteReferencesEventType {
     pub base: AuditNodeManagementEventType,
-    pub references_to_delete: types::DeleteReferencesItem,
+    pub references_to_delete: Vec<types::DeleteReferencesItem>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=2052")]
@@ -259,16 +259,16 @@ pub struct AuditEventType {
 #[opcua(identifier = "i=19095")]
 pub struct AuditHistoryAnnotationUpdateEventType {
     pub base: AuditHistoryUpdateEventType,
-    pub new_values: types::Annotation,
-    pub old_values: types::Annotation,
+    pub new_values: Vec<types::Annotation>,
+    pub old_values: Vec<types::Annotation>,
     pub perform_insert_replace: types::PerformUpdateType,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=3019")]
 pub struct AuditHistoryAtTimeDeleteEventType {
     pub base: AuditHistoryDeleteEventType,
-    pub old_values: types::DataValue,
-    pub req_times: types::UtcTime,
+    pub old_values: Vec<types::DataValue>,
+    pub req_times: Vec<types::UtcTime>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=32803")]
@@ -293,7 +293,7 @@ pub struct AuditHistoryDeleteEventType {
 #[opcua(identifier = "i=3022")]
 pub struct AuditHistoryEventDeleteEventType {
     pub base: AuditHistoryDeleteEventType,
-    pub event_ids: types::ByteString,
+    pub event_ids: Vec<types::ByteString>,
     pub old_values: types::HistoryEventFieldList,
 }
 #[derive(Debug, opcua::Event)]
@@ -301,8 +301,8 @@ pub struct AuditHistoryEventDeleteEventType {
 pub struct AuditHistoryEventUpdateEventType {
     pub base: AuditHistoryUpdateEventType,
     pub filter: types::EventFilter,
-    pub new_values: types::HistoryEventFieldList,
-    pub old_values: types::HistoryEventFieldList,
+    pub new_values: Vec<types::HistoryEventFieldList>,
+    pub old_values: Vec<types::HistoryEventFieldList>,
     pub perform_insert_replace: types::PerformUpdateType,
     pub updated_node: types::NodeId,
 }
@@ -312,7 +312,7 @@ pub struct AuditHistoryRawModifyDeleteEventType {
     pub base: AuditHistoryDeleteEventType,
     pub end_time: types::UtcTime,
     pub is_delete_modified: bool,
-    pub old_values: types::DataValue,
+    pub old_values: Vec<types::DataValue>,
     pub start_time: types::UtcTime,
 }
 #[derive(Debug, opcua::Event)]
@@ -325,8 +325,8 @@ pub struct AuditHistoryUpdateEventType {
 #[opcua(identifier = "i=3006")]
 pub struct AuditHistoryValueUpdateEventType {
     pub base: AuditHistoryUpdateEventType,
-    pub new_values: types::DataValue,
-    pub old_values: types::DataValue,
+    pub new_values: Vec<types::DataValue>,
+    pub old_values: Vec<types::DataValue>,
     pub perform_insert_replace: types::PerformUpdateType,
     pub updated_node: types::NodeId,
 }
@@ -374,9 +374,9 @@ pub struct AuditUpdateEventType {
 #[opcua(identifier = "i=2127")]
 pub struct AuditUpdateMethodEventType {
     pub base: AuditEventType,
-    pub input_arguments: opcua::types::ExtensionObject,
+    pub input_arguments: Vec<opcua::types::ExtensionObject>,
     pub method_id: types::NodeId,
-    pub output_arguments: opcua::types::ExtensionObject,
+    pub output_arguments: Vec<opcua::types::ExtensionObject>,
     pub status_code_id: types::StatusCode,
 }
 #[derive(Debug, opcua::Event)]
@@ -440,8 +440,8 @@ pub struct ConditionType {
     pub condition_name: opcua::types::UAString,
     pub condition_refresh: opcua::nodes::MethodEventField,
     pub condition_refresh_2: opcua::nodes::MethodEventField,
-    pub condition_sub_class_id: types::NodeId,
-    pub condition_sub_class_name: types::LocalizedText,
+    pub condition_sub_class_id: Vec<types::NodeId>,
+    pub condition_sub_class_name: Vec<types::LocalizedText>,
     pub disable: opcua::nodes::MethodEventField,
     pub enable: opcua::nodes::MethodEventField,
     pub enabled_state: TwoStateVariableType,
@@ -474,7 +474,7 @@ pub struct DialogConditionType {
     pub prompt: types::LocalizedText,
     pub respond: opcua::nodes::MethodEventField,
     pub respond_2: opcua::nodes::MethodEventField,
-    pub response_option_set: types::LocalizedText,
+    pub response_option_set: Vec<types::LocalizedText>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=17080")]
@@ -536,8 +536,8 @@ pub struct ExclusiveRateOfChangeAlarmType {
 pub struct FiniteStateMachineType {
     pub base: StateMachineType,
     pub node_id: opcua::types::NodeId,
-    pub available_states: types::NodeId,
-    pub available_transitions: types::NodeId,
+    pub available_states: Vec<types::NodeId>,
+    pub available_transitions: Vec<types::NodeId>,
     pub current_state: FiniteStateVariableType,
     pub last_transition: FiniteTransitionVariableType,
 }
@@ -557,7 +557,7 @@ pub struct FiniteTransitionVariableType {
 #[opcua(identifier = "i=2133")]
 pub struct GeneralModelChangeEventType {
     pub base: BaseModelChangeEventType,
-    pub changes: types::ModelChangeStructureDataType,
+    pub changes: Vec<types::ModelChangeStructureDataType>,
 }
 #[derive(Debug, opcua::Event)]
 #[opcua(identifier = "i=18347")]
@@ -700,7 +700,7 @@ pub struct RoleMappingRuleChangedAuditEventType {
 #[opcua(identifier = "i=2738")]
 pub struct SemanticChangeEventType {
     pub base: opcua::nodes::BaseEventType,
-    pub changes: types::SemanticChangeStructureDataType,
+    pub changes: Vec<types::SemanticChangeStructureDataType>,
 }
 #[derive(Debug, opcua::EventField, Default)]
 pub struct ShelvedStateMachineType {
@@ -7,14 +7,24 @@ use std::{
 };
 
 use tokio::io::AsyncWriteExt;
-use tracing::trace;
+use tracing::{error, trace};
 
 use crate::{
-    comms::{chunker::Chunker, message_chunk::MessageChunk, secure_channel::SecureChannel},
+    comms::{
+        chunker::{ChunkStream, Chunker},
+        interceptor::MessageDirection,
+        message_chunk::{MessageChunk, MessageChunkType, MessageIsFinalType},
+        metrics::TransportMetricsHandle,
+        parallel,
+        pool::BufferPool,
+        priority::{MessagePriority, PriorityPolicy},
+        rate_limit::RateLimiter,
+        secure_channel::SecureChannel,
+    },
     Message,
 };
 
-use opcua_types::{Error, SimpleBinaryEncodable, StatusCode};
+use opcua_types::{Error, SimpleBinaryEncodable, StatusCode, UAString};
 
 use super::{
     sequence_number::SequenceNumberHandle,
@@ -29,11 +39,64 @@ enum SendBufferState {
 
 #[derive(Debug)]
 enum PendingPayload {
-    Chunk(MessageChunk),
+    Chunk {
+        request_id: u32,
+        sequence_number: u32,
+        priority: MessagePriority,
+        chunk: MessageChunk,
+    },
+    /// A chunk that has already been signed and encrypted.
+    Secured {
+        request_id: u32,
+        sequence_number: u32,
+        data: Vec<u8>,
+    },
+    /// A message that is encoded into chunks as they are sent.
+    Stream {
+        /// Sequence numbers, starting at the sequence number of the next chunk.
+        sequence_numbers: SequenceNumberHandle,
+        priority: MessagePriority,
+        stream: ChunkStream,
+    },
     Ack(AcknowledgeMessage),
     Error(ErrorMessage),
 }
 
+impl PendingPayload {
+    fn request_id(&self) -> Option<u32> {
+        match self {
+            PendingPayload::Chunk { request_id, .. }
+            | PendingPayload::Secured { request_id, .. } => Some(*request_id),
+            PendingPayload::Stream { stream, .. } => Some(stream.request_id()),
+            _ => None,
+        }
+    }
+
+    fn sequence_number(&self) -> Option<u32> {
+        match self {
+            PendingPayload::Chunk {
+                sequence_number, ..
+            }
+            | PendingPayload::Secured {
+                sequence_number, ..
+            } => Some(*sequence_number),
+            PendingPayload::Stream {
+                sequence_numbers, ..
+            } => Some(sequence_numbers.current()),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            PendingPayload::Chunk { chunk, .. } => chunk.data.len(),
+            PendingPayload::Secured { data, .. } => data.len(),
+            PendingPayload::Stream { stream, .. } => stream.remaining_bytes(),
+            _ => 0,
+        }
+    }
+}
+
 /// General implementation of a buffer of outgoing messages.
 pub struct SendBuffer {
     /// The send buffer
@@ -50,6 +113,30 @@ pub struct SendBuffer {
     pub max_chunk_count: usize,
     /// Maximum size of each individual chunk.
     pub send_buffer_size: usize,
+    /// Minimum number of chunks in a message before they are signed and encrypted
+    /// in parallel. Use 0 to always secure chunks sequentially.
+    pub parallel_crypto_threshold: usize,
+    /// Minimum encoded size of a message, in bytes, before it is encoded into chunks
+    /// as they are sent, instead of all at once when it is written. This bounds the
+    /// memory used by very large messages. Use 0 to never stream messages.
+    pub stream_encoding_threshold: usize,
+    /// Metrics hooks notified about outgoing traffic.
+    pub metrics: TransportMetricsHandle,
+    /// Policy deciding which queued messages are sent first.
+    pub priority_policy: PriorityPolicy,
+    /// Maximum number of bytes queued for sending before the buffer is considered full.
+    /// Use 0 for no limit. See [`SendBuffer::is_full`].
+    pub max_queued_bytes: usize,
+    /// Maximum number of messages queued for sending before the buffer is considered full.
+    /// Use 0 for no limit. See [`SendBuffer::is_full`].
+    pub max_queued_messages: usize,
+    /// Limit on the rate data is written to the stream, if any.
+    pub rate_limiter: Option<RateLimiter>,
+    queued_bytes: usize,
+    queued_messages: usize,
+    /// Request ID of the message currently being sent, if some of its chunks
+    /// have been sent and some are still queued.
+    partially_sent: Option<u32>,
 
     state: SendBufferState,
 }
@@ -75,6 +162,16 @@ impl SendBuffer {
             max_message_size,
             max_chunk_count,
             send_buffer_size: buffer_size,
+            parallel_crypto_threshold: 0,
+            stream_encoding_threshold: 0,
+            metrics: TransportMetricsHandle::default(),
+            priority_policy: PriorityPolicy::default(),
+            max_queued_bytes: 0,
+            max_queued_messages: 0,
+            rate_limiter: None,
+            queued_bytes: 0,
+            queued_messages: 0,
+            partially_sent: None,
             state: SendBufferState::Writing,
         }
     }
@@ -85,12 +182,44 @@ impl SendBuffer {
             return Err(StatusCode::BadInvalidState);
         }
 
+        if !self.expand_stream(secure_channel)? {
+            return Ok(());
+        }
         let Some(next_chunk) = self.chunks.pop_front() else {
             return Ok(());
         };
 
+        self.partially_sent = next_chunk
+            .request_id()
+            .filter(|id| self.chunks.front().and_then(|c| c.request_id()) == Some(*id));
+        self.queued_bytes -= next_chunk.len();
+        if next_chunk.request_id().is_some() && self.partially_sent.is_none() {
+            self.queued_messages -= 1;
+        }
+
         let size = match next_chunk {
-            PendingPayload::Chunk(c) => secure_channel.apply_security(&c, self.buffer.get_mut())?,
+            PendingPayload::Chunk { chunk, .. } => {
+                self.metrics.chunk_sent();
+                secure_channel
+                    .apply_security(&chunk, self.buffer.get_mut())
+                    .inspect_err(|e| self.metrics.encoding_error(*e))?
+            }
+            PendingPayload::Secured { data, .. } => {
+                self.metrics.chunk_sent();
+                let size = data.len();
+                let dst = self.buffer.get_mut();
+                if size > dst.len() {
+                    error!(
+                        "The size of the secured chunk {} exceeds the size of the send buffer {}",
+                        size,
+                        dst.len()
+                    );
+                    return Err(StatusCode::BadEncodingLimitsExceeded);
+                }
+                dst[..size].copy_from_slice(&data);
+                BufferPool::global().put(data);
+                size
+            }
             PendingPayload::Ack(a) => {
                 a.encode(&mut self.buffer)?;
                 self.buffer.position() as usize
@@ -99,6 +228,10 @@ impl SendBuffer {
                 e.encode(&mut self.buffer)?;
                 self.buffer.position() as usize
             }
+            PendingPayload::Stream { .. } => {
+                error!("Attempted to send a streamed message without encoding its chunks");
+                return Err(StatusCode::BadInvalidState);
+            }
         };
         self.buffer.set_position(0);
         self.state = SendBufferState::Reading(size);
@@ -106,6 +239,70 @@ impl SendBuffer {
         Ok(())
     }
 
+    /// Wait until the next queued payload can be encoded. This only waits if the
+    /// next payload is a streamed message whose next chunk is still being encoded,
+    /// and should be awaited before [`SendBuffer::encode_next_chunk`]. This is cancel safe.
+    pub async fn wait_for_stream(&mut self) {
+        if let Some(PendingPayload::Stream { stream, .. }) = self.chunks.front_mut() {
+            stream.ready().await;
+        }
+    }
+
+    /// If the next queued payload is a streamed message, take its next chunk and queue
+    /// it in front of the rest of the message. Returns `false` if the next chunk has
+    /// not been encoded yet.
+    fn expand_stream(&mut self, secure_channel: &SecureChannel) -> Result<bool, StatusCode> {
+        let Some(PendingPayload::Stream {
+            sequence_numbers,
+            priority,
+            stream,
+        }) = self.chunks.front_mut()
+        else {
+            return Ok(true);
+        };
+        if !stream.try_ready() {
+            return Ok(false);
+        }
+
+        let request_id = stream.request_id();
+        let remaining_bytes = stream.remaining_bytes();
+        match stream.next_chunk(sequence_numbers.current(), secure_channel) {
+            Ok(chunk) => {
+                let payload = PendingPayload::Chunk {
+                    request_id,
+                    sequence_number: sequence_numbers.current(),
+                    priority: *priority,
+                    chunk,
+                };
+                sequence_numbers.increment(1);
+                self.queued_bytes =
+                    self.queued_bytes + payload.len() + stream.remaining_bytes() - remaining_bytes;
+                if stream.remaining_chunks() == 0 {
+                    self.chunks.pop_front();
+                }
+                self.chunks.push_front(payload);
+                Ok(true)
+            }
+            Err(e) => {
+                error!("Failed to encode streamed message: {e}");
+                self.metrics.encoding_error(e.status());
+                // Abort the message even if none of it has been sent yet, so that the
+                // receiver doesn't wait for a response that never arrives.
+                self.partially_sent = Some(request_id);
+                match self.abort(
+                    request_id,
+                    e.status(),
+                    "Failed to encode message",
+                    secure_channel,
+                ) {
+                    Ok(true) => Ok(true),
+                    Ok(false) => Err(e.status()),
+                    Err(e) => Err(e.status()),
+                }
+            }
+        }
+    }
+
     /// Set whether we are using legacy sequence numbers or not.
     /// This depends on the active security policy.
     pub fn set_sequence_number_legacy(&mut self, is_legacy: bool) {
@@ -117,6 +314,9 @@ impl SendBuffer {
     pub fn write_error(&mut self, error: ErrorMessage) {
         // Clear any pending chunks, we're erroring out
         self.chunks.clear();
+        self.partially_sent = None;
+        self.queued_bytes = 0;
+        self.queued_messages = 0;
         self.chunks.push_back(PendingPayload::Error(error));
     }
 
@@ -127,18 +327,44 @@ impl SendBuffer {
 
     /// Encode a message to chunks, then write them to the pending message queue.
     ///
-    /// The messages are encrypted as they are sent.
+    /// The messages are encrypted as they are sent. Messages of at least
+    /// `stream_encoding_threshold` bytes are also encoded as they are sent, see
+    /// [`Chunker::encode_stream`].
     pub fn write(
         &mut self,
         request_id: u32,
-        message: impl Message,
+        message: impl Message + Send + 'static,
+        secure_channel: &SecureChannel,
+    ) -> Result<u32, Error> {
+        let res = self.write_inner(request_id, message, secure_channel);
+        match &res {
+            Ok(_) => self.metrics.message_sent(),
+            Err(e) => self.metrics.encoding_error(e.status()),
+        }
+        res
+    }
+
+    fn write_inner(
+        &mut self,
+        request_id: u32,
+        message: impl Message + Send + 'static,
         secure_channel: &SecureChannel,
     ) -> Result<u32, Error> {
         trace!("Writing request to buffer");
 
+        if self.stream_encoding_threshold > 0 && self.send_buffer_size > 0 {
+            let size = message.byte_len(&secure_channel.context().context());
+            if size >= self.stream_encoding_threshold {
+                return self.write_stream(request_id, message, size, secure_channel);
+            }
+        }
+
+        let priority = self.priority_policy.priority(message.type_name());
+        let (index, mut sequence_numbers) = self.queue_position(priority);
+
         // Turn message to chunk(s)
         let chunks = Chunker::encode(
-            self.sequence_numbers.clone(),
+            sequence_numbers.clone(),
             request_id,
             self.max_message_size,
             self.send_buffer_size,
@@ -158,16 +384,312 @@ impl SendBuffer {
             )
             .with_context(Some(request_id), Some(message.request_handle())))
         } else {
+            let size = chunks.iter().map(|c| c.data.len()).sum();
+            secure_channel
+                .intercept(MessageDirection::Outgoing, &message, request_id, size)
+                .map_err(|e| {
+                    Error::new(e, "Outgoing message rejected by interceptor")
+                        .with_context(Some(request_id), Some(message.request_handle()))
+                })?;
+
             // Sequence number monotonically increases per chunk
-            self.sequence_numbers.increment(chunks.len() as u32);
+            let mut next_sequence_number = || {
+                let current = sequence_numbers.current();
+                sequence_numbers.increment(1);
+                current
+            };
 
-            // Send chunks
-            self.chunks
-                .extend(chunks.into_iter().map(PendingPayload::Chunk));
+            let payloads = if self.parallel_crypto_threshold > 0
+                && chunks.len() >= self.parallel_crypto_threshold
+                && secure_channel.is_secured()
+            {
+                // Secure the chunks up front, spreading the work over multiple threads.
+                let buffer_size = self.buffer.get_ref().len();
+                let secured = parallel::map_ordered(&chunks, |chunk| {
+                    let mut data = BufferPool::global().take(buffer_size);
+                    let size = secure_channel.apply_security(chunk, &mut data)?;
+                    data.truncate(size);
+                    Ok::<_, StatusCode>(data)
+                })
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    Error::new(e, "Failed to apply security to message chunks")
+                        .with_context(Some(request_id), Some(message.request_handle()))
+                })?;
+                secured
+                    .into_iter()
+                    .map(|data| PendingPayload::Secured {
+                        request_id,
+                        sequence_number: next_sequence_number(),
+                        data,
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                // Send chunks
+                chunks
+                    .into_iter()
+                    .map(|chunk| PendingPayload::Chunk {
+                        request_id,
+                        sequence_number: next_sequence_number(),
+                        priority,
+                        chunk,
+                    })
+                    .collect()
+            };
+
+            // Queue the message, then renumber any messages it was queued ahead of.
+            let displaced = self.chunks.split_off(index);
+            self.queued_bytes += payloads.iter().map(|p| p.len()).sum::<usize>();
+            self.queued_messages += 1;
+            self.chunks.extend(payloads);
+            let next = self.chunks.len();
+            self.chunks.extend(displaced);
+            self.renumber(next, sequence_numbers, secure_channel)?;
             Ok(request_id)
         }
     }
 
+    /// Queue a message that is encoded into chunks as they are sent.
+    fn write_stream(
+        &mut self,
+        request_id: u32,
+        message: impl Message + Send + 'static,
+        size: usize,
+        secure_channel: &SecureChannel,
+    ) -> Result<u32, Error> {
+        let priority = self.priority_policy.priority(message.type_name());
+        let (index, sequence_numbers) = self.queue_position(priority);
+        let request_handle = message.request_handle();
+        // The chunks don't exist yet, so interceptors are given the size of the body.
+        secure_channel
+            .intercept(MessageDirection::Outgoing, &message, request_id, size)
+            .map_err(|e| {
+                Error::new(e, "Outgoing message rejected by interceptor")
+                    .with_context(Some(request_id), Some(request_handle))
+            })?;
+
+        let stream = Chunker::encode_stream(
+            request_id,
+            self.max_message_size,
+            self.send_buffer_size,
+            secure_channel,
+            message,
+        )
+        .map_err(|e| e.with_context(Some(request_id), Some(request_handle)))?;
+
+        if self.max_chunk_count > 0 && stream.chunk_count() > self.max_chunk_count {
+            return Err(Error::new(
+                StatusCode::BadCommunicationError,
+                format!(
+                    "Cannot write message since {} chunks exceeds {} chunk limit",
+                    stream.chunk_count(),
+                    self.max_chunk_count
+                ),
+            )
+            .with_context(Some(request_id), Some(request_handle)));
+        }
+
+        let mut next_sequence_numbers = sequence_numbers.clone();
+        next_sequence_numbers.increment(stream.chunk_count() as u32);
+        let displaced = self.chunks.split_off(index);
+        self.queued_bytes += stream.remaining_bytes();
+        self.queued_messages += 1;
+        self.chunks.push_back(PendingPayload::Stream {
+            sequence_numbers,
+            priority,
+            stream,
+        });
+        let next = self.chunks.len();
+        self.chunks.extend(displaced);
+        self.renumber(next, next_sequence_numbers, secure_channel)?;
+        Ok(request_id)
+    }
+
+    /// Abort a message that has not been fully sent. Queued chunks of the message are
+    /// discarded, and if some of its chunks have already been sent, an abort chunk with
+    /// the given status and reason is queued instead, telling the receiver to discard
+    /// the chunks it has received so far.
+    ///
+    /// Chunks queued after the aborted message are renumbered to keep sequence numbers
+    /// contiguous. This is not possible for chunks that were already secured, in which
+    /// case the message is left alone.
+    ///
+    /// Returns `true` if the message was aborted, `false` if it wasn't queued, or
+    /// couldn't be aborted.
+    pub fn abort(
+        &mut self,
+        request_id: u32,
+        status: StatusCode,
+        reason: &str,
+        secure_channel: &SecureChannel,
+    ) -> Result<bool, Error> {
+        let Some(start) = self
+            .chunks
+            .iter()
+            .position(|c| c.request_id() == Some(request_id))
+        else {
+            return Ok(false);
+        };
+        let end = self
+            .chunks
+            .iter()
+            .skip(start)
+            .position(|c| c.request_id() != Some(request_id))
+            .map(|len| start + len)
+            .unwrap_or(self.chunks.len());
+        if self
+            .chunks
+            .range(end..)
+            .any(|c| matches!(c, PendingPayload::Secured { .. }))
+        {
+            return Ok(false);
+        }
+
+        let mut sequence_numbers = self.sequence_numbers.clone();
+        if let Some(first) = self.chunks[start].sequence_number() {
+            sequence_numbers.set(first);
+        }
+        self.chunks.drain(start..end);
+
+        let mut next = start;
+        if self.partially_sent == Some(request_id) {
+            self.partially_sent = None;
+            let mut body = Vec::new();
+            status.encode(&mut body)?;
+            UAString::from(reason).encode(&mut body)?;
+            let sequence_number = sequence_numbers.current();
+            let chunk = MessageChunk::new(
+                sequence_number,
+                request_id,
+                MessageChunkType::Message,
+                MessageIsFinalType::FinalError,
+                secure_channel,
+                &body,
+            )?;
+            self.chunks.insert(
+                start,
+                PendingPayload::Chunk {
+                    request_id,
+                    sequence_number,
+                    priority: MessagePriority::High,
+                    chunk,
+                },
+            );
+            sequence_numbers.increment(1);
+            next += 1;
+        }
+
+        self.renumber(next, sequence_numbers, secure_channel)?;
+        self.queued_bytes = self.chunks.iter().map(|p| p.len()).sum();
+        self.queued_messages = self
+            .chunks
+            .iter()
+            .zip(self.chunks.iter().skip(1).map(Some).chain([None]))
+            .filter(|(c, next)| {
+                c.request_id().is_some() && c.request_id() != next.and_then(|n| n.request_id())
+            })
+            .count();
+
+        Ok(true)
+    }
+
+    /// Find where to queue a message with the given priority, and the sequence numbers
+    /// starting at the first sequence number it takes over. Higher priority messages
+    /// are queued ahead of lower priority ones, taking over their sequence numbers.
+    fn queue_position(&self, priority: MessagePriority) -> (usize, SequenceNumberHandle) {
+        let index = self.insertion_index(priority);
+        let mut sequence_numbers = self.sequence_numbers.clone();
+        if let Some(first) = self.chunks.get(index).and_then(|c| c.sequence_number()) {
+            sequence_numbers.set(first);
+        }
+        (index, sequence_numbers)
+    }
+
+    /// Find where to queue a message with the given priority. Messages are queued after
+    /// any message with the same or a higher priority, and never ahead of a message that
+    /// has been partially sent or already secured, since those can't be renumbered.
+    fn insertion_index(&self, priority: MessagePriority) -> usize {
+        self.chunks
+            .iter()
+            .rposition(|c| match c {
+                PendingPayload::Chunk {
+                    request_id,
+                    priority: p,
+                    ..
+                } => *p >= priority || self.partially_sent == Some(*request_id),
+                PendingPayload::Stream {
+                    priority: p,
+                    stream,
+                    ..
+                } => *p >= priority || self.partially_sent == Some(stream.request_id()),
+                _ => true,
+            })
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    }
+
+    /// Renumber queued chunks starting at index `from`, giving them contiguous
+    /// sequence numbers starting at the current value of `sequence_numbers`.
+    fn renumber(
+        &mut self,
+        from: usize,
+        mut sequence_numbers: SequenceNumberHandle,
+        secure_channel: &SecureChannel,
+    ) -> Result<(), Error> {
+        for payload in self.chunks.range_mut(from..) {
+            match payload {
+                PendingPayload::Chunk {
+                    sequence_number,
+                    chunk,
+                    ..
+                } => {
+                    if *sequence_number != sequence_numbers.current() {
+                        *sequence_number = sequence_numbers.current();
+                        chunk.set_sequence_number(*sequence_number, secure_channel)?;
+                    }
+                    sequence_numbers.increment(1);
+                }
+                PendingPayload::Stream {
+                    sequence_numbers: stream_sequence_numbers,
+                    stream,
+                    ..
+                } => {
+                    *stream_sequence_numbers = sequence_numbers.clone();
+                    sequence_numbers.increment(stream.remaining_chunks() as u32);
+                }
+                _ => (),
+            }
+        }
+        self.sequence_numbers = sequence_numbers;
+        Ok(())
+    }
+
+    /// Get the number of bytes queued for sending, not including the chunk currently
+    /// being written to the stream.
+    pub fn queued_bytes(&self) -> usize {
+        self.queued_bytes
+    }
+
+    /// Get the number of messages queued for sending, including a message that has
+    /// been partially sent.
+    pub fn queued_messages(&self) -> usize {
+        self.queued_messages
+    }
+
+    /// Return `true` if the queue of outgoing messages has reached the limits set by
+    /// `max_queued_bytes` or `max_queued_messages`. Producers should stop writing
+    /// new messages to the buffer until it has been drained.
+    pub fn is_full(&self) -> bool {
+        self.max_queued_bytes > 0 && self.queued_bytes >= self.max_queued_bytes
+            || self.max_queued_messages > 0 && self.queued_messages >= self.max_queued_messages
+    }
+
+    /// Get the request ID of the message the next queued chunk belongs to.
+    pub fn next_queued_request_id(&self) -> Option<u32> {
+        self.chunks.front().and_then(|c| c.request_id())
+    }
+
     /// Get the next request ID.
     pub fn next_request_id(&mut self) -> u32 {
         self.last_request_id += 1;
@@ -191,11 +713,19 @@ impl SendBuffer {
         };
 
         let pos = self.buffer.position() as usize;
-        let buf = &self.buffer.get_ref()[pos..end];
+        let mut buf = &self.buffer.get_ref()[pos..end];
+        if let Some(limiter) = &mut self.rate_limiter {
+            let allowed = limiter.acquire(buf.len()).await;
+            buf = &buf[..allowed];
+        }
         // Write to the stream, note that we do not actually advance the stream before
         // after we have written. This means that since `write` is cancellation safe, our stream is
         // cancellation safe, which is essential.
         let written = write.write(buf).await?;
+        self.metrics.bytes_sent(written);
+        if let Some(limiter) = &mut self.rate_limiter {
+            limiter.consume(written);
+        }
 
         self.buffer.consume(written);
 
@@ -244,14 +774,17 @@ mod tests {
 
     use parking_lot::RwLock;
 
-    use super::SendBuffer;
+    use super::{PendingPayload, SendBuffer};
 
+    use crate::comms::message_chunk::MessageIsFinalType;
+    use crate::comms::priority::{MessagePriority, PriorityPolicy};
     use crate::comms::secure_channel::{Role, SecureChannel};
     use crate::RequestMessage;
     use opcua_crypto::CertificateStore;
     use opcua_types::StatusCode;
     use opcua_types::{
-        DateTime, NodeId, ReadRequest, ReadValueId, RequestHeader, TimestampsToReturn,
+        DateTime, NodeId, PublishRequest, ReadRequest, ReadValueId, RequestHeader,
+        TimestampsToReturn,
     };
 
     fn get_buffer_and_channel() -> (SendBuffer, SecureChannel) {
@@ -443,4 +976,230 @@ mod tests {
         assert!(!buffer.should_encode_chunks());
         assert!(!buffer.can_read());
     }
+
+    fn large_read(request_handle: u32) -> RequestMessage {
+        ReadRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::null(), request_handle),
+            max_age: 0.0,
+            timestamps_to_return: TimestampsToReturn::Both,
+            nodes_to_read: Some(
+                (0..1000)
+                    .map(|r| ReadValueId {
+                        node_id: (1, r).into(),
+                        attribute_id: 1,
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+        }
+        .into()
+    }
+
+    fn queued_chunks(
+        buffer: &SendBuffer,
+        channel: &SecureChannel,
+    ) -> Vec<(u32, u32, MessageIsFinalType)> {
+        buffer
+            .chunks
+            .iter()
+            .map(|p| {
+                let PendingPayload::Chunk { chunk, .. } = p else {
+                    panic!("Expected unsecured chunk");
+                };
+                let info = chunk.chunk_info(channel).unwrap();
+                (
+                    info.sequence_header.request_id,
+                    info.sequence_header.sequence_number,
+                    info.message_header.is_final,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buffer_abort_unsent() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        let first = buffer.sequence_numbers.current();
+        buffer.write(1, large_read(101), &channel).unwrap();
+        buffer.write(2, large_read(102), &channel).unwrap();
+        assert_eq!(buffer.chunks.len(), 6);
+
+        assert!(buffer
+            .abort(1, StatusCode::BadRequestCancelledByClient, "", &channel)
+            .unwrap());
+        // Nothing was sent, so the message is just dropped, and the next message
+        // takes over its sequence numbers.
+        let chunks = queued_chunks(&buffer, &channel);
+        assert_eq!(chunks.len(), 3);
+        for (i, (request_id, sequence_number, _)) in chunks.into_iter().enumerate() {
+            assert_eq!(request_id, 2);
+            assert_eq!(sequence_number, first + i as u32);
+        }
+        assert_eq!(buffer.sequence_numbers.current(), first + 3);
+        assert_eq!(buffer.queued_messages(), 1);
+
+        // Aborting an unknown message does nothing.
+        assert!(!buffer
+            .abort(1, StatusCode::BadRequestCancelledByClient, "", &channel)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_abort_partially_sent() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        let first = buffer.sequence_numbers.current();
+        buffer.write(1, large_read(101), &channel).unwrap();
+        buffer.write(2, large_read(102), &channel).unwrap();
+
+        let mut cursor = Cursor::new(Vec::new());
+        buffer.wait_for_stream().await;
+        buffer.encode_next_chunk(&channel).unwrap();
+        buffer.read_into_async(&mut cursor).await.unwrap();
+
+        assert!(buffer
+            .abort(
+                1,
+                StatusCode::BadRequestCancelledByClient,
+                "Cancelled",
+                &channel
+            )
+            .unwrap());
+        // The first chunk was sent, so the receiver must be told to discard it.
+        let chunks = queued_chunks(&buffer, &channel);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0], (1, first + 1, MessageIsFinalType::FinalError));
+        for (i, (request_id, sequence_number, _)) in chunks.into_iter().enumerate().skip(1) {
+            assert_eq!(request_id, 2);
+            assert_eq!(sequence_number, first + 1 + i as u32);
+        }
+        assert_eq!(buffer.sequence_numbers.current(), first + 5);
+        assert_eq!(buffer.queued_messages(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_priority() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        buffer.priority_policy = PriorityPolicy::default().with("Read", MessagePriority::Low);
+        let first = buffer.sequence_numbers.current();
+        buffer.write(1, large_read(101), &channel).unwrap();
+
+        let mut cursor = Cursor::new(Vec::new());
+        buffer.encode_next_chunk(&channel).unwrap();
+        buffer.read_into_async(&mut cursor).await.unwrap();
+
+        buffer.write(2, large_read(102), &channel).unwrap();
+        let publish: RequestMessage = PublishRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::null(), 103),
+            subscription_acknowledgements: None,
+        }
+        .into();
+        buffer.write(3, publish, &channel).unwrap();
+
+        // The publish request jumps ahead of the second read, but waits for the
+        // partially sent first read to complete.
+        let chunks = queued_chunks(&buffer, &channel);
+        let request_ids: Vec<_> = chunks.iter().map(|c| c.0).collect();
+        assert_eq!(request_ids, vec![1, 1, 3, 2, 2, 2]);
+        for (i, (_, sequence_number, _)) in chunks.into_iter().enumerate() {
+            assert_eq!(sequence_number, first + 1 + i as u32);
+        }
+        assert_eq!(buffer.sequence_numbers.current(), first + 7);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_queue_limits() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        buffer.max_queued_messages = 2;
+        buffer.write(1, large_read(101), &channel).unwrap();
+        assert!(!buffer.is_full());
+        buffer.write(2, large_read(102), &channel).unwrap();
+        assert!(buffer.is_full());
+        assert_eq!(buffer.queued_messages(), 2);
+        let bytes = buffer.queued_bytes();
+        assert!(bytes > 0);
+
+        // Sending part of a message frees up bytes, but not the message.
+        let mut cursor = Cursor::new(Vec::new());
+        buffer.encode_next_chunk(&channel).unwrap();
+        buffer.read_into_async(&mut cursor).await.unwrap();
+        assert!(buffer.queued_bytes() < bytes);
+        assert!(buffer.is_full());
+
+        buffer.max_queued_messages = 0;
+        buffer.max_queued_bytes = buffer.queued_bytes();
+        assert!(buffer.is_full());
+
+        while buffer.should_encode_chunks() {
+            buffer.encode_next_chunk(&channel).unwrap();
+            buffer.read_into_async(&mut cursor).await.unwrap();
+        }
+        assert_eq!(buffer.queued_messages(), 0);
+        assert_eq!(buffer.queued_bytes(), 0);
+        assert!(!buffer.is_full());
+    }
+
+    async fn send_all(buffer: &mut SendBuffer, channel: &SecureChannel) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        while buffer.should_encode_chunks() {
+            buffer.wait_for_stream().await;
+            buffer.encode_next_chunk(channel).unwrap();
+            buffer.read_into_async(&mut cursor).await.unwrap();
+        }
+        cursor.into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_buffer_stream() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        buffer.write(1, large_read(101), &channel).unwrap();
+        buffer.write(2, large_read(102), &channel).unwrap();
+        let expected = send_all(&mut buffer, &channel).await;
+
+        // A streamed message is queued as a single payload, and produces the same chunks.
+        let (mut buffer, channel) = get_buffer_and_channel();
+        buffer.stream_encoding_threshold = 1000;
+        buffer.write(1, large_read(101), &channel).unwrap();
+        assert_eq!(buffer.chunks.len(), 1);
+        buffer.write(2, large_read(102), &channel).unwrap();
+        assert_eq!(buffer.chunks.len(), 2);
+        assert_eq!(buffer.queued_messages(), 2);
+
+        assert_eq!(send_all(&mut buffer, &channel).await, expected);
+        assert_eq!(buffer.queued_messages(), 0);
+        assert_eq!(buffer.queued_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_stream_abort() {
+        let (mut buffer, channel) = get_buffer_and_channel();
+        buffer.stream_encoding_threshold = 1000;
+        let first = buffer.sequence_numbers.current();
+        buffer.write(1, large_read(101), &channel).unwrap();
+        buffer.write(2, large_read(102), &channel).unwrap();
+        assert_eq!(buffer.sequence_numbers.current(), first + 6);
+
+        let mut cursor = Cursor::new(Vec::new());
+        buffer.wait_for_stream().await;
+        buffer.encode_next_chunk(&channel).unwrap();
+        buffer.read_into_async(&mut cursor).await.unwrap();
+
+        assert!(buffer
+            .abort(
+                1,
+                StatusCode::BadRequestCancelledByClient,
+                "Cancelled",
+                &channel
+            )
+            .unwrap());
+        // The rest of the first message is replaced by an abort chunk, and the second
+        // message is renumbered without being encoded.
+        assert_eq!(buffer.chunks.len(), 2);
+        assert!(matches!(buffer.chunks[1], PendingPayload::Stream { .. }));
+        assert_eq!(buffer.chunks[1].sequence_number(), Some(first + 2));
+        assert_eq!(buffer.sequence_numbers.current(), first + 5);
+
+        send_all(&mut buffer, &channel).await;
+        assert_eq!(buffer.queued_messages(), 0);
+        assert_eq!(buffer.queued_bytes(), 0);
+    }
 }
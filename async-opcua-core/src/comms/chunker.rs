@@ -4,101 +4,62 @@
 
 //! Contains code for turning messages into chunks and chunks into messages.
 
-use std::io::{Read, Write};
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    ops::Range,
+};
 
 use crate::{
     comms::{
         message_chunk::{MessageChunk, MessageIsFinalType},
+        pool::BufferPool,
         secure_channel::SecureChannel,
         sequence_number::SequenceNumberHandle,
     },
     Message,
 };
 
+use bytes::Buf;
 use opcua_crypto::SecurityPolicy;
 use opcua_types::{
     encoding::BinaryEncodable, node_id::NodeId, status_code::StatusCode, BinaryDecodable,
     EncodingResult, Error, ObjectId,
 };
-use tracing::{debug, error, trace};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tracing::{debug, error, trace, warn};
 
 use super::message_chunk::MessageChunkType;
 
-/// Read implementation for a sequence of message chunks.
+/// Read implementation for a sequence of message chunk bodies.
 /// This lets us avoid allocating a buffer for the message.
 ///
-/// All this type does is `Read` to the end of each chunk, then step into the next
-/// chunk once the previous chunk is exhausted.
-struct ReceiveStream<'a, T> {
-    buffer: &'a [u8],
-    channel: &'a SecureChannel,
-    items: T,
-    num_items: usize,
-    pos: usize,
-    index: usize,
+/// All this type does is `Read` to the end of each body, then step into the next
+/// body once the previous one is exhausted. The bodies are either slices or
+/// [`Bytes`](bytes::Bytes) views into the chunks themselves, so no data is copied before it is decoded.
+struct ReceiveStream<B> {
+    bodies: VecDeque<B>,
 }
-impl<'a, T: Iterator<Item = &'a MessageChunk>> ReceiveStream<'a, T> {
-    fn new(channel: &'a SecureChannel, mut items: T, num_items: usize) -> Result<Self, Error> {
-        let Some(chunk) = items.next() else {
-            return Err(Error::new(
-                StatusCode::BadUnexpectedError,
-                "Stream contained no chunks",
-            ));
-        };
 
-        let chunk_info = chunk.chunk_info(channel)?;
-        let expected_is_final = if num_items == 1 {
-            MessageIsFinalType::Final
-        } else {
-            MessageIsFinalType::Intermediate
-        };
-        if chunk_info.message_header.is_final != expected_is_final {
-            return Err(Error::new(
-                StatusCode::BadDecodingError,
-                "Last chunk not marked as final",
-            ));
+impl<B: Buf> ReceiveStream<B> {
+    fn new(bodies: impl IntoIterator<Item = B>) -> Self {
+        Self {
+            bodies: bodies.into_iter().collect(),
         }
-
-        let body_start = chunk_info.body_offset;
-        let body_end = body_start + chunk_info.body_length;
-        let body_data = &chunk.data[body_start..body_end];
-        Ok(Self {
-            buffer: body_data,
-            channel,
-            items,
-            pos: 0,
-            num_items,
-            index: 0,
-        })
     }
 }
 
-impl<'a, T: Iterator<Item = &'a MessageChunk>> Read for ReceiveStream<'a, T> {
-    fn read(&mut self, mut buf: &mut [u8]) -> std::io::Result<usize> {
-        if self.buffer.len() == self.pos {
-            let Some(chunk) = self.items.next() else {
-                return Ok(0);
-            };
-            self.index += 1;
-            let chunk_info = chunk.chunk_info(self.channel)?;
-            let expected_is_final = if self.index == self.num_items - 1 {
-                MessageIsFinalType::Final
-            } else {
-                MessageIsFinalType::Intermediate
-            };
-            if chunk_info.message_header.is_final != expected_is_final {
-                return Err(StatusCode::BadDecodingError.into());
-            }
-
-            let body_start = chunk_info.body_offset;
-            let body_end = body_start + chunk_info.body_length;
-            let body_data = &chunk.data[body_start..body_end];
-            self.buffer = body_data;
-            self.pos = 0;
+impl<B: Buf> Read for ReceiveStream<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.bodies.front().is_some_and(|b| !b.has_remaining()) {
+            self.bodies.pop_front();
         }
-        let written = buf.write(&self.buffer[self.pos..])?;
-        self.pos += written;
-        Ok(written)
+        let Some(body) = self.bodies.front_mut() else {
+            return Ok(0);
+        };
+        let len = buf.len().min(body.chunk().len());
+        body.copy_to_slice(&mut buf[..len]);
+        Ok(len)
     }
 }
 
@@ -160,7 +121,7 @@ impl<'a> ChunkingStream<'a> {
                 chunks: Vec::with_capacity(expected_chunk_count),
                 expected_chunk_count,
                 max_body_per_chunk,
-                next_buf: vec![0; next_buf_size],
+                next_buf: BufferPool::global().take(next_buf_size),
                 buf_position: 0,
                 is_closed: false,
                 sequence_number,
@@ -177,7 +138,7 @@ impl<'a> ChunkingStream<'a> {
                 chunks: Vec::with_capacity(expected_chunk_count),
                 expected_chunk_count,
                 max_body_per_chunk,
-                next_buf: vec![0; next_buf_size],
+                next_buf: BufferPool::global().take(next_buf_size),
                 buf_position: 0,
                 is_closed: false,
                 sequence_number,
@@ -209,6 +170,7 @@ impl<'a> ChunkingStream<'a> {
             self.secure_channel,
             &buf,
         )?;
+        BufferPool::global().put(buf);
         self.sequence_number.increment(1);
         self.chunks.push(chunk);
 
@@ -218,7 +180,7 @@ impl<'a> ChunkingStream<'a> {
             } else {
                 self.max_body_per_chunk
             };
-            self.next_buf = vec![0; next_buf_size];
+            self.next_buf = BufferPool::global().take(next_buf_size);
             self.buf_position = 0;
         }
 
@@ -258,6 +220,176 @@ impl Write for ChunkingStream<'_> {
     }
 }
 
+/// Number of chunk bodies a streamed message may be encoded ahead of the chunks
+/// being sent.
+const STREAM_CHUNK_LOOKAHEAD: usize = 2;
+
+/// Write implementation that sends chunk bodies over a bounded channel as they fill up.
+/// This is used to encode a message on the blocking thread pool, where it blocks once it is
+/// [`STREAM_CHUNK_LOOKAHEAD`] chunks ahead of the reader.
+struct BodySender {
+    sender: mpsc::Sender<EncodingResult<Vec<u8>>>,
+    body_size: usize,
+    buf: Vec<u8>,
+}
+
+impl BodySender {
+    fn send_body(&mut self) -> std::io::Result<()> {
+        let body = std::mem::replace(
+            &mut self.buf,
+            BufferPool::global().take_with_capacity(self.body_size),
+        );
+        self.sender.blocking_send(Ok(body)).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "Chunk stream was dropped before the message was encoded",
+            )
+        })
+    }
+}
+
+impl Write for BodySender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let to_write = buf.len().min(self.body_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..to_write]);
+        if self.buf.len() == self.body_size {
+            self.send_body()?;
+        }
+        Ok(to_write)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A message that is encoded into chunks as they are needed, see [`Chunker::encode_stream`].
+///
+/// The message is encoded on the blocking thread pool, and stays at most a couple of chunks
+/// ahead of the reader, so only a few chunks of the message are in memory at any time.
+/// Dropping the stream stops the encoder.
+#[derive(Debug)]
+pub struct ChunkStream {
+    receiver: mpsc::Receiver<EncodingResult<Vec<u8>>>,
+    /// The body of the next chunk, if it has been received from the encoder.
+    next_body: Option<EncodingResult<Vec<u8>>>,
+    request_id: u32,
+    request_handle: Option<u32>,
+    message_type: MessageChunkType,
+    message_size: usize,
+    body_size: usize,
+    chunk_count: usize,
+    chunks_taken: usize,
+}
+
+impl ChunkStream {
+    /// Get the request ID of the streamed message.
+    pub fn request_id(&self) -> u32 {
+        self.request_id
+    }
+
+    /// Get the total number of chunks in the message.
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_count
+    }
+
+    /// Get the number of chunks that have not yet been taken from the stream.
+    pub fn remaining_chunks(&self) -> usize {
+        self.chunk_count - self.chunks_taken
+    }
+
+    /// Get the number of bytes of the message body that have not yet been taken
+    /// from the stream.
+    pub fn remaining_bytes(&self) -> usize {
+        self.message_size
+            .saturating_sub(self.chunks_taken * self.body_size)
+    }
+
+    fn encoder_stopped() -> EncodingResult<Vec<u8>> {
+        Err(Error::encoding(
+            "Message encoder stopped before the message was encoded",
+        ))
+    }
+
+    /// Wait until the next chunk of the message has been encoded, so that
+    /// [`ChunkStream::next_chunk`] can take it. This is cancel safe.
+    pub async fn ready(&mut self) {
+        if self.next_body.is_none() && self.remaining_chunks() > 0 {
+            self.next_body = Some(
+                self.receiver
+                    .recv()
+                    .await
+                    .unwrap_or_else(Self::encoder_stopped),
+            );
+        }
+    }
+
+    /// Check whether the next chunk of the message has been encoded, without waiting for it.
+    pub fn try_ready(&mut self) -> bool {
+        if self.next_body.is_none() && self.remaining_chunks() > 0 {
+            match self.receiver.try_recv() {
+                Ok(body) => self.next_body = Some(body),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => self.next_body = Some(Self::encoder_stopped()),
+            }
+        }
+        true
+    }
+
+    /// Take the next chunk of the message, giving it the sequence number `sequence_number`.
+    /// This fails if the chunk has not been encoded yet, use [`ChunkStream::ready`] to wait
+    /// for it.
+    pub fn next_chunk(
+        &mut self,
+        sequence_number: u32,
+        secure_channel: &SecureChannel,
+    ) -> Result<MessageChunk, Error> {
+        if self.remaining_chunks() == 0 {
+            return Err(Error::encoding("All chunks of the message have been taken")
+                .with_context(Some(self.request_id), self.request_handle));
+        }
+        if !self.try_ready() {
+            return Err(Error::new(
+                StatusCode::BadInvalidState,
+                "The next chunk of the message has not been encoded yet",
+            )
+            .with_context(Some(self.request_id), self.request_handle));
+        }
+        let is_final = self.remaining_chunks() == 1;
+        let expected_size = if is_final {
+            self.message_size % self.body_size
+        } else {
+            self.body_size
+        };
+        let body = match self.next_body.take() {
+            Some(Ok(body)) if body.len() == expected_size => body,
+            Some(Err(e)) => return Err(e),
+            Some(Ok(_)) | None => {
+                return Err(
+                    Error::encoding("Message did not encode to the expected size")
+                        .with_context(Some(self.request_id), self.request_handle),
+                )
+            }
+        };
+        self.chunks_taken += 1;
+
+        let chunk = MessageChunk::new(
+            sequence_number,
+            self.request_id,
+            self.message_type,
+            if is_final {
+                MessageIsFinalType::Final
+            } else {
+                MessageIsFinalType::Intermediate
+            },
+            secure_channel,
+            &body,
+        );
+        BufferPool::global().put(body);
+        chunk
+    }
+}
+
 /// The Chunker is responsible for turning messages to chunks and chunks into messages.
 pub struct Chunker;
 
@@ -319,11 +451,11 @@ impl Chunker {
                         expected_sequence_number,
                         sequence_number
                     );
-                    return Err(Error::new(
-                        StatusCode::BadSequenceNumberInvalid,
-                        format!(
-                            "Chunk sequence number of {sequence_number} is not the expected value of {expected_sequence_number}, idx {i}"
-                        ),
+                    return Err(Self::invalid_sequence_number(
+                        &sequence_numbers,
+                        secure_channel,
+                        sequence_number,
+                        i,
                     ));
                 }
             }
@@ -342,6 +474,62 @@ impl Chunker {
         Ok(sequence_numbers.current())
     }
 
+    /// Advance `sequence_numbers` past a chunk that is discarded without being decoded.
+    ///
+    /// Chunks of discarded messages may not have been validated, so a gap is accepted,
+    /// but only up to [`REPLAY_WINDOW`](super::sequence_number::REPLAY_WINDOW) sequence
+    /// numbers ahead of the expected one. Earlier sequence numbers are rejected, and
+    /// reported as replays if they were recently seen.
+    pub fn skip_sequence_number(
+        sequence_numbers: &mut SequenceNumberHandle,
+        secure_channel: &SecureChannel,
+        sequence_number: u32,
+    ) -> Result<(), Error> {
+        if !sequence_numbers.is_ahead(sequence_number) {
+            return Err(Self::invalid_sequence_number(
+                sequence_numbers,
+                secure_channel,
+                sequence_number,
+                0,
+            ));
+        }
+        sequence_numbers.set(sequence_number);
+        sequence_numbers.increment(1);
+        Ok(())
+    }
+
+    /// Create the error for a chunk with an unexpected sequence number, and report it
+    /// to the metrics hooks, as a replay if the sequence number was recently seen.
+    fn invalid_sequence_number(
+        sequence_numbers: &SequenceNumberHandle,
+        secure_channel: &SecureChannel,
+        sequence_number: u32,
+        idx: usize,
+    ) -> Error {
+        let expected_sequence_number = sequence_numbers.current();
+        if sequence_numbers.is_recent(sequence_number) {
+            warn!(
+                "Received chunk with sequence number {} which has already been seen, the message may be replayed",
+                sequence_number
+            );
+            secure_channel.metrics().replay_detected();
+            Error::new(
+                StatusCode::BadSequenceNumberInvalid,
+                format!(
+                    "Chunk sequence number of {sequence_number} has already been received, expected {expected_sequence_number}, idx {idx}"
+                ),
+            )
+        } else {
+            secure_channel.metrics().sequence_number_invalid();
+            Error::new(
+                StatusCode::BadSequenceNumberInvalid,
+                format!(
+                    "Chunk sequence number of {sequence_number} is not the expected value of {expected_sequence_number}, idx {idx}"
+                ),
+            )
+        }
+    }
+
     /// Encodes a message using the supplied sequence number and secure channel info and emits the corresponding chunks
     ///
     /// max_chunk_count refers to the maximum byte length that a chunk should not exceed or 0 for no limit
@@ -413,6 +601,147 @@ impl Chunker {
         stream.finish()
     }
 
+    /// Encodes a message into chunks as they are taken from the returned [`ChunkStream`],
+    /// instead of all at once. The message is encoded on the tokio blocking thread pool, so
+    /// only a few chunks are held in memory at a time, which bounds the memory used by very
+    /// large messages. This must be called from within a tokio runtime.
+    ///
+    /// `max_chunk_size` must be non-zero. The chunks are otherwise identical to those
+    /// produced by [`Chunker::encode`].
+    pub fn encode_stream(
+        request_id: u32,
+        max_message_size: usize,
+        max_chunk_size: usize,
+        secure_channel: &SecureChannel,
+        supported_message: impl Message + Send + 'static,
+    ) -> std::result::Result<ChunkStream, Error> {
+        let handle = supported_message.request_handle();
+        let ctx_handle = if handle > 0 { Some(handle) } else { None };
+        let ctx_owned = (*secure_channel.context()).clone();
+        let ctx = ctx_owned.context();
+
+        let message_size = supported_message.byte_len(&ctx);
+        if max_message_size > 0 && message_size > max_message_size {
+            return Err(Error::new(
+                if secure_channel.is_client_role() {
+                    StatusCode::BadRequestTooLarge
+                } else {
+                    StatusCode::BadResponseTooLarge
+                },
+                format!(
+                    "Max message size is {max_message_size} and message {message_size} exceeds that"
+                ),
+            )
+            .with_context(Some(request_id), ctx_handle));
+        }
+
+        let node_id = supported_message.type_id();
+        let message_size = message_size + node_id.byte_len(&ctx);
+        let message_type = supported_message.message_type();
+        let body_size =
+            MessageChunk::body_size_from_message_size(message_type, secure_channel, max_chunk_size)
+                .map_err(|_| {
+                    Error::new(
+                StatusCode::BadTcpInternalError,
+                format!("body_size_from_message_size error for max_chunk_size = {max_chunk_size}"),
+            )
+            .with_context(Some(request_id), ctx_handle)
+                })?;
+
+        let runtime = tokio::runtime::Handle::try_current().map_err(|e| {
+            Error::new(
+                StatusCode::BadResourceUnavailable,
+                format!("Cannot stream a message outside of a tokio runtime: {e}"),
+            )
+            .with_context(Some(request_id), ctx_handle)
+        })?;
+        let (sender, receiver) = mpsc::channel(STREAM_CHUNK_LOOKAHEAD);
+        // Dropping the join handle detaches the encoder, which stops once the stream is dropped.
+        drop(runtime.spawn_blocking(move || {
+            let ctx = ctx_owned.context();
+            let mut stream = BodySender {
+                sender,
+                body_size,
+                buf: BufferPool::global().take_with_capacity(body_size),
+            };
+            let res = node_id.encode(&mut stream, &ctx).and_then(|_| {
+                supported_message
+                    .encode(&mut stream, &ctx)
+                    .map_err(|e| e.with_context(Some(request_id), ctx_handle))
+            });
+            match res {
+                // The last chunk is sent even if it is empty, like `encode` does.
+                Ok(_) => {
+                    let _ = stream.send_body();
+                }
+                Err(e) => {
+                    let _ = stream.sender.blocking_send(Err(e));
+                }
+            }
+        }));
+
+        Ok(ChunkStream {
+            receiver,
+            next_body: None,
+            request_id,
+            request_handle: ctx_handle,
+            message_type,
+            message_size,
+            body_size,
+            chunk_count: message_size / body_size + 1,
+            chunks_taken: 0,
+        })
+    }
+
+    /// Encodes a message as a single body, the node ID of the message followed by the message
+    /// itself, without any chunk headers or security. This is the encoding used by transports
+    /// that do not split messages into chunks, such as HTTPS.
+    ///
+    /// max_message_size refers to the maximum byte length of a message or 0 for no limit
+    pub fn encode_unchunked(
+        max_message_size: usize,
+        secure_channel: &SecureChannel,
+        supported_message: &impl Message,
+    ) -> std::result::Result<Vec<u8>, Error> {
+        let handle = supported_message.request_handle();
+        let ctx_handle = if handle > 0 { Some(handle) } else { None };
+
+        let ctx_r = secure_channel.context();
+        let ctx = ctx_r.context();
+        let message_size = supported_message.byte_len(&ctx);
+        if max_message_size > 0 && message_size > max_message_size {
+            return Err(Error::new(
+                if secure_channel.is_client_role() {
+                    StatusCode::BadRequestTooLarge
+                } else {
+                    StatusCode::BadResponseTooLarge
+                },
+                format!(
+                    "Max message size is {max_message_size} and message {message_size} exceeds that"
+                ),
+            )
+            .with_context(None, ctx_handle));
+        }
+
+        let node_id = supported_message.type_id();
+        let mut body = Vec::with_capacity(message_size + node_id.byte_len(&ctx));
+        node_id.encode(&mut body, &ctx)?;
+        supported_message
+            .encode(&mut body, &ctx)
+            .map_err(|e| e.with_context(None, ctx_handle))?;
+        Ok(body)
+    }
+
+    /// Decodes a message from a single body, as written by [`Chunker::encode_unchunked`].
+    pub fn decode_unchunked<T: Message>(
+        mut body: &[u8],
+        secure_channel: &SecureChannel,
+    ) -> std::result::Result<T, Error> {
+        let res = Self::decode_body(&mut body, secure_channel, None);
+        Self::record_decode_result(secure_channel, &res);
+        res
+    }
+
     /// Decodes a series of chunks to create a message. The message must be of a `SupportedMessage`
     /// type otherwise an error will occur.
     pub fn decode<T: Message>(
@@ -420,7 +749,58 @@ impl Chunker {
         secure_channel: &SecureChannel,
         expected_node_id: Option<NodeId>,
     ) -> std::result::Result<T, Error> {
-        // Calculate the size of data held in all chunks
+        let res = Self::chunk_bodies(chunks, secure_channel).and_then(|bodies| {
+            let bodies = chunks
+                .iter()
+                .zip(bodies)
+                .map(|(chunk, range)| &chunk.data[range]);
+            Self::decode_bodies(bodies, secure_channel, expected_node_id)
+        });
+        Self::record_decode_result(secure_channel, &res);
+        res
+    }
+
+    /// Decodes a series of owned chunks into a message, like [`Chunker::decode`].
+    ///
+    /// Each chunk is turned into a [`Bytes`](bytes::Bytes) buffer and the message is decoded from slices
+    /// of those buffers, without copying the chunk bodies into a contiguous message.
+    /// The chunk buffers are returned to the pool once decoding is done.
+    pub fn decode_owned<T: Message>(
+        chunks: Vec<MessageChunk>,
+        secure_channel: &SecureChannel,
+        expected_node_id: Option<NodeId>,
+    ) -> std::result::Result<T, Error> {
+        let res = Self::chunk_bodies(&chunks, secure_channel).and_then(|bodies| {
+            let bodies = chunks
+                .into_iter()
+                .zip(bodies)
+                .map(|(chunk, range)| chunk.into_bytes().slice(range));
+            Self::decode_bodies(bodies, secure_channel, expected_node_id)
+        });
+        Self::record_decode_result(secure_channel, &res);
+        res
+    }
+
+    fn record_decode_result<T>(secure_channel: &SecureChannel, res: &Result<T, Error>) {
+        match res {
+            Ok(_) => secure_channel.metrics().message_received(),
+            Err(e) => secure_channel.metrics().decoding_error(e.status()),
+        }
+    }
+
+    /// Validate the chunks and find the body of each. Each chunk is only parsed once.
+    fn chunk_bodies(
+        chunks: &[MessageChunk],
+        secure_channel: &SecureChannel,
+    ) -> std::result::Result<Vec<Range<usize>>, Error> {
+        if chunks.is_empty() {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                "Stream contained no chunks",
+            ));
+        }
+
+        let mut bodies = Vec::with_capacity(chunks.len());
         for (i, chunk) in chunks.iter().enumerate() {
             let chunk_info = chunk.chunk_info(secure_channel)?;
             // The last most chunk is expected to be final, the rest intermediate
@@ -434,10 +814,39 @@ impl Chunker {
                     "Last message in sequence is not marked as final",
                 ));
             }
+            let body_start = chunk_info.body_offset;
+            let body_end = body_start + chunk_info.body_length;
+            bodies.push(body_start..body_end);
         }
+        Ok(bodies)
+    }
 
-        let mut stream = ReceiveStream::new(secure_channel, chunks.iter(), chunks.len())?;
+    fn decode_bodies<T: Message, B: Buf>(
+        bodies: impl Iterator<Item = B>,
+        secure_channel: &SecureChannel,
+        expected_node_id: Option<NodeId>,
+    ) -> std::result::Result<T, Error> {
+        let mut bodies = bodies.peekable();
+        let first = bodies.next();
+        match (first, bodies.peek()) {
+            // Single chunk messages are by far the most common, decode
+            // directly from the chunk body.
+            (Some(body), None) => {
+                Self::decode_body(&mut body.reader(), secure_channel, expected_node_id)
+            }
+            (first, _) => Self::decode_body(
+                &mut ReceiveStream::new(first.into_iter().chain(bodies)),
+                secure_channel,
+                expected_node_id,
+            ),
+        }
+    }
 
+    fn decode_body<T: Message>(
+        stream: &mut impl Read,
+        secure_channel: &SecureChannel,
+        expected_node_id: Option<NodeId>,
+    ) -> std::result::Result<T, Error> {
         // The extension object prefix is just the node id. A point the spec rather unhelpfully doesn't
         // elaborate on. Probably because people enjoy debugging why the stream pos is out by 1 byte
         // for hours.
@@ -446,18 +855,18 @@ impl Chunker {
         let ctx = ctx_r.context();
 
         // Read node id from stream
-        let node_id = NodeId::decode(&mut stream, &ctx)?;
+        let node_id = NodeId::decode(stream, &ctx)?;
         let object_id = Self::object_id_from_node_id(node_id, expected_node_id)?;
 
         // Now decode the payload using the node id.
-        match T::decode_by_object_id(&mut stream, object_id, &ctx) {
+        match T::decode_by_object_id(stream, object_id, &ctx) {
             Ok(decoded_message) => {
                 // debug!("Returning decoded msg {:?}", decoded_message);
                 Ok(decoded_message)
             }
             Err(err) => {
                 debug!("Cannot decode message {:?}, err = {:?}", object_id, err);
-                Err(err)
+                Err(err.with_node_id(object_id))
             }
         }
     }
@@ -470,11 +879,13 @@ impl Chunker {
             if node_id != id {
                 return Err(Error::decoding(format!(
                     "The message ID {node_id} is not the expected value {id}"
-                )));
+                ))
+                .with_node_id(node_id));
             }
         }
-        node_id
-            .as_object_id()
-            .map_err(|_| Error::decoding(format!("The message id {node_id} is not an object id")))
+        node_id.as_object_id().map_err(|_| {
+            Error::decoding(format!("The message id {node_id} is not an object id"))
+                .with_node_id(node_id.clone())
+        })
     }
 }
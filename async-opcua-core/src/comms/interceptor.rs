@@ -0,0 +1,157 @@
+//! Hooks for observing and filtering messages passing through a secure channel.
+//!
+//! Implement [`MessageInterceptor`] to see every request and response sent or received
+//! on a channel, for example for audit logging, shadowing requests to another system,
+//! or rejecting requests that break some security policy. All methods have default
+//! implementations accepting the message, so only the methods of interest need to
+//! be implemented.
+
+use std::sync::Arc;
+
+use opcua_crypto::SecurityPolicy;
+use opcua_types::{MessageSecurityMode, NodeId, StatusCode};
+
+use crate::{comms::secure_channel::SecureChannel, Message, RequestMessage, ResponseMessage};
+
+/// Whether a message is being received or sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageDirection {
+    /// The message was received and decoded.
+    Incoming,
+    /// The message is about to be sent.
+    Outgoing,
+}
+
+/// Summary of a message passed to a [`MessageInterceptor`].
+#[derive(Debug, Clone)]
+pub struct InterceptedMessage {
+    /// Whether the message is being received or sent.
+    pub direction: MessageDirection,
+    /// Type ID of the message.
+    pub type_id: NodeId,
+    /// Name of the service the message belongs to, such as `Read` or `Publish`.
+    pub service: &'static str,
+    /// Request handle of the message, from its request or response header.
+    pub request_handle: u32,
+    /// ID of the request on the secure channel.
+    pub request_id: u32,
+    /// Total size of the message chunks in bytes, including headers, but without
+    /// any signature, padding, or encryption. For outgoing messages that are
+    /// encoded as they are sent, this is the size of the encoded message alone.
+    pub size: usize,
+    /// ID of the secure channel the message was sent or received on.
+    pub secure_channel_id: u32,
+    /// Security policy of the secure channel.
+    pub security_policy: SecurityPolicy,
+    /// Security mode of the secure channel.
+    pub security_mode: MessageSecurityMode,
+}
+
+/// Trait for observing or rejecting messages on a secure channel. Methods are called
+/// inline on the connection task, so implementations should be fast, and hand off
+/// anything expensive to another task.
+///
+/// Returning an error rejects the message. A rejected incoming request is answered with
+/// a service fault containing the error, without being processed. A rejected outgoing
+/// request, or a rejected incoming response, fails the request with the error.
+/// A rejected outgoing response is replaced by a service fault.
+pub trait MessageInterceptor: Send + Sync {
+    /// Called with every request received or about to be sent.
+    #[allow(unused)]
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        Ok(())
+    }
+
+    /// Called with every response received or about to be sent.
+    #[allow(unused)]
+    fn on_response(
+        &self,
+        info: &InterceptedMessage,
+        response: &ResponseMessage,
+    ) -> Result<(), StatusCode> {
+        Ok(())
+    }
+}
+
+/// Forwarding implementation, so that the application can keep a reference to
+/// its interceptor, for example to read collected data.
+impl<T: MessageInterceptor + ?Sized> MessageInterceptor for Arc<T> {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        (**self).on_request(info, request)
+    }
+
+    fn on_response(
+        &self,
+        info: &InterceptedMessage,
+        response: &ResponseMessage,
+    ) -> Result<(), StatusCode> {
+        (**self).on_response(info, response)
+    }
+}
+
+/// Cheaply cloneable list of [`MessageInterceptor`]s, called in the order they were added.
+/// Empty by default.
+#[derive(Clone, Default)]
+pub struct MessageInterceptors(Vec<Arc<dyn MessageInterceptor>>);
+
+impl MessageInterceptors {
+    /// Add an interceptor, called after those added before it.
+    pub fn push(&mut self, interceptor: impl MessageInterceptor + 'static) {
+        self.0.push(Arc::new(interceptor));
+    }
+
+    /// Return `true` if there are no interceptors.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Pass a message sent or received on `channel` to each interceptor in turn,
+    /// stopping at the first one that rejects it.
+    pub fn intercept(
+        &self,
+        channel: &SecureChannel,
+        direction: MessageDirection,
+        message: &impl Message,
+        request_id: u32,
+        size: usize,
+    ) -> Result<(), StatusCode> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let info = InterceptedMessage {
+            direction,
+            type_id: message.type_id(),
+            service: message.type_name(),
+            request_handle: message.request_handle(),
+            request_id,
+            size,
+            secure_channel_id: channel.secure_channel_id(),
+            security_policy: channel.security_policy(),
+            security_mode: channel.security_mode(),
+        };
+        for interceptor in &self.0 {
+            message.intercept(&**interceptor, &info)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for MessageInterceptors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MessageInterceptors({})", self.0.len())
+    }
+}
+
+impl PartialEq for MessageInterceptors {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
@@ -7,6 +7,7 @@
 
 use std::io::{Cursor, Read, Write};
 
+use bytes::Bytes;
 use opcua_types::{
     process_decode_io_result, process_encode_io_result, read_u32, read_u8, status_code::StatusCode,
     write_u32, write_u8, DecodingOptions, EncodingResult, Error, SimpleBinaryDecodable,
@@ -16,6 +17,7 @@ use tracing::{error, trace};
 
 use super::{
     message_chunk_info::ChunkInfo,
+    pool::BufferPool,
     secure_channel::SecureChannel,
     security_header::{SecurityHeader, SequenceHeader},
     tcp_types::{
@@ -140,6 +142,8 @@ impl MessageChunkHeader {}
 /// The chunk's data may be signed and encrypted. To extract the message requires all the chunks
 /// to be available in sequence so they can be formed back into the message.
 #[derive(Debug)]
+///
+/// The chunk's buffer is taken from, and returned to, the global [`BufferPool`].
 pub struct MessageChunk {
     /// All of the chunk's data including headers, payload, padding, signature
     pub data: Vec<u8>,
@@ -186,7 +190,7 @@ impl SimpleBinaryDecodable for MessageChunk {
             ))
         } else {
             // Now make a buffer to write the header and message into
-            let data = vec![0u8; message_size];
+            let data = BufferPool::global().take(message_size);
             let mut stream = Cursor::new(data);
 
             // Write header to a buffer
@@ -204,6 +208,44 @@ impl SimpleBinaryDecodable for MessageChunk {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for MessageChunk {
+    /// Generate a chunk without security, with a valid header and an arbitrary body.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let message_type = *u.choose(&[
+            MessageChunkType::Message,
+            MessageChunkType::OpenSecureChannel,
+            MessageChunkType::CloseSecureChannel,
+        ])?;
+        let is_final = *u.choose(&[
+            MessageIsFinalType::Intermediate,
+            MessageIsFinalType::Final,
+            MessageIsFinalType::FinalError,
+        ])?;
+        MessageChunk::new(
+            u.arbitrary()?,
+            u.arbitrary()?,
+            message_type,
+            is_final,
+            &SecureChannel::new_no_certificate_store(),
+            u.arbitrary()?,
+        )
+        .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl AsRef<[u8]> for MessageChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for MessageChunk {
+    fn drop(&mut self) {
+        BufferPool::global().put(std::mem::take(&mut self.data));
+    }
+}
+
 #[derive(Debug)]
 /// Error returned if the chunk is too small, this indicates
 /// an error somewhere else.
@@ -244,7 +286,7 @@ impl MessageChunk {
             secure_channel_id,
         };
 
-        let mut buf = vec![0u8; message_size];
+        let mut buf = BufferPool::global().take(message_size);
         let buf_ref = &mut buf as &mut [u8];
         let mut stream = Cursor::new(buf_ref);
         // write chunk header
@@ -259,6 +301,14 @@ impl MessageChunk {
         Ok(MessageChunk { data: buf })
     }
 
+    /// Convert the chunk into a shared [`Bytes`] buffer, without copying its data.
+    ///
+    /// The chunk's buffer is returned to the pool once every slice of the
+    /// returned buffer has been dropped.
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from_owner(self)
+    }
+
     /// Calculates the body size that fit inside of a message chunk of a particular size.
     /// This requires calculating the size of the header, the signature, padding etc. and deducting it
     /// to reveal the message size
@@ -345,6 +395,17 @@ impl MessageChunk {
         ChunkInfo::new(self, secure_channel)
     }
 
+    /// Overwrite the sequence number of this chunk. The chunk must not be encrypted.
+    pub(crate) fn set_sequence_number(
+        &mut self,
+        sequence_number: u32,
+        secure_channel: &SecureChannel,
+    ) -> EncodingResult<()> {
+        let offset = self.chunk_info(secure_channel)?.sequence_header_offset;
+        let mut stream = Cursor::new(&mut self.data[offset..]);
+        write_u32(&mut stream, sequence_number)
+    }
+
     pub(crate) fn encrypted_data_offset(
         &self,
         decoding_options: &DecodingOptions,
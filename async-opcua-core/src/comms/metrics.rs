@@ -0,0 +1,155 @@
+//! Hooks for collecting metrics from the transport layer.
+//!
+//! Implement [`TransportMetrics`] to forward counters to a metrics system such as
+//! Prometheus or OpenTelemetry. All methods have empty default implementations, so
+//! only the counters of interest need to be implemented.
+
+use std::{
+    ops::Deref,
+    sync::{Arc, OnceLock},
+};
+
+use opcua_types::StatusCode;
+
+/// Trait for receiving metrics from the transport layer. Methods are called
+/// inline on the connection task, so implementations should be cheap, typically
+/// just incrementing an atomic counter.
+pub trait TransportMetrics: Send + Sync {
+    /// Called when bytes are received from the socket, once per received frame.
+    #[allow(unused)]
+    fn bytes_received(&self, bytes: usize) {}
+
+    /// Called when bytes are written to the socket.
+    #[allow(unused)]
+    fn bytes_sent(&self, bytes: usize) {}
+
+    /// Called when a message chunk is received.
+    fn chunk_received(&self) {}
+
+    /// Called when a message chunk is queued for sending.
+    fn chunk_sent(&self) {}
+
+    /// Called when a complete message has been decoded from received chunks.
+    fn message_received(&self) {}
+
+    /// Called when a complete message has been encoded and queued for sending.
+    fn message_sent(&self) {}
+
+    /// Called when decoding an incoming frame or message fails.
+    #[allow(unused)]
+    fn decoding_error(&self, status: StatusCode) {}
+
+    /// Called when encoding an outgoing message fails.
+    #[allow(unused)]
+    fn encoding_error(&self, status: StatusCode) {}
+
+    /// Called when the secure channel security token is renewed.
+    fn secure_channel_renewed(&self) {}
+
+    /// Called when a message is rejected as a replay, because its sequence number
+    /// or nonce has already been seen on the channel.
+    fn replay_detected(&self) {}
+
+    /// Called when a message is rejected because its sequence number is not
+    /// the expected one, and not a recently seen one either.
+    fn sequence_number_invalid(&self) {}
+}
+
+/// Forwarding implementation, so that the application can keep a reference to
+/// its metrics, for example to read counters.
+impl<T: TransportMetrics + ?Sized> TransportMetrics for Arc<T> {
+    fn bytes_received(&self, bytes: usize) {
+        (**self).bytes_received(bytes)
+    }
+
+    fn bytes_sent(&self, bytes: usize) {
+        (**self).bytes_sent(bytes)
+    }
+
+    fn chunk_received(&self) {
+        (**self).chunk_received()
+    }
+
+    fn chunk_sent(&self) {
+        (**self).chunk_sent()
+    }
+
+    fn message_received(&self) {
+        (**self).message_received()
+    }
+
+    fn message_sent(&self) {
+        (**self).message_sent()
+    }
+
+    fn decoding_error(&self, status: StatusCode) {
+        (**self).decoding_error(status)
+    }
+
+    fn encoding_error(&self, status: StatusCode) {
+        (**self).encoding_error(status)
+    }
+
+    fn secure_channel_renewed(&self) {
+        (**self).secure_channel_renewed()
+    }
+
+    fn replay_detected(&self) {
+        (**self).replay_detected()
+    }
+
+    fn sequence_number_invalid(&self) {
+        (**self).sequence_number_invalid()
+    }
+}
+
+/// Metrics implementation that discards everything.
+pub struct NoopTransportMetrics;
+
+impl TransportMetrics for NoopTransportMetrics {}
+
+/// Cheaply cloneable handle to a shared [`TransportMetrics`] implementation.
+/// Defaults to [`NoopTransportMetrics`].
+#[derive(Clone)]
+pub struct TransportMetricsHandle(Arc<dyn TransportMetrics>);
+
+impl TransportMetricsHandle {
+    /// Create a new handle wrapping `metrics`.
+    pub fn new(metrics: impl TransportMetrics + 'static) -> Self {
+        Self(Arc::new(metrics))
+    }
+}
+
+impl From<Arc<dyn TransportMetrics>> for TransportMetricsHandle {
+    fn from(value: Arc<dyn TransportMetrics>) -> Self {
+        Self(value)
+    }
+}
+
+impl Default for TransportMetricsHandle {
+    fn default() -> Self {
+        // Share a single no-op instance, so that default handles compare equal.
+        static NOOP: OnceLock<Arc<dyn TransportMetrics>> = OnceLock::new();
+        Self(NOOP.get_or_init(|| Arc::new(NoopTransportMetrics)).clone())
+    }
+}
+
+impl Deref for TransportMetricsHandle {
+    type Target = dyn TransportMetrics;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl std::fmt::Debug for TransportMetricsHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TransportMetricsHandle")
+    }
+}
+
+impl PartialEq for TransportMetricsHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
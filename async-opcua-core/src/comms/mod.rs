@@ -7,11 +7,19 @@
 
 pub mod buffer;
 pub mod chunker;
+pub mod interceptor;
 pub mod message_chunk;
 pub mod message_chunk_info;
+pub mod metrics;
+mod parallel;
+pub mod pool;
+pub mod priority;
+pub mod rate_limit;
 pub mod secure_channel;
 pub mod security_header;
 pub mod sequence_number;
+pub mod socket;
+pub mod stream;
 pub mod tcp_codec;
 pub mod tcp_types;
 pub mod url;
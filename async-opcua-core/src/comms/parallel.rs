@@ -0,0 +1,57 @@
+//! Helper for running CPU heavy work on message chunks, such as encryption and
+//! signing, on multiple threads.
+//!
+//! The work is only spread across threads with the `parallel-crypto` feature, otherwise
+//! chunks are always processed sequentially.
+
+/// Apply `f` to each item in `items`, splitting the work across the global rayon
+/// thread pool. The results are returned in the same order as the input, so chunks
+/// still go on the wire in sequence.
+///
+/// The calling thread waits for the work to finish. When called from a multi-threaded
+/// tokio runtime, the worker thread is handed over to the blocking pool while it
+/// waits, so other tasks keep running.
+#[cfg(feature = "parallel-crypto")]
+pub(crate) fn map_ordered<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    use rayon::prelude::*;
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    if items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let run = || items.par_iter().map(&f).collect();
+    match Handle::try_current() {
+        Ok(h) if h.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(run)
+        }
+        _ => run(),
+    }
+}
+
+/// Apply `f` to each item in `items`, in order. Without the `parallel-crypto`
+/// feature the work is done sequentially on the calling thread.
+#[cfg(not(feature = "parallel-crypto"))]
+pub(crate) fn map_ordered<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map_ordered;
+
+    #[test]
+    fn map_preserves_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let res = map_ordered(&items, |i| i * 2);
+        assert_eq!(res, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+
+        assert!(map_ordered(&[] as &[u32], |i| *i).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn map_in_multi_thread_runtime() {
+        let items: Vec<u32> = (0..1000).collect();
+        let res = map_ordered(&items, |i| i + 1);
+        assert_eq!(res, items.iter().map(|i| i + 1).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,153 @@
+//! Pool of byte buffers used for message chunks.
+//!
+//! Every chunk sent or received needs a buffer roughly the size of the negotiated
+//! chunk size. Under heavy load, allocating and freeing these buffers shows up as
+//! significant allocator pressure, so instead chunks take their buffers from a shared
+//! pool and return them when dropped.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::{const_mutex, Mutex};
+
+/// Default maximum number of buffers kept in the global pool.
+pub const DEFAULT_MAX_POOLED_BUFFERS: usize = 256;
+/// Default maximum capacity of a buffer that is returned to the global pool.
+/// Larger buffers are freed instead of being kept around.
+pub const DEFAULT_MAX_POOLED_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+static GLOBAL_POOL: BufferPool = BufferPool::new(
+    DEFAULT_MAX_POOLED_BUFFERS,
+    DEFAULT_MAX_POOLED_BUFFER_CAPACITY,
+);
+
+/// A bounded pool of reusable byte buffers.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: AtomicUsize,
+    max_buffer_capacity: AtomicUsize,
+}
+
+impl BufferPool {
+    /// Create a new empty pool, keeping at most `max_buffers` buffers, each with a
+    /// capacity of at most `max_buffer_capacity`.
+    pub const fn new(max_buffers: usize, max_buffer_capacity: usize) -> Self {
+        Self {
+            buffers: const_mutex(Vec::new()),
+            max_buffers: AtomicUsize::new(max_buffers),
+            max_buffer_capacity: AtomicUsize::new(max_buffer_capacity),
+        }
+    }
+
+    /// Get the process wide pool used for message chunks.
+    pub fn global() -> &'static BufferPool {
+        &GLOBAL_POOL
+    }
+
+    /// Change the limits of this pool. Setting `max_buffers` to zero disables pooling.
+    /// Buffers exceeding the new limits are freed.
+    pub fn set_limits(&self, max_buffers: usize, max_buffer_capacity: usize) {
+        self.max_buffers.store(max_buffers, Ordering::Relaxed);
+        self.max_buffer_capacity
+            .store(max_buffer_capacity, Ordering::Relaxed);
+        let mut buffers = self.buffers.lock();
+        buffers.retain(|b| b.capacity() <= max_buffer_capacity);
+        buffers.truncate(max_buffers);
+    }
+
+    /// Take a zeroed buffer of length `len` from the pool, allocating a new one if
+    /// the pool is empty.
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        let mut buf = self.take_empty();
+        buf.resize(len, 0);
+        buf
+    }
+
+    /// Take a buffer from the pool containing a copy of `data`.
+    pub fn take_copy(&self, data: &[u8]) -> Vec<u8> {
+        let mut buf = self.take_empty();
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    /// Take an empty buffer from the pool with room for at least `capacity` bytes.
+    pub fn take_with_capacity(&self, capacity: usize) -> Vec<u8> {
+        let mut buf = self.take_empty();
+        buf.reserve(capacity);
+        buf
+    }
+
+    fn take_empty(&self) -> Vec<u8> {
+        let mut buf = self.buffers.lock().pop().unwrap_or_default();
+        buf.clear();
+        buf
+    }
+
+    /// Return a buffer to the pool. The buffer is freed if the pool is full or
+    /// the buffer is too large.
+    pub fn put(&self, buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > self.max_buffer_capacity.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        let mut buffers = self.buffers.lock();
+        if buffers.len() < self.max_buffers.load(Ordering::Relaxed) {
+            buffers.push(buf);
+        }
+    }
+
+    /// Get the number of buffers currently in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().len()
+    }
+
+    /// Return `true` if the pool currently holds no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPool;
+
+    #[test]
+    fn reuse_buffers() {
+        let pool = BufferPool::new(2, 1024);
+        let mut buf = pool.take(100);
+        assert_eq!(buf.len(), 100);
+        buf[0] = 5;
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        assert_eq!(pool.len(), 1);
+
+        // The same allocation is reused, and cleared.
+        let buf = pool.take(50);
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(buf, vec![0u8; 50]);
+        assert!(pool.is_empty());
+
+        let copy = pool.take_copy(&[1, 2, 3]);
+        assert_eq!(copy, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn pool_limits() {
+        let pool = BufferPool::new(2, 1024);
+        // Too large
+        pool.put(Vec::with_capacity(2048));
+        assert!(pool.is_empty());
+        // Empty buffers are not worth keeping
+        pool.put(Vec::new());
+        assert!(pool.is_empty());
+
+        for _ in 0..3 {
+            pool.put(Vec::with_capacity(100));
+        }
+        assert_eq!(pool.len(), 2);
+
+        pool.set_limits(0, 1024);
+        assert!(pool.is_empty());
+        pool.put(Vec::with_capacity(100));
+        assert!(pool.is_empty());
+    }
+}
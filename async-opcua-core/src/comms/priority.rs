@@ -0,0 +1,60 @@
+//! Priorities for outgoing messages.
+//!
+//! By default messages are sent in the order they are written to the send buffer.
+//! A [`PriorityPolicy`] lets some services jump ahead of others in the queue, for
+//! example so that publish responses and keep-alives are not held up behind a large
+//! history read response on a slow link.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Priority of an outgoing message. Queued messages with a higher priority are sent
+/// before those with a lower priority, otherwise messages are sent in order.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum MessagePriority {
+    /// Sent after any queued messages with normal or high priority.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// Sent before any queued messages with normal or low priority.
+    High,
+}
+
+/// Policy deciding the priority of outgoing messages by the service they belong to.
+///
+/// A message that has been partially sent is always completed before the next message
+/// is started, so a high priority message waits for at most one chunk of another message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct PriorityPolicy {
+    /// Priority of messages by service name, such as `Publish` or `HistoryRead`.
+    /// Messages of services not listed here have normal priority.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub services: BTreeMap<String, MessagePriority>,
+}
+
+impl PriorityPolicy {
+    /// Policy that keeps subscriptions flowing during large transfers: publish messages
+    /// are sent first, and history reads and updates last.
+    pub fn subscriptions_first() -> Self {
+        Self::default()
+            .with("Publish", MessagePriority::High)
+            .with("Republish", MessagePriority::High)
+            .with("HistoryRead", MessagePriority::Low)
+            .with("HistoryUpdate", MessagePriority::Low)
+    }
+
+    /// Set the priority of messages belonging to `service`.
+    pub fn with(mut self, service: impl Into<String>, priority: MessagePriority) -> Self {
+        self.services.insert(service.into(), priority);
+        self
+    }
+
+    /// Get the priority of messages belonging to `service`.
+    pub fn priority(&self, service: &str) -> MessagePriority {
+        self.services.get(service).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,113 @@
+//! Token bucket rate limiting of outgoing traffic.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Limit on the rate data is written to a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Sustained rate in bytes per second.
+    pub bytes_per_second: u64,
+    /// Maximum number of bytes that can be written in a single burst after the
+    /// connection has been idle. Defaults to `bytes_per_second`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_bytes: Option<u64>,
+}
+
+/// Token bucket limiting the rate data is written to a connection.
+///
+/// The bucket holds up to `burst_bytes` tokens and is refilled at `bytes_per_second`.
+/// Writing a byte takes a token.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter, initially allowing a full burst.
+    pub fn new(limit: RateLimit) -> Self {
+        let rate = limit.bytes_per_second.max(1) as f64;
+        let burst = limit.burst_bytes.unwrap_or(limit.bytes_per_second).max(1) as f64;
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Get the number of bytes out of `len` that can be written now, or the time to wait
+    /// before at least `len` bytes, or a full burst, can be written.
+    fn available(&mut self, len: usize, now: Instant) -> Result<usize, Duration> {
+        self.refill(now);
+        let wanted = (len as f64).min(self.burst);
+        if self.tokens >= wanted {
+            Ok((self.tokens as usize).min(len))
+        } else {
+            Err(Duration::from_secs_f64((wanted - self.tokens) / self.rate))
+        }
+    }
+
+    /// Wait until data can be written, and return the number of bytes out of `len` that
+    /// can be written now. This does not take any tokens, call [`RateLimiter::consume`]
+    /// with the number of bytes actually written. This is cancel safe.
+    pub async fn acquire(&mut self, len: usize) -> usize {
+        loop {
+            match self.available(len, Instant::now()) {
+                Ok(n) => return n,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Take tokens for `bytes` written to the connection.
+    pub fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RateLimit, RateLimiter};
+
+    #[test]
+    fn token_bucket() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            bytes_per_second: 1000,
+            burst_bytes: Some(500),
+        });
+        let start = limiter.last_refill;
+        // The bucket starts full.
+        assert_eq!(limiter.available(2000, start), Ok(500));
+        limiter.consume(500);
+        // Wait for enough tokens for the full burst.
+        assert_eq!(
+            limiter.available(2000, start),
+            Err(Duration::from_millis(500))
+        );
+        assert!(limiter
+            .available(100, start + Duration::from_millis(50))
+            .is_err());
+        assert_eq!(
+            limiter.available(100, start + Duration::from_millis(150)),
+            Ok(100)
+        );
+        // Tokens never exceed the burst size.
+        assert_eq!(
+            limiter.available(2000, start + Duration::from_secs(10)),
+            Ok(500)
+        );
+    }
+}
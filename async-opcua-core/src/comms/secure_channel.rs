@@ -5,7 +5,7 @@
 //! The secure channel handles security on an OPC-UA connection.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::{Cursor, Write},
     ops::{Deref, Range},
     sync::Arc,
@@ -30,11 +30,22 @@ use opcua_types::{
 };
 use parking_lot::RwLock;
 
+use crate::Message;
+
 use super::{
+    interceptor::{MessageDirection, MessageInterceptors},
     message_chunk::{MessageChunk, MessageChunkHeader, MessageChunkType, MESSAGE_SIZE_OFFSET},
+    metrics::TransportMetricsHandle,
+    parallel,
+    pool::BufferPool,
     security_header::{AsymmetricSecurityHeader, SecurityHeader, SymmetricSecurityHeader},
+    tcp_types::ConnectionLimits,
 };
 
+/// Number of nonces received in OpenSecureChannel messages that are remembered
+/// on each channel, to detect replayed messages.
+const MAX_RECENT_NONCES: usize = 16;
+
 #[derive(Debug, PartialEq)]
 /// Role of an application in OPC-UA communication.
 pub enum Role {
@@ -67,6 +78,8 @@ pub struct SecureChannel {
     token_created_at: DateTime,
     /// Token lifetime
     token_lifetime: u32,
+    /// Percentage of the token lifetime after which the token should be renewed.
+    renewal_threshold_percent: u8,
     /// Token identifier
     token_id: u32,
     /// Our certificate
@@ -92,11 +105,18 @@ pub struct SecureChannel {
     local_keys: Option<(Vec<u8>, AesKey, Vec<u8>)>,
     /// Decoding options
     encoding_context: Arc<RwLock<ContextOwned>>,
+    /// Transport metrics hooks
+    metrics: TransportMetricsHandle,
+    /// Hooks observing or rejecting messages on this channel
+    interceptors: MessageInterceptors,
+    /// Nonces recently received in OpenSecureChannel messages, to detect replays
+    recent_remote_nonces: VecDeque<Vec<u8>>,
+    /// Message limits negotiated for the underlying connection
+    connection_limits: ConnectionLimits,
 }
 
 impl SecureChannel {
-    /// For testing purposes only
-    #[cfg(test)]
+    /// Create a secure channel without a certificate or private key, for testing and fuzzing.
     pub fn new_no_certificate_store() -> SecureChannel {
         SecureChannel {
             role: Role::Unknown,
@@ -106,6 +126,7 @@ impl SecureChannel {
             token_id: 0,
             token_created_at: DateTime::now(),
             token_lifetime: 0,
+            renewal_threshold_percent: 75,
             local_nonce: Vec::new(),
             remote_nonce: Vec::new(),
             cert: None,
@@ -114,6 +135,10 @@ impl SecureChannel {
             local_keys: None,
             encoding_context: Default::default(),
             remote_keys: HashMap::new(),
+            metrics: TransportMetricsHandle::default(),
+            interceptors: MessageInterceptors::default(),
+            recent_remote_nonces: VecDeque::new(),
+            connection_limits: ConnectionLimits::default(),
         }
     }
 
@@ -150,6 +175,7 @@ impl SecureChannel {
             token_id: 0,
             token_created_at: DateTime::now(),
             token_lifetime: 0,
+            renewal_threshold_percent: 75,
             local_nonce: Vec::new(),
             remote_nonce: Vec::new(),
             cert,
@@ -158,6 +184,10 @@ impl SecureChannel {
             local_keys: None,
             encoding_context,
             remote_keys: HashMap::new(),
+            metrics: TransportMetricsHandle::default(),
+            interceptors: MessageInterceptors::default(),
+            recent_remote_nonces: VecDeque::new(),
+            connection_limits: ConnectionLimits::default(),
         }
     }
 
@@ -292,14 +322,64 @@ impl SecureChannel {
         self.context().options().clone()
     }
 
+    /// Set the metrics hooks notified about traffic on this channel.
+    pub fn set_metrics(&mut self, metrics: TransportMetricsHandle) {
+        self.metrics = metrics;
+    }
+
+    /// Get the metrics hooks for this channel.
+    pub fn metrics(&self) -> &TransportMetricsHandle {
+        &self.metrics
+    }
+
+    /// Set the interceptors called with every message sent or received on this channel.
+    pub fn set_interceptors(&mut self, interceptors: MessageInterceptors) {
+        self.interceptors = interceptors;
+    }
+
+    /// Get the interceptors called with every message sent or received on this channel.
+    pub fn interceptors(&self) -> &MessageInterceptors {
+        &self.interceptors
+    }
+
+    /// Pass a message sent or received on this channel to its interceptors, see
+    /// [`MessageInterceptors::intercept`].
+    pub fn intercept(
+        &self,
+        direction: MessageDirection,
+        message: &impl Message,
+        request_id: u32,
+        size: usize,
+    ) -> Result<(), StatusCode> {
+        self.interceptors
+            .intercept(self, direction, message, request_id, size)
+    }
+
+    /// Set the message limits negotiated for the underlying connection.
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.connection_limits = limits;
+    }
+
+    /// Get the message limits negotiated for the underlying connection.
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        self.connection_limits
+    }
+
+    /// Set the percentage of the token lifetime that must elapse before the token
+    /// should be renewed. Defaults to 75.
+    pub fn set_renewal_threshold_percent(&mut self, percent: u8) {
+        self.renewal_threshold_percent = percent.clamp(1, 100);
+    }
+
     /// Test if the secure channel token needs to be renewed. The algorithm determines it needs
-    /// to be renewed if the issue period has elapsed by 75% or more.
+    /// to be renewed if the issue period has elapsed by the renewal threshold, 75% by default, or more.
     pub fn should_renew_security_token(&self) -> bool {
         if self.token_id() == 0 {
             false
         } else {
-            // Check if secure channel 75% close to expiration in which case send a renew
-            let renew_lifetime = (self.token_lifetime * 3) / 4;
+            // Check if secure channel is close to expiration in which case send a renew
+            let renew_lifetime =
+                self.token_lifetime as u64 * self.renewal_threshold_percent as u64 / 100;
             let renew_lifetime = Duration::milliseconds(renew_lifetime as i64);
             // Renew the token?
             DateTime::now() - self.token_created_at > renew_lifetime
@@ -387,6 +467,31 @@ impl SecureChannel {
         }
     }
 
+    /// Validate a nonce received in an OpenSecureChannel message. In addition to the
+    /// length, this checks that the nonce is not one of our own, or one recently received
+    /// on this channel, either of which means that the message is a replay.
+    pub fn validate_secure_channel_nonce(&mut self, nonce: &ByteString) -> Result<(), StatusCode> {
+        self.validate_secure_channel_nonce_length(nonce)?;
+        // Nonces are not used without security, so clients may send anything.
+        let Some(nonce) = nonce
+            .value
+            .as_ref()
+            .filter(|n| self.security_policy != SecurityPolicy::None && !n.is_empty())
+        else {
+            return Ok(());
+        };
+        if *nonce == self.local_nonce || self.recent_remote_nonces.contains(nonce) {
+            error!("Nonce has already been used on this channel, the message may be replayed");
+            self.metrics.replay_detected();
+            return Err(StatusCode::BadNonceInvalid);
+        }
+        if self.recent_remote_nonces.len() == MAX_RECENT_NONCES {
+            self.recent_remote_nonces.pop_front();
+        }
+        self.recent_remote_nonces.push_back(nonce.clone());
+        Ok(())
+    }
+
     /// Set their nonce which should be the same as the symmetric key
     pub fn set_remote_nonce_from_byte_string(
         &mut self,
@@ -594,7 +699,8 @@ impl SecureChannel {
             chunk_info.message_header.message_type,
         );
 
-        let buffer = Vec::with_capacity(message_chunk.data.len() + padding_size + signature_size);
+        let buffer = BufferPool::global()
+            .take_with_capacity(message_chunk.data.len() + padding_size + signature_size);
         let mut stream = Cursor::new(buffer);
 
         // First off just write out the src to the buffer. The message header, security header, sequence header and payload
@@ -694,6 +800,7 @@ impl SecureChannel {
             };
 
             Self::log_crypto_data("Chunk after encryption", &dst[..encrypted_size]);
+            BufferPool::global().put(data);
 
             encrypted_size
         } else {
@@ -708,6 +815,72 @@ impl SecureChannel {
         Ok(size)
     }
 
+    /// Decrypts and verifies a received chunk if the mode / policy requires it.
+    ///
+    /// Unlike [`SecureChannel::verify_and_remove_security`] this takes ownership of the chunk,
+    /// so if the channel is not secured the chunk is returned as is, without copying it.
+    pub fn remove_security(&mut self, chunk: MessageChunk) -> Result<MessageChunk, Error> {
+        if !self.is_secured() {
+            let header = chunk.message_header(&self.decoding_options())?;
+            // OpenSecureChannel may change the security policy, so it always goes through
+            // the full path. A size mismatch is reported there as well.
+            if !header.message_type.is_open_secure_channel()
+                && header.message_size as usize == chunk.data.len()
+            {
+                return Ok(chunk);
+            }
+        }
+        self.verify_and_remove_security(&chunk.data)
+    }
+
+    /// Decrypts and verifies a batch of received chunks, in order.
+    ///
+    /// If there are at least `parallel_threshold` chunks, and they are all secured
+    /// with symmetric keys, the work is spread across multiple threads. A threshold of
+    /// zero means chunks are always processed sequentially.
+    pub fn remove_security_batch(
+        &mut self,
+        chunks: Vec<MessageChunk>,
+        parallel_threshold: usize,
+    ) -> Result<Vec<MessageChunk>, Error> {
+        let decoding_options = self.decoding_options();
+        let parallel = parallel_threshold > 0
+            && chunks.len() >= parallel_threshold
+            && self.is_secured()
+            && chunks.iter().all(|c| {
+                c.message_header(&decoding_options)
+                    .is_ok_and(|h| !h.message_type.is_open_secure_channel())
+            });
+        if !parallel {
+            return chunks
+                .into_iter()
+                .map(|c| self.remove_security(c))
+                .collect();
+        }
+
+        let this = &*self;
+        parallel::map_ordered(&chunks, |chunk| {
+            let (_, security_header, encrypted_data_offset) =
+                this.decode_chunk_headers(&chunk.data)?;
+            let data = this.symmetric_remove_security(
+                &chunk.data,
+                security_header,
+                encrypted_data_offset,
+            )?;
+            Ok(MessageChunk { data })
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Return `true` if messages on this channel are signed, and possibly encrypted,
+    /// using the symmetric keys.
+    pub fn is_secured(&self) -> bool {
+        self.security_policy != SecurityPolicy::None
+            && (self.security_mode == MessageSecurityMode::Sign
+                || self.security_mode == MessageSecurityMode::SignAndEncrypt)
+    }
+
     /// Decrypts and verifies the body data if the mode / policy requires it
     pub fn verify_and_remove_security(&mut self, src: &[u8]) -> Result<MessageChunk, Error> {
         self.verify_and_remove_security_forensic(src, None)
@@ -722,31 +895,9 @@ impl SecureChannel {
         src: &[u8],
         their_key: Option<PrivateKey>,
     ) -> Result<MessageChunk, Error> {
-        // Get message & security header from data
-        let decoding_options = self.decoding_options();
-        let (message_header, security_header, encrypted_data_offset) = {
-            let mut stream = Cursor::new(&src);
-            let message_header = MessageChunkHeader::decode(&mut stream, &decoding_options)?;
-            let security_header = SecurityHeader::decode_from_stream(
-                &mut stream,
-                message_header.message_type.is_open_secure_channel(),
-                &decoding_options,
-            )?;
-            let encrypted_data_offset = stream.position() as usize;
-            (message_header, security_header, encrypted_data_offset)
-        };
-
+        let (message_header, security_header, encrypted_data_offset) =
+            self.decode_chunk_headers(src)?;
         let message_size = message_header.message_size as usize;
-        if message_size != src.len() {
-            return Err(Error::new(
-                StatusCode::BadUnexpectedError,
-                format!(
-                    "The message size {} is not the same as the supplied buffer {}",
-                    message_size,
-                    src.len()
-                ),
-            ));
-        }
 
         // S - Message Header
         // S - Security Header
@@ -761,11 +912,11 @@ impl SecureChannel {
 
             trace!("Decrypting OpenSecureChannel");
 
-            let security_header = match security_header {
-                SecurityHeader::Asymmetric(security_header) => security_header,
-                _ => {
-                    panic!();
-                }
+            let SecurityHeader::Asymmetric(security_header) = security_header else {
+                return Err(Error::new(
+                    StatusCode::BadUnexpectedError,
+                    format!("Expected asymmetric security header, got {security_header:?}"),
+                ));
             };
 
             // The security policy dictates the encryption / signature algorithms used by the request
@@ -779,7 +930,9 @@ impl SecureChannel {
                 }
                 SecurityPolicy::None => {
                     // Nothing to do
-                    return Ok(MessageChunk { data: src.to_vec() });
+                    return Ok(MessageChunk {
+                        data: BufferPool::global().take_copy(src),
+                    });
                 }
                 _ => {}
             }
@@ -817,7 +970,7 @@ impl SecureChannel {
             let receiver_thumbprint = security_header.receiver_certificate_thumbprint;
             trace!("Receiver thumbprint = {:?}", receiver_thumbprint);
 
-            let mut decrypted_data = vec![0u8; message_size];
+            let mut decrypted_data = BufferPool::global().take(message_size);
             let decrypted_size = self.asymmetric_decrypt_and_verify(
                 security_policy,
                 &verification_key,
@@ -829,43 +982,86 @@ impl SecureChannel {
             )?;
 
             Self::update_message_size_and_truncate(decrypted_data, decrypted_size)?
-        } else if self.security_policy != SecurityPolicy::None
-            && (self.security_mode == MessageSecurityMode::Sign
-                || self.security_mode == MessageSecurityMode::SignAndEncrypt)
-        {
-            // Symmetric decrypt and verify
-            let signature_size = self.security_policy.symmetric_signature_size();
-            let encrypted_range = encrypted_data_offset..message_size;
-            let signed_range = 0..(message_size - signature_size);
-            trace!(
-                "Decrypting block with signature info {:?} and encrypt info {:?}",
-                signed_range,
-                encrypted_range
-            );
+        } else if self.is_secured() {
+            self.symmetric_remove_security(src, security_header, encrypted_data_offset)?
+        } else {
+            BufferPool::global().take_copy(src)
+        };
 
-            let SecurityHeader::Symmetric(security_header) = security_header else {
-                return Err(Error::new(
-                    StatusCode::BadUnexpectedError,
-                    format!("Expected symmetric security header, got {security_header:?}"),
-                ));
-            };
+        Ok(MessageChunk { data })
+    }
 
-            let mut decrypted_data = vec![0u8; message_size];
-            let decrypted_size = self.symmetric_decrypt_and_verify(
-                src,
-                signed_range,
-                encrypted_range,
-                security_header.token_id,
-                &mut decrypted_data,
-            )?;
+    /// Decode the message and security headers of a received chunk, returning them
+    /// along with the offset of the encrypted data.
+    fn decode_chunk_headers(
+        &self,
+        src: &[u8],
+    ) -> Result<(MessageChunkHeader, SecurityHeader, usize), Error> {
+        let decoding_options = self.decoding_options();
+        let mut stream = Cursor::new(&src);
+        let message_header = MessageChunkHeader::decode(&mut stream, &decoding_options)?;
+        let security_header = SecurityHeader::decode_from_stream(
+            &mut stream,
+            message_header.message_type.is_open_secure_channel(),
+            &decoding_options,
+        )?;
+        let encrypted_data_offset = stream.position() as usize;
 
-            // Value returned from symmetric_decrypt_and_verify is the end of the actual decrypted data.
-            Self::update_message_size_and_truncate(decrypted_data, decrypted_size)?
-        } else {
-            src.to_vec()
+        let message_size = message_header.message_size as usize;
+        if message_size != src.len() {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!(
+                    "The message size {} is not the same as the supplied buffer {}",
+                    message_size,
+                    src.len()
+                ),
+            ));
+        }
+        Ok((message_header, security_header, encrypted_data_offset))
+    }
+
+    /// Symmetric decrypt and verify a chunk, returning the decrypted data.
+    fn symmetric_remove_security(
+        &self,
+        src: &[u8],
+        security_header: SecurityHeader,
+        encrypted_data_offset: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let message_size = src.len();
+        let signature_size = self.security_policy.symmetric_signature_size();
+        if message_size < encrypted_data_offset + signature_size {
+            return Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                format!("Message of size {message_size} is too small to hold a signature"),
+            ));
+        }
+        let encrypted_range = encrypted_data_offset..message_size;
+        let signed_range = 0..(message_size - signature_size);
+        trace!(
+            "Decrypting block with signature info {:?} and encrypt info {:?}",
+            signed_range,
+            encrypted_range
+        );
+
+        let SecurityHeader::Symmetric(security_header) = security_header else {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!("Expected symmetric security header, got {security_header:?}"),
+            ));
         };
 
-        Ok(MessageChunk { data })
+        let mut decrypted_data = BufferPool::global().take(message_size);
+        let decrypted_size = self.symmetric_decrypt_and_verify(
+            src,
+            signed_range,
+            encrypted_range,
+            security_header.token_id,
+            &mut decrypted_data,
+        )?;
+
+        // Value returned from symmetric_decrypt_and_verify is the end of the actual decrypted data.
+        Self::update_message_size_and_truncate(decrypted_data, decrypted_size)
     }
 
     /// Use the security policy to asymmetric encrypt and sign the specified chunk of data.
@@ -970,11 +1166,26 @@ impl SecureChannel {
         key_size: usize,
         padding_end: usize,
     ) -> Result<Range<usize>, Error> {
+        let invalid_padding = || {
+            Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                format!("Padding ending at {padding_end} is out of range"),
+            )
+        };
+        if padding_end > src.len() {
+            return Err(invalid_padding());
+        }
         let padding_range = if key_size > 256 {
+            if padding_end < 2 {
+                return Err(invalid_padding());
+            }
             let padding_byte = src[padding_end - 2];
             let extra_padding_byte = src[padding_end - 1];
             let padding_size = ((extra_padding_byte as usize) << 8) + (padding_byte as usize);
-            let padding_range = (padding_end - padding_size - 2)..padding_end;
+            let padding_start = padding_end
+                .checked_sub(padding_size + 2)
+                .ok_or_else(invalid_padding)?;
+            let padding_range = padding_start..padding_end;
 
             trace!("Extra padding - extra_padding_byte = {}, padding_byte = {}, padding_end = {}, padding_size = {}", extra_padding_byte, padding_byte, padding_end, padding_size);
 
@@ -995,9 +1206,15 @@ impl SecureChannel {
             }
             padding_range
         } else {
+            if padding_end < 1 {
+                return Err(invalid_padding());
+            }
             let padding_byte = src[padding_end - 1];
             let padding_size = padding_byte as usize;
-            let padding_range = (padding_end - padding_size - 1)..padding_end;
+            let padding_start = padding_end
+                .checked_sub(padding_size + 1)
+                .ok_or_else(invalid_padding)?;
+            let padding_range = padding_start..padding_end;
             // Check padding bytes
             Self::check_padding_bytes(
                 &src[padding_range.clone()],
@@ -1037,7 +1254,13 @@ impl SecureChannel {
         // The receiver certificate thumbprint identifies which of our certs was used by the client
         // to encrypt the message. We have to work out from the thumbprint which cert to use
 
-        let our_cert = self.cert.as_ref().unwrap();
+        let (Some(our_cert), Some(private_key)) = (self.cert.as_ref(), self.private_key.as_ref())
+        else {
+            return Err(Error::new(
+                StatusCode::BadNoValidCertificates,
+                "Cannot decrypt message without an application certificate and private key",
+            ));
+        };
         let our_thumbprint = our_cert.thumbprint();
         if our_thumbprint.value() != receiver_thumbprint.as_ref() {
             Err(Error::new(
@@ -1055,7 +1278,6 @@ impl SecureChannel {
             trace!("Decrypting message range {:?}", encrypted_range);
             let mut decrypted_tmp = vec![0u8; encrypted_size];
 
-            let private_key = self.private_key.as_ref().unwrap();
             let decrypted_size = security_policy.asymmetric_decrypt(
                 private_key,
                 &src[encrypted_range.clone()],
@@ -1079,8 +1301,14 @@ impl SecureChannel {
                 .copy_from_slice(&decrypted_tmp[0..decrypted_size]);
 
             // The signature range is at the end of the decrypted block for the verification key's signature
-            let signature_dst_offset =
-                encrypted_range.start + decrypted_size - verification_key_signature_size;
+            let Some(signature_dst_offset) = (encrypted_range.start + decrypted_size)
+                .checked_sub(verification_key_signature_size)
+            else {
+                return Err(Error::new(
+                    StatusCode::BadSecurityChecksFailed,
+                    "Decrypted message is too small to hold a signature",
+                ));
+            };
             let signature_range_dst =
                 signature_dst_offset..(signature_dst_offset + verification_key_signature_size);
 
@@ -1348,7 +1576,7 @@ impl SecureChannel {
                 dst[..encrypted_range.start].copy_from_slice(&src[..encrypted_range.start]);
 
                 // Decrypt encrypted portion
-                let mut decrypted_tmp = vec![0u8; ciphertext_size + 16]; // tmp includes +16 for blocksize
+                let mut decrypted_tmp = BufferPool::global().take(ciphertext_size + 16); // tmp includes +16 for blocksize
                 let (key, iv) = self.decryption_keys(token_id).ok_or_else(|| {
                     Error::new(
                         StatusCode::BadSecureChannelClosed,
@@ -1371,6 +1599,7 @@ impl SecureChannel {
                 let encrypted_range =
                     encrypted_range.start..(encrypted_range.start + decrypted_size);
                 dst[encrypted_range.clone()].copy_from_slice(&decrypted_tmp[..decrypted_size]);
+                BufferPool::global().put(decrypted_tmp);
                 Self::log_crypto_data("Decrypted buffer", &dst[..encrypted_range.end]);
 
                 // Verify signature (after encrypted portion)
@@ -1,5 +1,10 @@
 //! Utility for managing sequence numbers
 
+/// Number of sequence numbers before the expected one that are considered recently
+/// received. A message with one of these is rejected as a replay, rather than as
+/// simply out of order.
+pub const REPLAY_WINDOW: u32 = 4096;
+
 #[derive(Debug, Clone)]
 /// Utility for managing sequence numbers
 pub struct SequenceNumberHandle {
@@ -70,6 +75,32 @@ impl SequenceNumberHandle {
         self.current_value = value;
     }
 
+    /// Return `true` if `value` is one of the [`REPLAY_WINDOW`] sequence numbers
+    /// before the current one, taking wrap around into account. When receiving,
+    /// these have already been seen on the channel.
+    pub fn is_recent(&self, value: u32) -> bool {
+        self.distance(self.current_value, value)
+            .is_some_and(|behind| behind > 0 && behind <= u64::from(REPLAY_WINDOW))
+    }
+
+    /// Return `true` if `value` is the current sequence number, or one of the
+    /// [`REPLAY_WINDOW`] sequence numbers after it, taking wrap around into account.
+    pub fn is_ahead(&self, value: u32) -> bool {
+        self.distance(value, self.current_value)
+            .is_some_and(|ahead| ahead < u64::from(REPLAY_WINDOW))
+    }
+
+    /// Number of increments needed to get from `from` to `to`, or `None` if either
+    /// is not a valid sequence number.
+    fn distance(&self, to: u32, from: u32) -> Option<u64> {
+        let is_valid = |v: u32| v >= self.min_value() && v <= self.max_value();
+        if !is_valid(to) || !is_valid(from) {
+            return None;
+        }
+        let range = u64::from(self.max_value() - self.min_value()) + 1;
+        Some((u64::from(to) + range - u64::from(from)) % range)
+    }
+
     /// Increment the sequence number by the given value.
     pub fn increment(&mut self, value: u32) {
         let remaining = self.max_value() - self.current_value;
@@ -85,7 +116,7 @@ impl SequenceNumberHandle {
 
 #[cfg(test)]
 mod tests {
-    use super::SequenceNumberHandle;
+    use super::{SequenceNumberHandle, REPLAY_WINDOW};
 
     #[test]
     fn test_sequence_numbers() {
@@ -130,4 +161,37 @@ mod tests {
         seq.increment(3);
         assert_eq!(seq.current(), 1);
     }
+
+    #[test]
+    fn test_recent_sequence_numbers() {
+        let seq = SequenceNumberHandle::new_at(false, 10_000);
+        assert!(seq.is_recent(9_999));
+        assert!(seq.is_recent(10_000 - REPLAY_WINDOW));
+        assert!(!seq.is_recent(10_000 - REPLAY_WINDOW - 1));
+        assert!(!seq.is_recent(10_000));
+        assert!(!seq.is_recent(10_001));
+
+        // Sequence numbers before a wrap around are recent.
+        let seq = SequenceNumberHandle::new_at(true, 2);
+        assert!(seq.is_recent(1));
+        assert!(seq.is_recent(u32::MAX - 1024));
+        assert!(!seq.is_recent(u32::MAX - 1023));
+        assert!(!seq.is_recent(0));
+    }
+
+    #[test]
+    fn test_ahead_sequence_numbers() {
+        let seq = SequenceNumberHandle::new_at(false, 10_000);
+        assert!(seq.is_ahead(10_000));
+        assert!(seq.is_ahead(10_000 + REPLAY_WINDOW - 1));
+        assert!(!seq.is_ahead(10_000 + REPLAY_WINDOW));
+        assert!(!seq.is_ahead(9_999));
+
+        // Sequence numbers after a wrap around are ahead.
+        let seq = SequenceNumberHandle::new_at(true, u32::MAX - 1025);
+        assert!(seq.is_ahead(u32::MAX - 1024));
+        assert!(seq.is_ahead(1));
+        assert!(!seq.is_ahead(0));
+        assert!(!seq.is_ahead(u32::MAX - 1026));
+    }
 }
@@ -0,0 +1,274 @@
+//! Socket level tuning options for the TCP transport, shared by client and server.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tracing::warn;
+
+/// TCP keep-alive settings. Keep-alive probes let both ends detect a dead peer on links
+/// where the connection may be silently dropped, for example by a firewall or a
+/// cellular modem, even if no OPC-UA traffic is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeepAliveOptions {
+    /// Time in milliseconds the connection must be idle before the first keep-alive probe is sent.
+    pub time_ms: u64,
+    /// Time in milliseconds between each keep-alive probe. Uses the OS default if not set.
+    ///
+    /// Not supported on all platforms, ignored where it isn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_ms: Option<u64>,
+    /// Number of unanswered probes before the connection is considered dead.
+    /// Uses the OS default if not set.
+    ///
+    /// Not supported on all platforms, notably Windows, ignored where it isn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probes: Option<u32>,
+}
+
+impl KeepAliveOptions {
+    fn to_tcp_keepalive(&self) -> TcpKeepalive {
+        #[allow(unused_mut)]
+        let mut keepalive = TcpKeepalive::new().with_time(Duration::from_millis(self.time_ms));
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "windows",
+        ))]
+        if let Some(interval) = self.interval_ms {
+            keepalive = keepalive.with_interval(Duration::from_millis(interval));
+        }
+        #[cfg(any(
+            target_os = "android",
+            target_os = "freebsd",
+            target_os = "fuchsia",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+        ))]
+        if let Some(probes) = self.probes {
+            keepalive = keepalive.with_retries(probes);
+        }
+        keepalive
+    }
+}
+
+/// Socket options applied to TCP connections. Any option left as `None` uses the
+/// operating system default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct SocketOptions {
+    /// Set `TCP_NODELAY`, disabling Nagle's algorithm. This usually reduces latency
+    /// for small requests, at the cost of sending more packets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nodelay: Option<bool>,
+    /// Enable `SO_KEEPALIVE` with the given settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<KeepAliveOptions>,
+    /// Size of the OS receive buffer, `SO_RCVBUF`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recv_buffer_size: Option<u32>,
+    /// Size of the OS send buffer, `SO_SNDBUF`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_buffer_size: Option<u32>,
+    /// Local address to bind outgoing connections to. The port is chosen by the OS.
+    ///
+    /// Only used when connecting, the server binds to its configured host and port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<IpAddr>,
+    /// Name of a network interface to bind the socket to, i.e. `SO_BINDTODEVICE`.
+    ///
+    /// Only supported on Linux, Android and Fuchsia, this is an error on other platforms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_interface: Option<String>,
+    /// Timeout in milliseconds for establishing a TCP connection.
+    ///
+    /// Only used when connecting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_ms: Option<u64>,
+}
+
+impl SocketOptions {
+    /// Validate the socket options, returning a list of errors.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.recv_buffer_size == Some(0) {
+            errors.push("Socket receive buffer size must be greater than zero".to_owned());
+        }
+        if self.send_buffer_size == Some(0) {
+            errors.push("Socket send buffer size must be greater than zero".to_owned());
+        }
+        if let Some(keepalive) = &self.keepalive {
+            if keepalive.time_ms == 0 {
+                errors.push("Socket keep-alive time must be greater than zero".to_owned());
+            }
+        }
+        if self.bind_interface.as_ref().is_some_and(|i| i.is_empty()) {
+            errors.push("Socket bind interface must not be empty".to_owned());
+        }
+        if self.bind_interface.is_some()
+            && !cfg!(any(
+                target_os = "android",
+                target_os = "fuchsia",
+                target_os = "linux"
+            ))
+        {
+            errors.push(
+                "Binding a socket to an interface is not supported on this platform".to_owned(),
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn new_socket(&self, addr: &SocketAddr) -> io::Result<TcpSocket> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(interface) = &self.bind_interface {
+            Self::bind_device(&socket, interface)?;
+        }
+        Ok(socket)
+    }
+
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    fn bind_device(socket: &TcpSocket, interface: &str) -> io::Result<()> {
+        socket.bind_device(Some(interface.as_bytes()))
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+    fn bind_device(_socket: &TcpSocket, _interface: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Binding a socket to an interface is not supported on this platform",
+        ))
+    }
+
+    /// Open a TCP connection to `addr` using these options.
+    pub async fn connect(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = self.new_socket(&addr)?;
+        if let Some(local) = self.local_address {
+            socket.bind(SocketAddr::new(local, 0))?;
+        }
+        let stream = match self.connect_timeout_ms {
+            Some(timeout) => {
+                tokio::time::timeout(Duration::from_millis(timeout), socket.connect(addr))
+                    .await
+                    .map_err(|_| {
+                        io::Error::new(io::ErrorKind::TimedOut, "Timed out connecting to server")
+                    })??
+            }
+            None => socket.connect(addr).await?,
+        };
+        self.apply(&stream)?;
+        Ok(stream)
+    }
+
+    /// Create a TCP listener bound to `addr` using these options.
+    pub fn listen(&self, addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = self.new_socket(&addr)?;
+        // Same as tokio's `TcpListener::bind`, this lets the server restart
+        // while old connections are in TIME_WAIT.
+        #[cfg(not(windows))]
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        socket.listen(1024)
+    }
+
+    /// Apply the options that can be set on an already connected stream, i.e.
+    /// `TCP_NODELAY`, keep-alive, and buffer sizes. Use this on accepted connections.
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        if let Some(nodelay) = self.nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+        let sock = SockRef::from(stream);
+        if let Some(keepalive) = &self.keepalive {
+            sock.set_tcp_keepalive(&keepalive.to_tcp_keepalive())?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            sock.set_recv_buffer_size(size as usize)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            sock.set_send_buffer_size(size as usize)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the options to an accepted connection, logging any failure instead of
+    /// returning it, since failing to tune a socket is not a reason to drop the connection.
+    pub fn apply_or_warn(&self, stream: &TcpStream) {
+        if let Err(e) = self.apply(stream) {
+            warn!("Failed to apply socket options to connection: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeepAliveOptions, SocketOptions};
+
+    #[test]
+    fn validate_socket_options() {
+        assert!(SocketOptions::default().validate().is_ok());
+
+        let options = SocketOptions {
+            recv_buffer_size: Some(0),
+            keepalive: Some(KeepAliveOptions {
+                time_ms: 0,
+                interval_ms: None,
+                probes: None,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(options.validate().unwrap_err().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn connect_with_socket_options() {
+        let options = SocketOptions {
+            nodelay: Some(true),
+            keepalive: Some(KeepAliveOptions {
+                time_ms: 10_000,
+                interval_ms: Some(1_000),
+                probes: Some(3),
+            }),
+            recv_buffer_size: Some(65536),
+            send_buffer_size: Some(65536),
+            local_address: Some("127.0.0.1".parse().unwrap()),
+            bind_interface: None,
+            connect_timeout_ms: Some(5_000),
+        };
+        let listener = options.listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (client, server) = tokio::join!(options.connect(addr), listener.accept());
+        let client = client.unwrap();
+        let (server, _) = server.unwrap();
+        options.apply(&server).unwrap();
+
+        assert!(client.nodelay().unwrap());
+        assert!(server.nodelay().unwrap());
+        assert_eq!(client.local_addr().unwrap().ip(), addr.ip());
+    }
+}
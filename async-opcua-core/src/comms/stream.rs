@@ -0,0 +1,160 @@
+//! Byte stream abstraction over the different socket types that can carry
+//! OPC-UA binary framing, `opc.tcp` over TCP and `opc.uds` over Unix domain
+//! sockets or Windows named pipes, as well as in-memory streams used for testing.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::net::TcpStream;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{NamedPipeClient, NamedPipeServer};
+
+/// A connected stream used by an OPC-UA transport.
+///
+/// Every variant is `Unpin`, so the `AsyncRead` and `AsyncWrite` implementations
+/// simply forward to the inner stream.
+#[derive(Debug)]
+pub enum TransportStream {
+    /// TCP socket, used for `opc.tcp`.
+    Tcp(TcpStream),
+    /// Unix domain socket, used for `opc.uds` on unix platforms.
+    #[cfg(unix)]
+    Unix(UnixStream),
+    /// Client end of a named pipe, used for `opc.uds` on Windows.
+    #[cfg(windows)]
+    PipeClient(NamedPipeClient),
+    /// Server end of a named pipe, used for `opc.uds` on Windows.
+    #[cfg(windows)]
+    PipeServer(NamedPipeServer),
+    /// One end of an in-memory duplex stream, used to connect a client to a server
+    /// in the same process without a socket.
+    Memory(DuplexStream),
+}
+
+impl TransportStream {
+    /// Get a human readable description of the remote peer, used for logging.
+    pub fn peer_description(&self) -> String {
+        match self {
+            TransportStream::Tcp(s) => s
+                .peer_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_else(|_| "unknown tcp peer".to_owned()),
+            #[cfg(unix)]
+            TransportStream::Unix(s) => s
+                .peer_addr()
+                .ok()
+                .and_then(|a| a.as_pathname().map(|p| p.display().to_string()))
+                .unwrap_or_else(|| "unix socket".to_owned()),
+            #[cfg(windows)]
+            TransportStream::PipeClient(_) | TransportStream::PipeServer(_) => {
+                "named pipe".to_owned()
+            }
+            TransportStream::Memory(_) => "in-memory stream".to_owned(),
+        }
+    }
+}
+
+impl From<TcpStream> for TransportStream {
+    fn from(value: TcpStream) -> Self {
+        Self::Tcp(value)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixStream> for TransportStream {
+    fn from(value: UnixStream) -> Self {
+        Self::Unix(value)
+    }
+}
+
+#[cfg(windows)]
+impl From<NamedPipeClient> for TransportStream {
+    fn from(value: NamedPipeClient) -> Self {
+        Self::PipeClient(value)
+    }
+}
+
+#[cfg(windows)]
+impl From<NamedPipeServer> for TransportStream {
+    fn from(value: NamedPipeServer) -> Self {
+        Self::PipeServer(value)
+    }
+}
+
+impl From<DuplexStream> for TransportStream {
+    fn from(value: DuplexStream) -> Self {
+        Self::Memory(value)
+    }
+}
+
+macro_rules! forward_stream {
+    ($self:ident, $s:ident => $e:expr) => {
+        match $self.get_mut() {
+            TransportStream::Tcp($s) => $e,
+            #[cfg(unix)]
+            TransportStream::Unix($s) => $e,
+            #[cfg(windows)]
+            TransportStream::PipeClient($s) => $e,
+            #[cfg(windows)]
+            TransportStream::PipeServer($s) => $e,
+            TransportStream::Memory($s) => $e,
+        }
+    };
+}
+
+impl AsyncRead for TransportStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        forward_stream!(self, s => Pin::new(s).poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for TransportStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        forward_stream!(self, s => Pin::new(s).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        forward_stream!(self, s => Pin::new(s).poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        forward_stream!(self, s => Pin::new(s).poll_shutdown(cx))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        forward_stream!(self, s => Pin::new(s).poll_write_vectored(cx, bufs))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            TransportStream::Tcp(s) => s.is_write_vectored(),
+            #[cfg(unix)]
+            TransportStream::Unix(s) => s.is_write_vectored(),
+            #[cfg(windows)]
+            TransportStream::PipeClient(s) => s.is_write_vectored(),
+            #[cfg(windows)]
+            TransportStream::PipeServer(s) => s.is_write_vectored(),
+            TransportStream::Memory(s) => s.is_write_vectored(),
+        }
+    }
+}
@@ -8,6 +8,7 @@
 //! * HEL - Hello message
 //! * ACK - Acknowledge message
 //! * ERR - Error message
+//! * RHE - Reverse Hello message
 //! * MSG - Message chunk
 //! * OPN - Open Secure Channel message
 //! * CLO - Close Secure Channel message
@@ -24,9 +25,10 @@ use opcua_types::{
 
 use super::{
     message_chunk::MessageChunk,
+    metrics::TransportMetricsHandle,
     tcp_types::{
         AcknowledgeMessage, ErrorMessage, HelloMessage, MessageHeader, MessageType,
-        MESSAGE_HEADER_LEN,
+        ReverseHelloMessage, MESSAGE_HEADER_LEN,
     },
 };
 
@@ -42,6 +44,21 @@ pub enum Message {
     Error(ErrorMessage),
     /// Part of a general OPC-UA message.
     Chunk(MessageChunk),
+    /// Reverse hello message, sent by a server that connects to a client.
+    ReverseHello(ReverseHelloMessage),
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=4)? {
+            0 => Message::Hello(u.arbitrary()?),
+            1 => Message::Acknowledge(u.arbitrary()?),
+            2 => Message::Error(u.arbitrary()?),
+            3 => Message::ReverseHello(u.arbitrary()?),
+            _ => Message::Chunk(u.arbitrary()?),
+        })
+    }
 }
 
 /// Implements a tokio codec that as close as possible, allows incoming data to be transformed into
@@ -49,6 +66,7 @@ pub enum Message {
 /// messages so there is still some buffers within message chunks, but not at the raw socket level.
 pub struct TcpCodec {
     decoding_options: DecodingOptions,
+    metrics: TransportMetricsHandle,
 }
 
 impl Decoder for TcpCodec {
@@ -72,12 +90,17 @@ impl Decoder for TcpCodec {
             if buf.len() >= message_size {
                 // Extract the message bytes from the buffer & decode them into a message
                 let mut buf = buf.split_to(message_size);
+                self.metrics.bytes_received(message_size);
                 let message =
                     Self::decode_message(message_header, &mut buf, &self.decoding_options)
                         .map_err(|e| {
                             error!("Codec got an error {} while decoding a message", e);
+                            self.metrics.decoding_error(e);
                             io::Error::from(e)
                         })?;
+                if matches!(message, Message::Chunk(_)) {
+                    self.metrics.chunk_received();
+                }
                 Ok(Some(message))
             } else {
                 // Not enough bytes
@@ -98,6 +121,7 @@ impl Encoder<Message> for TcpCodec {
             Message::Acknowledge(msg) => self.write(msg, buf),
             Message::Error(msg) => self.write(msg, buf),
             Message::Chunk(msg) => self.write(msg, buf),
+            Message::ReverseHello(msg) => self.write(msg, buf),
         }
     }
 }
@@ -106,7 +130,16 @@ impl TcpCodec {
     /// Constructs a new TcpCodec. The abort flag is set to terminate the codec even while it is
     /// waiting for a frame to arrive.
     pub fn new(decoding_options: DecodingOptions) -> TcpCodec {
-        TcpCodec { decoding_options }
+        TcpCodec {
+            decoding_options,
+            metrics: TransportMetricsHandle::default(),
+        }
+    }
+
+    /// Set the metrics hooks notified about incoming traffic.
+    pub fn with_metrics(mut self, metrics: TransportMetricsHandle) -> Self {
+        self.metrics = metrics;
+        self
     }
 
     // Writes the encodable thing into the buffer.
@@ -145,6 +178,10 @@ impl TcpCodec {
                 &mut buf,
                 decoding_options,
             )?)),
+            MessageType::ReverseHello => Ok(Message::ReverseHello(ReverseHelloMessage::decode(
+                &mut buf,
+                decoding_options,
+            )?)),
             MessageType::Invalid => {
                 error!("Message type for chunk is invalid.");
                 Err(StatusCode::BadCommunicationError)
@@ -27,6 +27,8 @@ pub(crate) const HELLO_MESSAGE: &[u8] = b"HEL";
 pub(crate) const ACKNOWLEDGE_MESSAGE: &[u8] = b"ACK";
 /// Message header type for error messages.
 pub(crate) const ERROR_MESSAGE: &[u8] = b"ERR";
+/// Message header type for reverse hello messages.
+pub(crate) const REVERSE_HELLO_MESSAGE: &[u8] = b"RHE";
 
 /// ChunkIsFinal type for the final chunk in a message.
 pub(crate) const CHUNK_FINAL: u8 = b'F';
@@ -54,6 +56,8 @@ pub enum MessageType {
     Chunk,
     /// Fatal error, followed by shutting down the channel.
     Error,
+    /// REVERSE HELLO message, sent by a server that connects to a client.
+    ReverseHello,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -75,6 +79,7 @@ impl SimpleBinaryEncodable for MessageHeader {
             MessageType::Hello => stream.write_all(HELLO_MESSAGE),
             MessageType::Acknowledge => stream.write_all(ACKNOWLEDGE_MESSAGE),
             MessageType::Error => stream.write_all(ERROR_MESSAGE),
+            MessageType::ReverseHello => stream.write_all(REVERSE_HELLO_MESSAGE),
             MessageType::Chunk => {
                 panic!("Don't write chunks to stream with this call, use Chunk and Chunker");
             }
@@ -160,6 +165,7 @@ impl MessageHeader {
                 HELLO_MESSAGE => MessageType::Hello,
                 ACKNOWLEDGE_MESSAGE => MessageType::Acknowledge,
                 ERROR_MESSAGE => MessageType::Error,
+                REVERSE_HELLO_MESSAGE => MessageType::ReverseHello,
                 CHUNK_MESSAGE | OPEN_SECURE_CHANNEL_MESSAGE | CLOSE_SECURE_CHANNEL_MESSAGE => {
                     MessageType::Chunk
                 }
@@ -381,6 +387,62 @@ impl AcknowledgeMessage {
         ack.message_header.message_size = ack.byte_len() as u32;
         ack
     }
+
+    /// Check that this is a valid response to `hello`. The server may lower the
+    /// buffer sizes requested by the client, but not raise them, and neither may be
+    /// smaller than [`MIN_CHUNK_SIZE`].
+    pub fn validate(&self, hello: &HelloMessage) -> std::result::Result<(), opcua_types::Error> {
+        let invalid = |reason: String| {
+            Err(opcua_types::Error::new(
+                StatusCode::BadConnectionRejected,
+                reason,
+            ))
+        };
+        if self.receive_buffer_size < MIN_CHUNK_SIZE as u32
+            || self.send_buffer_size < MIN_CHUNK_SIZE as u32
+        {
+            return invalid(format!(
+                "Acknowledged buffer sizes {}/{} are smaller than the minimum of {MIN_CHUNK_SIZE}",
+                self.receive_buffer_size, self.send_buffer_size
+            ));
+        }
+        if self.receive_buffer_size > hello.send_buffer_size {
+            return invalid(format!(
+                "Acknowledged receive buffer size {} is greater than the requested send buffer size {}",
+                self.receive_buffer_size, hello.send_buffer_size
+            ));
+        }
+        if self.send_buffer_size > hello.receive_buffer_size {
+            return invalid(format!(
+                "Acknowledged send buffer size {} is greater than the requested receive buffer size {}",
+                self.send_buffer_size, hello.receive_buffer_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Limits on messages sent in one direction over a connection.
+/// A value of zero means there is no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageLimits {
+    /// Maximum size of a complete message in bytes.
+    pub max_message_size: usize,
+    /// Maximum size of a single message chunk in bytes.
+    pub max_chunk_size: usize,
+    /// Maximum number of chunks in a message.
+    pub max_chunk_count: usize,
+}
+
+/// Message limits of a connection, as negotiated in the HELLO/ACKNOWLEDGE exchange.
+/// Sending a message that exceeds the send limits fails locally, so callers that
+/// need to send large payloads can use these to split them up front.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Limits on messages sent by this side of the connection.
+    pub send: MessageLimits,
+    /// Limits on messages received by this side of the connection.
+    pub receive: MessageLimits,
 }
 
 /// Implementation of the ERR message in OPC UA
@@ -439,14 +501,122 @@ impl ErrorMessage {
     }
 }
 
+/// Implementation of the RHE message in OPC UA, sent by a server that opens the
+/// connection to a client, before the client sends the HEL message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReverseHelloMessage {
+    message_header: MessageHeader,
+    /// Application URI of the server that sent the message.
+    pub server_uri: UAString,
+    /// Endpoint URL that the client should use in the HEL message.
+    pub endpoint_url: UAString,
+}
+
+impl SimpleBinaryEncodable for ReverseHelloMessage {
+    fn byte_len(&self) -> usize {
+        self.message_header.byte_len() + self.server_uri.byte_len() + self.endpoint_url.byte_len()
+    }
+
+    fn encode<S: Write + ?Sized>(&self, stream: &mut S) -> EncodingResult<()> {
+        self.message_header.encode(stream)?;
+        self.server_uri.encode(stream)?;
+        self.endpoint_url.encode(stream)
+    }
+}
+
+impl SimpleBinaryDecodable for ReverseHelloMessage {
+    fn decode<S: Read + ?Sized>(
+        stream: &mut S,
+        decoding_options: &DecodingOptions,
+    ) -> EncodingResult<Self> {
+        let message_header = MessageHeader::decode(stream, decoding_options)?;
+        let server_uri = UAString::decode(stream, decoding_options)?;
+        let endpoint_url = UAString::decode(stream, decoding_options)?;
+        Ok(ReverseHelloMessage {
+            message_header,
+            server_uri,
+            endpoint_url,
+        })
+    }
+}
+
+impl ReverseHelloMessage {
+    const MAX_URI_LEN: usize = 4096;
+
+    /// Creates a RHE message
+    pub fn new(server_uri: &str, endpoint_url: &str) -> ReverseHelloMessage {
+        let mut msg = ReverseHelloMessage {
+            message_header: MessageHeader::new(MessageType::ReverseHello),
+            server_uri: UAString::from(server_uri),
+            endpoint_url: UAString::from(endpoint_url),
+        };
+        msg.message_header.message_size = msg.byte_len() as u32;
+        msg
+    }
+
+    /// Check that the server URI and endpoint URL are present and not too long.
+    pub fn is_valid(&self) -> bool {
+        [&self.server_uri, &self.endpoint_url].into_iter().all(|s| {
+            s.value()
+                .as_ref()
+                .is_some_and(|s| !s.is_empty() && s.len() <= Self::MAX_URI_LEN)
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for HelloMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut msg = HelloMessage::new(u.arbitrary()?, 0, 0, 0, 0);
+        msg.protocol_version = u.arbitrary()?;
+        msg.receive_buffer_size = u.arbitrary()?;
+        msg.send_buffer_size = u.arbitrary()?;
+        msg.max_message_size = u.arbitrary()?;
+        msg.max_chunk_count = u.arbitrary()?;
+        Ok(msg)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for AcknowledgeMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(AcknowledgeMessage::new(
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+        ))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ErrorMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ErrorMessage::new(
+            StatusCode::from(u32::arbitrary(u)?),
+            u.arbitrary()?,
+        ))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ReverseHelloMessage {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ReverseHelloMessage::new(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
-    use crate::comms::tcp_types::{AcknowledgeMessage, HelloMessage, MessageHeader, MessageType};
+    use crate::comms::tcp_types::{
+        AcknowledgeMessage, HelloMessage, MessageHeader, MessageType, ReverseHelloMessage,
+    };
     use opcua_types::{
         ApplicationDescription, ByteString, DecodingOptions, EndpointDescription,
-        MessageSecurityMode, SimpleBinaryDecodable, UAString,
+        MessageSecurityMode, SimpleBinaryDecodable, SimpleBinaryEncodable, StatusCode, UAString,
     };
 
     fn hello_data() -> Vec<u8> {
@@ -485,6 +655,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reverse_hello() {
+        let rhe = ReverseHelloMessage::new("urn:server", "opc.tcp://127.0.0.1:1234/");
+        let mut stream = Cursor::new(Vec::new());
+        rhe.encode(&mut stream).unwrap();
+        let data = stream.into_inner();
+        assert_eq!(&data[0..4], b"RHEF");
+        assert_eq!(data.len(), rhe.byte_len());
+        assert_eq!(
+            MessageHeader::message_type(&data[0..4]),
+            MessageType::ReverseHello
+        );
+
+        let decoded =
+            ReverseHelloMessage::decode(&mut Cursor::new(data), &DecodingOptions::test()).unwrap();
+        assert_eq!(decoded, rhe);
+        assert_eq!(decoded.message_header.message_size as usize, rhe.byte_len());
+        assert!(decoded.is_valid());
+        assert!(!ReverseHelloMessage::new("", "opc.tcp://127.0.0.1:1234/").is_valid());
+    }
+
     #[test]
     fn acknowledge() {
         let mut stream = Cursor::new(ack_data());
@@ -500,6 +691,30 @@ mod tests {
         assert_eq!(ack.max_chunk_count, 65535);
     }
 
+    #[test]
+    fn acknowledge_validation() {
+        let hello = HelloMessage::new("opc.tcp://127.0.0.1:1234/", 16384, 32768, 0, 0);
+        // The server may lower the buffer sizes.
+        assert!(AcknowledgeMessage::new(0, 16384, 32768, 0, 0)
+            .validate(&hello)
+            .is_ok());
+        assert!(AcknowledgeMessage::new(0, 8192, 8192, 0, 0)
+            .validate(&hello)
+            .is_ok());
+        // But not raise them.
+        let err = AcknowledgeMessage::new(0, 32768, 32768, 0, 0)
+            .validate(&hello)
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadConnectionRejected);
+        assert!(AcknowledgeMessage::new(0, 16384, 65535, 0, 0)
+            .validate(&hello)
+            .is_err());
+        // Or go below the minimum chunk size.
+        assert!(AcknowledgeMessage::new(0, 4096, 8192, 0, 0)
+            .validate(&hello)
+            .is_err());
+    }
+
     #[test]
     fn endpoint_url() {
         // Ensure hello with None endpoint is invalid
@@ -4,6 +4,8 @@
 
 //! Provides functions for parsing Urls from strings.
 
+use std::path::PathBuf;
+
 use tracing::error;
 use url::Url;
 
@@ -12,6 +14,15 @@ use opcua_types::status_code::StatusCode;
 /// Scheme for OPC-UA TCP.
 pub const OPC_TCP_SCHEME: &str = "opc.tcp";
 
+/// Scheme for OPC-UA binary framing over Unix domain sockets, or named pipes on Windows.
+pub const OPC_UDS_SCHEME: &str = "opc.uds";
+
+/// Scheme for the OPC-UA HTTPS transport, binary encoded messages over HTTPS.
+pub const OPC_HTTPS_SCHEME: &str = "opc.https";
+
+/// Default port for `opc.https` URLs without a port.
+pub const DEFAULT_OPC_UA_HTTPS_PORT: u16 = 443;
+
 /// Creates a `Url` from the input string, supplying a default port if necessary.
 fn opc_url_from_str(s: &str) -> Result<Url, url::ParseError> {
     Url::parse(s)
@@ -68,9 +79,9 @@ pub fn server_url_from_endpoint_url(
     })
 }
 
-/// Check if this is a valid OPC-UA TCP URL.
+/// Check if this is a valid OPC-UA URL, either `opc.tcp`, `opc.uds`, or `opc.https`.
 pub fn is_valid_opc_ua_url(url: &str) -> bool {
-    is_opc_ua_binary_url(url)
+    is_opc_ua_binary_url(url) || is_opc_ua_uds_url(url) || is_opc_ua_https_url(url)
 }
 
 /// Check if this is an OPC-UA TCP URL.
@@ -82,6 +93,96 @@ pub fn is_opc_ua_binary_url(url: &str) -> bool {
     }
 }
 
+/// Check if this is an OPC-UA Unix domain socket URL, i.e. `opc.uds:///path/to/socket`.
+pub fn is_opc_ua_uds_url(url: &str) -> bool {
+    if let Ok(url) = Url::parse(url) {
+        url.scheme() == OPC_UDS_SCHEME
+    } else {
+        false
+    }
+}
+
+/// Check if this is an OPC-UA HTTPS URL, i.e. `opc.https://host:port/path` or `https://host:port/path`.
+pub fn is_opc_ua_https_url(url: &str) -> bool {
+    if let Ok(url) = Url::parse(url) {
+        (url.scheme() == OPC_HTTPS_SCHEME || url.scheme() == "https") && url.has_host()
+    } else {
+        false
+    }
+}
+
+/// Get the hostname, port, and request path from an HTTPS URL, defaulting to port 443.
+pub fn https_host_port_path_from_url(url: &str) -> Result<(String, u16, String), StatusCode> {
+    let url = Url::parse(url).map_err(|_| StatusCode::BadTcpEndpointUrlInvalid)?;
+    if url.scheme() != OPC_HTTPS_SCHEME && url.scheme() != "https" {
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    }
+    let Some(host) = url.host_str() else {
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    };
+    let port = url.port().unwrap_or(DEFAULT_OPC_UA_HTTPS_PORT);
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    Ok((host.to_string(), port, path))
+}
+
+/// Get the socket path from an `opc.uds` URL.
+///
+/// On unix platforms the URL path is the path of the socket file, so `opc.uds:///run/opcua.sock`
+/// refers to `/run/opcua.sock`. On Windows the path is the name of a named pipe, so
+/// `opc.uds:///opcua` refers to `\\.\pipe\opcua`.
+pub fn uds_path_from_url(url: &str) -> Result<PathBuf, StatusCode> {
+    let url = Url::parse(url).map_err(|_| StatusCode::BadTcpEndpointUrlInvalid)?;
+    if url.scheme() != OPC_UDS_SCHEME {
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    }
+    // The socket is always local, only accept an empty host or localhost.
+    if url
+        .host_str()
+        .is_some_and(|h| !h.is_empty() && !h.eq_ignore_ascii_case("localhost"))
+    {
+        error!("Unix domain socket url {} must not have a remote host", url);
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    }
+    let path = percent_decode(url.path()).ok_or(StatusCode::BadTcpEndpointUrlInvalid)?;
+    if path.is_empty() || path == "/" {
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    }
+
+    if cfg!(windows) {
+        Ok(PathBuf::from(format!(
+            r"\\.\pipe\{}",
+            path.trim_start_matches('/')
+        )))
+    } else {
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// Decode `%XX` escapes in a URL component, returning `None` if the result is not valid UTF-8.
+pub fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(v) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
 /// Error returned when getting host name from URL.
 pub enum HostnameFromUrlError {
     /// URL failed to parse.
@@ -132,6 +233,51 @@ mod tests {
             "opc.tcp://[FEDC:BA98:7654:3210:FEDC:BA98:7654:3210]:80/xyz"
         ));
         assert!(!is_opc_ua_binary_url("http://foo/xyz"));
+        assert!(!is_opc_ua_binary_url("opc.uds:///tmp/opcua.sock"));
+        assert!(is_opc_ua_uds_url("opc.uds:///tmp/opcua.sock"));
+        assert!(is_valid_opc_ua_url("opc.uds:///tmp/opcua.sock"));
+        assert!(!is_opc_ua_uds_url("opc.tcp://foo/xyz"));
+        assert!(is_opc_ua_https_url("opc.https://foo/xyz"));
+        assert!(is_opc_ua_https_url("https://foo:8443/xyz"));
+        assert!(!is_opc_ua_https_url("opc.tcp://foo/xyz"));
+        assert!(!is_opc_ua_https_url("http://foo/xyz"));
+    }
+
+    #[test]
+    fn https_host_port_path_from_url_test() {
+        assert_eq!(
+            https_host_port_path_from_url("opc.https://foo/xyz").unwrap(),
+            ("foo".to_owned(), 443, "/xyz".to_owned())
+        );
+        assert_eq!(
+            https_host_port_path_from_url("https://foo:8443").unwrap(),
+            ("foo".to_owned(), 8443, "/".to_owned())
+        );
+        assert_eq!(
+            https_host_port_path_from_url("opc.https://foo:8443/ua/server?x=1").unwrap(),
+            ("foo".to_owned(), 8443, "/ua/server?x=1".to_owned())
+        );
+        assert!(https_host_port_path_from_url("opc.tcp://foo:4840/").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uds_path_from_url_test() {
+        assert_eq!(
+            uds_path_from_url("opc.uds:///tmp/opcua.sock").unwrap(),
+            PathBuf::from("/tmp/opcua.sock")
+        );
+        assert_eq!(
+            uds_path_from_url("opc.uds://localhost/tmp/opcua.sock").unwrap(),
+            PathBuf::from("/tmp/opcua.sock")
+        );
+        assert_eq!(
+            uds_path_from_url("opc.uds:///tmp/my%20server.sock").unwrap(),
+            PathBuf::from("/tmp/my server.sock")
+        );
+        assert!(uds_path_from_url("opc.uds://remote/tmp/opcua.sock").is_err());
+        assert!(uds_path_from_url("opc.uds:///").is_err());
+        assert!(uds_path_from_url("opc.tcp://localhost:4840/").is_err());
     }
 
     #[test]
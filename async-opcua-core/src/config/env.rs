@@ -0,0 +1,420 @@
+//! Expansion of environment variables in configuration files.
+//!
+//! When [`EnvExpansion::enabled`] is set, string values in a configuration file may
+//! reference environment variables using shell parameter expansion syntax:
+//!
+//!  * `${VAR}` - the value of `VAR`. What happens if `VAR` is not set is controlled by
+//!    [`EnvExpansion::unset`].
+//!  * `${VAR:-default}` - `default` if `VAR` is unset or empty.
+//!  * `${VAR-default}` - `default` if `VAR` is unset.
+//!  * `${VAR:?message}` - fail with `message` if `VAR` is unset or empty.
+//!  * `${VAR?message}` - fail with `message` if `VAR` is unset.
+//!  * `${VAR:+alternative}` - `alternative` if `VAR` is set and not empty, otherwise empty.
+//!  * `${VAR+alternative}` - `alternative` if `VAR` is set, otherwise empty.
+//!
+//! Defaults, messages and alternatives may themselves contain references, and are only
+//! expanded if they are used. Write `$${` to get a literal `${`.
+//!
+//! By default, a value that changes when expanded is converted to a number or a boolean
+//! if it looks like one. A value that is a single reference can instead give the type
+//! explicitly, as `${VAR:int}`, `${VAR:float}`, `${VAR:bool}` or `${VAR:str}`. With
+//! [`EnvExpansion::strict`] set, values without a type are always kept as strings.
+
+use super::{parse_env_value, ConfigError};
+
+/// How references to environment variables that are not set are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnsetVariable {
+    /// Loading the configuration fails.
+    #[default]
+    Error,
+    /// The reference expands to an empty string.
+    Empty,
+}
+
+/// Options for expanding environment variables in configuration files.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvExpansion {
+    /// Whether to expand references to environment variables at all. Defaults to `false`,
+    /// so that files containing a literal `${` load unchanged.
+    pub enabled: bool,
+    /// How references to variables that are not set are handled.
+    pub unset: UnsetVariable,
+    /// Never convert expanded values to numbers or booleans, unless the type is given
+    /// explicitly, as in `${PORT:int}`.
+    pub strict: bool,
+}
+
+/// Explicit type of an expanded value, as in `${PORT:int}`.
+#[derive(Debug, Clone, Copy)]
+enum TypeHint {
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+impl TypeHint {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "bool" => Self::Bool,
+            "str" => Self::Str,
+            _ => return None,
+        })
+    }
+
+    fn convert(self, name: &str, value: String) -> Result<serde_yaml::Value, String> {
+        // Don't include the value in the error, it may be a secret.
+        let invalid = |ty: &str| format!("Environment variable {name} is not a valid {ty}");
+        Ok(match self {
+            Self::Int => {
+                if let Ok(v) = value.parse::<i64>() {
+                    v.into()
+                } else {
+                    value.parse::<u64>().map_err(|_| invalid("int"))?.into()
+                }
+            }
+            Self::Float => value.parse::<f64>().map_err(|_| invalid("float"))?.into(),
+            Self::Bool => value.parse::<bool>().map_err(|_| invalid("bool"))?.into(),
+            Self::Str => serde_yaml::Value::String(value),
+        })
+    }
+}
+
+/// If `s` is a single reference with a type hint, like `${PORT:int}`, return the
+/// variable name and the hint.
+fn typed_reference(s: &str) -> Option<(&str, TypeHint)> {
+    let (name, hint) = s.strip_prefix("${")?.strip_suffix('}')?.split_once(':')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((name, TypeHint::from_name(hint)?))
+}
+
+impl EnvExpansion {
+    /// Expand references to environment variables in `input`, looking variables up
+    /// in the process environment.
+    pub fn expand(&self, input: &str) -> Result<String, String> {
+        self.expand_with(input, &|name| std::env::var(name).ok())
+    }
+
+    /// Expand references to environment variables in `input`, looking variables up
+    /// using `lookup`.
+    pub fn expand_with(
+        &self,
+        input: &str,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<String, String> {
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            if let Some(after) = after.strip_prefix("${") {
+                out.push_str("${");
+                rest = after;
+            } else if let Some(body) = after.strip_prefix('{') {
+                let end = closing_brace(body)
+                    .ok_or_else(|| format!("Unterminated variable reference in \"{input}\""))?;
+                out.push_str(&self.expand_reference(&body[..end], lookup)?);
+                rest = &body[end + 1..];
+            } else {
+                out.push('$');
+                rest = after;
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn expand_reference(
+        &self,
+        body: &str,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<String, String> {
+        let name_len = body
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(body.len());
+        let (name, op) = body.split_at(name_len);
+        if name.is_empty() {
+            return Err(format!("Invalid variable reference \"${{{body}}}\""));
+        }
+        let value = lookup(name);
+        let is_set = value.is_some();
+        let non_empty = value.as_ref().is_some_and(|v| !v.is_empty());
+
+        let (colon, op) = match op.strip_prefix(':') {
+            Some(op) => (true, op),
+            None => (false, op),
+        };
+        // With a colon, empty variables are treated the same as unset ones.
+        let present = if colon { non_empty } else { is_set };
+        let mut chars = op.chars();
+        let operator = chars.next();
+        let word = chars.as_str();
+
+        match operator {
+            None if !colon => match value {
+                Some(v) => Ok(v),
+                None => match self.unset {
+                    UnsetVariable::Error => Err(format!("Environment variable {name} is not set")),
+                    UnsetVariable::Empty => Ok(String::new()),
+                },
+            },
+            Some('-') => {
+                if present {
+                    Ok(value.unwrap_or_default())
+                } else {
+                    self.expand_with(word, lookup)
+                }
+            }
+            Some('?') => {
+                if present {
+                    Ok(value.unwrap_or_default())
+                } else if word.is_empty() {
+                    Err(format!(
+                        "Environment variable {name} is {}",
+                        if colon { "not set or empty" } else { "not set" }
+                    ))
+                } else {
+                    Err(format!("{name}: {}", self.expand_with(word, lookup)?))
+                }
+            }
+            Some('+') => {
+                if present {
+                    self.expand_with(word, lookup)
+                } else {
+                    Ok(String::new())
+                }
+            }
+            _ if colon && TypeHint::from_name(op).is_some() => Err(format!(
+                "Type of \"${{{body}}}\" can only be given when the reference is the entire value"
+            )),
+            _ => Err(format!(
+                "Unsupported operator in variable reference \"${{{body}}}\""
+            )),
+        }
+    }
+
+    /// Expand references to environment variables in all strings in `value`, looking
+    /// variables up in the process environment.
+    pub fn expand_value(&self, value: &mut serde_yaml::Value) -> Result<(), ConfigError> {
+        self.expand_value_with(value, &|name| std::env::var(name).ok())
+    }
+
+    /// Expand references to environment variables in all strings in `value`, looking
+    /// variables up using `lookup`.
+    pub fn expand_value_with(
+        &self,
+        value: &mut serde_yaml::Value,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        match value {
+            serde_yaml::Value::String(s) if s.contains('$') => {
+                if let Some(expanded) = self
+                    .expand_string(s, lookup)
+                    .map_err(ConfigError::EnvExpansion)?
+                {
+                    *value = expanded;
+                }
+            }
+            serde_yaml::Value::Sequence(seq) => {
+                for v in seq {
+                    self.expand_value_with(v, lookup)?;
+                }
+            }
+            serde_yaml::Value::Mapping(map) => {
+                for (_, v) in map.iter_mut() {
+                    self.expand_value_with(v, lookup)?;
+                }
+            }
+            serde_yaml::Value::Tagged(tagged) => {
+                self.expand_value_with(&mut tagged.value, lookup)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Expand a single string value, returning `None` if it is unchanged.
+    fn expand_string(
+        &self,
+        s: &str,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<serde_yaml::Value>, String> {
+        if let Some((name, hint)) = typed_reference(s) {
+            let value = match (lookup(name), hint, self.unset) {
+                (Some(v), _, _) => v,
+                (None, TypeHint::Str, UnsetVariable::Empty) => String::new(),
+                (None, _, _) => return Err(format!("Environment variable {name} is not set")),
+            };
+            return hint.convert(name, value).map(Some);
+        }
+        let expanded = self.expand_with(s, lookup)?;
+        if expanded == s {
+            Ok(None)
+        } else if self.strict {
+            Ok(Some(serde_yaml::Value::String(expanded)))
+        } else {
+            Ok(Some(parse_env_value(expanded)))
+        }
+    }
+}
+
+/// Find the `}` closing a variable reference, skipping nested references.
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvExpansion, UnsetVariable};
+    use serde_yaml::Value;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOST" => Some("localhost".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn expand(input: &str) -> Result<String, String> {
+        EnvExpansion::default().expand_with(input, &lookup)
+    }
+
+    #[test]
+    fn expand_plain() {
+        assert_eq!(
+            expand("opc.tcp://${HOST}:4855").unwrap(),
+            "opc.tcp://localhost:4855"
+        );
+        assert_eq!(expand("${EMPTY}").unwrap(), "");
+        assert_eq!(
+            expand("no references, $5 and $$").unwrap(),
+            "no references, $5 and $$"
+        );
+        assert_eq!(expand("$${HOST}").unwrap(), "${HOST}");
+        assert!(expand("${UNSET}").is_err());
+        assert!(expand("${HOST").is_err());
+        assert!(expand("${}").is_err());
+        assert!(expand("${HOST:%x}").is_err());
+
+        let empty = EnvExpansion {
+            unset: UnsetVariable::Empty,
+            ..Default::default()
+        };
+        assert_eq!(empty.expand_with("a${UNSET}b", &lookup).unwrap(), "ab");
+    }
+
+    #[test]
+    fn expand_operators() {
+        assert_eq!(expand("${HOST:-x}").unwrap(), "localhost");
+        assert_eq!(expand("${EMPTY:-x}").unwrap(), "x");
+        assert_eq!(expand("${UNSET:-x}").unwrap(), "x");
+        assert_eq!(expand("${EMPTY-x}").unwrap(), "");
+        assert_eq!(expand("${UNSET-x}").unwrap(), "x");
+        assert_eq!(expand("${UNSET:-${HOST}}").unwrap(), "localhost");
+        assert_eq!(expand("${UNSET:-}").unwrap(), "");
+
+        assert_eq!(expand("${HOST:+x}").unwrap(), "x");
+        assert_eq!(expand("${EMPTY:+x}").unwrap(), "");
+        assert_eq!(expand("${EMPTY+x}").unwrap(), "x");
+        assert_eq!(expand("${UNSET+x}").unwrap(), "");
+
+        assert_eq!(expand("${HOST:?missing}").unwrap(), "localhost");
+        assert_eq!(expand("${EMPTY?missing}").unwrap(), "");
+        assert_eq!(
+            expand("${EMPTY:?host is required}").unwrap_err(),
+            "EMPTY: host is required"
+        );
+        assert_eq!(
+            expand("${UNSET?}").unwrap_err(),
+            "Environment variable UNSET is not set"
+        );
+        // Unused words are not expanded, so they cannot fail.
+        assert_eq!(expand("${HOST:-${UNSET}}").unwrap(), "localhost");
+    }
+
+    #[test]
+    fn expand_typed() {
+        let expand_value = |options: &EnvExpansion, s: &str| {
+            let mut value = Value::String(s.to_owned());
+            options
+                .expand_value_with(&mut value, &|name| match name {
+                    "PORT" => Some("4855".to_owned()),
+                    "PASSWORD" => Some("true".to_owned()),
+                    "RATIO" => Some("0.5".to_owned()),
+                    _ => None,
+                })
+                .map(|_| value)
+        };
+        let options = EnvExpansion::default();
+        assert_eq!(
+            expand_value(&options, "${PORT:int}").unwrap(),
+            Value::from(4855)
+        );
+        assert_eq!(
+            expand_value(&options, "${PORT:str}").unwrap(),
+            Value::from("4855")
+        );
+        assert_eq!(
+            expand_value(&options, "${RATIO:float}").unwrap(),
+            Value::from(0.5)
+        );
+        assert_eq!(
+            expand_value(&options, "${PASSWORD:bool}").unwrap(),
+            Value::from(true)
+        );
+        assert_eq!(
+            expand_value(&options, "${PASSWORD:str}").unwrap(),
+            Value::from("true")
+        );
+        assert!(expand_value(&options, "${PASSWORD:int}").is_err());
+        assert!(expand_value(&options, "${UNSET:int}").is_err());
+        // Hints are only allowed for the entire value.
+        assert!(expand_value(&options, "port ${PORT:int}").is_err());
+
+        // Without a hint, values are converted if they look like a number or a boolean...
+        assert_eq!(
+            expand_value(&options, "${PORT}").unwrap(),
+            Value::from(4855)
+        );
+        assert_eq!(
+            expand_value(&options, "${PASSWORD}").unwrap(),
+            Value::from(true)
+        );
+        // ...unless in strict mode.
+        let strict = EnvExpansion {
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            expand_value(&strict, "${PASSWORD}").unwrap(),
+            Value::from("true")
+        );
+        assert_eq!(
+            expand_value(&strict, "${PORT:int}").unwrap(),
+            Value::from(4855)
+        );
+        // Values without references are left alone either way.
+        assert_eq!(expand_value(&options, "true").unwrap(), Value::from("true"));
+    }
+}
@@ -0,0 +1,486 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Common utilities for configuration files in both the server and client.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::result::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{self, de::DeserializeOwned, Deserialize, Serialize};
+use serde_yaml;
+
+use opcua_types::{
+    ApplicationDescription, ApplicationType, ChannelSecurityToken, LocalizedText, StatusCode,
+    UAString,
+};
+
+pub mod env;
+mod overrides;
+pub mod secret;
+pub mod validation;
+pub mod watch;
+
+pub use env::{EnvExpansion, UnsetVariable};
+pub use secret::{expose_secrets, Secret};
+pub use validation::{ConfigIssue, UnknownKeys};
+pub use watch::{ConfigDiff, ConfigWatcher};
+
+use overrides::{env_overrides, EnvOverrides};
+use validation::{deserialize_config, deserialize_config_from};
+
+/// Error returned from saving or loading config objects.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Configuration is invalid, with a list of problems found.
+    ConfigInvalid(Vec<ConfigIssue>),
+    /// Reading or writing file failed.
+    IO(std::io::Error),
+    /// Failed to serialize or deserialize config object.
+    Yaml(serde_yaml::Error),
+    /// Failed to deserialize config object from TOML.
+    TomlDeserialize(toml::de::Error),
+    /// Failed to serialize config object to TOML.
+    TomlSerialize(toml::ser::Error),
+    /// Failed to serialize or deserialize config object as JSON.
+    Json(serde_json::Error),
+    /// Failed to expand an environment variable referenced in the config.
+    EnvExpansion(String),
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Yaml(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::TomlDeserialize(value)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::TomlSerialize(value)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+/// File format of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    /// YAML, the default format.
+    #[default]
+    Yaml,
+    /// TOML.
+    Toml,
+    /// JSON.
+    Json,
+}
+
+impl ConfigFormat {
+    /// Pick a format based on the extension of `path`. Files ending in `.toml` are
+    /// TOML, files ending in `.json` are JSON, anything else is treated as YAML.
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// Serialize `value` to a string in this format.
+    pub fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Result<String, ConfigError> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Json => serde_json::to_string_pretty(value)?,
+        })
+    }
+
+    /// Deserialize a value from a string in this format.
+    pub fn deserialize<T: DeserializeOwned>(self, s: &str) -> Result<T, ConfigError> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(s)?,
+            Self::Toml => toml::from_str(s)?,
+            Self::Json => serde_json::from_str(s)?,
+        })
+    }
+}
+
+/// Options for loading configuration files.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Format of the files. If this is `None` the format is picked from the
+    /// extension of each file, see [`ConfigFormat::from_path`].
+    pub format: Option<ConfigFormat>,
+    /// How references to environment variables in the files are expanded.
+    pub env: EnvExpansion,
+    /// How keys that don't match any field are handled. By default a warning is logged,
+    /// so that typos don't silently fall back to the default value.
+    pub unknown_keys: UnknownKeys,
+}
+
+impl LoadOptions {
+    /// Create load options using the given format.
+    pub fn with_format(format: ConfigFormat) -> Self {
+        Self {
+            format: Some(format),
+            ..Default::default()
+        }
+    }
+}
+
+/// Read a config file into a generic value, and expand environment variables.
+fn read_config_file(path: &Path, options: &LoadOptions) -> Result<serde_yaml::Value, ConfigError> {
+    let mut f = File::open(path)?;
+    let mut s = String::new();
+    f.read_to_string(&mut s)?;
+    let format = options
+        .format
+        .unwrap_or_else(|| ConfigFormat::from_path(path));
+    let mut value = format.deserialize(&s)?;
+    if options.env.enabled {
+        options.env.expand_value(&mut value)?;
+    }
+    Ok(value)
+}
+
+/// Deep-merge `overlay` into `base`, see [`Config::load_layered`] for the rules.
+fn merge_values(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Parse the value of an environment variable. Numbers and booleans are converted,
+/// as are sequences and maps in flow style. Anything else is kept as a string, so
+/// that values such as passwords are never mangled by YAML parsing.
+fn parse_env_value(value: String) -> serde_yaml::Value {
+    match serde_yaml::from_str(&value) {
+        Ok(v @ (serde_yaml::Value::Bool(_) | serde_yaml::Value::Number(_))) => v,
+        Ok(v @ (serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_)))
+            if value.starts_with(['[', '{']) =>
+        {
+            v
+        }
+        _ => serde_yaml::Value::String(value),
+    }
+}
+
+/// A trait that handles the loading / saving and validity of configuration information for a
+/// client and/or server.
+pub trait Config: serde::Serialize {
+    /// Save the configuration object to a file. The format is picked from the
+    /// file extension, see [`ConfigFormat::from_path`]. Secrets such as passwords
+    /// are left out, see [`secret`].
+    fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        self.save_as(path, ConfigFormat::from_path(path))
+    }
+
+    /// Save the configuration object to a file in the given format.
+    fn save_as(&self, path: &Path, format: ConfigFormat) -> Result<(), ConfigError> {
+        if let Err(e) = self.validate() {
+            return Err(ConfigError::ConfigInvalid(
+                e.into_iter().map(ConfigIssue::from).collect(),
+            ));
+        }
+        let s = format.serialize(self)?;
+        let mut f = File::create(path)?;
+        f.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    /// Save the configuration object to a file, like [`Config::save`], but including
+    /// any secrets such as passwords. These are left out or masked by `save`.
+    fn save_with_secrets(&self, path: &Path) -> Result<(), ConfigError> {
+        expose_secrets(|| self.save(path))
+    }
+
+    /// Load the configuration object from the given path. The format is picked from the
+    /// file extension, see [`ConfigFormat::from_path`].
+    fn load<A>(path: &Path) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de>,
+    {
+        Self::load_as(path, ConfigFormat::from_path(path))
+    }
+
+    /// Load the configuration object from the given path, in the given format.
+    fn load_as<A>(path: &Path, format: ConfigFormat) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de>,
+    {
+        Self::load_with(path, &LoadOptions::with_format(format))
+    }
+
+    /// Load the configuration object from the given path, using the given options.
+    ///
+    /// References to environment variables in string values, such as `${HOST}`, are
+    /// expanded if enabled in `options`, see [`env`] for the supported syntax.
+    fn load_with<A>(path: &Path, options: &LoadOptions) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de>,
+    {
+        deserialize_config(read_config_file(path, options)?, options.unknown_keys)
+    }
+
+    /// Load the configuration object by deep-merging several files, in order. Each file
+    /// only needs to contain the fields it overrides, so a deployment can be described as
+    /// a base config followed by site specific overrides and a secrets file.
+    ///
+    /// Files may use different formats, each is picked from the file extension.
+    /// Merging follows these rules:
+    ///
+    ///  * Maps are merged key by key, recursively.
+    ///  * Any other value in a later file, including sequences, replaces the earlier
+    ///    value entirely. Sequences are never concatenated.
+    ///  * An explicit `null` in a later file replaces the earlier value with `null`.
+    fn load_layered<A>(paths: &[impl AsRef<Path>]) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de>,
+    {
+        Self::load_layered_with(paths, &LoadOptions::default())
+    }
+
+    /// Load the configuration object by deep-merging several files, using the given
+    /// options. See [`Config::load_layered`].
+    fn load_layered_with<A>(
+        paths: &[impl AsRef<Path>],
+        options: &LoadOptions,
+    ) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de>,
+    {
+        let Some((first, rest)) = paths.split_first() else {
+            return Err(ConfigError::ConfigInvalid(vec![ConfigIssue::new(
+                "No configuration files to load",
+            )]));
+        };
+        let mut merged = read_config_file(first.as_ref(), options)?;
+        for path in rest {
+            merge_values(&mut merged, read_config_file(path.as_ref(), options)?);
+        }
+        deserialize_config(merged, options.unknown_keys)
+    }
+
+    /// Create the configuration object from the default configuration, overridden by
+    /// environment variables starting with `prefix`, without reading any files.
+    ///
+    /// The rest of the variable name is the path of the field, with nested fields
+    /// separated by a double underscore. Field names are matched ignoring case, so with
+    /// the prefix `OPCUA_`, `OPCUA_SESSION_RETRY_LIMIT=5` sets `session_retry_limit`,
+    /// and `OPCUA_LIMITS__MAX_SESSIONS=10` sets `limits.max_sessions`. Keys of maps,
+    /// such as endpoint and user token IDs, are used exactly as written, so
+    /// `OPCUA_ENDPOINTS__MyServer__URL` sets the URL of the endpoint `MyServer`.
+    ///
+    /// Values are converted to the type of the field they set, so a numeric password
+    /// stays a string. Sequences and maps can be given in YAML flow style, e.g. `[en, de]`.
+    fn from_env_prefix<A>(prefix: &str) -> Result<A, ConfigError>
+    where
+        for<'de> A: Config + serde::Deserialize<'de> + Default,
+    {
+        let de = EnvOverrides::new(
+            serde_yaml::to_value(A::default())?,
+            env_overrides(prefix, std::env::vars()),
+        );
+        let source = de.merged();
+        deserialize_config_from(de, &source, UnknownKeys::default())
+    }
+
+    /// Validate the config struct, returning a list of validation errors if it fails.
+    fn validate(&self) -> Result<(), Vec<String>>;
+
+    /// Get the application name.
+    fn application_name(&self) -> UAString;
+
+    /// Get the application URI.
+    fn application_uri(&self) -> UAString;
+
+    /// Get the configured product URI.
+    fn product_uri(&self) -> UAString;
+
+    /// Get the application type.
+    fn application_type(&self) -> ApplicationType;
+
+    /// Get the registered discovery URLs for this application.
+    fn discovery_urls(&self) -> Option<Vec<UAString>> {
+        None
+    }
+
+    /// Create an application description for the configured application.
+    fn application_description(&self) -> ApplicationDescription {
+        ApplicationDescription {
+            application_uri: self.application_uri(),
+            application_name: LocalizedText::new("", self.application_name().as_ref()),
+            application_type: self.application_type(),
+            product_uri: self.product_uri(),
+            gateway_server_uri: UAString::null(),
+            discovery_profile_uri: UAString::null(),
+            discovery_urls: self.discovery_urls(),
+        }
+    }
+}
+
+/// Observer for secure channel token renewals, useful for diagnosing servers that
+/// misbehave around token expiry.
+pub trait TokenRenewalObserver: Send + Sync {
+    /// Called when the secure channel token was renewed.
+    #[allow(unused)]
+    fn on_token_renewed(&self, token: &ChannelSecurityToken) {}
+
+    /// Called when an attempt to renew the secure channel token failed.
+    /// `attempt` starts at 1, `will_retry` is `true` if the renewal will be attempted again.
+    #[allow(unused)]
+    fn on_renewal_failed(&self, status: StatusCode, attempt: u32, will_retry: bool) {}
+}
+
+/// Shared handle to an optional [`TokenRenewalObserver`].
+#[derive(Clone, Default)]
+pub struct TokenRenewalHooks(Option<Arc<dyn TokenRenewalObserver>>);
+
+impl TokenRenewalHooks {
+    /// Create a new handle wrapping `observer`.
+    pub fn new(observer: impl TokenRenewalObserver + 'static) -> Self {
+        Self(Some(Arc::new(observer)))
+    }
+
+    /// Notify the observer, if any, that the token was renewed.
+    pub fn token_renewed(&self, token: &ChannelSecurityToken) {
+        if let Some(o) = &self.0 {
+            o.on_token_renewed(token);
+        }
+    }
+
+    /// Notify the observer, if any, that a renewal attempt failed.
+    pub fn renewal_failed(&self, status: StatusCode, attempt: u32, will_retry: bool) {
+        if let Some(o) = &self.0 {
+            o.on_renewal_failed(status, attempt, will_retry);
+        }
+    }
+}
+
+impl std::fmt::Debug for TokenRenewalHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TokenRenewalHooks")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
+
+impl PartialEq for TokenRenewalHooks {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Policy for when and how secure channel security tokens are renewed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenRenewalPolicy {
+    /// Requested lifetime of secure channel tokens in milliseconds.
+    /// The server may revise this.
+    #[serde(default = "token_renewal_defaults::requested_lifetime_ms")]
+    pub requested_lifetime_ms: u32,
+    /// Percentage of the token lifetime that must elapse before the token is renewed,
+    /// between 1 and 100.
+    #[serde(default = "token_renewal_defaults::renewal_threshold_percent")]
+    pub renewal_threshold_percent: u8,
+    /// Number of times a failed renewal is retried before the error is returned.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay in milliseconds between renewal retries.
+    #[serde(default = "token_renewal_defaults::retry_interval_ms")]
+    pub retry_interval_ms: u64,
+    /// Observer notified when tokens are renewed or renewal fails.
+    #[serde(skip)]
+    pub hooks: TokenRenewalHooks,
+}
+
+mod token_renewal_defaults {
+    pub(super) fn requested_lifetime_ms() -> u32 {
+        60_000
+    }
+
+    pub(super) fn renewal_threshold_percent() -> u8 {
+        75
+    }
+
+    pub(super) fn retry_interval_ms() -> u64 {
+        1_000
+    }
+}
+
+impl Default for TokenRenewalPolicy {
+    fn default() -> Self {
+        Self {
+            requested_lifetime_ms: token_renewal_defaults::requested_lifetime_ms(),
+            renewal_threshold_percent: token_renewal_defaults::renewal_threshold_percent(),
+            max_retries: 0,
+            retry_interval_ms: token_renewal_defaults::retry_interval_ms(),
+            hooks: TokenRenewalHooks::default(),
+        }
+    }
+}
+
+impl TokenRenewalPolicy {
+    /// Validate the renewal policy, returning a list of errors.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.requested_lifetime_ms == 0 {
+            errors.push(
+                "Requested secure channel token lifetime must be greater than zero".to_owned(),
+            );
+        }
+        if self.renewal_threshold_percent == 0 || self.renewal_threshold_percent > 100 {
+            errors.push(format!(
+                "Token renewal threshold of {}% is invalid - must be between 1 and 100",
+                self.renewal_threshold_percent
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Get the delay between renewal retries.
+    pub fn retry_interval(&self) -> Duration {
+        Duration::from_millis(self.retry_interval_ms)
+    }
+}
@@ -0,0 +1,424 @@
+//! Overriding configuration values with environment variables, see
+//! [`Config::from_env_prefix`](super::Config::from_env_prefix).
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Unexpected, Visitor};
+use serde_yaml::{Mapping, Value};
+
+use super::{merge_values, parse_env_value};
+
+/// Collect the variables in `vars` starting with `prefix` into a nested map. Path
+/// segments are kept as written, and values are kept as strings until they are
+/// converted to the type of the field they set.
+pub(super) fn env_overrides(prefix: &str, vars: impl Iterator<Item = (String, String)>) -> Value {
+    let mut root = Value::Mapping(Default::default());
+    for (key, value) in vars {
+        let Some(path) = key.strip_prefix(prefix) else {
+            continue;
+        };
+        if path.is_empty() {
+            continue;
+        }
+        let mut overlay = Value::String(value);
+        for segment in path.rsplit("__") {
+            let mut map = Mapping::new();
+            map.insert(segment.into(), overlay);
+            overlay = Value::Mapping(map);
+        }
+        merge_values(&mut root, overlay);
+    }
+    root
+}
+
+/// Convert the strings in an overlay without knowing the target type, the same way
+/// as expanded environment variables, see [`parse_env_value`].
+fn untyped(overlay: Value) -> Value {
+    match overlay {
+        Value::String(s) => parse_env_value(s),
+        Value::Mapping(map) => {
+            Value::Mapping(map.into_iter().map(|(k, v)| (k, untyped(v))).collect())
+        }
+        v => v,
+    }
+}
+
+/// Deserializer for a configuration value with overrides from environment variables
+/// applied on top.
+///
+/// The overrides are applied using the type being deserialized. Path segments are
+/// matched to struct fields ignoring case, but keys of maps, such as endpoint IDs, must
+/// match exactly. Values are kept as strings unless the field they set is a number or
+/// a boolean.
+pub(super) struct EnvOverrides {
+    base: Value,
+    overlay: Option<Value>,
+}
+
+impl EnvOverrides {
+    /// Create a deserializer for `base`, overridden by `overlay`, as returned by
+    /// [`env_overrides`].
+    pub(super) fn new(base: Value, overlay: Value) -> Self {
+        Self {
+            base,
+            overlay: Some(overlay),
+        }
+    }
+
+    /// Get the value with the overrides merged in as written, for reporting errors.
+    pub(super) fn merged(&self) -> Value {
+        let mut merged = self.base.clone();
+        if let Some(overlay) = &self.overlay {
+            merge_values(&mut merged, untyped(overlay.clone()));
+        }
+        merged
+    }
+
+    fn into_merged(self) -> Value {
+        let mut merged = self.base;
+        if let Some(overlay) = self.overlay {
+            merge_values(&mut merged, untyped(overlay));
+        }
+        merged
+    }
+
+    /// Pair the entries of the base map with the overrides for them. If `fields` is
+    /// given, the map is a struct, and keys are matched to the fields ignoring case.
+    fn entries(base: Value, overlay: Mapping, fields: Option<&[&str]>) -> EntriesAccess {
+        let mut normalized = Mapping::new();
+        for (key, value) in overlay {
+            let key = match (fields, key) {
+                (Some(fields), Value::String(s)) if !fields.contains(&s.as_str()) => {
+                    let lower = s.to_ascii_lowercase();
+                    if fields.contains(&lower.as_str()) {
+                        Value::String(lower)
+                    } else {
+                        Value::String(s)
+                    }
+                }
+                (_, key) => key,
+            };
+            match normalized.get_mut(&key) {
+                Some(existing) => merge_values(existing, value),
+                None => {
+                    normalized.insert(key, value);
+                }
+            }
+        }
+
+        let base = match base {
+            Value::Mapping(map) => map,
+            _ => Mapping::new(),
+        };
+        let mut entries = Vec::with_capacity(base.len() + normalized.len());
+        for (key, value) in base {
+            let overlay = normalized.remove(&key);
+            entries.push((
+                Self {
+                    base: key,
+                    overlay: None,
+                },
+                Self {
+                    base: value,
+                    overlay,
+                },
+            ));
+        }
+        for (key, value) in normalized {
+            entries.push((
+                Self {
+                    base: Value::Null,
+                    overlay: Some(key),
+                },
+                Self {
+                    base: Value::Null,
+                    overlay: Some(value),
+                },
+            ));
+        }
+        EntriesAccess {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+fn parse_scalar<T: std::str::FromStr>(s: &str, expected: &str) -> Result<T, serde_yaml::Error> {
+    s.parse()
+        .map_err(|_| de::Error::invalid_value(Unexpected::Str(s), &expected))
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.overlay {
+                    Some(Value::String(s)) => {
+                        visitor.$visit(parse_scalar::<$ty>(&s, stringify!($ty))?)
+                    }
+                    Some(_) => self.into_merged().$method(visitor),
+                    None => self.base.$method(visitor),
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! deserialize_merged {
+    ($($method:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.into_merged().$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for EnvOverrides {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(Value::Mapping(overlay))
+                if matches!(self.base, Value::Mapping(_) | Value::Null) =>
+            {
+                visitor.visit_map(Self::entries(self.base, overlay, None))
+            }
+            Some(_) => self.into_merged().deserialize_any(visitor),
+            None => self.base.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+    }
+
+    deserialize_merged! {
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_seq,
+        deserialize_ignored_any,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(Value::String(s)) => visitor.visit_string(s),
+            Some(_) => self.into_merged().deserialize_string(visitor),
+            None => self.base.deserialize_string(visitor),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(_) => visitor.visit_some(self),
+            None => self.base.deserialize_option(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.into_merged().deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(_) => visitor.visit_newtype_struct(self),
+            None => self.base.deserialize_newtype_struct(name, visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.into_merged().deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.into_merged()
+            .deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(Value::Mapping(overlay)) => {
+                visitor.visit_map(Self::entries(self.base, overlay, None))
+            }
+            Some(_) => self.into_merged().deserialize_map(visitor),
+            None => self.base.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            Some(Value::Mapping(overlay)) => {
+                visitor.visit_map(Self::entries(self.base, overlay, Some(fields)))
+            }
+            Some(_) => self.into_merged().deserialize_struct(name, fields, visitor),
+            None => self.base.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.overlay {
+            // Unit variants are given by name, don't try to parse them as anything else.
+            Some(Value::String(s)) => Value::String(s).deserialize_enum(name, variants, visitor),
+            Some(_) => self.into_merged().deserialize_enum(name, variants, visitor),
+            None => self.base.deserialize_enum(name, variants, visitor),
+        }
+    }
+}
+
+/// Access to the entries of a map with overrides applied.
+struct EntriesAccess {
+    entries: std::vec::IntoIter<(EnvOverrides, EnvOverrides)>,
+    value: Option<EnvOverrides>,
+}
+
+impl<'de> MapAccess<'de> for EntriesAccess {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <serde_yaml::Error as de::Error>::custom("value is missing"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{env_overrides, EnvOverrides};
+
+    #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+    struct Token {
+        user: String,
+        password: Option<String>,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+    struct Limits {
+        max_sessions: u32,
+        enabled: bool,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+    struct TestConfig {
+        name: String,
+        limits: Limits,
+        tokens: BTreeMap<String, Token>,
+        locales: Vec<String>,
+    }
+
+    fn load(vars: &[(&str, &str)]) -> Result<TestConfig, serde_yaml::Error> {
+        let base = serde_yaml::to_value(TestConfig::default()).unwrap();
+        let overlay = env_overrides(
+            "TEST_",
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        TestConfig::deserialize(EnvOverrides::new(base, overlay))
+    }
+
+    #[test]
+    fn typed_values() {
+        let config = load(&[
+            ("TEST_NAME", "1234"),
+            ("TEST_LIMITS__MAX_SESSIONS", "10"),
+            ("TEST_LIMITS__ENABLED", "true"),
+            ("TEST_TOKENS__user__USER", "true"),
+            ("TEST_TOKENS__user__PASSWORD", "1234"),
+            ("TEST_LOCALES", "[en, de]"),
+            ("OTHER_NAME", "other"),
+        ])
+        .unwrap();
+        // Numbers and booleans are only parsed for fields of that type.
+        assert_eq!(config.name, "1234");
+        assert_eq!(config.limits.max_sessions, 10);
+        assert!(config.limits.enabled);
+        assert_eq!(config.tokens["user"].user, "true");
+        assert_eq!(config.tokens["user"].password.as_deref(), Some("1234"));
+        assert_eq!(config.locales, vec!["en", "de"]);
+
+        assert!(load(&[("TEST_LIMITS__MAX_SESSIONS", "many")]).is_err());
+    }
+
+    #[test]
+    fn map_keys_keep_case() {
+        let config = load(&[
+            ("TEST_TOKENS__MyUser__user", "first"),
+            ("TEST_TOKENS__MYUSER__USER", "second"),
+        ])
+        .unwrap();
+        assert_eq!(config.tokens.len(), 2);
+        assert_eq!(config.tokens["MyUser"].user, "first");
+        assert_eq!(config.tokens["MYUSER"].user, "second");
+    }
+}
@@ -0,0 +1,156 @@
+//! Redaction of secrets such as passwords in configuration.
+//!
+//! Values wrapped in [`Secret`] are replaced with `*****` when printed with `{:?}` or
+//! `{}`, and are masked when serialized. Optional secrets marked with
+//! `#[serde(skip_serializing_if = "skip_secret")]` are left out of saved files entirely,
+//! so they should be supplied separately, through a layered file or an environment
+//! variable. Use [`expose_secrets`] to serialize secrets as they are. This only applies
+//! inside the closure passed to it, every other serialization, including with
+//! `serde_json`, `toml` or `serde_yaml::to_value`, masks secrets.
+//!
+//! The passwords of user tokens are the only secrets stored in the client and server
+//! configurations. Certificates and private keys are referenced by path, and private
+//! keys are read unencrypted, so there is no key password to protect.
+
+use std::cell::Cell;
+use std::fmt::{Debug, Display};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Text written in place of a secret.
+pub const REDACTED: &str = "*****";
+
+thread_local! {
+    static EXPOSE_SECRETS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with secrets serialized as they are, instead of being redacted.
+/// This only applies to serialization on the current thread.
+pub fn expose_secrets<R>(f: impl FnOnce() -> R) -> R {
+    struct Reset(bool);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            EXPOSE_SECRETS.with(|e| e.set(self.0));
+        }
+    }
+    let _reset = Reset(EXPOSE_SECRETS.with(|e| e.replace(true)));
+    f()
+}
+
+fn secrets_exposed() -> bool {
+    EXPOSE_SECRETS.with(|e| e.get())
+}
+
+/// Check whether an optional secret should be left out when serializing.
+/// Use this with `#[serde(skip_serializing_if = "skip_secret")]`.
+pub fn skip_secret<T>(value: &Option<Secret<T>>) -> bool {
+    value.is_none() || !secrets_exposed()
+}
+
+/// A value, such as a password, that must not end up in logs.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T = String>(T);
+
+impl<T> Secret<T> {
+    /// Wrap a secret value.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Get the secret value. Make sure not to log this!
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Get the secret value, consuming the wrapper.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl<T> Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Secret").field(&REDACTED).finish()
+    }
+}
+
+impl<T> Display for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if secrets_exposed() {
+            self.0.serialize(serializer)
+        } else {
+            serializer.serialize_str(REDACTED)
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{expose_secrets, skip_secret, Secret};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Token {
+        user: String,
+        #[serde(default, skip_serializing_if = "skip_secret")]
+        password: Option<Secret>,
+    }
+
+    #[test]
+    fn redacted() {
+        let token = Token {
+            user: "user".to_owned(),
+            password: Some("hunter2".into()),
+        };
+        let debug = format!("{token:?}");
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert_eq!(Secret::from("hunter2").to_string(), "*****");
+
+        assert_eq!(serde_json::to_string(&token).unwrap(), r#"{"user":"user"}"#);
+        assert_eq!(
+            serde_json::to_string(&Secret::from("hunter2")).unwrap(),
+            r#""*****""#
+        );
+    }
+
+    #[test]
+    fn exposed() {
+        let token = Token {
+            user: "user".to_owned(),
+            password: Some("hunter2".into()),
+        };
+        let json = expose_secrets(|| serde_json::to_string(&token).unwrap());
+        assert_eq!(json, r#"{"user":"user","password":"hunter2"}"#);
+        // Only inside the closure.
+        assert_eq!(serde_json::to_string(&token).unwrap(), r#"{"user":"user"}"#);
+
+        let token2: Token = serde_json::from_str(&json).unwrap();
+        assert_eq!(token, token2);
+        assert_eq!(token2.password.unwrap().expose(), "hunter2");
+    }
+}
@@ -0,0 +1,264 @@
+//! Structured errors for invalid configuration, and detection of unknown keys.
+
+use std::fmt::{self, Display};
+
+use serde::{de::DeserializeOwned, Deserializer, Serialize};
+use tracing::warn;
+
+use super::ConfigError;
+
+/// A single problem found in a configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Path to the offending field, like `endpoints.none.security_mode`, or `None`
+    /// if the problem isn't tied to a single field.
+    pub path: Option<String>,
+    /// Description of the problem.
+    pub message: String,
+    /// The offending value, if known.
+    pub value: Option<String>,
+    /// Suggested fix, for example the name of a known field similar to an unknown one.
+    pub suggestion: Option<String>,
+}
+
+impl ConfigIssue {
+    /// Create a new issue with a message and nothing else.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            message: message.into(),
+            value: None,
+            suggestion: None,
+        }
+    }
+}
+
+impl From<String> for ConfigIssue {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{path}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(value) = &self.value {
+            write!(f, " (value: {value})")?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// How keys in a configuration file that don't match any field are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownKeys {
+    /// Unknown keys are silently ignored.
+    Ignore,
+    /// A warning is logged for each unknown key.
+    #[default]
+    Warn,
+    /// Loading fails if there are any unknown keys.
+    Error,
+}
+
+/// Deserialize a configuration from a generic value, reporting errors with the path
+/// to the offending field, and handling unknown keys according to `unknown_keys`.
+pub(super) fn deserialize_config<A>(
+    value: serde_yaml::Value,
+    unknown_keys: UnknownKeys,
+) -> Result<A, ConfigError>
+where
+    A: DeserializeOwned + Serialize,
+{
+    let source = value.clone();
+    deserialize_config_from(value, &source, unknown_keys)
+}
+
+/// Deserialize a configuration from `de`, like [`deserialize_config`]. `source` is the
+/// value being deserialized, used to show offending values in errors.
+pub(super) fn deserialize_config_from<'de, A, D>(
+    de: D,
+    source: &serde_yaml::Value,
+    unknown_keys: UnknownKeys,
+) -> Result<A, ConfigError>
+where
+    A: DeserializeOwned + Serialize,
+    D: Deserializer<'de>,
+{
+    let mut unknown = Vec::new();
+    let mut on_ignored = |path: serde_ignored::Path<'_>| {
+        let mut segments = Vec::new();
+        ignored_path(&path, &mut segments);
+        unknown.push(segments);
+    };
+    let de = serde_ignored::Deserializer::new(de, &mut on_ignored);
+    let config: A = match serde_path_to_error::deserialize(de) {
+        Ok(c) => c,
+        Err(e) => {
+            let mut segments = Vec::new();
+            for segment in e.path() {
+                match segment {
+                    serde_path_to_error::Segment::Seq { index } => {
+                        segments.push(Segment::Index(*index))
+                    }
+                    serde_path_to_error::Segment::Map { key } => {
+                        segments.push(Segment::Key(key.clone()))
+                    }
+                    serde_path_to_error::Segment::Enum { variant } => {
+                        segments.push(Segment::Key(variant.clone()))
+                    }
+                    serde_path_to_error::Segment::Unknown => break,
+                }
+            }
+            let issue = ConfigIssue {
+                path: (!segments.is_empty()).then(|| format_path(&segments)),
+                message: e.inner().to_string(),
+                value: lookup(source, &segments).and_then(display_value),
+                suggestion: None,
+            };
+            return Err(ConfigError::ConfigInvalid(vec![issue]));
+        }
+    };
+
+    if unknown.is_empty() || unknown_keys == UnknownKeys::Ignore {
+        return Ok(config);
+    }
+
+    // Suggest the closest known field. Known fields are found by serializing the
+    // result, so fields that are not serialized, such as `None`, can't be suggested.
+    let known = serde_yaml::to_value(&config).ok();
+    let issues: Vec<_> = unknown
+        .into_iter()
+        .map(|segments| {
+            let suggestion = match (segments.split_last(), &known) {
+                (Some((Segment::Key(key), parent)), Some(known)) => {
+                    lookup(known, parent).and_then(|v| closest_key(v, key))
+                }
+                _ => None,
+            };
+            ConfigIssue {
+                path: Some(format_path(&segments)),
+                message: "Unknown key".to_owned(),
+                value: None,
+                suggestion,
+            }
+        })
+        .collect();
+
+    if unknown_keys == UnknownKeys::Error {
+        return Err(ConfigError::ConfigInvalid(issues));
+    }
+    for issue in issues {
+        warn!("Config: {issue}");
+    }
+    Ok(config)
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn ignored_path(path: &serde_ignored::Path<'_>, segments: &mut Vec<Segment>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            ignored_path(parent, segments);
+            segments.push(Segment::Index(*index));
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            ignored_path(parent, segments);
+            segments.push(Segment::Key(key.clone()));
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_path(parent, segments),
+    }
+}
+
+fn format_path(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Segment::Index(index) => path.push_str(&format!("[{index}]")),
+        }
+    }
+    path
+}
+
+fn lookup<'a>(value: &'a serde_yaml::Value, segments: &[Segment]) -> Option<&'a serde_yaml::Value> {
+    segments
+        .iter()
+        .try_fold(value, |value, segment| match segment {
+            Segment::Key(key) => value.get(key.as_str()),
+            Segment::Index(index) => value.get(*index),
+        })
+}
+
+fn display_value(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(format!("\"{s}\"")),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Null => Some("null".to_owned()),
+        // Don't print entire maps or sequences.
+        _ => None,
+    }
+}
+
+/// Find the key in `value` closest to `key`, if any is close enough to be a likely typo.
+fn closest_key(value: &serde_yaml::Value, key: &str) -> Option<String> {
+    let serde_yaml::Value::Mapping(map) = value else {
+        return None;
+    };
+    let max_distance = (key.len() / 3).max(2);
+    map.keys()
+        .filter_map(|k| k.as_str())
+        .map(|k| (edit_distance(k, key), k))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k.to_owned())
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::edit_distance;
+
+    #[test]
+    fn distance() {
+        assert_eq!(
+            edit_distance("session_retry_limit", "sesion_retry_limit"),
+            1
+        );
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
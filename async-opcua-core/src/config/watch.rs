@@ -0,0 +1,217 @@
+//! Watcher for reloading configuration files at runtime.
+//!
+//! [`ConfigWatcher`] polls a configuration file for changes. When the file changes it is
+//! loaded and validated again, and the callback receives a [`ConfigDiff`] with both the
+//! old and the new configuration and the paths of the fields that changed. The
+//! application decides which of those changes it can apply without restarting, for
+//! example limits, trust lists or log levels.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde::de::DeserializeOwned;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use super::{
+    read_config_file, secret::expose_secrets, validation::deserialize_config, Config, ConfigError,
+    ConfigFormat, LoadOptions,
+};
+
+/// Default interval between checks of the watched file.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The difference between two versions of a configuration.
+#[derive(Debug)]
+pub struct ConfigDiff<C> {
+    /// The configuration before the change.
+    pub old: Arc<C>,
+    /// The configuration after the change.
+    pub new: Arc<C>,
+    /// Dot separated paths of the fields that changed, for example
+    /// `limits.max_message_size`. Maps are compared key by key, anything else,
+    /// including sequences, is reported as a single changed field.
+    pub changed: Vec<String>,
+}
+
+impl<C> ConfigDiff<C> {
+    /// Return `true` if the field at `path`, or any field below it, changed.
+    /// `changed("limits")` is `true` if any of the limits changed.
+    pub fn changed(&self, path: &str) -> bool {
+        self.changed
+            .iter()
+            .any(|c| is_at_or_below(c, path) || is_at_or_below(path, c))
+    }
+
+    /// Return `true` if any field other than those at or below the given paths changed.
+    /// Use this to find out whether a change requires a restart to take effect.
+    pub fn changed_except(&self, paths: &[&str]) -> bool {
+        self.changed
+            .iter()
+            .any(|c| !paths.iter().any(|p| is_at_or_below(c, p)))
+    }
+}
+
+fn is_at_or_below(path: &str, parent: &str) -> bool {
+    path.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Watches a configuration file, and reports changes to it.
+pub struct ConfigWatcher<C> {
+    path: PathBuf,
+    options: LoadOptions,
+    poll_interval: Duration,
+    current: Arc<C>,
+}
+
+impl<C> ConfigWatcher<C>
+where
+    C: Config + DeserializeOwned,
+{
+    /// Create a new watcher for the file at `path`, loading and validating the
+    /// current contents. The format is picked from the file extension.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        Self::new_with_options(path, LoadOptions::default())
+    }
+
+    /// Create a new watcher for the file at `path` in the given format.
+    pub fn new_with_format(
+        path: impl Into<PathBuf>,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigError> {
+        Self::new_with_options(path, LoadOptions::with_format(format))
+    }
+
+    /// Create a new watcher for the file at `path`, loading it with the given options.
+    pub fn new_with_options(
+        path: impl Into<PathBuf>,
+        options: LoadOptions,
+    ) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let current = Self::load(&path, &options)?;
+        Ok(Self {
+            path,
+            options,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            current: Arc::new(current),
+        })
+    }
+
+    /// Set the interval between checks of the file, default is [`DEFAULT_POLL_INTERVAL`].
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Get the most recent valid configuration.
+    pub fn current(&self) -> Arc<C> {
+        self.current.clone()
+    }
+
+    fn load(path: &Path, options: &LoadOptions) -> Result<C, ConfigError> {
+        let config: C = deserialize_config(read_config_file(path, options)?, options.unknown_keys)?;
+        config
+            .validate()
+            .map_err(|e| ConfigError::ConfigInvalid(e.into_iter().map(Into::into).collect()))?;
+        Ok(config)
+    }
+
+    /// Check the file once, returning the diff if the configuration changed.
+    /// If the new configuration cannot be loaded or is invalid, the error is returned
+    /// and the current configuration is kept.
+    pub fn reload(&mut self) -> Result<Option<ConfigDiff<C>>, ConfigError> {
+        let new = Arc::new(Self::load(&self.path, &self.options)?);
+        let changed = changed_paths(&*self.current, &*new)?;
+        if changed.is_empty() {
+            return Ok(None);
+        }
+        let old = std::mem::replace(&mut self.current, new.clone());
+        Ok(Some(ConfigDiff { old, new, changed }))
+    }
+
+    /// Watch the file until `token` is cancelled, calling `callback` each time the
+    /// configuration changes, or when a changed file fails to load or validate.
+    ///
+    /// The file is polled for changes to its modification time and size.
+    pub async fn run(
+        mut self,
+        token: CancellationToken,
+        mut callback: impl FnMut(Result<ConfigDiff<C>, ConfigError>),
+    ) {
+        let mut last = file_stamp(&self.path);
+        let mut interval = tokio::time::interval(self.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            let stamp = file_stamp(&self.path);
+            if stamp == last {
+                continue;
+            }
+            last = stamp;
+            debug!("Configuration file {} changed", self.path.display());
+            match self.reload() {
+                Ok(Some(diff)) => callback(Ok(diff)),
+                Ok(None) => {}
+                Err(e) => callback(Err(e)),
+            }
+        }
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn changed_paths<C: Config>(old: &C, new: &C) -> Result<Vec<String>, ConfigError> {
+    // Secrets are compared as well, only the paths of changes are reported.
+    let (old, new) = expose_secrets(|| (serde_yaml::to_value(old), serde_yaml::to_value(new)));
+    let (old, new) = (old?, new?);
+    let mut changed = Vec::new();
+    diff_values("", &old, &new, &mut changed);
+    Ok(changed)
+}
+
+fn diff_values(
+    path: &str,
+    old: &serde_yaml::Value,
+    new: &serde_yaml::Value,
+    changed: &mut Vec<String>,
+) {
+    match (old, new) {
+        (serde_yaml::Value::Mapping(old), serde_yaml::Value::Mapping(new)) => {
+            let keys = old
+                .keys()
+                .chain(new.keys().filter(|k| !old.contains_key(*k)));
+            for key in keys {
+                let name = match key {
+                    serde_yaml::Value::String(s) => s.clone(),
+                    k => serde_yaml::to_string(k)
+                        .map(|s| s.trim_end().to_owned())
+                        .unwrap_or_default(),
+                };
+                let child = if path.is_empty() {
+                    name
+                } else {
+                    format!("{path}.{name}")
+                };
+                match (old.get(key), new.get(key)) {
+                    (Some(o), Some(n)) => diff_values(&child, o, n, changed),
+                    _ => changed.push(child),
+                }
+            }
+        }
+        (old, new) => {
+            if old != new {
+                changed.push(path.to_owned());
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Entry points for fuzzing the protocol layer. Only compiled with the `fuzzing` feature.
+//!
+//! Each function runs its input through the same code a transport uses for data
+//! received from the network. They are deterministic, and must never panic, whatever
+//! the input. A panic is a bug. Call them from a `cargo fuzz` target, for example
+//!
+//! ```ignore
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let _ = opcua_core::fuzz::decode_chunks(data);
+//! });
+//! ```
+//!
+//! With the `arbitrary` feature, the messages in [`crate::comms::tcp_codec::Message`]
+//! implement `arbitrary::Arbitrary`, for structure-aware fuzzing.
+
+use bytes::BytesMut;
+use opcua_crypto::SecurityPolicy;
+use opcua_types::{
+    ChannelSecurityToken, DateTime, Error, MessageSecurityMode, OpenSecureChannelResponse,
+    ResponseHeader, StatusCode,
+};
+use tokio_util::codec::Decoder;
+
+use crate::{
+    comms::{
+        buffer::SendBuffer,
+        chunker::Chunker,
+        message_chunk::{MessageChunk, MessageChunkType, MessageIsFinalType},
+        secure_channel::SecureChannel,
+        security_header::SecurityHeader,
+        sequence_number::SequenceNumberHandle,
+        tcp_codec::{Message, TcpCodec},
+    },
+    RequestMessage, ResponseMessage,
+};
+
+/// Size of the send buffer used when encoding responses.
+const SEND_BUFFER_SIZE: usize = 65535;
+
+/// Decode a stream of raw bytes into TCP messages, then reassemble the chunks among
+/// them into requests, validating sequence numbers as a server would on a channel
+/// without security.
+///
+/// Returns the decoded requests, or the first error encountered.
+pub fn decode_chunks(data: &[u8]) -> Result<Vec<RequestMessage>, Error> {
+    let mut channel = SecureChannel::new_no_certificate_store();
+    let decoding_options = channel.decoding_options();
+    let mut codec = TcpCodec::new(decoding_options.clone());
+    let mut buf = BytesMut::from(data);
+    let mut sequence_numbers = SequenceNumberHandle::new(true);
+    let mut chunks = Vec::new();
+    let mut requests = Vec::new();
+
+    while let Some(message) = codec.decode(&mut buf).map_err(Error::decoding)? {
+        let Message::Chunk(chunk) = message else {
+            continue;
+        };
+        let chunk = channel.remove_security(chunk)?;
+        let header = chunk.message_header(&decoding_options)?;
+        match header.is_final {
+            MessageIsFinalType::Intermediate => {
+                if decoding_options.max_chunk_count > 0
+                    && chunks.len() >= decoding_options.max_chunk_count
+                {
+                    return Err(Error::decoding("Message has too many chunks"));
+                }
+                chunks.push(chunk);
+            }
+            MessageIsFinalType::FinalError => chunks.clear(),
+            MessageIsFinalType::Final => {
+                chunks.push(chunk);
+                sequence_numbers.set(Chunker::validate_chunks(
+                    sequence_numbers.clone(),
+                    &channel,
+                    &chunks,
+                )?);
+                requests.push(Chunker::decode(&chunks, &channel, None)?);
+                chunks.clear();
+            }
+        }
+    }
+    Ok(requests)
+}
+
+/// Decode the body of a single chunk, a node ID followed by the encoded request,
+/// into a request.
+pub fn decode_request(body: &[u8]) -> Result<RequestMessage, Error> {
+    decode_body(body)
+}
+
+/// Decode the body of a single chunk, a node ID followed by the encoded response,
+/// into a response.
+pub fn decode_response(body: &[u8]) -> Result<ResponseMessage, Error> {
+    decode_body(body)
+}
+
+fn decode_body<T: crate::Message>(body: &[u8]) -> Result<T, Error> {
+    let channel = SecureChannel::new_no_certificate_store();
+    let chunk = MessageChunk::new(
+        1,
+        1,
+        MessageChunkType::Message,
+        MessageIsFinalType::Final,
+        &channel,
+        body,
+    )?;
+    Chunker::decode(&[chunk], &channel, None)
+}
+
+/// Run the server side of the secure channel handshake on a stream of raw bytes.
+/// The chunks in `data` are decoded into an OpenSecureChannel request, which is
+/// validated and answered the way a server does, encoding the response.
+///
+/// The server has no certificate, so only requests without security can succeed,
+/// but the security headers, sender certificates and nonces of any request are parsed.
+pub fn open_secure_channel(data: &[u8]) -> Result<(), Error> {
+    let mut channel = SecureChannel::new_no_certificate_store();
+    let decoding_options = channel.decoding_options();
+    let mut codec = TcpCodec::new(decoding_options.clone());
+    let mut buf = BytesMut::from(data);
+    let mut chunks = Vec::new();
+
+    while let Some(message) = codec.decode(&mut buf).map_err(Error::decoding)? {
+        let Message::Chunk(chunk) = message else {
+            continue;
+        };
+        let header = chunk.message_header(&decoding_options)?;
+        if !header.message_type.is_open_secure_channel() {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                "Expected an OpenSecureChannel chunk",
+            ));
+        }
+        chunks.push(channel.remove_security(chunk)?);
+        if header.is_final != MessageIsFinalType::Intermediate {
+            break;
+        }
+    }
+
+    let Some(first) = chunks.first() else {
+        return Err(Error::decoding("No OpenSecureChannel chunks"));
+    };
+    let SecurityHeader::Asymmetric(security_header) = first.chunk_info(&channel)?.security_header
+    else {
+        return Err(Error::new(
+            StatusCode::BadUnexpectedError,
+            "OpenSecureChannel chunk does not have an asymmetric security header",
+        ));
+    };
+    let RequestMessage::OpenSecureChannel(request) = Chunker::decode(&chunks, &channel, None)?
+    else {
+        return Err(Error::new(
+            StatusCode::BadUnexpectedError,
+            "Expected an OpenSecureChannel request",
+        ));
+    };
+
+    channel.set_security_mode(request.security_mode);
+    channel.set_token_id(1);
+    channel.set_secure_channel_id(1);
+    channel
+        .set_remote_cert_from_byte_string(&security_header.sender_certificate)
+        .map_err(|e| Error::new(e, "Invalid sender certificate"))?;
+    channel.set_token_lifetime(request.requested_lifetime);
+    channel
+        .validate_secure_channel_nonce(&request.client_nonce)
+        .map_err(|e| Error::new(e, "Invalid client nonce"))?;
+    channel
+        .set_remote_nonce_from_byte_string(&request.client_nonce)
+        .map_err(|e| Error::new(e, "Invalid client nonce"))?;
+    // A fixed nonce, to keep things deterministic.
+    let security_policy = channel.security_policy();
+    channel.set_local_nonce(&vec![1u8; security_policy.secure_channel_nonce_length()]);
+    if security_policy != SecurityPolicy::None
+        && matches!(
+            request.security_mode,
+            MessageSecurityMode::Sign | MessageSecurityMode::SignAndEncrypt
+        )
+    {
+        channel.derive_keys();
+    }
+
+    let response = OpenSecureChannelResponse {
+        response_header: ResponseHeader::new_good(&request.request_header),
+        server_protocol_version: 0,
+        security_token: ChannelSecurityToken {
+            channel_id: channel.secure_channel_id(),
+            token_id: channel.token_id(),
+            created_at: DateTime::null(),
+            revised_lifetime: request.requested_lifetime,
+        },
+        server_nonce: channel.local_nonce_as_byte_string(),
+    };
+    let mut send_buffer = SendBuffer::new(SEND_BUFFER_SIZE, 0, 0, true);
+    send_buffer.write(1, ResponseMessage::from(response), &channel)?;
+    send_buffer
+        .encode_next_chunk(&channel)
+        .map_err(|e| Error::new(e, "Failed to encode response"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{
+        ByteString, DateTime, MessageSecurityMode, NodeId, OpenSecureChannelRequest, RequestHeader,
+        SecurityTokenRequestType,
+    };
+
+    use crate::{
+        comms::{
+            chunker::Chunker, secure_channel::SecureChannel, sequence_number::SequenceNumberHandle,
+        },
+        RequestMessage,
+    };
+
+    use super::{decode_chunks, decode_request, open_secure_channel};
+
+    fn open_secure_channel_bytes() -> Vec<u8> {
+        let channel = SecureChannel::new_no_certificate_store();
+        let request: RequestMessage = OpenSecureChannelRequest {
+            request_header: RequestHeader::new(&NodeId::null(), &DateTime::null(), 1),
+            client_protocol_version: 0,
+            request_type: SecurityTokenRequestType::Issue,
+            security_mode: MessageSecurityMode::None,
+            client_nonce: ByteString::null(),
+            requested_lifetime: 60_000,
+        }
+        .into();
+        let chunks = Chunker::encode(
+            SequenceNumberHandle::new(true),
+            1,
+            0,
+            65535,
+            &channel,
+            &request,
+        )
+        .unwrap();
+        chunks.iter().flat_map(|c| c.data.iter().copied()).collect()
+    }
+
+    #[test]
+    fn valid_input() {
+        let data = open_secure_channel_bytes();
+        assert_eq!(decode_chunks(&data).unwrap().len(), 1);
+        open_secure_channel(&data).unwrap();
+    }
+
+    #[test]
+    fn invalid_input() {
+        let data = open_secure_channel_bytes();
+        for len in 0..data.len() {
+            let _ = decode_chunks(&data[..len]);
+            let _ = open_secure_channel(&data[..len]);
+            let _ = decode_request(&data[len..]);
+        }
+        let mut data = data;
+        for i in 0..data.len() {
+            data[i] ^= 0xff;
+            let _ = decode_chunks(&data);
+            let _ = open_secure_channel(&data);
+            data[i] ^= 0xff;
+        }
+    }
+}
@@ -2,15 +2,20 @@
 
 use std::io::Read;
 
-use opcua_types::{BinaryEncodable, EncodingResult, NodeId, ObjectId};
+use opcua_types::{BinaryEncodable, EncodingResult, NodeId, ObjectId, StatusCode};
 
 mod request;
 mod response;
+mod span;
 
 pub use request::RequestMessage;
 pub use response::ResponseMessage;
+pub use span::ServiceSpan;
 
-use crate::comms::message_chunk::MessageChunkType;
+use crate::comms::{
+    interceptor::{InterceptedMessage, MessageInterceptor},
+    message_chunk::MessageChunkType,
+};
 
 /// Trait implemented by messages and message chunks.
 pub trait MessageType {
@@ -34,4 +39,14 @@ pub trait Message: BinaryEncodable + MessageType {
 
     /// Get the type ID of the message.
     fn type_id(&self) -> NodeId;
+
+    /// Get the name of the service the message belongs to, such as `Read` or `Publish`.
+    fn type_name(&self) -> &'static str;
+
+    /// Pass the message to the method of `interceptor` matching its kind.
+    fn intercept(
+        &self,
+        interceptor: &dyn MessageInterceptor,
+        info: &InterceptedMessage,
+    ) -> Result<(), StatusCode>;
 }
@@ -1,4 +1,7 @@
-use crate::comms::message_chunk::MessageChunkType;
+use crate::comms::{
+    interceptor::{InterceptedMessage, MessageInterceptor},
+    message_chunk::MessageChunkType,
+};
 
 use super::{Message, MessageType};
 use opcua_types::*;
@@ -43,6 +46,14 @@ macro_rules! request_enum {
                 }
             }
 
+            /// Get the request header mutably, for example to send a recorded request
+            /// again on another session.
+            pub fn request_header_mut(&mut self) -> &mut RequestHeader {
+                match self {
+                    $( Self::$name(value) => &mut value.request_header, )*
+                }
+            }
+
             /// Get the name of the request variant, for debugging and logging.
             pub fn type_name(&self) -> &'static str {
                 match self {
@@ -76,6 +87,18 @@ macro_rules! request_enum {
                     $( Self::$name(v) => v.type_id().into(), )*
                 }
             }
+
+            fn type_name(&self) -> &'static str {
+                RequestMessage::type_name(self)
+            }
+
+            fn intercept(
+                &self,
+                interceptor: &dyn MessageInterceptor,
+                info: &InterceptedMessage,
+            ) -> Result<(), StatusCode> {
+                interceptor.on_request(info, self)
+            }
         }
     };
 }
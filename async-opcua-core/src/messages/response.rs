@@ -1,4 +1,7 @@
-use crate::comms::message_chunk::MessageChunkType;
+use crate::comms::{
+    interceptor::{InterceptedMessage, MessageInterceptor},
+    message_chunk::MessageChunkType,
+};
 
 use super::{Message, MessageType};
 use opcua_types::*;
@@ -76,6 +79,18 @@ macro_rules! response_enum {
                     $( Self::$name(v) => v.type_id().into(), )*
                 }
             }
+
+            fn type_name(&self) -> &'static str {
+                ResponseMessage::type_name(self)
+            }
+
+            fn intercept(
+                &self,
+                interceptor: &dyn MessageInterceptor,
+                info: &InterceptedMessage,
+            ) -> Result<(), StatusCode> {
+                interceptor.on_response(info, self)
+            }
         }
     };
 }
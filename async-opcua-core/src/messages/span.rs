@@ -0,0 +1,99 @@
+//! Tracing spans covering a single service call, from request to response.
+//!
+//! Spans are only created when the `service-spans` feature is enabled, otherwise
+//! [`ServiceSpan`] is a no-op and costs next to nothing.
+
+use std::fmt::Display;
+
+use opcua_types::StatusCode;
+use tracing::Span;
+
+use super::RequestMessage;
+
+/// Span for a single service call. Carries the service name, request handle,
+/// session ID, and once finished, the resulting status code and duration of the call.
+pub struct ServiceSpan {
+    span: Span,
+    #[cfg(feature = "service-spans")]
+    start: std::time::Instant,
+}
+
+impl ServiceSpan {
+    /// Create a span for a request sent by a client.
+    pub fn client(request: &RequestMessage) -> Self {
+        #[cfg(feature = "service-spans")]
+        {
+            Self::new(tracing::info_span!(
+                "service_call",
+                otel.kind = "client",
+                service = request.type_name(),
+                request_handle = request.request_header().request_handle,
+                session_id = tracing::field::Empty,
+                status = tracing::field::Empty,
+                duration_ms = tracing::field::Empty,
+            ))
+        }
+        #[cfg(not(feature = "service-spans"))]
+        {
+            let _ = request;
+            Self::disabled()
+        }
+    }
+
+    /// Create a span for a request received by a server.
+    pub fn server(request: &RequestMessage) -> Self {
+        #[cfg(feature = "service-spans")]
+        {
+            Self::new(tracing::info_span!(
+                "service_call",
+                otel.kind = "server",
+                service = request.type_name(),
+                request_handle = request.request_header().request_handle,
+                session_id = tracing::field::Empty,
+                status = tracing::field::Empty,
+                duration_ms = tracing::field::Empty,
+            ))
+        }
+        #[cfg(not(feature = "service-spans"))]
+        {
+            let _ = request;
+            Self::disabled()
+        }
+    }
+
+    #[cfg(feature = "service-spans")]
+    fn new(span: Span) -> Self {
+        Self {
+            span,
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "service-spans"))]
+    fn disabled() -> Self {
+        Self { span: Span::none() }
+    }
+
+    /// Get the underlying span, for instrumenting futures or entering the span.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Record the ID of the session the request belongs to.
+    pub fn record_session_id(&self, session_id: impl Display) {
+        self.span
+            .record("session_id", tracing::field::display(session_id));
+    }
+
+    /// Record the outcome of the call, and the time elapsed since the span was created.
+    pub fn finish(&self, status: StatusCode) {
+        #[cfg(feature = "service-spans")]
+        {
+            self.span.record("status", tracing::field::display(status));
+            self.span
+                .record("duration_ms", self.start.elapsed().as_secs_f64() * 1000.0);
+        }
+        #[cfg(not(feature = "service-spans"))]
+        let _ = status;
+    }
+}
@@ -1,4 +1,10 @@
-use std::io::{Cursor, Write};
+use std::{
+    io::{Cursor, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use opcua_types::{
     BinaryDecodable, ByteString, DataValue, DateTime, DecodingOptions, DiagnosticBits,
@@ -9,7 +15,11 @@ use tracing::trace;
 
 use crate::{
     comms::{
-        chunker::*, message_chunk::*, secure_channel::*, sequence_number::SequenceNumberHandle,
+        chunker::*,
+        message_chunk::*,
+        metrics::{TransportMetrics, TransportMetricsHandle},
+        secure_channel::*,
+        sequence_number::{SequenceNumberHandle, REPLAY_WINDOW},
         tcp_types::MIN_CHUNK_SIZE,
     },
     tests::*,
@@ -143,6 +153,67 @@ fn chunk_multi_encode_decode() {
     assert_eq!(response, new_response);
 }
 
+/// Removing security from chunks on an unsecured channel should not copy the chunks,
+/// and the result should still decode to the original message.
+#[test]
+fn chunk_remove_security_no_copy() {
+    let _ = Test::setup();
+
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.set_decoding_options(DecodingOptions {
+        max_array_length: 20000,
+        ..Default::default()
+    });
+
+    let response = make_large_read_response();
+    let chunks = Chunker::encode(
+        SequenceNumberHandle::new_at(true, 1000),
+        100,
+        0,
+        MIN_CHUNK_SIZE,
+        &secure_channel,
+        &response,
+    )
+    .unwrap();
+    assert!(chunks.len() > 1);
+
+    let chunks: Vec<_> = chunks
+        .into_iter()
+        .map(|chunk| {
+            let ptr = chunk.data.as_ptr();
+            let chunk = secure_channel.remove_security(chunk).unwrap();
+            assert_eq!(chunk.data.as_ptr(), ptr);
+            chunk
+        })
+        .collect();
+
+    let new_response = Chunker::decode(&chunks, &secure_channel, None).unwrap();
+    assert_eq!(response, new_response);
+
+    let new_response = Chunker::decode_owned(chunks, &secure_channel, None).unwrap();
+    assert_eq!(response, new_response);
+}
+
+#[test]
+fn chunk_into_bytes_no_copy() {
+    let secure_channel = SecureChannel::new_no_certificate_store();
+    let chunk = MessageChunk::new(
+        1,
+        1,
+        MessageChunkType::Message,
+        MessageIsFinalType::Final,
+        &secure_channel,
+        &[1, 2, 3, 4],
+    )
+    .unwrap();
+    let ptr = chunk.data.as_ptr();
+    let len = chunk.data.len();
+    let bytes = chunk.into_bytes();
+    assert_eq!(bytes.as_ptr(), ptr);
+    assert_eq!(bytes.len(), len);
+    assert_eq!(&bytes[len - 4..], &[1, 2, 3, 4]);
+}
+
 /// Encode a large message with multiple chunks. Ensure all but the last chunk is marked intermediate
 /// and the last is marked final.
 #[test]
@@ -218,6 +289,34 @@ fn max_message_size() {
     assert_eq!(err.status(), StatusCode::BadResponseTooLarge);
 }
 
+/// Encode a message without chunks, as done by the HTTPS transport, and decode it again.
+#[test]
+fn unchunked_encode_decode() {
+    let _ = Test::setup();
+
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.set_decoding_options(DecodingOptions {
+        max_array_length: 20000,
+        ..Default::default()
+    });
+
+    let response = make_large_read_response();
+    let body = Chunker::encode_unchunked(0, &secure_channel, &response).unwrap();
+    let new_response: ResponseMessage = Chunker::decode_unchunked(&body, &secure_channel).unwrap();
+    assert_eq!(response, new_response);
+
+    let ctx_r = ContextOwned::default();
+    let ctx = ctx_r.context();
+    let err = Chunker::encode_unchunked(response.byte_len(&ctx) - 1, &secure_channel, &response)
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BadResponseTooLarge);
+
+    assert!(
+        Chunker::decode_unchunked::<ResponseMessage>(&body[..body.len() - 1], &secure_channel)
+            .is_err()
+    );
+}
+
 /// Encode a large message and then ensure verification throws error for secure channel id mismatch
 #[test]
 fn validate_chunks_secure_channel_id() {
@@ -312,6 +411,83 @@ fn validate_chunks_sequence_number() {
     );
 }
 
+#[derive(Default)]
+struct SequenceNumberMetrics {
+    replays: AtomicUsize,
+    invalid: AtomicUsize,
+}
+
+impl TransportMetrics for SequenceNumberMetrics {
+    fn replay_detected(&self) {
+        self.replays.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn sequence_number_invalid(&self) {
+        self.invalid.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Ensure replayed and out of window sequence numbers are told apart
+#[test]
+fn validate_chunks_replay() {
+    let _ = Test::setup();
+
+    let metrics = Arc::new(SequenceNumberMetrics::default());
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.set_metrics(TransportMetricsHandle::new(metrics.clone()));
+    let response = make_large_read_response();
+
+    let seq_handle = SequenceNumberHandle::new_at(true, 10_000);
+    let chunks = Chunker::encode(
+        seq_handle.clone(),
+        100,
+        0,
+        MIN_CHUNK_SIZE,
+        &secure_channel,
+        &response,
+    )
+    .unwrap();
+    let next = Chunker::validate_chunks(seq_handle.clone(), &secure_channel, &chunks).unwrap();
+
+    // Receiving the same chunks again is a replay
+    let after = SequenceNumberHandle::new_at(true, next);
+    assert_eq!(
+        Chunker::validate_chunks(after.clone(), &secure_channel, &chunks)
+            .unwrap_err()
+            .status(),
+        StatusCode::BadSequenceNumberInvalid
+    );
+    assert_eq!(metrics.replays.load(Ordering::Relaxed), 1);
+
+    // Skipping chunks of a discarded message does not allow replays either
+    let mut skip = after.clone();
+    assert!(Chunker::skip_sequence_number(&mut skip, &secure_channel, 10_000).is_err());
+    assert_eq!(metrics.replays.load(Ordering::Relaxed), 2);
+    Chunker::skip_sequence_number(&mut skip, &secure_channel, next + 5).unwrap();
+    assert_eq!(skip.current(), next + 6);
+    // Neither going backwards nor skipping past the replay window is allowed
+    let invalid = metrics.invalid.load(Ordering::Relaxed);
+    assert_eq!(
+        Chunker::skip_sequence_number(&mut skip, &secure_channel, next + 6 + REPLAY_WINDOW)
+            .unwrap_err()
+            .status(),
+        StatusCode::BadSequenceNumberInvalid
+    );
+    assert_eq!(metrics.invalid.load(Ordering::Relaxed), invalid + 1);
+    assert_eq!(skip.current(), next + 6);
+
+    // Chunks far ahead of the expected sequence number are invalid, but not replays
+    let before = SequenceNumberHandle::new_at(true, 1_000);
+    assert_eq!(
+        Chunker::validate_chunks(before, &secure_channel, &chunks)
+            .unwrap_err()
+            .status(),
+        StatusCode::BadSequenceNumberInvalid
+    );
+    assert_eq!(metrics.replays.load(Ordering::Relaxed), 2);
+    assert_eq!(metrics.invalid.load(Ordering::Relaxed), 2);
+}
+
 /// Encode a large message and ensure verification throws error for request id mismatches
 #[test]
 fn validate_chunks_request_id() {
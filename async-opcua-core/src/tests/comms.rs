@@ -1,5 +1,5 @@
 use opcua_crypto::SecurityPolicy;
-use opcua_types::{ByteString, MessageSecurityMode};
+use opcua_types::{ByteString, MessageSecurityMode, StatusCode};
 
 use crate::comms::secure_channel::*;
 
@@ -86,3 +86,25 @@ fn secure_channel_nonce_none() {
         .validate_secure_channel_nonce_length(&ByteString::from(b""))
         .is_ok());
 }
+
+#[test]
+fn secure_channel_nonce_replay() {
+    let mut sc = SecureChannel::new_no_certificate_store();
+    sc.set_security_mode(MessageSecurityMode::SignAndEncrypt);
+    sc.set_security_policy(SecurityPolicy::Basic256Sha256);
+    let first = ByteString::from(b"01234567890123456789012345678901");
+    let second = ByteString::from(b"abcdefghijabcdefghijabcdefghijab");
+    assert!(sc.validate_secure_channel_nonce(&first).is_ok());
+    assert!(sc.validate_secure_channel_nonce(&second).is_ok());
+    // Nonces already received on the channel are rejected
+    assert_eq!(
+        sc.validate_secure_channel_nonce(&first),
+        Err(StatusCode::BadNonceInvalid)
+    );
+    // So is our own nonce, reflected back at us
+    sc.set_local_nonce(b"ABCDEFGHIJABCDEFGHIJABCDEFGHIJAB");
+    assert_eq!(
+        sc.validate_secure_channel_nonce(&sc.local_nonce_as_byte_string()),
+        Err(StatusCode::BadNonceInvalid)
+    );
+}
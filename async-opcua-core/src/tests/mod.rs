@@ -178,4 +178,6 @@ mod chunk;
 mod comms;
 mod secure_channel;
 mod services;
+#[cfg(feature = "service-spans")]
+mod span;
 mod supported_message;
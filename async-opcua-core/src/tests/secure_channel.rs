@@ -2,10 +2,14 @@
 //! chunks containing messages
 
 use opcua_crypto::SecurityPolicy;
+use opcua_types::DecodingOptions;
 use tracing::{error, trace};
 
 use crate::{
-    comms::{chunker::*, secure_channel::*, sequence_number::SequenceNumberHandle},
+    comms::{
+        buffer::SendBuffer, chunker::*, message_chunk::MessageChunk, secure_channel::*,
+        sequence_number::SequenceNumberHandle,
+    },
     tests::*,
     Message,
 };
@@ -233,3 +237,73 @@ fn symmetric_sign_and_encrypt_message_chunk_basic256sha256() {
         SecurityPolicy::Basic256Sha256,
     );
 }
+
+/// Encode a message to many chunks, secure them in parallel through the send buffer, then
+/// verify and decrypt them in parallel and decode back to the message.
+#[tokio::test]
+async fn parallel_sign_and_encrypt_multiple_chunks() {
+    let _ = Test::setup();
+    let (secure_channel1, mut secure_channel2) = make_secure_channels(
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::Basic256Sha256,
+    );
+    secure_channel2.set_decoding_options(DecodingOptions {
+        max_array_length: 20000,
+        ..Default::default()
+    });
+
+    let message: RequestMessage = GetEndpointsRequest {
+        locale_ids: Some(
+            (0..5000)
+                .map(|i| UAString::from(format!("en-{i}")))
+                .collect(),
+        ),
+        ..Default::default()
+    }
+    .into();
+
+    let mut buffer = SendBuffer::new(8192, 0, 0, true);
+    buffer.parallel_crypto_threshold = 2;
+    buffer.write(1, message.clone(), &secure_channel1).unwrap();
+
+    let mut out = Cursor::new(Vec::new());
+    while buffer.should_encode_chunks() || buffer.can_read() {
+        if buffer.should_encode_chunks() {
+            buffer.encode_next_chunk(&secure_channel1).unwrap();
+        }
+        buffer.read_into_async(&mut out).await.unwrap();
+    }
+
+    let ctx_r = ContextOwned::default();
+    let ctx = ctx_r.context();
+    let data = out.into_inner();
+    let mut stream = Cursor::new(&data[..]);
+    let mut chunks = Vec::new();
+    while (stream.position() as usize) < data.len() {
+        chunks.push(MessageChunk::decode(&mut stream, &ctx).unwrap());
+    }
+    assert!(chunks.len() > 2);
+
+    let chunks = secure_channel2.remove_security_batch(chunks, 2).unwrap();
+    let message2: RequestMessage = Chunker::decode(&chunks, &secure_channel2, None).unwrap();
+    assert_eq!(message, message2);
+}
+
+#[test]
+fn token_renewal_threshold() {
+    let mut channel = SecureChannel::new_no_certificate_store();
+    // No token, nothing to renew.
+    assert!(!channel.should_renew_security_token());
+
+    channel.set_security_token(opcua_types::ChannelSecurityToken {
+        channel_id: 1,
+        token_id: 1,
+        created_at: opcua_types::DateTime::now() - chrono::Duration::milliseconds(600),
+        revised_lifetime: 1000,
+    });
+    // 60% of the lifetime has elapsed, below the default threshold of 75%.
+    assert!(!channel.should_renew_security_token());
+
+    channel.set_renewal_threshold_percent(50);
+    assert!(channel.should_renew_security_token());
+}
@@ -0,0 +1,76 @@
+//! Tests for the spans emitted per service call with the `service-spans` feature.
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use opcua_types::{GetEndpointsRequest, StatusCode};
+use parking_lot::Mutex;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+use crate::{RequestMessage, ServiceSpan};
+
+#[derive(Default, Debug)]
+struct RecordedSpan {
+    name: &'static str,
+    fields: HashMap<&'static str, String>,
+}
+
+impl Visit for RecordedSpan {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields.insert(field.name(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name(), value.to_owned());
+    }
+}
+
+/// Layer recording every span and the fields recorded on it.
+#[derive(Clone, Default)]
+struct RecordingLayer {
+    spans: Arc<Mutex<HashMap<Id, RecordedSpan>>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordingLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let mut span = RecordedSpan {
+            name: attrs.metadata().name(),
+            ..Default::default()
+        };
+        attrs.record(&mut span);
+        self.spans.lock().insert(id.clone(), span);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        if let Some(span) = self.spans.lock().get_mut(id) {
+            values.record(span);
+        }
+    }
+}
+
+#[test]
+fn service_span_records_name_and_status() {
+    let layer = RecordingLayer::default();
+    let subscriber = tracing_subscriber::registry().with(layer.clone());
+
+    let request: RequestMessage = GetEndpointsRequest::default().into();
+    tracing::subscriber::with_default(subscriber, || {
+        let span = ServiceSpan::client(&request);
+        span.record_session_id("ns=1;i=5");
+        span.finish(StatusCode::BadTimeout);
+    });
+
+    let spans = layer.spans.lock();
+    assert_eq!(spans.len(), 1);
+    let span = spans.values().next().unwrap();
+    assert_eq!(span.name, "service_call");
+    assert_eq!(span.fields["service"], "GetEndpoints");
+    assert_eq!(span.fields["otel.kind"], "client");
+    assert_eq!(span.fields["session_id"], "ns=1;i=5");
+    assert_eq!(span.fields["status"], StatusCode::BadTimeout.to_string());
+    assert!(span.fields.contains_key("duration_ms"));
+}
@@ -33,6 +33,7 @@ const REJECTED_CERTS_DIR: &str = "rejected";
 
 /// The certificate store manages the storage of a server/client's own certificate & private key
 /// and the trust / rejection of certificates from the other end.
+#[derive(Debug)]
 pub struct CertificateStore {
     /// Path to the applications own certificate
     own_certificate_path: PathBuf,
@@ -503,6 +504,78 @@ impl CertificateStore {
         Ok(cert_path)
     }
 
+    /// Replace the application's own certificate, and its private key if `pkey` is given,
+    /// for example with a certificate issued by a certificate authority. The new certificate
+    /// is used for connections made after this.
+    ///
+    /// # Errors
+    ///
+    /// A string description of any failure
+    ///
+    pub fn store_own_cert_and_pkey(
+        &self,
+        cert: &X509,
+        pkey: Option<&PrivateKey>,
+    ) -> Result<(), String> {
+        if let Some(pkey) = pkey {
+            let public_key = cert
+                .public_key()
+                .map_err(|e| format!("Invalid certificate public key: {e}"))?;
+            if public_key.value != pkey.value.to_public_key() {
+                return Err("Certificate does not match the private key".to_string());
+            }
+            use rsa::pkcs8;
+            use x509_cert::der::pem::PemLabel;
+            let doc = pkey
+                .to_der()
+                .map_err(|e| format!("Failed to encode private key: {e}"))?;
+            let pem = doc
+                .to_pem(rsa::pkcs8::PrivateKeyInfo::PEM_LABEL, pkcs8::LineEnding::CR)
+                .map_err(|e| format!("Failed to encode private key: {e}"))?;
+            let _ = CertificateStore::write_to_file(
+                pem.as_bytes(),
+                &self.own_private_key_path(),
+                true,
+            )?;
+        }
+        let _ = CertificateStore::store_cert(cert, &self.own_certificate_path(), true)?;
+        Ok(())
+    }
+
+    /// Replace the certificates in the trusted directory with `certs`, for example with
+    /// a trust list pushed by, or downloaded from, a Global Discovery Server. Files in the
+    /// trusted directory that are not one of `certs` are removed.
+    ///
+    /// # Errors
+    ///
+    /// A string description of any failure
+    ///
+    pub fn replace_trusted_certs(&self, certs: &[X509]) -> Result<(), String> {
+        self.ensure_pki_path()?;
+        let keep: Vec<_> = certs.iter().map(CertificateStore::cert_file_name).collect();
+        let trusted_dir = self.trusted_certs_dir();
+        let entries = std::fs::read_dir(&trusted_dir)
+            .map_err(|e| format!("Cannot read {}: {e}", trusted_dir.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_cert = path
+                .extension()
+                .is_some_and(|ext| ext == "der" || ext == "pem");
+            let keep = path
+                .file_name()
+                .is_some_and(|name| keep.iter().any(|k| name == k.as_str()));
+            if is_cert && !keep && path.is_file() {
+                info!("Removing X509 cert {} from trusted certs", path.display());
+                std::fs::remove_file(&path)
+                    .map_err(|e| format!("Cannot remove {}: {e}", path.display()))?;
+            }
+        }
+        for cert in certs {
+            self.store_trusted_cert(cert)?;
+        }
+        Ok(())
+    }
+
     /// Writes a cert to the specified directory
     ///
     /// # Errors
@@ -120,6 +120,89 @@ fn create_rejected_cert_in_pki() {
     drop(tmp_dir);
 }
 
+#[test]
+fn store_own_cert_and_pkey_in_pki() {
+    let (tmp_dir, cert_store) = make_certificate_store();
+
+    let (cert, pkey) = make_test_cert_1024();
+    let (_, other_pkey) = make_test_cert_1024();
+    assert!(cert_store
+        .store_own_cert_and_pkey(&cert, Some(&other_pkey))
+        .is_err());
+
+    cert_store
+        .store_own_cert_and_pkey(&cert, Some(&pkey))
+        .unwrap();
+    assert_eq!(
+        cert_store.read_own_cert().unwrap().thumbprint(),
+        cert.thumbprint()
+    );
+    assert_eq!(
+        cert_store
+            .read_own_pkey()
+            .unwrap()
+            .to_der()
+            .unwrap()
+            .as_bytes(),
+        pkey.to_der().unwrap().as_bytes()
+    );
+    drop(tmp_dir);
+}
+
+#[test]
+fn replace_trusted_certs_in_pki() {
+    let (tmp_dir, cert_store) = make_certificate_store();
+
+    let (cert1, _) = make_test_cert_1024();
+    let (cert2, _) = make_test_cert_1024();
+    let (cert3, _) = make_test_cert_1024();
+    cert_store
+        .replace_trusted_certs(&[cert1.clone(), cert2.clone()])
+        .unwrap();
+    cert_store
+        .replace_trusted_certs(&[cert2.clone(), cert3.clone()])
+        .unwrap();
+
+    let mut files: Vec<_> = std::fs::read_dir(cert_store.trusted_certs_dir())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    let mut expected = vec![
+        CertificateStore::cert_file_name(&cert2),
+        CertificateStore::cert_file_name(&cert3),
+    ];
+    expected.sort();
+    assert_eq!(files, expected);
+    drop(tmp_dir);
+}
+
+#[test]
+fn create_signing_request() {
+    use x509_cert::der::Decode;
+
+    let (_, pkey) = make_test_cert_1024();
+    let args = X509Data {
+        key_size: 1024,
+        common_name: "x".to_string(),
+        organization: "x.org".to_string(),
+        organizational_unit: "x.org ops".to_string(),
+        country: "EN".to_string(),
+        state: "London".to_string(),
+        alt_host_names: vec![APPLICATION_URI.to_string(), "host1".to_string()].into(),
+        certificate_duration_days: 60,
+    };
+    let der = X509::create_signing_request(&pkey, &args).unwrap();
+    let request = x509_cert::request::CertReq::from_der(&der).unwrap();
+    assert_eq!(
+        request.info.subject.to_string(),
+        "CN=x,O=x.org,OU=x.org ops,C=EN,ST=London"
+    );
+    assert_eq!(request.info.public_key, pkey.public_key_to_info().unwrap());
+    // The alternate host names are requested as an extension.
+    assert_eq!(request.info.attributes.len(), 1);
+}
+
 #[test]
 fn test_and_reject_application_instance_cert() {
     let (tmp_dir, cert_store) = make_certificate_store();
@@ -499,7 +499,6 @@ impl X509 {
     fn create_from_pkey(pkey: &PrivateKey, x509_data: &X509Data) -> Result<Self, BuilderError> {
         use std::time::Duration;
         use x509_cert::builder::{CertificateBuilder, Profile};
-        use x509_cert::name::Name;
         use x509_cert::serial_number::SerialNumber;
         use x509_cert::time::Validity;
 
@@ -522,19 +521,7 @@ impl X509 {
 
         let serial_number = SerialNumber::from(42u32);
 
-        let subject;
-
-        {
-            let mut issuer = String::new();
-            Self::append_to_name(&mut issuer, "CN", &x509_data.common_name);
-            Self::append_to_name(&mut issuer, "O", &x509_data.organization);
-            Self::append_to_name(&mut issuer, "OU", &x509_data.organizational_unit);
-            Self::append_to_name(&mut issuer, "C", &x509_data.country);
-            Self::append_to_name(&mut issuer, "ST", &x509_data.state);
-
-            use std::str::FromStr;
-            subject = Name::from_str(&issuer)?;
-        }
+        let subject = Self::subject_from_x509_data(x509_data)?;
 
         // Issuer and subject shall be the same for self-signed cert
         let profile = Profile::Manual {
@@ -608,6 +595,46 @@ impl X509 {
         Ok(X509 { value: built })
     }
 
+    fn subject_from_x509_data(x509_data: &X509Data) -> Result<x509_cert::name::Name, BuilderError> {
+        let mut name = String::new();
+        Self::append_to_name(&mut name, "CN", &x509_data.common_name);
+        Self::append_to_name(&mut name, "O", &x509_data.organization);
+        Self::append_to_name(&mut name, "OU", &x509_data.organizational_unit);
+        Self::append_to_name(&mut name, "C", &x509_data.country);
+        Self::append_to_name(&mut name, "ST", &x509_data.state);
+
+        use std::str::FromStr;
+        Ok(x509_cert::name::Name::from_str(&name)?)
+    }
+
+    /// Create a DER encoded PKCS #10 certificate signing request for the public key of `pkey`,
+    /// with the subject and alternate host names of `x509_data`. This is sent to a certificate
+    /// authority, such as a Global Discovery Server, to get a certificate for an existing key.
+    pub fn create_signing_request(
+        pkey: &PrivateKey,
+        x509_data: &X509Data,
+    ) -> Result<Vec<u8>, String> {
+        use x509_cert::builder::{Builder, RequestBuilder};
+        use x509_cert::der::Encode;
+
+        let subject =
+            Self::subject_from_x509_data(x509_data).map_err(|e| format!("Invalid subject: {e}"))?;
+        let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(pkey.value.clone());
+        let mut builder = RequestBuilder::new(subject, &signing_key)
+            .map_err(|e| format!("Failed to create signing request: {e}"))?;
+        if !x509_data.alt_host_names.is_empty() {
+            builder
+                .add_extension(&x509_data.alt_host_names.names)
+                .map_err(|e| format!("Invalid alternate host names: {e}"))?;
+        }
+        let request = builder
+            .build::<pkcs1v15::Signature>()
+            .map_err(|e| format!("Failed to sign signing request: {e}"))?;
+        request
+            .to_der()
+            .map_err(|e| format!("Failed to encode signing request: {e}"))
+    }
+
     /// Load a certificate from a der byte string.
     pub fn from_byte_string(data: &ByteString) -> Result<X509, Error> {
         if data.is_null_or_empty() {
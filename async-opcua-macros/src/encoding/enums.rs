@@ -168,6 +168,12 @@ pub(super) fn derive_ua_enum_impl(en: SimpleEnum) -> syn::Result<TokenStream> {
             }
         }
 
+        impl opcua::types::TryFromVariant for #ident {
+            fn try_from_variant(v: opcua::types::Variant) -> Result<Self, opcua::types::Error> {
+                Self::try_from(<#repr as opcua::types::TryFromVariant>::try_from_variant(v)?)
+            }
+        }
+
         impl TryFrom<#repr> for #ident {
             type Error = opcua::types::Error;
             fn try_from(value: #repr) -> Result<Self, opcua::types::Error> {
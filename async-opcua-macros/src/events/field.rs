@@ -17,11 +17,18 @@ pub(crate) fn parse_event_field_struct(input: DeriveInput) -> syn::Result<EventF
 pub(crate) fn generate_event_field_impls(event: EventFieldStruct) -> syn::Result<TokenStream> {
     let ident = event.ident;
     let mut get_arms = quote! {};
+    let mut set_arms = quote! {};
     let mut final_arm = quote! {
         opcua::types::Variant::Empty
     };
+    let mut final_set_arm = quote! {
+        false
+    };
     let mut pre_check_block = quote! {};
+    let mut pre_check_set_block = quote! {};
     let mut placeholder_fields = quote! {};
+    let mut placeholder_set_fields = quote! {};
+    let mut select_items = quote! {};
     for field in event.fields {
         if field.attr.ignore {
             continue;
@@ -34,39 +41,88 @@ pub(crate) fn generate_event_field_impls(event: EventFieldStruct) -> syn::Result
             .rename
             .unwrap_or_else(|| field.ident.to_string().to_case(Case::Pascal));
         let ident = field.ident;
+        let typ = field.typ;
+        let select_named = quote! {
+            {
+                let mut path = browse_path.to_vec();
+                path.push(opcua::types::QualifiedName::new(namespace, #name));
+                <#typ as opcua::nodes::SelectEventField>::select_fields(&path, namespace, namespaces, clauses);
+            }
+        };
 
         if field.attr.placeholder {
             placeholder_fields.extend(quote! {
                 if let Some(value) = self.#ident.try_get_value(field, attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])) {
                     return value;
                 }
-            })
+            });
+            placeholder_set_fields.extend(quote! {
+                if self.#ident.try_set_value(field, attribute_id, browse_path.get(1..).unwrap_or(&[]), value.clone()) {
+                    return true;
+                }
+            });
         } else if !has_rename {
             match ident.to_string().as_str() {
                 "base" => {
                     final_arm = quote! {
                         self.base.get_value(attribute_id, index_range, browse_path)
-                    }
+                    };
+                    final_set_arm = quote! {
+                        opcua::nodes::SetEventField::set_value(&mut self.base, attribute_id, browse_path, value)
+                    };
+                    select_items.extend(quote! {
+                        <#typ as opcua::nodes::SelectEventField>::select_fields(browse_path, namespace, namespaces, clauses);
+                    });
+                }
+                "node_id" => {
+                    pre_check_block.extend(quote! {
+                        if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::NodeId {
+                            let val: opcua::types::Variant = self.node_id.clone().into();
+                            return val.range_of_owned(index_range).unwrap_or(opcua::types::Variant::Empty);
+                        }
+                    });
+                    pre_check_set_block.extend(quote! {
+                        if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::NodeId {
+                            return opcua::nodes::SetEventField::set_value(&mut self.node_id, opcua::types::AttributeId::Value, browse_path, value);
+                        }
+                    });
+                    select_items.extend(quote! {
+                        clauses.push(opcua::types::event_field::select_clause(browse_path, opcua::types::AttributeId::NodeId));
+                    });
+                }
+                "value" => {
+                    pre_check_block.extend(quote! {
+                        if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::Value {
+                            return self.value.get_value(attribute_id, index_range, browse_path);
+                        }
+                    });
+                    pre_check_set_block.extend(quote! {
+                        if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::Value {
+                            return opcua::nodes::SetEventField::set_value(&mut self.value, attribute_id, browse_path, value);
+                        }
+                    });
+                    select_items.extend(quote! {
+                        clauses.push(opcua::types::event_field::select_clause(browse_path, opcua::types::AttributeId::Value));
+                    });
+                }
+                _ => {
+                    get_arms.extend(quote! {
+                        #name => self.#ident.get_value(attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])),
+                    });
+                    set_arms.extend(quote! {
+                        #name => opcua::nodes::SetEventField::set_value(&mut self.#ident, attribute_id, browse_path.get(1..).unwrap_or(&[]), value),
+                    });
+                    select_items.extend(select_named);
                 }
-                "node_id" => pre_check_block.extend(quote! {
-                    if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::NodeId {
-                        let val: opcua::types::Variant = self.node_id.clone().into();
-                        return val.range_of_owned(index_range).unwrap_or(opcua::types::Variant::Empty);
-                    }
-                }),
-                "value" => pre_check_block.extend(quote! {
-                    if browse_path.is_empty() && attribute_id == opcua::types::AttributeId::Value {
-                        return self.value.get_value(attribute_id, index_range, browse_path);
-                    }
-                }),
-                _ => get_arms.extend(quote! {
-                    #name => self.#ident.get_value(attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])),
-                })
             }
         } else {
             get_arms.extend(quote! {
                 #name => self.#ident.get_value(attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])),
             });
+            set_arms.extend(quote! {
+                #name => opcua::nodes::SetEventField::set_value(&mut self.#ident, attribute_id, browse_path.get(1..).unwrap_or(&[]), value),
+            });
+            select_items.extend(select_named);
         }
     }
     final_arm = quote! {
@@ -75,6 +131,12 @@ pub(crate) fn generate_event_field_impls(event: EventFieldStruct) -> syn::Result
             #final_arm
         }
     };
+    final_set_arm = quote! {
+        _ => {
+            #placeholder_set_fields
+            #final_set_arm
+        }
+    };
 
     Ok(quote! {
         impl opcua::nodes::EventField for #ident {
@@ -95,5 +157,36 @@ pub(crate) fn generate_event_field_impls(event: EventFieldStruct) -> syn::Result
                 }
             }
         }
+
+        impl opcua::nodes::SetEventField for #ident {
+            fn set_value(
+                &mut self,
+                attribute_id: opcua::types::AttributeId,
+                browse_path: &[opcua::types::QualifiedName],
+                value: opcua::types::Variant,
+            ) -> bool {
+                #pre_check_set_block
+                if browse_path.is_empty() {
+                    return false;
+                }
+                let field = &browse_path[0];
+                match field.name.as_ref() {
+                    #set_arms
+                    #final_set_arm
+                }
+            }
+        }
+
+        impl opcua::nodes::SelectEventField for #ident {
+            #[allow(unused_variables)]
+            fn select_fields(
+                browse_path: &[opcua::types::QualifiedName],
+                namespace: u16,
+                namespaces: &opcua::nodes::NamespaceMap,
+                clauses: &mut Vec<opcua::types::SimpleAttributeOperand>,
+            ) {
+                #select_items
+            }
+        }
     })
 }
@@ -6,8 +6,11 @@ use quote::quote;
 pub(crate) fn generate_event_impls(event: EventStruct) -> syn::Result<TokenStream> {
     let ident = event.ident;
     let mut get_arms = quote! {};
+    let mut set_arms = quote! {};
     let mut init_items = quote! {};
     let mut placeholder_fields = quote! {};
+    let mut placeholder_set_fields = quote! {};
+    let mut select_items = quote! {};
     for field in event.fields {
         let name = field
             .attr
@@ -19,11 +22,27 @@ pub(crate) fn generate_event_impls(event: EventStruct) -> syn::Result<TokenStrea
                 if let Some(value) = self.#ident.try_get_value(field, attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])) {
                     return value;
                 }
-            })
+            });
+            placeholder_set_fields.extend(quote! {
+                if self.#ident.try_set_value(field, attribute_id, browse_path.get(1..).unwrap_or(&[]), value.clone()) {
+                    return true;
+                }
+            });
         } else if !field.attr.ignore {
             get_arms.extend(quote! {
                 #name => self.#ident.get_value(attribute_id, index_range, browse_path.get(1..).unwrap_or(&[])),
             });
+            set_arms.extend(quote! {
+                #name => opcua::nodes::SetEventField::set_value(&mut self.#ident, attribute_id, browse_path.get(1..).unwrap_or(&[]), value),
+            });
+            let typ = &field.typ;
+            select_items.extend(quote! {
+                {
+                    let mut path = browse_path.to_vec();
+                    path.push(opcua::types::QualifiedName::new(namespace, #name));
+                    <#typ as opcua::nodes::SelectEventField>::select_fields(&path, namespace, namespaces, clauses);
+                }
+            });
         }
         init_items.extend(quote! {
             #ident: Default::default(),
@@ -81,6 +100,16 @@ pub(crate) fn generate_event_impls(event: EventStruct) -> syn::Result<TokenStrea
         quote! { 0 }
     };
 
+    let select_namespace = if let Some(ns) = &event.attribute.namespace {
+        quote! {
+            let Some(namespace) = namespaces.get_index(#ns) else {
+                return;
+            };
+        }
+    } else {
+        quote! { let namespace = 0u16; }
+    };
+
     let base_type = event.attribute.base_type.unwrap();
 
     if event.attribute.namespace.is_some() {
@@ -183,6 +212,42 @@ pub(crate) fn generate_event_impls(event: EventStruct) -> syn::Result<TokenStrea
             }
         }
 
+        impl opcua::nodes::SetEventField for #ident {
+            fn set_value(
+                &mut self,
+                attribute_id: opcua::types::AttributeId,
+                browse_path: &[opcua::types::QualifiedName],
+                value: opcua::types::Variant,
+            ) -> bool {
+                if browse_path.is_empty() {
+                    return false;
+                }
+                let field = &browse_path[0];
+                match field.name.as_ref() {
+                    #set_arms
+                    _ => {
+                        #placeholder_set_fields
+                        opcua::nodes::SetEventField::set_value(&mut self.base, attribute_id, browse_path, value)
+                    }
+                }
+            }
+        }
+
+        impl opcua::nodes::SelectEventField for #ident {
+            #[allow(unused_variables)]
+            fn select_fields(
+                browse_path: &[opcua::types::QualifiedName],
+                namespace: u16,
+                namespaces: &opcua::nodes::NamespaceMap,
+                clauses: &mut Vec<opcua::types::SimpleAttributeOperand>,
+            ) {
+                <#base_type as opcua::nodes::SelectEventField>::select_fields(browse_path, namespace, namespaces, clauses);
+                // Fields declared by this event type are in its own namespace.
+                #select_namespace
+                #select_items
+            }
+        }
+
         impl #ident {
             #ctors
         }
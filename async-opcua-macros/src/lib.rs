@@ -2,14 +2,18 @@
 
 mod encoding;
 mod events;
+mod method_args;
+mod tag_binding;
 mod utils;
 
 use encoding::{
     derive_all_inner, derive_ua_nullable_inner, generate_encoding_impl, EncodingToImpl,
 };
 use events::{derive_event_field_inner, derive_event_inner};
+use method_args::derive_method_args_inner;
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
+use tag_binding::derive_tag_binding_inner;
 
 #[proc_macro_derive(Event, attributes(opcua))]
 /// Derive the `Event` trait. This will also generate
@@ -72,6 +76,57 @@ pub fn derive_event_field(item: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_derive(MethodArgs)]
+/// Derive the `MethodArgs` trait, converting the struct to and from a list
+/// of method arguments.
+///
+/// Each field is an argument, in the order the fields are declared. All fields must
+/// implement `IntoVariant` and `TryFromVariant`.
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(MethodArgs)]
+/// struct AddInput {
+///     lhs: i64,
+///     rhs: i64,
+/// }
+/// ```
+pub fn derive_method_args(item: TokenStream) -> TokenStream {
+    match derive_method_args_inner(parse_macro_input!(item)) {
+        Ok(r) => r.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(TagBinding, attributes(opcua))]
+/// Derive the `TagBinding` trait, binding the fields of a struct to
+/// the values of nodes on a server.
+///
+/// Each field must have an attribute `opcua(node_id = ...)` with the node ID
+/// it is bound to, as a string such as `ns=2;s=Tag1` or `nsu=urn:my:namespace;i=5`,
+/// or be ignored with `opcua(ignore)`. Bound fields must implement `TryFromVariant`.
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(TagBinding, Default)]
+/// struct Tank {
+///     #[opcua(node_id = "ns=2;s=Tank.Level")]
+///     level: f64,
+///     #[opcua(node_id = "ns=2;s=Tank.Valve")]
+///     valve_open: bool,
+///     #[opcua(ignore)]
+///     updates: u32,
+/// }
+/// ```
+pub fn derive_tag_binding(item: TokenStream) -> TokenStream {
+    match derive_tag_binding_inner(parse_macro_input!(item)) {
+        Ok(r) => r.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[cfg(feature = "json")]
 #[proc_macro_derive(JsonEncodable, attributes(opcua))]
 /// Derive the `JsonEncodable` trait on this struct or enum, creating code
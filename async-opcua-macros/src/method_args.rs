@@ -0,0 +1,64 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::utils::{expect_struct, EmptyAttribute, StructItem};
+
+pub(crate) fn derive_method_args_inner(input: DeriveInput) -> syn::Result<TokenStream> {
+    let item = StructItem::<EmptyAttribute, EmptyAttribute>::from_input(
+        expect_struct(input.data)?,
+        input.attrs,
+        input.ident,
+    )?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let ident = &item.ident;
+    let num_fields = item.fields.len();
+    let mut encode = quote! {};
+    let mut decode = quote! {};
+    for field in &item.fields {
+        let name = &field.ident;
+        let typ = &field.typ;
+        let name_str = name.to_string();
+        encode.extend(quote! {
+            opcua::types::IntoVariant::into_variant(self.#name),
+        });
+        decode.extend(quote! {
+            #name: <#typ as opcua::types::TryFromVariant>::try_from_variant(
+                iter.next().unwrap_or_default()
+            ).map_err(|e| opcua::types::Error::new(
+                e.status(),
+                format!("Invalid argument {}: {}", #name_str, e)
+            ))?,
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics opcua::types::MethodArgs for #ident #ty_generics #where_clause {
+            fn into_arguments(self) -> Vec<opcua::types::Variant> {
+                vec![#encode]
+            }
+
+            fn from_arguments(
+                arguments: Vec<opcua::types::Variant>
+            ) -> Result<Self, opcua::types::Error> {
+                if arguments.len() != #num_fields {
+                    let status = if arguments.len() < #num_fields {
+                        opcua::types::StatusCode::BadArgumentsMissing
+                    } else {
+                        opcua::types::StatusCode::BadTooManyArguments
+                    };
+                    return Err(opcua::types::Error::new(
+                        status,
+                        format!("Expected {} arguments, got {}", #num_fields, arguments.len())
+                    ));
+                }
+                #[allow(unused_mut, unused_variables)]
+                let mut iter = arguments.into_iter();
+                Ok(Self {
+                    #decode
+                })
+            }
+        }
+    })
+}
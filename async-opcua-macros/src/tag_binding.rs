@@ -0,0 +1,106 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse::Parse, DeriveInput, Ident, LitStr, Token};
+
+use crate::utils::{expect_struct, EmptyAttribute, ItemAttr, StructItem};
+
+#[derive(Default, Debug)]
+pub(crate) struct TagFieldAttribute {
+    pub ignore: bool,
+    pub node_id: Option<LitStr>,
+}
+
+impl Parse for TagFieldAttribute {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut slf = Self::default();
+        loop {
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "ignore" => slf.ignore = true,
+                "node_id" => {
+                    input.parse::<Token![=]>()?;
+                    slf.node_id = Some(input.parse()?);
+                }
+                _ => return Err(syn::Error::new_spanned(ident, "Unknown attribute value")),
+            }
+            if !input.peek(Token![,]) {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(slf)
+    }
+}
+
+impl ItemAttr for TagFieldAttribute {
+    fn combine(&mut self, other: Self) {
+        self.ignore |= other.ignore;
+        if other.node_id.is_some() {
+            self.node_id = other.node_id;
+        }
+    }
+}
+
+pub(crate) fn derive_tag_binding_inner(input: DeriveInput) -> syn::Result<TokenStream> {
+    let item = StructItem::<TagFieldAttribute, EmptyAttribute>::from_input(
+        expect_struct(input.data)?,
+        input.attrs,
+        input.ident,
+    )?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let ident = &item.ident;
+    let mut node_ids = quote! {};
+    let mut set_arms = quote! {};
+    let mut index = 0usize;
+    for field in &item.fields {
+        if field.attr.ignore {
+            continue;
+        }
+        let Some(node_id) = &field.attr.node_id else {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                "Field must have a node ID, set with #[opcua(node_id = \"...\")], or be ignored with #[opcua(ignore)]",
+            ));
+        };
+        let name = &field.ident;
+        let typ = &field.typ;
+        node_ids.extend(quote! {
+            #node_id,
+        });
+        set_arms.extend(quote! {
+            #index => {
+                self.#name = <#typ as opcua::types::TryFromVariant>::try_from_variant(
+                    value.value.unwrap_or_default()
+                )?;
+            }
+        });
+        index += 1;
+    }
+
+    Ok(quote! {
+        impl #impl_generics opcua::client::TagBinding for #ident #ty_generics #where_clause {
+            fn node_ids() -> Vec<&'static str> {
+                vec![#node_ids]
+            }
+
+            #[allow(unused_variables)]
+            fn set_value(
+                &mut self,
+                index: usize,
+                value: opcua::types::DataValue,
+            ) -> Result<(), opcua::types::Error> {
+                match index {
+                    #set_arms
+                    _ => {
+                        return Err(opcua::types::Error::new(
+                            opcua::types::StatusCode::BadIndexRangeInvalid,
+                            format!("Invalid tag index {}", index),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+        }
+    })
+}
@@ -3,8 +3,9 @@
 // Copyright (C) 2017-2024 Adam Lock
 
 use opcua_types::{
-    status_code::StatusCode, AttributeId, DataEncoding, DataValue, LocalizedText, NodeClass,
-    NodeId, NumericRange, QualifiedName, TimestampsToReturn, Variant, WriteMask,
+    status_code::StatusCode, AccessRestrictionType, AttributeId, DataEncoding, DataValue,
+    ExtensionObject, LocalizedText, NodeClass, NodeId, NumericRange, QualifiedName,
+    RolePermissionType, TimestampsToReturn, Variant, WriteMask,
 };
 
 use super::node::{Node, NodeBase};
@@ -26,6 +27,10 @@ pub struct Base {
     pub(super) write_mask: Option<u32>,
     /// User write mask bits (optional)
     pub(super) user_write_mask: Option<u32>,
+    /// Permissions granted to roles on this node (optional)
+    pub(super) role_permissions: Option<Vec<RolePermissionType>>,
+    /// Access restrictions (optional)
+    pub(super) access_restrictions: Option<u16>,
 }
 
 impl NodeBase for Base {
@@ -72,6 +77,23 @@ impl NodeBase for Base {
     fn set_user_write_mask(&mut self, user_write_mask: WriteMask) {
         self.user_write_mask = Some(user_write_mask.bits());
     }
+
+    fn role_permissions(&self) -> Option<&[RolePermissionType]> {
+        self.role_permissions.as_deref()
+    }
+
+    fn set_role_permissions(&mut self, role_permissions: Vec<RolePermissionType>) {
+        self.role_permissions = Some(role_permissions);
+    }
+
+    fn access_restrictions(&self) -> Option<AccessRestrictionType> {
+        self.access_restrictions
+            .map(|v| AccessRestrictionType::from_bits_truncate(v as i16))
+    }
+
+    fn set_access_restrictions(&mut self, access_restrictions: AccessRestrictionType) {
+        self.access_restrictions = Some(access_restrictions.bits() as u16);
+    }
 }
 
 impl Node for Base {
@@ -94,6 +116,16 @@ impl Node for Base {
                 .map(|description| description.into()),
             AttributeId::WriteMask => self.write_mask.map(|v| v.into()),
             AttributeId::UserWriteMask => self.user_write_mask.map(|v| v.into()),
+            AttributeId::RolePermissions => self.role_permissions.as_ref().map(|r| {
+                Variant::from(
+                    r.iter()
+                        .cloned()
+                        .map(ExtensionObject::from_message)
+                        .collect::<Vec<_>>(),
+                )
+                .into()
+            }),
+            AttributeId::AccessRestrictions => self.access_restrictions.map(|v| v.into()),
             _ => None,
         }
     }
@@ -174,6 +206,31 @@ impl Node for Base {
                     Err(StatusCode::BadTypeMismatch)
                 }
             }
+            AttributeId::RolePermissions => {
+                let Variant::Array(arr) = value else {
+                    return Err(StatusCode::BadTypeMismatch);
+                };
+                let mut role_permissions = Vec::with_capacity(arr.values.len());
+                for v in arr.values {
+                    let Some(v) = (match v {
+                        Variant::ExtensionObject(o) => o.into_inner_as::<RolePermissionType>(),
+                        _ => None,
+                    }) else {
+                        return Err(StatusCode::BadTypeMismatch);
+                    };
+                    role_permissions.push(*v);
+                }
+                self.role_permissions = Some(role_permissions);
+                Ok(())
+            }
+            AttributeId::AccessRestrictions => {
+                if let Variant::UInt16(v) = value {
+                    self.access_restrictions = Some(v);
+                    Ok(())
+                } else {
+                    Err(StatusCode::BadTypeMismatch)
+                }
+            }
             _ => Err(StatusCode::BadAttributeIdInvalid),
         }
     }
@@ -195,6 +252,8 @@ impl Base {
             description: None,
             write_mask: None,
             user_write_mask: None,
+            role_permissions: None,
+            access_restrictions: None,
         }
     }
 
@@ -217,6 +276,8 @@ impl Base {
             description,
             write_mask,
             user_write_mask,
+            role_permissions: None,
+            access_restrictions: None,
         }
     }
 
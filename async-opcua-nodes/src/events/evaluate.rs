@@ -31,15 +31,27 @@ impl ParsedEventFilter {
             return None;
         }
 
+        Some(self.select(event, client_handle))
+    }
+
+    /// Check whether `event` passes the where clause of this filter, without
+    /// selecting any fields.
+    pub fn matches(&self, event: &dyn Event, type_tree: &dyn TypeTree) -> bool {
+        self.content_filter.evaluate(event, type_tree)
+    }
+
+    /// Select the fields of `event` given by the select clauses of this filter,
+    /// without evaluating its where clause.
+    pub fn select(&self, event: &dyn Event, client_handle: u32) -> EventFieldList {
         let fields: Vec<_> = self
             .select_clauses
             .iter()
             .map(|c| get_field(event, c))
             .collect();
-        Some(EventFieldList {
+        EventFieldList {
             client_handle,
             event_fields: Some(fields),
-        })
+        }
     }
 }
 
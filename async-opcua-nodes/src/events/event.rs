@@ -1,7 +1,8 @@
 use crate::NamespaceMap;
 use opcua_types::{
-    event_field::EventField, AttributeId, ByteString, DateTime, LocalizedText, NodeId,
-    NumericRange, ObjectTypeId, QualifiedName, TimeZoneDataType, UAString, Variant,
+    event_field::{select_clause, EventField, SelectEventField, SetEventField},
+    AttributeId, ByteString, DateTime, LocalizedText, NodeId, NumericRange, ObjectTypeId,
+    QualifiedName, SimpleAttributeOperand, TimeZoneDataType, UAString, Variant,
 };
 
 /// Trait implemented by all events.
@@ -141,6 +142,85 @@ impl EventField for BaseEventType {
     }
 }
 
+impl SetEventField for BaseEventType {
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if remaining_path.len() != 1 || attribute_id != AttributeId::Value {
+            // Field is not from base event type.
+            return false;
+        }
+        let field = &remaining_path[0];
+        if field.namespace_index != 0 {
+            return false;
+        }
+        match field.name.as_ref() {
+            "EventId" => self.event_id.set_value(attribute_id, &[], value),
+            "EventType" => self.event_type.set_value(attribute_id, &[], value),
+            "SourceNode" => self.source_node.set_value(attribute_id, &[], value),
+            // UAString has an inherent `set_value` method.
+            "SourceName" => {
+                SetEventField::set_value(&mut self.source_name, attribute_id, &[], value)
+            }
+            "Time" => self.time.set_value(attribute_id, &[], value),
+            "ReceiveTime" => self.receive_time.set_value(attribute_id, &[], value),
+            "LocalTime" => self.local_time.set_value(attribute_id, &[], value),
+            "Message" => self.message.set_value(attribute_id, &[], value),
+            "Severity" => self.severity.set_value(attribute_id, &[], value),
+            "ConditionClassId" => self.condition_class_id.set_value(attribute_id, &[], value),
+            "ConditionClassName" => self
+                .condition_class_name
+                .set_value(attribute_id, &[], value),
+            "ConditionSubClassId" => {
+                self.condition_sub_class_id
+                    .set_value(attribute_id, &[], value)
+            }
+            "ConditionSubClassName" => {
+                self.condition_sub_class_name
+                    .set_value(attribute_id, &[], value)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SelectEventField for BaseEventType {
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        _namespace: u16,
+        _namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        if !browse_path.is_empty() {
+            // Base event type fields are only set at the root of an event.
+            return;
+        }
+        for field in [
+            "EventId",
+            "EventType",
+            "SourceNode",
+            "SourceName",
+            "Time",
+            "ReceiveTime",
+            "LocalTime",
+            "Message",
+            "Severity",
+            "ConditionClassId",
+            "ConditionClassName",
+            "ConditionSubClassId",
+            "ConditionSubClassName",
+        ] {
+            clauses.push(select_clause(
+                &[QualifiedName::new(0, field)],
+                AttributeId::Value,
+            ));
+        }
+    }
+}
+
 impl BaseEventType {
     /// Create a new event with `Time` set to current time.
     pub fn new_now(
@@ -232,13 +312,16 @@ mod tests {
         pub(super) use opcua_types as types;
     }
 
-    use crate::{BaseEventType, Event, EventField};
-    use opcua_types::event_field::PlaceholderEventField;
+    use crate::{BaseEventType, Event, EventField, SetEventField};
+    use opcua_types::event_field::{
+        event_from_fields, event_select_clauses, PlaceholderEventField,
+    };
     use opcua_types::{
-        AttributeId, ByteString, EUInformation, KeyValuePair, LocalizedText, NodeId, NumericRange,
-        ObjectTypeId, QualifiedName, StatusCode, UAString, Variant,
+        AttributeId, ByteString, EUInformation, EventFilterBuilder, KeyValuePair, LocalizedText,
+        NodeId, NumericRange, ObjectTypeId, QualifiedName, SimpleAttributeOperand, StatusCode,
+        UAString, Variant,
     };
-    #[derive(Event)]
+    #[derive(Event, Default, Debug)]
     #[opcua(identifier = "s=myevent", namespace = "uri:my:namespace")]
     struct BasicValueEvent {
         base: BaseEventType,
@@ -401,7 +484,7 @@ mod tests {
         extra: PlaceholderEventField<i32>,
     }
 
-    #[derive(Event)]
+    #[derive(Event, Default, Debug)]
     #[opcua(identifier = "s=mynestedevent", namespace = "uri:my:namespace")]
     struct NestedEvent {
         base: BasicValueEvent,
@@ -545,4 +628,182 @@ mod tests {
             Variant::from(15)
         );
     }
+
+    #[test]
+    fn test_event_from_fields() {
+        let namespaces = namespace_map();
+        let id = NestedEvent::event_type_id(&namespaces);
+        let filter = EventFilterBuilder::new()
+            .select(ObjectTypeId::BaseEventType, "Message")
+            .select(ObjectTypeId::BaseEventType, "Severity")
+            .select(id.clone(), "Float")
+            .select(id.clone(), "Optvec")
+            .select(id.clone(), "Complex/Float")
+            .select(id.clone(), "SubComplex/gnirtS")
+            .select(id.clone(), "Fancy Name")
+            .select_operand(SimpleAttributeOperand::new(
+                id.clone(),
+                "Var",
+                AttributeId::NodeId,
+                NumericRange::None,
+            ))
+            .select(id.clone(), "Var")
+            .select(id.clone(), "Int")
+            .build();
+        let fields = vec![
+            LocalizedText::from("Some message").into(),
+            Variant::from(500u16),
+            Variant::from(2f32),
+            Variant::from(vec![3i32, 2i32, 1i32]),
+            Variant::from(3f32),
+            Variant::from("foo"),
+            Variant::from("bar"),
+            Variant::from(NodeId::new(0, 16)),
+            Variant::from(20i32),
+            Variant::Empty,
+        ];
+        let evt: NestedEvent =
+            event_from_fields(filter.select_clauses.as_deref().unwrap(), fields).unwrap();
+
+        assert_eq!(evt.base.base.message, LocalizedText::from("Some message"));
+        assert_eq!(evt.base.base.severity, 500);
+        assert_eq!(evt.base.float, 2f32);
+        assert_eq!(evt.base.optvec, Some(vec![3, 2, 1]));
+        assert_eq!(evt.base.int, None);
+        assert_eq!(evt.complex.float, 3f32);
+        assert_eq!(evt.sub_complex.string, UAString::from("foo"));
+        assert_eq!(evt.renamed, "bar");
+        assert_eq!(evt.var.node_id, NodeId::new(0, 16));
+        assert_eq!(evt.var.value, 20);
+
+        // Fields with the wrong type, or fields that don't exist, are errors.
+        let err = event_from_fields::<NestedEvent>(
+            &[SimpleAttributeOperand::new_value(id.clone(), "Float")],
+            vec![Variant::from(NodeId::new(0, 1))],
+        )
+        .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadTypeMismatch);
+        let err = event_from_fields::<NestedEvent>(
+            &[SimpleAttributeOperand::new_value(id.clone(), "FooBar")],
+            vec![Variant::from(1)],
+        )
+        .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadTypeMismatch);
+        // Ignored fields cannot be set.
+        let mut evt = NestedEvent::default();
+        assert!(!evt.set_value(AttributeId::Value, &["Ignored".into()], Variant::from(1)));
+
+        // Placeholders are only set if the field exists.
+        let name = QualifiedName::new(1, "Extra1");
+        assert!(!evt.set_value(
+            AttributeId::Value,
+            &[name.clone(), "Float".into()],
+            Variant::from(20f32)
+        ));
+        evt.extra_fields
+            .insert_field(name.clone(), SubComplexEventField::default());
+        assert!(evt.set_value(
+            AttributeId::Value,
+            &[name.clone(), "Float".into()],
+            Variant::from(20f32)
+        ));
+        assert_eq!(evt.extra_fields.get_field(&name).unwrap().base.float, 20f32);
+    }
+
+    #[test]
+    fn test_event_select_clauses() {
+        let namespaces = namespace_map();
+        let clauses = event_select_clauses::<NestedEvent>(&namespaces);
+        let paths: Vec<_> = clauses
+            .iter()
+            .map(|c| {
+                let path = c
+                    .browse_path
+                    .iter()
+                    .flatten()
+                    .map(|p| p.name.as_ref())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (path, AttributeId::from_u32(c.attribute_id).unwrap())
+            })
+            .collect();
+        assert!(clauses
+            .iter()
+            .all(|c| c.type_definition_id == ObjectTypeId::BaseEventType));
+        // Base event type fields come first, then fields of each subtype.
+        assert_eq!(paths.len(), 31);
+        assert_eq!(paths[0], ("EventId".to_owned(), AttributeId::Value));
+        assert_eq!(paths[13], ("Float".to_owned(), AttributeId::Value));
+        for expected in [
+            ("Optvec", AttributeId::Value),
+            ("Complex/Float", AttributeId::Value),
+            ("SubComplex/Float", AttributeId::Value),
+            ("SubComplex", AttributeId::NodeId),
+            ("SubComplex/gnirtS", AttributeId::Value),
+            ("Var", AttributeId::NodeId),
+            ("Var", AttributeId::Value),
+            ("Var/Id", AttributeId::Value),
+            ("Fancy Name", AttributeId::Value),
+        ] {
+            assert!(paths.contains(&(expected.0.to_owned(), expected.1)));
+        }
+        // Base event type fields are in namespace 0, fields declared by the event types,
+        // including those of nested fields, are in the namespace of the event type.
+        let own_ns = namespaces.get_index("uri:my:namespace").unwrap();
+        for clause in &clauses {
+            let path = clause.browse_path.as_deref().unwrap();
+            let expected = if clauses[..13].contains(clause) {
+                0
+            } else {
+                own_ns
+            };
+            assert!(path.iter().all(|p| p.namespace_index == expected));
+        }
+        // Unknown namespaces are not selected.
+        assert_eq!(
+            event_select_clauses::<NestedEvent>(&NamespaceMap::new()).len(),
+            13
+        );
+
+        // Ignored fields and placeholders are not selected.
+        assert!(!paths
+            .iter()
+            .any(|(p, _)| p == "Ignored" || p == "ExtraFields"));
+
+        // The selected fields can be read from an event, and used to recreate it.
+        let mut evt = NestedEvent::new_event_now(
+            NestedEvent::event_type_id(&namespaces),
+            ByteString::from_base64("dGVzdA==").unwrap(),
+            "Some message",
+            &namespaces,
+        );
+        evt.base.float = 2f32;
+        evt.base.optvec = Some(vec![1, 2]);
+        evt.sub_complex.node_id = NodeId::new(0, 15);
+        evt.sub_complex.string = "foo".into();
+        evt.var.value = 20;
+        evt.var.id = 5;
+        evt.renamed = "bar".to_owned();
+        let fields = clauses
+            .iter()
+            .map(|c| {
+                evt.get_field(
+                    &c.type_definition_id,
+                    AttributeId::from_u32(c.attribute_id).unwrap(),
+                    &c.index_range,
+                    c.browse_path.as_deref().unwrap(),
+                )
+            })
+            .collect();
+        let read: NestedEvent = event_from_fields(&clauses, fields).unwrap();
+        assert_eq!(read.base.base.event_id, evt.base.base.event_id);
+        assert_eq!(read.base.base.message, LocalizedText::from("Some message"));
+        assert_eq!(read.base.float, 2f32);
+        assert_eq!(read.base.optvec, Some(vec![1, 2]));
+        assert_eq!(read.sub_complex.node_id, NodeId::new(0, 15));
+        assert_eq!(read.sub_complex.string, UAString::from("foo"));
+        assert_eq!(read.var.value, 20);
+        assert_eq!(read.var.id, 5);
+        assert_eq!(read.renamed, "bar");
+    }
 }
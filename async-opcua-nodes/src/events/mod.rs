@@ -5,7 +5,7 @@ mod validation;
 
 pub use evaluate::AttributeQueryable;
 pub use event::{BaseEventType, Event, MethodEventField};
-pub use opcua_types::event_field::EventField;
+pub use opcua_types::event_field::{EventField, SelectEventField, SetEventField};
 pub use validation::{
     ParsedAttributeOperand, ParsedContentFilter, ParsedContentFilterElement, ParsedEventFilter,
     ParsedOperand, ParsedSimpleAttributeOperand,
@@ -106,6 +106,12 @@ impl ParsedEventFilter {
     ) -> (EventFilterResult, Result<Self, StatusCode>) {
         validate(raw, type_tree)
     }
+
+    /// Get the select clauses of the filter, in the order of the fields
+    /// returned by [`ParsedEventFilter::evaluate`].
+    pub fn select_clauses(&self) -> &[ParsedSimpleAttributeOperand] {
+        &self.select_clauses
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -228,6 +234,23 @@ fn validate_select_clause(
         });
     }
 
+    // The ConditionId of a condition is selected with an empty browse path and the
+    // NodeId attribute on ConditionType, see Part 9 5.5.2.
+    if path.is_empty()
+        && attribute_id == AttributeId::NodeId
+        && type_tree.is_subtype_of(
+            &clause.type_definition_id,
+            &ObjectTypeId::ConditionType.into(),
+        )
+    {
+        return Ok(ParsedSimpleAttributeOperand {
+            type_definition_id: clause.type_definition_id,
+            browse_path: path,
+            attribute_id,
+            index_range: clause.index_range,
+        });
+    }
+
     let Some(node) = type_tree.find_type_prop_by_browse_path(&clause.type_definition_id, &path)
     else {
         return Err(StatusCode::BadNodeIdUnknown);
@@ -0,0 +1,658 @@
+use std::io::Write;
+
+use hashbrown::HashMap;
+use opcua_types::{
+    xml::XmlEncodable, BrowseDirection, Context, DataTypeDefinition, Error, LocalizedText, NodeId,
+    QualifiedName, RolePermissionType, StructureType, Variant,
+};
+use opcua_xml::{
+    events::{BytesDecl, BytesStart, Event},
+    XmlStreamWriter,
+};
+
+use crate::{
+    DataType, DefaultTypeTree, Method, NodeSetModel, NodeType, Object, ObjectType,
+    ReferenceDirection, ReferenceType, References, Variable, VariableType, View,
+};
+
+const NODESET_NAMESPACE: &str = "http://opcfoundation.org/UA/2011/03/UANodeSet.xsd";
+const TYPES_NAMESPACE: &str = "http://opcfoundation.org/UA/2008/02/Types.xsd";
+
+type Writer<'a> = XmlStreamWriter<&'a mut dyn Write>;
+
+/// Write nodes and their references as a NodeSet2 XML document, which can be loaded again
+/// with [`NodeSet2Import`](crate::NodeSet2Import).
+///
+/// All namespaces in the namespace map of `ctx` except the base namespace are written
+/// to the namespace table of the node set, in the order of their index, and namespace
+/// indices are renumbered to positions in that table. References are
+/// written on both the source and target node, if both are exported. Nodes and references
+/// are sorted, so exporting the same nodes twice gives the same document.
+pub fn write_nodeset2<'a>(
+    writer: &mut dyn Write,
+    nodes: impl IntoIterator<Item = &'a NodeType>,
+    references: &References,
+    ctx: &Context<'_>,
+) -> Result<(), Error> {
+    write_nodeset2_inner(writer, None, nodes, references, ctx)
+}
+
+/// Write nodes and their references as a NodeSet2 XML document that declares `model`
+/// in its model table, see [`write_nodeset2`]. Tools such as the code generator require
+/// a model table to know which namespace a node set defines.
+pub fn write_model_nodeset2<'a>(
+    writer: &mut dyn Write,
+    model: &NodeSetModel,
+    nodes: impl IntoIterator<Item = &'a NodeType>,
+    references: &References,
+    ctx: &Context<'_>,
+) -> Result<(), Error> {
+    write_nodeset2_inner(writer, Some(model), nodes, references, ctx)
+}
+
+fn write_nodeset2_inner<'a>(
+    writer: &mut dyn Write,
+    model: Option<&NodeSetModel>,
+    nodes: impl IntoIterator<Item = &'a NodeType>,
+    references: &References,
+    ctx: &Context<'_>,
+) -> Result<(), Error> {
+    let mut namespaces: Vec<_> = ctx
+        .namespaces()
+        .known_namespaces()
+        .iter()
+        .filter(|(_, idx)| **idx > 0)
+        .collect();
+    namespaces.sort_by_key(|(_, idx)| **idx);
+    let index_map: HashMap<u16, u16> = namespaces
+        .iter()
+        .enumerate()
+        .map(|(i, (_, idx))| (i as u16 + 1, **idx))
+        .collect();
+    let mut ctx = ctx.clone();
+    ctx.set_index_map(&index_map);
+
+    let mut nodes: Vec<_> = nodes
+        .into_iter()
+        .map(|n| (n.as_node().node_id().to_string(), n))
+        .collect();
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut writer = XmlStreamWriter::new(writer);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+    writer.write_event(Event::Start(BytesStart::new("UANodeSet").with_attributes(
+        [("xmlns", NODESET_NAMESPACE), ("xmlns:uax", TYPES_NAMESPACE)],
+    )))?;
+    if !namespaces.is_empty() {
+        writer.write_start("NamespaceUris")?;
+        for (uri, _) in &namespaces {
+            writer.write_start("Uri")?;
+            writer.write_text(uri)?;
+            writer.write_end("Uri")?;
+        }
+        writer.write_end("NamespaceUris")?;
+    }
+    if let Some(model) = model {
+        writer.write_start("Models")?;
+        let publication_date = model.publication_date.map(|d| d.to_rfc3339());
+        let mut start =
+            BytesStart::new("Model").with_attributes([("ModelUri", model.model_uri.as_str())]);
+        if let Some(version) = &model.version {
+            start.push_attribute(("Version", version.as_str()));
+        }
+        if let Some(publication_date) = &publication_date {
+            start.push_attribute(("PublicationDate", publication_date.as_str()));
+        }
+        writer.write_event(Event::Start(start))?;
+        for required in &model.required_models {
+            writer.write_event(Event::Empty(
+                BytesStart::new("RequiredModel").with_attributes([("ModelUri", required.as_str())]),
+            ))?;
+        }
+        writer.write_end("Model")?;
+        writer.write_end("Models")?;
+    }
+
+    let type_tree = DefaultTypeTree::new();
+    for (_, node) in nodes {
+        let mut exporter = NodeExporter {
+            writer: &mut writer,
+            ctx: &ctx,
+        };
+        exporter.write_node(node, references, &type_tree)?;
+    }
+
+    writer.write_end("UANodeSet")?;
+    Ok(())
+}
+
+struct NodeExporter<'a, 'b> {
+    writer: &'a mut Writer<'b>,
+    ctx: &'a Context<'a>,
+}
+
+impl NodeExporter<'_, '_> {
+    fn node_id(&self, node_id: &NodeId) -> Result<String, Error> {
+        let namespace = self
+            .ctx
+            .resolve_namespace_index_inverse(node_id.namespace)?;
+        Ok(if namespace > 0 {
+            format!("ns={};{}", namespace, node_id.identifier)
+        } else {
+            node_id.identifier.to_string()
+        })
+    }
+
+    fn qualified_name(&self, name: &QualifiedName) -> Result<String, Error> {
+        let namespace = self
+            .ctx
+            .resolve_namespace_index_inverse(name.namespace_index)?;
+        Ok(if namespace > 0 {
+            format!("{}:{}", namespace, name.name)
+        } else {
+            name.name.to_string()
+        })
+    }
+
+    fn write_node(
+        &mut self,
+        node: &NodeType,
+        references: &References,
+        type_tree: &DefaultTypeTree,
+    ) -> Result<(), Error> {
+        let base = node.as_node();
+        let (tag, attributes) = match node {
+            NodeType::Object(n) => ("UAObject", self.object_attributes(n)),
+            NodeType::Variable(n) => ("UAVariable", self.variable_attributes(n)?),
+            NodeType::Method(n) => ("UAMethod", self.method_attributes(n)),
+            NodeType::View(n) => ("UAView", self.view_attributes(n)),
+            NodeType::ObjectType(n) => ("UAObjectType", self.object_type_attributes(n)),
+            NodeType::VariableType(n) => ("UAVariableType", self.variable_type_attributes(n)?),
+            NodeType::DataType(n) => ("UADataType", self.data_type_attributes(n)),
+            NodeType::ReferenceType(n) => ("UAReferenceType", self.reference_type_attributes(n)),
+        };
+
+        let mut start = BytesStart::new(tag);
+        start.push_attribute(("NodeId", self.node_id(base.node_id())?.as_str()));
+        start.push_attribute((
+            "BrowseName",
+            self.qualified_name(base.browse_name())?.as_str(),
+        ));
+        if let Some(mask) = base.write_mask().filter(|m| !m.is_empty()) {
+            start.push_attribute(("WriteMask", mask.bits().to_string().as_str()));
+        }
+        if let Some(mask) = base.user_write_mask().filter(|m| !m.is_empty()) {
+            start.push_attribute(("UserWriteMask", mask.bits().to_string().as_str()));
+        }
+        if let Some(restrictions) = base.access_restrictions().filter(|r| !r.is_empty()) {
+            start.push_attribute((
+                "AccessRestrictions",
+                restrictions.bits().to_string().as_str(),
+            ));
+        }
+        for (key, value) in &attributes {
+            start.push_attribute((*key, value.as_str()));
+        }
+        self.writer.write_event(Event::Start(start))?;
+
+        self.write_localized_text("DisplayName", base.display_name())?;
+        if let Some(description) = base.description() {
+            self.write_localized_text("Description", description)?;
+        }
+        self.write_references(base.node_id(), references, type_tree)?;
+        if let Some(role_permissions) = base.role_permissions() {
+            self.write_role_permissions(role_permissions)?;
+        }
+
+        match node {
+            NodeType::Variable(n) => {
+                if let Some(value) = n.value.value.as_ref() {
+                    self.write_value(value)?;
+                }
+            }
+            NodeType::VariableType(n) => {
+                if let Some(value) = n.value().and_then(|v| v.value.as_ref()) {
+                    self.write_value(value)?;
+                }
+            }
+            NodeType::DataType(n) => {
+                if let Some(definition) = n.data_type_definition() {
+                    self.write_definition(base.browse_name(), definition)?;
+                }
+            }
+            NodeType::ReferenceType(n) => {
+                if let Some(inverse_name) = n.inverse_name() {
+                    self.write_localized_text("InverseName", &inverse_name)?;
+                }
+            }
+            _ => (),
+        }
+
+        self.writer.write_end(tag)?;
+        Ok(())
+    }
+
+    fn object_attributes(&self, node: &Object) -> Vec<(&'static str, String)> {
+        let mut attributes = Vec::new();
+        if !node.event_notifier().is_empty() {
+            attributes.push(("EventNotifier", node.event_notifier().bits().to_string()));
+        }
+        attributes
+    }
+
+    fn variable_attributes(&self, node: &Variable) -> Result<Vec<(&'static str, String)>, Error> {
+        let mut attributes = vec![("DataType", self.node_id(&node.data_type())?)];
+        if node.value_rank() != -1 {
+            attributes.push(("ValueRank", node.value_rank().to_string()));
+        }
+        if let Some(dims) = node.array_dimensions() {
+            attributes.push(("ArrayDimensions", array_dimensions(&dims)));
+        }
+        if node.access_level().bits() != 1 {
+            attributes.push(("AccessLevel", node.access_level().bits().to_string()));
+        }
+        if node.user_access_level().bits() != 1 {
+            attributes.push((
+                "UserAccessLevel",
+                node.user_access_level().bits().to_string(),
+            ));
+        }
+        if let Some(interval) = node.minimum_sampling_interval().filter(|i| *i != 0.0) {
+            attributes.push(("MinimumSamplingInterval", interval.to_string()));
+        }
+        if node.historizing() {
+            attributes.push(("Historizing", "true".to_owned()));
+        }
+        Ok(attributes)
+    }
+
+    fn method_attributes(&self, node: &Method) -> Vec<(&'static str, String)> {
+        let mut attributes = Vec::new();
+        if node.executable() {
+            attributes.push(("Executable", "true".to_owned()));
+        }
+        if node.user_executable() {
+            attributes.push(("UserExecutable", "true".to_owned()));
+        }
+        attributes
+    }
+
+    fn view_attributes(&self, node: &View) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![("EventNotifier", node.event_notifier().bits().to_string())];
+        if node.contains_no_loops() {
+            attributes.push(("ContainsNoLoops", "true".to_owned()));
+        }
+        attributes
+    }
+
+    fn object_type_attributes(&self, node: &ObjectType) -> Vec<(&'static str, String)> {
+        is_abstract(node.is_abstract())
+    }
+
+    fn variable_type_attributes(
+        &self,
+        node: &VariableType,
+    ) -> Result<Vec<(&'static str, String)>, Error> {
+        let mut attributes = vec![("DataType", self.node_id(node.data_type())?)];
+        if node.value_rank() != -1 {
+            attributes.push(("ValueRank", node.value_rank().to_string()));
+        }
+        if let Some(dims) = node.array_dimensions() {
+            attributes.push(("ArrayDimensions", array_dimensions(&dims)));
+        }
+        attributes.extend(is_abstract(node.is_abstract()));
+        Ok(attributes)
+    }
+
+    fn data_type_attributes(&self, node: &DataType) -> Vec<(&'static str, String)> {
+        is_abstract(node.is_abstract())
+    }
+
+    fn reference_type_attributes(&self, node: &ReferenceType) -> Vec<(&'static str, String)> {
+        let mut attributes = is_abstract(node.is_abstract());
+        if node.symmetric() {
+            attributes.push(("Symmetric", "true".to_owned()));
+        }
+        attributes
+    }
+
+    fn write_localized_text(&mut self, tag: &str, text: &LocalizedText) -> Result<(), Error> {
+        let mut start = BytesStart::new(tag);
+        if !text.locale.is_empty() {
+            start.push_attribute(("Locale", text.locale.as_ref()));
+        }
+        self.writer.write_event(Event::Start(start))?;
+        self.writer.write_text(text.text.as_ref())?;
+        self.writer.write_end(tag)?;
+        Ok(())
+    }
+
+    fn write_references(
+        &mut self,
+        node_id: &NodeId,
+        references: &References,
+        type_tree: &DefaultTypeTree,
+    ) -> Result<(), Error> {
+        let mut refs = Vec::new();
+        for r in references.find_references(
+            node_id,
+            None::<(NodeId, bool)>,
+            type_tree,
+            BrowseDirection::Both,
+        ) {
+            refs.push((
+                self.node_id(r.reference_type)?,
+                r.direction == ReferenceDirection::Forward,
+                self.node_id(r.target_node)?,
+            ));
+        }
+        if refs.is_empty() {
+            return Ok(());
+        }
+        refs.sort();
+
+        self.writer.write_start("References")?;
+        for (reference_type, is_forward, target) in refs {
+            let mut start = BytesStart::new("Reference");
+            start.push_attribute(("ReferenceType", reference_type.as_str()));
+            if !is_forward {
+                start.push_attribute(("IsForward", "false"));
+            }
+            self.writer.write_event(Event::Start(start))?;
+            self.writer.write_text(&target)?;
+            self.writer.write_end("Reference")?;
+        }
+        self.writer.write_end("References")?;
+        Ok(())
+    }
+
+    fn write_role_permissions(
+        &mut self,
+        role_permissions: &[RolePermissionType],
+    ) -> Result<(), Error> {
+        self.writer.write_start("RolePermissions")?;
+        for permission in role_permissions {
+            let mut start = BytesStart::new("RolePermission");
+            start.push_attribute((
+                "Permissions",
+                permission.permissions.bits().to_string().as_str(),
+            ));
+            self.writer.write_event(Event::Start(start))?;
+            self.writer
+                .write_text(&self.node_id(&permission.role_id)?)?;
+            self.writer.write_end("RolePermission")?;
+        }
+        self.writer.write_end("RolePermissions")?;
+        Ok(())
+    }
+
+    fn write_value(&mut self, value: &Variant) -> Result<(), Error> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        // The value is encoded without namespace prefixes, so make the types
+        // namespace the default within the value element.
+        self.writer.write_event(Event::Start(
+            BytesStart::new("Value").with_attributes([("xmlns", TYPES_NAMESPACE)]),
+        ))?;
+        value.encode(self.writer, self.ctx)?;
+        self.writer.write_end("Value")?;
+        Ok(())
+    }
+
+    fn write_definition(
+        &mut self,
+        name: &QualifiedName,
+        definition: &DataTypeDefinition,
+    ) -> Result<(), Error> {
+        let mut start = BytesStart::new("Definition");
+        start.push_attribute(("Name", self.qualified_name(name)?.as_str()));
+        if matches!(
+            definition,
+            DataTypeDefinition::Structure(s) if s.structure_type == StructureType::Union
+        ) {
+            start.push_attribute(("IsUnion", "true"));
+        }
+        self.writer.write_event(Event::Start(start))?;
+
+        match definition {
+            DataTypeDefinition::Structure(s) => {
+                for field in s.fields.iter().flatten() {
+                    let mut start = BytesStart::new("Field");
+                    start.push_attribute(("Name", field.name.as_ref()));
+                    start.push_attribute(("DataType", self.node_id(&field.data_type)?.as_str()));
+                    if field.value_rank != -1 {
+                        start.push_attribute(("ValueRank", field.value_rank.to_string().as_str()));
+                    }
+                    if let Some(dims) = field.array_dimensions.as_ref() {
+                        start.push_attribute(("ArrayDimensions", array_dimensions(dims).as_str()));
+                    }
+                    if field.max_string_length > 0 {
+                        start.push_attribute((
+                            "MaxStringLength",
+                            field.max_string_length.to_string().as_str(),
+                        ));
+                    }
+                    if field.is_optional {
+                        start.push_attribute(("IsOptional", "true"));
+                    }
+                    self.write_field(start, None, &field.description)?;
+                }
+            }
+            DataTypeDefinition::Enum(e) => {
+                for field in e.fields.iter().flatten() {
+                    let mut start = BytesStart::new("Field");
+                    start.push_attribute(("Name", field.name.as_ref()));
+                    start.push_attribute(("Value", field.value.to_string().as_str()));
+                    self.write_field(start, Some(&field.display_name), &field.description)?;
+                }
+            }
+        }
+
+        self.writer.write_end("Definition")?;
+        Ok(())
+    }
+
+    fn write_field(
+        &mut self,
+        start: BytesStart<'_>,
+        display_name: Option<&LocalizedText>,
+        description: &LocalizedText,
+    ) -> Result<(), Error> {
+        let display_name = display_name.filter(|t| !t.text.is_empty());
+        if display_name.is_none() && description.text.is_empty() {
+            self.writer.write_event(Event::Empty(start))?;
+            return Ok(());
+        }
+        self.writer.write_event(Event::Start(start))?;
+        if let Some(display_name) = display_name {
+            self.write_localized_text("DisplayName", display_name)?;
+        }
+        if !description.text.is_empty() {
+            self.write_localized_text("Description", description)?;
+        }
+        self.writer.write_end("Field")?;
+        Ok(())
+    }
+}
+
+fn is_abstract(is_abstract: bool) -> Vec<(&'static str, String)> {
+    if is_abstract {
+        vec![("IsAbstract", "true".to_owned())]
+    } else {
+        Vec::new()
+    }
+}
+
+fn array_dimensions(dims: &[u32]) -> String {
+    dims.iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{
+        AccessRestrictionType, ContextOwned, DataTypeId, DecodingOptions, LocalizedText,
+        NamespaceMap, NodeId, NodeSetNamespaceMapper, ObjectId, ObjectTypeId, PermissionType,
+        QualifiedName, ReferenceTypeId, RolePermissionType, Variant,
+    };
+
+    use crate::{
+        NodeBase, NodeSet2Import, NodeSetImport, NodeType, ObjectBuilder, References,
+        VariableBuilder,
+    };
+
+    use super::{write_model_nodeset2, write_nodeset2, NodeSetModel};
+
+    #[test]
+    fn export_round_trip() {
+        // Namespace indices without nodes are left out of the exported namespace table.
+        let namespaces = NamespaceMap::new_full(
+            [
+                ("http://opcfoundation.org/UA/".to_owned(), 0),
+                ("http://first.com".to_owned(), 2),
+                ("http://second.com".to_owned(), 5),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let object_id = NodeId::new(5, "object");
+        let variable_id = NodeId::new(2, 5);
+        let nodes: Vec<NodeType> = vec![
+            ObjectBuilder::new(&object_id, QualifiedName::new(5, "Object"), "Object")
+                .description("An <object>")
+                .role_permissions(vec![RolePermissionType {
+                    role_id: ObjectId::WellKnownRole_Operator.into(),
+                    permissions: PermissionType::Browse | PermissionType::Call,
+                }])
+                .access_restrictions(AccessRestrictionType::EncryptionRequired)
+                .build()
+                .into(),
+            VariableBuilder::new(&variable_id, QualifiedName::new(2, "Var"), "Var")
+                .data_type(DataTypeId::Int32)
+                .value(Variant::from(vec![1i32, 2, 3]))
+                .value_rank(1)
+                .build()
+                .into(),
+        ];
+        let mut references = References::new();
+        references.insert_reference(
+            &object_id,
+            &ObjectTypeId::BaseObjectType.into(),
+            ReferenceTypeId::HasTypeDefinition,
+        );
+        references.insert_reference(&object_id, &variable_id, ReferenceTypeId::HasComponent);
+
+        let ctx = ContextOwned::new_default(namespaces, DecodingOptions::default());
+        let mut out = Vec::new();
+        write_nodeset2(&mut out, nodes.iter(), &references, &ctx.context()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let import = NodeSet2Import::new_str("en", &out, vec![]).unwrap();
+        assert_eq!(
+            import.get_own_namespaces(),
+            vec![
+                "http://first.com".to_owned(),
+                "http://second.com".to_owned()
+            ]
+        );
+        let mut ns = NamespaceMap::new();
+        let mut map = NodeSetNamespaceMapper::new(&mut ns);
+        import.register_namespaces(&mut map);
+        let items: Vec<_> = import.load(&map).collect();
+        assert_eq!(items.len(), 2);
+
+        // Nodes are sorted by node ID.
+        let NodeType::Variable(v) = &items[0].node else {
+            panic!("Unexpected node type");
+        };
+        assert_eq!(v.node_id(), &NodeId::new(1, 5));
+        assert_eq!(v.browse_name(), &QualifiedName::new(1, "Var"));
+        assert_eq!(v.value_rank(), 1);
+        assert_eq!(v.value.value, Some(Variant::from(vec![1i32, 2, 3])));
+        assert_eq!(items[0].references.len(), 1);
+        assert!(!items[0].references[0].is_forward);
+        assert_eq!(items[0].references[0].target_id, NodeId::new(2, "object"));
+
+        let NodeType::Object(o) = &items[1].node else {
+            panic!("Unexpected node type");
+        };
+        assert_eq!(o.node_id(), &NodeId::new(2, "object"));
+        assert_eq!(
+            o.description(),
+            Some(&LocalizedText::new("", "An <object>"))
+        );
+        assert_eq!(
+            o.role_permissions(),
+            Some(
+                &[RolePermissionType {
+                    role_id: ObjectId::WellKnownRole_Operator.into(),
+                    permissions: PermissionType::Browse | PermissionType::Call,
+                }][..]
+            )
+        );
+        assert!(v.role_permissions().is_none());
+        assert_eq!(
+            o.access_restrictions(),
+            Some(AccessRestrictionType::EncryptionRequired)
+        );
+        assert!(v.access_restrictions().is_none());
+        assert_eq!(items[1].references.len(), 2);
+    }
+
+    #[test]
+    fn export_model() {
+        let namespaces = NamespaceMap::new_full(
+            [
+                ("http://opcfoundation.org/UA/".to_owned(), 0),
+                ("http://first.com".to_owned(), 1),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let object_id = NodeId::new(1, "object");
+        let nodes: Vec<NodeType> =
+            vec![
+                ObjectBuilder::new(&object_id, QualifiedName::new(1, "Object"), "Object")
+                    .build()
+                    .into(),
+            ];
+        let model = NodeSetModel {
+            model_uri: "http://first.com".to_owned(),
+            required_models: vec!["http://opcfoundation.org/UA/".to_owned()],
+            version: Some("1.02".to_owned()),
+            ..Default::default()
+        };
+
+        let ctx = ContextOwned::new_default(namespaces, DecodingOptions::default());
+        let mut out = Vec::new();
+        write_model_nodeset2(
+            &mut out,
+            &model,
+            nodes.iter(),
+            &References::new(),
+            &ctx.context(),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let node_set = opcua_xml::load_nodeset2_file(&out)
+            .unwrap()
+            .node_set
+            .unwrap();
+        let models = node_set.models.unwrap().models;
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].model_uri, "http://first.com");
+        assert_eq!(models[0].version.as_deref(), Some("1.02"));
+        assert!(models[0].publication_date.is_none());
+        assert_eq!(models[0].required_model.len(), 1);
+        assert_eq!(
+            models[0].required_model[0].model_uri,
+            "http://opcfoundation.org/UA/"
+        );
+        assert_eq!(node_set.nodes.len(), 1);
+    }
+}
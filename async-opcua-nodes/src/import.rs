@@ -1,4 +1,4 @@
-use opcua_types::NodeId;
+use opcua_types::{DateTime, NodeId};
 
 use super::NodeType;
 
@@ -25,6 +25,30 @@ pub struct ImportedItem {
     pub references: Vec<ImportedReference>,
 }
 
+/// Model defined by a node set, as listed in the model table of NodeSet2 files.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct NodeSetModel {
+    /// URI of the namespace defined by the node set.
+    pub model_uri: String,
+    /// URIs of the models the node set depends on.
+    pub required_models: Vec<String>,
+    /// Version of the model.
+    pub version: Option<String>,
+    /// Time the model was published.
+    pub publication_date: Option<DateTime>,
+}
+
+impl NodeSetModel {
+    /// Create a new node set model for the namespace with the given URI.
+    pub fn new(model_uri: impl Into<String>) -> Self {
+        Self {
+            model_uri: model_uri.into(),
+            ..Default::default()
+        }
+    }
+}
+
 /// Trait for a type that wraps a nodeset import.
 /// Currently this is implemeneted by the [`crate::xml::NodeSet2Import`] type
 /// with the `xml` feature, and by a type in the root of node set imports generated by
@@ -37,6 +61,12 @@ pub trait NodeSetImport {
     /// namespaces it uses, registered in `register_namespaces`
     fn get_own_namespaces(&self) -> Vec<String>;
 
+    /// Get the models defined by this import, if known. This is used to populate
+    /// the namespace metadata of the owned namespaces.
+    fn get_models(&self) -> Vec<NodeSetModel> {
+        Vec::new()
+    }
+
     /// Create an iterator over items imported from the nodeset.
     /// This will usually be lazy.
     fn load<'a>(
@@ -6,6 +6,8 @@
 use bitflags::bitflags;
 
 mod events;
+#[cfg(feature = "xml")]
+mod export;
 mod generic;
 mod import;
 mod references;
@@ -13,13 +15,17 @@ mod type_tree;
 #[cfg(feature = "xml")]
 mod xml;
 #[cfg(feature = "xml")]
+pub use export::{write_model_nodeset2, write_nodeset2};
+#[cfg(feature = "xml")]
 pub use xml::NodeSet2Import;
 
 pub use base::Base;
 pub use data_type::{DataType, DataTypeBuilder};
 pub use events::*;
 pub use generic::new_node_from_attributes;
-pub use import::{ImportedItem, ImportedReference, NodeSetImport, NodeSetNamespaceMapper};
+pub use import::{
+    ImportedItem, ImportedReference, NodeSetImport, NodeSetModel, NodeSetNamespaceMapper,
+};
 pub use method::{Method, MethodBuilder};
 pub use node::{HasNodeId, Node, NodeBase, NodeType};
 pub use object::{Object, ObjectBuilder};
@@ -139,6 +145,24 @@ macro_rules! node_builder_impl {
                 self
             }
 
+            /// Sets the permissions granted to roles on the node
+            pub fn role_permissions(
+                mut self,
+                role_permissions: Vec<opcua_types::RolePermissionType>,
+            ) -> Self {
+                self.node.set_role_permissions(role_permissions);
+                self
+            }
+
+            /// Sets the access restrictions of the node
+            pub fn access_restrictions(
+                mut self,
+                access_restrictions: opcua_types::AccessRestrictionType,
+            ) -> Self {
+                self.node.set_access_restrictions(access_restrictions);
+                self
+            }
+
             /// Adds a reference to the node
             pub fn reference<T>(
                 mut self,
@@ -335,7 +359,7 @@ macro_rules! node_builder_impl_property_of {
 macro_rules! node_base_impl {
     ( $node_struct:ident ) => {
         use crate::NodeType;
-        use opcua_types::{NodeClass, WriteMask};
+        use opcua_types::{AccessRestrictionType, NodeClass, RolePermissionType, WriteMask};
 
         impl From<$node_struct> for NodeType {
             fn from(value: $node_struct) -> Self {
@@ -387,6 +411,22 @@ macro_rules! node_base_impl {
             fn set_user_write_mask(&mut self, user_write_mask: WriteMask) {
                 self.base.set_user_write_mask(user_write_mask)
             }
+
+            fn role_permissions(&self) -> Option<&[RolePermissionType]> {
+                self.base.role_permissions()
+            }
+
+            fn set_role_permissions(&mut self, role_permissions: Vec<RolePermissionType>) {
+                self.base.set_role_permissions(role_permissions)
+            }
+
+            fn access_restrictions(&self) -> Option<AccessRestrictionType> {
+                self.base.access_restrictions()
+            }
+
+            fn set_access_restrictions(&mut self, access_restrictions: AccessRestrictionType) {
+                self.base.set_access_restrictions(access_restrictions)
+            }
         }
     };
 }
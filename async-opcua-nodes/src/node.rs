@@ -3,8 +3,9 @@
 // Copyright (C) 2017-2024 Adam Lock
 
 use opcua_types::{
-    status_code::StatusCode, AttributeId, DataEncoding, DataValue, LocalizedText, NodeClass,
-    NodeId, NumericRange, QualifiedName, TimestampsToReturn, Variant, WriteMask,
+    status_code::StatusCode, AccessRestrictionType, AttributeId, DataEncoding, DataValue,
+    LocalizedText, NodeClass, NodeId, NumericRange, QualifiedName, RolePermissionType,
+    TimestampsToReturn, Variant, WriteMask,
 };
 
 use super::{DataType, Method, Object, ObjectType, ReferenceType, Variable, VariableType, View};
@@ -121,6 +122,38 @@ pub trait NodeBase {
 
     /// Set the user write mask for this node.
     fn set_user_write_mask(&mut self, write_mask: WriteMask);
+
+    /// Get the permissions granted to roles on this node, if set.
+    ///
+    /// The default implementation returns `None`, so the default role
+    /// permissions of the namespace apply.
+    fn role_permissions(&self) -> Option<&[RolePermissionType]> {
+        None
+    }
+
+    /// Set the permissions granted to roles on this node.
+    ///
+    /// The default implementation ignores the permissions, for nodes that
+    /// cannot store them.
+    fn set_role_permissions(&mut self, role_permissions: Vec<RolePermissionType>) {
+        let _ = role_permissions;
+    }
+
+    /// Get the access restrictions of this node, if set.
+    ///
+    /// The default implementation returns `None`, so the default access
+    /// restrictions of the namespace apply.
+    fn access_restrictions(&self) -> Option<AccessRestrictionType> {
+        None
+    }
+
+    /// Set the access restrictions of this node.
+    ///
+    /// The default implementation ignores the restrictions, for nodes that
+    /// cannot store them.
+    fn set_access_restrictions(&mut self, access_restrictions: AccessRestrictionType) {
+        let _ = access_restrictions;
+    }
 }
 
 /// Implemented by each node type's to provide a generic way to set or get attributes, e.g.
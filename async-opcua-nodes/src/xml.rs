@@ -6,9 +6,10 @@ use std::{
 
 use hashbrown::HashMap;
 use opcua_types::{
-    Context, DataTypeDefinition, DataValue, DecodingOptions, EnumDefinition, EnumField, Error,
-    LocalizedText, NodeClass, NodeId, QualifiedName, StructureDefinition, StructureField,
-    StructureType, TypeLoader, TypeLoaderCollection, Variant,
+    AccessRestrictionType, Context, DataTypeDefinition, DataValue, DateTime, DecodingOptions,
+    EnumDefinition, EnumField, Error, LocalizedText, NodeClass, NodeId, PermissionType,
+    QualifiedName, RolePermissionType, StructureDefinition, StructureField, StructureType,
+    TypeLoader, TypeLoaderCollection, Variant,
 };
 use opcua_xml::{
     load_nodeset2_file,
@@ -22,8 +23,8 @@ use regex::Regex;
 use tracing::warn;
 
 use crate::{
-    Base, DataType, EventNotifier, ImportedItem, ImportedReference, Method, NodeSetImport, Object,
-    ObjectType, ReferenceType, Variable, VariableType, View,
+    Base, DataType, EventNotifier, ImportedItem, ImportedReference, Method, NodeBase,
+    NodeSetImport, NodeSetModel, Object, ObjectType, ReferenceType, Variable, VariableType, View,
 };
 
 /// [`NodeSetImport`] implementation for dynamically loading NodeSet2 files at
@@ -276,7 +277,7 @@ impl NodeSet2Import {
         base: &ua_node_set::UANodeBase,
         node_class: NodeClass,
     ) -> Result<Base, Error> {
-        Ok(Base::new_full(
+        let mut res = Base::new_full(
             self.make_node_id(&base.node_id, ctx)?,
             node_class,
             self.make_qualified_name(&base.browse_name, ctx)?,
@@ -285,7 +286,26 @@ impl NodeSet2Import {
             self.select_localized_text(&base.description),
             Some(base.write_mask.0),
             Some(base.user_write_mask.0),
-        ))
+        );
+        if let Some(role_permissions) = &base.role_permissions {
+            let role_permissions = role_permissions
+                .role_permissions
+                .iter()
+                .map(|r| {
+                    Ok(RolePermissionType {
+                        role_id: self.make_node_id(&r.node_id, ctx)?,
+                        permissions: PermissionType::from_bits_truncate(r.permissions as i32),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            res.set_role_permissions(role_permissions);
+        }
+        if base.access_restrictions.0 != 0 {
+            res.set_access_restrictions(AccessRestrictionType::from_bits_truncate(
+                base.access_restrictions.0 as i16,
+            ));
+        }
+        Ok(res)
     }
 
     fn make_references(
@@ -482,6 +502,26 @@ impl NodeSetImport for NodeSet2Import {
             .unwrap_or_default()
     }
 
+    fn get_models(&self) -> Vec<NodeSetModel> {
+        let Some(models) = &self.file.models else {
+            return Vec::new();
+        };
+        models
+            .models
+            .iter()
+            .map(|m| NodeSetModel {
+                model_uri: m.model_uri.clone(),
+                required_models: m
+                    .required_model
+                    .iter()
+                    .map(|r| r.model_uri.clone())
+                    .collect(),
+                version: m.version.clone(),
+                publication_date: m.publication_date.map(DateTime::from),
+            })
+            .collect()
+    }
+
     fn load<'a>(
         &'a self,
         namespaces: &'a opcua_types::NodeSetNamespaceMapper,
@@ -492,6 +532,7 @@ impl NodeSetImport for NodeSet2Import {
             DecodingOptions::default(),
         );
         ctx.set_aliases(&self.aliases);
+        ctx.set_index_map(namespaces.index_map());
         Box::new(self.file.nodes.iter().filter_map(move |raw_node| {
             let r = match raw_node {
                 opcua_xml::schema::ua_node_set::UANode::Object(node) => {
@@ -531,7 +572,7 @@ impl NodeSetImport for NodeSet2Import {
 #[cfg(test)]
 mod tests {
     use opcua_types::{
-        DataTypeId, EUInformation, ExtensionObject, LocalizedText, NamespaceMap,
+        DataTypeId, DateTime, EUInformation, ExtensionObject, LocalizedText, NamespaceMap,
         NodeSetNamespaceMapper, QualifiedName, Variant,
     };
 
@@ -594,6 +635,18 @@ mod tests {
             import.get_own_namespaces(),
             vec!["http://test.com".to_owned()]
         );
+        let models = import.get_models();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].model_uri, "http://test.com");
+        assert_eq!(models[0].version.as_deref(), Some("1.00"));
+        assert_eq!(
+            models[0].publication_date,
+            Some(DateTime::parse_from_rfc3339("2013-11-06T00:00:00Z").unwrap())
+        );
+        assert_eq!(
+            models[0].required_models,
+            vec!["http://opcfoundation.org/UA/".to_owned()]
+        );
         let mut ns = NamespaceMap::new();
         let mut map = NodeSetNamespaceMapper::new(&mut ns);
         import.register_namespaces(&mut map);
@@ -15,10 +15,35 @@ use tracing::{debug, error, info, warn};
 
 use crate::node_manager::{ParsedReadValueId, ParsedWriteValue, RequestContext};
 use opcua_types::{
-    node_id::IntoNodeIdRef, BrowseDirection, DataValue, LocalizedText, NodeClass, NodeId,
-    QualifiedName, ReferenceTypeId, StatusCode, TimestampsToReturn,
+    node_id::IntoNodeIdRef, BrowseDirection, DataValue, IdType, Identifier, LocalizedText,
+    NodeClass, NodeId, QualifiedName, ReferenceTypeId, StatusCode, TimestampsToReturn,
 };
 
+/// How to handle nodes in an imported node set that already exist in the address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeSetConflictPolicy {
+    /// Keep the existing node and skip the node from the node set.
+    #[default]
+    Skip,
+    /// Replace the existing node with the node from the node set. References
+    /// from the node set are added to the existing references of the node.
+    Replace,
+    /// Fail the import without making any changes.
+    Fail,
+}
+
+/// Report of a node set import, returned from [AddressSpace::import_node_set_into].
+#[derive(Debug, Clone, Default)]
+pub struct NodeSetImportReport {
+    /// Nodes that were created.
+    pub created: Vec<NodeId>,
+    /// Existing nodes that were replaced.
+    pub replaced: Vec<NodeId>,
+    /// Nodes that were skipped, either because they already exist, or because
+    /// their namespace is not part of the address space.
+    pub skipped: Vec<NodeId>,
+}
+
 /// Represents an in-memory address space.
 #[derive(Default)]
 pub struct AddressSpace {
@@ -63,6 +88,107 @@ impl AddressSpace {
         info!("Imported {count} nodes");
     }
 
+    /// Import a node set into the namespace with index `namespace`, which must already
+    /// be part of this address space. Unlike [AddressSpace::import_node_set] this can
+    /// be used on a live address space, and does not register the namespace of the node set.
+    ///
+    /// The namespace of the model defined by the node set is mapped to `namespace`.
+    /// Any other namespaces used by the node set must already be registered in `namespaces`.
+    /// Nodes outside the namespaces of this address space are skipped, and nodes that already
+    /// exist are handled according to `policy`.
+    pub fn import_node_set_into<T: NodeSetImport + ?Sized>(
+        &mut self,
+        import: &T,
+        namespaces: &mut NamespaceMap,
+        namespace: u16,
+        policy: NodeSetConflictPolicy,
+    ) -> Result<NodeSetImportReport, StatusCode> {
+        if !self.namespaces.contains_key(&namespace) {
+            warn!("Cannot import node set into namespace {namespace}, it is not part of the address space");
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let model_uri = import
+            .get_models()
+            .into_iter()
+            .next()
+            .map(|m| m.model_uri)
+            .or_else(|| import.get_own_namespaces().into_iter().next());
+
+        // Register the namespaces on a copy of the namespace map to find out
+        // which namespace each index in the node set refers to.
+        let mut node_set_namespaces = namespaces.clone();
+        let mut map = NodeSetNamespaceMapper::new(&mut node_set_namespaces);
+        import.register_namespaces(&mut map);
+        let index_map = map.index_map().clone();
+        let mut indexes = Vec::with_capacity(index_map.len());
+        for (index_in_node_set, index) in index_map {
+            let Some((uri, _)) = node_set_namespaces
+                .known_namespaces()
+                .iter()
+                .find(|(_, i)| **i == index)
+            else {
+                continue;
+            };
+            if model_uri.as_ref() == Some(uri) {
+                indexes.push((index_in_node_set, namespace));
+            } else if let Some(index) = namespaces.get_index(uri) {
+                indexes.push((index_in_node_set, index));
+            } else {
+                warn!("Cannot import node set, namespace {uri} is not registered");
+                return Err(StatusCode::BadInvalidArgument);
+            }
+        }
+
+        let mut map = NodeSetNamespaceMapper::new(namespaces);
+        for (index_in_node_set, index) in indexes {
+            map.map_index(index_in_node_set, index);
+        }
+        let items: Vec<_> = import.load(&map).collect();
+
+        let is_conflict = |node_id: &NodeId| {
+            self.namespaces.contains_key(&node_id.namespace) && self.node_exists(node_id)
+        };
+        if policy == NodeSetConflictPolicy::Fail {
+            if let Some(item) = items.iter().find(|i| is_conflict(i.node.node_id())) {
+                warn!(
+                    "Cannot import node set, node {} already exists",
+                    item.node.node_id()
+                );
+                return Err(StatusCode::BadNodeIdExists);
+            }
+        }
+
+        let mut report = NodeSetImportReport::default();
+        for item in items {
+            let node_id = item.node.node_id().clone();
+            if !self.namespaces.contains_key(&node_id.namespace) {
+                report.skipped.push(node_id);
+                continue;
+            }
+            let exists = self.node_exists(&node_id);
+            if exists && policy == NodeSetConflictPolicy::Skip {
+                report.skipped.push(node_id);
+                continue;
+            }
+            self.node_map.insert(node_id.clone(), item.node);
+            for r in item.references {
+                self.references.import_reference(node_id.clone(), r);
+            }
+            if exists {
+                report.replaced.push(node_id);
+            } else {
+                report.created.push(node_id);
+            }
+        }
+        info!(
+            "Imported node set into namespace {namespace}, created {}, replaced {}, skipped {} nodes",
+            report.created.len(),
+            report.replaced.len(),
+            report.skipped.len()
+        );
+        Ok(report)
+    }
+
     /// Load types from this address space into the given type tree.
     pub fn load_into_type_tree(&self, type_tree: &mut DefaultTypeTree) {
         let mut found_ids = VecDeque::new();
@@ -349,6 +475,24 @@ impl AddressSpace {
         &self.namespaces
     }
 
+    /// Get the types of node ID used by nodes in the namespace with index `namespace`.
+    pub fn node_id_types(&self, namespace: u16) -> Vec<IdType> {
+        let mut types = Vec::new();
+        for id in self.node_map.keys().filter(|id| id.namespace == namespace) {
+            let id_type = match id.identifier {
+                Identifier::Numeric(_) => IdType::Numeric,
+                Identifier::String(_) => IdType::String,
+                Identifier::Guid(_) => IdType::Guid,
+                Identifier::ByteString(_) => IdType::Opaque,
+            };
+            if !types.contains(&id_type) {
+                types.push(id_type);
+            }
+        }
+        types.sort_by_key(|t| *t as u8);
+        types
+    }
+
     /// Find node by something that can be turned into a node id and return a reference to it.
     pub fn find<'b>(&self, node_id: impl IntoNodeIdRef<'b>) -> Option<&NodeType> {
         self.find_node(node_id)
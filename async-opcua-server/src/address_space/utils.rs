@@ -1,7 +1,8 @@
 use crate::node_manager::{ParsedReadValueId, ParsedWriteValue, RequestContext, ServerContext};
 use opcua_nodes::TypeTree;
 use opcua_types::{
-    AttributeId, DataEncoding, DataTypeId, DataValue, DateTime, NumericRange, StatusCode,
+    AccessRestrictionType, AttributeId, DataEncoding, DataTypeId, DataValue, DateTime,
+    ExtensionObject, MessageSecurityMode, NumericRange, PermissionType, StatusCode,
     TimestampsToReturn, Variant, WriteMask,
 };
 use tracing::debug;
@@ -11,6 +12,7 @@ use super::{AccessLevel, AddressSpace, HasNodeId, NodeType, Variable};
 /// Validate that the user given by `context` can read the value
 /// of the given node.
 pub fn is_readable(context: &RequestContext, node: &NodeType) -> Result<(), StatusCode> {
+    validate_access_restrictions(context, node, false)?;
     if !user_access_level(context, node).contains(AccessLevel::CURRENT_READ) {
         Err(StatusCode::BadUserAccessDenied)
     } else {
@@ -25,6 +27,7 @@ pub fn is_writable(
     node: &NodeType,
     attribute_id: AttributeId,
 ) -> Result<(), StatusCode> {
+    validate_access_restrictions(context, node, false)?;
     if let (NodeType::Variable(_), AttributeId::Value) = (node, attribute_id) {
         if !user_access_level(context, node).contains(AccessLevel::CURRENT_WRITE) {
             return Err(StatusCode::BadUserAccessDenied);
@@ -32,6 +35,13 @@ pub fn is_writable(
 
         Ok(())
     } else {
+        let permission = match attribute_id {
+            AttributeId::RolePermissions => PermissionType::WriteRolePermissions,
+            AttributeId::Historizing => PermissionType::WriteHistorizing,
+            _ => PermissionType::WriteAttribute,
+        };
+        has_permission(context, node, permission)?;
+
         let mask_value = match attribute_id {
             // The default address space does not support modifying node class or node id,
             // Custom node managers are allowed to.
@@ -77,13 +87,117 @@ pub fn user_access_level(context: &RequestContext, node: &NodeType) -> AccessLev
     } else {
         AccessLevel::CURRENT_READ
     };
-    context.authenticator.effective_user_access_level(
+    effective_user_access_level(context, node, user_access_level)
+}
+
+fn effective_user_access_level(
+    context: &RequestContext,
+    node: &NodeType,
+    user_access_level: AccessLevel,
+) -> AccessLevel {
+    let access_level = context.authenticator.effective_user_access_level(
         &context.token,
         user_access_level,
         node.node_id(),
+    );
+    // Remove any access not granted by the roles of the user.
+    let permissions = user_permissions(context, node);
+    let mut denied = AccessLevel::empty();
+    if !permissions.contains(PermissionType::Read) {
+        denied |= AccessLevel::CURRENT_READ;
+    }
+    if !permissions.contains(PermissionType::Write) {
+        denied |=
+            AccessLevel::CURRENT_WRITE | AccessLevel::STATUS_WRITE | AccessLevel::TIMESTAMP_WRITE;
+    }
+    if !permissions.contains(PermissionType::ReadHistory) {
+        denied |= AccessLevel::HISTORY_READ;
+    }
+    if !permissions.intersects(
+        PermissionType::InsertHistory
+            | PermissionType::ModifyHistory
+            | PermissionType::DeleteHistory,
+    ) {
+        denied |= AccessLevel::HISTORY_WRITE;
+    }
+    access_level - denied
+}
+
+/// Get the permissions granted to the user given by `context` on `node`
+/// by the `RolePermissions` of the node, or the default role permissions
+/// of its namespace.
+///
+/// If the session does not meet the `AccessRestrictions` of the node, it
+/// is at most allowed to browse the node.
+pub fn user_permissions(context: &RequestContext, node: &NodeType) -> PermissionType {
+    let permissions = context.info.roles.permissions(
+        &context.roles,
+        node.as_node().role_permissions(),
+        node.node_id().namespace,
+    );
+    if validate_access_restrictions(context, node, false).is_ok() {
+        permissions
+    } else if validate_access_restrictions(context, node, true).is_ok() {
+        permissions & PermissionType::Browse
+    } else {
+        PermissionType::empty()
+    }
+}
+
+/// Validate that the user given by `context` has `permission` on `node`.
+pub fn has_permission(
+    context: &RequestContext,
+    node: &NodeType,
+    permission: PermissionType,
+) -> Result<(), StatusCode> {
+    validate_access_restrictions(context, node, permission == PermissionType::Browse)?;
+    if user_permissions(context, node).contains(permission) {
+        Ok(())
+    } else {
+        Err(StatusCode::BadUserAccessDenied)
+    }
+}
+
+/// Get the access restrictions of `node`, given by its `AccessRestrictions`
+/// attribute, or the default access restrictions of its namespace.
+pub fn access_restrictions(context: &RequestContext, node: &NodeType) -> AccessRestrictionType {
+    context.info.roles.access_restrictions(
+        node.as_node().access_restrictions(),
+        node.node_id().namespace,
     )
 }
 
+/// Validate that the secure channel of the session given by `context` meets
+/// the access restrictions of `node`. If `browse` is true, the restrictions
+/// only apply if the node has `ApplyRestrictionsToBrowse` set.
+///
+/// `SessionRequired` is always met, since node managers are only called
+/// from within a session.
+pub fn validate_access_restrictions(
+    context: &RequestContext,
+    node: &NodeType,
+    browse: bool,
+) -> Result<(), StatusCode> {
+    let restrictions = access_restrictions(context, node);
+    if browse && !restrictions.contains(AccessRestrictionType::ApplyRestrictionsToBrowse) {
+        return Ok(());
+    }
+    let met = match context.security_mode {
+        MessageSecurityMode::SignAndEncrypt => true,
+        MessageSecurityMode::Sign => {
+            !restrictions.contains(AccessRestrictionType::EncryptionRequired)
+        }
+        _ => !restrictions.intersects(
+            AccessRestrictionType::SigningRequired | AccessRestrictionType::EncryptionRequired,
+        ),
+    };
+    if met {
+        Ok(())
+    } else {
+        Err(StatusCode::BadSecurityModeInsufficient)
+    }
+}
+
 /// Validate that the user given by `context` is allowed to read
 /// the value of `node`.
 pub fn validate_node_read(
@@ -91,7 +205,14 @@ pub fn validate_node_read(
     context: &RequestContext,
     node_to_read: &ParsedReadValueId,
 ) -> Result<(), StatusCode> {
-    is_readable(context, node)?;
+    // Attributes other than the value are visible to anyone allowed to browse the node.
+    match node_to_read.attribute_id {
+        AttributeId::Value => is_readable(context, node)?,
+        AttributeId::RolePermissions => {
+            has_permission(context, node, PermissionType::ReadRolePermissions)?
+        }
+        _ => has_permission(context, node, PermissionType::Browse)?,
+    }
 
     if node_to_read.attribute_id != AttributeId::Value
         && node_to_read.index_range != NumericRange::None
@@ -204,6 +325,23 @@ pub fn read_node_value(
 ) -> DataValue {
     let mut result_value = DataValue::null();
 
+    if node_to_read.attribute_id == AttributeId::UserRolePermissions {
+        let Some(permissions) = context.info.roles.user_role_permissions(
+            &context.roles,
+            node.as_node().role_permissions(),
+            node.node_id().namespace,
+        ) else {
+            result_value.status = Some(StatusCode::BadAttributeIdInvalid);
+            return result_value;
+        };
+        return DataValue::value_only(
+            permissions
+                .into_iter()
+                .map(ExtensionObject::from_message)
+                .collect::<Vec<_>>(),
+        );
+    }
+
     let Some(attribute) = node.as_node().get_attribute_max_age(
         timestamps_to_return,
         node_to_read.attribute_id,
@@ -219,11 +357,7 @@ pub fn read_node_value(
         match attribute.value {
             Some(Variant::Byte(val)) => {
                 let access_level = AccessLevel::from_bits_truncate(val);
-                let access_level = context.authenticator.effective_user_access_level(
-                    &context.token,
-                    access_level,
-                    node.node_id(),
-                );
+                let access_level = effective_user_access_level(context, node, access_level);
                 Some(Variant::from(access_level.bits()))
             }
             Some(v) => Some(v),
@@ -238,7 +372,8 @@ pub fn read_node_value(
             Some(Variant::Boolean(val)) => Some(Variant::from(
                 val && context
                     .authenticator
-                    .is_user_executable(&context.token, node.node_id()),
+                    .is_user_executable(&context.token, node.node_id())
+                    && user_permissions(context, node).contains(PermissionType::Call),
             )),
             r => r,
         }
@@ -0,0 +1,167 @@
+use opcua_nodes::{BaseEventType, Event, EventField};
+use opcua_types::{
+    AttributeId, DateTime, LocalizedText, NodeId, NumericRange, QualifiedName, UAString, Variant,
+};
+
+// The event derive macros refer to the types and nodes crates through `opcua`.
+mod opcua {
+    pub(super) use opcua_nodes as nodes;
+    pub(super) use opcua_types as types;
+}
+
+/// The fields of a `TwoStateVariableType` reported in condition events.
+#[derive(Debug, Default, opcua_nodes::EventField)]
+pub(super) struct TwoStateVariable {
+    pub value: LocalizedText,
+    pub id: bool,
+    pub transition_time: DateTime,
+    pub true_state: LocalizedText,
+    pub false_state: LocalizedText,
+}
+
+impl TwoStateVariable {
+    pub(super) fn new(
+        id: bool,
+        true_state: &str,
+        false_state: &str,
+        transition_time: DateTime,
+    ) -> Self {
+        Self {
+            value: if id { true_state } else { false_state }.into(),
+            id,
+            transition_time,
+            true_state: true_state.into(),
+            false_state: false_state.into(),
+        }
+    }
+}
+
+/// The fields of a `FiniteStateVariableType` reported in condition events.
+#[derive(Debug, Default, opcua_nodes::EventField)]
+pub(super) struct FiniteStateVariable {
+    pub value: LocalizedText,
+    pub id: NodeId,
+}
+
+/// The fields of an `ExclusiveLimitStateMachineType` reported in condition events.
+#[derive(Debug, Default, opcua_nodes::EventField)]
+pub(super) struct ExclusiveLimitStateMachine {
+    pub current_state: FiniteStateVariable,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2782")]
+pub(super) struct ConditionType {
+    pub base: BaseEventType,
+    pub condition_name: UAString,
+    pub branch_id: NodeId,
+    pub retain: bool,
+    pub enabled_state: TwoStateVariable,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2881")]
+pub(super) struct AcknowledgeableConditionType {
+    pub base: ConditionType,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2915")]
+pub(super) struct AlarmConditionType {
+    pub base: AcknowledgeableConditionType,
+    pub active_state: TwoStateVariable,
+    pub input_node: NodeId,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2955")]
+pub(super) struct LimitAlarmType {
+    pub base: AlarmConditionType,
+    pub high_high_limit: Option<f64>,
+    pub high_limit: Option<f64>,
+    pub low_limit: Option<f64>,
+    pub low_low_limit: Option<f64>,
+    pub high_high_deadband: Option<f64>,
+    pub high_deadband: Option<f64>,
+    pub low_deadband: Option<f64>,
+    pub low_low_deadband: Option<f64>,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=9341")]
+pub(super) struct ExclusiveLimitAlarmType {
+    pub base: LimitAlarmType,
+    pub limit_state: ExclusiveLimitStateMachine,
+}
+
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=9906")]
+pub(super) struct NonExclusiveLimitAlarmType {
+    pub base: LimitAlarmType,
+    pub high_high_state: Option<TwoStateVariable>,
+    pub high_state: Option<TwoStateVariable>,
+    pub low_state: Option<TwoStateVariable>,
+    pub low_low_state: Option<TwoStateVariable>,
+}
+
+/// A condition event, with the `ConditionId` of the condition it was raised for.
+///
+/// The `ConditionId` is selected with the `NodeId` attribute and an empty
+/// browse path, so it is not a regular field of the event.
+#[derive(Debug)]
+pub(super) struct ConditionEvent<T> {
+    pub condition_id: NodeId,
+    pub event: T,
+}
+
+impl<T: Event> EventField for ConditionEvent<T> {
+    fn get_value(
+        &self,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        if browse_path.is_empty() && attribute_id == AttributeId::NodeId {
+            return self.condition_id.clone().into();
+        }
+        self.event.get_value(attribute_id, index_range, browse_path)
+    }
+}
+
+impl<T: Event> Event for ConditionEvent<T> {
+    fn get_field(
+        &self,
+        type_definition_id: &NodeId,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        if browse_path.is_empty() && attribute_id == AttributeId::NodeId {
+            return self.condition_id.clone().into();
+        }
+        self.event
+            .get_field(type_definition_id, attribute_id, index_range, browse_path)
+    }
+
+    fn time(&self) -> &DateTime {
+        self.event.time()
+    }
+
+    fn event_type_id(&self) -> &NodeId {
+        self.event.event_type_id()
+    }
+}
+
+#[cfg(feature = "generated-address-space")]
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2787")]
+pub(crate) struct RefreshStartEventType {
+    pub base: BaseEventType,
+}
+
+#[cfg(feature = "generated-address-space")]
+#[derive(Debug, opcua_nodes::Event)]
+#[opcua(identifier = "i=2788")]
+pub(crate) struct RefreshEndEventType {
+    pub base: BaseEventType,
+}
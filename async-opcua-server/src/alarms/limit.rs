@@ -0,0 +1,599 @@
+use std::sync::Arc;
+
+use hashbrown::{HashMap, HashSet};
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_crypto::random;
+use opcua_nodes::{Event, NamespaceMap, ObjectBuilder, VariableBuilder};
+use opcua_types::{
+    AttributeId, DataEncoding, DataTypeId, DateTime, NodeId, NumericRange, ObjectId, ObjectTypeId,
+    QualifiedName, ReferenceTypeId, StatusCode, TimestampsToReturn, UAString, VariableTypeId,
+    Variant,
+};
+
+use crate::{
+    address_space::{AccessLevel, AddressSpace},
+    SubscriptionCache,
+};
+
+use super::events::{
+    ConditionEvent, ExclusiveLimitAlarmType, ExclusiveLimitStateMachine, FiniteStateVariable,
+    LimitAlarmType, NonExclusiveLimitAlarmType, TwoStateVariable,
+};
+
+/// One of the four limits of a limit alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlarmLimit {
+    /// The `HighHigh` limit, exceeded when the value is above it.
+    HighHigh,
+    /// The `High` limit, exceeded when the value is above it.
+    High,
+    /// The `Low` limit, exceeded when the value is below it.
+    Low,
+    /// The `LowLow` limit, exceeded when the value is below it.
+    LowLow,
+}
+
+impl AlarmLimit {
+    /// All limits, in the order of precedence when reporting the state of
+    /// an exclusive alarm.
+    const ALL: [AlarmLimit; 4] = [Self::HighHigh, Self::LowLow, Self::High, Self::Low];
+
+    fn index(self) -> usize {
+        match self {
+            AlarmLimit::HighHigh => 0,
+            AlarmLimit::High => 1,
+            AlarmLimit::Low => 2,
+            AlarmLimit::LowLow => 3,
+        }
+    }
+
+    /// The name of the limit, as used in the names of the limit properties
+    /// and states of limit alarms.
+    pub fn name(self) -> &'static str {
+        match self {
+            AlarmLimit::HighHigh => "HighHigh",
+            AlarmLimit::High => "High",
+            AlarmLimit::Low => "Low",
+            AlarmLimit::LowLow => "LowLow",
+        }
+    }
+
+    fn is_high(self) -> bool {
+        matches!(self, AlarmLimit::HighHigh | AlarmLimit::High)
+    }
+
+    fn state_id(self) -> ObjectId {
+        match self {
+            AlarmLimit::HighHigh => ObjectId::ExclusiveLimitStateMachineType_HighHigh,
+            AlarmLimit::High => ObjectId::ExclusiveLimitStateMachineType_High,
+            AlarmLimit::Low => ObjectId::ExclusiveLimitStateMachineType_Low,
+            AlarmLimit::LowLow => ObjectId::ExclusiveLimitStateMachineType_LowLow,
+        }
+    }
+
+    /// Check whether `value` exceeds the limit. A limit that is already exceeded
+    /// stays exceeded until the value is back within the limit by more than `deadband`.
+    fn is_exceeded(self, value: f64, limit: f64, deadband: f64, was_exceeded: bool) -> bool {
+        let deadband = if was_exceeded { deadband.max(0.0) } else { 0.0 };
+        if self.is_high() {
+            value > limit - deadband
+        } else {
+            value < limit + deadband
+        }
+    }
+}
+
+/// The type of a limit alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitAlarmKind {
+    /// An `ExclusiveLimitAlarmType`, which is in at most one limit state at a time,
+    /// the most severe limit exceeded.
+    Exclusive,
+    /// A `NonExclusiveLimitAlarmType`, with a separate state for each limit.
+    NonExclusive,
+}
+
+#[derive(Debug, Clone)]
+struct LimitConfig {
+    limit: f64,
+    deadband: f64,
+    severity: u16,
+    limit_node: NodeId,
+    deadband_node: NodeId,
+}
+
+/// An alarm raised when the value of a variable exceeds one or more limits.
+///
+/// Once added to [`LimitAlarms`], the alarm is an object in the address space with
+/// a property for each limit and deadband, for example `HighLimit` and `HighDeadband`.
+/// The alarm is evaluated whenever the input variable or one of these properties
+/// change, and raises a condition event on its source node when its state changes.
+///
+/// The deadband of a limit gives hysteresis: an exceeded limit is only cleared once
+/// the value is back within the limit by more than the deadband.
+///
+/// Alarms created this way do not support acknowledgement, and are retained while active.
+#[derive(Debug)]
+pub struct LimitAlarm {
+    node_id: NodeId,
+    kind: LimitAlarmKind,
+    name: String,
+    input_node: NodeId,
+    source_node: NodeId,
+    input_property: NodeId,
+    limits: [Option<LimitConfig>; 4],
+    exceeded: [bool; 4],
+    severity: u16,
+    transition_time: DateTime,
+}
+
+impl LimitAlarm {
+    /// Create a new limit alarm with ID `node_id`, evaluating the value of the
+    /// variable `input_node`. Events are raised on `source_node`.
+    pub fn new(
+        node_id: impl Into<NodeId>,
+        kind: LimitAlarmKind,
+        name: &str,
+        input_node: impl Into<NodeId>,
+        source_node: impl Into<NodeId>,
+    ) -> Self {
+        Self {
+            node_id: node_id.into(),
+            kind,
+            name: name.to_owned(),
+            input_node: input_node.into(),
+            source_node: source_node.into(),
+            input_property: NodeId::null(),
+            limits: Default::default(),
+            exceeded: [false; 4],
+            severity: 1,
+            transition_time: DateTime::null(),
+        }
+    }
+
+    /// Set the value of `limit`, and the severity of the events raised
+    /// while it is exceeded.
+    pub fn limit(mut self, limit: AlarmLimit, value: f64, severity: u16) -> Self {
+        let deadband = self.limits[limit.index()]
+            .as_ref()
+            .map(|l| l.deadband)
+            .unwrap_or_default();
+        self.limits[limit.index()] = Some(LimitConfig {
+            limit: value,
+            deadband,
+            severity: severity.clamp(1, 1000),
+            limit_node: NodeId::null(),
+            deadband_node: NodeId::null(),
+        });
+        self
+    }
+
+    /// Set the deadband of `limit`. The limit must be set first.
+    pub fn deadband(mut self, limit: AlarmLimit, deadband: f64) -> Self {
+        if let Some(l) = &mut self.limits[limit.index()] {
+            l.deadband = deadband;
+        }
+        self
+    }
+
+    /// Get the ID of the alarm.
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    /// Get the type of the alarm.
+    pub fn kind(&self) -> LimitAlarmKind {
+        self.kind
+    }
+
+    /// Get whether any limit is exceeded.
+    fn is_active(&self) -> bool {
+        self.exceeded.iter().any(|e| *e)
+    }
+
+    /// Get the limits currently exceeded, most severe first. An exclusive alarm
+    /// is in the state of the first limit.
+    fn exceeded_limits(&self) -> Vec<AlarmLimit> {
+        AlarmLimit::ALL
+            .into_iter()
+            .filter(|l| self.exceeded[l.index()])
+            .collect()
+    }
+
+    fn configured(&self) -> impl Iterator<Item = (AlarmLimit, &LimitConfig)> {
+        AlarmLimit::ALL
+            .into_iter()
+            .filter_map(|l| self.limits[l.index()].as_ref().map(|c| (l, c)))
+    }
+
+    /// Create the alarm object and its properties in `address_space`.
+    fn insert(&mut self, address_space: &mut AddressSpace) {
+        let namespace = self.node_id.namespace;
+        let type_id = match self.kind {
+            LimitAlarmKind::Exclusive => ObjectTypeId::ExclusiveLimitAlarmType,
+            LimitAlarmKind::NonExclusive => ObjectTypeId::NonExclusiveLimitAlarmType,
+        };
+        ObjectBuilder::new(
+            &self.node_id,
+            QualifiedName::new(namespace, self.name.as_str()),
+            self.name.as_str(),
+        )
+        .has_type_definition(type_id)
+        .reference(
+            self.source_node.clone(),
+            ReferenceTypeId::HasCondition,
+            opcua_nodes::ReferenceDirection::Inverse,
+        )
+        .insert(address_space);
+
+        let property = |address_space: &mut AddressSpace, name: &str, value: Variant, writable| {
+            let mut id = NodeId::next_numeric(namespace);
+            while address_space.node_exists(&id) {
+                id = NodeId::next_numeric(namespace);
+            }
+            let access_level = if writable {
+                AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE
+            } else {
+                AccessLevel::CURRENT_READ
+            };
+            VariableBuilder::new(&id, QualifiedName::new(0, name), name)
+                .property_of(self.node_id.clone())
+                .has_type_definition(VariableTypeId::PropertyType)
+                .data_type(match &value {
+                    Variant::NodeId(_) => DataTypeId::NodeId,
+                    _ => DataTypeId::Double,
+                })
+                .value(value)
+                .access_level(access_level)
+                .user_access_level(access_level)
+                .insert(address_space);
+            id
+        };
+
+        self.input_property = property(
+            address_space,
+            "InputNode",
+            self.input_node.clone().into(),
+            false,
+        );
+        for limit in AlarmLimit::ALL {
+            let Some(config) = &self.limits[limit.index()] else {
+                continue;
+            };
+            let (value, deadband) = (config.limit, config.deadband);
+            let limit_node = property(
+                address_space,
+                &format!("{}Limit", limit.name()),
+                value.into(),
+                true,
+            );
+            let deadband_node = property(
+                address_space,
+                &format!("{}Deadband", limit.name()),
+                deadband.into(),
+                true,
+            );
+            if let Some(config) = &mut self.limits[limit.index()] {
+                config.limit_node = limit_node;
+                config.deadband_node = deadband_node;
+            }
+        }
+    }
+
+    /// Evaluate the alarm against the current value of the input node and limit
+    /// properties, returning whether the state of the alarm changed.
+    fn evaluate(&mut self, address_space: &AddressSpace) -> bool {
+        let read = |id: &NodeId| {
+            let value = address_space.find(id)?.as_node().get_attribute(
+                TimestampsToReturn::Neither,
+                AttributeId::Value,
+                &NumericRange::None,
+                &DataEncoding::Binary,
+            )?;
+            if !value.status().is_good() {
+                return None;
+            }
+            value.value?.as_f64().filter(|v| !v.is_nan())
+        };
+
+        for config in self.limits.iter_mut().flatten() {
+            if let Some(limit) = read(&config.limit_node) {
+                config.limit = limit;
+            }
+            if let Some(deadband) = read(&config.deadband_node) {
+                config.deadband = deadband;
+            }
+        }
+        // If the input does not have a good numeric value the alarm keeps its state.
+        let Some(value) = read(&self.input_node) else {
+            return false;
+        };
+
+        let mut exceeded = [false; 4];
+        for (limit, config) in self.configured() {
+            exceeded[limit.index()] = limit.is_exceeded(
+                value,
+                config.limit,
+                config.deadband,
+                self.exceeded[limit.index()],
+            );
+        }
+        let previous = self.exceeded_limits();
+        self.exceeded = exceeded;
+        let current = self.exceeded_limits();
+        let changed = match self.kind {
+            LimitAlarmKind::Exclusive => previous.first() != current.first(),
+            LimitAlarmKind::NonExclusive => previous != current,
+        };
+        if changed {
+            self.transition_time = DateTime::now();
+            // When the alarm returns to normal, events keep the severity of the last limit.
+            if let Some(limit) = current.first() {
+                if let Some(config) = &self.limits[limit.index()] {
+                    self.severity = config.severity;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Create the fields of the event for the current state of the alarm,
+    /// common to both kinds of limit alarm.
+    fn limit_alarm_event(&self, address_space: &AddressSpace, type_id: NodeId) -> LimitAlarmType {
+        let active = self.is_active();
+        let message = match self.exceeded_limits().first() {
+            Some(limit) => format!("{} limit of {} exceeded", limit.name(), self.name),
+            None => format!("{} returned to normal", self.name),
+        };
+        let mut event = LimitAlarmType::new_event_now(
+            type_id,
+            random::byte_string(16),
+            message,
+            &NamespaceMap::new(),
+        );
+        let alarm = &mut event.base;
+        let condition = &mut alarm.base.base;
+        let source_name = address_space
+            .find(&self.source_node)
+            .map(|n| UAString::from(n.as_node().display_name().text.as_ref()))
+            .unwrap_or_default();
+        condition.base = std::mem::take(&mut condition.base)
+            .set_source_node(self.source_node.clone())
+            .set_source_name(source_name)
+            .set_severity(self.severity);
+        condition.condition_name = self.name.as_str().into();
+        condition.retain = active;
+        condition.enabled_state =
+            TwoStateVariable::new(true, "Enabled", "Disabled", DateTime::null());
+        alarm.active_state =
+            TwoStateVariable::new(active, "Active", "Inactive", self.transition_time);
+        alarm.input_node = self.input_node.clone();
+
+        let limit = |l: AlarmLimit| self.limits[l.index()].as_ref().map(|c| c.limit);
+        let deadband = |l: AlarmLimit| self.limits[l.index()].as_ref().map(|c| c.deadband);
+        event.high_high_limit = limit(AlarmLimit::HighHigh);
+        event.high_limit = limit(AlarmLimit::High);
+        event.low_limit = limit(AlarmLimit::Low);
+        event.low_low_limit = limit(AlarmLimit::LowLow);
+        event.high_high_deadband = deadband(AlarmLimit::HighHigh);
+        event.high_deadband = deadband(AlarmLimit::High);
+        event.low_deadband = deadband(AlarmLimit::Low);
+        event.low_low_deadband = deadband(AlarmLimit::LowLow);
+        event
+    }
+
+    /// Create a condition event for the current state of the alarm.
+    pub(super) fn event(&self, address_space: &AddressSpace) -> Arc<dyn Event + Send + Sync> {
+        match self.kind {
+            LimitAlarmKind::Exclusive => {
+                let state = match self.exceeded_limits().first() {
+                    Some(limit) => FiniteStateVariable {
+                        value: limit.name().into(),
+                        id: limit.state_id().into(),
+                    },
+                    None => FiniteStateVariable::default(),
+                };
+                Arc::new(ConditionEvent {
+                    condition_id: self.node_id.clone(),
+                    event: ExclusiveLimitAlarmType {
+                        base: self.limit_alarm_event(
+                            address_space,
+                            ExclusiveLimitAlarmType::event_type_id(),
+                        ),
+                        limit_state: ExclusiveLimitStateMachine {
+                            current_state: state,
+                        },
+                    },
+                })
+            }
+            LimitAlarmKind::NonExclusive => {
+                let state = |l: AlarmLimit| {
+                    self.limits[l.index()].as_ref().map(|_| {
+                        TwoStateVariable::new(
+                            self.exceeded[l.index()],
+                            "Active",
+                            "Inactive",
+                            self.transition_time,
+                        )
+                    })
+                };
+                Arc::new(ConditionEvent {
+                    condition_id: self.node_id.clone(),
+                    event: NonExclusiveLimitAlarmType {
+                        base: self.limit_alarm_event(
+                            address_space,
+                            NonExclusiveLimitAlarmType::event_type_id(),
+                        ),
+                        high_high_state: state(AlarmLimit::HighHigh),
+                        high_state: state(AlarmLimit::High),
+                        low_state: state(AlarmLimit::Low),
+                        low_low_state: state(AlarmLimit::LowLow),
+                    },
+                })
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct LimitAlarmsInner {
+    alarms: HashMap<NodeId, LimitAlarm>,
+    // Alarms evaluated when the value of a node changes, by node ID.
+    bindings: HashMap<NodeId, Vec<NodeId>>,
+}
+
+/// A collection of [`LimitAlarm`]s, evaluated when the nodes they are bound to change.
+///
+/// The [`SimpleNodeManager`](crate::node_manager::memory::SimpleNodeManager) evaluates
+/// its limit alarms on writes, and when values are set with
+/// [`InMemoryNodeManager::set_values`](crate::node_manager::memory::InMemoryNodeManager::set_values).
+/// Other node managers can call [`LimitAlarms::evaluate`] when values change.
+#[derive(Default)]
+pub struct LimitAlarms {
+    inner: RwLock<LimitAlarmsInner>,
+}
+
+impl LimitAlarms {
+    /// Create a new, empty collection of limit alarms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `alarm`, creating the alarm object and its properties in `address_space`.
+    ///
+    /// The initial state of the alarm is evaluated without raising an event, but
+    /// an alarm that is initially active is added to the retained conditions of
+    /// `subscriptions`. The source node of the alarm should be an event notifier
+    /// for clients to subscribe to its events.
+    pub fn add(
+        &self,
+        address_space: &mut AddressSpace,
+        subscriptions: &SubscriptionCache,
+        mut alarm: LimitAlarm,
+    ) -> Result<(), StatusCode> {
+        if !address_space
+            .namespaces()
+            .contains_key(&alarm.node_id.namespace)
+        {
+            return Err(StatusCode::BadNodeIdInvalid);
+        }
+        let mut inner = trace_write_lock!(self.inner);
+        if address_space.node_exists(&alarm.node_id) || inner.alarms.contains_key(&alarm.node_id) {
+            return Err(StatusCode::BadNodeIdExists);
+        }
+
+        alarm.insert(address_space);
+        alarm.evaluate(address_space);
+        if alarm.is_active() {
+            subscriptions.conditions().set(
+                alarm.node_id.clone(),
+                alarm.source_node.clone(),
+                alarm.event(address_space),
+            );
+        }
+
+        let bound = std::iter::once(alarm.input_node.clone()).chain(
+            alarm
+                .limits
+                .iter()
+                .flatten()
+                .flat_map(|c| [c.limit_node.clone(), c.deadband_node.clone()]),
+        );
+        for node in bound.collect::<Vec<_>>() {
+            inner
+                .bindings
+                .entry(node)
+                .or_default()
+                .push(alarm.node_id.clone());
+        }
+        inner.alarms.insert(alarm.node_id.clone(), alarm);
+        Ok(())
+    }
+
+    /// Remove the alarm with ID `alarm_id`, deleting it from `address_space`
+    /// and from the retained conditions of `subscriptions`.
+    pub fn remove(
+        &self,
+        address_space: &mut AddressSpace,
+        subscriptions: &SubscriptionCache,
+        alarm_id: &NodeId,
+    ) -> bool {
+        let mut inner = trace_write_lock!(self.inner);
+        let Some(alarm) = inner.alarms.remove(alarm_id) else {
+            return false;
+        };
+        subscriptions.conditions().remove(alarm_id);
+        inner.bindings.retain(|_, alarms| {
+            alarms.retain(|a| a != alarm_id);
+            !alarms.is_empty()
+        });
+        for config in alarm.limits.iter().flatten() {
+            address_space.delete(&config.limit_node, true);
+            address_space.delete(&config.deadband_node, true);
+        }
+        address_space.delete(&alarm.input_property, true);
+        address_space.delete(alarm_id, true);
+        true
+    }
+
+    /// Get the limits currently exceeded by the alarm with ID `alarm_id`,
+    /// most severe first.
+    pub fn exceeded_limits(&self, alarm_id: &NodeId) -> Option<Vec<AlarmLimit>> {
+        trace_read_lock!(self.inner)
+            .alarms
+            .get(alarm_id)
+            .map(|a| a.exceeded_limits())
+    }
+
+    /// Evaluate the alarms bound to any of the nodes in `changed`, raising
+    /// condition events for alarms that change state.
+    ///
+    /// Active alarms are kept in the retained conditions of `subscriptions`.
+    pub fn evaluate<'a>(
+        &self,
+        address_space: &AddressSpace,
+        subscriptions: &SubscriptionCache,
+        changed: impl IntoIterator<Item = &'a NodeId>,
+    ) {
+        let events = {
+            let mut inner = trace_write_lock!(self.inner);
+            if inner.alarms.is_empty() {
+                return;
+            }
+            let affected: HashSet<NodeId> = changed
+                .into_iter()
+                .filter_map(|id| inner.bindings.get(id))
+                .flatten()
+                .cloned()
+                .collect();
+
+            let mut events = Vec::new();
+            for id in affected {
+                let Some(alarm) = inner.alarms.get_mut(&id) else {
+                    continue;
+                };
+                if alarm.evaluate(address_space) {
+                    let event = alarm.event(address_space);
+                    if alarm.is_active() {
+                        subscriptions.conditions().set(
+                            id.clone(),
+                            alarm.source_node.clone(),
+                            event.clone(),
+                        );
+                    } else {
+                        subscriptions.conditions().remove(&id);
+                    }
+                    events.push((event, alarm.source_node.clone()));
+                }
+            }
+            events
+        };
+        if !events.is_empty() {
+            subscriptions.notify_events(
+                events
+                    .iter()
+                    .map(|(evt, source)| (&**evt as &dyn Event, source)),
+            );
+        }
+    }
+}
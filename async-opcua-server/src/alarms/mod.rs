@@ -0,0 +1,17 @@
+//! Helpers for alarms raised by the server.
+//!
+//! [`LimitAlarms`] holds alarms of type `ExclusiveLimitAlarmType` and
+//! `NonExclusiveLimitAlarmType`, which are evaluated automatically when the value
+//! they monitor changes, and raise condition events when they change state.
+//!
+//! [`RetainedConditions`] keeps the last event of each retained condition, which
+//! is replayed to subscriptions calling `ConditionRefresh` or `ConditionRefresh2`.
+
+mod events;
+mod limit;
+mod retained;
+
+#[cfg(feature = "generated-address-space")]
+pub(crate) use events::{RefreshEndEventType, RefreshStartEventType};
+pub use limit::{AlarmLimit, LimitAlarm, LimitAlarmKind, LimitAlarms};
+pub use retained::RetainedConditions;
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_nodes::Event;
+use opcua_types::NodeId;
+
+// Retained conditions are only sent again by the core node manager.
+#[cfg_attr(not(feature = "generated-address-space"), allow(dead_code))]
+struct RetainedCondition {
+    notifier: NodeId,
+    event: Arc<dyn Event + Send + Sync>,
+}
+
+/// Registry of the conditions currently retained by the server.
+///
+/// Conditions with `Retain` set to true should be stored here with the latest
+/// event raised for them, and removed once they are no longer retained. The
+/// stored events are sent again to subscriptions that call the `ConditionRefresh`
+/// or `ConditionRefresh2` methods.
+#[derive(Default)]
+pub struct RetainedConditions {
+    conditions: RwLock<HashMap<NodeId, RetainedCondition>>,
+}
+
+impl RetainedConditions {
+    /// Create a new empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the last event raised for the condition with ID `condition_id`,
+    /// reported on the notifier `notifier`.
+    pub fn set(&self, condition_id: NodeId, notifier: NodeId, event: Arc<dyn Event + Send + Sync>) {
+        trace_write_lock!(self.conditions)
+            .insert(condition_id, RetainedCondition { notifier, event });
+    }
+
+    /// Remove the condition with ID `condition_id`, returning `true` if it was retained.
+    pub fn remove(&self, condition_id: &NodeId) -> bool {
+        trace_write_lock!(self.conditions)
+            .remove(condition_id)
+            .is_some()
+    }
+
+    /// Check whether the condition with ID `condition_id` is retained.
+    pub fn contains(&self, condition_id: &NodeId) -> bool {
+        trace_read_lock!(self.conditions).contains_key(condition_id)
+    }
+
+    /// Get the number of retained conditions.
+    pub fn len(&self) -> usize {
+        trace_read_lock!(self.conditions).len()
+    }
+
+    /// Check whether there are no retained conditions.
+    pub fn is_empty(&self) -> bool {
+        trace_read_lock!(self.conditions).is_empty()
+    }
+
+    /// Get the events of the conditions reported on `notifier`. All conditions
+    /// are reported on the `Server` object.
+    #[cfg(feature = "generated-address-space")]
+    pub(crate) fn events_for(&self, notifier: &NodeId) -> Vec<Arc<dyn Event + Send + Sync>> {
+        let is_server = notifier == &opcua_types::ObjectId::Server;
+        trace_read_lock!(self.conditions)
+            .values()
+            .filter(|c| is_server || &c.notifier == notifier)
+            .map(|c| c.event.clone())
+            .collect()
+    }
+}
@@ -76,6 +76,16 @@ pub struct CoreServerPermissions {
     pub read_diagnostics: bool,
 }
 
+/// Claims about a user from its access token, matched against identity mapping
+/// rules of roles with the `Role` and `GroupId` criteria types.
+#[derive(Default, Debug, Clone)]
+pub struct IdentityClaims {
+    /// Roles claimed by the access token.
+    pub roles: Vec<String>,
+    /// Groups the user is a member of.
+    pub group_ids: Vec<String>,
+}
+
 #[allow(unused)]
 #[async_trait]
 /// The AuthManager trait is used to let servers control access to the server.
@@ -194,6 +204,12 @@ pub trait AuthManager: Send + Sync + 'static {
     fn core_permissions(&self, token: &UserToken) -> CoreServerPermissions {
         CoreServerPermissions::default()
     }
+
+    /// Return the claims of the access token of the given user, used to grant
+    /// roles to its sessions.
+    fn identity_claims(&self, token: &UserToken) -> IdentityClaims {
+        IdentityClaims::default()
+    }
 }
 
 /// A simple authenticator that keeps a map of valid users in memory.
@@ -237,7 +253,7 @@ impl AuthManager for DefaultAuthenticator {
                 if server_user_token.is_user_pass() && server_user_token.user == username {
                     // test for empty password
                     let valid = if let Some(server_password) = server_user_token.pass.as_ref() {
-                        server_password.as_bytes() == token_password.as_bytes()
+                        server_password.expose().as_bytes() == token_password.as_bytes()
                     } else {
                         token_password.is_empty()
                     };
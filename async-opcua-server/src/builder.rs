@@ -3,14 +3,27 @@ use std::{path::PathBuf, sync::Arc};
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
-use crate::{constants, node_manager::TypeTreeForUser};
-use opcua_core::config::Config;
+use crate::{
+    constants,
+    node_manager::{EventHistoryProvider, TypeTreeForUser},
+};
+use opcua_core::{
+    comms::{
+        interceptor::{MessageInterceptor, MessageInterceptors},
+        metrics::{TransportMetrics, TransportMetricsHandle},
+        priority::PriorityPolicy,
+        rate_limit::RateLimit,
+        socket::SocketOptions,
+    },
+    config::Config,
+};
 use opcua_crypto::SecurityPolicy;
 use opcua_types::{BuildInfo, MessageSecurityMode, TypeLoader, TypeLoaderCollection};
 
 use super::{
-    authenticator::AuthManager, node_manager::NodeManagerBuilder, Limits, Server, ServerConfig,
-    ServerEndpoint, ServerHandle, ServerUserToken, ANONYMOUS_USER_TOKEN_ID,
+    authenticator::AuthManager, node_manager::NodeManagerBuilder, CertificateValidation, Limits,
+    OperationalLimits, Server, ServerCapabilities, ServerConfig, ServerEndpoint, ServerHandle,
+    ServerUserToken, SubscriptionLimits, ANONYMOUS_USER_TOKEN_ID,
 };
 
 /// Server builder, used to configure the server programatically,
@@ -24,6 +37,10 @@ pub struct ServerBuilder {
     pub(crate) type_loaders: TypeLoaderCollection,
     pub(crate) token: CancellationToken,
     pub(crate) build_info: BuildInfo,
+    pub(crate) transport_metrics: TransportMetricsHandle,
+    pub(crate) interceptors: MessageInterceptors,
+    pub(crate) capabilities: ServerCapabilities,
+    pub(crate) event_history: Option<Arc<dyn EventHistoryProvider>>,
 }
 
 impl Default for ServerBuilder {
@@ -36,6 +53,10 @@ impl Default for ServerBuilder {
             type_tree_getter: None,
             build_info: BuildInfo::default(),
             type_loaders: TypeLoaderCollection::new(),
+            transport_metrics: TransportMetricsHandle::default(),
+            interceptors: MessageInterceptors::default(),
+            capabilities: ServerCapabilities::default(),
+            event_history: None,
         };
         #[cfg(feature = "generated-address-space")]
         {
@@ -104,7 +125,7 @@ impl ServerBuilder {
                 "sample_password_user",
                 ServerUserToken {
                     user: "sample1".to_string(),
-                    pass: Some("sample1pwd".to_string()),
+                    pass: Some("sample1pwd".into()),
                     ..Default::default()
                 },
             )
@@ -265,6 +286,21 @@ impl ServerBuilder {
         self
     }
 
+    /// Set hooks for collecting metrics about traffic on client connections,
+    /// such as bytes and messages sent and received.
+    pub fn with_transport_metrics(mut self, metrics: impl TransportMetrics + 'static) -> Self {
+        self.transport_metrics = TransportMetricsHandle::new(metrics);
+        self
+    }
+
+    /// Add an interceptor called with every request received and response sent
+    /// on client connections. Interceptors can observe or reject messages, and
+    /// are called in the order they were added.
+    pub fn with_interceptor(mut self, interceptor: impl MessageInterceptor + 'static) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
     /// Set a custom authenticator.
     pub fn with_authenticator(mut self, authenticator: Arc<dyn AuthManager>) -> Self {
         self.authenticator = Some(authenticator);
@@ -294,6 +330,22 @@ impl ServerBuilder {
         self
     }
 
+    /// Set the capabilities of the server, exposed in the `Server/ServerCapabilities`
+    /// and `HistoryServerCapabilities` objects. These should match what the node managers
+    /// of the server support.
+    pub fn capabilities(mut self, capabilities: ServerCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Set a provider used to record and read the history of events. Events raised
+    /// on notifiers added with [`EventHistoryCapture::historize`](crate::node_manager::EventHistoryCapture::historize)
+    /// are recorded automatically, the capture is available as `ServerInfo::event_history`.
+    pub fn event_history(mut self, provider: Arc<dyn EventHistoryProvider>) -> Self {
+        self.event_history = Some(provider);
+        self
+    }
+
     /// Server application name.
     pub fn application_name(mut self, application_name: impl Into<String>) -> Self {
         self.config.application_name = application_name.into();
@@ -364,6 +416,13 @@ impl ServerBuilder {
         self
     }
 
+    /// Timeout for clients to open a secure channel after the `HELLO` message has
+    /// been acknowledged, in seconds. If this is not set, `hello_timeout` is used.
+    pub fn open_secure_channel_timeout(mut self, timeout: u32) -> Self {
+        self.config.tcp_config.open_secure_channel_timeout = Some(timeout);
+        self
+    }
+
     /// Hostname to listen to incoming TCP connections on.
     pub fn host(mut self, host: impl Into<String>) -> Self {
         self.config.tcp_config.host = host.into();
@@ -376,12 +435,40 @@ impl ServerBuilder {
         self
     }
 
+    /// Path to a Unix domain socket to listen for incoming connections on, in addition
+    /// to TCP. On Windows this is the name of a named pipe, i.e. `\\.\pipe\my-server`.
+    ///
+    /// Clients connect to this using an `opc.uds:///path/to/socket` URL.
+    pub fn uds_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.tcp_config.uds_path = Some(path.into());
+        self
+    }
+
+    /// Socket options for the TCP listener and accepted connections, such as
+    /// `TCP_NODELAY`, keep-alive, and OS buffer sizes.
+    pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.config.tcp_config.socket = socket_options;
+        self
+    }
+
     /// General server limits.
     pub fn limits(mut self, limits: Limits) -> Self {
         self.config.limits = limits;
         self
     }
 
+    /// Limits on subscriptions and monitored items.
+    pub fn subscription_limits(mut self, limits: SubscriptionLimits) -> Self {
+        self.config.limits.subscriptions = limits;
+        self
+    }
+
+    /// Limits on the number of nodes and items per service call.
+    pub fn operational_limits(mut self, limits: OperationalLimits) -> Self {
+        self.config.limits.operational = limits;
+        self
+    }
+
     /// Supported locale IDs.
     pub fn locale_ids(mut self, locale_ids: Vec<String>) -> Self {
         self.config.locale_ids = locale_ids;
@@ -402,6 +489,18 @@ impl ServerBuilder {
         self
     }
 
+    /// Set the user tokens, replacing any added so far.
+    pub fn user_tokens(
+        mut self,
+        user_tokens: impl IntoIterator<Item = (impl Into<String>, ServerUserToken)>,
+    ) -> Self {
+        self.config.user_tokens = user_tokens
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect();
+        self
+    }
+
     /// Default endpoint ID.
     pub fn default_endpoint(mut self, endpoint_id: impl Into<String>) -> Self {
         self.config.default_endpoint = Some(endpoint_id.into());
@@ -418,6 +517,18 @@ impl ServerBuilder {
         self
     }
 
+    /// Set the endpoints supported by the server, replacing any added so far.
+    pub fn endpoints(
+        mut self,
+        endpoints: impl IntoIterator<Item = (impl Into<String>, impl Into<ServerEndpoint>)>,
+    ) -> Self {
+        self.config.endpoints = endpoints
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        self
+    }
+
     /// Interval in milliseconds between each time the subscriptions are polled.
     pub fn subscription_poll_interval_ms(mut self, interval: u64) -> Self {
         self.config.subscription_poll_interval_ms = interval;
@@ -486,6 +597,41 @@ impl ServerBuilder {
         self
     }
 
+    /// Maximum size in bytes of messages sent by the server, if it should differ
+    /// from `max_message_size`. Can be negotiated lower with clients.
+    pub fn max_send_message_size(mut self, max_send_message_size: usize) -> Self {
+        self.config.limits.max_send_message_size = Some(max_send_message_size);
+        self
+    }
+
+    /// Maximum chunk count of messages sent by the server, if it should differ
+    /// from `max_chunk_count`. Can be negotiated lower with clients.
+    pub fn max_send_chunk_count(mut self, max_send_chunk_count: usize) -> Self {
+        self.config.limits.max_send_chunk_count = Some(max_send_chunk_count);
+        self
+    }
+
+    /// Maximum number of bytes queued for sending on a single connection. Once reached,
+    /// the server stops reading requests and collecting responses on the connection
+    /// until the queue drains. 0 means no limit, which is the default.
+    pub fn max_send_queue_bytes(mut self, max_send_queue_bytes: usize) -> Self {
+        self.config.limits.max_send_queue_bytes = max_send_queue_bytes;
+        self
+    }
+
+    /// Maximum number of messages queued for sending on a single connection,
+    /// see [`ServerBuilder::max_send_queue_bytes`]. 0 means no limit, which is the default.
+    pub fn max_send_queue_messages(mut self, max_send_queue_messages: usize) -> Self {
+        self.config.limits.max_send_queue_messages = max_send_queue_messages;
+        self
+    }
+
+    /// Limit the rate data is written to each connection.
+    pub fn send_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.config.limits.send_rate_limit = Some(limit);
+        self
+    }
+
     /// Maximum send buffer size, can be negotiated lower with clients.
     pub fn send_buffer_size(mut self, send_buffer_size: usize) -> Self {
         self.config.limits.send_buffer_size = send_buffer_size;
@@ -550,10 +696,63 @@ impl ServerBuilder {
         self
     }
 
+    /// Minimum number of chunks in a message before the chunks are signed and encrypted,
+    /// or verified and decrypted, on multiple threads. This speeds up large
+    /// messages on secure channels, such as big history read responses with `SignAndEncrypt`.
+    ///
+    /// Defaults to 0, meaning that chunks are always processed sequentially. Chunks are
+    /// only processed on multiple threads with the `parallel-crypto` feature.
+    pub fn parallel_crypto_chunk_threshold(mut self, threshold: usize) -> Self {
+        self.config.parallel_crypto_chunk_threshold = threshold;
+        self
+    }
+
+    /// Minimum encoded size of a response, in bytes, before it is encoded directly into
+    /// chunks as they are sent, instead of all at once. The response is encoded on the
+    /// blocking thread pool and stays a few chunks ahead of the connection, so multi-megabyte
+    /// responses, such as big history reads or browses, only keep a few chunks in memory.
+    ///
+    /// Defaults to 0, meaning that responses are always encoded up front.
+    pub fn stream_encoding_threshold(mut self, threshold: usize) -> Self {
+        self.config.stream_encoding_threshold = threshold;
+        self
+    }
+
+    /// Policy deciding which queued responses are sent first. For example,
+    /// [`PriorityPolicy::subscriptions_first`] sends publish responses and keep-alives
+    /// ahead of large history read responses, so subscriptions aren't starved when
+    /// big transfers saturate the link.
+    ///
+    /// By default responses are sent in the order they complete.
+    pub fn send_priorities(mut self, policy: PriorityPolicy) -> Self {
+        self.config.send_priorities = policy;
+        self
+    }
+
+    /// Length of the nonce generated for CreateSession responses.
+    pub fn session_nonce_length(mut self, session_nonce_length: usize) -> Self {
+        self.config.session_nonce_length = session_nonce_length;
+        self
+    }
+
+    /// How client certificates are validated. This sets both
+    /// [`ServerBuilder::trust_client_certs`] and [`ServerBuilder::check_cert_time`].
+    pub fn certificate_validation(mut self, validation: CertificateValidation) -> Self {
+        self.config.certificate_validation = validation;
+        self
+    }
+
     /// Set whether to enable diagnostics on the server or not.
     /// Only users with the right permissions can read the diagnostics
     pub fn diagnostics_enabled(mut self, enabled: bool) -> Self {
         self.config.diagnostics = enabled;
         self
     }
+
+    /// Set whether to enable auditing on the server or not. When enabled, the server
+    /// raises audit events for operations such as history updates.
+    pub fn auditing(mut self, enabled: bool) -> Self {
+        self.config.auditing = enabled;
+        self
+    }
 }
@@ -1,3 +1,4 @@
+use opcua_core::comms::rate_limit::RateLimit;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -24,6 +25,27 @@ pub struct Limits {
     /// Receive buffer size in bytes
     #[serde(default = "defaults::receive_buffer_size")]
     pub receive_buffer_size: usize,
+    /// Maximum length in bytes of messages sent by the server, if different from
+    /// `max_message_size`. The client may lower this further.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_send_message_size: Option<usize>,
+    /// Maximum chunk count of messages sent by the server, if different from
+    /// `max_chunk_count`. The client may lower this further.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_send_chunk_count: Option<usize>,
+    /// Maximum number of bytes queued for sending on a single connection. Once reached,
+    /// the server stops reading requests and collecting responses on the connection until
+    /// the queue drains, so a slow client can't make the server buffer unbounded data.
+    /// 0 means no limit.
+    #[serde(default)]
+    pub max_send_queue_bytes: usize,
+    /// Maximum number of messages queued for sending on a single connection,
+    /// see `max_send_queue_bytes`. 0 means no limit.
+    #[serde(default)]
+    pub max_send_queue_messages: usize,
+    /// Limit on the rate data is written to each connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_rate_limit: Option<RateLimit>,
     /// Limits specific to subscriptions.
     #[serde(default)]
     pub subscriptions: SubscriptionLimits,
@@ -54,6 +76,11 @@ impl Default for Limits {
             max_chunk_count: defaults::max_chunk_count(),
             send_buffer_size: defaults::send_buffer_size(),
             receive_buffer_size: defaults::receive_buffer_size(),
+            max_send_message_size: None,
+            max_send_chunk_count: None,
+            max_send_queue_bytes: 0,
+            max_send_queue_messages: 0,
+            send_rate_limit: None,
             subscriptions: Default::default(),
             max_browse_continuation_points: defaults::max_browse_continuation_points(),
             max_history_continuation_points: defaults::max_history_continuation_points(),
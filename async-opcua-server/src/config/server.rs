@@ -13,7 +13,16 @@ use serde::{Deserialize, Serialize};
 use tracing::{trace, warn};
 
 use crate::constants;
-use opcua_core::{comms::url::url_matches_except_host, config::Config};
+use opcua_core::{
+    comms::{
+        priority::PriorityPolicy, socket::SocketOptions, tcp_types::MIN_CHUNK_SIZE,
+        url::url_matches_except_host,
+    },
+    config::{
+        secret::{skip_secret, Secret},
+        Config,
+    },
+};
 use opcua_crypto::{CertificateStore, SecurityPolicy, Thumbprint};
 use opcua_types::{
     ApplicationDescription, ApplicationType, DecodingOptions, LocalizedText, MessageSecurityMode,
@@ -30,10 +39,31 @@ pub const ANONYMOUS_USER_TOKEN_ID: &str = "ANONYMOUS";
 pub struct TcpConfig {
     /// Timeout for hello on a session in seconds
     pub hello_timeout: u32,
+    /// Timeout in seconds for a client to open a secure channel once the `HELLO` has
+    /// been acknowledged. Defaults to `hello_timeout`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_secure_channel_timeout: Option<u32>,
     /// The hostname to supply in the endpoints
     pub host: String,
     /// The port number of the service
     pub port: u16,
+    /// Optional path to a Unix domain socket (or the name of a named pipe on Windows)
+    /// to listen on in addition to TCP. Clients connect to this using
+    /// `opc.uds:///path/to/socket`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uds_path: Option<PathBuf>,
+    /// Socket options applied to the listener and to accepted TCP connections.
+    #[serde(default)]
+    pub socket: SocketOptions,
+}
+
+impl TcpConfig {
+    /// Timeout in seconds for a client to open a secure channel once the `HELLO`
+    /// has been acknowledged.
+    pub fn open_secure_channel_timeout(&self) -> u32 {
+        self.open_secure_channel_timeout
+            .unwrap_or(self.hello_timeout)
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
@@ -41,9 +71,10 @@ pub struct TcpConfig {
 pub struct ServerUserToken {
     /// User name
     pub user: String,
-    /// Password
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub pass: Option<String>,
+    /// Password. This is left out when the config is saved, unless it is saved
+    /// with [`Config::save_with_secrets`].
+    #[serde(default, skip_serializing_if = "skip_secret")]
+    pub pass: Option<Secret>,
     /// X509 file path (as a string)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub x509: Option<String>,
@@ -63,7 +94,7 @@ impl ServerUserToken {
     {
         ServerUserToken {
             user: user.into(),
-            pass: Some(pass.into()),
+            pass: Some(Secret::new(pass.into())),
             x509: None,
             thumbprint: None,
             read_diagnostics: false,
@@ -236,9 +267,27 @@ pub struct ServerConfig {
     /// Enable server diagnostics.
     #[serde(default)]
     pub diagnostics: bool,
+    /// Enable auditing, raising audit events for operations such as history updates.
+    /// This is the value of the `Server.Auditing` variable.
+    #[serde(default)]
+    pub auditing: bool,
     /// Length of the nonce generated for CreateSession responses.
     #[serde(default = "defaults::session_nonce_length")]
     pub session_nonce_length: usize,
+    /// Minimum number of chunks in a message before signing, encryption, and
+    /// decryption of the chunks is spread across multiple threads. Set to 0 to disable.
+    /// Requires the `parallel-crypto` feature.
+    #[serde(default)]
+    pub parallel_crypto_chunk_threshold: usize,
+    /// Minimum encoded size of a response, in bytes, before it is encoded into chunks
+    /// as they are sent, instead of all at once. This bounds the memory used by very
+    /// large responses. Set to 0 to disable.
+    #[serde(default)]
+    pub stream_encoding_threshold: usize,
+    /// Priority of outgoing responses by service. By default responses are sent
+    /// in the order they complete.
+    #[serde(default)]
+    pub send_priorities: PriorityPolicy,
 }
 
 mod defaults {
@@ -318,9 +367,32 @@ impl Config for ServerConfig {
                 "Server configuration is invalid. Max byte string length is invalid".to_owned(),
             );
         }
+        for (name, size) in [
+            ("Send buffer size", self.limits.send_buffer_size),
+            ("Receive buffer size", self.limits.receive_buffer_size),
+        ] {
+            if size < MIN_CHUNK_SIZE {
+                errors.push(format!(
+                    "Server configuration is invalid. {name} of {size} must be at least {MIN_CHUNK_SIZE}"
+                ));
+            }
+        }
+        if self
+            .limits
+            .send_rate_limit
+            .is_some_and(|r| r.bytes_per_second == 0)
+        {
+            errors.push(
+                "Server configuration is invalid. Send rate limit must be greater than 0"
+                    .to_owned(),
+            );
+        }
         if self.discovery_urls.is_empty() {
             errors.push("Server configuration is invalid. Discovery urls not set".to_owned());
         }
+        if let Err(e) = self.tcp_config.socket.validate() {
+            errors.extend(e);
+        }
 
         if errors.is_empty() {
             Ok(())
@@ -383,6 +455,9 @@ impl Default for ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: constants::DEFAULT_RUST_OPC_UA_SERVER_PORT,
                 hello_timeout: constants::DEFAULT_HELLO_TIMEOUT_SECONDS,
+                open_secure_channel_timeout: None,
+                uds_path: None,
+                socket: SocketOptions::default(),
             },
             limits: Limits::default(),
             user_tokens: BTreeMap::new(),
@@ -396,7 +471,11 @@ impl Default for ServerConfig {
             max_secure_channel_token_lifetime_ms: defaults::max_secure_channel_token_lifetime_ms(),
             max_session_timeout_ms: defaults::max_session_timeout_ms(),
             diagnostics: false,
+            auditing: false,
             session_nonce_length: defaults::session_nonce_length(),
+            parallel_crypto_chunk_threshold: 0,
+            stream_encoding_threshold: 0,
+            send_priorities: PriorityPolicy::default(),
         }
     }
 }
@@ -442,6 +521,9 @@ impl ServerConfig {
                 host,
                 port,
                 hello_timeout: constants::DEFAULT_HELLO_TIMEOUT_SECONDS,
+                open_secure_channel_timeout: None,
+                uds_path: None,
+                socket: SocketOptions::default(),
             },
             locale_ids,
             user_tokens,
@@ -2,10 +2,12 @@
 
 mod node_manager;
 mod server;
+mod session;
 pub use node_manager::{DiagnosticsNodeManager, DiagnosticsNodeManagerBuilder, NamespaceMetadata};
 use opcua_core::sync::Mutex;
 use opcua_types::{DataValue, DateTime, IntoVariant};
 pub use server::{ServerDiagnostics, ServerDiagnosticsSummary};
+pub use session::SessionDiagnostics;
 
 #[derive(Default)]
 /// Wrapper around a value in memory, used for metrics.
@@ -14,17 +14,20 @@ use crate::{
     node_manager::{
         as_opaque_node_id, from_opaque_node_id, impl_translate_browse_paths_using_browse,
         AddReferenceResult, BrowseNode, BrowsePathItem, DynNodeManager, ExternalReferenceRequest,
-        NodeManager, NodeManagerBuilder, NodeManagersRef, NodeMetadata, ReadNode, RequestContext,
-        ServerContext, SyncSampler,
+        MonitoredItemRef, MonitoredItemUpdateRef, NodeManager, NodeManagerBuilder, NodeManagersRef,
+        NodeMetadata, ReadNode, RequestContext, ServerContext, SyncSampler,
     },
+    CreateMonitoredItem, SubscriptionCache,
 };
 use opcua_types::{
     AccessLevelExType, AccessRestrictionType, AttributeId, BrowseDirection, DataTypeId, DataValue,
-    DateTime, ExpandedNodeId, ExtensionObject, IdType, LocalizedText, NodeClass, NodeId,
-    NumericRange, ObjectId, ObjectTypeId, QualifiedName, ReferenceDescription, ReferenceTypeId,
-    RolePermissionType, StatusCode, TimestampsToReturn, VariableTypeId, Variant,
+    DateTime, ExpandedNodeId, ExtensionObject, IdType, Identifier, LocalizedText, MonitoringMode,
+    NodeClass, NodeId, NumericRange, ObjectId, ObjectTypeId, QualifiedName, ReferenceDescription,
+    ReferenceTypeId, RolePermissionType, StatusCode, TimestampsToReturn, VariableTypeId, Variant,
 };
 
+use super::SessionDiagnostics;
+
 /// Node manager handling nodes in the server hierarchy that are not part of the
 /// core namespace, and that are somehow dynamic. This includes the node for each namespace,
 /// session diagnostics, etc.
@@ -51,9 +54,11 @@ of nodes without explicitly storing each node ID.
 /// Namespace metadata. This is visible in the namespace array under
 /// the `Server` node.
 pub struct NamespaceMetadata {
-    /// Default access restrictions on this namespace.
+    /// Default access restrictions on this namespace. The `InMemoryNodeManager`
+    /// enforces these by setting them on the [`RoleSet`](crate::roles::RoleSet) on init.
     pub default_access_restrictions: AccessRestrictionType,
-    /// Default role permissions on this namespace.
+    /// Default role permissions on this namespace. The `InMemoryNodeManager`
+    /// enforces these by setting them on the [`RoleSet`](crate::roles::RoleSet) on init.
     pub default_role_permissions: Option<Vec<RolePermissionType>>,
     /// Default user role permissions on this namespace.
     pub default_user_role_permissions: Option<Vec<RolePermissionType>>,
@@ -86,9 +91,23 @@ struct NamespaceNode {
     property: Option<String>,
 }
 
+/// Components of each `SessionDiagnosticsObjectType` instance.
+const SESSION_COMPONENTS: [&str; 3] = [
+    "SessionDiagnostics",
+    "SessionSecurityDiagnostics",
+    "SubscriptionDiagnosticsArray",
+];
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SessionNode {
+    session_id: u32,
+    component: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 enum DiagnosticsNode {
     Namespace(NamespaceNode),
+    Session(SessionNode),
 }
 
 /// Builder for the diagnostics node manager.
@@ -116,10 +135,36 @@ impl DiagnosticsNodeManager {
     }
 
     fn namespaces(&self, context: &RequestContext) -> BTreeMap<String, NamespaceMetadata> {
+        let roles = &context.info.roles;
         self.node_managers
             .iter()
             .flat_map(move |nm| nm.namespaces_for_user(context))
-            .map(|ns| (ns.namespace_uri.clone(), ns))
+            .map(|mut ns| {
+                // Show the defaults enforced by the role set. In-memory node managers
+                // initialize these from their namespace metadata.
+                if let Some(r) = roles.default_role_permissions(ns.namespace_index) {
+                    ns.default_role_permissions = Some(r);
+                }
+                if ns.default_user_role_permissions.is_none() {
+                    ns.default_user_role_permissions = roles.user_role_permissions(
+                        &context.roles,
+                        ns.default_role_permissions.as_deref(),
+                        ns.namespace_index,
+                    );
+                }
+                let access_restrictions = roles.default_access_restrictions(ns.namespace_index);
+                if !access_restrictions.is_empty() {
+                    ns.default_access_restrictions = access_restrictions;
+                }
+                // These properties are mandatory on `NamespaceMetadataType`, so
+                // they should have a value even if the node manager does not set one.
+                ns.is_namespace_subset.get_or_insert(false);
+                ns.static_node_id_types.get_or_insert_with(Vec::new);
+                ns.static_numeric_node_id_range.get_or_insert_with(Vec::new);
+                ns.static_string_node_id_pattern
+                    .get_or_insert_with(String::new);
+                (ns.namespace_uri.clone(), ns)
+            })
             .collect()
     }
 
@@ -423,7 +468,7 @@ impl DiagnosticsNodeManager {
             )
             .unwrap()
             .into(),
-            AttributeId::NodeClass => (NodeClass::Object as i32).into(),
+            AttributeId::NodeClass => (NodeClass::Variable as i32).into(),
             AttributeId::BrowseName => QualifiedName::new(0, prop).into(),
             AttributeId::DisplayName => LocalizedText::new("", prop).into(),
             AttributeId::Value => match prop {
@@ -494,15 +539,17 @@ impl DiagnosticsNodeManager {
                 }
             },
             AttributeId::ValueRank => match prop {
-                "DefaultRolePermissions" | "DefaultUserRolePermissions" | "StaticNodeIdTypes" => {
-                    1.into()
-                }
+                "DefaultRolePermissions"
+                | "DefaultUserRolePermissions"
+                | "StaticNodeIdTypes"
+                | "StaticNumericNodeIdRange" => 1.into(),
                 _ => (-1).into(),
             },
             AttributeId::ArrayDimensions => match prop {
-                "DefaultRolePermissions" | "DefaultUserRolePermissions" | "StaticNodeIdTypes" => {
-                    vec![0u32].into()
-                }
+                "DefaultRolePermissions"
+                | "DefaultUserRolePermissions"
+                | "StaticNodeIdTypes"
+                | "StaticNumericNodeIdRange" => vec![0u32].into(),
                 _ => Variant::Empty,
             },
             AttributeId::AccessLevel | AttributeId::UserAccessLevel => {
@@ -546,12 +593,305 @@ impl DiagnosticsNodeManager {
             self.read_namespace_metadata_node(start_time, node_to_read, namespace);
         }
     }
+
+    fn can_read_sessions(context: &RequestContext) -> bool {
+        context.info.diagnostics.enabled()
+            && context
+                .info
+                .authenticator
+                .core_permissions(&context.token)
+                .read_diagnostics
+    }
+
+    /// Get the diagnostics of the session referenced by `node`, if the
+    /// node is valid and the user is allowed to see it.
+    fn get_session(
+        context: &RequestContext,
+        node: &SessionNode,
+    ) -> Result<Arc<SessionDiagnostics>, StatusCode> {
+        if !Self::can_read_sessions(context) {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        if node
+            .component
+            .as_deref()
+            .is_some_and(|c| !Self::is_valid_session_component(c))
+        {
+            return Err(StatusCode::BadNodeIdUnknown);
+        }
+        context
+            .info
+            .diagnostics
+            .session(node.session_id)
+            .ok_or(StatusCode::BadNodeIdUnknown)
+    }
+
+    fn is_valid_session_component(component: &str) -> bool {
+        SESSION_COMPONENTS.contains(&component)
+    }
+
+    /// Get the type definition, its browse name, and the data type of
+    /// a component of a session diagnostics object.
+    fn session_component_type(component: &str) -> (VariableTypeId, &'static str, DataTypeId) {
+        match component {
+            "SessionDiagnostics" => (
+                VariableTypeId::SessionDiagnosticsVariableType,
+                "SessionDiagnosticsVariableType",
+                DataTypeId::SessionDiagnosticsDataType,
+            ),
+            "SessionSecurityDiagnostics" => (
+                VariableTypeId::SessionSecurityDiagnosticsType,
+                "SessionSecurityDiagnosticsType",
+                DataTypeId::SessionSecurityDiagnosticsDataType,
+            ),
+            _ => (
+                VariableTypeId::SubscriptionDiagnosticsArrayType,
+                "SubscriptionDiagnosticsArrayType",
+                DataTypeId::SubscriptionDiagnosticsDataType,
+            ),
+        }
+    }
+
+    fn sample_session_component(
+        session: &SessionDiagnostics,
+        component: &str,
+        subscriptions: &SubscriptionCache,
+    ) -> DataValue {
+        match component {
+            "SessionDiagnostics" => session.sample_diagnostics(subscriptions),
+            "SessionSecurityDiagnostics" => session.sample_security_diagnostics(),
+            _ => session.sample_subscription_diagnostics(subscriptions),
+        }
+    }
+
+    fn session_node_id(&self, session_id: u32, component: Option<&str>) -> NodeId {
+        as_opaque_node_id(
+            &DiagnosticsNode::Session(SessionNode {
+                session_id,
+                component: component.map(|c| c.to_owned()),
+            }),
+            self.namespace_index,
+        )
+        .unwrap_or_default()
+    }
+
+    fn session_node_metadata(&self, session: &SessionDiagnostics) -> NodeMetadata {
+        let name = session.session_name();
+        NodeMetadata {
+            node_id: self.session_node_id(session.session_id(), None).into(),
+            type_definition: ObjectTypeId::SessionDiagnosticsObjectType.into(),
+            browse_name: QualifiedName::new(self.namespace_index, name.as_ref()),
+            display_name: LocalizedText::new("", name.as_ref()),
+            node_class: NodeClass::Object,
+        }
+    }
+
+    fn session_component_metadata(&self, session_id: u32, component: &str) -> NodeMetadata {
+        let (type_definition, _, _) = Self::session_component_type(component);
+        NodeMetadata {
+            node_id: self.session_node_id(session_id, Some(component)).into(),
+            type_definition: type_definition.into(),
+            browse_name: QualifiedName::new(0, component),
+            display_name: LocalizedText::new("", component),
+            node_class: NodeClass::Variable,
+        }
+    }
+
+    fn browse_sessions(
+        &self,
+        context: &RequestContext,
+        node_to_browse: &mut BrowseNode,
+        type_tree: &DefaultTypeTree,
+    ) {
+        if !matches!(
+            node_to_browse.browse_direction(),
+            BrowseDirection::Forward | BrowseDirection::Both
+        ) || !Self::can_read_sessions(context)
+        {
+            return;
+        }
+
+        if !node_to_browse.allows_reference_type(&ReferenceTypeId::HasComponent.into(), type_tree) {
+            return;
+        }
+
+        let mut cp = BrowseContinuationPoint::default();
+
+        for session in context.info.diagnostics.sessions() {
+            let ref_desc = self
+                .session_node_metadata(&session)
+                .into_ref_desc(true, ReferenceTypeId::HasComponent);
+
+            if let AddReferenceResult::Full(c) = node_to_browse.add(type_tree, ref_desc) {
+                cp.nodes.push_back(c);
+            }
+        }
+
+        if !cp.nodes.is_empty() {
+            node_to_browse.set_next_continuation_point(Box::new(cp));
+        }
+    }
+
+    fn browse_session_node(
+        &self,
+        context: &RequestContext,
+        node_to_browse: &mut BrowseNode,
+        type_tree: &DefaultTypeTree,
+        session_node: &SessionNode,
+    ) {
+        let session = match Self::get_session(context, session_node) {
+            Ok(s) => s,
+            Err(e) => {
+                node_to_browse.set_status(e);
+                return;
+            }
+        };
+
+        let mut cp = BrowseContinuationPoint::default();
+        let mut add = |node_to_browse: &mut BrowseNode, ref_desc| {
+            if let AddReferenceResult::Full(c) = node_to_browse.add(type_tree, ref_desc) {
+                cp.nodes.push_back(c);
+            }
+        };
+
+        if matches!(
+            node_to_browse.browse_direction(),
+            BrowseDirection::Forward | BrowseDirection::Both
+        ) {
+            if session_node.component.is_none() {
+                for component in SESSION_COMPONENTS {
+                    let ref_desc = self
+                        .session_component_metadata(session.session_id(), component)
+                        .into_ref_desc(true, ReferenceTypeId::HasComponent);
+                    add(node_to_browse, ref_desc);
+                }
+            }
+
+            let (type_definition, name, node_class) = match session_node.component.as_deref() {
+                None => (
+                    NodeId::from(ObjectTypeId::SessionDiagnosticsObjectType),
+                    "SessionDiagnosticsObjectType",
+                    NodeClass::ObjectType,
+                ),
+                Some(component) => {
+                    let (type_definition, name, _) = Self::session_component_type(component);
+                    (type_definition.into(), name, NodeClass::VariableType)
+                }
+            };
+            add(
+                node_to_browse,
+                ReferenceDescription {
+                    reference_type_id: ReferenceTypeId::HasTypeDefinition.into(),
+                    is_forward: true,
+                    node_id: type_definition.into(),
+                    browse_name: QualifiedName::new(0, name),
+                    display_name: LocalizedText::new("", name),
+                    node_class,
+                    type_definition: ExpandedNodeId::null(),
+                },
+            );
+        }
+
+        if matches!(
+            node_to_browse.browse_direction(),
+            BrowseDirection::Inverse | BrowseDirection::Both
+        ) {
+            let ref_desc = if session_node.component.is_some() {
+                self.session_node_metadata(&session)
+                    .into_ref_desc(false, ReferenceTypeId::HasComponent)
+            } else {
+                ReferenceDescription {
+                    reference_type_id: ReferenceTypeId::HasComponent.into(),
+                    is_forward: false,
+                    node_id: ObjectId::Server_ServerDiagnostics_SessionsDiagnosticsSummary.into(),
+                    browse_name: QualifiedName::new(0, "SessionsDiagnosticsSummary"),
+                    display_name: LocalizedText::new("", "SessionsDiagnosticsSummary"),
+                    node_class: NodeClass::Object,
+                    type_definition: ObjectTypeId::SessionsDiagnosticsSummaryType.into(),
+                }
+            };
+            add(node_to_browse, ref_desc);
+        }
+
+        if !cp.nodes.is_empty() {
+            node_to_browse.set_next_continuation_point(Box::new(cp));
+        }
+    }
+
+    fn read_session_attribute(
+        &self,
+        subscriptions: &SubscriptionCache,
+        session: &SessionDiagnostics,
+        component: Option<&str>,
+        attribute_id: AttributeId,
+    ) -> Result<DataValue, StatusCode> {
+        let session_id = session.session_id();
+        let v: Variant = match (component, attribute_id) {
+            (_, AttributeId::NodeId) => self.session_node_id(session_id, component).into(),
+            (None, AttributeId::NodeClass) => (NodeClass::Object as i32).into(),
+            (Some(_), AttributeId::NodeClass) => (NodeClass::Variable as i32).into(),
+            (None, AttributeId::BrowseName) => {
+                QualifiedName::new(self.namespace_index, session.session_name().as_ref()).into()
+            }
+            (None, AttributeId::DisplayName) => {
+                LocalizedText::new("", session.session_name().as_ref()).into()
+            }
+            (Some(c), AttributeId::BrowseName) => QualifiedName::new(0, c).into(),
+            (Some(c), AttributeId::DisplayName) => LocalizedText::new("", c).into(),
+            (None, AttributeId::EventNotifier) => 0u8.into(),
+            (_, AttributeId::WriteMask | AttributeId::UserWriteMask) => 0u32.into(),
+            (Some(c), AttributeId::Value) => {
+                return Ok(Self::sample_session_component(session, c, subscriptions))
+            }
+            (Some(c), AttributeId::DataType) => {
+                Variant::NodeId(Box::new(Self::session_component_type(c).2.into()))
+            }
+            (Some("SubscriptionDiagnosticsArray"), AttributeId::ValueRank) => 1.into(),
+            (Some(_), AttributeId::ValueRank) => (-1).into(),
+            (Some("SubscriptionDiagnosticsArray"), AttributeId::ArrayDimensions) => {
+                vec![0u32].into()
+            }
+            (Some(_), AttributeId::ArrayDimensions) => Variant::Empty,
+            (Some(_), AttributeId::AccessLevel | AttributeId::UserAccessLevel) => {
+                AccessLevel::CURRENT_READ.bits().into()
+            }
+            (Some(_), AttributeId::AccessLevelEx) => {
+                (AccessLevelExType::CurrentRead.bits() as u32).into()
+            }
+            (Some(_), AttributeId::MinimumSamplingInterval) => 0.0.into(),
+            (Some(_), AttributeId::Historizing) => false.into(),
+            _ => return Err(StatusCode::BadAttributeIdInvalid),
+        };
+
+        Ok(DataValue::new_now(v))
+    }
+
+    fn read_session_node(
+        &self,
+        context: &RequestContext,
+        node_to_read: &mut ReadNode,
+        session_node: &SessionNode,
+    ) {
+        let res = Self::get_session(context, session_node).and_then(|session| {
+            self.read_session_attribute(
+                &context.subscriptions,
+                &session,
+                session_node.component.as_deref(),
+                node_to_read.node().attribute_id,
+            )
+        });
+        match res {
+            Ok(v) => node_to_read.set_result(v),
+            Err(e) => node_to_read.set_error(e),
+        }
+    }
 }
 
 #[async_trait]
 impl NodeManager for DiagnosticsNodeManager {
     fn owns_node(&self, id: &NodeId) -> bool {
-        id.namespace == self.namespace_index
+        // Roles added at runtime are also in the server namespace, with GUID node IDs.
+        id.namespace == self.namespace_index && matches!(id.identifier, Identifier::ByteString(_))
     }
 
     fn name(&self) -> &str {
@@ -611,6 +951,16 @@ impl NodeManager for DiagnosticsNodeManager {
                         self.namespace_node_metadata(ns_node)
                     }
                 }
+                DiagnosticsNode::Session(node) => {
+                    let Ok(session) = Self::get_session(context, &node) else {
+                        continue;
+                    };
+                    if let Some(component) = &node.component {
+                        self.session_component_metadata(node.session_id, component)
+                    } else {
+                        self.session_node_metadata(&session)
+                    }
+                }
             };
             req.set(meta);
         }
@@ -646,9 +996,12 @@ impl NodeManager for DiagnosticsNodeManager {
                     ObjectId::Server_Namespaces => {
                         self.browse_namespaces(node, &type_tree, namespaces);
                     }
+                    ObjectId::Server_ServerDiagnostics_SessionsDiagnosticsSummary => {
+                        self.browse_sessions(context, node, &type_tree);
+                    }
                     _ => continue,
                 }
-            } else if node.node_id().namespace == self.namespace_index {
+            } else if self.owns_node(node.node_id()) {
                 let Some(node_desc) = from_opaque_node_id::<DiagnosticsNode>(node.node_id()) else {
                     node.set_status(StatusCode::BadNodeIdUnknown);
                     continue;
@@ -659,6 +1012,9 @@ impl NodeManager for DiagnosticsNodeManager {
                             lazy_namespaces.get_or_insert_with(|| self.namespaces(context));
                         self.browse_namespace_node(node, &type_tree, namespaces, &ns);
                     }
+                    DiagnosticsNode::Session(session) => {
+                        self.browse_session_node(context, node, &type_tree, &session);
+                    }
                 }
             }
         }
@@ -688,11 +1044,110 @@ impl NodeManager for DiagnosticsNodeManager {
                         lazy_namespaces.get_or_insert_with(|| self.namespaces(context));
                     self.read_namespace_node(start_time, node, namespaces, &ns);
                 }
+                DiagnosticsNode::Session(session) => {
+                    self.read_session_node(context, node, &session);
+                }
             }
         }
         Ok(())
     }
 
+    async fn create_monitored_items(
+        &self,
+        context: &RequestContext,
+        items: &mut [&mut CreateMonitoredItem],
+    ) -> Result<(), StatusCode> {
+        for item in items {
+            let Some(DiagnosticsNode::Session(session_node)) =
+                from_opaque_node_id::<DiagnosticsNode>(&item.item_to_monitor().node_id)
+            else {
+                continue;
+            };
+            let session = match Self::get_session(context, &session_node) {
+                Ok(s) => s,
+                Err(e) => {
+                    item.set_status(e);
+                    continue;
+                }
+            };
+            let attribute_id = item.item_to_monitor().attribute_id;
+            match self.read_session_attribute(
+                &context.subscriptions,
+                &session,
+                session_node.component.as_deref(),
+                attribute_id,
+            ) {
+                Ok(v) => item.set_initial_value(v),
+                Err(e) => {
+                    item.set_status(e);
+                    continue;
+                }
+            }
+            item.set_status(StatusCode::Good);
+
+            if attribute_id != AttributeId::Value {
+                continue;
+            }
+            // Hold a weak reference, so that the sampler stops producing
+            // values once the session is gone.
+            let weak = Arc::downgrade(&session);
+            let subscriptions = context.subscriptions.clone();
+            let Some(component) = session_node.component else {
+                continue;
+            };
+            self.sampler.add_sampler(
+                item.item_to_monitor().node_id.clone(),
+                attribute_id,
+                move || {
+                    let session = weak.upgrade()?;
+                    Some(Self::sample_session_component(
+                        &session,
+                        &component,
+                        &subscriptions,
+                    ))
+                },
+                item.monitoring_mode(),
+                item.handle(),
+                Duration::from_millis(item.sampling_interval() as u64),
+            );
+        }
+        Ok(())
+    }
+
+    async fn modify_monitored_items(
+        &self,
+        _context: &RequestContext,
+        items: &[&MonitoredItemUpdateRef],
+    ) {
+        for item in items {
+            self.sampler.update_sampler(
+                item.node_id(),
+                item.attribute(),
+                item.handle(),
+                Duration::from_millis(item.update().revised_sampling_interval as u64),
+            );
+        }
+    }
+
+    async fn set_monitoring_mode(
+        &self,
+        _context: &RequestContext,
+        mode: MonitoringMode,
+        items: &[&MonitoredItemRef],
+    ) {
+        for item in items {
+            self.sampler
+                .set_sampler_mode(item.node_id(), item.attribute(), item.handle(), mode);
+        }
+    }
+
+    async fn delete_monitored_items(&self, _context: &RequestContext, items: &[&MonitoredItemRef]) {
+        for item in items {
+            self.sampler
+                .remove_sampler(item.node_id(), item.attribute(), item.handle());
+        }
+    }
+
     async fn translate_browse_paths_to_node_ids(
         &self,
         context: &RequestContext,
@@ -1,6 +1,16 @@
-use opcua_types::{DataValue, ServerDiagnosticsSummaryDataType, VariableId};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use super::LocalValue;
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_types::{DataValue, ExtensionObject, ServerDiagnosticsSummaryDataType, VariableId};
+
+use super::{LocalValue, SessionDiagnostics};
+use crate::SubscriptionCache;
 
 /// The server diagnostics struct, containing shared
 /// types for various forms of server diagnostics.
@@ -8,15 +18,37 @@ use super::LocalValue;
 pub struct ServerDiagnostics {
     /// Server diagnostics summary.
     pub summary: ServerDiagnosticsSummary,
-    /// Whether diagnostics are enabled or not.
-    /// Set on server startup.
-    pub enabled: bool,
+    enabled: AtomicBool,
+    sessions: RwLock<BTreeMap<u32, Arc<SessionDiagnostics>>>,
 }
 
 impl ServerDiagnostics {
+    /// Create a new server diagnostics object.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            ..Default::default()
+        }
+    }
+
+    /// Whether diagnostics are currently collected. This is the value of the
+    /// `EnabledFlag` variable of the `ServerDiagnostics` object.
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enable or disable collection of diagnostics.
+    ///
+    /// Counters keep their values while collection is disabled. Sessions are
+    /// still tracked, but their diagnostics are only exposed and updated
+    /// while collection is enabled.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
     /// Check if the given variable ID is managed by this object.
     pub fn is_mapped(&self, variable_id: VariableId) -> bool {
-        self.enabled && self.summary.is_mapped(variable_id)
+        self.enabled() && self.summary.is_mapped(variable_id)
     }
 
     /// Get the value of a diagnostics element by its ID.
@@ -24,79 +56,155 @@ impl ServerDiagnostics {
         self.summary.get(variable_id)
     }
 
-    /// Set the current session count.
-    pub fn set_current_session_count(&self, count: u32) {
-        if self.enabled {
-            self.summary.current_session_count.set(count);
+    /// Check if the given variable ID is one of the diagnostics arrays
+    /// listing sessions or subscriptions.
+    pub fn is_diagnostics_array(&self, variable_id: VariableId) -> bool {
+        self.enabled()
+            && matches!(
+                variable_id,
+                VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionDiagnosticsArray
+                    | VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionSecurityDiagnosticsArray
+                    | VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray
+            )
+    }
+
+    /// Get the value of one of the diagnostics arrays.
+    pub fn get_diagnostics_array(
+        &self,
+        variable_id: VariableId,
+        subscriptions: &SubscriptionCache,
+    ) -> Option<DataValue> {
+        let values: Vec<ExtensionObject> = match variable_id {
+            VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionDiagnosticsArray => trace_read_lock!(self.sessions)
+                .values()
+                .map(|s| ExtensionObject::from_message(s.diagnostics(subscriptions)))
+                .collect(),
+            VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionSecurityDiagnosticsArray => trace_read_lock!(self.sessions)
+                .values()
+                .map(|s| ExtensionObject::from_message(s.security_diagnostics()))
+                .collect(),
+            VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray => subscriptions
+                .subscription_diagnostics()
+                .into_iter()
+                .map(ExtensionObject::from_message)
+                .collect(),
+            _ => return None,
+        };
+        Some(DataValue::new_now(values))
+    }
+
+    /// Get the diagnostics of the session with numeric ID `session_id`.
+    /// This is `None` if diagnostics are disabled.
+    pub fn session(&self, session_id: u32) -> Option<Arc<SessionDiagnostics>> {
+        if !self.enabled() {
+            return None;
         }
+        self.tracked_session(session_id)
     }
 
-    /// Set the current subscription count.
-    pub fn set_current_subscription_count(&self, count: u32) {
-        if self.enabled {
-            self.summary.current_subscription_count.set(count);
+    /// Get the diagnostics of all current sessions, ordered by session ID.
+    /// This is empty if diagnostics are disabled.
+    pub fn sessions(&self) -> Vec<Arc<SessionDiagnostics>> {
+        if !self.enabled() {
+            return Vec::new();
         }
+        trace_read_lock!(self.sessions).values().cloned().collect()
+    }
+
+    /// Get the diagnostics of the session with numeric ID `session_id`, even
+    /// if diagnostics are disabled. Used to keep the session description
+    /// up to date.
+    pub(crate) fn tracked_session(&self, session_id: u32) -> Option<Arc<SessionDiagnostics>> {
+        trace_read_lock!(self.sessions).get(&session_id).cloned()
+    }
+
+    pub(crate) fn register_session(&self, diagnostics: SessionDiagnostics) {
+        trace_write_lock!(self.sessions).insert(diagnostics.session_id(), Arc::new(diagnostics));
+    }
+
+    pub(crate) fn unregister_session(&self, session_id: u32) {
+        trace_write_lock!(self.sessions).remove(&session_id);
+    }
+
+    /// Set the current session count. This is tracked even while diagnostics
+    /// are disabled, so that it is correct once they are enabled.
+    pub fn set_current_session_count(&self, count: u32) {
+        self.summary.current_session_count.set(count);
+    }
+
+    /// Set the current subscription count. This is tracked even while diagnostics
+    /// are disabled, so that it is correct once they are enabled.
+    pub fn set_current_subscription_count(&self, count: u32) {
+        self.summary.current_subscription_count.set(count);
     }
 
     /// Increment the cumulated session count.
     pub fn inc_session_count(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.cumulated_session_count.increment();
         }
     }
 
     /// Increment the cumulated subscription count.
     pub fn inc_subscription_count(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.cumulated_subscription_count.increment();
         }
     }
 
     /// Increment the rejected requests count.
     pub fn inc_rejected_requests(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.rejected_requests_count.increment();
         }
     }
 
     /// Increment the security rejected requests count.
     pub fn inc_security_rejected_requests(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.security_rejected_requests_count.increment();
         }
     }
 
+    /// Increment the rejected session count.
+    pub fn inc_rejected_session_count(&self) {
+        if self.enabled() {
+            self.summary.rejected_session_count.increment();
+        }
+    }
+
     /// Increment the security rejected session count.
     pub fn inc_security_rejected_session_count(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.security_rejected_session_count.increment();
         }
     }
 
     /// Set the number of server-created views.
     pub fn set_server_view_count(&self, count: u32) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.server_view_count.set(count);
         }
     }
 
     /// Increment the session abort count.
     pub fn inc_session_abort_count(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.session_abort_count.increment();
         }
     }
 
     /// Increment the session timeout count.
     pub fn inc_session_timeout_count(&self) {
-        if self.enabled {
+        if self.enabled() {
             self.summary.session_timeout_count.increment();
         }
     }
 
-    /// Set the number of publishing intervals supported by the server.
+    /// Set the number of distinct publishing intervals currently in use by
+    /// subscriptions on the server.
     pub fn set_publishing_interval_count(&self, count: u32) {
-        if self.enabled {
+        if self.enabled() && self.summary.publishing_interval_count.get() != count {
             self.summary.publishing_interval_count.set(count);
         }
     }
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use opcua_core::{sync::Mutex, RequestMessage};
+use opcua_types::{
+    profiles, ByteString, DataValue, DateTime, ExtensionObject, ServiceCounterDataType,
+    SessionDiagnosticsDataType, SessionSecurityDiagnosticsDataType, StatusCode,
+    SubscriptionDiagnosticsDataType, UAString,
+};
+
+use crate::{
+    authenticator::UserToken, identity_token::IdentityToken, info::ServerInfo,
+    session::instance::Session, SubscriptionCache,
+};
+
+type ServiceCounter = fn(&mut SessionDiagnosticsDataType) -> &mut ServiceCounterDataType;
+
+/// Diagnostics for a single session, exposed as an instance of
+/// `SessionDiagnosticsObjectType` under `SessionsDiagnosticsSummary`.
+pub struct SessionDiagnostics {
+    session_id: u32,
+    diagnostics: Mutex<SessionDiagnosticsDataType>,
+    security: Mutex<SessionSecurityDiagnosticsDataType>,
+}
+
+impl SessionDiagnostics {
+    pub(crate) fn new(session: &Session, info: &ServerInfo) -> Self {
+        let now = DateTime::now();
+        Self {
+            session_id: session.session_id_numeric(),
+            diagnostics: Mutex::new(SessionDiagnosticsDataType {
+                session_id: session.session_id().clone(),
+                session_name: session.session_name().into(),
+                client_description: session.application_description().clone(),
+                server_uri: info.application_uri.clone(),
+                endpoint_url: session.endpoint_url().clone(),
+                locale_ids: None,
+                actual_session_timeout: session.session_timeout().as_millis() as f64,
+                max_response_message_size: session.max_response_message_size(),
+                client_connection_time: now,
+                client_last_contact_time: now,
+                ..Default::default()
+            }),
+            security: Mutex::new(SessionSecurityDiagnosticsDataType {
+                session_id: session.session_id().clone(),
+                encoding: "UA Binary".into(),
+                transport_protocol: profiles::TRANSPORT_PROFILE_URI_BINARY.into(),
+                security_mode: session.message_security_mode(),
+                security_policy_uri: session.security_policy_uri().into(),
+                client_certificate: session
+                    .client_certificate()
+                    .map(|c| c.as_byte_string())
+                    .unwrap_or_else(ByteString::null),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Update the diagnostics when the session is activated.
+    pub(crate) fn activate(
+        &self,
+        identity: &IdentityToken,
+        user_token: &UserToken,
+        locale_ids: Option<&[UAString]>,
+    ) {
+        self.diagnostics.lock().locale_ids = locale_ids.map(|l| l.to_vec());
+
+        let mut security = self.security.lock();
+        let user_id = UAString::from(user_token.0.as_str());
+        security
+            .client_user_id_history
+            .get_or_insert_with(Vec::new)
+            .push(user_id.clone());
+        security.client_user_id_of_session = user_id;
+        security.authentication_mechanism = match identity {
+            IdentityToken::None | IdentityToken::Anonymous(_) => "Anonymous",
+            IdentityToken::UserName(_) => "UserName",
+            IdentityToken::X509(_) => "X509Certificate",
+            IdentityToken::IssuedToken(_) => "IssuedToken",
+            IdentityToken::Invalid(_) => "Invalid",
+        }
+        .into();
+    }
+
+    /// Record the start of a request on this session. The returned object
+    /// must be finished with the result of the request once it completes.
+    pub(crate) fn start_request(self: &Arc<Self>, request: &RequestMessage) -> SessionRequest {
+        let counter = Self::service_counter(request);
+        let mut diagnostics = self.diagnostics.lock();
+        diagnostics.client_last_contact_time = DateTime::now();
+        diagnostics.total_request_count.total_count += 1;
+        if let Some(counter) = counter {
+            counter(&mut diagnostics).total_count += 1;
+        }
+        SessionRequest {
+            diagnostics: self.clone(),
+            counter,
+        }
+    }
+
+    fn service_counter(request: &RequestMessage) -> Option<ServiceCounter> {
+        let counter: ServiceCounter = match request {
+            RequestMessage::Read(_) => |d| &mut d.read_count,
+            RequestMessage::HistoryRead(_) => |d| &mut d.history_read_count,
+            RequestMessage::Write(_) => |d| &mut d.write_count,
+            RequestMessage::HistoryUpdate(_) => |d| &mut d.history_update_count,
+            RequestMessage::Call(_) => |d| &mut d.call_count,
+            RequestMessage::CreateMonitoredItems(_) => |d| &mut d.create_monitored_items_count,
+            RequestMessage::ModifyMonitoredItems(_) => |d| &mut d.modify_monitored_items_count,
+            RequestMessage::SetMonitoringMode(_) => |d| &mut d.set_monitoring_mode_count,
+            RequestMessage::SetTriggering(_) => |d| &mut d.set_triggering_count,
+            RequestMessage::DeleteMonitoredItems(_) => |d| &mut d.delete_monitored_items_count,
+            RequestMessage::CreateSubscription(_) => |d| &mut d.create_subscription_count,
+            RequestMessage::ModifySubscription(_) => |d| &mut d.modify_subscription_count,
+            RequestMessage::SetPublishingMode(_) => |d| &mut d.set_publishing_mode_count,
+            RequestMessage::Publish(_) => |d| &mut d.publish_count,
+            RequestMessage::Republish(_) => |d| &mut d.republish_count,
+            RequestMessage::TransferSubscriptions(_) => |d| &mut d.transfer_subscriptions_count,
+            RequestMessage::DeleteSubscriptions(_) => |d| &mut d.delete_subscriptions_count,
+            RequestMessage::AddNodes(_) => |d| &mut d.add_nodes_count,
+            RequestMessage::AddReferences(_) => |d| &mut d.add_references_count,
+            RequestMessage::DeleteNodes(_) => |d| &mut d.delete_nodes_count,
+            RequestMessage::DeleteReferences(_) => |d| &mut d.delete_references_count,
+            RequestMessage::Browse(_) => |d| &mut d.browse_count,
+            RequestMessage::BrowseNext(_) => |d| &mut d.browse_next_count,
+            RequestMessage::TranslateBrowsePathsToNodeIds(_) => {
+                |d| &mut d.translate_browse_paths_to_node_ids_count
+            }
+            RequestMessage::QueryFirst(_) => |d| &mut d.query_first_count,
+            RequestMessage::QueryNext(_) => |d| &mut d.query_next_count,
+            RequestMessage::RegisterNodes(_) => |d| &mut d.register_nodes_count,
+            RequestMessage::UnregisterNodes(_) => |d| &mut d.unregister_nodes_count,
+            _ => return None,
+        };
+        Some(counter)
+    }
+
+    /// Get the numeric ID of the session.
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    /// Get the name of the session, as given by the client.
+    pub fn session_name(&self) -> UAString {
+        self.diagnostics.lock().session_name.clone()
+    }
+
+    /// Get the current diagnostics of the session. Counts of subscriptions,
+    /// monitored items and publish requests are taken from `subscriptions`.
+    pub fn diagnostics(&self, subscriptions: &SubscriptionCache) -> SessionDiagnosticsDataType {
+        let mut diagnostics = self.diagnostics.lock().clone();
+        if let Some(subs) = subscriptions.get_session_subscriptions(self.session_id) {
+            let subs = subs.lock();
+            diagnostics.current_subscriptions_count = subs.len() as u32;
+            diagnostics.current_monitored_items_count = subs.monitored_item_count() as u32;
+            diagnostics.current_publish_requests_in_queue = subs.publish_request_count() as u32;
+        }
+        diagnostics
+    }
+
+    /// Get the current security diagnostics of the session.
+    pub fn security_diagnostics(&self) -> SessionSecurityDiagnosticsDataType {
+        self.security.lock().clone()
+    }
+
+    /// Get the diagnostics of each subscription owned by the session.
+    pub fn subscription_diagnostics(
+        &self,
+        subscriptions: &SubscriptionCache,
+    ) -> Vec<SubscriptionDiagnosticsDataType> {
+        subscriptions
+            .get_session_subscriptions(self.session_id)
+            .map(|s| s.lock().subscription_diagnostics())
+            .unwrap_or_default()
+    }
+
+    /// Sample the diagnostics of the session as a data value.
+    pub fn sample_diagnostics(&self, subscriptions: &SubscriptionCache) -> DataValue {
+        DataValue::new_now(ExtensionObject::from_message(
+            self.diagnostics(subscriptions),
+        ))
+    }
+
+    /// Sample the diagnostics of the subscriptions owned by the session as a data value.
+    pub fn sample_subscription_diagnostics(&self, subscriptions: &SubscriptionCache) -> DataValue {
+        DataValue::new_now(
+            self.subscription_diagnostics(subscriptions)
+                .into_iter()
+                .map(ExtensionObject::from_message)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Sample the security diagnostics of the session as a data value.
+    pub fn sample_security_diagnostics(&self) -> DataValue {
+        DataValue::new_now(ExtensionObject::from_message(self.security_diagnostics()))
+    }
+}
+
+/// A request in progress on a session.
+pub(crate) struct SessionRequest {
+    diagnostics: Arc<SessionDiagnostics>,
+    counter: Option<ServiceCounter>,
+}
+
+impl SessionRequest {
+    /// Record the result of the request.
+    pub(crate) fn finish(self, status: StatusCode) {
+        if !status.is_bad() {
+            return;
+        }
+        let mut diagnostics = self.diagnostics.diagnostics.lock();
+        diagnostics.total_request_count.error_count += 1;
+        if let Some(counter) = self.counter {
+            counter(&mut diagnostics).error_count += 1;
+        }
+        if matches!(
+            status,
+            StatusCode::BadUserAccessDenied | StatusCode::BadSecurityModeInsufficient
+        ) {
+            diagnostics.unauthorized_request_count += 1;
+        }
+    }
+}
@@ -4,6 +4,7 @@
 
 //! Provides server state information, such as status, configuration, running servers and so on.
 
+use std::borrow::Cow;
 use std::sync::atomic::{AtomicU16, AtomicU8, Ordering};
 use std::sync::Arc;
 
@@ -13,8 +14,12 @@ use tracing::{debug, error, warn};
 
 use crate::authenticator::{user_pass_security_policy_id, Password};
 use crate::diagnostics::{ServerDiagnostics, ServerDiagnosticsSummary};
-use crate::node_manager::TypeTreeForUser;
-use opcua_core::comms::url::{hostname_from_url, url_matches_except_host};
+use crate::node_manager::{EventHistoryCapture, TypeTreeForUser};
+use crate::roles::RoleSet;
+use opcua_core::comms::url::{
+    hostname_from_url, is_opc_ua_uds_url, uds_path_from_url, url_matches_except_host,
+};
+use opcua_core::comms::{interceptor::MessageInterceptors, metrics::TransportMetricsHandle};
 use opcua_core::handle::AtomicHandle;
 use opcua_core::sync::RwLock;
 use opcua_crypto::{user_identity, PrivateKey, SecurityPolicy, X509};
@@ -89,6 +94,14 @@ pub struct ServerInfo {
     pub type_loaders: RwLock<TypeLoaderCollection>,
     /// Current server diagnostics.
     pub diagnostics: ServerDiagnostics,
+    /// Hooks for collecting transport metrics.
+    pub transport_metrics: TransportMetricsHandle,
+    /// Interceptors called with every message on client connections.
+    pub interceptors: MessageInterceptors,
+    /// Recorder of event history, if the server has an event history provider.
+    pub event_history: Option<Arc<EventHistoryCapture>>,
+    /// Roles of the server, granted to sessions when they are activated.
+    pub roles: RoleSet,
 }
 
 impl ServerInfo {
@@ -120,7 +133,8 @@ impl ServerInfo {
             }
         }
 
-        if let Ok(hostname) = hostname_from_url(endpoint_url.as_ref()) {
+        let endpoint_url = self.resolve_endpoint_url(endpoint_url.as_ref());
+        if let Ok(hostname) = hostname_from_url(&endpoint_url) {
             if !hostname.eq_ignore_ascii_case(&self.config.tcp_config.host) {
                 debug!("Endpoint url \"{}\" hostname supplied by caller does not match server's hostname \"{}\"", endpoint_url, &self.config.tcp_config.host);
             }
@@ -154,7 +168,7 @@ impl ServerInfo {
     ) -> bool {
         self.config
             .find_endpoint(
-                endpoint_url,
+                &self.resolve_endpoint_url(endpoint_url),
                 &self.base_endpoint(),
                 security_policy,
                 security_mode,
@@ -170,6 +184,7 @@ impl ServerInfo {
         endpoint_url: &str,
     ) -> Option<Vec<EndpointDescription>> {
         debug!("find_endpoint, url = {}", endpoint_url);
+        let endpoint_url = self.resolve_endpoint_url(endpoint_url);
         let base_endpoint_url = self.base_endpoint();
         let endpoints: Vec<EndpointDescription> = self
             .config
@@ -177,7 +192,7 @@ impl ServerInfo {
             .iter()
             .filter(|&(_, e)| {
                 // Test end point's security_policy_uri and matching url
-                url_matches_except_host(&e.endpoint_url(&base_endpoint_url), endpoint_url)
+                url_matches_except_host(&e.endpoint_url(&base_endpoint_url), &endpoint_url)
             })
             .map(|(_, e)| self.new_endpoint_description(e, false))
             .collect();
@@ -285,6 +300,33 @@ impl ServerInfo {
         )
     }
 
+    /// Map an endpoint URL supplied by a client to the URL used to match against the
+    /// configured endpoints.
+    ///
+    /// Clients connecting over `opc.uds` address the server by its socket path, which
+    /// does not carry an endpoint path. If the socket matches the configured
+    /// `uds_path`, this is mapped to the root endpoint on the base endpoint URL.
+    /// Any other URL is returned unchanged.
+    pub fn resolve_endpoint_url<'a>(&self, endpoint_url: &'a str) -> Cow<'a, str> {
+        let Some(uds_path) = self.config.tcp_config.uds_path.as_ref() else {
+            return Cow::Borrowed(endpoint_url);
+        };
+        if !is_opc_ua_uds_url(endpoint_url) {
+            return Cow::Borrowed(endpoint_url);
+        }
+        match uds_path_from_url(endpoint_url) {
+            Ok(path) if &path == uds_path => Cow::Owned(format!("{}/", self.base_endpoint())),
+            _ => {
+                debug!(
+                    "Endpoint url \"{}\" does not refer to the configured socket {}",
+                    endpoint_url,
+                    uds_path.display()
+                );
+                Cow::Borrowed(endpoint_url)
+            }
+        }
+    }
+
     /// Get the server certificate as a byte string.
     pub fn server_certificate_as_byte_string(&self) -> ByteString {
         if let Some(ref server_certificate) = self.server_certificate {
@@ -333,7 +375,7 @@ impl ServerInfo {
     ) -> Result<UserToken, Error> {
         // Get security from endpoint url
         if let Some(endpoint) = self.config.find_endpoint(
-            endpoint_url,
+            &self.resolve_endpoint_url(endpoint_url),
             &self.base_endpoint(),
             security_policy,
             security_mode,
@@ -515,6 +557,7 @@ impl ServerInfo {
                     let security_policy = user_identity_tokens
                         .iter()
                         .find(|t| t.token_type == UserTokenType::Certificate)
+                        .filter(|t| !t.security_policy_uri.is_empty())
                         .map(|t| SecurityPolicy::from_uri(t.security_policy_uri.as_ref()))
                         .unwrap_or_else(|| endpoint.security_policy());
 
@@ -11,6 +11,7 @@
 //! See docs for the main `opcua` crate for details on usage.
 
 pub mod address_space;
+pub mod alarms;
 pub mod authenticator;
 mod builder;
 mod config;
@@ -20,6 +21,7 @@ mod discovery;
 mod identity_token;
 mod info;
 pub mod node_manager;
+pub mod roles;
 mod server;
 mod server_handle;
 mod server_status;
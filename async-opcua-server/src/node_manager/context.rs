@@ -8,7 +8,7 @@ use crate::{
 };
 use opcua_core::{sync::RwLock, trace_read_lock};
 use opcua_nodes::TypeTree;
-use opcua_types::{BrowseDescriptionResultMask, NodeId};
+use opcua_types::{BrowseDescriptionResultMask, MessageSecurityMode, NodeId};
 use parking_lot::lock_api::{RawRwLock, RwLockReadGuard};
 use tracing::debug_span;
 use tracing_futures::Instrument;
@@ -94,6 +94,10 @@ pub struct RequestContext {
     pub authenticator: Arc<dyn AuthManager>,
     /// The current user token.
     pub token: UserToken,
+    /// Roles granted to the current session.
+    pub roles: Arc<[NodeId]>,
+    /// Security mode of the secure channel used by the current session.
+    pub security_mode: MessageSecurityMode,
     /// Index of the current node manager.
     pub current_node_manager_index: usize,
     /// Global type tree object.
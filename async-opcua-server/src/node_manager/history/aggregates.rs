@@ -0,0 +1,932 @@
+use std::ops::{Bound, Range};
+
+use opcua_types::{
+    AggregateConfiguration, DataValue, DateTime, HistoryData, NumericRange, ObjectId,
+    ReadProcessedDetails, StatusCode, StatusCodeValueType, TimestampsToReturn, Variant,
+};
+
+use crate::session::continuation_points::ContinuationPoint;
+
+use super::{
+    provider::{interpolate, prepare_value, value_time, HistoryProvider, HistoryRawQuery},
+    HistoryNode,
+};
+
+/// The aggregates calculated by [`impl_history_read_processed`].
+///
+/// Add these to [`HistoryServerCapabilities::aggregates`](crate::HistoryServerCapabilities::aggregates)
+/// to list them under `ServerCapabilities/AggregateFunctions` if your node managers use
+/// [`impl_history_read_processed`].
+pub const SUPPORTED_AGGREGATES: &[ObjectId] = &[
+    ObjectId::AggregateFunction_Interpolative,
+    ObjectId::AggregateFunction_Average,
+    ObjectId::AggregateFunction_TimeAverage,
+    ObjectId::AggregateFunction_TimeAverage2,
+    ObjectId::AggregateFunction_Total,
+    ObjectId::AggregateFunction_Total2,
+    ObjectId::AggregateFunction_Minimum,
+    ObjectId::AggregateFunction_Maximum,
+    ObjectId::AggregateFunction_MinimumActualTime,
+    ObjectId::AggregateFunction_MaximumActualTime,
+    ObjectId::AggregateFunction_Range,
+    ObjectId::AggregateFunction_Minimum2,
+    ObjectId::AggregateFunction_Maximum2,
+    ObjectId::AggregateFunction_MinimumActualTime2,
+    ObjectId::AggregateFunction_MaximumActualTime2,
+    ObjectId::AggregateFunction_Range2,
+    ObjectId::AggregateFunction_Count,
+    ObjectId::AggregateFunction_NumberOfTransitions,
+    ObjectId::AggregateFunction_Start,
+    ObjectId::AggregateFunction_End,
+    ObjectId::AggregateFunction_Delta,
+    ObjectId::AggregateFunction_StartBound,
+    ObjectId::AggregateFunction_EndBound,
+    ObjectId::AggregateFunction_DeltaBounds,
+    ObjectId::AggregateFunction_DurationGood,
+    ObjectId::AggregateFunction_DurationBad,
+    ObjectId::AggregateFunction_PercentGood,
+    ObjectId::AggregateFunction_PercentBad,
+    ObjectId::AggregateFunction_WorstQuality,
+    ObjectId::AggregateFunction_WorstQuality2,
+    ObjectId::AggregateFunction_DurationInStateZero,
+    ObjectId::AggregateFunction_DurationInStateNonZero,
+    ObjectId::AggregateFunction_StandardDeviationSample,
+    ObjectId::AggregateFunction_StandardDeviationPopulation,
+    ObjectId::AggregateFunction_VarianceSample,
+    ObjectId::AggregateFunction_VariancePopulation,
+];
+
+/// The maximum number of values searched outside of an interval for good bounding values.
+const BOUND_SEARCH_LIMIT: usize = 100;
+
+const TICKS_PER_MILLISECOND: f64 = 10_000.0;
+
+/// Continuation point for processed reads, the index of the next interval to calculate.
+struct ProcessedContinuationPoint {
+    index: u64,
+}
+
+/// The processing intervals of a processed read.
+struct Intervals {
+    start: i64,
+    end: i64,
+    length: i64,
+    reverse: bool,
+    count: u64,
+}
+
+/// A single processing interval, in ticks.
+struct Interval {
+    /// Earliest time of the interval, inclusive.
+    start: i64,
+    /// Latest time of the interval, exclusive.
+    end: i64,
+    /// The interval is shorter than the processing interval.
+    partial: bool,
+}
+
+impl Intervals {
+    fn new(details: &ReadProcessedDetails) -> Result<Self, StatusCode> {
+        if details.start_time.is_null()
+            || details.end_time.is_null()
+            || details.start_time == details.end_time
+            || details.processing_interval.is_nan()
+            || details.processing_interval < 0.0
+        {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let start = details.start_time.checked_ticks();
+        let end = details.end_time.checked_ticks();
+        let total = start.abs_diff(end).min(i64::MAX as u64) as i64;
+        if total == 0 {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let length =
+            (details.processing_interval * TICKS_PER_MILLISECOND).min(i64::MAX as f64) as i64;
+        // A processing interval of zero means a single interval covering the entire range.
+        let length = if length == 0 { total } else { length };
+        Ok(Self {
+            start,
+            end,
+            length,
+            reverse: end < start,
+            count: (total as u64 - 1) / length as u64 + 1,
+        })
+    }
+
+    /// Get the interval at `index`, counting from the start time of the read.
+    fn get(&self, index: u64) -> Interval {
+        let offset = (index as i64).saturating_mul(self.length);
+        let (start, end) = if self.reverse {
+            let end = self.start.saturating_sub(offset);
+            (end.saturating_sub(self.length).max(self.end), end)
+        } else {
+            let start = self.start.saturating_add(offset);
+            (start, start.saturating_add(self.length).min(self.end))
+        };
+        Interval {
+            start,
+            end,
+            partial: end - start < self.length,
+        }
+    }
+}
+
+fn ticks(value: &DataValue) -> i64 {
+    value_time(value).checked_ticks()
+}
+
+/// Get a value as a number, booleans are treated as 0 or 1.
+fn numeric(value: &DataValue) -> Option<f64> {
+    match value.value.as_ref()? {
+        Variant::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+        v => v.as_f64(),
+    }
+}
+
+/// Rank status codes by severity, worst first, for the WorstQuality aggregates.
+fn severity_rank(status: StatusCode) -> u8 {
+    if status.is_bad() {
+        2
+    } else if status.is_uncertain() {
+        1
+    } else {
+        0
+    }
+}
+
+/// The result of an aggregate for a single interval.
+struct Outcome {
+    value: Option<Variant>,
+    status: StatusCode,
+    /// Timestamp of the result, if it is not the start of the interval.
+    time: Option<i64>,
+    value_type: StatusCodeValueType,
+}
+
+impl Outcome {
+    fn calculated(value: impl Into<Variant>, status: StatusCode) -> Self {
+        Self {
+            value: Some(value.into()),
+            status,
+            time: None,
+            value_type: StatusCodeValueType::Calculated,
+        }
+    }
+
+    fn status(status: StatusCode) -> Self {
+        Self {
+            value: None,
+            status,
+            time: None,
+            value_type: StatusCodeValueType::Raw,
+        }
+    }
+
+    fn no_data() -> Self {
+        Self::status(StatusCode::BadNoData)
+    }
+
+    /// A value taken from a single raw or interpolated value.
+    fn from_value(value: DataValue) -> Self {
+        let status = value.status();
+        Self {
+            time: Some(ticks(&value)),
+            value: value.value,
+            status,
+            value_type: status.value_type(),
+        }
+    }
+
+    fn at(mut self, time: i64) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// The raw values needed to calculate aggregates for a single interval.
+struct IntervalData<'a> {
+    interval: Interval,
+    config: &'a AggregateConfiguration,
+    /// Values in and around the interval, ordered by time.
+    values: Vec<DataValue>,
+    /// The range of `values` inside the interval.
+    inside: Range<usize>,
+}
+
+impl<'a> IntervalData<'a> {
+    async fn read(
+        provider: &dyn HistoryProvider,
+        node_id: &opcua_types::NodeId,
+        interval: Interval,
+        config: &'a AggregateConfiguration,
+    ) -> Result<Self, StatusCode> {
+        let start = DateTime::from(interval.start);
+        let end = DateTime::from(interval.end);
+        let mut values = provider
+            .read_raw(
+                node_id,
+                &HistoryRawQuery {
+                    start: Bound::Unbounded,
+                    end: Bound::Excluded(start),
+                    reverse: true,
+                    max_values: BOUND_SEARCH_LIMIT,
+                },
+            )
+            .await?;
+        values.reverse();
+        let inside_start = values.len();
+        values.extend(
+            provider
+                .read_raw(
+                    node_id,
+                    &HistoryRawQuery {
+                        start: Bound::Included(start),
+                        end: Bound::Excluded(end),
+                        reverse: false,
+                        max_values: usize::MAX,
+                    },
+                )
+                .await?,
+        );
+        let inside = inside_start..values.len();
+        values.extend(
+            provider
+                .read_raw(
+                    node_id,
+                    &HistoryRawQuery {
+                        start: Bound::Included(end),
+                        end: Bound::Unbounded,
+                        reverse: false,
+                        max_values: BOUND_SEARCH_LIMIT,
+                    },
+                )
+                .await?,
+        );
+        Ok(Self {
+            interval,
+            config,
+            values,
+            inside,
+        })
+    }
+
+    fn inside(&self) -> &[DataValue] {
+        &self.values[self.inside.clone()]
+    }
+
+    /// Whether a value is used in calculations, uncertain values count as good
+    /// unless configured otherwise.
+    fn is_good(&self, value: &DataValue) -> bool {
+        let status = value.status();
+        value.value.is_some()
+            && (status.is_good() || status.is_uncertain() && !self.config.treat_uncertain_as_bad)
+    }
+
+    /// The status of an aggregate given the amount of good and bad data it is calculated from.
+    fn quality(&self, good: f64, bad: f64) -> Option<StatusCode> {
+        let total = good + bad;
+        if total <= 0.0 {
+            return None;
+        }
+        Some(
+            if bad * 100.0 / total >= self.config.percent_data_bad as f64 {
+                StatusCode::Bad
+            } else if good * 100.0 / total >= self.config.percent_data_good as f64 {
+                StatusCode::Good
+            } else {
+                StatusCode::UncertainDataSubNormal
+            },
+        )
+    }
+
+    /// Status of an aggregate based on the number of good and bad values in the interval.
+    fn count_quality(&self) -> Option<StatusCode> {
+        let good = self.inside().iter().filter(|v| self.is_good(v)).count();
+        self.quality(good as f64, (self.inside.len() - good) as f64)
+    }
+
+    /// Status of an aggregate based on how long values in the interval were good or bad.
+    fn time_quality(&self) -> Option<StatusCode> {
+        let (good, bad) = self.durations();
+        self.quality(good as f64, bad as f64)
+    }
+
+    /// The good values in the interval as numbers, fails if any good value is not numeric.
+    fn numeric_values(&self) -> Result<Vec<(&DataValue, f64)>, StatusCode> {
+        self.inside()
+            .iter()
+            .filter(|v| self.is_good(v))
+            .map(|v| {
+                numeric(v)
+                    .map(|n| (v, n))
+                    .ok_or(StatusCode::BadAggregateInvalidInputs)
+            })
+            .collect()
+    }
+
+    /// The periods of the interval each value is in effect for, assuming values are stepped.
+    /// Time before the first known value is not included.
+    fn segments(&self) -> Vec<(i64, i64, &DataValue)> {
+        let first = self.values[..self.inside.start]
+            .iter()
+            .rposition(|v| ticks(v) <= self.interval.start)
+            .unwrap_or(self.inside.start);
+        let mut segments = Vec::new();
+        for idx in first..self.inside.end {
+            let start = ticks(&self.values[idx]).max(self.interval.start);
+            let end = self
+                .values
+                .get(idx + 1)
+                .map(ticks)
+                .unwrap_or(self.interval.end)
+                .min(self.interval.end);
+            if end > start {
+                segments.push((start, end, &self.values[idx]));
+            }
+        }
+        segments
+    }
+
+    /// The time in ticks the values in the interval were good and bad.
+    fn durations(&self) -> (i64, i64) {
+        self.segments()
+            .into_iter()
+            .fold((0, 0), |(good, bad), (start, end, value)| {
+                if self.is_good(value) {
+                    (good + end - start, bad)
+                } else {
+                    (good, bad + end - start)
+                }
+            })
+    }
+
+    /// The value at `time`, interpolated from the closest good values around it.
+    fn interpolated_bound(&self, time: i64) -> Option<DataValue> {
+        let prior_idx = self
+            .values
+            .iter()
+            .rposition(|v| ticks(v) <= time && self.is_good(v))?;
+        let prior = &self.values[prior_idx];
+        if ticks(prior) == time {
+            return Some(prior.clone());
+        }
+        let next = self.values[prior_idx + 1..]
+            .iter()
+            .find(|v| self.is_good(v));
+        let (value, status) = match next {
+            Some(next) => {
+                let status = if prior.status().is_good() && next.status().is_good() {
+                    StatusCode::Good
+                } else {
+                    StatusCode::UncertainDataSubNormal
+                };
+                (interpolate(prior, next, DateTime::from(time)), status)
+            }
+            None => {
+                // There is no later good value, so the value is extrapolated.
+                let earlier = self.values[..prior_idx]
+                    .iter()
+                    .rev()
+                    .find(|v| self.is_good(v));
+                let value = match earlier {
+                    Some(earlier) if self.config.use_sloped_extrapolation => {
+                        interpolate(earlier, prior, DateTime::from(time))
+                    }
+                    _ => prior.value.clone(),
+                };
+                (value, StatusCode::UncertainDataSubNormal)
+            }
+        };
+        Some(DataValue {
+            value,
+            status: Some(status.set_value_type(StatusCodeValueType::Interpolated)),
+            source_timestamp: Some(DateTime::from(time)),
+            ..Default::default()
+        })
+    }
+
+    /// The value at `time`, from the closest values around it regardless of their status.
+    /// The value is interpolated if both values are good, otherwise the earlier value is
+    /// used, along with its status.
+    fn simple_bound(&self, time: i64) -> Option<DataValue> {
+        let prior_idx = self.values.iter().rposition(|v| ticks(v) <= time)?;
+        let prior = &self.values[prior_idx];
+        if ticks(prior) == time {
+            return Some(prior.clone());
+        }
+        let (value, status) = match self.values.get(prior_idx + 1) {
+            Some(next) if self.is_good(prior) && self.is_good(next) => {
+                let status = if prior.status().is_good() && next.status().is_good() {
+                    StatusCode::Good
+                } else {
+                    StatusCode::UncertainDataSubNormal
+                };
+                (interpolate(prior, next, DateTime::from(time)), status)
+            }
+            _ => (prior.value.clone(), prior.status()),
+        };
+        Some(DataValue {
+            value,
+            status: Some(status.set_value_type(StatusCodeValueType::Interpolated)),
+            source_timestamp: Some(DateTime::from(time)),
+            ..Default::default()
+        })
+    }
+
+    fn average(&self) -> Result<Outcome, StatusCode> {
+        let Some(status) = self.count_quality() else {
+            return Ok(Outcome::no_data());
+        };
+        let values = self.numeric_values()?;
+        if status.is_bad() || values.is_empty() {
+            return Ok(Outcome::status(StatusCode::Bad));
+        }
+        let sum: f64 = values.iter().map(|(_, v)| v).sum();
+        Ok(Outcome::calculated(sum / values.len() as f64, status))
+    }
+
+    /// Time weighted average using interpolated bounds, and the length in seconds of the
+    /// interval it covers.
+    fn time_average(&self) -> Result<Option<f64>, StatusCode> {
+        let mut points = Vec::new();
+        if let Some(bound) = self.interpolated_bound(self.interval.start) {
+            if let Some(v) = numeric(&bound) {
+                points.push((self.interval.start, v));
+            }
+        }
+        for (value, v) in self.numeric_values()? {
+            let time = ticks(value);
+            if time > self.interval.start {
+                points.push((time, v));
+            }
+        }
+        if let Some(bound) = self.interpolated_bound(self.interval.end) {
+            if let Some(v) = numeric(&bound) {
+                points.push((self.interval.end, v));
+            }
+        }
+        let Some(&(first_time, first)) = points.first() else {
+            return Ok(None);
+        };
+        let span = points
+            .last()
+            .map(|(t, _)| t - first_time)
+            .unwrap_or_default();
+        if span <= 0 {
+            return Ok(Some(first));
+        }
+        let area: f64 = points
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0) as f64 * (w[0].1 + w[1].1) / 2.0)
+            .sum();
+        Ok(Some(area / span as f64))
+    }
+
+    /// Integral of the value over the time it is good, using simple bounds, in value-seconds,
+    /// and the time in seconds it is good.
+    fn good_area(&self) -> Result<(f64, f64), StatusCode> {
+        let mut points: Vec<(i64, Option<f64>)> = Vec::new();
+        let mut push = |time: i64, value: &DataValue| -> Result<(), StatusCode> {
+            let v = if self.is_good(value) {
+                Some(numeric(value).ok_or(StatusCode::BadAggregateInvalidInputs)?)
+            } else {
+                None
+            };
+            points.push((time, v));
+            Ok(())
+        };
+        if let Some(bound) = self.simple_bound(self.interval.start) {
+            push(self.interval.start, &bound)?;
+        }
+        for value in self.inside() {
+            let time = ticks(value);
+            if time > self.interval.start {
+                push(time, value)?;
+            }
+        }
+        if let Some(bound) = self.simple_bound(self.interval.end) {
+            push(self.interval.end, &bound)?;
+        }
+        let mut area = 0.0;
+        let mut good_time = 0;
+        for w in points.windows(2) {
+            let ((t0, v0), (t1, v1)) = (w[0], w[1]);
+            let Some(v0) = v0 else {
+                continue;
+            };
+            let dt = t1 - t0;
+            // Slope towards the next value if it is good, otherwise hold the value.
+            area += dt as f64 * (v0 + v1.unwrap_or(v0)) / 2.0;
+            good_time += dt;
+        }
+        let seconds = TICKS_PER_MILLISECOND * 1000.0;
+        Ok((area / seconds, good_time as f64 / seconds))
+    }
+
+    /// Find the extreme value of the interval, the minimum if `max` is false. If
+    /// `with_bound` is set, the simple bound at the start of the interval is included.
+    fn extreme(
+        &self,
+        max: bool,
+        actual_time: bool,
+        with_bound: bool,
+    ) -> Result<Outcome, StatusCode> {
+        let status = if with_bound {
+            self.time_quality()
+        } else {
+            self.count_quality()
+        };
+        let Some(status) = status else {
+            return Ok(Outcome::no_data());
+        };
+        // The bound is only needed if there is no raw value at the start of the interval.
+        let bound = if with_bound && self.inside().first().map(ticks) != Some(self.interval.start) {
+            self.simple_bound(self.interval.start)
+                .filter(|b| self.is_good(b))
+        } else {
+            None
+        };
+        let mut candidates = self.numeric_values()?;
+        if let Some(bound) = &bound {
+            if let Some(v) = numeric(bound) {
+                candidates.insert(0, (bound, v));
+            }
+        }
+        if status.is_bad() {
+            return Ok(Outcome::status(StatusCode::Bad));
+        }
+        let best = candidates.into_iter().reduce(|best, c| {
+            if (max && c.1 > best.1) || (!max && c.1 < best.1) {
+                c
+            } else {
+                best
+            }
+        });
+        let Some((value, _)) = best else {
+            return Ok(Outcome::status(StatusCode::Bad));
+        };
+        let outcome = Outcome::calculated(value.value.clone().unwrap_or_default(), status);
+        Ok(if actual_time {
+            outcome.at(ticks(value))
+        } else {
+            outcome
+        })
+    }
+
+    fn range(&self, with_bound: bool) -> Result<Outcome, StatusCode> {
+        let min = self.extreme(false, false, with_bound)?;
+        let max = self.extreme(true, false, with_bound)?;
+        let (Some(min_v), Some(max_v)) = (
+            min.value.as_ref().and_then(Variant::as_f64),
+            max.value.as_ref().and_then(Variant::as_f64),
+        ) else {
+            return Ok(min);
+        };
+        Ok(Outcome::calculated(max_v - min_v, min.status))
+    }
+
+    fn variance(&self, sample: bool) -> Result<Option<Outcome>, StatusCode> {
+        let Some(status) = self.count_quality() else {
+            return Ok(None);
+        };
+        let values = self.numeric_values()?;
+        let n = values.len();
+        if status.is_bad() || n == 0 || (sample && n < 2) {
+            return Ok(None);
+        }
+        let mean = values.iter().map(|(_, v)| v).sum::<f64>() / n as f64;
+        let squares: f64 = values.iter().map(|(_, v)| (v - mean).powi(2)).sum();
+        let divisor = if sample { n - 1 } else { n };
+        Ok(Some(Outcome::calculated(squares / divisor as f64, status)))
+    }
+
+    fn calculate(&self, aggregate: ObjectId) -> Result<Outcome, StatusCode> {
+        let interval_ticks = (self.interval.end - self.interval.start) as f64;
+        let outcome = match aggregate {
+            ObjectId::AggregateFunction_Interpolative => {
+                match self.interpolated_bound(self.interval.start) {
+                    Some(v) => Outcome::from_value(v).at(self.interval.start),
+                    None => Outcome::no_data(),
+                }
+            }
+            ObjectId::AggregateFunction_Average => self.average()?,
+            ObjectId::AggregateFunction_TimeAverage | ObjectId::AggregateFunction_Total => {
+                let Some(status) = self.time_quality() else {
+                    return Ok(Outcome::no_data());
+                };
+                match self.time_average()? {
+                    _ if status.is_bad() => Outcome::status(StatusCode::Bad),
+                    Some(avg) if aggregate == ObjectId::AggregateFunction_Total => {
+                        let seconds = interval_ticks / (TICKS_PER_MILLISECOND * 1000.0);
+                        Outcome::calculated(avg * seconds, status)
+                    }
+                    Some(avg) => Outcome::calculated(avg, status),
+                    None => Outcome::no_data(),
+                }
+            }
+            ObjectId::AggregateFunction_TimeAverage2 | ObjectId::AggregateFunction_Total2 => {
+                let Some(status) = self.time_quality() else {
+                    return Ok(Outcome::no_data());
+                };
+                let (area, good_seconds) = self.good_area()?;
+                if status.is_bad() {
+                    Outcome::status(StatusCode::Bad)
+                } else if aggregate == ObjectId::AggregateFunction_Total2 {
+                    Outcome::calculated(area, status)
+                } else if good_seconds > 0.0 {
+                    Outcome::calculated(area / good_seconds, status)
+                } else {
+                    Outcome::no_data()
+                }
+            }
+            ObjectId::AggregateFunction_Minimum => self.extreme(false, false, false)?,
+            ObjectId::AggregateFunction_Maximum => self.extreme(true, false, false)?,
+            ObjectId::AggregateFunction_MinimumActualTime => self.extreme(false, true, false)?,
+            ObjectId::AggregateFunction_MaximumActualTime => self.extreme(true, true, false)?,
+            ObjectId::AggregateFunction_Minimum2 => self.extreme(false, false, true)?,
+            ObjectId::AggregateFunction_Maximum2 => self.extreme(true, false, true)?,
+            ObjectId::AggregateFunction_MinimumActualTime2 => self.extreme(false, true, true)?,
+            ObjectId::AggregateFunction_MaximumActualTime2 => self.extreme(true, true, true)?,
+            ObjectId::AggregateFunction_Range => self.range(false)?,
+            ObjectId::AggregateFunction_Range2 => self.range(true)?,
+            ObjectId::AggregateFunction_Count => {
+                let count = self.inside().iter().filter(|v| self.is_good(v)).count();
+                let status = self.count_quality().unwrap_or(StatusCode::Good);
+                Outcome::calculated(count as i32, status)
+            }
+            ObjectId::AggregateFunction_NumberOfTransitions => {
+                let mut last = self.values[..self.inside.start]
+                    .iter()
+                    .rev()
+                    .find(|v| self.is_good(v))
+                    .and_then(|v| v.value.as_ref());
+                let mut transitions = 0;
+                for value in self.inside().iter().filter(|v| self.is_good(v)) {
+                    if last.is_some_and(|l| Some(l) != value.value.as_ref()) {
+                        transitions += 1;
+                    }
+                    last = value.value.as_ref();
+                }
+                let status = self.count_quality().unwrap_or(StatusCode::Good);
+                Outcome::calculated(transitions, status)
+            }
+            ObjectId::AggregateFunction_Start => match self.inside().first() {
+                Some(v) => Outcome::from_value(v.clone()),
+                None => Outcome::no_data(),
+            },
+            ObjectId::AggregateFunction_End => match self.inside().last() {
+                Some(v) => Outcome::from_value(v.clone()),
+                None => Outcome::no_data(),
+            },
+            ObjectId::AggregateFunction_Delta => {
+                let Some(status) = self.count_quality() else {
+                    return Ok(Outcome::no_data());
+                };
+                let values = self.numeric_values()?;
+                match (values.first(), values.last()) {
+                    _ if status.is_bad() => Outcome::status(StatusCode::Bad),
+                    (Some((_, first)), Some((_, last))) => {
+                        Outcome::calculated(last - first, status)
+                    }
+                    _ => Outcome::status(StatusCode::Bad),
+                }
+            }
+            ObjectId::AggregateFunction_StartBound => {
+                match self.simple_bound(self.interval.start) {
+                    Some(v) => Outcome::from_value(v).at(self.interval.start),
+                    None => Outcome::no_data(),
+                }
+            }
+            ObjectId::AggregateFunction_EndBound => match self.simple_bound(self.interval.end) {
+                Some(v) => Outcome::from_value(v).at(self.interval.end),
+                None => Outcome::no_data(),
+            },
+            ObjectId::AggregateFunction_DeltaBounds => {
+                let (Some(start), Some(end)) = (
+                    self.simple_bound(self.interval.start),
+                    self.simple_bound(self.interval.end),
+                ) else {
+                    return Ok(Outcome::no_data());
+                };
+                if !self.is_good(&start) || !self.is_good(&end) {
+                    return Ok(Outcome::status(StatusCode::Bad));
+                }
+                let (Some(s), Some(e)) = (numeric(&start), numeric(&end)) else {
+                    return Err(StatusCode::BadAggregateInvalidInputs);
+                };
+                let status = if start.status().is_good() && end.status().is_good() {
+                    StatusCode::Good
+                } else {
+                    StatusCode::UncertainDataSubNormal
+                };
+                Outcome::calculated(e - s, status)
+            }
+            ObjectId::AggregateFunction_DurationGood
+            | ObjectId::AggregateFunction_DurationBad
+            | ObjectId::AggregateFunction_PercentGood
+            | ObjectId::AggregateFunction_PercentBad => {
+                let (good, _) = self.durations();
+                // Time without any known value counts as bad.
+                let duration = match aggregate {
+                    ObjectId::AggregateFunction_DurationGood
+                    | ObjectId::AggregateFunction_PercentGood => good,
+                    _ => self.interval.end - self.interval.start - good,
+                } as f64;
+                if matches!(
+                    aggregate,
+                    ObjectId::AggregateFunction_DurationGood
+                        | ObjectId::AggregateFunction_DurationBad
+                ) {
+                    Outcome::calculated(duration / TICKS_PER_MILLISECOND, StatusCode::Good)
+                } else {
+                    Outcome::calculated(duration * 100.0 / interval_ticks, StatusCode::Good)
+                }
+            }
+            ObjectId::AggregateFunction_WorstQuality
+            | ObjectId::AggregateFunction_WorstQuality2 => {
+                let bound = (aggregate == ObjectId::AggregateFunction_WorstQuality2)
+                    .then(|| self.values[..self.inside.start].last())
+                    .flatten();
+                let worst = bound
+                    .into_iter()
+                    .chain(self.inside())
+                    .map(|v| v.status())
+                    .reduce(|worst, s| {
+                        if severity_rank(s) > severity_rank(worst) {
+                            s
+                        } else {
+                            worst
+                        }
+                    });
+                match worst {
+                    Some(worst) => Outcome::calculated(worst, StatusCode::Good),
+                    None => Outcome::no_data(),
+                }
+            }
+            ObjectId::AggregateFunction_DurationInStateZero
+            | ObjectId::AggregateFunction_DurationInStateNonZero => {
+                let Some(status) = self.time_quality() else {
+                    return Ok(Outcome::no_data());
+                };
+                let zero = aggregate == ObjectId::AggregateFunction_DurationInStateZero;
+                let mut duration = 0;
+                for (start, end, value) in self.segments() {
+                    if !self.is_good(value) {
+                        continue;
+                    }
+                    let v = numeric(value).ok_or(StatusCode::BadAggregateInvalidInputs)?;
+                    if (v == 0.0) == zero {
+                        duration += end - start;
+                    }
+                }
+                Outcome::calculated(duration as f64 / TICKS_PER_MILLISECOND, status)
+            }
+            ObjectId::AggregateFunction_VarianceSample
+            | ObjectId::AggregateFunction_VariancePopulation
+            | ObjectId::AggregateFunction_StandardDeviationSample
+            | ObjectId::AggregateFunction_StandardDeviationPopulation => {
+                let sample = matches!(
+                    aggregate,
+                    ObjectId::AggregateFunction_VarianceSample
+                        | ObjectId::AggregateFunction_StandardDeviationSample
+                );
+                let Some(mut outcome) = self.variance(sample)? else {
+                    return Ok(match self.count_quality() {
+                        Some(_) => Outcome::status(StatusCode::Bad),
+                        None => Outcome::no_data(),
+                    });
+                };
+                if matches!(
+                    aggregate,
+                    ObjectId::AggregateFunction_StandardDeviationSample
+                        | ObjectId::AggregateFunction_StandardDeviationPopulation
+                ) {
+                    outcome.value = outcome
+                        .value
+                        .and_then(|v| v.as_f64())
+                        .map(|v| Variant::Double(v.sqrt()));
+                }
+                outcome
+            }
+            _ => return Err(StatusCode::BadAggregateNotSupported),
+        };
+        Ok(outcome)
+    }
+}
+
+/// Implement the history read processed service for a single node using
+/// a [`HistoryProvider`], writing the result to `node`.
+///
+/// This calculates the aggregate given by [`HistoryNode::aggregate_type`] over the raw values
+/// of the provider, for each processing interval, using the aggregate configuration of the
+/// request or [`HistoryProvider::aggregate_configuration`]. See [`SUPPORTED_AGGREGATES`]
+/// for the supported aggregates. Values are timestamped with the earliest time of their
+/// interval, also when reading in reverse.
+///
+/// Range, Delta and DeltaBounds are calculated as `Double`, as are all aggregates that
+/// average values. Minimum and Maximum keep the type of the raw values.
+pub async fn impl_history_read_processed(
+    provider: &dyn HistoryProvider,
+    details: &ReadProcessedDetails,
+    node: &mut HistoryNode,
+    timestamps_to_return: TimestampsToReturn,
+) {
+    if timestamps_to_return == TimestampsToReturn::Neither {
+        node.set_status(StatusCode::BadTimestampsToReturnInvalid);
+        return;
+    }
+    let Some(aggregate) = node.aggregate_type() else {
+        node.set_status(StatusCode::BadAggregateListMismatch);
+        return;
+    };
+    let aggregate = match aggregate.as_object_id() {
+        Ok(a) if SUPPORTED_AGGREGATES.contains(&a) => a,
+        _ => {
+            node.set_status(StatusCode::BadAggregateNotSupported);
+            return;
+        }
+    };
+    let intervals = match Intervals::new(details) {
+        Ok(i) => i,
+        Err(e) => {
+            node.set_status(e);
+            return;
+        }
+    };
+    let config = if details
+        .aggregate_configuration
+        .use_server_capabilities_defaults
+    {
+        provider.aggregate_configuration(node.node_id())
+    } else {
+        details.aggregate_configuration.clone()
+    };
+    if config.percent_data_bad > 100
+        || config.percent_data_good > 100
+        || (config.percent_data_bad as u16 + config.percent_data_good as u16) < 100
+    {
+        node.set_status(StatusCode::BadAggregateConfigurationRejected);
+        return;
+    }
+
+    let start_index = match node.continuation_point() {
+        Some(cp) => match cp.get::<ProcessedContinuationPoint>() {
+            Some(cp) => cp.index,
+            None => {
+                node.set_status(StatusCode::BadContinuationPointInvalid);
+                return;
+            }
+        },
+        None => 0,
+    };
+    let end_index = start_index
+        .saturating_add(provider.max_values_per_node().max(1) as u64)
+        .min(intervals.count);
+
+    let node_id = node.node_id().clone();
+    let mut values = Vec::new();
+    for index in start_index..end_index {
+        let interval = intervals.get(index);
+        let (start, partial) = (interval.start, interval.partial);
+        let outcome = match IntervalData::read(provider, &node_id, interval, &config).await {
+            Ok(data) => data.calculate(aggregate),
+            Err(e) => Err(e),
+        };
+        let outcome = match outcome {
+            Ok(o) => o,
+            Err(e) => {
+                node.set_status(e);
+                return;
+            }
+        };
+        let mut status = outcome.status;
+        if !status.is_bad() {
+            status = status
+                .set_value_type(outcome.value_type)
+                .set_partial(partial);
+        }
+        let time = DateTime::from(outcome.time.unwrap_or(start));
+        values.push(prepare_value(
+            DataValue {
+                value: if status.is_bad() { None } else { outcome.value },
+                status: Some(status),
+                source_timestamp: Some(time),
+                server_timestamp: Some(time),
+                ..Default::default()
+            },
+            &NumericRange::None,
+            timestamps_to_return,
+        ));
+    }
+
+    node.set_result(HistoryData {
+        data_values: Some(values),
+    });
+    if end_index < intervals.count {
+        node.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+            ProcessedContinuationPoint { index: end_index },
+        ))));
+    }
+    node.set_status(StatusCode::Good);
+}
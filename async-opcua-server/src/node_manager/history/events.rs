@@ -0,0 +1,504 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use hashbrown::HashMap;
+use opcua_core::{
+    sync::RwLock,
+    {trace_read_lock, trace_write_lock},
+};
+use opcua_nodes::{DefaultTypeTree, Event, EventField, ParsedEventFilter, TypeTree};
+use opcua_types::{
+    AttributeId, BrowseDirection, DataEncoding, DataTypeId, DateTime, EventFilter, ExtensionObject,
+    HistoryEvent, HistoryEventFieldList, NodeId, NumericRange, ObjectId, QualifiedName,
+    ReadEventDetails, ReferenceTypeId, StatusCode, TimestampsToReturn, VariableId, VariableTypeId,
+    Variant,
+};
+use tracing::warn;
+
+use crate::{
+    address_space::{AddressSpace, EventNotifier, NodeType, VariableBuilder},
+    node_manager::{RequestContext, TypeTreeForUserStatic},
+    session::continuation_points::ContinuationPoint,
+};
+
+use super::{
+    provider::{query_range, read_page, HistoryRawQuery},
+    HistoryNode,
+};
+
+/// A single field of a [`HistoricalEvent`].
+#[derive(Debug, Clone)]
+pub struct HistoricalEventField {
+    /// Browse path of the field, from the event type.
+    pub browse_path: Vec<QualifiedName>,
+    /// Attribute of the field, usually `Value`.
+    pub attribute_id: AttributeId,
+    /// Value of the field when the event was recorded.
+    pub value: Variant,
+}
+
+/// An event stored in history, with the fields selected by the historical event filter
+/// of its notifier when it was recorded.
+///
+/// Historical events implement [`Event`], so they can be filtered like live events.
+/// Fields that were not recorded are empty.
+#[derive(Debug, Clone)]
+pub struct HistoricalEvent {
+    /// Time the event occurred.
+    pub time: DateTime,
+    /// Type of the event.
+    pub event_type: NodeId,
+    /// Recorded fields of the event.
+    pub fields: Vec<HistoricalEventField>,
+}
+
+impl HistoricalEvent {
+    /// Record the fields of `event` selected by `filter`, or return `None` if the
+    /// event does not pass the where clause of `filter`.
+    pub fn capture(
+        event: &dyn Event,
+        filter: &ParsedEventFilter,
+        type_tree: &dyn TypeTree,
+    ) -> Option<Self> {
+        let values = filter.evaluate(event, 0, type_tree)?.event_fields;
+        let time = if event.time().is_null() {
+            DateTime::now()
+        } else {
+            *event.time()
+        };
+        Some(Self {
+            time,
+            event_type: event.event_type_id().clone(),
+            fields: filter
+                .select_clauses()
+                .iter()
+                .zip(values.into_iter().flatten())
+                .map(|(clause, value)| HistoricalEventField {
+                    browse_path: clause.browse_path.clone(),
+                    attribute_id: clause.attribute_id,
+                    value,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl EventField for HistoricalEvent {
+    fn get_value(
+        &self,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        remaining_path: &[QualifiedName],
+    ) -> Variant {
+        let value = match self
+            .fields
+            .iter()
+            .find(|f| f.attribute_id == attribute_id && f.browse_path == remaining_path)
+        {
+            Some(field) => field.value.clone(),
+            // The event type and time are always known, even if they were not selected.
+            None if attribute_id == AttributeId::Value
+                && remaining_path.len() == 1
+                && remaining_path[0].namespace_index == 0 =>
+            {
+                match remaining_path[0].name.as_ref() {
+                    "EventType" => self.event_type.clone().into(),
+                    "Time" => self.time.into(),
+                    _ => return Variant::Empty,
+                }
+            }
+            None => return Variant::Empty,
+        };
+        value.range_of_owned(index_range).unwrap_or(Variant::Empty)
+    }
+}
+
+impl Event for HistoricalEvent {
+    fn get_field(
+        &self,
+        _type_definition_id: &NodeId,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        // Fields are only recorded if they were set on the event, so the type
+        // definition they are read from does not matter.
+        self.get_value(attribute_id, index_range, browse_path)
+    }
+
+    fn time(&self) -> &DateTime {
+        &self.time
+    }
+
+    fn event_type_id(&self) -> &NodeId {
+        &self.event_type
+    }
+}
+
+/// The filter of a history read events request, given to
+/// [`EventHistoryProvider::read_events`] so that providers only return matching events.
+pub struct HistoryEventFilter<'a> {
+    filter: Option<&'a ParsedEventFilter>,
+    type_tree: Option<Arc<dyn TypeTreeForUserStatic>>,
+}
+
+impl<'a> HistoryEventFilter<'a> {
+    /// Create a filter matching events passing the where clause of `filter`, evaluated
+    /// with the type tree given by `type_tree`.
+    pub fn new(filter: &'a ParsedEventFilter, type_tree: Arc<dyn TypeTreeForUserStatic>) -> Self {
+        Self {
+            filter: Some(filter),
+            type_tree: Some(type_tree),
+        }
+    }
+
+    /// Create a filter matching all events.
+    pub fn all() -> Self {
+        Self {
+            filter: None,
+            type_tree: None,
+        }
+    }
+
+    /// Collect up to `max` events from `events` that pass the filter.
+    pub fn filter<'b>(
+        &self,
+        events: impl Iterator<Item = &'b HistoricalEvent>,
+        max: usize,
+    ) -> Vec<HistoricalEvent> {
+        let (Some(filter), Some(type_tree)) = (self.filter, &self.type_tree) else {
+            return events.take(max).cloned().collect();
+        };
+        let type_tree = type_tree.get_type_tree();
+        events
+            .filter(|e| filter.matches(*e, type_tree.get()))
+            .take(max)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Storage of historical events, used to implement the `HistoryRead` service
+/// with `ReadEventDetails`.
+///
+/// A provider only needs to be able to return the events of a notifier in a time range
+/// passing a filter, paging with continuation points and selecting the fields of each
+/// event is handled by [`impl_history_read_events`].
+/// See [`InMemoryEventHistoryProvider`](super::InMemoryEventHistoryProvider) for a simple
+/// implementation.
+#[async_trait]
+pub trait EventHistoryProvider: Send + Sync + 'static {
+    /// Read up to `query.max_values` events of the notifier `notifier` passing `filter`,
+    /// with a time in the range of `query`, ordered by time.
+    async fn read_events(
+        &self,
+        notifier: &NodeId,
+        query: &HistoryRawQuery,
+        filter: &HistoryEventFilter<'_>,
+    ) -> Result<Vec<HistoricalEvent>, StatusCode>;
+
+    /// Record new events for the given notifiers.
+    async fn record_events(&self, events: Vec<(NodeId, HistoricalEvent)>)
+        -> Result<(), StatusCode>;
+
+    /// The maximum number of events returned for a notifier in a single read, more
+    /// events are returned with continuation points.
+    fn max_events_per_node(&self) -> usize {
+        10_000
+    }
+}
+
+/// Read up to `query.max_values` events passing `filter`, returning their time and
+/// selected fields.
+async fn read_matching(
+    provider: &dyn EventHistoryProvider,
+    context: &RequestContext,
+    notifier: &NodeId,
+    filter: &ParsedEventFilter,
+    query: HistoryRawQuery,
+) -> Result<Vec<(DateTime, Option<Vec<Variant>>)>, StatusCode> {
+    let type_tree = context.type_tree_getter.get_type_tree_static(context);
+    let events = provider
+        .read_events(
+            notifier,
+            &query,
+            &HistoryEventFilter::new(filter, type_tree),
+        )
+        .await?;
+    Ok(events
+        .iter()
+        .map(|e| (e.time, filter.select(e, 0).event_fields))
+        .collect())
+}
+
+/// Implement the history read events service for a single notifier using
+/// an [`EventHistoryProvider`], writing the result to `node`.
+///
+/// `filter` is the parsed filter of `details`. This handles continuation points
+/// and the number of events per node.
+pub async fn impl_history_read_events(
+    provider: &dyn EventHistoryProvider,
+    context: &RequestContext,
+    details: &ReadEventDetails,
+    filter: &ParsedEventFilter,
+    node: &mut HistoryNode,
+) {
+    let (start, end, reverse) = match query_range(
+        details.start_time,
+        details.end_time,
+        details.num_values_per_node,
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            node.set_status(e);
+            return;
+        }
+    };
+    let max_per_node = provider.max_events_per_node().max(1);
+    let per_node = match details.num_values_per_node as usize {
+        0 => max_per_node,
+        n => n.min(max_per_node),
+    };
+    let query = HistoryRawQuery {
+        start,
+        end,
+        reverse,
+        max_values: per_node,
+    };
+
+    let notifier = node.node_id().clone();
+    let res = read_page(
+        node,
+        query,
+        per_node,
+        |q| read_matching(provider, context, &notifier, filter, q),
+        |(time, _)| *time,
+    )
+    .await;
+    match res {
+        Ok((events, cp)) => {
+            node.set_result(HistoryEvent {
+                events: Some(
+                    events
+                        .into_iter()
+                        .map(|(_, event_fields)| HistoryEventFieldList { event_fields })
+                        .collect(),
+                ),
+            });
+            node.set_next_continuation_point(cp.map(|cp| ContinuationPoint::new(Box::new(cp))));
+            node.set_status(StatusCode::Good);
+        }
+        Err(e) => node.set_status(e),
+    }
+}
+
+/// Records events raised on the server in an [`EventHistoryProvider`].
+///
+/// Events are only recorded for notifiers added with [`EventHistoryCapture::historize`],
+/// using the `HistoricalEventFilter` of the notifier to select which events and fields
+/// are stored. Events raised on any node are also recorded for the `Server` object,
+/// if it is historized.
+pub struct EventHistoryCapture {
+    provider: Arc<dyn EventHistoryProvider>,
+    type_tree: Arc<RwLock<DefaultTypeTree>>,
+    notifiers: RwLock<HashMap<NodeId, ParsedEventFilter>>,
+}
+
+impl EventHistoryCapture {
+    pub(crate) fn new(
+        provider: Arc<dyn EventHistoryProvider>,
+        type_tree: Arc<RwLock<DefaultTypeTree>>,
+    ) -> Self {
+        Self {
+            provider,
+            type_tree,
+            notifiers: Default::default(),
+        }
+    }
+
+    /// Get the provider events are recorded in.
+    pub fn provider(&self) -> &Arc<dyn EventHistoryProvider> {
+        &self.provider
+    }
+
+    /// Start recording events raised on `notifier`, selected by `filter`.
+    ///
+    /// The filter is stored in the `HistoricalEventFilter` property of the notifier in
+    /// `address_space`, which is added if it does not exist, and `HISTORY_READ` is set
+    /// on the event notifier of the node.
+    ///
+    /// Fails if the filter is invalid or the notifier is not an object or view
+    /// in `address_space`.
+    pub fn historize(
+        &self,
+        address_space: &mut AddressSpace,
+        notifier: impl Into<NodeId>,
+        filter: EventFilter,
+    ) -> Result<(), StatusCode> {
+        let notifier = notifier.into();
+        let parsed = ParsedEventFilter::new(filter.clone(), &*trace_read_lock!(self.type_tree)).1?;
+        match address_space.find_mut(&notifier) {
+            Some(NodeType::Object(o)) => {
+                o.set_event_notifier(o.event_notifier() | EventNotifier::HISTORY_READ)
+            }
+            Some(NodeType::View(v)) => {
+                v.set_event_notifier(v.event_notifier() | EventNotifier::HISTORY_READ)
+            }
+            _ => return Err(StatusCode::BadNodeIdUnknown),
+        }
+
+        let value = Variant::from(ExtensionObject::from_message(filter));
+        let server_id: NodeId = ObjectId::Server.into();
+        let property = match self.find_filter_property(address_space, &notifier) {
+            Some(id) => id,
+            // The server object uses the standard `HistoricalEventFilter` node.
+            None if notifier == server_id
+                && address_space.node_exists(&VariableId::HistoricalEventFilter.into()) =>
+            {
+                let id = VariableId::HistoricalEventFilter.into();
+                address_space.insert_reference(&notifier, &id, ReferenceTypeId::HasProperty);
+                id
+            }
+            None => {
+                let mut id = NodeId::next_numeric(notifier.namespace);
+                while address_space.node_exists(&id) {
+                    id = NodeId::next_numeric(notifier.namespace);
+                }
+                VariableBuilder::new(&id, "HistoricalEventFilter", "HistoricalEventFilter")
+                    .property_of(notifier.clone())
+                    .has_type_definition(VariableTypeId::PropertyType)
+                    .data_type(DataTypeId::EventFilter)
+                    .insert(address_space);
+                id
+            }
+        };
+        if let Some(NodeType::Variable(v)) = address_space.find_mut(&property) {
+            let now = DateTime::now();
+            v.set_value_direct(value, StatusCode::Good, &now, &now)?;
+        }
+
+        trace_write_lock!(self.notifiers).insert(notifier, parsed);
+        Ok(())
+    }
+
+    /// Start recording events raised on `notifier`, selected by the value of its
+    /// `HistoricalEventFilter` property in `address_space`, for example from an
+    /// imported NodeSet.
+    ///
+    /// Fails if the notifier has no valid `HistoricalEventFilter` property.
+    pub fn historize_from_property(
+        &self,
+        address_space: &mut AddressSpace,
+        notifier: impl Into<NodeId>,
+    ) -> Result<(), StatusCode> {
+        let notifier = notifier.into();
+        let property = self
+            .find_filter_property(address_space, &notifier)
+            .ok_or(StatusCode::BadNoData)?;
+        let Some(NodeType::Variable(v)) = address_space.find(&property) else {
+            return Err(StatusCode::BadNoData);
+        };
+        let value = v.value(
+            TimestampsToReturn::Neither,
+            &NumericRange::None,
+            &DataEncoding::Binary,
+            0.0,
+        );
+        let Some(Variant::ExtensionObject(obj)) = value.value else {
+            return Err(StatusCode::BadTypeMismatch);
+        };
+        let filter = obj
+            .into_inner_as::<EventFilter>()
+            .ok_or(StatusCode::BadTypeMismatch)?;
+        self.historize(address_space, notifier, *filter)
+    }
+
+    /// Find the `HistoricalEventFilter` property of `notifier`.
+    fn find_filter_property(
+        &self,
+        address_space: &AddressSpace,
+        notifier: &NodeId,
+    ) -> Option<NodeId> {
+        let type_tree = trace_read_lock!(self.type_tree);
+        address_space
+            .find_node_by_browse_name(
+                notifier,
+                Some((ReferenceTypeId::HasProperty, false)),
+                &*type_tree,
+                BrowseDirection::Forward,
+                "HistoricalEventFilter",
+            )
+            .map(|n| n.as_node().node_id().clone())
+    }
+
+    /// Stop recording events raised on `notifier`. Events that are already
+    /// recorded are kept, as is the `HistoricalEventFilter` property of the notifier.
+    pub fn stop_historizing(&self, notifier: &NodeId) {
+        trace_write_lock!(self.notifiers).remove(notifier);
+    }
+
+    /// Check whether events raised on `notifier` are recorded.
+    pub fn is_historized(&self, notifier: &NodeId) -> bool {
+        trace_read_lock!(self.notifiers).contains_key(notifier)
+    }
+
+    /// Implement the history read events service for a list of notifiers, reading events
+    /// from the provider. Node managers with historized notifiers can call this from
+    /// their `history_read_events` methods.
+    pub async fn history_read_events(
+        &self,
+        context: &RequestContext,
+        details: &ReadEventDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+    ) -> Result<(), StatusCode> {
+        let filter = {
+            let type_tree = context.get_type_tree_for_user();
+            ParsedEventFilter::new(details.filter.clone(), type_tree.get()).1?
+        };
+        for node in nodes {
+            impl_history_read_events(&*self.provider, context, details, &filter, node).await;
+        }
+        Ok(())
+    }
+
+    /// Capture `event`, raised on `node_id`, for each historized notifier it belongs to.
+    pub(crate) fn capture(
+        &self,
+        event: &dyn Event,
+        node_id: &NodeId,
+        captured: &mut Vec<(NodeId, HistoricalEvent)>,
+    ) {
+        let notifiers = trace_read_lock!(self.notifiers);
+        if notifiers.is_empty() {
+            return;
+        }
+        let type_tree = trace_read_lock!(self.type_tree);
+        let server_id: NodeId = ObjectId::Server.into();
+        let targets = [Some(node_id), (node_id != &server_id).then_some(&server_id)];
+        for notifier in targets.into_iter().flatten() {
+            let Some(filter) = notifiers.get(notifier) else {
+                continue;
+            };
+            if let Some(evt) = HistoricalEvent::capture(event, filter, &*type_tree) {
+                captured.push((notifier.clone(), evt));
+            }
+        }
+    }
+
+    /// Record captured events in the background.
+    pub(crate) fn record(&self, events: Vec<(NodeId, HistoricalEvent)>) {
+        if events.is_empty() {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            warn!("Events can only be recorded in history from inside a tokio runtime");
+            return;
+        };
+        let provider = self.provider.clone();
+        handle.spawn(async move {
+            if let Err(e) = provider.record_events(events).await {
+                warn!("Failed to record event history: {e}");
+            }
+        });
+    }
+}
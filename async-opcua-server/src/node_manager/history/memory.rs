@@ -0,0 +1,355 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::{Bound, Range},
+};
+
+use async_trait::async_trait;
+use opcua_core::{
+    sync::RwLock,
+    {trace_read_lock, trace_write_lock},
+};
+use opcua_types::{
+    DataValue, DateTime, HistoryUpdateType, ModificationInfo, NodeId, PerformUpdateType, StatusCode,
+};
+
+use super::{
+    events::{EventHistoryProvider, HistoricalEvent, HistoryEventFilter},
+    provider::{value_time, HistoryProvider, HistoryRawQuery, HistoryValueUpdate},
+};
+
+/// A [`HistoryProvider`] keeping the most recent values of each node in memory,
+/// in a ring buffer of fixed size per node.
+///
+/// History is lost when the server restarts. This is useful for testing, or for servers
+/// that only need to provide a short history of values.
+///
+/// Values changed with `HistoryUpdate` are kept in a second buffer of the same size,
+/// for reading modified values.
+pub struct InMemoryHistoryProvider {
+    capacity: usize,
+    // Each buffer is ordered by source timestamp.
+    values: RwLock<HashMap<NodeId, VecDeque<DataValue>>>,
+    modified: RwLock<HashMap<NodeId, VecDeque<(DataValue, ModificationInfo)>>>,
+}
+
+/// Get the indices of the values in `buffer` with a time between `start` and `end`.
+fn range_of<T>(
+    buffer: &VecDeque<T>,
+    start: Bound<DateTime>,
+    end: Bound<DateTime>,
+    time: impl Fn(&T) -> DateTime,
+) -> Range<usize> {
+    let lower = match start {
+        Bound::Included(s) => buffer.partition_point(|v| time(v) < s),
+        Bound::Excluded(s) => buffer.partition_point(|v| time(v) <= s),
+        Bound::Unbounded => 0,
+    };
+    let upper = match end {
+        Bound::Included(e) => buffer.partition_point(|v| time(v) <= e),
+        Bound::Excluded(e) => buffer.partition_point(|v| time(v) < e),
+        Bound::Unbounded => buffer.len(),
+    };
+    lower..upper.max(lower)
+}
+
+fn read_range<T: Clone>(
+    buffer: &VecDeque<T>,
+    query: &HistoryRawQuery,
+    time: impl Fn(&T) -> DateTime,
+) -> Vec<T> {
+    let in_range = buffer.range(range_of(buffer, query.start, query.end, time));
+    if query.reverse {
+        in_range.rev().take(query.max_values).cloned().collect()
+    } else {
+        in_range.take(query.max_values).cloned().collect()
+    }
+}
+
+impl InMemoryHistoryProvider {
+    /// Create a new in-memory history provider keeping up to `capacity` values per node.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            values: Default::default(),
+            modified: Default::default(),
+        }
+    }
+
+    /// Record a single value for the node given by `node_id`, dropping the oldest
+    /// value of the node if its buffer is full.
+    pub fn push_value(&self, node_id: &NodeId, mut value: DataValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        if value.source_timestamp.is_none() {
+            value.source_timestamp = Some(value.server_timestamp.unwrap_or_else(DateTime::now));
+        }
+        let mut values = trace_write_lock!(self.values);
+        let buffer = values.entry(node_id.clone()).or_default();
+        let time = value_time(&value);
+        // Values usually arrive in order, so this is almost always the end of the buffer.
+        let index = buffer.partition_point(|v| value_time(v) <= time);
+        buffer.insert(index, value);
+        while buffer.len() > self.capacity {
+            buffer.pop_front();
+        }
+    }
+
+    /// Remove all recorded values for the node given by `node_id`.
+    pub fn clear(&self, node_id: &NodeId) {
+        trace_write_lock!(self.values).remove(node_id);
+        trace_write_lock!(self.modified).remove(node_id);
+    }
+
+    /// Keep a value that was changed by a history update, for reading modified values.
+    fn push_modified(
+        &self,
+        modified: &mut HashMap<NodeId, VecDeque<(DataValue, ModificationInfo)>>,
+        node_id: &NodeId,
+        value: DataValue,
+        update_type: HistoryUpdateType,
+        user_name: &str,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let buffer = modified.entry(node_id.clone()).or_default();
+        let time = value_time(&value);
+        let index = buffer.partition_point(|(v, _)| value_time(v) <= time);
+        buffer.insert(
+            index,
+            (
+                value,
+                ModificationInfo {
+                    modification_time: DateTime::now(),
+                    update_type,
+                    user_name: user_name.into(),
+                },
+            ),
+        );
+        while buffer.len() > self.capacity {
+            buffer.pop_front();
+        }
+    }
+}
+
+#[async_trait]
+impl HistoryProvider for InMemoryHistoryProvider {
+    async fn read_raw(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryRawQuery,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        let values = trace_read_lock!(self.values);
+        Ok(values
+            .get(node_id)
+            .map(|buffer| read_range(buffer, query, value_time))
+            .unwrap_or_default())
+    }
+
+    async fn read_modified(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryRawQuery,
+    ) -> Result<Vec<(DataValue, ModificationInfo)>, StatusCode> {
+        let modified = trace_read_lock!(self.modified);
+        Ok(modified
+            .get(node_id)
+            .map(|buffer| read_range(buffer, query, |(v, _)| value_time(v)))
+            .unwrap_or_default())
+    }
+
+    async fn record_values(&self, values: Vec<(NodeId, DataValue)>) -> Result<(), StatusCode> {
+        for (node_id, value) in values {
+            self.push_value(&node_id, value);
+        }
+        Ok(())
+    }
+
+    async fn update_values(
+        &self,
+        node_id: &NodeId,
+        perform_update: PerformUpdateType,
+        new_values: Vec<DataValue>,
+        user_name: &str,
+    ) -> Result<Vec<HistoryValueUpdate>, StatusCode> {
+        let mut values = trace_write_lock!(self.values);
+        let mut modified = trace_write_lock!(self.modified);
+        let mut results = Vec::with_capacity(new_values.len());
+        for value in new_values {
+            let Some(time) = value.source_timestamp else {
+                results.push(HistoryValueUpdate::new(StatusCode::BadInvalidTimestamp));
+                continue;
+            };
+            let buffer = values.entry(node_id.clone()).or_default();
+            let existing = range_of(
+                buffer,
+                Bound::Included(time),
+                Bound::Included(time),
+                value_time,
+            );
+            let index = (!existing.is_empty()).then_some(existing.start);
+            let result = match (perform_update, index) {
+                (PerformUpdateType::Insert, Some(_)) => {
+                    HistoryValueUpdate::new(StatusCode::BadEntryExists)
+                }
+                (PerformUpdateType::Replace | PerformUpdateType::Remove, None) => {
+                    HistoryValueUpdate::new(StatusCode::BadNoEntryExists)
+                }
+                (PerformUpdateType::Insert | PerformUpdateType::Update, None) => {
+                    if self.capacity == 0 {
+                        HistoryValueUpdate::new(StatusCode::BadOutOfMemory)
+                    } else {
+                        buffer.insert(existing.start, value.clone());
+                        while buffer.len() > self.capacity {
+                            buffer.pop_front();
+                        }
+                        self.push_modified(
+                            &mut modified,
+                            node_id,
+                            value,
+                            HistoryUpdateType::Insert,
+                            user_name,
+                        );
+                        HistoryValueUpdate::new(StatusCode::GoodEntryInserted)
+                    }
+                }
+                (PerformUpdateType::Replace | PerformUpdateType::Update, Some(index)) => {
+                    let old = std::mem::replace(&mut buffer[index], value);
+                    let update_type = if perform_update == PerformUpdateType::Replace {
+                        HistoryUpdateType::Replace
+                    } else {
+                        HistoryUpdateType::Update
+                    };
+                    self.push_modified(&mut modified, node_id, old.clone(), update_type, user_name);
+                    HistoryValueUpdate {
+                        status: StatusCode::GoodEntryReplaced,
+                        old_value: Some(old),
+                    }
+                }
+                (PerformUpdateType::Remove, Some(index)) => {
+                    let old = buffer.remove(index).unwrap_or_default();
+                    self.push_modified(
+                        &mut modified,
+                        node_id,
+                        old.clone(),
+                        HistoryUpdateType::Delete,
+                        user_name,
+                    );
+                    HistoryValueUpdate {
+                        status: StatusCode::Good,
+                        old_value: Some(old),
+                    }
+                }
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    async fn delete_values(
+        &self,
+        node_id: &NodeId,
+        start: Bound<DateTime>,
+        end: Bound<DateTime>,
+        is_delete_modified: bool,
+        user_name: &str,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        let mut values = trace_write_lock!(self.values);
+        let mut modified = trace_write_lock!(self.modified);
+        if is_delete_modified {
+            let Some(buffer) = modified.get_mut(node_id) else {
+                return Ok(Vec::new());
+            };
+            let range = range_of(buffer, start, end, |(v, _)| value_time(v));
+            return Ok(buffer.drain(range).map(|(v, _)| v).collect());
+        }
+        let Some(buffer) = values.get_mut(node_id) else {
+            return Ok(Vec::new());
+        };
+        let range = range_of(buffer, start, end, value_time);
+        let deleted: Vec<_> = buffer.drain(range).collect();
+        for value in &deleted {
+            self.push_modified(
+                &mut modified,
+                node_id,
+                value.clone(),
+                HistoryUpdateType::Delete,
+                user_name,
+            );
+        }
+        Ok(deleted)
+    }
+}
+
+/// An [`EventHistoryProvider`] keeping the most recent events of each notifier in memory,
+/// in a ring buffer of fixed size per notifier.
+///
+/// Like [`InMemoryHistoryProvider`], history is lost when the server restarts.
+pub struct InMemoryEventHistoryProvider {
+    capacity: usize,
+    // Each buffer is ordered by event time.
+    events: RwLock<HashMap<NodeId, VecDeque<HistoricalEvent>>>,
+}
+
+impl InMemoryEventHistoryProvider {
+    /// Create a new in-memory event history provider keeping up to `capacity` events
+    /// per notifier.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Default::default(),
+        }
+    }
+
+    /// Record a single event for the notifier given by `notifier`, dropping the oldest
+    /// event of the notifier if its buffer is full.
+    pub fn push_event(&self, notifier: &NodeId, event: HistoricalEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = trace_write_lock!(self.events);
+        let buffer = events.entry(notifier.clone()).or_default();
+        let index = buffer.partition_point(|e| e.time <= event.time);
+        buffer.insert(index, event);
+        while buffer.len() > self.capacity {
+            buffer.pop_front();
+        }
+    }
+
+    /// Remove all recorded events for the notifier given by `notifier`.
+    pub fn clear(&self, notifier: &NodeId) {
+        trace_write_lock!(self.events).remove(notifier);
+    }
+}
+
+#[async_trait]
+impl EventHistoryProvider for InMemoryEventHistoryProvider {
+    async fn read_events(
+        &self,
+        notifier: &NodeId,
+        query: &HistoryRawQuery,
+        filter: &HistoryEventFilter<'_>,
+    ) -> Result<Vec<HistoricalEvent>, StatusCode> {
+        let events = trace_read_lock!(self.events);
+        let Some(buffer) = events.get(notifier) else {
+            return Ok(Vec::new());
+        };
+        let in_range = buffer.range(range_of(buffer, query.start, query.end, |e| e.time));
+        Ok(if query.reverse {
+            filter.filter(in_range.rev(), query.max_values)
+        } else {
+            filter.filter(in_range, query.max_values)
+        })
+    }
+
+    async fn record_events(
+        &self,
+        events: Vec<(NodeId, HistoricalEvent)>,
+    ) -> Result<(), StatusCode> {
+        for (notifier, event) in events {
+            self.push_event(&notifier, event);
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,25 @@
+mod aggregates;
+mod events;
+mod memory;
+mod provider;
+#[cfg(feature = "history-sqlite")]
+mod sqlite;
+mod update;
+
+pub use aggregates::{impl_history_read_processed, SUPPORTED_AGGREGATES};
+pub use events::{
+    impl_history_read_events, EventHistoryCapture, EventHistoryProvider, HistoricalEvent,
+    HistoricalEventField, HistoryEventFilter,
+};
+pub use memory::{InMemoryEventHistoryProvider, InMemoryHistoryProvider};
+pub use provider::{
+    impl_history_read_at_time, impl_history_read_raw_modified, HistoryProvider, HistoryRawQuery,
+    HistoryValueUpdate,
+};
+#[cfg(feature = "history-sqlite")]
+pub use sqlite::{SqliteHistoryProvider, SqliteHistoryRetention};
+pub use update::impl_history_update;
+
 use crate::session::{continuation_points::ContinuationPoint, instance::Session};
 use opcua_crypto::random;
 use opcua_types::{
@@ -16,6 +38,7 @@ pub struct HistoryNode {
     data_encoding: QualifiedName,
     input_continuation_point: Option<ContinuationPoint>,
     next_continuation_point: Option<ContinuationPoint>,
+    aggregate_type: Option<NodeId>,
     result: Option<ExtensionObject>,
     status: StatusCode,
 }
@@ -116,6 +139,7 @@ impl HistoryNode {
             data_encoding: node.data_encoding,
             input_continuation_point: cp,
             next_continuation_point: None,
+            aggregate_type: None,
             result: None,
             status,
         }
@@ -146,6 +170,15 @@ impl HistoryNode {
         self.next_continuation_point.as_ref()
     }
 
+    /// Get the aggregate to calculate for this node, only set for processed reads.
+    pub fn aggregate_type(&self) -> Option<&NodeId> {
+        self.aggregate_type.as_ref()
+    }
+
+    pub(crate) fn set_aggregate_type(&mut self, aggregate_type: NodeId) {
+        self.aggregate_type = Some(aggregate_type);
+    }
+
     /// Set the next continuation point.
     pub fn set_next_continuation_point(&mut self, continuation_point: Option<ContinuationPoint>) {
         self.next_continuation_point = continuation_point;
@@ -0,0 +1,624 @@
+use std::{future::Future, ops::Bound};
+
+use async_trait::async_trait;
+use opcua_types::{
+    AggregateConfiguration, DataValue, DateTime, HistoryData, HistoryModifiedData,
+    ModificationInfo, NodeId, NumericRange, PerformUpdateType, ReadAtTimeDetails,
+    ReadRawModifiedDetails, StatusCode, StatusCodeValueType, TimestampsToReturn, Variant,
+};
+
+use crate::session::continuation_points::ContinuationPoint;
+
+use super::HistoryNode;
+
+/// A query for historical values of a single node, in a time range given by the
+/// source timestamps of the values.
+#[derive(Debug, Clone)]
+pub struct HistoryRawQuery {
+    /// Lower bound of the time range.
+    pub start: Bound<DateTime>,
+    /// Upper bound of the time range.
+    pub end: Bound<DateTime>,
+    /// Return the newest values first, instead of the oldest.
+    pub reverse: bool,
+    /// The maximum number of values to return.
+    pub max_values: usize,
+}
+
+impl HistoryRawQuery {
+    /// Check whether `time` is inside the time range of the query.
+    pub fn contains(&self, time: &DateTime) -> bool {
+        (match self.start {
+            Bound::Included(s) => *time >= s,
+            Bound::Excluded(s) => *time > s,
+            Bound::Unbounded => true,
+        }) && (match self.end {
+            Bound::Included(e) => *time <= e,
+            Bound::Excluded(e) => *time < e,
+            Bound::Unbounded => true,
+        })
+    }
+}
+
+/// The outcome of updating a single historical value.
+#[derive(Debug, Clone)]
+pub struct HistoryValueUpdate {
+    /// Status of the update, `GoodEntryInserted` or `GoodEntryReplaced` if it succeeded,
+    /// otherwise a bad status such as `BadEntryExists` or `BadNoEntryExists`.
+    pub status: StatusCode,
+    /// The value that was replaced or removed, if any.
+    pub old_value: Option<DataValue>,
+}
+
+impl HistoryValueUpdate {
+    /// Create an outcome for an update that did not replace any value.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            old_value: None,
+        }
+    }
+}
+
+/// Storage of historical values, used to implement the `HistoryRead` service.
+///
+/// A provider only needs to be able to return values of a node in a time range,
+/// paging with continuation points, bounding values, reading at specific times and
+/// calculating aggregates are handled by [`impl_history_read_raw_modified`],
+/// [`impl_history_read_at_time`] and [`impl_history_read_processed`](super::impl_history_read_processed).
+/// Providers that support changing history can also implement `update_values` and
+/// `delete_values`, used by [`impl_history_update`](super::impl_history_update).
+/// See [`InMemoryHistoryProvider`](super::InMemoryHistoryProvider) for a simple implementation.
+#[async_trait]
+pub trait HistoryProvider: Send + Sync + 'static {
+    /// Read the values of `node_id` with a source timestamp in the range of `query`,
+    /// ordered by source timestamp.
+    async fn read_raw(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryRawQuery,
+    ) -> Result<Vec<DataValue>, StatusCode>;
+
+    /// Read the values of `node_id` that were modified after they were recorded,
+    /// with a source timestamp in the range of `query`, ordered by source timestamp.
+    ///
+    /// The default implementation does not support reading modified values.
+    async fn read_modified(
+        &self,
+        _node_id: &NodeId,
+        _query: &HistoryRawQuery,
+    ) -> Result<Vec<(DataValue, ModificationInfo)>, StatusCode> {
+        Err(StatusCode::BadHistoryOperationUnsupported)
+    }
+
+    /// Record new values for the given nodes. Values without a source timestamp
+    /// should be recorded with their server timestamp.
+    async fn record_values(&self, values: Vec<(NodeId, DataValue)>) -> Result<(), StatusCode>;
+
+    /// Insert, replace, or insert or replace values of `node_id` as given by `perform_update`,
+    /// matching existing values by source timestamp, and return the outcome for each value.
+    /// `PerformUpdateType::Remove` removes the existing value instead.
+    ///
+    /// `user_name` identifies the user making the change, for providers that keep replaced
+    /// values to serve modified reads.
+    ///
+    /// The default implementation does not support updating history.
+    async fn update_values(
+        &self,
+        _node_id: &NodeId,
+        _perform_update: PerformUpdateType,
+        _values: Vec<DataValue>,
+        _user_name: &str,
+    ) -> Result<Vec<HistoryValueUpdate>, StatusCode> {
+        Err(StatusCode::BadHistoryOperationUnsupported)
+    }
+
+    /// Delete the values of `node_id` with a source timestamp between `start` and `end`,
+    /// or the modified values if `is_delete_modified` is set, and return the deleted values.
+    ///
+    /// The default implementation does not support deleting history.
+    async fn delete_values(
+        &self,
+        _node_id: &NodeId,
+        _start: Bound<DateTime>,
+        _end: Bound<DateTime>,
+        _is_delete_modified: bool,
+        _user_name: &str,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        Err(StatusCode::BadHistoryOperationUnsupported)
+    }
+
+    /// The maximum number of values returned for a node in a single read, more
+    /// values are returned with continuation points.
+    fn max_values_per_node(&self) -> usize {
+        10_000
+    }
+
+    /// The configuration used to calculate aggregates for `node_id` when the client
+    /// asks for the server defaults.
+    ///
+    /// The default treats uncertain values as good, and only reports aggregates as good
+    /// or bad if all values in the interval are good or bad.
+    fn aggregate_configuration(&self, _node_id: &NodeId) -> AggregateConfiguration {
+        AggregateConfiguration {
+            use_server_capabilities_defaults: false,
+            treat_uncertain_as_bad: false,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        }
+    }
+}
+
+/// Continuation point for raw, modified and event reads, the position of the last value read.
+pub(super) struct RawContinuationPoint {
+    /// Source timestamp of the last value read.
+    last: DateTime,
+    /// Number of values with that source timestamp that were read already.
+    skip: usize,
+}
+
+/// Continuation point for reads at time, the index of the next time to read.
+struct AtTimeContinuationPoint {
+    index: usize,
+}
+
+/// Get the time a historical value is ordered by.
+pub(super) fn value_time(value: &DataValue) -> DateTime {
+    value
+        .source_timestamp
+        .or(value.server_timestamp)
+        .unwrap_or_else(DateTime::null)
+}
+
+/// Apply the index range and timestamps to return of a history read to a value.
+pub(super) fn prepare_value(
+    mut value: DataValue,
+    index_range: &NumericRange,
+    timestamps_to_return: TimestampsToReturn,
+) -> DataValue {
+    if !matches!(index_range, NumericRange::None) {
+        if let Some(v) = value.value.take() {
+            match v.range_of_owned(index_range) {
+                Ok(v) => value.value = Some(v),
+                Err(e) => value.status = Some(e),
+            }
+        }
+    }
+    match timestamps_to_return {
+        TimestampsToReturn::Source => {
+            value.server_timestamp = None;
+            value.server_picoseconds = None;
+        }
+        TimestampsToReturn::Server => {
+            value.source_timestamp = None;
+            value.source_picoseconds = None;
+        }
+        _ => (),
+    }
+    value
+}
+
+/// Get the time range, and whether it is read in reverse, of a raw/modified or event read.
+pub(super) fn query_range(
+    start_time: DateTime,
+    end_time: DateTime,
+    num_values_per_node: u32,
+) -> Result<(Bound<DateTime>, Bound<DateTime>, bool), StatusCode> {
+    let start = (!start_time.is_null()).then_some(start_time);
+    let end = (!end_time.is_null()).then_some(end_time);
+    match (start, end) {
+        (None, None) => Err(StatusCode::BadHistoryOperationInvalid),
+        // With only one time, the number of values must be limited.
+        (Some(_), None) | (None, Some(_)) if num_values_per_node == 0 => {
+            Err(StatusCode::BadHistoryOperationInvalid)
+        }
+        (Some(s), None) => Ok((Bound::Included(s), Bound::Unbounded, false)),
+        (None, Some(e)) => Ok((Bound::Unbounded, Bound::Included(e), true)),
+        (Some(s), Some(e)) if s == e => Ok((Bound::Included(s), Bound::Included(e), false)),
+        (Some(s), Some(e)) if s < e => Ok((Bound::Included(s), Bound::Excluded(e), false)),
+        (Some(s), Some(e)) => Ok((Bound::Excluded(e), Bound::Included(s), true)),
+    }
+}
+
+/// Read a page of values, resuming from `cp`. Returns the values and the
+/// continuation point for the next page, if there are more values.
+pub(super) async fn read_page<T, Fut: Future<Output = Result<Vec<T>, StatusCode>>>(
+    node: &HistoryNode,
+    mut query: HistoryRawQuery,
+    per_node: usize,
+    read: impl FnOnce(HistoryRawQuery) -> Fut,
+    time: impl Fn(&T) -> DateTime,
+) -> Result<(Vec<T>, Option<RawContinuationPoint>), StatusCode> {
+    let skip_at = match node.continuation_point() {
+        Some(cp) => {
+            let Some(cp) = cp.get::<RawContinuationPoint>() else {
+                return Err(StatusCode::BadContinuationPointInvalid);
+            };
+            if query.reverse {
+                query.end = Bound::Included(cp.last);
+            } else {
+                query.start = Bound::Included(cp.last);
+            }
+            Some((cp.last, cp.skip))
+        }
+        None => None,
+    };
+    let skip = skip_at.map(|(_, s)| s).unwrap_or_default();
+    // Read one value more than we return, to know if there are more.
+    query.max_values = per_node.saturating_add(skip).saturating_add(1);
+
+    let mut values = read(query).await?;
+    if let Some((last, skip)) = skip_at {
+        let already_read = values
+            .iter()
+            .take(skip)
+            .take_while(|v| time(v) == last)
+            .count();
+        values.drain(..already_read);
+    }
+    if values.len() <= per_node {
+        return Ok((values, None));
+    }
+    values.truncate(per_node);
+    let Some(last) = values.last().map(&time) else {
+        return Ok((values, None));
+    };
+    let mut skip = values.iter().rev().take_while(|v| time(v) == last).count();
+    if let Some((prev_last, prev_skip)) = skip_at {
+        if prev_last == last && skip == values.len() {
+            skip += prev_skip;
+        }
+    }
+    Ok((values, Some(RawContinuationPoint { last, skip })))
+}
+
+/// Find the bounding value of a raw read outside of the time range, the first value
+/// in `range`, or a value with status `BadBoundNotFound` at `time` if there is none.
+async fn read_bound(
+    provider: &dyn HistoryProvider,
+    node_id: &NodeId,
+    start: Bound<DateTime>,
+    end: Bound<DateTime>,
+    reverse: bool,
+    time: DateTime,
+) -> Result<DataValue, StatusCode> {
+    let query = HistoryRawQuery {
+        start,
+        end,
+        reverse,
+        max_values: 1,
+    };
+    Ok(provider
+        .read_raw(node_id, &query)
+        .await?
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| DataValue {
+            status: Some(StatusCode::BadBoundNotFound),
+            source_timestamp: Some(time),
+            server_timestamp: Some(time),
+            ..Default::default()
+        }))
+}
+
+async fn read_raw_node(
+    provider: &dyn HistoryProvider,
+    details: &ReadRawModifiedDetails,
+    node: &mut HistoryNode,
+    query: HistoryRawQuery,
+    per_node: usize,
+    timestamps_to_return: TimestampsToReturn,
+) -> Result<(), StatusCode> {
+    let node_id = node.node_id().clone();
+    let is_first_page = node.continuation_point().is_none();
+    let (mut values, cp) = read_page(
+        node,
+        query.clone(),
+        per_node,
+        |q| {
+            let node_id = &node_id;
+            async move { provider.read_raw(node_id, &q).await }
+        },
+        value_time,
+    )
+    .await?;
+
+    if details.return_bounds {
+        // Bounds are the values at or outside the ends of the time range, in the direction
+        // of the read. A read with only an end time reads backwards from the end time.
+        let (read_start, read_end) = if details.start_time.is_null() {
+            (details.end_time, DateTime::null())
+        } else {
+            (details.start_time, details.end_time)
+        };
+        if is_first_page {
+            let at_start = values.first().is_some_and(|v| value_time(v) == read_start);
+            if !at_start {
+                let (start, end) = if query.reverse {
+                    (Bound::Excluded(read_start), Bound::Unbounded)
+                } else {
+                    (Bound::Unbounded, Bound::Excluded(read_start))
+                };
+                let bound =
+                    read_bound(provider, &node_id, start, end, !query.reverse, read_start).await?;
+                values.insert(0, bound);
+            }
+        }
+        if cp.is_none() && !read_end.is_null() && read_end != read_start {
+            let (start, end) = if query.reverse {
+                (Bound::Unbounded, Bound::Included(read_end))
+            } else {
+                (Bound::Included(read_end), Bound::Unbounded)
+            };
+            let bound = read_bound(provider, &node_id, start, end, query.reverse, read_end).await?;
+            values.push(bound);
+        }
+    }
+
+    let index_range = node.index_range().clone();
+    node.set_result(HistoryData {
+        data_values: Some(
+            values
+                .into_iter()
+                .map(|v| prepare_value(v, &index_range, timestamps_to_return))
+                .collect(),
+        ),
+    });
+    node.set_next_continuation_point(cp.map(|cp| ContinuationPoint::new(Box::new(cp))));
+    Ok(())
+}
+
+async fn read_modified_node(
+    provider: &dyn HistoryProvider,
+    node: &mut HistoryNode,
+    query: HistoryRawQuery,
+    per_node: usize,
+    timestamps_to_return: TimestampsToReturn,
+) -> Result<(), StatusCode> {
+    let node_id = node.node_id().clone();
+    let (values, cp) = read_page(
+        node,
+        query,
+        per_node,
+        |q| {
+            let node_id = &node_id;
+            async move { provider.read_modified(node_id, &q).await }
+        },
+        |(v, _)| value_time(v),
+    )
+    .await?;
+
+    let index_range = node.index_range().clone();
+    let (data_values, modification_infos) = values
+        .into_iter()
+        .map(|(v, i)| (prepare_value(v, &index_range, timestamps_to_return), i))
+        .unzip();
+    node.set_result(HistoryModifiedData {
+        data_values: Some(data_values),
+        modification_infos: Some(modification_infos),
+    });
+    node.set_next_continuation_point(cp.map(|cp| ContinuationPoint::new(Box::new(cp))));
+    Ok(())
+}
+
+/// Implement the history read raw modified service for a single node using
+/// a [`HistoryProvider`], writing the result to `node`.
+///
+/// This handles continuation points, the number of values per node,
+/// bounding values, index ranges and timestamps to return.
+pub async fn impl_history_read_raw_modified(
+    provider: &dyn HistoryProvider,
+    details: &ReadRawModifiedDetails,
+    node: &mut HistoryNode,
+    timestamps_to_return: TimestampsToReturn,
+) {
+    if timestamps_to_return == TimestampsToReturn::Neither {
+        node.set_status(StatusCode::BadTimestampsToReturnInvalid);
+        return;
+    }
+    let (start, end, reverse) = match query_range(
+        details.start_time,
+        details.end_time,
+        details.num_values_per_node,
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            node.set_status(e);
+            return;
+        }
+    };
+    let max_per_node = provider.max_values_per_node().max(1);
+    let per_node = match details.num_values_per_node as usize {
+        0 => max_per_node,
+        n => n.min(max_per_node),
+    };
+    let query = HistoryRawQuery {
+        start,
+        end,
+        reverse,
+        max_values: per_node,
+    };
+
+    let res = if details.is_read_modified {
+        read_modified_node(provider, node, query, per_node, timestamps_to_return).await
+    } else {
+        read_raw_node(
+            provider,
+            details,
+            node,
+            query,
+            per_node,
+            timestamps_to_return,
+        )
+        .await
+    };
+    match res {
+        Ok(()) => node.set_status(StatusCode::Good),
+        Err(e) => node.set_status(e),
+    }
+}
+
+/// Find the value to interpolate from at or before `time` if `reverse`,
+/// otherwise the value to interpolate to after `time`. Unless `use_simple_bounds`
+/// is set, bad values are skipped.
+async fn find_interpolation_bound(
+    provider: &dyn HistoryProvider,
+    node_id: &NodeId,
+    time: DateTime,
+    reverse: bool,
+    use_simple_bounds: bool,
+) -> Result<Option<DataValue>, StatusCode> {
+    let query = if reverse {
+        HistoryRawQuery {
+            start: Bound::Unbounded,
+            end: Bound::Included(time),
+            reverse,
+            max_values: 1,
+        }
+    } else {
+        HistoryRawQuery {
+            start: Bound::Excluded(time),
+            end: Bound::Unbounded,
+            reverse,
+            max_values: 1,
+        }
+    };
+    if use_simple_bounds {
+        return Ok(provider.read_raw(node_id, &query).await?.into_iter().next());
+    }
+    let query = HistoryRawQuery {
+        max_values: 100,
+        ..query
+    };
+    Ok(provider
+        .read_raw(node_id, &query)
+        .await?
+        .into_iter()
+        .find(|v| !v.status().is_bad()))
+}
+
+/// Interpolate linearly between `prior` and `next` at `time`, casting the result to the
+/// type of `prior`. Values that are not numeric are stepped, taking the value of `prior`.
+pub(super) fn interpolate(prior: &DataValue, next: &DataValue, time: DateTime) -> Option<Variant> {
+    let prior_time = value_time(prior);
+    let (Some(p), Some(n)) = (&prior.value, &next.value) else {
+        return prior.value.clone();
+    };
+    let (Some(pv), Some(nv)) = (p.as_f64(), n.as_f64()) else {
+        return prior.value.clone();
+    };
+    let span = (value_time(next) - prior_time).num_microseconds();
+    let offset = (time - prior_time).num_microseconds();
+    match (span, offset) {
+        (Some(span), Some(offset)) if span > 0 => {
+            let v = pv + (nv - pv) * (offset as f64 / span as f64);
+            Some(Variant::Double(v).cast(p.type_id()))
+        }
+        _ => prior.value.clone(),
+    }
+}
+
+/// Get the value of a node at `time`, interpolated from the values around it.
+async fn read_at_time(
+    provider: &dyn HistoryProvider,
+    node_id: &NodeId,
+    time: DateTime,
+    use_simple_bounds: bool,
+) -> Result<DataValue, StatusCode> {
+    let Some(prior) =
+        find_interpolation_bound(provider, node_id, time, true, use_simple_bounds).await?
+    else {
+        return Ok(DataValue {
+            status: Some(StatusCode::BadNoData),
+            source_timestamp: Some(time),
+            server_timestamp: Some(time),
+            ..Default::default()
+        });
+    };
+    let prior_time = value_time(&prior);
+    if prior_time == time {
+        return Ok(prior);
+    }
+    let next = find_interpolation_bound(provider, node_id, time, false, use_simple_bounds).await?;
+
+    let (value, status) = match &next {
+        Some(next) => {
+            let value = interpolate(&prior, next, time);
+            let status = if prior.status().is_good() && next.status().is_good() {
+                StatusCode::Good
+            } else {
+                StatusCode::UncertainDataSubNormal
+            };
+            (value, status)
+        }
+        // There is no later value, so the prior value is extrapolated.
+        None => (prior.value.clone(), StatusCode::UncertainDataSubNormal),
+    };
+
+    Ok(DataValue {
+        value,
+        status: Some(status.set_value_type(StatusCodeValueType::Interpolated)),
+        source_timestamp: Some(time),
+        server_timestamp: Some(time),
+        ..Default::default()
+    })
+}
+
+/// Implement the history read at time service for a single node using
+/// a [`HistoryProvider`], writing the result to `node`.
+///
+/// Values at times between recorded values are interpolated linearly for numeric
+/// values, and stepped for other values.
+pub async fn impl_history_read_at_time(
+    provider: &dyn HistoryProvider,
+    details: &ReadAtTimeDetails,
+    node: &mut HistoryNode,
+    timestamps_to_return: TimestampsToReturn,
+) {
+    if timestamps_to_return == TimestampsToReturn::Neither {
+        node.set_status(StatusCode::BadTimestampsToReturnInvalid);
+        return;
+    }
+    let req_times = details.req_times.as_deref().unwrap_or_default();
+    if req_times.is_empty() {
+        node.set_status(StatusCode::BadHistoryOperationInvalid);
+        return;
+    }
+    let start_index = match node.continuation_point() {
+        Some(cp) => match cp.get::<AtTimeContinuationPoint>() {
+            Some(cp) => cp.index,
+            None => {
+                node.set_status(StatusCode::BadContinuationPointInvalid);
+                return;
+            }
+        },
+        None => 0,
+    };
+    let end_index = start_index
+        .saturating_add(provider.max_values_per_node().max(1))
+        .min(req_times.len());
+
+    let node_id = node.node_id().clone();
+    let index_range = node.index_range().clone();
+    let mut values = Vec::with_capacity(end_index.saturating_sub(start_index));
+    for time in req_times.iter().take(end_index).skip(start_index) {
+        match read_at_time(provider, &node_id, *time, details.use_simple_bounds).await {
+            Ok(v) => values.push(prepare_value(v, &index_range, timestamps_to_return)),
+            Err(e) => {
+                node.set_status(e);
+                return;
+            }
+        }
+    }
+
+    node.set_result(HistoryData {
+        data_values: Some(values),
+    });
+    if end_index < req_times.len() {
+        node.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+            AtTimeContinuationPoint { index: end_index },
+        ))));
+    }
+    node.set_status(StatusCode::Good);
+}
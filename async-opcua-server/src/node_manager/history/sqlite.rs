@@ -0,0 +1,560 @@
+use std::{
+    collections::HashSet,
+    io::Cursor,
+    ops::Bound,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use opcua_core::sync::Mutex;
+use opcua_types::{
+    BinaryDecodable, BinaryEncodable, Context, ContextOwned, DataValue, DateTime,
+    HistoryUpdateType, ModificationInfo, NodeId, PerformUpdateType, StatusCode,
+};
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension};
+use tracing::error;
+
+use super::provider::{value_time, HistoryProvider, HistoryRawQuery, HistoryValueUpdate};
+
+/// Limits on how much history a [`SqliteHistoryProvider`] keeps.
+/// Old values are removed when new values are recorded, at most once every
+/// `cleanup_interval`, so the limits may be exceeded in between.
+#[derive(Debug, Clone)]
+pub struct SqliteHistoryRetention {
+    /// Remove values with a source timestamp older than this.
+    pub max_age: Option<Duration>,
+    /// Keep at most this many values per node, removing the oldest.
+    pub max_values_per_node: Option<usize>,
+    /// Minimum time between removing old values. Defaults to 10 seconds.
+    pub cleanup_interval: Duration,
+}
+
+impl Default for SqliteHistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            max_values_per_node: None,
+            cleanup_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// When retention was last applied, and the nodes written since.
+#[derive(Default)]
+struct RetentionState {
+    last_cleanup: Option<Instant>,
+    written_nodes: HashSet<String>,
+}
+
+impl RetentionState {
+    /// Note that `node_ids` were written, returning the nodes to remove old values
+    /// from if retention is due.
+    fn take_due(
+        &mut self,
+        retention: &SqliteHistoryRetention,
+        node_ids: impl IntoIterator<Item = String>,
+    ) -> Option<Vec<String>> {
+        if retention.max_age.is_none() && retention.max_values_per_node.is_none() {
+            return None;
+        }
+        self.written_nodes.extend(node_ids);
+        let now = Instant::now();
+        if self
+            .last_cleanup
+            .is_some_and(|last| now.duration_since(last) < retention.cleanup_interval)
+        {
+            return None;
+        }
+        self.last_cleanup = Some(now);
+        Some(self.written_nodes.drain().collect())
+    }
+}
+
+/// A [`HistoryProvider`] storing historical values in an SQLite database.
+///
+/// Values are stored in OPC UA binary encoding, indexed by node ID and source timestamp.
+/// Values changed with `HistoryUpdate` are kept in a separate table, for reading
+/// modified values.
+/// Values containing custom structures can only be decoded if the type loaders for
+/// them are given with [`SqliteHistoryProvider::with_encoding_context`].
+pub struct SqliteHistoryProvider {
+    connection: Arc<Mutex<Connection>>,
+    retention: SqliteHistoryRetention,
+    retention_state: Arc<Mutex<RetentionState>>,
+    encoding_context: Arc<ContextOwned>,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS history_values (
+    id INTEGER PRIMARY KEY,
+    node_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    value BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_values_node_time ON history_values (node_id, time, id);
+CREATE INDEX IF NOT EXISTS history_values_time ON history_values (time);
+CREATE TABLE IF NOT EXISTS history_modified (
+    id INTEGER PRIMARY KEY,
+    node_id TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    value BLOB NOT NULL,
+    modification_time INTEGER NOT NULL,
+    update_type INTEGER NOT NULL,
+    user_name TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_modified_node_time ON history_modified (node_id, time, id);
+CREATE INDEX IF NOT EXISTS history_modified_time ON history_modified (time);
+";
+
+fn db_error(e: rusqlite::Error) -> StatusCode {
+    error!("History database error: {e}");
+    StatusCode::BadInternalError
+}
+
+impl SqliteHistoryProvider {
+    /// Open the history database at `path`, creating it if it does not exist.
+    pub fn open(
+        path: impl AsRef<Path>,
+        retention: SqliteHistoryRetention,
+    ) -> Result<Self, rusqlite::Error> {
+        Self::from_connection(Connection::open(path)?, retention)
+    }
+
+    /// Create a history database in memory, mostly useful for testing.
+    pub fn open_in_memory(retention: SqliteHistoryRetention) -> Result<Self, rusqlite::Error> {
+        Self::from_connection(Connection::open_in_memory()?, retention)
+    }
+
+    fn from_connection(
+        connection: Connection,
+        retention: SqliteHistoryRetention,
+    ) -> Result<Self, rusqlite::Error> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            retention,
+            retention_state: Default::default(),
+            encoding_context: Arc::new(ContextOwned::default()),
+        })
+    }
+
+    /// Set the encoding context used to encode and decode stored values.
+    pub fn with_encoding_context(mut self, encoding_context: ContextOwned) -> Self {
+        self.encoding_context = Arc::new(encoding_context);
+        self
+    }
+
+    /// Run `f` with the database connection on the blocking thread pool.
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection, &ContextOwned) -> Result<T, StatusCode> + Send + 'static,
+    ) -> Result<T, StatusCode> {
+        let connection = self.connection.clone();
+        let encoding_context = self.encoding_context.clone();
+        tokio::task::spawn_blocking(move || f(&mut connection.lock(), &encoding_context))
+            .await
+            .map_err(|e| {
+                error!("History database task failed: {e}");
+                StatusCode::BadInternalError
+            })?
+    }
+}
+
+/// Append the condition for `bound` on the time column to `sql`.
+fn push_bound(sql: &mut String, params: &mut Vec<Value>, bound: Bound<DateTime>, lower: bool) {
+    let (op, time) = match (bound, lower) {
+        (Bound::Included(t), true) => (">=", t),
+        (Bound::Excluded(t), true) => (">", t),
+        (Bound::Included(t), false) => ("<=", t),
+        (Bound::Excluded(t), false) => ("<", t),
+        (Bound::Unbounded, _) => return,
+    };
+    params.push(Value::Integer(time.checked_ticks()));
+    sql.push_str(&format!(" AND time {op} ?{}", params.len()));
+}
+
+fn decode_value(bytes: Vec<u8>, ctx: &Context<'_>) -> Result<DataValue, StatusCode> {
+    DataValue::decode(&mut Cursor::new(bytes), ctx).map_err(|e| {
+        error!("Failed to decode historical value: {e}");
+        StatusCode::BadDataLost
+    })
+}
+
+/// Build a query for the values of a node in the time range of `start` and `end`,
+/// selecting `columns` from `table`.
+fn range_query(
+    columns: &str,
+    table: &str,
+    node_id: &NodeId,
+    start: Bound<DateTime>,
+    end: Bound<DateTime>,
+) -> (String, Vec<Value>) {
+    let mut sql = format!("SELECT {columns} FROM {table} WHERE node_id = ?1");
+    let mut params = vec![Value::Text(node_id.to_string())];
+    push_bound(&mut sql, &mut params, start, true);
+    push_bound(&mut sql, &mut params, end, false);
+    (sql, params)
+}
+
+/// Add ordering and the value limit of `query` to a range query.
+fn push_order_limit(sql: &mut String, params: &mut Vec<Value>, query: &HistoryRawQuery) {
+    if query.reverse {
+        sql.push_str(" ORDER BY time DESC, id DESC");
+    } else {
+        sql.push_str(" ORDER BY time ASC, id ASC");
+    }
+    params.push(Value::Integer(
+        query.max_values.try_into().unwrap_or(i64::MAX),
+    ));
+    sql.push_str(&format!(" LIMIT ?{}", params.len()));
+}
+
+/// Keep a value that was changed by a history update, for reading modified values.
+fn push_modified(
+    tx: &rusqlite::Transaction<'_>,
+    ctx: &Context<'_>,
+    node_id: &str,
+    value: &DataValue,
+    update_type: HistoryUpdateType,
+    user_name: &str,
+) -> Result<(), rusqlite::Error> {
+    tx.prepare_cached(
+        "INSERT INTO history_modified
+        (node_id, time, value, modification_time, update_type, user_name)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(rusqlite::params![
+        node_id,
+        value_time(value).checked_ticks(),
+        value.encode_to_vec(ctx),
+        DateTime::now().checked_ticks(),
+        update_type as i32,
+        user_name
+    ])?;
+    Ok(())
+}
+
+fn remove_expired(
+    tx: &rusqlite::Transaction<'_>,
+    retention: &SqliteHistoryRetention,
+    node_ids: &[String],
+) -> Result<(), rusqlite::Error> {
+    if let Some(max_age) = retention.max_age {
+        let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+        let oldest = DateTime::now()
+            .as_chrono()
+            .checked_sub_signed(max_age)
+            .map(DateTime::from)
+            .unwrap_or_else(DateTime::null);
+        tx.execute(
+            "DELETE FROM history_values WHERE time < ?1",
+            [oldest.checked_ticks()],
+        )?;
+        tx.execute(
+            "DELETE FROM history_modified WHERE time < ?1",
+            [oldest.checked_ticks()],
+        )?;
+    }
+    if let Some(max_values) = retention.max_values_per_node {
+        for table in ["history_values", "history_modified"] {
+            // Skip the newest values using the node and time index, deleting only the rest.
+            let mut stmt = tx.prepare_cached(&format!(
+                "DELETE FROM {table} WHERE id IN (
+                    SELECT id FROM {table} WHERE node_id = ?1
+                    ORDER BY time DESC, id DESC LIMIT -1 OFFSET ?2
+                )"
+            ))?;
+            for node_id in node_ids {
+                stmt.execute(rusqlite::params![node_id, max_values as i64])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl HistoryProvider for SqliteHistoryProvider {
+    async fn read_raw(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryRawQuery,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        let (mut sql, mut params) =
+            range_query("value", "history_values", node_id, query.start, query.end);
+        push_order_limit(&mut sql, &mut params, query);
+
+        self.with_connection(move |connection, encoding_context| {
+            let mut stmt = connection.prepare_cached(&sql).map_err(db_error)?;
+            let rows = stmt
+                .query_map(params_from_iter(params), |r| r.get::<_, Vec<u8>>(0))
+                .map_err(db_error)?;
+            let ctx = encoding_context.context();
+            let mut values = Vec::new();
+            for row in rows {
+                values.push(decode_value(row.map_err(db_error)?, &ctx)?);
+            }
+            Ok(values)
+        })
+        .await
+    }
+
+    async fn read_modified(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryRawQuery,
+    ) -> Result<Vec<(DataValue, ModificationInfo)>, StatusCode> {
+        let (mut sql, mut params) = range_query(
+            "value, modification_time, update_type, user_name",
+            "history_modified",
+            node_id,
+            query.start,
+            query.end,
+        );
+        push_order_limit(&mut sql, &mut params, query);
+
+        self.with_connection(move |connection, encoding_context| {
+            let mut stmt = connection.prepare_cached(&sql).map_err(db_error)?;
+            let rows = stmt
+                .query_map(params_from_iter(params), |r| {
+                    Ok((
+                        r.get::<_, Vec<u8>>(0)?,
+                        r.get::<_, i64>(1)?,
+                        r.get::<_, i32>(2)?,
+                        r.get::<_, String>(3)?,
+                    ))
+                })
+                .map_err(db_error)?;
+            let ctx = encoding_context.context();
+            let mut values = Vec::new();
+            for row in rows {
+                let (bytes, modification_time, update_type, user_name) = row.map_err(db_error)?;
+                let info = ModificationInfo {
+                    modification_time: DateTime::from(modification_time),
+                    update_type: HistoryUpdateType::try_from(update_type)
+                        .map_err(|_| StatusCode::BadDataLost)?,
+                    user_name: user_name.into(),
+                };
+                values.push((decode_value(bytes, &ctx)?, info));
+            }
+            Ok(values)
+        })
+        .await
+    }
+
+    async fn record_values(&self, values: Vec<(NodeId, DataValue)>) -> Result<(), StatusCode> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let retention = self.retention.clone();
+        let retention_state = self.retention_state.clone();
+        self.with_connection(move |connection, encoding_context| {
+            let ctx = encoding_context.context();
+            let tx = connection.transaction().map_err(db_error)?;
+            let mut node_ids = HashSet::new();
+            {
+                let mut stmt = tx
+                    .prepare_cached(
+                        "INSERT INTO history_values (node_id, time, value) VALUES (?1, ?2, ?3)",
+                    )
+                    .map_err(db_error)?;
+                for (node_id, mut value) in values {
+                    if value.source_timestamp.is_none() {
+                        value.source_timestamp =
+                            Some(value.server_timestamp.unwrap_or_else(DateTime::now));
+                    }
+                    let node_id = node_id.to_string();
+                    stmt.execute(rusqlite::params![
+                        node_id,
+                        value_time(&value).checked_ticks(),
+                        value.encode_to_vec(&ctx)
+                    ])
+                    .map_err(db_error)?;
+                    node_ids.insert(node_id);
+                }
+            }
+            let due = retention_state.lock().take_due(&retention, node_ids);
+            if let Some(node_ids) = due {
+                remove_expired(&tx, &retention, &node_ids).map_err(db_error)?;
+            }
+            tx.commit().map_err(db_error)
+        })
+        .await
+    }
+
+    async fn update_values(
+        &self,
+        node_id: &NodeId,
+        perform_update: PerformUpdateType,
+        values: Vec<DataValue>,
+        user_name: &str,
+    ) -> Result<Vec<HistoryValueUpdate>, StatusCode> {
+        let node_id = node_id.to_string();
+        let user_name = user_name.to_owned();
+        let retention = self.retention.clone();
+        let retention_state = self.retention_state.clone();
+        self.with_connection(move |connection, encoding_context| {
+            let ctx = encoding_context.context();
+            let tx = connection.transaction().map_err(db_error)?;
+            let mut results = Vec::with_capacity(values.len());
+            for value in values {
+                let Some(time) = value.source_timestamp else {
+                    results.push(HistoryValueUpdate::new(StatusCode::BadInvalidTimestamp));
+                    continue;
+                };
+                let existing = tx
+                    .prepare_cached(
+                        "SELECT id, value FROM history_values WHERE node_id = ?1 AND time = ?2
+                        ORDER BY id LIMIT 1",
+                    )
+                    .and_then(|mut stmt| {
+                        stmt.query_row(rusqlite::params![node_id, time.checked_ticks()], |r| {
+                            Ok((r.get::<_, i64>(0)?, r.get::<_, Vec<u8>>(1)?))
+                        })
+                        .optional()
+                    })
+                    .map_err(db_error)?;
+                let existing = match existing {
+                    Some((id, bytes)) => Some((id, decode_value(bytes, &ctx)?)),
+                    None => None,
+                };
+
+                let result = match (perform_update, existing) {
+                    (PerformUpdateType::Insert, Some(_)) => {
+                        HistoryValueUpdate::new(StatusCode::BadEntryExists)
+                    }
+                    (PerformUpdateType::Replace | PerformUpdateType::Remove, None) => {
+                        HistoryValueUpdate::new(StatusCode::BadNoEntryExists)
+                    }
+                    (PerformUpdateType::Insert | PerformUpdateType::Update, None) => {
+                        tx.prepare_cached(
+                            "INSERT INTO history_values (node_id, time, value) VALUES (?1, ?2, ?3)",
+                        )
+                        .and_then(|mut stmt| {
+                            stmt.execute(rusqlite::params![
+                                node_id,
+                                time.checked_ticks(),
+                                value.encode_to_vec(&ctx)
+                            ])
+                        })
+                        .map_err(db_error)?;
+                        push_modified(
+                            &tx,
+                            &ctx,
+                            &node_id,
+                            &value,
+                            HistoryUpdateType::Insert,
+                            &user_name,
+                        )
+                        .map_err(db_error)?;
+                        HistoryValueUpdate::new(StatusCode::GoodEntryInserted)
+                    }
+                    (PerformUpdateType::Replace | PerformUpdateType::Update, Some((id, old))) => {
+                        tx.prepare_cached("UPDATE history_values SET value = ?1 WHERE id = ?2")
+                            .and_then(|mut stmt| {
+                                stmt.execute(rusqlite::params![value.encode_to_vec(&ctx), id])
+                            })
+                            .map_err(db_error)?;
+                        let update_type = if perform_update == PerformUpdateType::Replace {
+                            HistoryUpdateType::Replace
+                        } else {
+                            HistoryUpdateType::Update
+                        };
+                        push_modified(&tx, &ctx, &node_id, &old, update_type, &user_name)
+                            .map_err(db_error)?;
+                        HistoryValueUpdate {
+                            status: StatusCode::GoodEntryReplaced,
+                            old_value: Some(old),
+                        }
+                    }
+                    (PerformUpdateType::Remove, Some((id, old))) => {
+                        tx.prepare_cached("DELETE FROM history_values WHERE id = ?1")
+                            .and_then(|mut stmt| stmt.execute([id]))
+                            .map_err(db_error)?;
+                        push_modified(
+                            &tx,
+                            &ctx,
+                            &node_id,
+                            &old,
+                            HistoryUpdateType::Delete,
+                            &user_name,
+                        )
+                        .map_err(db_error)?;
+                        HistoryValueUpdate {
+                            status: StatusCode::Good,
+                            old_value: Some(old),
+                        }
+                    }
+                };
+                results.push(result);
+            }
+            let due = retention_state.lock().take_due(&retention, [node_id]);
+            if let Some(node_ids) = due {
+                remove_expired(&tx, &retention, &node_ids).map_err(db_error)?;
+            }
+            tx.commit().map_err(db_error)?;
+            Ok(results)
+        })
+        .await
+    }
+
+    async fn delete_values(
+        &self,
+        node_id: &NodeId,
+        start: Bound<DateTime>,
+        end: Bound<DateTime>,
+        is_delete_modified: bool,
+        user_name: &str,
+    ) -> Result<Vec<DataValue>, StatusCode> {
+        let table = if is_delete_modified {
+            "history_modified"
+        } else {
+            "history_values"
+        };
+        let (mut sql, params) = range_query("id, value", table, node_id, start, end);
+        sql.push_str(" ORDER BY time ASC, id ASC");
+        let node_id = node_id.to_string();
+        let user_name = user_name.to_owned();
+
+        self.with_connection(move |connection, encoding_context| {
+            let ctx = encoding_context.context();
+            let tx = connection.transaction().map_err(db_error)?;
+            let rows = {
+                let mut stmt = tx.prepare_cached(&sql).map_err(db_error)?;
+                let rows = stmt
+                    .query_map(params_from_iter(params), |r| {
+                        Ok((r.get::<_, i64>(0)?, r.get::<_, Vec<u8>>(1)?))
+                    })
+                    .map_err(db_error)?;
+                rows.collect::<Result<Vec<_>, _>>().map_err(db_error)?
+            };
+            let mut deleted = Vec::with_capacity(rows.len());
+            {
+                let mut stmt = tx
+                    .prepare_cached(&format!("DELETE FROM {table} WHERE id = ?1"))
+                    .map_err(db_error)?;
+                for (id, bytes) in rows {
+                    stmt.execute([id]).map_err(db_error)?;
+                    deleted.push(decode_value(bytes, &ctx)?);
+                }
+            }
+            if !is_delete_modified {
+                for value in &deleted {
+                    push_modified(
+                        &tx,
+                        &ctx,
+                        &node_id,
+                        value,
+                        HistoryUpdateType::Delete,
+                        &user_name,
+                    )
+                    .map_err(db_error)?;
+                }
+            }
+            tx.commit().map_err(db_error)?;
+            Ok(deleted)
+        })
+        .await
+    }
+}
@@ -0,0 +1,362 @@
+use std::ops::Bound;
+
+use opcua_types::{DataTypeId, DataValue, DateTime, NodeId, PerformUpdateType, StatusCode};
+
+use crate::node_manager::RequestContext;
+
+use super::{provider::HistoryProvider, HistoryUpdateDetails, HistoryUpdateNode};
+
+/// Audit events raised for history updates when `Server.Auditing` is enabled.
+/// The event types come from the core namespace, so without the generated address
+/// space no events are raised.
+#[cfg(feature = "generated-address-space")]
+mod audit {
+    use opcua_core_namespace::events::{
+        AuditHistoryAtTimeDeleteEventType, AuditHistoryDeleteEventType,
+        AuditHistoryRawModifyDeleteEventType, AuditHistoryUpdateEventType,
+        AuditHistoryValueUpdateEventType,
+    };
+    use opcua_crypto::random;
+    use opcua_nodes::{Event, NamespaceMap};
+    use opcua_types::{DataTypeId, DataValue, DateTime, NodeId, ObjectId, PerformUpdateType};
+
+    use crate::node_manager::RequestContext;
+
+    /// Create the common part of a history update audit event.
+    fn audit_base(
+        context: &RequestContext,
+        type_id: NodeId,
+        node_id: &NodeId,
+        parameter_data_type_id: DataTypeId,
+        status: bool,
+    ) -> AuditHistoryUpdateEventType {
+        let mut event = AuditHistoryUpdateEventType::new_event_now(
+            type_id,
+            random::byte_string(16),
+            "History updated",
+            &NamespaceMap::new(),
+        );
+        let audit = &mut event.base.base;
+        audit.base = std::mem::take(&mut audit.base)
+            .set_source_node(node_id.clone())
+            .set_severity(if status { 100 } else { 500 });
+        audit.action_time_stamp = audit.base.time;
+        audit.status = status;
+        audit.server_id = context.info.application_uri.clone();
+        audit.client_user_id = context.token.0.as_str().into();
+        event.parameter_data_type_id = parameter_data_type_id.into();
+        event
+    }
+
+    fn notify(context: &RequestContext, event: &dyn Event) {
+        let server_id: NodeId = ObjectId::Server.into();
+        context
+            .subscriptions
+            .notify_events([(event, &server_id)].into_iter());
+    }
+
+    pub(super) fn enabled(context: &RequestContext) -> bool {
+        context.info.config.auditing
+    }
+
+    pub(super) fn value_update(
+        context: &RequestContext,
+        node_id: &NodeId,
+        parameter_data_type_id: DataTypeId,
+        status: bool,
+        perform_update: PerformUpdateType,
+        new_values: Vec<DataValue>,
+        old_values: Vec<DataValue>,
+    ) {
+        let event = AuditHistoryValueUpdateEventType {
+            base: audit_base(
+                context,
+                AuditHistoryValueUpdateEventType::event_type_id(),
+                node_id,
+                parameter_data_type_id,
+                status,
+            ),
+            updated_node: node_id.clone(),
+            perform_insert_replace: perform_update,
+            new_values,
+            old_values,
+        };
+        notify(context, &event);
+    }
+
+    pub(super) fn raw_modify_delete(
+        context: &RequestContext,
+        node_id: &NodeId,
+        is_delete_modified: bool,
+        start_time: DateTime,
+        end_time: DateTime,
+        old_values: Vec<DataValue>,
+    ) {
+        let event = AuditHistoryRawModifyDeleteEventType {
+            base: AuditHistoryDeleteEventType {
+                base: audit_base(
+                    context,
+                    AuditHistoryRawModifyDeleteEventType::event_type_id(),
+                    node_id,
+                    DataTypeId::DeleteRawModifiedDetails,
+                    true,
+                ),
+                updated_node: node_id.clone(),
+            },
+            is_delete_modified,
+            start_time,
+            end_time,
+            old_values,
+        };
+        notify(context, &event);
+    }
+
+    pub(super) fn at_time_delete(
+        context: &RequestContext,
+        node_id: &NodeId,
+        status: bool,
+        req_times: Vec<DateTime>,
+        old_values: Vec<DataValue>,
+    ) {
+        let event = AuditHistoryAtTimeDeleteEventType {
+            base: AuditHistoryDeleteEventType {
+                base: audit_base(
+                    context,
+                    AuditHistoryAtTimeDeleteEventType::event_type_id(),
+                    node_id,
+                    DataTypeId::DeleteAtTimeDetails,
+                    status,
+                ),
+                updated_node: node_id.clone(),
+            },
+            req_times,
+            old_values,
+        };
+        notify(context, &event);
+    }
+}
+
+#[cfg(not(feature = "generated-address-space"))]
+mod audit {
+    use opcua_types::{DataTypeId, DataValue, DateTime, NodeId, PerformUpdateType};
+
+    use crate::node_manager::RequestContext;
+
+    pub(super) fn enabled(_context: &RequestContext) -> bool {
+        false
+    }
+
+    pub(super) fn value_update(
+        _context: &RequestContext,
+        _node_id: &NodeId,
+        _parameter_data_type_id: DataTypeId,
+        _status: bool,
+        _perform_update: PerformUpdateType,
+        _new_values: Vec<DataValue>,
+        _old_values: Vec<DataValue>,
+    ) {
+    }
+
+    pub(super) fn raw_modify_delete(
+        _context: &RequestContext,
+        _node_id: &NodeId,
+        _is_delete_modified: bool,
+        _start_time: DateTime,
+        _end_time: DateTime,
+        _old_values: Vec<DataValue>,
+    ) {
+    }
+
+    pub(super) fn at_time_delete(
+        _context: &RequestContext,
+        _node_id: &NodeId,
+        _status: bool,
+        _req_times: Vec<DateTime>,
+        _old_values: Vec<DataValue>,
+    ) {
+    }
+}
+
+async fn update_data(
+    provider: &dyn HistoryProvider,
+    context: &RequestContext,
+    node_id: &NodeId,
+    perform_update: PerformUpdateType,
+    values: Vec<DataValue>,
+    parameter_data_type_id: DataTypeId,
+) -> Result<Option<Vec<StatusCode>>, StatusCode> {
+    if values.is_empty() {
+        return Err(StatusCode::BadNothingToDo);
+    }
+    let new_values = audit::enabled(context).then(|| values.clone());
+    let outcomes = provider
+        .update_values(node_id, perform_update, values, &context.token.0)
+        .await?;
+    let status = outcomes.iter().all(|o| o.status.is_good());
+    let (results, old_values) = outcomes
+        .into_iter()
+        .map(|o| (o.status, o.old_value))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+
+    if let Some(new_values) = new_values {
+        audit::value_update(
+            context,
+            node_id,
+            parameter_data_type_id,
+            status,
+            perform_update,
+            new_values,
+            old_values.into_iter().flatten().collect(),
+        );
+    }
+    Ok(Some(results))
+}
+
+async fn delete_raw_modified(
+    provider: &dyn HistoryProvider,
+    context: &RequestContext,
+    node_id: &NodeId,
+    is_delete_modified: bool,
+    start_time: DateTime,
+    end_time: DateTime,
+) -> Result<Option<Vec<StatusCode>>, StatusCode> {
+    if start_time.is_null() || end_time.is_null() {
+        return Err(StatusCode::BadInvalidArgument);
+    }
+    let (start, end) = if start_time == end_time {
+        (Bound::Included(start_time), Bound::Included(end_time))
+    } else {
+        (
+            Bound::Included(start_time.min(end_time)),
+            Bound::Excluded(start_time.max(end_time)),
+        )
+    };
+    let old_values = provider
+        .delete_values(node_id, start, end, is_delete_modified, &context.token.0)
+        .await?;
+    let found = !old_values.is_empty();
+
+    if audit::enabled(context) {
+        audit::raw_modify_delete(
+            context,
+            node_id,
+            is_delete_modified,
+            start_time,
+            end_time,
+            old_values,
+        );
+    }
+    if found {
+        Ok(None)
+    } else {
+        Err(StatusCode::GoodNoData)
+    }
+}
+
+async fn delete_at_time(
+    provider: &dyn HistoryProvider,
+    context: &RequestContext,
+    node_id: &NodeId,
+    req_times: Vec<DateTime>,
+) -> Result<Option<Vec<StatusCode>>, StatusCode> {
+    if req_times.is_empty() {
+        return Err(StatusCode::BadNothingToDo);
+    }
+    let mut results = Vec::with_capacity(req_times.len());
+    let mut old_values = Vec::new();
+    for time in &req_times {
+        let deleted = provider
+            .delete_values(
+                node_id,
+                Bound::Included(*time),
+                Bound::Included(*time),
+                false,
+                &context.token.0,
+            )
+            .await?;
+        results.push(if deleted.is_empty() {
+            StatusCode::BadNoEntryExists
+        } else {
+            StatusCode::Good
+        });
+        old_values.extend(deleted);
+    }
+
+    if audit::enabled(context) {
+        let status = results.iter().all(|r| r.is_good());
+        audit::at_time_delete(context, node_id, status, req_times, old_values);
+    }
+    Ok(Some(results))
+}
+
+/// Implement the history update service for a single node using a [`HistoryProvider`],
+/// writing the status and operation results to `node`.
+///
+/// This supports updating and deleting data values, `UpdateData`, `UpdateStructureData`,
+/// `DeleteRawModified` and `DeleteAtTime`. If auditing is enabled with
+/// [`ServerBuilder::auditing`](crate::ServerBuilder::auditing), each update raises the
+/// matching audit event, such as `AuditHistoryValueUpdateEventType`, on the `Server` object.
+pub async fn impl_history_update(
+    provider: &dyn HistoryProvider,
+    context: &RequestContext,
+    node: &mut HistoryUpdateNode,
+) {
+    let res = match node.details() {
+        HistoryUpdateDetails::UpdateData(d) => {
+            if d.perform_insert_replace == PerformUpdateType::Remove {
+                Err(StatusCode::BadHistoryOperationInvalid)
+            } else {
+                update_data(
+                    provider,
+                    context,
+                    &d.node_id,
+                    d.perform_insert_replace,
+                    d.update_values.clone().unwrap_or_default(),
+                    DataTypeId::UpdateDataDetails,
+                )
+                .await
+            }
+        }
+        HistoryUpdateDetails::UpdateStructureData(d) => {
+            update_data(
+                provider,
+                context,
+                &d.node_id,
+                d.perform_insert_replace,
+                d.update_values.clone().unwrap_or_default(),
+                DataTypeId::UpdateStructureDataDetails,
+            )
+            .await
+        }
+        HistoryUpdateDetails::DeleteRawModified(d) => {
+            delete_raw_modified(
+                provider,
+                context,
+                &d.node_id,
+                d.is_delete_modified,
+                d.start_time,
+                d.end_time,
+            )
+            .await
+        }
+        HistoryUpdateDetails::DeleteAtTime(d) => {
+            delete_at_time(
+                provider,
+                context,
+                &d.node_id,
+                d.req_times.clone().unwrap_or_default(),
+            )
+            .await
+        }
+        HistoryUpdateDetails::UpdateEvent(_) | HistoryUpdateDetails::DeleteEvent(_) => {
+            Err(StatusCode::BadHistoryOperationUnsupported)
+        }
+    };
+    match res {
+        Ok(results) => {
+            node.set_operation_results(results);
+            node.set_status(StatusCode::Good);
+        }
+        Err(e) => node.set_status(e),
+    }
+}
@@ -3,24 +3,25 @@ use std::{sync::Arc, time::Duration};
 use async_trait::async_trait;
 use chrono::Offset;
 use hashbrown::HashMap;
-use opcua_nodes::NodeType;
+use opcua_nodes::{EventNotifier, NodeType};
 
 use crate::{
     address_space::{read_node_value, AddressSpace, CoreNamespace},
     diagnostics::NamespaceMetadata,
     load_method_args,
     node_manager::{
-        MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagersRef, ParsedReadValueId,
-        RequestContext, ServerContext, SyncSampler,
+        HistoryNode, MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagersRef,
+        ParsedReadValueId, ParsedWriteValue, RequestContext, ServerContext, SyncSampler, WriteNode,
     },
     subscriptions::CreateMonitoredItem,
     ServerCapabilities, ServerStatusWrapper,
 };
-use opcua_core::{sync::RwLock, trace_lock};
+use opcua_core::{sync::RwLock, trace_lock, trace_read_lock, trace_write_lock};
 use opcua_types::{
-    DataValue, DateTime, ExtensionObject, IdType, Identifier, MethodId, MonitoringMode, NodeId,
-    NumericRange, ObjectId, ReferenceTypeId, StatusCode, TimeZoneDataType, TimestampsToReturn,
-    VariableId, Variant, VariantScalarTypeId, VariantTypeId,
+    node_id::IntoNodeIdRef, AttributeId, DataValue, DateTime, ExtensionObject, IdType, Identifier,
+    IdentityMappingRuleType, MessageSecurityMode, MethodId, MonitoringMode, NodeId, NumericRange,
+    ObjectId, QualifiedName, ReadEventDetails, ReferenceTypeId, StatusCode, TimeZoneDataType,
+    TimestampsToReturn, VariableId, Variant, VariantScalarTypeId, VariantTypeId,
 };
 
 use super::{InMemoryNodeManager, InMemoryNodeManagerImpl, InMemoryNodeManagerImplBuilder};
@@ -30,6 +31,8 @@ pub struct CoreNodeManagerImpl {
     sampler: SyncSampler,
     node_managers: NodeManagersRef,
     status: Arc<ServerStatusWrapper>,
+    has_event_history: bool,
+    server_namespace: u16,
 }
 
 /// Node manager for the core namespace.
@@ -42,12 +45,22 @@ impl InMemoryNodeManagerImplBuilder for CoreNodeManagerBuilder {
     type Impl = CoreNodeManagerImpl;
 
     fn build(self, context: ServerContext, address_space: &mut AddressSpace) -> Self::Impl {
-        {
+        let server_namespace = {
             let mut type_tree = context.type_tree.write();
             address_space.import_node_set(&CoreNamespace, type_tree.namespaces_mut());
-        }
+            // Roles added at runtime are created in the namespace of the server.
+            let application_uri = context.info.application_uri.as_ref();
+            let index = type_tree.namespaces_mut().add_namespace(application_uri);
+            address_space.add_namespace(application_uri, index);
+            index
+        };
 
-        CoreNodeManagerImpl::new(context.node_managers.clone(), context.status.clone())
+        CoreNodeManagerImpl::new(
+            context.node_managers.clone(),
+            context.status.clone(),
+            context.info.event_history.is_some(),
+            server_namespace,
+        )
     }
 }
 
@@ -64,6 +77,12 @@ of changes to these to the one doing the modifying.
 impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
     async fn init(&self, address_space: &mut AddressSpace, context: ServerContext) {
         self.add_aggregates(address_space, &context.info.capabilities);
+        if self.has_event_history {
+            // Events raised on the server can be historized.
+            if let Some(NodeType::Object(server)) = address_space.find_mut(ObjectId::Server) {
+                server.set_event_notifier(server.event_notifier() | EventNotifier::HISTORY_READ);
+            }
+        }
         let interval = context
             .info
             .config
@@ -79,6 +98,20 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
         // Some core methods should be generally executable
         Self::set_method_executable(address_space, MethodId::Server_GetMonitoredItems);
         Self::set_method_executable(address_space, MethodId::Server_ResendData);
+        Self::set_method_executable(address_space, MethodId::ConditionType_ConditionRefresh);
+        Self::set_method_executable(address_space, MethodId::ConditionType_ConditionRefresh2);
+        // Role management is restricted to security admins when the methods are called.
+        Self::set_method_executable(
+            address_space,
+            MethodId::Server_ServerCapabilities_RoleSet_AddRole,
+        );
+        Self::set_method_executable(
+            address_space,
+            MethodId::Server_ServerCapabilities_RoleSet_RemoveRole,
+        );
+        for method in context.info.roles.identity_methods() {
+            Self::set_method_executable(address_space, &method);
+        }
     }
 
     fn namespaces(&self) -> Vec<NamespaceMetadata> {
@@ -100,6 +133,16 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
         "core"
     }
 
+    fn owns_node(&self, id: &NodeId) -> bool {
+        // The rest of the server namespace belongs to the diagnostics node manager,
+        // which uses opaque node IDs.
+        id.namespace != self.server_namespace || matches!(id.identifier, Identifier::Guid(_))
+    }
+
+    fn owns_server_events(&self) -> bool {
+        self.has_event_history
+    }
+
     async fn read_values(
         &self,
         context: &RequestContext,
@@ -118,20 +161,51 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
             .collect()
     }
 
+    async fn history_read_events(
+        &self,
+        context: &RequestContext,
+        details: &ReadEventDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        _timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let Some(history) = &context.info.event_history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        history.history_read_events(context, details, nodes).await
+    }
+
     async fn call(
         &self,
         context: &RequestContext,
-        _address_space: &RwLock<AddressSpace>,
+        address_space: &RwLock<AddressSpace>,
         methods_to_call: &mut [&mut &mut MethodCall],
     ) -> Result<(), StatusCode> {
         for method in methods_to_call {
-            if let Err(e) = self.call_builtin_method(method, context) {
+            if let Err(e) = self.call_builtin_method(method, context, address_space) {
                 method.set_status(e);
             }
         }
         Ok(())
     }
 
+    async fn write(
+        &self,
+        context: &RequestContext,
+        _address_space: &RwLock<AddressSpace>,
+        nodes_to_write: &mut [&mut WriteNode],
+    ) -> Result<(), StatusCode> {
+        for write in nodes_to_write {
+            let status = match self.get_variable_id(&write.value().node_id) {
+                Some(VariableId::Server_ServerDiagnostics_EnabledFlag) => {
+                    Self::write_diagnostics_enabled(context, write.value())
+                }
+                _ => Err(StatusCode::BadNotWritable),
+            };
+            write.set_status(status.err().unwrap_or(StatusCode::Good));
+        }
+        Ok(())
+    }
+
     async fn create_value_monitored_items(
         &self,
         context: &RequestContext,
@@ -185,6 +259,13 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
                     item.handle(),
                     mode,
                 );
+            } else {
+                self.sampler.set_sampler_mode(
+                    item.node_id(),
+                    item.attribute(),
+                    item.handle(),
+                    mode,
+                );
             }
         }
     }
@@ -195,12 +276,20 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
         items: &[&MonitoredItemUpdateRef],
     ) {
         for item in items {
+            let interval = Duration::from_millis(item.update().revised_sampling_interval as u64);
             if self.status.get_managed_id(item.node_id()).is_some() {
                 self.status.sampler().update_sampler(
                     item.node_id(),
                     item.attribute(),
                     item.handle(),
-                    Duration::from_millis(item.update().revised_sampling_interval as u64),
+                    interval,
+                );
+            } else {
+                self.sampler.update_sampler(
+                    item.node_id(),
+                    item.attribute(),
+                    item.handle(),
+                    interval,
                 );
             }
         }
@@ -214,17 +303,29 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
                     item.attribute(),
                     item.handle(),
                 );
+            } else {
+                // Diagnostics may have been disabled since the item was created,
+                // so remove any internal sampler regardless of `is_internal_sampled`.
+                self.sampler
+                    .remove_sampler(item.node_id(), item.attribute(), item.handle());
             }
         }
     }
 }
 
 impl CoreNodeManagerImpl {
-    pub(super) fn new(node_managers: NodeManagersRef, status: Arc<ServerStatusWrapper>) -> Self {
+    pub(super) fn new(
+        node_managers: NodeManagersRef,
+        status: Arc<ServerStatusWrapper>,
+        has_event_history: bool,
+        server_namespace: u16,
+    ) -> Self {
         Self {
             sampler: SyncSampler::new(),
             status,
             node_managers,
+            has_event_history,
+            server_namespace,
         }
     }
 
@@ -252,6 +353,8 @@ impl CoreNodeManagerImpl {
         // In this case, the values are largely read from configuration.
         if let Some(v) = self.read_server_value(context, node_to_read) {
             v
+        } else if let Some(v) = self.read_role_value(context, node_to_read) {
+            v
         } else {
             // If it can't be found, read it from the node hierarchy.
             read_node_value(node, context, node_to_read, max_age, timestamps_to_return)
@@ -274,6 +377,7 @@ impl CoreNodeManagerImpl {
         };
 
         context.info.diagnostics.is_mapped(variable_id)
+            || context.info.diagnostics.is_diagnostics_array(variable_id)
     }
 
     fn add_internal_sampler(
@@ -296,11 +400,39 @@ impl CoreNodeManagerImpl {
                 Duration::from_millis(monitored_item.sampling_interval() as u64),
             );
             Ok(())
+        } else if context.info.diagnostics.is_diagnostics_array(var_id) {
+            let info = context.info.clone();
+            let subscriptions = context.subscriptions.clone();
+            self.sampler.add_sampler(
+                monitored_item.item_to_monitor().node_id.clone(),
+                monitored_item.item_to_monitor().attribute_id,
+                move || {
+                    info.diagnostics
+                        .get_diagnostics_array(var_id, &subscriptions)
+                },
+                monitored_item.monitoring_mode(),
+                monitored_item.handle(),
+                Duration::from_millis(monitored_item.sampling_interval() as u64),
+            );
+            Ok(())
         } else {
             Err(StatusCode::BadNodeIdUnknown)
         }
     }
 
+    /// Read the `Identities` property of a role, which is stored in the role set.
+    fn read_role_value(
+        &self,
+        context: &RequestContext,
+        node: &ParsedReadValueId,
+    ) -> Option<DataValue> {
+        if node.attribute_id != AttributeId::Value {
+            return None;
+        }
+        let v = context.info.roles.identities_value(&node.node_id)?;
+        Some(Self::server_data_value(context, node, v))
+    }
+
     fn read_server_value(
         &self,
         context: &RequestContext,
@@ -312,6 +444,9 @@ impl CoreNodeManagerImpl {
         let hist_cap = &context.info.capabilities.history;
 
         let v: Variant = match var_id {
+            VariableId::Server_ServerCapabilities_LocaleIdArray => {
+                context.info.config.locale_ids.clone().into()
+            }
             VariableId::Server_ServerCapabilities_MaxArrayLength => {
                 (limits.max_array_length as u32).into()
             }
@@ -424,6 +559,10 @@ impl CoreNodeManagerImpl {
             VariableId::Server_ServiceLevel => {
                 context.info.service_level.load(std::sync::atomic::Ordering::Relaxed).into()
             }
+            VariableId::Server_ServerDiagnostics_EnabledFlag => {
+                context.info.diagnostics.enabled().into()
+            }
+            VariableId::Server_Auditing => context.info.config.auditing.into(),
             VariableId::Server_LocalTime => {
                 let offset = chrono::Local::now().offset().fix().local_minus_utc() / 60;
                 ExtensionObject::from_message(TimeZoneDataType {
@@ -501,45 +640,100 @@ impl CoreNodeManagerImpl {
                 }
             }
 
+            r if context.info.diagnostics.is_diagnostics_array(r) => {
+                let perms = context.info.authenticator.core_permissions(&context.token);
+                if !perms.read_diagnostics {
+                    return Some(DataValue::new_now_status(Variant::Empty, StatusCode::BadUserAccessDenied));
+                } else {
+                    return Some(context.info.diagnostics.get_diagnostics_array(r, &context.subscriptions).unwrap_or_default())
+                }
+            }
+
             _ => return None,
 
         };
 
+        Some(Self::server_data_value(context, node, v))
+    }
+
+    fn server_data_value(
+        context: &RequestContext,
+        node: &ParsedReadValueId,
+        v: Variant,
+    ) -> DataValue {
         let v = if !matches!(node.index_range, NumericRange::None) {
             match v.range_of(&node.index_range) {
                 Ok(v) => v,
                 Err(e) => {
-                    return Some(DataValue {
+                    return DataValue {
                         value: None,
                         status: Some(e),
                         ..Default::default()
-                    })
+                    }
                 }
             }
         } else {
             v
         };
 
-        Some(DataValue {
+        DataValue {
             value: Some(v),
             status: Some(StatusCode::Good),
             source_timestamp: Some(**context.info.start_time.load()),
             server_timestamp: Some(**context.info.start_time.load()),
             ..Default::default()
-        })
+        }
     }
 
     fn add_aggregates(&self, address_space: &mut AddressSpace, capabilities: &ServerCapabilities) {
         for aggregate in &capabilities.history.aggregates {
-            address_space.insert_reference(
-                &ObjectId::HistoryServerCapabilities_AggregateFunctions.into(),
-                aggregate,
-                ReferenceTypeId::Organizes,
-            )
+            for folder in [
+                ObjectId::HistoryServerCapabilities_AggregateFunctions,
+                ObjectId::Server_ServerCapabilities_AggregateFunctions,
+            ] {
+                address_space.insert_reference(
+                    &folder.into(),
+                    aggregate,
+                    ReferenceTypeId::Organizes,
+                )
+            }
         }
     }
 
-    fn set_method_executable(address_space: &mut AddressSpace, method: MethodId) {
+    /// Enable or disable diagnostics by writing the `EnabledFlag` of the
+    /// `ServerDiagnostics` object. This requires the `ConfigureAdmin` role.
+    fn write_diagnostics_enabled(
+        context: &RequestContext,
+        value: &ParsedWriteValue,
+    ) -> Result<(), StatusCode> {
+        if value.attribute_id != AttributeId::Value {
+            return Err(StatusCode::BadNotWritable);
+        }
+        if value.index_range.has_range() {
+            return Err(StatusCode::BadWriteNotSupported);
+        }
+        if !context
+            .roles
+            .contains(&ObjectId::WellKnownRole_ConfigureAdmin.into())
+        {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        let Some(Variant::Boolean(enabled)) = value.value.value else {
+            return Err(StatusCode::BadTypeMismatch);
+        };
+        context.info.diagnostics.set_enabled(enabled);
+        context.subscriptions.notify_data_change(
+            [(
+                DataValue::new_now(enabled),
+                &value.node_id,
+                AttributeId::Value,
+            )]
+            .into_iter(),
+        );
+        Ok(())
+    }
+
+    fn set_method_executable<'a>(address_space: &mut AddressSpace, method: impl IntoNodeIdRef<'a>) {
         let Some(NodeType::Method(m)) = address_space.find_mut(method) else {
             return;
         };
@@ -547,11 +741,44 @@ impl CoreNodeManagerImpl {
         m.set_user_executable(true);
     }
 
+    /// Check that the session may manage roles, which requires the `SecurityAdmin`
+    /// role and an encrypted channel.
+    fn check_security_admin(context: &RequestContext) -> Result<(), StatusCode> {
+        if !context
+            .roles
+            .contains(&ObjectId::WellKnownRole_SecurityAdmin.into())
+        {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        if trace_read_lock!(context.session).message_security_mode()
+            != MessageSecurityMode::SignAndEncrypt
+        {
+            return Err(StatusCode::BadSecurityModeInsufficient);
+        }
+        Ok(())
+    }
+
     fn call_builtin_method(
         &self,
         call: &mut MethodCall,
         context: &RequestContext,
+        address_space: &RwLock<AddressSpace>,
     ) -> Result<(), StatusCode> {
+        let roles = &context.info.roles;
+        if let Some((role_id, is_add)) = roles.identity_method(call.method_id()) {
+            Self::check_security_admin(context)?;
+            let rule = load_method_args!(call, ExtensionObject)?
+                .into_inner_as::<IdentityMappingRuleType>()
+                .ok_or(StatusCode::BadInvalidArgument)?;
+            if is_add {
+                roles.add_identity(&role_id, *rule)?;
+            } else {
+                roles.remove_identity(&role_id, &rule)?;
+            }
+            call.set_status(StatusCode::Good);
+            return Ok(());
+        }
+
         let Ok(id) = call.method_id().as_method_id() else {
             return Ok(());
         };
@@ -583,6 +810,47 @@ impl CoreNodeManagerImpl {
                 sub.set_resend_data();
                 call.set_status(StatusCode::Good);
             }
+            MethodId::ConditionType_ConditionRefresh => {
+                let id = load_method_args!(call, UInt32)?;
+                context
+                    .subscriptions
+                    .condition_refresh(context.session_id, id, None)?;
+                call.set_status(StatusCode::Good);
+            }
+            MethodId::Server_ServerCapabilities_RoleSet_AddRole => {
+                Self::check_security_admin(context)?;
+                let (name, namespace_uri) = load_method_args!(call, String, String)?;
+                // An empty namespace URI refers to the namespace of the server.
+                let namespace_uri = if namespace_uri.is_empty() {
+                    context.info.application_uri.as_ref()
+                } else {
+                    namespace_uri.as_ref()
+                };
+                let namespace = trace_read_lock!(context.type_tree)
+                    .namespaces()
+                    .get_index(namespace_uri)
+                    .ok_or(StatusCode::BadInvalidArgument)?;
+                let role_id = roles.add_role(
+                    &mut *trace_write_lock!(address_space),
+                    self.server_namespace,
+                    QualifiedName::new(namespace, name.as_ref()),
+                )?;
+                call.set_outputs(vec![role_id.into()]);
+                call.set_status(StatusCode::Good);
+            }
+            MethodId::Server_ServerCapabilities_RoleSet_RemoveRole => {
+                Self::check_security_admin(context)?;
+                let role_id = load_method_args!(call, NodeId)?;
+                roles.remove_role(&mut *trace_write_lock!(address_space), &role_id)?;
+                call.set_status(StatusCode::Good);
+            }
+            MethodId::ConditionType_ConditionRefresh2 => {
+                let (id, item_id) = load_method_args!(call, UInt32, UInt32)?;
+                context
+                    .subscriptions
+                    .condition_refresh(context.session_id, id, Some(item_id))?;
+                call.set_status(StatusCode::Good);
+            }
             _ => return Err(StatusCode::BadNotSupported),
         }
         Ok(())
@@ -9,6 +9,7 @@ use crate::{
         RegisterNodeItem, RequestContext, ServerContext, WriteNode,
     },
     subscriptions::CreateMonitoredItem,
+    SubscriptionCache,
 };
 use opcua_core::sync::RwLock;
 use opcua_types::{
@@ -53,13 +54,32 @@ pub trait InMemoryNodeManagerImpl: Send + Sync + 'static {
     /// Return the static list of namespaces this node manager uses.
     fn namespaces(&self) -> Vec<NamespaceMetadata>;
 
-    /// Return whether this node should handle requests to create a node
-    /// for the given parent ID. This is only called if no new node ID is
-    /// requested, otherwise owns_node is called on the requested node ID.
+    /// Return whether this node manager owns the node `id`, which is in one of the
+    /// namespaces of its address space. Node managers sharing a namespace with another
+    /// node manager can return `false` for the nodes they do not own.
+    fn owns_node(&self, id: &NodeId) -> bool {
+        true
+    }
+
+    /// Return whether this node manager owns events on the server.
+    /// The first node manager that returns true here will be called when
+    /// reading or updating historical server events.
     fn owns_server_events(&self) -> bool {
         false
     }
 
+    /// Called after attributes of `nodes` were changed with
+    /// [InMemoryNodeManager::set_attributes](crate::node_manager::memory::InMemoryNodeManager::set_attributes)
+    /// or [InMemoryNodeManager::set_values](crate::node_manager::memory::InMemoryNodeManager::set_values),
+    /// while the address space is still locked for reading.
+    fn nodes_changed(
+        &self,
+        address_space: &AddressSpace,
+        subscriptions: &SubscriptionCache,
+        nodes: &[&NodeId],
+    ) {
+    }
+
     /// Return `true` if a node with no requested node ID and parent `parent_id`
     /// should be created using this node manager.
     ///
@@ -26,8 +26,9 @@ use hashbrown::HashMap;
 
 use crate::{
     address_space::{
-        read_node_value, user_access_level, AccessLevel, EventNotifier, NodeType,
-        ReferenceDirection,
+        has_permission, read_node_value, user_access_level, user_permissions,
+        validate_access_restrictions, AccessLevel, EventNotifier, NodeSetConflictPolicy,
+        NodeSetImport, NodeSetImportReport, NodeType, ReferenceDirection,
     },
     diagnostics::NamespaceMetadata,
     subscriptions::CreateMonitoredItem,
@@ -36,11 +37,12 @@ use crate::{
 use opcua_core::sync::RwLock;
 use opcua_types::{
     argument::Argument, AttributeId, BrowseDescriptionResultMask, BrowseDirection, DataEncoding,
-    DataValue, DateTime, ExpandedNodeId, MonitoringMode, NodeClass, NodeId, NumericRange,
-    ReadAnnotationDataDetails, ReadAtTimeDetails, ReadEventDetails, ReadProcessedDetails,
-    ReadRawModifiedDetails, ReferenceDescription, ReferenceTypeId, StatusCode, TimestampsToReturn,
-    Variant,
+    DataValue, DateTime, ExpandedNodeId, IdType, MonitoringMode, NodeClass, NodeId, NumericRange,
+    PerformUpdateType, PermissionType, ReadAnnotationDataDetails, ReadAtTimeDetails,
+    ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, ReferenceDescription,
+    ReferenceTypeId, RolePermissionType, StatusCode, TimestampsToReturn, Variant,
 };
+use parking_lot::RwLockWriteGuard;
 
 use super::{
     build::NodeManagerBuilder,
@@ -66,6 +68,7 @@ struct BrowseContinuationPoint {
 pub struct InMemoryNodeManager<TImpl> {
     address_space: Arc<RwLock<AddressSpace>>,
     namespaces: HashMap<u16, String>,
+    static_node_id_types: RwLock<HashMap<u16, Vec<IdType>>>,
     inner: TImpl,
 }
 
@@ -95,6 +98,7 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         Self {
             namespaces: address_space.namespaces().clone(),
             address_space: Arc::new(RwLock::new(address_space)),
+            static_node_id_types: Default::default(),
             inner,
         }
     }
@@ -115,6 +119,30 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         &self.namespaces
     }
 
+    /// Import the node set `import` into the namespace with index `namespace`,
+    /// which must be managed by this node manager. Any types defined by the node set
+    /// are added to `type_tree`.
+    ///
+    /// See [AddressSpace::import_node_set_into] for details.
+    pub fn import_node_set(
+        &self,
+        import: &dyn NodeSetImport,
+        type_tree: &RwLock<DefaultTypeTree>,
+        namespace: u16,
+        policy: NodeSetConflictPolicy,
+    ) -> Result<NodeSetImportReport, StatusCode> {
+        let mut address_space = trace_write_lock!(self.address_space);
+        let mut type_tree = trace_write_lock!(type_tree);
+        let report = address_space.import_node_set_into(
+            import,
+            type_tree.namespaces_mut(),
+            namespace,
+            policy,
+        )?;
+        address_space.load_into_type_tree(&mut type_tree);
+        Ok(report)
+    }
+
     /// Set the attributes given in `values` and notify any subscriptions
     /// about the changes.
     ///
@@ -140,6 +168,9 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 output.push((id, attribute_id));
             }
         }
+        let changed: Vec<_> = output.iter().map(|(id, _)| *id).collect();
+        // Events raised for the changes look up their source nodes in the address space.
+        let address_space = RwLockWriteGuard::downgrade(address_space);
 
         subscriptions.maybe_notify(
             output.into_iter(),
@@ -155,6 +186,8 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 )
             },
         );
+        self.inner
+            .nodes_changed(&address_space, subscriptions, &changed);
 
         Ok(())
     }
@@ -210,6 +243,9 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
 
             output.push((id, AttributeId::Value));
         }
+        let changed: Vec<_> = output.iter().map(|(id, _)| *id).collect();
+        // Events raised for the changes look up their source nodes in the address space.
+        let address_space = RwLockWriteGuard::downgrade(address_space);
 
         subscriptions.maybe_notify(
             output.into_iter(),
@@ -225,6 +261,8 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 )
             },
         );
+        self.inner
+            .nodes_changed(&address_space, subscriptions, &changed);
 
         Ok(())
     }
@@ -289,6 +327,7 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
     fn browse_node(
         address_space: &AddressSpace,
         type_tree: &DefaultTypeTree,
+        context: &RequestContext,
         node: &mut BrowseNode,
         namespaces: &hashbrown::HashMap<u16, String>,
     ) {
@@ -338,6 +377,11 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 continue;
             };
 
+            // References to nodes the user may not browse are hidden.
+            if !user_permissions(context, target_node).contains(PermissionType::Browse) {
+                continue;
+            }
+
             let r_node =
                 Self::get_reference(address_space, type_tree, target_node, node.result_mask());
 
@@ -418,6 +462,10 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                             continue;
                         };
 
+                        if !user_permissions(context, node).contains(PermissionType::Browse) {
+                            continue;
+                        }
+
                         if element.target_name.is_null()
                             || node.as_node().browse_name() == &element.target_name
                         {
@@ -450,6 +498,11 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 continue;
             };
 
+            if let Err(e) = validate_access_restrictions(context, node, false) {
+                history_node.set_status(e);
+                continue;
+            }
+
             if is_for_events {
                 let NodeType::Object(object) = node else {
                     history_node.set_status(StatusCode::BadHistoryOperationUnsupported);
@@ -463,6 +516,11 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                     history_node.set_status(StatusCode::BadHistoryOperationUnsupported);
                     continue;
                 }
+
+                if let Err(e) = has_permission(context, node, PermissionType::ReadHistory) {
+                    history_node.set_status(e);
+                    continue;
+                }
             } else {
                 let NodeType::Variable(_) = node else {
                     history_node.set_status(StatusCode::BadHistoryOperationUnsupported);
@@ -497,6 +555,11 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 continue;
             };
 
+            if let Err(e) = validate_access_restrictions(context, node, false) {
+                history_node.set_status(e);
+                continue;
+            }
+
             let is_for_events = matches!(
                 history_node.details(),
                 HistoryUpdateDetails::DeleteEvent(_) | HistoryUpdateDetails::UpdateEvent(_)
@@ -529,12 +592,68 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 }
             }
 
+            if let Err(e) = has_permission(
+                context,
+                node,
+                Self::history_update_permission(history_node.details()),
+            ) {
+                history_node.set_status(e);
+                continue;
+            }
+
             valid.push(history_node);
         }
 
         valid
     }
 
+    /// Filter out items where the user lacks `permission` on the node given by
+    /// `node_id`, setting their status to `BadUserAccessDenied`. Items for nodes
+    /// outside this node manager are left for the node manager owning them.
+    fn validate_node_permission<'b, T>(
+        &self,
+        context: &RequestContext,
+        items: &'b mut [&mut T],
+        permission: PermissionType,
+        node_id: impl Fn(&T) -> &NodeId,
+        set_status: impl Fn(&mut T, StatusCode),
+    ) -> Vec<&'b mut T> {
+        let address_space = trace_read_lock!(self.address_space);
+        let mut valid = Vec::with_capacity(items.len());
+
+        for item in items {
+            if let Some(node) = address_space.find(node_id(item)) {
+                if let Err(e) = has_permission(context, node, permission) {
+                    set_status(item, e);
+                    continue;
+                }
+            }
+            valid.push(&mut **item);
+        }
+
+        valid
+    }
+
+    /// Get the permission required to perform the given history update.
+    fn history_update_permission(details: &HistoryUpdateDetails) -> PermissionType {
+        let perform_insert_replace = match details {
+            HistoryUpdateDetails::UpdateData(d) => d.perform_insert_replace,
+            HistoryUpdateDetails::UpdateStructureData(d) => d.perform_insert_replace,
+            HistoryUpdateDetails::UpdateEvent(d) => d.perform_insert_replace,
+            HistoryUpdateDetails::DeleteRawModified(_)
+            | HistoryUpdateDetails::DeleteAtTime(_)
+            | HistoryUpdateDetails::DeleteEvent(_) => return PermissionType::DeleteHistory,
+        };
+        match perform_insert_replace {
+            PerformUpdateType::Insert => PermissionType::InsertHistory,
+            PerformUpdateType::Replace => PermissionType::ModifyHistory,
+            PerformUpdateType::Update => {
+                PermissionType::InsertHistory | PermissionType::ModifyHistory
+            }
+            PerformUpdateType::Remove => PermissionType::DeleteHistory,
+        }
+    }
+
     fn validate_method_calls<'a, 'b>(
         &self,
         context: &RequestContext,
@@ -558,16 +677,23 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 continue;
             };
 
-            let Some(NodeType::Method(method_node)) = address_space.find(method_ref.target_node)
+            let Some(node @ NodeType::Method(method_node)) =
+                address_space.find(method_ref.target_node)
             else {
                 method.set_status(StatusCode::BadMethodInvalid);
                 continue;
             };
 
+            if let Err(e) = validate_access_restrictions(context, node, false) {
+                method.set_status(e);
+                continue;
+            }
+
             if !method_node.user_executable()
                 || !context
                     .authenticator
                     .is_user_executable(&context.token, method.method_id())
+                || !user_permissions(context, node).contains(PermissionType::Call)
             {
                 method.set_status(StatusCode::BadUserAccessDenied);
                 continue;
@@ -652,25 +778,67 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
 #[async_trait]
 impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl> {
     fn owns_node(&self, id: &NodeId) -> bool {
-        self.namespaces.contains_key(&id.namespace)
+        self.namespaces.contains_key(&id.namespace) && self.inner.owns_node(id)
     }
 
     fn name(&self) -> &str {
         self.inner.name()
     }
 
+    fn owns_server_events(&self) -> bool {
+        self.inner.owns_server_events()
+    }
+
+    fn role_permissions(&self, id: &NodeId) -> Option<Vec<RolePermissionType>> {
+        // Events may be raised while the address space is read locked, e.g. from
+        // `nodes_changed`, so this must not wait for pending writers.
+        self.address_space
+            .read_recursive()
+            .find(id)?
+            .as_node()
+            .role_permissions()
+            .map(|r| r.to_vec())
+    }
+
     #[allow(clippy::await_holding_lock)]
     async fn init(&self, type_tree: &mut DefaultTypeTree, context: ServerContext) {
         // During init we effectively own the address space, so this should be safe.
         let mut address_space = trace_write_lock!(self.address_space);
 
+        // The role set enforces the defaults given in the namespace metadata.
+        let roles = &context.info.roles;
+        for ns in self.inner.namespaces() {
+            if let Some(role_permissions) = ns.default_role_permissions {
+                roles.set_default_role_permissions(ns.namespace_index, Some(role_permissions));
+            }
+            if !ns.default_access_restrictions.is_empty() {
+                roles.set_default_access_restrictions(
+                    ns.namespace_index,
+                    ns.default_access_restrictions,
+                );
+            }
+        }
+
         self.inner.init(&mut address_space, context).await;
 
+        // Any node present once the node manager is initialized counts as static.
+        let mut static_node_id_types = trace_write_lock!(self.static_node_id_types);
+        for ns in self.namespaces.keys() {
+            static_node_id_types.insert(*ns, address_space.node_id_types(*ns));
+        }
+
         address_space.load_into_type_tree(type_tree);
     }
 
     fn namespaces_for_user(&self, _context: &RequestContext) -> Vec<NamespaceMetadata> {
-        self.inner.namespaces()
+        let mut namespaces = self.inner.namespaces();
+        let static_node_id_types = trace_read_lock!(self.static_node_id_types);
+        for ns in &mut namespaces {
+            if ns.static_node_id_types.is_none() {
+                ns.static_node_id_types = static_node_id_types.get(&ns.namespace_index).cloned();
+            }
+        }
+        namespaces
     }
 
     fn handle_new_node(&self, parent_id: &ExpandedNodeId) -> bool {
@@ -692,6 +860,10 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
                 continue;
             };
 
+            if !user_permissions(context, target_node).contains(PermissionType::Browse) {
+                continue;
+            }
+
             item.set(Self::get_reference(
                 &address_space,
                 &type_tree,
@@ -731,7 +903,13 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
                     node.set_next_continuation_point(point);
                 }
             } else {
-                Self::browse_node(&address_space, &type_tree, node, &self.namespaces);
+                if let Some(source) = address_space.find(node.node_id()) {
+                    if let Err(e) = has_permission(context, source, PermissionType::Browse) {
+                        node.set_status(e);
+                        continue;
+                    }
+                }
+                Self::browse_node(&address_space, &type_tree, context, node, &self.namespaces);
             }
         }
 
@@ -868,6 +1046,10 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
                         node.set_status(StatusCode::BadAttributeIdInvalid);
                         continue;
                     }
+                    if let Err(e) = has_permission(context, n, PermissionType::ReceiveEvents) {
+                        node.set_status(e);
+                        continue;
+                    }
 
                     // No further action beyond just validation.
                     node.set_status(StatusCode::Good);
@@ -997,7 +1179,7 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         nodes: &mut [&mut HistoryNode],
         timestamps_to_return: TimestampsToReturn,
     ) -> Result<(), StatusCode> {
-        let mut nodes = self.validate_history_read_nodes(context, nodes, false);
+        let mut nodes = self.validate_history_read_nodes(context, nodes, true);
         self.inner
             .history_read_events(context, details, &mut nodes, timestamps_to_return)
             .await
@@ -1055,8 +1237,18 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         nodes_to_add: &mut [&mut AddNodeItem],
     ) -> Result<(), StatusCode> {
+        let mut valid = self.validate_node_permission(
+            context,
+            nodes_to_add,
+            PermissionType::AddNode,
+            |n| &n.parent_node_id().node_id,
+            |n, e| n.set_result(NodeId::null(), e),
+        );
+        if valid.is_empty() {
+            return Ok(());
+        }
         self.inner
-            .add_nodes(context, &self.address_space, nodes_to_add)
+            .add_nodes(context, &self.address_space, &mut valid)
             .await
     }
 
@@ -1065,8 +1257,18 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         references_to_add: &mut [&mut AddReferenceItem],
     ) -> Result<(), StatusCode> {
+        let mut valid = self.validate_node_permission(
+            context,
+            references_to_add,
+            PermissionType::AddReference,
+            |r| r.source_node_id(),
+            |r, e| r.set_source_result(e),
+        );
+        if valid.is_empty() {
+            return Ok(());
+        }
         self.inner
-            .add_references(context, &self.address_space, references_to_add)
+            .add_references(context, &self.address_space, &mut valid)
             .await
     }
 
@@ -1075,8 +1277,18 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         nodes_to_delete: &mut [&mut DeleteNodeItem],
     ) -> Result<(), StatusCode> {
+        let mut valid = self.validate_node_permission(
+            context,
+            nodes_to_delete,
+            PermissionType::DeleteNode,
+            |n| n.node_id(),
+            |n, e| n.set_result(e),
+        );
+        if valid.is_empty() {
+            return Ok(());
+        }
         self.inner
-            .delete_nodes(context, &self.address_space, nodes_to_delete)
+            .delete_nodes(context, &self.address_space, &mut valid)
             .await
     }
 
@@ -1095,8 +1307,18 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         references_to_delete: &mut [&mut DeleteReferenceItem],
     ) -> Result<(), StatusCode> {
+        let mut valid = self.validate_node_permission(
+            context,
+            references_to_delete,
+            PermissionType::RemoveReference,
+            |r| r.source_node_id(),
+            |r, e| r.set_source_result(e),
+        );
+        if valid.is_empty() {
+            return Ok(());
+        }
         self.inner
-            .delete_references(context, &self.address_space, references_to_delete)
+            .delete_references(context, &self.address_space, &mut valid)
             .await
     }
 }
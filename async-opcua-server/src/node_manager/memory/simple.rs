@@ -2,21 +2,26 @@ use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use opcua_core::{trace_read_lock, trace_write_lock};
-use opcua_nodes::{HasNodeId, NodeSetImport};
+use opcua_nodes::{HasNodeId, NodeSetImport, NodeType};
 
 use crate::{
     address_space::{read_node_value, write_node_value, AddressSpace},
+    alarms::LimitAlarms,
     node_manager::{
-        DefaultTypeTree, MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagerBuilder,
-        NodeManagersRef, ParsedReadValueId, RequestContext, ServerContext, SyncSampler, WriteNode,
+        impl_history_read_at_time, impl_history_read_processed, impl_history_read_raw_modified,
+        impl_history_update, DefaultTypeTree, HistoryNode, HistoryProvider, HistoryUpdateNode,
+        MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagerBuilder, NodeManagersRef,
+        ParsedReadValueId, RequestContext, ServerContext, SyncSampler, WriteNode,
     },
-    CreateMonitoredItem,
+    CreateMonitoredItem, SubscriptionCache,
 };
 use opcua_core::sync::RwLock;
 use opcua_types::{
-    AttributeId, DataValue, MonitoringMode, NodeClass, NodeId, NumericRange, StatusCode,
-    TimestampsToReturn, Variant,
+    AttributeId, DataValue, MonitoringMode, NodeClass, NodeId, NumericRange, ReadAtTimeDetails,
+    ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, StatusCode, TimestampsToReturn,
+    Variant,
 };
+use tracing::warn;
 
 use super::{
     InMemoryNodeManager, InMemoryNodeManagerBuilder, InMemoryNodeManagerImpl,
@@ -41,6 +46,7 @@ pub struct SimpleNodeManagerBuilder {
     namespaces: Vec<NamespaceMetadata>,
     imports: Vec<Box<dyn NodeSetImport>>,
     name: String,
+    history: Option<Arc<dyn HistoryProvider>>,
 }
 
 impl SimpleNodeManagerBuilder {
@@ -51,6 +57,7 @@ impl SimpleNodeManagerBuilder {
             namespaces: vec![namespace],
             imports: Vec::new(),
             name: name.to_owned(),
+            history: None,
         }
     }
 
@@ -61,8 +68,17 @@ impl SimpleNodeManagerBuilder {
             namespaces: Vec::new(),
             imports,
             name: name.to_owned(),
+            history: None,
         }
     }
+
+    /// Set a history provider used to serve history reads and updates of variables in
+    /// this node manager. Values written to variables with `Historizing` set are
+    /// recorded in the provider.
+    pub fn history(mut self, history: Arc<dyn HistoryProvider>) -> Self {
+        self.history = Some(history);
+        self
+    }
 }
 
 impl InMemoryNodeManagerImplBuilder for SimpleNodeManagerBuilder {
@@ -73,11 +89,16 @@ impl InMemoryNodeManagerImplBuilder for SimpleNodeManagerBuilder {
             let mut type_tree = context.type_tree.write();
             for import in self.imports {
                 address_space.import_node_set(&*import, type_tree.namespaces_mut());
+                let models = import.get_models();
                 let nss = import.get_own_namespaces();
                 for ns in nss {
                     if !self.namespaces.iter().any(|n| n.namespace_uri == ns) {
+                        let model = models.iter().find(|m| m.model_uri == ns);
                         self.namespaces.push(NamespaceMetadata {
                             namespace_uri: ns,
+                            namespace_version: model.and_then(|m| m.version.clone()),
+                            namespace_publication_date: model.and_then(|m| m.publication_date),
+                            is_namespace_subset: Some(false),
                             ..Default::default()
                         });
                     }
@@ -90,7 +111,12 @@ impl InMemoryNodeManagerImplBuilder for SimpleNodeManagerBuilder {
         for ns in &self.namespaces {
             address_space.add_namespace(&ns.namespace_uri, ns.namespace_index);
         }
-        SimpleNodeManagerImpl::new(self.namespaces, &self.name, context.node_managers.clone())
+        SimpleNodeManagerImpl::new(
+            self.namespaces,
+            &self.name,
+            context.node_managers.clone(),
+            self.history,
+        )
     }
 }
 
@@ -125,6 +151,8 @@ pub struct SimpleNodeManagerImpl {
     node_managers: NodeManagersRef,
     name: String,
     samplers: SyncSampler,
+    history: Option<Arc<dyn HistoryProvider>>,
+    limit_alarms: LimitAlarms,
 }
 
 #[async_trait]
@@ -151,6 +179,16 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
         &self.name
     }
 
+    fn nodes_changed(
+        &self,
+        address_space: &AddressSpace,
+        subscriptions: &SubscriptionCache,
+        nodes: &[&NodeId],
+    ) {
+        self.limit_alarms
+            .evaluate(address_space, subscriptions, nodes.iter().copied());
+    }
+
     async fn read_values(
         &self,
         context: &RequestContext,
@@ -267,14 +305,115 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
         address_space: &RwLock<AddressSpace>,
         nodes_to_write: &mut [&mut WriteNode],
     ) -> Result<(), StatusCode> {
-        let mut address_space = trace_write_lock!(address_space);
-        let type_tree = trace_read_lock!(context.type_tree);
-        let cbs = trace_read_lock!(self.write_cbs);
+        let mut history = Vec::new();
+        let mut written = Vec::new();
+        {
+            let mut address_space = trace_write_lock!(address_space);
+            let type_tree = trace_read_lock!(context.type_tree);
+            let cbs = trace_read_lock!(self.write_cbs);
+
+            for write in nodes_to_write.iter_mut() {
+                if let Some(value) =
+                    self.write_node_value(&cbs, context, &mut address_space, &type_tree, write)
+                {
+                    history.push((write.value().node_id.clone(), value));
+                }
+                if write.status().is_good() {
+                    written.push(write.value().node_id.clone());
+                }
+            }
+        }
+        if !written.is_empty() {
+            self.limit_alarms.evaluate(
+                &*trace_read_lock!(address_space),
+                &context.subscriptions,
+                &written,
+            );
+        }
+
+        if let Some(provider) = &self.history {
+            if !history.is_empty() {
+                if let Err(e) = provider.record_values(history).await {
+                    warn!("Failed to record history of written values: {e}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn history_read_raw_modified(
+        &self,
+        _context: &RequestContext,
+        details: &ReadRawModifiedDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let Some(provider) = &self.history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        for node in nodes {
+            impl_history_read_raw_modified(&**provider, details, node, timestamps_to_return).await;
+        }
+        Ok(())
+    }
+
+    async fn history_read_at_time(
+        &self,
+        _context: &RequestContext,
+        details: &ReadAtTimeDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let Some(provider) = &self.history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        for node in nodes {
+            impl_history_read_at_time(&**provider, details, node, timestamps_to_return).await;
+        }
+        Ok(())
+    }
 
-        for write in nodes_to_write {
-            self.write_node_value(&cbs, context, &mut address_space, &type_tree, write);
+    async fn history_read_processed(
+        &self,
+        _context: &RequestContext,
+        details: &ReadProcessedDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let Some(provider) = &self.history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        for node in nodes {
+            impl_history_read_processed(&**provider, details, node, timestamps_to_return).await;
         }
+        Ok(())
+    }
+
+    async fn history_read_events(
+        &self,
+        context: &RequestContext,
+        details: &ReadEventDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        _timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let Some(history) = &context.info.event_history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        history.history_read_events(context, details, nodes).await
+    }
 
+    async fn history_update(
+        &self,
+        context: &RequestContext,
+        nodes: &mut [&mut &mut HistoryUpdateNode],
+    ) -> Result<(), StatusCode> {
+        let Some(provider) = &self.history else {
+            return Err(StatusCode::BadHistoryOperationUnsupported);
+        };
+        for node in nodes {
+            impl_history_update(&**provider, context, node).await;
+        }
         Ok(())
     }
 
@@ -302,7 +441,12 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
 }
 
 impl SimpleNodeManagerImpl {
-    fn new(namespaces: Vec<NamespaceMetadata>, name: &str, node_managers: NodeManagersRef) -> Self {
+    fn new(
+        namespaces: Vec<NamespaceMetadata>,
+        name: &str,
+        node_managers: NodeManagersRef,
+        history: Option<Arc<dyn HistoryProvider>>,
+    ) -> Self {
         Self {
             write_cbs: Default::default(),
             read_cbs: Default::default(),
@@ -311,6 +455,8 @@ impl SimpleNodeManagerImpl {
             name: name.to_owned(),
             node_managers,
             samplers: SyncSampler::new(),
+            history,
+            limit_alarms: LimitAlarms::new(),
         }
     }
 
@@ -355,12 +501,12 @@ impl SimpleNodeManagerImpl {
         address_space: &mut AddressSpace,
         type_tree: &DefaultTypeTree,
         write: &mut WriteNode,
-    ) {
+    ) -> Option<DataValue> {
         let node = match address_space.validate_node_write(context, write.value(), type_tree) {
             Ok(v) => v,
             Err(e) => {
                 write.set_status(e);
-                return;
+                return None;
             }
         };
 
@@ -368,7 +514,7 @@ impl SimpleNodeManagerImpl {
             || write.value().attribute_id != AttributeId::Value
         {
             write.set_status(StatusCode::BadNotWritable);
-            return;
+            return None;
         }
 
         if let Some(cb) = cbs.get(node.as_node().node_id()) {
@@ -384,18 +530,31 @@ impl SimpleNodeManagerImpl {
             // If no value is passed return an error.
             write.set_status(StatusCode::BadNothingToDo);
         }
-        if write.status().is_good() {
-            if let Some(val) = node.as_mut_node().get_attribute(
-                TimestampsToReturn::Both,
-                write.value().attribute_id,
-                &NumericRange::None,
-                &opcua_types::DataEncoding::Binary,
-            ) {
-                context.subscriptions.notify_data_change(
-                    [(val, node.node_id(), write.value().attribute_id)].into_iter(),
-                );
-            }
+        if !write.status().is_good() {
+            return None;
         }
+        let val = node.as_mut_node().get_attribute(
+            TimestampsToReturn::Both,
+            write.value().attribute_id,
+            &NumericRange::None,
+            &opcua_types::DataEncoding::Binary,
+        )?;
+        let historizing = match &*node {
+            NodeType::Variable(v) => v.historizing(),
+            _ => false,
+        };
+        context.subscriptions.notify_data_change(
+            [(val.clone(), node.node_id(), write.value().attribute_id)].into_iter(),
+        );
+        // Written values are only recorded in history if the variable is historizing.
+        historizing.then_some(val)
+    }
+
+    /// Get the limit alarms of this node manager. The alarms are evaluated when
+    /// their input or limit properties are written, or set with
+    /// [InMemoryNodeManager::set_values].
+    pub fn limit_alarms(&self) -> &LimitAlarms {
+        &self.limit_alarms
     }
 
     /// Add a callback called on `Write` for the node given by `id`.
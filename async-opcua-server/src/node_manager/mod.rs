@@ -14,7 +14,8 @@ use opcua_core::sync::RwLock;
 use opcua_nodes::DefaultTypeTree;
 use opcua_types::{
     ExpandedNodeId, MonitoringMode, NodeId, ReadAnnotationDataDetails, ReadAtTimeDetails,
-    ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, StatusCode, TimestampsToReturn,
+    ReadEventDetails, ReadProcessedDetails, ReadRawModifiedDetails, RolePermissionType, StatusCode,
+    TimestampsToReturn,
 };
 use tokio::sync::OnceCell;
 
@@ -41,7 +42,14 @@ pub use {
     attributes::{ParsedReadValueId, ParsedWriteValue, ReadNode, WriteNode},
     build::NodeManagerBuilder,
     context::{RequestContext, TypeTreeForUser, TypeTreeForUserStatic, TypeTreeReadContext},
-    history::{HistoryNode, HistoryResult, HistoryUpdateDetails, HistoryUpdateNode},
+    history::{
+        impl_history_read_at_time, impl_history_read_events, impl_history_read_processed,
+        impl_history_read_raw_modified, impl_history_update, EventHistoryCapture,
+        EventHistoryProvider, HistoricalEvent, HistoricalEventField, HistoryEventFilter,
+        HistoryNode, HistoryProvider, HistoryRawQuery, HistoryResult, HistoryUpdateDetails,
+        HistoryUpdateNode, HistoryValueUpdate, InMemoryEventHistoryProvider,
+        InMemoryHistoryProvider, SUPPORTED_AGGREGATES,
+    },
     method::MethodCall,
     monitored_items::{MonitoredItemRef, MonitoredItemUpdateRef},
     node_management::{AddNodeItem, AddReferenceItem, DeleteNodeItem, DeleteReferenceItem},
@@ -53,6 +61,9 @@ pub use {
     },
 };
 
+#[cfg(feature = "history-sqlite")]
+pub use history::{SqliteHistoryProvider, SqliteHistoryRetention};
+
 pub(crate) use context::resolve_external_references;
 pub(crate) use context::DefaultTypeTreeGetter;
 pub(crate) use history::HistoryReadDetails;
@@ -297,6 +308,16 @@ pub trait NodeManager: IntoAnyArc + Any {
         false
     }
 
+    /// Get the `RolePermissions` attribute of the node with ID `id`, owned by
+    /// this node manager, if it is set. This is used to check the `ReceiveEvents`
+    /// permission on the source node of events before they are sent to a session.
+    ///
+    /// This is called while events are being raised, so it must not block.
+    /// If `None` is returned, the default role permissions of the namespace apply.
+    fn role_permissions(&self, id: &NodeId) -> Option<Vec<RolePermissionType>> {
+        None
+    }
+
     /// Return whether this node should handle requests to create a node
     /// for the given parent ID. This is only called if no new node ID is
     /// requested, otherwise owns_node is called on the requested node ID.
@@ -0,0 +1,578 @@
+//! Roles of the server, and the mapping of sessions to roles.
+//!
+//! The [`RoleSet`] contains the well-known roles defined by the standard, and
+//! any roles added at runtime with the `AddRole` method or [`RoleSet::add_role`].
+//! Each role has a list of identity mapping rules. When a session is activated,
+//! it is granted every role with a rule matching its [`SessionIdentity`].
+//!
+//! The permissions of a session on a node are given by the `RolePermissions`
+//! attribute of the node, or the default role permissions of its namespace.
+//! Likewise, the security required to access a node is given by its
+//! `AccessRestrictions` attribute, or the default access restrictions of its namespace.
+
+use hashbrown::HashMap;
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_crypto::X509;
+use opcua_nodes::{MethodBuilder, ObjectBuilder, VariableBuilder};
+use opcua_types::{
+    AccessRestrictionType, DataTypeId, Guid, IdentityCriteriaType, IdentityMappingRuleType,
+    MessageSecurityMode, MethodId, NodeId, ObjectId, ObjectTypeId, PermissionType, QualifiedName,
+    RolePermissionType, StatusCode, UAString, UserTokenType, VariableId, VariableTypeId,
+};
+
+use crate::{address_space::AddressSpace, authenticator::IdentityClaims, IdentityToken};
+
+/// The identity of a session, matched against the identity mapping rules of roles.
+#[derive(Debug, Clone)]
+pub struct SessionIdentity {
+    /// The type of user identity token the session was activated with.
+    pub token_type: UserTokenType,
+    /// User name, for sessions activated with a user name identity token.
+    pub user_name: Option<String>,
+    /// Thumbprint of the user certificate as a hex string, for sessions
+    /// activated with an X509 identity token.
+    pub certificate_thumbprint: Option<String>,
+    /// Subject name of the user certificate, for sessions activated with an
+    /// X509 identity token.
+    pub certificate_subject: Option<String>,
+    /// Application URI of the client.
+    pub application_uri: String,
+    /// Whether the client application was authenticated with a trusted
+    /// application instance certificate.
+    pub trusted_application: bool,
+    /// Roles claimed by the access token of the user.
+    pub roles: Vec<String>,
+    /// Groups the user is a member of, according to its access token.
+    pub group_ids: Vec<String>,
+}
+
+impl SessionIdentity {
+    pub(crate) fn new(
+        identity: &IdentityToken,
+        application_uri: &str,
+        client_certificate: Option<&X509>,
+        security_mode: MessageSecurityMode,
+        claims: IdentityClaims,
+    ) -> Self {
+        let mut res = Self {
+            token_type: UserTokenType::Anonymous,
+            user_name: None,
+            certificate_thumbprint: None,
+            certificate_subject: None,
+            application_uri: application_uri.to_owned(),
+            trusted_application: client_certificate.is_some()
+                && security_mode != MessageSecurityMode::None,
+            roles: claims.roles,
+            group_ids: claims.group_ids,
+        };
+        match identity {
+            IdentityToken::UserName(token) => {
+                res.token_type = UserTokenType::UserName;
+                res.user_name = Some(token.user_name.as_ref().to_owned());
+            }
+            IdentityToken::X509(token) => {
+                res.token_type = UserTokenType::Certificate;
+                if let Ok(cert) = X509::from_byte_string(&token.certificate_data) {
+                    res.certificate_thumbprint = Some(cert.thumbprint().as_hex_string());
+                    res.certificate_subject = Some(cert.subject_name());
+                }
+            }
+            IdentityToken::IssuedToken(_) => res.token_type = UserTokenType::IssuedToken,
+            _ => (),
+        }
+        res
+    }
+
+    /// Check whether the identity mapping rule `rule` matches this identity.
+    pub fn matches(&self, rule: &IdentityMappingRuleType) -> bool {
+        let criteria = rule.criteria.as_ref();
+        let anonymous = self.token_type == UserTokenType::Anonymous;
+        match rule.criteria_type {
+            IdentityCriteriaType::UserName => self.user_name.as_deref() == Some(criteria),
+            IdentityCriteriaType::Thumbprint => self
+                .certificate_thumbprint
+                .as_ref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(criteria)),
+            IdentityCriteriaType::Role => self.roles.iter().any(|r| r == criteria),
+            IdentityCriteriaType::GroupId => self.group_ids.iter().any(|g| g == criteria),
+            IdentityCriteriaType::Anonymous => anonymous,
+            IdentityCriteriaType::AuthenticatedUser => !anonymous,
+            IdentityCriteriaType::Application => {
+                self.trusted_application && self.application_uri == criteria
+            }
+            IdentityCriteriaType::X509Subject => {
+                self.certificate_subject.as_deref() == Some(criteria)
+            }
+            IdentityCriteriaType::TrustedApplication => self.trusted_application,
+        }
+    }
+}
+
+/// A role in the [`RoleSet`].
+#[derive(Debug, Clone)]
+pub struct Role {
+    node_id: NodeId,
+    browse_name: QualifiedName,
+    identities: Vec<IdentityMappingRuleType>,
+    identities_property: NodeId,
+    add_identity: NodeId,
+    remove_identity: NodeId,
+    well_known: bool,
+    // Nodes created for a role added at runtime, deleted when it is removed.
+    nodes: Vec<NodeId>,
+}
+
+impl Role {
+    /// The node ID of the role object.
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    /// The browse name of the role, which is also its name.
+    pub fn browse_name(&self) -> &QualifiedName {
+        &self.browse_name
+    }
+
+    /// The identity mapping rules of the role.
+    pub fn identities(&self) -> &[IdentityMappingRuleType] {
+        &self.identities
+    }
+
+    /// Whether this is one of the well-known roles defined by the standard,
+    /// which cannot be removed.
+    pub fn is_well_known(&self) -> bool {
+        self.well_known
+    }
+
+    fn well_known(
+        object: ObjectId,
+        name: &str,
+        identities: VariableId,
+        add_identity: MethodId,
+        remove_identity: MethodId,
+        rules: &[IdentityCriteriaType],
+    ) -> Self {
+        Self {
+            node_id: object.into(),
+            browse_name: QualifiedName::new(0, name),
+            identities: rules
+                .iter()
+                .map(|&criteria_type| IdentityMappingRuleType {
+                    criteria_type,
+                    criteria: UAString::null(),
+                })
+                .collect(),
+            identities_property: identities.into(),
+            add_identity: add_identity.into(),
+            remove_identity: remove_identity.into(),
+            well_known: true,
+            nodes: Vec::new(),
+        }
+    }
+}
+
+/// The roles of the server, with the identity mapping rules used to grant
+/// roles to sessions.
+///
+/// The `Identities` property of each role is read from here, so rules can be
+/// changed with [`RoleSet::add_identity`] and [`RoleSet::remove_identity`]
+/// without touching the address space. Changes only affect sessions activated
+/// after the change.
+pub struct RoleSet {
+    roles: RwLock<Vec<Role>>,
+    default_role_permissions: RwLock<HashMap<u16, Vec<RolePermissionType>>>,
+    default_access_restrictions: RwLock<HashMap<u16, AccessRestrictionType>>,
+}
+
+impl Default for RoleSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoleSet {
+    /// Create a new role set with the well-known roles.
+    ///
+    /// Anonymous sessions are granted the `Anonymous` role, sessions with any
+    /// other identity the `AuthenticatedUser` role, and sessions from trusted
+    /// applications the `TrustedApplication` role. The remaining roles have no
+    /// rules until they are added.
+    pub fn new() -> Self {
+        use IdentityCriteriaType as C;
+        let roles = vec![
+            Role::well_known(
+                ObjectId::WellKnownRole_Anonymous,
+                "Anonymous",
+                VariableId::WellKnownRole_Anonymous_Identities,
+                MethodId::WellKnownRole_Anonymous_AddIdentity,
+                MethodId::WellKnownRole_Anonymous_RemoveIdentity,
+                &[C::Anonymous],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_AuthenticatedUser,
+                "AuthenticatedUser",
+                VariableId::WellKnownRole_AuthenticatedUser_Identities,
+                MethodId::WellKnownRole_AuthenticatedUser_AddIdentity,
+                MethodId::WellKnownRole_AuthenticatedUser_RemoveIdentity,
+                &[C::AuthenticatedUser],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_Observer,
+                "Observer",
+                VariableId::WellKnownRole_Observer_Identities,
+                MethodId::WellKnownRole_Observer_AddIdentity,
+                MethodId::WellKnownRole_Observer_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_Operator,
+                "Operator",
+                VariableId::WellKnownRole_Operator_Identities,
+                MethodId::WellKnownRole_Operator_AddIdentity,
+                MethodId::WellKnownRole_Operator_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_Engineer,
+                "Engineer",
+                VariableId::WellKnownRole_Engineer_Identities,
+                MethodId::WellKnownRole_Engineer_AddIdentity,
+                MethodId::WellKnownRole_Engineer_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_Supervisor,
+                "Supervisor",
+                VariableId::WellKnownRole_Supervisor_Identities,
+                MethodId::WellKnownRole_Supervisor_AddIdentity,
+                MethodId::WellKnownRole_Supervisor_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_ConfigureAdmin,
+                "ConfigureAdmin",
+                VariableId::WellKnownRole_ConfigureAdmin_Identities,
+                MethodId::WellKnownRole_ConfigureAdmin_AddIdentity,
+                MethodId::WellKnownRole_ConfigureAdmin_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_SecurityAdmin,
+                "SecurityAdmin",
+                VariableId::WellKnownRole_SecurityAdmin_Identities,
+                MethodId::WellKnownRole_SecurityAdmin_AddIdentity,
+                MethodId::WellKnownRole_SecurityAdmin_RemoveIdentity,
+                &[],
+            ),
+            Role::well_known(
+                ObjectId::WellKnownRole_TrustedApplication,
+                "TrustedApplication",
+                VariableId::WellKnownRole_TrustedApplication_Identities,
+                MethodId::WellKnownRole_TrustedApplication_AddIdentity,
+                MethodId::WellKnownRole_TrustedApplication_RemoveIdentity,
+                &[C::TrustedApplication],
+            ),
+        ];
+        Self {
+            roles: RwLock::new(roles),
+            default_role_permissions: RwLock::new(HashMap::new()),
+            default_access_restrictions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get all roles in the role set.
+    pub fn roles(&self) -> Vec<Role> {
+        trace_read_lock!(self.roles).clone()
+    }
+
+    /// Get the role with node ID `role_id`.
+    pub fn get(&self, role_id: &NodeId) -> Option<Role> {
+        trace_read_lock!(self.roles)
+            .iter()
+            .find(|r| &r.node_id == role_id)
+            .cloned()
+    }
+
+    /// Add a role named `browse_name`, creating the role object in `address_space`,
+    /// which should be the address space of the core node manager.
+    ///
+    /// The role object gets a `Identities` property and `AddIdentity` and
+    /// `RemoveIdentity` methods. Returns the node ID of the new role.
+    pub fn add_role(
+        &self,
+        address_space: &mut AddressSpace,
+        namespace: u16,
+        browse_name: QualifiedName,
+    ) -> Result<NodeId, StatusCode> {
+        if browse_name.name.is_empty() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let mut roles = trace_write_lock!(self.roles);
+        if roles.iter().any(|r| r.browse_name == browse_name) {
+            return Err(StatusCode::BadBrowseNameDuplicated);
+        }
+
+        let new_id = || NodeId::new(namespace, Guid::new());
+        let mut role = Role {
+            node_id: new_id(),
+            browse_name,
+            identities: Vec::new(),
+            identities_property: new_id(),
+            add_identity: new_id(),
+            remove_identity: new_id(),
+            well_known: false,
+            nodes: Vec::new(),
+        };
+        let name = role.browse_name.name.as_ref();
+        ObjectBuilder::new(&role.node_id, role.browse_name.clone(), name)
+            .has_type_definition(ObjectTypeId::RoleType)
+            .component_of(ObjectId::Server_ServerCapabilities_RoleSet)
+            .insert(address_space);
+        VariableBuilder::new(&role.identities_property, "Identities", "Identities")
+            .property_of(role.node_id.clone())
+            .has_type_definition(VariableTypeId::PropertyType)
+            .data_type(DataTypeId::IdentityMappingRuleType)
+            .value_rank(1)
+            .insert(address_space);
+        role.nodes = vec![role.node_id.clone(), role.identities_property.clone()];
+        for (id, name) in [
+            (&role.add_identity, "AddIdentity"),
+            (&role.remove_identity, "RemoveIdentity"),
+        ] {
+            let args = new_id();
+            MethodBuilder::new(id, name, name)
+                .component_of(role.node_id.clone())
+                .input_args(
+                    address_space,
+                    &args,
+                    &[("Rule", DataTypeId::IdentityMappingRuleType).into()],
+                )
+                .executable(true)
+                .user_executable(true)
+                .insert(address_space);
+            role.nodes.extend([id.clone(), args]);
+        }
+
+        let id = role.node_id.clone();
+        roles.push(role);
+        Ok(id)
+    }
+
+    /// Remove the role with node ID `role_id`, deleting it from `address_space`.
+    /// The well-known roles cannot be removed.
+    pub fn remove_role(
+        &self,
+        address_space: &mut AddressSpace,
+        role_id: &NodeId,
+    ) -> Result<(), StatusCode> {
+        let mut roles = trace_write_lock!(self.roles);
+        let Some(idx) = roles.iter().position(|r| &r.node_id == role_id) else {
+            return Err(StatusCode::BadNodeIdUnknown);
+        };
+        if roles[idx].well_known {
+            return Err(StatusCode::BadRequestNotAllowed);
+        }
+        for node in roles.remove(idx).nodes {
+            address_space.delete(&node, true);
+        }
+        Ok(())
+    }
+
+    /// Add the identity mapping rule `rule` to the role with node ID `role_id`.
+    /// Adding a rule the role already has does nothing.
+    pub fn add_identity(
+        &self,
+        role_id: &NodeId,
+        rule: IdentityMappingRuleType,
+    ) -> Result<(), StatusCode> {
+        let needs_criteria = !matches!(
+            rule.criteria_type,
+            IdentityCriteriaType::Anonymous
+                | IdentityCriteriaType::AuthenticatedUser
+                | IdentityCriteriaType::TrustedApplication
+        );
+        if needs_criteria && rule.criteria.is_empty() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let mut roles = trace_write_lock!(self.roles);
+        let role = roles
+            .iter_mut()
+            .find(|r| &r.node_id == role_id)
+            .ok_or(StatusCode::BadNodeIdUnknown)?;
+        if !role.identities.contains(&rule) {
+            role.identities.push(rule);
+        }
+        Ok(())
+    }
+
+    /// Remove the identity mapping rule `rule` from the role with node ID `role_id`.
+    pub fn remove_identity(
+        &self,
+        role_id: &NodeId,
+        rule: &IdentityMappingRuleType,
+    ) -> Result<(), StatusCode> {
+        let mut roles = trace_write_lock!(self.roles);
+        let role = roles
+            .iter_mut()
+            .find(|r| &r.node_id == role_id)
+            .ok_or(StatusCode::BadNodeIdUnknown)?;
+        let Some(idx) = role.identities.iter().position(|r| r == rule) else {
+            return Err(StatusCode::BadNotFound);
+        };
+        role.identities.remove(idx);
+        Ok(())
+    }
+
+    /// Get the node IDs of the roles granted to `identity`.
+    pub fn roles_for(&self, identity: &SessionIdentity) -> Vec<NodeId> {
+        trace_read_lock!(self.roles)
+            .iter()
+            .filter(|r| r.identities.iter().any(|i| identity.matches(i)))
+            .map(|r| r.node_id.clone())
+            .collect()
+    }
+
+    /// Set the default role permissions of the namespace with index `namespace`.
+    /// These apply to nodes in the namespace without a `RolePermissions` attribute.
+    /// If `role_permissions` is `None`, the defaults are removed.
+    pub fn set_default_role_permissions(
+        &self,
+        namespace: u16,
+        role_permissions: Option<Vec<RolePermissionType>>,
+    ) {
+        let mut defaults = trace_write_lock!(self.default_role_permissions);
+        match role_permissions {
+            Some(r) => defaults.insert(namespace, r),
+            None => defaults.remove(&namespace),
+        };
+    }
+
+    /// Get the default role permissions of the namespace with index `namespace`.
+    pub fn default_role_permissions(&self, namespace: u16) -> Option<Vec<RolePermissionType>> {
+        trace_read_lock!(self.default_role_permissions)
+            .get(&namespace)
+            .cloned()
+    }
+
+    /// Get the permissions granted to `roles` on a node in `namespace` with
+    /// the `RolePermissions` attribute `role_permissions`.
+    ///
+    /// If the node has no role permissions, the default role permissions of the
+    /// namespace are used. If neither is set, all permissions are granted.
+    pub fn permissions(
+        &self,
+        roles: &[NodeId],
+        role_permissions: Option<&[RolePermissionType]>,
+        namespace: u16,
+    ) -> PermissionType {
+        let grant = |r: &[RolePermissionType]| {
+            r.iter()
+                .filter(|p| roles.contains(&p.role_id))
+                .fold(PermissionType::empty(), |acc, p| acc | p.permissions)
+        };
+        if let Some(r) = role_permissions {
+            return grant(r);
+        }
+        match trace_read_lock!(self.default_role_permissions).get(&namespace) {
+            Some(r) => grant(r),
+            None => PermissionType::all(),
+        }
+    }
+
+    /// Get the role permissions that apply to `roles` on a node in `namespace`
+    /// with the `RolePermissions` attribute `role_permissions`. This is the
+    /// value of the `UserRolePermissions` attribute.
+    pub fn user_role_permissions(
+        &self,
+        roles: &[NodeId],
+        role_permissions: Option<&[RolePermissionType]>,
+        namespace: u16,
+    ) -> Option<Vec<RolePermissionType>> {
+        let filter = |r: &[RolePermissionType]| {
+            r.iter()
+                .filter(|p| roles.contains(&p.role_id))
+                .cloned()
+                .collect()
+        };
+        match role_permissions {
+            Some(r) => Some(filter(r)),
+            None => trace_read_lock!(self.default_role_permissions)
+                .get(&namespace)
+                .map(|r| filter(r)),
+        }
+    }
+
+    /// Set the default access restrictions of the namespace with index `namespace`.
+    /// These apply to nodes in the namespace without an `AccessRestrictions` attribute.
+    pub fn set_default_access_restrictions(
+        &self,
+        namespace: u16,
+        access_restrictions: AccessRestrictionType,
+    ) {
+        let mut defaults = trace_write_lock!(self.default_access_restrictions);
+        if access_restrictions.is_empty() {
+            defaults.remove(&namespace);
+        } else {
+            defaults.insert(namespace, access_restrictions);
+        }
+    }
+
+    /// Get the default access restrictions of the namespace with index `namespace`.
+    pub fn default_access_restrictions(&self, namespace: u16) -> AccessRestrictionType {
+        trace_read_lock!(self.default_access_restrictions)
+            .get(&namespace)
+            .copied()
+            .unwrap_or(AccessRestrictionType::empty())
+    }
+
+    /// Get the access restrictions of a node in `namespace` with the
+    /// `AccessRestrictions` attribute `access_restrictions`, falling back to
+    /// the default access restrictions of the namespace.
+    pub fn access_restrictions(
+        &self,
+        access_restrictions: Option<AccessRestrictionType>,
+        namespace: u16,
+    ) -> AccessRestrictionType {
+        access_restrictions.unwrap_or_else(|| self.default_access_restrictions(namespace))
+    }
+
+    /// Get the value of the `Identities` property with node ID `node_id`, if
+    /// it belongs to a role.
+    #[cfg(feature = "generated-address-space")]
+    pub(crate) fn identities_value(&self, node_id: &NodeId) -> Option<opcua_types::Variant> {
+        trace_read_lock!(self.roles)
+            .iter()
+            .find(|r| &r.identities_property == node_id)
+            .map(|r| {
+                r.identities
+                    .iter()
+                    .map(|i| opcua_types::ExtensionObject::from_message(i.clone()))
+                    .collect::<Vec<_>>()
+                    .into()
+            })
+    }
+
+    /// Get the `AddIdentity` and `RemoveIdentity` methods of all roles.
+    #[cfg(feature = "generated-address-space")]
+    pub(crate) fn identity_methods(&self) -> Vec<NodeId> {
+        trace_read_lock!(self.roles)
+            .iter()
+            .flat_map(|r| [r.add_identity.clone(), r.remove_identity.clone()])
+            .collect()
+    }
+
+    /// Get the role that `method_id` manages the identities of, and whether it
+    /// is the `AddIdentity` method.
+    #[cfg(feature = "generated-address-space")]
+    pub(crate) fn identity_method(&self, method_id: &NodeId) -> Option<(NodeId, bool)> {
+        trace_read_lock!(self.roles).iter().find_map(|r| {
+            if &r.add_identity == method_id {
+                Some((r.node_id.clone(), true))
+            } else if &r.remove_identity == method_id {
+                Some((r.node_id.clone(), false))
+            } else {
+                None
+            }
+        })
+    }
+}
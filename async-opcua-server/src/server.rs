@@ -10,7 +10,7 @@ use std::{
 
 use arc_swap::ArcSwap;
 use futures::{future::Either, never::Never, stream::FuturesUnordered, FutureExt, StreamExt};
-use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_core::{comms::stream::TransportStream, sync::RwLock, trace_read_lock, trace_write_lock};
 use opcua_nodes::DefaultTypeTree;
 use tokio::{
     net::TcpListener,
@@ -26,9 +26,13 @@ use opcua_crypto::CertificateStore;
 
 use crate::{
     diagnostics::ServerDiagnostics,
-    node_manager::{DefaultTypeTreeGetter, ServerContext},
+    node_manager::{DefaultTypeTreeGetter, EventHistoryCapture, ServerContext},
+    roles::RoleSet,
     session::controller::{ControllerCommand, SessionStarter},
-    transport::tcp::{TcpConnector, TransportConfig},
+    transport::{
+        tcp::{TcpConnector, TransportConfig},
+        uds::UdsListener,
+    },
     ServerStatusWrapper,
 };
 use opcua_types::{DateTime, LocalizedText, ServerState, UAString};
@@ -42,7 +46,6 @@ use super::{
     server_handle::ServerHandle,
     session::manager::SessionManager,
     subscriptions::SubscriptionCache,
-    ServerCapabilities,
 };
 
 struct ConnectionInfo {
@@ -156,25 +159,32 @@ impl Server {
             subscription_id_handle: AtomicHandle::new(1),
             monitored_item_id_handle: AtomicHandle::new(1),
             secure_channel_id_handle: Arc::new(AtomicHandle::new(1)),
-            capabilities: ServerCapabilities::default(),
+            capabilities: builder.capabilities,
             service_level: service_level.clone(),
             port: AtomicU16::new(0),
             type_tree_getter: builder
                 .type_tree_getter
                 .unwrap_or_else(|| Arc::new(DefaultTypeTreeGetter)),
             type_loaders: RwLock::new(builder.type_loaders),
-            diagnostics: ServerDiagnostics {
-                enabled: config.diagnostics,
-                ..Default::default()
-            },
+            diagnostics: ServerDiagnostics::new(config.diagnostics),
+            transport_metrics: builder.transport_metrics,
+            interceptors: builder.interceptors,
+            event_history: builder
+                .event_history
+                .map(|p| Arc::new(EventHistoryCapture::new(p, type_tree.clone()))),
+            roles: RoleSet::new(),
         };
 
         let certificate_store = Arc::new(RwLock::new(certificate_store));
 
         let info = Arc::new(info);
-        let subscriptions = Arc::new(SubscriptionCache::new(config.limits.subscriptions));
-
         let node_managers_ref = NodeManagersRef::new_empty();
+        let subscriptions = Arc::new(SubscriptionCache::new(
+            config.limits.subscriptions,
+            info.clone(),
+            node_managers_ref.clone(),
+        ));
+
         let status_wrapper = Arc::new(ServerStatusWrapper::new(
             builder.build_info,
             subscriptions.clone(),
@@ -304,6 +314,23 @@ impl Server {
             .port
             .store(addr.port(), std::sync::atomic::Ordering::Relaxed);
 
+        let mut uds_listener = match self.config.tcp_config.uds_path.as_deref() {
+            Some(path) => {
+                let listener = UdsListener::bind(path).map_err(|e| {
+                    format!(
+                        "Failed to bind unix domain socket {}: {e:?}",
+                        path.display()
+                    )
+                })?;
+                info!(
+                    "Now listening for connections on {}",
+                    listener.path().display()
+                );
+                Some(listener)
+            }
+            None => None,
+        };
+
         self.log_endpoint_info();
 
         let mut connection_counter = 0;
@@ -334,6 +361,11 @@ impl Server {
                 Either::Right(self.connections.next())
             };
 
+            let uds_fut = match uds_listener.as_mut() {
+                Some(l) => Either::Left(l.accept()),
+                None => Either::Right(futures::future::pending()),
+            };
+
             tokio::select! {
                 conn_res = conn_fut => {
                     match conn_res.unwrap() {
@@ -351,27 +383,10 @@ impl Server {
                     match rs {
                         Ok((socket, addr)) => {
                             info!("Accept new connection from {addr} ({connection_counter})");
-                            let conn = SessionStarter::new(
-                                TcpConnector::new(socket, TransportConfig {
-                                    send_buffer_size: self.info.config.limits.send_buffer_size,
-                                    max_message_size: self.info.config.limits.max_message_size,
-                                    max_chunk_count: self.info.config.limits.max_chunk_count,
-                                    receive_buffer_size: self.info.config.limits.receive_buffer_size,
-                                    hello_timeout: Duration::from_secs(self.info.config.tcp_config.hello_timeout as u64),
-                                }, self.info.decoding_options()),
-                                self.info.clone(),
-                                self.session_manager.clone(),
-                                self.certificate_store.clone(),
-                                self.node_managers.clone(),
-                                self.subscriptions.clone()
-                            );
-
-                            let (send, recv) = tokio::sync::mpsc::channel(5);
-                            let handle = tokio::spawn(conn.run(recv).map(move |_| connection_counter));
+                            self.config.tcp_config.socket.apply_or_warn(&socket);
+                            let (handle, conn) = self.start_connection(socket.into(), connection_counter);
                             self.connections.push(handle);
-                            self.connection_map.insert(connection_counter, ConnectionInfo {
-                                command_send: send
-                            });
+                            self.connection_map.insert(connection_counter, conn);
                             connection_counter += 1;
                         }
                         Err(e) => {
@@ -379,6 +394,20 @@ impl Server {
                         }
                     }
                 }
+                rs = uds_fut => {
+                    match rs {
+                        Ok(socket) => {
+                            info!("Accept new connection on {} ({connection_counter})", socket.peer_description());
+                            let (handle, conn) = self.start_connection(socket, connection_counter);
+                            self.connections.push(handle);
+                            self.connection_map.insert(connection_counter, conn);
+                            connection_counter += 1;
+                        }
+                        Err(e) => {
+                            error!("Failed to accept client connection on unix domain socket: {:?}", e);
+                        }
+                    }
+                }
                 _ = self.token.cancelled() => {
                     for conn in self.connection_map.values() {
                         let _ = conn.command_send.send(ControllerCommand::Close).await;
@@ -400,7 +429,7 @@ impl Server {
         };
 
         info!("Try to bind address at {addr}");
-        let listener = match TcpListener::bind(&addr).await {
+        let listener = match self.config.tcp_config.socket.listen(addr) {
             Ok(listener) => listener,
             Err(e) => {
                 error!("Failed to bind socket: {:?}", e);
@@ -411,6 +440,49 @@ impl Server {
         self.run_with(listener).await
     }
 
+    fn start_connection(
+        &self,
+        socket: TransportStream,
+        connection_counter: u32,
+    ) -> (JoinHandle<u32>, ConnectionInfo) {
+        let limits = &self.info.config.limits;
+        let conn = SessionStarter::new(
+            TcpConnector::new(
+                socket,
+                TransportConfig {
+                    send_buffer_size: limits.send_buffer_size,
+                    max_send_message_size: limits
+                        .max_send_message_size
+                        .unwrap_or(limits.max_message_size),
+                    max_send_chunk_count: limits
+                        .max_send_chunk_count
+                        .unwrap_or(limits.max_chunk_count),
+                    receive_buffer_size: limits.receive_buffer_size,
+                    hello_timeout: Duration::from_secs(
+                        self.info.config.tcp_config.hello_timeout as u64,
+                    ),
+                    parallel_crypto_threshold: self.info.config.parallel_crypto_chunk_threshold,
+                    stream_encoding_threshold: self.info.config.stream_encoding_threshold,
+                    priority_policy: self.info.config.send_priorities.clone(),
+                    max_send_queue_bytes: limits.max_send_queue_bytes,
+                    max_send_queue_messages: limits.max_send_queue_messages,
+                    send_rate_limit: limits.send_rate_limit,
+                    metrics: self.info.transport_metrics.clone(),
+                },
+                self.info.decoding_options(),
+            ),
+            self.info.clone(),
+            self.session_manager.clone(),
+            self.certificate_store.clone(),
+            self.node_managers.clone(),
+            self.subscriptions.clone(),
+        );
+
+        let (send, recv) = tokio::sync::mpsc::channel(5);
+        let handle = tokio::spawn(conn.run(recv).map(move |_| connection_counter));
+        (handle, ConnectionInfo { command_send: send })
+    }
+
     async fn run_subscription_ticks(interval: u64, context: &ServerContext) -> Never {
         if interval == 0 {
             futures::future::pending().await
@@ -1,11 +1,14 @@
 use std::{
+    collections::HashMap,
     pin::Pin,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use futures::{future::Either, stream::FuturesUnordered, Future, StreamExt};
-use opcua_core::{trace_read_lock, trace_write_lock, Message, RequestMessage, ResponseMessage};
+use opcua_core::{
+    trace_read_lock, trace_write_lock, Message, RequestMessage, ResponseMessage, ServiceSpan,
+};
 use tracing::{debug, debug_span, error, trace, warn};
 
 use opcua_core::{
@@ -18,9 +21,9 @@ use opcua_core::{
 };
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{
-    ChannelSecurityToken, DateTime, FindServersResponse, GetEndpointsResponse, MessageSecurityMode,
-    OpenSecureChannelRequest, OpenSecureChannelResponse, ResponseHeader, SecurityTokenRequestType,
-    ServiceFault, StatusCode,
+    CancelResponse, ChannelSecurityToken, DateTime, FindServersResponse, GetEndpointsResponse,
+    MessageSecurityMode, OpenSecureChannelRequest, OpenSecureChannelResponse, ResponseHeader,
+    SecurityTokenRequestType, ServiceFault, StatusCode,
 };
 use tokio_util::sync::CancellationToken;
 use tracing_futures::Instrument;
@@ -70,6 +73,61 @@ pub(crate) enum ControllerCommand {
 
 type PendingMessageResponse = dyn Future<Output = Result<Response, String>> + Send + Sync + 'static;
 
+/// A request in progress that can be cancelled with the Cancel service.
+struct CancellableRequest {
+    session_id: u32,
+    request_handle: u32,
+    token: CancellationToken,
+}
+
+/// Requests in progress on a connection, by request ID.
+#[derive(Default)]
+struct CancellableRequests {
+    requests: HashMap<u32, CancellableRequest>,
+}
+
+impl CancellableRequests {
+    /// Register a request, returning a token that is cancelled if the client cancels it.
+    fn register(
+        &mut self,
+        request_id: u32,
+        session_id: u32,
+        request_handle: u32,
+    ) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.requests.insert(
+            request_id,
+            CancellableRequest {
+                session_id,
+                request_handle,
+                token: token.clone(),
+            },
+        );
+        token
+    }
+
+    /// Forget a request once its response is ready.
+    fn complete(&mut self, request_id: u32) {
+        self.requests.remove(&request_id);
+    }
+
+    /// Cancel the requests with the given request handle in a session, returning the
+    /// number of cancelled requests.
+    fn cancel(&mut self, session_id: u32, request_handle: u32) -> u32 {
+        let mut count = 0;
+        self.requests.retain(|_, r| {
+            if r.session_id == session_id && r.request_handle == request_handle {
+                r.token.cancel();
+                count += 1;
+                false
+            } else {
+                true
+            }
+        });
+        count
+    }
+}
+
 /// Master type managing a single connection.
 pub(crate) struct SessionController {
     channel: SecureChannel,
@@ -79,6 +137,7 @@ pub(crate) struct SessionController {
     certificate_store: Arc<RwLock<CertificateStore>>,
     message_handler: MessageHandler,
     pending_messages: FuturesUnordered<Pin<Box<PendingMessageResponse>>>,
+    cancellable: CancellableRequests,
     info: Arc<ServerInfo>,
     deadline: Instant,
 }
@@ -168,11 +227,14 @@ impl SessionController {
         node_managers: NodeManagers,
         subscriptions: Arc<SubscriptionCache>,
     ) -> Self {
-        let channel = SecureChannel::new(
+        let mut channel = SecureChannel::new(
             certificate_store.clone(),
             opcua_core::comms::secure_channel::Role::Server,
             Arc::new(RwLock::new(info.initial_encoding_context())),
         );
+        channel.set_metrics(info.transport_metrics.clone());
+        channel.set_interceptors(info.interceptors.clone());
+        channel.set_connection_limits(transport.connection_limits());
 
         Self {
             channel,
@@ -182,19 +244,22 @@ impl SessionController {
             certificate_store,
             message_handler: MessageHandler::new(info.clone(), node_managers, subscriptions),
             deadline: Instant::now()
-                + Duration::from_secs(info.config.tcp_config.hello_timeout as u64),
+                + Duration::from_secs(info.config.tcp_config.open_secure_channel_timeout() as u64),
             info,
             pending_messages: FuturesUnordered::new(),
+            cancellable: CancellableRequests::default(),
         }
     }
 
     async fn run(mut self, mut command: tokio::sync::mpsc::Receiver<ControllerCommand>) {
         loop {
-            let resp_fut = if self.pending_messages.is_empty() {
-                Either::Left(futures::future::pending::<Option<Result<Response, String>>>())
-            } else {
-                Either::Right(self.pending_messages.next())
-            };
+            // Leave completed responses where they are while the send queue is full.
+            let resp_fut =
+                if self.pending_messages.is_empty() || self.transport.is_send_queue_full() {
+                    Either::Left(futures::future::pending::<Option<Result<Response, String>>>())
+                } else {
+                    Either::Right(self.pending_messages.next())
+                };
 
             tokio::select! {
                 _ = tokio::time::sleep_until(self.deadline.into()) => {
@@ -219,6 +284,7 @@ impl SessionController {
                         // Cannot happen, pending_messages is non-empty or this future never returns.
                         None => unreachable!(),
                     };
+                    self.cancellable.complete(msg.request_id);
                     self.response_metrics(&msg);
 
                     if let Err(e) = self.transport.enqueue_message_for_send(
@@ -258,7 +324,7 @@ impl SessionController {
     }
 
     fn response_metrics(&self, msg: &Response) {
-        if self.info.diagnostics.enabled {
+        if self.info.diagnostics.enabled() {
             let status = msg.message.response_header().service_result;
             if status.is_bad() {
                 self.info.diagnostics.inc_rejected_requests();
@@ -274,6 +340,33 @@ impl SessionController {
         }
     }
 
+    fn session_metrics<T>(&self, res: &Result<T, StatusCode>) {
+        let &Err(status) = res else {
+            return;
+        };
+        self.info.diagnostics.inc_rejected_session_count();
+        if matches!(
+            status,
+            StatusCode::BadSecurityChecksFailed
+                | StatusCode::BadUserAccessDenied
+                | StatusCode::BadIdentityTokenInvalid
+                | StatusCode::BadIdentityTokenRejected
+                | StatusCode::BadApplicationSignatureInvalid
+                | StatusCode::BadUserSignatureInvalid
+                | StatusCode::BadNonceInvalid
+                | StatusCode::BadSecureChannelIdInvalid
+                | StatusCode::BadSecurityPolicyRejected
+                | StatusCode::BadCertificateInvalid
+                | StatusCode::BadCertificateUntrusted
+                | StatusCode::BadCertificateTimeInvalid
+                | StatusCode::BadCertificateRevoked
+                | StatusCode::BadCertificateUriInvalid
+                | StatusCode::BadCertificateUseNotAllowed
+        ) {
+            self.info.diagnostics.inc_security_rejected_session_count();
+        }
+    }
+
     fn fatal_error(&mut self, err: StatusCode, msg: &str) {
         if !self.transport.is_closing() {
             self.transport.enqueue_error(ErrorMessage::new(err, msg));
@@ -282,12 +375,15 @@ impl SessionController {
     }
 
     async fn process_request(&mut self, req: Request) -> RequestProcessResult {
-        let span = debug_span!(
-            "Incoming request",
-            request_id = req.request_id,
-            request_type = %req.message.type_name(),
-            request_handle = req.message.request_handle(),
-        );
+        let service_span = ServiceSpan::server(&req.message);
+        let span = service_span.span().in_scope(|| {
+            debug_span!(
+                "Incoming request",
+                request_id = req.request_id,
+                request_type = %req.message.type_name(),
+                request_handle = req.message.request_handle(),
+            )
+        });
 
         let id = req.request_id;
         match req.message {
@@ -304,6 +400,10 @@ impl SessionController {
                     self.info.diagnostics.inc_rejected_requests();
                     self.info.diagnostics.inc_security_rejected_requests();
                 }
+                service_span.finish(match &res {
+                    Ok(r) => r.response_header().service_result,
+                    Err(e) => *e,
+                });
                 match res {
                     Ok(r) => match self
                         .transport
@@ -333,7 +433,13 @@ impl SessionController {
                 let mut mgr = trace_write_lock!(self.session_manager);
                 let res = mgr.create_session(&mut self.channel, &self.certificate_store, &request);
                 drop(mgr);
-                self.process_service_result(res, request.request_header.request_handle, id)
+                self.session_metrics(&res);
+                self.process_service_result(
+                    res,
+                    request.request_header.request_handle,
+                    id,
+                    &service_span,
+                )
             }
 
             RequestMessage::ActivateSession(request) => {
@@ -346,7 +452,13 @@ impl SessionController {
                 .instrument(span.clone())
                 .await;
                 let _h = span.enter();
-                self.process_service_result(res, request.request_header.request_handle, id)
+                self.session_metrics(&res);
+                self.process_service_result(
+                    res,
+                    request.request_header.request_handle,
+                    id,
+                    &service_span,
+                )
             }
 
             RequestMessage::CloseSession(request) => {
@@ -359,7 +471,12 @@ impl SessionController {
                 .instrument(span.clone())
                 .await;
                 let _h = span.enter();
-                self.process_service_result(res, request.request_header.request_handle, id)
+                self.process_service_result(
+                    res,
+                    request.request_header.request_handle,
+                    id,
+                    &service_span,
+                )
             }
             RequestMessage::GetEndpoints(request) => {
                 // TODO some of the arguments in the request are ignored
@@ -378,6 +495,7 @@ impl SessionController {
                     }),
                     request.request_header.request_handle,
                     id,
+                    &service_span,
                 )
             }
             RequestMessage::FindServers(request) => {
@@ -406,10 +524,12 @@ impl SessionController {
                     }),
                     request.request_header.request_handle,
                     id,
+                    &service_span,
                 )
             }
             RequestMessage::FindServersOnNetwork(request) => {
                 let _h = span.enter();
+                service_span.finish(StatusCode::BadServiceUnsupported);
                 if let Err(e) = self.transport.enqueue_message_for_send(
                     &mut self.channel,
                     ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
@@ -424,6 +544,7 @@ impl SessionController {
             }
             RequestMessage::RegisterServer(request) => {
                 let _h = span.enter();
+                service_span.finish(StatusCode::BadServiceUnsupported);
                 if let Err(e) = self.transport.enqueue_message_for_send(
                     &mut self.channel,
                     ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
@@ -438,6 +559,7 @@ impl SessionController {
             }
             RequestMessage::RegisterServer2(request) => {
                 let _h = span.enter();
+                service_span.finish(StatusCode::BadServiceUnsupported);
                 if let Err(e) = self.transport.enqueue_message_for_send(
                     &mut self.channel,
                     ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
@@ -463,6 +585,7 @@ impl SessionController {
                         Err(e) => {
                             self.info.diagnostics.inc_rejected_requests();
                             self.info.diagnostics.inc_security_rejected_requests();
+                            service_span.finish(e.response_header().service_result);
                             match self
                                 .transport
                                 .enqueue_message_for_send(&mut self.channel, e, id)
@@ -477,6 +600,31 @@ impl SessionController {
                     };
 
                 debug!("Received request on session {session_id}");
+                service_span.record_session_id(session_id);
+
+                if let RequestMessage::Cancel(request) = &message {
+                    drop(mgr);
+                    let cancel_count = self.cancellable.cancel(session_id, request.request_handle);
+                    debug!(
+                        "Cancelled {cancel_count} requests with handle {}",
+                        request.request_handle
+                    );
+                    return self.process_service_result(
+                        Ok(CancelResponse {
+                            response_header: ResponseHeader::new_good(&request.request_header),
+                            cancel_count,
+                        }),
+                        request.request_header.request_handle,
+                        id,
+                        &service_span,
+                    );
+                }
+
+                let diagnostics = self
+                    .info
+                    .diagnostics
+                    .session(session_id)
+                    .map(|d| d.start_request(&message));
 
                 let deadline = {
                     let timeout = message.request_header().timeout_hint;
@@ -501,12 +649,13 @@ impl SessionController {
                     .handle_message(message, session_id, session, user_token, id)
                 {
                     super::message_handler::HandleMessageResult::AsyncMessage(mut handle) => {
+                        let cancelled = self.cancellable.register(id, session_id, request_handle);
                         self.pending_messages
                             .push(Box::pin(async move {
                                 // Select biased because if for some reason there's a long time between polls,
                                 // we want to return the response even if the timeout expired. We only want to send a timeout
                                 // if the call has not been finished yet.
-                                tokio::select! {
+                                let res = tokio::select! {
                                     biased;
                                     r = &mut handle => {
                                         match r {
@@ -523,11 +672,24 @@ impl SessionController {
                                             }
                                         }
                                     }
+                                    _ = cancelled.cancelled() => {
+                                        handle.abort();
+                                        Ok(Response { message: ServiceFault::new(request_handle, StatusCode::BadRequestCancelledByClient).into(), request_id: id })
+                                    }
                                     _ = tokio::time::sleep_until(deadline.into()) => {
                                         handle.abort();
                                         Ok(Response { message: ServiceFault::new(request_handle, StatusCode::BadTimeout).into(), request_id: id })
                                     }
+                                };
+                                let status = match &res {
+                                    Ok(r) => r.message.response_header().service_result,
+                                    Err(_) => StatusCode::BadInternalError,
+                                };
+                                service_span.finish(status);
+                                if let Some(diagnostics) = diagnostics {
+                                    diagnostics.finish(status);
                                 }
+                                res
                             }.instrument(span.clone())));
                         RequestProcessResult::Ok
                     }
@@ -537,6 +699,10 @@ impl SessionController {
                             "Sending response of type {}", s.message.type_name()
                         );
                         self.response_metrics(&s);
+                        service_span.finish(s.message.response_header().service_result);
+                        if let Some(diagnostics) = diagnostics {
+                            diagnostics.finish(s.message.response_header().service_result);
+                        }
 
                         if let Err(e) = self.transport.enqueue_message_for_send(
                             &mut self.channel,
@@ -549,7 +715,16 @@ impl SessionController {
                         RequestProcessResult::Ok
                     }
                     super::message_handler::HandleMessageResult::PublishResponse(resp) => {
-                        self.pending_messages.push(Box::pin(resp.recv()));
+                        self.pending_messages.push(Box::pin(async move {
+                            let res = resp.recv().await;
+                            if let Ok(r) = &res {
+                                service_span.finish(r.message.response_header().service_result);
+                                if let Some(diagnostics) = diagnostics {
+                                    diagnostics.finish(r.message.response_header().service_result);
+                                }
+                            }
+                            res
+                        }));
                         RequestProcessResult::Ok
                     }
                 }
@@ -562,8 +737,9 @@ impl SessionController {
         res: Result<impl Into<ResponseMessage>, StatusCode>,
         request_handle: u32,
         request_id: u32,
+        service_span: &ServiceSpan,
     ) -> RequestProcessResult {
-        let message = match res {
+        let message: ResponseMessage = match res {
             Ok(m) => m.into(),
             Err(e) => {
                 self.info.diagnostics.inc_rejected_requests();
@@ -579,6 +755,7 @@ impl SessionController {
                 ServiceFault::new(request_handle, e).into()
             }
         };
+        service_span.finish(message.response_header().service_result);
         if let Err(e) =
             self.transport
                 .enqueue_message_for_send(&mut self.channel, message, request_id)
@@ -673,6 +850,7 @@ impl SessionController {
                     return Err(StatusCode::BadUnexpectedError);
                 }
                 self.secure_channel_state.renew_count += 1;
+                self.channel.metrics().secure_channel_renewed();
                 self.channel.secure_channel_id()
             }
         };
@@ -715,7 +893,7 @@ impl SessionController {
         self.channel.set_token_lifetime(revised_lifetime);
 
         self.channel
-            .validate_secure_channel_nonce_length(&request.client_nonce)?;
+            .validate_secure_channel_nonce(&request.client_nonce)?;
         self.channel
             .set_remote_nonce_from_byte_string(&request.client_nonce)?;
         self.channel.create_random_nonce();
@@ -773,3 +951,25 @@ impl SecureChannelState {
         self.last_token_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CancellableRequests;
+
+    #[test]
+    fn cancel_requests() {
+        let mut requests = CancellableRequests::default();
+        let first = requests.register(1, 1, 10);
+        let other_session = requests.register(2, 2, 10);
+        let completed = requests.register(3, 1, 11);
+        requests.complete(3);
+
+        assert_eq!(requests.cancel(1, 11), 0);
+        assert!(!completed.is_cancelled());
+        assert_eq!(requests.cancel(1, 10), 1);
+        assert!(first.is_cancelled());
+        assert!(!other_session.is_cancelled());
+        // Requests are only cancelled once.
+        assert_eq!(requests.cancel(1, 10), 0);
+    }
+}
@@ -11,6 +11,7 @@ use crate::authenticator::UserToken;
 use crate::identity_token::IdentityToken;
 use crate::info::ServerInfo;
 use crate::node_manager::{BrowseContinuationPoint, QueryContinuationPoint};
+use opcua_core::comms::tcp_types::ConnectionLimits;
 use opcua_crypto::X509;
 use opcua_types::{
     ApplicationDescription, ByteString, MessageSecurityMode, NodeId, StatusCode, UAString,
@@ -44,6 +45,8 @@ pub struct Session {
     max_request_message_size: u32,
     /// Negotiated max response message size
     max_response_message_size: u32,
+    /// Message limits of the connection the session is bound to
+    connection_limits: ConnectionLimits,
     /// Endpoint url for this session
     endpoint_url: UAString,
     /// Maximum number of continuation points for browse
@@ -66,6 +69,8 @@ pub struct Session {
     query_continuation_points: HashMap<ByteString, QueryContinuationPoint>,
     /// User token.
     user_token: Option<UserToken>,
+    /// Roles granted to the session when it was activated.
+    roles: Arc<[NodeId]>,
     /// Whether the session has been closed.
     is_closed: bool,
 }
@@ -88,6 +93,7 @@ impl Session {
         session_name: UAString,
         application_description: ApplicationDescription,
         message_security_mode: MessageSecurityMode,
+        connection_limits: ConnectionLimits,
     ) -> Self {
         let (session_id, session_id_numeric) = next_session_id();
         Self {
@@ -109,6 +115,7 @@ impl Session {
             locale_ids: None,
             max_request_message_size,
             max_response_message_size,
+            connection_limits,
             endpoint_url,
             max_browse_continuation_points: info.config.limits.max_browse_continuation_points,
             max_history_continuation_points: info.config.limits.max_history_continuation_points,
@@ -117,6 +124,7 @@ impl Session {
             history_continuation_points: Default::default(),
             query_continuation_points: Default::default(),
             user_token: None,
+            roles: Arc::new([]),
             application_description,
             message_security_mode,
             is_closed: false,
@@ -172,6 +180,7 @@ impl Session {
     }
 
     /// Activate the session.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn activate(
         &mut self,
         secure_channel_id: u32,
@@ -179,9 +188,13 @@ impl Session {
         identity: IdentityToken,
         locale_ids: Option<Vec<UAString>>,
         user_token: UserToken,
+        roles: Vec<NodeId>,
+        connection_limits: ConnectionLimits,
     ) {
         self.user_token = Some(user_token);
+        self.roles = roles.into();
         self.secure_channel_id = secure_channel_id;
+        self.connection_limits = connection_limits;
         self.session_nonce = server_nonce;
         self.user_identity = identity;
         self.locale_ids = locale_ids;
@@ -215,6 +228,12 @@ impl Session {
         &self.session_nonce
     }
 
+    /// Get the locales the client requested for human readable strings when the
+    /// session was last activated, in order of preference.
+    pub fn locale_ids(&self) -> Option<&[UAString]> {
+        self.locale_ids.as_deref()
+    }
+
     /// Whether this session is activated.
     pub fn is_activated(&self) -> bool {
         self.user_token.is_some() && !self.is_closed
@@ -301,6 +320,12 @@ impl Session {
         self.user_token.as_ref()
     }
 
+    /// Get the node IDs of the roles granted to this session. This is empty
+    /// if the session is not activated.
+    pub fn roles(&self) -> &Arc<[NodeId]> {
+        &self.roles
+    }
+
     /// Get the message security mode used by this session.
     pub fn message_security_mode(&self) -> MessageSecurityMode {
         self.message_security_mode
@@ -321,11 +346,22 @@ impl Session {
         self.max_response_message_size
     }
 
+    /// Get the message limits negotiated for the connection this session is
+    /// currently bound to.
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        self.connection_limits
+    }
+
     /// Get the name of this session as set by the client.
     pub fn session_name(&self) -> &str {
         self.session_name.as_ref()
     }
 
+    /// Get the revised session timeout.
+    pub fn session_timeout(&self) -> Duration {
+        self.session_timeout
+    }
+
     /// Get the security policy URI of this session.
     pub fn security_policy_uri(&self) -> &str {
         &self.security_policy_uri
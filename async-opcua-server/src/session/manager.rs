@@ -13,11 +13,14 @@ use parking_lot::RwLock;
 use tokio::sync::Notify;
 use tracing::{error, info};
 
-use crate::{identity_token::IdentityToken, info::ServerInfo};
+use crate::{
+    diagnostics::SessionDiagnostics, identity_token::IdentityToken, info::ServerInfo,
+    roles::SessionIdentity,
+};
 use opcua_types::{
     ActivateSessionRequest, ActivateSessionResponse, CloseSessionRequest, CloseSessionResponse,
-    CreateSessionRequest, CreateSessionResponse, Error, NodeId, ResponseHeader, SignatureData,
-    StatusCode,
+    CreateSessionRequest, CreateSessionResponse, Error, ErrorContext, NodeId, ResponseHeader,
+    SignatureData, StatusCode,
 };
 
 use super::{instance::Session, message_handler::MessageHandler};
@@ -51,6 +54,11 @@ impl SessionManager {
         Self::find_by_token_int(&self.sessions, authentication_token)
     }
 
+    /// Get a session by its session ID.
+    pub fn find_by_id(&self, session_id: &NodeId) -> Option<Arc<RwLock<Session>>> {
+        self.sessions.get(session_id).cloned()
+    }
+
     fn find_by_token_int(
         sessions: &HashMap<NodeId, Arc<RwLock<Session>>>,
         authentication_token: &NodeId,
@@ -98,14 +106,12 @@ impl SessionManager {
         }
 
         let client_certificate = if security_policy != SecurityPolicy::None {
-            let cert = opcua_crypto::X509::from_byte_string(&request.client_certificate)?;
+            let cert = opcua_crypto::X509::from_byte_string(&request.client_certificate)
+                .context("reading client certificate")?;
             let store = trace_read_lock!(certificate_store);
-            store.validate_or_reject_application_instance_cert(
-                &cert,
-                security_policy,
-                None,
-                None,
-            )?;
+            store
+                .validate_or_reject_application_instance_cert(&cert, security_policy, None, None)
+                .context("validating client certificate")?;
             Some(cert)
         } else {
             None
@@ -156,8 +162,12 @@ impl SessionManager {
             request.session_name.clone(),
             request.client_description.clone(),
             channel.security_mode(),
+            channel.connection_limits(),
         );
         info!("Created new session with ID {}", session.session_id());
+        self.info
+            .diagnostics
+            .register_session(SessionDiagnostics::new(&session, &self.info));
 
         let session_id = session.session_id().clone();
         self.sessions
@@ -199,7 +209,8 @@ impl SessionManager {
                     client_certificate,
                     server_certificate,
                     session.session_nonce().as_ref(),
-                )?;
+                )
+                .context("verifying client signature")?;
                 Ok(())
             } else {
                 Err(Error::new(
@@ -227,6 +238,9 @@ impl SessionManager {
         info!("Session {id} has expired, removing it from the session map. Subscriptions will remain until they individually expire");
 
         let mut session = trace_write_lock!(session);
+        self.info
+            .diagnostics
+            .unregister_session(session.session_id_numeric());
         session.close();
     }
 
@@ -283,6 +297,7 @@ pub(crate) async fn close_session(
         mgr.info
             .diagnostics
             .set_current_session_count(mgr.sessions.len() as u32);
+        mgr.info.diagnostics.unregister_session(id);
         (session, id, token)
     };
 
@@ -338,7 +353,8 @@ pub(crate) async fn activate_session(
                     &mgr.info,
                     &session,
                     &request.client_signature,
-                )?;
+                )
+                .context("activating session")?;
             }
             (endpoint_url, session.session_nonce().clone())
         };
@@ -354,7 +370,20 @@ pub(crate) async fn activate_session(
             request.user_identity_token.clone(),
             &session_nonce,
         )
-        .await?;
+        .await
+        .context_with(|| format!("authenticating user for endpoint {endpoint_url}"))?;
+
+    let identity = IdentityToken::new(request.user_identity_token.clone());
+    let roles = {
+        let session = trace_read_lock!(session_lck);
+        info.roles.roles_for(&SessionIdentity::new(
+            &identity,
+            session.application_description().application_uri.as_ref(),
+            session.client_certificate(),
+            security_mode,
+            info.authenticator.identity_claims(&user_token),
+        ))
+    };
 
     let (server_nonce, session_id) = {
         let mut session = trace_write_lock!(session_lck);
@@ -372,12 +401,20 @@ pub(crate) async fn activate_session(
         // The standard also mentions that a server may need to
         // "Tear down connections to an underlying system and re-establish them using the new credentials". We need some way to
         // handle this eventuality, perhaps a dedicated node-manager endpoint that can be called here.
+        if let Some(diagnostics) = info
+            .diagnostics
+            .tracked_session(session.session_id_numeric())
+        {
+            diagnostics.activate(&identity, &user_token, request.locale_ids.as_deref());
+        }
         session.activate(
             secure_channel_id,
             server_nonce,
-            IdentityToken::new(request.user_identity_token.clone()),
+            identity,
             request.locale_ids.clone(),
             user_token.clone(),
+            roles,
+            channel.connection_limits(),
         );
         (
             session.session_nonce().clone(),
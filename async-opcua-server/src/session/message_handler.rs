@@ -1,7 +1,7 @@
 use std::{sync::Arc, time::Instant};
 
 use chrono::Utc;
-use opcua_core::{Message, RequestMessage, ResponseMessage};
+use opcua_core::{trace_read_lock, Message, RequestMessage, ResponseMessage};
 use parking_lot::RwLock;
 use tokio::task::JoinHandle;
 use tracing::{debug, warn};
@@ -120,10 +120,16 @@ impl<T> Request<T> {
 
     /// Get a request context object from this request.
     pub(super) fn context(&self) -> RequestContext {
+        let (roles, security_mode) = {
+            let session = trace_read_lock!(self.session);
+            (session.roles().clone(), session.message_security_mode())
+        };
         RequestContext {
             session: self.session.clone(),
             authenticator: self.info.authenticator.clone(),
             token: self.token.clone(),
+            roles,
+            security_mode,
             current_node_manager_index: 0,
             type_tree: self.info.type_tree.clone(),
             type_tree_getter: self.info.type_tree_getter.clone(),
@@ -365,11 +371,17 @@ impl MessageHandler {
             return;
         }
 
+        let (roles, security_mode) = {
+            let session = trace_read_lock!(session);
+            (session.roles().clone(), session.message_security_mode())
+        };
         let mut context = RequestContext {
             session,
             session_id,
             authenticator: self.info.authenticator.clone(),
             token,
+            roles,
+            security_mode,
             current_node_manager_index: 0,
             type_tree: self.info.type_tree.clone(),
             subscriptions: self.subscriptions.clone(),
@@ -396,10 +408,16 @@ impl MessageHandler {
         session_id: u32,
         token: UserToken,
     ) -> NamespaceMap {
+        let (roles, security_mode) = {
+            let session = trace_read_lock!(session);
+            (session.roles().clone(), session.message_security_mode())
+        };
         let ctx = RequestContext {
             session,
             authenticator: self.info.authenticator.clone(),
             token,
+            roles,
+            security_mode,
             current_node_manager_index: 0,
             type_tree: self.info.type_tree.clone(),
             type_tree_getter: self.info.type_tree_getter.clone(),
@@ -164,7 +164,18 @@ pub(crate) async fn history_read(
     {
         return service_fault!(request, StatusCode::BadTooManyOperations);
     }
-    let mut nodes: Vec<_> = {
+    // Processed reads must give an aggregate for each node.
+    let aggregates = match &details {
+        HistoryReadDetails::Processed(d) => d.aggregate_type.as_deref().unwrap_or_default(),
+        _ => &[],
+    };
+    if matches!(details, HistoryReadDetails::Processed(_))
+        && !request.request.release_continuation_points
+        && aggregates.len() != items.len()
+    {
+        return service_fault!(request, StatusCode::BadAggregateListMismatch);
+    }
+    let mut nodes: Vec<HistoryNode> = {
         let mut session = trace_write_lock!(request.session);
         items
             .into_iter()
@@ -189,6 +200,9 @@ pub(crate) async fn history_read(
             })
             .collect()
     };
+    for (node, aggregate) in nodes.iter_mut().zip(aggregates) {
+        node.set_aggregate_type(aggregate.clone());
+    }
 
     // If we are releasing continuation points we should not return any data.
     if request.request.release_continuation_points {
@@ -6,7 +6,7 @@ mod subscription;
 use std::{hash::Hash, sync::Arc, time::Instant};
 
 use chrono::Utc;
-use hashbrown::{Equivalent, HashMap};
+use hashbrown::{Equivalent, HashMap, HashSet};
 pub use monitored_item::{CreateMonitoredItem, MonitoredItem};
 use opcua_core::{trace_read_lock, trace_write_lock, ResponseMessage};
 use opcua_nodes::{Event, TypeTree};
@@ -15,6 +15,7 @@ use subscription::TickReason;
 pub use subscription::{MonitoredItemHandle, Subscription, SubscriptionState};
 use tracing::error;
 
+use notify::EventPermissions;
 pub use notify::{
     SubscriptionDataNotifier, SubscriptionDataNotifierBatch, SubscriptionEventNotifier,
     SubscriptionEventNotifierBatch,
@@ -29,14 +30,18 @@ use opcua_types::{
     MonitoredItemCreateResult, MonitoredItemModifyRequest, MonitoringMode, NodeId,
     NotificationMessage, NumericRange, PublishRequest, RepublishRequest, RepublishResponse,
     ResponseHeader, SetPublishingModeRequest, SetPublishingModeResponse, StatusCode,
-    TimestampsToReturn, TransferResult, TransferSubscriptionsRequest,
-    TransferSubscriptionsResponse,
+    SubscriptionDiagnosticsDataType, TimestampsToReturn, TransferResult,
+    TransferSubscriptionsRequest, TransferSubscriptionsResponse,
 };
 
 use super::{
+    alarms::RetainedConditions,
     authenticator::UserToken,
     info::ServerInfo,
-    node_manager::{MonitoredItemRef, MonitoredItemUpdateRef, RequestContext, ServerContext},
+    node_manager::{
+        EventHistoryCapture, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagersRef,
+        RequestContext, ServerContext,
+    },
     session::instance::Session,
     SubscriptionLimits,
 };
@@ -99,10 +104,22 @@ pub struct SubscriptionCache {
     inner: RwLock<SubscriptionCacheInner>,
     /// Configured limits on subscriptions.
     limits: SubscriptionLimits,
+    /// Recorder of event history.
+    event_history: Option<Arc<EventHistoryCapture>>,
+    /// Conditions replayed on `ConditionRefresh`.
+    conditions: RetainedConditions,
+    /// Node managers, used to find the role permissions of event sources.
+    node_managers: NodeManagersRef,
+    /// Server info, used to check the permissions of sessions on event sources.
+    info: Arc<ServerInfo>,
 }
 
 impl SubscriptionCache {
-    pub(crate) fn new(limits: SubscriptionLimits) -> Self {
+    pub(crate) fn new(
+        limits: SubscriptionLimits,
+        info: Arc<ServerInfo>,
+        node_managers: NodeManagersRef,
+    ) -> Self {
         Self {
             inner: RwLock::new(SubscriptionCacheInner {
                 session_subscriptions: HashMap::new(),
@@ -110,9 +127,42 @@ impl SubscriptionCache {
                 monitored_items: HashMap::new(),
             }),
             limits,
+            event_history: info.event_history.clone(),
+            conditions: RetainedConditions::new(),
+            node_managers,
+            info,
         }
     }
 
+    /// Get the registry of retained conditions, sent to subscriptions that call
+    /// `ConditionRefresh` or `ConditionRefresh2`.
+    pub fn conditions(&self) -> &RetainedConditions {
+        &self.conditions
+    }
+
+    /// Send the retained conditions to the subscription with ID `subscription_id`
+    /// owned by the session with ID `session_id`, framed by a `RefreshStartEvent`
+    /// and a `RefreshEndEvent`. If `monitored_item_id` is given, only that
+    /// monitored item is refreshed.
+    #[cfg(feature = "generated-address-space")]
+    pub(crate) fn condition_refresh(
+        &self,
+        session_id: u32,
+        subscription_id: u32,
+        monitored_item_id: Option<u32>,
+    ) -> Result<(), StatusCode> {
+        let Some(cache) = self.get_session_subscriptions(session_id) else {
+            return Err(StatusCode::BadSubscriptionIdInvalid);
+        };
+        let mut cache = cache.lock();
+        cache.condition_refresh(
+            subscription_id,
+            monitored_item_id,
+            &self.conditions,
+            &mut EventPermissions::new(&self.node_managers, &self.info.roles),
+        )
+    }
+
     /// Get the `SessionSubscriptions` object for a single session by its numeric ID.
     pub fn get_session_subscriptions(
         &self,
@@ -122,6 +172,24 @@ impl SubscriptionCache {
         inner.session_subscriptions.get(&session_id).cloned()
     }
 
+    /// Get the diagnostics of every subscription on the server, ordered by session.
+    pub fn subscription_diagnostics(&self) -> Vec<SubscriptionDiagnosticsDataType> {
+        let sessions: Vec<_> = {
+            let inner = trace_read_lock!(self.inner);
+            let mut sessions: Vec<_> = inner
+                .session_subscriptions
+                .iter()
+                .map(|(id, s)| (*id, s.clone()))
+                .collect();
+            sessions.sort_by_key(|s| s.0);
+            sessions
+        };
+        sessions
+            .into_iter()
+            .flat_map(|(_, s)| s.lock().subscription_diagnostics())
+            .collect()
+    }
+
     /// This is the periodic subscription tick where we check for
     /// triggered subscriptions.
     ///
@@ -131,12 +199,17 @@ impl SubscriptionCache {
         // be more efficient, and would be more responsive.
         let mut to_delete = Vec::new();
         let mut items_to_delete = Vec::new();
+        let count_intervals = context.info.diagnostics.enabled();
+        let mut intervals = HashSet::new();
         {
             let now = Utc::now();
             let now_instant = Instant::now();
             let lck = trace_read_lock!(self.inner);
             for (session_id, sub) in lck.session_subscriptions.iter() {
                 let mut sub_lck = sub.lock();
+                if count_intervals {
+                    intervals.extend(sub_lck.publishing_intervals());
+                }
                 items_to_delete.push((
                     sub_lck.session().clone(),
                     sub_lck.tick(&now, now_instant, TickReason::TickTimerFired),
@@ -146,6 +219,12 @@ impl SubscriptionCache {
                 }
             }
         }
+        if count_intervals {
+            context
+                .info
+                .diagnostics
+                .set_publishing_interval_count(intervals.len() as u32);
+        }
         if !to_delete.is_empty() {
             let mut lck = trace_write_lock!(self.inner);
             for id in to_delete {
@@ -168,20 +247,27 @@ impl SubscriptionCache {
         for (session, items) in items_to_delete {
             // Create a local request context, since we need to call delete monitored items.
 
-            let (id, token) = {
+            let (id, token, roles, security_mode) = {
                 let lck = session.read();
                 let Some(token) = lck.user_token() else {
                     error!("Active session missing user token, this should be impossible");
                     continue;
                 };
 
-                (lck.session_id_numeric(), token.clone())
+                (
+                    lck.session_id_numeric(),
+                    token.clone(),
+                    lck.roles().clone(),
+                    lck.message_security_mode(),
+                )
             };
             let ctx = RequestContext {
                 session,
                 session_id: id,
                 authenticator: context.authenticator.clone(),
                 token,
+                roles,
+                security_mode,
                 current_node_manager_index: 0,
                 type_tree: context.type_tree.clone(),
                 subscriptions: context.subscriptions.clone(),
@@ -290,7 +376,7 @@ impl SubscriptionCache {
         }) else {
             return Err(StatusCode::BadNoSubscription);
         };
-        let cache_lck = cache.lock();
+        let mut cache_lck = cache.lock();
         cache_lck.republish(request)
     }
 
@@ -348,7 +434,10 @@ impl SubscriptionCache {
     /// }
     /// ```
     pub fn event_notifier<'a, 'b>(&'a self) -> SubscriptionEventNotifier<'a, 'b> {
-        SubscriptionEventNotifier::new(trace_read_lock!(self.inner))
+        SubscriptionEventNotifier::new(
+            trace_read_lock!(self.inner),
+            EventPermissions::new(&self.node_managers, &self.info.roles),
+        )
     }
 
     /// Notify any listening clients about a list of data changes.
@@ -390,11 +479,21 @@ impl SubscriptionCache {
 
     /// Notify listening clients to events. Without a custom node manager implementing
     /// event history, this is the only way to report events in the server.
+    ///
+    /// If the server has an event history provider, events raised on historized
+    /// notifiers are also recorded.
     pub fn notify_events<'a>(&self, items: impl Iterator<Item = (&'a dyn Event, &'a NodeId)>) {
         let mut notif = self.event_notifier();
+        let mut captured = Vec::new();
         for (evt, id) in items {
+            if let Some(history) = &self.event_history {
+                history.capture(evt, id, &mut captured);
+            }
             notif.notify(id, evt);
         }
+        if let Some(history) = &self.event_history {
+            history.record(captured);
+        }
     }
 
     pub(crate) fn create_monitored_items(
@@ -632,6 +731,10 @@ impl SubscriptionCache {
             .collect();
 
         let key = Self::get_key(&context.session);
+        let client_uri = trace_read_lock!(context.session)
+            .application_description()
+            .application_uri
+            .clone();
         {
             let mut lck = trace_write_lock!(self.inner);
             let session_subs = lck
@@ -669,6 +772,9 @@ impl SubscriptionCache {
                 };
 
                 let mut session_lck = session_cache.lock();
+                if let Some(sub) = session_lck.get_mut(*sub_id) {
+                    sub.counters_mut().transfer_request_count += 1;
+                }
 
                 if !session_lck.user_token().is_equivalent_for_transfer(&key) {
                     res.status_code = StatusCode::BadUserAccessDenied;
@@ -685,14 +791,24 @@ impl SubscriptionCache {
                     res.available_sequence_numbers =
                         Some(notifs.iter().map(|n| n.message.sequence_number).collect());
 
+                    let same_client = trace_read_lock!(session_lck.session())
+                        .application_description()
+                        .application_uri
+                        == client_uri;
                     if let Err((e, sub, notifs)) = session_subs_lck.insert(sub, notifs) {
                         res.status_code = e;
                         let _ = session_lck.insert(sub, notifs);
                     } else {
-                        if req.send_initial_values {
-                            if let Some(sub) = session_subs_lck.get_mut(*sub_id) {
+                        if let Some(sub) = session_subs_lck.get_mut(*sub_id) {
+                            if req.send_initial_values {
                                 sub.set_resend_data();
                             }
+                            let counters = sub.counters_mut();
+                            if same_client {
+                                counters.transferred_to_same_client_count += 1;
+                            } else {
+                                counters.transferred_to_alt_client_count += 1;
+                            }
                         }
                         lck.subscription_to_session
                             .insert(*sub_id, context.session_id);
@@ -299,6 +299,7 @@ pub struct MonitoredItem {
     queue_size: usize,
     notification_queue: VecDeque<Notification>,
     queue_overflow: bool,
+    queue_overflow_count: u32,
     timestamps_to_return: TimestampsToReturn,
     last_data_value: Option<DataValue>,
     /// Value skipped due to sampling interval, we keep these
@@ -332,6 +333,7 @@ impl MonitoredItem {
             queue_size: request.queue_size,
             notification_queue: VecDeque::new(),
             queue_overflow: false,
+            queue_overflow_count: 0,
             any_new_notification: false,
             eu_range: request.eu_range,
         };
@@ -590,6 +592,22 @@ impl MonitoredItem {
         true
     }
 
+    #[cfg(feature = "generated-address-space")]
+    pub(super) fn notify_refresh_event(&mut self, event: &dyn Event) -> bool {
+        if self.monitoring_mode == MonitoringMode::Disabled {
+            return false;
+        }
+
+        let FilterType::EventFilter(filter) = &self.filter else {
+            return false;
+        };
+
+        let notif = filter.select(event, self.client_handle);
+        self.enqueue_notification(notif);
+
+        true
+    }
+
     fn enqueue_notification(&mut self, notification: impl Into<Notification>) {
         self.any_new_notification = true;
         let overflow = self.notification_queue.len() == self.queue_size;
@@ -607,6 +625,7 @@ impl MonitoredItem {
                 n.value.status = Some(n.value.status().set_overflow(true));
             }
             self.queue_overflow = true;
+            self.queue_overflow_count += 1;
         }
 
         self.notification_queue.push_back(notification);
@@ -744,6 +763,16 @@ impl MonitoredItem {
     pub fn client_handle(&self) -> u32 {
         self.client_handle
     }
+
+    /// Whether this monitored item reports events.
+    pub fn is_event_item(&self) -> bool {
+        matches!(self.filter, FilterType::EventFilter(_))
+    }
+
+    /// Number of times the queue of this monitored item has overflowed.
+    pub fn queue_overflow_count(&self) -> u32 {
+        self.queue_overflow_count
+    }
 }
 
 #[cfg(test)]
@@ -783,6 +812,7 @@ pub(super) mod tests {
             queue_size: 10,
             notification_queue: Default::default(),
             queue_overflow: false,
+            queue_overflow_count: 0,
             timestamps_to_return: opcua_types::TimestampsToReturn::Both,
             last_data_value: None,
             sample_skipped_data_value: None,
@@ -1,9 +1,14 @@
 use hashbrown::HashMap;
 use opcua_nodes::Event;
-use opcua_types::{node_id::IntoNodeIdRef, AttributeId, DataValue, DateTime, ObjectId, Variant};
+use opcua_types::{
+    node_id::IntoNodeIdRef, AttributeId, DataValue, DateTime, NodeId, NumericRange, ObjectId,
+    ObjectTypeId, PermissionType, RolePermissionType, Variant,
+};
 use parking_lot::RwLockReadGuard;
 
 use crate::{
+    node_manager::NodeManagersRef,
+    roles::RoleSet,
     subscriptions::{MonitoredItemEntry, MonitoredItemKeyRef, SubscriptionCacheInner},
     MonitoredItemHandle,
 };
@@ -145,6 +150,51 @@ impl<'a> Drop for SubscriptionDataNotifier<'a> {
 pub struct SubscriptionEventNotifier<'a, 'b> {
     lock: RwLockReadGuard<'a, SubscriptionCacheInner>,
     by_subscription: HashMap<u32, Vec<(MonitoredItemHandle, &'b dyn Event)>>,
+    permissions: EventPermissions<'a>,
+}
+
+/// Checks the `ReceiveEvents` permission of sessions on the source nodes of
+/// events, looking up the role permissions of each source node only once.
+pub(super) struct EventPermissions<'a> {
+    node_managers: &'a NodeManagersRef,
+    roles: &'a RoleSet,
+    sources: HashMap<NodeId, Option<Vec<RolePermissionType>>>,
+}
+
+impl<'a> EventPermissions<'a> {
+    pub(super) fn new(node_managers: &'a NodeManagersRef, roles: &'a RoleSet) -> Self {
+        Self {
+            node_managers,
+            roles,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Check whether a session with `roles` may receive `event`.
+    /// Events without a source node are sent to every session.
+    pub(super) fn can_receive(&mut self, event: &dyn Event, roles: &[NodeId]) -> bool {
+        let Variant::NodeId(source) = event.get_field(
+            &ObjectTypeId::BaseEventType.into(),
+            AttributeId::Value,
+            &NumericRange::None,
+            &["SourceNode".into()],
+        ) else {
+            return true;
+        };
+        if source.is_null() {
+            return true;
+        }
+        let node_managers = self.node_managers;
+        let role_permissions = self.sources.entry((*source).clone()).or_insert_with(|| {
+            node_managers
+                .iter()
+                .find(|nm| nm.owns_node(&source))
+                .and_then(|nm| nm.role_permissions(&source))
+        });
+        self.roles
+            .permissions(roles, role_permissions.as_deref(), source.namespace)
+            .contains(PermissionType::ReceiveEvents)
+    }
 }
 
 /// Notifier for a specific node emitting events.
@@ -176,10 +226,14 @@ impl<'a, 'b> SubscriptionEventNotifierBatch<'a, 'b> {
 }
 
 impl<'a, 'b> SubscriptionEventNotifier<'a, 'b> {
-    pub(super) fn new(lock: RwLockReadGuard<'a, SubscriptionCacheInner>) -> Self {
+    pub(super) fn new(
+        lock: RwLockReadGuard<'a, SubscriptionCacheInner>,
+        permissions: EventPermissions<'a>,
+    ) -> Self {
         Self {
             lock,
             by_subscription: Default::default(),
+            permissions,
         }
     }
 
@@ -250,7 +304,7 @@ impl<'a, 'b> Drop for SubscriptionEventNotifier<'a, 'b> {
                 continue;
             };
             let mut cache_lck = cache.lock();
-            cache_lck.notify_events(items);
+            cache_lck.notify_events(items, &mut self.permissions);
         }
     }
 }
@@ -6,26 +6,35 @@ use std::{
 
 use super::{
     monitored_item::MonitoredItem,
+    notify::EventPermissions,
     subscription::{MonitoredItemHandle, Subscription, TickReason, TickResult},
     CreateMonitoredItem, NonAckedPublish, PendingPublish, PersistentSessionKey,
 };
 use hashbrown::{HashMap, HashSet};
 use opcua_nodes::{Event, TypeTree};
 
+// Condition refresh is only handled by the core node manager.
+#[cfg(feature = "generated-address-space")]
+use crate::alarms::{RefreshEndEventType, RefreshStartEventType, RetainedConditions};
+#[cfg(feature = "generated-address-space")]
+use opcua_crypto::random;
+#[cfg(feature = "generated-address-space")]
+use opcua_nodes::NamespaceMap;
+
 use crate::{
     info::ServerInfo,
     node_manager::{MonitoredItemRef, MonitoredItemUpdateRef, TypeTreeForUserStatic},
     session::instance::Session,
     SubscriptionLimits,
 };
-use opcua_core::sync::RwLock;
+use opcua_core::{sync::RwLock, trace_read_lock};
 use opcua_types::{
     AttributeId, CreateSubscriptionRequest, CreateSubscriptionResponse, DataValue, DateTime,
     DateTimeUtc, ExtensionObject, ModifySubscriptionRequest, ModifySubscriptionResponse,
     MonitoredItemCreateResult, MonitoredItemModifyRequest, MonitoredItemModifyResult,
     MonitoringMode, NodeId, NotificationMessage, PublishRequest, PublishResponse, RepublishRequest,
     RepublishResponse, ResponseHeader, ServiceFault, SetPublishingModeRequest,
-    SetPublishingModeResponse, StatusCode, TimestampsToReturn,
+    SetPublishingModeResponse, StatusCode, SubscriptionDiagnosticsDataType, TimestampsToReturn,
 };
 
 /// Subscriptions belonging to a single session. Note that they are technically _owned_ by
@@ -44,6 +53,8 @@ pub struct SessionSubscriptions {
 
     /// Static reference to the session owning this, required to cleanly handle deletion.
     session: Arc<RwLock<Session>>,
+    /// ID of the session owning this, used for diagnostics.
+    session_id: NodeId,
     /// Static reference to the type-tree for the user owning this.
     type_tree_for_user: Arc<dyn TypeTreeForUserStatic>,
 }
@@ -55,8 +66,10 @@ impl SessionSubscriptions {
         session: Arc<RwLock<Session>>,
         type_tree_for_user: Arc<dyn TypeTreeForUserStatic>,
     ) -> Self {
+        let session_id = trace_read_lock!(session).session_id().clone();
         Self {
             user_token,
+            session_id,
             subscriptions: HashMap::new(),
             publish_request_queue: VecDeque::new(),
             retransmission_queue: VecDeque::new(),
@@ -104,6 +117,49 @@ impl SessionSubscriptions {
         self.subscriptions.keys().copied().collect()
     }
 
+    /// Get the number of subscriptions in this session.
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Return `true` if the session has no subscriptions.
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Get the total number of monitored items in all subscriptions in this session.
+    pub fn monitored_item_count(&self) -> usize {
+        self.subscriptions.values().map(|s| s.len()).sum()
+    }
+
+    /// Get the number of publish requests currently queued for this session.
+    pub fn publish_request_count(&self) -> usize {
+        self.publish_request_queue.len()
+    }
+
+    /// Get the publishing interval of each subscription in this session.
+    pub(super) fn publishing_intervals(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.subscriptions.values().map(|s| s.publishing_interval())
+    }
+
+    /// Get the diagnostics of each subscription in this session.
+    pub fn subscription_diagnostics(&self) -> Vec<SubscriptionDiagnosticsDataType> {
+        let mut diagnostics: Vec<_> = self
+            .subscriptions
+            .values()
+            .map(|sub| {
+                let unacknowledged = self
+                    .retransmission_queue
+                    .iter()
+                    .filter(|m| m.subscription_id == sub.id())
+                    .count();
+                sub.diagnostics(&self.session_id, unacknowledged as u32)
+            })
+            .collect();
+        diagnostics.sort_by_key(|d| d.subscription_id);
+        diagnostics
+    }
+
     pub(super) fn remove(
         &mut self,
         subscription_id: u32,
@@ -197,6 +253,7 @@ impl SessionSubscriptions {
         subscription.reset_lifetime_counter();
         subscription.reset_keep_alive_counter();
         subscription.set_max_notifications_per_publish(max_notifications_per_publish);
+        subscription.counters_mut().modify_count += 1;
 
         Ok(ModifySubscriptionResponse {
             response_header: ResponseHeader::new_good(&request.request_header),
@@ -236,13 +293,21 @@ impl SessionSubscriptions {
     }
 
     pub(super) fn republish(
-        &self,
+        &mut self,
         request: &RepublishRequest,
     ) -> Result<RepublishResponse, StatusCode> {
+        if let Some(sub) = self.subscriptions.get_mut(&request.subscription_id) {
+            let counters = sub.counters_mut();
+            counters.republish_request_count += 1;
+            counters.republish_message_request_count += 1;
+        }
         let msg = self.find_notification_message(
             request.subscription_id,
             request.retransmit_sequence_number,
         )?;
+        if let Some(sub) = self.subscriptions.get_mut(&request.subscription_id) {
+            sub.counters_mut().republish_message_count += 1;
+        }
         Ok(RepublishResponse {
             response_header: ResponseHeader::new_good(&request.request_header),
             notification_message: msg,
@@ -607,6 +672,7 @@ impl SessionSubscriptions {
             while !self.publish_request_queue.is_empty() {
                 if let Some(notification_message) = subscription.take_notification() {
                     tracing::trace!("Sending notification message {:?}", notification_message);
+                    subscription.record_publish(&notification_message);
                     let publish_request = self.publish_request_queue.pop_front().unwrap();
                     responses.push((publish_request, notification_message, sub_id));
                 } else {
@@ -647,7 +713,11 @@ impl SessionSubscriptions {
             let available_sequence_numbers = self.available_sequence_numbers(subscription_id);
 
             if self.retransmission_queue.len() >= self.max_publish_requests() * 2 {
-                self.retransmission_queue.pop_front();
+                if let Some(discarded) = self.retransmission_queue.pop_front() {
+                    if let Some(sub) = self.subscriptions.get_mut(&discarded.subscription_id) {
+                        sub.counters_mut().discarded_message_count += 1;
+                    }
+                }
             }
             self.retransmission_queue.push_back(NonAckedPublish {
                 message: notification.clone(),
@@ -767,18 +837,81 @@ impl SessionSubscriptions {
         }
     }
 
-    pub(super) fn notify_events(&mut self, events: Vec<(MonitoredItemHandle, &dyn Event)>) {
+    pub(super) fn notify_events(
+        &mut self,
+        events: Vec<(MonitoredItemHandle, &dyn Event)>,
+        permissions: &mut EventPermissions<'_>,
+    ) {
+        let roles = trace_read_lock!(self.session).roles().clone();
         // Only get the inner type tree if we need to, for performance.
         let mut lck = None;
         for (handle, event) in events {
             let Some(sub) = self.subscriptions.get_mut(&handle.subscription_id) else {
                 continue;
             };
+            if !permissions.can_receive(event, &roles) {
+                continue;
+            }
             let type_tree = lck.get_or_insert_with(|| self.type_tree_for_user.get_type_tree());
             sub.notify_event(&handle.monitored_item_id, event, type_tree.get());
         }
     }
 
+    #[cfg(feature = "generated-address-space")]
+    pub(super) fn condition_refresh(
+        &mut self,
+        subscription_id: u32,
+        monitored_item_id: Option<u32>,
+        conditions: &RetainedConditions,
+        permissions: &mut EventPermissions<'_>,
+    ) -> Result<(), StatusCode> {
+        let roles = trace_read_lock!(self.session).roles().clone();
+        let Some(sub) = self.subscriptions.get_mut(&subscription_id) else {
+            return Err(StatusCode::BadSubscriptionIdInvalid);
+        };
+        let is_event_item = |item: &MonitoredItem| {
+            item.item_to_monitor().attribute_id == AttributeId::EventNotifier
+        };
+        let items: Vec<_> = match monitored_item_id {
+            Some(id) => {
+                let item = sub
+                    .get(&id)
+                    .filter(|i| is_event_item(i))
+                    .ok_or(StatusCode::BadMonitoredItemIdInvalid)?;
+                vec![(item.id(), item.item_to_monitor().node_id.clone())]
+            }
+            None => sub
+                .items()
+                .filter(|i| is_event_item(i))
+                .map(|i| (i.id(), i.item_to_monitor().node_id.clone()))
+                .collect(),
+        };
+
+        let type_tree = self.type_tree_for_user.get_type_tree();
+        for (id, notifier) in items {
+            let start = RefreshStartEventType::new_event_now(
+                RefreshStartEventType::event_type_id(),
+                random::byte_string(16),
+                "Condition refresh started",
+                &NamespaceMap::new(),
+            );
+            sub.notify_refresh_event(&id, &start);
+            for event in conditions.events_for(&notifier) {
+                if permissions.can_receive(&*event, &roles) {
+                    sub.notify_event(&id, &*event, type_tree.get());
+                }
+            }
+            let end = RefreshEndEventType::new_event_now(
+                RefreshEndEventType::event_type_id(),
+                random::byte_string(16),
+                "Condition refresh ended",
+                &NamespaceMap::new(),
+            );
+            sub.notify_refresh_event(&id, &end);
+        }
+        Ok(())
+    }
+
     pub(super) fn user_token(&self) -> &PersistentSessionKey {
         &self.user_token
     }
@@ -5,7 +5,10 @@ use std::{
 
 use opcua_core::handle::Handle;
 use opcua_nodes::{Event, TypeTree};
-use opcua_types::{DataValue, DateTime, DateTimeUtc, NotificationMessage, StatusCode};
+use opcua_types::{
+    DataChangeNotification, DataValue, DateTime, DateTimeUtc, EventNotificationList,
+    MonitoringMode, NodeId, NotificationMessage, StatusCode, SubscriptionDiagnosticsDataType,
+};
 use tracing::{debug, trace, warn};
 
 use super::monitored_item::{MonitoredItem, Notification};
@@ -127,6 +130,9 @@ pub struct Subscription {
     max_queued_notifications: usize,
     /// Maximum number of notifications per publish.
     max_notifications_per_publish: usize,
+    /// Diagnostic counters. Only the counters are kept up to date here,
+    /// the rest is filled in from the current state when sampled.
+    diagnostics: SubscriptionDiagnosticsDataType,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -168,6 +174,7 @@ impl Subscription {
             notifications: VecDeque::new(),
             max_queued_notifications,
             max_notifications_per_publish: max_notifications_per_publish as usize,
+            diagnostics: SubscriptionDiagnosticsDataType::default(),
         }
     }
 
@@ -211,7 +218,14 @@ impl Subscription {
     }
 
     pub(super) fn remove(&mut self, id: &u32) -> Option<MonitoredItem> {
-        self.monitored_items.remove(id)
+        let item = self.monitored_items.remove(id)?;
+        // Keep the overflow counts of the item after it is gone.
+        if item.is_event_item() {
+            self.diagnostics.event_queue_overflow_count += item.queue_overflow_count();
+        } else {
+            self.diagnostics.monitoring_queue_overflow_count += item.queue_overflow_count();
+        }
+        Some(item)
     }
 
     pub(super) fn insert(&mut self, id: u32, item: MonitoredItem) {
@@ -237,6 +251,17 @@ impl Subscription {
         }
     }
 
+    /// Notify the given monitored item of a `RefreshStartEvent` or `RefreshEndEvent`,
+    /// which are sent regardless of the where clause of its event filter.
+    #[cfg(feature = "generated-address-space")]
+    pub(super) fn notify_refresh_event(&mut self, id: &u32, event: &dyn Event) {
+        if let Some(item) = self.monitored_items.get_mut(id) {
+            if item.notify_refresh_event(event) {
+                self.notified_monitored_items.insert(*id);
+            }
+        }
+    }
+
     /// Tests if the publishing interval has elapsed since the last time this function in which case
     /// it returns `true` and updates its internal state.
     fn test_and_set_publishing_interval_elapsed(&mut self, now: Instant) -> bool {
@@ -394,6 +419,7 @@ impl Subscription {
             }
             HandledState::IntervalElapsed8 => {
                 self.start_publishing_timer();
+                self.diagnostics.late_publish_request_count += 1;
                 self.state = SubscriptionState::Late;
                 UpdateStateAction::None
             }
@@ -443,6 +469,7 @@ impl Subscription {
             }
             HandledState::KeepAlive17 => {
                 self.start_publishing_timer();
+                self.diagnostics.late_publish_request_count += 1;
                 self.state = SubscriptionState::Late;
                 UpdateStateAction::None
             }
@@ -537,6 +564,7 @@ impl Subscription {
         if self.notifications.len() >= self.max_queued_notifications {
             warn!("Maximum number of queued notifications exceeded, dropping oldest. Subscription ID: {}", self.id);
             self.notifications.pop_front();
+            self.diagnostics.discarded_message_count += 1;
         }
 
         // debug!("Enqueuing notification {:?}", notification);
@@ -756,6 +784,70 @@ impl Subscription {
 
     pub(super) fn set_publishing_enabled(&mut self, publishing_enabled: bool) {
         self.publishing_enabled = publishing_enabled;
+        if publishing_enabled {
+            self.diagnostics.enable_count += 1;
+        } else {
+            self.diagnostics.disable_count += 1;
+        }
+    }
+
+    /// Get the diagnostic counters of this subscription for modification.
+    pub(super) fn counters_mut(&mut self) -> &mut SubscriptionDiagnosticsDataType {
+        &mut self.diagnostics
+    }
+
+    /// Record that a notification message was sent in response to a publish request.
+    pub(super) fn record_publish(&mut self, message: &NotificationMessage) {
+        let diagnostics = &mut self.diagnostics;
+        diagnostics.publish_request_count += 1;
+        for data in message.notification_data.iter().flatten() {
+            if let Some(n) = data.inner_as::<DataChangeNotification>() {
+                let count = n.monitored_items.as_ref().map(|m| m.len()).unwrap_or(0) as u32;
+                diagnostics.data_change_notifications_count += count;
+                diagnostics.notifications_count += count;
+            } else if let Some(n) = data.inner_as::<EventNotificationList>() {
+                let count = n.events.as_ref().map(|m| m.len()).unwrap_or(0) as u32;
+                diagnostics.event_notifications_count += count;
+                diagnostics.notifications_count += count;
+            }
+        }
+    }
+
+    /// Get the current diagnostics of this subscription.
+    pub(super) fn diagnostics(
+        &self,
+        session_id: &NodeId,
+        unacknowledged_message_count: u32,
+    ) -> SubscriptionDiagnosticsDataType {
+        let mut diagnostics = SubscriptionDiagnosticsDataType {
+            session_id: session_id.clone(),
+            subscription_id: self.id,
+            priority: self.priority,
+            publishing_interval: self.publishing_interval.as_secs_f64() * 1000.0,
+            max_keep_alive_count: self.max_keep_alive_counter,
+            max_lifetime_count: self.max_lifetime_counter,
+            max_notifications_per_publish: self.max_notifications_per_publish as u32,
+            publishing_enabled: self.publishing_enabled,
+            current_keep_alive_count: self.keep_alive_counter,
+            current_lifetime_count: self.lifetime_counter,
+            unacknowledged_message_count,
+            monitored_item_count: self.monitored_items.len() as u32,
+            disabled_monitored_item_count: self
+                .monitored_items
+                .values()
+                .filter(|m| m.monitoring_mode() == MonitoringMode::Disabled)
+                .count() as u32,
+            next_sequence_number: self.sequence_number.peek_next(),
+            ..self.diagnostics.clone()
+        };
+        for item in self.monitored_items.values() {
+            if item.is_event_item() {
+                diagnostics.event_queue_overflow_count += item.queue_overflow_count();
+            } else {
+                diagnostics.monitoring_queue_overflow_count += item.queue_overflow_count();
+            }
+        }
+        diagnostics
     }
 
     /// The publishing interval of this subscription.
@@ -1,3 +1,4 @@
 mod connect;
 pub(crate) mod tcp;
+pub(crate) mod uds;
 pub(crate) use connect::Connector;
@@ -7,34 +7,36 @@ use opcua_core::{
     comms::{
         buffer::SendBuffer,
         chunker::Chunker,
+        interceptor::MessageDirection,
         message_chunk::{MessageChunk, MessageIsFinalType},
         message_chunk_info::ChunkInfo,
+        metrics::TransportMetricsHandle,
+        priority::PriorityPolicy,
+        rate_limit::{RateLimit, RateLimiter},
         secure_channel::SecureChannel,
         sequence_number::SequenceNumberHandle,
+        stream::TransportStream,
         tcp_codec::{Message, TcpCodec},
-        tcp_types::{AcknowledgeMessage, ErrorMessage},
+        tcp_types::{AcknowledgeMessage, ConnectionLimits, ErrorMessage, MessageLimits},
     },
     RequestMessage, ResponseMessage,
 };
-use tracing::error;
+use tracing::{debug, error};
 use tracing_futures::Instrument;
 
 use crate::info::ServerInfo;
-use opcua_types::{DecodingOptions, Error, ResponseHeader, ServiceFault, StatusCode};
+use opcua_types::{DecodingOptions, Error, ErrorContext, ResponseHeader, ServiceFault, StatusCode};
 
 use futures::StreamExt;
-use tokio::{
-    io::{AsyncWriteExt, ReadHalf, WriteHalf},
-    net::TcpStream,
-};
+use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio_util::{codec::FramedRead, sync::CancellationToken};
 
 use super::connect::Connector;
 
 /// Transport implementation for opc.tcp.
 pub(crate) struct TcpTransport {
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+    write: WriteHalf<TransportStream>,
     send_buffer: SendBuffer,
     state: TransportState,
     pending_chunks: Vec<MessageChunk>,
@@ -42,6 +44,8 @@ pub(crate) struct TcpTransport {
     pub(crate) client_protocol_version: u32,
     /// Last decoded sequence number
     sequence_numbers: SequenceNumberHandle,
+    /// Message limits negotiated during HELLO
+    limits: ConnectionLimits,
 }
 
 enum TransportState {
@@ -53,9 +57,16 @@ enum TransportState {
 pub(crate) struct TransportConfig {
     pub send_buffer_size: usize,
     pub receive_buffer_size: usize,
-    pub max_message_size: usize,
-    pub max_chunk_count: usize,
+    pub max_send_message_size: usize,
+    pub max_send_chunk_count: usize,
     pub hello_timeout: Duration,
+    pub parallel_crypto_threshold: usize,
+    pub stream_encoding_threshold: usize,
+    pub priority_policy: PriorityPolicy,
+    pub max_send_queue_bytes: usize,
+    pub max_send_queue_messages: usize,
+    pub send_rate_limit: Option<RateLimit>,
+    pub metrics: TransportMetricsHandle,
 }
 
 #[derive(Debug)]
@@ -76,19 +87,9 @@ pub(crate) enum TransportPollResult {
     Closed,
 }
 
-fn min_zero_infinite(server: u32, client: u32) -> u32 {
-    if client == 0 {
-        server
-    } else if server == 0 {
-        client
-    } else {
-        client.min(server)
-    }
-}
-
 pub(crate) struct TcpConnector {
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+    write: WriteHalf<TransportStream>,
     deadline: Instant,
     config: TransportConfig,
     decoding_options: DecodingOptions,
@@ -96,12 +97,15 @@ pub(crate) struct TcpConnector {
 
 impl TcpConnector {
     pub(crate) fn new(
-        stream: TcpStream,
+        stream: impl Into<TransportStream>,
         config: TransportConfig,
         decoding_options: DecodingOptions,
     ) -> Self {
-        let (read, write) = tokio::io::split(stream);
-        let read = FramedRead::new(read, TcpCodec::new(decoding_options.clone()));
+        let (read, write) = tokio::io::split(stream.into());
+        let read = FramedRead::new(
+            read,
+            TcpCodec::new(decoding_options.clone()).with_metrics(config.metrics.clone()),
+        );
         TcpConnector {
             read,
             write,
@@ -111,8 +115,11 @@ impl TcpConnector {
         }
     }
 
-    async fn connect_inner(&mut self, info: Arc<ServerInfo>) -> Result<SendBuffer, ErrorMessage> {
-        let hello = match self.read.next().await {
+    async fn connect_inner(
+        &mut self,
+        info: Arc<ServerInfo>,
+    ) -> Result<(SendBuffer, ConnectionLimits), ErrorMessage> {
+        let mut hello = match self.read.next().await {
             Some(Ok(Message::Hello(hello))) => Ok(hello),
             Some(Ok(bad_msg)) => Err(ErrorMessage::new(
                 StatusCode::BadCommunicationError,
@@ -132,11 +139,17 @@ impl TcpConnector {
 
         let mut buffer = SendBuffer::new(
             self.config.send_buffer_size,
-            self.config.max_message_size,
-            self.config.max_chunk_count,
+            self.config.max_send_message_size,
+            self.config.max_send_chunk_count,
             true,
         );
 
+        // Clients connecting over opc.uds address the server by socket path,
+        // map that to the configured endpoint URL before validating it.
+        let endpoint_url = info
+            .resolve_endpoint_url(hello.endpoint_url.as_ref())
+            .into_owned();
+        hello.endpoint_url = endpoint_url.into();
         let endpoints = info.endpoints(&hello.endpoint_url, &None);
 
         if !endpoints.is_some_and(|e| hello.is_endpoint_url_valid(&e)) {
@@ -163,25 +176,42 @@ impl TcpConnector {
 
         let decoding_options = &self.decoding_options;
 
-        // Send acknowledge
+        // Send acknowledge. The max message size and chunk count in the ACK are the
+        // limits on messages sent by the client, while the limits in the HELLO apply
+        // to messages sent by the server.
         let acknowledge = AcknowledgeMessage::new(
             server_protocol_version,
             (self.config.receive_buffer_size as u32).min(hello.send_buffer_size),
             (buffer.send_buffer_size as u32).min(hello.receive_buffer_size),
-            min_zero_infinite(
-                decoding_options.max_message_size as u32,
-                hello.max_message_size,
-            ),
-            min_zero_infinite(
-                decoding_options.max_chunk_count as u32,
-                hello.max_chunk_count,
-            ),
+            decoding_options.max_message_size as u32,
+            decoding_options.max_chunk_count as u32,
         );
         buffer.revise(
             acknowledge.send_buffer_size as usize,
-            acknowledge.max_message_size as usize,
-            acknowledge.max_chunk_count as usize,
+            hello.max_message_size as usize,
+            hello.max_chunk_count as usize,
         );
+        buffer.parallel_crypto_threshold = self.config.parallel_crypto_threshold;
+        buffer.stream_encoding_threshold = self.config.stream_encoding_threshold;
+        buffer.priority_policy = self.config.priority_policy.clone();
+        buffer.max_queued_bytes = self.config.max_send_queue_bytes;
+        buffer.max_queued_messages = self.config.max_send_queue_messages;
+        buffer.rate_limiter = self.config.send_rate_limit.map(RateLimiter::new);
+        buffer.metrics = self.config.metrics.clone();
+
+        let limits = ConnectionLimits {
+            send: MessageLimits {
+                max_message_size: buffer.max_message_size,
+                max_chunk_size: buffer.send_buffer_size,
+                max_chunk_count: buffer.max_chunk_count,
+            },
+            receive: MessageLimits {
+                max_message_size: decoding_options.max_message_size,
+                max_chunk_size: acknowledge.receive_buffer_size as usize,
+                max_chunk_count: decoding_options.max_chunk_count,
+            },
+        };
+        debug!("Negotiated connection limits: {limits:?}");
 
         let mut buf =
             Vec::with_capacity(opcua_types::SimpleBinaryEncodable::byte_len(&acknowledge));
@@ -195,7 +225,7 @@ impl TcpConnector {
             )
         })?;
 
-        Ok(buffer)
+        Ok((buffer, limits))
     }
 }
 
@@ -214,7 +244,9 @@ impl Connector for TcpConnector {
             }
             r = self.connect_inner(info).instrument(tracing::info_span!("OPC-UA TCP handshake")) => {
                 match r {
-                    Ok(r) => return Ok(TcpTransport::new(self.read, self.write, r)),
+                    Ok((buffer, limits)) => {
+                        return Ok(TcpTransport::new(self.read, self.write, buffer, limits))
+                    }
                     Err(e) => e,
                 }
             }
@@ -233,13 +265,15 @@ impl Connector for TcpConnector {
 
 impl TcpTransport {
     fn new(
-        read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-        write: WriteHalf<TcpStream>,
+        read: FramedRead<ReadHalf<TransportStream>, TcpCodec>,
+        write: WriteHalf<TransportStream>,
         send_buffer: SendBuffer,
+        limits: ConnectionLimits,
     ) -> Self {
         Self {
             read,
             write,
+            limits,
             state: TransportState::Running,
             pending_chunks: Vec::new(),
             sequence_numbers: SequenceNumberHandle::new(true),
@@ -250,6 +284,10 @@ impl TcpTransport {
 
     /// Set the transport state to closing, once the final message is sent
     /// the connection will be closed.
+    pub(crate) fn connection_limits(&self) -> ConnectionLimits {
+        self.limits
+    }
+
     pub(crate) fn set_closing(&mut self) {
         self.state = TransportState::Closing;
     }
@@ -258,6 +296,12 @@ impl TcpTransport {
         matches!(self.state, TransportState::Closing)
     }
 
+    /// Return `true` if the send queue is full, and no more responses should be
+    /// enqueued until it drains.
+    pub(crate) fn is_send_queue_full(&self) -> bool {
+        self.send_buffer.is_full()
+    }
+
     pub(crate) fn enqueue_error(&mut self, message: ErrorMessage) {
         self.send_buffer.write_error(message);
     }
@@ -300,6 +344,7 @@ impl TcpTransport {
         // If there's nothing in the send buffer, but there are chunks available,
         // write them to the send buffer before proceeding.
         if self.send_buffer.should_encode_chunks() {
+            self.send_buffer.wait_for_stream().await;
             if let Err(e) = self.send_buffer.encode_next_chunk(channel) {
                 return TransportPollResult::Error(e);
             }
@@ -307,8 +352,15 @@ impl TcpTransport {
 
         // If there is something in the send buffer, write to the stream.
         // If not, wait for outgoing messages.
-        // Either way, listen to incoming messages while we do this.
-        if self.send_buffer.can_read() {
+        // Either way, listen to incoming messages while we do this, unless the send
+        // queue is full, in which case the client has to wait for us to catch up.
+        if self.send_buffer.can_read() && self.send_buffer.is_full() {
+            if let Err(e) = self.send_buffer.read_into_async(&mut self.write).await {
+                error!("write bytes task failed: {}", e);
+                return TransportPollResult::Closed;
+            }
+            TransportPollResult::OutgoingMessageSent
+        } else if self.send_buffer.can_read() {
             tokio::select! {
                 r = self.send_buffer.read_into_async(&mut self.write) => {
                     if let Err(e) = r {
@@ -371,10 +423,26 @@ impl TcpTransport {
                 let header = chunk.message_header(&channel.decoding_options())?;
 
                 if header.is_final == MessageIsFinalType::FinalError {
+                    // The client aborted the message, discard any chunks received so far
+                    // and continue after the sequence number of the abort chunk.
                     self.pending_chunks.clear();
+                    let chunk = channel.remove_security(chunk)?;
+                    let chunk_info = chunk.chunk_info(channel)?;
+                    Chunker::skip_sequence_number(
+                        &mut self.sequence_numbers,
+                        channel,
+                        chunk_info.sequence_header.sequence_number,
+                    )?;
                     Ok(None)
                 } else {
-                    let chunk = channel.verify_and_remove_security(&chunk.data)?;
+                    // If parallel crypto is enabled, chunks are verified all at once
+                    // when the final chunk arrives.
+                    let parallel_threshold = self.send_buffer.parallel_crypto_threshold;
+                    let chunk = if parallel_threshold > 0 {
+                        chunk
+                    } else {
+                        channel.remove_security(chunk)?
+                    };
 
                     if self.send_buffer.max_chunk_count > 0
                         && self.pending_chunks.len() == self.send_buffer.max_chunk_count
@@ -390,18 +458,43 @@ impl TcpTransport {
                         return Ok(None);
                     }
 
+                    if parallel_threshold > 0 {
+                        self.pending_chunks = channel.remove_security_batch(
+                            std::mem::take(&mut self.pending_chunks),
+                            parallel_threshold,
+                        )?;
+                    }
+
                     let chunk_info = self.pending_chunks[0].chunk_info(channel)?;
 
-                    self.sequence_numbers.set(Chunker::validate_chunks(
-                        self.sequence_numbers.clone(),
+                    self.sequence_numbers.set(
+                        Chunker::validate_chunks(
+                            self.sequence_numbers.clone(),
+                            channel,
+                            &self.pending_chunks,
+                        )
+                        .context("validating request chunks")?,
+                    );
+
+                    let request_id = chunk_info.sequence_header.request_id;
+                    let size = self.pending_chunks.iter().map(|c| c.data.len()).sum();
+                    let request: RequestMessage = Chunker::decode_owned(
+                        std::mem::take(&mut self.pending_chunks),
                         channel,
-                        &self.pending_chunks,
-                    )?);
-
-                    let request = Chunker::decode(&self.pending_chunks, channel, None)
-                        .map_err(|e| e.with_request_id(chunk_info.sequence_header.request_id))?;
+                        None,
+                    )
+                    .map_err(|e| e.with_request_id(request_id))
+                    .context("decoding request")?;
+                    channel
+                        .intercept(MessageDirection::Incoming, &request, request_id, size)
+                        .map_err(|e| {
+                            Error::new(e, "Request rejected by interceptor").with_context(
+                                Some(request_id),
+                                Some(request.request_header().request_handle),
+                            )
+                        })?;
                     Ok(Some(Request {
-                        request_id: chunk_info.sequence_header.request_id,
+                        request_id,
                         chunk_info,
                         message: request,
                     }))
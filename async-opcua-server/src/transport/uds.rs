@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+use opcua_core::comms::stream::TransportStream;
+
+/// Listener for `opc.uds` connections, a Unix domain socket on unix platforms,
+/// or a named pipe on Windows.
+pub(crate) struct UdsListener {
+    path: PathBuf,
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    #[cfg(windows)]
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl UdsListener {
+    /// Bind to the given socket path. On unix, a stale socket at the path
+    /// is removed first, any other kind of file is left alone.
+    #[cfg(unix)]
+    pub(crate) fn bind(path: &Path) -> std::io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            listener: tokio::net::UnixListener::bind(path)?,
+        })
+    }
+
+    /// Create the first instance of the named pipe.
+    #[cfg(windows)]
+    pub(crate) fn bind(path: &Path) -> std::io::Result<Self> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        Ok(Self {
+            path: path.to_owned(),
+            next: ServerOptions::new()
+                .first_pipe_instance(true)
+                .create(path)?,
+        })
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub(crate) fn bind(_path: &Path) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "opc.uds transport is not supported on this platform",
+        ))
+    }
+
+    /// Get the path this listener is bound to.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait for the next incoming connection. This is cancel safe.
+    #[cfg(unix)]
+    pub(crate) async fn accept(&mut self) -> std::io::Result<TransportStream> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(stream.into())
+    }
+
+    /// Wait for a client to connect to the current pipe instance, then create
+    /// a new instance for the next client. This is cancel safe.
+    #[cfg(windows)]
+    pub(crate) async fn accept(&mut self) -> std::io::Result<TransportStream> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        self.next.connect().await?;
+        let next = ServerOptions::new().create(&self.path)?;
+        let connected = std::mem::replace(&mut self.next, next);
+        Ok(connected.into())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub(crate) async fn accept(&mut self) -> std::io::Result<TransportStream> {
+        futures::future::pending().await
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UdsListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
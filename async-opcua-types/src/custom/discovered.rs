@@ -0,0 +1,116 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{Context, NodeId, TypeLoader};
+
+use super::{DataTypeTree, DynamicTypeLoader};
+
+type UnknownTypeCallback = dyn Fn(&NodeId) + Send + Sync;
+
+/// Type loader for types discovered at runtime, for example by reading the data type
+/// definitions of a server. The type tree can be replaced while the loader is in use,
+/// and until one is set, the loader does not load any types.
+///
+/// Encoding IDs the loader does not know are passed to the callback set with
+/// [`DiscoveredTypeLoader::on_unknown_type`], which can be used to discover types on demand.
+/// The value is then left to the next type loader, typically the
+/// [`FallbackTypeLoader`](crate::FallbackTypeLoader).
+pub struct DiscoveredTypeLoader {
+    inner: RwLock<Option<Arc<DynamicTypeLoader>>>,
+    on_unknown: Option<Box<UnknownTypeCallback>>,
+}
+
+impl Default for DiscoveredTypeLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiscoveredTypeLoader {
+    /// Create a new type loader without any types.
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(None),
+            on_unknown: None,
+        }
+    }
+
+    /// Set a callback called with each encoding ID the loader does not know.
+    /// This is called while decoding, so it must not block.
+    pub fn on_unknown_type(mut self, callback: impl Fn(&NodeId) + Send + Sync + 'static) -> Self {
+        self.on_unknown = Some(Box::new(callback));
+        self
+    }
+
+    /// Load types from the given type tree, replacing the current one.
+    pub fn set_type_tree(&self, type_tree: Arc<DataTypeTree>) {
+        let loader = Arc::new(DynamicTypeLoader::new(type_tree));
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = Some(loader);
+    }
+
+    /// Remove the current type tree, so that no types are loaded.
+    pub fn clear(&self) {
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Get the current type tree, if one is set.
+    pub fn type_tree(&self) -> Option<Arc<DataTypeTree>> {
+        self.loader().map(|l| l.type_tree.clone())
+    }
+
+    fn loader(&self) -> Option<Arc<DynamicTypeLoader>> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn load<T>(
+        &self,
+        node_id: &NodeId,
+        load: impl FnOnce(&DynamicTypeLoader) -> Option<T>,
+    ) -> Option<T> {
+        let res = self.loader().and_then(|l| load(&l));
+        if res.is_none() {
+            if let Some(on_unknown) = &self.on_unknown {
+                on_unknown(node_id);
+            }
+        }
+        res
+    }
+}
+
+impl TypeLoader for DiscoveredTypeLoader {
+    fn load_from_binary(
+        &self,
+        node_id: &NodeId,
+        stream: &mut dyn std::io::Read,
+        ctx: &Context<'_>,
+        length: Option<usize>,
+    ) -> Option<crate::EncodingResult<Box<dyn crate::DynEncodable>>> {
+        self.load(node_id, |l| {
+            l.load_from_binary(node_id, stream, ctx, length)
+        })
+    }
+
+    fn priority(&self) -> crate::TypeLoaderPriority {
+        crate::TypeLoaderPriority::Dynamic(50)
+    }
+
+    #[cfg(feature = "xml")]
+    fn load_from_xml(
+        &self,
+        node_id: &crate::NodeId,
+        stream: &mut crate::xml::XmlStreamReader<&mut dyn std::io::Read>,
+        ctx: &Context<'_>,
+        name: &str,
+    ) -> Option<crate::EncodingResult<Box<dyn crate::DynEncodable>>> {
+        self.load(node_id, |l| l.load_from_xml(node_id, stream, ctx, name))
+    }
+
+    #[cfg(feature = "json")]
+    fn load_from_json(
+        &self,
+        node_id: &crate::NodeId,
+        stream: &mut crate::json::JsonStreamReader<&mut dyn std::io::Read>,
+        ctx: &Context<'_>,
+    ) -> Option<crate::EncodingResult<Box<dyn crate::DynEncodable>>> {
+        self.load(node_id, |l| l.load_from_json(node_id, stream, ctx))
+    }
+}
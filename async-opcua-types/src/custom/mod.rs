@@ -2,6 +2,7 @@
 //! using [`crate::DataTypeDefinition`] to encode and decode values.
 
 mod custom_struct;
+mod discovered;
 #[cfg(feature = "json")]
 mod json;
 mod type_tree;
@@ -9,6 +10,7 @@ mod type_tree;
 mod xml;
 
 pub use custom_struct::{DynamicStructure, DynamicTypeLoader};
+pub use discovered::DiscoveredTypeLoader;
 pub use type_tree::{
     DataTypeTree, DataTypeVariant, EncodingIds, EnumTypeInfo, ParentIds, ParsedStructureField,
     StructTypeInfo, TypeInfo, TypeInfoRef,
@@ -1,6 +1,60 @@
 //! Implementation of data change filters, and `Deadband`
 
-use crate::{DataChangeFilter, DataChangeTrigger, DataValue, DeadbandType, StatusCode, Variant};
+use crate::{
+    DataChangeFilter, DataChangeTrigger, DataValue, DeadbandType, Range, StatusCode, Variant,
+};
+
+impl DataChangeFilter {
+    /// Create a data change filter without a deadband.
+    pub fn no_deadband(trigger: DataChangeTrigger) -> Self {
+        Self {
+            trigger,
+            deadband_type: DeadbandType::None as u32,
+            deadband_value: 0.0,
+        }
+    }
+
+    /// Create a data change filter that only reports changes larger than `deadband`.
+    pub fn absolute_deadband(trigger: DataChangeTrigger, deadband: f64) -> Self {
+        Self {
+            trigger,
+            deadband_type: DeadbandType::Absolute as u32,
+            deadband_value: deadband,
+        }
+    }
+
+    /// Create a data change filter that only reports changes larger than `percent`
+    /// percent of the EURange of the node, between 0 and 100.
+    ///
+    /// Servers may not support percent deadband, see
+    /// [`DataChangeFilter::absolute_deadband_from_percent`] for computing an equivalent
+    /// absolute deadband on the client.
+    pub fn percent_deadband(trigger: DataChangeTrigger, percent: f64) -> Self {
+        Self {
+            trigger,
+            deadband_type: DeadbandType::Percent as u32,
+            deadband_value: percent,
+        }
+    }
+
+    /// Create a data change filter with an absolute deadband equal to `percent`
+    /// percent of `eu_range`.
+    pub fn absolute_deadband_from_percent(
+        trigger: DataChangeTrigger,
+        percent: f64,
+        eu_range: &Range,
+    ) -> Self {
+        Self::absolute_deadband(
+            trigger,
+            (eu_range.high - eu_range.low).abs() * percent / 100.0,
+        )
+    }
+
+    /// Get the deadband of this filter, in percent, if it is a percent deadband filter.
+    pub fn percent(&self) -> Option<f64> {
+        (self.deadband_type == DeadbandType::Percent as u32).then_some(self.deadband_value)
+    }
+}
 
 #[derive(Debug, Clone)]
 /// Parsed percent deadband. Percent deadband works by applying a deadband
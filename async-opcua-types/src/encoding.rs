@@ -17,7 +17,7 @@ use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use chrono::Duration;
 use tracing::error;
 
-use crate::{constants, status_code::StatusCode, Context, QualifiedName};
+use crate::{constants, status_code::StatusCode, Context, NodeId, QualifiedName};
 
 #[derive(Debug, Clone, Default)]
 /// Parsed data encoding.
@@ -63,18 +63,33 @@ pub type EncodingResult<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 /// General OPC-UA error.
 ///
-/// Contains context about the request this error occured as part of, if that is possible to retrieve,
+/// Contains context about the request this error occurred as part of, if that is possible to retrieve,
 /// as well as details about the error that caused this, and a status code.
+///
+/// As the error is propagated, callers can add the operations that failed with
+/// [`Error::with_operation`], or [`ErrorContext::context`] on results, and the node
+/// the error concerns with [`Error::with_node_id`]. These are included when the
+/// error is displayed, outermost operation first.
 pub struct Error {
     status: StatusCode,
     request_id: Option<u32>,
     request_handle: Option<u32>,
     context: Box<dyn StdError + Send + Sync>,
+    operations: Vec<String>,
+    node_id: Option<Box<NodeId>>,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {}", self.status(), self.context)
+        write!(f, "{}: ", self.status())?;
+        for operation in self.operations.iter().rev() {
+            write!(f, "{operation}: ")?;
+        }
+        write!(f, "{}", self.context)?;
+        if let Some(node_id) = &self.node_id {
+            write!(f, " (node {node_id})")?;
+        }
+        Ok(())
     }
 }
 
@@ -93,29 +108,21 @@ impl Error {
             request_handle: None,
             request_id: None,
             context: context.into(),
+            operations: Vec::new(),
+            node_id: None,
         }
     }
 
     /// Create a new error with status code `BadDecodingError` and
     /// `context` as a dynamic error source.
     pub fn decoding(context: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
-        Self {
-            status: StatusCode::BadDecodingError,
-            request_handle: None,
-            request_id: None,
-            context: context.into(),
-        }
+        Self::new(StatusCode::BadDecodingError, context)
     }
 
     /// Create a new error with status code `BadEncodingError` and
     /// `context` as a dynamic error source.
     pub fn encoding(context: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
-        Self {
-            status: StatusCode::BadEncodingError,
-            request_handle: None,
-            request_id: None,
-            context: context.into(),
-        }
+        Self::new(StatusCode::BadEncodingError, context)
     }
 
     /// Add request ID and request handle to this error.
@@ -158,6 +165,93 @@ impl Error {
             None
         }
     }
+
+    /// Get the request ID of the request this error occurred as part of, if known.
+    pub fn request_id(&self) -> Option<u32> {
+        self.request_id
+    }
+
+    /// Get the handle of the request this error occurred as part of, if known.
+    pub fn request_handle(&self) -> Option<u32> {
+        self.request_handle
+    }
+
+    /// Add an operation that failed because of this error, such as
+    /// `"reading namespace array"`. Operations are added from the innermost out
+    /// as the error is propagated.
+    pub fn with_operation(mut self, operation: impl Into<String>) -> Self {
+        self.operations.push(operation.into());
+        self
+    }
+
+    /// Get the operations that failed because of this error, innermost first.
+    pub fn operations(&self) -> &[String] {
+        &self.operations
+    }
+
+    /// Set the node this error concerns. This does not replace a node set earlier,
+    /// since the innermost node is the most specific.
+    pub fn with_node_id(mut self, node_id: impl Into<NodeId>) -> Self {
+        if self.node_id.is_none() {
+            self.node_id = Some(Box::new(node_id.into()));
+        }
+        self
+    }
+
+    /// Get the node this error concerns, if known.
+    pub fn node_id(&self) -> Option<&NodeId> {
+        self.node_id.as_deref()
+    }
+}
+
+impl From<StatusCode> for Error {
+    fn from(value: StatusCode) -> Self {
+        Self::new(value, value.sub_code().description())
+    }
+}
+
+/// Extension trait for adding context to the error of a result, converting
+/// it into an [`Error`].
+///
+/// ```
+/// # use opcua_types::{ErrorContext, NodeId, StatusCode};
+/// let res: Result<(), StatusCode> = Err(StatusCode::BadNodeIdUnknown);
+/// let err = res
+///     .node_context(NodeId::new(1, 5))
+///     .context("reading value")
+///     .unwrap_err();
+/// assert_eq!(err.status(), StatusCode::BadNodeIdUnknown);
+/// assert_eq!(err.node_id(), Some(&NodeId::new(1, 5)));
+/// ```
+pub trait ErrorContext<T> {
+    /// Add an operation that failed, see [`Error::with_operation`].
+    fn context(self, operation: impl Into<String>) -> std::result::Result<T, Error>;
+
+    /// Add an operation that failed, only evaluating `operation` if there is an error.
+    fn context_with<R: Into<String>>(
+        self,
+        operation: impl FnOnce() -> R,
+    ) -> std::result::Result<T, Error>;
+
+    /// Set the node the error concerns, see [`Error::with_node_id`].
+    fn node_context(self, node_id: impl Into<NodeId>) -> std::result::Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ErrorContext<T> for std::result::Result<T, E> {
+    fn context(self, operation: impl Into<String>) -> std::result::Result<T, Error> {
+        self.map_err(|e| e.into().with_operation(operation))
+    }
+
+    fn context_with<R: Into<String>>(
+        self,
+        operation: impl FnOnce() -> R,
+    ) -> std::result::Result<T, Error> {
+        self.map_err(|e| e.into().with_operation(operation()))
+    }
+
+    fn node_context(self, node_id: impl Into<NodeId>) -> std::result::Result<T, Error> {
+        self.map_err(|e| e.into().with_node_id(node_id))
+    }
 }
 
 impl From<Error> for StatusCode {
@@ -1,8 +1,12 @@
-//! Core logic for reading Variant values from an event.
+//! Core logic for reading Variant values from an event, and for populating an event
+//! from Variant values read by a client.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
-use crate::{Array, AttributeId, IntoVariant, NumericRange, QualifiedName, Variant, VariantType};
+use crate::{
+    Array, AttributeId, Error, IntoVariant, NamespaceMap, NumericRange, ObjectTypeId,
+    QualifiedName, SimpleAttributeOperand, StatusCode, TryFromVariant, Variant, VariantType,
+};
 
 /// Trait implemented by any type that can be a field in an event.
 pub trait EventField {
@@ -164,6 +168,302 @@ impl EventField for NumericRange {
     }
 }
 
+/// Trait implemented by any type that can be set from a field of an event.
+/// This is the inverse of [`EventField`], used to read events on the client.
+pub trait SetEventField {
+    /// Set the value at the given path from a variant.
+    ///
+    /// Returns `false` if the path does not refer to this field or one of its children,
+    /// or if the value could not be converted to the type of the field.
+    ///
+    /// # Arguments
+    ///
+    ///  * `attribute_id` - the attribute to set. Should be either `NodeId` or `Value`.
+    ///  * `remaining_path` - the remaining path to the actual value to set.
+    ///  * `value` - the value to set.
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool;
+}
+
+impl<T> SetEventField for T
+where
+    T: IntoVariant + TryFromVariant,
+{
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if !remaining_path.is_empty() || attribute_id != AttributeId::Value {
+            return false;
+        }
+        match T::try_from_variant(value) {
+            Ok(v) => {
+                *self = v;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl<T> SetEventField for Option<T>
+where
+    T: SetEventField + Default,
+{
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if remaining_path.is_empty() && value.is_empty() {
+            *self = None;
+            return true;
+        }
+        let mut inner = self.take().unwrap_or_default();
+        let res = inner.set_value(attribute_id, remaining_path, value);
+        *self = Some(inner);
+        res
+    }
+}
+
+impl<T> SetEventField for Vec<T>
+where
+    T: SetEventField + Default,
+{
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if !remaining_path.is_empty() {
+            return false;
+        }
+        let values = match value {
+            Variant::Empty => Vec::new(),
+            Variant::Array(a) => a.values,
+            r => vec![r],
+        };
+        let mut res = Vec::with_capacity(values.len());
+        for value in values {
+            let mut item = T::default();
+            if !item.set_value(attribute_id, &[], value) {
+                return false;
+            }
+            res.push(item);
+        }
+        *self = res;
+        true
+    }
+}
+
+impl SetEventField for Variant {
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if !remaining_path.is_empty() || attribute_id != AttributeId::Value {
+            return false;
+        }
+        *self = value;
+        true
+    }
+}
+
+impl SetEventField for NumericRange {
+    fn set_value(
+        &mut self,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        if !remaining_path.is_empty() || attribute_id != AttributeId::Value {
+            return false;
+        }
+        let Variant::String(s) = value else {
+            return false;
+        };
+        match NumericRange::from_str(s.as_ref()) {
+            Ok(r) => {
+                *self = r;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Trait implemented by any type that can list the event fields it is set from.
+/// This is used to build the select clauses of an event filter on the client,
+/// matching the fields set by [`SetEventField`].
+pub trait SelectEventField {
+    /// Append a select clause for each value of this field to `clauses`.
+    ///
+    /// # Arguments
+    ///
+    ///  * `browse_path` - the browse path to this field.
+    ///  * `namespace` - index of the namespace of the type declaring this field, which
+    ///    is used for the browse names of its children.
+    ///  * `namespaces` - namespaces on the server, used to find the namespace index of
+    ///    event types outside namespace 0.
+    ///  * `clauses` - the list of select clauses to append to.
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        namespace: u16,
+        namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    );
+}
+
+/// Create a select clause for the field at `browse_path`. The type definition is
+/// `BaseEventType`, so that the server evaluates the path on any event type.
+pub fn select_clause(
+    browse_path: &[QualifiedName],
+    attribute_id: AttributeId,
+) -> SimpleAttributeOperand {
+    SimpleAttributeOperand {
+        type_definition_id: ObjectTypeId::BaseEventType.into(),
+        browse_path: Some(browse_path.to_vec()),
+        attribute_id: attribute_id as u32,
+        index_range: NumericRange::None,
+    }
+}
+
+impl<T> SelectEventField for T
+where
+    T: IntoVariant + TryFromVariant,
+{
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        _namespace: u16,
+        _namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        if !browse_path.is_empty() {
+            clauses.push(select_clause(browse_path, AttributeId::Value));
+        }
+    }
+}
+
+impl<T> SelectEventField for Option<T>
+where
+    T: SelectEventField,
+{
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        namespace: u16,
+        namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        T::select_fields(browse_path, namespace, namespaces, clauses);
+    }
+}
+
+impl<T> SelectEventField for Vec<T>
+where
+    T: SetEventField + Default,
+{
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        _namespace: u16,
+        _namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        // Arrays can only be set as a single value, see `SetEventField`.
+        if !browse_path.is_empty() {
+            clauses.push(select_clause(browse_path, AttributeId::Value));
+        }
+    }
+}
+
+impl SelectEventField for Variant {
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        _namespace: u16,
+        _namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        if !browse_path.is_empty() {
+            clauses.push(select_clause(browse_path, AttributeId::Value));
+        }
+    }
+}
+
+impl SelectEventField for NumericRange {
+    fn select_fields(
+        browse_path: &[QualifiedName],
+        _namespace: u16,
+        _namespaces: &NamespaceMap,
+        clauses: &mut Vec<SimpleAttributeOperand>,
+    ) {
+        if !browse_path.is_empty() {
+            clauses.push(select_clause(browse_path, AttributeId::Value));
+        }
+    }
+}
+
+/// Get the select clauses for every field of `T`, in the order expected by
+/// [`event_from_fields`]. `namespaces` are the namespaces on the server, the fields of
+/// event types in namespaces that are not in `namespaces` are not selected.
+pub fn event_select_clauses<T: SelectEventField>(
+    namespaces: &NamespaceMap,
+) -> Vec<SimpleAttributeOperand> {
+    let mut clauses = Vec::new();
+    T::select_fields(&[], 0, namespaces, &mut clauses);
+    clauses
+}
+
+/// Create an event from a list of event fields returned from a server, for example
+/// in an event notification or a history read. `select_clauses` are the select clauses
+/// of the event filter used to read the event, in the same order as `fields`.
+///
+/// Empty fields are skipped, since servers return empty values for fields that do
+/// not exist on an event. Fails if a field could not be set on `T`.
+pub fn event_from_fields<T>(
+    select_clauses: &[SimpleAttributeOperand],
+    fields: Vec<Variant>,
+) -> Result<T, Error>
+where
+    T: SetEventField + Default,
+{
+    let mut event = T::default();
+    for (clause, value) in select_clauses.iter().zip(fields) {
+        if value.is_empty() {
+            continue;
+        }
+        let attribute_id = AttributeId::from_u32(clause.attribute_id).map_err(|_| {
+            Error::new(
+                StatusCode::BadAttributeIdInvalid,
+                format!("Invalid attribute ID {}", clause.attribute_id),
+            )
+        })?;
+        let browse_path = clause.browse_path.as_deref().unwrap_or_default();
+        if !event.set_value(attribute_id, browse_path, value) {
+            return Err(Error::new(
+                StatusCode::BadTypeMismatch,
+                format!(
+                    "Failed to set event field {}",
+                    browse_path
+                        .iter()
+                        .map(|p| p.name.as_ref())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                ),
+            ));
+        }
+    }
+    Ok(event)
+}
+
 #[derive(Debug)]
 /// Struct for an event field placeholder, i.e. a dynamic list of fields.
 pub struct PlaceholderEventField<T> {
@@ -230,3 +530,20 @@ impl<T: EventField> PlaceholderEventField<T> {
         Some(field.get_value(attribute_id, index_range, remaining_path))
     }
 }
+
+impl<T: SetEventField> PlaceholderEventField<T> {
+    /// Try to set the inner event value given by `key`. Only fields that are
+    /// already present in the placeholder are set.
+    pub fn try_set_value(
+        &mut self,
+        key: &QualifiedName,
+        attribute_id: AttributeId,
+        remaining_path: &[QualifiedName],
+        value: Variant,
+    ) -> bool {
+        let Some(field) = self.get_field_mut(key) else {
+            return false;
+        };
+        field.set_value(attribute_id, remaining_path, value)
+    }
+}
@@ -265,6 +265,7 @@ mod impls;
 #[cfg(feature = "json")]
 pub mod json;
 pub mod localized_text;
+pub mod method_args;
 pub mod namespaces;
 pub mod node_id;
 pub mod notification_message;
@@ -290,6 +291,7 @@ pub use opcua_macros::{XmlDecodable, XmlEncodable, XmlType};
 pub use opcua_macros::ua_encodable;
 pub use opcua_macros::BinaryDecodable;
 pub use opcua_macros::BinaryEncodable;
+pub use opcua_macros::MethodArgs;
 pub use opcua_macros::UaEnum;
 pub use opcua_macros::UaNullable;
 mod ua_enum;
@@ -314,6 +316,7 @@ pub use self::{
     guid::*,
     impls::*,
     localized_text::*,
+    method_args::*,
     namespaces::*,
     node_id::{Identifier, NodeId, NodeIdError},
     numeric_range::*,
@@ -0,0 +1,141 @@
+//! The [`MethodArgs`] trait, for converting the input and output arguments of
+//! method calls to and from Rust types, and logic for checking arguments against
+//! the declared [`Argument`] list of a method.
+
+use crate::{Argument, DataTypeId, Error, StatusCode, Variant};
+
+/// Trait implemented by types that can be used as the input or output arguments of
+/// a method call. This is typically derived with `#[derive(MethodArgs)]`, which converts
+/// each field of a struct to an argument, in the order they are declared.
+pub trait MethodArgs: Sized {
+    /// Convert this into a list of method arguments.
+    fn into_arguments(self) -> Vec<Variant>;
+
+    /// Try to create this from a list of method arguments.
+    fn from_arguments(arguments: Vec<Variant>) -> Result<Self, Error>;
+}
+
+impl MethodArgs for () {
+    fn into_arguments(self) -> Vec<Variant> {
+        Vec::new()
+    }
+
+    fn from_arguments(arguments: Vec<Variant>) -> Result<Self, Error> {
+        if !arguments.is_empty() {
+            return Err(Error::new(
+                StatusCode::BadTooManyArguments,
+                format!("Expected no arguments, got {}", arguments.len()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl MethodArgs for Vec<Variant> {
+    fn into_arguments(self) -> Vec<Variant> {
+        self
+    }
+
+    fn from_arguments(arguments: Vec<Variant>) -> Result<Self, Error> {
+        Ok(arguments)
+    }
+}
+
+/// Check that `arguments` match the arguments declared in the `InputArguments`
+/// or `OutputArguments` property of a method.
+///
+/// This checks the number of arguments, whether each argument is a scalar or an array,
+/// and the data type of arguments declared with a concrete built-in data type. Empty
+/// arguments, and arguments with other data types, such as structures, enumerations or
+/// abstract types, are not checked further.
+pub fn validate_arguments(declared: &[Argument], arguments: &[Variant]) -> Result<(), Error> {
+    if arguments.len() < declared.len() {
+        return Err(Error::new(
+            StatusCode::BadArgumentsMissing,
+            format!(
+                "Expected {} arguments, got {}",
+                declared.len(),
+                arguments.len()
+            ),
+        ));
+    }
+    if arguments.len() > declared.len() {
+        return Err(Error::new(
+            StatusCode::BadTooManyArguments,
+            format!(
+                "Expected {} arguments, got {}",
+                declared.len(),
+                arguments.len()
+            ),
+        ));
+    }
+
+    for (arg, value) in declared.iter().zip(arguments) {
+        if value.is_empty() {
+            continue;
+        }
+        // Value rank -1 is scalar, 1 or more is an array. Other value ranks allow both.
+        if (arg.value_rank == -1 && value.is_array()) || (arg.value_rank > 0 && !value.is_array()) {
+            return Err(Error::new(
+                StatusCode::BadTypeMismatch,
+                format!(
+                    "Argument {} has value rank {}, got {}",
+                    arg.name,
+                    arg.value_rank,
+                    if value.is_array() { "array" } else { "scalar" }
+                ),
+            ));
+        }
+        let Ok(data_type) = arg.data_type.as_data_type_id() else {
+            continue;
+        };
+        if !is_concrete_builtin(data_type) {
+            continue;
+        }
+        // Empty arrays have no data type.
+        let Some(actual) = value.data_type() else {
+            continue;
+        };
+        if !actual.namespace_uri.is_null() || actual.node_id != data_type {
+            return Err(Error::new(
+                StatusCode::BadTypeMismatch,
+                format!(
+                    "Argument {} has data type {}, got {}",
+                    arg.name, arg.data_type, actual.node_id
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `data_type` is a built-in data type that is always encoded as
+/// the corresponding variant type.
+fn is_concrete_builtin(data_type: DataTypeId) -> bool {
+    matches!(
+        data_type,
+        DataTypeId::Boolean
+            | DataTypeId::SByte
+            | DataTypeId::Byte
+            | DataTypeId::Int16
+            | DataTypeId::UInt16
+            | DataTypeId::Int32
+            | DataTypeId::UInt32
+            | DataTypeId::Int64
+            | DataTypeId::UInt64
+            | DataTypeId::Float
+            | DataTypeId::Double
+            | DataTypeId::String
+            | DataTypeId::DateTime
+            | DataTypeId::Guid
+            | DataTypeId::ByteString
+            | DataTypeId::XmlElement
+            | DataTypeId::NodeId
+            | DataTypeId::ExpandedNodeId
+            | DataTypeId::StatusCode
+            | DataTypeId::QualifiedName
+            | DataTypeId::LocalizedText
+            | DataTypeId::DataValue
+            | DataTypeId::DiagnosticInfo
+    )
+}
@@ -107,6 +107,12 @@ impl<'a> NodeSetNamespaceMapper<'a> {
         self.index_map.insert(index_in_node_set, index);
     }
 
+    /// Map `index_in_node_set` in the NodeSet2 file being loaded to the existing
+    /// namespace with index `index`, without registering a new namespace.
+    pub fn map_index(&mut self, index_in_node_set: u16, index: u16) {
+        self.index_map.insert(index_in_node_set, index);
+    }
+
     /// Get the index of a namespace given its index in a NodeSet2 file.
     pub fn get_index(&self, index_in_node_set: u16) -> Result<u16, UninitializedIndex> {
         if index_in_node_set == 0 {
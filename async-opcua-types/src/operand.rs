@@ -10,7 +10,7 @@ use std::convert::TryFrom;
 
 use crate::{
     attribute::AttributeId, match_extension_object_owned, status_code::StatusCode,
-    AttributeOperand, ContentFilter, ContentFilterElement, DataTypeId, ElementOperand,
+    AttributeOperand, ContentFilter, ContentFilterElement, DataTypeId, ElementOperand, EventFilter,
     ExtensionObject, FilterOperator, LiteralOperand, MethodId, NodeId, NumericRange, ObjectId,
     ObjectTypeId, QualifiedName, ReferenceTypeId, SimpleAttributeOperand, VariableId,
     VariableTypeId, Variant,
@@ -474,6 +474,81 @@ impl ContentFilterBuilder {
     }
 }
 
+/// Builder for an [`EventFilter`], selecting a list of event fields, with an
+/// optional where clause.
+///
+/// Events returned for the filter contain the selected fields in the order they
+/// were added to the builder.
+///
+/// ```
+/// use opcua_types::{ContentFilterBuilder, EventFilterBuilder, ObjectTypeId, Operand};
+///
+/// let filter = EventFilterBuilder::new()
+///     .select(ObjectTypeId::BaseEventType, "EventId")
+///     .select(ObjectTypeId::BaseEventType, "Time")
+///     .select(ObjectTypeId::BaseEventType, "Message")
+///     .where_clause(
+///         ContentFilterBuilder::new()
+///             .gte(
+///                 Operand::simple_attribute(
+///                     ObjectTypeId::BaseEventType,
+///                     "Severity",
+///                     opcua_types::AttributeId::Value,
+///                     opcua_types::NumericRange::None,
+///                 ),
+///                 Operand::literal(500u16),
+///             )
+///             .build(),
+///     )
+///     .build();
+/// assert_eq!(filter.select_clauses.as_ref().unwrap().len(), 3);
+/// ```
+#[derive(Debug, Default)]
+pub struct EventFilterBuilder {
+    select_clauses: Vec<SimpleAttributeOperand>,
+    where_clause: ContentFilter,
+}
+
+impl EventFilterBuilder {
+    /// Create a new empty event filter builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select the value of the field given by `browse_path`, a `/` separated
+    /// path from the event type `type_definition_id`.
+    pub fn select<T>(self, type_definition_id: T, browse_path: &str) -> Self
+    where
+        T: Into<NodeId>,
+    {
+        self.select_operand(SimpleAttributeOperand::new_value(
+            type_definition_id,
+            browse_path,
+        ))
+    }
+
+    /// Select the field given by a simple attribute operand.
+    pub fn select_operand(mut self, operand: SimpleAttributeOperand) -> Self {
+        self.select_clauses.push(operand);
+        self
+    }
+
+    /// Set the where clause of the filter, only events matching this
+    /// filter are returned.
+    pub fn where_clause(mut self, where_clause: ContentFilter) -> Self {
+        self.where_clause = where_clause;
+        self
+    }
+
+    /// Build the event filter.
+    pub fn build(self) -> EventFilter {
+        EventFilter {
+            select_clauses: Some(self.select_clauses),
+            where_clause: self.where_clause,
+        }
+    }
+}
+
 impl SimpleAttributeOperand {
     /// Create a new simple attribute operand.
     pub fn new<T>(
@@ -85,6 +85,82 @@ impl RelativePath {
     }
 }
 
+impl RelativePath {
+    /// Create a builder for a relative path, as an alternative to parsing it from a string.
+    pub fn builder() -> RelativePathBuilder {
+        RelativePathBuilder::new()
+    }
+}
+
+/// Builder for a [`RelativePath`], one element at a time.
+///
+/// # Example
+///
+/// ```
+/// use opcua_types::{QualifiedName, RelativePath};
+///
+/// // Equivalent to "/2:Block.2:Output"
+/// let path = RelativePath::builder()
+///     .child(QualifiedName::new(2, "Block"))
+///     .aggregate(QualifiedName::new(2, "Output"))
+///     .build();
+/// assert_eq!(String::from(&path), "/2:Block.2:Output");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RelativePathBuilder {
+    elements: Vec<RelativePathElement>,
+}
+
+impl RelativePathBuilder {
+    /// Create a new, empty relative path builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow a hierarchical reference, or any subtype, to a target with the
+    /// given browse name. This is `/` in a path string.
+    pub fn child(self, target_name: impl Into<QualifiedName>) -> Self {
+        self.reference(
+            ReferenceTypeId::HierarchicalReferences,
+            true,
+            false,
+            target_name,
+        )
+    }
+
+    /// Follow an aggregates reference, or any subtype, to a target with the
+    /// given browse name. This is `.` in a path string.
+    pub fn aggregate(self, target_name: impl Into<QualifiedName>) -> Self {
+        self.reference(ReferenceTypeId::Aggregates, true, false, target_name)
+    }
+
+    /// Follow a reference of the given type to a target with the given browse name.
+    /// This is `<ReferenceType>` in a path string, with `#` to exclude subtypes, and
+    /// `!` for inverse references.
+    pub fn reference(
+        mut self,
+        reference_type_id: impl Into<NodeId>,
+        include_subtypes: bool,
+        is_inverse: bool,
+        target_name: impl Into<QualifiedName>,
+    ) -> Self {
+        self.elements.push(RelativePathElement {
+            reference_type_id: reference_type_id.into(),
+            is_inverse,
+            include_subtypes,
+            target_name: target_name.into(),
+        });
+        self
+    }
+
+    /// Build the relative path.
+    pub fn build(self) -> RelativePath {
+        RelativePath {
+            elements: Some(self.elements),
+        }
+    }
+}
+
 impl From<&[QualifiedName]> for RelativePath {
     fn from(value: &[QualifiedName]) -> Self {
         let elements = value
@@ -691,3 +767,20 @@ fn test_relative_path() {
         assert_eq!(relative_path, actual);
     });
 }
+
+#[test]
+fn test_relative_path_builder() {
+    let path = RelativePath::builder()
+        .child(QualifiedName::new(2, "Block"))
+        .aggregate(QualifiedName::new(3, "Output"))
+        .reference(
+            ReferenceTypeId::HasChild,
+            false,
+            true,
+            QualifiedName::new(1, "Parent"),
+        )
+        .build();
+    assert_eq!(String::from(&path), "/2:Block.3:Output<#!HasChild>1:Parent");
+    let parsed = RelativePath::try_from("/2:Block.3:Output<#!HasChild>1:Parent").unwrap();
+    assert_eq!(path, parsed);
+}
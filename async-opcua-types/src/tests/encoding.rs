@@ -690,3 +690,33 @@ fn test_xml_in_binary() {
     let decoded = ExtensionObject::decode(&mut stream, &ctx).unwrap();
     assert_eq!(decoded.inner_as::<EUInformation>().unwrap(), &rf);
 }
+
+#[test]
+fn error_context() {
+    use crate::{Error, ErrorContext, StatusCode};
+
+    let err = Error::new(StatusCode::BadNodeIdUnknown, "Node not found");
+    assert_eq!(err.to_string(), "BadNodeIdUnknown: Node not found");
+
+    let res: Result<(), Error> = Err(err);
+    let err = res
+        .node_context(NodeId::new(2, "foo"))
+        .context("reading value")
+        .node_context(NodeId::new(2, "bar"))
+        .context_with(|| "loading config".to_owned())
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BadNodeIdUnknown);
+    // The innermost node is kept.
+    assert_eq!(err.node_id(), Some(&NodeId::new(2, "foo")));
+    assert_eq!(err.operations(), ["reading value", "loading config"]);
+    assert_eq!(
+        err.to_string(),
+        "BadNodeIdUnknown: loading config: reading value: Node not found (node ns=2;s=foo)"
+    );
+
+    // Status codes convert into errors, keeping the status.
+    let res: Result<(), StatusCode> = Err(StatusCode::BadTimeout);
+    let err = res.context("connecting").unwrap_err();
+    assert_eq!(err.status(), StatusCode::BadTimeout);
+    assert_eq!(StatusCode::from(err), StatusCode::BadTimeout);
+}
@@ -216,6 +216,7 @@ where
 /// Owned variant of [Context], this is stored by clients and servers, which
 /// call the [ContextOwned::context] method to produce a [Context]
 /// for decoding/encoding.
+#[derive(Clone)]
 pub struct ContextOwned {
     namespaces: NamespaceMap,
     loaders: TypeLoaderCollection,
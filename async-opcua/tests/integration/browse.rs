@@ -1,16 +1,27 @@
-use super::utils::setup;
+use std::{collections::HashSet, time::Duration};
+
+use super::utils::{setup, test_server, TestNodeManager, Tester};
+use futures::{StreamExt, TryStreamExt};
 use opcua::{
-    nodes::TypeTree,
-    server::address_space::{ObjectBuilder, ReferenceDirection, VariableBuilder},
+    nodes::{
+        ImportedItem, ImportedReference, NodeBase, NodeSet2Import, NodeSetImport, NodeSetModel,
+        NodeType, TypeTree,
+    },
+    server::address_space::{
+        ObjectBuilder, ObjectTypeBuilder, ReferenceDirection, VariableBuilder,
+    },
     types::{
         BrowseDescription, BrowseDirection, BrowsePath, BrowseResultMask, ByteString, DataTypeId,
         NodeClass, NodeClassMask, NodeId, ObjectId, ObjectTypeId, ReferenceTypeId, RelativePath,
         RelativePathElement, StatusCode, VariableTypeId,
     },
 };
-use opcua_client::browser::BrowseFilter;
+use opcua_client::{browser::BrowseFilter, services::Browse, BrowseResultStream, UARequest};
 use opcua_nodes::DefaultTypeTree;
-use opcua_types::{AttributeId, ReadValueId, TimestampsToReturn, VariableId, Variant};
+use opcua_types::{
+    AttributeId, DataEncoding, DateTime, NamespaceMap, NodeSetNamespaceMapper, NumericRange,
+    QualifiedName, ReadValueId, TimestampsToReturn, VariableId, Variant,
+};
 
 fn hierarchical_desc(node_id: NodeId) -> BrowseDescription {
     BrowseDescription {
@@ -349,6 +360,195 @@ async fn browse_release_continuation_point() {
     assert!(refs.is_empty());
 }
 
+fn add_children(tester: &Tester, nm: &TestNodeManager, count: usize) -> NodeId {
+    let root_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&root_id, "TestObj1", "TestObj1")
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&ObjectTypeId::FolderType.into()),
+        Vec::new(),
+    );
+    for i in 0..count {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&id, format!("Var{i}"), format!("Var{i}"))
+                .data_type(DataTypeId::Int32)
+                .build()
+                .into(),
+            &root_id,
+            &ReferenceTypeId::HasComponent.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+    }
+    root_id
+}
+
+#[tokio::test]
+async fn browse_all() {
+    let (tester, nm, session) = setup().await;
+    let first = add_children(&tester, &nm, 1000);
+    let second = add_children(&tester, &nm, 250);
+
+    let descs = [hierarchical_desc(first), hierarchical_desc(second)];
+    let r = assert_send(session.browse_all(&descs, 100, None))
+        .await
+        .unwrap();
+    assert_eq!(2, r.len());
+    for (it, count) in r.iter().zip([1000, 250]) {
+        assert_eq!(StatusCode::Good, it.status_code);
+        assert!(it.continuation_point.is_null());
+        assert_eq!(count, it.references.as_ref().unwrap().len());
+    }
+
+    // The stream returns the result of each request. After the third batch,
+    // only the first node has references left.
+    let batches = session
+        .browse_all_stream(&descs, 100, None)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(10, batches.len());
+    assert_eq!(2, batches[2].len());
+    assert_eq!(1, batches[3].len());
+    assert_eq!(0, batches[3][0].0);
+    assert!(batches
+        .iter()
+        .flatten()
+        .all(|(_, r)| r.continuation_point.is_null()));
+
+    // Follow continuation points from a manual browse.
+    let r = session.browse(&descs, 100, None).await.unwrap();
+    let cps: Vec<_> = r.iter().map(|r| r.continuation_point.clone()).collect();
+    let r = session.browse_next_all(&cps).await.unwrap();
+    assert_eq!(2, r.len());
+    assert_eq!(900, r[0].references.as_ref().unwrap().len());
+    assert_eq!(150, r[1].references.as_ref().unwrap().len());
+}
+
+#[tokio::test]
+async fn browse_next_all_invalid_continuation_point() {
+    let (tester, nm, session) = setup().await;
+    let root_id = add_children(&tester, &nm, 1000);
+
+    let r = session
+        .browse(&[hierarchical_desc(root_id)], 100, None)
+        .await
+        .unwrap();
+    let cp = r[0].continuation_point.clone();
+    let r = session
+        .browse_next_all(&[cp, ByteString::from(vec![1u8])])
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::Good, r[0].status_code);
+    assert_eq!(900, r[0].references.as_ref().unwrap().len());
+    assert_eq!(StatusCode::BadContinuationPointInvalid, r[1].status_code);
+}
+
+#[tokio::test]
+async fn browse_result_stream() {
+    let (tester, nm, session) = setup().await;
+    let first = add_children(&tester, &nm, 1000);
+    let second = add_children(&tester, &nm, 250);
+    let descs = vec![
+        hierarchical_desc(first.clone()),
+        hierarchical_desc(second.clone()),
+    ];
+    let refs: Vec<_> = BrowseResultStream::new(session.clone(), descs, 100, None)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(1000, refs.iter().filter(|(n, _)| n == &first).count());
+    assert_eq!(250, refs.iter().filter(|(n, _)| n == &second).count());
+
+    // Stream combinators stop requesting pages once they have what they need.
+    let names: Vec<_> =
+        BrowseResultStream::new(session.clone(), vec![hierarchical_desc(first)], 100, None)
+            .map_ok(|(_, r)| r.browse_name.name.to_string())
+            .take(150)
+            .try_collect()
+            .await
+            .unwrap();
+    assert_eq!(150, names.len());
+}
+
+#[tokio::test]
+async fn browse_result_stream_release() {
+    let mut server = test_server();
+    server.limits_mut().max_browse_continuation_points = 1;
+    let mut tester = Tester::new(server, false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    session.wait_for_connection().await;
+    let root_id = add_children(&tester, &nm, 10);
+    let descs = [hierarchical_desc(root_id.clone())];
+
+    // The stream holds the only continuation point the server allows.
+    let mut stream = BrowseResultStream::new(session.clone(), descs.to_vec(), 1, None);
+    stream.next().await.unwrap().unwrap();
+    let r = session.browse(&descs, 1, None).await.unwrap();
+    assert_eq!(StatusCode::BadNoContinuationPoints, r[0].status_code);
+
+    stream.release().await;
+    let r = session.browse(&descs, 1, None).await.unwrap();
+    assert_eq!(StatusCode::Good, r[0].status_code);
+    session
+        .browse_next(true, &[r[0].continuation_point.clone()])
+        .await
+        .unwrap();
+
+    // The second node gets an error, since the server is out of continuation points,
+    // and the stream continues with the first.
+    let other_id = add_children(&tester, &nm, 10);
+    let items: Vec<_> = BrowseResultStream::new(
+        session.clone(),
+        vec![
+            hierarchical_desc(root_id.clone()),
+            hierarchical_desc(other_id),
+        ],
+        1,
+        None,
+    )
+    .collect()
+    .await;
+    assert_eq!(11, items.len());
+    assert_eq!(
+        vec![&StatusCode::BadNoContinuationPoints],
+        items
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .collect::<Vec<_>>()
+    );
+    assert!(items.iter().flatten().all(|(n, _)| n == &root_id));
+
+    // Dropping the stream releases the continuation point in the background.
+    let mut stream = BrowseResultStream::new(session.clone(), descs.to_vec(), 1, None);
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    let mut status = StatusCode::BadNoContinuationPoints;
+    for _ in 0..50 {
+        let r = session.browse(&descs, 1, None).await.unwrap();
+        status = r[0].status_code;
+        if status != StatusCode::BadNoContinuationPoints {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(StatusCode::Good, status);
+}
+
 #[tokio::test]
 async fn browse_limits() {
     let (tester, _nm, session) = setup().await;
@@ -369,9 +569,19 @@ async fn browse_limits() {
     let ops: Vec<_> = (0..(browse_limit + 1))
         .map(|r| hierarchical_desc(NodeId::new(2, r as u32)))
         .collect();
-    let r = session.browse(&ops, 1000, None).await.unwrap_err();
+    // The session splits requests by the server operation limits, so send the request directly.
+    let r = Browse::new(&session)
+        .nodes_to_browse(ops.clone())
+        .max_references_per_node(1000)
+        .send(session.channel())
+        .await
+        .unwrap_err();
     assert_eq!(r, StatusCode::BadTooManyOperations);
 
+    // Through the session, the request is split into several requests.
+    let r = session.browse(&ops, 1000, None).await.unwrap();
+    assert_eq!(r.len(), ops.len());
+
     // Browse next zero
     let r = session.browse_next(false, &[]).await.unwrap_err();
     assert_eq!(r, StatusCode::BadNothingToDo);
@@ -436,6 +646,72 @@ async fn translate_browse_path() {
     assert_eq!(t.target_id.node_id, parent);
 }
 
+#[tokio::test]
+async fn translate_path() {
+    let (tester, nm, session) = setup().await;
+    // Make a tree of three nodes under each other Obj0 -> Obj1 -> Obj2
+    let ids: Vec<_> = (0..3).map(|_| nm.inner().next_node_id()).collect();
+    let mut parent: NodeId = ObjectId::ObjectsFolder.into();
+    for (i, id) in ids.iter().enumerate() {
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            ObjectBuilder::new(id, format!("Obj{i}"), format!("Obj{i}"))
+                .build()
+                .into(),
+            &parent,
+            &ReferenceTypeId::HasComponent.into(),
+            Some(&ObjectTypeId::FolderType.into()),
+            Vec::new(),
+        );
+        parent = id.clone();
+    }
+
+    let id = session
+        .translate_path(ObjectId::ObjectsFolder, "/0:Obj0.0:Obj1/Obj2")
+        .await
+        .unwrap();
+    assert_eq!(id, ids[2]);
+
+    let r = session
+        .translate_paths(&[
+            (
+                ids[0].clone(),
+                RelativePath::builder()
+                    .aggregate("Obj1")
+                    .child("Obj2")
+                    .build(),
+            ),
+            (
+                ids[0].clone(),
+                RelativePath::builder().child("Obj2").build(),
+            ),
+            (
+                ids[2].clone(),
+                RelativePath::builder()
+                    .reference(ReferenceTypeId::HasComponent, false, true, "Obj1")
+                    .build(),
+            ),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(
+        r,
+        vec![
+            Ok(ids[2].clone()),
+            Err(StatusCode::BadNoMatch),
+            Ok(ids[1].clone())
+        ]
+    );
+
+    // Too many elements to be a valid path.
+    let r = session
+        .translate_path(ObjectId::ObjectsFolder, &"/Obj0".repeat(40))
+        .await
+        .unwrap_err();
+    assert_eq!(r, StatusCode::BadBrowseNameInvalid);
+}
+
 #[tokio::test]
 async fn translate_browse_path_cross_node_manager() {
     // Same test as above, but start the translate process from the objects folder,
@@ -662,3 +938,384 @@ async fn test_recursive_browser_multi_hit() {
     // Note: This value is expected to change with new versions of the standard.
     assert_eq!(rs.len(), 2247);
 }
+
+#[tokio::test]
+async fn browse_by_type() {
+    let (tester, nm, session) = setup().await;
+
+    let add_object_type = |name: &str, parent: &NodeId| {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            ObjectTypeBuilder::new(&id, name, name).build().into(),
+            parent,
+            &ReferenceTypeId::HasSubtype.into(),
+            None,
+            Vec::new(),
+        );
+        id
+    };
+    let add_object = |name: &str, parent: &NodeId, type_id: &NodeId| {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            ObjectBuilder::new(&id, name, name).build().into(),
+            parent,
+            &ReferenceTypeId::Organizes.into(),
+            Some(type_id),
+            Vec::new(),
+        );
+        id
+    };
+
+    let machine_type = add_object_type("MachineType", &ObjectTypeId::BaseObjectType.into());
+    let special_type = add_object_type("SpecialMachineType", &machine_type);
+
+    let plant = add_object(
+        "Plant",
+        &ObjectId::ObjectsFolder.into(),
+        &ObjectTypeId::FolderType.into(),
+    );
+    let line = add_object("Line", &plant, &ObjectTypeId::FolderType.into());
+    let machine = add_object("Machine", &plant, &machine_type);
+    let special = add_object("Special", &line, &special_type);
+
+    let var_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&var_id, "Speed", "Speed")
+            .value(1)
+            .data_type(DataTypeId::Int32)
+            .build()
+            .into(),
+        &machine,
+        &ReferenceTypeId::HasComponent.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let vars = session.browse_variables(&machine).await.unwrap();
+    assert_eq!(vars.len(), 1);
+    assert_eq!(vars[0].node_id.node_id, var_id);
+    assert_eq!(vars[0].node_class, NodeClass::Variable);
+
+    let children = session
+        .browse_children_of_type(&plant, &machine_type)
+        .await
+        .unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].node_id.node_id, machine);
+
+    let found: HashSet<_> = session
+        .find_objects_of_type(&ObjectId::ObjectsFolder.into(), &machine_type)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.node_id.node_id)
+        .collect();
+    assert_eq!(found, HashSet::from([machine, special.clone()]));
+
+    let found = session
+        .find_objects_of_type(&ObjectId::ObjectsFolder.into(), &special_type)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].node_id.node_id, special);
+}
+
+#[tokio::test]
+async fn crawl_address_space() {
+    let (tester, nm, session) = setup().await;
+
+    let add_object = |name: &str, parent: &NodeId, type_id: ObjectTypeId| {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            ObjectBuilder::new(&id, name, name).build().into(),
+            parent,
+            &ReferenceTypeId::Organizes.into(),
+            Some(&type_id.into()),
+            Vec::new(),
+        );
+        id
+    };
+    let folder = add_object(
+        "Crawl",
+        &ObjectId::ObjectsFolder.into(),
+        ObjectTypeId::FolderType,
+    );
+    let machine = add_object("Machine", &folder, ObjectTypeId::BaseObjectType);
+    let var_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&var_id, "Speed", "Speed")
+            .value(5)
+            .data_type(DataTypeId::Int32)
+            .build()
+            .into(),
+        &machine,
+        &ReferenceTypeId::HasComponent.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let model = session
+        .crawler()
+        .roots(vec![folder.clone()])
+        .crawl()
+        .await
+        .unwrap();
+    assert_eq!(model.len(), 3);
+    let Some(NodeType::Variable(var)) = model.get(&var_id) else {
+        panic!("Expected variable");
+    };
+    let value = var.value(
+        TimestampsToReturn::Neither,
+        &NumericRange::None,
+        &DataEncoding::Binary,
+        0.0,
+    );
+    assert_eq!(value.value, Some(Variant::Int32(5)));
+    assert_eq!(var.data_type(), DataTypeId::Int32);
+    assert_eq!(
+        model.type_definition(&machine),
+        Some(&ObjectTypeId::BaseObjectType.into())
+    );
+    let components = model.targets(&machine, &ReferenceTypeId::HasComponent.into());
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].as_node().node_id(), &var_id);
+    assert_eq!(
+        model
+            .find_by_browse_name(&QualifiedName::from("Machine"))
+            .count(),
+        1
+    );
+
+    let model = session
+        .crawler()
+        .roots(vec![folder.clone()])
+        .max_depth(1)
+        .crawl()
+        .await
+        .unwrap();
+    assert_eq!(model.len(), 2);
+    assert!(model.get(&var_id).is_none());
+
+    // Merging a crawl of the missing node completes the model.
+    let mut model = model;
+    let rest = session
+        .crawler()
+        .roots(vec![var_id.clone()])
+        .crawl()
+        .await
+        .unwrap();
+    model.extend(rest);
+    assert_eq!(model.len(), 3);
+    let components = model.targets(&machine, &ReferenceTypeId::HasComponent.into());
+    assert_eq!(components.len(), 1);
+    assert_eq!(
+        model.type_definition(&var_id),
+        Some(&VariableTypeId::BaseDataVariableType.into())
+    );
+
+    let model = session
+        .crawler()
+        .roots(vec![folder.clone()])
+        .node_class_mask(NodeClassMask::VARIABLE)
+        .crawl()
+        .await
+        .unwrap();
+    assert_eq!(model.len(), 1);
+    assert!(model.get(&var_id).is_some());
+
+    let model = session
+        .crawler()
+        .roots(vec![folder.clone()])
+        .namespaces([0])
+        .crawl()
+        .await
+        .unwrap();
+    assert!(model.is_empty());
+
+    // Export the full crawl and load it again.
+    let model = session
+        .crawler()
+        .roots(vec![folder.clone()])
+        .crawl()
+        .await
+        .unwrap();
+    let mut xml = Vec::new();
+    model.write_nodeset2(&mut xml).unwrap();
+    let import = NodeSet2Import::new_str("en", std::str::from_utf8(&xml).unwrap(), vec![]).unwrap();
+    let mut namespaces = NamespaceMap::new();
+    let mut mapper = NodeSetNamespaceMapper::new(&mut namespaces);
+    import.register_namespaces(&mut mapper);
+    let items: Vec<_> = import.load(&mapper).collect();
+    assert_eq!(items.len(), 3);
+    let var = items
+        .iter()
+        .find_map(|i| match &i.node {
+            NodeType::Variable(v) => Some(v),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(var.browse_name(), &QualifiedName::from("Speed"));
+    let value = var.value(
+        TimestampsToReturn::Neither,
+        &NumericRange::None,
+        &DataEncoding::Binary,
+        0.0,
+    );
+    assert_eq!(value.value, Some(Variant::Int32(5)));
+}
+
+/// Import with a single object in its own namespace, and a model table.
+struct MetadataImport;
+
+impl NodeSetImport for MetadataImport {
+    fn register_namespaces(&self, namespaces: &mut NodeSetNamespaceMapper) {
+        namespaces.add_namespace("urn:NamespaceMetadataTest", 1);
+    }
+
+    fn get_own_namespaces(&self) -> Vec<String> {
+        vec!["urn:NamespaceMetadataTest".to_owned()]
+    }
+
+    fn get_models(&self) -> Vec<NodeSetModel> {
+        let mut model = NodeSetModel::new("urn:NamespaceMetadataTest");
+        model.version = Some("1.02".to_owned());
+        model.publication_date = Some(DateTime::ymd(2021, 3, 4));
+        vec![model]
+    }
+
+    fn load<'a>(
+        &'a self,
+        namespaces: &'a NodeSetNamespaceMapper,
+    ) -> Box<dyn Iterator<Item = ImportedItem> + 'a> {
+        let id = NodeId::new(namespaces.get_index(1).unwrap(), 1);
+        Box::new(std::iter::once(ImportedItem {
+            node: ObjectBuilder::new(&id, "Imported", "Imported")
+                .build()
+                .into(),
+            references: vec![ImportedReference {
+                target_id: ObjectId::ObjectsFolder.into(),
+                type_id: ReferenceTypeId::Organizes.into(),
+                is_forward: false,
+            }],
+        }))
+    }
+}
+
+async fn namespace_properties(
+    session: &opcua::client::Session,
+    uri: &str,
+) -> std::collections::HashMap<String, (NodeClass, Variant)> {
+    let r = session
+        .browse(
+            &[hierarchical_desc(ObjectId::Server_Namespaces.into())],
+            1000,
+            None,
+        )
+        .await
+        .unwrap();
+    let ns_node = r[0]
+        .references
+        .as_ref()
+        .unwrap()
+        .iter()
+        .find(|r| r.browse_name.name.as_ref() == uri)
+        .unwrap()
+        .node_id
+        .node_id
+        .clone();
+
+    let r = session
+        .browse(
+            &[BrowseDescription {
+                node_id: ns_node,
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::HasProperty.into(),
+                include_subtypes: true,
+                node_class_mask: NodeClassMask::all().bits(),
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            1000,
+            None,
+        )
+        .await
+        .unwrap();
+    let props = r[0].references.clone().unwrap_or_default();
+    let to_read: Vec<_> = props
+        .iter()
+        .map(|p| ReadValueId::new_value(p.node_id.node_id.clone()))
+        .collect();
+    let values = session
+        .read(&to_read, TimestampsToReturn::Neither, 0.0)
+        .await
+        .unwrap();
+
+    props
+        .into_iter()
+        .zip(values)
+        .map(|(p, v)| {
+            (
+                p.browse_name.name.as_ref().to_owned(),
+                (p.node_class, v.value.unwrap_or(Variant::Empty)),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn namespace_metadata() {
+    let server = test_server().with_node_manager(
+        opcua::server::node_manager::memory::simple_node_manager_imports(
+            vec![Box::new(MetadataImport)],
+            "imported",
+        ),
+    );
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    // Metadata of an imported namespace is taken from the nodeset model.
+    let props = namespace_properties(&session, "urn:NamespaceMetadataTest").await;
+    assert_eq!(
+        props["NamespaceUri"].1,
+        Variant::from("urn:NamespaceMetadataTest")
+    );
+    assert_eq!(props["NamespaceVersion"].1, Variant::from("1.02"));
+    let Variant::DateTime(date) = &props["NamespacePublicationDate"].1 else {
+        panic!(
+            "Expected publication date, got {:?}",
+            props["NamespacePublicationDate"]
+        );
+    };
+    assert_eq!(date.as_chrono().to_rfc3339(), "2021-03-04T00:00:00+00:00");
+    assert_eq!(props["IsNamespaceSubset"].1, Variant::Boolean(false));
+    assert_eq!(
+        props["StaticNodeIdTypes"].1,
+        Variant::from(vec![opcua::types::IdType::Numeric as u8])
+    );
+    for (name, (class, _)) in &props {
+        assert_eq!(*class, NodeClass::Variable, "{name}");
+    }
+
+    // Mandatory properties are filled with defaults for namespaces without metadata.
+    let props = namespace_properties(&session, "urn:rustopcuatestserver").await;
+    assert_eq!(props["IsNamespaceSubset"].1, Variant::Boolean(false));
+    assert_eq!(props["StaticStringNodeIdPattern"].1, Variant::from(""));
+    // Node ID types are computed for every in-memory namespace, this one has no static nodes.
+    let Variant::Array(id_types) = &props["StaticNodeIdTypes"].1 else {
+        panic!("Expected array, got {:?}", props["StaticNodeIdTypes"]);
+    };
+    assert!(id_types.values.is_empty());
+}
@@ -1,37 +1,63 @@
 use std::{
-    sync::{atomic::Ordering, Arc},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
 use super::utils::hostname;
 use async_trait::async_trait;
 use bytes::BytesMut;
+use futures::StreamExt;
 use log::debug;
 use opcua::{
-    client::IdentityToken,
+    client::{
+        metrics::RequestMetrics,
+        recording::{MessageRecorder, RecordFormat, RecordingReader, Replayer},
+        transport::ReverseConnectListener,
+        IdentityToken,
+    },
+    core::comms::interceptor::{InterceptedMessage, MessageDirection, MessageInterceptor},
+    core::comms::metrics::TransportMetrics,
+    core::comms::rate_limit::RateLimit,
+    core::comms::socket::SocketOptions,
     core::comms::tcp_codec::{Message, TcpCodec},
-    core::config::Config,
+    core::comms::tcp_types::ReverseHelloMessage,
+    core::config::{Config, TokenRenewalObserver},
+    core::{RequestMessage, ResponseMessage},
     crypto::SecurityPolicy,
+    crypto::Thumbprint,
     types::{
-        ApplicationType, DecodingOptions, MessageSecurityMode, NodeId, ReadValueId, StatusCode,
-        TimestampsToReturn, VariableId, Variant,
+        ApplicationType, AttributeId, ChannelSecurityToken, DataValue, DecodingOptions,
+        MessageSecurityMode, NodeId, ReadValueId, StatusCode, TimestampsToReturn, VariableId,
+        Variant, WriteValue,
     },
 };
-use opcua_client::IssuedTokenWrapper;
+use opcua_client::{
+    services::Read, ConnectionEvent, ExponentialReconnect, IssuedTokenWrapper, ReconnectDecision,
+    ReconnectEvent, RequestLimitOptions, RequestOverflow, RequireSecurity, ScoreEndpoints,
+    ServiceRetryOptions, SessionPool, UARequest,
+};
 use opcua_server::{
     authenticator::{issued_token_security_policy, AuthManager, UserToken},
     ServerEndpoint,
 };
-use opcua_types::{ByteString, Error, UAString, UserTokenPolicy, UserTokenType};
+use opcua_types::{
+    ByteString, ContextOwned, EndpointDescription, Error, SimpleBinaryEncodable, UAString,
+    UserTokenPolicy, UserTokenType,
+};
+use tempdir::TempDir;
 use tokio::{
-    io::AsyncReadExt,
+    io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
 };
 use tokio_util::codec::Decoder;
 
 use crate::utils::{
-    client_user_token, client_x509_token, copy_shared_certs, default_server, test_server, Tester,
-    CLIENT_USERPASS_ID, TEST_COUNTER,
+    client_user_token, client_x509_token, copy_shared_certs, default_client, default_server,
+    test_server, ChannelNotifications, Tester, CLIENT_USERPASS_ID, TEST_COUNTER,
 };
 
 #[tokio::test]
@@ -234,162 +260,346 @@ async fn connect_basic128rsa15_with_x509_token() {
 }
 
 #[tokio::test]
-async fn connect_basic128rsa_15_with_invalid_token() {
-    let mut tester = Tester::new_default_server(true).await;
-    let (_, handle) = tester
+async fn connect_parallel_crypto() {
+    let server = default_server().parallel_crypto_chunk_threshold(2);
+    let client = default_client(0, false).parallel_crypto_chunk_threshold(2);
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester
         .connect(
-            SecurityPolicy::Basic128Rsa15,
+            SecurityPolicy::Basic256Sha256,
             MessageSecurityMode::SignAndEncrypt,
-            IdentityToken::UserName(CLIENT_USERPASS_ID.to_owned(), "invalid".into()),
+            IdentityToken::Anonymous,
         )
         .await
         .unwrap();
-    let res = handle.spawn().await.unwrap();
-    assert_eq!(res, StatusCode::BadIdentityTokenRejected);
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    // Large enough that both request and response span several chunks.
+    let to_read: Vec<_> = (0..5000)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect();
+    let res = session
+        .read(&to_read, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 5000);
+    assert!(res
+        .iter()
+        .all(|v| v.status.unwrap_or(StatusCode::Good).is_good()));
 }
 
 #[tokio::test]
-async fn find_servers() {
-    let tester = Tester::new_default_server(true).await;
-    let servers = tester
-        .client
-        .find_servers(tester.endpoint(), None, None)
+async fn connect_stream_encoding() {
+    let server = default_server().stream_encoding_threshold(16 * 1024);
+    let mut tester = Tester::new(server, false).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
         .await
         .unwrap();
-    assert_eq!(servers.len(), 1);
+    let _h = handle.spawn();
 
-    let s = &servers[0];
-    let discovery_urls = s.discovery_urls.as_ref().unwrap();
-    assert!(!discovery_urls.is_empty());
-    assert_eq!(s.application_type, ApplicationType::Server);
-    assert_eq!(s.application_name.text.as_ref(), "integration_server");
-    assert_eq!(s.application_uri.as_ref(), "urn:integration_server");
-    assert_eq!(s.product_uri.as_ref(), "urn:integration_server Testkit");
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    // Large enough that the response is streamed.
+    let to_read: Vec<_> = (0..5000)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect();
+    let res = session
+        .read(&to_read, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 5000);
+    assert!(res
+        .iter()
+        .all(|v| v.status.unwrap_or(StatusCode::Good).is_good()));
 }
 
 #[tokio::test]
-async fn discovery_test() {
-    let tester = Tester::new_default_server(true).await;
-    // Get all
-    let endpoints = tester
-        .client
-        .get_endpoints(tester.endpoint(), &[], &[])
+async fn negotiated_connection_limits() {
+    let server = default_server().max_send_chunk_count(16);
+    let client = default_client(0, false)
+        .max_send_message_size(64 * 1024)
+        .max_send_chunk_count(128);
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
         .await
         .unwrap();
-    assert_eq!(endpoints.len(), 11);
 
-    // Get with wrong profile URIs
-    let endpoints = tester
-        .client
-        .get_endpoints(tester.endpoint(), &[], &["wrongwrong"])
+    let limits = session.connection_limits();
+    // Our own send limit is lower than what the server accepts.
+    assert_eq!(limits.send.max_message_size, 64 * 1024);
+    // The server accepts fewer chunks than we would send.
+    assert_eq!(limits.send.max_chunk_count, 64);
+    assert_eq!(limits.receive.max_message_size, 1024 * 1024 * 64);
+    assert_eq!(limits.receive.max_chunk_count, 64);
+    assert!(limits.send.max_chunk_size > 0);
+    assert!(limits.receive.max_chunk_size > 0);
+
+    // Requests larger than the send limit fail before they are sent.
+    let to_read: Vec<_> = (0..5000)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect();
+    let err = session
+        .read(&to_read, TimestampsToReturn::Both, 0.0)
         .await
-        .unwrap();
-    assert!(endpoints.is_empty());
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadRequestTooLarge);
 
-    // Get all binary endpoints (all of them)
-    let endpoints = tester
-        .client
-        .get_endpoints(
-            tester.endpoint(),
-            &[],
-            &["http://opcfoundation.org/UA-Profile/Transport/uatcp-uasc-uabinary"],
-        )
+    // The connection is still usable afterwards.
+    session
+        .read(&to_read[..10], TimestampsToReturn::Both, 0.0)
         .await
         .unwrap();
-    assert_eq!(endpoints.len(), 11);
 }
 
 #[tokio::test]
-async fn multi_client_test() {
-    // Simple multi-client test, checking that we can send and receive requests with multiple clients
-    // to the same server, and also that the client SDK can handle multiple sessions in the same client.
-    let mut tester = Tester::new_default_server(true).await;
+async fn small_hello_buffers() {
+    let server = default_server()
+        .send_buffer_size(8192)
+        .receive_buffer_size(8192);
+    let client = default_client(0, false)
+        .max_chunk_size(16384)
+        .max_incoming_chunk_size(8192);
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
 
-    let c1 = tester
-        .connect_and_wait(
-            SecurityPolicy::Basic128Rsa15,
-            MessageSecurityMode::SignAndEncrypt,
-            IdentityToken::UserName(
-                CLIENT_USERPASS_ID.to_owned(),
-                format!("{CLIENT_USERPASS_ID}_password").into(),
-            ),
-        )
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
         .await
         .unwrap();
-    // Same user token, should still be fine
-    let c2 = tester
-        .connect_and_wait(
-            SecurityPolicy::Basic256,
-            MessageSecurityMode::SignAndEncrypt,
-            IdentityToken::UserName(
-                CLIENT_USERPASS_ID.to_owned(),
-                format!("{CLIENT_USERPASS_ID}_password").into(),
-            ),
-        )
+
+    let limits = session.connection_limits();
+    assert_eq!(limits.send.max_chunk_size, 8192);
+    assert_eq!(limits.receive.max_chunk_size, 8192);
+
+    // Messages spanning several chunks still work.
+    let to_read: Vec<_> = (0..500)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect();
+    let res = session
+        .read(&to_read, TimestampsToReturn::Both, 0.0)
         .await
         .unwrap();
+    assert_eq!(res.len(), 500);
+}
 
-    // Different user, anonymous
-    let c3 = tester
-        .connect_and_wait(
-            SecurityPolicy::None,
-            MessageSecurityMode::None,
-            IdentityToken::Anonymous,
-        )
+#[tokio::test]
+async fn client_hello_timeout() {
+    let _ = env_logger::try_init();
+
+    // A server that accepts connections, but never answers.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut streams = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            streams.push(stream);
+        }
+    });
+
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let client = default_client(test_id, false)
+        .pki_dir(format!("./pki-client/{test_id}"))
+        .session_retry_limit(0)
+        .hello_timeout(Duration::from_millis(500))
+        .client()
+        .unwrap();
+
+    let err = tokio::time::timeout(
+        Duration::from_secs(10),
+        client.get_server_endpoints_from_url(&*format!("opc.tcp://127.0.0.1:{port}")),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BadTimeout);
+}
+
+#[tokio::test]
+async fn send_queue_backpressure() {
+    let server = default_server()
+        .max_send_queue_messages(1)
+        .send_rate_limit(RateLimit {
+            bytes_per_second: 1024 * 1024,
+            burst_bytes: Some(64 * 1024),
+        });
+    let mut tester = Tester::new(server, false).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
         .await
         .unwrap();
 
-    // Read the service level a few times
-    let mut val = 100;
-    for _ in 0..5 {
-        val += 10;
-        tester.handle.set_service_level(val);
-        for session in &[c1.clone(), c2.clone(), c3.clone()] {
-            let value = session
-                .read(
-                    &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
-                        VariableId::Server_ServiceLevel,
-                    ))],
-                    TimestampsToReturn::Both,
-                    0.0,
-                )
+    let to_read: Vec<_> = (0..2000)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect();
+
+    // Responses wait for the queue to drain, but all of them are delivered.
+    let mut reads = tokio::task::JoinSet::new();
+    for _ in 0..10 {
+        let session = session.clone();
+        let to_read = to_read.clone();
+        reads.spawn(async move {
+            session
+                .read(&to_read, TimestampsToReturn::Both, 0.0)
                 .await
-                .unwrap();
-            let Some(Variant::Byte(v)) = value[0].value else {
-                panic!("Wrong result type");
-            };
-            assert_eq!(val, v);
+                .unwrap()
+        });
+    }
+    while let Some(res) = reads.join_next().await {
+        assert_eq!(res.unwrap().len(), 2000);
+    }
+}
+
+/// Reports the request handle of every large Read request as it is handed to the send buffer.
+struct LargeReadSent(tokio::sync::mpsc::UnboundedSender<u32>);
+
+impl MessageInterceptor for LargeReadSent {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        if let RequestMessage::Read(read) = request {
+            let len = read.nodes_to_read.as_ref().map_or(0, |n| n.len());
+            if info.direction == MessageDirection::Outgoing && len > 1000 {
+                let _ = self.0.send(info.request_handle);
+            }
         }
+        Ok(())
     }
 }
 
+async fn next_read_sent(rx: &mut tokio::sync::mpsc::UnboundedReceiver<u32>) -> u32 {
+    tokio::time::timeout(Duration::from_secs(20), rx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
 #[tokio::test]
-async fn recoverable_error_test_server() {
-    // Test that if we send a too large message to the server, we don't lose the connection
-    // entirely.
-    let mut server = test_server();
-    server = server.max_array_length(50);
-    let mut tester = Tester::new(server, false).await;
-    let (session, lp) = tester.connect_default().await.unwrap();
-    lp.spawn();
-    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+async fn abort_in_flight_request() {
+    let (sent_tx, mut sent_rx) = tokio::sync::mpsc::unbounded_channel();
+    let client = default_client(0, false).interceptor(LargeReadSent(sent_tx));
+    let mut tester = Tester::new_custom_client(test_server(), client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
         .await
         .unwrap();
 
-    let ids = (0..100)
+    let to_read: Vec<_> = (0..50_000)
         .map(|_| {
             ReadValueId::from(<VariableId as Into<NodeId>>::into(
                 VariableId::Server_ServiceLevel,
             ))
         })
-        .collect::<Vec<_>>();
+        .collect();
+
+    // Abort a large request once it is in flight.
+    let read = Read::new(&session).nodes_to_read(to_read.clone());
+    let request_handle = read.header().request_handle;
+    let session_ref = session.clone();
+    let task = tokio::spawn(async move { read.send(session_ref.channel()).await });
+    assert_eq!(next_read_sent(&mut sent_rx).await, request_handle);
+    session.channel().abort_request(request_handle);
+    let err = task.await.unwrap().unwrap_err();
+    assert_eq!(err, StatusCode::BadRequestCancelledByClient);
+
+    // Drop a large request once it is in flight, before it completes.
+    let session_ref = session.clone();
+    let nodes = to_read.clone();
+    let task = tokio::spawn(async move {
+        session_ref
+            .read(&nodes, TimestampsToReturn::Both, 0.0)
+            .await
+    });
+    next_read_sent(&mut sent_rx).await;
+    task.abort();
+    assert!(task.await.unwrap_err().is_cancelled());
 
+    // The channel is still usable afterwards.
     let res = session
-        .read(&ids, TimestampsToReturn::Both, 0.0)
+        .read(&to_read[..10], TimestampsToReturn::Both, 0.0)
         .await
-        .unwrap_err();
-    assert_eq!(res, StatusCode::BadDecodingError);
+        .unwrap();
+    assert_eq!(res.len(), 10);
+}
+
+struct RenewalCounter(Arc<AtomicU32>);
+
+impl TokenRenewalObserver for RenewalCounter {
+    fn on_token_renewed(&self, _token: &ChannelSecurityToken) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[tokio::test]
+async fn renew_token_with_policy() {
+    let renewals = Arc::new(AtomicU32::new(0));
+    let client = default_client(0, false)
+        .channel_lifetime(1000)
+        .token_renewal_threshold(50)
+        .token_renewal_observer(RenewalCounter(renewals.clone()));
+    let mut tester = Tester::new_custom_client(default_server(), client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
 
+    // Renewal happens when sending a request after half the lifetime has elapsed.
+    tokio::time::sleep(Duration::from_millis(600)).await;
     session
         .read(
             &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
@@ -400,36 +610,807 @@ async fn recoverable_error_test_server() {
         )
         .await
         .unwrap();
+    assert!(renewals.load(Ordering::Relaxed) >= 1);
 }
 
-struct IssuedTokenAuthenticator;
+#[derive(Default)]
+struct CountingMetrics {
+    bytes_received: AtomicUsize,
+    bytes_sent: AtomicUsize,
+    chunks_received: AtomicUsize,
+    messages_received: AtomicUsize,
+    messages_sent: AtomicUsize,
+}
 
-#[async_trait]
-impl AuthManager for IssuedTokenAuthenticator {
-    fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy> {
-        if endpoint.path == "/issued_token" {
-            vec![UserTokenPolicy {
-                policy_id: issued_token_security_policy(endpoint),
-                token_type: UserTokenType::IssuedToken,
-                issued_token_type: opcua::types::issued_token_types::JSON_WEB_TOKEN.into(),
-                // Yes this is JSON in a string. The real thing would have a lot more fields.
-                issuer_endpoint_url: "{\"ua:tokenEndpoint\": \"example.com/token\"}".into(),
-                security_policy_uri: UAString::null(),
-            }]
-        } else {
-            vec![]
-        }
+impl TransportMetrics for CountingMetrics {
+    fn bytes_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
     }
 
-    async fn authenticate_issued_identity_token(
-        &self,
-        _endpoint: &ServerEndpoint,
-        token: &ByteString,
-    ) -> Result<UserToken, Error> {
-        let token_str =
-            String::from_utf8(token.value.clone().unwrap_or_default()).map_err(Error::decoding)?;
-        if token_str == "valid" {
-            Ok(UserToken("valid".into()))
+    fn bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn chunk_received(&self) {
+        self.chunks_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn message_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn message_sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[tokio::test]
+async fn transport_metrics() {
+    let client_metrics = Arc::new(CountingMetrics::default());
+    let server_metrics = Arc::new(CountingMetrics::default());
+    let server = default_server().with_transport_metrics(server_metrics.clone());
+    let client = default_client(0, false).transport_metrics(client_metrics.clone());
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+
+    for metrics in [&client_metrics, &server_metrics] {
+        // At least open secure channel, create and activate session, and read.
+        assert!(metrics.messages_sent.load(Ordering::Relaxed) >= 4);
+        assert!(metrics.messages_received.load(Ordering::Relaxed) >= 4);
+        assert!(metrics.chunks_received.load(Ordering::Relaxed) >= 4);
+        assert!(metrics.bytes_sent.load(Ordering::Relaxed) > 0);
+        assert!(metrics.bytes_received.load(Ordering::Relaxed) > 0);
+    }
+}
+
+#[tokio::test]
+async fn request_metrics() {
+    let metrics = Arc::new(RequestMetrics::new());
+    let client = default_client(0, false).request_metrics(metrics.clone());
+    let mut tester = Tester::new_custom_client(default_server(), client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+    assert!(Arc::ptr_eq(session.request_metrics(), &metrics));
+    assert_eq!(
+        metrics
+            .snapshot()
+            .service("ActivateSession")
+            .unwrap()
+            .requests,
+        1
+    );
+    // The session may still be reading server information in the background.
+    metrics.reset();
+
+    let to_read = [ReadValueId::from(<VariableId as Into<NodeId>>::into(
+        VariableId::Server_ServiceLevel,
+    ))];
+    for _ in 0..3 {
+        session
+            .read(&to_read, TimestampsToReturn::Both, 0.0)
+            .await
+            .unwrap();
+    }
+    let err = session
+        .read(&to_read, TimestampsToReturn::Both, -1.0)
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadMaxAgeInvalid);
+
+    let snapshot = metrics.snapshot();
+    let read = snapshot.service("Read").unwrap();
+    assert!(read.requests >= 4);
+    assert_eq!(read.errors, vec![(StatusCode::BadMaxAgeInvalid, 1)]);
+    assert_eq!(read.latency.count, read.requests);
+    assert_eq!(read.latency.counts.iter().sum::<u64>(), read.requests);
+    assert!(read.latency.sum > Duration::ZERO);
+
+    let text = snapshot.to_prometheus();
+    assert!(text.contains(&format!(
+        "opcua_client_requests_total{{service=\"Read\"}} {}\n",
+        read.requests
+    )));
+    assert!(text.contains(
+        "opcua_client_request_errors_total{service=\"Read\",status=\"BadMaxAgeInvalid\"} 1\n"
+    ));
+    assert!(text.contains(&format!(
+        "opcua_client_request_duration_seconds_count{{service=\"Read\"}} {}\n",
+        read.requests
+    )));
+}
+
+/// Records the messages it sees, and rejects requests for a given service.
+#[derive(Default)]
+struct RecordingInterceptor {
+    reject: Option<&'static str>,
+    seen: Mutex<Vec<(MessageDirection, &'static str, usize)>>,
+    timeout_hints: Mutex<Vec<(&'static str, u32)>>,
+}
+
+impl RecordingInterceptor {
+    fn seen(&self, direction: MessageDirection, service: &str) -> bool {
+        self.seen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(d, s, size)| *d == direction && *s == service && *size > 0)
+    }
+}
+
+impl MessageInterceptor for RecordingInterceptor {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        self.seen
+            .lock()
+            .unwrap()
+            .push((info.direction, info.service, info.size));
+        self.timeout_hints
+            .lock()
+            .unwrap()
+            .push((info.service, request.request_header().timeout_hint));
+        if self.reject == Some(info.service) {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        info: &InterceptedMessage,
+        _response: &ResponseMessage,
+    ) -> Result<(), StatusCode> {
+        self.seen
+            .lock()
+            .unwrap()
+            .push((info.direction, info.service, info.size));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn message_interceptors() {
+    let client_interceptor = Arc::new(RecordingInterceptor::default());
+    let server_interceptor = Arc::new(RecordingInterceptor {
+        reject: Some("Write"),
+        ..Default::default()
+    });
+    let server = default_server().with_interceptor(server_interceptor.clone());
+    let client = default_client(0, false).interceptor(client_interceptor.clone());
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let node_id: NodeId = VariableId::Server_ServiceLevel.into();
+    session
+        .read(
+            &[ReadValueId::from(node_id.clone())],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+
+    // The server rejects the write without processing it.
+    let err = session
+        .write(&[WriteValue {
+            node_id,
+            attribute_id: AttributeId::Value as u32,
+            value: DataValue::new_now(0u8),
+            ..Default::default()
+        }])
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadUserAccessDenied);
+
+    assert!(client_interceptor.seen(MessageDirection::Outgoing, "Read"));
+    assert!(client_interceptor.seen(MessageDirection::Incoming, "Read"));
+    assert!(client_interceptor.seen(MessageDirection::Outgoing, "Write"));
+    assert!(client_interceptor.seen(MessageDirection::Incoming, "ServiceFault"));
+    assert!(server_interceptor.seen(MessageDirection::Incoming, "Read"));
+    assert!(server_interceptor.seen(MessageDirection::Outgoing, "Read"));
+    assert!(server_interceptor.seen(MessageDirection::Incoming, "Write"));
+    assert!(!server_interceptor.seen(MessageDirection::Outgoing, "Write"));
+}
+
+#[tokio::test]
+async fn record_and_replay_requests() {
+    let dir = TempDir::new("recording").unwrap();
+    let path = dir.path().join("requests.jsonl");
+    let recorder = Arc::new(MessageRecorder::create(&path, RecordFormat::JsonLines).unwrap());
+    let client = default_client(0, false).interceptor(recorder.clone());
+    let mut tester = Tester::new_custom_client(default_server(), client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let node_id: NodeId = VariableId::Server_ServiceLevel.into();
+    let value = session
+        .read(
+            &[ReadValueId::from(node_id.clone())],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    recorder.flush().unwrap();
+
+    let ctx_owned = ContextOwned::default();
+    let ctx = ctx_owned.context();
+    let messages = RecordingReader::open(&path)
+        .unwrap()
+        .read_all(&ctx)
+        .unwrap();
+    let read = messages
+        .iter()
+        .filter(|m| m.direction == MessageDirection::Outgoing && m.service == "Read")
+        .find_map(|m| match m.request(&ctx).unwrap() {
+            Some(RequestMessage::Read(r))
+                if r.nodes_to_read.as_ref().unwrap()[0].node_id == node_id =>
+            {
+                Some((m, r))
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(read.0.security_policy, SecurityPolicy::Basic256Sha256);
+    assert_eq!(read.0.security_mode, MessageSecurityMode::SignAndEncrypt);
+    assert_ne!(read.0.secure_channel_id, 0);
+    // Credentials are redacted by default.
+    assert!(read.1.request_header.authentication_token.is_null());
+    let activate = messages
+        .iter()
+        .find_map(|m| match m.request(&ctx).unwrap() {
+            Some(RequestMessage::ActivateSession(r)) => Some(r),
+            _ => None,
+        })
+        .unwrap();
+    assert!(activate.user_identity_token.is_null());
+
+    // Replaying the recording sends the read again, on the current session.
+    let replayed = Replayer::new().replay(&session, &messages).await.unwrap();
+    assert!(replayed
+        .iter()
+        .all(|r| r.recorded.service != "ActivateSession"));
+    let replayed_read = replayed
+        .iter()
+        .find(|r| r.recorded.request_handle == read.0.request_handle)
+        .unwrap();
+    let Some(ResponseMessage::Read(recorded)) = &replayed_read.recorded_response else {
+        panic!("Expected recorded read response");
+    };
+    assert_eq!(recorded.results.as_ref().unwrap()[0].value, value[0].value);
+    let Ok(ResponseMessage::Read(response)) = &replayed_read.response else {
+        panic!("Expected read response, got {:?}", replayed_read.response);
+    };
+    assert_eq!(response.results.as_ref().unwrap()[0].value, value[0].value);
+}
+
+#[tokio::test]
+async fn endpoint_selector() {
+    let client = default_client(0, false).endpoint_selector(RequireSecurity::new(
+        SecurityPolicy::Basic256Sha256,
+        MessageSecurityMode::SignAndEncrypt,
+    ));
+    let mut tester = Tester::new_custom_client(default_server(), client).await;
+    let url = tester.endpoint();
+
+    let best = tester.client.get_best_endpoint(url.as_str()).await.unwrap();
+    assert_eq!(best.security_mode, MessageSecurityMode::SignAndEncrypt);
+    assert_eq!(
+        best.security_policy_uri.as_ref(),
+        SecurityPolicy::Basic256Sha256.to_uri()
+    );
+
+    let (session, handle) = tester
+        .client
+        .connect_to_selected_endpoint(&url, client_user_token())
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+    let endpoint = &session.endpoint_info().endpoint;
+    assert_eq!(endpoint.security_mode, MessageSecurityMode::SignAndEncrypt);
+    assert_eq!(
+        endpoint.security_policy_uri.as_ref(),
+        SecurityPolicy::Basic256Sha256.to_uri()
+    );
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+
+    // A selector rejecting every endpoint fails the connection.
+    let mut client = default_client(0, false)
+        .endpoint_selector(ScoreEndpoints::new(|_: &EndpointDescription| None))
+        .client()
+        .unwrap();
+    let Err(err) = client
+        .connect_to_selected_endpoint(&url, IdentityToken::Anonymous)
+        .await
+    else {
+        panic!("Expected connection to fail");
+    };
+    assert_eq!(err.status(), StatusCode::BadSecurityPolicyRejected);
+}
+
+#[tokio::test]
+async fn connect_simple() {
+    let mut tester = Tester::new_custom_client(default_server(), default_client(0, false)).await;
+    let url = tester.endpoint();
+
+    // Securing an opc.tcp channel needs the server certificate from GetEndpoints.
+    let Err(err) = tester.client.connect_simple(
+        &url,
+        SecurityPolicy::Basic256Sha256,
+        IdentityToken::Anonymous,
+    ) else {
+        panic!("Expected connection to fail");
+    };
+    assert_eq!(err.status(), StatusCode::BadSecurityPolicyRejected);
+
+    for token in [IdentityToken::Anonymous, client_user_token()] {
+        let (session, handle) = tester
+            .client
+            .connect_simple(&url, SecurityPolicy::None, token)
+            .unwrap();
+        let _h = handle.spawn();
+        tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+            .await
+            .unwrap();
+        // The user token policies come from the endpoints returned in CreateSession.
+        let endpoint = &session.current_endpoint_info().endpoint;
+        assert_eq!(endpoint.endpoint_url.as_ref(), url);
+        assert_eq!(endpoint.security_mode, MessageSecurityMode::None);
+        assert!(!endpoint.user_identity_tokens.as_ref().unwrap().is_empty());
+        session
+            .read(
+                &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                    VariableId::Server_ServiceLevel,
+                ))],
+                TimestampsToReturn::Both,
+                0.0,
+            )
+            .await
+            .unwrap();
+        session.disconnect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn alternate_connection_paths() {
+    let tester = Tester::new_default_server(false).await;
+    let endpoint = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|e| e.security_mode == MessageSecurityMode::None)
+        .unwrap();
+
+    // Nothing listens on the primary path.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let primary_url = format!(
+        "opc.tcp://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    drop(listener);
+
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .connect_to_endpoint_directly(EndpointDescription {
+            endpoint_url: primary_url.into(),
+            ..endpoint
+        })
+        .unwrap()
+        .alternate_urls(vec![tester.endpoint()])
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+    let mut events = session.connection_events();
+    let _h = event_loop.spawn();
+
+    let mut received = Vec::new();
+    while !matches!(received.last(), Some(ConnectionEvent::PathChanged { .. })) {
+        let event = tokio::time::timeout(Duration::from_secs(20), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(event);
+    }
+    assert_eq!(
+        received,
+        vec![
+            ConnectionEvent::Connecting,
+            ConnectionEvent::Connected,
+            ConnectionEvent::PathChanged {
+                priority: 1,
+                url: tester.endpoint(),
+            },
+        ]
+    );
+    assert_eq!(session.connection_path(), 1);
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn request_timeout_override_and_cancel() {
+    let server_interceptor = Arc::new(RecordingInterceptor::default());
+    let server = default_server().with_interceptor(server_interceptor.clone());
+    let mut tester = Tester::new_custom_client(server, default_client(0, false)).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let node_id: NodeId = VariableId::Server_ServiceLevel.into();
+    session
+        .with_timeout(
+            Duration::from_secs(123),
+            session.read(
+                &[ReadValueId::from(node_id.clone())],
+                TimestampsToReturn::Both,
+                0.0,
+            ),
+        )
+        .await
+        .unwrap();
+    assert!(server_interceptor
+        .timeout_hints
+        .lock()
+        .unwrap()
+        .contains(&("Read", 123_000)));
+
+    // The server answers cancels of requests that are no longer in progress.
+    assert_eq!(session.cancel(12345).await, Ok(0));
+
+    // Dropping a request before it completes cancels it on the server.
+    let to_read = vec![ReadValueId::from(node_id); 50_000];
+    let _ = tokio::time::timeout(
+        Duration::from_millis(5),
+        session.read(&to_read, TimestampsToReturn::Both, 0.0),
+    )
+    .await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !server_interceptor.seen(MessageDirection::Incoming, "Cancel") {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn connect_basic128rsa_15_with_invalid_token() {
+    let mut tester = Tester::new_default_server(true).await;
+    let (_, handle) = tester
+        .connect(
+            SecurityPolicy::Basic128Rsa15,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::UserName(CLIENT_USERPASS_ID.to_owned(), "invalid".into()),
+        )
+        .await
+        .unwrap();
+    let res = handle.spawn().await.unwrap();
+    assert_eq!(res, StatusCode::BadIdentityTokenRejected);
+}
+
+#[tokio::test]
+async fn find_servers() {
+    let tester = Tester::new_default_server(true).await;
+    let servers = tester
+        .client
+        .find_servers(tester.endpoint(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(servers.len(), 1);
+
+    let s = &servers[0];
+    let discovery_urls = s.discovery_urls.as_ref().unwrap();
+    assert!(!discovery_urls.is_empty());
+    assert_eq!(s.application_type, ApplicationType::Server);
+    assert_eq!(s.application_name.text.as_ref(), "integration_server");
+    assert_eq!(s.application_uri.as_ref(), "urn:integration_server");
+    assert_eq!(s.product_uri.as_ref(), "urn:integration_server Testkit");
+}
+
+#[tokio::test]
+async fn discovery_test() {
+    let tester = Tester::new_default_server(true).await;
+    // Get all
+    let endpoints = tester
+        .client
+        .get_endpoints(tester.endpoint(), &[], &[])
+        .await
+        .unwrap();
+    assert_eq!(endpoints.len(), 11);
+
+    // Get with wrong profile URIs
+    let endpoints = tester
+        .client
+        .get_endpoints(tester.endpoint(), &[], &["wrongwrong"])
+        .await
+        .unwrap();
+    assert!(endpoints.is_empty());
+
+    // Get all binary endpoints (all of them)
+    let endpoints = tester
+        .client
+        .get_endpoints(
+            tester.endpoint(),
+            &[],
+            &["http://opcfoundation.org/UA-Profile/Transport/uatcp-uasc-uabinary"],
+        )
+        .await
+        .unwrap();
+    assert_eq!(endpoints.len(), 11);
+}
+
+#[tokio::test]
+async fn multi_client_test() {
+    // Simple multi-client test, checking that we can send and receive requests with multiple clients
+    // to the same server, and also that the client SDK can handle multiple sessions in the same client.
+    let mut tester = Tester::new_default_server(true).await;
+
+    let c1 = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic128Rsa15,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::UserName(
+                CLIENT_USERPASS_ID.to_owned(),
+                format!("{CLIENT_USERPASS_ID}_password").into(),
+            ),
+        )
+        .await
+        .unwrap();
+    // Same user token, should still be fine
+    let c2 = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::UserName(
+                CLIENT_USERPASS_ID.to_owned(),
+                format!("{CLIENT_USERPASS_ID}_password").into(),
+            ),
+        )
+        .await
+        .unwrap();
+
+    // Different user, anonymous
+    let c3 = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+
+    // Read the service level a few times
+    let mut val = 100;
+    for _ in 0..5 {
+        val += 10;
+        tester.handle.set_service_level(val);
+        for session in &[c1.clone(), c2.clone(), c3.clone()] {
+            let value = session
+                .read(
+                    &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                        VariableId::Server_ServiceLevel,
+                    ))],
+                    TimestampsToReturn::Both,
+                    0.0,
+                )
+                .await
+                .unwrap();
+            let Some(Variant::Byte(v)) = value[0].value else {
+                panic!("Wrong result type");
+            };
+            assert_eq!(val, v);
+        }
+    }
+}
+
+#[tokio::test]
+async fn recoverable_error_test_server() {
+    // Test that if we send a too large message to the server, we don't lose the connection
+    // entirely.
+    let mut server = test_server();
+    server = server.max_array_length(50);
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let ids = (0..100)
+        .map(|_| {
+            ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let res = session
+        .read(&ids, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap_err();
+    assert_eq!(res, StatusCode::BadDecodingError);
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+/// Authenticator with an X509 user token policy that does not set a security policy,
+/// so the security policy of the channel is used to sign the token.
+struct X509ChannelPolicyAuthenticator;
+
+#[async_trait]
+impl AuthManager for X509ChannelPolicyAuthenticator {
+    fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy> {
+        if endpoint.path == "/x509" {
+            vec![UserTokenPolicy {
+                // The server only accepts X509 tokens with this policy ID.
+                policy_id: "x509".into(),
+                token_type: UserTokenType::Certificate,
+                issued_token_type: UAString::null(),
+                issuer_endpoint_url: UAString::null(),
+                security_policy_uri: UAString::null(),
+            }]
+        } else {
+            vec![]
+        }
+    }
+
+    async fn authenticate_x509_identity_token(
+        &self,
+        _endpoint: &ServerEndpoint,
+        _signing_thumbprint: &Thumbprint,
+    ) -> Result<UserToken, Error> {
+        Ok(UserToken("x509".into()))
+    }
+}
+
+#[tokio::test]
+async fn x509_token_channel_policy_test() {
+    let server = test_server()
+        .add_endpoint(
+            "x509",
+            (
+                "/x509",
+                SecurityPolicy::Basic256Sha256,
+                MessageSecurityMode::SignAndEncrypt,
+                &[] as &[&str],
+            ),
+        )
+        .with_authenticator(Arc::new(X509ChannelPolicyAuthenticator));
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester
+        .connect_path(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            client_x509_token().unwrap(),
+            "x509",
+        )
+        .await
+        .unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(5), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+struct IssuedTokenAuthenticator;
+
+#[async_trait]
+impl AuthManager for IssuedTokenAuthenticator {
+    fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy> {
+        if endpoint.path == "/issued_token" {
+            vec![UserTokenPolicy {
+                policy_id: issued_token_security_policy(endpoint),
+                token_type: UserTokenType::IssuedToken,
+                issued_token_type: opcua::types::issued_token_types::JSON_WEB_TOKEN.into(),
+                // Yes this is JSON in a string. The real thing would have a lot more fields.
+                issuer_endpoint_url: "{\"ua:tokenEndpoint\": \"example.com/token\"}".into(),
+                security_policy_uri: UAString::null(),
+            }]
+        } else {
+            vec![]
+        }
+    }
+
+    async fn authenticate_issued_identity_token(
+        &self,
+        _endpoint: &ServerEndpoint,
+        token: &ByteString,
+    ) -> Result<UserToken, Error> {
+        let token_str =
+            String::from_utf8(token.value.clone().unwrap_or_default()).map_err(Error::decoding)?;
+        if token_str == "valid" {
+            Ok(UserToken("valid".into()))
         } else {
             Err(Error::new(
                 StatusCode::BadIdentityTokenRejected,
@@ -440,7 +1421,49 @@ impl AuthManager for IssuedTokenAuthenticator {
 }
 
 #[tokio::test]
-async fn issued_token_test() {
+async fn issued_token_test() {
+    let server = test_server()
+        .add_endpoint(
+            "issued_token",
+            (
+                "/issued_token",
+                SecurityPolicy::Aes128Sha256RsaOaep,
+                MessageSecurityMode::SignAndEncrypt,
+                &[] as &[&str],
+            ),
+        )
+        .with_authenticator(Arc::new(IssuedTokenAuthenticator));
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester
+        .connect_path(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::IssuedToken(IssuedTokenWrapper::new_source(ByteString::from(
+                "valid".as_bytes(),
+            ))),
+            "issued_token",
+        )
+        .await
+        .unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(5), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn issued_token_callback_test() {
     let server = test_server()
         .add_endpoint(
             "issued_token",
@@ -453,13 +1476,20 @@ async fn issued_token_test() {
         )
         .with_authenticator(Arc::new(IssuedTokenAuthenticator));
     let mut tester = Tester::new(server, false).await;
+    // The first token is rejected, the session should be activated with a new token.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_ref = calls.clone();
     let (session, lp) = tester
         .connect_path(
             SecurityPolicy::Aes128Sha256RsaOaep,
             MessageSecurityMode::SignAndEncrypt,
-            IdentityToken::IssuedToken(IssuedTokenWrapper::new_source(ByteString::from(
-                "valid".as_bytes(),
-            ))),
+            IdentityToken::new_issued_token_fn(move || {
+                let call = calls_ref.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    let token = if call == 0 { "expired" } else { "valid" };
+                    Ok(ByteString::from(token.as_bytes()))
+                }
+            }),
             "issued_token",
         )
         .await
@@ -468,8 +1498,635 @@ async fn issued_token_test() {
     tokio::time::timeout(Duration::from_secs(5), session.wait_for_connection())
         .await
         .unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn session_pool_test() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let client = std::mem::replace(
+        &mut tester.client,
+        default_client(tester.test_id, false).client().unwrap(),
+    );
+    let pool = SessionPool::connect_to_matching_endpoint(
+        client,
+        (
+            &tester.endpoint() as &str,
+            SecurityPolicy::None.to_str(),
+            MessageSecurityMode::None,
+        ),
+        IdentityToken::Anonymous,
+        2,
+        2,
+    )
+    .await
+    .unwrap();
+    for session in pool.sessions() {
+        tokio::time::timeout(Duration::from_secs(5), session.wait_for_connection())
+            .await
+            .unwrap();
+    }
+
+    // Checkouts are spread over the two sessions.
+    let first = pool.checkout().await.unwrap();
+    let second = pool.checkout().await.unwrap();
+    assert!(!Arc::ptr_eq(first.session(), second.session()));
+    let third = pool.checkout().await.unwrap();
+    let _fourth = pool.checkout().await.unwrap();
+
+    // The pool is exhausted, so the next checkout waits until a session is returned.
+    let pool_ref = pool.clone();
+    let waiting = tokio::spawn(async move { pool_ref.checkout().await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+    let returned = third.session().clone();
+    drop(third);
+    let fifth = tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(Arc::ptr_eq(fifth.session(), &returned));
+
+    fifth
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    drop((first, second, fifth));
+
+    pool.close().await;
+    assert_eq!(pool.checkout().await.err(), Some(StatusCode::BadShutdown));
+}
+
+#[derive(Default)]
+struct AuthTokenInterceptor {
+    tokens: Mutex<Vec<(&'static str, NodeId)>>,
+}
+
+impl MessageInterceptor for AuthTokenInterceptor {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        self.tokens.lock().unwrap().push((
+            info.service,
+            request.request_header().authentication_token.clone(),
+        ));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn sessionless_invoke() {
+    let interceptor = Arc::new(AuthTokenInterceptor::default());
+    let server = test_server().with_interceptor(interceptor.clone());
+    let tester = Tester::new(server, false).await;
+    let endpoint = tester.endpoint();
+    let channel = tester
+        .client
+        .connect_sessionless(
+            &endpoint as &str,
+            &(
+                &endpoint as &str,
+                SecurityPolicy::None.to_str(),
+                MessageSecurityMode::None,
+            )
+                .into(),
+            "access-token",
+        )
+        .await
+        .unwrap();
+    assert_eq!(channel.auth_token(), &NodeId::new(0, "access-token"));
+
+    // The test server does not support session-less invocation, so it rejects
+    // the requests, but they are sent with the access token and no session.
+    let node_id: NodeId = VariableId::Server_ServiceLevel.into();
+    let err = channel
+        .read(
+            &[ReadValueId::from(node_id.clone())],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadSessionIdInvalid);
+    let err = channel
+        .write(&[WriteValue {
+            node_id,
+            attribute_id: AttributeId::Value as u32,
+            value: DataValue::new_now(0u8),
+            ..Default::default()
+        }])
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadSessionIdInvalid);
+    channel.close().await;
+
+    let tokens = interceptor.tokens.lock().unwrap();
+    assert!(!tokens.iter().any(|(s, _)| *s == "CreateSession"));
+    for service in ["Read", "Write"] {
+        assert!(tokens
+            .iter()
+            .any(|(s, t)| *s == service && *t == NodeId::new(0, "access-token")));
+    }
+}
+
+#[tokio::test]
+async fn reconnect_strategy_rediscovers_endpoint() {
+    let tester = Tester::new(test_server(), false).await;
+    let endpoint = tester.endpoint();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_ref = events.clone();
+    let strategy = ExponentialReconnect::new(Duration::from_millis(50), Duration::from_millis(200))
+        .max_attempts(Some(5))
+        .rediscover_after(Some(1))
+        .on_event(move |e| events_ref.lock().unwrap().push(e.clone()));
+
+    // The endpoint has the wrong server certificate, so connecting fails until
+    // the endpoint is refreshed from the server.
+    let wrong_cert = tester
+        .client
+        .certificate_store()
+        .read()
+        .read_own_cert()
+        .unwrap()
+        .as_byte_string();
+    let mut endpoint_description: EndpointDescription = (
+        &endpoint as &str,
+        SecurityPolicy::Basic256Sha256.to_str(),
+        MessageSecurityMode::SignAndEncrypt,
+    )
+        .into();
+    endpoint_description.server_certificate = wrong_cert.clone();
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .connect_to_endpoint_directly(endpoint_description)
+        .unwrap()
+        .reconnect_strategy(strategy)
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+    let _h = event_loop.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+    assert_ne!(
+        session.current_endpoint_info().endpoint.server_certificate,
+        wrong_cert
+    );
+    assert_eq!(
+        session.endpoint_info().endpoint.server_certificate,
+        wrong_cert
+    );
+
+    let events = events.lock().unwrap().clone();
+    assert!(matches!(
+        events[0],
+        ReconnectEvent::AttemptFailed {
+            attempt: 1,
+            decision: ReconnectDecision::RediscoverAndRetry(_),
+            ..
+        }
+    ));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, ReconnectEvent::Rediscovered { .. })));
+    assert!(matches!(
+        events.last(),
+        Some(ReconnectEvent::Connected { .. })
+    ));
+
+    session.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn reconnect_strategy_gives_up() {
+    let tester = Tester::new(test_server(), false).await;
+    let attempts = Arc::new(AtomicU32::new(0));
+    let attempts_ref = attempts.clone();
+    let strategy = ExponentialReconnect::new(Duration::from_millis(10), Duration::from_millis(50))
+        .max_attempts(Some(3))
+        .on_event(move |e| {
+            if matches!(e, ReconnectEvent::AttemptFailed { .. }) {
+                attempts_ref.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+    // Nothing listens on this port.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "opc.tcp://127.0.0.1:{}",
+        listener.local_addr().unwrap().port()
+    );
+    drop(listener);
+
+    let (_session, event_loop) = tester
+        .client
+        .session_builder()
+        .connect_to_endpoint_directly((
+            &url as &str,
+            SecurityPolicy::None.to_str(),
+            MessageSecurityMode::None,
+        ))
+        .unwrap()
+        .reconnect_strategy(strategy)
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+
+    let status = tokio::time::timeout(Duration::from_secs(20), event_loop.run())
+        .await
+        .unwrap();
+    assert!(status.is_bad());
+    assert_eq!(attempts.load(Ordering::Relaxed), 3);
+}
+
+#[tokio::test]
+async fn preferred_locales() {
+    let tester = Tester::new(test_server(), false).await;
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::Aes256Sha256RsaPss.to_str(),
+            MessageSecurityMode::SignAndEncrypt,
+        ))
+        .unwrap()
+        .preferred_locales(vec!["de".to_owned(), "en".to_owned()])
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+    let _h = event_loop.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let locales_on_server = || {
+        let server_session = tester
+            .handle
+            .session_manager()
+            .read()
+            .find_by_id(&session.server_session_id())
+            .unwrap();
+        let server_session = server_session.read();
+        server_session
+            .locale_ids()
+            .unwrap_or_default()
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(locales_on_server(), vec!["de", "en"]);
+    assert_eq!(session.server_locales().await.unwrap(), vec!["en"]);
+
+    // Changing the locales activates the session again.
+    session
+        .set_preferred_locales(vec!["fr".to_owned()])
+        .await
+        .unwrap();
+    assert_eq!(session.preferred_locales(), vec!["fr"]);
+    assert_eq!(locales_on_server(), vec!["fr"]);
+    let value = session
+        .read(
+            &[ReadValueId::new_value(
+                VariableId::Server_ServiceLevel.into(),
+            )],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert!(value[0].value.is_some());
+}
+
+#[tokio::test]
+async fn change_identity() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Aes256Sha256RsaPss,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let user_on_server = || {
+        let server_session = tester
+            .handle
+            .session_manager()
+            .read()
+            .find_by_id(&session.server_session_id())
+            .unwrap();
+        let server_session = server_session.read();
+        server_session.user_token().map(|t| t.0.clone())
+    };
+    let anonymous = user_on_server().unwrap();
+
+    let (notifs, _data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    // Log in, the subscription is kept.
+    session.change_identity(client_user_token()).await.unwrap();
+    assert_eq!(user_on_server().unwrap(), CLIENT_USERPASS_ID);
+    assert!(matches!(
+        session.current_endpoint_info().user_identity_token,
+        IdentityToken::UserName(..)
+    ));
+    let res = session.set_publishing_mode(&[sub_id], true).await.unwrap();
+    assert_eq!(res, vec![StatusCode::Good]);
+
+    // An invalid token is rejected, and the session keeps the previous user.
+    let err = session
+        .change_identity(IdentityToken::UserName(
+            CLIENT_USERPASS_ID.to_owned(),
+            "wrong".into(),
+        ))
+        .await
+        .unwrap_err();
+    assert!(err.is_bad());
+    assert_eq!(user_on_server().unwrap(), CLIENT_USERPASS_ID);
+    assert!(matches!(
+        session.current_endpoint_info().user_identity_token,
+        IdentityToken::UserName(_, ref p) if p.0 != "wrong"
+    ));
+
+    // Log out again.
+    session
+        .change_identity(IdentityToken::Anonymous)
+        .await
+        .unwrap();
+    assert_eq!(user_on_server().unwrap(), anonymous);
+    let res = session.set_publishing_mode(&[sub_id], false).await.unwrap();
+    assert_eq!(res, vec![StatusCode::Good]);
+    let value = session
+        .read(
+            &[ReadValueId::new_value(
+                VariableId::Server_ServiceLevel.into(),
+            )],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert!(value[0].value.is_some());
+}
+
+#[derive(Default)]
+struct InflightCounter {
+    current: AtomicUsize,
+    max: AtomicUsize,
+}
+
+impl MessageInterceptor for InflightCounter {
+    fn on_request(
+        &self,
+        info: &InterceptedMessage,
+        _request: &RequestMessage,
+    ) -> Result<(), StatusCode> {
+        if info.service == "Read" {
+            let current = self.current.fetch_add(1, Ordering::Relaxed) + 1;
+            self.max.fetch_max(current, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn on_response(
+        &self,
+        info: &InterceptedMessage,
+        _response: &ResponseMessage,
+    ) -> Result<(), StatusCode> {
+        if info.service == "Read" {
+            self.current.fetch_sub(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn request_limit() {
+    let counter = Arc::new(InflightCounter::default());
+    let client = default_client(0, false)
+        .interceptor(counter.clone())
+        .request_limit_options(RequestLimitOptions {
+            max_inflight_requests: 2,
+            overflow: RequestOverflow::Queue,
+        });
+    let mut tester = Tester::new_custom_client(test_server(), client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let nodes = [ReadValueId::new_value(
+        VariableId::Server_ServiceLevel.into(),
+    )];
+    let read = || session.read(&nodes, TimestampsToReturn::Neither, 0.0);
+    let results = futures::future::join_all((0..20).map(|_| read())).await;
+    assert!(results.iter().all(|r| r.is_ok()));
+    assert_eq!(counter.max.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn service_retry() {
+    let client = default_client(0, false).service_retry_options(ServiceRetryOptions {
+        max_retries: 3,
+        ..Default::default()
+    });
+    let mut tester = Tester::new_custom_client(test_server(), client).await;
+    let (session, handle) = tester.connect_default().await.unwrap();
+    let _h = handle.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    let session_id = session.server_session_id();
 
+    // Make the server reject the session, as if it had expired.
     session
+        .channel()
+        .set_auth_token(NodeId::new(0, "invalid-token"));
+
+    // Write is not retried by default.
+    let err = session
+        .write(&[WriteValue {
+            node_id: VariableId::Server_ServiceLevel.into(),
+            attribute_id: AttributeId::Value as u32,
+            value: DataValue::new_now(1u8),
+            ..Default::default()
+        }])
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadSessionIdInvalid);
+
+    // Read is retried in a new session, once the old one cannot be re-activated.
+    let nodes = [ReadValueId::new_value(
+        VariableId::Server_ServiceLevel.into(),
+    )];
+    let results = session
+        .read(&nodes, TimestampsToReturn::Neither, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].status.is_none_or(|s| s.is_good()));
+    assert_ne!(session.server_session_id(), session_id);
+}
+
+/// Act as a server that supports reverse connect: dial the client, send a reverse hello,
+/// then forward the connection to the test server until `stop` is dropped.
+async fn reverse_dial(
+    client: SocketAddr,
+    server: SocketAddr,
+    hello: ReverseHelloMessage,
+    stop: tokio::sync::oneshot::Receiver<()>,
+) {
+    let mut client = TcpStream::connect(client).await.unwrap();
+    client.write_all(&hello.encode_to_vec()).await.unwrap();
+    let mut server = TcpStream::connect(server).await.unwrap();
+    tokio::select! {
+        _ = tokio::io::copy_bidirectional(&mut client, &mut server) => (),
+        _ = stop => (),
+    }
+}
+
+#[tokio::test]
+async fn reverse_connect() {
+    let tester = Tester::new_default_server(false).await;
+    let endpoint = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|e| e.security_mode == MessageSecurityMode::None)
+        .unwrap();
+    let hello = ReverseHelloMessage::new(
+        endpoint.server.application_uri.as_ref(),
+        endpoint.endpoint_url.as_ref(),
+    );
+
+    let listener =
+        ReverseConnectListener::bind("127.0.0.1:0".parse().unwrap(), SocketOptions::default())
+            .unwrap()
+            .wait_timeout(Duration::from_secs(20));
+    let client_addr = listener.local_addr();
+
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .with_connector(listener)
+        .connect_to_endpoint_directly(endpoint.clone())
+        .unwrap()
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+    let _h = event_loop.spawn();
+
+    // A server that no session was created for is rejected.
+    let mut stream = TcpStream::connect(client_addr).await.unwrap();
+    stream
+        .write_all(
+            &ReverseHelloMessage::new("urn:unknown", endpoint.endpoint_url.as_ref())
+                .encode_to_vec(),
+        )
+        .await
+        .unwrap();
+    let mut bytes = BytesMut::with_capacity(1024);
+    stream.read_buf(&mut bytes).await.unwrap();
+    let msg = TcpCodec::new(DecodingOptions::default())
+        .decode(&mut bytes)
+        .unwrap();
+    let Some(Message::Error(msg)) = msg else {
+        panic!("Expected error got {msg:?}");
+    };
+    assert_eq!(msg.error, StatusCode::BadTcpEndpointUrlInvalid);
+
+    let nodes = [ReadValueId::new_value(
+        VariableId::Server_ServiceLevel.into(),
+    )];
+    // The session connects once the server dials the client, and reconnects when
+    // the server dials again after the connection is lost.
+    for _ in 0..2 {
+        let (stop, stop_recv) = tokio::sync::oneshot::channel();
+        let dial = tokio::spawn(reverse_dial(
+            client_addr,
+            tester.addr,
+            hello.clone(),
+            stop_recv,
+        ));
+        tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+            .await
+            .unwrap();
+        session
+            .read(&nodes, TimestampsToReturn::Neither, 0.0)
+            .await
+            .unwrap();
+
+        let mut events = session.connection_events();
+        drop(stop);
+        dial.await.unwrap();
+        while !matches!(
+            tokio::time::timeout(Duration::from_secs(20), events.next())
+                .await
+                .unwrap()
+                .unwrap(),
+            ConnectionEvent::Disconnected { .. }
+        ) {}
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn connect_uds() {
+    let dir = TempDir::new("uds").unwrap();
+    let path = dir.path().join("server.sock");
+    let mut tester = Tester::new(default_server().uds_path(&path), false).await;
+
+    let (session, handle) = tester
+        .client
+        .connect_simple(
+            &format!("opc.uds://{}", path.display()),
+            SecurityPolicy::None,
+            IdentityToken::Anonymous,
+        )
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let res = session
         .read(
             &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
                 VariableId::Server_ServiceLevel,
@@ -479,4 +2136,30 @@ async fn issued_token_test() {
         )
         .await
         .unwrap();
+    assert!(res[0].status.unwrap_or(StatusCode::Good).is_good());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn uds_path_not_a_socket() {
+    let _ = env_logger::try_init();
+
+    let dir = TempDir::new("uds").unwrap();
+    let path = dir.path().join("config.txt");
+    std::fs::write(&path, "not a socket").unwrap();
+
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = default_server()
+        .discovery_urls(vec![format!("opc.tcp://{}:{}", hostname(), port)])
+        .pki_dir(format!("./pki-server/{test_id}"))
+        .uds_path(&path);
+    copy_shared_certs(test_id, &server.config().application_description());
+    let (server, _handle) = server.build().unwrap();
+
+    // The server refuses to start rather than deleting the file.
+    let err = server.run_with(listener).await.unwrap_err();
+    assert!(err.contains("not a socket"), "{err}");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
 }
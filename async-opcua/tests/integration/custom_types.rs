@@ -1,15 +1,17 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use opcua_client::custom_types::DataTypeTreeBuilder;
+use opcua_client::{
+    custom_types::DataTypeTreeBuilder, CustomTypeDiscovery, IdentityToken, Session,
+};
 use opcua_nodes::{AccessLevel, DataTypeBuilder, VariableBuilder};
 use opcua_types::{
     custom::{DynamicStructure, DynamicTypeLoader},
-    DataTypeDefinition, DataTypeId, EUInformation, IntoVariant, LocalizedText, NodeId, ObjectId,
-    ReadValueId, ReferenceTypeId, StructureDefinition, StructureField, StructureType,
-    TimestampsToReturn, TypeLoader, VariableTypeId, Variant,
+    ByteStringBody, DataTypeDefinition, DataTypeId, EUInformation, IntoVariant, LocalizedText,
+    MessageSecurityMode, NodeId, ObjectId, ReadValueId, ReferenceTypeId, StructureDefinition,
+    StructureField, StructureType, TimestampsToReturn, TypeLoader, VariableTypeId, Variant,
 };
 
-use crate::utils::setup;
+use crate::utils::{default_client, setup, test_server};
 
 use super::utils::{TestNodeManager, Tester};
 
@@ -154,3 +156,141 @@ async fn test_data_type_tree_builder() {
     );
     assert!(v.get_field(2).is_none());
 }
+
+/// Add a custom data type and a variable with a value of it, returning the ID of the variable.
+async fn add_custom_variable(nm: &TestNodeManager, tester: &Tester, session: &Session) -> NodeId {
+    let type_id = add_custom_data_type(nm, tester, struct_type_def());
+    let type_tree = Arc::new(
+        DataTypeTreeBuilder::new(|f| f.namespace <= type_id.namespace)
+            .build(session)
+            .await
+            .unwrap(),
+    );
+    let typ = type_tree.get_struct_type(&type_id).unwrap().clone();
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(
+                DynamicStructure::new_struct(
+                    typ,
+                    type_tree,
+                    vec![
+                        EUInformation {
+                            namespace_uri: "some.namespace.uri".into(),
+                            unit_id: 1,
+                            display_name: "Some unit".into(),
+                            description: "This is a unit".into(),
+                        }
+                        .into_variant(),
+                        Variant::Variant(Box::new(5i32.into_variant())),
+                    ],
+                )
+                .unwrap()
+                .into_variant(),
+            )
+            .data_type(type_id)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    id
+}
+
+async fn discovery_tester(mode: CustomTypeDiscovery) -> (Tester, Arc<TestNodeManager>) {
+    let tester = Tester::new_custom_client(
+        test_server(),
+        default_client(0, false).custom_type_discovery(mode),
+    )
+    .await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    (tester, nm)
+}
+
+async fn read_type_name(session: &Session, id: &NodeId) -> String {
+    let r = session
+        .read(
+            &[ReadValueId::new_value(id.clone())],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .next()
+        .unwrap();
+    let Some(Variant::ExtensionObject(v)) = r.value else {
+        panic!("Unexpected variant type");
+    };
+    v.type_name().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn discover_custom_types_on_connect() {
+    let (mut tester, nm) = discovery_tester(CustomTypeDiscovery::OnConnect).await;
+    // The type is added after the first session discovered types, so read it with a new one.
+    let session = tester
+        .connect_and_wait(
+            opcua_crypto::SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let id = add_custom_variable(&nm, &tester, &session).await;
+    let _ = session.disconnect().await;
+
+    let session = tester
+        .connect_and_wait(
+            opcua_crypto::SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        read_type_name(&session, &id).await,
+        "opcua_types::custom::custom_struct::DynamicStructure"
+    );
+}
+
+#[tokio::test]
+async fn discover_custom_types_lazy() {
+    let (mut tester, nm) = discovery_tester(CustomTypeDiscovery::Lazy).await;
+    let session = tester
+        .connect_and_wait(
+            opcua_crypto::SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let id = add_custom_variable(&nm, &tester, &session).await;
+
+    // The first value of an unknown type is not decoded, but starts discovery.
+    assert_eq!(
+        read_type_name(&session, &id).await,
+        std::any::type_name::<ByteStringBody>()
+    );
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while read_type_name(&session, &id).await
+            != "opcua_types::custom::custom_struct::DynamicStructure"
+        {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,390 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use super::utils::{setup, TestNodeManager, Tester};
+use opcua::{
+    client::gds::{GdsClient, ServerConfigurationClient},
+    crypto::{CertificateStore, X509Data, X509},
+    server::address_space::{MethodBuilder, ObjectBuilder},
+    sync::RwLock,
+    types::{
+        BinaryEncodable, ByteString, ContextOwned, DataTypeId, NodeId, ObjectId, ObjectTypeId,
+        QualifiedName, ReferenceTypeId, StatusCode, TrustListDataType, TrustListMasks, Variant,
+    },
+};
+use tempdir::TempDir;
+
+fn x509_data(common_name: &str) -> X509Data {
+    X509Data {
+        key_size: 2048,
+        common_name: common_name.to_owned(),
+        organization: "OPC UA for Rust".to_owned(),
+        organizational_unit: "Tests".to_owned(),
+        country: "DE".to_owned(),
+        state: "Berlin".to_owned(),
+        alt_host_names: vec!["urn:gds-test-client".to_owned(), "localhost".to_owned()].into(),
+        certificate_duration_days: 30,
+    }
+}
+
+fn add_object(
+    tester: &Tester,
+    nm: &TestNodeManager,
+    browse_name: QualifiedName,
+    parent: &NodeId,
+) -> NodeId {
+    let id = nm.inner().next_node_id();
+    let display_name = browse_name.name.to_string();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&id, browse_name, display_name)
+            .build()
+            .into(),
+        parent,
+        &ReferenceTypeId::HasComponent.into(),
+        Some(&ObjectTypeId::BaseObjectType.into()),
+        Vec::new(),
+    );
+    id
+}
+
+/// Add methods with the given names and input arguments to `parent`, with browse names
+/// in namespace `ns`.
+fn add_methods<const N: usize>(
+    nm: &TestNodeManager,
+    parent: &NodeId,
+    ns: u16,
+    methods: [(&str, &[&str]); N],
+) -> [NodeId; N] {
+    let mut sp = nm.address_space().write();
+    methods.map(|(name, inputs)| {
+        let id = nm.inner().next_node_id();
+        let inputs: Vec<_> = inputs
+            .iter()
+            .map(|arg| (*arg, DataTypeId::BaseDataType).into())
+            .collect();
+        MethodBuilder::new(&id, QualifiedName::new(ns, name), name)
+            .executable(true)
+            .user_executable(true)
+            .component_of(parent.clone())
+            .input_args(&mut *sp, &nm.inner().next_node_id(), &inputs)
+            .output_args(
+                &mut *sp,
+                &nm.inner().next_node_id(),
+                &[("Result", DataTypeId::BaseDataType).into()],
+            )
+            .insert(&mut *sp);
+        id
+    })
+}
+
+#[tokio::test]
+async fn gds_pull_certificate_and_trust_list() {
+    let (tester, nm, session) = setup().await;
+
+    let tmp_dir = TempDir::new("gds-pki").unwrap();
+    let store = CertificateStore::new(tmp_dir.path());
+    store.ensure_pki_path().unwrap();
+    let (_, pkey) = store
+        .create_and_store_application_instance_cert(&x509_data("Client"), false)
+        .unwrap();
+    // The GDS issues a certificate for the key in the signing request.
+    let issued = X509::from_pkey(&pkey, &x509_data("Issued by GDS")).unwrap();
+    let (trusted, _) = X509::cert_and_pkey(&x509_data("Trusted")).unwrap();
+    let (untrusted, _) = X509::cert_and_pkey(&x509_data("Untrusted")).unwrap();
+    store.replace_trusted_certs(&[untrusted]).unwrap();
+    let store = RwLock::new(store);
+
+    let ns = nm.inner().next_node_id().namespace;
+    let directory_id = add_object(
+        &tester,
+        &nm,
+        QualifiedName::new(ns, "Directory"),
+        &ObjectId::ObjectsFolder.into(),
+    );
+    let trust_list_id = add_object(&tester, &nm, "TrustList".into(), &directory_id);
+    let [start_id, finish_id, get_trust_list_id] = add_methods(
+        &nm,
+        &directory_id,
+        ns,
+        [
+            (
+                "StartSigningRequest",
+                &[
+                    "ApplicationId",
+                    "CertificateGroupId",
+                    "CertificateTypeId",
+                    "CertificateRequest",
+                ][..],
+            ),
+            ("FinishRequest", &["ApplicationId", "RequestId"]),
+            ("GetTrustList", &["ApplicationId", "CertificateGroupId"]),
+        ],
+    );
+    let [open_id, read_id, close_id] = add_methods(
+        &nm,
+        &trust_list_id,
+        0,
+        [
+            ("Open", &["Mode"][..]),
+            ("Read", &["FileHandle", "Length"]),
+            ("Close", &["FileHandle"]),
+        ],
+    );
+
+    let request_id = NodeId::new(ns, "Request1");
+    nm.inner().add_method_cb(start_id, move |args| {
+        let Some(Variant::ByteString(csr)) = args.get(3) else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        assert!(!csr.is_null_or_empty());
+        Ok(vec![request_id.clone().into()])
+    });
+    // The first poll finds the request still pending.
+    let polls = Arc::new(AtomicU32::new(0));
+    let polls_ref = polls.clone();
+    let issued_bytes = issued.as_byte_string();
+    nm.inner().add_method_cb(finish_id, move |_| {
+        if polls_ref.fetch_add(1, Ordering::Relaxed) == 0 {
+            return Err(StatusCode::BadNothingToDo);
+        }
+        Ok(vec![
+            issued_bytes.clone().into(),
+            ByteString::null().into(),
+            Vec::<ByteString>::new().into(),
+        ])
+    });
+    let trust_list_id_ref = trust_list_id.clone();
+    nm.inner().add_method_cb(get_trust_list_id, move |_| {
+        Ok(vec![trust_list_id_ref.clone().into()])
+    });
+    nm.inner()
+        .add_method_cb(open_id, |_| Ok(vec![Variant::UInt32(1)]));
+    let trust_list = TrustListDataType {
+        specified_lists: TrustListMasks::TrustedCertificates as u32,
+        trusted_certificates: Some(vec![trusted.as_byte_string()]),
+        trusted_crls: None,
+        issuer_certificates: None,
+        issuer_crls: None,
+    };
+    let mut file = trust_list
+        .encode_to_vec(&ContextOwned::default().context())
+        .into_iter();
+    nm.inner().add_method_cb(read_id, move |args| {
+        let Some(Variant::Int32(len)) = args.get(1) else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        let chunk: Vec<u8> = file.by_ref().take(*len as usize).collect();
+        Ok(vec![ByteString::from(chunk).into()])
+    });
+    nm.inner().add_method_cb(close_id, |_| Ok(vec![]));
+
+    let gds = GdsClient::new(session, directory_id, ns);
+    let application_id = NodeId::new(ns, "Application1");
+    let cert = gds
+        .request_certificate(
+            &store,
+            &application_id,
+            &NodeId::null(),
+            &NodeId::null(),
+            &x509_data("Client"),
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap();
+    assert_eq!(cert.thumbprint(), issued.thumbprint());
+    assert_eq!(polls.load(Ordering::Relaxed), 2);
+    assert_eq!(
+        store.read().read_own_cert().unwrap().thumbprint(),
+        issued.thumbprint()
+    );
+
+    gds.update_trust_list(&store, &application_id, &NodeId::null())
+        .await
+        .unwrap();
+    let store = store.read();
+    let mut trusted_files: Vec<_> = std::fs::read_dir(store.trusted_certs_dir())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    trusted_files.sort();
+    assert_eq!(
+        trusted_files,
+        vec![CertificateStore::cert_file_name(&trusted)]
+    );
+}
+
+#[tokio::test]
+async fn gds_push_server_configuration() {
+    let (tester, nm, session) = setup().await;
+
+    let config_id = add_object(
+        &tester,
+        &nm,
+        "ServerConfiguration".into(),
+        &ObjectId::ObjectsFolder.into(),
+    );
+    let groups_id = add_object(&tester, &nm, "CertificateGroups".into(), &config_id);
+    let group_id = add_object(&tester, &nm, "DefaultApplicationGroup".into(), &groups_id);
+    let trust_list_id = add_object(&tester, &nm, "TrustList".into(), &group_id);
+    let [csr_id, update_id, rejected_id, apply_id] = add_methods(
+        &nm,
+        &config_id,
+        0,
+        [
+            (
+                "CreateSigningRequest",
+                &[
+                    "CertificateGroupId",
+                    "CertificateTypeId",
+                    "SubjectName",
+                    "RegeneratePrivateKey",
+                    "Nonce",
+                ][..],
+            ),
+            (
+                "UpdateCertificate",
+                &[
+                    "CertificateGroupId",
+                    "CertificateTypeId",
+                    "Certificate",
+                    "IssuerCertificates",
+                    "PrivateKeyFormat",
+                    "PrivateKey",
+                ],
+            ),
+            ("GetRejectedList", &[]),
+            ("ApplyChanges", &[]),
+        ],
+    );
+    let [open_id, read_id, write_id, close_and_update_id, close_id] = add_methods(
+        &nm,
+        &trust_list_id,
+        0,
+        [
+            ("Open", &["Mode"][..]),
+            ("Read", &["FileHandle", "Length"]),
+            ("Write", &["FileHandle", "Data"]),
+            ("CloseAndUpdate", &["FileHandle"]),
+            ("Close", &["FileHandle"]),
+        ],
+    );
+
+    let (cert, _) = X509::cert_and_pkey(&x509_data("Server")).unwrap();
+    let (rejected, _) = X509::cert_and_pkey(&x509_data("Rejected")).unwrap();
+
+    nm.inner().add_method_cb(csr_id, |args| {
+        assert_eq!(args[2], Variant::from("CN=Server"));
+        assert_eq!(args[3], Variant::Boolean(false));
+        Ok(vec![ByteString::from(b"csr").into()])
+    });
+    let cert_bytes = cert.as_byte_string();
+    nm.inner().add_method_cb(update_id, move |args| {
+        assert_eq!(args[2], Variant::from(cert_bytes.clone()));
+        Ok(vec![true.into()])
+    });
+    let rejected_bytes = rejected.as_byte_string();
+    nm.inner().add_method_cb(rejected_id, move |_| {
+        Ok(vec![vec![rejected_bytes.clone()].into()])
+    });
+    let applied = Arc::new(AtomicU32::new(0));
+    let applied_ref = applied.clone();
+    nm.inner().add_method_cb(apply_id, move |_| {
+        applied_ref.fetch_add(1, Ordering::Relaxed);
+        Ok(vec![])
+    });
+
+    // The trust list file, and the read position in it.
+    let file = Arc::new(Mutex::new((Vec::<u8>::new(), 0usize)));
+    let file_ref = file.clone();
+    nm.inner().add_method_cb(open_id, move |args| {
+        let Some(Variant::Byte(mode)) = args.first() else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        let mut file = file_ref.lock().unwrap();
+        if mode & 4 != 0 {
+            file.0.clear();
+        }
+        file.1 = 0;
+        Ok(vec![Variant::UInt32(1)])
+    });
+    let file_ref = file.clone();
+    nm.inner().add_method_cb(read_id, move |args| {
+        let Some(Variant::Int32(len)) = args.get(1) else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        let mut file = file_ref.lock().unwrap();
+        let (data, pos) = &mut *file;
+        let end = (*pos + *len as usize).min(data.len());
+        let chunk = data[*pos..end].to_vec();
+        *pos = end;
+        Ok(vec![ByteString::from(chunk).into()])
+    });
+    let file_ref = file.clone();
+    nm.inner().add_method_cb(write_id, move |args| {
+        let Some(Variant::ByteString(chunk)) = args.get(1) else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        file_ref.lock().unwrap().0.extend_from_slice(chunk.as_ref());
+        Ok(vec![])
+    });
+    nm.inner()
+        .add_method_cb(close_and_update_id, |_| Ok(vec![true.into()]));
+    nm.inner().add_method_cb(close_id, |_| Ok(vec![]));
+
+    let client = ServerConfigurationClient::with_object_id(session, config_id);
+    let csr = client
+        .create_signing_request(
+            &NodeId::null(),
+            &NodeId::null(),
+            Some("CN=Server"),
+            false,
+            ByteString::null(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(csr, ByteString::from(b"csr"));
+    let apply_changes_required = client
+        .update_certificate(
+            &NodeId::null(),
+            &NodeId::null(),
+            cert.as_byte_string(),
+            Vec::new(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(apply_changes_required);
+    assert_eq!(
+        client.get_rejected_list().await.unwrap(),
+        vec![rejected.as_byte_string()]
+    );
+
+    let id = client
+        .trust_list_id("DefaultApplicationGroup")
+        .await
+        .unwrap();
+    assert_eq!(id, trust_list_id);
+    let trust_list = TrustListDataType {
+        specified_lists: TrustListMasks::TrustedCertificates as u32,
+        trusted_certificates: Some(vec![cert.as_byte_string()]),
+        trusted_crls: None,
+        issuer_certificates: None,
+        issuer_crls: None,
+    };
+    assert!(client.write_trust_list(&id, &trust_list).await.unwrap());
+    let read = client
+        .read_trust_list(&id, TrustListMasks::All as u32)
+        .await
+        .unwrap();
+    assert_eq!(read, trust_list);
+
+    client.apply_changes().await.unwrap();
+    assert_eq!(applied.load(Ordering::Relaxed), 1);
+}
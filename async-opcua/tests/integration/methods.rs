@@ -1,18 +1,19 @@
 use std::{
-    sync::{atomic::AtomicU64, Arc},
+    sync::{atomic::AtomicU64, Arc, Mutex},
     time::Duration,
 };
 
 use crate::utils::ChannelNotifications;
 
-use super::utils::setup;
+use super::utils::{setup, TestNodeManager, Tester};
 use opcua::{
-    server::address_space::MethodBuilder,
+    server::address_space::{MethodBuilder, ObjectBuilder},
     types::{
-        AttributeId, CallMethodRequest, DataTypeId, NodeId, ObjectId, StatusCode, Variant,
-        VariantTypeId,
+        AttributeId, ByteString, CallMethodRequest, DataTypeId, MethodArgs, NodeId, ObjectId,
+        ObjectTypeId, OpenFileMode, ReferenceTypeId, StatusCode, Variant, VariantTypeId,
     },
 };
+use opcua_client::{services::Call, RemoteFile, UARequest};
 use opcua_types::{
     MonitoredItemCreateRequest, MonitoringParameters, ReadValueId, TimestampsToReturn, VariableId,
     VariantScalarTypeId,
@@ -230,6 +231,129 @@ async fn call_fail() {
     assert_eq!(r.status_code, StatusCode::BadTooManyArguments);
 }
 
+#[derive(MethodArgs)]
+struct AddInput {
+    lhs: i64,
+    rhs: i64,
+}
+
+#[derive(MethodArgs, Debug)]
+struct AddOutput {
+    result: i64,
+}
+
+#[derive(MethodArgs)]
+struct WrongInput {
+    lhs: String,
+    rhs: i64,
+}
+
+#[tokio::test]
+async fn call_typed() {
+    let (_tester, nm, session) = setup().await;
+    let called = Arc::new(AtomicU64::new(0));
+
+    let id = nm.inner().next_node_id();
+    let input_id = nm.inner().next_node_id();
+    let output_id = nm.inner().next_node_id();
+    let trivial_id = nm.inner().next_node_id();
+    {
+        let mut sp = nm.address_space().write();
+        MethodBuilder::new(&id, "MethodAdd", "MethodAdd")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .input_args(
+                &mut *sp,
+                &input_id,
+                &[
+                    ("Lhs", DataTypeId::Int64).into(),
+                    ("Rhs", DataTypeId::Int64).into(),
+                ],
+            )
+            .output_args(
+                &mut *sp,
+                &output_id,
+                &[("Result", DataTypeId::Int64).into()],
+            )
+            .insert(&mut *sp);
+        // Method without an InputArguments property.
+        MethodBuilder::new(&trivial_id, "TestMethod1", "TestMethod1")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .insert(&mut *sp);
+    }
+
+    let called_ref = called.clone();
+    nm.inner().add_method_cb(id.clone(), move |args| {
+        called_ref.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let (Some(Variant::Int64(lhs)), Some(Variant::Int64(rhs))) = (args.first(), args.get(1))
+        else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        Ok(vec![Variant::Int64(lhs + rhs)])
+    });
+    nm.inner()
+        .add_method_cb(trivial_id.clone(), move |_| Ok(Vec::new()));
+
+    let r: AddOutput = session
+        .call_typed(
+            ObjectId::ObjectsFolder,
+            id.clone(),
+            AddInput { lhs: 3, rhs: 2 },
+        )
+        .await
+        .unwrap();
+    assert_eq!(r.result, 5);
+
+    // Input arguments are checked against the InputArguments property before calling.
+    let r = session
+        .call_typed::<_, AddOutput>(
+            ObjectId::ObjectsFolder,
+            id.clone(),
+            WrongInput {
+                lhs: "foo".to_owned(),
+                rhs: 2,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(r, StatusCode::BadTypeMismatch);
+    let r = session
+        .call_typed::<_, AddOutput>(ObjectId::ObjectsFolder, id.clone(), ())
+        .await
+        .unwrap_err();
+    assert_eq!(r, StatusCode::BadArgumentsMissing);
+    assert_eq!(1, called.load(std::sync::atomic::Ordering::Relaxed));
+
+    // Output arguments that do not match the output type.
+    let r = session
+        .call_typed::<_, ()>(
+            ObjectId::ObjectsFolder,
+            id.clone(),
+            AddInput { lhs: 3, rhs: 2 },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(r, StatusCode::BadTooManyArguments);
+
+    // Methods without input arguments.
+    let () = session
+        .call_typed(ObjectId::ObjectsFolder, trivial_id.clone(), ())
+        .await
+        .unwrap();
+    let r = session
+        .call_typed::<_, ()>(
+            ObjectId::ObjectsFolder,
+            trivial_id.clone(),
+            AddInput { lhs: 3, rhs: 2 },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(r, StatusCode::BadTooManyArguments);
+}
+
 #[tokio::test]
 async fn call_limits() {
     let (tester, _nm, session) = setup().await;
@@ -246,9 +370,9 @@ async fn call_limits() {
     let e = session.call(Vec::new()).await.unwrap_err();
     assert_eq!(e, StatusCode::BadNothingToDo);
 
-    // Call too many
-    let e = session
-        .call(
+    // Call too many. The session would split this request, so send it directly.
+    let e = Call::new(&session)
+        .methods_to_call(
             (0..(limit + 1))
                 .map(|i| CallMethodRequest {
                     object_id: ObjectId::ObjectsFolder.into(),
@@ -257,6 +381,7 @@ async fn call_limits() {
                 })
                 .collect(),
         )
+        .send(session.channel())
         .await
         .unwrap_err();
     assert_eq!(e, StatusCode::BadTooManyOperations);
@@ -304,3 +429,225 @@ async fn call_get_monitored_items() {
     assert_eq!(handles.len(), 1);
     assert_eq!(15, handles[0]);
 }
+
+type FileMethodCb =
+    Box<dyn FnMut(&[Variant]) -> Result<Vec<Variant>, StatusCode> + Send + Sync + 'static>;
+type FileMethodArgs = &'static [(&'static str, DataTypeId)];
+
+#[derive(Default)]
+struct TestFile {
+    data: Vec<u8>,
+    position: usize,
+    handle: Option<u32>,
+    reads: usize,
+    max_write: usize,
+}
+
+/// Add an object with the methods of `FileType`, backed by an in-memory file.
+fn add_test_file(tester: &Tester, nm: &TestNodeManager, file: Arc<Mutex<TestFile>>) -> NodeId {
+    let file_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&file_id, "TestFile", "TestFile")
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&ObjectTypeId::FileType.into()),
+        Vec::new(),
+    );
+
+    fn check_handle(file: &TestFile, args: &[Variant]) -> Result<(), StatusCode> {
+        match (file.handle, args.first()) {
+            (Some(h), Some(Variant::UInt32(a))) if h == *a => Ok(()),
+            _ => Err(StatusCode::BadInvalidArgument),
+        }
+    }
+
+    let methods: [(&str, FileMethodArgs, FileMethodArgs, FileMethodCb); 6] = [
+        (
+            "Open",
+            &[("Mode", DataTypeId::Byte)],
+            &[("FileHandle", DataTypeId::UInt32)],
+            {
+                let file = file.clone();
+                Box::new(move |args| {
+                    let mut file = file.lock().unwrap();
+                    let Some(Variant::Byte(mode)) = args.first() else {
+                        return Err(StatusCode::BadInvalidArgument);
+                    };
+                    if file.handle.is_some() {
+                        return Err(StatusCode::BadNotWritable);
+                    }
+                    if mode & OpenFileMode::EraseExisting as u8 != 0 {
+                        file.data.clear();
+                    }
+                    file.position = 0;
+                    file.handle = Some(7);
+                    Ok(vec![Variant::UInt32(7)])
+                })
+            },
+        ),
+        ("Close", &[("FileHandle", DataTypeId::UInt32)], &[], {
+            let file = file.clone();
+            Box::new(move |args| {
+                let mut file = file.lock().unwrap();
+                check_handle(&file, args)?;
+                file.handle = None;
+                Ok(vec![])
+            })
+        }),
+        (
+            "Read",
+            &[
+                ("FileHandle", DataTypeId::UInt32),
+                ("Length", DataTypeId::Int32),
+            ],
+            &[("Data", DataTypeId::ByteString)],
+            {
+                let file = file.clone();
+                Box::new(move |args| {
+                    let mut file = file.lock().unwrap();
+                    check_handle(&file, args)?;
+                    let Some(Variant::Int32(length)) = args.get(1) else {
+                        return Err(StatusCode::BadInvalidArgument);
+                    };
+                    let end = (file.position + *length as usize).min(file.data.len());
+                    let data = file.data[file.position..end].to_vec();
+                    file.position = end;
+                    file.reads += 1;
+                    Ok(vec![Variant::from(ByteString::from(data))])
+                })
+            },
+        ),
+        (
+            "Write",
+            &[
+                ("FileHandle", DataTypeId::UInt32),
+                ("Data", DataTypeId::ByteString),
+            ],
+            &[],
+            {
+                let file = file.clone();
+                Box::new(move |args| {
+                    let mut file = file.lock().unwrap();
+                    check_handle(&file, args)?;
+                    let Some(Variant::ByteString(data)) = args.get(1) else {
+                        return Err(StatusCode::BadInvalidArgument);
+                    };
+                    let data = data.as_ref();
+                    let position = file.position;
+                    file.data.truncate(position);
+                    file.data.extend_from_slice(data);
+                    file.position += data.len();
+                    file.max_write = file.max_write.max(data.len());
+                    Ok(vec![])
+                })
+            },
+        ),
+        (
+            "GetPosition",
+            &[("FileHandle", DataTypeId::UInt32)],
+            &[("Position", DataTypeId::UInt64)],
+            {
+                let file = file.clone();
+                Box::new(move |args| {
+                    let file = file.lock().unwrap();
+                    check_handle(&file, args)?;
+                    Ok(vec![Variant::UInt64(file.position as u64)])
+                })
+            },
+        ),
+        (
+            "SetPosition",
+            &[
+                ("FileHandle", DataTypeId::UInt32),
+                ("Position", DataTypeId::UInt64),
+            ],
+            &[],
+            {
+                let file = file.clone();
+                Box::new(move |args| {
+                    let mut file = file.lock().unwrap();
+                    check_handle(&file, args)?;
+                    let Some(Variant::UInt64(position)) = args.get(1) else {
+                        return Err(StatusCode::BadInvalidArgument);
+                    };
+                    file.position = (*position as usize).min(file.data.len());
+                    Ok(vec![])
+                })
+            },
+        ),
+    ];
+
+    for (name, inputs, outputs, cb) in methods {
+        let id = nm.inner().next_node_id();
+        let input_id = nm.inner().next_node_id();
+        let output_id = nm.inner().next_node_id();
+        let inputs: Vec<_> = inputs.iter().map(|a| (*a).into()).collect();
+        let outputs: Vec<_> = outputs.iter().map(|a| (*a).into()).collect();
+        {
+            let mut sp = nm.address_space().write();
+            MethodBuilder::new(&id, name, name)
+                .executable(true)
+                .user_executable(true)
+                .component_of(file_id.clone())
+                .input_args(&mut *sp, &input_id, &inputs)
+                .output_args(&mut *sp, &output_id, &outputs)
+                .insert(&mut *sp);
+        }
+        nm.inner().add_method_cb(id, cb);
+    }
+    file_id
+}
+
+#[tokio::test]
+async fn file_transfer() {
+    let (tester, nm, session) = setup().await;
+    let file = Arc::new(Mutex::new(TestFile::default()));
+    let file_id = add_test_file(&tester, &nm, file.clone());
+
+    // Larger than the default maximum byte string length of both client and server.
+    let contents: Vec<u8> = (0..200_000u32).map(|v| (v % 251) as u8).collect();
+    let uploaded = session
+        .upload_file(file_id.clone(), &contents[..])
+        .await
+        .unwrap();
+    assert_eq!(contents.len() as u64, uploaded);
+    {
+        let file = file.lock().unwrap();
+        assert_eq!(contents, file.data);
+        assert!(file.handle.is_none());
+        assert!(file.max_write <= 65535);
+    }
+
+    let mut downloaded = Vec::new();
+    let size = session
+        .download_file(file_id.clone(), &mut downloaded)
+        .await
+        .unwrap();
+    assert_eq!(contents.len() as u64, size);
+    assert_eq!(contents, downloaded);
+    {
+        let file = file.lock().unwrap();
+        assert!(file.handle.is_none());
+        // Four reads with data, and one at the end of the file.
+        assert_eq!(5, file.reads);
+    }
+
+    // Use the file directly.
+    let remote = RemoteFile::open(&session, file_id.clone(), OpenFileMode::Read as u8)
+        .await
+        .unwrap();
+    remote.set_position(1000).await.unwrap();
+    assert_eq!(contents[1000..1010], remote.read(10).await.unwrap()[..]);
+    assert_eq!(1010, remote.position().await.unwrap());
+    // The server only allows the file to be opened once.
+    assert_eq!(
+        Err(StatusCode::BadNotWritable),
+        session.upload_file(file_id.clone(), &b"abc"[..]).await
+    );
+    remote.close().await.unwrap();
+    assert!(file.lock().unwrap().handle.is_none());
+}
@@ -1,9 +1,12 @@
 mod browse;
 mod core_tests;
 mod custom_types;
+mod gds;
 mod methods;
 mod node_management;
 mod read;
+mod redundancy;
+mod roles;
 mod subscriptions;
 mod write;
 
@@ -1,10 +1,14 @@
 use super::utils::setup;
 use opcua::{
-    server::address_space::{EventNotifier, NodeBase, NodeType, ObjectBuilder},
+    nodes::NodeSet2Import,
+    server::address_space::{
+        EventNotifier, NodeBase, NodeSetConflictPolicy, NodeType, ObjectBuilder,
+    },
     types::{
-        AddNodeAttributes, AddNodesItem, AddReferencesItem, DeleteNodesItem, DeleteReferencesItem,
-        ExpandedNodeId, NodeClass, NodeId, ObjectAttributes, ObjectId, ObjectTypeId,
-        ReferenceTypeId, StatusCode,
+        AddNodeAttributes, AddNodesItem, AddReferencesItem, BrowseDescription, BrowseDirection,
+        BrowseResultMask, DeleteNodesItem, DeleteReferencesItem, EUInformation, ExpandedNodeId,
+        NodeClass, NodeId, ObjectAttributes, ObjectId, ObjectTypeId, ReadValueId, ReferenceTypeId,
+        StatusCode, TimestampsToReturn, Variant,
     },
 };
 
@@ -212,3 +216,129 @@ async fn add_delete_reference_limits() {
         .unwrap_err();
     assert_eq!(e, StatusCode::BadTooManyOperations);
 }
+
+const RUNTIME_NODESET: &str = r#"
+<UANodeSet xmlns="http://opcfoundation.org/UA/2011/03/UANodeSet.xsd">
+  <NamespaceUris>
+    <Uri>urn:RuntimeImport</Uri>
+  </NamespaceUris>
+  <Models>
+    <Model ModelUri="urn:RuntimeImport" Version="1.00" PublicationDate="2024-01-01T00:00:00Z" />
+  </Models>
+  <UAObject NodeId="ns=1;i=1" BrowseName="1:Imported">
+    <DisplayName>Imported</DisplayName>
+    <References>
+      <Reference ReferenceType="i=35" IsForward="false">i=85</Reference>
+      <Reference ReferenceType="i=40">i=61</Reference>
+    </References>
+  </UAObject>
+  <UAVariable NodeId="ns=1;i=2" BrowseName="1:Units" DataType="i=887">
+    <DisplayName>Units</DisplayName>
+    <References>
+      <Reference ReferenceType="i=40">i=68</Reference>
+      <Reference ReferenceType="i=46" IsForward="false">ns=1;i=1</Reference>
+    </References>
+    <Value>
+      <ExtensionObject>
+        <TypeId><Identifier>i=888</Identifier></TypeId>
+        <Body>
+          <EUInformation>
+            <NamespaceUri>http://unit-namespace.namespace</NamespaceUri>
+            <UnitId>15</UnitId>
+            <DisplayName><Locale>en</Locale><Text>Degrees Celsius</Text></DisplayName>
+          </EUInformation>
+        </Body>
+      </ExtensionObject>
+    </Value>
+  </UAVariable>
+  <UAObject NodeId="i=90001" BrowseName="OutsideNamespace">
+    <DisplayName>OutsideNamespace</DisplayName>
+  </UAObject>
+</UANodeSet>
+"#;
+
+#[tokio::test]
+async fn import_node_set_runtime() {
+    let (tester, nm, session) = setup().await;
+    let ns = tester
+        .handle
+        .get_namespace_index("urn:rustopcuatestserver")
+        .unwrap();
+    let import = NodeSet2Import::new_str("en", RUNTIME_NODESET, vec![]).unwrap();
+    let type_tree = tester.handle.type_tree();
+    let object_id = NodeId::new(ns, 1);
+    let variable_id = NodeId::new(ns, 2);
+
+    // The namespace must be managed by the node manager.
+    let e = nm
+        .import_node_set(&import, type_tree, 100, NodeSetConflictPolicy::Skip)
+        .unwrap_err();
+    assert_eq!(e, StatusCode::BadInvalidArgument);
+
+    // The model namespace is mapped to the target namespace, nodes in other namespaces are skipped.
+    let report = nm
+        .import_node_set(&import, type_tree, ns, NodeSetConflictPolicy::Skip)
+        .unwrap();
+    assert_eq!(report.created, vec![object_id.clone(), variable_id.clone()]);
+    assert!(report.replaced.is_empty());
+    assert_eq!(report.skipped, vec![NodeId::new(0, 90001)]);
+    assert!(tester
+        .handle
+        .get_namespace_index("urn:RuntimeImport")
+        .is_none());
+
+    // The imported nodes are visible to clients.
+    let r = session
+        .browse(
+            &[BrowseDescription {
+                node_id: ObjectId::ObjectsFolder.into(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::Organizes.into(),
+                include_subtypes: true,
+                node_class_mask: 0,
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            1000,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(r[0]
+        .references
+        .as_ref()
+        .unwrap()
+        .iter()
+        .any(|r| r.node_id.node_id == object_id));
+    let r = session
+        .read(
+            &[ReadValueId::new_value(variable_id.clone())],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::ExtensionObject(obj)) = &r[0].value else {
+        panic!("Expected extension object, got {:?}", r[0].value);
+    };
+    let units = obj.inner_as::<EUInformation>().unwrap();
+    assert_eq!(units.unit_id, 15);
+    assert_eq!(units.display_name.text.as_ref(), "Degrees Celsius");
+
+    // Importing the same nodes again follows the conflict policy.
+    let report = nm
+        .import_node_set(&import, type_tree, ns, NodeSetConflictPolicy::Skip)
+        .unwrap();
+    assert!(report.created.is_empty());
+    assert_eq!(report.skipped.len(), 3);
+
+    let e = nm
+        .import_node_set(&import, type_tree, ns, NodeSetConflictPolicy::Fail)
+        .unwrap_err();
+    assert_eq!(e, StatusCode::BadNodeIdExists);
+
+    let report = nm
+        .import_node_set(&import, type_tree, ns, NodeSetConflictPolicy::Replace)
+        .unwrap();
+    assert!(report.created.is_empty());
+    assert_eq!(report.replaced, vec![object_id, variable_id]);
+}
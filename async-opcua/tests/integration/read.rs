@@ -1,23 +1,59 @@
-use std::{sync::atomic::Ordering, time::Duration};
+use std::{
+    ops::Bound,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
-use crate::utils::{client_user_token, default_server, Tester};
+use crate::utils::{
+    client_user_token, default_client, default_server, test_server, ChannelNotifications, Tester,
+    CLIENT_USERPASS_ID,
+};
 
-use super::utils::{array_value, read_value_id, read_value_ids, setup};
+use super::utils::{array_value, read_value_id, read_value_ids, setup, TestNodeManager};
 use chrono::TimeDelta;
+use futures::{StreamExt, TryStreamExt};
 use opcua::{
-    client::HistoryReadAction,
-    server::address_space::{
-        AccessLevel, DataTypeBuilder, EventNotifier, MethodBuilder, ObjectBuilder,
-        ObjectTypeBuilder, ReferenceTypeBuilder, VariableBuilder, VariableTypeBuilder, ViewBuilder,
+    client::{HistoryReadAction, HistoryReadRawOptions, HistoryUpdateAction, IdentityToken},
+    crypto::SecurityPolicy,
+    nodes::{BaseEventType, Event, NodeBase, NodeType, ParsedEventFilter},
+    server::{
+        address_space::{
+            AccessLevel, DataTypeBuilder, EventNotifier, MethodBuilder, ObjectBuilder,
+            ObjectTypeBuilder, ReferenceTypeBuilder, VariableBuilder, VariableTypeBuilder,
+            ViewBuilder,
+        },
+        diagnostics::NamespaceMetadata,
+        node_manager::{
+            memory::{
+                CoreNodeManager, InMemoryNodeManagerBuilder, SimpleNodeManager,
+                SimpleNodeManagerBuilder,
+            },
+            EventHistoryProvider, HistoryEventFilter, HistoryProvider, HistoryRawQuery,
+            InMemoryEventHistoryProvider, InMemoryHistoryProvider, SqliteHistoryProvider,
+            SqliteHistoryRetention, SUPPORTED_AGGREGATES,
+        },
+        HistoryServerCapabilities, ServerCapabilities,
     },
     types::{
-        AttributeId, DataTypeId, DataValue, DateTime, HistoryData, HistoryReadValueId, NodeClass,
-        NodeId, ObjectId, ObjectTypeId, QualifiedName, ReadRawModifiedDetails, ReadValueId,
-        ReferenceTypeId, StatusCode, TimestampsToReturn, VariableId, VariableTypeId, Variant,
-        WriteMask,
+        AttributeId, BrowseDescription, BrowseDirection, BrowseResultMask, ByteString,
+        ContentFilterBuilder, DataEncoding, DataTypeId, DataValue, DateTime, DeleteAtTimeDetails,
+        DeleteRawModifiedDetails, EventFilterBuilder, ExtensionObject, HistoryData,
+        HistoryModifiedData, HistoryReadResult, HistoryReadValueId, HistoryUpdateType,
+        IdentityCriteriaType, IdentityMappingRuleType, MessageSecurityMode,
+        MonitoredItemCreateRequest, MonitoringMode, MonitoringParameters, NodeClass, NodeId,
+        NumericRange, ObjectId, ObjectTypeId, Operand, PerformUpdateType, QualifiedName,
+        ReadAtTimeDetails, ReadRawModifiedDetails, ReadValueId, ReferenceTypeId,
+        SessionDiagnosticsDataType, StatusCode, StatusCodeValueType,
+        SubscriptionDiagnosticsDataType, TimestampsToReturn, UpdateDataDetails, VariableId,
+        VariableTypeId, Variant, WriteMask, WriteValue,
     },
 };
-use opcua_client::{services::Read, DefaultRetryPolicy, ExponentialBackoff};
+use opcua_client::{
+    services::{HistoryRead, Read},
+    DefaultRetryPolicy, ExponentialBackoff, HistoryValueStream, ReadCacheOptions, RegisteredNodes,
+    Session, UARequest,
+};
+use tempdir::TempDir;
 
 #[tokio::test]
 async fn read() {
@@ -186,6 +222,56 @@ async fn read_object() {
     );
 }
 
+#[tokio::test]
+async fn read_node() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(5)
+            .description("Description")
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let NodeType::Variable(var) = session.read_node(&id).await.unwrap() else {
+        panic!("Expected variable");
+    };
+    assert_eq!(var.node_id(), &id);
+    assert_eq!(var.browse_name(), &QualifiedName::from("TestVar1"));
+    assert_eq!(var.description(), Some(&"Description".into()));
+    assert_eq!(var.data_type(), DataTypeId::Int32);
+    assert_eq!(var.access_level().bits(), AccessLevel::CURRENT_READ.bits());
+    let value = var.value(
+        TimestampsToReturn::Neither,
+        &NumericRange::None,
+        &DataEncoding::Binary,
+        0.0,
+    );
+    assert_eq!(value.value, Some(Variant::Int32(5)));
+
+    let NodeType::Object(obj) = session.read_node(&ObjectId::Server.into()).await.unwrap() else {
+        panic!("Expected object");
+    };
+    assert_eq!(obj.browse_name(), &QualifiedName::from("Server"));
+    assert!(obj
+        .event_notifier()
+        .contains(EventNotifier::SUBSCRIBE_TO_EVENTS));
+
+    let r = session.read_node(&NodeId::new(2, "NotANode")).await;
+    assert_eq!(r.unwrap_err(), StatusCode::BadNodeIdUnknown);
+}
+
 #[tokio::test]
 async fn read_view() {
     let (tester, nm, session) = setup().await;
@@ -728,12 +814,36 @@ async fn read_limits() {
     let ops: Vec<_> = (0..(read_limit + 1))
         .map(|r| read_value_id(AttributeId::Value, NodeId::new(2, r as u32)))
         .collect();
-    let r = session
-        .read(&ops, TimestampsToReturn::Both, 0.0)
+    // The session splits requests by the server operation limits, so send the request directly.
+    let r = Read::new(&session)
+        .nodes_to_read(ops.clone())
+        .send(session.channel())
         .await
         .unwrap_err();
     assert_eq!(r, StatusCode::BadTooManyOperations);
 
+    // Through the session, the request is split and the results merged in order.
+    let ops: Vec<_> = (0..(read_limit * 2 + 1))
+        .map(|_| read_value_id(AttributeId::BrowseName, ObjectId::Server))
+        .chain([read_value_id(
+            AttributeId::BrowseName,
+            ObjectId::ObjectsFolder,
+        )])
+        .collect();
+    let r = session
+        .read(&ops, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r.len(), ops.len());
+    assert_eq!(
+        r[0].value,
+        Some(Variant::from(QualifiedName::new(0, "Server")))
+    );
+    assert_eq!(
+        r.last().unwrap().value,
+        Some(Variant::from(QualifiedName::new(0, "Objects")))
+    );
+
     // Exact number of operations, should not fail, though the reads will probably fail, mostly.
     let ops: Vec<_> = (0..read_limit)
         .map(|r| read_value_id(AttributeId::Value, NodeId::new(2, r as u32)))
@@ -973,6 +1083,501 @@ async fn history_read_release_continuation_points() {
     assert!(v.history_data.is_null());
 }
 
+fn add_history_variable(
+    tester: &Tester,
+    nm: &TestNodeManager,
+    start: DateTime,
+    count: i64,
+) -> NodeId {
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar", "TestVar")
+            .historizing(true)
+            .value(0)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ | AccessLevel::HISTORY_READ)
+            .user_access_level(AccessLevel::CURRENT_READ | AccessLevel::HISTORY_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    nm.inner().add_history(
+        &id,
+        (0..count).map(|v| DataValue {
+            value: Some((v as i32).into()),
+            status: Some(StatusCode::Good),
+            source_timestamp: Some(start + TimeDelta::try_seconds(v).unwrap()),
+            server_timestamp: Some(start + TimeDelta::try_seconds(v).unwrap()),
+            ..Default::default()
+        }),
+    );
+    id
+}
+
+#[tokio::test]
+async fn history_read_raw_stream() {
+    let (tester, nm, session) = setup().await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let id1 = add_history_variable(&tester, &nm, start, 1000);
+    let id2 = add_history_variable(&tester, &nm, start, 250);
+    let missing = NodeId::new(2, "missing");
+
+    let nodes = [id1.clone(), id2.clone(), missing.clone()];
+    let pages: Vec<_> = session
+        .history_read_raw(
+            &nodes,
+            start..(start + TimeDelta::try_seconds(2000).unwrap()),
+            HistoryReadRawOptions {
+                num_values_per_node: 100,
+                ..Default::default()
+            },
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+    // 10 requests are needed to read the 1000 values of the first node.
+    assert_eq!(pages.len(), 10);
+    let values: Vec<_> = pages.into_iter().flatten().collect();
+
+    for (id, count) in [(&id1, 1000), (&id2, 250)] {
+        let node_values: Vec<_> = values
+            .iter()
+            .filter(|(n, _)| n == id)
+            .map(|(_, v)| v)
+            .collect();
+        assert_eq!(node_values.len(), count);
+        for (idx, v) in node_values.into_iter().enumerate() {
+            assert_eq!(v.value, Some(Variant::Int32(idx as i32)));
+            assert_eq!(
+                v.source_timestamp,
+                Some(start + TimeDelta::try_seconds(idx as i64).unwrap())
+            );
+        }
+    }
+
+    // The missing node gets a single value with a bad status.
+    let missing_values: Vec<_> = values.iter().filter(|(n, _)| n == &missing).collect();
+    assert_eq!(missing_values.len(), 1);
+    assert!(missing_values[0].1.status.unwrap().is_bad());
+    assert!(missing_values[0].1.value.is_none());
+}
+
+#[tokio::test]
+async fn history_value_stream() {
+    let (tester, nm, session) = setup().await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let id1 = add_history_variable(&tester, &nm, start, 1000);
+    let id2 = add_history_variable(&tester, &nm, start, 250);
+    let time_range = start..(start + TimeDelta::try_seconds(2000).unwrap());
+    let options = HistoryReadRawOptions {
+        num_values_per_node: 100,
+        ..Default::default()
+    };
+
+    let values: Vec<_> = HistoryValueStream::new(
+        session.clone(),
+        vec![id1.clone(), id2.clone()],
+        time_range.clone(),
+        options.clone(),
+    )
+    .try_collect()
+    .await
+    .unwrap();
+    for (id, count) in [(&id1, 1000), (&id2, 250)] {
+        let node_values: Vec<_> = values
+            .iter()
+            .filter(|(n, _)| n == id)
+            .map(|(_, v)| v.value.clone())
+            .collect();
+        assert_eq!(
+            (0..count)
+                .map(|v| Some(Variant::Int32(v)))
+                .collect::<Vec<_>>(),
+            node_values
+        );
+    }
+
+    // Stop after the first page, releasing the continuation point of the node.
+    let mut stream =
+        HistoryValueStream::new(session.clone(), vec![id1.clone()], time_range, options);
+    let first: Vec<_> = stream.by_ref().take(100).try_collect().await.unwrap();
+    assert_eq!(100, first.len());
+    stream.release().await;
+}
+
+#[tokio::test]
+async fn history_read_processed() {
+    let (tester, nm, session) = setup().await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let id1 = add_history_variable(&tester, &nm, start, 1000);
+    let id2 = add_history_variable(&tester, &nm, start, 250);
+
+    let series = session
+        .history_read_processed(
+            &[
+                (id1.clone(), ObjectId::AggregateFunction_Average.into()),
+                (id1.clone(), ObjectId::AggregateFunction_Maximum.into()),
+                (id2.clone(), ObjectId::AggregateFunction_Minimum.into()),
+                (id2.clone(), ObjectId::AggregateFunction_Count.into()),
+            ],
+            start..(start + TimeDelta::try_seconds(950).unwrap()),
+            Duration::from_secs(100),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(series.len(), 4);
+
+    // Average, the last interval only covers half the processing interval.
+    let avg = &series[0];
+    assert_eq!(avg.node_id, id1);
+    assert_eq!(avg.status, StatusCode::Good);
+    assert_eq!(avg.values.len(), 10);
+    for (idx, v) in avg.values.iter().enumerate() {
+        assert!(v.is_usable());
+        assert_eq!(v.value_type(), StatusCodeValueType::Calculated);
+        assert_eq!(
+            v.timestamp.unwrap().ticks(),
+            (start + TimeDelta::try_seconds(idx as i64 * 100).unwrap()).checked_ticks()
+        );
+        if idx == 9 {
+            assert!(v.is_partial());
+            assert_eq!(v.status.sub_code(), StatusCode::Good.sub_code());
+            assert_eq!(v.value, Some(Variant::Double(924.5)));
+        } else {
+            assert!(!v.is_partial());
+            assert_eq!(v.value, Some(Variant::Double(idx as f64 * 100.0 + 49.5)));
+        }
+    }
+
+    let max = &series[1];
+    assert_eq!(max.values.len(), 10);
+    assert_eq!(max.values[0].value, Some(Variant::Double(99.0)));
+    assert_eq!(max.values[9].value, Some(Variant::Double(949.0)));
+
+    // The second node only has data for the first three intervals.
+    let min = &series[2];
+    assert_eq!(min.node_id, id2);
+    assert_eq!(min.values.len(), 10);
+    assert_eq!(min.values[2].value, Some(Variant::Double(200.0)));
+    assert!(min.values[2].is_usable());
+    assert!(!min.values[3].is_usable());
+    assert_eq!(min.values[3].status, StatusCode::BadNoData);
+
+    // Count is not supported by the test node manager.
+    assert_eq!(series[3].status, StatusCode::BadAggregateNotSupported);
+    assert!(series[3].values.is_empty());
+}
+
+#[tokio::test]
+async fn history_read_events() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&id, "EventSource", "EventSource")
+            .event_notifier(EventNotifier::SUBSCRIBE_TO_EVENTS | EventNotifier::HISTORY_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&ObjectTypeId::BaseObjectType.into()),
+        Vec::new(),
+    );
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    nm.inner().add_history_events(
+        &id,
+        (0..100).map(|i| {
+            Box::new(
+                BaseEventType::new(
+                    ObjectTypeId::BaseEventType,
+                    ByteString::from(vec![i as u8]),
+                    format!("Event {i}"),
+                    start + TimeDelta::try_seconds(i).unwrap(),
+                )
+                .set_source_node(id.clone())
+                .set_severity(i as u16 * 10),
+            ) as _
+        }),
+    );
+
+    let filter = EventFilterBuilder::new()
+        .select(ObjectTypeId::BaseEventType, "EventId")
+        .select(ObjectTypeId::BaseEventType, "EventType")
+        .select(ObjectTypeId::BaseEventType, "Time")
+        .select(ObjectTypeId::BaseEventType, "Message")
+        .select(ObjectTypeId::BaseEventType, "Severity")
+        .where_clause(
+            ContentFilterBuilder::new()
+                .gte(
+                    Operand::simple_attribute(
+                        ObjectTypeId::BaseEventType,
+                        "Severity",
+                        AttributeId::Value,
+                        NumericRange::None,
+                    ),
+                    Operand::literal(200u16),
+                )
+                .build(),
+        )
+        .build();
+
+    // Read with a small page size, so that the helper has to follow continuation points.
+    let res = session
+        .history_read_events::<BaseEventType>(
+            std::slice::from_ref(&id),
+            start..(start + TimeDelta::try_seconds(90).unwrap()),
+            &filter,
+            7,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 1);
+    let events = &res[0];
+    assert_eq!(events.node_id, id);
+    assert_eq!(events.status, StatusCode::Good);
+    assert_eq!(events.events.len(), 70);
+    for (idx, evt) in events.events.iter().enumerate() {
+        let i = idx as i64 + 20;
+        assert_eq!(evt.event_id, ByteString::from(vec![i as u8]));
+        assert_eq!(evt.event_type, ObjectTypeId::BaseEventType);
+        assert_eq!(evt.time, start + TimeDelta::try_seconds(i).unwrap());
+        assert_eq!(evt.message.text.as_ref(), format!("Event {i}"));
+        assert_eq!(evt.severity, i as u16 * 10);
+        // Not selected, so left at its default value.
+        assert!(evt.source_node.is_null());
+    }
+}
+
+#[tokio::test]
+async fn history_event_capture() {
+    let provider = Arc::new(InMemoryEventHistoryProvider::new(100));
+    let server = test_server().event_history(provider.clone());
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    // Only record events with a severity of at least 100, without the source node.
+    let severity = || {
+        Operand::simple_attribute(
+            ObjectTypeId::BaseEventType,
+            "Severity",
+            AttributeId::Value,
+            NumericRange::None,
+        )
+    };
+    let capture = tester.handle.info().event_history.clone().unwrap();
+    let core = tester
+        .handle
+        .node_managers()
+        .get_of_type::<CoreNodeManager>()
+        .unwrap();
+    capture
+        .historize(
+            &mut core.address_space().write(),
+            ObjectId::Server,
+            EventFilterBuilder::new()
+                .select(ObjectTypeId::BaseEventType, "EventId")
+                .select(ObjectTypeId::BaseEventType, "EventType")
+                .select(ObjectTypeId::BaseEventType, "Time")
+                .select(ObjectTypeId::BaseEventType, "Message")
+                .select(ObjectTypeId::BaseEventType, "Severity")
+                .where_clause(
+                    ContentFilterBuilder::new()
+                        .gte(severity(), Operand::literal(100u16))
+                        .build(),
+                )
+                .build(),
+        )
+        .unwrap();
+    assert!(capture.is_historized(&ObjectId::Server.into()));
+
+    // The filter is exposed as the `HistoricalEventFilter` property of the server.
+    let r = session
+        .read(
+            &[ReadValueId {
+                node_id: VariableId::HistoricalEventFilter.into(),
+                attribute_id: AttributeId::Value as u32,
+                ..Default::default()
+            }],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::ExtensionObject(filter)) = &r[0].value else {
+        panic!("Expected an event filter, got {:?}", r[0].value);
+    };
+    let filter = filter.inner_as::<opcua::types::EventFilter>().unwrap();
+    assert_eq!(filter.select_clauses.as_ref().unwrap().len(), 5);
+    let r = session
+        .browse(
+            &[BrowseDescription {
+                node_id: ObjectId::Server.into(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::HasProperty.into(),
+                include_subtypes: false,
+                node_class_mask: 0,
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            1000,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(r[0]
+        .references
+        .as_ref()
+        .unwrap()
+        .iter()
+        .any(|r| r.node_id.node_id == VariableId::HistoricalEventFilter));
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let events: Vec<_> = (0..20)
+        .map(|i| {
+            BaseEventType::new(
+                ObjectTypeId::BaseEventType,
+                ByteString::from(vec![i as u8]),
+                format!("Event {i}"),
+                start + TimeDelta::try_seconds(i).unwrap(),
+            )
+            .set_source_node(ObjectId::ObjectsFolder.into())
+            .set_severity(i as u16 * 10)
+        })
+        .collect();
+    // Events raised on other nodes are recorded for the server too.
+    let server_id: NodeId = ObjectId::Server.into();
+    let objects_id: NodeId = ObjectId::ObjectsFolder.into();
+    tester
+        .handle
+        .subscriptions()
+        .notify_events(events.iter().enumerate().map(|(i, e)| {
+            (
+                e as &dyn Event,
+                if i % 2 == 0 { &server_id } else { &objects_id },
+            )
+        }));
+
+    // Events are recorded in the background.
+    let query = HistoryRawQuery {
+        start: Bound::Unbounded,
+        end: Bound::Unbounded,
+        reverse: false,
+        max_values: 100,
+    };
+    for _ in 0..50 {
+        if provider
+            .read_events(&server_id, &query, &HistoryEventFilter::all())
+            .await
+            .unwrap()
+            .len()
+            == 10
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        provider
+            .read_events(&server_id, &query, &HistoryEventFilter::all())
+            .await
+            .unwrap()
+            .len(),
+        10
+    );
+
+    // Providers only return events passing the where clause of the filter.
+    let severe = ParsedEventFilter::new(
+        EventFilterBuilder::new()
+            .select(ObjectTypeId::BaseEventType, "Severity")
+            .where_clause(
+                ContentFilterBuilder::new()
+                    .gte(severity(), Operand::literal(150u16))
+                    .build(),
+            )
+            .build(),
+        &*tester.handle.type_tree().read(),
+    )
+    .1
+    .unwrap();
+    let events = provider
+        .read_events(
+            &server_id,
+            &HistoryRawQuery {
+                max_values: 3,
+                ..query
+            },
+            &HistoryEventFilter::new(&severe, tester.handle.info().type_tree.clone()),
+        )
+        .await
+        .unwrap();
+    let times: Vec<_> = events.iter().map(|e| e.time).collect();
+    let expected: Vec<_> = (15..18)
+        .map(|i| start + TimeDelta::try_seconds(i).unwrap())
+        .collect();
+    assert_eq!(times, expected);
+
+    let filter = EventFilterBuilder::new()
+        .select(ObjectTypeId::BaseEventType, "EventId")
+        .select(ObjectTypeId::BaseEventType, "Time")
+        .select(ObjectTypeId::BaseEventType, "Message")
+        .select(ObjectTypeId::BaseEventType, "Severity")
+        .select(ObjectTypeId::BaseEventType, "SourceNode")
+        .where_clause(
+            ContentFilterBuilder::new()
+                .gte(severity(), Operand::literal(150u16))
+                .build(),
+        )
+        .build();
+    let res = session
+        .history_read_events::<BaseEventType>(
+            std::slice::from_ref(&server_id),
+            start..(start + TimeDelta::try_seconds(100).unwrap()),
+            &filter,
+            2,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res[0].status, StatusCode::Good);
+    let events = &res[0].events;
+    assert_eq!(events.len(), 5);
+    for (idx, evt) in events.iter().enumerate() {
+        let i = idx as i64 + 15;
+        assert_eq!(evt.event_id, ByteString::from(vec![i as u8]));
+        assert_eq!(evt.time, start + TimeDelta::try_seconds(i).unwrap());
+        assert_eq!(evt.message.text.as_ref(), format!("Event {i}"));
+        assert_eq!(evt.severity, i as u16 * 10);
+        // Not recorded.
+        assert!(evt.source_node.is_null());
+    }
+
+    // Nodes without event history are rejected.
+    let res = session
+        .history_read_events::<BaseEventType>(
+            std::slice::from_ref(&objects_id),
+            start..(start + TimeDelta::try_seconds(100).unwrap()),
+            &filter,
+            2,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res[0].status, StatusCode::BadHistoryOperationUnsupported);
+}
+
 #[tokio::test]
 async fn history_read_fail() {
     let (tester, nm, session) = setup().await;
@@ -1021,21 +1626,20 @@ async fn history_read_fail() {
         .operational
         .max_nodes_per_history_read_data;
 
-    // Read too many
-    let r = session
-        .history_read(
-            action.clone(),
-            TimestampsToReturn::Both,
-            false,
-            &(0..(history_read_limit + 1))
+    // Read too many. The session would split this request, so send it directly.
+    let r = HistoryRead::new(action.clone(), &session)
+        .timestamps_to_return(TimestampsToReturn::Both)
+        .nodes_to_read(
+            (0..(history_read_limit + 1))
                 .map(|i| HistoryReadValueId {
                     node_id: NodeId::new(2, i as u32),
                     index_range: Default::default(),
                     data_encoding: Default::default(),
                     continuation_point: Default::default(),
                 })
-                .collect::<Vec<_>>(),
+                .collect(),
         )
+        .send(session.channel())
         .await
         .unwrap_err();
     assert_eq!(r, StatusCode::BadTooManyOperations);
@@ -1077,43 +1681,784 @@ async fn history_read_fail() {
     assert_eq!(r[0].status_code, StatusCode::BadNodeIdUnknown);
 }
 
-#[tokio::test]
-async fn read_retry() {
-    let (tester, nm, session) = setup().await;
+fn history_values(v: &HistoryReadResult) -> Vec<DataValue> {
+    v.history_data
+        .inner_as::<HistoryData>()
+        .unwrap()
+        .data_values
+        .clone()
+        .unwrap()
+}
 
-    let id = nm.inner().next_node_id();
-    nm.inner().add_node(
-        nm.address_space(),
-        tester.handle.type_tree(),
-        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+/// Start a server with a simple node manager using `provider` for history, and a
+/// historizing variable in it.
+async fn history_provider_setup(
+    provider: Arc<dyn HistoryProvider>,
+) -> (Tester, Arc<Session>, NodeId) {
+    let server = test_server()
+        .with_node_manager(InMemoryNodeManagerBuilder::new(
+            SimpleNodeManagerBuilder::new(
+                NamespaceMetadata {
+                    namespace_uri: "urn:HistoryProviderTest".to_owned(),
+                    ..Default::default()
+                },
+                "history",
+            )
+            .history(provider),
+        ))
+        .auditing(true)
+        .capabilities(ServerCapabilities {
+            history: HistoryServerCapabilities {
+                access_history_data: true,
+                aggregates: SUPPORTED_AGGREGATES.iter().map(|a| (*a).into()).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let ns = tester
+        .handle
+        .get_namespace_index("urn:HistoryProviderTest")
+        .unwrap();
+    let id = NodeId::new(ns, "historized");
+    let access_level = AccessLevel::CURRENT_READ
+        | AccessLevel::CURRENT_WRITE
+        | AccessLevel::HISTORY_READ
+        | AccessLevel::HISTORY_WRITE;
+    nm.address_space().write().add_variables(
+        vec![VariableBuilder::new(&id, "Historized", "Historized")
             .historizing(true)
-            .value(1)
-            .description("Description")
+            .value(0)
             .data_type(DataTypeId::Int32)
-            .access_level(AccessLevel::CURRENT_READ)
-            .user_access_level(AccessLevel::CURRENT_READ)
-            .build()
-            .into(),
+            .access_level(access_level)
+            .user_access_level(access_level)
+            .build()],
         &ObjectId::ObjectsFolder.into(),
-        &ReferenceTypeId::Organizes.into(),
-        Some(&VariableTypeId::BaseDataVariableType.into()),
-        Vec::new(),
     );
-    // Tell the node manager to fail fatally three times.
-    nm.inner().issues().fatal_read.store(3, Ordering::Relaxed);
+    (tester, session, id)
+}
 
-    // Make one request first, it should fail
-    let e = session
-        .read(
-            &[ReadValueId {
-                node_id: id.clone(),
-                attribute_id: AttributeId::Value as u32,
-                ..Default::default()
-            }],
-            TimestampsToReturn::Both,
-            0.0,
-        )
-        .await
+#[tokio::test]
+async fn history_provider() {
+    let provider = Arc::new(InMemoryHistoryProvider::new(100));
+    let (_tester, session, id) = history_provider_setup(provider.clone()).await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let at = |secs: i64| start + TimeDelta::try_seconds(secs).unwrap();
+    for i in 0..10 {
+        provider.push_value(&id, DataValue::new_at((i * 10) as i32, at(i * 10)));
+    }
+    let node = |cp: ByteString| HistoryReadValueId {
+        node_id: id.clone(),
+        index_range: Default::default(),
+        data_encoding: Default::default(),
+        continuation_point: cp,
+    };
+
+    // Page through the values with bounds, three at a time.
+    let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+        is_read_modified: false,
+        start_time: at(5),
+        end_time: at(95),
+        num_values_per_node: 3,
+        return_bounds: true,
+    });
+    let mut data = Vec::new();
+    let mut cp = ByteString::null();
+    for i in 0..3 {
+        let r = session
+            .history_read(action.clone(), TimestampsToReturn::Both, false, &[node(cp)])
+            .await
+            .unwrap();
+        assert_eq!(r[0].status_code, StatusCode::Good);
+        assert_eq!(r[0].continuation_point.is_null(), i == 2);
+        data.extend(history_values(&r[0]));
+        cp = r[0].continuation_point.clone();
+    }
+    let values: Vec<_> = data.iter().map(|v| v.value.clone()).collect();
+    let expected: Vec<_> = (0..10)
+        .map(|i| Some(Variant::Int32(i * 10)))
+        .chain([None])
+        .collect();
+    assert_eq!(values, expected);
+    assert_eq!(data[10].status, Some(StatusCode::BadBoundNotFound));
+    assert_eq!(data[10].source_timestamp, Some(at(95)));
+
+    // Read backwards, without bounds.
+    let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+        is_read_modified: false,
+        start_time: at(95),
+        end_time: at(5),
+        num_values_per_node: 0,
+        return_bounds: false,
+    });
+    let r = session
+        .history_read(
+            action,
+            TimestampsToReturn::Both,
+            false,
+            &[node(ByteString::null())],
+        )
+        .await
+        .unwrap();
+    let values: Vec<_> = history_values(&r[0])
+        .into_iter()
+        .map(|v| v.value.unwrap())
+        .collect();
+    let expected: Vec<_> = (1..10).rev().map(|i| Variant::Int32(i * 10)).collect();
+    assert_eq!(values, expected);
+
+    // Read at times between, at, after and before the recorded values.
+    let action = HistoryReadAction::ReadAtTimeDetails(ReadAtTimeDetails {
+        req_times: Some(vec![at(15), at(90), at(200), at(-10)]),
+        use_simple_bounds: true,
+    });
+    let r = session
+        .history_read(
+            action,
+            TimestampsToReturn::Both,
+            false,
+            &[node(ByteString::null())],
+        )
+        .await
+        .unwrap();
+    let data = history_values(&r[0]);
+    assert_eq!(data[0].value, Some(Variant::Int32(15)));
+    assert_eq!(
+        data[0].status().value_type(),
+        StatusCodeValueType::Interpolated
+    );
+    assert!(data[0].status().is_good());
+    assert_eq!(data[1].value, Some(Variant::Int32(90)));
+    assert_eq!(data[1].status(), StatusCode::Good);
+    assert_eq!(data[2].value, Some(Variant::Int32(90)));
+    assert!(data[2].status().is_uncertain());
+    assert_eq!(data[3].status(), StatusCode::BadNoData);
+
+    // Written values are recorded.
+    let r = session
+        .write(&[WriteValue {
+            node_id: id.clone(),
+            attribute_id: AttributeId::Value as u32,
+            index_range: Default::default(),
+            value: DataValue::new_now(123),
+        }])
+        .await
+        .unwrap();
+    assert_eq!(r[0], StatusCode::Good);
+    let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+        is_read_modified: false,
+        start_time: at(100),
+        end_time: DateTime::null(),
+        num_values_per_node: 10,
+        return_bounds: false,
+    });
+    let r = session
+        .history_read(
+            action,
+            TimestampsToReturn::Both,
+            false,
+            &[node(ByteString::null())],
+        )
+        .await
+        .unwrap();
+    let data = history_values(&r[0]);
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].value, Some(Variant::Int32(123)));
+}
+
+#[tokio::test]
+async fn history_sqlite() {
+    let dir = TempDir::new("history_sqlite").unwrap();
+    let path = dir.path().join("history.db");
+    let retention = SqliteHistoryRetention {
+        max_values_per_node: Some(5),
+        ..Default::default()
+    };
+    let provider = Arc::new(SqliteHistoryProvider::open(&path, retention.clone()).unwrap());
+    let (tester, session, id) = history_provider_setup(provider.clone()).await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let at = |secs: i64| start + TimeDelta::try_seconds(secs).unwrap();
+    provider
+        .record_values(
+            (0..10)
+                .map(|i| (id.clone(), DataValue::new_at((i * 10) as i32, at(i * 10))))
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    // Only the five newest values are kept.
+    let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+        is_read_modified: false,
+        start_time: start,
+        end_time: at(1000),
+        num_values_per_node: 0,
+        return_bounds: false,
+    });
+    let r = session
+        .history_read(
+            action,
+            TimestampsToReturn::Both,
+            false,
+            &[HistoryReadValueId {
+                node_id: id.clone(),
+                index_range: Default::default(),
+                data_encoding: Default::default(),
+                continuation_point: Default::default(),
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].status_code, StatusCode::Good);
+    let data = history_values(&r[0]);
+    let values: Vec<_> = data.iter().map(|v| v.value.clone().unwrap()).collect();
+    let expected: Vec<_> = (5..10).map(|i| Variant::Int32(i * 10)).collect();
+    assert_eq!(values, expected);
+    assert_eq!(data[0].source_timestamp, Some(at(50)));
+
+    // History is kept when the database is opened again.
+    drop(session);
+    drop(tester);
+    drop(provider);
+    let provider = SqliteHistoryProvider::open(&path, retention).unwrap();
+    let values = provider
+        .read_raw(
+            &id,
+            &HistoryRawQuery {
+                start: Bound::Included(at(60)),
+                end: Bound::Unbounded,
+                reverse: true,
+                max_values: 2,
+            },
+        )
+        .await
+        .unwrap();
+    let values: Vec<_> = values.into_iter().map(|v| v.value.unwrap()).collect();
+    assert_eq!(values, vec![Variant::Int32(90), Variant::Int32(80)]);
+}
+
+#[tokio::test]
+async fn history_sqlite_max_age() {
+    let retention = SqliteHistoryRetention {
+        max_age: Some(Duration::from_secs(3600)),
+        cleanup_interval: Duration::from_secs(3600),
+        ..Default::default()
+    };
+    let provider = SqliteHistoryProvider::open_in_memory(retention).unwrap();
+    let id = NodeId::new(2, "value");
+    let ago = |secs: i64| DateTime::now() - TimeDelta::try_seconds(secs).unwrap();
+    let read_all = || {
+        provider.read_raw(
+            &id,
+            &HistoryRawQuery {
+                start: Bound::Unbounded,
+                end: Bound::Unbounded,
+                reverse: false,
+                max_values: 10,
+            },
+        )
+    };
+
+    // Values older than the max age are removed when values are recorded.
+    provider
+        .record_values(vec![
+            (id.clone(), DataValue::new_at(1, ago(7200))),
+            (id.clone(), DataValue::new_at(2, ago(60))),
+        ])
+        .await
+        .unwrap();
+    let values: Vec<_> = read_all()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.value.unwrap())
+        .collect();
+    assert_eq!(values, vec![Variant::Int32(2)]);
+
+    // Old values are only removed once per cleanup interval.
+    provider
+        .record_values(vec![(id.clone(), DataValue::new_at(3, ago(7200)))])
+        .await
+        .unwrap();
+    let values: Vec<_> = read_all()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.value.unwrap())
+        .collect();
+    assert_eq!(values, vec![Variant::Int32(3), Variant::Int32(2)]);
+}
+
+/// Update and delete history of a node through `provider`, checking the results,
+/// the modified values and the audit events raised.
+async fn check_history_update(provider: Arc<dyn HistoryProvider>) {
+    let (_tester, session, id) = history_provider_setup(provider.clone()).await;
+
+    // Audit events are only raised when auditing is enabled.
+    let r = session
+        .read(
+            &[read_value_id(
+                AttributeId::Value,
+                VariableId::Server_Auditing,
+            )],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Boolean(true)));
+
+    let (notifs, _, mut events) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let filter = EventFilterBuilder::new()
+        .select(ObjectTypeId::BaseEventType, "EventType")
+        .select(ObjectTypeId::AuditEventType, "Status")
+        .select(
+            ObjectTypeId::AuditHistoryValueUpdateEventType,
+            "UpdatedNode",
+        )
+        .build();
+    session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId {
+                    node_id: ObjectId::Server.into(),
+                    attribute_id: AttributeId::EventNotifier as u32,
+                    ..Default::default()
+                },
+                monitoring_mode: MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    queue_size: 10,
+                    filter: ExtensionObject::new(filter),
+                    ..Default::default()
+                },
+            }],
+        )
+        .await
+        .unwrap();
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let at = |secs: i64| start + TimeDelta::try_seconds(secs).unwrap();
+    provider
+        .record_values(
+            (0..5)
+                .map(|i| (id.clone(), DataValue::new_at((i * 10) as i32, at(i * 10))))
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let update = |perform_insert_replace, values: Vec<(i32, i64)>| {
+        HistoryUpdateAction::UpdateDataDetails(UpdateDataDetails {
+            node_id: id.clone(),
+            perform_insert_replace,
+            update_values: Some(
+                values
+                    .into_iter()
+                    .map(|(v, t)| DataValue::new_at(v, at(t)))
+                    .collect(),
+            ),
+        })
+    };
+    let results = session
+        .history_update(&[
+            update(PerformUpdateType::Insert, vec![(1, 5), (2, 10)]),
+            update(PerformUpdateType::Replace, vec![(3, 20), (4, 25)]),
+            update(PerformUpdateType::Update, vec![(5, 30), (6, 35)]),
+            update(PerformUpdateType::Remove, vec![(7, 40)]),
+        ])
+        .await
+        .unwrap();
+    let statuses: Vec<_> = results
+        .iter()
+        .map(|r| {
+            (
+                r.status_code,
+                r.operation_results.clone().unwrap_or_default(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (
+                StatusCode::Good,
+                vec![StatusCode::GoodEntryInserted, StatusCode::BadEntryExists]
+            ),
+            (
+                StatusCode::Good,
+                vec![StatusCode::GoodEntryReplaced, StatusCode::BadNoEntryExists]
+            ),
+            (
+                StatusCode::Good,
+                vec![StatusCode::GoodEntryReplaced, StatusCode::GoodEntryInserted]
+            ),
+            (StatusCode::BadHistoryOperationInvalid, vec![]),
+        ]
+    );
+
+    let read = |is_read_modified| {
+        let session = session.clone();
+        let id = id.clone();
+        async move {
+            let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+                is_read_modified,
+                start_time: at(0),
+                end_time: at(100),
+                num_values_per_node: 0,
+                return_bounds: false,
+            });
+            let r = session
+                .history_read(
+                    action,
+                    TimestampsToReturn::Both,
+                    false,
+                    &[HistoryReadValueId {
+                        node_id: id,
+                        index_range: Default::default(),
+                        data_encoding: Default::default(),
+                        continuation_point: Default::default(),
+                    }],
+                )
+                .await
+                .unwrap();
+            assert_eq!(r[0].status_code, StatusCode::Good);
+            r.into_iter().next().unwrap()
+        }
+    };
+    let values = |r: &HistoryReadResult| -> Vec<(i32, DateTime)> {
+        let data = if let Some(d) = r.history_data.inner_as::<HistoryModifiedData>() {
+            d.data_values.clone().unwrap()
+        } else {
+            history_values(r)
+        };
+        data.into_iter()
+            .map(|v| {
+                let Some(Variant::Int32(i)) = v.value else {
+                    panic!("Unexpected value {:?}", v.value);
+                };
+                (i, v.source_timestamp.unwrap())
+            })
+            .collect()
+    };
+
+    let r = read(false).await;
+    assert_eq!(
+        values(&r),
+        vec![
+            (0, at(0)),
+            (1, at(5)),
+            (10, at(10)),
+            (3, at(20)),
+            (5, at(30)),
+            (6, at(35)),
+            (40, at(40))
+        ]
+    );
+
+    // Delete a range of values, and values at specific times.
+    let results = session
+        .history_update(&[
+            HistoryUpdateAction::DeleteRawModifiedDetails(DeleteRawModifiedDetails {
+                node_id: id.clone(),
+                is_delete_modified: false,
+                start_time: at(30),
+                end_time: at(40),
+            }),
+            HistoryUpdateAction::DeleteAtTimeDetails(DeleteAtTimeDetails {
+                node_id: id.clone(),
+                req_times: Some(vec![at(5), at(6)]),
+            }),
+            HistoryUpdateAction::DeleteRawModifiedDetails(DeleteRawModifiedDetails {
+                node_id: id.clone(),
+                is_delete_modified: false,
+                start_time: at(500),
+                end_time: at(600),
+            }),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(results[0].status_code, StatusCode::Good);
+    assert_eq!(results[1].status_code, StatusCode::Good);
+    assert_eq!(
+        results[1].operation_results,
+        Some(vec![StatusCode::Good, StatusCode::BadNoEntryExists])
+    );
+    assert_eq!(results[2].status_code, StatusCode::GoodNoData);
+
+    let r = read(false).await;
+    assert_eq!(
+        values(&r),
+        vec![(0, at(0)), (10, at(10)), (3, at(20)), (40, at(40))]
+    );
+
+    // Modified values are the inserted values and the values before they were replaced
+    // or deleted.
+    let r = read(true).await;
+    let modified = r.history_data.inner_as::<HistoryModifiedData>().unwrap();
+    let update_types: Vec<_> = modified
+        .modification_infos
+        .iter()
+        .flatten()
+        .map(|i| i.update_type)
+        .collect();
+    assert_eq!(
+        values(&r),
+        vec![
+            (1, at(5)),
+            (1, at(5)),
+            (20, at(20)),
+            (30, at(30)),
+            (5, at(30)),
+            (6, at(35)),
+            (6, at(35)),
+        ]
+    );
+    assert_eq!(update_types.len(), 7);
+    assert!(update_types.contains(&HistoryUpdateType::Replace));
+    assert!(update_types.contains(&HistoryUpdateType::Delete));
+
+    // Each successful update raised an audit event.
+    let mut received = Vec::new();
+    while received.len() < 6 {
+        let (_, evt) = tokio::time::timeout(Duration::from_secs(2), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(evt.unwrap());
+    }
+    let value_update: NodeId = ObjectTypeId::AuditHistoryValueUpdateEventType.into();
+    let raw_delete: NodeId = ObjectTypeId::AuditHistoryRawModifyDeleteEventType.into();
+    let at_time_delete: NodeId = ObjectTypeId::AuditHistoryAtTimeDeleteEventType.into();
+    let summary: Vec<_> = received
+        .iter()
+        .map(|e| (e[0].clone(), e[1].clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (value_update.clone().into(), false.into()),
+            (value_update.clone().into(), false.into()),
+            (value_update.into(), true.into()),
+            (raw_delete.clone().into(), true.into()),
+            (at_time_delete.into(), false.into()),
+            (raw_delete.into(), true.into()),
+        ]
+    );
+    assert_eq!(received[0][2], Variant::from(id.clone()));
+}
+
+#[tokio::test]
+async fn history_provider_update() {
+    check_history_update(Arc::new(InMemoryHistoryProvider::new(100))).await;
+}
+
+#[tokio::test]
+async fn history_sqlite_update() {
+    let provider = SqliteHistoryProvider::open_in_memory(Default::default()).unwrap();
+    check_history_update(Arc::new(provider)).await;
+}
+
+#[tokio::test]
+async fn history_provider_aggregates() {
+    let provider = Arc::new(InMemoryHistoryProvider::new(100));
+    let (_tester, session, id) = history_provider_setup(provider.clone()).await;
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let at = |secs: i64| start + TimeDelta::try_seconds(secs).unwrap();
+    for i in 0..10 {
+        provider.push_value(&id, DataValue::new_at((i * 10) as i32, at(i * 10)));
+    }
+    // A bad value in the second interval, replaced by a good value at 50 seconds.
+    provider.push_value(
+        &id,
+        DataValue {
+            value: Some(Variant::Int32(1000)),
+            status: Some(StatusCode::BadSensorFailure),
+            source_timestamp: Some(at(45)),
+            ..Default::default()
+        },
+    );
+
+    let aggregates = [
+        ObjectId::AggregateFunction_Average,
+        ObjectId::AggregateFunction_TimeAverage,
+        ObjectId::AggregateFunction_Minimum,
+        ObjectId::AggregateFunction_Count,
+        ObjectId::AggregateFunction_PercentBad,
+        ObjectId::AggregateFunction_AnnotationCount,
+    ];
+    let nodes: Vec<_> = aggregates
+        .iter()
+        .map(|a| (id.clone(), (*a).into()))
+        .collect();
+    let series = session
+        .history_read_processed(&nodes, at(0)..at(100), Duration::from_secs(30), None)
+        .await
+        .unwrap();
+    let values = |idx: usize| {
+        assert_eq!(series[idx].status, StatusCode::Good);
+        series[idx]
+            .values
+            .iter()
+            .map(|v| v.value.clone().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // The bad value is left out, which makes the second interval uncertain.
+    assert_eq!(
+        values(0),
+        vec![10.0.into(), 40.0.into(), 70.0.into(), 90.0.into()]
+    );
+    let avg = &series[0].values;
+    assert_eq!(avg[0].status.sub_code(), StatusCode::Good.sub_code());
+    assert_eq!(
+        avg[1].status.sub_code(),
+        StatusCode::UncertainDataSubNormal.sub_code()
+    );
+    assert_eq!(avg[0].value_type(), StatusCodeValueType::Calculated);
+    assert_eq!(avg[2].timestamp, Some(at(60)));
+    // The last interval is shorter than the processing interval.
+    assert!(!avg[2].is_partial());
+    assert!(avg[3].is_partial());
+
+    // The time average interpolates between values, including the bounds of the interval.
+    assert_eq!(values(1)[0], Variant::Double(15.0));
+    assert_eq!(values(2), vec![0.into(), 30.into(), 60.into(), 90.into()]);
+    assert_eq!(values(3), vec![3.into(), 3.into(), 3.into(), 1.into()]);
+    // The bad value is in effect for 5 of 30 seconds.
+    let Variant::Double(percent_bad) = values(4)[1] else {
+        panic!("Expected a double");
+    };
+    assert!((percent_bad - 100.0 / 6.0).abs() < 1e-9);
+    assert_eq!(values(4)[0], Variant::Double(0.0));
+    assert_eq!(series[5].status, StatusCode::BadAggregateNotSupported);
+
+    // Interpolated values between raw values keep the type of the raw values.
+    let series = session
+        .history_read_processed(
+            &[(id.clone(), ObjectId::AggregateFunction_Interpolative.into())],
+            at(5)..at(35),
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+    let interpolated = &series[0].values;
+    assert_eq!(
+        interpolated
+            .iter()
+            .map(|v| v.value.clone().unwrap())
+            .collect::<Vec<_>>(),
+        vec![5.into(), 15.into(), 25.into()]
+    );
+    assert_eq!(
+        interpolated[0].value_type(),
+        StatusCodeValueType::Interpolated
+    );
+
+    // Reading in reverse returns the intervals from the start time backwards.
+    let series = session
+        .history_read_processed(
+            &[(id.clone(), ObjectId::AggregateFunction_Maximum.into())],
+            at(100)..at(0),
+            Duration::from_secs(30),
+            None,
+        )
+        .await
+        .unwrap();
+    let max = &series[0].values;
+    assert_eq!(
+        max.iter()
+            .map(|v| (v.timestamp.unwrap(), v.value.clone().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![
+            (at(70), 90.into()),
+            (at(40), 60.into()),
+            (at(10), 30.into()),
+            (at(0), 0.into())
+        ]
+    );
+    assert!(max[3].is_partial());
+
+    // The supported aggregates are listed in the server capabilities.
+    let r = session
+        .browse(
+            &[BrowseDescription {
+                node_id: ObjectId::Server_ServerCapabilities_AggregateFunctions.into(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::Organizes.into(),
+                include_subtypes: true,
+                node_class_mask: 0,
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            1000,
+            None,
+        )
+        .await
+        .unwrap();
+    let refs = r[0].references.clone().unwrap_or_default();
+    assert_eq!(refs.len(), SUPPORTED_AGGREGATES.len());
+    assert!(refs
+        .iter()
+        .any(|r| r.node_id.node_id == ObjectId::AggregateFunction_TimeAverage2));
+}
+
+#[tokio::test]
+async fn read_retry() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .historizing(true)
+            .value(1)
+            .description("Description")
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    // Tell the node manager to fail fatally three times.
+    nm.inner().issues().fatal_read.store(3, Ordering::Relaxed);
+
+    // Make one request first, it should fail
+    let e = session
+        .read(
+            &[ReadValueId {
+                node_id: id.clone(),
+                attribute_id: AttributeId::Value as u32,
+                ..Default::default()
+            }],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
         .unwrap_err();
     assert_eq!(e, StatusCode::BadInternalError);
 
@@ -1196,3 +2541,553 @@ async fn test_diagnostics() {
     assert_eq!(diagnostics[2].value, Some(Variant::UInt32(1)));
     assert_eq!(diagnostics[3].value, Some(Variant::UInt32(0)));
 }
+
+async fn read_summary_counts(session: &Session, ids: &[VariableId]) -> Vec<Option<Variant>> {
+    let to_read: Vec<_> = ids
+        .iter()
+        .map(|id| ReadValueId::new_value((*id).into()))
+        .collect();
+    session
+        .read(&to_read, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.value)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_diagnostics_summary_and_enabled_flag() {
+    let server = default_server().diagnostics_enabled(true);
+    let mut tester = Tester::new(server, true).await;
+
+    // A session rejected because of invalid credentials.
+    let (_, lp) = tester
+        .connect(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::UserName(CLIENT_USERPASS_ID.to_owned(), "invalid".into()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        lp.spawn().await.unwrap(),
+        StatusCode::BadIdentityTokenRejected
+    );
+
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let (notifs, _data, _) = ChannelNotifications::new();
+    session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let (notifs, _data2, _) = ChannelNotifications::new();
+    session
+        .create_subscription(Duration::from_millis(200), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let counts = read_summary_counts(
+        &session,
+        &[
+            VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_RejectedSessionCount,
+            VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_SecurityRejectedSessionCount,
+            VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_PublishingIntervalCount,
+            VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSubscriptionCount,
+            VariableId::Server_ServerDiagnostics_EnabledFlag,
+        ],
+    )
+    .await;
+    // The client may retry activating the rejected session.
+    for count in &counts[..2] {
+        let Some(Variant::UInt32(count)) = count else {
+            panic!("Expected count, got {count:?}");
+        };
+        assert!(*count >= 1);
+    }
+    assert_eq!(counts[2], Some(Variant::UInt32(2)));
+    assert_eq!(counts[3], Some(Variant::UInt32(2)));
+    assert_eq!(counts[4], Some(Variant::Boolean(true)));
+
+    let disable = WriteValue {
+        node_id: VariableId::Server_ServerDiagnostics_EnabledFlag.into(),
+        attribute_id: AttributeId::Value as u32,
+        value: DataValue::new_now(false),
+        ..Default::default()
+    };
+    // Only users with the ConfigureAdmin role may toggle diagnostics.
+    let r = session.write(std::slice::from_ref(&disable)).await.unwrap();
+    assert_eq!(r[0], StatusCode::BadUserAccessDenied);
+
+    tester
+        .handle
+        .info()
+        .roles
+        .add_identity(
+            &ObjectId::WellKnownRole_ConfigureAdmin.into(),
+            IdentityMappingRuleType {
+                criteria_type: IdentityCriteriaType::UserName,
+                criteria: CLIENT_USERPASS_ID.into(),
+            },
+        )
+        .unwrap();
+    let admin = tester
+        .connect_and_wait(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let r = admin.write(std::slice::from_ref(&disable)).await.unwrap();
+    assert_eq!(r[0], StatusCode::Good);
+    assert!(!tester.handle.info().diagnostics.enabled());
+
+    // Sessions created while diagnostics are disabled are not counted.
+    let ids = [
+        VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSessionCount,
+        VariableId::Server_ServerDiagnostics_EnabledFlag,
+    ];
+    let counts = read_summary_counts(&admin, &ids).await;
+    assert_eq!(counts[1], Some(Variant::Boolean(false)));
+    let cumulated = tester
+        .handle
+        .info()
+        .diagnostics
+        .summary
+        .get(VariableId::Server_ServerDiagnostics_ServerDiagnosticsSummary_CumulatedSessionCount)
+        .unwrap()
+        .value;
+    let late = tester
+        .connect_and_wait(
+            SecurityPolicy::Aes128Sha256RsaOaep,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    assert!(tester.handle.info().diagnostics.sessions().is_empty());
+
+    let r = admin
+        .write(&[WriteValue {
+            value: DataValue::new_now(true),
+            ..disable
+        }])
+        .await
+        .unwrap();
+    assert_eq!(r[0], StatusCode::Good);
+    let counts = read_summary_counts(&admin, &ids).await;
+    assert_eq!(counts[0], cumulated);
+    assert_eq!(counts[1], Some(Variant::Boolean(true)));
+
+    // Sessions are still tracked while diagnostics are disabled.
+    let (diagnostics, _) = read_session_diagnostics(&admin).await;
+    for s in [&session, &admin, &late] {
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.session_id == s.server_session_id()));
+    }
+}
+
+async fn read_session_diagnostics(session: &Session) -> (Vec<SessionDiagnosticsDataType>, usize) {
+    let r = session
+        .read(
+            &[
+                ReadValueId::new_value(
+                    VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionDiagnosticsArray
+                        .into(),
+                ),
+                ReadValueId::new_value(
+                    VariableId::Server_ServerDiagnostics_SessionsDiagnosticsSummary_SessionSecurityDiagnosticsArray
+                        .into(),
+                ),
+            ],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::Array(diagnostics)) = &r[0].value else {
+        panic!("Expected array, got {:?}", r[0]);
+    };
+    let Some(Variant::Array(security)) = &r[1].value else {
+        panic!("Expected array, got {:?}", r[1]);
+    };
+    let diagnostics: Vec<SessionDiagnosticsDataType> = diagnostics
+        .values
+        .iter()
+        .map(|v| {
+            let Variant::ExtensionObject(o) = v else {
+                panic!("Expected extension object");
+            };
+            o.inner_as::<SessionDiagnosticsDataType>().unwrap().clone()
+        })
+        .collect();
+    (diagnostics, security.values.len())
+}
+
+#[tokio::test]
+async fn test_session_diagnostics() {
+    let server = default_server().diagnostics_enabled(true);
+    let mut tester = Tester::new(server, false).await;
+    let mut sessions = Vec::new();
+    for _ in 0..2 {
+        let (session, lp) = tester
+            .connect(
+                opcua_crypto::SecurityPolicy::Aes128Sha256RsaOaep,
+                opcua_types::MessageSecurityMode::SignAndEncrypt,
+                client_user_token(),
+            )
+            .await
+            .unwrap();
+        lp.spawn();
+        tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+            .await
+            .unwrap();
+        sessions.push(session);
+    }
+    let session = &sessions[0];
+
+    // Make a request that should fail
+    session
+        .read(
+            &[read_value_id(AttributeId::DisplayName, ObjectId::Server)],
+            TimestampsToReturn::Both,
+            -15.0,
+        )
+        .await
+        .unwrap_err();
+
+    let (diagnostics, security_count) = read_session_diagnostics(session).await;
+    assert_eq!(2, diagnostics.len());
+    assert_eq!(2, security_count);
+    let own = diagnostics
+        .iter()
+        .find(|d| d.session_id == session.server_session_id())
+        .unwrap();
+    assert!(own.read_count.total_count >= 2);
+    assert_eq!(own.read_count.error_count, 1);
+    assert_eq!(own.total_request_count.error_count, 1);
+    assert!(!own.client_description.application_uri.is_empty());
+
+    // The session objects are available under SessionsDiagnosticsSummary.
+    let r = session
+        .browse(
+            &[BrowseDescription {
+                node_id: ObjectId::Server_ServerDiagnostics_SessionsDiagnosticsSummary.into(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::HasComponent.into(),
+                include_subtypes: true,
+                node_class_mask: NodeClass::Object as u32,
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            100,
+            None,
+        )
+        .await
+        .unwrap();
+    let refs = r[0].references.clone().unwrap_or_default();
+    assert_eq!(2, refs.len());
+    for rf in &refs {
+        assert_eq!(
+            rf.type_definition.node_id,
+            NodeId::from(ObjectTypeId::SessionDiagnosticsObjectType)
+        );
+    }
+
+    // Read the diagnostics of each session object.
+    let mut session_ids = Vec::new();
+    for rf in &refs {
+        let r = session
+            .browse(
+                &[BrowseDescription {
+                    node_id: rf.node_id.node_id.clone(),
+                    browse_direction: BrowseDirection::Forward,
+                    reference_type_id: ReferenceTypeId::HasComponent.into(),
+                    include_subtypes: true,
+                    node_class_mask: NodeClass::Variable as u32,
+                    result_mask: BrowseResultMask::All as u32,
+                }],
+                100,
+                None,
+            )
+            .await
+            .unwrap();
+        let vars = r[0].references.clone().unwrap_or_default();
+        assert_eq!(3, vars.len());
+        let var = vars
+            .iter()
+            .find(|v| v.browse_name.name.as_ref() == "SessionDiagnostics")
+            .unwrap();
+        let r = session
+            .read(
+                &[ReadValueId::new_value(var.node_id.node_id.clone())],
+                TimestampsToReturn::Both,
+                0.0,
+            )
+            .await
+            .unwrap();
+        let Some(Variant::ExtensionObject(o)) = &r[0].value else {
+            panic!("Expected extension object, got {:?}", r[0]);
+        };
+        let diag = o.inner_as::<SessionDiagnosticsDataType>().unwrap();
+        session_ids.push(diag.session_id.clone());
+    }
+    assert!(session_ids.contains(&session.server_session_id()));
+    assert!(session_ids.contains(&sessions[1].server_session_id()));
+
+    // Closing a session removes it from the diagnostics.
+    sessions[1].disconnect().await.unwrap();
+    let (diagnostics, security_count) = read_session_diagnostics(session).await;
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(1, security_count);
+    assert_eq!(diagnostics[0].session_id, session.server_session_id());
+}
+
+#[tokio::test]
+async fn test_subscription_diagnostics() {
+    let server = default_server().diagnostics_enabled(true);
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester
+        .connect(
+            opcua_crypto::SecurityPolicy::Aes128Sha256RsaOaep,
+            opcua_types::MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId::new_value(
+                    VariableId::Server_ServerStatus_CurrentTime.into(),
+                ),
+                monitoring_mode: MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    sampling_interval: 100.0,
+                    queue_size: 10,
+                    discard_oldest: true,
+                    ..Default::default()
+                },
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res[0].result.status_code, StatusCode::Good);
+    tokio::time::timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    session
+        .modify_subscription(sub_id, Duration::from_millis(200), 100, 20, 1000, 0)
+        .await
+        .unwrap();
+    session.set_publishing_mode(&[sub_id], false).await.unwrap();
+
+    let r = session
+        .read(
+            &[ReadValueId::new_value(
+                VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray.into(),
+            )],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::Array(arr)) = &r[0].value else {
+        panic!("Expected array, got {:?}", r[0]);
+    };
+    assert_eq!(1, arr.values.len());
+    let Variant::ExtensionObject(o) = &arr.values[0] else {
+        panic!("Expected extension object");
+    };
+    let diag = o.inner_as::<SubscriptionDiagnosticsDataType>().unwrap();
+    assert_eq!(diag.subscription_id, sub_id);
+    assert_eq!(diag.session_id, session.server_session_id());
+    assert_eq!(diag.publishing_interval, 200.0);
+    assert!(!diag.publishing_enabled);
+    assert_eq!(diag.modify_count, 1);
+    assert_eq!(diag.disable_count, 1);
+    assert_eq!(diag.enable_count, 0);
+    assert_eq!(diag.monitored_item_count, 1);
+    assert!(diag.publish_request_count >= 1);
+    assert!(diag.data_change_notifications_count >= 1);
+    assert_eq!(
+        diag.notifications_count,
+        diag.data_change_notifications_count + diag.event_notifications_count
+    );
+}
+
+#[tokio::test]
+async fn read_registered_nodes() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(5)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let mut nodes = RegisteredNodes::register(session.clone(), vec![id.clone()])
+        .await
+        .unwrap();
+    assert_eq!(nodes.registered_node_ids(), std::slice::from_ref(&id));
+    nm.inner()
+        .with_call_info(|c| assert_eq!(c.register_nodes, vec![id.clone()]));
+
+    for _ in 0..3 {
+        let r = nodes.read(TimestampsToReturn::Both, 0.0).await.unwrap();
+        assert_eq!(1, r.len());
+        assert_eq!(&Variant::Int32(5), r[0].value.as_ref().unwrap());
+    }
+
+    // Nodes are unregistered in the background when dropped.
+    drop(nodes);
+    for _ in 0..50 {
+        if nm
+            .inner()
+            .with_call_info(|c| !c.unregister_nodes.is_empty())
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    nm.inner()
+        .with_call_info(|c| assert_eq!(c.unregister_nodes, vec![id.clone()]));
+}
+
+#[tokio::test]
+async fn read_cache() {
+    let client = default_client(0, false).read_cache_options(ReadCacheOptions { max_entries: 10 });
+    let mut tester = Tester::new_custom_client(test_server(), client).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let service_level = [read_value_id(
+        AttributeId::Value,
+        VariableId::Server_ServiceLevel,
+    )];
+    let read = |nodes: &[ReadValueId], max_age: f64| {
+        let nodes = nodes.to_vec();
+        let session = session.clone();
+        async move {
+            session
+                .read(&nodes, TimestampsToReturn::Both, max_age)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|v| v.value.unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    tester.handle.set_service_level(100);
+    assert_eq!(
+        read(&service_level, 60_000.0).await,
+        vec![Variant::Byte(100)]
+    );
+    tester.handle.set_service_level(200);
+    // A read accepting older values is answered from the cache.
+    assert_eq!(
+        read(&service_level, 60_000.0).await,
+        vec![Variant::Byte(100)]
+    );
+    assert_eq!(
+        read(&service_level, f64::from(i32::MAX)).await,
+        vec![Variant::Byte(100)]
+    );
+    // A max age of 0 always reads from the server, and updates the cache.
+    assert_eq!(read(&service_level, 0.0).await, vec![Variant::Byte(200)]);
+    tester.handle.set_service_level(150);
+    assert_eq!(
+        read(&service_level, 60_000.0).await,
+        vec![Variant::Byte(200)]
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(read(&service_level, 10.0).await, vec![Variant::Byte(150)]);
+
+    // Writing a value removes it from the cache, cached and uncached values are
+    // returned in the order they were requested.
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "CachedVar", "CachedVar")
+            .value(1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+            .user_access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    let var = ReadValueId::new_value(id.clone());
+    assert_eq!(
+        read(std::slice::from_ref(&var), 60_000.0).await,
+        vec![Variant::Int32(1)]
+    );
+    let r = session
+        .write(&[WriteValue {
+            node_id: id.clone(),
+            attribute_id: AttributeId::Value as u32,
+            value: DataValue::new_now(2),
+            ..Default::default()
+        }])
+        .await
+        .unwrap();
+    assert_eq!(r, vec![StatusCode::Good]);
+    assert_eq!(
+        read(&[service_level[0].clone(), var.clone()], 60_000.0).await,
+        vec![Variant::Byte(150), Variant::Int32(2)]
+    );
+
+    session.clear_read_cache();
+    tester.handle.set_service_level(50);
+    assert_eq!(
+        read(&service_level, 60_000.0).await,
+        vec![Variant::Byte(50)]
+    );
+}
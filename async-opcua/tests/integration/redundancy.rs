@@ -0,0 +1,342 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::utils::{
+    default_client, setup, test_server, ChannelNotifications, TestNodeManager, Tester,
+};
+use futures::{stream::BoxStream, StreamExt};
+use opcua::{
+    server::{
+        address_space::{AccessLevel, VariableBuilder},
+        ServerBuilder,
+    },
+    types::{
+        AttributeId, DataTypeId, DataValue, MessageSecurityMode, MonitoredItemCreateRequest,
+        MonitoringParameters, NodeId, ObjectId, ReadValueId, RedundancySupport, ReferenceTypeId,
+        ServerState, StatusCode, TimestampsToReturn, VariableTypeId, Variant,
+    },
+};
+use opcua_client::{
+    FailoverMode, IdentityToken, RedundancyEvent, RedundancyOptions, RedundantSession,
+    ServerStatusEvent,
+};
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+async fn redundant_server() -> (Tester, Arc<TestNodeManager>, NodeId) {
+    redundant_server_from(test_server()).await
+}
+
+async fn redundant_server_from(server: ServerBuilder) -> (Tester, Arc<TestNodeManager>, NodeId) {
+    let tester = Tester::new(server, false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "RedundantVar", "RedundantVar")
+            .value(-1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    (tester, nm, id)
+}
+
+async fn recv_value(data: &mut UnboundedReceiver<(ReadValueId, DataValue)>, expected: i32) {
+    timeout(Duration::from_secs(2), async {
+        while let Some((_, v)) = data.recv().await {
+            if v.value == Some(Variant::Int32(expected)) {
+                return;
+            }
+        }
+        panic!("Notification channel closed");
+    })
+    .await
+    .unwrap();
+}
+
+async fn connect_redundant(testers: [&Tester; 2], mode: FailoverMode) -> RedundantSession {
+    let mut options = RedundancyOptions::new(mode);
+    options.service_level_interval = Duration::from_millis(100);
+    options.connect_timeout = Duration::from_secs(2);
+    let client = default_client(testers[0].test_id, false).client().unwrap();
+    let mut endpoints = Vec::new();
+    for tester in testers {
+        let endpoint = client
+            .get_server_endpoints_from_url(tester.endpoint().as_str())
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|e| e.security_mode == MessageSecurityMode::None)
+            .unwrap();
+        endpoints.push(endpoint);
+    }
+    RedundantSession::connect(client, endpoints, IdentityToken::Anonymous, options).unwrap()
+}
+
+async fn wait_for_event(
+    events: &mut BoxStream<'static, RedundancyEvent>,
+    expected: impl Fn(&RedundancyEvent) -> bool,
+) {
+    timeout(Duration::from_secs(10), async {
+        while let Some(evt) = events.next().await {
+            if expected(&evt) {
+                return;
+            }
+        }
+        panic!("Event stream ended");
+    })
+    .await
+    .unwrap();
+}
+
+async fn failover(mode: FailoverMode) {
+    let (tester_a, _, id) = redundant_server().await;
+    let (tester_b, nm_b, id_b) = redundant_server().await;
+    assert_eq!(id, id_b);
+
+    let redundant = connect_redundant([&tester_a, &tester_b], mode).await;
+    let mut events = redundant.events();
+
+    let session = redundant.session();
+    timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    let info = session.read_server_redundancy().await.unwrap();
+    assert_eq!(info.redundancy_support, RedundancySupport::None);
+    assert_eq!(info.service_level, 255);
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId::new(id.clone(), AttributeId::Value),
+                monitoring_mode: opcua::types::MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    client_handle: 1,
+                    sampling_interval: 0.0,
+                    queue_size: 10,
+                    discard_oldest: true,
+                    ..Default::default()
+                },
+            }],
+        )
+        .await
+        .unwrap();
+    let (_, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(v.value, Some(Variant::Int32(-1)));
+
+    // Give the monitor time to copy the subscription to the backup server in hot mode.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    nm_b.set_value(
+        tester_b.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(5),
+    )
+    .unwrap();
+
+    tester_a.handle.set_service_level(0);
+    wait_for_event(&mut events, |e| {
+        *e == RedundancyEvent::FailoverCompleted { from: 0, to: 1 }
+    })
+    .await;
+    assert_eq!(redundant.active_server(), 1);
+    assert!(!Arc::ptr_eq(&session, &redundant.session()));
+
+    // The subscription now gets values from the backup server, through the same callback.
+    // In hot mode, the copy of the subscription also delivers the values it sampled before
+    // failover.
+    recv_value(&mut data, 5).await;
+    nm_b.set_value(
+        tester_b.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(6),
+    )
+    .unwrap();
+    recv_value(&mut data, 6).await;
+
+    let levels = redundant.service_levels();
+    assert_eq!(levels[1], Some(255));
+
+    redundant.close().await;
+}
+
+#[tokio::test]
+async fn redundancy_cold_failover() {
+    failover(FailoverMode::Cold).await;
+}
+
+#[tokio::test]
+async fn redundancy_warm_failover() {
+    failover(FailoverMode::Warm).await;
+}
+
+#[tokio::test]
+async fn redundancy_hot_failover() {
+    failover(FailoverMode::Hot).await;
+}
+
+#[tokio::test]
+async fn redundancy_failover_on_shutdown() {
+    let (tester_a, _, _) = redundant_server().await;
+    let (tester_b, _, _) = redundant_server().await;
+
+    let redundant = connect_redundant([&tester_a, &tester_b], FailoverMode::Warm).await;
+    let mut events = redundant.events();
+    timeout(
+        Duration::from_secs(2),
+        redundant.session().wait_for_connection(),
+    )
+    .await
+    .unwrap();
+
+    // The session moves to the backup server before the active server goes away.
+    tester_a
+        .handle
+        .shutdown_after(Duration::from_secs(60), "Maintenance");
+    wait_for_event(&mut events, |e| {
+        matches!(
+            e,
+            RedundancyEvent::ShutdownAnnounced {
+                server: 0,
+                seconds_till_shutdown: Some(1..=60),
+            }
+        )
+    })
+    .await;
+    wait_for_event(&mut events, |e| {
+        *e == RedundancyEvent::FailoverCompleted { from: 0, to: 1 }
+    })
+    .await;
+    assert_eq!(redundant.active_server(), 1);
+
+    redundant.close().await;
+}
+
+#[tokio::test]
+async fn redundancy_partial_failover() {
+    let (tester_a, _, _) = redundant_server().await;
+    // The backup server rejects all subscriptions.
+    let mut server = test_server();
+    server
+        .limits_mut()
+        .subscriptions
+        .max_subscriptions_per_session = 0;
+    let (tester_b, _, _) = redundant_server_from(server).await;
+
+    let redundant = connect_redundant([&tester_a, &tester_b], FailoverMode::Cold).await;
+    let mut events = redundant.events();
+    let session = redundant.session();
+    timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    let (notifs, _data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    // The failover is reported as partial, with the subscription that could not be moved.
+    tester_a.handle.set_service_level(0);
+    wait_for_event(&mut events, |e| {
+        *e == RedundancyEvent::FailoverPartial {
+            from: 0,
+            to: 1,
+            failed: vec![(sub_id, StatusCode::BadTooManySubscriptions)],
+        }
+    })
+    .await;
+    assert_eq!(redundant.active_server(), 1);
+
+    redundant.close().await;
+}
+
+async fn next_status_event(
+    events: &mut BoxStream<'static, ServerStatusEvent>,
+) -> ServerStatusEvent {
+    timeout(Duration::from_secs(2), events.next())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn server_status_monitor() {
+    let (tester, _nm, session) = setup().await;
+
+    let monitor = session.monitor_server_status(Duration::from_millis(100));
+    let (tx, mut callback_events) = tokio::sync::mpsc::unbounded_channel();
+    monitor.on_event(move |e| {
+        let _ = tx.send(e.clone());
+    });
+    let mut events = monitor.events();
+
+    assert_eq!(
+        next_status_event(&mut events).await,
+        ServerStatusEvent::StateChanged {
+            previous: None,
+            state: ServerState::Running
+        }
+    );
+    assert_eq!(
+        next_status_event(&mut events).await,
+        ServerStatusEvent::ServiceLevelChanged {
+            previous: None,
+            service_level: 255
+        }
+    );
+
+    tester.handle.set_service_level(100);
+    assert_eq!(
+        next_status_event(&mut events).await,
+        ServerStatusEvent::ServiceLevelChanged {
+            previous: Some(255),
+            service_level: 100
+        }
+    );
+
+    tester
+        .handle
+        .shutdown_after(Duration::from_secs(60), "Maintenance");
+    let ServerStatusEvent::ShutdownAnnounced {
+        seconds_till_shutdown,
+        reason,
+    } = next_status_event(&mut events).await
+    else {
+        panic!("Expected a shutdown announcement");
+    };
+    assert!(seconds_till_shutdown.is_some_and(|s| s > 0 && s <= 60));
+    assert_eq!(reason.unwrap().text.as_ref(), "Maintenance");
+    assert!(monitor.status().unwrap().shutdown_announced());
+
+    // Callbacks get the same events.
+    let mut received = Vec::new();
+    while let Ok(e) = callback_events.try_recv() {
+        received.push(e);
+    }
+    assert_eq!(received.len(), 4);
+    assert!(matches!(
+        received[3],
+        ServerStatusEvent::ShutdownAnnounced { .. }
+    ));
+}
@@ -0,0 +1,919 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::utils::{
+    client_user_token, setup, test_server, ChannelNotifications, Tester, CLIENT_USERPASS_ID,
+};
+use opcua::{
+    client::{IdentityToken, Session},
+    crypto::SecurityPolicy,
+    nodes::{BaseEventType, Event},
+    server::{
+        address_space::{
+            AccessLevel, EventNotifier, MethodBuilder, ObjectBuilder, VariableBuilder,
+        },
+        diagnostics::NamespaceMetadata,
+        node_manager::memory::{
+            InMemoryNodeManagerBuilder, SimpleNodeManager, SimpleNodeManagerBuilder,
+        },
+    },
+    types::{
+        AccessRestrictionType, AddNodeAttributes, AddNodesItem, AttributeId, BrowseDescription,
+        BrowseDirection, BrowseResultMask, CallMethodRequest, ContentFilter, DataTypeId, DataValue,
+        DeleteNodesItem, EventFilter, ExpandedNodeId, ExtensionObject, IdentityCriteriaType,
+        IdentityMappingRuleType, MessageSecurityMode, MethodId, MonitoredItemCreateRequest,
+        MonitoringMode, MonitoringParameters, NodeClass, NodeId, ObjectAttributes, ObjectId,
+        ObjectTypeId, PermissionType, ReadValueId, ReferenceTypeId, RolePermissionType,
+        SimpleAttributeOperand, StatusCode, TimestampsToReturn, VariableId, VariableTypeId,
+        Variant, WriteValue,
+    },
+};
+
+fn session_roles(tester: &Tester, session: &Session) -> Vec<NodeId> {
+    let server_session = tester
+        .handle
+        .session_manager()
+        .read()
+        .find_by_id(&session.server_session_id())
+        .unwrap();
+    let server_session = server_session.read();
+    server_session.roles().to_vec()
+}
+
+fn user_rule() -> IdentityMappingRuleType {
+    IdentityMappingRuleType {
+        criteria_type: IdentityCriteriaType::UserName,
+        criteria: CLIENT_USERPASS_ID.into(),
+    }
+}
+
+#[tokio::test]
+async fn session_roles_from_identity() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    assert_eq!(
+        session_roles(&tester, &session),
+        vec![NodeId::from(ObjectId::WellKnownRole_Anonymous)]
+    );
+
+    // Anonymous users may not manage roles.
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_AddRole.into(),
+            input_arguments: Some(vec!["MyRole".into(), "".into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadUserAccessDenied);
+
+    // Map the test user to the operator role as well.
+    tester
+        .handle
+        .info()
+        .roles
+        .add_identity(&ObjectId::WellKnownRole_Operator.into(), user_rule())
+        .unwrap();
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let roles = session_roles(&tester, &session);
+    assert_eq!(roles.len(), 2);
+    assert!(roles.contains(&ObjectId::WellKnownRole_AuthenticatedUser.into()));
+    assert!(roles.contains(&ObjectId::WellKnownRole_Operator.into()));
+
+    // The identities are reflected in the address space.
+    let r = session
+        .read(
+            &[ReadValueId {
+                node_id: VariableId::WellKnownRole_Operator_Identities.into(),
+                attribute_id: AttributeId::Value as u32,
+                ..Default::default()
+            }],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::Array(arr)) = &r[0].value else {
+        panic!("Expected array, got {:?}", r[0].value);
+    };
+    assert_eq!(arr.values.len(), 1);
+    let Variant::ExtensionObject(o) = &arr.values[0] else {
+        panic!("Expected extension object");
+    };
+    assert_eq!(o.inner_as::<IdentityMappingRuleType>(), Some(&user_rule()));
+
+    // Anonymous users are never mapped to roles matching user names.
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        session_roles(&tester, &session),
+        vec![NodeId::from(ObjectId::WellKnownRole_Anonymous)]
+    );
+}
+
+#[tokio::test]
+async fn manage_roles() {
+    let mut tester = Tester::new(test_server(), false).await;
+    tester
+        .handle
+        .info()
+        .roles
+        .add_identity(&ObjectId::WellKnownRole_SecurityAdmin.into(), user_rule())
+        .unwrap();
+
+    // Role management requires an encrypted channel.
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_AddRole.into(),
+            input_arguments: Some(vec!["MyRole".into(), "".into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadSecurityModeInsufficient);
+
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_AddRole.into(),
+            input_arguments: Some(vec!["MyRole".into(), "".into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    let Some(Variant::NodeId(role_id)) = r.output_arguments.as_ref().and_then(|o| o.first()) else {
+        panic!("Expected node ID output, got {:?}", r.output_arguments);
+    };
+    let role_id = (**role_id).clone();
+    // New roles are created in the server namespace.
+    let server_ns = tester
+        .handle
+        .get_namespace_index(tester.handle.info().application_uri.as_ref())
+        .unwrap();
+    assert_eq!(role_id.namespace, server_ns);
+    let role = tester.handle.info().roles.get(&role_id).unwrap();
+    assert_eq!(role.browse_name().name.as_ref(), "MyRole");
+    assert!(!role.is_well_known());
+
+    let r = session
+        .read(
+            &[ReadValueId {
+                node_id: role_id.clone(),
+                attribute_id: AttributeId::BrowseName as u32,
+                ..Default::default()
+            }],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::QualifiedName(name)) = &r[0].value else {
+        panic!("Expected browse name, got {:?}", r[0]);
+    };
+    assert_eq!(name.name.as_ref(), "MyRole");
+
+    // Adding the same role twice fails.
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_AddRole.into(),
+            input_arguments: Some(vec!["MyRole".into(), "".into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadBrowseNameDuplicated);
+
+    // Find the AddIdentity method of the new role by browsing.
+    let add_identity = session
+        .browse(
+            &[BrowseDescription {
+                node_id: role_id.clone(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::HasComponent.into(),
+                include_subtypes: true,
+                node_class_mask: 0,
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            100,
+            None,
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .references
+        .unwrap_or_default()
+        .into_iter()
+        .find(|r| r.browse_name.name.as_ref() == "AddIdentity")
+        .unwrap()
+        .node_id
+        .node_id;
+
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: role_id.clone(),
+            method_id: add_identity,
+            input_arguments: Some(vec![ExtensionObject::from_message(user_rule()).into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert_eq!(
+        tester
+            .handle
+            .info()
+            .roles
+            .get(&role_id)
+            .unwrap()
+            .identities(),
+        &[user_rule()]
+    );
+
+    // Well-known roles cannot be removed.
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_RemoveRole.into(),
+            input_arguments: Some(vec![NodeId::from(ObjectId::WellKnownRole_Observer).into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadRequestNotAllowed);
+
+    let r = session
+        .call_one(CallMethodRequest {
+            object_id: ObjectId::Server_ServerCapabilities_RoleSet.into(),
+            method_id: MethodId::Server_ServerCapabilities_RoleSet_RemoveRole.into(),
+            input_arguments: Some(vec![role_id.clone().into()]),
+        })
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert!(tester.handle.info().roles.get(&role_id).is_none());
+
+    let r = session
+        .read(
+            &[ReadValueId {
+                node_id: role_id,
+                attribute_id: AttributeId::BrowseName as u32,
+                ..Default::default()
+            }],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].status, Some(StatusCode::BadNodeIdUnknown));
+}
+
+fn permission(role: ObjectId, permissions: PermissionType) -> RolePermissionType {
+    RolePermissionType {
+        role_id: role.into(),
+        permissions,
+    }
+}
+
+fn read_value_id(node_id: &NodeId, attribute_id: AttributeId) -> ReadValueId {
+    ReadValueId {
+        node_id: node_id.clone(),
+        attribute_id: attribute_id as u32,
+        ..Default::default()
+    }
+}
+
+fn add_node_item(parent: &NodeId) -> AddNodesItem {
+    AddNodesItem {
+        parent_node_id: parent.clone().into(),
+        reference_type_id: ReferenceTypeId::HasComponent.into(),
+        requested_new_node_id: ExpandedNodeId::null(),
+        browse_name: "NewNode".into(),
+        node_class: NodeClass::Object,
+        node_attributes: AddNodeAttributes::Object(ObjectAttributes {
+            specified_attributes: 1 << 6,
+            display_name: "NewNode".into(),
+            ..Default::default()
+        })
+        .as_extension_object(),
+        type_definition: ExpandedNodeId::new(ObjectTypeId::BaseObjectType),
+    }
+}
+
+fn event_item(node_id: &NodeId) -> MonitoredItemCreateRequest {
+    MonitoredItemCreateRequest {
+        item_to_monitor: read_value_id(node_id, AttributeId::EventNotifier),
+        monitoring_mode: MonitoringMode::Reporting,
+        requested_parameters: MonitoringParameters {
+            filter: ExtensionObject::from_message(EventFilter {
+                select_clauses: Some(vec![SimpleAttributeOperand::new_value(
+                    ObjectTypeId::BaseEventType,
+                    "EventId",
+                )]),
+                where_clause: ContentFilter { elements: None },
+            }),
+            queue_size: 10,
+            ..Default::default()
+        },
+    }
+}
+
+#[tokio::test]
+async fn role_permissions() {
+    let (mut tester, nm, anon) = setup().await;
+    tester
+        .handle
+        .info()
+        .roles
+        .add_identity(&ObjectId::WellKnownRole_Operator.into(), user_rule())
+        .unwrap();
+
+    // Anonymous users may only browse, operators may do anything.
+    let obj_id = nm.inner().next_node_id();
+    let var_id = nm.inner().next_node_id();
+    let hidden_id = nm.inner().next_node_id();
+    let method_id = nm.inner().next_node_id();
+    let plain_id = nm.inner().next_node_id();
+    let permissions = vec![
+        permission(ObjectId::WellKnownRole_Anonymous, PermissionType::Browse),
+        permission(ObjectId::WellKnownRole_Operator, PermissionType::all()),
+    ];
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&obj_id, "Obj", "Obj")
+            .event_notifier(EventNotifier::SUBSCRIBE_TO_EVENTS)
+            .role_permissions(permissions.clone())
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&ObjectTypeId::BaseObjectType.into()),
+        Vec::new(),
+    );
+    for (id, name, permissions) in [
+        (&var_id, "Var", Some(permissions.clone())),
+        (
+            &hidden_id,
+            "Hidden",
+            Some(vec![permission(
+                ObjectId::WellKnownRole_Operator,
+                PermissionType::all(),
+            )]),
+        ),
+        (&plain_id, "Plain", None),
+    ] {
+        let mut builder = VariableBuilder::new(id, name, name)
+            .data_type(DataTypeId::Int32)
+            .value(1)
+            .access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+            .user_access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE);
+        if let Some(permissions) = permissions {
+            builder = builder.role_permissions(permissions);
+        }
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            builder.build().into(),
+            &obj_id,
+            &ReferenceTypeId::HasComponent.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+    }
+    {
+        let mut sp = nm.address_space().write();
+        MethodBuilder::new(&method_id, "Method", "Method")
+            .executable(true)
+            .user_executable(true)
+            .component_of(obj_id.clone())
+            .role_permissions(permissions)
+            .insert(&mut *sp);
+    }
+    nm.inner().add_method_cb(method_id.clone(), |_| Ok(vec![]));
+
+    let user = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+
+    // Read
+    let r = anon
+        .read(
+            &[
+                read_value_id(&var_id, AttributeId::Value),
+                read_value_id(&var_id, AttributeId::DisplayName),
+                read_value_id(&var_id, AttributeId::RolePermissions),
+                read_value_id(&var_id, AttributeId::UserRolePermissions),
+                read_value_id(&var_id, AttributeId::UserAccessLevel),
+            ],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].status, Some(StatusCode::BadUserAccessDenied));
+    assert_eq!(r[1].status.unwrap_or_default(), StatusCode::Good);
+    assert_eq!(r[2].status, Some(StatusCode::BadUserAccessDenied));
+    let Some(Variant::Array(arr)) = &r[3].value else {
+        panic!("Expected array, got {:?}", r[3].value);
+    };
+    assert_eq!(arr.values.len(), 1);
+    assert_eq!(r[4].value, Some(Variant::Byte(0)));
+
+    let r = user
+        .read(
+            &[
+                read_value_id(&var_id, AttributeId::Value),
+                read_value_id(&var_id, AttributeId::RolePermissions),
+            ],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+    let Some(Variant::Array(arr)) = &r[1].value else {
+        panic!("Expected array, got {:?}", r[1].value);
+    };
+    assert_eq!(arr.values.len(), 2);
+
+    // Write
+    let write = [WriteValue {
+        node_id: var_id.clone(),
+        attribute_id: AttributeId::Value as u32,
+        index_range: Default::default(),
+        value: DataValue::new_now(2),
+    }];
+    let r = anon.write(&write).await.unwrap();
+    assert_eq!(r[0], StatusCode::BadUserAccessDenied);
+    let r = user.write(&write).await.unwrap();
+    assert_eq!(r[0], StatusCode::Good);
+
+    // Browse
+    let browse = |session: Arc<Session>| {
+        let obj_id = obj_id.clone();
+        async move {
+            let mut refs: Vec<_> = session
+                .browse(
+                    &[BrowseDescription {
+                        node_id: obj_id,
+                        browse_direction: BrowseDirection::Forward,
+                        reference_type_id: ReferenceTypeId::HasComponent.into(),
+                        include_subtypes: true,
+                        node_class_mask: 0,
+                        result_mask: BrowseResultMask::All as u32,
+                    }],
+                    100,
+                    None,
+                )
+                .await
+                .unwrap()
+                .remove(0)
+                .references
+                .unwrap_or_default()
+                .into_iter()
+                .map(|r| r.browse_name.name.to_string())
+                .collect();
+            refs.sort();
+            refs
+        }
+    };
+    assert_eq!(browse(anon.clone()).await, vec!["Method", "Plain", "Var"]);
+    assert_eq!(
+        browse(user.clone()).await,
+        vec!["Hidden", "Method", "Plain", "Var"]
+    );
+
+    // Call
+    let call = CallMethodRequest {
+        object_id: obj_id.clone(),
+        method_id: method_id.clone(),
+        input_arguments: None,
+    };
+    let r = anon.call_one(call.clone()).await.unwrap();
+    assert_eq!(r.status_code, StatusCode::BadUserAccessDenied);
+    let r = user.call_one(call).await.unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+
+    // AddNodes
+    let r = anon.add_nodes(&[add_node_item(&obj_id)]).await.unwrap();
+    assert_eq!(r[0].status_code, StatusCode::BadUserAccessDenied);
+    let r = user.add_nodes(&[add_node_item(&obj_id)]).await.unwrap();
+    assert_eq!(r[0].status_code, StatusCode::Good);
+
+    // DeleteNodes
+    let delete = [DeleteNodesItem {
+        node_id: var_id.clone(),
+        delete_target_references: true,
+    }];
+    let r = anon.delete_nodes(&delete).await.unwrap();
+    assert_eq!(r[0], StatusCode::BadUserAccessDenied);
+
+    // Events
+    let server_id: NodeId = ObjectId::Server.into();
+    let mut receivers = Vec::new();
+    for (session, status) in [
+        (&anon, StatusCode::BadUserAccessDenied),
+        (&user, StatusCode::Good),
+    ] {
+        let (notifs, _, events) = ChannelNotifications::new();
+        let sub_id = session
+            .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+            .await
+            .unwrap();
+        let r = session
+            .create_monitored_items(
+                sub_id,
+                TimestampsToReturn::Both,
+                vec![event_item(&obj_id), event_item(&server_id)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(r[0].result.status_code, status);
+        assert_eq!(r[1].result.status_code, StatusCode::Good);
+        receivers.push(events);
+    }
+
+    // Events are only sent to sessions allowed to receive events from their source node.
+    let event = |id: u8, source: &NodeId| {
+        BaseEventType::new_now(ObjectTypeId::BaseEventType, vec![id].into(), "Event")
+            .set_source_node(source.clone())
+    };
+    let hidden_evt = event(1, &obj_id);
+    let plain_evt = event(2, &plain_id);
+    tester.handle.subscriptions().notify_events(
+        [
+            (&hidden_evt as &dyn Event, &server_id),
+            (&plain_evt, &server_id),
+        ]
+        .into_iter(),
+    );
+    let mut received = Vec::new();
+    for events in &mut receivers {
+        let (_, v) = tokio::time::timeout(Duration::from_millis(500), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(v.unwrap().remove(0));
+    }
+    assert_eq!(
+        received,
+        vec![
+            Variant::from(plain_evt.event_id.clone()),
+            Variant::from(hidden_evt.event_id.clone())
+        ]
+    );
+
+    // Nodes without role permissions use the defaults of their namespace.
+    let plain = [read_value_id(&plain_id, AttributeId::Value)];
+    let r = anon
+        .read(&plain, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+
+    let roles = &tester.handle.info().roles;
+    roles.set_default_role_permissions(
+        plain_id.namespace,
+        Some(vec![permission(
+            ObjectId::WellKnownRole_Operator,
+            PermissionType::all(),
+        )]),
+    );
+    let r = anon
+        .read(&plain, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].status, Some(StatusCode::BadUserAccessDenied));
+    let r = user
+        .read(&plain, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+
+    roles.set_default_role_permissions(plain_id.namespace, None);
+    let r = anon
+        .read(&plain, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+}
+
+/// Start a server with a simple node manager for the namespace given by `metadata`,
+/// and a readable variable in it.
+async fn namespace_metadata_setup(metadata: NamespaceMetadata) -> (Tester, NodeId) {
+    let uri = metadata.namespace_uri.clone();
+    let server = test_server().with_node_manager(InMemoryNodeManagerBuilder::new(
+        SimpleNodeManagerBuilder::new(metadata, "defaults"),
+    ));
+    let tester = Tester::new(server, false).await;
+    tester
+        .handle
+        .info()
+        .roles
+        .add_identity(&ObjectId::WellKnownRole_Operator.into(), user_rule())
+        .unwrap();
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let ns = tester.handle.get_namespace_index(&uri).unwrap();
+    let id = NodeId::new(ns, "var");
+    nm.address_space().write().add_variables(
+        vec![VariableBuilder::new(&id, "Var", "Var")
+            .data_type(DataTypeId::Int32)
+            .value(1)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()],
+        &ObjectId::ObjectsFolder.into(),
+    );
+    (tester, id)
+}
+
+#[tokio::test]
+async fn namespace_metadata_role_permissions() {
+    let (mut tester, id) = namespace_metadata_setup(NamespaceMetadata {
+        namespace_uri: "urn:RoleDefaultsTest".to_owned(),
+        default_role_permissions: Some(vec![permission(
+            ObjectId::WellKnownRole_Operator,
+            PermissionType::all(),
+        )]),
+        ..Default::default()
+    })
+    .await;
+
+    // The default role permissions of the namespace metadata are enforced.
+    let read = [read_value_id(&id, AttributeId::Value)];
+    let anon = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let r = anon
+        .read(&read, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].status, Some(StatusCode::BadUserAccessDenied));
+
+    let user = tester
+        .connect_and_wait(
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let r = user
+        .read(&read, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+}
+
+#[tokio::test]
+async fn namespace_metadata_access_restrictions() {
+    let (mut tester, id) = namespace_metadata_setup(NamespaceMetadata {
+        namespace_uri: "urn:AccessDefaultsTest".to_owned(),
+        default_access_restrictions: AccessRestrictionType::EncryptionRequired,
+        ..Default::default()
+    })
+    .await;
+
+    // The default access restrictions of the namespace metadata are enforced.
+    let read = [read_value_id(&id, AttributeId::Value)];
+    for (policy, mode, status) in [
+        (
+            SecurityPolicy::None,
+            MessageSecurityMode::None,
+            StatusCode::BadSecurityModeInsufficient,
+        ),
+        (
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            StatusCode::Good,
+        ),
+    ] {
+        let session = tester
+            .connect_and_wait(policy, mode, IdentityToken::Anonymous)
+            .await
+            .unwrap();
+        let r = session
+            .read(&read, TimestampsToReturn::Both, 0.0)
+            .await
+            .unwrap();
+        assert_eq!(r[0].status.unwrap_or_default(), status);
+    }
+}
+
+#[tokio::test]
+async fn access_restrictions() {
+    let (mut tester, nm, anon) = setup().await;
+
+    let obj_id = nm.inner().next_node_id();
+    let encrypted_id = nm.inner().next_node_id();
+    let signed_id = nm.inner().next_node_id();
+    let plain_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectBuilder::new(&obj_id, "Obj", "Obj").build().into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&ObjectTypeId::BaseObjectType.into()),
+        Vec::new(),
+    );
+    for (id, name, restrictions) in [
+        (
+            &encrypted_id,
+            "Encrypted",
+            Some(AccessRestrictionType::EncryptionRequired),
+        ),
+        (
+            &signed_id,
+            "Signed",
+            Some(
+                AccessRestrictionType::SigningRequired
+                    | AccessRestrictionType::ApplyRestrictionsToBrowse,
+            ),
+        ),
+        (&plain_id, "Plain", None),
+    ] {
+        let mut builder = VariableBuilder::new(id, name, name)
+            .data_type(DataTypeId::Int32)
+            .value(1)
+            .access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+            .user_access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE);
+        if let Some(restrictions) = restrictions {
+            builder = builder.access_restrictions(restrictions);
+        }
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            builder.build().into(),
+            &obj_id,
+            &ReferenceTypeId::HasComponent.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+    }
+
+    let signed = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::Sign,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let encrypted = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+
+    let read = |session: Arc<Session>, attribute_id: AttributeId| {
+        let ids = [encrypted_id.clone(), signed_id.clone(), plain_id.clone()];
+        async move {
+            session
+                .read(
+                    &ids.map(|id| read_value_id(&id, attribute_id)),
+                    TimestampsToReturn::Both,
+                    0.0,
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|v| v.status.unwrap_or_default())
+                .collect::<Vec<_>>()
+        }
+    };
+    let denied = StatusCode::BadSecurityModeInsufficient;
+    let good = StatusCode::Good;
+
+    // Read
+    assert_eq!(
+        read(anon.clone(), AttributeId::Value).await,
+        vec![denied, denied, good]
+    );
+    assert_eq!(
+        read(signed.clone(), AttributeId::Value).await,
+        vec![denied, good, good]
+    );
+    assert_eq!(
+        read(encrypted.clone(), AttributeId::Value).await,
+        vec![good, good, good]
+    );
+    // Other attributes are only restricted if the restrictions apply to browse.
+    assert_eq!(
+        read(anon.clone(), AttributeId::DisplayName).await,
+        vec![good, denied, good]
+    );
+
+    // Write
+    let write = [WriteValue {
+        node_id: encrypted_id.clone(),
+        attribute_id: AttributeId::Value as u32,
+        index_range: Default::default(),
+        value: DataValue::new_now(2),
+    }];
+    assert_eq!(signed.write(&write).await.unwrap()[0], denied);
+    assert_eq!(encrypted.write(&write).await.unwrap()[0], good);
+
+    // Browse
+    let browse = |session: Arc<Session>| {
+        let obj_id = obj_id.clone();
+        async move {
+            let mut refs: Vec<_> = session
+                .browse(
+                    &[BrowseDescription {
+                        node_id: obj_id,
+                        browse_direction: BrowseDirection::Forward,
+                        reference_type_id: ReferenceTypeId::HasComponent.into(),
+                        include_subtypes: true,
+                        node_class_mask: 0,
+                        result_mask: BrowseResultMask::All as u32,
+                    }],
+                    100,
+                    None,
+                )
+                .await
+                .unwrap()
+                .remove(0)
+                .references
+                .unwrap_or_default()
+                .into_iter()
+                .map(|r| r.browse_name.name.to_string())
+                .collect();
+            refs.sort();
+            refs
+        }
+    };
+    assert_eq!(browse(anon.clone()).await, vec!["Encrypted", "Plain"]);
+    assert_eq!(
+        browse(signed.clone()).await,
+        vec!["Encrypted", "Plain", "Signed"]
+    );
+
+    // Nodes without access restrictions use the defaults of their namespace.
+    let roles = &tester.handle.info().roles;
+    roles.set_default_access_restrictions(
+        plain_id.namespace,
+        AccessRestrictionType::EncryptionRequired,
+    );
+    assert_eq!(
+        read(signed.clone(), AttributeId::Value).await,
+        vec![denied, good, denied]
+    );
+    assert_eq!(
+        read(encrypted.clone(), AttributeId::Value).await,
+        vec![good, good, good]
+    );
+
+    roles.set_default_access_restrictions(plain_id.namespace, AccessRestrictionType::empty());
+    assert_eq!(
+        read(anon.clone(), AttributeId::Value).await,
+        vec![denied, denied, good]
+    );
+}
@@ -1,32 +1,47 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use crate::utils::{test_server, ChannelNotifications, TestNodeManager, Tester};
+use crate::utils::{default_client, test_server, ChannelNotifications, TestNodeManager, Tester};
 
 use super::utils::setup;
 use chrono::DateTime;
+use futures::StreamExt;
 use opcua::{
-    server::address_space::{AccessLevel, VariableBuilder},
+    server::{
+        address_space::{AccessLevel, VariableBuilder},
+        alarms::{AlarmLimit, LimitAlarm, LimitAlarmKind},
+        diagnostics::NamespaceMetadata,
+        node_manager::memory::{simple_node_manager, SimpleNodeManager},
+    },
     types::{
         AttributeId, DataTypeId, DataValue, MonitoredItemCreateRequest, MonitoredItemModifyRequest,
         MonitoringMode, MonitoringParameters, NodeId, ObjectId, ReadValueId, ReferenceTypeId,
         StatusCode, TimestampsToReturn, VariableTypeId, Variant,
     },
+    EventField,
 };
 use opcua_client::{
     services::{
-        CreateMonitoredItems, CreateSubscription, Publish, Republish, TransferSubscriptions,
+        CreateMonitoredItems, CreateSubscription, DeleteMonitoredItems, DeleteSubscriptions,
+        Publish, Republish, TransferSubscriptions,
     },
-    IdentityToken, Subscription, UARequest,
+    ConnectionEvent, DataChangeCallback, HealthStatus, IdentityToken, MonitoredItemChange,
+    MonitoredItemHandle, NotificationStream, OnSubscriptionNotification,
+    OnSubscriptionNotificationCore, PersistedMonitoredItem, PersistedSubscription,
+    PersistedSubscriptions, PublishOptions, SampledFallbackOptions, SequenceGap,
+    SequenceGapRecovery, Session, SessionEventLoop, Subscription, SubscriptionChange,
+    SubscriptionNotification, SubscriptionStore, SubscriptionTransferPolicy, TagBinding, UARequest,
 };
 use opcua_core_namespace::events::{
-    AuditHistoryBulkInsertEventType, AuditSecurityEventType, ProgressEventType,
+    AlarmConditionType, AuditHistoryBulkInsertEventType, AuditSecurityEventType, ProgressEventType,
+    RefreshEndEventType, RefreshStartEventType,
 };
 use opcua_crypto::{random, SecurityPolicy};
-use opcua_nodes::Event;
+use opcua_nodes::{BaseEventType, Event};
 use opcua_types::{
-    ContentFilterBuilder, DataChangeFilter, DataChangeTrigger, DeadbandType, EventFilter,
-    ExtensionObject, LiteralOperand, MessageSecurityMode, ObjectTypeId, Operand, Range,
-    SimpleAttributeOperand,
+    BrowseDirection, ByteString, ContentFilter, ContentFilterBuilder, DataChangeFilter,
+    DataChangeTrigger, DeadbandType, EventFilter, ExtensionObject, LiteralOperand,
+    MessageSecurityMode, NamespaceMap, NumericRange, ObjectTypeId, Operand, QualifiedName, Range,
+    SimpleAttributeOperand, StatusChangeNotification, WriteValue,
 };
 use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
 
@@ -369,6 +384,308 @@ async fn modify_subscription() {
     session.delete_subscription(sub_id).await.unwrap();
 }
 
+#[tokio::test]
+async fn tune_subscriptions_and_items() {
+    let (tester, nm, session) = setup().await;
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&id, format!("TestVar{i}"), format!("TestVar{i}"))
+                .value(i)
+                .data_type(DataTypeId::Int32)
+                .access_level(AccessLevel::CURRENT_READ)
+                .user_access_level(AccessLevel::CURRENT_READ)
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+        ids.push(id);
+    }
+    let item = |id: &NodeId| {
+        MonitoredItemCreateRequest::new(
+            ReadValueId::new_value(id.clone()),
+            MonitoringMode::Reporting,
+            MonitoringParameters {
+                sampling_interval: 0.0,
+                queue_size: 10,
+                discard_oldest: true,
+                ..Default::default()
+            },
+        )
+    };
+
+    // Items spread over two subscriptions.
+    let mut sub_ids = Vec::new();
+    let mut handles = Vec::new();
+    for items in [&ids[..2], &ids[2..]] {
+        let (notifs, _data, _) = ChannelNotifications::new();
+        let sub_id = session
+            .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+            .await
+            .unwrap();
+        let res = session
+            .create_monitored_items(
+                sub_id,
+                TimestampsToReturn::Both,
+                items.iter().map(item).collect(),
+            )
+            .await
+            .unwrap();
+        handles.extend(
+            res.iter()
+                .map(|r| MonitoredItemHandle::new(r.requested_parameters.client_handle)),
+        );
+        sub_ids.push(sub_id);
+    }
+
+    let filter = ExtensionObject::from_message(DataChangeFilter {
+        trigger: DataChangeTrigger::StatusValue,
+        deadband_type: DeadbandType::Absolute as u32,
+        deadband_value: 2.0,
+    });
+    let mut to_modify = handles.clone();
+    to_modify.push(MonitoredItemHandle::new(u32::MAX));
+    let res = session
+        .modify_all_monitored_items(
+            TimestampsToReturn::Both,
+            &to_modify,
+            MonitoredItemChange::new()
+                .sampling_interval(200.0)
+                .queue_size(5)
+                .filter(filter.clone()),
+        )
+        .await;
+    assert_eq!(res.len(), 4);
+    assert_eq!(
+        res[3].as_ref().unwrap_err(),
+        &StatusCode::BadMonitoredItemIdInvalid
+    );
+    for (r, handle) in res.iter().zip(&handles) {
+        let item = r.as_ref().unwrap();
+        assert_eq!(item.handle(), *handle);
+        assert_eq!(item.sampling_interval(), 200.0);
+        assert_eq!(item.queue_size(), 5);
+        assert!(item.discard_oldest());
+        assert_eq!(item.filter(), &filter);
+    }
+
+    // Only change the queue size of one item, the rest is kept.
+    let res = session
+        .modify_monitored_items_by_handle(
+            TimestampsToReturn::Both,
+            &[(handles[2], MonitoredItemChange::new().queue_size(3))],
+        )
+        .await;
+    let item = res[0].as_ref().unwrap();
+    assert_eq!(item.sampling_interval(), 200.0);
+    assert_eq!(item.queue_size(), 3);
+    assert_eq!(item.filter(), &filter);
+
+    let session_id = session.server_session_id();
+    let opcua::types::Identifier::Numeric(session_id_num) = &session_id.identifier else {
+        panic!("Expected numeric session ID");
+    };
+    let sess_subs = tester
+        .handle
+        .subscriptions()
+        .get_session_subscriptions(*session_id_num)
+        .unwrap();
+    {
+        let lck = sess_subs.lock();
+        let sub = lck.get(sub_ids[1]).unwrap();
+        let (_, client_item) = session.find_monitored_item(handles[2]).unwrap();
+        let item = sub.get(&client_item.id()).unwrap();
+        assert_eq!(200.0, item.sampling_interval());
+        assert_eq!(3, item.queue_size());
+    }
+
+    let mut to_tune = sub_ids.clone();
+    to_tune.push(u32::MAX);
+    let res = session
+        .tune_subscriptions(
+            &to_tune,
+            &SubscriptionChange::new()
+                .publishing_interval(Duration::from_millis(250))
+                .max_keep_alive_count(50)
+                .priority(2),
+        )
+        .await;
+    assert_eq!(
+        res,
+        vec![
+            StatusCode::Good,
+            StatusCode::Good,
+            StatusCode::BadSubscriptionIdInvalid
+        ]
+    );
+
+    {
+        let lck = sess_subs.lock();
+        let state = session.subscription_state().lock();
+        for sub_id in &sub_ids {
+            let server_sub = lck.get(*sub_id).unwrap();
+            assert_eq!(server_sub.publishing_interval(), Duration::from_millis(250));
+            assert_eq!(server_sub.priority(), 2);
+            assert_eq!(server_sub.max_notifications_per_publish(), 1000);
+
+            let sub = state.get(*sub_id).unwrap();
+            assert_eq!(sub.publishing_interval(), Duration::from_millis(250));
+            assert_eq!(sub.priority(), 2);
+            assert_eq!(sub.max_keep_alive_count(), 50);
+            // The lifetime count is raised to three times the keep-alive count.
+            assert_eq!(sub.lifetime_count(), 150);
+            assert_eq!(sub.max_notifications_per_publish(), 1000);
+        }
+    }
+
+    // Delete the first subscription behind the back of the client. Its items fail, the
+    // items of the other subscription are still modified.
+    DeleteSubscriptions::new(&session)
+        .subscription_ids(vec![sub_ids[0]])
+        .send(session.channel())
+        .await
+        .unwrap();
+    let res = session
+        .modify_all_monitored_items(
+            TimestampsToReturn::Both,
+            &handles,
+            MonitoredItemChange::new().queue_size(4),
+        )
+        .await;
+    assert_eq!(
+        res[0].as_ref().unwrap_err(),
+        &StatusCode::BadSubscriptionIdInvalid
+    );
+    assert_eq!(
+        res[1].as_ref().unwrap_err(),
+        &StatusCode::BadSubscriptionIdInvalid
+    );
+    for r in &res[2..] {
+        assert_eq!(r.as_ref().unwrap().queue_size(), 4);
+    }
+}
+
+#[tokio::test]
+async fn resynchronize_monitored_items() {
+    let (tester, nm, session) = setup().await;
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&id, format!("TestVar{i}"), format!("TestVar{i}"))
+                .value(i)
+                .data_type(DataTypeId::Int32)
+                .access_level(AccessLevel::CURRENT_READ)
+                .user_access_level(AccessLevel::CURRENT_READ)
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+        ids.push(id);
+    }
+    let item = |id: &NodeId| {
+        MonitoredItemCreateRequest::new(
+            ReadValueId::new_value(id.clone()),
+            MonitoringMode::Reporting,
+            MonitoringParameters {
+                sampling_interval: 0.0,
+                queue_size: 10,
+                discard_oldest: true,
+                ..Default::default()
+            },
+        )
+    };
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            ids[..2].iter().map(item).collect(),
+        )
+        .await
+        .unwrap();
+    let handles = res
+        .iter()
+        .map(|r| MonitoredItemHandle::new(r.requested_parameters.client_handle))
+        .collect::<Vec<_>>();
+    recv_n(&mut data, 2).await;
+
+    let report = session.resynchronize_monitored_items(sub_id).await.unwrap();
+    assert!(report.was_in_sync());
+
+    // Delete an item and create another one behind the back of the client.
+    DeleteMonitoredItems::new(sub_id, &session)
+        .item(res[0].result.monitored_item_id)
+        .send(session.channel())
+        .await
+        .unwrap();
+    let orphan_id = CreateMonitoredItems::new(sub_id, &session)
+        .item(item(&ids[2]))
+        .timestamps_to_return(TimestampsToReturn::Both)
+        .send(session.channel())
+        .await
+        .unwrap()
+        .results[0]
+        .result
+        .monitored_item_id;
+
+    let report = session.resynchronize_monitored_items(sub_id).await.unwrap();
+    assert_eq!(report.subscription_id, sub_id);
+    assert_eq!(report.orphaned, vec![orphan_id]);
+    assert_eq!(report.recreated, vec![handles[0]]);
+    assert!(report.failed.is_empty());
+
+    // The server and the client agree again.
+    let (server_ids, server_handles) = session.call_get_monitored_items(sub_id).await.unwrap();
+    assert_eq!(server_ids.len(), 2);
+    for (id, handle) in server_ids.into_iter().zip(server_handles) {
+        let (item_sub_id, client_item) = session
+            .find_monitored_item(MonitoredItemHandle::new(handle))
+            .unwrap();
+        assert_eq!(item_sub_id, sub_id);
+        assert_eq!(client_item.id(), id);
+    }
+    {
+        let state = session.subscription_state().lock();
+        assert_eq!(state.get(sub_id).unwrap().monitored_items().len(), 2);
+    }
+
+    // The recreated item reports values again.
+    timeout(Duration::from_millis(500), async {
+        loop {
+            let (r, _) = data.recv().await.unwrap();
+            if r.node_id == ids[0] {
+                break;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let res = session.resynchronize_subscriptions().await;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].0, sub_id);
+    assert!(res[0].1.as_ref().unwrap().was_in_sync());
+}
+
 #[tokio::test]
 async fn subscription_limits() {
     let (tester, _nm, session) = setup().await;
@@ -612,34 +929,46 @@ async fn transfer_subscriptions() {
     assert_eq!(-1, val);
 }
 
-#[tokio::test]
-async fn test_data_change_filters() {
-    let (tester, nm, session) = setup().await;
+struct StatusChanges(tokio::sync::mpsc::UnboundedSender<StatusCode>);
+
+impl OnSubscriptionNotification for StatusChanges {
+    fn on_subscription_status_change(&mut self, notification: StatusChangeNotification) {
+        let _ = self.0.send(notification.status);
+    }
+}
+
+/// Create a subscription on one session, then disconnect and start a new session
+/// with the given policy, which finds the subscription in its state when it connects.
+/// Returns the new session, the ID of the original subscription, and the node it monitors.
+async fn reconnect_with_subscription(
+    tester: &mut Tester,
+    nm: &TestNodeManager,
+    policy: SubscriptionTransferPolicy,
+    subscription_id_offset: u32,
+    callback: Box<dyn OnSubscriptionNotificationCore>,
+    item_callback: Option<ChannelNotifications>,
+) -> (Arc<Session>, u32, NodeId) {
+    // Need to use an encrypted connection, or transfer won't work.
+    let (session, lp) = tester
+        .connect(
+            SecurityPolicy::Aes256Sha256RsaPss,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
 
     let id = nm.inner().next_node_id();
-    // Add a pair of nodes
     nm.inner().add_node(
         nm.address_space(),
         tester.handle.type_tree(),
         VariableBuilder::new(&id, "TestVar1", "TestVar1")
-            .value(0.0f64)
-            .data_type(DataTypeId::Double)
-            .access_level(AccessLevel::CURRENT_READ)
-            .user_access_level(AccessLevel::CURRENT_READ)
-            .build()
-            .into(),
-        &ObjectId::ObjectsFolder.into(),
-        &ReferenceTypeId::Organizes.into(),
-        Some(&VariableTypeId::BaseDataVariableType.into()),
-        Vec::new(),
-    );
-    let id2 = nm.inner().next_node_id();
-    nm.inner().add_node(
-        nm.address_space(),
-        tester.handle.type_tree(),
-        VariableBuilder::new(&id2, "TestVar2", "TestVar2")
-            .value(6.0f64)
-            .data_type(DataTypeId::Double)
+            .value(-1)
+            .data_type(DataTypeId::Int32)
             .access_level(AccessLevel::CURRENT_READ)
             .user_access_level(AccessLevel::CURRENT_READ)
             .build()
@@ -649,163 +978,2136 @@ async fn test_data_change_filters() {
         Some(&VariableTypeId::BaseDataVariableType.into()),
         Vec::new(),
     );
-    let prop_id = nm.inner().next_node_id();
-    // And an EURange property
-    nm.inner().add_node(
-        nm.address_space(),
-        tester.handle.type_tree(),
-        VariableBuilder::new(&prop_id, "EURange", "EURange")
-            .value(Range {
-                low: 5.0,
-                high: 15.0,
-            })
-            .data_type(DataTypeId::Range)
-            .access_level(AccessLevel::CURRENT_READ)
-            .user_access_level(AccessLevel::CURRENT_READ)
-            .build()
-            .into(),
-        &id2,
-        &ReferenceTypeId::HasProperty.into(),
-        Some(&VariableTypeId::PropertyType.into()),
-        Vec::new(),
-    );
-
-    let (notifs, mut data, _) = ChannelNotifications::new();
 
-    // Create a subscription
+    let (notifs, _data, _) = ChannelNotifications::new();
     let sub_id = session
         .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
         .await
         .unwrap();
-
-    let res = session
+    session
         .create_monitored_items(
             sub_id,
             TimestampsToReturn::Both,
-            vec![
-                MonitoredItemCreateRequest {
-                    item_to_monitor: ReadValueId {
-                        node_id: id.clone(),
-                        attribute_id: AttributeId::Value as u32,
-                        ..Default::default()
-                    },
-                    monitoring_mode: opcua::types::MonitoringMode::Reporting,
-                    requested_parameters: MonitoringParameters {
-                        sampling_interval: 0.0,
-                        queue_size: 10,
-                        discard_oldest: true,
-                        filter: ExtensionObject::from_message(DataChangeFilter {
-                            trigger: DataChangeTrigger::StatusValue,
-                            deadband_type: DeadbandType::Absolute as u32,
-                            deadband_value: 2.0,
-                        }),
-                        ..Default::default()
-                    },
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId {
+                    node_id: id.clone(),
+                    attribute_id: AttributeId::Value as u32,
+                    ..Default::default()
                 },
-                MonitoredItemCreateRequest {
-                    item_to_monitor: ReadValueId {
-                        node_id: id2.clone(),
-                        attribute_id: AttributeId::Value as u32,
-                        ..Default::default()
-                    },
-                    monitoring_mode: opcua::types::MonitoringMode::Reporting,
-                    requested_parameters: MonitoringParameters {
-                        sampling_interval: 0.0,
-                        queue_size: 10,
-                        discard_oldest: true,
-                        filter: ExtensionObject::from_message(DataChangeFilter {
-                            trigger: DataChangeTrigger::StatusValue,
-                            deadband_type: DeadbandType::Percent as u32,
-                            // 20% change is equal to a change of 2, since the range is from 5 to 15
-                            deadband_value: 20.0,
-                        }),
-                        ..Default::default()
-                    },
+                monitoring_mode: MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    sampling_interval: 0.0,
+                    queue_size: 10,
+                    discard_oldest: true,
+                    ..Default::default()
                 },
-            ],
+            }],
         )
         .await
         .unwrap();
-    assert_eq!(res.len(), 2);
+    let old_item = {
+        let state = session.subscription_state().lock();
+        state
+            .get(sub_id)
+            .unwrap()
+            .monitored_items()
+            .values()
+            .next()
+            .unwrap()
+            .clone()
+    };
+    let old_session_id = session.server_session_id();
+    session
+        .disconnect_without_delete_subscriptions()
+        .await
+        .unwrap();
+
+    // The old session is closed, so reactivating it fails and a new session is created.
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let (session, lp) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::Aes256Sha256RsaPss.to_str(),
+            MessageSecurityMode::SignAndEncrypt,
+        ))
+        .unwrap()
+        .session_id(old_session_id)
+        .subscription_transfer_policy(policy)
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+
+    let mut sub = Subscription::new(
+        sub_id + subscription_id_offset,
+        Duration::from_millis(100),
+        100,
+        20,
+        1000,
+        0,
+        true,
+        callback,
+    );
+    let handle = old_item.handle();
+    sub.insert_existing_monitored_item(old_item);
+    session.subscription_state().lock().add_subscription(sub);
+    if let Some(item_callback) = item_callback {
+        session
+            .set_monitored_item_callback(&[handle], item_callback)
+            .unwrap();
+    }
+
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    (session, sub_id, id)
+}
+
+#[tokio::test]
+async fn transfer_subscriptions_on_reconnect() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let (session, sub_id, id) = reconnect_with_subscription(
+        &mut tester,
+        &nm,
+        SubscriptionTransferPolicy::Transfer,
+        0,
+        Box::new(notifs),
+        None,
+    )
+    .await;
+
+    // The subscription keeps its ID, and the initial value is sent again.
+    assert_eq!(
+        session.subscription_state().lock().subscription_ids(),
+        Some(vec![sub_id])
+    );
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(-1)));
+}
+
+#[tokio::test]
+async fn recreate_subscriptions_on_reconnect() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let (session, sub_id, id) = reconnect_with_subscription(
+        &mut tester,
+        &nm,
+        SubscriptionTransferPolicy::Recreate,
+        0,
+        Box::new(notifs),
+        None,
+    )
+    .await;
+
+    // The subscription is created again with a new ID.
+    let ids = session
+        .subscription_state()
+        .lock()
+        .subscription_ids()
+        .unwrap();
+    assert_eq!(ids.len(), 1);
+    assert_ne!(ids[0], sub_id);
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(-1)));
+}
+
+#[tokio::test]
+async fn fail_subscriptions_on_reconnect() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+
+    // Use a subscription ID that doesn't exist on the server, so the transfer fails.
+    let (send, mut recv) = tokio::sync::mpsc::unbounded_channel();
+    let (session, _, _) = reconnect_with_subscription(
+        &mut tester,
+        &nm,
+        SubscriptionTransferPolicy::Fail,
+        1000,
+        Box::new(StatusChanges(send)),
+        None,
+    )
+    .await;
+
+    assert_eq!(session.subscription_state().lock().subscription_ids(), None);
+    let status = timeout(Duration::from_millis(500), recv.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status, StatusCode::BadSubscriptionIdInvalid);
+}
+
+#[tokio::test]
+async fn connection_events_on_recreated_session() {
+    let tester = Tester::new(test_server(), false).await;
+
+    // Reactivating a session that does not exist fails, so a new session is created,
+    // and the subscription in the session state is recreated on it.
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let (session, lp) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::None.to_str(),
+            MessageSecurityMode::None,
+        ))
+        .unwrap()
+        .session_id(NodeId::new(1, "missing-session"))
+        .subscription_transfer_policy(SubscriptionTransferPolicy::Recreate)
+        .build(tester.client.certificate_store().clone())
+        .unwrap();
+    let (notifs, _data, _) = ChannelNotifications::new();
+    session
+        .subscription_state()
+        .lock()
+        .add_subscription(Subscription::new(
+            1,
+            Duration::from_millis(100),
+            100,
+            20,
+            1000,
+            0,
+            true,
+            Box::new(notifs),
+        ));
+
+    let mut events = session.connection_events();
+    lp.spawn();
+    let mut received = Vec::new();
+    while received.last() != Some(&ConnectionEvent::Connected) {
+        let event = timeout(Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(event);
+    }
+    assert_eq!(
+        received,
+        vec![
+            ConnectionEvent::Connecting,
+            ConnectionEvent::SessionRecreated,
+            ConnectionEvent::SubscriptionsRestored,
+            ConnectionEvent::Connected,
+        ]
+    );
+
+    session.disconnect().await.unwrap();
+    let event = timeout(Duration::from_secs(5), events.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(event, ConnectionEvent::Disconnected { .. }));
+}
+
+#[derive(Default)]
+struct MemorySubscriptionStore(std::sync::Mutex<Option<PersistedSubscriptions>>);
+
+impl MemorySubscriptionStore {
+    fn get(&self) -> PersistedSubscriptions {
+        self.0.lock().unwrap().clone().unwrap()
+    }
+}
+
+impl SubscriptionStore for MemorySubscriptionStore {
+    fn save(&self, subscriptions: &PersistedSubscriptions) -> Result<(), opcua_types::Error> {
+        *self.0.lock().unwrap() = Some(subscriptions.clone());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<PersistedSubscriptions>, opcua_types::Error> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+}
+
+async fn session_with_store(
+    tester: &Tester,
+    store: Arc<MemorySubscriptionStore>,
+) -> (Arc<Session>, SessionEventLoop) {
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::Aes256Sha256RsaPss.to_str(),
+            MessageSecurityMode::SignAndEncrypt,
+        ))
+        .unwrap()
+        .subscription_store(store)
+        .build(tester.client.certificate_store().clone())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn restore_subscriptions_from_store() {
+    let tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    let store = Arc::new(MemorySubscriptionStore::default());
+
+    let (session, lp) = session_with_store(&tester, store.clone()).await;
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(-1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    session
+        .create_monitored_items(sub_id, TimestampsToReturn::Both, vec![id.clone().into()])
+        .await
+        .unwrap();
+    timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Changes are saved in the background, and when the session disconnects.
+    let old_session_id = session.server_session_id();
+    session
+        .disconnect_without_delete_subscriptions()
+        .await
+        .unwrap();
+
+    // The store has the subscription, its monitored item, and the last sequence number.
+    let stored = store.get();
+    assert_eq!(stored.session_id, old_session_id);
+    assert_eq!(stored.subscriptions.len(), 1);
+    let stored_sub = &stored.subscriptions[0];
+    assert_eq!(stored_sub.subscription_id, sub_id);
+    assert!(stored_sub.last_sequence_number.is_some());
+    assert_eq!(stored_sub.monitored_items.len(), 1);
+    assert_eq!(
+        stored_sub.monitored_items[0]
+            .request
+            .item_to_monitor
+            .node_id,
+        id
+    );
+
+    // A new session, for example after a restart, picks up the subscription from the store.
+    let (session, lp) = session_with_store(&tester, store.clone()).await;
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let mut notifs = Some(notifs);
+    let restored = session
+        .restore_subscriptions(|_| Box::new(notifs.take().unwrap()))
+        .unwrap();
+    assert_eq!(restored, 1);
+    assert_eq!(session.server_session_id(), old_session_id);
+
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        session.subscription_state().lock().subscription_ids(),
+        Some(vec![sub_id])
+    );
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(-1)));
+
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(1),
+    )
+    .unwrap();
+    let (_, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(v.value, Some(Variant::Int32(1)));
+
+    // The store now refers to the new session.
+    let new_session_id = session.server_session_id();
+    session
+        .disconnect_without_delete_subscriptions()
+        .await
+        .unwrap();
+    let stored = store.get();
+    assert_eq!(stored.session_id, new_session_id);
+    assert_ne!(stored.session_id, old_session_id);
+}
+
+#[tokio::test]
+async fn remap_namespaces_on_reconnect() {
+    let (tester, nm, session) = setup().await;
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(3)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    // The namespace array was read on connect.
+    let uri = "urn:rustopcuatestserver";
+    assert_eq!(session.namespace_uri(id.namespace).as_deref(), Some(uri));
+    let resolved = session
+        .resolve(uri, &id.identifier.to_string())
+        .await
+        .unwrap();
+    assert_eq!(resolved, id);
+    assert_eq!(
+        session.resolve("urn:missing", "i=1").await,
+        Err(StatusCode::BadNoMatch)
+    );
+    session.disconnect().await.unwrap();
+
+    // A subscription stored while the namespace of the server was at index 1.
+    let stale_id = NodeId {
+        namespace: 1,
+        identifier: id.identifier.clone(),
+    };
+    let store = Arc::new(MemorySubscriptionStore(std::sync::Mutex::new(Some(
+        PersistedSubscriptions {
+            session_id: NodeId::new(1, "missing-session"),
+            subscriptions: vec![PersistedSubscription {
+                subscription_id: 1,
+                publishing_interval: Duration::from_millis(100),
+                lifetime_count: 100,
+                max_keep_alive_count: 20,
+                max_notifications_per_publish: 1000,
+                priority: 0,
+                publishing_enabled: true,
+                last_sequence_number: None,
+                monitored_items: vec![PersistedMonitoredItem {
+                    id: 1,
+                    request: MonitoredItemCreateRequest {
+                        item_to_monitor: ReadValueId::new(stale_id, AttributeId::Value),
+                        monitoring_mode: MonitoringMode::Reporting,
+                        requested_parameters: MonitoringParameters {
+                            client_handle: 1,
+                            sampling_interval: 0.0,
+                            queue_size: 10,
+                            discard_oldest: true,
+                            ..Default::default()
+                        },
+                    },
+                    triggered_items: Vec::new(),
+                }],
+            }],
+        },
+    ))));
+    let (session, lp) = session_with_store(&tester, store.clone()).await;
+    session.set_namespaces(
+        NamespaceMap::new_from_variant_array(&["http://opcfoundation.org/UA/".into(), uri.into()])
+            .unwrap(),
+    );
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let mut notifs = Some(notifs);
+    session
+        .restore_subscriptions(|_| Box::new(notifs.take().unwrap()))
+        .unwrap();
+
+    let mut events = session.connection_events();
+    lp.spawn();
+    let mut received = Vec::new();
+    while received.last() != Some(&ConnectionEvent::Connected) {
+        let event = timeout(Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        received.push(event);
+    }
+    assert!(received.contains(&ConnectionEvent::NamespacesChanged {
+        remapped: HashMap::from([(1, id.namespace)]),
+    }));
+
+    // The monitored item was recreated with the new namespace index.
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(3)));
+    session
+        .disconnect_without_delete_subscriptions()
+        .await
+        .unwrap();
+    let stored = store.get();
+    assert_eq!(
+        stored.subscriptions[0].monitored_items[0]
+            .request
+            .item_to_monitor
+            .node_id,
+        id
+    );
+}
+
+#[tokio::test]
+async fn test_data_change_filters() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    // Add a pair of nodes
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(0.0f64)
+            .data_type(DataTypeId::Double)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    let id2 = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id2, "TestVar2", "TestVar2")
+            .value(6.0f64)
+            .data_type(DataTypeId::Double)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    let prop_id = nm.inner().next_node_id();
+    // And an EURange property
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&prop_id, "EURange", "EURange")
+            .value(Range {
+                low: 5.0,
+                high: 15.0,
+            })
+            .data_type(DataTypeId::Range)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &id2,
+        &ReferenceTypeId::HasProperty.into(),
+        Some(&VariableTypeId::PropertyType.into()),
+        Vec::new(),
+    );
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+
+    // Create a subscription
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![
+                MonitoredItemCreateRequest {
+                    item_to_monitor: ReadValueId {
+                        node_id: id.clone(),
+                        attribute_id: AttributeId::Value as u32,
+                        ..Default::default()
+                    },
+                    monitoring_mode: opcua::types::MonitoringMode::Reporting,
+                    requested_parameters: MonitoringParameters {
+                        sampling_interval: 0.0,
+                        queue_size: 10,
+                        discard_oldest: true,
+                        filter: ExtensionObject::from_message(DataChangeFilter {
+                            trigger: DataChangeTrigger::StatusValue,
+                            deadband_type: DeadbandType::Absolute as u32,
+                            deadband_value: 2.0,
+                        }),
+                        ..Default::default()
+                    },
+                },
+                MonitoredItemCreateRequest {
+                    item_to_monitor: ReadValueId {
+                        node_id: id2.clone(),
+                        attribute_id: AttributeId::Value as u32,
+                        ..Default::default()
+                    },
+                    monitoring_mode: opcua::types::MonitoringMode::Reporting,
+                    requested_parameters: MonitoringParameters {
+                        sampling_interval: 0.0,
+                        queue_size: 10,
+                        discard_oldest: true,
+                        filter: ExtensionObject::from_message(DataChangeFilter {
+                            trigger: DataChangeTrigger::StatusValue,
+                            deadband_type: DeadbandType::Percent as u32,
+                            // 20% change is equal to a change of 2, since the range is from 5 to 15
+                            deadband_value: 20.0,
+                        }),
+                        ..Default::default()
+                    },
+                },
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 2);
     let it = &res[0];
     assert_eq!(it.result.status_code, StatusCode::Good);
     let it = &res[1];
     assert_eq!(it.result.status_code, StatusCode::Good);
 
-    // We should quickly get two data values, this is due to the initial queued publish request.
-    let (r1, v1) = timeout(Duration::from_millis(500), data.recv())
+    // We should quickly get two data values, this is due to the initial queued publish request.
+    let (r1, v1) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let (r2, v2) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut by_id = HashMap::new();
+    by_id.insert(r1.node_id, v1);
+    by_id.insert(r2.node_id, v2);
+
+    assert_eq!(
+        &Variant::Double(0.0),
+        by_id.get(&id).as_ref().unwrap().value.as_ref().unwrap()
+    );
+    assert_eq!(
+        &Variant::Double(6.0),
+        by_id.get(&id2).as_ref().unwrap().value.as_ref().unwrap()
+    );
+
+    // Update the first node with a change that won't trigger the filter.
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(1.0),
+    )
+    .unwrap();
+
+    // Update it again, this time with something that would trigger a notification.
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(3.0),
+    )
+    .unwrap();
+
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value.unwrap(), Variant::Double(3.0));
+
+    // Do the same with the second node.
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id2,
+        None,
+        DataValue::new_now(7.0),
+    )
+    .unwrap();
+
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id2,
+        None,
+        DataValue::new_now(9.0),
+    )
+    .unwrap();
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id2);
+    assert_eq!(v.value.unwrap(), Variant::Double(9.0));
+}
+
+#[tokio::test]
+async fn percent_deadband_falls_back_to_absolute() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(6.0f64)
+            .data_type(DataTypeId::Double)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    // The server only looks for the EURange through HasProperty, so referencing it with
+    // HasComponent makes the server reject percent deadband, while the client still finds it.
+    let prop_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&prop_id, "EURange", "EURange")
+            .value(Range {
+                low: 5.0,
+                high: 15.0,
+            })
+            .data_type(DataTypeId::Range)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &id,
+        &ReferenceTypeId::HasComponent.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let range = session.read_eu_range(&id).await.unwrap();
+    assert_eq!(range.low, 5.0);
+    assert_eq!(range.high, 15.0);
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    let item = MonitoredItemCreateRequest {
+        item_to_monitor: ReadValueId::new(id.clone(), AttributeId::Value),
+        monitoring_mode: MonitoringMode::Reporting,
+        requested_parameters: MonitoringParameters {
+            sampling_interval: 0.0,
+            queue_size: 10,
+            discard_oldest: true,
+            // 20% of the range from 5 to 15 is an absolute deadband of 2
+            filter: ExtensionObject::from_message(DataChangeFilter::percent_deadband(
+                DataChangeTrigger::StatusValue,
+                20.0,
+            )),
+            ..Default::default()
+        },
+    };
+
+    let res = session
+        .create_monitored_items(sub_id, TimestampsToReturn::Both, vec![item.clone()])
+        .await
+        .unwrap();
+    assert_eq!(
+        res[0].result.status_code,
+        StatusCode::BadDeadbandFilterInvalid
+    );
+
+    let res = session
+        .create_monitored_items_with_deadband_fallback(sub_id, TimestampsToReturn::Both, vec![item])
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].result.status_code, StatusCode::Good);
+    let filter = res[0]
+        .requested_parameters
+        .filter
+        .inner_as::<DataChangeFilter>()
+        .unwrap();
+    assert_eq!(filter.deadband_type, DeadbandType::Absolute as u32);
+    assert_eq!(filter.deadband_value, 2.0);
+
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value.unwrap(), Variant::Double(6.0));
+
+    // A change within the deadband is not reported, one outside it is.
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(7.0),
+    )
+    .unwrap();
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(9.0),
+    )
+    .unwrap();
+    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value.unwrap(), Variant::Double(9.0));
+}
+
+#[tokio::test]
+async fn test_manual_republish() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(-1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    // Create a subscription
+    let res = CreateSubscription::new(&session)
+        .publishing_interval(Duration::from_millis(100))
+        .max_lifetime_count(100)
+        .max_keep_alive_count(20)
+        .max_notifications_per_publish(1000)
+        .priority(0)
+        .publishing_enabled(true)
+        .send(session.channel())
+        .await
+        .unwrap();
+    let sub_id = res.subscription_id;
+
+    let res = CreateMonitoredItems::new(sub_id, &session)
+        .item(MonitoredItemCreateRequest {
+            item_to_monitor: ReadValueId {
+                node_id: id.clone(),
+                attribute_id: AttributeId::Value as u32,
+                ..Default::default()
+            },
+            monitoring_mode: opcua::types::MonitoringMode::Reporting,
+            requested_parameters: MonitoringParameters {
+                sampling_interval: 0.0,
+                queue_size: 10,
+                discard_oldest: true,
+                ..Default::default()
+            },
+        })
+        .timestamps_to_return(TimestampsToReturn::Both)
+        .send(session.channel())
+        .await
+        .unwrap();
+
+    assert_eq!(res.results.len(), 1);
+    let it = &res.results[0];
+    assert_eq!(it.result.status_code, StatusCode::Good);
+
+    // Send a publish request, this should return a notification.
+    let pubres = Publish::new(&session)
+        .timeout(Duration::from_millis(500))
+        .send(session.channel())
+        .await
+        .unwrap();
+
+    assert_eq!(pubres.subscription_id, sub_id);
+    let sequence_number = pubres.notification_message.sequence_number;
+    let notifs = pubres.notification_message.into_notifications().unwrap().0;
+    assert_eq!(notifs.len(), 1);
+    let notif = &notifs[0];
+    let items = notif.monitored_items.as_ref().unwrap();
+    assert_eq!(items.len(), 1);
+    let value = items[0].value.value.as_ref().unwrap();
+    assert_eq!(value, &Variant::Int32(-1));
+
+    // Then, request a re-publish of the same notification.
+    let res = Republish::new(sub_id, sequence_number, &session)
+        .timeout(Duration::from_millis(500))
+        .send(session.channel())
+        .await
+        .unwrap();
+    let notifs = res.notification_message.into_notifications().unwrap().0;
+    assert_eq!(notifs.len(), 1);
+    let notif = &notifs[0];
+    let items = notif.monitored_items.as_ref().unwrap();
+    assert_eq!(items.len(), 1);
+    let value = items[0].value.value.as_ref().unwrap();
+    assert_eq!(value, &Variant::Int32(-1));
+}
+
+#[tokio::test]
+async fn test_event_subscriptions() {
+    let (tester, _nm, session) = setup().await;
+
+    // Create a subscription
+    let (notifs, _, mut events) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    // Create a monitored item for audit events with OfType filter and a simple equality filter on Status.
+    session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId {
+                    node_id: ObjectId::Server.into(),
+                    attribute_id: AttributeId::EventNotifier as u32,
+                    ..Default::default()
+                },
+                monitoring_mode: opcua::types::MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    sampling_interval: 0.0,
+                    queue_size: 10,
+                    discard_oldest: true,
+                    filter: ExtensionObject::new(EventFilter {
+                        select_clauses: Some(vec![
+                            SimpleAttributeOperand::new_value(
+                                ObjectTypeId::BaseEventType,
+                                "EventId",
+                            ),
+                            SimpleAttributeOperand::new_value(
+                                ObjectTypeId::BaseEventType,
+                                "Message",
+                            ),
+                            SimpleAttributeOperand::new_value(
+                                ObjectTypeId::AuditEventType,
+                                "Status",
+                            ),
+                            SimpleAttributeOperand::new_value(
+                                ObjectTypeId::AuditHistoryBulkInsertEventType,
+                                "StartTime",
+                            ),
+                        ]),
+                        where_clause: ContentFilterBuilder::new()
+                            .and(Operand::element(1), Operand::element(2))
+                            .of_type(LiteralOperand::from(ObjectTypeId::AuditEventType))
+                            .eq(
+                                LiteralOperand::from(true),
+                                SimpleAttributeOperand::new_value(
+                                    ObjectTypeId::AuditEventType,
+                                    "Status",
+                                ),
+                            )
+                            .build(),
+                    }),
+                    ..Default::default()
+                },
+            }],
+        )
+        .await
+        .unwrap();
+
+    // Publish a few events.
+    let miss_evt_1 = ProgressEventType::new_event_now(
+        ProgressEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello",
+        &tester.handle.type_tree().read().namespaces(),
+    );
+    let miss_evt_2 = AuditSecurityEventType::new_event_now(
+        AuditSecurityEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello 2",
+        &tester.handle.type_tree().read().namespaces(),
+    );
+    let mut hit_evt = AuditHistoryBulkInsertEventType::new_event_now(
+        AuditHistoryBulkInsertEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello 3",
+        tester.handle.type_tree().read().namespaces(),
+    );
+    hit_evt.base.status = true;
+    hit_evt.start_time = DateTime::from_timestamp(10_000, 0).unwrap().into();
+    hit_evt.base.base.severity = 50;
+
+    let server_id = ObjectId::Server.into();
+    tester.handle.subscriptions().notify_events(
+        [
+            (&miss_evt_1 as &dyn Event, &server_id),
+            (&miss_evt_2, &server_id),
+            (&hit_evt, &server_id),
+        ]
+        .into_iter(),
+    );
+
+    // Only the last event should be received, due to the filter.
+    let (r1, v1) = timeout(Duration::from_millis(500), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r1.node_id, server_id);
+    let evt = v1.unwrap();
+    assert_eq!(4, evt.len());
+    assert_eq!(Variant::from(hit_evt.base.base.event_id.clone()), evt[0]);
+    assert_eq!(Variant::from(hit_evt.base.base.message.clone()), evt[1]);
+    assert_eq!(Variant::from(hit_evt.base.status), evt[2]);
+    assert_eq!(
+        Variant::from(DateTime::from_timestamp(10_000, 0).unwrap()),
+        evt[3]
+    );
+}
+
+/// A condition event, with the `ConditionId` the generated event types do not include.
+struct TestCondition {
+    condition_id: NodeId,
+    event: AlarmConditionType,
+}
+
+impl TestCondition {
+    fn new(tester: &Tester, condition_id: &NodeId, retain: bool, acked: bool) -> Self {
+        let mut event = AlarmConditionType::new_event_now(
+            AlarmConditionType::event_type_id(),
+            random::byte_string(6),
+            "Alarm",
+            tester.handle.type_tree().read().namespaces(),
+        );
+        event.base.base.base.source_node = ObjectId::Server.into();
+        event.base.base.base.severity = 500;
+        event.base.base.retain = retain;
+        event.base.acked_state.id = acked;
+        event.active_state.id = true;
+        Self {
+            condition_id: condition_id.clone(),
+            event,
+        }
+    }
+
+    fn event_id(&self) -> &ByteString {
+        &self.event.base.base.base.event_id
+    }
+}
+
+impl opcua_nodes::EventField for TestCondition {
+    fn get_value(
+        &self,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        if browse_path.is_empty() && attribute_id == AttributeId::NodeId {
+            return self.condition_id.clone().into();
+        }
+        self.event.get_value(attribute_id, index_range, browse_path)
+    }
+}
+
+impl Event for TestCondition {
+    fn get_field(
+        &self,
+        type_definition_id: &NodeId,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        if browse_path.is_empty() && attribute_id == AttributeId::NodeId {
+            return self.condition_id.clone().into();
+        }
+        self.event
+            .get_field(type_definition_id, attribute_id, index_range, browse_path)
+    }
+
+    fn time(&self) -> &opcua::types::DateTime {
+        self.event.time()
+    }
+
+    fn event_type_id(&self) -> &NodeId {
+        self.event.event_type_id()
+    }
+}
+
+#[tokio::test]
+async fn subscribe_conditions() {
+    let (tester, nm, session) = setup().await;
+
+    let server_id: NodeId = ObjectId::Server.into();
+    let mut conditions = session
+        .subscribe_conditions(
+            &server_id,
+            Duration::from_millis(100),
+            ContentFilter::default(),
+        )
+        .await
+        .unwrap();
+    let alarm_1 = nm.inner().next_node_id();
+    let alarm_2 = nm.inner().next_node_id();
+
+    let evt_1 = TestCondition::new(&tester, &alarm_1, true, false);
+    let evt_2 = TestCondition::new(&tester, &alarm_2, true, false);
+    // Events that are not conditions are skipped.
+    let other = ProgressEventType::new_event_now(
+        ProgressEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello",
+        tester.handle.type_tree().read().namespaces(),
+    );
+    tester.handle.subscriptions().notify_events(
+        [
+            (&evt_1 as &dyn Event, &server_id),
+            (&other, &server_id),
+            (&evt_2, &server_id),
+        ]
+        .into_iter(),
+    );
+
+    for (evt, id) in [(&evt_1, &alarm_1), (&evt_2, &alarm_2)] {
+        let state = timeout(Duration::from_secs(2), conditions.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&state.condition_id, id);
+        assert_eq!(&state.event_id, evt.event_id());
+        assert_eq!(state.event_type, AlarmConditionType::event_type_id());
+        assert_eq!(state.source_node, server_id);
+        assert_eq!(state.severity, 500);
+        assert!(state.branch_id.is_null());
+        assert!(state.retain);
+        assert_eq!(state.acked, Some(false));
+        assert_eq!(state.active, Some(true));
+    }
+    assert_eq!(conditions.conditions().len(), 2);
+    assert_eq!(
+        &conditions.get(&alarm_1).unwrap().event_id,
+        evt_1.event_id()
+    );
+    assert_eq!(
+        conditions
+            .find_event(evt_2.event_id())
+            .unwrap()
+            .condition_id,
+        alarm_2
+    );
+
+    // A refresh only sends the first alarm, so the second is no longer retained.
+    let namespaces = tester.handle.type_tree().read().namespaces().clone();
+    let start = RefreshStartEventType::new_event_now(
+        RefreshStartEventType::event_type_id(),
+        random::byte_string(6),
+        "Refresh",
+        &namespaces,
+    );
+    let evt_1 = TestCondition::new(&tester, &alarm_1, true, true);
+    let end = RefreshEndEventType::new_event_now(
+        RefreshEndEventType::event_type_id(),
+        random::byte_string(6),
+        "Refresh",
+        &namespaces,
+    );
+    tester.handle.subscriptions().notify_events(
+        [
+            (&start as &dyn Event, &server_id),
+            (&evt_1, &server_id),
+            (&end, &server_id),
+        ]
+        .into_iter(),
+    );
+    let state = timeout(Duration::from_secs(2), conditions.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.acked, Some(true));
+    timeout(Duration::from_secs(2), async {
+        while conditions.conditions().len() != 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        &conditions.get(&alarm_1).unwrap().event_id,
+        evt_1.event_id()
+    );
+    assert!(conditions.get(&alarm_2).is_none());
+
+    // A condition that is no longer retained is removed.
+    let evt_1 = TestCondition::new(&tester, &alarm_1, false, true);
+    tester
+        .handle
+        .subscriptions()
+        .notify_events([(&evt_1 as &dyn Event, &server_id)].into_iter());
+    let state = timeout(Duration::from_secs(2), conditions.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.retain);
+    assert!(conditions.conditions().is_empty());
+
+    // Conditions in the retained conditions of the server are sent on refresh.
+    let retained = tester.handle.subscriptions().conditions();
+    retained.set(alarm_2.clone(), server_id.clone(), Arc::new(evt_2));
+    conditions.refresh(&session).await.unwrap();
+    let state = next_condition(&mut conditions).await;
+    assert_eq!(state.condition_id, alarm_2);
+    assert!(state.retain);
+    timeout(Duration::from_secs(2), async {
+        while conditions.conditions().len() != 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // ConditionRefresh2 refreshes a single monitored item, which must monitor events.
+    assert_eq!(
+        session
+            .condition_refresh2(conditions.subscription_id(), 12345)
+            .await
+            .unwrap_err(),
+        StatusCode::BadMonitoredItemIdInvalid
+    );
+    assert_eq!(
+        session.condition_refresh(12345).await.unwrap_err(),
+        StatusCode::BadSubscriptionIdInvalid
+    );
+    assert!(retained.remove(&alarm_2));
+    session
+        .condition_refresh2(conditions.subscription_id(), conditions.monitored_item_id())
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(2), async {
+        while !conditions.conditions().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    // The alarms are not nodes on the server.
+    assert_eq!(
+        session
+            .acknowledge_condition(&alarm_1, evt_1.event_id(), "Acknowledged")
+            .await
+            .unwrap_err(),
+        StatusCode::BadMethodInvalid
+    );
+}
+
+async fn next_condition<S: futures::Stream + Unpin>(conditions: &mut S) -> S::Item {
+    timeout(Duration::from_secs(2), conditions.next())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn limit_alarms() {
+    let server = test_server().with_node_manager(simple_node_manager(
+        NamespaceMetadata {
+            namespace_uri: "urn:LimitAlarmTest".to_owned(),
+            ..Default::default()
+        },
+        "alarms",
+    ));
+    let mut tester = Tester::new(server, false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let ns = tester
+        .handle
+        .get_namespace_index("urn:LimitAlarmTest")
+        .unwrap();
+
+    let input = NodeId::new(ns, "Temperature");
+    let exclusive = NodeId::new(ns, "TemperatureAlarm");
+    let non_exclusive = NodeId::new(ns, "TemperatureLevels");
+    let server_id: NodeId = ObjectId::Server.into();
+    {
+        let mut address_space = nm.address_space().write();
+        address_space.add_variables(
+            vec![VariableBuilder::new(&input, "Temperature", "Temperature")
+                .value(20.0)
+                .data_type(DataTypeId::Double)
+                .access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+                .user_access_level(AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE)
+                .build()],
+            &ObjectId::ObjectsFolder.into(),
+        );
+        let alarms = nm.inner().limit_alarms();
+        alarms
+            .add(
+                &mut address_space,
+                tester.handle.subscriptions(),
+                LimitAlarm::new(
+                    &exclusive,
+                    LimitAlarmKind::Exclusive,
+                    "TemperatureAlarm",
+                    &input,
+                    &server_id,
+                )
+                .limit(AlarmLimit::HighHigh, 100.0, 900)
+                .limit(AlarmLimit::High, 80.0, 600)
+                .deadband(AlarmLimit::High, 5.0),
+            )
+            .unwrap();
+        alarms
+            .add(
+                &mut address_space,
+                tester.handle.subscriptions(),
+                LimitAlarm::new(
+                    &non_exclusive,
+                    LimitAlarmKind::NonExclusive,
+                    "TemperatureLevels",
+                    &input,
+                    &server_id,
+                )
+                .limit(AlarmLimit::High, 80.0, 500)
+                .limit(AlarmLimit::HighHigh, 100.0, 800),
+            )
+            .unwrap();
+        assert_eq!(
+            alarms
+                .add(
+                    &mut address_space,
+                    tester.handle.subscriptions(),
+                    LimitAlarm::new(
+                        &exclusive,
+                        LimitAlarmKind::Exclusive,
+                        "Duplicate",
+                        &input,
+                        &server_id,
+                    ),
+                )
+                .unwrap_err(),
+            StatusCode::BadNodeIdExists
+        );
+    }
+
+    let mut conditions = session
+        .subscribe_conditions(
+            &server_id,
+            Duration::from_millis(100),
+            ContentFilter::default(),
+        )
+        .await
+        .unwrap();
+    let set = |value: f64| {
+        nm.set_value(
+            tester.handle.subscriptions(),
+            &input,
+            None,
+            DataValue::new_now(value),
+        )
+        .unwrap();
+    };
+    let exceeded = |id: &NodeId| nm.inner().limit_alarms().exceeded_limits(id).unwrap();
+
+    // Exceeding the high limit activates both alarms.
+    set(90.0);
+    let mut states = [
+        next_condition(&mut conditions).await,
+        next_condition(&mut conditions).await,
+    ];
+    states.sort_by_key(|s| s.severity);
+    assert_eq!(states[0].condition_id, non_exclusive);
+    assert_eq!(states[0].severity, 500);
+    assert_eq!(states[1].condition_id, exclusive);
+    assert_eq!(states[1].severity, 600);
+    assert_eq!(states[1].event_type, ObjectTypeId::ExclusiveLimitAlarmType);
+    assert_eq!(states[1].condition_name.as_ref(), "TemperatureAlarm");
+    assert_eq!(states[1].source_node, server_id);
+    for state in &states {
+        assert_eq!(state.active, Some(true));
+        assert!(state.retain);
+    }
+    assert_eq!(exceeded(&exclusive), vec![AlarmLimit::High]);
+
+    set(105.0);
+    let mut states = [
+        next_condition(&mut conditions).await,
+        next_condition(&mut conditions).await,
+    ];
+    states.sort_by_key(|s| s.severity);
+    assert_eq!(states[0].severity, 800);
+    assert_eq!(states[1].severity, 900);
+    assert_eq!(
+        exceeded(&non_exclusive),
+        vec![AlarmLimit::HighHigh, AlarmLimit::High]
+    );
+
+    // Without a deadband on the high high limit, both alarms leave it.
+    set(78.0);
+    let mut states = [
+        next_condition(&mut conditions).await,
+        next_condition(&mut conditions).await,
+    ];
+    states.sort_by_key(|s| s.condition_id == exclusive);
+    assert_eq!(exceeded(&exclusive), vec![AlarmLimit::High]);
+    assert_eq!(states[1].severity, 600);
+    assert_eq!(states[1].active, Some(true));
+    // The non exclusive alarm has no deadband on the high limit either.
+    // Returning to normal keeps the severity of the last limit.
+    assert_eq!(states[0].condition_id, non_exclusive);
+    assert_eq!(states[0].severity, 800);
+    assert_eq!(states[0].active, Some(false));
+    assert!(!states[0].retain);
+
+    // The exclusive alarm stays active until the value is below the high limit
+    // by more than the deadband.
+    set(76.0);
+    assert_eq!(exceeded(&exclusive), vec![AlarmLimit::High]);
+    assert!(exceeded(&non_exclusive).is_empty());
+
+    // Only the active alarm is retained, and sent again on refresh.
+    let retained = tester.handle.subscriptions().conditions();
+    assert!(retained.contains(&exclusive));
+    assert!(!retained.contains(&non_exclusive));
+    conditions.refresh(&session).await.unwrap();
+    let state = next_condition(&mut conditions).await;
+    assert_eq!(state.condition_id, exclusive);
+    assert_eq!(state.event_id, states[1].event_id);
+    assert!(timeout(Duration::from_millis(300), conditions.next())
+        .await
+        .is_err());
+
+    // Writing a limit property re-evaluates the alarm.
+    let high_limit = nm
+        .address_space()
+        .read()
+        .find_node_by_browse_name(
+            &exclusive,
+            Some((ReferenceTypeId::HasProperty, false)),
+            &*tester.handle.type_tree().read(),
+            BrowseDirection::Forward,
+            QualifiedName::new(0, "HighLimit"),
+        )
+        .unwrap()
+        .as_node()
+        .node_id()
+        .clone();
+    let res = session
+        .write(&[WriteValue {
+            node_id: high_limit,
+            attribute_id: AttributeId::Value as u32,
+            index_range: NumericRange::None,
+            value: DataValue::new_now(85.0),
+        }])
+        .await
+        .unwrap();
+    assert_eq!(res, vec![StatusCode::Good]);
+    let state = next_condition(&mut conditions).await;
+    assert_eq!(state.condition_id, exclusive);
+    assert_eq!(state.active, Some(false));
+    assert!(!state.retain);
+    assert_eq!(state.severity, 600);
+    assert!(exceeded(&exclusive).is_empty());
+    assert!(retained.is_empty());
+
+    // Values that are not numeric are ignored.
+    set(f64::NAN);
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &input,
+        None,
+        DataValue::new_now("hot"),
+    )
+    .unwrap();
+    assert!(exceeded(&exclusive).is_empty());
+    assert!(timeout(Duration::from_millis(300), conditions.next())
+        .await
+        .is_err());
+
+    // Removing an active alarm removes it from the retained conditions.
+    set(90.0);
+    next_condition(&mut conditions).await;
+    next_condition(&mut conditions).await;
+    assert!(retained.contains(&exclusive));
+    assert!(nm.inner().limit_alarms().remove(
+        &mut nm.address_space().write(),
+        tester.handle.subscriptions(),
+        &exclusive
+    ));
+    assert!(!retained.contains(&exclusive));
+    assert!(!nm.address_space().read().node_exists(&exclusive));
+    assert!(nm
+        .inner()
+        .limit_alarms()
+        .exceeded_limits(&exclusive)
+        .is_none());
+}
+
+#[derive(TagBinding, Default, Clone, Debug)]
+struct ServerTags {
+    #[opcua(node_id = "i=2267")]
+    service_level: u8,
+    #[opcua(node_id = "nsu=http://opcfoundation.org/UA/;i=2267")]
+    service_level_by_uri: u8,
+    #[opcua(ignore)]
+    _other: String,
+}
+
+#[tokio::test]
+async fn subscribe_tags() {
+    let (tester, _nm, session) = setup().await;
+
+    tester.handle.set_service_level(123);
+    let mut tags = session
+        .subscribe_tags::<ServerTags>(Duration::from_millis(100))
+        .await
+        .unwrap();
+
+    let values = timeout(Duration::from_secs(2), tags.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(values.service_level, 123);
+    assert_eq!(values.service_level_by_uri, 123);
+
+    tester.handle.set_service_level(200);
+    timeout(Duration::from_secs(2), async {
+        loop {
+            let values = tags.changed().await.unwrap();
+            if values.service_level == 200 && values.service_level_by_uri == 200 {
+                break;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(tags.current().service_level, 200);
+
+    session
+        .delete_subscription(tags.subscription_id())
+        .await
+        .unwrap();
+}
+
+#[derive(TagBinding, Default)]
+struct MissingTags {
+    #[opcua(node_id = "ns=99;s=missing")]
+    _missing: i32,
+}
+
+#[tokio::test]
+async fn subscribe_tags_invalid() {
+    let (_tester, _nm, session) = setup().await;
+
+    let err = session
+        .subscribe_tags::<MissingTags>(Duration::from_millis(100))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err, StatusCode::BadNodeIdUnknown);
+}
+
+#[derive(EventField, Default, Debug)]
+struct BulkInsertEvent {
+    base: BaseEventType,
+    status: bool,
+    start_time: opcua::types::DateTime,
+}
+
+#[tokio::test]
+async fn subscribe_events_typed() {
+    let (tester, _nm, session) = setup().await;
+
+    let server_id: NodeId = ObjectId::Server.into();
+    let mut events = session
+        .subscribe_events::<BulkInsertEvent>(
+            &server_id,
+            Duration::from_millis(100),
+            ContentFilterBuilder::new()
+                .of_type(Operand::literal(
+                    AuditHistoryBulkInsertEventType::event_type_id(),
+                ))
+                .build(),
+        )
+        .await
+        .unwrap();
+
+    let miss_evt = ProgressEventType::new_event_now(
+        ProgressEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello",
+        tester.handle.type_tree().read().namespaces(),
+    );
+    let mut hit_evt = AuditHistoryBulkInsertEventType::new_event_now(
+        AuditHistoryBulkInsertEventType::event_type_id(),
+        random::byte_string(6),
+        "Hello 2",
+        tester.handle.type_tree().read().namespaces(),
+    );
+    hit_evt.base.status = true;
+    hit_evt.start_time = DateTime::from_timestamp(10_000, 0).unwrap().into();
+    hit_evt.base.base.severity = 50;
+
+    tester.handle.subscriptions().notify_events(
+        [
+            (&miss_evt as &dyn Event, &server_id),
+            (&hit_evt, &server_id),
+        ]
+        .into_iter(),
+    );
+
+    let evt = timeout(Duration::from_millis(500), events.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(evt.base.event_id, hit_evt.base.base.event_id);
+    assert_eq!(
+        evt.base.event_type,
+        AuditHistoryBulkInsertEventType::event_type_id()
+    );
+    assert_eq!(evt.base.message, hit_evt.base.base.message);
+    assert_eq!(evt.base.severity, 50);
+    assert!(evt.status);
+    assert_eq!(
+        evt.start_time,
+        DateTime::from_timestamp(10_000, 0).unwrap().into()
+    );
+
+    session
+        .delete_subscription(events.subscription_id())
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_millis(500), events.next())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn subscription_stream() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(-1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let (sub_id, mut stream) = session
+        .create_subscription_stream(Duration::from_millis(100), 100, 20, 1000, 0, true, 10)
+        .await
+        .unwrap();
+    let item = MonitoredItemCreateRequest::new(
+        ReadValueId::new_value(id.clone()),
+        MonitoringMode::Reporting,
+        MonitoringParameters {
+            client_handle: 5,
+            sampling_interval: 0.0,
+            queue_size: 10,
+            discard_oldest: true,
+            ..Default::default()
+        },
+    );
+    session
+        .create_monitored_items(sub_id, TimestampsToReturn::Both, vec![item])
+        .await
+        .unwrap();
+
+    let next = timeout(Duration::from_millis(500), stream.next())
+        .await
+        .unwrap();
+    let Some(SubscriptionNotification::DataChange {
+        client_handle,
+        node_id,
+        value,
+    }) = next
+    else {
+        panic!("Expected data change, got {next:?}");
+    };
+    assert_eq!(client_handle, 5);
+    assert_eq!(node_id, id);
+    assert_eq!(value.value, Some(Variant::Int32(-1)));
+
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(1),
+    )
+    .unwrap();
+    let next = timeout(Duration::from_millis(500), stream.next())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next,
+        Some(SubscriptionNotification::DataChange { value, .. }) if value.value == Some(Variant::Int32(1))
+    ));
+
+    session.delete_subscription(sub_id).await.unwrap();
+    assert!(timeout(Duration::from_millis(500), stream.next())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn session_health() {
+    let (_tester, _nm, session) = setup().await;
+
+    let health = session.health();
+    assert_eq!(health.status, HealthStatus::Healthy);
+    assert!(health.is_alive());
+    assert!(health.connected);
+    assert!(health.last_keep_alive_age.unwrap() < Duration::from_secs(5));
+    assert!(health.subscriptions.is_empty());
+
+    let (notifs, _data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let health = session.health();
+    assert_eq!(health.status, HealthStatus::Healthy);
+    assert_eq!(health.subscriptions.len(), 1);
+    let sub = &health.subscriptions[0];
+    assert_eq!(sub.subscription_id, sub_id);
+    assert!(!sub.overdue);
+    assert_eq!(sub.late_publish_count, 0);
+
+    session.disconnect().await.unwrap();
+    let health = session.health();
+    assert_eq!(health.status, HealthStatus::Unhealthy);
+    assert!(!health.is_alive());
+    assert!(!health.connected);
+}
+
+fn add_int_variable(tester: &Tester, nm: &TestNodeManager, name: &str) -> NodeId {
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, name, name)
+            .value(-1)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+    id
+}
+
+async fn recv_int(data: &mut UnboundedReceiver<(ReadValueId, DataValue)>) -> i32 {
+    let (_, v) = timeout(Duration::from_secs(2), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    match v.value {
+        Some(Variant::Int32(v)) => v,
+        _ => panic!("Expected integer value"),
+    }
+}
+
+#[tokio::test]
+async fn sampled_fallback() {
+    let mut server = test_server();
+    server
+        .limits_mut()
+        .subscriptions
+        .max_subscriptions_per_session = 0;
+    let client = default_client(0, false).sampled_fallback_options(SampledFallbackOptions {
+        enabled: true,
+        ..Default::default()
+    });
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+    let id = add_int_variable(&tester, &nm, "TestVar1");
+
+    // The server rejects the subscription, so the client emulates it.
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    assert!(session
+        .subscription_state()
+        .lock()
+        .get(sub_id)
+        .unwrap()
+        .is_sampled());
+
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest::new(
+                ReadValueId::new_value(id.clone()),
+                MonitoringMode::Reporting,
+                MonitoringParameters::default(),
+            )],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res[0].result.status_code, StatusCode::Good);
+    assert_eq!(res[0].result.revised_sampling_interval, 100.0);
+    let item_id = res[0].result.monitored_item_id;
+
+    // The initial value, then only changes are delivered.
+    assert_eq!(recv_int(&mut data).await, -1);
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(1),
+    )
+    .unwrap();
+    assert_eq!(recv_int(&mut data).await, 1);
+    assert!(timeout(Duration::from_millis(300), data.recv())
+        .await
+        .is_err());
+
+    let res = session
+        .set_monitoring_mode(sub_id, MonitoringMode::Disabled, &[item_id, 1234])
+        .await
+        .unwrap();
+    assert_eq!(
+        res,
+        vec![StatusCode::Good, StatusCode::BadMonitoredItemIdInvalid]
+    );
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(2),
+    )
+    .unwrap();
+    assert!(timeout(Duration::from_millis(300), data.recv())
+        .await
+        .is_err());
+
+    assert_eq!(
+        session.delete_subscription(sub_id).await.unwrap(),
+        StatusCode::Good
+    );
+    assert_eq!(session.subscription_state().lock().len(), 0);
+}
+
+#[tokio::test]
+async fn fall_back_to_sampling() {
+    let (tester, nm, session) = setup().await;
+    let id = add_int_variable(&tester, &nm, "TestVar1");
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest::new(
+                ReadValueId::new_value(id.clone()),
+                MonitoringMode::Reporting,
+                MonitoringParameters::default(),
+            )],
+        )
+        .await
+        .unwrap();
+    assert_eq!(recv_int(&mut data).await, -1);
+
+    // The subscription is deleted on the server, and the current value is read again.
+    session.fall_back_to_sampling(sub_id).await.unwrap();
+    assert!(session
+        .subscription_state()
+        .lock()
+        .get(sub_id)
+        .unwrap()
+        .is_sampled());
+    let r = DeleteSubscriptions::new(&session)
+        .subscription(sub_id)
+        .send(session.channel())
+        .await
+        .unwrap();
+    assert_eq!(
+        r.results.unwrap(),
+        vec![StatusCode::BadSubscriptionIdInvalid]
+    );
+    assert_eq!(recv_int(&mut data).await, -1);
+
+    nm.set_value(
+        tester.handle.subscriptions(),
+        &id,
+        None,
+        DataValue::new_now(1),
+    )
+    .unwrap();
+    assert_eq!(recv_int(&mut data).await, 1);
+
+    session.delete_subscription(sub_id).await.unwrap();
+}
+
+// TODO: Add more detailed high level tests on subscriptions.
+
+#[tokio::test]
+async fn monitored_item_callbacks() {
+    let (tester, nm, session) = setup().await;
+
+    let mut ids = Vec::new();
+    for i in 0..2 {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&id, format!("TestVar{i}"), format!("TestVar{i}"))
+                .value(i)
+                .data_type(DataTypeId::Int32)
+                .access_level(AccessLevel::CURRENT_READ)
+                .user_access_level(AccessLevel::CURRENT_READ)
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+        ids.push(id);
+    }
+    let item = |id: &NodeId| {
+        MonitoredItemCreateRequest::new(
+            ReadValueId::new_value(id.clone()),
+            MonitoringMode::Reporting,
+            MonitoringParameters {
+                sampling_interval: 0.0,
+                queue_size: 10,
+                discard_oldest: true,
+                ..Default::default()
+            },
+        )
+    };
+
+    let (notifs, mut sub_data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    // One item with its own callback, and one without.
+    let (item_notifs, mut item_data, _) = ChannelNotifications::new();
+    let handle = session
+        .create_monitored_item_with_callback(
+            sub_id,
+            TimestampsToReturn::Both,
+            item(&ids[0]),
+            item_notifs,
+        )
+        .await
+        .unwrap();
+    session
+        .create_monitored_items(sub_id, TimestampsToReturn::Both, vec![item(&ids[1])])
+        .await
+        .unwrap();
+
+    let (r, v) = timeout(Duration::from_millis(500), item_data.recv())
         .await
         .unwrap()
         .unwrap();
-    let (r2, v2) = timeout(Duration::from_millis(500), data.recv())
+    assert_eq!(r.node_id, ids[0]);
+    assert_eq!(v.value, Some(Variant::Int32(0)));
+    let (r, v) = timeout(Duration::from_millis(500), sub_data.recv())
         .await
         .unwrap()
         .unwrap();
-    let mut by_id = HashMap::new();
-    by_id.insert(r1.node_id, v1);
-    by_id.insert(r2.node_id, v2);
-
-    assert_eq!(
-        &Variant::Double(0.0),
-        by_id.get(&id).as_ref().unwrap().value.as_ref().unwrap()
-    );
-    assert_eq!(
-        &Variant::Double(6.0),
-        by_id.get(&id2).as_ref().unwrap().value.as_ref().unwrap()
-    );
+    assert_eq!(r.node_id, ids[1]);
+    assert_eq!(v.value, Some(Variant::Int32(1)));
 
-    // Update the first node with a change that won't trigger the filter.
-    nm.set_value(
-        tester.handle.subscriptions(),
-        &id,
-        None,
-        DataValue::new_now(1.0),
-    )
-    .unwrap();
+    let (subscription_id, monitored_item) = session.find_monitored_item(handle).unwrap();
+    assert_eq!(subscription_id, sub_id);
+    assert_eq!(monitored_item.item_to_monitor().node_id, ids[0]);
 
-    // Update it again, this time with something that would trigger a notification.
+    // Without its callback, notifications for the item go to the subscription callback.
+    assert!(session.remove_monitored_item_callback(handle));
     nm.set_value(
         tester.handle.subscriptions(),
-        &id,
+        &ids[0],
         None,
-        DataValue::new_now(3.0),
+        DataValue::new_now(5),
     )
     .unwrap();
-
-    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+    let (r, v) = timeout(Duration::from_millis(500), sub_data.recv())
         .await
         .unwrap()
         .unwrap();
-    assert_eq!(r.node_id, id);
-    assert_eq!(v.value.unwrap(), Variant::Double(3.0));
+    assert_eq!(r.node_id, ids[0]);
+    assert_eq!(v.value, Some(Variant::Int32(5)));
+    assert!(item_data.try_recv().is_err());
 
-    // Do the same with the second node.
-    nm.set_value(
-        tester.handle.subscriptions(),
-        &id2,
-        None,
-        DataValue::new_now(7.0),
-    )
-    .unwrap();
+    assert_eq!(
+        session.set_monitored_item_callback(
+            &[MonitoredItemHandle::new(u32::MAX)],
+            DataChangeCallback::new(|_, _| {})
+        ),
+        Err(StatusCode::BadMonitoredItemIdInvalid)
+    );
+}
 
-    nm.set_value(
-        tester.handle.subscriptions(),
-        &id2,
-        None,
-        DataValue::new_now(9.0),
+#[tokio::test]
+async fn monitored_item_callbacks_on_reconnect() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+
+    let (notifs, mut sub_data, _) = ChannelNotifications::new();
+    let (item_notifs, mut item_data, _) = ChannelNotifications::new();
+    let (session, sub_id, id) = reconnect_with_subscription(
+        &mut tester,
+        &nm,
+        SubscriptionTransferPolicy::Recreate,
+        0,
+        Box::new(notifs),
+        Some(item_notifs),
     )
-    .unwrap();
-    let (r, v) = timeout(Duration::from_millis(500), data.recv())
+    .await;
+
+    // The recreated subscription has a new ID, but the item keeps its handle and callback.
+    let (r, v) = timeout(Duration::from_millis(500), item_data.recv())
         .await
         .unwrap()
         .unwrap();
-    assert_eq!(r.node_id, id2);
-    assert_eq!(v.value.unwrap(), Variant::Double(9.0));
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(-1)));
+    assert!(sub_data.try_recv().is_err());
+
+    let state = session.subscription_state().lock();
+    let new_id = state.subscription_ids().unwrap()[0];
+    assert_ne!(new_id, sub_id);
+    let handle = state
+        .get(new_id)
+        .unwrap()
+        .monitored_items()
+        .values()
+        .next()
+        .unwrap()
+        .handle();
+    drop(state);
+    assert_eq!(session.find_monitored_item(handle).unwrap().0, new_id);
 }
 
-#[tokio::test]
-async fn test_manual_republish() {
-    let (tester, nm, session) = setup().await;
+/// Create a subscription on a session that is then closed, receiving notifications 1 and 2
+/// without acknowledging them, and restore it on a new session as if only notification 1
+/// was received. When the subscription is transferred, the initial value in notification 3
+/// reveals that notification 2 is missing.
+async fn restore_with_sequence_gap(
+    tester: &Tester,
+    nm: &TestNodeManager,
+    recovery: SequenceGapRecovery,
+) -> (Arc<Session>, NotificationStream) {
+    let store = Arc::new(MemorySubscriptionStore::default());
+    let (session, lp) = session_with_store(tester, store.clone()).await;
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
 
     let id = nm.inner().next_node_id();
     nm.inner().add_node(
@@ -824,188 +3126,164 @@ async fn test_manual_republish() {
         Vec::new(),
     );
 
-    // Create a subscription
-    let res = CreateSubscription::new(&session)
+    // The session does not know about the subscription, so it sends no publish requests.
+    let sub_id = CreateSubscription::new(&session)
         .publishing_interval(Duration::from_millis(100))
         .max_lifetime_count(100)
         .max_keep_alive_count(20)
         .max_notifications_per_publish(1000)
-        .priority(0)
-        .publishing_enabled(true)
         .send(session.channel())
         .await
-        .unwrap();
-    let sub_id = res.subscription_id;
-
-    let res = CreateMonitoredItems::new(sub_id, &session)
-        .item(MonitoredItemCreateRequest {
-            item_to_monitor: ReadValueId {
-                node_id: id.clone(),
-                attribute_id: AttributeId::Value as u32,
-                ..Default::default()
-            },
-            monitoring_mode: opcua::types::MonitoringMode::Reporting,
-            requested_parameters: MonitoringParameters {
-                sampling_interval: 0.0,
-                queue_size: 10,
-                discard_oldest: true,
-                ..Default::default()
-            },
-        })
+        .unwrap()
+        .subscription_id;
+    let request = MonitoredItemCreateRequest::new(
+        ReadValueId::new_value(id.clone()),
+        MonitoringMode::Reporting,
+        MonitoringParameters {
+            client_handle: 1,
+            sampling_interval: 0.0,
+            queue_size: 10,
+            discard_oldest: true,
+            ..Default::default()
+        },
+    );
+    let item_id = CreateMonitoredItems::new(sub_id, &session)
+        .item(request.clone())
         .timestamps_to_return(TimestampsToReturn::Both)
         .send(session.channel())
         .await
+        .unwrap()
+        .results[0]
+        .result
+        .monitored_item_id;
+
+    for (value, sequence_number) in [(-1, 1), (1, 2)] {
+        nm.set_value(
+            tester.handle.subscriptions(),
+            &id,
+            None,
+            DataValue::new_now(value),
+        )
         .unwrap();
-
-    assert_eq!(res.results.len(), 1);
-    let it = &res.results[0];
-    assert_eq!(it.result.status_code, StatusCode::Good);
-
-    // Send a publish request, this should return a notification.
-    let pubres = Publish::new(&session)
-        .timeout(Duration::from_millis(500))
-        .send(session.channel())
+        let res = Publish::new(&session)
+            .timeout(Duration::from_millis(500))
+            .send(session.channel())
+            .await
+            .unwrap();
+        assert_eq!(res.notification_message.sequence_number, sequence_number);
+    }
+    let session_id = session.server_session_id();
+    session
+        .disconnect_without_delete_subscriptions()
         .await
         .unwrap();
 
-    assert_eq!(pubres.subscription_id, sub_id);
-    let sequence_number = pubres.notification_message.sequence_number;
-    let notifs = pubres.notification_message.into_notifications().unwrap().0;
-    assert_eq!(notifs.len(), 1);
-    let notif = &notifs[0];
-    let items = notif.monitored_items.as_ref().unwrap();
-    assert_eq!(items.len(), 1);
-    let value = items[0].value.value.as_ref().unwrap();
-    assert_eq!(value, &Variant::Int32(-1));
-
-    // Then, request a re-publish of the same notification.
-    let res = Republish::new(sub_id, sequence_number, &session)
-        .timeout(Duration::from_millis(500))
-        .send(session.channel())
+    let store = Arc::new(MemorySubscriptionStore(std::sync::Mutex::new(Some(
+        PersistedSubscriptions {
+            session_id,
+            subscriptions: vec![PersistedSubscription {
+                subscription_id: sub_id,
+                publishing_interval: Duration::from_millis(100),
+                lifetime_count: 100,
+                max_keep_alive_count: 20,
+                max_notifications_per_publish: 1000,
+                priority: 0,
+                publishing_enabled: true,
+                last_sequence_number: Some(1),
+                monitored_items: vec![PersistedMonitoredItem {
+                    id: item_id,
+                    request,
+                    triggered_items: Vec::new(),
+                }],
+            }],
+        },
+    ))));
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
         .await
         .unwrap();
-    let notifs = res.notification_message.into_notifications().unwrap().0;
-    assert_eq!(notifs.len(), 1);
-    let notif = &notifs[0];
-    let items = notif.monitored_items.as_ref().unwrap();
-    assert_eq!(items.len(), 1);
-    let value = items[0].value.value.as_ref().unwrap();
-    assert_eq!(value, &Variant::Int32(-1));
-}
-
-#[tokio::test]
-async fn test_event_subscriptions() {
-    let (tester, _nm, session) = setup().await;
-
-    // Create a subscription
-    let (notifs, _, mut events) = ChannelNotifications::new();
-    let sub_id = session
-        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
-        .await
+    let (session, lp) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::Aes256Sha256RsaPss.to_str(),
+            MessageSecurityMode::SignAndEncrypt,
+        ))
+        .unwrap()
+        .subscription_store(store)
+        .publish_options(PublishOptions {
+            republish_missed_notifications: false,
+            sequence_gap_recovery: recovery,
+            ..Default::default()
+        })
+        .build(tester.client.certificate_store().clone())
         .unwrap();
-
-    // Create a monitored item for audit events with OfType filter and a simple equality filter on Status.
+    let (sender, stream) = NotificationStream::channel(100);
+    let mut sender = Some(sender);
     session
-        .create_monitored_items(
-            sub_id,
-            TimestampsToReturn::Both,
-            vec![MonitoredItemCreateRequest {
-                item_to_monitor: ReadValueId {
-                    node_id: ObjectId::Server.into(),
-                    attribute_id: AttributeId::EventNotifier as u32,
-                    ..Default::default()
-                },
-                monitoring_mode: opcua::types::MonitoringMode::Reporting,
-                requested_parameters: MonitoringParameters {
-                    sampling_interval: 0.0,
-                    queue_size: 10,
-                    discard_oldest: true,
-                    filter: ExtensionObject::new(EventFilter {
-                        select_clauses: Some(vec![
-                            SimpleAttributeOperand::new_value(
-                                ObjectTypeId::BaseEventType,
-                                "EventId",
-                            ),
-                            SimpleAttributeOperand::new_value(
-                                ObjectTypeId::BaseEventType,
-                                "Message",
-                            ),
-                            SimpleAttributeOperand::new_value(
-                                ObjectTypeId::AuditEventType,
-                                "Status",
-                            ),
-                            SimpleAttributeOperand::new_value(
-                                ObjectTypeId::AuditHistoryBulkInsertEventType,
-                                "StartTime",
-                            ),
-                        ]),
-                        where_clause: ContentFilterBuilder::new()
-                            .and(Operand::element(1), Operand::element(2))
-                            .of_type(LiteralOperand::from(ObjectTypeId::AuditEventType))
-                            .eq(
-                                LiteralOperand::from(true),
-                                SimpleAttributeOperand::new_value(
-                                    ObjectTypeId::AuditEventType,
-                                    "Status",
-                                ),
-                            )
-                            .build(),
-                    }),
-                    ..Default::default()
-                },
-            }],
-        )
+        .restore_subscriptions(|_| Box::new(sender.take().unwrap()))
+        .unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
         .await
         .unwrap();
 
-    // Publish a few events.
-    let miss_evt_1 = ProgressEventType::new_event_now(
-        ProgressEventType::event_type_id(),
-        random::byte_string(6),
-        "Hello",
-        &tester.handle.type_tree().read().namespaces(),
-    );
-    let miss_evt_2 = AuditSecurityEventType::new_event_now(
-        AuditSecurityEventType::event_type_id(),
-        random::byte_string(6),
-        "Hello 2",
-        &tester.handle.type_tree().read().namespaces(),
-    );
-    let mut hit_evt = AuditHistoryBulkInsertEventType::new_event_now(
-        AuditHistoryBulkInsertEventType::event_type_id(),
-        random::byte_string(6),
-        "Hello 3",
-        tester.handle.type_tree().read().namespaces(),
-    );
-    hit_evt.base.status = true;
-    hit_evt.start_time = DateTime::from_timestamp(10_000, 0).unwrap().into();
-    hit_evt.base.base.severity = 50;
+    (session, stream)
+}
 
-    let server_id = ObjectId::Server.into();
-    tester.handle.subscriptions().notify_events(
-        [
-            (&miss_evt_1 as &dyn Event, &server_id),
-            (&miss_evt_2, &server_id),
-            (&hit_evt, &server_id),
-        ]
-        .into_iter(),
-    );
+/// Collect the values received until the sequence gap is reported.
+async fn values_until_gap(stream: &mut NotificationStream) -> (Vec<Variant>, SequenceGap) {
+    let mut values = Vec::new();
+    loop {
+        match timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            SubscriptionNotification::DataChange { value, .. } => values.push(value.value.unwrap()),
+            SubscriptionNotification::SequenceGap(gap) => return (values, gap),
+            n => panic!("Unexpected notification {n:?}"),
+        }
+    }
+}
 
-    // Only the last event should be received, due to the filter.
-    let (r1, v1) = timeout(Duration::from_millis(500), events.recv())
-        .await
-        .unwrap()
+#[tokio::test]
+async fn recover_sequence_gaps() {
+    let tester = Tester::new(test_server(), false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
         .unwrap();
-    assert_eq!(r1.node_id, server_id);
-    let evt = v1.unwrap();
-    assert_eq!(4, evt.len());
-    assert_eq!(Variant::from(hit_evt.base.base.event_id.clone()), evt[0]);
-    assert_eq!(Variant::from(hit_evt.base.base.message.clone()), evt[1]);
-    assert_eq!(Variant::from(hit_evt.base.status), evt[2]);
-    assert_eq!(
-        Variant::from(DateTime::from_timestamp(10_000, 0).unwrap()),
-        evt[3]
-    );
-}
 
-// TODO: Add more detailed high level tests on subscriptions.
+    // The missing notification is republished.
+    let (_session, mut stream) =
+        restore_with_sequence_gap(&tester, &nm, SequenceGapRecovery::Republish).await;
+    let (values, gap) = values_until_gap(&mut stream).await;
+    assert_eq!(values, vec![Variant::Int32(1), Variant::Int32(1)]);
+    assert_eq!(gap.first_sequence_number, 2);
+    assert_eq!(gap.last_sequence_number, 2);
+    assert_eq!(gap.recovery, SequenceGapRecovery::Republish);
+    assert_eq!(gap.count(), 1);
+    assert_eq!(gap.lost(), 0);
+    assert!(gap.start_time.is_none());
+
+    // The current value is read again instead.
+    let (_session, mut stream) =
+        restore_with_sequence_gap(&tester, &nm, SequenceGapRecovery::Resync).await;
+    let (values, gap) = values_until_gap(&mut stream).await;
+    assert_eq!(values, vec![Variant::Int32(1), Variant::Int32(1)]);
+    assert_eq!(gap.recovery, SequenceGapRecovery::Resync);
+    assert_eq!(gap.lost(), 1);
+
+    // The gap is only reported.
+    let (_session, mut stream) =
+        restore_with_sequence_gap(&tester, &nm, SequenceGapRecovery::Report).await;
+    let (values, gap) = values_until_gap(&mut stream).await;
+    assert_eq!(values, vec![Variant::Int32(1)]);
+    assert_eq!(gap.recovery, SequenceGapRecovery::Report);
+    assert_eq!(gap.lost(), 1);
+}
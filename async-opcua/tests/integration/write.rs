@@ -7,11 +7,14 @@ use opcua::{
     },
     types::{
         AttributeId, ByteString, DataTypeId, DataValue, DateTime, HistoryData, HistoryReadValueId,
-        LocalizedText, NodeId, ObjectId, ObjectTypeId, QualifiedName, ReadRawModifiedDetails,
-        ReferenceTypeId, StatusCode, TimestampsToReturn, UpdateDataDetails, VariableTypeId,
-        Variant, WriteMask, WriteValue,
+        LocalizedText, NodeId, ObjectId, ObjectTypeId, PerformUpdateType, QualifiedName,
+        ReadRawModifiedDetails, ReferenceTypeId, StatusCode, TimestampsToReturn, UpdateDataDetails,
+        VariableTypeId, Variant, WriteMask, WriteValue,
     },
 };
+use opcua_client::{
+    services::Write, DefaultRetryPolicy, ExponentialBackoff, UARequest, WriteVerification,
+};
 use opcua_types::NumericRange;
 // Write is not implemented in the core library itself, only in the test node manager,
 // we still test here to test write functionality in the address space.
@@ -559,7 +562,12 @@ async fn write_limits() {
         .map(|r| write_value(AttributeId::Value, 123, NodeId::new(2, r as u32)))
         .collect();
 
-    let r = session.write(&ops).await.unwrap_err();
+    // The session splits requests by the server operation limits, so send the request directly.
+    let r = Write::new(&session)
+        .nodes_to_write(ops)
+        .send(session.channel())
+        .await
+        .unwrap_err();
     assert_eq!(r, StatusCode::BadTooManyOperations);
 
     // Exact number of operations
@@ -800,6 +808,138 @@ async fn history_update_insert() {
     }
 }
 
+#[tokio::test]
+async fn history_update_typed() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .historizing(true)
+            .value(0)
+            .data_type(DataTypeId::Int32)
+            .access_level(AccessLevel::HISTORY_WRITE | AccessLevel::HISTORY_READ)
+            .user_access_level(AccessLevel::HISTORY_WRITE | AccessLevel::HISTORY_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    let time = |s: i64| start + TimeDelta::try_seconds(s).unwrap();
+    let value = |v: i32, s: i64| DataValue {
+        value: Some(v.into()),
+        status: Some(StatusCode::Good),
+        source_timestamp: Some(time(s)),
+        ..Default::default()
+    };
+
+    // Backfill ten values.
+    let r = session
+        .history_update_data(
+            &id,
+            PerformUpdateType::Insert,
+            (0..10).map(|i| value(i as i32, i)).collect(),
+        )
+        .await
+        .unwrap();
+    assert!(r.is_good());
+    assert_eq!(r.operation_results, vec![StatusCode::GoodEntryInserted; 10]);
+
+    // Correct one value, and try to replace one that does not exist.
+    let r = session
+        .history_update_data(
+            &id,
+            PerformUpdateType::Replace,
+            vec![value(100, 5), value(100, 50)],
+        )
+        .await
+        .unwrap();
+    assert!(!r.is_good());
+    assert_eq!(r.status, StatusCode::Good);
+    assert_eq!(
+        r.operation_results,
+        vec![StatusCode::GoodEntryReplaced, StatusCode::BadNoEntryExists]
+    );
+
+    // Delete two values by timestamp, one of which does not exist.
+    let r = session
+        .history_delete_at_time(&id, vec![time(0), time(50)])
+        .await
+        .unwrap();
+    assert_eq!(
+        r.operation_results,
+        vec![StatusCode::Good, StatusCode::BadNoEntryExists]
+    );
+
+    // Delete a range of values.
+    let r = session
+        .history_delete_raw_modified(&id, time(7)..time(9), false)
+        .await
+        .unwrap();
+    assert!(r.is_good());
+    assert!(r.operation_results.is_empty());
+
+    // Deleting modified values is not supported by the test node manager.
+    let r = session
+        .history_delete_raw_modified(&id, time(0)..time(10), true)
+        .await
+        .unwrap();
+    assert_eq!(r.status, StatusCode::BadHistoryOperationUnsupported);
+
+    // The node is not an annotation node, so structure updates fail for every value.
+    let r = session
+        .history_update_structure_data(&id, PerformUpdateType::Insert, vec![value(1, 1)])
+        .await
+        .unwrap();
+    assert_eq!(r.status, StatusCode::BadHistoryOperationUnsupported);
+    assert_eq!(
+        r.operation_results,
+        vec![StatusCode::BadHistoryOperationUnsupported]
+    );
+
+    let r = session
+        .history_read(
+            HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+                is_read_modified: false,
+                start_time: start,
+                end_time: time(100),
+                num_values_per_node: 100,
+                return_bounds: false,
+            }),
+            TimestampsToReturn::Both,
+            false,
+            &[HistoryReadValueId {
+                node_id: id.clone(),
+                index_range: Default::default(),
+                data_encoding: Default::default(),
+                continuation_point: Default::default(),
+            }],
+        )
+        .await
+        .unwrap();
+    let data = r[0]
+        .history_data
+        .inner_as::<HistoryData>()
+        .unwrap()
+        .data_values
+        .as_ref()
+        .unwrap();
+    let values: Vec<_> = data
+        .iter()
+        .map(|v| match v.value {
+            Some(Variant::Int32(v)) => v,
+            _ => panic!("Wrong value type: {:?}", v.value),
+        })
+        .collect();
+    assert_eq!(values, vec![1, 2, 3, 4, 100, 6, 9]);
+}
+
 #[tokio::test]
 async fn history_update_fail() {
     let (tester, nm, session) = setup().await;
@@ -877,3 +1017,65 @@ async fn history_update_fail() {
 
     assert_eq!(r[0].status_code, StatusCode::BadNodeIdUnknown);
 }
+
+#[tokio::test]
+async fn write_all_verified() {
+    let (tester, nm, session) = setup().await;
+
+    let mut ids = Vec::new();
+    for access in [
+        AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE,
+        AccessLevel::CURRENT_READ,
+    ] {
+        let id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&id, "TestVar1", "TestVar1")
+                .value(0)
+                .data_type(DataTypeId::Int32)
+                .access_level(access)
+                .user_access_level(access)
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+        ids.push(id);
+    }
+    nm.inner()
+        .issues()
+        .transient_write
+        .lock()
+        .insert(ids[0].clone(), 2);
+
+    let report = session
+        .write_all_verified(
+            &[
+                write_value(AttributeId::Value, 5, ids[0].clone()),
+                write_value(AttributeId::Value, 6, ids[1].clone()),
+            ],
+            DefaultRetryPolicy::new(ExponentialBackoff::new(
+                std::time::Duration::from_millis(100),
+                Some(3),
+                std::time::Duration::from_millis(10),
+            )),
+            true,
+        )
+        .await;
+
+    assert!(!report.is_success());
+    let written = &report.results[0];
+    assert_eq!(written.status, StatusCode::Good);
+    assert_eq!(written.attempts, 3);
+    assert_eq!(written.verification, WriteVerification::Verified);
+
+    let failed: Vec<_> = report.failed().collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].node_id, ids[1]);
+    assert_eq!(failed[0].status, StatusCode::BadUserAccessDenied);
+    assert_eq!(failed[0].attempts, 1);
+    assert_eq!(failed[0].verification, WriteVerification::NotVerified);
+}
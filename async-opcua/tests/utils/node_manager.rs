@@ -14,20 +14,21 @@ use opcua::{
             memory::{InMemoryNodeManager, InMemoryNodeManagerBuilder, InMemoryNodeManagerImpl},
             AddNodeItem, AddReferenceItem, DeleteNodeItem, DeleteReferenceItem, HistoryNode,
             HistoryUpdateNode, MethodCall, MonitoredItemRef, MonitoredItemUpdateRef,
-            NodeManagerBuilder, NodeManagersRef, ParsedReadValueId, RequestContext, ServerContext,
-            WriteNode,
+            NodeManagerBuilder, NodeManagersRef, ParsedReadValueId, RegisterNodeItem,
+            RequestContext, ServerContext, WriteNode,
         },
         ContinuationPoint, CreateMonitoredItem,
     },
     sync::{Mutex, RwLock},
     types::{
-        AttributeId, DataValue, DateTime, ExpandedNodeId, MonitoringMode, NodeClass, NodeId,
-        PerformUpdateType, ReadRawModifiedDetails, ReferenceTypeId, StatusCode, TimestampsToReturn,
-        Variant,
+        AttributeId, DataValue, DateTime, ExpandedNodeId, HistoryEvent, HistoryEventFieldList,
+        MonitoringMode, NodeClass, NodeId, ObjectId, PerformUpdateType, ReadEventDetails,
+        ReadProcessedDetails, ReadRawModifiedDetails, ReferenceTypeId, StatusCode,
+        StatusCodeValueType, TimestampsToReturn, Variant,
     },
 };
 use opcua_core::{trace_read_lock, trace_write_lock};
-use opcua_nodes::{DefaultTypeTree, TypeTree, TypeTreeNode};
+use opcua_nodes::{DefaultTypeTree, Event, ParsedEventFilter, TypeTree, TypeTreeNode};
 use opcua_server::{address_space::add_namespaces, diagnostics::NamespaceMetadata};
 use opcua_types::DataEncoding;
 
@@ -50,6 +51,7 @@ pub struct TestNodeManagerImpl {
     // In practice you would never store history data in memory, and you would not want
     // a single global lock on all history.
     history_data: RwLock<HashMap<NodeId, HistoryData>>,
+    history_events: RwLock<HashMap<NodeId, Vec<Box<dyn Event + Send + Sync>>>>,
     call_info: Mutex<CallInfo>,
     method_cbs: Mutex<HashMap<NodeId, Box<MethodCb>>>,
     node_id_generator: AtomicU32,
@@ -61,6 +63,8 @@ pub struct TestNodeManagerImpl {
 #[derive(Default)]
 pub struct IssueEmulation {
     pub fatal_read: AtomicU32,
+    /// Number of times writes to each node fail with `BadResourceUnavailable`.
+    pub transient_write: Mutex<HashMap<NodeId, u32>>,
 }
 
 /// Information about calls made to the node manager impl, for verifying in tests.
@@ -137,6 +141,30 @@ impl InMemoryNodeManagerImpl for TestNodeManagerImpl {
         Ok(())
     }
 
+    async fn history_read_processed(
+        &self,
+        _context: &RequestContext,
+        details: &ReadProcessedDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        _timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        self.history_read_processed(details, nodes);
+        Ok(())
+    }
+
+    async fn history_read_events(
+        &self,
+        context: &RequestContext,
+        details: &ReadEventDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+        _timestamps_to_return: TimestampsToReturn,
+    ) -> Result<(), StatusCode> {
+        let type_tree = context.get_type_tree_for_user();
+        let (_, filter) = ParsedEventFilter::new(details.filter.clone(), type_tree.get());
+        self.history_read_events(details, &filter?, type_tree.get(), nodes);
+        Ok(())
+    }
+
     async fn read_values(
         &self,
         context: &RequestContext,
@@ -250,6 +278,20 @@ impl InMemoryNodeManagerImpl for TestNodeManagerImpl {
         }
     }
 
+    async fn register_nodes(
+        &self,
+        _context: &RequestContext,
+        _address_space: &RwLock<AddressSpace>,
+        nodes: &mut [&mut RegisterNodeItem],
+    ) -> Result<(), StatusCode> {
+        let mut call_info = self.call_info.lock();
+        for node in nodes {
+            call_info.register_nodes.push(node.node_id().clone());
+            node.set_registered(true);
+        }
+        Ok(())
+    }
+
     async fn unregister_nodes(
         &self,
         _context: &RequestContext,
@@ -319,6 +361,17 @@ impl InMemoryNodeManagerImpl for TestNodeManagerImpl {
         let type_tree = trace_read_lock!(context.type_tree);
 
         for write in nodes_to_write {
+            if let Some(count) = self
+                .issues
+                .transient_write
+                .lock()
+                .get_mut(&write.value().node_id)
+                .filter(|c| **c > 0)
+            {
+                *count -= 1;
+                write.set_status(StatusCode::BadResourceUnavailable);
+                continue;
+            }
             let node = match address_space.validate_node_write(context, write.value(), &*type_tree)
             {
                 Ok(v) => v,
@@ -713,6 +766,7 @@ impl TestNodeManagerImpl {
     pub fn new(namespace_index: u16, node_managers: NodeManagersRef) -> Self {
         Self {
             history_data: Default::default(),
+            history_events: Default::default(),
             call_info: Default::default(),
             method_cbs: Default::default(),
             node_id_generator: AtomicU32::new(1),
@@ -722,6 +776,11 @@ impl TestNodeManagerImpl {
         }
     }
 
+    #[allow(unused)]
+    pub fn with_call_info<R>(&self, f: impl FnOnce(&CallInfo) -> R) -> R {
+        f(&self.call_info.lock())
+    }
+
     pub fn issues(&self) -> &IssueEmulation {
         &self.issues
     }
@@ -840,9 +899,200 @@ impl TestNodeManagerImpl {
         }
     }
 
+    /// Simple implementation of the Average, Minimum and Maximum aggregates over Int32 values,
+    /// only supporting forward reads. Assumes that every node in the request belongs to this
+    /// node manager, so that the nodes line up with the aggregate types.
+    fn history_read_processed(
+        &self,
+        details: &ReadProcessedDetails,
+        nodes: &mut [&mut &mut HistoryNode],
+    ) {
+        let aggregates = details.aggregate_type.as_deref().unwrap_or_default();
+        let interval = (details.processing_interval * 10_000.0) as i64;
+        let start_time = details.start_time.checked_ticks();
+        let end_time = details.end_time.checked_ticks();
+        if aggregates.len() != nodes.len() || interval <= 0 || end_time <= start_time {
+            for node in nodes {
+                node.set_status(StatusCode::BadInvalidArgument);
+            }
+            return;
+        }
+
+        let history = trace_read_lock!(self.history_data);
+        for (node, aggregate) in nodes.iter_mut().zip(aggregates) {
+            let Ok(aggregate) = aggregate.as_object_id() else {
+                node.set_status(StatusCode::BadAggregateNotSupported);
+                continue;
+            };
+            if !matches!(
+                aggregate,
+                ObjectId::AggregateFunction_Average
+                    | ObjectId::AggregateFunction_Minimum
+                    | ObjectId::AggregateFunction_Maximum
+            ) {
+                node.set_status(StatusCode::BadAggregateNotSupported);
+                continue;
+            }
+            let values = history
+                .get(node.node_id())
+                .map(|d| d.values.as_slice())
+                .unwrap_or_default();
+            let mut result = Vec::new();
+            let mut time = start_time;
+            while time < end_time {
+                let interval_end = time.saturating_add(interval);
+                let in_interval = values
+                    .iter()
+                    .filter(|v| {
+                        let ticks = v
+                            .source_timestamp
+                            .as_ref()
+                            .map(|v| v.checked_ticks())
+                            .unwrap_or_default();
+                        ticks >= time && ticks < interval_end.min(end_time)
+                    })
+                    .filter_map(|v| match v.value {
+                        Some(Variant::Int32(v)) => Some(v as f64),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                let value = match aggregate {
+                    _ if in_interval.is_empty() => None,
+                    ObjectId::AggregateFunction_Average => {
+                        Some(in_interval.iter().sum::<f64>() / in_interval.len() as f64)
+                    }
+                    ObjectId::AggregateFunction_Minimum => {
+                        in_interval.iter().copied().reduce(f64::min)
+                    }
+                    _ => in_interval.iter().copied().reduce(f64::max),
+                };
+
+                let status = match value {
+                    Some(_) => StatusCode::Good
+                        .set_value_type(StatusCodeValueType::Calculated)
+                        .set_partial(interval_end > end_time),
+                    None => StatusCode::BadNoData,
+                };
+                result.push(DataValue {
+                    value: value.map(Variant::from),
+                    status: Some(status),
+                    source_timestamp: Some(DateTime::from(time)),
+                    ..Default::default()
+                });
+                time = interval_end;
+            }
+
+            node.set_status(StatusCode::Good);
+            node.set_result(opcua::types::HistoryData {
+                data_values: Some(result),
+            });
+        }
+    }
+
+    /// Read stored events, only supporting forward reads.
+    fn history_read_events(
+        &self,
+        details: &ReadEventDetails,
+        filter: &ParsedEventFilter,
+        type_tree: &dyn TypeTree,
+        nodes: &mut [&mut &mut HistoryNode],
+    ) {
+        let per_node = if details.num_values_per_node == 0 {
+            10_000
+        } else {
+            details.num_values_per_node.min(10_000)
+        } as usize;
+
+        let history = trace_read_lock!(self.history_events);
+        for node in nodes {
+            let start_index = if let Some(cp) = node.continuation_point() {
+                let Some(cp) = cp.get::<HistoryContinuationPoint>() else {
+                    node.set_status(StatusCode::BadContinuationPointInvalid);
+                    continue;
+                };
+                cp.index
+            } else {
+                0
+            };
+
+            let mut matching = history
+                .get(node.node_id())
+                .map(|e| e.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .skip(start_index)
+                .filter(|(_, evt)| {
+                    (details.start_time.is_null() || *evt.time() >= details.start_time)
+                        && (details.end_time.is_null() || *evt.time() < details.end_time)
+                })
+                .filter_map(|(idx, evt)| {
+                    filter
+                        .evaluate(evt.as_ref(), 0, type_tree)
+                        .map(|f| (idx, f.event_fields))
+                });
+
+            let events: Vec<_> = (&mut matching)
+                .take(per_node)
+                .map(|(_, event_fields)| HistoryEventFieldList { event_fields })
+                .collect();
+            if let Some((index, _)) = matching.next() {
+                node.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+                    HistoryContinuationPoint { index },
+                ))));
+            }
+
+            node.set_status(StatusCode::Good);
+            node.set_result(HistoryEvent {
+                events: Some(events),
+            });
+        }
+    }
+
     fn history_update_node(&self, node: &mut HistoryUpdateNode) -> Result<(), StatusCode> {
         let details = match node.details() {
             opcua::server::node_manager::HistoryUpdateDetails::UpdateData(d) => d,
+            opcua::server::node_manager::HistoryUpdateDetails::DeleteRawModified(d) => {
+                // Modified values are not stored.
+                if d.is_delete_modified {
+                    return Err(StatusCode::BadHistoryOperationUnsupported);
+                }
+                let mut data = trace_write_lock!(self.history_data);
+                if let Some(values) = data.get_mut(&d.node_id) {
+                    values.values.retain(|v| {
+                        let ts = v.source_timestamp.unwrap_or_default();
+                        ts < d.start_time || ts >= d.end_time
+                    });
+                }
+                node.set_status(StatusCode::Good);
+                return Ok(());
+            }
+            opcua::server::node_manager::HistoryUpdateDetails::DeleteAtTime(d) => {
+                let mut data = trace_write_lock!(self.history_data);
+                let values = data.entry(d.node_id.clone()).or_default();
+                let results = d
+                    .req_times
+                    .iter()
+                    .flatten()
+                    .map(|time| {
+                        match values
+                            .values
+                            .iter()
+                            .position(|v| v.source_timestamp.as_ref() == Some(time))
+                        {
+                            Some(idx) => {
+                                values.values.remove(idx);
+                                StatusCode::Good
+                            }
+                            None => StatusCode::BadNoEntryExists,
+                        }
+                    })
+                    .collect();
+                node.set_operation_results(Some(results));
+                node.set_status(StatusCode::Good);
+                return Ok(());
+            }
             _ => return Err(StatusCode::BadHistoryOperationUnsupported),
         };
 
@@ -944,6 +1194,16 @@ impl TestNodeManagerImpl {
         data.values.extend(values);
     }
 
+    #[allow(unused)]
+    pub fn add_history_events(
+        &self,
+        node_id: &NodeId,
+        events: impl Iterator<Item = Box<dyn Event + Send + Sync>>,
+    ) {
+        let mut hist = trace_write_lock!(self.history_events);
+        hist.entry(node_id.clone()).or_default().extend(events);
+    }
+
     #[allow(unused, clippy::too_many_arguments)]
     pub fn add_node<'a>(
         &self,
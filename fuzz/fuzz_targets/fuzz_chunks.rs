@@ -0,0 +1,13 @@
+#![cfg_attr(feature = "nightly", no_main)]
+
+#[cfg(not(feature = "nightly"))]
+fn main() {
+    panic!("Fuzzing requires the nightly feature to be enabled.");
+}
+
+#[cfg(feature = "nightly")]
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+    // Decode the data into chunks and reassemble them into requests. This should
+    // either return the requests or an error. It shouldn't panic.
+    let _ = opcua::core::fuzz::decode_chunks(data);
+});
@@ -0,0 +1,26 @@
+#![cfg_attr(feature = "nightly", no_main)]
+
+#[cfg(not(feature = "nightly"))]
+fn main() {
+    panic!("Fuzzing requires the nightly feature to be enabled.");
+}
+
+#[cfg(feature = "nightly")]
+libfuzzer_sys::fuzz_target!(|messages: Vec<opcua::core::comms::tcp_codec::Message>| {
+    use bytes::BytesMut;
+    use tokio_util::codec::Encoder;
+
+    use opcua::core::comms::tcp_codec::TcpCodec;
+    use opcua::types::DecodingOptions;
+
+    // Encode a sequence of well formed messages, with arbitrary contents, then try
+    // to reassemble the chunks among them into requests.
+    let mut codec = TcpCodec::new(DecodingOptions::default());
+    let mut buf = BytesMut::new();
+    for message in messages {
+        if codec.encode(message, &mut buf).is_err() {
+            return;
+        }
+    }
+    let _ = opcua::core::fuzz::decode_chunks(&buf);
+});
@@ -0,0 +1,12 @@
+#![cfg_attr(feature = "nightly", no_main)]
+
+#[cfg(not(feature = "nightly"))]
+fn main() {
+    panic!("Fuzzing requires the nightly feature to be enabled.");
+}
+
+#[cfg(feature = "nightly")]
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+    // Run the server side of the secure channel handshake on the data.
+    let _ = opcua::core::fuzz::open_secure_channel(data);
+});
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use opcua::{
+    client::browser::CrawledAddressSpace,
+    types::{BrowseDirection, Identifier, NodeClass, NodeId, ReferenceTypeId},
+};
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_quote, Ident, Item};
+
+/// Reference types that make the symbolic name of the target node `Parent_Child`.
+const NAMING_REFERENCES: [ReferenceTypeId; 3] = [
+    ReferenceTypeId::HasComponent,
+    ReferenceTypeId::HasProperty,
+    ReferenceTypeId::HasOrderedComponent,
+];
+
+/// Generate enums of the IDs of the crawled nodes in namespace `index`, one enum per
+/// node class, named like the enums of the base namespace.
+pub(crate) fn generate_ids(space: &CrawledAddressSpace, index: u16, uri: &str) -> syn::File {
+    let mut names = SymbolicNames::new(space);
+    let mut by_class: BTreeMap<&'static str, Vec<(String, &NodeId)>> = BTreeMap::new();
+    for node in space.iter() {
+        let node_id = node.as_node().node_id();
+        if node_id.namespace != index {
+            continue;
+        }
+        by_class
+            .entry(enum_name(node.node_class()))
+            .or_default()
+            .push((names.name(node_id), node_id));
+    }
+
+    let mut items: Vec<Item> = vec![parse_quote! {
+        /// URI of the namespace the node IDs in this module belong to.
+        pub const NAMESPACE_URI: &str = #uri;
+    }];
+    for (name, mut nodes) in by_class {
+        nodes.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| a.1.to_string().cmp(&b.1.to_string()))
+        });
+        items.extend(render(name, unique_variants(nodes)));
+    }
+
+    syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items,
+    }
+}
+
+fn enum_name(node_class: NodeClass) -> &'static str {
+    match node_class {
+        NodeClass::Object => "ObjectId",
+        NodeClass::Variable => "VariableId",
+        NodeClass::Method => "MethodId",
+        NodeClass::ObjectType => "ObjectTypeId",
+        NodeClass::VariableType => "VariableTypeId",
+        NodeClass::ReferenceType => "ReferenceTypeId",
+        NodeClass::DataType => "DataTypeId",
+        NodeClass::View | NodeClass::Unspecified => "ViewId",
+    }
+}
+
+/// Make variant names unique by suffixing repeated names with a counter.
+fn unique_variants(nodes: Vec<(String, &NodeId)>) -> Vec<(String, &NodeId)> {
+    let mut seen = HashSet::new();
+    let mut res = Vec::with_capacity(nodes.len());
+    for (name, node_id) in nodes {
+        let mut unique = name.clone();
+        let mut counter = 1;
+        while !seen.insert(unique.clone()) {
+            counter += 1;
+            unique = format!("{name}_{counter}");
+        }
+        res.push((unique, node_id));
+    }
+    res
+}
+
+fn render(name: &str, variants: Vec<(String, &NodeId)>) -> Vec<Item> {
+    let name = Ident::new(name, Span::call_site());
+    let mut vs = quote! {};
+    let mut arms = quote! {};
+    for (variant, node_id) in variants {
+        let ident = Ident::new(&variant, Span::call_site());
+        let identifier = render_identifier(&node_id.identifier);
+        vs.extend(quote! { #ident, });
+        arms.extend(quote! { Self::#ident => #identifier, });
+    }
+
+    vec![
+        parse_quote! {
+            #[allow(non_camel_case_types, clippy::enum_variant_names)]
+            #[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+            pub enum #name {
+                #vs
+            }
+        },
+        parse_quote! {
+            impl #name {
+                /// Get the identifier of the node in the namespace [`NAMESPACE_URI`].
+                pub fn identifier(&self) -> opcua::types::Identifier {
+                    match self {
+                        #arms
+                    }
+                }
+
+                /// Get the node ID, if [`NAMESPACE_URI`] is in `namespaces`.
+                pub fn node_id(
+                    &self,
+                    namespaces: &opcua::types::NamespaceMap,
+                ) -> Option<opcua::types::NodeId> {
+                    namespaces
+                        .get_index(NAMESPACE_URI)
+                        .map(|ns| opcua::types::NodeId::new(ns, self.identifier()))
+                }
+            }
+        },
+        parse_quote! {
+            impl From<#name> for opcua::types::ExpandedNodeId {
+                fn from(r: #name) -> Self {
+                    Self {
+                        node_id: opcua::types::NodeId::new(0, r.identifier()),
+                        namespace_uri: NAMESPACE_URI.into(),
+                        server_index: 0,
+                    }
+                }
+            }
+        },
+    ]
+}
+
+fn render_identifier(identifier: &Identifier) -> proc_macro2::TokenStream {
+    match identifier {
+        Identifier::Numeric(i) => quote! { opcua::types::Identifier::Numeric(#i) },
+        Identifier::String(s) => {
+            let s = s.as_ref();
+            quote! { opcua::types::Identifier::String(#s.into()) }
+        }
+        Identifier::Guid(g) => {
+            let bytes = g.as_bytes();
+            quote! {
+                opcua::types::Identifier::Guid(
+                    opcua::types::Guid::from_slice(&[#(#bytes),*]).unwrap()
+                )
+            }
+        }
+        Identifier::ByteString(b) => {
+            let bytes = b.as_ref();
+            quote! {
+                opcua::types::Identifier::ByteString(
+                    opcua::types::ByteString::from(vec![#(#bytes),*])
+                )
+            }
+        }
+    }
+}
+
+/// Symbolic names of crawled nodes, following the convention of the base namespace:
+/// the browse name of a node, prefixed by the name of its parent if it is a component
+/// or property of a crawled node, like `MyDeviceType_Temperature`, and
+/// `MyStruct_Encoding_DefaultBinary` for data type encodings.
+struct SymbolicNames<'a> {
+    space: &'a CrawledAddressSpace,
+    names: HashMap<&'a NodeId, String>,
+}
+
+impl<'a> SymbolicNames<'a> {
+    fn new(space: &'a CrawledAddressSpace) -> Self {
+        Self {
+            space,
+            names: HashMap::new(),
+        }
+    }
+
+    fn name(&mut self, node_id: &'a NodeId) -> String {
+        let mut visiting = HashSet::new();
+        self.name_inner(node_id, &mut visiting)
+    }
+
+    fn name_inner(&mut self, node_id: &'a NodeId, visiting: &mut HashSet<&'a NodeId>) -> String {
+        if let Some(name) = self.names.get(node_id) {
+            return name.clone();
+        }
+        let own = self
+            .space
+            .get(node_id)
+            .map(|n| sanitize(n.as_node().browse_name().name.as_ref()))
+            .unwrap_or_default();
+        // Guard against reference cycles, by not using a parent that is being named.
+        visiting.insert(node_id);
+        let name = match self.parent(node_id, visiting) {
+            Some((parent, infix)) => {
+                format!("{}_{infix}{own}", self.name_inner(parent, visiting))
+            }
+            None => own,
+        };
+        visiting.remove(node_id);
+        self.names.insert(node_id, name.clone());
+        name
+    }
+
+    /// Find the parent of a node, which its name is prefixed with, and an infix to add
+    /// between the names.
+    fn parent(
+        &self,
+        node_id: &'a NodeId,
+        visiting: &HashSet<&'a NodeId>,
+    ) -> Option<(&'a NodeId, &'static str)> {
+        let space = self.space;
+        let is_candidate = |id: &&NodeId| space.get(id).is_some() && !visiting.contains(id);
+        if let Some(data_type) = space
+            .find_references(
+                node_id,
+                Some(&ReferenceTypeId::HasEncoding.into()),
+                BrowseDirection::Inverse,
+            )
+            .into_iter()
+            .map(|r| r.target_node)
+            .find(is_candidate)
+        {
+            return Some((data_type, "Encoding_"));
+        }
+        NAMING_REFERENCES
+            .iter()
+            .flat_map(|t| {
+                space.find_references(node_id, Some(&(*t).into()), BrowseDirection::Inverse)
+            })
+            .map(|r| r.target_node)
+            .filter(is_candidate)
+            // Pick the same parent every time, if there are several.
+            .min_by_key(|id| id.to_string())
+            .map(|id| (id, ""))
+    }
+}
+
+/// Turn a browse name into a valid identifier.
+fn sanitize(name: &str) -> String {
+    let mut res: String = name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if res.is_empty() || res.starts_with(|c: char| c.is_numeric()) {
+        res.insert(0, '_');
+    }
+    if syn::parse_str::<Ident>(&res).is_err() {
+        res.push('_');
+    }
+    res
+}
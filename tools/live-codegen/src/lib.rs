@@ -0,0 +1,494 @@
+//! Generate Rust code for the data types and nodes of a live OPC UA server.
+//!
+//! The generator connects to a server with a client session, crawls the types and objects
+//! of the chosen namespaces, and writes them as a NodeSet2 file. This node set is then fed
+//! to the regular code generator to produce typed structs and enums with a `TypeLoader`
+//! implementation, and to a generator for enums of the IDs of every crawled node.
+//!
+//! For each namespace, the output directory contains:
+//!
+//!  - `NodeSet2.xml`, the node set crawled from the server.
+//!  - `types/`, the generated data types and a `GeneratedTypeLoader`.
+//!  - `ids.rs`, enums of node IDs by node class, and the namespace URI.
+//!  - `mod.rs`, declaring the `ids` and `types` modules.
+//!
+//! The generated code expects the `async-opcua` crate to be available as `opcua`.
+
+#![warn(missing_docs)]
+
+mod ids;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use opcua::{
+    client::{browser::CrawledAddressSpace, Session},
+    types::{BrowseDirection, DataTypeDefinition, NodeClass, NodeId, ObjectId, ReferenceTypeId},
+};
+use opcua_codegen::{
+    run_codegen, CodeGenConfig, CodeGenError, CodeGenSource, CodeGenTarget, TypeCodeGenTarget,
+};
+use opcua_nodes::{NodeSetModel, NodeType};
+use tracing::info;
+
+const BASE_NAMESPACE: &str = "http://opcfoundation.org/UA/";
+
+/// Name of the node set file written to the output directory of each namespace.
+pub const NODESET_FILE: &str = "NodeSet2.xml";
+
+/// Error returned by the live code generator.
+#[derive(Debug, thiserror::Error)]
+pub enum LiveCodeGenError {
+    /// Reading from the server failed.
+    #[error("Failed to read from server: {0}")]
+    Client(#[from] opcua::types::Error),
+    /// Generating types from the crawled node set failed.
+    #[error("Code generation failed: {0}")]
+    CodeGen(#[from] CodeGenError),
+    /// Writing output failed.
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    /// Other error.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// A namespace of the server to generate code for.
+#[derive(Debug, Clone)]
+pub struct NamespaceTarget {
+    /// URI of the namespace.
+    pub uri: String,
+    /// Directory the generated module is written to. The directory of the `types`
+    /// module is cleared first.
+    pub output_dir: PathBuf,
+}
+
+/// Configuration of the live code generator.
+#[derive(Debug, Clone, Default)]
+pub struct LiveCodeGenConfig {
+    /// Namespaces to generate code for.
+    pub targets: Vec<NamespaceTarget>,
+    /// Schema files or directories the generated types depend on. This must include the
+    /// base node set `Opc.Ua.NodeSet2.xml`, and node sets of any other namespace that the
+    /// data types of the targets are derived from or use as field types.
+    pub dependencies: Vec<PathBuf>,
+    /// Extra header to add to each generated file.
+    pub extra_header: String,
+    /// Preferred locale to use when loading localized text. Defaults to nothing,
+    /// which picks the first available.
+    pub preferred_locale: String,
+}
+
+/// Crawl the types and objects in the namespace with URI `uri`, including the encoding
+/// nodes of data types.
+///
+/// # Arguments
+///
+/// * `session` - The session to read from.
+/// * `uri` - The URI of the namespace to crawl.
+///
+/// # Returns
+///
+/// * `Ok(CrawledAddressSpace)` - The nodes of the namespace and their references.
+/// * `Err(LiveCodeGenError)` - If the namespace is unknown or crawling failed.
+///
+pub async fn crawl_namespace(
+    session: &Session,
+    uri: &str,
+) -> Result<CrawledAddressSpace, LiveCodeGenError> {
+    let index = session.get_namespace_index(uri).await?;
+    let mut space = session
+        .crawler()
+        .roots(vec![
+            ObjectId::TypesFolder.into(),
+            ObjectId::ObjectsFolder.into(),
+        ])
+        .namespaces([index])
+        .crawl()
+        .await?;
+
+    // Encodings are referenced with HasEncoding, which is not hierarchical, so they are
+    // not found by the first crawl.
+    let encodings: Vec<NodeId> = space
+        .iter()
+        .filter(|n| n.node_class() == NodeClass::DataType)
+        .flat_map(|n| {
+            space.find_references(
+                n.as_node().node_id(),
+                Some(&ReferenceTypeId::HasEncoding.into()),
+                BrowseDirection::Forward,
+            )
+        })
+        .map(|r| r.target_node)
+        .filter(|id| id.namespace == index && space.get(id).is_none())
+        .cloned()
+        .collect();
+    if !encodings.is_empty() {
+        let encodings = session
+            .crawler()
+            .roots(encodings)
+            .max_depth(1)
+            .namespaces([index])
+            .crawl()
+            .await?;
+        space.extend(encodings);
+    }
+    info!("Crawled {} nodes in namespace {uri}", space.len());
+
+    Ok(space)
+}
+
+/// Generate code for the namespace with URI `uri` from a crawl of the server, writing
+/// it to `output_dir`.
+///
+/// # Arguments
+///
+/// * `space` - The crawled nodes of the namespace, see [`crawl_namespace`].
+/// * `uri` - The URI of the namespace.
+/// * `output_dir` - The directory to write the generated module to.
+/// * `config` - Configuration of the generator.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the module was generated.
+/// * `Err(LiveCodeGenError)` - If the namespace is unknown or generation failed.
+///
+pub fn generate_namespace(
+    space: &CrawledAddressSpace,
+    uri: &str,
+    output_dir: &Path,
+    config: &LiveCodeGenConfig,
+) -> Result<(), LiveCodeGenError> {
+    let Some(index) = space.namespaces.get_index(uri) else {
+        return Err(LiveCodeGenError::Other(format!(
+            "Namespace {uri} is not in the namespace array of the crawl"
+        )));
+    };
+    std::fs::create_dir_all(output_dir).map_err(|e| {
+        LiveCodeGenError::Io(format!("Failed to create dir {}", output_dir.display()), e)
+    })?;
+
+    let mut model = NodeSetModel::new(uri);
+    model.required_models = required_models(space, index);
+    let mut xml = Vec::new();
+    space.write_model_nodeset2(&mut xml, &model)?;
+    write_file(&output_dir.join(NODESET_FILE), &xml)?;
+
+    let root_path = output_dir
+        .to_str()
+        .ok_or_else(|| LiveCodeGenError::Other("Output path must be valid UTF-8".to_owned()))?;
+    let mut sources = Vec::with_capacity(config.dependencies.len() + 1);
+    for dependency in &config.dependencies {
+        // Relative paths are resolved against the output directory by the code generator.
+        let path = std::path::absolute(dependency).map_err(|e| {
+            LiveCodeGenError::Io(format!("Invalid path {}", dependency.display()), e)
+        })?;
+        let path = path
+            .to_str()
+            .ok_or_else(|| LiveCodeGenError::Other("Schema path must be valid UTF-8".to_owned()))?;
+        sources.push(CodeGenSource::Implicit(path.to_owned()));
+    }
+    sources.push(CodeGenSource::Implicit(NODESET_FILE.to_owned()));
+    let codegen_config = CodeGenConfig {
+        extra_header: config.extra_header.clone(),
+        preferred_locale: config.preferred_locale.clone(),
+        targets: vec![CodeGenTarget::Types(TypeCodeGenTarget {
+            file: NODESET_FILE.to_owned(),
+            output_dir: "types".to_owned(),
+            id_path: "crate".to_owned(),
+            node_ids_from_nodeset: true,
+            ..Default::default()
+        })],
+        sources,
+    };
+    run_codegen(&codegen_config, root_path)?;
+
+    let header = make_header(uri, &config.extra_header);
+    let ids = ids::generate_ids(space, index, uri);
+    write_file(
+        &output_dir.join("ids.rs"),
+        format!("{header}{}", prettyplease::unparse(&ids)).as_bytes(),
+    )?;
+    write_file(
+        &output_dir.join("mod.rs"),
+        format!("{header}\npub mod ids;\npub mod types;\n").as_bytes(),
+    )?;
+    info!(
+        "Generated code for namespace {uri} in {}",
+        output_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Crawl the server and generate code for each namespace in `config`.
+///
+/// # Arguments
+///
+/// * `session` - The session to read from.
+/// * `config` - Configuration of the generator.
+///
+/// # Returns
+///
+/// * `Ok(())` - If code was generated for all namespaces.
+/// * `Err(LiveCodeGenError)` - If crawling or generation failed for a namespace.
+///
+pub async fn run_live_codegen(
+    session: &Session,
+    config: &LiveCodeGenConfig,
+) -> Result<(), LiveCodeGenError> {
+    for target in &config.targets {
+        let space = crawl_namespace(session, &target.uri).await?;
+        generate_namespace(&space, &target.uri, &target.output_dir, config)?;
+    }
+    Ok(())
+}
+
+/// Find the URIs of the namespaces that the data types in namespace `index` derive
+/// from or use as field types.
+fn required_models(space: &CrawledAddressSpace, index: u16) -> Vec<String> {
+    let mut required = BTreeSet::new();
+    for node in space.iter() {
+        let NodeType::DataType(data_type) = node else {
+            continue;
+        };
+        let node_id = node.as_node().node_id();
+        if node_id.namespace != index {
+            continue;
+        }
+        for parent in space.find_references(
+            node_id,
+            Some(&ReferenceTypeId::HasSubtype.into()),
+            BrowseDirection::Inverse,
+        ) {
+            required.insert(parent.target_node.namespace);
+        }
+        if let Some(DataTypeDefinition::Structure(s)) = data_type.data_type_definition() {
+            for field in s.fields.iter().flatten() {
+                required.insert(field.data_type.namespace);
+            }
+        }
+    }
+
+    let uris: HashMap<_, _> = space
+        .namespaces
+        .known_namespaces()
+        .iter()
+        .map(|(uri, idx)| (*idx, uri.as_str()))
+        .collect();
+    let mut models = vec![BASE_NAMESPACE.to_owned()];
+    models.extend(
+        required
+            .into_iter()
+            .filter(|ns| *ns != 0 && *ns != index)
+            .filter_map(|ns| uris.get(&ns).map(|uri| (*uri).to_owned())),
+    );
+    models
+}
+
+fn make_header(uri: &str, extra: &str) -> String {
+    let mut header = format!(
+        r#"// This file was autogenerated from namespace {uri} by async-opcua-live-codegen
+//
+// DO NOT EDIT THIS FILE
+"#
+    );
+    if !extra.is_empty() {
+        header.push('\n');
+        header.push_str(extra.trim());
+        header.push('\n');
+    }
+    header
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), LiveCodeGenError> {
+    std::fs::write(path, data)
+        .map_err(|e| LiveCodeGenError::Io(format!("Failed to write file {}", path.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use opcua::{
+        client::browser::CrawledAddressSpace,
+        types::{
+            DataTypeDefinition, DataTypeId, NamespaceMap, NodeId, ObjectId, ObjectTypeId,
+            QualifiedName, ReferenceTypeId, StructureDefinition, StructureField, StructureType,
+        },
+    };
+    use opcua_nodes::{
+        DataTypeBuilder, NodeType, ObjectBuilder, ObjectTypeBuilder, References, VariableBuilder,
+    };
+    use tempdir::TempDir;
+
+    use super::{generate_namespace, LiveCodeGenConfig, NODESET_FILE};
+
+    const URI: &str = "urn:vendor";
+
+    fn crawled_space() -> CrawledAddressSpace {
+        let namespaces = NamespaceMap::new_full(
+            [
+                ("http://opcfoundation.org/UA/".to_owned(), 0),
+                ("urn:server".to_owned(), 1),
+                (URI.to_owned(), 2),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let struct_id = NodeId::new(2, 100);
+        let encoding_id = NodeId::new(2, 101);
+        let type_id = NodeId::new(2, "DeviceType");
+        let temperature_id = NodeId::new(2, "DeviceType.Temperature");
+        let device_id = NodeId::new(2, 200);
+
+        let definition = DataTypeDefinition::Structure(StructureDefinition {
+            default_encoding_id: encoding_id.clone(),
+            base_data_type: DataTypeId::Structure.into(),
+            structure_type: StructureType::Structure,
+            fields: Some(vec![
+                StructureField {
+                    name: "Value".into(),
+                    data_type: DataTypeId::Int32.into(),
+                    value_rank: -1,
+                    ..Default::default()
+                },
+                StructureField {
+                    name: "Name".into(),
+                    data_type: DataTypeId::String.into(),
+                    value_rank: -1,
+                    ..Default::default()
+                },
+            ]),
+        });
+        let nodes: Vec<NodeType> = vec![
+            DataTypeBuilder::new(
+                &struct_id,
+                QualifiedName::new(2, "VendorStruct"),
+                "VendorStruct",
+            )
+            .data_type_definition(definition)
+            .build()
+            .into(),
+            ObjectBuilder::new(
+                &encoding_id,
+                QualifiedName::new(0, "Default Binary"),
+                "Default Binary",
+            )
+            .build()
+            .into(),
+            ObjectTypeBuilder::new(&type_id, QualifiedName::new(2, "DeviceType"), "DeviceType")
+                .build()
+                .into(),
+            VariableBuilder::new(
+                &temperature_id,
+                QualifiedName::new(2, "Temperature"),
+                "Temperature",
+            )
+            .data_type(DataTypeId::Double)
+            .build()
+            .into(),
+            ObjectBuilder::new(&device_id, QualifiedName::new(2, "Device 1"), "Device 1")
+                .build()
+                .into(),
+        ];
+
+        let mut references = References::new();
+        references.insert_reference(
+            &DataTypeId::Structure.into(),
+            &struct_id,
+            ReferenceTypeId::HasSubtype,
+        );
+        references.insert_reference(&struct_id, &encoding_id, ReferenceTypeId::HasEncoding);
+        references.insert_reference(
+            &encoding_id,
+            &ObjectTypeId::DataTypeEncodingType.into(),
+            ReferenceTypeId::HasTypeDefinition,
+        );
+        references.insert_reference(
+            &ObjectTypeId::BaseObjectType.into(),
+            &type_id,
+            ReferenceTypeId::HasSubtype,
+        );
+        references.insert_reference(&type_id, &temperature_id, ReferenceTypeId::HasComponent);
+        references.insert_reference(
+            &ObjectId::ObjectsFolder.into(),
+            &device_id,
+            ReferenceTypeId::Organizes,
+        );
+        references.insert_reference(&device_id, &type_id, ReferenceTypeId::HasTypeDefinition);
+
+        CrawledAddressSpace {
+            namespaces,
+            nodes: nodes
+                .into_iter()
+                .map(|n| (n.as_node().node_id().clone(), n))
+                .collect(),
+            references,
+        }
+    }
+
+    #[test]
+    fn generate_from_crawl() {
+        let dir = TempDir::new("live-codegen").unwrap();
+        let output_dir = dir.path().join("vendor");
+        let config = LiveCodeGenConfig {
+            dependencies: vec![PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../../schemas/1.05/Opc.Ua.NodeSet2.xml"
+            ))],
+            ..Default::default()
+        };
+        generate_namespace(&crawled_space(), URI, &output_dir, &config).unwrap();
+
+        let node_set = std::fs::read_to_string(output_dir.join(NODESET_FILE)).unwrap();
+        assert!(node_set.contains(r#"<Model ModelUri="urn:vendor">"#));
+
+        let types = std::fs::read_to_string(output_dir.join("types/vendor_struct.rs")).unwrap();
+        assert!(types.contains("pub struct VendorStruct"));
+        assert!(types.contains("pub value: i32"));
+        let types_mod = std::fs::read_to_string(output_dir.join("types/mod.rs")).unwrap();
+        assert!(types_mod.contains("GeneratedTypeLoader"));
+
+        let ids = std::fs::read_to_string(output_dir.join("ids.rs")).unwrap();
+        assert!(ids.contains(r#"pub const NAMESPACE_URI: &str = "urn:vendor";"#));
+        for expected in [
+            "pub enum DataTypeId",
+            "VendorStruct =>",
+            "pub enum ObjectId",
+            "VendorStruct_Encoding_DefaultBinary =>",
+            "Device1 =>",
+            "pub enum VariableId",
+            "DeviceType_Temperature =>",
+            r#"opcua::types::Identifier::String("DeviceType".into())"#,
+        ] {
+            assert!(ids.contains(expected), "Missing {expected} in {ids}");
+        }
+
+        let module = std::fs::read_to_string(output_dir.join("mod.rs")).unwrap();
+        assert!(module.contains("pub mod ids;"));
+        assert!(module.contains("pub mod types;"));
+
+        // A copy of the generated code is compiled by the tests in `tests/generated.rs`.
+        let compiled_dir = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/generated/vendor"
+        ));
+        for file in [
+            NODESET_FILE,
+            "mod.rs",
+            "ids.rs",
+            "types/mod.rs",
+            "types/vendor_struct.rs",
+        ] {
+            let generated = std::fs::read_to_string(output_dir.join(file)).unwrap();
+            let compiled = std::fs::read_to_string(compiled_dir.join(file)).unwrap();
+            assert!(
+                generated == compiled,
+                "Generated {file} differs from tests/generated/vendor/{file}"
+            );
+        }
+    }
+}
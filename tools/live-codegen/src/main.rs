@@ -0,0 +1,210 @@
+use std::{path::PathBuf, process::ExitCode, str::FromStr};
+
+use opcua::{
+    client::{ClientBuilder, IdentityToken},
+    crypto::SecurityPolicy,
+    types::MessageSecurityMode,
+};
+use opcua_live_codegen::{run_live_codegen, LiveCodeGenConfig, NamespaceTarget};
+
+struct Args {
+    help: bool,
+    url: String,
+    security_policy: String,
+    security_mode: String,
+    user: Option<String>,
+    password: Option<String>,
+    pki_dir: PathBuf,
+    trust_server_certs: bool,
+    namespaces: Vec<String>,
+    dependencies: Vec<PathBuf>,
+    output_dir: PathBuf,
+    extra_header: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn parse_args() -> Result<Args, Box<dyn std::error::Error>> {
+        let mut args = pico_args::Arguments::from_env();
+        Ok(Args {
+            help: args.contains(["-h", "--help"]),
+            url: args
+                .opt_value_from_str("--url")?
+                .unwrap_or_else(|| String::from(DEFAULT_URL)),
+            security_policy: args
+                .opt_value_from_str("--security-policy")?
+                .unwrap_or_else(|| String::from(DEFAULT_SECURITY_POLICY)),
+            security_mode: args
+                .opt_value_from_str("--security-mode")?
+                .unwrap_or_else(|| String::from(DEFAULT_SECURITY_MODE)),
+            user: args.opt_value_from_str("--user")?,
+            password: args.opt_value_from_str("--password")?,
+            pki_dir: args
+                .opt_value_from_str("--pki-dir")?
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_PKI_DIR)),
+            trust_server_certs: args.contains("--trust-server-certs"),
+            namespaces: args.values_from_str("--namespace")?,
+            dependencies: args.values_from_str("--dependency")?,
+            output_dir: args
+                .opt_value_from_str("--output-dir")?
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR)),
+            extra_header: args.opt_value_from_str("--extra-header")?,
+        })
+    }
+
+    pub fn usage() {
+        println!(
+            r#"OPC UA Live Code Generator
+
+Connects to a server and generates Rust modules with the data types and node IDs of
+the given namespaces, using the types of the async-opcua crate.
+
+Usage:
+  -h, --help              Show help.
+  --url url               Url of the server to connect to. (default: {DEFAULT_URL})
+  --security-policy name  Security policy of the endpoint to connect to, for example
+                          Basic256Sha256. (default: {DEFAULT_SECURITY_POLICY})
+  --security-mode mode    Security mode of the endpoint to connect to, None, Sign or
+                          SignAndEncrypt. (default: {DEFAULT_SECURITY_MODE})
+  --user name             Log in with this user name instead of anonymously.
+  --password password     Password of the user.
+  --pki-dir path          Directory of the client certificate and the trusted server
+                          certificates. (default: {DEFAULT_PKI_DIR})
+  --trust-server-certs    Trust the certificate of any server.
+  --namespace uri=module  Generate code for the namespace with the given URI into the
+                          module directory `module`, relative to the output dir. Repeat
+                          for each namespace.
+  --dependency path       Schema file or directory that the generated types depend on.
+                          Must include Opc.Ua.NodeSet2.xml. Repeat for each dependency.
+  --output-dir path       Directory to write the generated modules to. (default: {DEFAULT_OUTPUT_DIR})
+  --extra-header path     File with an extra header to add to each generated file."#
+        );
+    }
+
+    fn endpoint(&self) -> Result<(SecurityPolicy, MessageSecurityMode), String> {
+        let security_policy = SecurityPolicy::from_str(&self.security_policy)
+            .ok()
+            .filter(|p| *p != SecurityPolicy::Unknown)
+            .ok_or_else(|| format!("Invalid security policy {}", self.security_policy))?;
+        let security_mode = MessageSecurityMode::from(self.security_mode.as_str());
+        if security_mode == MessageSecurityMode::Invalid {
+            return Err(format!("Invalid security mode {}", self.security_mode));
+        }
+        if (security_policy == SecurityPolicy::None) != (security_mode == MessageSecurityMode::None)
+        {
+            return Err(format!(
+                "Security policy {} cannot be used with security mode {}",
+                self.security_policy, self.security_mode
+            ));
+        }
+        Ok((security_policy, security_mode))
+    }
+
+    fn identity_token(&self) -> Result<IdentityToken, String> {
+        match (&self.user, &self.password) {
+            (Some(user), Some(password)) => Ok(IdentityToken::UserName(
+                user.clone(),
+                password.as_str().into(),
+            )),
+            (None, None) => Ok(IdentityToken::Anonymous),
+            _ => Err("--user and --password must be given together".to_owned()),
+        }
+    }
+
+    fn config(&self) -> Result<LiveCodeGenConfig, String> {
+        let mut targets = Vec::with_capacity(self.namespaces.len());
+        for namespace in &self.namespaces {
+            let Some((uri, module)) = namespace.rsplit_once('=') else {
+                return Err(format!(
+                    "Invalid namespace {namespace}, expected uri=module"
+                ));
+            };
+            targets.push(NamespaceTarget {
+                uri: uri.to_owned(),
+                output_dir: self.output_dir.join(module),
+            });
+        }
+        let extra_header = match &self.extra_header {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?,
+            None => String::new(),
+        };
+        Ok(LiveCodeGenConfig {
+            targets,
+            dependencies: self.dependencies.clone(),
+            extra_header,
+            preferred_locale: String::new(),
+        })
+    }
+}
+
+const DEFAULT_URL: &str = "opc.tcp://localhost:4855";
+const DEFAULT_SECURITY_POLICY: &str = "None";
+const DEFAULT_SECURITY_MODE: &str = "None";
+const DEFAULT_PKI_DIR: &str = "pki";
+const DEFAULT_OUTPUT_DIR: &str = "src/generated";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let Ok(args) = Args::parse_args() else {
+        Args::usage();
+        return ExitCode::FAILURE;
+    };
+    if args.help || args.namespaces.is_empty() {
+        Args::usage();
+        return ExitCode::FAILURE;
+    }
+    env_logger::init();
+
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: &Args) -> Result<(), String> {
+    let config = args.config()?;
+    let (security_policy, security_mode) = args.endpoint()?;
+    let identity_token = args.identity_token()?;
+
+    let mut client = ClientBuilder::new()
+        .application_name("Live Code Generator")
+        .application_uri("urn:LiveCodeGenerator")
+        .product_uri("urn:LiveCodeGenerator")
+        .pki_dir(&args.pki_dir)
+        .trust_server_certs(args.trust_server_certs)
+        .create_sample_keypair(true)
+        .session_retry_limit(3)
+        .client()
+        .map_err(|e| format!("Invalid client configuration: {}", e.join(", ")))?;
+    let (session, event_loop) = client
+        .connect_to_matching_endpoint(
+            (args.url.as_ref(), security_policy.to_str(), security_mode),
+            identity_token,
+        )
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {e}", args.url))?;
+    let mut handle = event_loop.spawn();
+    // The event loop ends if the session gives up connecting.
+    tokio::select! {
+        connected = session.wait_for_connection() => {
+            if !connected {
+                return Err(format!("Failed to connect to {}", args.url));
+            }
+        }
+        status = &mut handle => {
+            let reason = match status {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            return Err(format!("Failed to connect to {}: {reason}", args.url));
+        }
+    }
+
+    let result = run_live_codegen(&session, &config).await;
+    let _ = session.disconnect().await;
+    let _ = handle.await;
+    result.map_err(|e| e.to_string())
+}
@@ -0,0 +1,72 @@
+//! Compiles the code generated from the crawl in the `generate_from_crawl` test, which
+//! checks that the generator still produces exactly the files in `generated/vendor`.
+
+// The generated type loader has code behind the `xml` and `json` features of the crate
+// it is generated into.
+#![cfg(all(feature = "xml", feature = "json"))]
+
+use std::sync::Arc;
+
+// Keep the generated code exactly as generated.
+#[rustfmt::skip]
+#[path = "generated/vendor/mod.rs"]
+mod vendor;
+
+use opcua::types::{
+    BinaryDecodable, BinaryEncodable, ContextOwned, DecodingOptions, ExpandedMessageInfo,
+    NamespaceMap, NodeId, TypeLoader,
+};
+use vendor::{
+    ids::{DataTypeId, ObjectId, VariableId, NAMESPACE_URI},
+    types::{GeneratedTypeLoader, VendorStruct},
+};
+
+#[test]
+fn generated_ids() {
+    let mut namespaces = NamespaceMap::new();
+    assert_eq!(DataTypeId::VendorStruct.node_id(&namespaces), None);
+    let ns = namespaces.add_namespace(NAMESPACE_URI);
+
+    assert_eq!(
+        DataTypeId::VendorStruct.node_id(&namespaces),
+        Some(NodeId::new(ns, 100))
+    );
+    assert_eq!(
+        ObjectId::Device1.node_id(&namespaces),
+        Some(NodeId::new(ns, 200))
+    );
+    assert_eq!(
+        VariableId::DeviceType_Temperature.node_id(&namespaces),
+        Some(NodeId::new(ns, "DeviceType.Temperature"))
+    );
+}
+
+#[test]
+fn generated_types() {
+    let mut namespaces = NamespaceMap::new();
+    let ns = namespaces.add_namespace(NAMESPACE_URI);
+    let mut ctx = ContextOwned::new_default(namespaces, DecodingOptions::test());
+    ctx.loaders_mut().add(Arc::new(GeneratedTypeLoader));
+
+    let value = VendorStruct {
+        value: 5,
+        name: "Sensor".into(),
+    };
+    assert_eq!(value.full_type_id().namespace_uri.as_ref(), NAMESPACE_URI);
+
+    let ctx_ref = ctx.context();
+    let mut buf = Vec::new();
+    value.encode(&mut buf, &ctx_ref).unwrap();
+    let decoded = GeneratedTypeLoader
+        .load_from_binary(&NodeId::new(ns, 101), &mut buf.as_slice(), &ctx_ref, None)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        decoded.as_dyn_any_ref().downcast_ref::<VendorStruct>(),
+        Some(&value)
+    );
+    assert_eq!(
+        VendorStruct::decode(&mut buf.as_slice(), &ctx_ref).unwrap(),
+        value
+    );
+}